@@ -0,0 +1,44 @@
+//! Cost of calling [`Agent::decide`] statically versus through [`DynAgent`]
+//!
+//! The static path polls the decision future in place; the dynamic path
+//! boxes it once per call, as the runner does.
+
+use std::sync::Arc;
+
+use agent_wallet_agent::agent::{Agent, DynAgent};
+use agent_wallet_agent::context::AgentContext;
+use agent_wallet_agent::decision::AgentAction;
+use agent_wallet_agent::Result;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use futures::executor::block_on;
+use solana_sdk::pubkey::Pubkey;
+
+/// Agent that never acts
+struct Idle;
+
+impl Agent for Idle {
+    fn name(&self) -> &str {
+        "idle"
+    }
+
+    async fn decide(&self, _context: &AgentContext) -> Result<Option<AgentAction>> {
+        Ok(None)
+    }
+}
+
+fn decide(c: &mut Criterion) {
+    let context = AgentContext::new(Pubkey::new_unique());
+    let shared: Arc<dyn DynAgent> = Arc::new(Idle);
+
+    let mut group = c.benchmark_group("decide");
+    group.bench_function("static", |b| {
+        b.iter(|| block_on(Agent::decide(black_box(&Idle), &context)))
+    });
+    group.bench_function("dyn", |b| {
+        b.iter(|| block_on(black_box(&shared).decide(&context)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decide);
+criterion_main!(benches);
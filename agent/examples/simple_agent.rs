@@ -14,13 +14,16 @@ async fn main() -> Result<()> {
     println!("Starting simple agent example...");
 
     // Create a deterministic agent with a periodic transfer strategy
-    let agent = DeterministicAgent::new(DeterministicStrategy::PeriodicTransfer {
-        interval_seconds: 3600, // Transfer every hour
-        recipient: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"
-            .parse()
-            .expect("Invalid recipient address"),
-        amount_sol: 0.1,
-    });
+    let agent = DeterministicAgent::try_new(
+        DeterministicStrategy::PeriodicTransfer {
+            interval_seconds: 3600, // Transfer every hour
+            recipient: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"
+                .parse()
+                .expect("Invalid recipient address"),
+            amount_sol: 0.1,
+        },
+        &AgentLimits::default(),
+    )?;
 
     // Create agent context with current state
     let context = AgentContext {
@@ -0,0 +1,210 @@
+//! Agent trait
+//!
+//! Every agent type implements [`Agent`]. The wallet never exposes keys to
+//! agents: an agent only looks at the context and proposes an action.
+//!
+//! [`Agent`] uses native `async fn` and is not object safe, so calls through a
+//! concrete or generic agent are not boxed. Anything holding agents of mixed
+//! types, such as the [`AgentRunner`](crate::runner::AgentRunner), stores them
+//! as [`DynAgent`], which every [`Agent`] implements and which boxes the
+//! decision future once at that boundary.
+//!
+//! # Example
+//!
+//! Implementing an agent needs no `async_trait`:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_agent::agent::{Agent, DynAgent};
+//! use agent_wallet_agent::context::AgentContext;
+//! use agent_wallet_agent::decision::AgentAction;
+//! use agent_wallet_agent::Result;
+//!
+//! struct Idle;
+//!
+//! impl Agent for Idle {
+//!     fn name(&self) -> &str {
+//!         "idle"
+//!     }
+//!
+//!     async fn decide(&self, _context: &AgentContext) -> Result<Option<AgentAction>> {
+//!         Ok(None)
+//!     }
+//! }
+//!
+//! let agent: Arc<dyn DynAgent> = Arc::new(Idle);
+//! assert_eq!(agent.name(), "idle");
+//! ```
+//!
+//! Agents doing I/O, such as LLM agents, should also implement
+//! [`Agent::decide_cancellable`] and stop their in-flight requests once the
+//! token is cancelled; the sandbox cancels it when a decision times out.
+//!
+//! Agents whose decisions depend on what they did before implement
+//! [`Agent::state_key`], [`Agent::load_state`] and [`Agent::save_state`] so
+//! a runner with an [`AgentStateStore`](crate::state::AgentStateStore) can
+//! carry their state across restarts.
+
+use std::future::Future;
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+pub use tokio_util::sync::CancellationToken;
+
+use agent_wallet_core::template::ActionTemplate;
+
+use crate::context::AgentContext;
+use crate::decision::AgentAction;
+use crate::error::Result;
+use crate::trigger::TriggerSchema;
+
+pub use agent_wallet_core::types::{AgentId, AgentStatus};
+
+/// Unified interface for all agent types
+///
+/// Not object safe; use [`DynAgent`] for trait objects.
+pub trait Agent: Send + Sync {
+    /// Human-readable agent name
+    fn name(&self) -> &str;
+
+    /// Decide on the next action, if any
+    fn decide(
+        &self,
+        context: &AgentContext,
+    ) -> impl Future<Output = Result<Option<AgentAction>>> + Send;
+
+    /// Decide on the next action, giving up once `cancel` is cancelled
+    ///
+    /// The sandbox calls this with a token it cancels when the decision
+    /// timeout passes. The default ignores the token and calls
+    /// [`Agent::decide`], whose future is then simply dropped.
+    fn decide_cancellable(
+        &self,
+        context: &AgentContext,
+        _cancel: CancellationToken,
+    ) -> impl Future<Output = Result<Option<AgentAction>>> + Send {
+        self.decide(context)
+    }
+
+    /// Shapes of the actions this agent always emits
+    ///
+    /// The runner prepares these at start so matching decisions only need
+    /// their amount patched in. Agents without a fixed action shape keep the
+    /// default of no templates.
+    fn templates(&self) -> Vec<ActionTemplate> {
+        Vec::new()
+    }
+
+    /// Schema of the external trigger payloads this agent reacts to
+    ///
+    /// Used by the runner when its trigger configuration has no schema.
+    fn trigger_schema(&self) -> Option<TriggerSchema> {
+        None
+    }
+
+    /// Key the agent's state is persisted under, `None` if it keeps none
+    fn state_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Continue from state saved by an earlier run
+    fn load_state(&self, _state: Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// State to persist after a decision
+    fn save_state(&self) -> Result<Option<Value>> {
+        Ok(None)
+    }
+
+    /// Explanation the agent gave for its latest decision, if any
+    ///
+    /// Reported by the runner next to the decided action.
+    fn last_rationale(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Object-safe form of [`Agent`], implemented for every agent
+///
+/// Each [`DynAgent::decide`] call boxes the agent's decision future.
+pub trait DynAgent: Send + Sync {
+    /// Human-readable agent name
+    fn name(&self) -> &str;
+
+    /// Decide on the next action, if any
+    fn decide<'a>(
+        &'a self,
+        context: &'a AgentContext,
+    ) -> BoxFuture<'a, Result<Option<AgentAction>>>;
+
+    /// Decide on the next action, giving up once `cancel` is cancelled
+    fn decide_cancellable<'a>(
+        &'a self,
+        context: &'a AgentContext,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<AgentAction>>>;
+
+    /// Shapes of the actions this agent always emits
+    fn templates(&self) -> Vec<ActionTemplate>;
+
+    /// Schema of the external trigger payloads this agent reacts to
+    fn trigger_schema(&self) -> Option<TriggerSchema>;
+
+    /// Key the agent's state is persisted under, `None` if it keeps none
+    fn state_key(&self) -> Option<String>;
+
+    /// Continue from state saved by an earlier run
+    fn load_state(&self, state: Value) -> Result<()>;
+
+    /// State to persist after a decision
+    fn save_state(&self) -> Result<Option<Value>>;
+
+    /// Explanation the agent gave for its latest decision, if any
+    fn last_rationale(&self) -> Option<String>;
+}
+
+impl<A: Agent> DynAgent for A {
+    fn name(&self) -> &str {
+        Agent::name(self)
+    }
+
+    fn decide<'a>(
+        &'a self,
+        context: &'a AgentContext,
+    ) -> BoxFuture<'a, Result<Option<AgentAction>>> {
+        Box::pin(Agent::decide(self, context))
+    }
+
+    fn decide_cancellable<'a>(
+        &'a self,
+        context: &'a AgentContext,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<AgentAction>>> {
+        Box::pin(Agent::decide_cancellable(self, context, cancel))
+    }
+
+    fn templates(&self) -> Vec<ActionTemplate> {
+        Agent::templates(self)
+    }
+
+    fn trigger_schema(&self) -> Option<TriggerSchema> {
+        Agent::trigger_schema(self)
+    }
+
+    fn state_key(&self) -> Option<String> {
+        Agent::state_key(self)
+    }
+
+    fn load_state(&self, state: Value) -> Result<()> {
+        Agent::load_state(self, state)
+    }
+
+    fn save_state(&self) -> Result<Option<Value>> {
+        Agent::save_state(self)
+    }
+
+    fn last_rationale(&self) -> Option<String> {
+        Agent::last_rationale(self)
+    }
+}
@@ -0,0 +1,692 @@
+//! Human approval of large actions
+//!
+//! An [`ApprovalGate`] stands between an agent's decision and its execution.
+//! Actions worth less than [`ApprovalPolicy::auto_approve_below_sol`] pass
+//! straight through. Larger ones, and actions whose value cannot be
+//! determined, are parked as a pending [`ApprovalRequest`] and announced to
+//! the policy's approvers as an `approval_requested` webhook carrying the
+//! action, its simulated balance changes and the request's token. The
+//! runner keeps ticking meanwhile and executes the action once it is
+//! approved, through `POST /approvals/{token}` on the HTTP service or
+//! `agent approve <token>` on the command line. A request nobody answers
+//! within [`ApprovalPolicy::timeout_secs`] resolves as
+//! [`ApprovalPolicy::on_timeout`] says.
+//!
+//! Requests are kept in an [`ApprovalStore`]. Stores opened on an
+//! [`AgentWorkspace`] persist their requests and re-read them before every
+//! operation, so the CLI and the service can answer requests of an agent
+//! running in another process. Answering a request again with the same
+//! decision changes nothing; contradicting an earlier answer fails.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::approval::{ApprovalDecision, ApprovalStore};
+//! use agent_wallet_agent::workspace::AgentWorkspaces;
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let workspace = AgentWorkspaces::open("/home/me/.agent-wallet")?.open_workspace("trader-1")?;
+//! let store = ApprovalStore::open(workspace)?;
+//! let now = chrono::Utc::now();
+//! for request in store.pending(now)? {
+//!     println!("{} waits for approval: {}", request.token, request.description);
+//!     store.resolve(&request.token, ApprovalDecision::Approve, now)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use agent_wallet_core::config::{ApprovalPolicy, ApprovalTimeoutAction, WebhookSettings};
+use agent_wallet_core::token::TOKEN_ACCOUNT_RENT_LAMPORTS;
+use agent_wallet_core::{Lamports, TransferPreview};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::agent::AgentId;
+use crate::context::AgentContext;
+use crate::decision::AgentAction;
+use crate::error::{AgentError, Result};
+use crate::notify::{AgentEvent, Notification, Notifier, WebhookNotifier};
+use crate::sync::lock;
+use crate::workspace::{AgentWorkspace, ArtifactKind};
+
+/// Hours answered requests are kept, so repeated answers stay idempotent
+const RESOLVED_RETENTION_HOURS: i64 = 24;
+
+/// Answer given to an approval request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Execute the action
+    Approve,
+    /// Drop the action
+    Reject,
+}
+
+/// Where an approval request stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    /// Waiting for an answer
+    Pending,
+    /// The action may be executed
+    Approved,
+    /// The action is dropped
+    Rejected,
+}
+
+impl From<ApprovalDecision> for ApprovalStatus {
+    fn from(decision: ApprovalDecision) -> Self {
+        match decision {
+            ApprovalDecision::Approve => Self::Approved,
+            ApprovalDecision::Reject => Self::Rejected,
+        }
+    }
+}
+
+/// Action parked until a human approves or rejects it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    /// Secret token answering the request
+    pub token: String,
+    /// Agent that decided on the action
+    pub agent_id: AgentId,
+    /// Id of the decision, shared with its audit records
+    pub decision_id: Uuid,
+    /// The parked action
+    pub action: AgentAction,
+    /// Description of the action
+    pub description: String,
+    /// SOL the action puts at stake, if it could be valued
+    pub value_sol: Option<f64>,
+    /// Balance changes the action's simulation predicted
+    pub preview: Option<TransferPreview>,
+    /// When the request was made
+    pub created_at: DateTime<Utc>,
+    /// When the request resolves on its own
+    pub expires_at: DateTime<Utc>,
+    /// How the request resolves once it expires
+    pub on_timeout: ApprovalTimeoutAction,
+    /// Where the request stands
+    pub status: ApprovalStatus,
+    /// When the request was answered or expired
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// Whether the request resolved by expiring
+    pub timed_out: bool,
+    /// Whether the runner has acted on the resolution
+    pub released: bool,
+}
+
+impl ApprovalRequest {
+    /// Resolve the request as `on_timeout` says if it expired by `now`
+    ///
+    /// Returns whether it expired.
+    fn expire(&mut self, now: DateTime<Utc>) -> bool {
+        if self.status != ApprovalStatus::Pending || now < self.expires_at {
+            return false;
+        }
+        self.status = match self.on_timeout {
+            ApprovalTimeoutAction::Approve => ApprovalStatus::Approved,
+            ApprovalTimeoutAction::Reject => ApprovalStatus::Rejected,
+        };
+        self.resolved_at = Some(self.expires_at);
+        self.timed_out = true;
+        true
+    }
+}
+
+/// SOL an action puts at stake, or `None` if it cannot be valued
+///
+/// SOL leaving the wallet counts at face value and token spends at their
+/// price in `context`. Unstaking and closing accounts bring SOL back and
+/// are worth nothing.
+pub fn value_at_stake(action: &AgentAction, context: &AgentContext) -> Option<f64> {
+    let lamports = match action {
+        AgentAction::TransferSol { amount, .. } | AgentAction::StakeTokens { amount, .. } => {
+            amount.as_u64()
+        }
+        AgentAction::BatchTransfer {
+            transfers,
+            mint: None,
+            ..
+        } => AgentAction::batch_total(transfers).ok()?,
+        AgentAction::CreateTokenAccount { .. } => TOKEN_ACCOUNT_RENT_LAMPORTS,
+        AgentAction::UnstakeTokens { .. }
+        | AgentAction::CloseTokenAccount { .. }
+        | AgentAction::NoOp => 0,
+        _ => {
+            let (mint, amount) = action.token_spend()?;
+            context.token_sol_value(&mint, amount).ok()?.as_u64()
+        }
+    };
+    Some(Lamports::new(lamports).to_sol_f64())
+}
+
+struct StoreInner {
+    workspace: Option<AgentWorkspace>,
+    requests: StdMutex<BTreeMap<String, ApprovalRequest>>,
+}
+
+/// Approval requests of one agent, by token
+#[derive(Clone)]
+pub struct ApprovalStore {
+    inner: Arc<StoreInner>,
+}
+
+impl Default for ApprovalStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApprovalStore {
+    /// Store kept in memory only
+    pub fn new() -> Self {
+        Self::with_state(None, BTreeMap::new())
+    }
+
+    /// Store persisted in the agent's workspace
+    pub fn open(workspace: AgentWorkspace) -> Result<Self> {
+        let requests = Self::load(&workspace)?;
+        Ok(Self::with_state(Some(workspace), requests))
+    }
+
+    fn with_state(
+        workspace: Option<AgentWorkspace>,
+        requests: BTreeMap<String, ApprovalRequest>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(StoreInner {
+                workspace,
+                requests: StdMutex::new(requests),
+            }),
+        }
+    }
+
+    /// Add a new request
+    pub fn insert(&self, request: ApprovalRequest) -> Result<()> {
+        self.update(request.created_at, |requests| {
+            requests.insert(request.token.clone(), request);
+            Ok(((), true))
+        })
+    }
+
+    /// Request with `token` as it stands at `now`
+    pub fn get(&self, token: &str, now: DateTime<Utc>) -> Result<Option<ApprovalRequest>> {
+        self.update(now, |requests| Ok((requests.get(token).cloned(), false)))
+    }
+
+    /// Requests still waiting for an answer at `now`, oldest first
+    pub fn pending(&self, now: DateTime<Utc>) -> Result<Vec<ApprovalRequest>> {
+        self.update(now, |requests| {
+            let mut pending: Vec<ApprovalRequest> = requests
+                .values()
+                .filter(|request| request.status == ApprovalStatus::Pending)
+                .cloned()
+                .collect();
+            pending.sort_by_key(|request| request.created_at);
+            Ok((pending, false))
+        })
+    }
+
+    /// Answer the request with `token`
+    ///
+    /// Giving the answer the request already has returns it unchanged, so
+    /// approvals delivered twice are harmless. Fails if the token is unknown
+    /// or the request was already resolved the other way, including by
+    /// expiring.
+    pub fn resolve(
+        &self,
+        token: &str,
+        decision: ApprovalDecision,
+        now: DateTime<Utc>,
+    ) -> Result<ApprovalRequest> {
+        self.update(now, |requests| {
+            let request = requests.get_mut(token).ok_or_else(|| {
+                AgentError::invalid_state(format!("No approval request with token {}", token))
+            })?;
+            let wanted = ApprovalStatus::from(decision);
+            match request.status {
+                ApprovalStatus::Pending => {
+                    request.status = wanted;
+                    request.resolved_at = Some(now);
+                    info!(
+                        agent = %request.agent_id,
+                        decision = %request.decision_id,
+                        status = ?wanted,
+                        "Approval request answered"
+                    );
+                    Ok((request.clone(), true))
+                }
+                status if status == wanted => Ok((request.clone(), false)),
+                status => Err(AgentError::invalid_state(format!(
+                    "Approval request {} is already {}{}",
+                    token,
+                    match status {
+                        ApprovalStatus::Approved => "approved",
+                        _ => "rejected",
+                    },
+                    if request.timed_out {
+                        " after timing out"
+                    } else {
+                        ""
+                    }
+                ))),
+            }
+        })
+    }
+
+    /// Resolved requests the runner has not acted on yet, marking them as acted on
+    ///
+    /// Requests expiring by `now` are resolved first. Resolved requests are
+    /// kept for a day after that, so late duplicate answers stay harmless.
+    pub fn take_resolved(&self, now: DateTime<Utc>) -> Result<Vec<ApprovalRequest>> {
+        let retention = chrono::Duration::hours(RESOLVED_RETENTION_HOURS);
+        self.update(now, |requests| {
+            let before = requests.len();
+            requests.retain(|_, request| {
+                !request.released
+                    || request
+                        .resolved_at
+                        .is_some_and(|at| now.signed_duration_since(at) < retention)
+            });
+            let mut resolved: Vec<ApprovalRequest> = requests
+                .values_mut()
+                .filter(|request| request.status != ApprovalStatus::Pending && !request.released)
+                .map(|request| {
+                    request.released = true;
+                    request.clone()
+                })
+                .collect();
+            resolved.sort_by_key(|request| request.created_at);
+            let changed = !resolved.is_empty() || requests.len() != before;
+            Ok((resolved, changed))
+        })
+    }
+
+    /// Run `f` on the requests as they stand at `now`, persisting changes
+    ///
+    /// `f` returns its result and whether it changed the requests.
+    fn update<T>(
+        &self,
+        now: DateTime<Utc>,
+        f: impl FnOnce(&mut BTreeMap<String, ApprovalRequest>) -> Result<(T, bool)>,
+    ) -> Result<T> {
+        let mut requests = lock(&self.inner.requests);
+        // Another process may have answered a request since the last read,
+        // and none may write in between until the changes are persisted
+        let _file_lock = match &self.inner.workspace {
+            Some(workspace) => {
+                let file_lock = workspace.lock(ArtifactKind::Approvals.file_name())?;
+                *requests = Self::load(workspace)?;
+                Some(file_lock)
+            }
+            None => None,
+        };
+        let mut expired = false;
+        for request in requests.values_mut() {
+            expired |= request.expire(now);
+        }
+        let (result, changed) = f(&mut requests)?;
+        if expired || changed {
+            self.persist(&requests)?;
+        }
+        Ok(result)
+    }
+
+    fn load(workspace: &AgentWorkspace) -> Result<BTreeMap<String, ApprovalRequest>> {
+        let requests: Vec<ApprovalRequest> = workspace
+            .read_json(ArtifactKind::Approvals.file_name())?
+            .unwrap_or_default();
+        Ok(requests
+            .into_iter()
+            .map(|request| (request.token.clone(), request))
+            .collect())
+    }
+
+    fn persist(&self, requests: &BTreeMap<String, ApprovalRequest>) -> Result<()> {
+        let Some(workspace) = &self.inner.workspace else {
+            return Ok(());
+        };
+        let requests: Vec<&ApprovalRequest> = requests.values().collect();
+        workspace.write_json(ArtifactKind::Approvals.file_name(), &requests)
+    }
+}
+
+/// Notifier fanning approval requests out to every approver
+struct Approvers(Vec<WebhookNotifier>);
+
+impl Notifier for Approvers {
+    fn notify(&self, notification: Notification) {
+        for approver in &self.0 {
+            approver.notify(notification.clone());
+        }
+    }
+}
+
+/// Holds back actions above the policy's threshold until they are approved
+#[derive(Clone)]
+pub struct ApprovalGate {
+    policy: ApprovalPolicy,
+    store: ApprovalStore,
+    notifier: Arc<dyn Notifier>,
+}
+
+impl ApprovalGate {
+    /// Gate following `policy`, keeping its requests in `store`
+    ///
+    /// Requests are announced to the policy's approvers by webhook.
+    pub fn new(policy: ApprovalPolicy, store: ApprovalStore) -> Result<Self> {
+        let approvers = policy
+            .approvers
+            .iter()
+            .map(|approver| {
+                WebhookNotifier::new(&WebhookSettings {
+                    urls: vec![approver.url.clone()],
+                    secret: approver.secret.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            policy,
+            store,
+            notifier: Arc::new(Approvers(approvers)),
+        })
+    }
+
+    /// Announce requests to `notifier` instead of the policy's approvers
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Policy the gate follows
+    pub fn policy(&self) -> &ApprovalPolicy {
+        &self.policy
+    }
+
+    /// Store of the gate's requests
+    pub fn store(&self) -> &ApprovalStore {
+        &self.store
+    }
+
+    /// Whether an action worth `value_sol` needs approval
+    ///
+    /// Actions that could not be valued always do.
+    pub fn requires_approval(&self, value_sol: Option<f64>) -> bool {
+        match value_sol {
+            Some(value) => value >= self.policy.auto_approve_below_sol,
+            None => true,
+        }
+    }
+
+    /// Park `action` until it is approved, announcing it to the approvers
+    pub fn request(
+        &self,
+        agent_id: &AgentId,
+        wallet: &Pubkey,
+        decision_id: Uuid,
+        action: &AgentAction,
+        value_sol: Option<f64>,
+        preview: Option<TransferPreview>,
+    ) -> Result<ApprovalRequest> {
+        let now = Utc::now();
+        let timeout =
+            chrono::Duration::seconds(i64::try_from(self.policy.timeout_secs).unwrap_or(i64::MAX));
+        let request = ApprovalRequest {
+            token: Uuid::new_v4().simple().to_string(),
+            agent_id: agent_id.clone(),
+            decision_id,
+            action: action.clone(),
+            description: action.description(),
+            value_sol,
+            preview,
+            created_at: now,
+            expires_at: now
+                .checked_add_signed(timeout)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            on_timeout: self.policy.on_timeout,
+            status: ApprovalStatus::Pending,
+            resolved_at: None,
+            timed_out: false,
+            released: false,
+        };
+        self.store.insert(request.clone())?;
+        info!(
+            agent = %agent_id,
+            decision = %request.decision_id,
+            "Action waits for approval: {}",
+            request.description
+        );
+        self.notifier.notify(Notification::new(
+            AgentEvent::ApprovalRequested {
+                agent_id: agent_id.clone(),
+                action: request.description.clone(),
+                value_sol,
+                preview: request.preview.clone(),
+                token: request.token.clone(),
+                expires_at: request.expires_at,
+            },
+            wallet,
+        ));
+        Ok(request)
+    }
+
+    /// Requests resolved since the last call, see [`ApprovalStore::take_resolved`]
+    pub fn take_resolved(&self, now: DateTime<Utc>) -> Result<Vec<ApprovalRequest>> {
+        self.store.take_resolved(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::AgentWorkspaces;
+    use tempfile::tempdir;
+
+    /// Notifier remembering what it was handed
+    #[derive(Default)]
+    struct Recorder(StdMutex<Vec<Notification>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, notification: Notification) {
+            lock(&self.0).push(notification);
+        }
+    }
+
+    fn gate(store: ApprovalStore, on_timeout: ApprovalTimeoutAction) -> Result<ApprovalGate> {
+        let policy = ApprovalPolicy {
+            auto_approve_below_sol: 1.0,
+            approvers: Vec::new(),
+            timeout_secs: 60,
+            on_timeout,
+        };
+        ApprovalGate::new(policy, store)
+    }
+
+    fn transfer(amount: u64) -> AgentAction {
+        AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(amount),
+            memo: None,
+        }
+    }
+
+    fn park(gate: &ApprovalGate) -> Result<ApprovalRequest> {
+        gate.request(
+            &"alpha".to_string(),
+            &Pubkey::new_unique(),
+            Uuid::new_v4(),
+            &transfer(2_000_000_000),
+            Some(2.0),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_threshold_and_unvalued_actions() -> Result<()> {
+        let gate = gate(ApprovalStore::new(), ApprovalTimeoutAction::Reject)?;
+        let context = AgentContext::new(Pubkey::new_unique());
+        let small = value_at_stake(&transfer(500_000_000), &context);
+        let large = value_at_stake(&transfer(1_000_000_000), &context);
+        assert_eq!(small, Some(0.5));
+        assert!(!gate.requires_approval(small));
+        assert!(gate.requires_approval(large));
+
+        // Token spends without a price cannot be valued and need approval
+        let unpriced = AgentAction::TransferToken {
+            mint: Pubkey::new_unique(),
+            to: Pubkey::new_unique(),
+            amount: 1,
+            memo: None,
+        };
+        assert_eq!(value_at_stake(&unpriced, &context), None);
+        assert!(gate.requires_approval(None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_approved_request_is_released_once() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspace = AgentWorkspaces::open(dir.path())?.register("alpha")?;
+        let recorder = Arc::new(Recorder::default());
+        let gate = gate(
+            ApprovalStore::open(workspace.clone())?,
+            ApprovalTimeoutAction::Reject,
+        )?
+        .with_notifier(recorder.clone());
+        let request = park(&gate)?;
+        let now = Utc::now();
+
+        let notified = lock(&recorder.0).clone();
+        assert!(matches!(
+            &notified[..],
+            [Notification { event: AgentEvent::ApprovalRequested { token, value_sol: Some(_), .. }, .. }]
+                if *token == request.token
+        ));
+        assert!(gate.take_resolved(now)?.is_empty());
+
+        // Answered from another process sharing the workspace
+        let other = ApprovalStore::open(workspace)?;
+        assert_eq!(other.pending(now)?.len(), 1);
+        let approved = other.resolve(&request.token, ApprovalDecision::Approve, now)?;
+        assert_eq!(approved.status, ApprovalStatus::Approved);
+
+        let released = gate.take_resolved(now)?;
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].status, ApprovalStatus::Approved);
+        assert!(!released[0].timed_out);
+        assert!(gate.take_resolved(now)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_request_is_released_as_rejected() -> Result<()> {
+        let gate = gate(ApprovalStore::new(), ApprovalTimeoutAction::Approve)?;
+        let request = park(&gate)?;
+        let now = Utc::now();
+
+        gate.store()
+            .resolve(&request.token, ApprovalDecision::Reject, now)?;
+        let released = gate.take_resolved(now)?;
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].status, ApprovalStatus::Rejected);
+
+        // A rejected request cannot be approved afterwards
+        assert!(gate
+            .store()
+            .resolve(&request.token, ApprovalDecision::Approve, now)
+            .is_err());
+        assert!(gate
+            .store()
+            .resolve("unknown", ApprovalDecision::Approve, now)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_request_follows_on_timeout() -> Result<()> {
+        let rejecting = gate(ApprovalStore::new(), ApprovalTimeoutAction::Reject)?;
+        let request = park(&rejecting)?;
+        let now = Utc::now();
+        let later = now + chrono::Duration::seconds(61);
+
+        assert!(rejecting.store().pending(later)?.is_empty());
+        let released = rejecting.take_resolved(later)?;
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].status, ApprovalStatus::Rejected);
+        assert!(released[0].timed_out);
+        // Approving too late does not bring the action back
+        assert!(rejecting
+            .store()
+            .resolve(&request.token, ApprovalDecision::Approve, later)
+            .is_err());
+
+        let approving = gate(ApprovalStore::new(), ApprovalTimeoutAction::Approve)?;
+        park(&approving)?;
+        let released = approving.take_resolved(later)?;
+        assert_eq!(released[0].status, ApprovalStatus::Approved);
+        assert!(released[0].timed_out);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_stores_keep_every_request() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspace = AgentWorkspaces::open(dir.path())?.register("alpha")?;
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let store = ApprovalStore::open(workspace.clone());
+                std::thread::spawn(move || -> Result<()> {
+                    let gate = gate(store?, ApprovalTimeoutAction::Reject)?;
+                    for _ in 0..10 {
+                        park(&gate)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer
+                .join()
+                .map_err(|_| AgentError::invalid_state("writer panicked"))??;
+        }
+
+        let store = ApprovalStore::open(workspace)?;
+        assert_eq!(store.pending(Utc::now())?.len(), 40);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_approvals_are_idempotent() -> Result<()> {
+        let gate = gate(ApprovalStore::new(), ApprovalTimeoutAction::Reject)?;
+        let request = park(&gate)?;
+        let now = Utc::now();
+
+        let first = gate
+            .store()
+            .resolve(&request.token, ApprovalDecision::Approve, now)?;
+        let later = now + chrono::Duration::seconds(5);
+        let second = gate
+            .store()
+            .resolve(&request.token, ApprovalDecision::Approve, later)?;
+        assert_eq!(second.status, ApprovalStatus::Approved);
+        assert_eq!(second.resolved_at, first.resolved_at);
+
+        // The action is released once, and approving again after that is
+        // still harmless
+        assert_eq!(gate.take_resolved(later)?.len(), 1);
+        gate.store()
+            .resolve(&request.token, ApprovalDecision::Approve, later)?;
+        assert!(gate.take_resolved(later)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+//! Audit log of agent decisions
+//!
+//! Every decision an [`AgentRunner`](crate::runner::AgentRunner) records is
+//! appended to the agent's [`AuditLog`] with a sequence number that only
+//! grows. Logs opened on an [`AgentWorkspace`] persist their records as
+//! JSON lines, so sequence numbers continue where they left off after a
+//! restart and clients can resume from the last sequence they saw.
+//!
+//! Appended records are also broadcast to subscribers. Each subscriber can
+//! fall at most [`AuditConfig::stream_buffer`] records behind; a subscriber
+//! that lags further is told so instead of the log buffering without bound.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::audit::{AuditConfig, AuditLog};
+//! use agent_wallet_agent::workspace::AgentWorkspaces;
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let workspace = AgentWorkspaces::open("/home/me/.agent-wallet")?.register("trader-1")?;
+//! let audit = AuditLog::open(workspace, AuditConfig::default())?;
+//! for record in audit.since(Some(41))? {
+//!     println!("#{} {:?}", record.sequence, record.decision.outcome);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use agent_wallet_core::Error as CoreError;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::agent::AgentId;
+use crate::decision::{AgentAction, DecisionRecord};
+use crate::error::Result;
+use crate::sync::lock;
+use crate::workspace::{AgentWorkspace, ArtifactKind};
+
+/// Replacement for redacted values
+pub const REDACTED: &str = "[redacted]";
+
+/// Decision with its position in the agent's audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Sequence number, starting at 1 and increasing by one per record
+    pub sequence: u64,
+    /// The recorded decision
+    #[serde(flatten)]
+    pub decision: DecisionRecord,
+}
+
+/// Fields hidden from audit records and prompts shown outside the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionPolicy {
+    /// Hide the data of external triggers
+    pub trigger_data: bool,
+    /// Hide transfer memos
+    pub memos: bool,
+    /// Shorten addresses that are context rather than something to act on,
+    /// such as the wallet's own and past destinations
+    ///
+    /// Audit records only hold addresses actions were sent to, so this only
+    /// affects prompts.
+    pub addresses: bool,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            trigger_data: true,
+            memos: true,
+            addresses: false,
+        }
+    }
+}
+
+impl RedactionPolicy {
+    /// Show records unchanged
+    pub fn none() -> Self {
+        Self {
+            trigger_data: false,
+            memos: false,
+            addresses: false,
+        }
+    }
+
+    /// Policy for contexts sent to a language model
+    ///
+    /// Trigger data stays, since it is often what the model has to react to.
+    pub fn for_prompts() -> Self {
+        Self {
+            trigger_data: false,
+            memos: true,
+            addresses: true,
+        }
+    }
+
+    /// Copy of `record` with the policy's fields redacted
+    pub fn apply(&self, record: &AuditRecord) -> AuditRecord {
+        let mut record = record.clone();
+        if self.trigger_data {
+            if let Some(trigger) = &mut record.decision.trigger {
+                trigger.data = serde_json::Value::String(REDACTED.to_string());
+            }
+        }
+        if self.memos {
+            if let Some(
+                AgentAction::TransferSol { memo, .. }
+                | AgentAction::TransferToken { memo, .. }
+                | AgentAction::BatchTransfer { memo, .. },
+            ) = &mut record.decision.action
+            {
+                if memo.is_some() {
+                    *memo = Some(REDACTED.to_string());
+                }
+            }
+        }
+        record
+    }
+}
+
+/// Audit log settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditConfig {
+    /// Records a subscriber may fall behind before it is dropped
+    pub stream_buffer: usize,
+    /// Records kept in memory by logs without a workspace
+    pub retained_in_memory: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            stream_buffer: 256,
+            retained_in_memory: 1024,
+        }
+    }
+}
+
+struct LogState {
+    next_sequence: u64,
+    recent: VecDeque<AuditRecord>,
+}
+
+struct Inner {
+    agent_id: AgentId,
+    workspace: Option<AgentWorkspace>,
+    config: AuditConfig,
+    state: StdMutex<LogState>,
+    sender: broadcast::Sender<AuditRecord>,
+}
+
+/// Sequenced, subscribable log of an agent's decisions
+#[derive(Clone)]
+pub struct AuditLog {
+    inner: Arc<Inner>,
+}
+
+impl AuditLog {
+    /// Log kept in memory only
+    pub fn in_memory(agent_id: impl Into<AgentId>, config: AuditConfig) -> Self {
+        Self::with_state(agent_id.into(), None, config, 1)
+    }
+
+    /// Log persisted in the agent's workspace, resuming its sequence
+    pub fn open(workspace: AgentWorkspace, config: AuditConfig) -> Result<Self> {
+        let last = read_records(&workspace)?
+            .last()
+            .map(|record| record.sequence)
+            .unwrap_or(0);
+        let agent_id = workspace.agent_id().clone();
+        Ok(Self::with_state(
+            agent_id,
+            Some(workspace),
+            config,
+            last + 1,
+        ))
+    }
+
+    fn with_state(
+        agent_id: AgentId,
+        workspace: Option<AgentWorkspace>,
+        config: AuditConfig,
+        next_sequence: u64,
+    ) -> Self {
+        let (sender, _) = broadcast::channel(config.stream_buffer.max(1));
+        Self {
+            inner: Arc::new(Inner {
+                agent_id,
+                workspace,
+                config,
+                state: StdMutex::new(LogState {
+                    next_sequence,
+                    recent: VecDeque::new(),
+                }),
+                sender,
+            }),
+        }
+    }
+
+    /// Agent the log belongs to
+    pub fn agent_id(&self) -> &AgentId {
+        &self.inner.agent_id
+    }
+
+    /// Log settings
+    pub fn config(&self) -> &AuditConfig {
+        &self.inner.config
+    }
+
+    /// Sequence number of the latest record, 0 if the log is empty
+    pub fn last_sequence(&self) -> u64 {
+        lock(&self.inner.state).next_sequence - 1
+    }
+
+    /// Append a decision and notify subscribers
+    pub fn append(&self, decision: DecisionRecord) -> Result<AuditRecord> {
+        let mut state = lock(&self.inner.state);
+        let record = AuditRecord {
+            sequence: state.next_sequence,
+            decision,
+        };
+
+        match &self.inner.workspace {
+            Some(workspace) => {
+                let mut line = serde_json::to_vec(&record).map_err(CoreError::from)?;
+                line.push(b'\n');
+                workspace.append(ArtifactKind::AuditLog.file_name(), &line)?;
+            }
+            None => {
+                if state.recent.len() == self.inner.config.retained_in_memory {
+                    state.recent.pop_front();
+                }
+                state.recent.push_back(record.clone());
+            }
+        }
+        state.next_sequence += 1;
+
+        // Sending under the lock keeps broadcast order equal to sequence order
+        let _ = self.inner.sender.send(record.clone());
+        Ok(record)
+    }
+
+    /// Records after `cursor`, oldest first; all retained records for `None`
+    pub fn since(&self, cursor: Option<u64>) -> Result<Vec<AuditRecord>> {
+        let after = cursor.unwrap_or(0);
+        let records = match &self.inner.workspace {
+            Some(workspace) => read_records(workspace)?,
+            None => lock(&self.inner.state).recent.iter().cloned().collect(),
+        };
+        Ok(records
+            .into_iter()
+            .filter(|record| record.sequence > after)
+            .collect())
+    }
+
+    /// Receive records appended from now on
+    ///
+    /// Subscribe before reading the backlog with [`AuditLog::since`] so no
+    /// record falls between the two; skip records already seen by sequence.
+    pub fn subscribe(&self) -> broadcast::Receiver<AuditRecord> {
+        self.inner.sender.subscribe()
+    }
+}
+
+/// Read the persisted records of a workspace
+fn read_records(workspace: &AgentWorkspace) -> Result<Vec<AuditRecord>> {
+    let Some(bytes) = workspace.read(ArtifactKind::AuditLog.file_name())? else {
+        return Ok(Vec::new());
+    };
+    bytes
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).map_err(|e| CoreError::from(e).into()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision::DecisionOutcome;
+    use crate::error::AgentError;
+    use crate::workspace::AgentWorkspaces;
+    use agent_wallet_core::TriggerPayload;
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    fn decision(agent_id: &str) -> DecisionRecord {
+        DecisionRecord {
+            agent_id: agent_id.to_string(),
+            timestamp: Utc::now(),
+            action: None,
+            outcome: DecisionOutcome::NoAction,
+            trigger: Some(TriggerPayload::new(
+                "http",
+                serde_json::json!({ "secret": "x" }),
+            )),
+        }
+    }
+
+    #[test]
+    fn test_sequence_persists_across_reopen() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspace = AgentWorkspaces::open(dir.path())?.register("alpha")?;
+
+        let log = AuditLog::open(workspace.clone(), AuditConfig::default())?;
+        log.append(decision("alpha"))?;
+        log.append(decision("alpha"))?;
+
+        let reopened = AuditLog::open(workspace, AuditConfig::default())?;
+        assert_eq!(reopened.last_sequence(), 2);
+        assert_eq!(reopened.append(decision("alpha"))?.sequence, 3);
+        let sequences: Vec<u64> = reopened
+            .since(Some(1))?
+            .iter()
+            .map(|record| record.sequence)
+            .collect();
+        assert_eq!(sequences, vec![2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_redaction() -> Result<()> {
+        let log = AuditLog::in_memory("alpha", AuditConfig::default());
+        let record = log.append(decision("alpha"))?;
+
+        let redacted = RedactionPolicy::default().apply(&record);
+        let trigger = redacted
+            .decision
+            .trigger
+            .ok_or_else(|| AgentError::invalid_state("trigger was dropped"))?;
+        assert_eq!(trigger.data, serde_json::json!(REDACTED));
+        assert_eq!(
+            RedactionPolicy::none().apply(&record).decision.trigger,
+            record.decision.trigger
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,636 @@
+//! Several strategies sharing one wallet
+//!
+//! A [`CompositeAgent`] owns an ordered list of child agents. On each tick it
+//! asks every child for a decision, detects proposals that conflict and
+//! resolves them according to its [`ConflictPolicy`]. Two proposals conflict
+//! when together they spend more than the wallet's remaining daily budget,
+//! or when they swap the same pair of tokens in opposite directions.
+//!
+//! Each child may be given a share of the wallet's daily limit. The child
+//! then sees only its sub-budget in the context it decides on, and spending
+//! attributed to one child never eats into another's share. Spending is
+//! attributed when a proposal is selected, so a selected action that later
+//! fails to execute still counts against its child for the day.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_agent::composite::{ChildAgent, CompositeAgent, ConflictPolicy};
+//! use agent_wallet_agent::prelude::*;
+//!
+//! # fn example(
+//! #     payroll: DeterministicAgent,
+//! #     staking: DeterministicAgent,
+//! # ) -> agent_wallet_agent::Result<()> {
+//! let agent = CompositeAgent::new("treasury", ConflictPolicy::HighestPriority)
+//!     .with_child(ChildAgent::new(Arc::new(payroll)).with_priority(10).with_budget_share(0.6))?
+//!     .with_child(ChildAgent::new(Arc::new(staking)).with_budget_share(0.4))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use agent_wallet_core::sol::Lamports;
+use agent_wallet_core::template::ActionTemplate;
+use agent_wallet_core::token::TOKEN_ACCOUNT_RENT_LAMPORTS;
+
+use crate::agent::{Agent, CancellationToken, DynAgent};
+use crate::context::AgentContext;
+use crate::decision::AgentAction;
+use crate::error::{AgentError, Result};
+use crate::sync::lock;
+
+/// How conflicting proposals of child agents are resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The proposal of the child listed first wins
+    #[default]
+    FirstWins,
+    /// The proposal of the child with the highest priority wins; ties go to
+    /// the child listed first
+    HighestPriority,
+    /// Every proposal involved in a conflict is dropped
+    RejectConflicting,
+}
+
+/// Why two proposals cannot both be executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// Together they spend more than the remaining daily budget
+    Budget,
+    /// They swap the same pair of tokens in opposite directions
+    OppositeSwaps,
+}
+
+/// A child of a [`CompositeAgent`]
+pub struct ChildAgent {
+    agent: Arc<dyn DynAgent>,
+    priority: i32,
+    budget_share: Option<f64>,
+}
+
+impl ChildAgent {
+    /// Child with priority 0 and no sub-budget of its own
+    pub fn new(agent: Arc<dyn DynAgent>) -> Self {
+        Self {
+            agent,
+            priority: 0,
+            budget_share: None,
+        }
+    }
+
+    /// Priority used by [`ConflictPolicy::HighestPriority`]
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Reserve a fraction (0-1) of the wallet's daily limit for this child
+    pub fn with_budget_share(mut self, share: f64) -> Self {
+        self.budget_share = Some(share);
+        self
+    }
+
+    /// Name of the child agent
+    pub fn name(&self) -> &str {
+        self.agent.name()
+    }
+
+    /// Priority of the child
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Fraction of the daily limit reserved for the child, if any
+    pub fn budget_share(&self) -> Option<f64> {
+        self.budget_share
+    }
+}
+
+/// An action proposed by one child
+#[derive(Debug, Clone)]
+pub struct Proposal {
+    /// Index of the proposing child
+    pub child: usize,
+    /// Priority of the proposing child
+    pub priority: i32,
+    /// Proposed action
+    pub action: AgentAction,
+    /// Amount the action counts against the daily budget
+    pub cost: Lamports,
+}
+
+/// Spending attributed to each child since the last daily reset
+#[derive(Debug, Default)]
+struct SubBudgets {
+    last_reset: Option<DateTime<Utc>>,
+    spent: Vec<Lamports>,
+}
+
+/// Agent running several child agents against one wallet
+pub struct CompositeAgent {
+    name: String,
+    policy: ConflictPolicy,
+    children: Vec<ChildAgent>,
+    budgets: Mutex<SubBudgets>,
+}
+
+impl CompositeAgent {
+    /// Composite without children
+    pub fn new(name: impl Into<String>, policy: ConflictPolicy) -> Self {
+        Self {
+            name: name.into(),
+            policy,
+            children: Vec::new(),
+            budgets: Mutex::new(SubBudgets::default()),
+        }
+    }
+
+    /// Append a child, after the children added before it
+    ///
+    /// Fails if the child's budget share is outside (0, 1] or the shares of
+    /// all children add up to more than the whole daily limit.
+    pub fn with_child(mut self, child: ChildAgent) -> Result<Self> {
+        if let Some(share) = child.budget_share {
+            if !(share > 0.0 && share <= 1.0) {
+                return Err(AgentError::config(format!(
+                    "Budget share of child agent '{}' must be in (0, 1], got {}",
+                    child.name(),
+                    share
+                )));
+            }
+        }
+        let total: f64 = self
+            .children
+            .iter()
+            .chain(std::iter::once(&child))
+            .filter_map(|child| child.budget_share)
+            .sum();
+        if total > 1.0 + f64::EPSILON {
+            return Err(AgentError::config(format!(
+                "Budget shares of the children of '{}' add up to {}, more than the daily limit",
+                self.name, total
+            )));
+        }
+        self.children.push(child);
+        lock(&self.budgets).spent.push(Lamports::ZERO);
+        Ok(self)
+    }
+
+    /// Conflict policy of the composite
+    pub fn policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+
+    /// Children in the order they were added
+    pub fn children(&self) -> &[ChildAgent] {
+        &self.children
+    }
+
+    /// Amount spent by each child today, as attributed by the composite
+    pub fn spent(&self) -> Vec<Lamports> {
+        lock(&self.budgets).spent.clone()
+    }
+
+    /// Collect the children's proposals and resolve their conflicts
+    ///
+    /// Returns the selected actions in the order they should be executed;
+    /// none of them conflicts with another, and all of them are counted
+    /// against their children's sub-budgets. [`Agent::decide`] selects only
+    /// the first of them.
+    pub async fn decide_all(&self, context: &AgentContext) -> Result<Vec<AgentAction>> {
+        let selected = self.select(context, CancellationToken::new()).await;
+        Ok(self.commit(selected))
+    }
+
+    /// Resolved proposals of the children, best first
+    async fn select(&self, context: &AgentContext, cancel: CancellationToken) -> Vec<Proposal> {
+        let contexts = self.child_contexts(context);
+        let decisions = join_all(
+            self.children
+                .iter()
+                .zip(&contexts)
+                .map(|(child, context)| child.agent.decide_cancellable(context, cancel.clone())),
+        )
+        .await;
+
+        let mut proposals = Vec::new();
+        for (index, (decision, child_context)) in decisions.into_iter().zip(&contexts).enumerate() {
+            let child = &self.children[index];
+            let action = match decision {
+                Ok(Some(AgentAction::NoOp)) | Ok(None) => continue,
+                Ok(Some(action)) => action,
+                Err(e) => {
+                    warn!("Child agent '{}' failed to decide: {}", child.name(), e);
+                    continue;
+                }
+            };
+            let cost = budget_cost(&action);
+            if child.budget_share.is_some()
+                && cost
+                    > child_context
+                        .spending_limits
+                        .remaining_daily_budget_lamports
+            {
+                warn!(
+                    "Dropping proposal of child agent '{}': {} lamports exceed its sub-budget",
+                    child.name(),
+                    cost.as_u64()
+                );
+                continue;
+            }
+            proposals.push(Proposal {
+                child: index,
+                priority: child.priority,
+                action,
+                cost,
+            });
+        }
+
+        let budget = context.spending_limits.remaining_daily_budget_lamports;
+        resolve(self.policy, proposals, budget)
+    }
+
+    /// Attribute selected proposals to their children's sub-budgets
+    fn commit(&self, selected: Vec<Proposal>) -> Vec<AgentAction> {
+        let mut budgets = lock(&self.budgets);
+        selected
+            .into_iter()
+            .map(|proposal| {
+                let spent = &mut budgets.spent[proposal.child];
+                *spent = spent
+                    .checked_add(proposal.cost)
+                    .unwrap_or(Lamports::new(u64::MAX));
+                proposal.action
+            })
+            .collect()
+    }
+
+    /// Context each child decides on, narrowed to its sub-budget
+    fn child_contexts(&self, context: &AgentContext) -> Vec<AgentContext> {
+        let mut budgets = lock(&self.budgets);
+        let last_reset = context.spending_limits.last_reset;
+        if budgets.last_reset != Some(last_reset) {
+            budgets.last_reset = Some(last_reset);
+            budgets
+                .spent
+                .iter_mut()
+                .for_each(|spent| *spent = Lamports::ZERO);
+        }
+
+        let limits = &context.spending_limits;
+        self.children
+            .iter()
+            .zip(&budgets.spent)
+            .map(|(child, spent)| {
+                let mut child_context = context.clone();
+                if let Some(share) = child.budget_share {
+                    let daily = Lamports::new(
+                        (limits.daily_limit_lamports.as_u64() as f64 * share).floor() as u64,
+                    );
+                    let child_limits = &mut child_context.spending_limits;
+                    child_limits.daily_limit_lamports = daily;
+                    child_limits.per_transaction_limit_lamports =
+                        limits.per_transaction_limit_lamports.min(daily);
+                    child_limits.remaining_daily_budget_lamports = daily
+                        .saturating_sub(*spent)
+                        .min(limits.remaining_daily_budget_lamports);
+                }
+                child_context
+            })
+            .collect()
+    }
+}
+
+impl Agent for CompositeAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn decide(&self, context: &AgentContext) -> Result<Option<AgentAction>> {
+        self.decide_cancellable(context, CancellationToken::new())
+            .await
+    }
+
+    async fn decide_cancellable(
+        &self,
+        context: &AgentContext,
+        cancel: CancellationToken,
+    ) -> Result<Option<AgentAction>> {
+        let mut selected = self.select(context, cancel).await;
+        selected.truncate(1);
+        Ok(self.commit(selected).pop())
+    }
+
+    fn templates(&self) -> Vec<ActionTemplate> {
+        let mut templates = Vec::new();
+        for template in self
+            .children
+            .iter()
+            .flat_map(|child| child.agent.templates())
+        {
+            if !templates.contains(&template) {
+                templates.push(template);
+            }
+        }
+        templates
+    }
+}
+
+/// Why `a` and `b` cannot both be executed out of `budget`, if they cannot
+pub fn conflict_between(a: &Proposal, b: &Proposal, budget: Lamports) -> Option<Conflict> {
+    if let (
+        AgentAction::SwapTokens {
+            input_mint: a_in,
+            output_mint: a_out,
+            ..
+        },
+        AgentAction::SwapTokens {
+            input_mint: b_in,
+            output_mint: b_out,
+            ..
+        },
+    ) = (&a.action, &b.action)
+    {
+        if a_in == b_out && a_out == b_in {
+            return Some(Conflict::OppositeSwaps);
+        }
+    }
+    match a.cost.checked_add(b.cost) {
+        Some(total) if total <= budget => None,
+        _ => Some(Conflict::Budget),
+    }
+}
+
+/// Select the proposals to execute under `policy`
+fn resolve(
+    policy: ConflictPolicy,
+    mut proposals: Vec<Proposal>,
+    budget: Lamports,
+) -> Vec<Proposal> {
+    if policy == ConflictPolicy::RejectConflicting {
+        let mut rejected = vec![false; proposals.len()];
+        for (i, a) in proposals.iter().enumerate() {
+            for (j, b) in proposals.iter().enumerate().skip(i + 1) {
+                if let Some(conflict) = conflict_between(a, b, budget) {
+                    debug!(
+                        "Proposals of children {} and {} conflict: {:?}",
+                        a.child, b.child, conflict
+                    );
+                    rejected[i] = true;
+                    rejected[j] = true;
+                }
+            }
+        }
+        return proposals
+            .into_iter()
+            .zip(rejected)
+            .filter_map(|(proposal, rejected)| (!rejected).then_some(proposal))
+            .collect();
+    }
+
+    if policy == ConflictPolicy::HighestPriority {
+        // Stable, so equal priorities keep the order of the children
+        proposals.sort_by_key(|proposal| std::cmp::Reverse(proposal.priority));
+    }
+    let mut selected: Vec<Proposal> = Vec::new();
+    let mut committed = Lamports::ZERO;
+    for proposal in proposals {
+        let opposed = selected.iter().any(|chosen| {
+            conflict_between(chosen, &proposal, Lamports::new(u64::MAX))
+                == Some(Conflict::OppositeSwaps)
+        });
+        let affordable = committed
+            .checked_add(proposal.cost)
+            .is_some_and(|total| total <= budget);
+        if opposed || !affordable {
+            debug!(
+                "Proposal of child {} loses to earlier selections",
+                proposal.child
+            );
+            continue;
+        }
+        committed = committed.checked_add(proposal.cost).unwrap_or(committed);
+        selected.push(proposal);
+    }
+    selected
+}
+
+/// Amount an action counts against the daily budget
+///
+/// Values actions the way the wallet does: tokens count 1:1 with lamports,
+/// and actions that bring funds back cost nothing.
+pub fn budget_cost(action: &AgentAction) -> Lamports {
+    match action {
+        AgentAction::TransferSol { amount, .. } | AgentAction::StakeTokens { amount, .. } => {
+            *amount
+        }
+        AgentAction::TransferToken { amount, .. }
+        | AgentAction::SwapTokens { amount, .. }
+        | AgentAction::BurnToken { amount, .. } => Lamports::new(*amount),
+        AgentAction::BatchTransfer { transfers, .. } => {
+            Lamports::new(AgentAction::batch_total(transfers).unwrap_or(u64::MAX))
+        }
+        AgentAction::ProvideLiquidity {
+            token_a_amount,
+            token_b_amount,
+            ..
+        } => Lamports::new(token_a_amount.saturating_add(*token_b_amount)),
+        AgentAction::CreateTokenAccount { .. } => Lamports::new(TOKEN_ACCOUNT_RENT_LAMPORTS),
+        AgentAction::RemoveLiquidity { .. }
+        | AgentAction::UnstakeTokens { .. }
+        | AgentAction::CloseTokenAccount { .. }
+        | AgentAction::MintToken { .. }
+        | AgentAction::ProtocolInteraction { .. }
+        | AgentAction::NoOp => Lamports::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_wallet_core::sol::LAMPORTS_PER_SOL;
+    use solana_sdk::pubkey::Pubkey;
+
+    /// Child that always proposes the same action
+    struct Scripted(&'static str, AgentAction);
+
+    impl Agent for Scripted {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn decide(&self, _context: &AgentContext) -> Result<Option<AgentAction>> {
+            Ok(Some(self.1.clone()))
+        }
+    }
+
+    /// Child that proposes to spend whatever budget it is shown
+    struct Greedy(Pubkey);
+
+    impl Agent for Greedy {
+        fn name(&self) -> &str {
+            "greedy"
+        }
+
+        async fn decide(&self, context: &AgentContext) -> Result<Option<AgentAction>> {
+            let amount = context.spending_limits.remaining_daily_budget_lamports;
+            Ok(
+                (amount > Lamports::ZERO).then_some(AgentAction::TransferSol {
+                    to: self.0,
+                    amount,
+                    memo: None,
+                }),
+            )
+        }
+    }
+
+    fn transfer(to: Pubkey, sol: u64) -> AgentAction {
+        AgentAction::TransferSol {
+            to,
+            amount: Lamports::new(sol * LAMPORTS_PER_SOL),
+            memo: None,
+        }
+    }
+
+    fn swap(input_mint: Pubkey, output_mint: Pubkey) -> AgentAction {
+        AgentAction::SwapTokens {
+            input_mint,
+            output_mint,
+            amount: 1_000,
+            min_output_amount: 900,
+        }
+    }
+
+    fn context(daily_sol: u64) -> AgentContext {
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        let daily = Lamports::new(daily_sol * LAMPORTS_PER_SOL);
+        context.spending_limits.daily_limit_lamports = daily;
+        context.spending_limits.per_transaction_limit_lamports = daily;
+        context.spending_limits.remaining_daily_budget_lamports = daily;
+        context
+    }
+
+    fn composite(
+        policy: ConflictPolicy,
+        children: Vec<(AgentAction, i32)>,
+    ) -> Result<CompositeAgent> {
+        let mut agent = CompositeAgent::new("composite", policy);
+        for (action, priority) in children {
+            agent = agent.with_child(
+                ChildAgent::new(Arc::new(Scripted("child", action))).with_priority(priority),
+            )?;
+        }
+        Ok(agent)
+    }
+
+    #[tokio::test]
+    async fn test_budget_conflict_per_policy() -> Result<()> {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        // Each fits the 10 SOL budget alone, not together
+        let children = || vec![(transfer(first, 6), 1), (transfer(second, 7), 5)];
+        let context = context(10);
+
+        let agent = composite(ConflictPolicy::FirstWins, children())?;
+        let actions = agent.decide_all(&context).await?;
+        assert!(matches!(
+            actions.as_slice(),
+            [AgentAction::TransferSol { to, .. }] if *to == first
+        ));
+
+        let agent = composite(ConflictPolicy::HighestPriority, children())?;
+        assert!(matches!(
+            agent.decide(&context).await?,
+            Some(AgentAction::TransferSol { to, .. }) if to == second
+        ));
+
+        let agent = composite(ConflictPolicy::RejectConflicting, children())?;
+        assert!(agent.decide(&context).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_opposite_swaps_per_policy() -> Result<()> {
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let payee = Pubkey::new_unique();
+        let children = || {
+            vec![
+                (swap(sol, usdc), 0),
+                (swap(usdc, sol), 3),
+                (transfer(payee, 1), 0),
+            ]
+        };
+        let context = context(10);
+
+        let agent = composite(ConflictPolicy::FirstWins, children())?;
+        let actions = agent.decide_all(&context).await?;
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(
+            &actions[0],
+            AgentAction::SwapTokens { input_mint, .. } if *input_mint == sol
+        ));
+
+        let agent = composite(ConflictPolicy::HighestPriority, children())?;
+        let actions = agent.decide_all(&context).await?;
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(
+            &actions[0],
+            AgentAction::SwapTokens { input_mint, .. } if *input_mint == usdc
+        ));
+
+        // Only the unrelated transfer survives
+        let agent = composite(ConflictPolicy::RejectConflicting, children())?;
+        let actions = agent.decide_all(&context).await?;
+        assert!(matches!(
+            actions.as_slice(),
+            [AgentAction::TransferSol { to, .. }] if *to == payee
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_children_spend_isolated_sub_budgets() -> Result<()> {
+        let agent = CompositeAgent::new("composite", ConflictPolicy::FirstWins)
+            .with_child(
+                ChildAgent::new(Arc::new(Greedy(Pubkey::new_unique()))).with_budget_share(0.25),
+            )?
+            .with_child(
+                ChildAgent::new(Arc::new(Greedy(Pubkey::new_unique()))).with_budget_share(0.75),
+            )?;
+        let context = context(4);
+
+        // Each child spends exactly its own share of the 4 SOL limit
+        let actions = agent.decide_all(&context).await?;
+        let amounts: Vec<u64> = actions
+            .iter()
+            .map(|action| budget_cost(action).as_u64())
+            .collect();
+        assert_eq!(amounts, vec![LAMPORTS_PER_SOL, 3 * LAMPORTS_PER_SOL]);
+        assert!(agent.decide_all(&context).await?.is_empty());
+
+        // A new day frees the shares again
+        let mut tomorrow = context.clone();
+        tomorrow.spending_limits.last_reset += chrono::Duration::days(1);
+        assert_eq!(agent.decide_all(&tomorrow).await?.len(), 2);
+
+        // Shares cannot exceed the whole limit
+        let overcommitted = CompositeAgent::new("composite", ConflictPolicy::FirstWins)
+            .with_child(
+                ChildAgent::new(Arc::new(Greedy(Pubkey::new_unique()))).with_budget_share(0.5),
+            )?
+            .with_child(
+                ChildAgent::new(Arc::new(Greedy(Pubkey::new_unique()))).with_budget_share(0.6),
+            );
+        assert!(matches!(overcommitted, Err(AgentError::Config(_))));
+        Ok(())
+    }
+}
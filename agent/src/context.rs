@@ -0,0 +1,11 @@
+//! Agent context
+//!
+//! Agents decide based on the [`AgentContext`] maintained by the wallet:
+//! balances, market data, spending limits and recent history. The type lives
+//! in the core crate so the wallet and the agents share one definition, and
+//! the wallet's [`ContextBuilder`] refreshes it before each decision.
+
+pub use agent_wallet_core::context::{ContextBuilder, ContextTtls};
+pub use agent_wallet_core::types::{
+    AgentContext, ContextFreshness, MarketConditions, SpendingLimits,
+};
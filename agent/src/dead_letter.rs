@@ -0,0 +1,382 @@
+//! Dead-lettering of actions that keep failing validation
+//!
+//! A language model stuck on the same invalid action would spend its repair
+//! attempts on it every tick. The [`DeadLetterQueue`] counts validation
+//! failures per action digest across ticks; once a digest has failed
+//! [`DeadLetterConfig::threshold`] times it is parked. Parked actions are not
+//! repaired again, are summarized in the prompt so the model stops proposing
+//! them, and are announced to subscribers. An entry leaves the queue when an
+//! operator clears it or once [`DeadLetterConfig::cool_off`] has passed.
+//!
+//! Queues opened on an [`AgentWorkspace`] persist their parked entries, so
+//! the CLI can list and clear them while the agent is not running.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::dead_letter::{DeadLetterConfig, DeadLetterQueue};
+//! use agent_wallet_agent::workspace::AgentWorkspaces;
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let workspace = AgentWorkspaces::open("/home/me/.agent-wallet")?.register("trader-1")?;
+//! let queue = DeadLetterQueue::open(workspace, DeadLetterConfig::default())?;
+//! for letter in queue.list(chrono::Utc::now()) {
+//!     println!("{} failed {} times: {:?}", letter.digest, letter.failures, letter.issues);
+//! }
+//! queue.clear(None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::error::Result;
+use crate::sync::lock;
+use crate::workspace::{AgentWorkspace, ArtifactKind};
+
+/// Default number of failing ticks before an action is parked
+pub const DEFAULT_DEAD_LETTER_THRESHOLD: u32 = 3;
+
+/// Default time a parked action stays parked
+const DEFAULT_COOL_OFF: Duration = Duration::from_secs(60 * 60);
+
+/// Distinct validation issues kept per action
+const MAX_ISSUES: usize = 5;
+
+/// Parked actions announced to a subscriber that has not caught up
+const NOTIFICATION_BUFFER: usize = 16;
+
+/// Dead-letter settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetterConfig {
+    /// Failing ticks after which an action is parked
+    pub threshold: u32,
+    /// Time after which a parked action is released automatically
+    pub cool_off: Duration,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_DEAD_LETTER_THRESHOLD,
+            cool_off: DEFAULT_COOL_OFF,
+        }
+    }
+}
+
+/// Action parked after repeatedly failing validation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// Digest of the action, see [`action_digest`]
+    pub digest: String,
+    /// The action as the model emitted it
+    pub action: Value,
+    /// Validation issues reported for the action, oldest first
+    pub issues: Vec<String>,
+    /// Ticks on which the action failed validation
+    pub failures: u32,
+    /// When the action first failed
+    pub first_failed_at: DateTime<Utc>,
+    /// When the action was parked
+    pub parked_at: DateTime<Utc>,
+}
+
+impl DeadLetter {
+    /// When the action is released after `cool_off`
+    pub fn expires_at(&self, cool_off: Duration) -> DateTime<Utc> {
+        let cool_off = chrono::Duration::from_std(cool_off).unwrap_or(chrono::Duration::MAX);
+        self.parked_at
+            .checked_add_signed(cool_off)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// Digest identifying an action independently of its formatting
+///
+/// Object keys are sorted before hashing, so the same action emitted with
+/// different whitespace or field order has the same digest.
+pub fn action_digest(action: &Value) -> String {
+    // serde_json keeps object keys sorted, making this encoding canonical
+    let hash = Sha256::digest(action.to_string().as_bytes());
+    hex::encode(&hash[..8])
+}
+
+/// Failures of an action that is not parked yet
+struct Pending {
+    failures: u32,
+    first_failed_at: DateTime<Utc>,
+    issues: Vec<String>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: HashMap<String, Pending>,
+    parked: BTreeMap<String, DeadLetter>,
+}
+
+struct Inner {
+    config: DeadLetterConfig,
+    workspace: Option<AgentWorkspace>,
+    state: StdMutex<QueueState>,
+    sender: broadcast::Sender<DeadLetter>,
+}
+
+/// Shared dead-letter list of one agent
+#[derive(Clone)]
+pub struct DeadLetterQueue {
+    inner: Arc<Inner>,
+}
+
+impl DeadLetterQueue {
+    /// Queue kept in memory only
+    pub fn new(config: DeadLetterConfig) -> Self {
+        Self::with_state(config, None, BTreeMap::new())
+    }
+
+    /// Queue persisted in the agent's workspace
+    pub fn open(workspace: AgentWorkspace, config: DeadLetterConfig) -> Result<Self> {
+        let parked: Vec<DeadLetter> = workspace
+            .read_json(ArtifactKind::DeadLetters.file_name())?
+            .unwrap_or_default();
+        let parked = parked
+            .into_iter()
+            .map(|letter| (letter.digest.clone(), letter))
+            .collect();
+        Ok(Self::with_state(config, Some(workspace), parked))
+    }
+
+    fn with_state(
+        config: DeadLetterConfig,
+        workspace: Option<AgentWorkspace>,
+        parked: BTreeMap<String, DeadLetter>,
+    ) -> Self {
+        let (sender, _) = broadcast::channel(NOTIFICATION_BUFFER);
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                workspace,
+                state: StdMutex::new(QueueState {
+                    pending: HashMap::new(),
+                    parked,
+                }),
+                sender,
+            }),
+        }
+    }
+
+    /// Queue settings
+    pub fn config(&self) -> &DeadLetterConfig {
+        &self.inner.config
+    }
+
+    /// Count a tick on which `action` failed validation with `issue`
+    ///
+    /// Returns the dead letter when this failure parks the action.
+    pub fn record_failure(
+        &self,
+        action: &Value,
+        issue: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DeadLetter>> {
+        let digest = action_digest(action);
+        let mut state = lock(&self.inner.state);
+        self.expire(&mut state, now)?;
+        if state.parked.contains_key(&digest) {
+            return Ok(None);
+        }
+
+        let pending = state.pending.entry(digest.clone()).or_insert(Pending {
+            failures: 0,
+            first_failed_at: now,
+            issues: Vec::new(),
+        });
+        pending.failures += 1;
+        if !pending.issues.iter().any(|known| known == issue) {
+            if pending.issues.len() == MAX_ISSUES {
+                pending.issues.remove(0);
+            }
+            pending.issues.push(issue.to_string());
+        }
+        if pending.failures < self.inner.config.threshold.max(1) {
+            return Ok(None);
+        }
+
+        let Some(pending) = state.pending.remove(&digest) else {
+            return Ok(None);
+        };
+        let letter = DeadLetter {
+            digest: digest.clone(),
+            action: action.clone(),
+            issues: pending.issues,
+            failures: pending.failures,
+            first_failed_at: pending.first_failed_at,
+            parked_at: now,
+        };
+        state.parked.insert(digest, letter.clone());
+        self.persist(&state)?;
+
+        warn!(
+            digest = %letter.digest,
+            failures = letter.failures,
+            issues = ?letter.issues,
+            "Action dead-lettered after repeated validation failures"
+        );
+        let _ = self.inner.sender.send(letter.clone());
+        Ok(Some(letter))
+    }
+
+    /// Whether the action with `digest` is parked at `now`
+    pub fn is_parked(&self, digest: &str, now: DateTime<Utc>) -> bool {
+        let state = lock(&self.inner.state);
+        state
+            .parked
+            .get(digest)
+            .is_some_and(|letter| letter.expires_at(self.inner.config.cool_off) > now)
+    }
+
+    /// Parked actions at `now`, releasing those past their cool-off
+    pub fn list(&self, now: DateTime<Utc>) -> Vec<DeadLetter> {
+        let mut state = lock(&self.inner.state);
+        if let Err(e) = self.expire(&mut state, now) {
+            warn!("Failed to persist released dead letters: {}", e);
+        }
+        state.parked.values().cloned().collect()
+    }
+
+    /// Release the parked action with `digest`, or every action for `None`
+    ///
+    /// Returns the number of released actions. Their failure counts start
+    /// over.
+    pub fn clear(&self, digest: Option<&str>) -> Result<usize> {
+        let mut state = lock(&self.inner.state);
+        let released = match digest {
+            Some(digest) => {
+                state.pending.remove(digest);
+                usize::from(state.parked.remove(digest).is_some())
+            }
+            None => {
+                state.pending.clear();
+                std::mem::take(&mut state.parked).len()
+            }
+        };
+        if released > 0 {
+            self.persist(&state)?;
+        }
+        Ok(released)
+    }
+
+    /// Prompt section listing the parked actions, if there are any
+    pub fn prompt_summary(&self, now: DateTime<Utc>) -> Option<String> {
+        let letters = self.list(now);
+        if letters.is_empty() {
+            return None;
+        }
+
+        let mut summary = String::from(
+            "The following actions were rejected repeatedly and are blocked. Do not propose \
+             them again:",
+        );
+        for letter in letters {
+            summary.push_str(&format!(
+                "\n- {} (rejected: {})",
+                letter.action,
+                letter.issues.join("; ")
+            ));
+        }
+        Some(summary)
+    }
+
+    /// Release parked actions past their cool-off, returning how many
+    pub fn release_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        let mut state = lock(&self.inner.state);
+        self.expire(&mut state, now)
+    }
+
+    /// Receive actions parked from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<DeadLetter> {
+        self.inner.sender.subscribe()
+    }
+
+    fn expire(&self, state: &mut QueueState, now: DateTime<Utc>) -> Result<usize> {
+        let cool_off = self.inner.config.cool_off;
+        let before = state.parked.len();
+        state
+            .parked
+            .retain(|_, letter| letter.expires_at(cool_off) > now);
+        let released = before - state.parked.len();
+        if released > 0 {
+            self.persist(state)?;
+        }
+        Ok(released)
+    }
+
+    fn persist(&self, state: &QueueState) -> Result<()> {
+        let Some(workspace) = &self.inner.workspace else {
+            return Ok(());
+        };
+        let parked: Vec<&DeadLetter> = state.parked.values().collect();
+        workspace.write_json(ArtifactKind::DeadLetters.file_name(), &parked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AgentError;
+    use crate::workspace::AgentWorkspaces;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_digest_ignores_key_order() -> Result<()> {
+        let a: Value = serde_json::from_str(r#"{"type": "transfer_sol", "params": {"to": "x"}}"#)
+            .map_err(agent_wallet_core::Error::from)?;
+        let b: Value = serde_json::from_str(r#"{"params":{"to":"x"},"type":"transfer_sol"}"#)
+            .map_err(agent_wallet_core::Error::from)?;
+        assert_eq!(action_digest(&a), action_digest(&b));
+        assert_ne!(
+            action_digest(&a),
+            action_digest(&json!({ "type": "no_op" }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parks_after_threshold_and_releases_after_cool_off() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspace = AgentWorkspaces::open(dir.path())?.register("alpha")?;
+        let config = DeadLetterConfig {
+            threshold: 2,
+            cool_off: Duration::from_secs(60),
+        };
+        let queue = DeadLetterQueue::open(workspace.clone(), config)?;
+        let action = json!({ "type": "transfer_sol", "params": { "to": "nowhere" } });
+        let now = Utc::now();
+
+        assert!(queue.record_failure(&action, "bad address", now)?.is_none());
+        let letter = queue
+            .record_failure(&action, "bad address", now)?
+            .ok_or_else(|| AgentError::invalid_state("action was not parked"))?;
+        assert_eq!(letter.failures, 2);
+        assert_eq!(letter.issues, vec!["bad address".to_string()]);
+        assert!(queue.is_parked(&letter.digest, now));
+
+        // Parked entries survive a restart
+        let reopened = DeadLetterQueue::open(workspace, config)?;
+        assert_eq!(reopened.list(now).len(), 1);
+
+        let later = now + chrono::Duration::seconds(61);
+        assert!(!reopened.is_parked(&letter.digest, later));
+        assert!(reopened.list(later).is_empty());
+        Ok(())
+    }
+}
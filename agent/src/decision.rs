@@ -0,0 +1,107 @@
+//! Agent decisions
+//!
+//! Agents emit [`AgentAction`]s; the runner wraps them in an
+//! [`AgentDecision`] and reports what happened as a [`DecisionOutcome`].
+//! Every tick is kept in the runner's decision log as a [`DecisionRecord`].
+
+use agent_wallet_core::preview::TransferPreview;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Signature;
+
+pub use agent_wallet_core::types::{AgentAction, TriggerPayload};
+
+use crate::agent::AgentId;
+
+/// A decision made by an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentDecision {
+    /// Action to perform
+    pub action: AgentAction,
+    /// Optional explanation of the decision
+    pub reasoning: Option<String>,
+    /// Confidence in the decision (0-1 scale)
+    pub confidence: f64,
+    /// When the decision was made
+    pub decided_at: DateTime<Utc>,
+}
+
+impl AgentDecision {
+    /// Create a decision for an action
+    pub fn new(action: AgentAction) -> Self {
+        Self {
+            action,
+            reasoning: None,
+            confidence: 1.0,
+            decided_at: Utc::now(),
+        }
+    }
+
+    /// Attach reasoning to the decision
+    pub fn with_reasoning(mut self, reasoning: impl Into<String>) -> Self {
+        self.reasoning = Some(reasoning.into());
+        self
+    }
+
+    /// Set the decision confidence
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// Outcome of a decision tick
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecisionOutcome {
+    /// The agent decided to do nothing
+    NoAction,
+    /// The action was executed
+    Executed {
+        /// Transaction signature
+        signature: Signature,
+    },
+    /// The action was executed through the wallet but failed
+    Failed {
+        /// Error reported by the wallet
+        reason: String,
+    },
+    /// The agent did not decide in time and its decision was cancelled
+    TimedOut {
+        /// Decision timeout in milliseconds
+        timeout_ms: u64,
+    },
+    /// Dry run: the action was validated, signed and simulated but not sent
+    Simulated {
+        /// Signature of the transaction that would have been sent
+        would_have_sent: Signature,
+        /// Balance changes the simulation predicted
+        preview: TransferPreview,
+    },
+    /// The action waits for a human to approve it and runs on a later tick
+    AwaitingApproval {
+        /// Token of the approval request
+        token: String,
+    },
+}
+
+/// Decision log entry for one tick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    /// Agent that decided
+    pub agent_id: AgentId,
+    /// When the tick ran
+    pub timestamp: DateTime<Utc>,
+    /// Action the agent chose, if any
+    pub action: Option<AgentAction>,
+    /// What happened
+    pub outcome: DecisionOutcome,
+    /// External event that triggered the tick
+    pub trigger: Option<TriggerPayload>,
+}
+
+impl DecisionRecord {
+    /// Whether the tick was triggered by an external event
+    pub fn is_triggered(&self) -> bool {
+        self.trigger.is_some()
+    }
+}
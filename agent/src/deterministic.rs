@@ -0,0 +1,1885 @@
+//! Deterministic, rule-based agents
+//!
+//! A [`DeterministicAgent`] follows a fixed [`DeterministicStrategy`]. Since
+//! the shape of every action is known in advance, these agents publish
+//! templates so the runner can pre-build their transactions. An agent can
+//! also declare a [`TriggerHandler`] to act on external events as they
+//! arrive instead of waiting for its interval.
+//!
+//! [`DeterministicStrategy::IdleSweep`] keeps state between decisions: how
+//! much it has put to work and whether an unwind is still cooling down, so a
+//! pending deactivation is never requested twice.
+//!
+//! [`DeterministicStrategy::DollarCostAverage`] buys a fixed amount on a
+//! schedule of seconds or a cron expression, whatever the price. Its
+//! [`DcaState`] counts what it has spent so it stops for good once the cap
+//! is reached; [`DeterministicAgent::with_dca_state`] continues from the
+//! progress of an earlier run.
+//!
+//! [`DeterministicStrategy::Rebalance`] keeps a portfolio near target
+//! weights. It emits one swap per tick, from the most overweight asset
+//! straight into the most underweight one: a swap pairing them needs no
+//! funds freed by an earlier sell, and the next tick weighs the portfolio
+//! again from settled balances instead of acting on quotes that may have
+//! moved while a batch was in flight.
+//!
+//! [`DeterministicStrategy::StopLoss`] guards a position and can run next to
+//! other agents in a [`CompositeAgent`](crate::composite::CompositeAgent).
+//! Its [`StopLossState`] remembers the entry and high-water prices and
+//! whether an exit is under way, so a partly filled exit is retried and a
+//! closed position is left alone until it is re-entered.
+//!
+//! The agent saves this state, along with when it last fired a periodic
+//! transfer, through [`Agent::save_state`] under its name, so a restarted
+//! runner with an [`AgentStateStore`](crate::state::AgentStateStore)
+//! neither repeats a transfer before its interval nor forgets what a
+//! strategy has spent.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, warn};
+
+use agent_wallet_core::sol::Lamports;
+use agent_wallet_core::template::ActionTemplate;
+use agent_wallet_core::token::NATIVE_MINT;
+
+use crate::agent::Agent;
+use crate::context::AgentContext;
+use crate::decision::AgentAction;
+use crate::error::{AgentError, Result};
+use crate::limits::AgentLimits;
+use crate::sync::lock;
+use crate::trigger::{TriggerHandler, TriggerSchema};
+
+/// Rule-based strategies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeterministicStrategy {
+    /// Transfer a fixed amount of SOL at a regular interval
+    PeriodicTransfer {
+        /// Seconds between transfers
+        interval_seconds: u64,
+        /// Destination address
+        recipient: Pubkey,
+        /// Amount in SOL
+        amount_sol: f64,
+    },
+    /// Transfer a fixed amount of an SPL token at a regular interval
+    PeriodicTokenTransfer {
+        /// Seconds between transfers
+        interval_seconds: u64,
+        /// Token mint address
+        mint: Pubkey,
+        /// Destination address
+        recipient: Pubkey,
+        /// Amount in token base units
+        amount: u64,
+    },
+    /// Put SOL above a liquidity floor to work and pull it back when needed
+    ///
+    /// Sweeps everything above `floor_sol` once the excess reaches
+    /// `min_sweep_sol`. When the liquid balance drops below
+    /// `unwind_when_below_sol` it unwinds enough to restore the floor, and
+    /// waits for those funds to come back before unwinding again.
+    IdleSweep {
+        /// Liquid SOL to keep in the wallet
+        floor_sol: f64,
+        /// Where idle SOL is put to work
+        target: SweepTarget,
+        /// Smallest excess worth sweeping, in SOL
+        min_sweep_sol: f64,
+        /// Liquid balance below which swept SOL is pulled back
+        unwind_when_below_sol: f64,
+    },
+    /// Buy a fixed amount of a token on a schedule, regardless of price
+    ///
+    /// Each swap sells `amount_per_interval` of `input_mint`, or what is
+    /// left of `max_total_spent` if that is less, and expects at least the
+    /// price feed's output minus `slippage_bps`. Ticks without a price feed
+    /// for either mint, or where paying would dip into the SOL reserve, are
+    /// skipped.
+    DollarCostAverage {
+        /// Mint sold; the native mint spends SOL
+        input_mint: Pubkey,
+        /// Mint bought
+        output_mint: Pubkey,
+        /// Input sold per swap, in base units
+        amount_per_interval: u64,
+        /// When to buy
+        interval: DcaInterval,
+        /// Input sold over the strategy's lifetime, in base units
+        max_total_spent: u64,
+        /// Accepted shortfall from the price feed quote, in basis points
+        slippage_bps: u16,
+        /// No buys before this time
+        start_at: Option<DateTime<Utc>>,
+        /// No buys from this time on
+        end_at: Option<DateTime<Utc>>,
+    },
+    /// Swap towards target portfolio weights
+    ///
+    /// Weights are fractions of the portfolio's value by mint; native SOL
+    /// takes part under the native mint, valued from the wallet balance.
+    /// Assets without a price feed are left out and the remaining weights
+    /// scaled up. Once an asset is more than `tolerance_bps` off its
+    /// weight, each tick swaps the most overweight asset into the most
+    /// underweight one, unless the trade is worth less than
+    /// `min_trade_sol_value`.
+    Rebalance {
+        /// Target share of the portfolio value per mint, summing to 1
+        targets: HashMap<Pubkey, f64>,
+        /// Deviation from a target that triggers a trade, in basis points
+        tolerance_bps: u16,
+        /// Smallest trade worth its fees, in SOL
+        min_trade_sol_value: f64,
+    },
+    /// Sell a position once its price falls or rises past a threshold
+    ///
+    /// Sells the whole balance of `mint` into `exit_mint` once the price is
+    /// `stop_loss_pct` below the entry price, or below the highest price
+    /// seen when `trailing`, or `take_profit_pct` above the entry price.
+    /// Without an `entry_price` the price of the first tick holding the
+    /// token is used. After the exit the strategy stays dormant until the
+    /// wallet holds the token again, then guards the new position from the
+    /// price it was re-entered at.
+    StopLoss {
+        /// Token guarded
+        mint: Pubkey,
+        /// Mint the position is sold into, e.g. the native mint or USDC
+        exit_mint: Pubkey,
+        /// USD price the position was bought at
+        entry_price: Option<f64>,
+        /// Loss that triggers the exit, in percent
+        stop_loss_pct: f64,
+        /// Gain that triggers the exit, in percent
+        take_profit_pct: f64,
+        /// Measure the loss from the highest price seen instead of the entry
+        trailing: bool,
+    },
+}
+
+/// Schedule of a dollar-cost averaging strategy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DcaInterval {
+    /// Every this many seconds; the first tick buys right away
+    Seconds(u64),
+    /// At the times of a cron expression with a leading seconds field,
+    /// e.g. `0 0 9 * * Mon` for Mondays at 09:00 UTC
+    Cron(String),
+}
+
+impl DcaInterval {
+    /// Reject zero intervals and cron expressions that do not parse
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            DcaInterval::Seconds(0) => Err(AgentError::config("DCA interval must be positive")),
+            DcaInterval::Seconds(_) => Ok(()),
+            DcaInterval::Cron(expression) => cron_schedule(expression).map(|_| ()),
+        }
+    }
+
+    /// Whether a buy is due at `now`
+    ///
+    /// Cron schedules count from the last buy, or from `from` before the
+    /// first one.
+    fn is_due(
+        &self,
+        last: Option<DateTime<Utc>>,
+        from: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool> {
+        match self {
+            DcaInterval::Seconds(seconds) => Ok(match last {
+                Some(last) => now.signed_duration_since(last).num_seconds() >= *seconds as i64,
+                None => true,
+            }),
+            DcaInterval::Cron(expression) => Ok(cron_schedule(expression)?
+                .after(&last.unwrap_or(from))
+                .next()
+                .is_some_and(|next| next <= now)),
+        }
+    }
+}
+
+fn cron_schedule(expression: &str) -> Result<cron::Schedule> {
+    cron::Schedule::from_str(expression)
+        .map_err(|e| AgentError::config(format!("Invalid cron expression '{}': {}", expression, e)))
+}
+
+pub(crate) fn check_interval(field: &str, seconds: u64, limits: &AgentLimits) -> Result<()> {
+    if seconds < limits.min_interval_seconds.max(1) {
+        return Err(AgentError::config(format!(
+            "{} must be at least {} seconds, got {}",
+            field,
+            limits.min_interval_seconds.max(1),
+            seconds
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn positive_sol(field: &str, sol: f64) -> Result<()> {
+    if !sol.is_finite() || sol <= 0.0 {
+        return Err(AgentError::config(format!(
+            "{} must be a positive amount, got {}",
+            field, sol
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn non_negative_sol(field: &str, sol: f64) -> Result<()> {
+    if !sol.is_finite() || sol < 0.0 {
+        return Err(AgentError::config(format!(
+            "{} must not be negative, got {}",
+            field, sol
+        )));
+    }
+    Ok(())
+}
+
+fn within_sol_limit(field: &str, sol: f64, limits: &AgentLimits) -> Result<()> {
+    if sol > limits.max_sol_per_transaction {
+        return Err(AgentError::config(format!(
+            "{} of {} SOL exceeds max_sol_per_transaction of {} SOL",
+            field, sol, limits.max_sol_per_transaction
+        )));
+    }
+    Ok(())
+}
+
+fn check_bps(field: &str, bps: u16) -> Result<()> {
+    if bps > 10_000 {
+        return Err(AgentError::config(format!(
+            "{} must not exceed 10000, got {}",
+            field, bps
+        )));
+    }
+    Ok(())
+}
+
+/// Slippage accepted on rebalancing and exit swaps, in basis points
+const SWAP_SLIPPAGE_BPS: u16 = 100;
+
+/// Reject empty or negative portfolio weights and weights not summing to 1
+pub(crate) fn check_weights(targets: &HashMap<Pubkey, f64>) -> Result<()> {
+    if targets.is_empty() {
+        return Err(AgentError::config("Rebalance targets must not be empty"));
+    }
+    if targets
+        .values()
+        .any(|weight| !weight.is_finite() || *weight < 0.0)
+    {
+        return Err(AgentError::config(
+            "Rebalance target weights must not be negative",
+        ));
+    }
+    let total: f64 = targets.values().sum();
+    if (total - 1.0).abs() > 1e-6 {
+        return Err(AgentError::config(format!(
+            "Rebalance target weights must sum to 1, got {}",
+            total
+        )));
+    }
+    Ok(())
+}
+
+/// Asset of a portfolio being rebalanced
+struct Holding {
+    mint: Pubkey,
+    value_sol: f64,
+    target: f64,
+}
+
+/// What a dollar-cost averaging strategy has done so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DcaState {
+    /// Input sold so far, in base units
+    pub total_spent: u64,
+    /// Swaps emitted
+    pub swaps: u32,
+    /// When the last swap was emitted
+    pub last_swap_at: Option<DateTime<Utc>>,
+    /// Time the schedule counts from before the first swap
+    pub schedule_from: Option<DateTime<Utc>>,
+    /// Whether the cap was reached; the strategy never buys again
+    pub completed: bool,
+}
+
+/// Where a stop-loss strategy stands with its position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StopLossState {
+    /// USD price the position is measured from
+    pub entry_price: Option<f64>,
+    /// Highest USD price seen since entry
+    pub high_water: Option<f64>,
+    /// An exit was emitted and the position is not closed yet
+    pub exiting: bool,
+    /// The position was closed; nothing happens until it is re-entered
+    pub dormant: bool,
+}
+
+/// Destination of swept SOL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SweepTarget {
+    /// Deposit into a stake pool; unwinding withdraws from it
+    StakePool(Pubkey),
+    /// Delegate to a validator vote account; unwinding deactivates the stake
+    Validator(Pubkey),
+}
+
+impl SweepTarget {
+    /// Pool or vote account the stake actions address
+    pub fn address(&self) -> Pubkey {
+        match self {
+            SweepTarget::StakePool(address) | SweepTarget::Validator(address) => *address,
+        }
+    }
+}
+
+/// What an idle sweep has done so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SweepState {
+    /// Lamports swept and not yet unwound
+    pub swept_lamports: u64,
+    /// Lamports of an unwind still cooling down or in flight
+    pub pending_unwind_lamports: Option<u64>,
+}
+
+impl DeterministicStrategy {
+    /// Seconds between actions
+    fn interval_seconds(&self) -> u64 {
+        match self {
+            DeterministicStrategy::PeriodicTransfer {
+                interval_seconds, ..
+            }
+            | DeterministicStrategy::PeriodicTokenTransfer {
+                interval_seconds, ..
+            } => *interval_seconds,
+            // Balance driven or on its own schedule, so it looks at every tick
+            DeterministicStrategy::IdleSweep { .. }
+            | DeterministicStrategy::DollarCostAverage { .. }
+            | DeterministicStrategy::Rebalance { .. }
+            | DeterministicStrategy::StopLoss { .. } => 0,
+        }
+    }
+
+    /// Check every parameter against its allowed range and `limits`
+    ///
+    /// Amounts must be positive and, for SOL, within
+    /// `max_sol_per_transaction`; intervals in seconds must be at least
+    /// `min_interval_seconds`; percentages lie between 0 and 100, basis
+    /// points up to 10000. A take-profit threshold may exceed 100%, as a
+    /// position can more than double. Errors name the field that failed.
+    pub fn validate(&self, limits: &AgentLimits) -> Result<()> {
+        match self {
+            DeterministicStrategy::PeriodicTransfer {
+                interval_seconds,
+                amount_sol,
+                ..
+            } => {
+                check_interval("interval_seconds", *interval_seconds, limits)?;
+                positive_sol("amount_sol", *amount_sol)?;
+                within_sol_limit("amount_sol", *amount_sol, limits)
+            }
+            DeterministicStrategy::PeriodicTokenTransfer {
+                interval_seconds,
+                amount,
+                ..
+            } => {
+                check_interval("interval_seconds", *interval_seconds, limits)?;
+                if *amount == 0 {
+                    return Err(AgentError::config("amount must be positive"));
+                }
+                if *amount > limits.max_tokens_per_transaction {
+                    return Err(AgentError::config(format!(
+                        "amount of {} exceeds max_tokens_per_transaction of {}",
+                        amount, limits.max_tokens_per_transaction
+                    )));
+                }
+                Ok(())
+            }
+            DeterministicStrategy::IdleSweep {
+                floor_sol,
+                min_sweep_sol,
+                unwind_when_below_sol,
+                ..
+            } => {
+                non_negative_sol("floor_sol", *floor_sol)?;
+                positive_sol("min_sweep_sol", *min_sweep_sol)?;
+                non_negative_sol("unwind_when_below_sol", *unwind_when_below_sol)?;
+                if unwind_when_below_sol > floor_sol {
+                    return Err(AgentError::config(
+                        "unwind_when_below_sol must not exceed floor_sol",
+                    ));
+                }
+                Ok(())
+            }
+            DeterministicStrategy::DollarCostAverage {
+                input_mint,
+                output_mint,
+                amount_per_interval,
+                interval,
+                max_total_spent,
+                slippage_bps,
+                start_at,
+                end_at,
+            } => {
+                interval.validate()?;
+                if let DcaInterval::Seconds(seconds) = interval {
+                    check_interval("interval", *seconds, limits)?;
+                }
+                if *amount_per_interval == 0 || *max_total_spent == 0 {
+                    return Err(AgentError::config(
+                        "amount_per_interval and max_total_spent must be positive",
+                    ));
+                }
+                if *input_mint == NATIVE_MINT {
+                    let sol = Lamports::new(*amount_per_interval).to_sol_f64();
+                    within_sol_limit("amount_per_interval", sol, limits)?;
+                }
+                if input_mint == output_mint {
+                    return Err(AgentError::config("input_mint and output_mint must differ"));
+                }
+                check_bps("slippage_bps", *slippage_bps)?;
+                if let (Some(start), Some(end)) = (start_at, end_at) {
+                    if end <= start {
+                        return Err(AgentError::config("end_at must be after start_at"));
+                    }
+                }
+                Ok(())
+            }
+            DeterministicStrategy::Rebalance {
+                targets,
+                tolerance_bps,
+                min_trade_sol_value,
+            } => {
+                check_weights(targets)?;
+                check_bps("tolerance_bps", *tolerance_bps)?;
+                non_negative_sol("min_trade_sol_value", *min_trade_sol_value)
+            }
+            DeterministicStrategy::StopLoss {
+                mint,
+                exit_mint,
+                entry_price,
+                stop_loss_pct,
+                take_profit_pct,
+                ..
+            } => {
+                if mint == exit_mint {
+                    return Err(AgentError::config("mint and exit_mint must differ"));
+                }
+                if let Some(price) = entry_price {
+                    positive_sol("entry_price", *price)?;
+                }
+                if !stop_loss_pct.is_finite() || *stop_loss_pct <= 0.0 || *stop_loss_pct > 100.0 {
+                    return Err(AgentError::config(format!(
+                        "stop_loss_pct must be between 0 and 100, got {}",
+                        stop_loss_pct
+                    )));
+                }
+                positive_sol("take_profit_pct", *take_profit_pct)
+            }
+        }
+    }
+
+    /// Refuse a strategy that pays the wallet it runs on, burning only fees
+    pub fn check_recipient(&self, wallet: &Pubkey) -> Result<()> {
+        match self {
+            DeterministicStrategy::PeriodicTransfer { recipient, .. }
+            | DeterministicStrategy::PeriodicTokenTransfer { recipient, .. }
+                if recipient == wallet =>
+            {
+                Err(AgentError::config(format!(
+                    "recipient {} is the agent's own wallet",
+                    recipient
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Template of the action this strategy emits, if it has a fixed shape
+    fn template(&self) -> Option<ActionTemplate> {
+        match self {
+            DeterministicStrategy::PeriodicTransfer { recipient, .. } => {
+                Some(ActionTemplate::TransferSol {
+                    to: *recipient,
+                    memo: None,
+                })
+            }
+            DeterministicStrategy::PeriodicTokenTransfer {
+                mint, recipient, ..
+            } => Some(ActionTemplate::TransferToken {
+                mint: *mint,
+                to: *recipient,
+                memo: None,
+            }),
+            DeterministicStrategy::IdleSweep { .. }
+            | DeterministicStrategy::DollarCostAverage { .. }
+            | DeterministicStrategy::Rebalance { .. }
+            | DeterministicStrategy::StopLoss { .. } => None,
+        }
+    }
+}
+
+/// State a deterministic agent persists between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedState {
+    #[serde(default)]
+    last_fired: Option<DateTime<Utc>>,
+    #[serde(default)]
+    sweep: SweepState,
+    #[serde(default)]
+    dca: DcaState,
+    #[serde(default)]
+    stop_loss: StopLossState,
+}
+
+/// Agent driven by a deterministic strategy
+#[derive(Debug, Clone)]
+pub struct DeterministicAgent {
+    name: String,
+    strategy: DeterministicStrategy,
+    trigger_handler: Option<TriggerHandler>,
+    last_fired: Arc<Mutex<Option<DateTime<Utc>>>>,
+    sweep: Arc<Mutex<SweepState>>,
+    dca: Arc<Mutex<DcaState>>,
+    stop_loss: Arc<Mutex<StopLossState>>,
+}
+
+impl DeterministicAgent {
+    /// Create an agent for a strategy, refusing parameters outside `limits`
+    ///
+    /// See [`DeterministicStrategy::validate`].
+    pub fn try_new(strategy: DeterministicStrategy, limits: &AgentLimits) -> Result<Self> {
+        strategy.validate(limits)?;
+        Ok(Self::unchecked(strategy))
+    }
+
+    /// Create an agent for a strategy without checking its parameters
+    #[deprecated(note = "use `DeterministicAgent::try_new`, which validates the strategy")]
+    pub fn new(strategy: DeterministicStrategy) -> Self {
+        Self::unchecked(strategy)
+    }
+
+    fn unchecked(strategy: DeterministicStrategy) -> Self {
+        Self {
+            name: "deterministic".to_string(),
+            strategy,
+            trigger_handler: None,
+            last_fired: Arc::new(Mutex::new(None)),
+            sweep: Arc::new(Mutex::new(SweepState::default())),
+            dca: Arc::new(Mutex::new(DcaState::default())),
+            stop_loss: Arc::new(Mutex::new(StopLossState::default())),
+        }
+    }
+
+    /// Set the agent name
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Act on external triggers with the given handler
+    pub fn with_trigger_handler(mut self, handler: TriggerHandler) -> Self {
+        self.trigger_handler = Some(handler);
+        self
+    }
+
+    /// Strategy followed by the agent
+    pub fn strategy(&self) -> &DeterministicStrategy {
+        &self.strategy
+    }
+
+    /// Progress of an idle sweep strategy
+    pub fn sweep_state(&self) -> SweepState {
+        *lock(&self.sweep)
+    }
+
+    /// Continue a dollar-cost averaging strategy from an earlier run
+    pub fn with_dca_state(self, state: DcaState) -> Self {
+        *lock(&self.dca) = state;
+        self
+    }
+
+    /// Progress of a dollar-cost averaging strategy
+    pub fn dca_state(&self) -> DcaState {
+        *lock(&self.dca)
+    }
+
+    /// Continue a stop-loss strategy from an earlier run
+    pub fn with_stop_loss_state(self, state: StopLossState) -> Self {
+        *lock(&self.stop_loss) = state;
+        self
+    }
+
+    /// Position tracking of a stop-loss strategy
+    pub fn stop_loss_state(&self) -> StopLossState {
+        *lock(&self.stop_loss)
+    }
+
+    /// When the agent last fired a periodic transfer
+    pub fn last_fired(&self) -> Option<DateTime<Utc>> {
+        *lock(&self.last_fired)
+    }
+
+    /// Whether the strategy interval has elapsed since the last action
+    ///
+    /// The last action is the later of the wallet's last action and the
+    /// agent's own last transfer, which survives restarts.
+    fn is_due(&self, context: &AgentContext) -> bool {
+        match context.last_action_time.max(self.last_fired()) {
+            Some(last) => {
+                let elapsed = context.timestamp.signed_duration_since(last).num_seconds();
+                elapsed >= self.strategy.interval_seconds() as i64
+            }
+            None => true,
+        }
+    }
+
+    /// Next idle sweep action: sweep the excess, unwind, or nothing
+    fn decide_sweep(
+        &self,
+        context: &AgentContext,
+        floor_sol: f64,
+        target: SweepTarget,
+        min_sweep_sol: f64,
+        unwind_when_below_sol: f64,
+    ) -> Result<Option<AgentAction>> {
+        if floor_sol < 0.0 || min_sweep_sol <= 0.0 {
+            return Err(AgentError::config(
+                "Sweep floor must not be negative and minimum sweep must be positive",
+            ));
+        }
+        if unwind_when_below_sol > floor_sol {
+            return Err(AgentError::config(
+                "Sweep unwind threshold must not exceed the floor",
+            ));
+        }
+
+        let balance = Lamports::from_sol_f64_rounded(context.wallet_balance)?.as_u64();
+        let floor = Lamports::from_sol_f64_rounded(floor_sol)?.as_u64();
+        let min_sweep = Lamports::from_sol_f64_rounded(min_sweep_sol)?.as_u64();
+        let unwind_below = Lamports::from_sol_f64_rounded(unwind_when_below_sol)?.as_u64();
+
+        let mut state = lock(&self.sweep);
+        if state.pending_unwind_lamports.is_some() {
+            // The unwound SOL has not reached the wallet yet; cooldown
+            // semantics forbid deactivating the same stake again
+            if balance < unwind_below {
+                return Ok(None);
+            }
+            state.pending_unwind_lamports = None;
+        }
+
+        if balance < unwind_below {
+            let amount = floor.saturating_sub(balance).min(state.swept_lamports);
+            if amount == 0 {
+                return Ok(None);
+            }
+            state.swept_lamports -= amount;
+            state.pending_unwind_lamports = Some(amount);
+            return Ok(Some(AgentAction::UnstakeTokens {
+                staking_pool: target.address(),
+                amount: Lamports::new(amount),
+            }));
+        }
+
+        let excess = balance.saturating_sub(floor);
+        if excess < min_sweep {
+            return Ok(None);
+        }
+        state.swept_lamports = state.swept_lamports.saturating_add(excess);
+        Ok(Some(AgentAction::StakeTokens {
+            staking_pool: target.address(),
+            amount: Lamports::new(excess),
+        }))
+    }
+
+    /// Swap moving the portfolio towards its targets, if one is needed
+    fn decide_rebalance(
+        &self,
+        context: &AgentContext,
+        targets: &HashMap<Pubkey, f64>,
+        tolerance_bps: u16,
+        min_trade_sol_value: f64,
+    ) -> Result<Option<AgentAction>> {
+        check_weights(targets)?;
+        if !min_trade_sol_value.is_finite() || min_trade_sol_value < 0.0 {
+            return Err(AgentError::config(
+                "Minimum rebalancing trade must not be negative",
+            ));
+        }
+        let Some((sol_usd, _)) = price_and_decimals(context, &NATIVE_MINT) else {
+            warn!("Skipping rebalance: no SOL price feed");
+            return Ok(None);
+        };
+
+        let mut holdings = Vec::with_capacity(targets.len());
+        for (mint, target) in targets {
+            let Some((price, decimals)) = price_and_decimals(context, mint) else {
+                warn!("Leaving {} out of the rebalance: no price feed", mint);
+                continue;
+            };
+            let units = if *mint == NATIVE_MINT {
+                Lamports::from_sol_f64_rounded(context.wallet_balance)?.as_u64()
+            } else {
+                context.token_balances.get(mint).copied().unwrap_or(0)
+            };
+            holdings.push(Holding {
+                mint: *mint,
+                value_sol: units as f64 / 10f64.powi(i32::from(decimals)) * price / sol_usd,
+                target: *target,
+            });
+        }
+        let total_value: f64 = holdings.iter().map(|holding| holding.value_sol).sum();
+        let total_target: f64 = holdings.iter().map(|holding| holding.target).sum();
+        if total_value <= 0.0 || total_target <= 0.0 {
+            return Ok(None);
+        }
+
+        let deviation =
+            |holding: &Holding| holding.value_sol / total_value - holding.target / total_target;
+        let by_deviation = |a: &&Holding, b: &&Holding| deviation(a).total_cmp(&deviation(b));
+        let (Some(over), Some(under)) = (
+            holdings.iter().max_by(by_deviation),
+            holdings.iter().min_by(by_deviation),
+        ) else {
+            return Ok(None);
+        };
+        let tolerance = f64::from(tolerance_bps) / 10_000.0;
+        if deviation(over) <= tolerance && -deviation(under) <= tolerance {
+            return Ok(None);
+        }
+
+        let mut trade_sol = deviation(over).min(-deviation(under)) * total_value;
+        if over.mint == NATIVE_MINT {
+            trade_sol = trade_sol.min(context.spendable_balance());
+        }
+        if trade_sol <= 0.0 || trade_sol < min_trade_sol_value {
+            debug!(
+                "Skipping rebalance of {} into {}: {} SOL is below the minimum trade",
+                over.mint, under.mint, trade_sol
+            );
+            return Ok(None);
+        }
+
+        let Some((price, decimals)) = price_and_decimals(context, &over.mint) else {
+            return Ok(None);
+        };
+        let amount = (trade_sol * sol_usd / price * 10f64.powi(i32::from(decimals))).floor() as u64;
+        let Some(min_output_amount) =
+            min_swap_output(context, &over.mint, &under.mint, amount, SWAP_SLIPPAGE_BPS)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(AgentAction::SwapTokens {
+            input_mint: over.mint,
+            output_mint: under.mint,
+            amount,
+            min_output_amount,
+        }))
+    }
+
+    /// Exit swap of a guarded position, if a threshold was crossed
+    fn decide_stop_loss(&self, context: &AgentContext) -> Result<Option<AgentAction>> {
+        let DeterministicStrategy::StopLoss {
+            mint,
+            exit_mint,
+            entry_price,
+            stop_loss_pct,
+            take_profit_pct,
+            trailing,
+        } = &self.strategy
+        else {
+            return Ok(None);
+        };
+        let valid = (0.0..100.0).contains(stop_loss_pct)
+            && *stop_loss_pct > 0.0
+            && take_profit_pct.is_finite()
+            && *take_profit_pct > 0.0;
+        if !valid {
+            return Err(AgentError::config(
+                "Stop loss must be between 0 and 100% and take profit positive",
+            ));
+        }
+        let Some((price, _)) = price_and_decimals(context, mint) else {
+            warn!("Cannot guard {}: no price feed", mint);
+            return Ok(None);
+        };
+        let balance = context.token_balances.get(mint).copied().unwrap_or(0);
+
+        let mut state = lock(&self.stop_loss);
+        if balance == 0 {
+            if state.exiting {
+                info!("Position in {} closed", mint);
+                *state = StopLossState {
+                    dormant: true,
+                    ..StopLossState::default()
+                };
+            }
+            return Ok(None);
+        }
+        if state.dormant {
+            info!("Position in {} re-entered at {}", mint, price);
+            *state = StopLossState {
+                entry_price: Some(price),
+                ..StopLossState::default()
+            };
+        }
+
+        let entry = *state
+            .entry_price
+            .get_or_insert(entry_price.unwrap_or(price));
+        let high_water = state.high_water.map_or(price, |high| high.max(price));
+        state.high_water = Some(high_water);
+
+        let reference = if *trailing { high_water } else { entry };
+        let stop = reference * (1.0 - stop_loss_pct / 100.0);
+        let target = entry * (1.0 + take_profit_pct / 100.0);
+        if !state.exiting && price > stop && price < target {
+            return Ok(None);
+        }
+
+        let Some(min_output_amount) =
+            min_swap_output(context, mint, exit_mint, balance, SWAP_SLIPPAGE_BPS)
+        else {
+            warn!("Cannot exit {}: no price feed for {}", mint, exit_mint);
+            return Ok(None);
+        };
+        if state.exiting {
+            info!("Retrying exit of {} with {} left", mint, balance);
+        } else {
+            info!(
+                "Exiting {} at {} (entry {}, stop {}, target {})",
+                mint, price, entry, stop, target
+            );
+        }
+        state.exiting = true;
+        Ok(Some(AgentAction::SwapTokens {
+            input_mint: *mint,
+            output_mint: *exit_mint,
+            amount: balance,
+            min_output_amount,
+        }))
+    }
+
+    /// Next dollar-cost averaging swap, if one is due and affordable
+    fn decide_dca(&self, context: &AgentContext) -> Result<Option<AgentAction>> {
+        let DeterministicStrategy::DollarCostAverage {
+            input_mint,
+            output_mint,
+            amount_per_interval,
+            interval,
+            max_total_spent,
+            slippage_bps,
+            start_at,
+            end_at,
+        } = &self.strategy
+        else {
+            return Ok(None);
+        };
+        if *amount_per_interval == 0 || *max_total_spent == 0 {
+            return Err(AgentError::config(
+                "DCA amount and spending cap must be positive",
+            ));
+        }
+        if *slippage_bps > 10_000 {
+            return Err(AgentError::config("DCA slippage must not exceed 10000 bps"));
+        }
+
+        let now = context.timestamp;
+        if start_at.is_some_and(|start| now < start) || end_at.is_some_and(|end| now >= end) {
+            return Ok(None);
+        }
+
+        let mut state = lock(&self.dca);
+        if state.completed {
+            return Ok(None);
+        }
+        let from = *state.schedule_from.get_or_insert(start_at.unwrap_or(now));
+        if !interval.is_due(state.last_swap_at, from, now)? {
+            return Ok(None);
+        }
+
+        let amount = (*amount_per_interval).min(max_total_spent.saturating_sub(state.total_spent));
+        if !can_fund_swap(context, input_mint, amount)? {
+            debug!("Skipping DCA swap of {}: balance too low", amount);
+            return Ok(None);
+        }
+        let Some(min_output_amount) =
+            min_swap_output(context, input_mint, output_mint, amount, *slippage_bps)
+        else {
+            warn!(
+                "Skipping DCA swap of {} into {}: no price feed to bound slippage",
+                input_mint, output_mint
+            );
+            return Ok(None);
+        };
+
+        state.total_spent = state.total_spent.saturating_add(amount);
+        state.swaps += 1;
+        state.last_swap_at = Some(now);
+        state.completed = state.total_spent >= *max_total_spent;
+        Ok(Some(AgentAction::SwapTokens {
+            input_mint: *input_mint,
+            output_mint: *output_mint,
+            amount,
+            min_output_amount,
+        }))
+    }
+}
+
+impl Agent for DeterministicAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn decide(&self, context: &AgentContext) -> Result<Option<AgentAction>> {
+        if let (Some(handler), Some(payload)) = (&self.trigger_handler, &context.trigger) {
+            let action = handler.action(payload)?;
+            return Ok(can_afford(context, &action).then_some(action));
+        }
+
+        if !self.is_due(context) {
+            return Ok(None);
+        }
+
+        let amount = match &self.strategy {
+            DeterministicStrategy::PeriodicTransfer { amount_sol, .. } => {
+                if *amount_sol <= 0.0 {
+                    return Err(AgentError::config("Transfer amount must be positive"));
+                }
+                Lamports::from_sol_f64_rounded(*amount_sol)?.as_u64()
+            }
+            DeterministicStrategy::PeriodicTokenTransfer { amount, .. } => *amount,
+            DeterministicStrategy::IdleSweep {
+                floor_sol,
+                target,
+                min_sweep_sol,
+                unwind_when_below_sol,
+            } => {
+                return self.decide_sweep(
+                    context,
+                    *floor_sol,
+                    *target,
+                    *min_sweep_sol,
+                    *unwind_when_below_sol,
+                );
+            }
+            DeterministicStrategy::DollarCostAverage { .. } => return self.decide_dca(context),
+            DeterministicStrategy::Rebalance {
+                targets,
+                tolerance_bps,
+                min_trade_sol_value,
+            } => {
+                return self.decide_rebalance(
+                    context,
+                    targets,
+                    *tolerance_bps,
+                    *min_trade_sol_value,
+                );
+            }
+            DeterministicStrategy::StopLoss { .. } => return self.decide_stop_loss(context),
+        };
+
+        let action = self
+            .strategy
+            .template()
+            .ok_or_else(|| AgentError::invalid_state("Strategy has no action template"))?
+            .instantiate(amount);
+        if !can_afford(context, &action) {
+            return Ok(None);
+        }
+        // Marked when decided: after a crash a transfer that may have been
+        // sent is not sent again before its interval
+        *lock(&self.last_fired) = Some(context.timestamp);
+        Ok(Some(action))
+    }
+
+    fn templates(&self) -> Vec<ActionTemplate> {
+        self.strategy.template().into_iter().collect()
+    }
+
+    fn trigger_schema(&self) -> Option<TriggerSchema> {
+        self.trigger_handler.as_ref().map(TriggerHandler::schema)
+    }
+
+    fn state_key(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn load_state(&self, state: Value) -> Result<()> {
+        let saved: SavedState = serde_json::from_value(state).map_err(|e| {
+            AgentError::invalid_state(format!("Saved state of {} is invalid: {}", self.name, e))
+        })?;
+        *lock(&self.last_fired) = saved.last_fired;
+        *lock(&self.sweep) = saved.sweep;
+        *lock(&self.dca) = saved.dca;
+        *lock(&self.stop_loss) = saved.stop_loss;
+        Ok(())
+    }
+
+    fn save_state(&self) -> Result<Option<Value>> {
+        let saved = SavedState {
+            last_fired: self.last_fired(),
+            sweep: self.sweep_state(),
+            dca: self.dca_state(),
+            stop_loss: self.stop_loss_state(),
+        };
+        serde_json::to_value(saved)
+            .map(Some)
+            .map_err(|e| AgentError::invalid_state(format!("Cannot save state: {}", e)))
+    }
+}
+
+/// Whether the wallet holds enough to perform a transfer
+///
+/// SOL transfers may only spend the balance above the wallet's reserve.
+fn can_afford(context: &AgentContext, action: &AgentAction) -> bool {
+    match action {
+        AgentAction::TransferSol { amount, .. } => {
+            Lamports::from_sol_f64_rounded(context.spendable_balance())
+                .is_ok_and(|balance| balance >= *amount)
+        }
+        AgentAction::TransferToken { mint, amount, .. } => {
+            context.token_balances.get(mint).copied().unwrap_or(0) >= *amount
+        }
+        _ => true,
+    }
+}
+
+/// Whether selling `amount` of `mint` leaves the SOL reserve intact
+fn can_fund_swap(context: &AgentContext, mint: &Pubkey, amount: u64) -> Result<bool> {
+    let balance = Lamports::from_sol_f64_rounded(context.wallet_balance)?.as_u64();
+    let reserve = context.spending_limits.min_sol_reserve_lamports;
+    if *mint == NATIVE_MINT {
+        return Ok(balance >= reserve.saturating_add(amount));
+    }
+    Ok(balance >= reserve && context.token_balances.get(mint).copied().unwrap_or(0) >= amount)
+}
+
+/// Least output of a swap the price feeds justify, less `slippage_bps`
+///
+/// `None` when a price feed or the decimals of either mint are unknown.
+fn min_swap_output(
+    context: &AgentContext,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+    slippage_bps: u16,
+) -> Option<u64> {
+    let (input_price, input_decimals) = price_and_decimals(context, input_mint)?;
+    let (output_price, output_decimals) = price_and_decimals(context, output_mint)?;
+    let input = amount as f64 / 10f64.powi(i32::from(input_decimals));
+    let output = input * input_price / output_price * 10f64.powi(i32::from(output_decimals));
+    let accepted = output * f64::from(10_000 - slippage_bps) / 10_000.0;
+    Some(accepted.floor() as u64)
+}
+
+/// USD price and decimals of a mint, from the context's feeds
+fn price_and_decimals(context: &AgentContext, mint: &Pubkey) -> Option<(f64, u8)> {
+    let (symbol, decimals) = if *mint == NATIVE_MINT {
+        ("SOL".to_string(), 9)
+    } else {
+        (mint.to_string(), *context.token_decimals.get(mint)?)
+    };
+    let price = context.price_feeds.get(&symbol).copied()?;
+    (price > 0.0 && price.is_finite()).then_some((price, decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trigger::{self, Param, TriggerConfig, TriggerPayload};
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_periodic_transfer_matches_its_template() -> Result<()> {
+        let recipient = Pubkey::new_unique();
+        let agent = DeterministicAgent::unchecked(DeterministicStrategy::PeriodicTransfer {
+            interval_seconds: 3600,
+            recipient,
+            amount_sol: 0.1,
+        });
+
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.wallet_balance = 5.0;
+
+        let action = agent
+            .decide(&context)
+            .await?
+            .ok_or_else(|| AgentError::decision("expected a transfer"))?;
+        assert!(matches!(
+            action,
+            AgentAction::TransferSol { to, amount: Lamports(100_000_000), .. } if to == recipient
+        ));
+        assert!(agent.templates().iter().any(|t| t.matches(&action)));
+
+        // Not due again until the interval elapses
+        context.last_action_time = Some(context.timestamp - Duration::minutes(30));
+        assert!(agent.decide(&context).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restarted_agent_does_not_fire_early() -> Result<()> {
+        use crate::state::{AgentStateStore, FileStateStore};
+        use crate::workspace::AgentWorkspaces;
+
+        let dir = tempfile::tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let strategy = DeterministicStrategy::PeriodicTransfer {
+            interval_seconds: 3600,
+            recipient: Pubkey::new_unique(),
+            amount_sol: 0.1,
+        };
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.wallet_balance = 5.0;
+        let fired_at = context.timestamp;
+
+        let store = FileStateStore::new(AgentWorkspaces::open(dir.path())?);
+        let agent = DeterministicAgent::unchecked(strategy.clone()).with_name("payroll");
+        assert!(agent.decide(&context).await?.is_some());
+        let key = agent
+            .state_key()
+            .ok_or_else(|| AgentError::invalid_state("agent has no state key"))?;
+        if let Some(state) = agent.save_state()? {
+            store.put(&key, state)?;
+        }
+
+        // A new process knows nothing of the last transfer but the store
+        let store = FileStateStore::new(AgentWorkspaces::open(dir.path())?);
+        let restarted = DeterministicAgent::unchecked(strategy).with_name("payroll");
+        if let Some(state) = store.get(&key)? {
+            restarted.load_state(state)?;
+        }
+        assert_eq!(restarted.last_fired(), Some(fired_at));
+        assert_eq!(context.last_action_time, None);
+
+        context.timestamp = fired_at + Duration::minutes(59);
+        assert!(restarted.decide(&context).await?.is_none());
+        context.timestamp = fired_at + Duration::hours(1);
+        assert!(restarted.decide(&context).await?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_triggered_transfer_uses_payload_fields() -> Result<()> {
+        let recipient = Pubkey::new_unique();
+        let agent = DeterministicAgent::unchecked(DeterministicStrategy::PeriodicTransfer {
+            interval_seconds: 3600,
+            recipient: Pubkey::new_unique(),
+            amount_sol: 0.1,
+        })
+        .with_trigger_handler(TriggerHandler::TransferSol {
+            recipient: Param::Field {
+                field: "recipient".to_string(),
+            },
+            amount_sol: Param::Field {
+                field: "amount_sol".to_string(),
+            },
+        });
+
+        let config = TriggerConfig {
+            schema: agent.trigger_schema(),
+            ..Default::default()
+        };
+        let (handle, mut receiver) = trigger::channel("alerts", config);
+        handle.fire(TriggerPayload::new(
+            "webhook",
+            json!({ "recipient": recipient.to_string(), "amount_sol": 0.5 }),
+        ))?;
+
+        // A trigger acts immediately, even though the interval has not elapsed
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.wallet_balance = 5.0;
+        context.last_action_time = Some(context.timestamp);
+        context.trigger = receiver.recv().await;
+
+        let action = agent
+            .decide(&context)
+            .await?
+            .ok_or_else(|| AgentError::decision("expected a triggered transfer"))?;
+        assert!(matches!(
+            action,
+            AgentAction::TransferSol { to, amount: Lamports(500_000_000), .. } if to == recipient
+        ));
+
+        // Without a trigger the schedule applies
+        context.trigger = None;
+        assert!(agent.decide(&context).await?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_strategy_validation_names_the_failing_field() -> Result<()> {
+        let (wallet, mint, usdc) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let limits = AgentLimits::default();
+        let periodic =
+            |interval_seconds: u64, amount_sol: f64| DeterministicStrategy::PeriodicTransfer {
+                interval_seconds,
+                recipient: Pubkey::new_unique(),
+                amount_sol,
+            };
+        let tokens = |amount: u64| DeterministicStrategy::PeriodicTokenTransfer {
+            interval_seconds: 60,
+            mint,
+            recipient: Pubkey::new_unique(),
+            amount,
+        };
+        let sweep = |floor_sol: f64, min_sweep_sol: f64, unwind_when_below_sol: f64| {
+            DeterministicStrategy::IdleSweep {
+                floor_sol,
+                target: SweepTarget::Validator(Pubkey::new_unique()),
+                min_sweep_sol,
+                unwind_when_below_sol,
+            }
+        };
+        let dca = |input_mint: Pubkey, amount: u64, interval: DcaInterval, slippage_bps: u16| {
+            DeterministicStrategy::DollarCostAverage {
+                input_mint,
+                output_mint: usdc,
+                amount_per_interval: amount,
+                interval,
+                max_total_spent: 10 * amount.max(1),
+                slippage_bps,
+                start_at: None,
+                end_at: None,
+            }
+        };
+        let rebalance =
+            |targets: HashMap<Pubkey, f64>, tolerance_bps: u16| DeterministicStrategy::Rebalance {
+                targets,
+                tolerance_bps,
+                min_trade_sol_value: 0.1,
+            };
+        let stop_loss = |exit_mint: Pubkey, stop_loss_pct: f64, take_profit_pct: f64| {
+            DeterministicStrategy::StopLoss {
+                mint,
+                exit_mint,
+                entry_price: None,
+                stop_loss_pct,
+                take_profit_pct,
+                trailing: false,
+            }
+        };
+        let halves = HashMap::from([(NATIVE_MINT, 0.5), (usdc, 0.5)]);
+
+        for (strategy, reason) in [
+            (periodic(3600, 0.1), ""),
+            (periodic(0, 0.1), "interval_seconds"),
+            (periodic(5, 0.1), "interval_seconds"),
+            (periodic(3600, 0.0), "amount_sol"),
+            (periodic(3600, -0.1), "amount_sol"),
+            (periodic(3600, f64::NAN), "amount_sol"),
+            (periodic(3600, 5.0), "max_sol_per_transaction"),
+            (tokens(0), "amount"),
+            (
+                tokens(limits.max_tokens_per_transaction + 1),
+                "max_tokens_per_transaction",
+            ),
+            (sweep(2.0, 0.5, 1.0), ""),
+            (sweep(-1.0, 0.5, 0.0), "floor_sol"),
+            (sweep(2.0, 0.0, 1.0), "min_sweep_sol"),
+            (sweep(2.0, 0.5, 3.0), "unwind_when_below_sol"),
+            (
+                dca(NATIVE_MINT, 100_000_000, DcaInterval::Seconds(86_400), 100),
+                "",
+            ),
+            (
+                dca(NATIVE_MINT, 100_000_000, DcaInterval::Seconds(1), 100),
+                "interval",
+            ),
+            (
+                dca(NATIVE_MINT, 0, DcaInterval::Seconds(86_400), 100),
+                "amount_per_interval",
+            ),
+            (
+                dca(
+                    NATIVE_MINT,
+                    5_000_000_000,
+                    DcaInterval::Seconds(86_400),
+                    100,
+                ),
+                "amount_per_interval",
+            ),
+            (
+                dca(usdc, 1_000, DcaInterval::Seconds(86_400), 100),
+                "input_mint",
+            ),
+            (
+                dca(NATIVE_MINT, 1_000, DcaInterval::Seconds(86_400), 10_001),
+                "slippage_bps",
+            ),
+            (rebalance(halves.clone(), 500), ""),
+            (rebalance(HashMap::from([(usdc, 0.5)]), 500), "weights"),
+            (rebalance(halves, 10_001), "tolerance_bps"),
+            (stop_loss(NATIVE_MINT, 10.0, 150.0), ""),
+            (stop_loss(mint, 10.0, 25.0), "exit_mint"),
+            (stop_loss(NATIVE_MINT, 0.0, 25.0), "stop_loss_pct"),
+            (stop_loss(NATIVE_MINT, 101.0, 25.0), "stop_loss_pct"),
+            (stop_loss(NATIVE_MINT, 10.0, -5.0), "take_profit_pct"),
+        ] {
+            match DeterministicAgent::try_new(strategy, &limits) {
+                Ok(_) => assert!(reason.is_empty(), "accepted strategy with bad {}", reason),
+                Err(AgentError::Config(message)) => {
+                    assert!(!reason.is_empty(), "rejected valid strategy: {}", message);
+                    assert!(
+                        message.contains(reason),
+                        "{} does not name {}",
+                        message,
+                        reason
+                    );
+                }
+                Err(other) => return Err(other),
+            }
+        }
+
+        // The minimum interval is configurable
+        let hasty = AgentLimits {
+            min_interval_seconds: 1,
+            ..AgentLimits::default()
+        };
+        assert!(periodic(5, 0.1).validate(&hasty).is_ok());
+
+        // Paying the wallet itself only burns fees
+        let to_self = DeterministicStrategy::PeriodicTransfer {
+            interval_seconds: 3600,
+            recipient: wallet,
+            amount_sol: 0.1,
+        };
+        assert!(to_self.validate(&limits).is_ok());
+        assert!(matches!(
+            to_self.check_recipient(&wallet),
+            Err(AgentError::Config(message)) if message.contains("recipient")
+        ));
+        assert!(to_self.check_recipient(&Pubkey::new_unique()).is_ok());
+        Ok(())
+    }
+
+    fn sweep_agent() -> (DeterministicAgent, Pubkey) {
+        let validator = Pubkey::new_unique();
+        let agent = DeterministicAgent::unchecked(DeterministicStrategy::IdleSweep {
+            floor_sol: 2.0,
+            target: SweepTarget::Validator(validator),
+            min_sweep_sol: 0.5,
+            unwind_when_below_sol: 1.0,
+        });
+        (agent, validator)
+    }
+
+    fn with_balance(sol: f64) -> AgentContext {
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.wallet_balance = sol;
+        context
+    }
+
+    #[tokio::test]
+    async fn test_idle_sweep_stakes_excess_above_floor() -> Result<()> {
+        let (agent, validator) = sweep_agent();
+
+        let action = agent
+            .decide(&with_balance(5.0))
+            .await?
+            .ok_or_else(|| AgentError::decision("expected a sweep"))?;
+        assert!(matches!(
+            action,
+            AgentAction::StakeTokens { staking_pool, amount: Lamports(3_000_000_000) }
+                if staking_pool == validator
+        ));
+        assert_eq!(agent.sweep_state().swept_lamports, 3_000_000_000);
+        assert!(agent.templates().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_sweep_holds_inside_band() -> Result<()> {
+        let (agent, _) = sweep_agent();
+
+        // Excess below the minimum sweep, and balance above the unwind threshold
+        assert!(agent.decide(&with_balance(2.4)).await?.is_none());
+        assert!(agent.decide(&with_balance(1.5)).await?.is_none());
+        assert_eq!(agent.sweep_state(), SweepState::default());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_sweep_unwinds_below_threshold() -> Result<()> {
+        let (agent, validator) = sweep_agent();
+        agent.decide(&with_balance(5.0)).await?;
+
+        let action = agent
+            .decide(&with_balance(0.5))
+            .await?
+            .ok_or_else(|| AgentError::decision("expected an unwind"))?;
+        assert!(matches!(
+            action,
+            AgentAction::UnstakeTokens { staking_pool, amount: Lamports(1_500_000_000) }
+                if staking_pool == validator
+        ));
+        assert_eq!(
+            agent.sweep_state(),
+            SweepState {
+                swept_lamports: 1_500_000_000,
+                pending_unwind_lamports: Some(1_500_000_000),
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_sweep_waits_for_pending_deactivation() -> Result<()> {
+        let (agent, _) = sweep_agent();
+        agent.decide(&with_balance(5.0)).await?;
+        agent.decide(&with_balance(0.5)).await?;
+
+        // Still cooling down: no second deactivation
+        assert!(agent.decide(&with_balance(0.4)).await?.is_none());
+        assert_eq!(
+            agent.sweep_state().pending_unwind_lamports,
+            Some(1_500_000_000)
+        );
+
+        // Funds are back, so the next drop may unwind again
+        assert!(agent.decide(&with_balance(1.9)).await?.is_none());
+        assert_eq!(agent.sweep_state().pending_unwind_lamports, None);
+        let action = agent.decide(&with_balance(0.9)).await?;
+        assert!(matches!(
+            action,
+            Some(AgentAction::UnstakeTokens {
+                amount: Lamports(1_100_000_000),
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    const USDC_DECIMALS: u8 = 6;
+
+    fn dca_agent(
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount_per_interval: u64,
+        interval: DcaInterval,
+        max_total_spent: u64,
+    ) -> DeterministicAgent {
+        DeterministicAgent::unchecked(DeterministicStrategy::DollarCostAverage {
+            input_mint,
+            output_mint,
+            amount_per_interval,
+            interval,
+            max_total_spent,
+            slippage_bps: 100,
+            start_at: None,
+            end_at: None,
+        })
+    }
+
+    /// Context at `timestamp` with 10 SOL, SOL at $150 and USDC at $1
+    fn market(usdc: Pubkey, timestamp: DateTime<Utc>) -> AgentContext {
+        let mut context = with_balance(10.0);
+        context.timestamp = timestamp;
+        context.price_feeds.insert("SOL".to_string(), 150.0);
+        context.price_feeds.insert(usdc.to_string(), 1.0);
+        context.token_decimals.insert(usdc, USDC_DECIMALS);
+        context
+    }
+
+    fn monday() -> Result<DateTime<Utc>> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0)
+            .single()
+            .ok_or_else(|| AgentError::config("invalid test date"))
+    }
+
+    /// Swaps emitted over a week of hourly ticks
+    async fn week_of_hourly_ticks(
+        agent: &DeterministicAgent,
+        usdc: Pubkey,
+    ) -> Result<Vec<AgentAction>> {
+        let start = monday()?;
+        let mut swaps = Vec::new();
+        for hour in 0..24 * 7 {
+            let context = market(usdc, start + Duration::hours(hour));
+            swaps.extend(agent.decide(&context).await?);
+        }
+        Ok(swaps)
+    }
+
+    #[tokio::test]
+    async fn test_dca_buys_daily_over_a_week() -> Result<()> {
+        let usdc = Pubkey::new_unique();
+        let agent = dca_agent(
+            NATIVE_MINT,
+            usdc,
+            100_000_000,
+            DcaInterval::Seconds(86_400),
+            u64::MAX,
+        );
+
+        let swaps = week_of_hourly_ticks(&agent, usdc).await?;
+        assert_eq!(swaps.len(), 7);
+        // 0.1 SOL at $150 buys 15 USDC, less 1% slippage
+        assert!(matches!(
+            swaps[0],
+            AgentAction::SwapTokens {
+                input_mint,
+                output_mint,
+                amount: 100_000_000,
+                min_output_amount: 14_850_000,
+            } if input_mint == NATIVE_MINT && output_mint == usdc
+        ));
+        assert_eq!(agent.dca_state().total_spent, 700_000_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dca_follows_cron_schedule() -> Result<()> {
+        let usdc = Pubkey::new_unique();
+        let agent = dca_agent(
+            NATIVE_MINT,
+            usdc,
+            10_000_000,
+            DcaInterval::Cron("0 0 */6 * * *".to_string()),
+            u64::MAX,
+        );
+
+        // 06:00, 12:00 and 18:00 of the first day, then four a day; the
+        // schedule counts from the first tick at midnight
+        let swaps = week_of_hourly_ticks(&agent, usdc).await?;
+        assert_eq!(swaps.len(), 27);
+        assert_eq!(
+            agent.dca_state().last_swap_at,
+            Some(monday()? + Duration::hours(162))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dca_stops_at_spending_cap() -> Result<()> {
+        let usdc = Pubkey::new_unique();
+        let bonk = Pubkey::new_unique();
+        let agent = dca_agent(
+            usdc,
+            bonk,
+            100_000_000,
+            DcaInterval::Seconds(3600),
+            250_000_000,
+        );
+
+        let start = monday()?;
+        let mut amounts = Vec::new();
+        for hour in 0..24 * 7 {
+            let mut context = market(usdc, start + Duration::hours(hour));
+            context.price_feeds.insert(bonk.to_string(), 0.00002);
+            context.token_decimals.insert(bonk, 5);
+            context.token_balances.insert(usdc, 1_000_000_000);
+            if let Some(AgentAction::SwapTokens { amount, .. }) = agent.decide(&context).await? {
+                amounts.push(amount);
+            }
+        }
+
+        // The last swap only spends what is left of the cap
+        assert_eq!(amounts, vec![100_000_000, 100_000_000, 50_000_000]);
+        let state = agent.dca_state();
+        assert!(state.completed);
+        assert_eq!(state.total_spent, 250_000_000);
+
+        // A restarted agent picks up where the first one stopped
+        let restarted = dca_agent(
+            usdc,
+            bonk,
+            100_000_000,
+            DcaInterval::Seconds(3600),
+            250_000_000,
+        )
+        .with_dca_state(state);
+        assert!(restarted
+            .decide(&market(usdc, start + Duration::days(30)))
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dca_skips_unaffordable_and_unpriced_ticks() -> Result<()> {
+        let usdc = Pubkey::new_unique();
+        let agent = dca_agent(
+            NATIVE_MINT,
+            usdc,
+            1_000_000_000,
+            DcaInterval::Seconds(3600),
+            u64::MAX,
+        );
+        let start = monday()?;
+
+        // 1 SOL would dip into the 9.5 SOL reserve
+        let mut context = market(usdc, start);
+        context.spending_limits.min_sol_reserve_lamports = 9_500_000_000;
+        assert!(agent.decide(&context).await?.is_none());
+
+        // No quote for the output mint, so no slippage bound
+        let mut context = market(usdc, start);
+        context.price_feeds.remove(&usdc.to_string());
+        assert!(agent.decide(&context).await?.is_none());
+        assert_eq!(agent.dca_state().swaps, 0);
+
+        // Skipped ticks do not delay the schedule
+        assert!(agent.decide(&market(usdc, start)).await?.is_some());
+        Ok(())
+    }
+
+    /// 50% SOL, 25% USDC and 25% of a second token, with SOL at $100
+    fn rebalance_agent(usdc: Pubkey, bonk: Pubkey) -> DeterministicAgent {
+        DeterministicAgent::unchecked(DeterministicStrategy::Rebalance {
+            targets: HashMap::from([(NATIVE_MINT, 0.5), (usdc, 0.25), (bonk, 0.25)]),
+            tolerance_bps: 500,
+            min_trade_sol_value: 0.1,
+        })
+    }
+
+    /// Portfolio of `sol` SOL, `usdc_dollars` of USDC and `bonk_dollars` of
+    /// the second token, priced unless `bonk_priced` is false
+    fn portfolio(
+        usdc: Pubkey,
+        bonk: Pubkey,
+        sol: f64,
+        usdc_dollars: u64,
+        bonk_dollars: u64,
+        bonk_priced: bool,
+    ) -> AgentContext {
+        let mut context = with_balance(sol);
+        context.price_feeds.insert("SOL".to_string(), 100.0);
+        context.price_feeds.insert(usdc.to_string(), 1.0);
+        context.token_decimals.insert(usdc, USDC_DECIMALS);
+        context
+            .token_balances
+            .insert(usdc, usdc_dollars * 1_000_000);
+        if bonk_priced {
+            context.price_feeds.insert(bonk.to_string(), 0.5);
+        }
+        context.token_decimals.insert(bonk, 5);
+        context
+            .token_balances
+            .insert(bonk, bonk_dollars * 2 * 100_000);
+        context
+    }
+
+    #[tokio::test]
+    async fn test_balanced_portfolio_is_left_alone() -> Result<()> {
+        let (usdc, bonk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let agent = rebalance_agent(usdc, bonk);
+
+        assert!(agent
+            .decide(&portfolio(usdc, bonk, 10.0, 500, 500, true))
+            .await?
+            .is_none());
+        // 4% off its weight is inside the 5% tolerance
+        assert!(agent
+            .decide(&portfolio(usdc, bonk, 10.0, 580, 420, true))
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overweight_asset_is_sold_into_underweight_one() -> Result<()> {
+        let (usdc, bonk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let agent = rebalance_agent(usdc, bonk);
+
+        // USDC is half of the $2000 portfolio, twice its weight, and SOL
+        // is 25% short; $500 of USDC buys 5 SOL, less 1% slippage
+        let action = agent
+            .decide(&portfolio(usdc, bonk, 5.0, 1000, 500, true))
+            .await?
+            .ok_or_else(|| AgentError::decision("expected a rebalancing swap"))?;
+        assert!(matches!(
+            action,
+            AgentAction::SwapTokens {
+                input_mint,
+                output_mint,
+                amount: 500_000_000,
+                min_output_amount: 4_950_000_000,
+            } if input_mint == usdc && output_mint == NATIVE_MINT
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unpriced_asset_is_left_out_of_rebalance() -> Result<()> {
+        let (usdc, bonk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let agent = rebalance_agent(usdc, bonk);
+
+        // Without the second token, SOL and USDC aim for 2/3 and 1/3 of
+        // their $1500 and sit at 1/3 and 2/3
+        let action = agent
+            .decide(&portfolio(usdc, bonk, 5.0, 1000, 500, false))
+            .await?
+            .ok_or_else(|| AgentError::decision("expected a rebalancing swap"))?;
+        assert!(matches!(
+            action,
+            AgentAction::SwapTokens { input_mint, output_mint, .. }
+                if input_mint == usdc && output_mint == NATIVE_MINT
+        ));
+
+        // Weights that do not add up are a configuration error
+        let broken = DeterministicAgent::unchecked(DeterministicStrategy::Rebalance {
+            targets: HashMap::from([(NATIVE_MINT, 0.5), (usdc, 0.25)]),
+            tolerance_bps: 500,
+            min_trade_sol_value: 0.1,
+        });
+        assert!(matches!(
+            broken
+                .decide(&portfolio(usdc, bonk, 5.0, 1000, 500, true))
+                .await,
+            Err(AgentError::Config(_))
+        ));
+        Ok(())
+    }
+
+    fn stop_loss_agent(
+        mint: Pubkey,
+        entry_price: Option<f64>,
+        take_profit_pct: f64,
+        trailing: bool,
+    ) -> DeterministicAgent {
+        DeterministicAgent::unchecked(DeterministicStrategy::StopLoss {
+            mint,
+            exit_mint: NATIVE_MINT,
+            entry_price,
+            stop_loss_pct: 10.0,
+            take_profit_pct,
+            trailing,
+        })
+    }
+
+    /// Context holding `balance` base units of `mint` priced at `price`
+    fn position(mint: Pubkey, balance: u64, price: f64) -> AgentContext {
+        let mut context = with_balance(1.0);
+        context.price_feeds.insert("SOL".to_string(), 100.0);
+        context.price_feeds.insert(mint.to_string(), price);
+        context.token_decimals.insert(mint, USDC_DECIMALS);
+        context.token_balances.insert(mint, balance);
+        context
+    }
+
+    /// Amounts sold on each tick of a price and balance path
+    async fn walk(
+        agent: &DeterministicAgent,
+        mint: Pubkey,
+        path: &[(u64, f64)],
+    ) -> Result<Vec<Option<u64>>> {
+        let mut sold = Vec::new();
+        for (balance, price) in path {
+            sold.push(
+                match agent.decide(&position(mint, *balance, *price)).await? {
+                    Some(AgentAction::SwapTokens {
+                        amount,
+                        output_mint,
+                        ..
+                    }) if output_mint == NATIVE_MINT => Some(amount),
+                    Some(other) => {
+                        return Err(AgentError::decision(format!("unexpected {:?}", other)))
+                    }
+                    None => None,
+                },
+            );
+        }
+        Ok(sold)
+    }
+
+    #[tokio::test]
+    async fn test_stop_loss_exits_below_inferred_entry() -> Result<()> {
+        let mint = Pubkey::new_unique();
+        let agent = stop_loss_agent(mint, None, 50.0, false);
+
+        // Nothing held yet: a crash sells nothing and sets no entry
+        let sold = walk(
+            &agent,
+            mint,
+            &[(0, 50.0), (1_000, 100.0), (1_000, 95.0), (1_000, 89.0)],
+        )
+        .await?;
+        assert_eq!(sold, vec![None, None, None, Some(1_000)]);
+        assert_eq!(agent.stop_loss_state().entry_price, Some(100.0));
+
+        // Closed, then dormant whatever the price does
+        let sold = walk(&agent, mint, &[(0, 80.0), (0, 20.0), (0, 200.0)]).await?;
+        assert_eq!(sold, vec![None, None, None]);
+        assert!(agent.stop_loss_state().dormant);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_take_profit_exits_above_configured_entry() -> Result<()> {
+        let mint = Pubkey::new_unique();
+        let agent = stop_loss_agent(mint, Some(100.0), 20.0, false);
+
+        let sold = walk(&agent, mint, &[(500, 110.0), (500, 119.0), (500, 121.0)]).await?;
+        assert_eq!(sold, vec![None, None, Some(500)]);
+
+        // 1 token at $121 is 1.21 SOL, less 1% slippage
+        let action = stop_loss_agent(mint, Some(100.0), 20.0, false)
+            .decide(&position(mint, 1_000_000, 121.0))
+            .await?;
+        assert!(matches!(
+            action,
+            Some(AgentAction::SwapTokens {
+                min_output_amount: 1_197_900_000,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trailing_stop_ratchets_up_with_price() -> Result<()> {
+        let mint = Pubkey::new_unique();
+        let trailing = stop_loss_agent(mint, Some(100.0), 50.0, true);
+        let fixed = stop_loss_agent(mint, Some(100.0), 50.0, false);
+        let path = [
+            (1_000, 100.0),
+            (1_000, 120.0),
+            (1_000, 130.0),
+            (1_000, 118.0),
+            (1_000, 116.0),
+        ];
+
+        // 10% below the $130 high is $117
+        assert_eq!(
+            walk(&trailing, mint, &path).await?,
+            vec![None, None, None, None, Some(1_000)]
+        );
+        assert_eq!(trailing.stop_loss_state().high_water, Some(130.0));
+        // Measured from the entry, $116 is well above the $90 stop
+        assert_eq!(walk(&fixed, mint, &path).await?, vec![None; 5]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partial_exit_is_retried_until_closed() -> Result<()> {
+        let mint = Pubkey::new_unique();
+        let agent = stop_loss_agent(mint, Some(100.0), 50.0, false);
+
+        // Half the exit filled: the rest is sold even after a rebound
+        let sold = walk(&agent, mint, &[(1_000, 85.0), (500, 95.0), (0, 95.0)]).await?;
+        assert_eq!(sold, vec![Some(1_000), Some(500), None]);
+        assert!(agent.stop_loss_state().dormant);
+
+        // Re-entered at $60: guarded from there, not from the old entry
+        let sold = walk(&agent, mint, &[(2_000, 60.0), (2_000, 56.0), (2_000, 53.0)]).await?;
+        assert_eq!(sold, vec![None, None, Some(2_000)]);
+        Ok(())
+    }
+}
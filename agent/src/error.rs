@@ -0,0 +1,79 @@
+//! Error types for the agent framework
+//!
+//! Agent logic reports failures through [`AgentError`], which also wraps
+//! errors coming from the core wallet library.
+
+/// Result type alias for agent operations
+pub type Result<T> = std::result::Result<T, AgentError>;
+
+/// Error type for agent operations
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    /// Error from the core wallet library
+    #[error("Wallet error: {0}")]
+    Wallet(#[from] agent_wallet_core::Error),
+
+    /// Agent failed to reach a decision
+    #[error("Decision error: {0}")]
+    Decision(String),
+
+    /// Invalid agent configuration
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// Agent limit exceeded
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// Agent acted faster than its rate limit allows
+    #[error("Rate limit exceeded: {0}")]
+    RateLimitExceeded(String),
+
+    /// Agent logic violated its sandbox
+    #[error("Sandbox violation: {0}")]
+    SandboxViolation(String),
+
+    /// Agent did not decide within its decision timeout
+    #[error("Decision timed out after {0:?}")]
+    DecisionTimeout(std::time::Duration),
+
+    /// Agent is not in a state that allows the operation
+    #[error("Invalid agent state: {0}")]
+    InvalidState(String),
+
+    /// External trigger payload was rejected
+    #[error("Invalid trigger: {0}")]
+    InvalidTrigger(String),
+}
+
+impl AgentError {
+    /// Create a decision error
+    pub fn decision(msg: impl Into<String>) -> Self {
+        Self::Decision(msg.into())
+    }
+
+    /// Create a configuration error
+    pub fn config(msg: impl Into<String>) -> Self {
+        Self::Config(msg.into())
+    }
+
+    /// Create a rate limit error
+    pub fn rate_limit_exceeded(msg: impl Into<String>) -> Self {
+        Self::RateLimitExceeded(msg.into())
+    }
+
+    /// Create a sandbox violation error
+    pub fn sandbox_violation(msg: impl Into<String>) -> Self {
+        Self::SandboxViolation(msg.into())
+    }
+
+    /// Create an invalid state error
+    pub fn invalid_state(msg: impl Into<String>) -> Self {
+        Self::InvalidState(msg.into())
+    }
+
+    /// Create an invalid trigger error
+    pub fn invalid_trigger(msg: impl Into<String>) -> Self {
+        Self::InvalidTrigger(msg.into())
+    }
+}
@@ -0,0 +1,164 @@
+//! Typed events of an agent runner
+//!
+//! [`AgentRunner::subscribe`](crate::runner::AgentRunner::subscribe) hands
+//! out receivers of the [`RunnerEvent`]s a runner emits at each step of a
+//! tick, so an application embedding the agent can follow it without reading
+//! the audit log. A successful tick emits, in order:
+//!
+//! ```text
+//! TickStarted, ContextBuilt, DecisionMade, TransactionSent, Confirmed
+//! ```
+//!
+//! `Confirmed` is left out when the runner does not wait for confirmation,
+//! and in dry runs, where `TransactionSent` carries the signature the
+//! transaction would have had. A tick that goes wrong ends with
+//! `ValidationFailed` if the wallet refused the action before sending it, or
+//! `Failed` otherwise. A tick whose action has to wait for a human ends with
+//! `ApprovalRequested` instead of `TransactionSent`.
+//!
+//! The channel is bounded and never holds up the runner: a receiver that
+//! falls more than [`RUNNER_EVENT_CAPACITY`] events behind gets
+//! [`RecvError::Lagged`](tokio::sync::broadcast::error::RecvError::Lagged)
+//! and continues from the oldest event still buffered.
+//!
+//! Unlike the alerts of [`notify`](crate::notify), which are delivered to
+//! webhooks, these events describe every tick and are only kept in memory.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Signature;
+use uuid::Uuid;
+
+use crate::agent::AgentStatus;
+use crate::context::AgentContext;
+use crate::decision::AgentAction;
+
+/// Events buffered for each subscriber of a runner
+pub const RUNNER_EVENT_CAPACITY: usize = 256;
+
+/// A step of an agent runner's tick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunnerEvent {
+    /// A tick began
+    TickStarted {
+        /// Id of the decision, shared with its audit records and memo
+        decision_id: Uuid,
+        /// Whether an external trigger started the tick
+        triggered: bool,
+    },
+    /// The agent context was refreshed from the wallet
+    ContextBuilt {
+        /// What the agent is deciding on
+        summary: ContextSummary,
+    },
+    /// The agent decided; `NoOp` when it chose to do nothing
+    DecisionMade {
+        /// Action the agent decided on
+        action: AgentAction,
+        /// Explanation the agent gave, if any
+        rationale: Option<String>,
+    },
+    /// The wallet refused the action before anything was sent
+    ValidationFailed {
+        /// Why the action was refused
+        errors: Vec<String>,
+    },
+    /// The transaction was sent, or only simulated in a dry run
+    TransactionSent {
+        /// Transaction signature
+        signature: Signature,
+        /// Whether the transaction was only simulated
+        dry_run: bool,
+    },
+    /// The transaction reached the runner's confirmation level
+    Confirmed {
+        /// Transaction signature
+        signature: Signature,
+        /// Fee paid, in lamports
+        fee: u64,
+    },
+    /// The action was held back until a human approves it
+    ApprovalRequested {
+        /// Token of the approval request
+        token: String,
+        /// When the request resolves on its own
+        expires_at: DateTime<Utc>,
+    },
+    /// The tick failed
+    Failed {
+        /// What went wrong
+        error: String,
+    },
+    /// The runner changed status
+    StatusChanged {
+        /// Previous status
+        from: AgentStatus,
+        /// New status
+        to: AgentStatus,
+    },
+}
+
+impl RunnerEvent {
+    /// Name of the event, as in its serialized `event` field
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::TickStarted { .. } => "tick_started",
+            Self::ContextBuilt { .. } => "context_built",
+            Self::DecisionMade { .. } => "decision_made",
+            Self::ValidationFailed { .. } => "validation_failed",
+            Self::TransactionSent { .. } => "transaction_sent",
+            Self::Confirmed { .. } => "confirmed",
+            Self::ApprovalRequested { .. } => "approval_requested",
+            Self::Failed { .. } => "failed",
+            Self::StatusChanged { .. } => "status_changed",
+        }
+    }
+}
+
+/// Key figures of the context an agent decides on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextSummary {
+    /// Wallet balance in SOL
+    pub balance_sol: f64,
+    /// Number of tokens the wallet holds
+    pub token_balances: usize,
+    /// Daily budget left, in lamports
+    pub remaining_daily_budget_lamports: u64,
+}
+
+impl ContextSummary {
+    /// Summarize `context`
+    pub fn new(context: &AgentContext) -> Self {
+        Self {
+            balance_sol: context.wallet_balance,
+            token_balances: context.token_balances.len(),
+            remaining_daily_budget_lamports: context
+                .spending_limits
+                .remaining_daily_budget_lamports
+                .as_u64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use agent_wallet_core::Error as CoreError;
+
+    #[test]
+    fn test_event_serialization_is_tagged() -> Result<()> {
+        let event = RunnerEvent::StatusChanged {
+            from: AgentStatus::Stopped,
+            to: AgentStatus::Active,
+        };
+        let json = serde_json::to_value(&event).map_err(CoreError::from)?;
+        assert_eq!(json["event"], event.kind());
+        assert_eq!(json["to"], "Active");
+
+        let back: RunnerEvent = serde_json::from_value(json).map_err(CoreError::from)?;
+        assert_eq!(back.kind(), "status_changed");
+        Ok(())
+    }
+}
@@ -12,6 +12,15 @@
 //! - **Context Management**: Structured context for agent decision-making
 //! - **Decision Framework**: Types for agent decisions and actions
 //! - **Sandboxed Execution**: Safe environment for agent logic
+//! - **External Triggers**: Event-driven decisions from webhooks and alerts
+//! - **Agent Workspaces**: Isolated per-agent directories for persisted artifacts
+//! - **Audit Log**: Sequenced decision records that clients can tail from a cursor
+//! - **Dead Letters**: Actions that keep failing validation are parked instead of repaired
+//! - **Agent Specs**: YAML or JSON files describing an agent, validated before it runs
+//! - **Notifications**: Agent events delivered to signed webhooks in the background
+//! - **Composite Agents**: Several strategies on one wallet with conflict resolution and sub-budgets
+//! - **Persistent State**: Strategy state saved between runs so restarts never repeat an action
+//! - **Approval Gate**: Large actions wait for a human to approve them while the agent keeps running
 //!
 //! # Quick Start
 //!
@@ -22,13 +31,14 @@
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     // Create a deterministic agent
-//!     let agent = DeterministicAgent::new(
+//!     let agent = DeterministicAgent::try_new(
 //!         DeterministicStrategy::PeriodicTransfer {
 //!             interval_seconds: 3600,
 //!             recipient: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".parse()?,
 //!             amount_sol: 0.1,
-//!         }
-//!     );
+//!         },
+//!         &AgentLimits::default(),
+//!     )?;
 //!
 //!     // Create agent context
 //!     let context = AgentContext {
@@ -87,35 +97,65 @@
 #![warn(clippy::expect_used)]
 
 pub mod agent;
+pub mod approval;
+pub mod audit;
+pub mod composite;
 pub mod context;
+pub mod dead_letter;
 pub mod decision;
 pub mod deterministic;
 pub mod error;
+pub mod events;
 pub mod limits;
+pub mod notify;
+pub mod registry;
+pub mod runner;
 pub mod sandbox;
+pub mod spec;
+pub mod state;
+mod sync;
+pub mod trigger;
+pub mod workspace;
 
 #[cfg(feature = "llm")]
 pub mod llm;
 
 // Re-exports for convenience
-pub use agent::{Agent, AgentId, AgentStatus};
+pub use agent::{Agent, AgentId, AgentStatus, CancellationToken, DynAgent};
+pub use approval::{
+    ApprovalDecision, ApprovalGate, ApprovalRequest, ApprovalStatus, ApprovalStore,
+};
+pub use audit::{AuditLog, AuditRecord, RedactionPolicy};
+pub use composite::{ChildAgent, CompositeAgent, ConflictPolicy};
 pub use context::AgentContext;
-pub use decision::{AgentAction, AgentDecision, DecisionOutcome};
-pub use deterministic::{DeterministicAgent, DeterministicStrategy};
+pub use dead_letter::{DeadLetter, DeadLetterConfig, DeadLetterQueue};
+pub use decision::{AgentAction, AgentDecision, DecisionOutcome, DecisionRecord};
+pub use deterministic::{
+    DcaInterval, DcaState, DeterministicAgent, DeterministicStrategy, StopLossState, SweepState,
+    SweepTarget,
+};
 pub use error::{AgentError, Result};
+pub use events::{ContextSummary, RunnerEvent};
 
 #[cfg(feature = "llm")]
-pub use llm::{LlmAgent, LlmConfig, LlmProvider};
+pub use llm::{ContextSerializer, LlmAgent, LlmConfig, LlmProvider};
 
-pub use limits::{AgentLimits, RateLimit, SpendingLimit};
+pub use limits::{AgentLimits, RateLimit, SpendingLimit, TopUp};
+pub use notify::{AgentEvent, NoopNotifier, Notification, Notifier, WebhookNotifier};
+pub use registry::{AgentRegistry, RegistryEntry};
+pub use runner::AgentRunner;
 pub use sandbox::{Sandbox, SandboxConfig};
+pub use spec::{AgentKind, AgentSpec};
+pub use state::{AgentStateStore, FileStateStore, MemoryStateStore};
+pub use trigger::{TriggerConfig, TriggerHandle, TriggerHandler, TriggerPayload, TriggerSchema};
+pub use workspace::{AgentWorkspace, AgentWorkspaces, ArtifactKind, Retention};
 
 /// Prelude module for easy importing of common types
 pub mod prelude {
     pub use super::{
         Agent, AgentAction, AgentContext, AgentDecision, AgentError, AgentId, AgentLimits,
-        AgentStatus, DecisionOutcome, DeterministicAgent, DeterministicStrategy, RateLimit, Result,
-        Sandbox, SandboxConfig, SpendingLimit,
+        AgentRunner, AgentStatus, DecisionOutcome, DeterministicAgent, DeterministicStrategy,
+        RateLimit, Result, Sandbox, SandboxConfig, SpendingLimit,
     };
 
     #[cfg(feature = "llm")]
@@ -132,3 +172,7 @@ pub const DEFAULT_DECISION_TIMEOUT_SECS: u64 = 30;
 pub const DEFAULT_RATE_LIMIT_DECISIONS_PER_MINUTE: u32 = 60;
 /// Default spending limit per day in SOL
 pub const DEFAULT_SPENDING_LIMIT_SOL_PER_DAY: f64 = 10.0;
+/// Default rate limit: external triggers per minute
+pub const DEFAULT_TRIGGER_RATE_LIMIT_PER_MINUTE: u32 = 10;
+/// Default maximum size of a trigger payload in bytes
+pub const DEFAULT_TRIGGER_MAX_PAYLOAD_BYTES: usize = 4096;
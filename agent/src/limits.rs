@@ -0,0 +1,194 @@
+//! Agent limits
+//!
+//! Rate and spending limits applied to agents on top of the wallet's own
+//! spending checks.
+//!
+//! A [`RateLimit`] is enforced by a [`RateLimiter`], a token bucket that
+//! allows a burst of `max_decisions` and refills evenly over the window.
+//!
+//! A [`TopUp`] refills a wallet that fell below its minimum SOL reserve,
+//! either from a funding wallet or by asking operators through the notifier.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use agent_wallet_core::guard::BalanceGuard;
+use agent_wallet_core::rate_limit::TokenBucket;
+use agent_wallet_core::sol::Lamports;
+use agent_wallet_core::types::{AgentAction, AgentContext};
+use agent_wallet_core::Wallet;
+use serde::{Deserialize, Serialize};
+
+pub use agent_wallet_core::rate_limit::RateLimitState;
+pub use agent_wallet_core::types::AgentLimits;
+
+use crate::error::{AgentError, Result};
+use crate::{DEFAULT_RATE_LIMIT_DECISIONS_PER_MINUTE, DEFAULT_SPENDING_LIMIT_SOL_PER_DAY};
+
+/// Maximum number of decisions within a time window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Decisions allowed per window
+    pub max_decisions: u32,
+    /// Window length
+    pub window: Duration,
+}
+
+impl RateLimit {
+    /// Allow `max_decisions` per minute
+    pub fn per_minute(max_decisions: u32) -> Self {
+        Self {
+            max_decisions,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    /// Create a limiter enforcing this limit
+    pub fn limiter(&self) -> RateLimiter {
+        RateLimiter {
+            bucket: TokenBucket::new(self.max_decisions, self.window),
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::per_minute(DEFAULT_RATE_LIMIT_DECISIONS_PER_MINUTE)
+    }
+}
+
+/// Thread-safe enforcement of a [`RateLimit`]
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: TokenBucket,
+}
+
+impl RateLimiter {
+    /// Record an action at `now`, failing if the limit is used up
+    ///
+    /// A rejected action is not recorded.
+    pub fn check_and_record(&self, now: Instant) -> Result<()> {
+        self.bucket.check_and_record(now).map_err(|e| match e {
+            agent_wallet_core::Error::RateLimitExceeded(msg) => {
+                AgentError::rate_limit_exceeded(msg)
+            }
+            other => AgentError::from(other),
+        })
+    }
+
+    /// Remaining capacity and next allowed time at `now`
+    pub fn state(&self, now: Instant) -> RateLimitState {
+        self.bucket.state(now)
+    }
+}
+
+/// Spending caps for an agent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpendingLimit {
+    /// Maximum SOL spent per day
+    pub max_sol_per_day: f64,
+    /// Maximum SOL spent per transaction
+    pub max_sol_per_transaction: f64,
+}
+
+impl Default for SpendingLimit {
+    fn default() -> Self {
+        Self {
+            max_sol_per_day: DEFAULT_SPENDING_LIMIT_SOL_PER_DAY,
+            max_sol_per_transaction: 1.0,
+        }
+    }
+}
+
+/// Refill of a wallet that fell below its minimum SOL reserve
+#[derive(Clone)]
+pub struct TopUp {
+    /// Balance restored by a top-up, in SOL
+    ///
+    /// A target under the reserve tops up to the reserve itself.
+    pub target_sol: f64,
+    /// Wallet paying for top-ups; without one they are only requested
+    pub funding: Option<Arc<Wallet>>,
+}
+
+impl TopUp {
+    /// Top up to `target_sol` from `funding`
+    pub fn from_wallet(funding: Arc<Wallet>, target_sol: f64) -> Self {
+        Self {
+            target_sol,
+            funding: Some(funding),
+        }
+    }
+
+    /// Request top-ups to `target_sol` through the notifier only
+    pub fn notify_only(target_sol: f64) -> Self {
+        Self {
+            target_sol,
+            funding: None,
+        }
+    }
+
+    /// Transfer refilling the context's wallet, if it fell below its reserve
+    pub fn request(&self, context: &AgentContext) -> Option<AgentAction> {
+        let guard = BalanceGuard::new(Lamports::new(
+            context.spending_limits.min_sol_reserve_lamports,
+        ));
+        let balance = Lamports::from_sol_f64_rounded(context.wallet_balance).ok()?;
+        let target = Lamports::from_sol_f64_rounded(self.target_sol).ok()?;
+        let amount = guard.top_up_amount(balance, target)?;
+        Some(AgentAction::TransferSol {
+            to: context.wallet_pubkey,
+            amount,
+            memo: Some("agent-wallet top-up".to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_limiter_rejects_instead_of_dropping() {
+        let limiter = RateLimit {
+            max_decisions: 2,
+            window: Duration::from_secs(2),
+        }
+        .limiter();
+        let now = Instant::now();
+
+        assert!(limiter.check_and_record(now).is_ok());
+        assert!(limiter.check_and_record(now).is_ok());
+        assert!(matches!(
+            limiter.check_and_record(now),
+            Err(AgentError::RateLimitExceeded(_))
+        ));
+
+        let state = limiter.state(now);
+        assert_eq!(state.remaining, 0);
+        assert_eq!(state.next_allowed, Some(now + Duration::from_secs(1)));
+        assert!(limiter
+            .check_and_record(now + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_top_up_requested_below_reserve() {
+        let wallet = Pubkey::new_unique();
+        let mut context = AgentContext::new(wallet);
+        context.spending_limits.min_sol_reserve_lamports = 500_000_000;
+        context.wallet_balance = 0.2;
+        let top_up = TopUp::notify_only(1.0);
+
+        assert!(context.spendable_balance().abs() < f64::EPSILON);
+        assert!(matches!(
+            top_up.request(&context),
+            Some(AgentAction::TransferSol { to, amount: Lamports(800_000_000), .. }) if to == wallet
+        ));
+
+        context.wallet_balance = 0.6;
+        assert!(top_up.request(&context).is_none());
+        assert!((context.spendable_balance() - 0.1).abs() < 1e-9);
+    }
+}
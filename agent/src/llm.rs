@@ -0,0 +1,1057 @@
+//! Language-model driven agents
+//!
+//! An [`LlmAgent`] sends the agent context to an [`LlmProvider`] and expects
+//! a decision back as JSON matching [`DECISION_SCHEMA`]. The output is parsed
+//! strictly: unknown action types or fields, malformed addresses and
+//! out-of-range amounts are rejected before an action can reach the wallet.
+//! When parsing fails the model is asked again with the validation error
+//! appended, up to [`LlmConfig::max_attempts`] times.
+//!
+//! The context reaches the model through a [`ContextSerializer`]: history
+//! is summarized, fields the policy in [`LlmConfig::redact`] names are
+//! hidden, and the result is capped at [`LlmConfig::max_context_bytes`],
+//! always in the same way, so the same context yields the same prompt.
+//!
+//! An agent given a [`DeadLetterQueue`] counts the ticks on which an action
+//! failed validation. Once the queue parks an action, proposing it again
+//! fails the tick without spending repair attempts, and the prompt lists the
+//! parked actions so the model stops proposing them.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_agent::llm::{LlmAgent, LlmConfig, MockLlmProvider};
+//! use agent_wallet_agent::{Agent, AgentContext};
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example() -> agent_wallet_agent::Result<()> {
+//! let provider = MockLlmProvider::new(vec![
+//!     r#"{"action": {"type": "no_op"}, "confidence": 0.9, "rationale": "Nothing to do"}"#.to_string(),
+//! ]);
+//! let agent = LlmAgent::new(Arc::new(provider), LlmConfig::default());
+//!
+//! let decision = agent.decide(&AgentContext::new(Pubkey::new_unique())).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, warn};
+
+use agent_wallet_core::policy::AddressPolicy;
+use agent_wallet_core::sol::Lamports;
+use agent_wallet_core::types::{OracleData, PermissionLevel, TransactionStatus, TriggerPayload};
+
+use crate::agent::{Agent, CancellationToken};
+use crate::audit::{RedactionPolicy, REDACTED};
+use crate::context::{AgentContext, ContextFreshness, MarketConditions, SpendingLimits};
+use crate::dead_letter::{action_digest, DeadLetterQueue};
+use crate::decision::{AgentAction, AgentDecision};
+use crate::error::{AgentError, Result};
+use crate::sync::lock;
+
+/// JSON schema every model response must follow
+pub const DECISION_SCHEMA: &str = r#"{
+  "type": "object",
+  "additionalProperties": false,
+  "required": ["action", "confidence", "rationale"],
+  "properties": {
+    "action": {
+      "oneOf": [
+        { "type": "object", "additionalProperties": false, "required": ["type"],
+          "properties": { "type": { "const": "no_op" } } },
+        { "type": "object", "additionalProperties": false, "required": ["type", "params"],
+          "properties": { "type": { "const": "transfer_sol" },
+            "params": { "type": "object", "additionalProperties": false, "required": ["to", "amount_sol"],
+              "properties": { "to": { "type": "string", "description": "base58 address" },
+                              "amount_sol": { "type": "number", "exclusiveMinimum": 0 },
+                              "memo": { "type": "string" } } } } },
+        { "type": "object", "additionalProperties": false, "required": ["type", "params"],
+          "properties": { "type": { "const": "transfer_token" },
+            "params": { "type": "object", "additionalProperties": false, "required": ["mint", "to", "amount"],
+              "properties": { "mint": { "type": "string" }, "to": { "type": "string" },
+                              "amount": { "type": "integer", "minimum": 1, "description": "base units" },
+                              "memo": { "type": "string" } } } } },
+        { "type": "object", "additionalProperties": false, "required": ["type", "params"],
+          "properties": { "type": { "const": "swap_tokens" },
+            "params": { "type": "object", "additionalProperties": false,
+              "required": ["input_mint", "output_mint", "amount", "min_output_amount"],
+              "properties": { "input_mint": { "type": "string" }, "output_mint": { "type": "string" },
+                              "amount": { "type": "integer", "minimum": 1 },
+                              "min_output_amount": { "type": "integer", "minimum": 0 } } } } }
+      ]
+    },
+    "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+    "rationale": { "type": "string" }
+  }
+}"#;
+
+/// Author of a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmRole {
+    /// Instructions for the model
+    System,
+    /// Input to the model
+    User,
+    /// Earlier model output
+    Assistant,
+}
+
+/// Message of a chat completion request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LlmMessage {
+    /// Message author
+    pub role: LlmRole,
+    /// Message text
+    pub content: String,
+}
+
+impl LlmMessage {
+    /// Create a message
+    pub fn new(role: LlmRole, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+        }
+    }
+}
+
+/// Language model backend
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Provider name for logging
+    fn name(&self) -> &str;
+
+    /// Complete a chat conversation, returning the model's reply
+    async fn complete(&self, messages: &[LlmMessage], config: &LlmConfig) -> Result<String>;
+}
+
+/// LLM agent configuration
+///
+/// Credentials are not part of the configuration; providers read them from
+/// the environment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmConfig {
+    /// Model identifier
+    pub model: String,
+    /// Sampling temperature
+    pub temperature: f32,
+    /// Maximum tokens in a response
+    pub max_tokens: u32,
+    /// Attempts at obtaining a valid decision before giving up
+    pub max_attempts: u32,
+    /// Extra instructions prepended to the prompt
+    pub system_prompt: Option<String>,
+    /// Largest serialized context sent to the model, in bytes
+    #[serde(default = "default_max_context_bytes")]
+    pub max_context_bytes: usize,
+    /// Context fields hidden from the model
+    #[serde(default = "RedactionPolicy::for_prompts")]
+    pub redact: RedactionPolicy,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            model: "gpt-4o-mini".to_string(),
+            temperature: 0.0,
+            max_tokens: 512,
+            max_attempts: 3,
+            system_prompt: None,
+            max_context_bytes: default_max_context_bytes(),
+            redact: RedactionPolicy::for_prompts(),
+        }
+    }
+}
+
+/// Default cap on the serialized context, about 4,000 tokens
+fn default_max_context_bytes() -> usize {
+    16 * 1024
+}
+
+/// Transactions listed individually next to the history summary
+const RECENT_TRANSACTIONS: usize = 10;
+
+/// Serializes agent contexts for prompts, redacted and size-capped
+///
+/// Transaction history becomes aggregate statistics plus the latest
+/// records. Maps are sorted by key, so the same context always yields the
+/// same bytes. When the result exceeds the cap, the oldest listed
+/// transactions are dropped first, then the oldest errors, then prices and
+/// token balances from the last key backwards; what was dropped is counted
+/// under `omitted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextSerializer {
+    max_bytes: usize,
+    redact: RedactionPolicy,
+    recent_transactions: usize,
+}
+
+impl ContextSerializer {
+    /// Serializer capping output at `max_bytes` and applying `redact`
+    pub fn new(max_bytes: usize, redact: RedactionPolicy) -> Self {
+        Self {
+            max_bytes,
+            redact,
+            recent_transactions: RECENT_TRANSACTIONS,
+        }
+    }
+
+    /// Serializer with the limits of an agent configuration
+    pub fn from_config(config: &LlmConfig) -> Self {
+        Self::new(config.max_context_bytes, config.redact)
+    }
+
+    /// List at most `count` transactions individually
+    pub fn with_recent_transactions(mut self, count: usize) -> Self {
+        self.recent_transactions = count;
+        self
+    }
+
+    /// Serialize `context` as compact JSON within the byte cap
+    ///
+    /// Fails if even the context without history, errors, prices and token
+    /// balances exceeds the cap.
+    pub fn serialize(&self, context: &AgentContext) -> Result<String> {
+        let mut view = self.view(context);
+        loop {
+            let value = serde_json::to_value(&view)
+                .map_err(|e| AgentError::decision(format!("Failed to serialize context: {}", e)))?;
+            let json = canonical(value).to_string();
+            if json.len() <= self.max_bytes {
+                return Ok(json);
+            }
+            if !view.history.recent.is_empty() {
+                view.history.recent.remove(0);
+                view.omit("transactions");
+            } else if !view.recent_errors.is_empty() {
+                view.recent_errors.remove(0);
+                view.omit("errors");
+            } else if view.prices.pop_last().is_some() {
+                view.omit("prices");
+            } else if view.token_balances.pop().is_some() {
+                view.omit("token_balances");
+            } else {
+                return Err(AgentError::config(format!(
+                    "Context needs {} bytes, more than max_context_bytes ({})",
+                    json.len(),
+                    self.max_bytes
+                )));
+            }
+        }
+    }
+
+    fn view<'a>(&self, context: &'a AgentContext) -> PromptContext<'a> {
+        let mut history: Vec<_> = context.transaction_history.iter().collect();
+        history.sort_by_key(|record| (record.timestamp, record.signature.to_string()));
+        let mut summary = HistorySummary {
+            count: history.len(),
+            first: history.first().map(|record| record.timestamp),
+            last: history.last().map(|record| record.timestamp),
+            ..HistorySummary::default()
+        };
+        for record in &history {
+            *summary
+                .by_status
+                .entry(format!("{:?}", record.status))
+                .or_default() += 1;
+            *summary
+                .by_action
+                .entry(record.action_type.clone())
+                .or_default() += 1;
+            summary.fee_lamports = summary.fee_lamports.saturating_add(record.fee);
+        }
+        let skip = history.len().saturating_sub(self.recent_transactions);
+        summary.recent = history[skip..]
+            .iter()
+            .copied()
+            .map(|record| PromptTransaction {
+                timestamp: record.timestamp,
+                action_type: &record.action_type,
+                status: record.status,
+                amount: record.amount,
+                token_mint: record.token_mint.map(|mint| mint.to_string()),
+                destination: record.destination.map(|to| self.address(&to)),
+                fee: record.fee,
+                memo: record.memo.as_ref().filter(|_| !self.redact.memos).cloned(),
+            })
+            .collect();
+
+        let mut token_balances: Vec<_> = context
+            .token_balances
+            .iter()
+            .map(|(mint, amount)| PromptTokenBalance {
+                mint: mint.to_string(),
+                amount: *amount,
+                decimals: context.token_decimals.get(mint).copied(),
+            })
+            .collect();
+        token_balances.sort_by(|a, b| a.mint.cmp(&b.mint));
+
+        let mut recent_errors: Vec<_> = context
+            .recent_errors
+            .iter()
+            .map(|error| PromptError {
+                timestamp: error.timestamp,
+                error_type: &error.error_type,
+                message: &error.message,
+                recoverable: error.recoverable,
+            })
+            .collect();
+        recent_errors.sort_by_key(|error| error.timestamp);
+
+        let trigger = context.trigger.as_ref().map(|trigger| {
+            let mut trigger = trigger.clone();
+            if self.redact.trigger_data {
+                trigger.data = Value::String(REDACTED.to_string());
+            }
+            trigger
+        });
+
+        PromptContext {
+            wallet: self.address(&context.wallet_pubkey),
+            sol_balance: context.wallet_balance,
+            token_balances,
+            stale_token_mints: context
+                .stale_token_mints
+                .iter()
+                .map(|mint| mint.to_string())
+                .collect(),
+            stale_prices: context.stale_price_feeds.iter().cloned().collect(),
+            prices: context
+                .price_feeds
+                .iter()
+                .map(|(symbol, price)| (symbol.clone(), *price))
+                .collect(),
+            market: &context.market_conditions,
+            oracle: context.oracle_data.as_ref(),
+            timestamp: context.timestamp,
+            last_action_time: context.last_action_time,
+            decision_count: context.decision_count,
+            success_rate: context.success_rate,
+            spending_limits: &context.spending_limits,
+            permission_level: context.permission_level,
+            address_policy: &context.address_policy,
+            history: summary,
+            recent_errors,
+            trigger,
+            freshness: &context.freshness,
+            omitted: BTreeMap::new(),
+        }
+    }
+
+    /// An address as the policy allows it to be shown
+    fn address(&self, key: &Pubkey) -> String {
+        let key = key.to_string();
+        if !self.redact.addresses || key.len() <= 8 {
+            return key;
+        }
+        format!("{}...{}", &key[..4], &key[key.len() - 4..])
+    }
+}
+
+/// Context as shown to the model
+#[derive(Serialize)]
+struct PromptContext<'a> {
+    wallet: String,
+    sol_balance: f64,
+    token_balances: Vec<PromptTokenBalance>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stale_token_mints: Vec<String>,
+    prices: BTreeMap<String, f64>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    stale_prices: BTreeSet<String>,
+    market: &'a MarketConditions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    oracle: Option<&'a OracleData>,
+    timestamp: DateTime<Utc>,
+    last_action_time: Option<DateTime<Utc>>,
+    decision_count: u64,
+    success_rate: f64,
+    spending_limits: &'a SpendingLimits,
+    permission_level: PermissionLevel,
+    address_policy: &'a AddressPolicy,
+    history: HistorySummary<'a>,
+    recent_errors: Vec<PromptError<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trigger: Option<TriggerPayload>,
+    freshness: &'a ContextFreshness,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    omitted: BTreeMap<&'static str, usize>,
+}
+
+impl PromptContext<'_> {
+    fn omit(&mut self, what: &'static str) {
+        *self.omitted.entry(what).or_default() += 1;
+    }
+}
+
+/// Transaction history reduced to totals and the latest records
+#[derive(Default, Serialize)]
+struct HistorySummary<'a> {
+    count: usize,
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    by_status: BTreeMap<String, usize>,
+    by_action: BTreeMap<String, usize>,
+    fee_lamports: u64,
+    recent: Vec<PromptTransaction<'a>>,
+}
+
+#[derive(Serialize)]
+struct PromptTransaction<'a> {
+    timestamp: DateTime<Utc>,
+    action_type: &'a str,
+    status: TransactionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_mint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
+    fee: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PromptTokenBalance {
+    mint: String,
+    amount: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decimals: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct PromptError<'a> {
+    timestamp: DateTime<Utc>,
+    error_type: &'a str,
+    message: &'a str,
+    recoverable: bool,
+}
+
+/// `value` with the keys of every object in sorted order
+///
+/// Fields backed by hash maps would otherwise come out in a different
+/// order on every run.
+fn canonical(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonical(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonical).collect()),
+        other => other,
+    }
+}
+
+/// Model response as described by [`DECISION_SCHEMA`]
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDecision {
+    action: RawAction,
+    confidence: f64,
+    rationale: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "params",
+    rename_all = "snake_case",
+    deny_unknown_fields
+)]
+enum RawAction {
+    NoOp,
+    TransferSol(RawTransferSol),
+    TransferToken(RawTransferToken),
+    SwapTokens(RawSwap),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTransferSol {
+    to: String,
+    amount_sol: f64,
+    #[serde(default)]
+    memo: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTransferToken {
+    mint: String,
+    to: String,
+    amount: u64,
+    #[serde(default)]
+    memo: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSwap {
+    input_mint: String,
+    output_mint: String,
+    amount: u64,
+    min_output_amount: u64,
+}
+
+/// Parse and validate a model response into a decision
+///
+/// Markdown code fences around the JSON are tolerated; anything else that
+/// deviates from [`DECISION_SCHEMA`] is an error describing the problem.
+pub fn parse_decision(output: &str) -> Result<AgentDecision> {
+    let json = extract_json(output)
+        .ok_or_else(|| AgentError::decision("Response does not contain a JSON object"))?;
+    let raw: RawDecision = serde_json::from_str(json)
+        .map_err(|e| AgentError::decision(format!("Response does not match the schema: {}", e)))?;
+
+    if !(0.0..=1.0).contains(&raw.confidence) {
+        return Err(AgentError::decision(format!(
+            "confidence must be between 0 and 1, got {}",
+            raw.confidence
+        )));
+    }
+
+    let action = match raw.action {
+        RawAction::NoOp => AgentAction::NoOp,
+        RawAction::TransferSol(params) => {
+            if !params.amount_sol.is_finite() || params.amount_sol <= 0.0 {
+                return Err(AgentError::decision(format!(
+                    "params.amount_sol must be a positive number, got {}",
+                    params.amount_sol
+                )));
+            }
+            AgentAction::TransferSol {
+                to: parse_pubkey("params.to", &params.to)?,
+                amount: Lamports::from_sol_f64_rounded(params.amount_sol)
+                    .map_err(|e| AgentError::decision(format!("params.amount_sol: {}", e)))?,
+                memo: params.memo,
+            }
+        }
+        RawAction::TransferToken(params) => AgentAction::TransferToken {
+            mint: parse_pubkey("params.mint", &params.mint)?,
+            to: parse_pubkey("params.to", &params.to)?,
+            amount: positive("params.amount", params.amount)?,
+            memo: params.memo,
+        },
+        RawAction::SwapTokens(params) => AgentAction::SwapTokens {
+            input_mint: parse_pubkey("params.input_mint", &params.input_mint)?,
+            output_mint: parse_pubkey("params.output_mint", &params.output_mint)?,
+            amount: positive("params.amount", params.amount)?,
+            min_output_amount: params.min_output_amount,
+        },
+    };
+
+    Ok(AgentDecision::new(action)
+        .with_reasoning(raw.rationale)
+        .with_confidence(raw.confidence))
+}
+
+/// Action of a rejected response, as the model wrote it
+///
+/// Falls back to the whole JSON object, or the trimmed text when the
+/// response has no JSON at all.
+fn rejected_action(output: &str) -> Value {
+    match extract_json(output).and_then(|json| serde_json::from_str::<Value>(json).ok()) {
+        Some(Value::Object(mut object)) => object.remove("action").unwrap_or(Value::Object(object)),
+        Some(value) => value,
+        None => Value::String(output.trim().to_string()),
+    }
+}
+
+/// The outermost JSON object in a response
+fn extract_json(output: &str) -> Option<&str> {
+    let start = output.find('{')?;
+    let end = output.rfind('}')?;
+    (start < end).then(|| &output[start..=end])
+}
+
+fn parse_pubkey(field: &str, value: &str) -> Result<Pubkey> {
+    Pubkey::from_str(value)
+        .map_err(|_| AgentError::decision(format!("{} is not a valid address: '{}'", field, value)))
+}
+
+fn positive(field: &str, value: u64) -> Result<u64> {
+    if value == 0 {
+        return Err(AgentError::decision(format!("{} must be positive", field)));
+    }
+    Ok(value)
+}
+
+/// Agent that delegates decisions to a language model
+pub struct LlmAgent {
+    name: String,
+    provider: Arc<dyn LlmProvider>,
+    config: LlmConfig,
+    dead_letters: Option<DeadLetterQueue>,
+    last_rationale: Mutex<Option<String>>,
+}
+
+impl LlmAgent {
+    /// Create an agent backed by a provider
+    pub fn new(provider: Arc<dyn LlmProvider>, config: LlmConfig) -> Self {
+        Self {
+            name: "llm".to_string(),
+            provider,
+            config,
+            dead_letters: None,
+            last_rationale: Mutex::new(None),
+        }
+    }
+
+    /// Set the agent name
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Agent configuration
+    pub fn config(&self) -> &LlmConfig {
+        &self.config
+    }
+
+    /// Park actions that keep failing validation in `queue`
+    pub fn with_dead_letters(mut self, queue: DeadLetterQueue) -> Self {
+        self.dead_letters = Some(queue);
+        self
+    }
+
+    /// Dead-letter queue of the agent, if any
+    pub fn dead_letters(&self) -> Option<&DeadLetterQueue> {
+        self.dead_letters.as_ref()
+    }
+
+    /// Ask the model for a decision, re-prompting on invalid output
+    pub async fn decide_with_reasoning(&self, context: &AgentContext) -> Result<AgentDecision> {
+        let now = Utc::now();
+        let mut messages = self.prompt(context, now)?;
+        let attempts = self.config.max_attempts.max(1);
+        // Repeating an action within one tick counts as a single failure
+        let mut failed = HashSet::new();
+
+        for attempt in 1..=attempts {
+            let output = self.provider.complete(&messages, &self.config).await?;
+            match parse_decision(&output) {
+                Ok(decision) => {
+                    debug!(
+                        "{} decided after {} attempt(s): {}",
+                        self.name,
+                        attempt,
+                        decision.action.description()
+                    );
+                    return Ok(decision);
+                }
+                Err(e) => {
+                    warn!(
+                        "{} returned an invalid decision (attempt {}/{}): {}",
+                        self.name, attempt, attempts, e
+                    );
+                    if let Some(queue) = &self.dead_letters {
+                        let action = rejected_action(&output);
+                        let digest = action_digest(&action);
+                        if queue.is_parked(&digest, now) {
+                            return Err(AgentError::decision(format!(
+                                "{} proposed dead-lettered action {} again: {}",
+                                self.name, digest, e
+                            )));
+                        }
+                        if failed.insert(digest) {
+                            if let Some(letter) = queue.record_failure(&action, &issue(&e), now)? {
+                                return Err(AgentError::decision(format!(
+                                    "Action {} of {} dead-lettered after failing {} ticks: {}",
+                                    letter.digest, self.name, letter.failures, e
+                                )));
+                            }
+                        }
+                    }
+                    messages.push(LlmMessage::new(LlmRole::Assistant, output));
+                    messages.push(LlmMessage::new(
+                        LlmRole::User,
+                        format!(
+                            "Your response was rejected: {}. Reply again with only a JSON \
+                             object matching the schema.",
+                            e
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Err(AgentError::decision(format!(
+            "No valid decision from {} after {} attempts",
+            self.provider.name(),
+            attempts
+        )))
+    }
+
+    /// Initial conversation for a context
+    fn prompt(&self, context: &AgentContext, now: DateTime<Utc>) -> Result<Vec<LlmMessage>> {
+        let mut system = String::from(
+            "You are the decision engine of an autonomous Solana wallet. Respond with a \
+             single JSON object matching this schema and nothing else:\n",
+        );
+        system.push_str(DECISION_SCHEMA);
+        if let Some(extra) = &self.config.system_prompt {
+            system.push_str("\n\n");
+            system.push_str(extra);
+        }
+        if let Some(summary) = self
+            .dead_letters
+            .as_ref()
+            .and_then(|queue| queue.prompt_summary(now))
+        {
+            system.push_str("\n\n");
+            system.push_str(&summary);
+        }
+
+        let context = ContextSerializer::from_config(&self.config).serialize(context)?;
+        Ok(vec![
+            LlmMessage::new(LlmRole::System, system),
+            LlmMessage::new(LlmRole::User, format!("Current context:\n{}", context)),
+        ])
+    }
+}
+
+impl Agent for LlmAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn decide(&self, context: &AgentContext) -> Result<Option<AgentAction>> {
+        let decision = self.decide_with_reasoning(context).await?;
+        *lock(&self.last_rationale) = decision.reasoning;
+        match decision.action {
+            AgentAction::NoOp => Ok(None),
+            action => Ok(Some(action)),
+        }
+    }
+
+    async fn decide_cancellable(
+        &self,
+        context: &AgentContext,
+        cancel: CancellationToken,
+    ) -> Result<Option<AgentAction>> {
+        // Dropping the decision aborts the provider request in flight
+        tokio::select! {
+            decided = Agent::decide(self, context) => decided,
+            _ = cancel.cancelled() => Err(AgentError::decision(format!(
+                "Decision of {} was cancelled",
+                self.name
+            ))),
+        }
+    }
+
+    fn last_rationale(&self) -> Option<String> {
+        lock(&self.last_rationale).clone()
+    }
+}
+
+/// Provider replaying scripted responses, for tests
+#[derive(Debug, Default)]
+pub struct MockLlmProvider {
+    responses: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<Vec<LlmMessage>>>,
+}
+
+impl MockLlmProvider {
+    /// Create a provider returning `responses` in order
+    pub fn new(responses: Vec<String>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Conversations received so far
+    pub fn requests(&self) -> Vec<Vec<LlmMessage>> {
+        lock(&self.requests).clone()
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockLlmProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn complete(&self, messages: &[LlmMessage], _config: &LlmConfig) -> Result<String> {
+        lock(&self.requests).push(messages.to_vec());
+        lock(&self.responses)
+            .pop_front()
+            .ok_or_else(|| AgentError::decision("Mock provider has no responses left"))
+    }
+}
+
+/// Validation issue of a rejected response, without the error prefix
+fn issue(error: &AgentError) -> String {
+    match error {
+        AgentError::Decision(message) => message.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer_json(to: &str, amount_sol: f64) -> String {
+        format!(
+            r#"{{"action": {{"type": "transfer_sol", "params": {{"to": "{}", "amount_sol": {}}}}},
+                "confidence": 0.8, "rationale": "Rebalance"}}"#,
+            to, amount_sol
+        )
+    }
+
+    #[tokio::test]
+    async fn test_retries_after_malformed_output() -> Result<()> {
+        let recipient = Pubkey::new_unique();
+        let provider = Arc::new(MockLlmProvider::new(vec![
+            "Sure! {\"action\": {\"type\": \"transfer_sol\"".to_string(),
+            format!(
+                "```json\n{}\n```",
+                transfer_json(&recipient.to_string(), 0.5)
+            ),
+        ]));
+        let agent = LlmAgent::new(provider.clone(), LlmConfig::default());
+
+        let decision = agent
+            .decide_with_reasoning(&AgentContext::new(Pubkey::new_unique()))
+            .await?;
+        assert!(matches!(
+            decision.action,
+            AgentAction::TransferSol { to, amount: Lamports(500_000_000), .. } if to == recipient
+        ));
+        assert_eq!(decision.reasoning.as_deref(), Some("Rebalance"));
+        assert_eq!(decision.confidence, 0.8);
+
+        // The second request carries the rejected output and the error
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        let retry = &requests[1];
+        assert_eq!(retry.len(), 4);
+        assert_eq!(retry[2].role, LlmRole::Assistant);
+        assert!(retry[3].content.contains("rejected"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let provider = Arc::new(MockLlmProvider::new(vec![
+            transfer_json("not-a-pubkey", 0.5),
+            transfer_json(&Pubkey::new_unique().to_string(), -1.0),
+            transfer_json(&Pubkey::new_unique().to_string(), 1.0),
+        ]));
+        let config = LlmConfig {
+            max_attempts: 2,
+            ..Default::default()
+        };
+        let agent = LlmAgent::new(provider.clone(), config);
+
+        let result = agent.decide(&AgentContext::new(Pubkey::new_unique())).await;
+        assert!(result.is_err());
+        assert_eq!(provider.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_and_unknown_values() {
+        let key = Pubkey::new_unique().to_string();
+        let invalid = [
+            transfer_json(&key, 0.0),
+            transfer_json(&key, -0.5),
+            transfer_json("11111", 1.0),
+            r#"{"action": {"type": "no_op"}, "confidence": 1.5, "rationale": ""}"#.to_string(),
+            r#"{"action": {"type": "withdraw_all"}, "confidence": 1, "rationale": ""}"#.to_string(),
+            r#"{"action": {"type": "no_op"}, "confidence": 1, "rationale": "", "extra": 1}"#
+                .to_string(),
+            format!(
+                r#"{{"action": {{"type": "transfer_token", "params": {{"mint": "{0}", "to": "{0}", "amount": -5}}}},
+                    "confidence": 1, "rationale": ""}}"#,
+                key
+            ),
+        ];
+        for output in invalid {
+            assert!(parse_decision(&output).is_err(), "accepted: {}", output);
+        }
+
+        let no_op = r#"{"action": {"type": "no_op"}, "confidence": 1, "rationale": "Hold"}"#;
+        assert!(matches!(
+            parse_decision(no_op).map(|d| d.action),
+            Ok(AgentAction::NoOp)
+        ));
+    }
+
+    /// Context with a long history of memo-carrying transfers
+    fn bloated_context(memo: &str) -> AgentContext {
+        use agent_wallet_core::types::{AgentError as ErrorRecord, TransactionRecord};
+        use solana_sdk::signature::Signature;
+
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        let start = context.timestamp - chrono::Duration::hours(1_000);
+        for i in 0..500 {
+            context.transaction_history.push(TransactionRecord {
+                signature: Signature::new_unique(),
+                timestamp: start + chrono::Duration::hours(i),
+                action_type: "transfer_sol".to_string(),
+                amount: Some(1_000 * (i as u64 + 1)),
+                token_mint: None,
+                destination: Some(Pubkey::new_unique()),
+                status: TransactionStatus::Confirmed,
+                fee: 5_000,
+                memo: Some(format!("{} #{}", memo, i)),
+                attribution: None,
+            });
+        }
+        for i in 0..10 {
+            context.recent_errors.push(ErrorRecord {
+                message: "x".repeat(500),
+                error_type: "rpc".to_string(),
+                timestamp: start + chrono::Duration::hours(i),
+                context: "refresh".to_string(),
+                recoverable: true,
+            });
+        }
+        for i in 0..200 {
+            context.token_balances.insert(Pubkey::new_unique(), i);
+            context.price_feeds.insert(format!("TOKEN{}", i), i as f64);
+        }
+        context
+    }
+
+    #[test]
+    fn test_bloated_context_is_capped_and_redacted() -> Result<()> {
+        let memo = "payroll for alice@example.com";
+        let context = bloated_context(memo);
+        let policy = RedactionPolicy::for_prompts();
+        let parse = |json: &str| -> Result<Value> {
+            serde_json::from_str(json).map_err(|e| AgentError::decision(e.to_string()))
+        };
+        let full = ContextSerializer::new(usize::MAX, policy).serialize(&context)?;
+        assert!(full.len() > 4_096);
+
+        // Just over the cap, only the oldest listed transaction goes
+        let json = ContextSerializer::new(full.len() - 1, policy).serialize(&context)?;
+        let value = parse(&json)?;
+        assert_eq!(value["omitted"], serde_json::json!({ "transactions": 1 }));
+        let Some(recent) = value["history"]["recent"].as_array() else {
+            panic!("no recent transactions in {}", json);
+        };
+        assert_eq!(recent.len(), RECENT_TRANSACTIONS - 1);
+        assert_eq!(recent[0]["amount"], 492_000);
+        assert_eq!(recent[recent.len() - 1]["amount"], 500_000);
+
+        let json = ContextSerializer::new(4_096, policy).serialize(&context)?;
+        assert!(json.len() <= 4_096, "{} bytes", json.len());
+        assert!(!json.contains(memo));
+        assert!(!json.contains(&context.wallet_pubkey.to_string()));
+
+        // The history is summarized in full even when records were dropped
+        let value = parse(&json)?;
+        assert_eq!(value["history"]["count"], 500);
+        assert_eq!(value["history"]["fee_lamports"], 2_500_000);
+
+        // The prompt carries the same bytes
+        let config = LlmConfig {
+            max_context_bytes: 4_096,
+            ..Default::default()
+        };
+        let agent = LlmAgent::new(Arc::new(MockLlmProvider::default()), config);
+        let messages = agent.prompt(&context, Utc::now())?;
+        assert_eq!(messages[1].content, format!("Current context:\n{}", json));
+
+        // A cap nothing fits in is an error, not an oversized prompt
+        assert!(ContextSerializer::new(16, RedactionPolicy::for_prompts())
+            .serialize(&context)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_serialization_is_deterministic() -> Result<()> {
+        let context = bloated_context("memo");
+        let serializer = ContextSerializer::new(64 * 1024, RedactionPolicy::none());
+
+        // Rebuilding the hash maps changes their iteration order, not the output
+        let mut rebuilt = context.clone();
+        rebuilt.token_balances = context
+            .token_balances
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        rebuilt.price_feeds = context.price_feeds.clone().into_iter().collect();
+        rebuilt.transaction_history.reverse();
+        let json = serializer.serialize(&context)?;
+        assert_eq!(json, serializer.serialize(&rebuilt)?);
+
+        // Without redaction, memos and full addresses are kept
+        assert!(json.contains("memo #499"));
+        assert!(json.contains(&context.wallet_pubkey.to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_repeating_invalid_action_is_dead_lettered() -> Result<()> {
+        use crate::dead_letter::DeadLetterConfig;
+
+        let invalid = transfer_json("not-a-pubkey", 0.5);
+        let valid = r#"{"action": {"type": "no_op"}, "confidence": 1, "rationale": "Hold"}"#;
+        let provider = Arc::new(MockLlmProvider::new(vec![
+            invalid.clone(),
+            invalid.clone(),
+            invalid.clone(),
+            invalid,
+            valid.to_string(),
+        ]));
+        let queue = DeadLetterQueue::new(DeadLetterConfig {
+            threshold: 2,
+            ..DeadLetterConfig::default()
+        });
+        let config = LlmConfig {
+            max_attempts: 2,
+            ..Default::default()
+        };
+        let agent = LlmAgent::new(provider.clone(), config).with_dead_letters(queue.clone());
+        let context = AgentContext::new(Pubkey::new_unique());
+
+        // First tick: one failure, repaired once, still invalid
+        assert!(agent.decide(&context).await.is_err());
+        assert_eq!(provider.requests().len(), 2);
+        assert!(queue.list(Utc::now()).is_empty());
+
+        // Second tick: the second failing tick parks the action without repair
+        assert!(agent.decide(&context).await.is_err());
+        assert_eq!(provider.requests().len(), 3);
+        let parked = queue.list(Utc::now());
+        assert_eq!(parked.len(), 1);
+        assert!(parked[0].issues[0].contains("not a valid address"));
+
+        // Third tick: the prompt lists the parked action and it is not repaired
+        assert!(agent.decide(&context).await.is_err());
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[3][0].content.contains("not-a-pubkey"));
+
+        // Operator clears the queue; the prompt no longer mentions the action
+        assert_eq!(queue.clear(None)?, 1);
+        assert!(agent.decide(&context).await?.is_none());
+        let requests = provider.requests();
+        assert!(!requests[4][0].content.contains("not-a-pubkey"));
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+//! Notifications of agent events
+//!
+//! An [`AgentRunner`](crate::runner::AgentRunner) reports what its agent does
+//! through a [`Notifier`]: executed decisions, actions refused by a spending
+//! limit, failures, confirmation timeouts, a wallet balance falling below
+//! its alert threshold, top-ups no funding wallet could pay for and wallet
+//! keys due for rotation. An [`ApprovalGate`](crate::approval::ApprovalGate)
+//! also sends its approvers an `approval_requested` event for each action
+//! it holds back. The default [`NoopNotifier`] drops every event.
+//!
+//! [`WebhookNotifier`] POSTs each event as JSON to the URLs configured under
+//! `monitoring.webhooks`, e.g. Slack or Discord relays. Deliveries run in the
+//! background and are retried with exponential backoff; a failed delivery is
+//! logged and never holds up or fails the action it reports. With a shared
+//! secret configured, every request carries the hex HMAC-SHA256 of its body
+//! in [`SIGNATURE_HEADER`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_agent::notify::WebhookNotifier;
+//! use agent_wallet_agent::AgentRunner;
+//! use agent_wallet_core::config::WebhookSettings;
+//!
+//! # fn example(runner: AgentRunner) -> agent_wallet_agent::Result<()> {
+//! let settings = WebhookSettings {
+//!     urls: vec!["https://hooks.example.com/agent-wallet".to_string()],
+//!     secret: Some("shared-secret".to_string()),
+//!     balance_threshold_sol: Some(0.5),
+//!     ..Default::default()
+//! };
+//! let runner = runner
+//!     .with_notifier(Arc::new(WebhookNotifier::new(&settings)?))
+//!     .with_balance_alert(0.5);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_wallet_core::config::{WebhookEvents, WebhookSettings};
+use agent_wallet_core::retry::backoff_delay;
+use agent_wallet_core::{Error as CoreError, TransferPreview};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, warn};
+
+use crate::agent::AgentId;
+use crate::error::Result;
+
+/// Header carrying the hex HMAC-SHA256 of the request body
+pub const SIGNATURE_HEADER: &str = "X-Agent-Wallet-Signature";
+
+/// Something an agent did that operators may want to hear about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// An action was executed, or rehearsed in a dry run
+    DecisionExecuted {
+        /// Agent that decided
+        agent_id: AgentId,
+        /// Description of the action
+        action: String,
+        /// Signature of the transaction
+        signature: String,
+        /// Whether the action was only simulated
+        dry_run: bool,
+    },
+    /// An action was refused by a spending limit
+    SpendingLimitHit {
+        /// Agent whose action was refused
+        agent_id: AgentId,
+        /// Limit that refused it
+        reason: String,
+    },
+    /// The agent failed to decide or its action failed
+    AgentErrored {
+        /// Agent that failed
+        agent_id: AgentId,
+        /// What went wrong
+        error: String,
+    },
+    /// A sent transaction did not reach its commitment in time
+    ConfirmationTimeout {
+        /// Agent that sent it
+        agent_id: AgentId,
+        /// Signature of the transaction, which may still land
+        signature: String,
+    },
+    /// The wallet balance fell below the alert threshold
+    BalanceBelowThreshold {
+        /// Agent operating the wallet
+        agent_id: AgentId,
+        /// Current balance in SOL
+        balance_sol: f64,
+        /// Configured threshold in SOL
+        threshold_sol: f64,
+    },
+    /// The wallet fell below its SOL reserve and no funding wallet refills it
+    TopUpRequested {
+        /// Agent operating the wallet
+        agent_id: AgentId,
+        /// Lamports that restore the wallet to its top-up target
+        amount_lamports: u64,
+        /// Current balance in SOL
+        balance_sol: f64,
+        /// Minimum reserve in SOL
+        reserve_sol: f64,
+    },
+    /// The wallet key crossed a threshold of its rotation policy
+    KeyRotationNeeded {
+        /// Agent operating the wallet
+        agent_id: AgentId,
+        /// Threshold that was crossed
+        reason: String,
+    },
+    /// An action waits for a human to approve it
+    ApprovalRequested {
+        /// Agent that decided on the action
+        agent_id: AgentId,
+        /// Description of the action
+        action: String,
+        /// SOL the action puts at stake, if it could be valued
+        value_sol: Option<f64>,
+        /// Balance changes the action's simulation predicted
+        preview: Option<TransferPreview>,
+        /// Token approving or rejecting the action
+        token: String,
+        /// When the request resolves on its own
+        expires_at: DateTime<Utc>,
+    },
+}
+
+impl AgentEvent {
+    /// Whether `events` switches this kind of event on
+    pub fn is_enabled(&self, events: &WebhookEvents) -> bool {
+        match self {
+            Self::DecisionExecuted { .. } => events.decision_executed,
+            Self::SpendingLimitHit { .. } => events.spending_limit_hit,
+            Self::AgentErrored { .. } => events.agent_errored,
+            Self::ConfirmationTimeout { .. } => events.confirmation_timeout,
+            Self::BalanceBelowThreshold { .. } => events.balance_below_threshold,
+            Self::TopUpRequested { .. } => events.top_up_requested,
+            Self::KeyRotationNeeded { .. } => events.key_rotation_needed,
+            Self::ApprovalRequested { .. } => events.approval_requested,
+        }
+    }
+}
+
+/// An event with the wallet it concerns, as delivered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    /// What happened
+    #[serde(flatten)]
+    pub event: AgentEvent,
+    /// Wallet the agent operates, base58
+    pub wallet: String,
+    /// When the event happened
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Notification {
+    /// Notification of `event` in `wallet`, happening now
+    pub fn new(event: AgentEvent, wallet: &Pubkey) -> Self {
+        Self {
+            event,
+            wallet: wallet.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Receiver of agent events
+///
+/// `notify` is called on the agent's decision path, so implementations hand
+/// the notification off and return without waiting for delivery.
+pub trait Notifier: Send + Sync {
+    /// Hand a notification over for delivery
+    fn notify(&self, notification: Notification);
+}
+
+/// Notifier dropping every event
+pub struct NoopNotifier;
+
+impl Notifier for NoopNotifier {
+    fn notify(&self, _notification: Notification) {}
+}
+
+/// Notifier POSTing events to webhook URLs
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    inner: Arc<WebhookTarget>,
+}
+
+struct WebhookTarget {
+    client: reqwest::Client,
+    settings: WebhookSettings,
+}
+
+impl WebhookNotifier {
+    /// Notifier delivering to the URLs in `settings`
+    pub fn new(settings: &WebhookSettings) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(settings.timeout_seconds))
+            .build()
+            .map_err(|e| CoreError::config(format!("Failed to build webhook client: {}", e)))?;
+        Ok(Self {
+            inner: Arc::new(WebhookTarget {
+                client,
+                settings: settings.clone(),
+            }),
+        })
+    }
+
+    /// Deliver a notification to every URL, retrying failed attempts
+    ///
+    /// Fails if any URL still refused it after the last attempt.
+    pub async fn deliver(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::to_vec(notification).map_err(CoreError::from)?;
+        let signature = self
+            .inner
+            .settings
+            .secret
+            .as_ref()
+            .map(|secret| sign(secret.as_bytes(), &body));
+
+        let mut failed = Vec::new();
+        for url in &self.inner.settings.urls {
+            if let Err(e) = self.deliver_to(url, &body, signature.as_deref()).await {
+                failed.push(format!("{}: {}", url, e));
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(
+                CoreError::Network(format!("Webhook delivery failed: {}", failed.join("; ")))
+                    .into(),
+            )
+        }
+    }
+
+    /// POST `body` to one URL, retrying with exponential backoff
+    async fn deliver_to(
+        &self,
+        url: &str,
+        body: &[u8],
+        signature: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let settings = &self.inner.settings;
+        let attempts = settings.max_attempts.max(1);
+        let mut last_error = String::new();
+        for attempt in 0..attempts {
+            if attempt > 0 {
+                let max_ms = settings.backoff_base_ms.saturating_mul(8);
+                tokio::time::sleep(backoff_delay(
+                    attempt - 1,
+                    settings.backoff_base_ms,
+                    max_ms,
+                    rand::random(),
+                ))
+                .await;
+            }
+
+            let mut request = self
+                .inner
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_vec());
+            if let Some(signature) = signature {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+            match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => last_error = format!("HTTP {}", response.status()),
+                Err(e) => last_error = e.to_string(),
+            }
+            debug!(
+                "Webhook attempt {} of {} to {} failed: {}",
+                attempt + 1,
+                attempts,
+                url,
+                last_error
+            );
+        }
+        Err(last_error)
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, notification: Notification) {
+        if !notification.event.is_enabled(&self.inner.settings.events)
+            || self.inner.settings.urls.is_empty()
+        {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("Dropped webhook notification: no async runtime");
+            return;
+        };
+        let notifier = self.clone();
+        runtime.spawn(async move {
+            if let Err(e) = notifier.deliver(&notification).await {
+                warn!("{}", e);
+            }
+        });
+    }
+}
+
+/// Hex HMAC-SHA256 of `body` under `secret`
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret) {
+        Ok(mac) => mac,
+        Err(_) => return String::new(),
+    };
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use wiremock::matchers::{header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn settings(server: &MockServer) -> WebhookSettings {
+        WebhookSettings {
+            urls: vec![format!("{}/hook", server.uri())],
+            secret: Some("shared-secret".to_string()),
+            backoff_base_ms: 1,
+            ..Default::default()
+        }
+    }
+
+    fn executed() -> Notification {
+        Notification::new(
+            AgentEvent::DecisionExecuted {
+                agent_id: "trader-1".to_string(),
+                action: "Transfer 0.1 SOL".to_string(),
+                signature: "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb".to_string(),
+                dry_run: false,
+            },
+            &Pubkey::new_unique(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_webhook_payload_is_signed() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header_exists(SIGNATURE_HEADER))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let notifier = WebhookNotifier::new(&settings(&server))?;
+        let notification = executed();
+        notifier.deliver(&notification).await?;
+
+        let requests = server.received_requests().await.unwrap_or_default();
+        let [request] = requests.as_slice() else {
+            return Err(CoreError::validation(format!("{} requests", requests.len())).into());
+        };
+        let payload: serde_json::Value =
+            serde_json::from_slice(&request.body).map_err(CoreError::from)?;
+        assert_eq!(payload["event"], "decision_executed");
+        assert_eq!(payload["agent_id"], "trader-1");
+        assert_eq!(payload["wallet"], notification.wallet);
+        assert!(payload["timestamp"].is_string());
+        let signature = request
+            .headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok());
+        assert_eq!(
+            signature,
+            Some(sign(b"shared-secret", &request.body).as_str())
+        );
+        assert_ne!(
+            sign(b"other-secret", &request.body),
+            sign(b"shared-secret", &request.body)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried_and_never_blocks() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let notifier = WebhookNotifier::new(&settings(&server))?;
+        assert!(notifier.deliver(&executed()).await.is_err());
+
+        // notify hands off even when the endpoint answers slowly
+        let slow = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&slow)
+            .await;
+        let notifier = WebhookNotifier::new(&settings(&slow))?;
+        let started = Instant::now();
+        notifier.notify(executed());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disabled_events_are_not_sent() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let mut settings = settings(&server);
+        settings.events.decision_executed = false;
+        let notifier = WebhookNotifier::new(&settings)?;
+        notifier.notify(executed());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let limit = AgentEvent::SpendingLimitHit {
+            agent_id: "trader-1".to_string(),
+            reason: "daily limit".to_string(),
+        };
+        assert!(limit.is_enabled(&settings.events));
+        assert!(!executed().event.is_enabled(&settings.events));
+        Ok(())
+    }
+}
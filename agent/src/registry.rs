@@ -0,0 +1,456 @@
+//! Registry of agents running outside the current process
+//!
+//! Agents started in daemon mode record themselves in an [`AgentRegistry`]:
+//! one JSON entry per agent under `<dir>/`, holding the agent type, wallet,
+//! strategy, process id, the counters the CLI reports and the agent's
+//! [`AgentStats`]. A running agent
+//! refreshes its entry's heartbeat after every tick; an entry that claims to
+//! be active but has not been refreshed within the registry's staleness
+//! threshold is reported as [`AgentStatus::Error`], since its process most
+//! likely died without cleaning up.
+//!
+//! Each agent only ever writes its own entry, so heartbeats of concurrent
+//! daemons never contend for the same file. Stopping is cooperative:
+//! [`AgentRegistry::request_stop`] drops a signal file next to the entry,
+//! which the agent polls between ticks.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::registry::{AgentRegistry, RegistryEntry};
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let registry = AgentRegistry::open("/home/me/.config/agent-wallet/agents")?;
+//! registry.register(RegistryEntry::new("trader-1", "deterministic", "main"))?;
+//! registry.heartbeat("trader-1", 1, None)?;
+//!
+//! for entry in registry.list()? {
+//!     println!("{} {:?}", entry.id, registry.status_of(&entry, chrono::Utc::now()));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use agent_wallet_core::{AgentStats, Error as CoreError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::{AgentId, AgentStatus};
+use crate::error::{AgentError, Result};
+use crate::workspace::{io_error, validate_agent_id};
+
+/// Default time without a heartbeat after which an active entry is stale
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Extension of registry entries
+const ENTRY_EXTENSION: &str = "json";
+
+/// Extension of stop signal files
+const STOP_EXTENSION: &str = "stop";
+
+/// Registry record of one agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    /// Agent identifier
+    pub id: AgentId,
+    /// Agent type the agent was started as
+    pub agent_type: String,
+    /// Name of the wallet the agent trades with
+    pub wallet: String,
+    /// Strategy configuration the agent was started with
+    #[serde(default)]
+    pub strategy: Option<Value>,
+    /// Process running the agent, if known
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// When the agent was started
+    pub started_at: DateTime<Utc>,
+    /// When the agent last reported in
+    pub last_heartbeat: DateTime<Utc>,
+    /// Status the agent last reported
+    pub status: AgentStatus,
+    /// Decisions made since the agent started
+    #[serde(default)]
+    pub decisions: u64,
+    /// Most recent error, if any
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Outcome statistics of the agent's decisions
+    #[serde(default)]
+    pub stats: AgentStats,
+    /// Whether the agent only rehearses its actions
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl RegistryEntry {
+    /// Entry of an agent starting now in the current process
+    pub fn new(
+        id: impl Into<AgentId>,
+        agent_type: impl Into<String>,
+        wallet: impl Into<String>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: id.into(),
+            agent_type: agent_type.into(),
+            wallet: wallet.into(),
+            strategy: None,
+            pid: Some(std::process::id()),
+            started_at: now,
+            last_heartbeat: now,
+            status: AgentStatus::Active,
+            decisions: 0,
+            last_error: None,
+            stats: AgentStats::new(),
+            dry_run: false,
+        }
+    }
+
+    /// Record the strategy configuration
+    pub fn with_strategy(mut self, strategy: Value) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Record that the agent runs dry, sending nothing
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Record the process running the agent
+    pub fn with_pid(mut self, pid: u32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Whether an active entry missed its heartbeat by more than `stale_after`
+    pub fn is_stale(&self, now: DateTime<Utc>, stale_after: Duration) -> bool {
+        let stale_after = chrono::Duration::from_std(stale_after).unwrap_or(chrono::Duration::MAX);
+        matches!(self.status, AgentStatus::Active | AgentStatus::Paused)
+            && now.signed_duration_since(self.last_heartbeat) > stale_after
+    }
+}
+
+/// Directory of registry entries
+#[derive(Debug, Clone)]
+pub struct AgentRegistry {
+    dir: PathBuf,
+    stale_after: Duration,
+}
+
+impl AgentRegistry {
+    /// Open the registry kept in `dir`, creating it if needed
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| io_error("create agent registry", &dir, e))?;
+        Ok(Self {
+            dir,
+            stale_after: DEFAULT_STALE_AFTER,
+        })
+    }
+
+    /// Treat active entries as stale after `stale_after` without a heartbeat
+    pub fn with_stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Directory holding the entries
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Status to report for an entry at `now`
+    pub fn status_of(&self, entry: &RegistryEntry, now: DateTime<Utc>) -> AgentStatus {
+        if entry.is_stale(now, self.stale_after) {
+            AgentStatus::Error
+        } else {
+            entry.status
+        }
+    }
+
+    /// Record a starting agent
+    ///
+    /// Fails if an agent with the same id is still running.
+    pub fn register(&self, entry: RegistryEntry) -> Result<()> {
+        if let Some(existing) = self.get(&entry.id)? {
+            if self.status_of(&existing, Utc::now()) == AgentStatus::Active {
+                return Err(AgentError::invalid_state(format!(
+                    "Agent {} is already running",
+                    entry.id
+                )));
+            }
+        }
+        self.clear_stop(&entry.id)?;
+        self.write(&entry)
+    }
+
+    /// Entry of an agent, if registered
+    pub fn get(&self, id: &str) -> Result<Option<RegistryEntry>> {
+        let path = self.entry_path(id)?;
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(
+                serde_json::from_slice(&bytes).map_err(CoreError::from)?,
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error("read registry entry", &path, e)),
+        }
+    }
+
+    /// All registered agents, sorted by id
+    pub fn list(&self) -> Result<Vec<RegistryEntry>> {
+        let files =
+            fs::read_dir(&self.dir).map_err(|e| io_error("list agent registry", &self.dir, e))?;
+        let mut entries = Vec::new();
+        for file in files {
+            let path = file
+                .map_err(|e| io_error("list agent registry", &self.dir, e))?
+                .path();
+            if path.extension().and_then(|e| e.to_str()) != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if let Some(entry) = self.get(id)? {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(entries)
+    }
+
+    /// Refresh an agent's heartbeat and counters
+    pub fn heartbeat(&self, id: &str, decisions: u64, last_error: Option<String>) -> Result<()> {
+        self.update(id, |entry| {
+            entry.last_heartbeat = Utc::now();
+            entry.decisions = decisions;
+            if last_error.is_some() {
+                entry.last_error = last_error;
+            }
+        })
+    }
+
+    /// Store an agent's latest statistics
+    pub fn record_stats(&self, id: &str, stats: &AgentStats) -> Result<()> {
+        self.update(id, |entry| entry.stats = stats.clone())
+    }
+
+    /// Record the status an agent moved to
+    pub fn set_status(&self, id: &str, status: AgentStatus) -> Result<()> {
+        self.update(id, |entry| {
+            entry.status = status;
+            entry.last_heartbeat = Utc::now();
+        })
+    }
+
+    /// Ask a running agent to stop and mark its entry stopped
+    ///
+    /// Returns `false` if the agent is not registered.
+    pub fn request_stop(&self, id: &str) -> Result<bool> {
+        if self.get(id)?.is_none() {
+            return Ok(false);
+        }
+        let path = self.stop_path(id)?;
+        fs::write(&path, Utc::now().to_rfc3339())
+            .map_err(|e| io_error("write stop signal", &path, e))?;
+        self.update(id, |entry| entry.status = AgentStatus::Stopped)?;
+        Ok(true)
+    }
+
+    /// Whether a stop was requested for the agent
+    pub fn stop_requested(&self, id: &str) -> Result<bool> {
+        Ok(self.stop_path(id)?.exists())
+    }
+
+    /// Record that an agent exited, consuming any stop signal
+    pub fn mark_stopped(&self, id: &str, error: Option<String>) -> Result<()> {
+        self.clear_stop(id)?;
+        self.update(id, |entry| {
+            entry.status = if error.is_some() {
+                AgentStatus::Error
+            } else {
+                AgentStatus::Stopped
+            };
+            entry.last_heartbeat = Utc::now();
+            if error.is_some() {
+                entry.last_error = error;
+            }
+        })
+    }
+
+    /// Forget an agent, returning whether it was registered
+    pub fn remove(&self, id: &str) -> Result<bool> {
+        self.clear_stop(id)?;
+        let path = self.entry_path(id)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(io_error("remove registry entry", &path, e)),
+        }
+    }
+
+    /// Read, modify and write back an agent's entry
+    fn update(&self, id: &str, change: impl FnOnce(&mut RegistryEntry)) -> Result<()> {
+        let mut entry = self
+            .get(id)?
+            .ok_or_else(|| AgentError::invalid_state(format!("Agent {} is not registered", id)))?;
+        change(&mut entry);
+        self.write(&entry)
+    }
+
+    /// Atomically write an entry
+    fn write(&self, entry: &RegistryEntry) -> Result<()> {
+        let path = self.entry_path(&entry.id)?;
+        let bytes = serde_json::to_vec_pretty(entry).map_err(CoreError::from)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, bytes).map_err(|e| io_error("write registry entry", &tmp, e))?;
+        fs::rename(&tmp, &path).map_err(|e| io_error("replace registry entry", &path, e))
+    }
+
+    fn clear_stop(&self, id: &str) -> Result<()> {
+        let path = self.stop_path(id)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error("remove stop signal", &path, e)),
+        }
+    }
+
+    fn entry_path(&self, id: &str) -> Result<PathBuf> {
+        validate_agent_id(id)?;
+        Ok(self.dir.join(format!("{}.{}", id, ENTRY_EXTENSION)))
+    }
+
+    fn stop_path(&self, id: &str) -> Result<PathBuf> {
+        validate_agent_id(id)?;
+        Ok(self.dir.join(format!("{}.{}", id, STOP_EXTENSION)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_wallet_core::sol::Lamports;
+    use agent_wallet_core::types::AgentAction;
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_entries_round_trip() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let registry = AgentRegistry::open(dir.path())?;
+
+        let entry = RegistryEntry::new("trader-1", "deterministic", "main")
+            .with_strategy(json!({"interval_seconds": 60}));
+        registry.register(entry.clone())?;
+        registry.register(RegistryEntry::new("sweeper", "deterministic", "treasury"))?;
+
+        assert_eq!(registry.get("trader-1")?, Some(entry));
+        let ids: Vec<_> = registry.list()?.into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec!["sweeper".to_string(), "trader-1".to_string()]);
+
+        registry.heartbeat("trader-1", 7, Some("rpc timeout".to_string()))?;
+        registry.heartbeat("trader-1", 8, None)?;
+        let updated = registry
+            .get("trader-1")?
+            .ok_or_else(|| AgentError::invalid_state("entry missing"))?;
+        assert_eq!(updated.decisions, 8);
+        assert_eq!(updated.last_error.as_deref(), Some("rpc timeout"));
+
+        assert!(registry.remove("sweeper")?);
+        assert!(!registry.remove("sweeper")?);
+        assert_eq!(registry.list()?.len(), 1);
+        assert!(registry.heartbeat("sweeper", 1, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_persist_with_the_entry() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let registry = AgentRegistry::open(dir.path())?;
+        registry.register(RegistryEntry::new("trader-1", "deterministic", "main"))?;
+
+        let mut stats = AgentStats::new();
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        stats.record_failed(&action, "Insufficient funds", Utc::now());
+        stats.record_no_action();
+        registry.record_stats("trader-1", &stats)?;
+
+        let entry = registry
+            .get("trader-1")?
+            .ok_or_else(|| AgentError::invalid_state("entry missing"))?;
+        assert_eq!(entry.stats, stats);
+        assert_eq!(entry.stats.totals.failed, 1);
+        assert!(registry.record_stats("sweeper", &stats).is_err());
+
+        // Entries written before statistics existed still load
+        let mut legacy = serde_json::to_value(&entry).map_err(CoreError::from)?;
+        if let Some(fields) = legacy.as_object_mut() {
+            fields.remove("stats");
+        }
+        let legacy: RegistryEntry = serde_json::from_value(legacy).map_err(CoreError::from)?;
+        assert_eq!(legacy.stats, AgentStats::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missed_heartbeat_reports_error() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let registry = AgentRegistry::open(dir.path())?.with_stale_after(Duration::from_secs(30));
+
+        let mut entry = RegistryEntry::new("trader-1", "deterministic", "main");
+        let now = entry.last_heartbeat;
+        assert_eq!(registry.status_of(&entry, now), AgentStatus::Active);
+
+        let later = now + chrono::Duration::seconds(31);
+        assert_eq!(registry.status_of(&entry, later), AgentStatus::Error);
+
+        // A stopped agent is not expected to report in
+        entry.status = AgentStatus::Stopped;
+        assert_eq!(registry.status_of(&entry, later), AgentStatus::Stopped);
+
+        // A crashed agent's id can be reused once its heartbeat is stale
+        let mut crashed = RegistryEntry::new("trader-2", "deterministic", "main");
+        registry.register(crashed.clone())?;
+        assert!(registry.register(crashed.clone()).is_err());
+        crashed.last_heartbeat = Utc::now() - chrono::Duration::seconds(60);
+        registry.write(&crashed)?;
+        registry.register(RegistryEntry::new("trader-2", "deterministic", "main"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_signal() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let registry = AgentRegistry::open(dir.path())?;
+
+        assert!(!registry.request_stop("trader-1")?);
+        registry.register(RegistryEntry::new("trader-1", "deterministic", "main"))?;
+        assert!(!registry.stop_requested("trader-1")?);
+
+        assert!(registry.request_stop("trader-1")?);
+        assert!(registry.stop_requested("trader-1")?);
+        let entry = registry
+            .get("trader-1")?
+            .ok_or_else(|| AgentError::invalid_state("entry missing"))?;
+        assert_eq!(entry.status, AgentStatus::Stopped);
+
+        registry.mark_stopped("trader-1", None)?;
+        assert!(!registry.stop_requested("trader-1")?);
+        assert!(registry.get("../escape").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1607 @@
+//! Agent runner
+//!
+//! The [`AgentRunner`] drives one agent against one wallet: it asks the agent
+//! for a decision on each tick, runs it through the sandbox and executes the
+//! resulting action. When the agent starts or is reconfigured, the runner
+//! prepares the agent's action templates in the wallet so templated
+//! decisions skip rebuilding their transaction.
+//!
+//! Ticks can also be triggered by external events: [`AgentRunner::enable_triggers`]
+//! returns a [`TriggerHandle`] whose payloads are handed to the agent through
+//! the context. Every tick is recorded in the runner's decision log, with
+//! triggered ticks marked by their payload.
+//!
+//! Executed actions are rate limited: a decision arriving while the runner's
+//! [`RateLimit`] is used up fails with `RateLimitExceeded` instead of being
+//! executed, and [`AgentRunner::wait_for_rate_limit`] sleeps until the next
+//! action is allowed.
+//!
+//! A runner given an [`AgentWorkspace`] snapshots the context of its latest
+//! decision into the workspace.
+//!
+//! Agents that repair invalid output share a [`DeadLetterQueue`] with their
+//! runner. The runner releases parked actions once their cool-off has passed
+//! and exposes the queue so operators can list and clear it.
+//!
+//! A runner given an [`AuditSink`] writes an intent record for every action
+//! before executing it and an outcome record afterwards, attributed to the
+//! agent. An action whose intent cannot be recorded is not executed.
+//!
+//! Every outcome also feeds the runner's [`AgentStats`]: executed and failed
+//! actions by kind, the SOL and fees spent and the latest outcomes with their
+//! signatures. A summary of the statistics is handed to the agent in its
+//! context, so strategies can back off after a run of failures.
+//!
+//! A decision the sandbox cancels for exceeding its timeout is recorded as
+//! [`DecisionOutcome::TimedOut`] and the tick returns, so a hung agent never
+//! blocks the runner. Timeouts count towards the sandbox's violation limit.
+//!
+//! A runner in [`RunMode::DryRun`] only starts against a wallet opened in
+//! dry-run mode. Its decisions go through the whole pipeline up to and
+//! including simulation, are recorded as [`DecisionOutcome::Simulated`] and
+//! never reach the network.
+//!
+//! A runner given a [`Notifier`] reports executed decisions, actions refused
+//! by a spending limit, failures and confirmation timeouts to it, and with
+//! [`AgentRunner::with_balance_alert`] also the wallet balance falling below a
+//! threshold. Notifiers deliver in the background, so a slow or failing
+//! webhook never holds up the agent.
+//!
+//! A runner given a [`TopUp`] refills the wallet once it falls below its
+//! minimum SOL reserve: from the funding wallet if there is one, otherwise by
+//! requesting the transfer through the notifier.
+//!
+//! A runner given an [`AgentStateStore`] restores the agent's state when it
+//! starts and saves it right after each decision, before the action is
+//! executed, so a restart never repeats an action the agent already
+//! decided on. State that cannot be restored is logged and the agent starts
+//! fresh.
+//!
+//! Each tick records the wallet balance, the daily budget left, the
+//! decision's outcome and its latency in [`Metrics::global`], or the
+//! metrics given to [`AgentRunner::with_metrics`].
+//!
+//! Applications embedding the runner follow it through
+//! [`AgentRunner::subscribe`], which streams a typed [`RunnerEvent`] for each
+//! step of every tick and each status change, in dry runs too. See
+//! [`events`](crate::events) for the order they come in.
+//!
+//! A runner given a shutdown token with [`AgentRunner::with_shutdown`] stops
+//! taking ticks once it is cancelled. A tick waiting for its transaction to
+//! confirm stops waiting and records it as unconfirmed; [`AgentRunner::shutdown`]
+//! then waits a bounded time for such transactions to resolve, records their
+//! outcome and flushes the audit sink and state store before the runner
+//! stops.
+//!
+//! A runner given an [`ApprovalGate`] holds back actions worth more than
+//! the gate's threshold: the tick records [`DecisionOutcome::AwaitingApproval`]
+//! and the agent keeps ticking. The action is executed, or dropped, on the
+//! first tick after a human answers the request or it times out.
+//!
+//! While started, a runner whose wallet has a websocket configured keeps the
+//! context's SOL balance current from account notifications instead of
+//! waiting for the balance to be polled again.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use agent_wallet_core::audit::{AuditEntry, AuditOutcome, AuditSink};
+use agent_wallet_core::memo::MemoAttribution;
+use agent_wallet_core::metrics::Metrics;
+use agent_wallet_core::shutdown::ShutdownSignal;
+use agent_wallet_core::transaction::TransactionOptions;
+use agent_wallet_core::{AgentContext, AgentStats, Error as CoreError, Lamports, RunMode, Wallet};
+use chrono::Utc;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn, Instrument};
+use uuid::Uuid;
+
+use crate::agent::{AgentId, AgentStatus, CancellationToken, DynAgent};
+use crate::approval::{self, ApprovalGate, ApprovalRequest, ApprovalStatus};
+use crate::audit::AuditLog;
+use crate::dead_letter::DeadLetterQueue;
+use crate::decision::{AgentAction, DecisionOutcome, DecisionRecord};
+use crate::error::{AgentError, Result};
+use crate::events::{ContextSummary, RunnerEvent, RUNNER_EVENT_CAPACITY};
+use crate::limits::{RateLimit, RateLimitState, RateLimiter, TopUp};
+use crate::notify::{AgentEvent, NoopNotifier, Notification, Notifier};
+use crate::sandbox::Sandbox;
+use crate::state::AgentStateStore;
+use crate::trigger::{self, TriggerConfig, TriggerHandle, TriggerPayload, TriggerReceiver};
+use crate::workspace::{AgentWorkspace, ArtifactKind};
+
+/// Number of decisions kept in the runner's log
+const DECISION_LOG_CAPACITY: usize = 256;
+
+/// Sent transaction whose confirmation is still unknown
+struct PendingConfirmation {
+    signature: Signature,
+    intent: Option<AuditEntry>,
+}
+
+/// Runs an agent's decisions against a wallet
+pub struct AgentRunner {
+    id: AgentId,
+    agent: Arc<dyn DynAgent>,
+    wallet: Arc<Wallet>,
+    sandbox: Sandbox,
+    options: TransactionOptions,
+    status: AgentStatus,
+    triggers: Option<TriggerReceiver>,
+    decision_log: VecDeque<DecisionRecord>,
+    workspace: Option<AgentWorkspace>,
+    rate_limiter: RateLimiter,
+    audit: Option<AuditLog>,
+    dead_letters: Option<DeadLetterQueue>,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    stats: AgentStats,
+    mode: RunMode,
+    notifier: Arc<dyn Notifier>,
+    balance_threshold_sol: Option<f64>,
+    below_threshold: bool,
+    top_up: Option<TopUp>,
+    topping_up: bool,
+    rotation_due: bool,
+    metrics: Metrics,
+    state_store: Option<Arc<dyn AgentStateStore>>,
+    events: broadcast::Sender<RunnerEvent>,
+    shutdown: CancellationToken,
+    unconfirmed: Vec<PendingConfirmation>,
+    approval_gate: Option<ApprovalGate>,
+    balance_watch: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl AgentRunner {
+    /// Create a stopped runner
+    pub fn new(id: impl Into<AgentId>, agent: Arc<dyn DynAgent>, wallet: Arc<Wallet>) -> Self {
+        Self {
+            id: id.into(),
+            agent,
+            wallet,
+            sandbox: Sandbox::default(),
+            options: TransactionOptions::default(),
+            status: AgentStatus::Stopped,
+            triggers: None,
+            decision_log: VecDeque::new(),
+            workspace: None,
+            rate_limiter: RateLimit::default().limiter(),
+            audit: None,
+            dead_letters: None,
+            audit_sink: None,
+            stats: AgentStats::new(),
+            mode: RunMode::Live,
+            notifier: Arc::new(NoopNotifier),
+            balance_threshold_sol: None,
+            below_threshold: false,
+            top_up: None,
+            topping_up: false,
+            rotation_due: false,
+            metrics: Metrics::global().clone(),
+            state_store: None,
+            events: broadcast::channel(RUNNER_EVENT_CAPACITY).0,
+            shutdown: CancellationToken::new(),
+            unconfirmed: Vec::new(),
+            approval_gate: None,
+            balance_watch: None,
+        }
+    }
+
+    /// Use a custom sandbox
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Use custom transaction options for executed actions
+    pub fn with_options(mut self, options: TransactionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Require the wallet to rehearse actions instead of sending them
+    pub fn with_run_mode(mut self, mode: RunMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Whether the agent's actions are broadcast or only rehearsed
+    pub fn run_mode(&self) -> RunMode {
+        match self.wallet.run_mode() {
+            RunMode::DryRun => RunMode::DryRun,
+            RunMode::Live => self.mode,
+        }
+    }
+
+    /// Limit how often the agent's actions are executed
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limiter = limit.limiter();
+        self
+    }
+
+    /// Remaining action capacity and when the next action is allowed
+    pub fn rate_limit_state(&self) -> RateLimitState {
+        self.rate_limiter.state(Instant::now())
+    }
+
+    /// Sleep until the rate limit allows another action
+    ///
+    /// Fails if the limit never allows an action.
+    pub async fn wait_for_rate_limit(&self) -> Result<()> {
+        let now = Instant::now();
+        match self.rate_limiter.state(now).next_allowed {
+            Some(at) => {
+                tokio::time::sleep(at.saturating_duration_since(now)).await;
+                Ok(())
+            }
+            None => Err(AgentError::rate_limit_exceeded(format!(
+                "Agent {} is not allowed any actions",
+                self.id
+            ))),
+        }
+    }
+
+    /// Append every decision to an audit log
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Audit log receiving the agent's decisions, if any
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// Write intent and outcome records of executed actions to `sink`
+    pub fn with_audit_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit_sink = Some(sink);
+        self
+    }
+
+    /// Report the agent's events to `notifier`
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Report the wallet balance falling below `threshold_sol`
+    ///
+    /// The event fires once per crossing: the balance has to recover above
+    /// the threshold before another one is sent.
+    pub fn with_balance_alert(mut self, threshold_sol: f64) -> Self {
+        self.balance_threshold_sol = Some(threshold_sol);
+        self
+    }
+
+    /// Refill the wallet when it falls below its minimum SOL reserve
+    pub fn with_top_up(mut self, top_up: TopUp) -> Self {
+        self.top_up = Some(top_up);
+        self
+    }
+
+    /// Record balances and decisions in `metrics` instead of [`Metrics::global`]
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Persist the agent's state in `store` across restarts
+    pub fn with_state_store(mut self, store: Arc<dyn AgentStateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// Stop taking ticks once `token` is cancelled
+    ///
+    /// A tick waiting for confirmation when it is cancelled returns right
+    /// away; [`AgentRunner::shutdown`] resumes the wait.
+    pub fn with_shutdown(mut self, token: CancellationToken) -> Self {
+        self.shutdown = token;
+        self
+    }
+
+    /// Token that shuts the runner down when cancelled
+    pub fn shutdown_token(&self) -> &CancellationToken {
+        &self.shutdown
+    }
+
+    /// Hold back actions above the gate's threshold until a human approves them
+    pub fn with_approval_gate(mut self, gate: ApprovalGate) -> Self {
+        self.approval_gate = Some(gate);
+        self
+    }
+
+    /// Gate holding back large actions, if any
+    pub fn approval_gate(&self) -> Option<&ApprovalGate> {
+        self.approval_gate.as_ref()
+    }
+
+    /// Continue from statistics persisted by an earlier run
+    pub fn with_stats(mut self, stats: AgentStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Outcome statistics of the agent's decisions
+    pub fn stats(&self) -> &AgentStats {
+        &self.stats
+    }
+
+    /// Share the agent's dead-letter queue with the runner
+    pub fn with_dead_letters(mut self, queue: DeadLetterQueue) -> Self {
+        self.dead_letters = Some(queue);
+        self
+    }
+
+    /// Actions parked after repeatedly failing validation, if tracked
+    pub fn dead_letters(&self) -> Option<&DeadLetterQueue> {
+        self.dead_letters.as_ref()
+    }
+
+    /// Persist the agent's artifacts in a workspace
+    pub fn with_workspace(mut self, workspace: AgentWorkspace) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Workspace holding the agent's artifacts, if any
+    pub fn workspace(&self) -> Option<&AgentWorkspace> {
+        self.workspace.as_ref()
+    }
+
+    /// Agent identifier
+    pub fn id(&self) -> &AgentId {
+        &self.id
+    }
+
+    /// Current runner status
+    pub fn status(&self) -> AgentStatus {
+        self.status
+    }
+
+    /// Receive the runner's events from now on
+    ///
+    /// The channel is bounded: a receiver that falls behind gets
+    /// [`RecvError::Lagged`](broadcast::error::RecvError::Lagged) instead of
+    /// holding up the runner.
+    pub fn subscribe(&self) -> broadcast::Receiver<RunnerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Recent decisions, oldest first
+    pub fn decision_log(&self) -> impl Iterator<Item = &DecisionRecord> {
+        self.decision_log.iter()
+    }
+
+    /// Accept external triggers, returning the handle that fires them
+    ///
+    /// Without a schema in `config`, payloads are validated against the
+    /// agent's own trigger schema. Enabling again replaces earlier handles.
+    pub fn enable_triggers(&mut self, mut config: TriggerConfig) -> TriggerHandle {
+        if config.schema.is_none() {
+            config.schema = self.agent.trigger_schema();
+        }
+        let (handle, receiver) = trigger::channel(self.id.clone(), config);
+        self.triggers = Some(receiver);
+        handle
+    }
+
+    /// Wait for the next external trigger
+    ///
+    /// Returns `None` if triggers are not enabled or every handle was dropped.
+    pub async fn next_trigger(&mut self) -> Option<TriggerPayload> {
+        match &mut self.triggers {
+            Some(receiver) => receiver.recv().await,
+            None => None,
+        }
+    }
+
+    /// Start the agent, preparing its action templates
+    pub async fn start(&mut self) -> Result<()> {
+        if self.mode == RunMode::DryRun && self.wallet.run_mode() == RunMode::Live {
+            return Err(AgentError::invalid_state(format!(
+                "Agent {} runs dry but its wallet is live; open the wallet with dry_run",
+                self.id
+            )));
+        }
+        self.prepare_templates().await?;
+        self.restore_state();
+        self.sandbox.reset_violations();
+        self.watch_balance();
+        self.set_status(AgentStatus::Active);
+        info!("Agent {} started", self.id);
+        Ok(())
+    }
+
+    /// Replace the agent logic and prepare the new agent's templates
+    pub async fn reconfigure(&mut self, agent: Arc<dyn DynAgent>) -> Result<()> {
+        self.agent = agent;
+        self.prepare_templates().await?;
+        self.restore_state();
+        info!("Agent {} reconfigured", self.id);
+        Ok(())
+    }
+
+    /// Pause decision making
+    pub fn pause(&mut self) {
+        self.set_status(AgentStatus::Paused);
+    }
+
+    /// Stop the agent
+    pub fn stop(&mut self) {
+        self.unwatch_balance();
+        self.set_status(AgentStatus::Stopped);
+    }
+
+    /// Stop the agent once its in-flight transactions are settled
+    ///
+    /// Cancels the shutdown token so no further tick starts, then waits up
+    /// to `timeout` for transactions that were sent but not confirmed to
+    /// resolve, recording each outcome. The agent's state is saved and the
+    /// audit sink and state store flushed before the runner stops.
+    pub async fn shutdown(&mut self, timeout: Duration) -> Result<()> {
+        self.shutdown.cancel();
+        self.settle_unconfirmed(timeout).await;
+        self.save_state();
+        let audit = self.audit_sink.as_ref().map_or(Ok(()), |sink| sink.flush());
+        let state = self
+            .state_store
+            .as_ref()
+            .map_or(Ok(()), |store| store.flush());
+        self.unwatch_balance();
+        self.set_status(AgentStatus::Stopped);
+        info!("Agent {} shut down", self.id);
+        audit.map_err(AgentError::from)?;
+        state
+    }
+
+    /// Wait for unconfirmed transactions until `timeout` and record how they ended
+    ///
+    /// Transactions still unresolved at the deadline stay audited as
+    /// unconfirmed.
+    async fn settle_unconfirmed(&mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let commitment = self
+            .options
+            .confirmation
+            .requirement()
+            .map_or(CommitmentConfig::confirmed(), |(commitment, _)| commitment);
+        for pending in std::mem::take(&mut self.unconfirmed) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let signature = pending.signature;
+            match self
+                .wallet
+                .wait_for_confirmation(&signature, commitment, remaining)
+                .await
+            {
+                Ok(()) => {
+                    info!("Transaction {} of agent {} confirmed", signature, self.id);
+                    self.audit_outcome(
+                        pending.intent.as_ref(),
+                        AuditOutcome::Confirmed,
+                        Some(signature),
+                        None,
+                    );
+                }
+                Err(CoreError::ConfirmationTimeout { .. }) => warn!(
+                    "Transaction {} of agent {} is still unconfirmed at shutdown",
+                    signature, self.id
+                ),
+                Err(e) => self.audit_outcome(
+                    pending.intent.as_ref(),
+                    AuditOutcome::Failed,
+                    Some(signature),
+                    Some(&e),
+                ),
+            }
+        }
+    }
+
+    /// Run one decision cycle
+    pub async fn tick(&mut self) -> Result<DecisionOutcome> {
+        self.run_decision(None).await
+    }
+
+    /// Run a decision cycle for an external trigger
+    pub async fn tick_triggered(&mut self, payload: TriggerPayload) -> Result<DecisionOutcome> {
+        self.run_decision(Some(payload)).await
+    }
+
+    /// Decide, execute and log one tick inside an `agent.decide` span
+    ///
+    /// The span's decision id is also the id of the tick's audit intent.
+    async fn run_decision(&mut self, trigger: Option<TriggerPayload>) -> Result<DecisionOutcome> {
+        let decision_id = Uuid::new_v4();
+        let span = tracing::info_span!(
+            "agent.decide",
+            agent_id = %self.id,
+            decision_id = %decision_id
+        );
+        let started = Instant::now();
+        let result = self
+            .decide_and_execute(trigger, decision_id)
+            .instrument(span)
+            .await;
+        let outcome = match &result {
+            Ok(DecisionOutcome::NoAction) => "no_action",
+            Ok(DecisionOutcome::Executed { .. }) => "executed",
+            Ok(DecisionOutcome::Failed { .. }) => "failed",
+            Ok(DecisionOutcome::TimedOut { .. }) => "timed_out",
+            Ok(DecisionOutcome::Simulated { .. }) => "simulated",
+            Ok(DecisionOutcome::AwaitingApproval { .. }) => "awaiting_approval",
+            Err(_) => "error",
+        };
+        self.metrics
+            .record_decision(&self.id, outcome, started.elapsed());
+        result
+    }
+
+    /// Decide, execute and log one tick
+    async fn decide_and_execute(
+        &mut self,
+        trigger: Option<TriggerPayload>,
+        decision_id: Uuid,
+    ) -> Result<DecisionOutcome> {
+        if self.status != AgentStatus::Active {
+            return Err(AgentError::invalid_state(format!(
+                "Agent {} is not active",
+                self.id
+            )));
+        }
+        if self.shutdown.is_cancelled() {
+            return Err(AgentError::invalid_state(format!(
+                "Agent {} is shutting down",
+                self.id
+            )));
+        }
+        self.emit(RunnerEvent::TickStarted {
+            decision_id,
+            triggered: trigger.is_some(),
+        });
+
+        if let Some(queue) = &self.dead_letters {
+            match queue.release_expired(Utc::now()) {
+                Ok(0) => {}
+                Ok(released) => info!(
+                    "Released {} dead-lettered actions of agent {}",
+                    released, self.id
+                ),
+                Err(e) => warn!("Failed to release dead letters of {}: {}", self.id, e),
+            }
+        }
+
+        let mut context = match self.wallet.refresh_agent_context().await {
+            Ok(context) => context,
+            Err(e) => {
+                self.emit(RunnerEvent::Failed {
+                    error: e.to_string(),
+                });
+                return Err(e.into());
+            }
+        };
+        context.trigger = trigger.clone();
+        context.performance = Some(self.stats.summary(Utc::now()));
+        self.snapshot_context(&context);
+        self.emit(RunnerEvent::ContextBuilt {
+            summary: ContextSummary::new(&context),
+        });
+        self.metrics
+            .set_wallet_balance(self.wallet.name(), context.wallet_balance);
+        self.metrics.set_spending_remaining(
+            self.wallet.name(),
+            context.spending_limits.remaining_daily_budget_lamports,
+        );
+        self.check_balance(context.wallet_balance);
+        self.check_key_rotation().await;
+        self.top_up_if_needed(&context).await;
+        self.release_approvals().await;
+        let decided = match self.sandbox.execute(self.agent.as_ref(), &context).await {
+            Ok(decided) => decided,
+            Err(AgentError::DecisionTimeout(timeout)) => {
+                warn!(
+                    "Agent {} did not decide within {:?} ({} in a row)",
+                    self.id,
+                    timeout,
+                    self.sandbox.consecutive_timeouts()
+                );
+                let error = AgentError::DecisionTimeout(timeout).to_string();
+                self.notify(AgentEvent::AgentErrored {
+                    agent_id: self.id.clone(),
+                    error: error.clone(),
+                });
+                self.emit(RunnerEvent::Failed { error });
+                if self.sandbox.is_tripped() {
+                    warn!(
+                        "Agent {} reached its sandbox violation limit, stopping it",
+                        self.id
+                    );
+                    self.set_status(AgentStatus::Error);
+                }
+                let outcome = DecisionOutcome::TimedOut {
+                    timeout_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+                };
+                self.record(DecisionRecord {
+                    agent_id: self.id.clone(),
+                    timestamp: Utc::now(),
+                    action: None,
+                    outcome: outcome.clone(),
+                    trigger,
+                });
+                return Ok(outcome);
+            }
+            Err(e) => {
+                self.notify(AgentEvent::AgentErrored {
+                    agent_id: self.id.clone(),
+                    error: e.to_string(),
+                });
+                self.emit(RunnerEvent::Failed {
+                    error: e.to_string(),
+                });
+                if self.sandbox.is_tripped() {
+                    warn!(
+                        "Agent {} reached its sandbox violation limit, stopping it",
+                        self.id
+                    );
+                    self.set_status(AgentStatus::Error);
+                }
+                return Err(e);
+            }
+        };
+        self.save_state();
+        self.emit(RunnerEvent::DecisionMade {
+            action: decided.clone().unwrap_or(AgentAction::NoOp),
+            rationale: self.agent.last_rationale(),
+        });
+        let action = match decided {
+            None | Some(AgentAction::NoOp) => None,
+            Some(action) => Some(action),
+        };
+
+        let intent = match (&action, &self.audit_sink) {
+            (Some(action), Some(sink)) => {
+                let intent = AuditEntry::intent(self.wallet.public_key(), action.clone())
+                    .with_id(decision_id)
+                    .with_agent(self.id.clone())
+                    .with_dry_run(self.run_mode() == RunMode::DryRun);
+                if let Err(e) = sink.record(&intent) {
+                    self.emit(RunnerEvent::Failed {
+                        error: e.to_string(),
+                    });
+                    return Err(e.into());
+                }
+                Some(intent)
+            }
+            _ => None,
+        };
+
+        if let Some(action) = &action {
+            if let Err(e) = self.rate_limiter.check_and_record(Instant::now()) {
+                warn!("Agent {} action rejected: {}", self.id, e);
+                self.emit(RunnerEvent::Failed {
+                    error: e.to_string(),
+                });
+                self.audit_outcome(intent.as_ref(), AuditOutcome::Failed, None, Some(&e));
+                self.stats.record_failed(action, &e.to_string(), Utc::now());
+                self.record(DecisionRecord {
+                    agent_id: self.id.clone(),
+                    timestamp: Utc::now(),
+                    action,
+                    outcome: DecisionOutcome::Failed {
+                        reason: e.to_string(),
+                    },
+                    trigger,
+                });
+                return Err(e);
+            }
+        }
+
+        let outcome = match &action {
+            None => {
+                self.stats.record_no_action();
+                DecisionOutcome::NoAction
+            }
+            Some(action) => match self.hold_for_approval(action, &context, decision_id).await {
+                Ok(Some(request)) => DecisionOutcome::AwaitingApproval {
+                    token: request.token,
+                },
+                Ok(None) => {
+                    self.execute_action(action, intent.as_ref(), decision_id)
+                        .await
+                }
+                Err(e) => self.fail_action(action, intent.as_ref(), e),
+            },
+        };
+
+        self.record(DecisionRecord {
+            agent_id: self.id.clone(),
+            timestamp: Utc::now(),
+            action,
+            outcome: outcome.clone(),
+            trigger,
+        });
+        Ok(outcome)
+    }
+
+    /// Execute a decided action, reporting and auditing how it went
+    async fn execute_action(
+        &mut self,
+        action: &AgentAction,
+        intent: Option<&AuditEntry>,
+        decision_id: Uuid,
+    ) -> DecisionOutcome {
+        debug!("Agent {} decided: {}", self.id, action.description());
+        // Attributed memos name the decision that sent the transaction.
+        // On shutdown the confirmation wait ends at once and is
+        // resumed by `shutdown`.
+        let options = TransactionOptions {
+            memo_attribution: Some(MemoAttribution::new(self.id.clone(), decision_id)),
+            shutdown: Some(ShutdownSignal::new(self.shutdown.clone(), Duration::ZERO)),
+            ..self.options.clone()
+        };
+        let receipt = match self
+            .wallet
+            .execute_action_with_receipt(action, &options)
+            .await
+        {
+            Ok(receipt) => receipt,
+            Err(e) => return self.fail_action(action, intent, e),
+        };
+        let signature = receipt.signature;
+        self.emit(RunnerEvent::TransactionSent {
+            signature,
+            dry_run: receipt.is_dry_run(),
+        });
+        if !receipt.is_dry_run() && self.options.confirmation.requirement().is_some() {
+            self.emit(RunnerEvent::Confirmed {
+                signature,
+                fee: receipt.fee_lamports,
+            });
+        }
+        self.stats.record_executed(action, &receipt, Utc::now());
+        let audited = if receipt.is_dry_run() {
+            AuditOutcome::Simulated
+        } else if self.options.confirmation.requirement().is_some() {
+            AuditOutcome::Confirmed
+        } else {
+            AuditOutcome::Sent
+        };
+        self.audit_outcome(intent, audited, Some(signature), None);
+        self.notify(AgentEvent::DecisionExecuted {
+            agent_id: self.id.clone(),
+            action: action.description(),
+            signature: signature.to_string(),
+            dry_run: receipt.is_dry_run(),
+        });
+        match receipt.simulated {
+            Some(preview) => DecisionOutcome::Simulated {
+                would_have_sent: signature,
+                preview,
+            },
+            None => DecisionOutcome::Executed { signature },
+        }
+    }
+
+    /// Report, audit and count an action that failed
+    fn fail_action(
+        &mut self,
+        action: &AgentAction,
+        intent: Option<&AuditEntry>,
+        e: CoreError,
+    ) -> DecisionOutcome {
+        warn!("Agent {} action failed: {}", self.id, e);
+        self.emit_failure(&e);
+        if let CoreError::ConfirmationTimeout { signature, .. } = &e {
+            self.audit_outcome(
+                intent,
+                AuditOutcome::Unconfirmed,
+                Some(*signature),
+                Some(&e),
+            );
+            self.unconfirmed.push(PendingConfirmation {
+                signature: *signature,
+                intent: intent.cloned(),
+            });
+        } else {
+            self.audit_outcome(intent, AuditOutcome::Failed, None, Some(&e));
+        }
+        self.stats.record_failed(action, &e.to_string(), Utc::now());
+        self.notify_failure(&e);
+        DecisionOutcome::Failed {
+            reason: e.to_string(),
+        }
+    }
+
+    /// Park `action` with the approval gate if it needs approval
+    ///
+    /// The action is previewed first, so one that would fail anyway fails
+    /// now instead of waiting for a human. Returns the request it waits on.
+    async fn hold_for_approval(
+        &self,
+        action: &AgentAction,
+        context: &AgentContext,
+        decision_id: Uuid,
+    ) -> std::result::Result<Option<ApprovalRequest>, CoreError> {
+        let Some(gate) = &self.approval_gate else {
+            return Ok(None);
+        };
+        let value_sol = approval::value_at_stake(action, context);
+        if !gate.requires_approval(value_sol) {
+            return Ok(None);
+        }
+        let preview = self.wallet.preview_action(action).await?;
+        let request = gate
+            .request(
+                &self.id,
+                &self.wallet.public_key(),
+                decision_id,
+                action,
+                value_sol,
+                Some(preview),
+            )
+            .map_err(|e| match e {
+                AgentError::Wallet(e) => e,
+                other => CoreError::storage(other.to_string()),
+            })?;
+        self.emit(RunnerEvent::ApprovalRequested {
+            token: request.token.clone(),
+            expires_at: request.expires_at,
+        });
+        Ok(Some(request))
+    }
+
+    /// Act on approval requests resolved since the last tick
+    ///
+    /// Approved actions are executed and rejected ones dropped, each
+    /// recorded as a decision of its own. Executing an approved action
+    /// does not count against the rate limit again.
+    async fn release_approvals(&mut self) {
+        let Some(gate) = &self.approval_gate else {
+            return;
+        };
+        let resolved = match gate.take_resolved(Utc::now()) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                warn!("Failed to read approvals of agent {}: {}", self.id, e);
+                return;
+            }
+        };
+        for request in resolved {
+            // The intent was written when the action was decided
+            let intent = self.audit_sink.as_ref().map(|_| {
+                AuditEntry::intent(self.wallet.public_key(), request.action.clone())
+                    .with_id(request.decision_id)
+                    .with_agent(self.id.clone())
+                    .with_dry_run(self.run_mode() == RunMode::DryRun)
+            });
+            let outcome = match request.status {
+                ApprovalStatus::Approved => {
+                    info!(
+                        "Executing approved action of agent {}: {}",
+                        self.id, request.description
+                    );
+                    self.execute_action(&request.action, intent.as_ref(), request.decision_id)
+                        .await
+                }
+                _ => {
+                    let reason = if request.timed_out {
+                        format!("Approval request {} timed out", request.token)
+                    } else {
+                        format!("Approval request {} was rejected", request.token)
+                    };
+                    info!("Dropped action of agent {}: {}", self.id, reason);
+                    self.audit_outcome(intent.as_ref(), AuditOutcome::Failed, None, Some(&reason));
+                    DecisionOutcome::Failed { reason }
+                }
+            };
+            self.record(DecisionRecord {
+                agent_id: self.id.clone(),
+                timestamp: Utc::now(),
+                action: Some(request.action),
+                outcome,
+                trigger: None,
+            });
+        }
+    }
+
+    /// Write the outcome record of an audited action
+    ///
+    /// The action already ran, so a failing sink is only logged.
+    fn audit_outcome(
+        &self,
+        intent: Option<&AuditEntry>,
+        outcome: AuditOutcome,
+        signature: Option<Signature>,
+        error: Option<&dyn std::fmt::Display>,
+    ) {
+        let (Some(intent), Some(sink)) = (intent, &self.audit_sink) else {
+            return;
+        };
+        let mut entry = intent.outcome(outcome);
+        if let Some(signature) = signature {
+            entry = entry.with_signature(signature);
+        }
+        if let Some(error) = error {
+            entry = entry.with_error(error);
+        }
+        if let Err(e) = sink.record(&entry) {
+            warn!("Failed to audit outcome of agent {}: {}", self.id, e);
+        }
+    }
+
+    /// Follow the wallet's balance over the websocket, if configured
+    ///
+    /// Without a subscription the balance is still polled with the context.
+    fn watch_balance(&mut self) {
+        if self.balance_watch.is_some() {
+            return;
+        }
+        match self.wallet.watch_balance() {
+            Ok(watch) => self.balance_watch = watch,
+            Err(e) => warn!(
+                "Failed to subscribe to the balance of agent {}: {}",
+                self.id, e
+            ),
+        }
+    }
+
+    /// Stop following the wallet's balance
+    fn unwatch_balance(&mut self) {
+        if let Some(watch) = self.balance_watch.take() {
+            watch.abort();
+        }
+    }
+
+    /// Change status, telling subscribers
+    fn set_status(&mut self, status: AgentStatus) {
+        if status != self.status {
+            self.emit(RunnerEvent::StatusChanged {
+                from: self.status,
+                to: status,
+            });
+        }
+        self.status = status;
+    }
+
+    /// Hand an event to the runner's subscribers
+    fn emit(&self, event: RunnerEvent) {
+        // Having no subscribers is not an error
+        let _ = self.events.send(event);
+    }
+
+    /// Report a failed action to subscribers
+    ///
+    /// Actions the wallet refused never reached the network; a transaction
+    /// that timed out waiting for confirmation was sent and may still land.
+    fn emit_failure(&self, error: &CoreError) {
+        match error {
+            CoreError::Validation(_)
+            | CoreError::TransactionValidation(_)
+            | CoreError::InvalidAmount(_)
+            | CoreError::InvalidAddress(_)
+            | CoreError::LimitExceeded(_)
+            | CoreError::PermissionDenied(_)
+            | CoreError::InsufficientFunds { .. }
+            | CoreError::ReserveBreached { .. } => {
+                self.emit(RunnerEvent::ValidationFailed {
+                    errors: vec![error.to_string()],
+                });
+                return;
+            }
+            CoreError::ConfirmationTimeout { signature, .. } => {
+                self.emit(RunnerEvent::TransactionSent {
+                    signature: *signature,
+                    dry_run: false,
+                })
+            }
+            _ => {}
+        }
+        self.emit(RunnerEvent::Failed {
+            error: error.to_string(),
+        });
+    }
+
+    /// Hand an event about the agent to the notifier
+    fn notify(&self, event: AgentEvent) {
+        self.notifier
+            .notify(Notification::new(event, &self.wallet.public_key()));
+    }
+
+    /// Report a failed action as the event it amounts to
+    fn notify_failure(&self, error: &CoreError) {
+        let agent_id = self.id.clone();
+        self.notify(match error {
+            CoreError::LimitExceeded(reason) => AgentEvent::SpendingLimitHit {
+                agent_id,
+                reason: reason.clone(),
+            },
+            CoreError::ConfirmationTimeout { signature, .. } => AgentEvent::ConfirmationTimeout {
+                agent_id,
+                signature: signature.to_string(),
+            },
+            other => AgentEvent::AgentErrored {
+                agent_id,
+                error: other.to_string(),
+            },
+        });
+    }
+
+    /// Report the balance crossing below the alert threshold
+    fn check_balance(&mut self, balance_sol: f64) {
+        let Some(threshold_sol) = self.balance_threshold_sol else {
+            return;
+        };
+        let below = balance_sol < threshold_sol;
+        if below && !self.below_threshold {
+            self.notify(AgentEvent::BalanceBelowThreshold {
+                agent_id: self.id.clone(),
+                balance_sol,
+                threshold_sol,
+            });
+        }
+        self.below_threshold = below;
+    }
+
+    /// Report the wallet key crossing a threshold of its rotation policy
+    ///
+    /// The runner only shares the wallet, so rotating is left to its owner.
+    async fn check_key_rotation(&mut self) {
+        let reason = self.wallet.needs_rotation().await;
+        if let (Some(reason), false) = (&reason, self.rotation_due) {
+            warn!("Wallet key of agent {} needs rotating: {}", self.id, reason);
+            self.notify(AgentEvent::KeyRotationNeeded {
+                agent_id: self.id.clone(),
+                reason: reason.to_string(),
+            });
+        }
+        self.rotation_due = reason.is_some();
+    }
+
+    /// Refill the wallet once per fall below its reserve
+    ///
+    /// A failed top-up is tried again on the next tick.
+    async fn top_up_if_needed(&mut self, context: &AgentContext) {
+        let Some(top_up) = self.top_up.clone() else {
+            return;
+        };
+        let Some(action) = top_up.request(context) else {
+            self.topping_up = false;
+            return;
+        };
+        if self.topping_up {
+            return;
+        }
+        self.topping_up = true;
+
+        let Some(funding) = &top_up.funding else {
+            let AgentAction::TransferSol { amount, .. } = &action else {
+                return;
+            };
+            info!(
+                "Agent {} wallet is below its reserve, requesting a top-up",
+                self.id
+            );
+            self.notify(AgentEvent::TopUpRequested {
+                agent_id: self.id.clone(),
+                amount_lamports: amount.as_u64(),
+                balance_sol: context.wallet_balance,
+                reserve_sol: Lamports::new(context.spending_limits.min_sol_reserve_lamports)
+                    .to_sol_f64(),
+            });
+            return;
+        };
+        match funding
+            .execute_action_with_receipt(&action, &self.options)
+            .await
+        {
+            Ok(receipt) => info!(
+                "Topped up wallet of agent {} from {}: {}",
+                self.id,
+                funding.public_key(),
+                receipt.signature
+            ),
+            Err(e) => {
+                warn!("Failed to top up wallet of agent {}: {}", self.id, e);
+                self.topping_up = false;
+                self.notify(AgentEvent::AgentErrored {
+                    agent_id: self.id.clone(),
+                    error: format!("Top-up failed: {}", e),
+                });
+            }
+        }
+    }
+
+    /// Hand the agent the state saved by an earlier run
+    fn restore_state(&self) {
+        let (Some(store), Some(key)) = (&self.state_store, self.agent.state_key()) else {
+            return;
+        };
+        let restored = match store.get(&key) {
+            Ok(Some(state)) => self.agent.load_state(state),
+            Ok(None) => {
+                debug!("No saved state for agent {}", self.id);
+                return;
+            }
+            Err(e) => Err(e),
+        };
+        match restored {
+            Ok(()) => info!("Restored state of agent {} from {}", self.id, key),
+            Err(e) => error!(
+                "Failed to restore state of agent {}, starting fresh: {}",
+                self.id, e
+            ),
+        }
+    }
+
+    /// Save the agent's state after a decision
+    ///
+    /// The decision stands either way, so a failing store is only logged.
+    fn save_state(&self) {
+        let (Some(store), Some(key)) = (&self.state_store, self.agent.state_key()) else {
+            return;
+        };
+        let saved = self
+            .agent
+            .save_state()
+            .and_then(|state| state.map_or(Ok(()), |state| store.put(&key, state)));
+        if let Err(e) = saved {
+            error!("Failed to save state of agent {}: {}", self.id, e);
+        }
+    }
+
+    /// Save the context of the current decision in the workspace
+    fn snapshot_context(&self, context: &AgentContext) {
+        let Some(workspace) = &self.workspace else {
+            return;
+        };
+        let path = workspace
+            .artifact(ArtifactKind::ContextSnapshots)
+            .join("latest.json");
+        if let Err(e) = workspace.write_json(path, context) {
+            warn!("Failed to snapshot context of agent {}: {}", self.id, e);
+        }
+    }
+
+    /// Append a record to the decision log
+    fn record(&mut self, record: DecisionRecord) {
+        info!(
+            agent = %record.agent_id,
+            triggered = record.is_triggered(),
+            outcome = ?record.outcome,
+            "Agent decision"
+        );
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.append(record.clone()) {
+                warn!("Failed to audit decision of agent {}: {}", self.id, e);
+            }
+        }
+        if self.decision_log.len() == DECISION_LOG_CAPACITY {
+            self.decision_log.pop_front();
+        }
+        self.decision_log.push_back(record);
+    }
+
+    /// Ask the agent for its templates and pre-build them in the wallet
+    async fn prepare_templates(&self) -> Result<()> {
+        let templates = self.agent.templates();
+        let prepared = self
+            .wallet
+            .prepare_templates(templates, &self.options)
+            .await?;
+        debug!("Prepared {} action templates for {}", prepared, self.id);
+        Ok(())
+    }
+}
+
+impl Drop for AgentRunner {
+    fn drop(&mut self) {
+        self.unwatch_balance();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    use agent_wallet_core::audit::{AuditPhase, MemoryAuditSink};
+    use agent_wallet_core::config::ApprovalPolicy;
+    use agent_wallet_core::manager::SharedComponents;
+    use agent_wallet_core::prelude::Zeroizing;
+    use agent_wallet_core::sol::LAMPORTS_PER_SOL;
+    use agent_wallet_core::{RpcProvider, WalletConfig};
+    use solana_client::rpc_config::RpcProgramAccountsConfig;
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_sdk::account::Account;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::transaction::Transaction;
+    use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+    use tempfile::tempdir;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use crate::agent::Agent;
+    use crate::approval::{ApprovalDecision, ApprovalStore};
+    use crate::sync::lock;
+
+    type CoreResult<T> = agent_wallet_core::Result<T>;
+
+    /// Cluster where the wallet holds `balance` and every transaction confirms
+    struct ScriptedRpc {
+        balance: u64,
+        send_error: StdMutex<Option<CoreError>>,
+        confirmed_from: StdMutex<Option<Instant>>,
+    }
+
+    impl ScriptedRpc {
+        fn new(balance: u64) -> Self {
+            Self {
+                balance,
+                send_error: StdMutex::new(None),
+                confirmed_from: StdMutex::new(None),
+            }
+        }
+
+        /// Report transactions as unknown until `delay` from now
+        fn delay_confirmation(&self, delay: Duration) {
+            *lock(&self.confirmed_from) = Some(Instant::now() + delay);
+        }
+
+        /// Reject the next transaction sent with `error`
+        fn reject_next_send(&self, error: CoreError) {
+            *lock(&self.send_error) = Some(error);
+        }
+    }
+
+    impl RpcProvider for ScriptedRpc {
+        async fn get_balance(&self, _pubkey: &Pubkey) -> CoreResult<u64> {
+            Ok(self.balance)
+        }
+
+        async fn get_account(&self, pubkey: &Pubkey) -> CoreResult<Account> {
+            Err(CoreError::AccountNotFound(pubkey.to_string()))
+        }
+
+        async fn get_multiple_accounts(
+            &self,
+            pubkeys: &[Pubkey],
+        ) -> CoreResult<Vec<Option<Account>>> {
+            Ok(vec![None; pubkeys.len()])
+        }
+
+        async fn get_latest_blockhash(&self) -> CoreResult<Hash> {
+            Ok(Hash::new_unique())
+        }
+
+        async fn send_transaction(&self, transaction: &Transaction) -> CoreResult<Signature> {
+            let error = lock(&self.send_error).take();
+            match error {
+                Some(error) => Err(error),
+                None => Ok(transaction.signatures.first().copied().unwrap_or_default()),
+            }
+        }
+
+        async fn simulate_transaction(
+            &self,
+            _transaction: &Transaction,
+        ) -> CoreResult<RpcSimulateTransactionResult> {
+            Ok(RpcSimulateTransactionResult {
+                err: None,
+                logs: Some(Vec::new()),
+                accounts: None,
+                units_consumed: Some(450),
+                return_data: None,
+                inner_instructions: None,
+            })
+        }
+
+        async fn get_signature_statuses(
+            &self,
+            signatures: &[Signature],
+        ) -> CoreResult<Vec<Option<TransactionStatus>>> {
+            let confirmed_from = *lock(&self.confirmed_from);
+            if confirmed_from.is_some_and(|at| Instant::now() < at) {
+                return Ok(vec![None; signatures.len()]);
+            }
+            Ok(signatures
+                .iter()
+                .map(|_| {
+                    Some(TransactionStatus {
+                        slot: 1,
+                        confirmations: Some(1),
+                        status: Ok(()),
+                        err: None,
+                        confirmation_status: Some(TransactionConfirmationStatus::Confirmed),
+                    })
+                })
+                .collect())
+        }
+
+        async fn get_program_accounts(
+            &self,
+            _program_id: &Pubkey,
+            _config: Option<RpcProgramAccountsConfig>,
+        ) -> CoreResult<Vec<(Pubkey, Account)>> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Agent deciding on the same action every tick
+    struct Scripted(AgentAction);
+
+    impl Agent for Scripted {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        async fn decide(&self, _context: &AgentContext) -> Result<Option<AgentAction>> {
+            Ok(Some(self.0.clone()))
+        }
+
+        fn last_rationale(&self) -> Option<String> {
+            Some("scripted".to_string())
+        }
+    }
+
+    fn transfer(amount: u64) -> AgentAction {
+        AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(amount),
+            memo: None,
+        }
+    }
+
+    async fn scripted_runner(
+        rpc: Arc<ScriptedRpc>,
+        dir: &std::path::Path,
+        action: AgentAction,
+    ) -> Result<AgentRunner> {
+        let mut config = WalletConfig::default();
+        config.wallet.storage.path = dir.join("wallets");
+        config.wallet.storage.backup_path = dir.join("backups");
+        config.rpc.use_websocket = false;
+        let shared = SharedComponents::new(rpc, config.rpc.commitment.to_solana_commitment());
+        let passphrase = Zeroizing::new("runner passphrase".to_string());
+        let wallet = Wallet::create_with_shared("runner", &passphrase, config, &shared).await?;
+        Ok(AgentRunner::new(
+            "scripted",
+            Arc::new(Scripted(action)),
+            Arc::new(wallet),
+        ))
+    }
+
+    fn received(events: &mut broadcast::Receiver<RunnerEvent>) -> Vec<RunnerEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    fn kinds(events: &[RunnerEvent]) -> Vec<&'static str> {
+        events.iter().map(RunnerEvent::kind).collect()
+    }
+
+    #[tokio::test]
+    async fn test_successful_tick_emits_full_event_sequence() -> Result<()> {
+        let dir = tempdir().map_err(CoreError::from)?;
+        let rpc = Arc::new(ScriptedRpc::new(LAMPORTS_PER_SOL));
+        let mut runner = scripted_runner(rpc, dir.path(), transfer(10_000_000)).await?;
+        let mut events = runner.subscribe();
+
+        runner.start().await?;
+        let DecisionOutcome::Executed { signature } = runner.tick().await? else {
+            panic!("the transfer was not executed");
+        };
+
+        let events = received(&mut events);
+        assert_eq!(
+            kinds(&events),
+            [
+                "status_changed",
+                "tick_started",
+                "context_built",
+                "decision_made",
+                "transaction_sent",
+                "confirmed"
+            ]
+        );
+        assert!(matches!(
+            events[0],
+            RunnerEvent::StatusChanged {
+                from: AgentStatus::Stopped,
+                to: AgentStatus::Active
+            }
+        ));
+        assert!(matches!(
+            &events[2],
+            RunnerEvent::ContextBuilt { summary } if summary.balance_sol == 1.0
+        ));
+        assert!(matches!(
+            &events[3],
+            RunnerEvent::DecisionMade {
+                action: AgentAction::TransferSol { amount: Lamports(10_000_000), .. },
+                rationale: Some(rationale),
+            } if rationale == "scripted"
+        ));
+        assert!(matches!(
+            events[4],
+            RunnerEvent::TransactionSent { signature: sent, dry_run: false } if sent == signature
+        ));
+        assert!(matches!(
+            events[5],
+            RunnerEvent::Confirmed { signature: confirmed, .. } if confirmed == signature
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_ticks_end_with_failure_events() -> Result<()> {
+        let dir = tempdir().map_err(CoreError::from)?;
+        let rpc = Arc::new(ScriptedRpc::new(LAMPORTS_PER_SOL));
+        let mut runner = scripted_runner(rpc.clone(), dir.path(), transfer(10_000_000)).await?;
+        runner.start().await?;
+        let mut events = runner.subscribe();
+
+        // Rejected by the node
+        rpc.reject_next_send(CoreError::transaction("rejected by the node"));
+        assert!(matches!(
+            runner.tick().await?,
+            DecisionOutcome::Failed { .. }
+        ));
+        let failed = received(&mut events);
+        assert_eq!(
+            kinds(&failed),
+            ["tick_started", "context_built", "decision_made", "failed"]
+        );
+        assert!(matches!(
+            &failed[3],
+            RunnerEvent::Failed { error } if error.contains("rejected by the node")
+        ));
+
+        // Refused by the wallet before anything was sent
+        let dir = tempdir().map_err(CoreError::from)?;
+        let rpc = Arc::new(ScriptedRpc::new(LAMPORTS_PER_SOL));
+        let mut runner = scripted_runner(rpc, dir.path(), transfer(5 * LAMPORTS_PER_SOL)).await?;
+        runner.start().await?;
+        let mut events = runner.subscribe();
+        assert!(matches!(
+            runner.tick().await?,
+            DecisionOutcome::Failed { .. }
+        ));
+        let refused = received(&mut events);
+        assert_eq!(
+            kinds(&refused),
+            [
+                "tick_started",
+                "context_built",
+                "decision_made",
+                "validation_failed"
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_held_action_runs_once_approved() -> Result<()> {
+        let dir = tempdir().map_err(CoreError::from)?;
+        let rpc = Arc::new(ScriptedRpc::new(LAMPORTS_PER_SOL));
+        let policy = ApprovalPolicy {
+            auto_approve_below_sol: 0.001,
+            ..Default::default()
+        };
+        let gate = ApprovalGate::new(policy, ApprovalStore::new())?;
+        let mut runner = scripted_runner(rpc, dir.path(), transfer(10_000_000))
+            .await?
+            .with_approval_gate(gate.clone());
+        runner.start().await?;
+        let mut events = runner.subscribe();
+
+        let DecisionOutcome::AwaitingApproval { token } = runner.tick().await? else {
+            panic!("the transfer was not held back");
+        };
+        assert_eq!(
+            kinds(&received(&mut events)),
+            [
+                "tick_started",
+                "context_built",
+                "decision_made",
+                "approval_requested"
+            ]
+        );
+        let pending = gate.store().pending(Utc::now())?;
+        let [request] = pending.as_slice() else {
+            panic!("{} requests are pending", pending.len());
+        };
+        assert_eq!(request.token, token);
+        assert!(request.preview.is_some());
+
+        // The next tick executes the approved action and holds back its own
+        gate.store()
+            .resolve(&token, ApprovalDecision::Approve, Utc::now())?;
+        assert!(matches!(
+            runner.tick().await?,
+            DecisionOutcome::AwaitingApproval { .. }
+        ));
+        let executed: Vec<_> = runner
+            .decision_log()
+            .filter(|record| matches!(record.outcome, DecisionOutcome::Executed { .. }))
+            .collect();
+        assert_eq!(executed.len(), 1);
+        assert!(gate.take_resolved(Utc::now())?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_lags_instead_of_blocking() -> Result<()> {
+        let dir = tempdir().map_err(CoreError::from)?;
+        let rpc = Arc::new(ScriptedRpc::new(LAMPORTS_PER_SOL));
+        let mut runner = scripted_runner(rpc, dir.path(), AgentAction::NoOp).await?;
+        let mut events = runner.subscribe();
+
+        for _ in 0..RUNNER_EVENT_CAPACITY {
+            runner.pause();
+            runner.stop();
+        }
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Lagged(_))));
+        // The receiver continues from the oldest event still buffered
+        assert!(matches!(
+            events.try_recv(),
+            Ok(RunnerEvent::StatusChanged { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_mid_confirmation_records_the_outcome() -> Result<()> {
+        let dir = tempdir().map_err(CoreError::from)?;
+        let rpc = Arc::new(ScriptedRpc::new(LAMPORTS_PER_SOL));
+        let sink = Arc::new(MemoryAuditSink::new());
+        let token = CancellationToken::new();
+        let mut runner = scripted_runner(rpc.clone(), dir.path(), transfer(10_000_000))
+            .await?
+            .with_audit_sink(sink.clone())
+            .with_shutdown(token.clone());
+        runner.start().await?;
+        let mut events = runner.subscribe();
+        let runtime = tokio::runtime::Handle::current().metrics();
+        let tasks_before = runtime.num_alive_tasks();
+
+        // The transaction lands a second after it is sent, but the shutdown
+        // begins while the tick is still waiting for it
+        rpc.delay_confirmation(Duration::from_secs(1));
+        let started = Instant::now();
+        let (outcome, ()) = tokio::join!(runner.tick(), async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            token.cancel();
+        });
+        assert!(matches!(outcome?, DecisionOutcome::Failed { .. }));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            kinds(&received(&mut events)),
+            [
+                "tick_started",
+                "context_built",
+                "decision_made",
+                "transaction_sent",
+                "failed"
+            ]
+        );
+        assert!(runner.tick().await.is_err());
+
+        runner.shutdown(Duration::from_secs(5)).await?;
+        assert_eq!(runner.status(), AgentStatus::Stopped);
+
+        let outcomes: Vec<_> = sink
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.phase == AuditPhase::Outcome)
+            .collect();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].outcome, Some(AuditOutcome::Unconfirmed));
+        assert_eq!(outcomes[1].outcome, Some(AuditOutcome::Confirmed));
+        assert!(outcomes[1].signature.is_some());
+        assert_eq!(outcomes[0].signature, outcomes[1].signature);
+
+        // Nothing was left running in the background
+        assert!(runtime.num_alive_tasks() <= tasks_before);
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+//! Sandboxed execution of agent logic
+//!
+//! The [`Sandbox`] runs an agent's decision step and rejects actions outside
+//! the agent's permission boundary before they reach the wallet.
+//!
+//! Resource limits are enforced around the decision: `decide()` is cancelled
+//! once it exceeds the decision timeout, failing with
+//! [`AgentError::DecisionTimeout`], and the serialized context handed to
+//! the agent and the action it returns are capped in size so a runaway agent
+//! cannot grow either without bound. Every breach counts as a violation; once
+//! [`SandboxConfig::max_violations`] is reached the sandbox reports itself as
+//! tripped and the runner moves the agent to [`AgentStatus::Error`].
+//!
+//! A timed-out decision is dropped and the [`CancellationToken`] passed to
+//! [`Agent::decide_cancellable`] is cancelled, so agents doing I/O can abort
+//! requests they started elsewhere. Consecutive timeouts are tracked apart
+//! from other violations; one decision finishing in time resets them.
+//!
+//! CPU limits from [`SandboxSettings`] are not enforced in-process.
+//!
+//! [`Agent::decide_cancellable`]: crate::agent::Agent::decide_cancellable
+//!
+//! [`AgentStatus::Error`]: crate::agent::AgentStatus::Error
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_wallet_core::config::SandboxSettings;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::agent::{CancellationToken, DynAgent};
+use crate::context::AgentContext;
+use crate::decision::AgentAction;
+use crate::error::{AgentError, Result};
+use crate::DEFAULT_DECISION_TIMEOUT_SECS;
+
+/// Default cap on the serialized context in bytes
+const DEFAULT_MAX_CONTEXT_BYTES: usize = 1024 * 1024;
+
+/// Default cap on the serialized action in bytes
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Default number of violations before an agent is put in the error state
+const DEFAULT_MAX_VIOLATIONS: u32 = 3;
+
+/// Sandbox configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxConfig {
+    /// Whether resource limits are enforced
+    ///
+    /// The permission boundary is checked even when disabled.
+    pub enabled: bool,
+    /// Maximum time an agent may take to decide
+    pub decision_timeout: Duration,
+    /// Maximum size of the serialized context given to the agent
+    pub max_context_bytes: usize,
+    /// Maximum size of the serialized action returned by the agent
+    pub max_response_bytes: usize,
+    /// Violations after which the agent is put in the error state
+    pub max_violations: u32,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            decision_timeout: Duration::from_secs(DEFAULT_DECISION_TIMEOUT_SECS),
+            max_context_bytes: DEFAULT_MAX_CONTEXT_BYTES,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            max_violations: DEFAULT_MAX_VIOLATIONS,
+        }
+    }
+}
+
+impl From<&SandboxSettings> for SandboxConfig {
+    fn from(settings: &SandboxSettings) -> Self {
+        let memory_limit = usize::try_from(settings.memory_limit_mb.saturating_mul(1024 * 1024))
+            .unwrap_or(usize::MAX);
+        Self {
+            enabled: settings.enabled,
+            decision_timeout: Duration::from_secs(settings.decision_timeout_seconds),
+            max_context_bytes: memory_limit,
+            ..Self::default()
+        }
+    }
+}
+
+/// Execution environment for agent logic
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    config: SandboxConfig,
+    violations: Arc<AtomicU32>,
+    consecutive_timeouts: Arc<AtomicU32>,
+}
+
+impl Sandbox {
+    /// Create a sandbox with the given configuration
+    pub fn new(config: SandboxConfig) -> Self {
+        Self {
+            config,
+            violations: Arc::default(),
+            consecutive_timeouts: Arc::default(),
+        }
+    }
+
+    /// Sandbox configuration
+    pub fn config(&self) -> &SandboxConfig {
+        &self.config
+    }
+
+    /// Number of violations recorded since the last reset
+    pub fn violations(&self) -> u32 {
+        self.violations.load(Ordering::Relaxed)
+    }
+
+    /// Decisions in a row that exceeded the decision timeout
+    pub fn consecutive_timeouts(&self) -> u32 {
+        self.consecutive_timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Whether the agent has reached its violation limit
+    pub fn is_tripped(&self) -> bool {
+        self.violations() >= self.config.max_violations
+    }
+
+    /// Clear the violation counter
+    pub fn reset_violations(&self) {
+        self.violations.store(0, Ordering::Relaxed);
+    }
+
+    /// Run the agent's decision step
+    pub async fn execute(
+        &self,
+        agent: &dyn DynAgent,
+        context: &AgentContext,
+    ) -> Result<Option<AgentAction>> {
+        if self.config.enabled {
+            let size = serialized_size(context)?;
+            if size > self.config.max_context_bytes {
+                return Err(self.violation(
+                    agent,
+                    format!(
+                        "context of {} bytes exceeds the {} byte limit",
+                        size, self.config.max_context_bytes
+                    ),
+                ));
+            }
+        }
+
+        let cancel = CancellationToken::new();
+        let decision = agent.decide_cancellable(context, cancel.clone());
+        let action = if self.config.enabled {
+            match tokio::time::timeout(self.config.decision_timeout, decision).await {
+                Ok(action) => {
+                    self.consecutive_timeouts.store(0, Ordering::Relaxed);
+                    action?
+                }
+                Err(_) => {
+                    cancel.cancel();
+                    let timeouts = self.consecutive_timeouts.fetch_add(1, Ordering::Relaxed) + 1;
+                    self.count_violation(
+                        agent,
+                        &format!(
+                            "decision exceeded the {:?} timeout ({} in a row)",
+                            self.config.decision_timeout, timeouts
+                        ),
+                    );
+                    return Err(AgentError::DecisionTimeout(self.config.decision_timeout));
+                }
+            }
+        } else {
+            decision.await?
+        };
+
+        if let Some(action) = &action {
+            if self.config.enabled {
+                let size = serialized_size(action)?;
+                if size > self.config.max_response_bytes {
+                    return Err(self.violation(
+                        agent,
+                        format!(
+                            "action of {} bytes exceeds the {} byte limit",
+                            size, self.config.max_response_bytes
+                        ),
+                    ));
+                }
+            }
+
+            let required = action.required_permission();
+            if !context.permission_level.can_perform(required) {
+                return Err(AgentError::sandbox_violation(format!(
+                    "{} requires {} permission, agent has {}",
+                    action.description(),
+                    required,
+                    context.permission_level
+                )));
+            }
+        }
+
+        Ok(action)
+    }
+
+    /// Count a resource violation and build its error
+    fn violation(&self, agent: &dyn DynAgent, reason: String) -> AgentError {
+        self.count_violation(agent, &reason);
+        AgentError::sandbox_violation(reason)
+    }
+
+    /// Count a resource violation
+    fn count_violation(&self, agent: &dyn DynAgent, reason: &str) {
+        let count = self.violations.fetch_add(1, Ordering::Relaxed) + 1;
+        warn!(
+            "Agent {} sandbox violation {}/{}: {}",
+            agent.name(),
+            count,
+            self.config.max_violations,
+            reason
+        );
+    }
+}
+
+fn serialized_size<T: Serialize>(value: &T) -> Result<usize> {
+    serde_json::to_vec(value)
+        .map(|bytes| bytes.len())
+        .map_err(|e| AgentError::from(agent_wallet_core::Error::from(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Agent;
+    use solana_sdk::pubkey::Pubkey;
+
+    struct SlowAgent(Duration);
+
+    impl Agent for SlowAgent {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        async fn decide(&self, _context: &AgentContext) -> Result<Option<AgentAction>> {
+            tokio::time::sleep(self.0).await;
+            Ok(Some(AgentAction::NoOp))
+        }
+    }
+
+    fn config() -> SandboxConfig {
+        SandboxConfig {
+            decision_timeout: Duration::from_millis(20),
+            max_violations: 2,
+            ..SandboxConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_decision_times_out_and_trips() -> Result<()> {
+        let sandbox = Sandbox::new(config());
+        let agent = SlowAgent(Duration::from_secs(5));
+        let context = AgentContext::new(Pubkey::new_unique());
+
+        let result = sandbox.execute(&agent, &context).await;
+        assert!(matches!(result, Err(AgentError::DecisionTimeout(_))));
+        assert_eq!(sandbox.violations(), 1);
+        assert!(!sandbox.is_tripped());
+
+        assert!(sandbox.execute(&agent, &context).await.is_err());
+        assert!(sandbox.is_tripped());
+
+        sandbox.reset_violations();
+        let fast = SlowAgent(Duration::ZERO);
+        assert!(matches!(
+            sandbox.execute(&fast, &context).await?,
+            Some(AgentAction::NoOp)
+        ));
+        assert_eq!(sandbox.violations(), 0);
+        Ok(())
+    }
+
+    /// Agent that hangs until cancelled, keeping the token it was given
+    #[derive(Default)]
+    struct HangingAgent {
+        token: std::sync::Mutex<Option<CancellationToken>>,
+    }
+
+    impl Agent for HangingAgent {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        async fn decide(&self, _context: &AgentContext) -> Result<Option<AgentAction>> {
+            std::future::pending().await
+        }
+
+        async fn decide_cancellable(
+            &self,
+            _context: &AgentContext,
+            cancel: CancellationToken,
+        ) -> Result<Option<AgentAction>> {
+            if let Ok(mut token) = self.token.lock() {
+                *token = Some(cancel.clone());
+            }
+            cancel.cancelled().await;
+            Err(AgentError::decision("cancelled"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_decision_is_cancelled() -> Result<()> {
+        let sandbox = Sandbox::new(config());
+        let agent = HangingAgent::default();
+        let context = AgentContext::new(Pubkey::new_unique());
+
+        let result = sandbox.execute(&agent, &context).await;
+        assert!(matches!(result, Err(AgentError::DecisionTimeout(_))));
+        let token = agent.token.lock().ok().and_then(|token| token.clone());
+        assert!(token.is_some_and(|token| token.is_cancelled()));
+
+        assert!(sandbox.execute(&agent, &context).await.is_err());
+        assert_eq!(sandbox.consecutive_timeouts(), 2);
+
+        // A decision in time ends the streak
+        sandbox
+            .execute(&SlowAgent(Duration::ZERO), &context)
+            .await?;
+        assert_eq!(sandbox.consecutive_timeouts(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_size_caps() -> Result<()> {
+        let agent = SlowAgent(Duration::ZERO);
+        let context = AgentContext::new(Pubkey::new_unique());
+
+        let sandbox = Sandbox::new(SandboxConfig {
+            max_context_bytes: 16,
+            ..config()
+        });
+        assert!(sandbox.execute(&agent, &context).await.is_err());
+        assert_eq!(sandbox.violations(), 1);
+
+        let sandbox = Sandbox::new(SandboxConfig {
+            max_response_bytes: 1,
+            ..config()
+        });
+        assert!(sandbox.execute(&agent, &context).await.is_err());
+        assert_eq!(sandbox.violations(), 1);
+
+        let sandbox = Sandbox::new(SandboxConfig {
+            enabled: false,
+            max_context_bytes: 16,
+            max_response_bytes: 1,
+            ..config()
+        });
+        assert!(matches!(
+            sandbox.execute(&agent, &context).await?,
+            Some(AgentAction::NoOp)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_from_settings() {
+        let settings = SandboxSettings {
+            enabled: true,
+            memory_limit_mb: 2,
+            cpu_limit_percent: 50,
+            decision_timeout_seconds: 7,
+        };
+        let config = SandboxConfig::from(&settings);
+        assert_eq!(config.decision_timeout, Duration::from_secs(7));
+        assert_eq!(config.max_context_bytes, 2 * 1024 * 1024);
+        assert!(config.enabled);
+    }
+}
@@ -0,0 +1,1027 @@
+//! Agent specification files
+//!
+//! An [`AgentSpec`] describes an agent in a YAML or JSON file: which kind of
+//! agent to run and with what strategy, how often it decides, limit
+//! overrides, whether it only rehearses its actions, and the mints whose
+//! prices feed its context. Specs are parsed strictly: unknown fields,
+//! negative or non-finite amounts, intervals under the minimum and amounts
+//! over the spec's limits are rejected when the file is loaded, so a typo
+//! cannot silently fall back to a default.
+//!
+//! Addresses are written as base58 strings. The recipient of a periodic
+//! transfer may also be a `.sol` domain; it is resolved through the wallet
+//! with [`AgentSpec::resolve_recipients`] before the agent is built. LLM
+//! agents carry their model settings but never credentials; providers read
+//! API keys from the environment.
+//!
+//! ```yaml
+//! name: payroll
+//! agent:
+//!   type: deterministic
+//!   strategy:
+//!     kind: periodic_transfer
+//!     interval_seconds: 3600
+//!     recipient: 9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM
+//!     amount_sol: 0.1
+//! poll_interval_seconds: 60
+//! limits:
+//!   max_transactions_per_hour: 2
+//! ```
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::spec::AgentSpec;
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let spec = AgentSpec::from_file("payroll.yaml")?;
+//! let agent = spec.to_agent()?;
+//! println!("{} polls every {}s", agent.name(), spec.poll_interval_seconds);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use agent_wallet_core::sns::Recipient;
+use agent_wallet_core::token::NATIVE_MINT;
+use agent_wallet_core::Wallet;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::agent::DynAgent;
+use crate::audit::RedactionPolicy;
+use crate::deterministic::{
+    self, DcaInterval, DeterministicAgent, DeterministicStrategy, SweepTarget,
+};
+use crate::error::{AgentError, Result};
+use crate::limits::{AgentLimits, RateLimit};
+
+/// Seconds between decisions when a spec does not set them
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 10;
+
+/// Largest transaction Solana accepts, in bytes
+const MAX_TRANSACTION_SIZE: usize = 1232;
+
+/// Agent described by a specification file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentSpec {
+    /// Agent name, used as its identifier when none is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Kind of agent and its settings
+    pub agent: AgentKind,
+    /// Overrides of the default agent limits
+    #[serde(default)]
+    pub limits: LimitOverrides,
+    /// Seconds between decisions
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_seconds: u64,
+    /// Validate, sign and simulate actions without sending them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Symbols or mint addresses whose prices feed the context
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watchlist: Vec<String>,
+}
+
+fn default_poll_interval() -> u64 {
+    DEFAULT_POLL_INTERVAL_SECS
+}
+
+/// Kind of agent a spec runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AgentKind {
+    /// Rule-based agent following a strategy
+    Deterministic {
+        /// Strategy the agent follows
+        strategy: StrategySpec,
+    },
+    /// Agent delegating its decisions to a language model
+    Llm {
+        /// Model settings
+        #[serde(default)]
+        model: LlmSpec,
+    },
+}
+
+impl AgentKind {
+    /// Agent type name as the registry records it
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            AgentKind::Deterministic { .. } => "deterministic",
+            AgentKind::Llm { .. } => "llm",
+        }
+    }
+}
+
+/// [`DeterministicStrategy`] as written in a spec file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum StrategySpec {
+    /// Transfer a fixed amount of SOL at a regular interval
+    PeriodicTransfer {
+        /// Seconds between transfers
+        interval_seconds: u64,
+        /// Destination address or `.sol` domain
+        recipient: Recipient,
+        /// Amount in SOL
+        amount_sol: f64,
+    },
+    /// Transfer a fixed amount of an SPL token at a regular interval
+    PeriodicTokenTransfer {
+        /// Seconds between transfers
+        interval_seconds: u64,
+        /// Token mint address
+        #[serde(with = "pubkey_string")]
+        mint: Pubkey,
+        /// Destination address
+        #[serde(with = "pubkey_string")]
+        recipient: Pubkey,
+        /// Amount in token base units
+        amount: u64,
+    },
+    /// Put SOL above a liquidity floor to work and pull it back when needed
+    IdleSweep {
+        /// Liquid SOL to keep in the wallet
+        floor_sol: f64,
+        /// Where idle SOL is put to work
+        target: SweepTargetSpec,
+        /// Smallest excess worth sweeping, in SOL
+        min_sweep_sol: f64,
+        /// Liquid balance below which swept SOL is pulled back
+        unwind_when_below_sol: f64,
+    },
+    /// Buy a fixed amount of a token on a schedule, regardless of price
+    DollarCostAverage {
+        /// Mint sold; the native mint spends SOL
+        #[serde(with = "pubkey_string")]
+        input_mint: Pubkey,
+        /// Mint bought
+        #[serde(with = "pubkey_string")]
+        output_mint: Pubkey,
+        /// Input sold per swap, in base units
+        amount_per_interval: u64,
+        /// Seconds between buys, or a cron expression with a seconds field
+        interval: DcaInterval,
+        /// Input sold over the strategy's lifetime, in base units
+        max_total_spent: u64,
+        /// Accepted shortfall from the price feed quote, in basis points
+        #[serde(default = "default_slippage_bps")]
+        slippage_bps: u16,
+        /// No buys before this time
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start_at: Option<DateTime<Utc>>,
+        /// No buys from this time on
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end_at: Option<DateTime<Utc>>,
+    },
+    /// Swap towards target portfolio weights
+    Rebalance {
+        /// Target share of the portfolio value per mint, summing to 1
+        #[serde(with = "pubkey_weights")]
+        targets: HashMap<Pubkey, f64>,
+        /// Deviation from a target that triggers a trade, in basis points
+        tolerance_bps: u16,
+        /// Smallest trade worth its fees, in SOL
+        min_trade_sol_value: f64,
+    },
+    /// Sell a position once its price falls or rises past a threshold
+    StopLoss {
+        /// Token guarded
+        #[serde(with = "pubkey_string")]
+        mint: Pubkey,
+        /// Mint the position is sold into; SOL when not set
+        #[serde(with = "pubkey_string", default = "native_mint")]
+        exit_mint: Pubkey,
+        /// USD price the position was bought at; the first observed price
+        /// when not set
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        entry_price: Option<f64>,
+        /// Loss that triggers the exit, in percent
+        stop_loss_pct: f64,
+        /// Gain that triggers the exit, in percent
+        take_profit_pct: f64,
+        /// Measure the loss from the highest price seen instead of the entry
+        #[serde(default)]
+        trailing: bool,
+    },
+}
+
+fn native_mint() -> Pubkey {
+    NATIVE_MINT
+}
+
+fn default_slippage_bps() -> u16 {
+    100
+}
+
+/// [`SweepTarget`] as written in a spec file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SweepTargetSpec {
+    /// Deposit into a stake pool
+    StakePool(#[serde(with = "pubkey_string")] Pubkey),
+    /// Delegate to a validator vote account
+    Validator(#[serde(with = "pubkey_string")] Pubkey),
+}
+
+/// Fails for a recipient domain that has not been resolved yet
+impl TryFrom<&StrategySpec> for DeterministicStrategy {
+    type Error = AgentError;
+
+    fn try_from(spec: &StrategySpec) -> Result<Self> {
+        Ok(match *spec {
+            StrategySpec::PeriodicTransfer {
+                interval_seconds,
+                ref recipient,
+                amount_sol,
+            } => DeterministicStrategy::PeriodicTransfer {
+                interval_seconds,
+                recipient: recipient.address().ok_or_else(|| {
+                    agent_wallet_core::Error::InvalidAddress(format!(
+                        "{} has not been resolved to an address",
+                        recipient
+                    ))
+                })?,
+                amount_sol,
+            },
+            StrategySpec::PeriodicTokenTransfer {
+                interval_seconds,
+                mint,
+                recipient,
+                amount,
+            } => DeterministicStrategy::PeriodicTokenTransfer {
+                interval_seconds,
+                mint,
+                recipient,
+                amount,
+            },
+            StrategySpec::IdleSweep {
+                floor_sol,
+                target,
+                min_sweep_sol,
+                unwind_when_below_sol,
+            } => DeterministicStrategy::IdleSweep {
+                floor_sol,
+                target: match target {
+                    SweepTargetSpec::StakePool(pool) => SweepTarget::StakePool(pool),
+                    SweepTargetSpec::Validator(vote) => SweepTarget::Validator(vote),
+                },
+                min_sweep_sol,
+                unwind_when_below_sol,
+            },
+            StrategySpec::DollarCostAverage {
+                input_mint,
+                output_mint,
+                amount_per_interval,
+                ref interval,
+                max_total_spent,
+                slippage_bps,
+                start_at,
+                end_at,
+            } => DeterministicStrategy::DollarCostAverage {
+                input_mint,
+                output_mint,
+                amount_per_interval,
+                interval: interval.clone(),
+                max_total_spent,
+                slippage_bps,
+                start_at,
+                end_at,
+            },
+            StrategySpec::Rebalance {
+                ref targets,
+                tolerance_bps,
+                min_trade_sol_value,
+            } => DeterministicStrategy::Rebalance {
+                targets: targets.clone(),
+                tolerance_bps,
+                min_trade_sol_value,
+            },
+            StrategySpec::StopLoss {
+                mint,
+                exit_mint,
+                entry_price,
+                stop_loss_pct,
+                take_profit_pct,
+                trailing,
+            } => DeterministicStrategy::StopLoss {
+                mint,
+                exit_mint,
+                entry_price,
+                stop_loss_pct,
+                take_profit_pct,
+                trailing,
+            },
+        })
+    }
+}
+
+impl From<&DeterministicStrategy> for StrategySpec {
+    fn from(strategy: &DeterministicStrategy) -> Self {
+        match *strategy {
+            DeterministicStrategy::PeriodicTransfer {
+                interval_seconds,
+                recipient,
+                amount_sol,
+            } => StrategySpec::PeriodicTransfer {
+                interval_seconds,
+                recipient: recipient.into(),
+                amount_sol,
+            },
+            DeterministicStrategy::PeriodicTokenTransfer {
+                interval_seconds,
+                mint,
+                recipient,
+                amount,
+            } => StrategySpec::PeriodicTokenTransfer {
+                interval_seconds,
+                mint,
+                recipient,
+                amount,
+            },
+            DeterministicStrategy::IdleSweep {
+                floor_sol,
+                target,
+                min_sweep_sol,
+                unwind_when_below_sol,
+            } => StrategySpec::IdleSweep {
+                floor_sol,
+                target: match target {
+                    SweepTarget::StakePool(pool) => SweepTargetSpec::StakePool(pool),
+                    SweepTarget::Validator(vote) => SweepTargetSpec::Validator(vote),
+                },
+                min_sweep_sol,
+                unwind_when_below_sol,
+            },
+            DeterministicStrategy::DollarCostAverage {
+                input_mint,
+                output_mint,
+                amount_per_interval,
+                ref interval,
+                max_total_spent,
+                slippage_bps,
+                start_at,
+                end_at,
+            } => StrategySpec::DollarCostAverage {
+                input_mint,
+                output_mint,
+                amount_per_interval,
+                interval: interval.clone(),
+                max_total_spent,
+                slippage_bps,
+                start_at,
+                end_at,
+            },
+            DeterministicStrategy::Rebalance {
+                ref targets,
+                tolerance_bps,
+                min_trade_sol_value,
+            } => StrategySpec::Rebalance {
+                targets: targets.clone(),
+                tolerance_bps,
+                min_trade_sol_value,
+            },
+            DeterministicStrategy::StopLoss {
+                mint,
+                exit_mint,
+                entry_price,
+                stop_loss_pct,
+                take_profit_pct,
+                trailing,
+            } => StrategySpec::StopLoss {
+                mint,
+                exit_mint,
+                entry_price,
+                stop_loss_pct,
+                take_profit_pct,
+                trailing,
+            },
+        }
+    }
+}
+
+impl StrategySpec {
+    /// Check the strategy's parameters against `limits`
+    ///
+    /// See [`DeterministicStrategy::validate`]. A recipient domain is not
+    /// resolved for this; only the amounts and intervals around it are
+    /// checked.
+    pub fn validate(&self, limits: &AgentLimits) -> Result<()> {
+        let mut checked = self.clone();
+        if let StrategySpec::PeriodicTransfer { recipient, .. } = &mut checked {
+            if recipient.address().is_none() {
+                *recipient = Recipient::Address(Pubkey::default());
+            }
+        }
+        DeterministicStrategy::try_from(&checked)?.validate(limits)
+    }
+}
+
+/// Language model settings of an LLM agent
+///
+/// Mirrors `LlmConfig` without depending on the `llm` feature, so specs of
+/// LLM agents can be linted by builds without it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmSpec {
+    /// Model identifier
+    pub model: String,
+    /// Sampling temperature
+    pub temperature: f32,
+    /// Maximum tokens in a response
+    pub max_tokens: u32,
+    /// Attempts at obtaining a valid decision before giving up
+    pub max_attempts: u32,
+    /// Extra instructions prepended to the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Largest serialized context sent to the model, in bytes
+    pub max_context_bytes: usize,
+    /// Context fields hidden from the model
+    pub redact: RedactionPolicy,
+}
+
+impl Default for LlmSpec {
+    fn default() -> Self {
+        Self {
+            model: "gpt-4o-mini".to_string(),
+            temperature: 0.0,
+            max_tokens: 512,
+            max_attempts: 3,
+            system_prompt: None,
+            max_context_bytes: 16 * 1024,
+            redact: RedactionPolicy::for_prompts(),
+        }
+    }
+}
+
+impl LlmSpec {
+    /// Reject empty models and out-of-range sampling settings
+    pub fn validate(&self) -> Result<()> {
+        if self.model.trim().is_empty() {
+            return Err(AgentError::config("model must not be empty"));
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(AgentError::config(format!(
+                "temperature must be between 0 and 2, got {}",
+                self.temperature
+            )));
+        }
+        if self.max_tokens == 0 || self.max_attempts == 0 || self.max_context_bytes == 0 {
+            return Err(AgentError::config(
+                "max_tokens, max_attempts and max_context_bytes must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "llm")]
+impl From<&LlmSpec> for crate::llm::LlmConfig {
+    fn from(spec: &LlmSpec) -> Self {
+        Self {
+            model: spec.model.clone(),
+            temperature: spec.temperature,
+            max_tokens: spec.max_tokens,
+            max_attempts: spec.max_attempts,
+            system_prompt: spec.system_prompt.clone(),
+            max_context_bytes: spec.max_context_bytes,
+            redact: spec.redact,
+        }
+    }
+}
+
+/// Limits a spec overrides; unset fields keep their defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitOverrides {
+    /// Maximum SOL per transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sol_per_transaction: Option<f64>,
+    /// Maximum tokens per transaction, in base units
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens_per_transaction: Option<u64>,
+    /// Maximum transactions per hour
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_transactions_per_hour: Option<u32>,
+    /// Maximum transaction size in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_transaction_size: Option<usize>,
+    /// Shortest interval a periodic strategy may act on, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_interval_seconds: Option<u64>,
+}
+
+impl LimitOverrides {
+    /// Apply the overrides to `limits`
+    pub fn apply(&self, mut limits: AgentLimits) -> AgentLimits {
+        if let Some(sol) = self.max_sol_per_transaction {
+            limits.max_sol_per_transaction = sol;
+        }
+        if let Some(tokens) = self.max_tokens_per_transaction {
+            limits.max_tokens_per_transaction = tokens;
+        }
+        if let Some(per_hour) = self.max_transactions_per_hour {
+            limits.max_transactions_per_hour = per_hour;
+        }
+        if let Some(size) = self.max_transaction_size {
+            limits.max_transaction_size = size;
+        }
+        if let Some(seconds) = self.min_interval_seconds {
+            limits.min_interval_seconds = seconds;
+        }
+        limits
+    }
+
+    /// Reject zero, negative and out-of-range limits
+    pub fn validate(&self) -> Result<()> {
+        if let Some(sol) = self.max_sol_per_transaction {
+            deterministic::positive_sol("max_sol_per_transaction", sol)?;
+        }
+        if self.max_tokens_per_transaction == Some(0) || self.max_transactions_per_hour == Some(0) {
+            return Err(AgentError::config(
+                "max_tokens_per_transaction and max_transactions_per_hour must be positive",
+            ));
+        }
+        if let Some(size) = self.max_transaction_size {
+            if size == 0 || size > MAX_TRANSACTION_SIZE {
+                return Err(AgentError::config(format!(
+                    "max_transaction_size must be between 1 and {} bytes",
+                    MAX_TRANSACTION_SIZE
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AgentSpec {
+    /// Spec running a deterministic strategy with default settings
+    pub fn deterministic(strategy: &DeterministicStrategy) -> Self {
+        Self {
+            name: None,
+            agent: AgentKind::Deterministic {
+                strategy: strategy.into(),
+            },
+            limits: LimitOverrides::default(),
+            poll_interval_seconds: DEFAULT_POLL_INTERVAL_SECS,
+            dry_run: false,
+            watchlist: Vec::new(),
+        }
+    }
+
+    /// Load and validate a spec, as YAML or JSON by file extension
+    ///
+    /// Files without a `.json` extension are read as YAML, which also
+    /// accepts JSON.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AgentError::config(format!("{}: {}", path.display(), e)))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let spec = if is_json {
+            Self::from_json(&contents)
+        } else {
+            Self::from_yaml(&contents)
+        };
+        spec.map_err(|e| match e {
+            AgentError::Config(message) => {
+                AgentError::config(format!("{}: {}", path.display(), message))
+            }
+            other => other,
+        })
+    }
+
+    /// Parse and validate a YAML spec
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        let spec: Self =
+            serde_yaml::from_str(contents).map_err(|e| AgentError::config(e.to_string()))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Parse and validate a JSON spec
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let spec: Self =
+            serde_json::from_str(contents).map_err(|e| AgentError::config(e.to_string()))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Serialize the spec as YAML
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(|e| AgentError::config(e.to_string()))
+    }
+
+    /// Check every value against its allowed range
+    pub fn validate(&self) -> Result<()> {
+        self.limits.validate()?;
+        if self.poll_interval_seconds == 0 {
+            return Err(AgentError::config("poll_interval_seconds must be positive"));
+        }
+        if self
+            .name
+            .as_deref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err(AgentError::config("name must not be empty"));
+        }
+        if self.watchlist.iter().any(|entry| entry.trim().is_empty()) {
+            return Err(AgentError::config("watchlist entries must not be empty"));
+        }
+        match &self.agent {
+            AgentKind::Deterministic { strategy } => strategy.validate(&self.limits())?,
+            AgentKind::Llm { model } => model.validate()?,
+        }
+        Ok(())
+    }
+
+    /// Agent limits with the spec's overrides applied
+    pub fn limits(&self) -> AgentLimits {
+        self.limits.apply(AgentLimits::default())
+    }
+
+    /// Hourly action limit, when the spec overrides it
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.limits
+            .max_transactions_per_hour
+            .map(|max_decisions| RateLimit {
+                max_decisions,
+                window: Duration::from_secs(3600),
+            })
+    }
+
+    /// Strategy of a deterministic agent
+    ///
+    /// `None` for LLM agents and while a recipient domain is unresolved.
+    pub fn strategy(&self) -> Option<DeterministicStrategy> {
+        match &self.agent {
+            AgentKind::Deterministic { strategy } => strategy.try_into().ok(),
+            AgentKind::Llm { .. } => None,
+        }
+    }
+
+    /// Replace `.sol` recipient domains with the addresses they resolve to
+    ///
+    /// A domain that is not registered fails with
+    /// [`agent_wallet_core::Error::InvalidAddress`] naming it.
+    pub async fn resolve_recipients(&mut self, wallet: &Wallet) -> Result<()> {
+        if let AgentKind::Deterministic {
+            strategy: StrategySpec::PeriodicTransfer { recipient, .. },
+        } = &mut self.agent
+        {
+            if let Recipient::Domain(domain) = recipient {
+                *recipient = Recipient::Address(wallet.resolve_recipient(domain).await?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the agent the spec describes
+    ///
+    /// LLM agents need a provider and are built with `to_llm_agent`; recipient
+    /// domains must be resolved first.
+    pub fn to_agent(&self) -> Result<Box<dyn DynAgent>> {
+        match &self.agent {
+            AgentKind::Deterministic { strategy } => {
+                let mut agent = DeterministicAgent::try_new(strategy.try_into()?, &self.limits())?;
+                if let Some(name) = &self.name {
+                    agent = agent.with_name(name.clone());
+                }
+                Ok(Box::new(agent))
+            }
+            AgentKind::Llm { .. } => Err(AgentError::config(
+                "LLM agents need a model provider and cannot be built from a spec alone",
+            )),
+        }
+    }
+
+    /// Build the LLM agent the spec describes on top of `provider`
+    #[cfg(feature = "llm")]
+    pub fn to_llm_agent(
+        &self,
+        provider: std::sync::Arc<dyn crate::llm::LlmProvider>,
+    ) -> Result<crate::llm::LlmAgent> {
+        match &self.agent {
+            AgentKind::Llm { model } => {
+                let mut agent = crate::llm::LlmAgent::new(provider, model.into());
+                if let Some(name) = &self.name {
+                    agent = agent.with_name(name.clone());
+                }
+                Ok(agent)
+            }
+            AgentKind::Deterministic { .. } => Err(AgentError::config(
+                "spec describes a deterministic agent, not an LLM agent",
+            )),
+        }
+    }
+}
+
+/// Serde for addresses as base58 strings, as spec files write them
+mod pubkey_string {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(key: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(key)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Serde for weights keyed by base58 addresses
+mod pubkey_weights {
+    use std::collections::HashMap;
+
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(
+        weights: &HashMap<Pubkey, f64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut sorted: Vec<_> = weights
+            .iter()
+            .map(|(key, weight)| (key.to_string(), *weight))
+            .collect();
+        sorted.sort_by(|a, b| a.0.cmp(&b.0));
+        serializer.collect_map(sorted)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<Pubkey, f64>, D::Error> {
+        HashMap::<String, f64>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, weight)| Ok((key.parse().map_err(D::Error::custom)?, weight)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIODIC_YAML: &str = include_str!("../tests/fixtures/periodic_transfer.yaml");
+    const SWEEP_JSON: &str = include_str!("../tests/fixtures/idle_sweep.json");
+    const LLM_YAML: &str = include_str!("../tests/fixtures/llm.yaml");
+
+    #[test]
+    fn test_sample_specs_parse() -> Result<()> {
+        let periodic = AgentSpec::from_yaml(PERIODIC_YAML)?;
+        assert_eq!(periodic.name.as_deref(), Some("payroll"));
+        assert_eq!(periodic.poll_interval_seconds, 60);
+        assert!(periodic.dry_run);
+        assert_eq!(periodic.watchlist, vec!["SOL".to_string()]);
+        assert!(matches!(
+            periodic.strategy(),
+            Some(DeterministicStrategy::PeriodicTransfer {
+                interval_seconds: 3600,
+                ..
+            })
+        ));
+        assert_eq!(periodic.limits().max_transactions_per_hour, 2);
+        assert_eq!(
+            periodic.limits().max_sol_per_transaction,
+            AgentLimits::default().max_sol_per_transaction
+        );
+        assert_eq!(
+            periodic.rate_limit().map(|limit| limit.max_decisions),
+            Some(2)
+        );
+        assert_eq!(periodic.to_agent()?.name(), "payroll");
+
+        let sweep = AgentSpec::from_json(SWEEP_JSON)?;
+        assert_eq!(sweep.poll_interval_seconds, DEFAULT_POLL_INTERVAL_SECS);
+        assert!(sweep.rate_limit().is_none());
+        assert!(matches!(
+            sweep.strategy(),
+            Some(DeterministicStrategy::IdleSweep {
+                target: SweepTarget::Validator(_),
+                ..
+            })
+        ));
+
+        let llm = AgentSpec::from_yaml(LLM_YAML)?;
+        assert_eq!(llm.agent.type_name(), "llm");
+        assert!(matches!(
+            &llm.agent,
+            AgentKind::Llm { model } if model.model == "gpt-4o" && model.max_attempts == 3
+        ));
+        assert!(llm.to_agent().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_specs_round_trip() -> Result<()> {
+        for spec in [
+            AgentSpec::from_yaml(PERIODIC_YAML)?,
+            AgentSpec::from_json(SWEEP_JSON)?,
+            AgentSpec::from_yaml(LLM_YAML)?,
+        ] {
+            assert_eq!(AgentSpec::from_yaml(&spec.to_yaml()?)?, spec);
+            let json = serde_json::to_string(&spec).map_err(agent_wallet_core::Error::from)?;
+            assert_eq!(AgentSpec::from_json(&json)?, spec);
+        }
+
+        // Strategies survive the trip through their spec form
+        let strategy = DeterministicStrategy::PeriodicTokenTransfer {
+            interval_seconds: 60,
+            mint: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            amount: 5,
+        };
+        let spec = AgentSpec::deterministic(&strategy);
+        assert_eq!(
+            AgentSpec::from_yaml(&spec.to_yaml()?)?.strategy(),
+            Some(strategy)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_recipient_domain_needs_resolving() -> Result<()> {
+        let yaml = "agent:\n  type: deterministic\n  strategy:\n    kind: periodic_transfer\n    interval_seconds: 60\n    recipient: alice.sol\n    amount_sol: 0.1\n";
+        let mut spec = AgentSpec::from_yaml(yaml)?;
+        assert!(matches!(
+            &spec.agent,
+            AgentKind::Deterministic {
+                strategy: StrategySpec::PeriodicTransfer {
+                    recipient: Recipient::Domain(domain),
+                    ..
+                }
+            } if domain == "alice.sol"
+        ));
+        assert_eq!(AgentSpec::from_yaml(&spec.to_yaml()?)?, spec);
+
+        // The agent is only built once the domain is an address
+        assert!(spec.strategy().is_none());
+        let error = spec
+            .to_agent()
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+        assert!(error.contains("alice.sol"));
+
+        let owner = Pubkey::new_unique();
+        if let AgentKind::Deterministic {
+            strategy: StrategySpec::PeriodicTransfer { recipient, .. },
+        } = &mut spec.agent
+        {
+            *recipient = Recipient::Address(owner);
+        }
+        assert!(matches!(
+            spec.strategy(),
+            Some(DeterministicStrategy::PeriodicTransfer { recipient, .. }) if recipient == owner
+        ));
+
+        // Anything else is refused when the file is loaded
+        assert!(AgentSpec::from_yaml(&yaml.replace("alice.sol", "alice")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_specs_are_rejected() -> Result<()> {
+        let recipient = Pubkey::new_unique();
+        let periodic = |fields: &str| {
+            format!(
+                "agent:\n  type: deterministic\n  strategy:\n    kind: periodic_transfer\n    recipient: {}\n{}",
+                recipient, fields
+            )
+        };
+
+        for (spec, reason) in [
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.1\n"),
+                "",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: -0.1\n"),
+                "amount_sol",
+            ),
+            (
+                periodic("    interval_seconds: 0\n    amount_sol: 0.1\n"),
+                "interval_seconds",
+            ),
+            (
+                periodic("    interval_seconds: 5\n    amount_sol: 0.1\n"),
+                "interval_seconds",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.5\nlimits:\n  max_sol_per_transaction: 0.25\n"),
+                "max_sol_per_transaction",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.1\n    amount_lamports: 1\n"),
+                "amount_lamports",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.1\npoll_interval_seconds: 0\n"),
+                "poll_interval_seconds",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.1\nlimits:\n  max_sol_per_transaction: -1\n"),
+                "max_sol_per_transaction",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.1\nlimits:\n  max_spend: 1\n"),
+                "max_spend",
+            ),
+            (
+                periodic("    interval_seconds: 60\n    amount_sol: 0.1\nintervall: 5\n"),
+                "intervall",
+            ),
+            (
+                "agent:\n  type: llm\n  model:\n    temperature: 3.5\n".to_string(),
+                "temperature",
+            ),
+            (
+                "agent:\n  type: llm\n  model:\n    api_key: sk-secret\n".to_string(),
+                "api_key",
+            ),
+        ] {
+            match AgentSpec::from_yaml(&spec) {
+                Ok(_) => assert!(reason.is_empty(), "accepted spec with bad {}", reason),
+                Err(AgentError::Config(message)) => {
+                    assert!(!reason.is_empty(), "rejected valid spec: {}", message);
+                    assert!(message.contains(reason), "{} does not name {}", message, reason);
+                }
+                Err(other) => return Err(other),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_strategy_specs_parse() -> Result<()> {
+        let dca = |interval: &str| {
+            format!(
+                "agent:\n  type: deterministic\n  strategy:\n    kind: dollar_cost_average\n    \
+                 input_mint: So11111111111111111111111111111111111111112\n    \
+                 output_mint: EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v\n    \
+                 amount_per_interval: 100000000\n    max_total_spent: 1000000000\n    \
+                 interval: {}\n",
+                interval
+            )
+        };
+
+        let daily = AgentSpec::from_yaml(&dca("86400"))?;
+        assert!(matches!(
+            daily.strategy(),
+            Some(DeterministicStrategy::DollarCostAverage {
+                interval: DcaInterval::Seconds(86_400),
+                slippage_bps: 100,
+                ..
+            })
+        ));
+        let mondays = AgentSpec::from_yaml(&dca("\"0 0 9 * * Mon\""))?;
+        assert_eq!(AgentSpec::from_yaml(&mondays.to_yaml()?)?, mondays);
+
+        let rebalance = DeterministicStrategy::Rebalance {
+            targets: HashMap::from([(Pubkey::new_unique(), 0.6), (Pubkey::new_unique(), 0.4)]),
+            tolerance_bps: 250,
+            min_trade_sol_value: 0.05,
+        };
+        let spec = AgentSpec::deterministic(&rebalance);
+        assert_eq!(
+            AgentSpec::from_yaml(&spec.to_yaml()?)?.strategy(),
+            Some(rebalance)
+        );
+
+        let guard = AgentSpec::from_yaml(&format!(
+            "agent:\n  type: deterministic\n  strategy:\n    kind: stop_loss\n    \
+             mint: {}\n    stop_loss_pct: 8\n    take_profit_pct: 25\n    trailing: true\n",
+            Pubkey::new_unique()
+        ))?;
+        assert!(matches!(
+            guard.strategy(),
+            Some(DeterministicStrategy::StopLoss {
+                exit_mint: NATIVE_MINT,
+                entry_price: None,
+                trailing: true,
+                ..
+            })
+        ));
+
+        for bad in ["0", "\"every monday\""] {
+            assert!(matches!(
+                AgentSpec::from_yaml(&dca(bad)),
+                Err(AgentError::Config(_))
+            ));
+        }
+        Ok(())
+    }
+}
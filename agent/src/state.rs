@@ -0,0 +1,167 @@
+//! Persistent agent state
+//!
+//! Strategies that act on "time since the last action" or count what they
+//! have spent would start over after a restart and fire again at once. An
+//! [`AgentStateStore`] keeps each agent's state as a JSON value under its
+//! [`Agent::state_key`](crate::agent::Agent::state_key); the
+//! [`AgentRunner`](crate::runner::AgentRunner) restores it when it starts
+//! and saves it after every decision.
+//!
+//! [`FileStateStore`] writes the state into each agent's workspace, one file
+//! per agent replaced atomically, so agents never overwrite each other. A
+//! state file that cannot be parsed is moved aside and the agent starts
+//! fresh.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::state::{AgentStateStore, FileStateStore};
+//! use agent_wallet_agent::workspace::AgentWorkspaces;
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let store = FileStateStore::new(AgentWorkspaces::open("/home/me/.agent-wallet")?);
+//! store.put("payroll", serde_json::json!({ "last_fired": null }))?;
+//! assert!(store.get("payroll")?.is_some());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+use tracing::error;
+
+use crate::error::Result;
+use crate::sync::lock;
+use crate::workspace::{AgentWorkspaces, ArtifactKind};
+
+/// Suffix of a state file moved aside because it could not be parsed
+const CORRUPT_SUFFIX: &str = ".corrupt";
+
+/// Storage of agent state between runs, keyed by agent
+pub trait AgentStateStore: Send + Sync {
+    /// State saved under `key`, `None` if there is none
+    fn get(&self, key: &str) -> Result<Option<Value>>;
+
+    /// Replace the state saved under `key`
+    fn put(&self, key: &str, value: Value) -> Result<()>;
+
+    /// Write out anything still buffered, e.g. before shutting down
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// State store writing each agent's state into its workspace
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    workspaces: AgentWorkspaces,
+}
+
+impl FileStateStore {
+    /// Store state in the workspaces under `workspaces`
+    pub fn new(workspaces: AgentWorkspaces) -> Self {
+        Self { workspaces }
+    }
+}
+
+impl AgentStateStore for FileStateStore {
+    fn get(&self, key: &str) -> Result<Option<Value>> {
+        let workspace = self.workspaces.register(key)?;
+        let path = workspace.artifact(ArtifactKind::State);
+        let Some(bytes) = workspace.read(&path)? else {
+            return Ok(None);
+        };
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                let mut aside = path.clone().into_os_string();
+                aside.push(CORRUPT_SUFFIX);
+                error!(
+                    "State of agent {} at {} is corrupted ({}); moving it to {} and starting fresh",
+                    key,
+                    path.display(),
+                    e,
+                    aside.to_string_lossy()
+                );
+                workspace.write(&aside, &bytes)?;
+                workspace.remove(&path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn put(&self, key: &str, value: Value) -> Result<()> {
+        let workspace = self.workspaces.register(key)?;
+        workspace.write_json(workspace.artifact(ArtifactKind::State), &value)
+    }
+}
+
+/// State store kept in memory, for tests and short-lived agents
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    states: Mutex<HashMap<String, Value>>,
+}
+
+impl MemoryStateStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AgentStateStore for MemoryStateStore {
+    fn get(&self, key: &str) -> Result<Option<Value>> {
+        Ok(lock(&self.states).get(key).cloned())
+    }
+
+    fn put(&self, key: &str, value: Value) -> Result<()> {
+        lock(&self.states).insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AgentError;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_agents_keep_separate_state() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let store = FileStateStore::new(AgentWorkspaces::open(dir.path())?);
+        assert_eq!(store.get("alpha")?, None);
+
+        store.put("alpha", json!({ "step": 1 }))?;
+        store.put("beta", json!({ "step": 7 }))?;
+        store.put("alpha", json!({ "step": 2 }))?;
+
+        let reopened = FileStateStore::new(AgentWorkspaces::open(dir.path())?);
+        assert_eq!(reopened.get("alpha")?, Some(json!({ "step": 2 })));
+        assert_eq!(reopened.get("beta")?, Some(json!({ "step": 7 })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_state_starts_fresh() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspaces = AgentWorkspaces::open(dir.path())?;
+        let workspace = workspaces.register("alpha")?;
+        workspace.write(workspace.artifact(ArtifactKind::State), b"{\"step\":")?;
+
+        let store = FileStateStore::new(workspaces);
+        assert_eq!(store.get("alpha")?, None);
+        assert_eq!(
+            workspace.read("state.json.corrupt")?,
+            Some(b"{\"step\":".to_vec())
+        );
+
+        // The next save starts a clean file
+        store.put("alpha", json!({ "step": 1 }))?;
+        assert_eq!(store.get("alpha")?, Some(json!({ "step": 1 })));
+        Ok(())
+    }
+}
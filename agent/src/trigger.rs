@@ -0,0 +1,496 @@
+//! Externally triggered decisions
+//!
+//! Besides its regular ticks, a runner can accept events from outside (a
+//! webhook, a price alert) through a [`TriggerHandle`]. Each accepted event
+//! queues a decision tick whose context carries the [`TriggerPayload`].
+//! Triggers are rate limited, size bounded and validated against the
+//! agent's [`TriggerSchema`] before they are queued.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::trigger::{self, FieldKind, TriggerConfig, TriggerSchema};
+//! use agent_wallet_core::TriggerPayload;
+//!
+//! # async fn example() -> agent_wallet_agent::Result<()> {
+//! let config = TriggerConfig {
+//!     schema: Some(TriggerSchema::new().with_field("amount_sol", FieldKind::Number)),
+//!     ..Default::default()
+//! };
+//! let (handle, mut receiver) = trigger::channel("alerts", config);
+//!
+//! handle.fire(TriggerPayload::new("tradingview", serde_json::json!({ "amount_sol": 0.5 })))?;
+//! let payload = receiver.recv().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::mpsc;
+
+use agent_wallet_core::sol::Lamports;
+pub use agent_wallet_core::types::TriggerPayload;
+
+use crate::agent::AgentId;
+use crate::decision::AgentAction;
+use crate::error::{AgentError, Result};
+use crate::limits::RateLimit;
+use crate::sync::lock;
+use crate::{DEFAULT_TRIGGER_MAX_PAYLOAD_BYTES, DEFAULT_TRIGGER_RATE_LIMIT_PER_MINUTE};
+
+/// Number of accepted triggers that may wait for the runner
+const DEFAULT_TRIGGER_QUEUE_CAPACITY: usize = 32;
+
+/// JSON type expected for a payload field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    /// Any string
+    String,
+    /// Any number
+    Number,
+    /// A boolean
+    Bool,
+    /// A base58 public key string
+    Pubkey,
+}
+
+impl FieldKind {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            FieldKind::String => value.is_string(),
+            FieldKind::Number => value.is_number(),
+            FieldKind::Bool => value.is_boolean(),
+            FieldKind::Pubkey => value.as_str().is_some_and(|s| Pubkey::from_str(s).is_ok()),
+        }
+    }
+}
+
+/// Fields a trigger payload must contain
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerSchema {
+    /// Required fields and their types
+    pub fields: BTreeMap<String, FieldKind>,
+    /// Whether fields outside the schema are accepted
+    #[serde(default)]
+    pub allow_unknown_fields: bool,
+}
+
+impl TriggerSchema {
+    /// Create an empty schema
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require a field of the given type
+    pub fn with_field(mut self, name: impl Into<String>, kind: FieldKind) -> Self {
+        self.fields.insert(name.into(), kind);
+        self
+    }
+
+    /// Check that payload data matches the schema
+    pub fn validate(&self, data: &Value) -> Result<()> {
+        let object = data
+            .as_object()
+            .ok_or_else(|| AgentError::invalid_trigger("Payload must be a JSON object"))?;
+
+        for (name, kind) in &self.fields {
+            match object.get(name) {
+                None => {
+                    return Err(AgentError::invalid_trigger(format!(
+                        "Missing field '{}'",
+                        name
+                    )))
+                }
+                Some(value) if !kind.matches(value) => {
+                    return Err(AgentError::invalid_trigger(format!(
+                        "Field '{}' must be a {:?}",
+                        name, kind
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+
+        if !self.allow_unknown_fields {
+            if let Some(name) = object.keys().find(|name| !self.fields.contains_key(*name)) {
+                return Err(AgentError::invalid_trigger(format!(
+                    "Unexpected field '{}'",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits applied to an agent's triggers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriggerConfig {
+    /// Maximum accepted triggers per window
+    pub rate_limit: RateLimit,
+    /// Maximum size of the serialized payload data
+    pub max_payload_bytes: usize,
+    /// Accepted triggers that may wait for the runner
+    pub queue_capacity: usize,
+    /// Payload schema; the runner falls back to the agent's own
+    pub schema: Option<TriggerSchema>,
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self {
+            rate_limit: RateLimit::per_minute(DEFAULT_TRIGGER_RATE_LIMIT_PER_MINUTE),
+            max_payload_bytes: DEFAULT_TRIGGER_MAX_PAYLOAD_BYTES,
+            queue_capacity: DEFAULT_TRIGGER_QUEUE_CAPACITY,
+            schema: None,
+        }
+    }
+}
+
+/// Sends external events to a runner
+#[derive(Debug, Clone)]
+pub struct TriggerHandle {
+    agent_id: AgentId,
+    config: Arc<TriggerConfig>,
+    sender: mpsc::Sender<TriggerPayload>,
+    accepted: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl TriggerHandle {
+    /// Agent the triggers are delivered to
+    pub fn agent_id(&self) -> &AgentId {
+        &self.agent_id
+    }
+
+    /// Trigger limits
+    pub fn config(&self) -> &TriggerConfig {
+        &self.config
+    }
+
+    /// Check a payload's size and schema without queueing it
+    pub fn validate(&self, payload: &TriggerPayload) -> Result<()> {
+        let size = payload.size();
+        if size > self.config.max_payload_bytes {
+            return Err(AgentError::invalid_trigger(format!(
+                "Payload is {} bytes, limit is {}",
+                size, self.config.max_payload_bytes
+            )));
+        }
+        match &self.config.schema {
+            Some(schema) => schema.validate(&payload.data),
+            None => Ok(()),
+        }
+    }
+
+    /// Queue a decision tick carrying the payload
+    pub fn fire(&self, payload: TriggerPayload) -> Result<()> {
+        self.validate(&payload)?;
+        self.record_trigger()?;
+
+        self.sender.try_send(payload).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => AgentError::LimitExceeded(format!(
+                "Trigger queue of agent {} is full",
+                self.agent_id
+            )),
+            mpsc::error::TrySendError::Closed(_) => AgentError::invalid_state(format!(
+                "Agent {} is not accepting triggers",
+                self.agent_id
+            )),
+        })
+    }
+
+    /// Count a trigger against the rate limit, rejecting it when exhausted
+    fn record_trigger(&self) -> Result<()> {
+        let limit = self.config.rate_limit;
+        let now = Instant::now();
+        let mut accepted = lock(&self.accepted);
+
+        while accepted
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= limit.window)
+        {
+            accepted.pop_front();
+        }
+        if accepted.len() >= limit.max_decisions as usize {
+            return Err(AgentError::LimitExceeded(format!(
+                "Agent {} accepts at most {} triggers per {:?}",
+                self.agent_id, limit.max_decisions, limit.window
+            )));
+        }
+
+        accepted.push_back(now);
+        Ok(())
+    }
+}
+
+/// Receiving end of an agent's triggers
+#[derive(Debug)]
+pub struct TriggerReceiver {
+    receiver: mpsc::Receiver<TriggerPayload>,
+}
+
+impl TriggerReceiver {
+    /// Wait for the next trigger; `None` once every handle is dropped
+    pub async fn recv(&mut self) -> Option<TriggerPayload> {
+        self.receiver.recv().await
+    }
+
+    /// Take a queued trigger without waiting
+    pub fn try_recv(&mut self) -> Option<TriggerPayload> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Create a trigger channel for an agent
+pub fn channel(
+    agent_id: impl Into<AgentId>,
+    config: TriggerConfig,
+) -> (TriggerHandle, TriggerReceiver) {
+    let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+    let handle = TriggerHandle {
+        agent_id: agent_id.into(),
+        config: Arc::new(config),
+        sender,
+        accepted: Arc::new(Mutex::new(VecDeque::new())),
+    };
+    (handle, TriggerReceiver { receiver })
+}
+
+/// Action parameter given either directly or by a payload field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Param<T> {
+    /// Read the value from a payload field
+    Field {
+        /// Payload field name
+        field: String,
+    },
+    /// Fixed value
+    Value(T),
+}
+
+/// Maps trigger payload fields to the parameters of an action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TriggerHandler {
+    /// Transfer SOL
+    TransferSol {
+        /// Destination address
+        recipient: Param<Pubkey>,
+        /// Amount in SOL
+        amount_sol: Param<f64>,
+    },
+    /// Transfer an SPL token
+    TransferToken {
+        /// Token mint address
+        mint: Pubkey,
+        /// Destination address
+        recipient: Param<Pubkey>,
+        /// Amount in token base units
+        amount: Param<u64>,
+    },
+}
+
+impl TriggerHandler {
+    /// Schema requiring every payload field the handler reads
+    pub fn schema(&self) -> TriggerSchema {
+        let mut schema = TriggerSchema::new();
+        let (recipient, amount) = match self {
+            TriggerHandler::TransferSol {
+                recipient,
+                amount_sol,
+            } => (recipient, field_name(amount_sol)),
+            TriggerHandler::TransferToken {
+                recipient, amount, ..
+            } => (recipient, field_name(amount)),
+        };
+        if let Param::Field { field } = recipient {
+            schema = schema.with_field(field.clone(), FieldKind::Pubkey);
+        }
+        if let Some(field) = amount {
+            schema = schema.with_field(field.to_string(), FieldKind::Number);
+        }
+        schema
+    }
+
+    /// Build the action for a payload
+    pub fn action(&self, payload: &TriggerPayload) -> Result<AgentAction> {
+        match self {
+            TriggerHandler::TransferSol {
+                recipient,
+                amount_sol,
+            } => {
+                let amount_sol = match amount_sol {
+                    Param::Value(value) => *value,
+                    Param::Field { field } => field_value(payload, field)?
+                        .as_f64()
+                        .ok_or_else(|| invalid_field(field, "a number"))?,
+                };
+                if !amount_sol.is_finite() || amount_sol <= 0.0 {
+                    return Err(AgentError::invalid_trigger(
+                        "Transfer amount must be positive",
+                    ));
+                }
+                Ok(AgentAction::TransferSol {
+                    to: resolve_pubkey(recipient, payload)?,
+                    amount: Lamports::from_sol_f64_rounded(amount_sol)
+                        .map_err(|e| AgentError::invalid_trigger(e.to_string()))?,
+                    memo: None,
+                })
+            }
+            TriggerHandler::TransferToken {
+                mint,
+                recipient,
+                amount,
+            } => {
+                let amount = match amount {
+                    Param::Value(value) => *value,
+                    Param::Field { field } => field_value(payload, field)?
+                        .as_u64()
+                        .ok_or_else(|| invalid_field(field, "a whole number"))?,
+                };
+                if amount == 0 {
+                    return Err(AgentError::invalid_trigger(
+                        "Transfer amount must be positive",
+                    ));
+                }
+                Ok(AgentAction::TransferToken {
+                    mint: *mint,
+                    to: resolve_pubkey(recipient, payload)?,
+                    amount,
+                    memo: None,
+                })
+            }
+        }
+    }
+}
+
+fn field_name<T>(param: &Param<T>) -> Option<&str> {
+    match param {
+        Param::Field { field } => Some(field),
+        Param::Value(_) => None,
+    }
+}
+
+fn field_value<'a>(payload: &'a TriggerPayload, field: &str) -> Result<&'a Value> {
+    payload
+        .data
+        .get(field)
+        .ok_or_else(|| AgentError::invalid_trigger(format!("Missing field '{}'", field)))
+}
+
+fn invalid_field(field: &str, expected: &str) -> AgentError {
+    AgentError::invalid_trigger(format!("Field '{}' must be {}", field, expected))
+}
+
+fn resolve_pubkey(param: &Param<Pubkey>, payload: &TriggerPayload) -> Result<Pubkey> {
+    match param {
+        Param::Value(pubkey) => Ok(*pubkey),
+        Param::Field { field } => field_value(payload, field)?
+            .as_str()
+            .and_then(|s| Pubkey::from_str(s).ok())
+            .ok_or_else(|| invalid_field(field, "a public key")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_rate_limit_rejects_excess_triggers() -> Result<()> {
+        let config = TriggerConfig {
+            rate_limit: RateLimit {
+                max_decisions: 2,
+                window: Duration::from_secs(60),
+            },
+            ..Default::default()
+        };
+        let (handle, mut receiver) = channel("agent-1", config);
+
+        handle.fire(TriggerPayload::new("test", json!({})))?;
+        handle.fire(TriggerPayload::new("test", json!({})))?;
+        let rejected = handle.fire(TriggerPayload::new("test", json!({})));
+        assert!(matches!(rejected, Err(AgentError::LimitExceeded(_))));
+
+        assert!(receiver.recv().await.is_some());
+        assert!(receiver.recv().await.is_some());
+        assert!(receiver.try_recv().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_validation_failures() {
+        let config = TriggerConfig {
+            max_payload_bytes: 64,
+            schema: Some(
+                TriggerSchema::new()
+                    .with_field("recipient", FieldKind::Pubkey)
+                    .with_field("amount_sol", FieldKind::Number),
+            ),
+            ..Default::default()
+        };
+        let (handle, mut receiver) = channel("agent-1", config);
+        let recipient = Pubkey::new_unique().to_string();
+
+        let invalid = [
+            json!({ "recipient": recipient }),
+            json!({ "recipient": "not-a-key", "amount_sol": 1.0 }),
+            json!({ "recipient": recipient, "amount_sol": "1.0" }),
+            json!({ "recipient": recipient, "amount_sol": 1.0, "extra": true }),
+            json!({ "recipient": recipient, "amount_sol": 1.0, "padding": "x".repeat(64) }),
+            json!([recipient]),
+        ];
+        for data in invalid {
+            let result = handle.fire(TriggerPayload::new("test", data));
+            assert!(matches!(result, Err(AgentError::InvalidTrigger(_))));
+        }
+        assert!(receiver.try_recv().is_none());
+
+        let valid = json!({ "recipient": recipient, "amount_sol": 1.0 });
+        assert!(handle.fire(TriggerPayload::new("test", valid)).is_ok());
+    }
+
+    #[test]
+    fn test_handler_maps_payload_fields() -> Result<()> {
+        let recipient = Pubkey::new_unique();
+        let handler = TriggerHandler::TransferSol {
+            recipient: Param::Field {
+                field: "to".to_string(),
+            },
+            amount_sol: Param::Field {
+                field: "amount".to_string(),
+            },
+        };
+        let schema = handler.schema();
+        assert_eq!(schema.fields.get("to"), Some(&FieldKind::Pubkey));
+        assert_eq!(schema.fields.get("amount"), Some(&FieldKind::Number));
+
+        let payload = TriggerPayload::new(
+            "test",
+            json!({ "to": recipient.to_string(), "amount": 0.25 }),
+        );
+        let action = handler.action(&payload)?;
+        assert!(matches!(
+            action,
+            AgentAction::TransferSol { to, amount: Lamports(250_000_000), .. } if to == recipient
+        ));
+
+        let negative = TriggerPayload::new(
+            "test",
+            json!({ "to": recipient.to_string(), "amount": -1.0 }),
+        );
+        assert!(handler.action(&negative).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,594 @@
+//! Per-agent working directories
+//!
+//! Every registered agent owns an isolated directory under
+//! `<data_dir>/agents/<agent_id>/` holding a `workspace.json` manifest and
+//! the agent's artifacts: persisted state, context snapshots, the
+//! idempotency journal, the outbox, stats, the audit log and dead letters. Artifacts are only reachable
+//! through an [`AgentWorkspace`], which resolves relative paths inside the
+//! agent's directory and rejects anything that would escape it, so two
+//! agents writing an artifact of the same name never see each other's data.
+//!
+//! Earlier releases kept artifacts as flat files named
+//! `<agent_id>.<artifact>` directly in `<data_dir>/agents/`. Opening the
+//! workspace root moves any such files into their agent's workspace.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_agent::workspace::{AgentWorkspaces, ArtifactKind, Retention};
+//!
+//! # fn main() -> agent_wallet_agent::Result<()> {
+//! let workspaces = AgentWorkspaces::open("/home/me/.agent-wallet")?;
+//! let workspace = workspaces.register("trader-1")?;
+//! workspace.write(workspace.artifact(ArtifactKind::State), br#"{"step":1}"#)?;
+//!
+//! workspaces.unregister("trader-1", Retention::Archive)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use agent_wallet_core::Error as CoreError;
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::agent::AgentId;
+use crate::error::{AgentError, Result};
+
+/// Directory under the data dir holding all agent workspaces
+const AGENTS_DIR: &str = "agents";
+
+/// Directory under the agents dir holding archived workspaces
+const ARCHIVE_DIR: &str = ".archive";
+
+/// Manifest file written into every workspace
+const MANIFEST_FILE: &str = "workspace.json";
+
+/// Current workspace layout version
+const LAYOUT_VERSION: u32 = 1;
+
+/// Artifacts an agent persists in its workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtifactKind {
+    /// Persisted agent state
+    State,
+    /// Directory of context snapshots
+    ContextSnapshots,
+    /// Journal of idempotency keys for submitted actions
+    IdempotencyJournal,
+    /// Actions waiting to be delivered
+    Outbox,
+    /// Execution statistics
+    Stats,
+    /// Sequenced audit log of decisions
+    AuditLog,
+    /// Actions parked after repeatedly failing validation
+    DeadLetters,
+    /// Actions waiting for a human to approve them
+    Approvals,
+}
+
+impl ArtifactKind {
+    /// All artifact kinds
+    pub const ALL: [ArtifactKind; 8] = [
+        ArtifactKind::State,
+        ArtifactKind::ContextSnapshots,
+        ArtifactKind::IdempotencyJournal,
+        ArtifactKind::Outbox,
+        ArtifactKind::Stats,
+        ArtifactKind::AuditLog,
+        ArtifactKind::DeadLetters,
+        ArtifactKind::Approvals,
+    ];
+
+    /// File or directory name of the artifact inside a workspace
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ArtifactKind::State => "state.json",
+            ArtifactKind::ContextSnapshots => "snapshots",
+            ArtifactKind::IdempotencyJournal => "journal.jsonl",
+            ArtifactKind::Outbox => "outbox.jsonl",
+            ArtifactKind::Stats => "stats.json",
+            ArtifactKind::AuditLog => "audit.jsonl",
+            ArtifactKind::DeadLetters => "dead_letters.json",
+            ArtifactKind::Approvals => "approvals.json",
+        }
+    }
+}
+
+/// What happens to a workspace when its agent is unregistered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// Move the workspace under `agents/.archive/`
+    Archive,
+    /// Remove the workspace and all its artifacts
+    Delete,
+}
+
+/// Manifest describing a workspace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceManifest {
+    /// Agent owning the workspace
+    pub agent_id: AgentId,
+    /// When the workspace was created
+    pub created_at: DateTime<Utc>,
+    /// Layout version of the workspace
+    pub layout_version: u32,
+}
+
+/// Result of moving flat-layout artifacts into workspaces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Number of artifacts moved
+    pub moved: usize,
+    /// Legacy files left in place because the workspace already had them
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Root of all agent workspaces
+#[derive(Debug, Clone)]
+pub struct AgentWorkspaces {
+    root: PathBuf,
+}
+
+impl AgentWorkspaces {
+    /// Open the workspaces under `data_dir`, migrating flat-layout artifacts
+    pub fn open(data_dir: impl AsRef<Path>) -> Result<Self> {
+        let root = data_dir.as_ref().join(AGENTS_DIR);
+        fs::create_dir_all(&root).map_err(|e| io_error("create agents directory", &root, e))?;
+
+        let workspaces = Self { root };
+        let report = workspaces.migrate_flat_layout()?;
+        if report.moved > 0 {
+            info!(
+                "Moved {} agent artifacts into per-agent workspaces",
+                report.moved
+            );
+        }
+        for path in &report.skipped {
+            warn!(
+                "Left legacy agent artifact {} in place: workspace already has it",
+                path.display()
+            );
+        }
+        Ok(workspaces)
+    }
+
+    /// Directory holding the workspaces
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create the workspace of a newly registered agent
+    ///
+    /// Registering an agent that already has a workspace returns it.
+    pub fn register(&self, agent_id: &str) -> Result<AgentWorkspace> {
+        validate_agent_id(agent_id)?;
+        let dir = self.root.join(agent_id);
+        if dir.join(MANIFEST_FILE).exists() {
+            return self.open_workspace(agent_id);
+        }
+
+        fs::create_dir_all(&dir).map_err(|e| io_error("create workspace", &dir, e))?;
+        let workspace = AgentWorkspace {
+            agent_id: agent_id.to_string(),
+            dir,
+        };
+        let manifest = WorkspaceManifest {
+            agent_id: agent_id.to_string(),
+            created_at: Utc::now(),
+            layout_version: LAYOUT_VERSION,
+        };
+        workspace.write_json(MANIFEST_FILE, &manifest)?;
+        info!("Created workspace for agent {}", agent_id);
+        Ok(workspace)
+    }
+
+    /// Workspace of a registered agent
+    pub fn open_workspace(&self, agent_id: &str) -> Result<AgentWorkspace> {
+        validate_agent_id(agent_id)?;
+        let workspace = AgentWorkspace {
+            agent_id: agent_id.to_string(),
+            dir: self.root.join(agent_id),
+        };
+        let manifest: WorkspaceManifest = workspace.read_json(MANIFEST_FILE)?.ok_or_else(|| {
+            AgentError::invalid_state(format!("Agent {} has no workspace", agent_id))
+        })?;
+        if manifest.agent_id != agent_id {
+            return Err(AgentError::invalid_state(format!(
+                "Workspace {} belongs to agent {}",
+                agent_id, manifest.agent_id
+            )));
+        }
+        Ok(workspace)
+    }
+
+    /// Agents that have a workspace
+    pub fn list(&self) -> Result<Vec<AgentId>> {
+        let entries =
+            fs::read_dir(&self.root).map_err(|e| io_error("list workspaces", &self.root, e))?;
+        let mut ids: Vec<AgentId> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(MANIFEST_FILE).is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| validate_agent_id(name).is_ok())
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Remove an agent's workspace, archiving or deleting its artifacts
+    ///
+    /// Returns the archive location when the workspace was archived.
+    pub fn unregister(&self, agent_id: &str, retention: Retention) -> Result<Option<PathBuf>> {
+        let workspace = self.open_workspace(agent_id)?;
+        match retention {
+            Retention::Delete => {
+                fs::remove_dir_all(&workspace.dir)
+                    .map_err(|e| io_error("delete workspace", &workspace.dir, e))?;
+                info!("Deleted workspace of agent {}", agent_id);
+                Ok(None)
+            }
+            Retention::Archive => {
+                let archive_dir = self.root.join(ARCHIVE_DIR);
+                fs::create_dir_all(&archive_dir)
+                    .map_err(|e| io_error("create archive directory", &archive_dir, e))?;
+                let target = archive_dir.join(format!(
+                    "{}-{}",
+                    agent_id,
+                    Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+                ));
+                fs::rename(&workspace.dir, &target)
+                    .map_err(|e| io_error("archive workspace", &workspace.dir, e))?;
+                info!(
+                    "Archived workspace of agent {} to {}",
+                    agent_id,
+                    target.display()
+                );
+                Ok(Some(target))
+            }
+        }
+    }
+
+    /// Move flat `<agent_id>.<artifact>` files into their workspaces
+    pub fn migrate_flat_layout(&self) -> Result<MigrationReport> {
+        let entries = fs::read_dir(&self.root)
+            .map_err(|e| io_error("scan agents directory", &self.root, e))?;
+        let mut report = MigrationReport::default();
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let Some((agent_id, kind)) = parse_legacy_name(&name) else {
+                continue;
+            };
+            if entry.path().join(MANIFEST_FILE).exists() {
+                continue;
+            }
+
+            let workspace = self.register(agent_id)?;
+            let target = workspace.artifact(kind);
+            if target.exists() {
+                report.skipped.push(entry.path());
+                continue;
+            }
+            fs::rename(entry.path(), &target)
+                .map_err(|e| io_error("migrate artifact", &entry.path(), e))?;
+            report.moved += 1;
+        }
+        Ok(report)
+    }
+}
+
+/// Handle to one agent's working directory
+#[derive(Debug, Clone)]
+pub struct AgentWorkspace {
+    agent_id: AgentId,
+    dir: PathBuf,
+}
+
+/// Exclusive advisory lock on one artifact, released when dropped
+///
+/// The lock is held on a separate `.lock` file because writes replace the
+/// artifact itself by renaming.
+pub struct ArtifactLock {
+    file: fs::File,
+}
+
+impl Drop for ArtifactLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+impl AgentWorkspace {
+    /// Agent owning the workspace
+    pub fn agent_id(&self) -> &AgentId {
+        &self.agent_id
+    }
+
+    /// Workspace directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of a well-known artifact
+    pub fn artifact(&self, kind: ArtifactKind) -> PathBuf {
+        self.dir.join(kind.file_name())
+    }
+
+    /// Resolve a path relative to the workspace, rejecting escapes
+    ///
+    /// Absolute paths, `..` components and symlinks leading outside the
+    /// workspace are refused. Paths already inside the workspace directory
+    /// are accepted as-is.
+    pub fn path(&self, relative: impl AsRef<Path>) -> Result<PathBuf> {
+        let relative = relative.as_ref();
+        let relative = relative.strip_prefix(&self.dir).unwrap_or(relative);
+        let escape = || {
+            AgentError::sandbox_violation(format!(
+                "Path {} escapes the workspace of agent {}",
+                relative.display(),
+                self.agent_id
+            ))
+        };
+
+        if relative.as_os_str().is_empty()
+            || !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(escape());
+        }
+
+        // Resolve the deepest existing ancestor so symlinks are followed
+        // even when the final file does not exist yet
+        let path = self.dir.join(relative);
+        if let Some(resolved) = path.ancestors().find_map(|p| p.canonicalize().ok()) {
+            let dir = self
+                .dir
+                .canonicalize()
+                .map_err(|e| io_error("resolve workspace", &self.dir, e))?;
+            if !resolved.starts_with(dir) {
+                return Err(escape());
+            }
+        }
+        Ok(path)
+    }
+
+    /// Read a file, returning `None` if it does not exist
+    pub fn read(&self, relative: impl AsRef<Path>) -> Result<Option<Vec<u8>>> {
+        let path = self.path(relative)?;
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error("read artifact", &path, e)),
+        }
+    }
+
+    /// Atomically replace a file
+    pub fn write(&self, relative: impl AsRef<Path>, bytes: &[u8]) -> Result<()> {
+        let path = self.path(relative)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| io_error("create directory", parent, e))?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        fs::write(&tmp, bytes).map_err(|e| io_error("write artifact", &tmp, e))?;
+        fs::rename(&tmp, &path).map_err(|e| io_error("replace artifact", &path, e))
+    }
+
+    /// Append to a file, creating it if needed
+    pub fn append(&self, relative: impl AsRef<Path>, bytes: &[u8]) -> Result<()> {
+        let path = self.path(relative)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| io_error("create directory", parent, e))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(bytes))
+            .map_err(|e| io_error("append to artifact", &path, e))
+    }
+
+    /// Remove a file if it exists
+    pub fn remove(&self, relative: impl AsRef<Path>) -> Result<()> {
+        let path = self.path(relative)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error("remove artifact", &path, e)),
+        }
+    }
+
+    /// Take the exclusive lock guarding read-modify-write cycles of a file
+    ///
+    /// Blocks until other processes holding the lock release it.
+    pub fn lock(&self, relative: impl AsRef<Path>) -> Result<ArtifactLock> {
+        let path = self.path(relative)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| io_error("create directory", parent, e))?;
+        }
+        let mut lock_path = path.into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| io_error("open lock", &lock_path, e))?;
+        FileExt::lock_exclusive(&file).map_err(|e| io_error("lock", &lock_path, e))?;
+        Ok(ArtifactLock { file })
+    }
+
+    /// Read and deserialize a JSON file
+    pub fn read_json<T: DeserializeOwned>(&self, relative: impl AsRef<Path>) -> Result<Option<T>> {
+        self.read(relative)?
+            .map(|bytes| serde_json::from_slice(&bytes).map_err(CoreError::from))
+            .transpose()
+            .map_err(AgentError::from)
+    }
+
+    /// Serialize and atomically write a JSON file
+    pub fn write_json<T: Serialize>(&self, relative: impl AsRef<Path>, value: &T) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(value).map_err(CoreError::from)?;
+        self.write(relative, &bytes)
+    }
+}
+
+/// Check that an agent id is usable as a single directory name
+pub(crate) fn validate_agent_id(agent_id: &str) -> Result<()> {
+    let valid = !agent_id.is_empty()
+        && !agent_id.starts_with('.')
+        && agent_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::config(format!(
+            "Agent id '{}' may only contain letters, digits, '-', '_' and '.', and may not start with '.'",
+            agent_id
+        )))
+    }
+}
+
+/// Split a flat-layout file name into its agent id and artifact
+fn parse_legacy_name(name: &str) -> Option<(&str, ArtifactKind)> {
+    ArtifactKind::ALL.into_iter().find_map(|kind| {
+        let agent_id = name.strip_suffix(kind.file_name())?.strip_suffix('.')?;
+        validate_agent_id(agent_id).ok()?;
+        Some((agent_id, kind))
+    })
+}
+
+pub(crate) fn io_error(action: &str, path: &Path, e: std::io::Error) -> AgentError {
+    AgentError::from(CoreError::storage(format!(
+        "Failed to {} {}: {}",
+        action,
+        path.display(),
+        e
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_agents_are_isolated() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspaces = AgentWorkspaces::open(dir.path())?;
+        let alpha = workspaces.register("alpha")?;
+        let beta = workspaces.register("beta")?;
+
+        alpha.write(alpha.artifact(ArtifactKind::State), b"alpha")?;
+        beta.write(beta.artifact(ArtifactKind::State), b"beta")?;
+        alpha.write("snapshots/latest.json", b"{}")?;
+
+        assert_eq!(
+            alpha.read(ArtifactKind::State.file_name())?,
+            Some(b"alpha".to_vec())
+        );
+        assert_eq!(
+            beta.read(ArtifactKind::State.file_name())?,
+            Some(b"beta".to_vec())
+        );
+        assert_eq!(beta.read("snapshots/latest.json")?, None);
+        assert_eq!(workspaces.list()?, vec!["alpha".to_string(), "beta".into()]);
+
+        let archived = workspaces
+            .unregister("alpha", Retention::Archive)?
+            .ok_or_else(|| AgentError::invalid_state("workspace was not archived"))?;
+        assert!(archived.join(ArtifactKind::State.file_name()).is_file());
+        workspaces.unregister("beta", Retention::Delete)?;
+        assert!(!beta.dir().exists());
+        assert!(workspaces.list()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_escapes_are_rejected() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let workspaces = AgentWorkspaces::open(dir.path())?;
+        let alpha = workspaces.register("alpha")?;
+        workspaces.register("beta")?;
+
+        for escape in ["../beta/state.json", "/etc/passwd", "", "snapshots/../../x"] {
+            assert!(
+                matches!(alpha.path(escape), Err(AgentError::SandboxViolation(_))),
+                "{}",
+                escape
+            );
+        }
+        assert!(alpha.write("../beta/state.json", b"owned").is_err());
+        assert!(workspaces.register("../beta").is_err());
+        assert!(workspaces.register(".archive").is_err());
+
+        #[cfg(unix)]
+        {
+            let link = alpha.dir().join("link");
+            std::os::unix::fs::symlink(workspaces.root().join("beta"), &link)
+                .map_err(|e| AgentError::config(e.to_string()))?;
+            assert!(alpha.path("link/workspace.json").is_err());
+            assert!(alpha.write("link/new.json", b"owned").is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_flat_layout_is_migrated() -> Result<()> {
+        let dir = tempdir().map_err(|e| AgentError::config(e.to_string()))?;
+        let agents = dir.path().join(AGENTS_DIR);
+        fs::create_dir_all(&agents).map_err(|e| AgentError::config(e.to_string()))?;
+        for (name, contents) in [
+            ("alpha.state.json", "alpha state"),
+            ("beta.state.json", "beta state"),
+            ("alpha.outbox.jsonl", "alpha outbox"),
+            ("notes.txt", "unrelated"),
+        ] {
+            fs::write(agents.join(name), contents)
+                .map_err(|e| AgentError::config(e.to_string()))?;
+        }
+
+        let workspaces = AgentWorkspaces::open(dir.path())?;
+        let alpha = workspaces.open_workspace("alpha")?;
+        let beta = workspaces.open_workspace("beta")?;
+        assert_eq!(
+            alpha.read(ArtifactKind::State.file_name())?,
+            Some(b"alpha state".to_vec())
+        );
+        assert_eq!(
+            alpha.read(ArtifactKind::Outbox.file_name())?,
+            Some(b"alpha outbox".to_vec())
+        );
+        assert_eq!(
+            beta.read(ArtifactKind::State.file_name())?,
+            Some(b"beta state".to_vec())
+        );
+        assert!(!agents.join("alpha.state.json").exists());
+        assert!(agents.join("notes.txt").exists());
+
+        // A second run leaves existing workspace artifacts untouched
+        fs::write(agents.join("alpha.state.json"), "stale")
+            .map_err(|e| AgentError::config(e.to_string()))?;
+        let report = workspaces.migrate_flat_layout()?;
+        assert_eq!(report.moved, 0);
+        assert_eq!(report.skipped, vec![agents.join("alpha.state.json")]);
+        assert_eq!(
+            alpha.read(ArtifactKind::State.file_name())?,
+            Some(b"alpha state".to_vec())
+        );
+        Ok(())
+    }
+}
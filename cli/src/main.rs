@@ -6,12 +6,47 @@
 
 use std::path::PathBuf;
 
-use agent_wallet_core::{Wallet, WalletConfig};
+mod service;
+
+use agent_wallet_core::audit::{AuditEntry, AuditPhase, JsonlAuditSink};
+use agent_wallet_core::config::LogLevel;
+use agent_wallet_core::destination::{DestinationOptions, DestinationTransfer};
+use agent_wallet_core::metrics::{Metrics, MetricsServer};
+use agent_wallet_core::prelude::Zeroizing;
+use agent_wallet_core::shutdown::{CancellationToken, DEFAULT_SHUTDOWN_TIMEOUT};
+use agent_wallet_core::token::utils::{format_sol, format_token_amount, parse_sol_amount};
+use agent_wallet_core::transaction::TransactionOptions;
+use agent_wallet_core::{
+    multisig, AddressPolicy, AgentAction, Lamports, NonceInfo, RunMode, SignedEnvelope,
+    StorageService, TransactionEnvelope, TransferPreview, UnsignedEnvelope, Wallet, WalletConfig,
+};
 use agent_wallet_agent::prelude::*;
+use agent_wallet_agent::audit::{AuditConfig, AuditLog};
+use agent_wallet_agent::approval::{ApprovalDecision, ApprovalGate, ApprovalStore};
+use agent_wallet_agent::dead_letter::{DeadLetter, DeadLetterConfig, DeadLetterQueue};
+use agent_wallet_agent::notify::WebhookNotifier;
+use agent_wallet_agent::registry::{AgentRegistry, RegistryEntry};
+use agent_wallet_agent::spec::{AgentKind, AgentSpec, DEFAULT_POLL_INTERVAL_SECS};
+use agent_wallet_agent::state::FileStateStore;
+use agent_wallet_agent::DynAgent;
+use agent_wallet_agent::workspace::AgentWorkspaces;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use tracing::{error, info, warn, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::{error, info, warn};
+
+/// Directory next to the configuration file holding the agent registry
+const REGISTRY_DIR: &str = "agents";
+
+/// Environment variable supplying the wallet passphrase
+const PASSPHRASE_ENV: &str = "AGENT_WALLET_PASSPHRASE";
+
+/// Printed in bold red before a secret key is written in plain text
+const SECRET_EXPORT_WARNING: &str = "\x1b[1;31mWARNING: the exported file holds your secret key \
+UNENCRYPTED.\nAnyone who reads it controls the wallet's funds. Store it offline and delete it \
+when done.\x1b[0m";
+
+/// How often a running agent refreshes its registry heartbeat
+const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
 
 /// AI Agent Wallet CLI
 #[derive(Parser, Debug)]
@@ -55,6 +90,14 @@ enum Commands {
     #[command(subcommand, alias = "cfg")]
     Config(ConfigCommands),
 
+    /// dApp and DeFi protocol operations
+    #[command(subcommand)]
+    Dapp(DappCommands),
+
+    /// Address book management
+    #[command(subcommand)]
+    Contacts(ContactCommands),
+
     /// Start the agent wallet service
     #[command(alias = "srv")]
     Service {
@@ -69,6 +112,14 @@ enum Commands {
         /// Enable CORS for web dashboard
         #[arg(long)]
         cors: bool,
+
+        /// JSON file listing API tokens and their capabilities
+        #[arg(long)]
+        tokens: Option<PathBuf>,
+
+        /// Directory holding the agent workspaces whose audit logs are served
+        #[arg(long, default_value = "~/.agent-wallet")]
+        data_dir: PathBuf,
     },
 
     /// Show current version
@@ -100,10 +151,15 @@ enum WalletCommands {
         detailed: bool,
     },
 
-    /// Import wallet from private key
+    /// Import wallet from a private key or a Solana CLI keypair file
     Import {
-        /// Private key (base58 or hex)
-        key: String,
+        /// Private key (base58)
+        #[arg(required_unless_present = "file", conflicts_with = "file")]
+        key: Option<String>,
+
+        /// Keypair file in Solana CLI JSON format (e.g. ~/.config/solana/id.json)
+        #[arg(long)]
+        file: Option<PathBuf>,
 
         /// Wallet name
         #[arg(short, long)]
@@ -125,12 +181,114 @@ enum WalletCommands {
         tokens: bool,
     },
 
+    /// Fund a stored wallet from the devnet or testnet faucet
+    Airdrop {
+        /// Wallet name
+        name: String,
+
+        /// Amount in SOL, e.g. 1.5, 1.5sol or 2500lamports
+        amount: String,
+    },
+
     /// Show wallet information
     Info {
         /// Wallet file path
         #[arg(default_value = "wallet.json")]
         wallet: PathBuf,
     },
+
+    /// Change the passphrase of a stored wallet
+    RotatePassphrase {
+        /// Wallet name
+        name: String,
+    },
+
+    /// Re-encrypt a stored wallet in the current format and configured KDF
+    UpgradeEncryption {
+        /// Wallet name
+        name: String,
+    },
+
+    /// List the stored backups of a wallet
+    Backups {
+        /// Wallet name
+        name: String,
+    },
+
+    /// Restore a wallet from a backup
+    Restore {
+        /// Wallet name
+        name: String,
+
+        /// Backup version to restore, as listed by `wallet backups`; newest if omitted
+        #[arg(long)]
+        version: Option<String>,
+    },
+
+    /// Export the unencrypted keypair of a wallet
+    Export {
+        /// Wallet file path
+        #[arg(default_value = "wallet.json")]
+        wallet: PathBuf,
+
+        /// Key file format
+        #[arg(long, value_enum, default_value = "solana-json")]
+        format: KeyFormat,
+
+        /// File the keypair is written to
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Confirm that the output holds the secret key in plain text
+        #[arg(long)]
+        yes_i_know: bool,
+
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Write a passphrase-only copy of a wallet file for backups
+    ExportRecovery {
+        /// Wallet file path
+        #[arg(default_value = "wallet.json")]
+        wallet: PathBuf,
+
+        /// File the recovery copy is written to
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print SOL and token balance changes of a wallet as they happen
+    Watch {
+        /// Wallet name
+        name: String,
+    },
+
+    /// Delete a stored wallet and every backup of it
+    Delete {
+        /// Wallet name
+        name: String,
+
+        /// The wallet name typed again; prompted for if omitted
+        #[arg(long)]
+        confirm: Option<String>,
+
+        /// Delete the wallet even though it still holds funds
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Formats a keypair can be exported in
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum KeyFormat {
+    /// 64-element byte array JSON, as written by `solana-keygen`
+    SolanaJson,
 }
 
 /// Agent management subcommands
@@ -138,21 +296,51 @@ enum WalletCommands {
 enum AgentCommands {
     /// Run an agent
     Run {
-        /// Agent type
-        #[arg(short, long)]
-        r#type: String,
+        /// Agent type; taken from the spec when one is given
+        #[arg(short, long, required_unless_present = "spec")]
+        r#type: Option<String>,
 
         /// Wallet file path
         #[arg(short, long, default_value = "wallet.json")]
         wallet: PathBuf,
 
-        /// Agent configuration file
+        /// Agent strategy configuration file (JSON)
         #[arg(short, long)]
-        config: Option<PathBuf>,
+        strategy: Option<PathBuf>,
+
+        /// Agent spec file (YAML or JSON) describing the agent and its strategy
+        #[arg(long, conflicts_with = "strategy")]
+        spec: Option<PathBuf>,
 
         /// Run in background (daemon mode)
         #[arg(short, long)]
         daemon: bool,
+
+        /// Agent ID; generated if omitted
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Seconds between decisions [default: the spec's poll interval, or 10]
+        #[arg(long)]
+        interval: Option<u64>,
+
+        /// Validate, sign and simulate actions without sending them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Read the wallet passphrase from stdin (used by daemon mode)
+        #[arg(long, hide = true)]
+        passphrase_stdin: bool,
+
+        /// Directory holding the agent workspaces, where approval requests are kept
+        #[arg(long, default_value = "~/.agent-wallet")]
+        data_dir: PathBuf,
+    },
+
+    /// Check an agent spec file without running it
+    Validate {
+        /// Agent spec file (YAML or JSON)
+        file: PathBuf,
     },
 
     /// List running agents
@@ -172,14 +360,36 @@ enum AgentCommands {
     Status {
         /// Agent ID
         id: String,
+
+        /// Directory holding the agent workspaces
+        #[arg(long, default_value = "~/.agent-wallet")]
+        data_dir: PathBuf,
+    },
+
+    /// Approve, or reject, an action waiting for approval
+    Approve {
+        /// Token of the approval request
+        token: String,
+
+        /// Reject the action instead
+        #[arg(long)]
+        reject: bool,
+
+        /// Directory holding the agent workspaces
+        #[arg(long, default_value = "~/.agent-wallet")]
+        data_dir: PathBuf,
     },
 
-    /// Show agent logs
+    /// Inspect or release actions parked after repeated validation failures
+    #[command(subcommand, name = "deadletter")]
+    DeadLetter(DeadLetterCommands),
+
+    /// Show the audit trail of an agent's actions
     Logs {
         /// Agent ID
         id: String,
 
-        /// Number of log lines to show
+        /// Number of records to show
         #[arg(short, long, default_value_t = 50)]
         lines: usize,
 
@@ -189,6 +399,33 @@ enum AgentCommands {
     },
 }
 
+/// Dead-letter subcommands
+#[derive(Subcommand, Debug)]
+enum DeadLetterCommands {
+    /// List parked actions
+    List {
+        /// Agent ID
+        id: String,
+
+        /// Directory holding the agent workspaces
+        #[arg(long, default_value = "~/.agent-wallet")]
+        data_dir: PathBuf,
+    },
+
+    /// Release parked actions
+    Clear {
+        /// Agent ID
+        id: String,
+
+        /// Digest of the action to release; all actions if omitted
+        digest: Option<String>,
+
+        /// Directory holding the agent workspaces
+        #[arg(long, default_value = "~/.agent-wallet")]
+        data_dir: PathBuf,
+    },
+}
+
 /// Transaction operation subcommands
 #[derive(Subcommand, Debug)]
 enum TransactionCommands {
@@ -198,16 +435,42 @@ enum TransactionCommands {
         #[arg(short, long, default_value = "wallet.json")]
         wallet: PathBuf,
 
-        /// Recipient address
+        /// Recipient address or .sol domain
         to: String,
 
-        /// Amount in SOL
-        amount: f64,
+        /// Amount in SOL, e.g. 0.1, 1_000sol or 2500lamports
+        amount: String,
 
         /// Transaction memo
         #[arg(short, long)]
         memo: Option<String>,
 
+        /// Durable nonce account to use instead of a recent blockhash
+        #[arg(long)]
+        nonce_account: Option<String>,
+
+        /// Allow sending to a program id
+        #[arg(long)]
+        allow_program_destination: bool,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+
+    /// Pay a Solana Pay transfer request URI
+    Pay {
+        /// Wallet file path
+        #[arg(short, long, default_value = "wallet.json")]
+        wallet: PathBuf,
+
+        /// Transfer request, e.g. solana:<recipient>?amount=1.5&reference=<key>
+        uri: String,
+
+        /// Refuse URIs with parameters outside the Solana Pay spec
+        #[arg(long)]
+        strict: bool,
+
         /// Skip confirmation prompt
         #[arg(short = 'y', long)]
         yes: bool,
@@ -232,12 +495,96 @@ enum TransactionCommands {
     Status {
         /// Transaction signature
         signature: String,
+
+        /// Wait until the transaction is confirmed
+        #[arg(long)]
+        wait: bool,
+
+        /// With --wait, wait until the transaction is finalized instead
+        #[arg(long, requires = "wait")]
+        finalized: bool,
+
+        /// Seconds to wait before giving up
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
     },
 
-    /// Simulate transaction
+    /// Simulate a transaction envelope or an action and report the result
     Simulate {
-        /// Transaction to simulate (JSON or file path)
+        /// Transaction envelope or action to simulate (JSON or file path)
+        input: String,
+
+        /// Wallet file path
+        #[arg(short, long, default_value = "wallet.json")]
+        wallet: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Sign a base64-encoded transaction offline
+    Sign {
+        /// Transaction (base64 or file path)
         transaction: String,
+
+        /// Signer key file (base58 secret key)
+        #[arg(short, long)]
+        keypair: PathBuf,
+
+        /// Allow other signatures to still be missing (multisig)
+        #[arg(long)]
+        partial: bool,
+    },
+
+    /// Merge signatures from partially signed copies of a transaction
+    Merge {
+        /// Partially signed transactions (base64 or file paths)
+        #[arg(required = true, num_args = 2..)]
+        transactions: Vec<String>,
+    },
+
+    /// Build an unsigned transaction envelope to sign on another machine
+    Build {
+        /// Action to build (JSON or file path)
+        action: String,
+
+        /// Public key of the wallet that will sign
+        #[arg(short, long)]
+        from: String,
+
+        /// Durable nonce account to use instead of a recent blockhash; the
+        /// envelope then does not expire
+        #[arg(long)]
+        nonce_account: Option<String>,
+
+        /// Write the envelope to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Sign an unsigned envelope without network access
+    SignOffline {
+        /// Unsigned envelope (JSON or file path)
+        envelope: String,
+
+        /// Wallet file path
+        #[arg(short, long, default_value = "wallet.json")]
+        wallet: PathBuf,
+
+        /// Write the signed envelope to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+
+    /// Broadcast a signed envelope
+    Broadcast {
+        /// Signed envelope (JSON or file path)
+        envelope: String,
     },
 }
 
@@ -251,20 +598,20 @@ enum ConfigCommands {
         force: bool,
     },
 
-    /// Show current configuration
+    /// Show the effective configuration, environment overrides included
     Show {
         /// Show as JSON
-        #[arg(short, long)]
+        #[arg(short, long, conflicts_with = "yaml")]
         json: bool,
 
-        /// Show as YAML
+        /// Show as YAML (the default)
         #[arg(short, long)]
         yaml: bool,
     },
 
     /// Set configuration value
     Set {
-        /// Key to set (e.g., rpc.url, wallet.encryption)
+        /// Dotted key to set (e.g., rpc.timeout_seconds, rpc.endpoints.0.url, policy.allowlist)
         key: String,
 
         /// Value to set
@@ -273,168 +620,1538 @@ enum ConfigCommands {
 
     /// Get configuration value
     Get {
-        /// Key to get
+        /// Dotted key to get (e.g., agent.limits.daily_spend_limit_sol)
         key: String,
     },
 }
 
-/// Initialize logging based on verbosity
-fn init_logging(verbose: bool) {
-    let level = if verbose { Level::DEBUG } else { Level::INFO };
-    let subscriber = FmtSubscriber::builder().with_max_level(level).finish();
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+/// Address book subcommands
+#[derive(Subcommand, Debug)]
+enum ContactCommands {
+    /// List contacts and pending changes
+    List {
+        /// Address book file
+        #[arg(long, default_value = "~/.agent-wallet/contacts.json")]
+        book: PathBuf,
+
+        /// Public key the address book must be signed with
+        #[arg(long)]
+        signer: String,
+    },
+
+    /// Enable two-person integrity for whitelisted contacts
+    Init {
+        /// Approver public keys
+        #[arg(long = "approver", required = true)]
+        approvers: Vec<String>,
+
+        /// Number of approvals required to activate a change
+        #[arg(long, default_value = "1")]
+        quorum: usize,
+
+        /// Address book file
+        #[arg(long, default_value = "~/.agent-wallet/contacts.json")]
+        book: PathBuf,
+
+        /// Wallet or operator key file used to sign the address book
+        #[arg(long)]
+        signer: PathBuf,
+    },
+
+    /// Propose adding or modifying a whitelisted contact
+    Propose {
+        /// Contact name
+        #[arg(short, long)]
+        name: String,
+
+        /// Contact address
+        #[arg(short, long)]
+        address: String,
+
+        /// Optional note
+        #[arg(long)]
+        note: Option<String>,
+
+        /// Address book file
+        #[arg(long, default_value = "~/.agent-wallet/contacts.json")]
+        book: PathBuf,
+
+        /// Proposer key file (base58 secret key)
+        #[arg(short, long)]
+        keypair: PathBuf,
+
+        /// Wallet or operator key file used to sign the address book
+        #[arg(long)]
+        signer: PathBuf,
+    },
+
+    /// Approve a pending contact change
+    Approve {
+        /// Pending change ID
+        id: String,
+
+        /// Address book file
+        #[arg(long, default_value = "~/.agent-wallet/contacts.json")]
+        book: PathBuf,
+
+        /// Approver key file (base58 secret key)
+        #[arg(short, long)]
+        keypair: PathBuf,
+
+        /// Wallet or operator key file used to sign the address book
+        #[arg(long)]
+        signer: PathBuf,
+    },
+}
+
+/// dApp subcommands
+#[derive(Subcommand, Debug)]
+enum DappCommands {
+    /// Cached protocol pool metadata
+    #[command(subcommand)]
+    Pools(PoolCommands),
 }
 
-/// Main entry point
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-    init_logging(cli.verbose);
+/// Pool cache subcommands
+#[derive(Subcommand, Debug)]
+enum PoolCommands {
+    /// Rescan pools on chain and rebuild the cache
+    Refresh {
+        /// Only refresh this protocol (raydium, orca)
+        #[arg(short, long)]
+        protocol: Option<String>,
+    },
 
-    info!("AI Agent Wallet CLI v{}", env!("CARGO_PKG_VERSION"));
+    /// List cached pools
+    List {
+        /// Only show pools for this pair (e.g., SOL/USDC); scans if missing
+        #[arg(long)]
+        pair: Option<String>,
 
-    match cli.command {
-        Commands::Wallet(cmd) => handle_wallet_command(cmd).await?,
-        Commands::Agent(cmd) => handle_agent_command(cmd).await?,
-        Commands::Transaction(cmd) => handle_transaction_command(cmd).await?,
-        Commands::Config(cmd) => handle_config_command(cmd).await?,
-        Commands::Service { port, host, cors } => {
-            info!("Starting agent wallet service on {}:{}", host, port);
-            info!("CORS enabled: {}", cors);
-            // TODO: Implement service startup
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-            info!("Service started (placeholder implementation)");
-        }
-        Commands::Version => {
-            println!("AI Agent Wallet CLI v{}", env!("CARGO_PKG_VERSION"));
-            println!("Core library: {}", agent_wallet_core::VERSION);
-            println!("Agent library: {}", agent_wallet_agent::VERSION);
-            println!("dApp library: {}", agent_wallet_dapp::VERSION);
-        }
+        /// Only show pools of this protocol (raydium, orca)
+        #[arg(short, long)]
+        protocol: Option<String>,
+    },
+}
+
+/// Load the effective configuration: the file, or defaults when absent,
+/// with `AGENT_WALLET__*` environment overrides applied
+fn load_config(path: &std::path::Path) -> Result<WalletConfig> {
+    Ok(WalletConfig::load(expand_path(path))?)
+}
+
+/// Load only what the configuration file says, for writing it back
+fn load_config_file(path: &std::path::Path) -> Result<WalletConfig> {
+    let expanded = expand_path(path);
+    if expanded.exists() {
+        Ok(WalletConfig::from_file(&expanded)?)
+    } else {
+        Ok(WalletConfig::default())
     }
+}
 
+/// Write the configuration file, as JSON for `.json` paths and YAML otherwise
+fn save_config(config: &WalletConfig, path: &std::path::Path) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    if path.extension().is_some_and(|ext| ext == "json") {
+        config.save_to_json_file(path)?;
+    } else {
+        config.save_to_yaml_file(path)?;
+    }
     Ok(())
 }
 
-/// Handle wallet commands
-async fn handle_wallet_command(cmd: WalletCommands) -> Result<()> {
-    match cmd {
-        WalletCommands::Create {
-            name,
-            output,
-            no_encrypt,
-        } => {
-            info!("Creating wallet '{}' at {}", name, output.display());
-            if no_encrypt {
-                warn!("Wallet encryption disabled - NOT RECOMMENDED for production use");
-            }
-            // TODO: Implement wallet creation
-            info!("Wallet created (placeholder implementation)");
+/// Apply `config set policy.<field> <value>` to an address policy
+///
+/// Lists take comma-separated addresses; an empty value clears the list.
+fn set_policy_key(policy: &mut AddressPolicy, key: &str, value: &str) -> Result<()> {
+    let addresses = || -> Result<Vec<solana_sdk::pubkey::Pubkey>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(|address| Ok(address.parse()?))
+            .collect()
+    };
+    match key {
+        "policy.mode" => {
+            policy.mode = serde_json::from_value(serde_json::Value::String(value.to_string()))
+                .map_err(|_| anyhow::anyhow!("Invalid policy mode '{}'; expected allow_all or allowlist_only", value))?;
         }
-        WalletCommands::List { detailed } => {
-            if detailed {
-                info!("Listing all wallets with details");
-            } else {
-                info!("Listing all wallets");
-            }
-            // TODO: Implement wallet listing
-            println!("Wallets list (placeholder)");
-        }
-        WalletCommands::Import { key, name, output } => {
-            info!("Importing wallet '{}' from key", name);
-            info!("Output: {}", output.display());
-            // TODO: Implement wallet import
-            info!("Wallet imported (placeholder implementation)");
-        }
-        WalletCommands::Balance { wallet, tokens } => {
-            info!("Getting balance for wallet: {}", wallet.display());
-            if tokens {
-                info!("Including token balances");
+        "policy.allowlist" => policy.allowlist = addresses()?,
+        "policy.denylist" => policy.denylist = addresses()?,
+        _ => anyhow::bail!(
+            "Unknown key '{}'; expected policy.mode, policy.allowlist or policy.denylist",
+            key
+        ),
+    }
+    Ok(())
+}
+
+/// Expand `~` in a path argument
+fn expand_path(path: &std::path::Path) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).into_owned())
+}
+
+/// Load the wallet stored at a file path, named after the file stem
+async fn open_wallet(
+    path: &std::path::Path,
+    config_path: &std::path::Path,
+    passphrase: &Zeroizing<String>,
+) -> Result<(String, Wallet)> {
+    open_wallet_in_mode(path, config_path, passphrase, RunMode::Live).await
+}
+
+/// Load a wallet that broadcasts or only rehearses its actions
+async fn open_wallet_in_mode(
+    path: &std::path::Path,
+    config_path: &std::path::Path,
+    passphrase: &Zeroizing<String>,
+    mode: RunMode,
+) -> Result<(String, Wallet)> {
+    let mut config = load_config(config_path)?;
+    config.dry_run |= mode == RunMode::DryRun;
+    open_wallet_with_config(path, config, passphrase).await
+}
+
+/// Load the wallet stored at a file path with an already loaded configuration
+async fn open_wallet_with_config(
+    path: &std::path::Path,
+    mut config: WalletConfig,
+    passphrase: &Zeroizing<String>,
+) -> Result<(String, Wallet)> {
+    let wallet_name = locate_wallet(path, &mut config)?;
+    let wallet = Wallet::load(wallet_name.clone(), passphrase, config).await?;
+    Ok((wallet_name, wallet))
+}
+
+/// Load the wallet stored at a file path without connecting to a cluster
+async fn open_wallet_offline(
+    path: &std::path::Path,
+    config_path: &std::path::Path,
+    passphrase: &Zeroizing<String>,
+) -> Result<Wallet> {
+    let mut config = load_config(config_path)?;
+    let wallet_name = locate_wallet(path, &mut config)?;
+    Ok(Wallet::load_offline(wallet_name, passphrase, config).await?)
+}
+
+/// Name of the wallet stored at a file path, pointing storage at its directory
+fn locate_wallet(path: &std::path::Path, config: &mut WalletConfig) -> Result<String> {
+    let wallet_path = expand_path(path);
+    let wallet_name = wallet_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid wallet path {}", path.display()))?
+        .to_string();
+    if let Some(dir) = wallet_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        config.wallet.storage.path = dir.to_path_buf();
+    }
+    Ok(wallet_name)
+}
+
+/// Read input given inline or as a file path
+fn read_input(input: &str) -> Result<String> {
+    let path = expand_path(std::path::Path::new(input));
+    if path.is_file() {
+        Ok(std::fs::read_to_string(path)?)
+    } else {
+        Ok(input.to_string())
+    }
+}
+
+/// Print JSON to stdout, or write it to a file
+fn write_output(json: &str, output: Option<&std::path::Path>) -> Result<()> {
+    match output {
+        Some(path) => {
+            let path = expand_path(path);
+            std::fs::write(&path, json)?;
+            eprintln!("Wrote {}", path.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Read a base58-encoded secret key file
+fn read_keypair(path: &std::path::Path) -> Result<agent_wallet_core::SecureKeypair> {
+    let contents = std::fs::read_to_string(expand_path(path))?;
+    Ok(agent_wallet_core::SecureKeypair::from_base58(contents.trim())?)
+}
+
+/// Read a base64 transaction given inline or as a file path
+fn read_transaction(input: &str) -> Result<solana_sdk::transaction::Transaction> {
+    let path = expand_path(std::path::Path::new(input));
+    let encoded = if path.is_file() {
+        std::fs::read_to_string(path)?
+    } else {
+        input.to_string()
+    };
+    Ok(multisig::decode_transaction(&encoded)?)
+}
+
+/// Print the balance changes a simulated action would cause
+fn print_preview(preview: &TransferPreview) {
+    println!("Simulation succeeded");
+    let sign = if preview.lamports_delta < 0 { "-" } else { "+" };
+    println!(
+        "  SOL change: {}{}",
+        sign,
+        format_sol(Lamports::new(preview.lamports_delta.unsigned_abs()))
+    );
+    println!("  Fee:        {}", format_sol(Lamports::new(preview.fee)));
+    for delta in &preview.token_deltas {
+        println!("  Token {} of {}: {:+}", delta.mint, delta.owner, delta.delta);
+    }
+    for line in &preview.logs {
+        println!("  log: {}", line);
+    }
+    for warning in &preview.warnings {
+        println!("  Warning: {}", warning);
+    }
+}
+
+/// Print a transaction as base64 along with the signers still missing
+fn print_transaction(transaction: &solana_sdk::transaction::Transaction) -> Result<()> {
+    println!("{}", multisig::encode_transaction(transaction)?);
+    let missing = multisig::missing_signers(transaction);
+    if missing.is_empty() {
+        eprintln!("All required signatures present");
+    } else {
+        for signer in missing {
+            eprintln!("Missing signature: {}", signer);
+        }
+    }
+    Ok(())
+}
+
+/// Initialize logging from the configuration; `--verbose` raises the level to debug
+fn init_logging(config_path: &std::path::Path, verbose: bool) -> Result<()> {
+    // A broken configuration file is reported by the command that reads it
+    let mut settings = load_config(config_path)
+        .map(|config| config.monitoring.logging)
+        .unwrap_or_default();
+    if verbose {
+        settings.level = LogLevel::Debug;
+    }
+    Ok(agent_wallet_core::logging::init_logging(&settings)?)
+}
+
+/// Main entry point
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_logging(&cli.config, cli.verbose)?;
+
+    info!("AI Agent Wallet CLI v{}", env!("CARGO_PKG_VERSION"));
+
+    match cli.command {
+        Commands::Wallet(cmd) => handle_wallet_command(cmd, &cli.config).await?,
+        Commands::Agent(cmd) => handle_agent_command(cmd, &cli.config).await?,
+        Commands::Transaction(cmd) => handle_transaction_command(cmd, &cli.config).await?,
+        Commands::Config(cmd) => handle_config_command(cmd, &cli.config).await?,
+        Commands::Dapp(cmd) => handle_dapp_command(cmd, &cli.config).await?,
+        Commands::Contacts(cmd) => handle_contacts_command(cmd)?,
+        Commands::Service { port, host, cors, tokens, data_dir } => {
+            info!("Starting agent wallet service on {}:{}", host, port);
+            info!("CORS enabled: {}", cors);
+            let tokens: Vec<service::ApiToken> = match tokens {
+                Some(path) => serde_json::from_str(&std::fs::read_to_string(expand_path(&path))?)?,
+                None => {
+                    warn!("No API tokens configured; all requests will be rejected");
+                    Vec::new()
+                }
+            };
+            let mut state = service::ServiceState::new(tokens).with_registry(open_registry(&cli.config)?);
+            // The service exports metrics itself rather than on a second port
+            if load_config(&cli.config)?.monitoring.metrics.enabled {
+                state = state.with_metrics(Metrics::global().clone());
+            }
+            let workspaces = AgentWorkspaces::open(expand_path(&data_dir))?;
+            for agent_id in workspaces.list()? {
+                let workspace = workspaces.open_workspace(&agent_id)?;
+                let log = AuditLog::open(workspace.clone(), AuditConfig::default())?;
+                state.register_audit_log(log).await;
+                state.register_approvals(agent_id, ApprovalStore::open(workspace)?).await;
+            }
+            let shutdown = CancellationToken::new();
+            cancel_on_signal(shutdown.clone());
+            service::serve(&format!("{}:{}", host, port), state, shutdown).await?;
+        }
+        Commands::Version => {
+            println!("AI Agent Wallet CLI v{}", env!("CARGO_PKG_VERSION"));
+            println!("Core library: {}", agent_wallet_core::VERSION);
+            println!("Agent library: {}", agent_wallet_agent::VERSION);
+            println!("dApp library: {}", agent_wallet_dapp::VERSION);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle wallet commands
+async fn handle_wallet_command(cmd: WalletCommands, config_path: &std::path::Path) -> Result<()> {
+    match cmd {
+        WalletCommands::Create {
+            name,
+            output,
+            no_encrypt,
+        } => {
+            info!("Creating wallet '{}' at {}", name, output.display());
+            if no_encrypt {
+                warn!("Wallet encryption disabled - NOT RECOMMENDED for production use");
+            }
+            // TODO: Implement wallet creation
+            info!("Wallet created (placeholder implementation)");
+        }
+        WalletCommands::List { detailed } => {
+            let config = load_config(config_path)?;
+            // Detailed listings batch one balance lookup for every stored wallet
+            let wallets = Wallet::list_wallets(&config, detailed).await?;
+            if wallets.is_empty() {
+                println!("No stored wallets");
+            }
+            for wallet in &wallets {
+                if detailed {
+                    println!(
+                        "{:<24} {} {:>20} created {}",
+                        wallet.name,
+                        wallet.public_key,
+                        format_sol(Lamports::new(wallet.balance_lamports)),
+                        wallet.created_at.format("%Y-%m-%d")
+                    );
+                } else {
+                    println!("{:<24} {}", wallet.name, wallet.public_key);
+                }
+            }
+        }
+        WalletCommands::Import { key, file, name, output } => {
+            let keypair = match (key, file) {
+                (_, Some(file)) => {
+                    info!("Importing wallet '{}' from {}", name, file.display());
+                    agent_wallet_core::SecureKeypair::from_solana_json_file(expand_path(&file))?
+                }
+                (Some(key), None) => {
+                    info!("Importing wallet '{}' from key", name);
+                    agent_wallet_core::SecureKeypair::from_base58(key.trim())?
+                }
+                (None, None) => anyhow::bail!("Pass a private key or --file"),
+            };
+            let output = expand_path(&output);
+            let mut config = load_config(config_path)?;
+            if let Some(dir) = output.parent().filter(|d| !d.as_os_str().is_empty()) {
+                config.wallet.storage.path = dir.to_path_buf();
+            }
+            let passphrase = read_new_passphrase()?;
+            let wallet = Wallet::import(name.clone(), keypair, &passphrase, config).await?;
+            println!("Imported wallet '{}': {}", name, wallet.public_key());
+        }
+        WalletCommands::Balance { wallet, tokens } => {
+            info!("Getting balance for wallet: {}", wallet.display());
+            let passphrase = read_passphrase(false)?;
+            let (name, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+            let owner = wallet.public_key();
+            let lamports = Lamports::new(wallet.rpc_client().get_balance(&owner).await?);
+            println!("Wallet '{}' ({}): {}", name, owner, format_sol(lamports));
+            if tokens {
+                let token_manager = wallet.token_manager();
+                let token_manager = token_manager.read().await;
+                let accounts = token_manager.get_wallet_token_accounts(&owner).await?;
+                if accounts.is_empty() {
+                    println!("No token accounts");
+                }
+                for account in accounts {
+                    match token_manager.get_token_info(&account.mint).await {
+                        Ok(info) => {
+                            let symbol = info
+                                .metadata
+                                .as_ref()
+                                .map(|metadata| metadata.symbol.trim())
+                                .filter(|symbol| !symbol.is_empty());
+                            println!(
+                                "  {}  {}  ({} base units)",
+                                account.mint,
+                                format_token_amount(account.balance, info.decimals, symbol),
+                                account.balance
+                            );
+                        }
+                        // Without the mint's decimals only base units are certain
+                        Err(e) => println!(
+                            "  {}  {} base units (mint unavailable: {})",
+                            account.mint, account.balance, e
+                        ),
+                    }
+                }
+            }
+        }
+        WalletCommands::Airdrop { name, amount } => {
+            use agent_wallet_core::Error as CoreError;
+
+            // Refuse a malformed amount before asking for the passphrase
+            let lamports = parse_sol_amount(&amount)?;
+            let passphrase = Zeroizing::new(
+                dialoguer::Password::new()
+                    .with_prompt("Passphrase")
+                    .interact()?,
+            );
+            let wallet = Wallet::load(name.clone(), &passphrase, load_config(config_path)?).await?;
+            match wallet.request_airdrop(lamports).await {
+                Ok(signature) => println!(
+                    "Airdropped {} to wallet '{}': {}",
+                    format_sol(lamports),
+                    name,
+                    signature
+                ),
+                Err(CoreError::RateLimitExceeded(_)) => anyhow::bail!(
+                    "The faucet is rate-limiting requests; wait a while and try again, \
+                     ask for less SOL, or use https://faucet.solana.com"
+                ),
+                Err(CoreError::PermissionDenied(_)) => anyhow::bail!(
+                    "The configured RPC endpoint serves mainnet-beta, which has no faucet"
+                ),
+                Err(e) => return Err(e.into()),
             }
-            // TODO: Implement balance checking
-            println!("Balance: 10.0 SOL (placeholder)");
         }
         WalletCommands::Info { wallet } => {
             info!("Getting info for wallet: {}", wallet.display());
-            // TODO: Implement wallet info
-            println!("Wallet info (placeholder)");
+            let passphrase = read_passphrase(false)?;
+            let (_, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+            let info = wallet.get_info().await?;
+            println!("Name:         {}", info.name);
+            println!("Public key:   {}", info.public_key);
+            println!("Balance:      {}", format_sol(Lamports::new(info.balance_lamports)));
+            println!("Transactions: {}", info.transaction_count_label());
+            println!("Permission:   {}", info.permission_level);
+            println!("Created:      {}", info.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            if info.token_accounts.is_empty() {
+                println!("No token accounts");
+            }
+            for token in &info.token_accounts {
+                let amount = token
+                    .ui_amount
+                    .map_or_else(|| "unknown amount".to_string(), |amount| amount.to_string());
+                match &token.symbol {
+                    Some(symbol) => println!("  {}  {} {}", token.mint, amount, symbol),
+                    None => println!("  {}  {}", token.mint, amount),
+                }
+            }
+        }
+        WalletCommands::RotatePassphrase { name } => {
+            let old = Zeroizing::new(
+                dialoguer::Password::new()
+                    .with_prompt("Current passphrase")
+                    .interact()?,
+            );
+            let wallet = Wallet::load(name.clone(), &old, load_config(config_path)?).await?;
+            let new = Zeroizing::new(
+                dialoguer::Password::new()
+                    .with_prompt("New passphrase")
+                    .with_confirmation("Repeat new passphrase", "Passphrases do not match")
+                    .interact()?,
+            );
+            if new.is_empty() {
+                anyhow::bail!("The new passphrase must not be empty");
+            }
+            wallet.change_passphrase(&old, &new).await?;
+            println!("Passphrase of wallet '{}' changed; the previous file was backed up", name);
+        }
+        WalletCommands::UpgradeEncryption { name } => {
+            let passphrase = Zeroizing::new(
+                dialoguer::Password::new()
+                    .with_prompt("Passphrase")
+                    .interact()?,
+            );
+            let config = load_config(config_path)?;
+            let kdf = config.wallet.encryption.kdf;
+            let wallet = Wallet::load(name.clone(), &passphrase, config).await?;
+            if wallet.upgrade_encryption(&passphrase).await? {
+                println!(
+                    "Wallet '{}' re-encrypted with {}; the previous file was backed up",
+                    name, kdf
+                );
+            } else {
+                println!("Wallet '{}' already uses {}", name, kdf);
+            }
+        }
+        WalletCommands::Backups { name } => {
+            let storage = StorageService::new(load_config(config_path)?.wallet.storage)?;
+            let backups = storage.list_backups(&name)?;
+            if backups.is_empty() {
+                println!("No backups of wallet '{}'", name);
+            }
+            for backup in backups.iter().rev() {
+                println!(
+                    "{}  {}  {} bytes",
+                    backup.version,
+                    backup.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                    backup.size
+                );
+            }
+        }
+        WalletCommands::Restore { name, version } => {
+            let storage = StorageService::new(load_config(config_path)?.wallet.storage)?;
+            let backup = match version {
+                Some(version) => storage.restore_wallet_version(&name, &version)?,
+                None => storage.restore_wallet(&name)?,
+            };
+            println!(
+                "Restored wallet '{}' from backup {} ({})",
+                name,
+                backup.version,
+                backup.path.display()
+            );
+        }
+        WalletCommands::Export { wallet, format, output, yes_i_know, force } => {
+            eprintln!("{}", SECRET_EXPORT_WARNING);
+            if !yes_i_know {
+                anyhow::bail!("Refusing to export the secret key without --yes-i-know");
+            }
+            let passphrase = read_passphrase(false)?;
+            // The operator confirmed the export and entered the passphrase at the terminal
+            let mut config = load_config(config_path)?;
+            config.agent.default_permission_level = agent_wallet_core::PermissionLevel::Administrator;
+            let (name, wallet) = open_wallet_with_config(&wallet, config, &passphrase).await?;
+            let output = expand_path(&output);
+            match format {
+                KeyFormat::SolanaJson => wallet.export_keypair(&passphrase, &output, force).await?,
+            }
+            println!("Exported the keypair of wallet '{}' to {}", name, output.display());
+        }
+        WalletCommands::ExportRecovery { wallet, output, force } => {
+            let passphrase = read_passphrase(false)?;
+            let (name, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+            let output = expand_path(&output);
+            wallet.export_recovery(&passphrase, &output, force).await?;
+            println!(
+                "Wrote a passphrase-only recovery copy of wallet '{}' to {}",
+                name,
+                output.display()
+            );
+            println!("It opens without this machine's keychain; store it as carefully as the passphrase.");
+        }
+        WalletCommands::Watch { name } => {
+            let passphrase = read_passphrase(false)?;
+            let wallet = Wallet::load(name.clone(), &passphrase, load_config(config_path)?).await?;
+            watch_wallet(&name, &wallet).await?;
         }
+        WalletCommands::Delete { name, confirm, force } => {
+            let config = load_config(config_path)?;
+            if !Wallet::exists(name.clone(), &config).await? {
+                anyhow::bail!("No stored wallet named '{}'", name);
+            }
+            let typed = match confirm {
+                Some(typed) => typed,
+                None => dialoguer::Input::<String>::new()
+                    .with_prompt(format!(
+                        "This destroys the key of '{}' and all its backups. Type its name to confirm",
+                        name
+                    ))
+                    .interact_text()?,
+            };
+            if typed.trim() != name {
+                anyhow::bail!("The typed name does not match; wallet '{}' was not deleted", name);
+            }
+            let report = Wallet::delete(name.clone(), &config, force).await?;
+            println!(
+                "Deleted wallet '{}': {} file(s) and {} backup(s) removed",
+                name, report.files_removed, report.backups_removed
+            );
+            if report.final_balance_lamports > 0 {
+                println!(
+                    "It still held {}; that balance is now unreachable",
+                    format_sol(Lamports::new(report.final_balance_lamports))
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print balance changes of a wallet until interrupted
+///
+/// Follows the wallet over websocket subscriptions when they are enabled and
+/// polls otherwise. Webhooks are alerted when the SOL balance falls below
+/// `monitoring.webhooks.balance_threshold_sol`; the wallet name stands in for
+/// the agent id, as no agent is involved.
+async fn watch_wallet(name: &str, wallet: &Wallet) -> Result<()> {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use agent_wallet_agent::notify::{AgentEvent, Notification, Notifier};
+    use agent_wallet_core::rpc::SubscriptionClient;
+    use agent_wallet_core::watch::BalanceEventKind;
+    use agent_wallet_core::BalanceWatcher;
+    use futures::StreamExt;
+
+    let config = wallet.config();
+    let owner = wallet.public_key();
+    let rpc = wallet.rpc_client();
+    let mut watcher = BalanceWatcher::new(owner, rpc.clone())
+        .with_commitment(config.rpc.commitment.to_solana_commitment());
+    match SubscriptionClient::from_settings(&config.rpc) {
+        Some(client) => watcher = watcher.with_subscriptions(Arc::new(client)),
+        None => info!("Websockets are disabled; polling for balance changes"),
+    }
+    let webhooks = &config.monitoring.webhooks;
+    let notifier = if webhooks.urls.is_empty() {
+        None
+    } else {
+        Some(WebhookNotifier::new(webhooks)?)
+    };
+    let mut below_threshold = false;
+    let mut check_threshold = |lamports: u64| {
+        let Some(threshold_sol) = webhooks.balance_threshold_sol else {
+            return;
+        };
+        let balance_sol = Lamports(lamports).to_sol_f64();
+        let below = balance_sol < threshold_sol;
+        if below && !below_threshold {
+            warn!("Balance of wallet '{}' fell below {} SOL", name, threshold_sol);
+            if let Some(notifier) = &notifier {
+                notifier.notify(Notification::new(
+                    AgentEvent::BalanceBelowThreshold {
+                        agent_id: name.to_string(),
+                        balance_sol,
+                        threshold_sol,
+                    },
+                    &owner,
+                ));
+            }
+        }
+        below_threshold = below;
+    };
+
+    let balance = rpc.get_balance(&owner).await?;
+    println!(
+        "Watching wallet '{}' ({}): {}",
+        name,
+        owner,
+        format_sol(Lamports(balance))
+    );
+    check_threshold(balance);
+
+    let token_manager = wallet.token_manager();
+    let mut mint_decimals: HashMap<solana_sdk::pubkey::Pubkey, u8> = HashMap::new();
+    let mut events = Box::pin(watcher.watch().await?);
+    loop {
+        let event = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            event = events.next() => match event {
+                Some(event) => event,
+                None => anyhow::bail!("Subscriptions of wallet '{}' ended", name),
+            },
+        };
+
+        let (label, decimals) = match event.mint {
+            None => {
+                check_threshold(event.balance);
+                ("SOL".to_string(), 9)
+            }
+            Some(mint) => {
+                if !mint_decimals.contains_key(&mint) {
+                    // Unknown mints print in base units
+                    let info = token_manager.read().await.get_token_info(&mint).await;
+                    mint_decimals.insert(mint, info.map_or(0, |info| info.decimals));
+                }
+                (format!("token {}", mint), mint_decimals[&mint])
+            }
+        };
+        let delta = format_token_amount(event.delta().unsigned_abs() as u64, decimals, None);
+        let sign = if event.delta() < 0 { "-" } else { "+" };
+        let note = match event.kind {
+            BalanceEventKind::Changed => "",
+            BalanceEventKind::Opened => " (account opened)",
+            BalanceEventKind::Closed => " (account closed)",
+        };
+        println!(
+            "{}  {}  {}{}  -> {}{}{}",
+            chrono::Utc::now().format("%H:%M:%S"),
+            label,
+            sign,
+            delta,
+            format_token_amount(event.balance, decimals, None),
+            note,
+            event
+                .signature
+                .map(|signature| format!("  {}", signature))
+                .unwrap_or_default()
+        );
     }
     Ok(())
 }
 
 /// Handle agent commands
-async fn handle_agent_command(cmd: AgentCommands) -> Result<()> {
+async fn handle_agent_command(cmd: AgentCommands, config_path: &std::path::Path) -> Result<()> {
     match cmd {
         AgentCommands::Run {
             r#type,
             wallet,
-            config,
+            strategy,
+            spec,
             daemon,
+            id,
+            interval,
+            dry_run,
+            passphrase_stdin,
+            data_dir,
         } => {
-            info!("Running {} agent with wallet: {}", r#type, wallet.display());
-            if let Some(config_path) = config {
-                info!("Using config: {}", config_path.display());
-            }
+            let agent_spec = spec
+                .as_deref()
+                .map(|path| AgentSpec::from_file(expand_path(path)))
+                .transpose()?;
+            let agent_type = match (r#type, &agent_spec) {
+                (Some(agent_type), Some(agent_spec))
+                    if agent_type != agent_spec.agent.type_name() =>
+                {
+                    anyhow::bail!(
+                        "--type {} contradicts the spec, which describes a {} agent",
+                        agent_type,
+                        agent_spec.agent.type_name()
+                    )
+                }
+                (Some(agent_type), _) => agent_type,
+                (None, Some(agent_spec)) => agent_spec.agent.type_name().to_string(),
+                (None, None) => anyhow::bail!("Either --type or --spec is required"),
+            };
+            let id = id
+                .or_else(|| agent_spec.as_ref().and_then(|s| s.name.clone()))
+                .unwrap_or_else(|| {
+                    format!("{}-{}", agent_type, &uuid::Uuid::new_v4().simple().to_string()[..8])
+                });
+            let run = RunSpec {
+                id,
+                agent_type,
+                wallet,
+                strategy,
+                interval: interval
+                    .or(agent_spec.as_ref().map(|s| s.poll_interval_seconds))
+                    .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+                dry_run: dry_run || agent_spec.as_ref().is_some_and(|s| s.dry_run),
+                data_dir,
+                spec_file: spec,
+                agent_spec,
+            };
+            let passphrase = read_passphrase(passphrase_stdin)?;
             if daemon {
-                info!("Running in background (daemon mode)");
+                spawn_daemon(&run, config_path, &passphrase)?;
+            } else {
+                let registry = open_registry(config_path)?;
+                run_agent(run, config_path, &registry, &passphrase).await?;
             }
-            // TODO: Implement agent execution
-            info!("Agent started (placeholder implementation)");
+        }
+        AgentCommands::Validate { file } => {
+            let spec = AgentSpec::from_file(expand_path(&file))?;
+            println!("{} is a valid agent spec", file.display());
+            print_agent_spec(&spec);
         }
         AgentCommands::List { detailed } => {
-            if detailed {
-                info!("Listing all agents with details");
-            } else {
-                info!("Listing all agents");
+            let registry = open_registry(config_path)?;
+            let entries = registry.list()?;
+            if entries.is_empty() {
+                println!("No registered agents");
+            }
+            let now = chrono::Utc::now();
+            for entry in &entries {
+                println!(
+                    "{:<24} {:<14} {:<8} wallet {:<16} decisions {:<6} heartbeat {}",
+                    entry.id,
+                    entry.agent_type,
+                    format!("{:?}", registry.status_of(entry, now)),
+                    entry.wallet,
+                    entry.decisions,
+                    entry.last_heartbeat.format("%Y-%m-%d %H:%M:%S")
+                );
+                if detailed {
+                    print_registry_details(entry);
+                }
             }
-            // TODO: Implement agent listing
-            println!("Agents list (placeholder)");
         }
         AgentCommands::Stop { id } => {
-            info!("Stopping agent: {}", id);
-            // TODO: Implement agent stopping
-            info!("Agent stopped (placeholder implementation)");
+            let registry = open_registry(config_path)?;
+            if !registry.request_stop(&id)? {
+                anyhow::bail!("Agent {} is not registered", id);
+            }
+            println!("Stop requested for agent {}", id);
         }
-        AgentCommands::Status { id } => {
-            info!("Getting status for agent: {}", id);
-            // TODO: Implement agent status
-            println!("Agent status (placeholder)");
+        AgentCommands::Status { id, data_dir } => {
+            let registry = open_registry(config_path)?;
+            match registry.get(&id)? {
+                Some(entry) => {
+                    println!("Agent:     {}", entry.id);
+                    println!("Status:    {:?}", registry.status_of(&entry, chrono::Utc::now()));
+                    println!("Type:      {}", entry.agent_type);
+                    println!("Wallet:    {}", entry.wallet);
+                    println!("Decisions: {}", entry.decisions);
+                    if entry.dry_run {
+                        println!("Mode:      dry run (nothing is sent)");
+                    }
+                    print_registry_details(&entry);
+                    print_agent_stats(&entry.stats);
+                }
+                None => println!("Agent {} is not registered", id),
+            }
+            match open_dead_letters(&id, &data_dir) {
+                Ok(queue) => print_dead_letters(&queue.list(chrono::Utc::now())),
+                Err(e) => warn!("Dead letters of {} unavailable: {}", id, e),
+            }
+        }
+        AgentCommands::Approve { token, reject, data_dir } => {
+            let decision = if reject {
+                ApprovalDecision::Reject
+            } else {
+                ApprovalDecision::Approve
+            };
+            let now = chrono::Utc::now();
+            let workspaces = AgentWorkspaces::open(expand_path(&data_dir))?;
+            let mut answered = None;
+            for agent_id in workspaces.list()? {
+                let store = ApprovalStore::open(workspaces.open_workspace(&agent_id)?)?;
+                if store.get(&token, now)?.is_some() {
+                    answered = Some(store.resolve(&token, decision, now)?);
+                    break;
+                }
+            }
+            let Some(request) = answered else {
+                anyhow::bail!("No approval request with token {}", token);
+            };
+            println!(
+                "{:?}: {} (agent {})",
+                request.status, request.description, request.agent_id
+            );
+        }
+        AgentCommands::DeadLetter(DeadLetterCommands::List { id, data_dir }) => {
+            let queue = open_dead_letters(&id, &data_dir)?;
+            print_dead_letters(&queue.list(chrono::Utc::now()));
+        }
+        AgentCommands::DeadLetter(DeadLetterCommands::Clear { id, digest, data_dir }) => {
+            let queue = open_dead_letters(&id, &data_dir)?;
+            let released = queue.clear(digest.as_deref())?;
+            println!("Released {} dead-lettered action(s) of {}", released, id);
         }
         AgentCommands::Logs {
             id,
             lines,
             follow,
         } => {
-            info!("Getting logs for agent: {}", id);
-            info!("Lines: {}, Follow: {}", lines, follow);
-            // TODO: Implement agent logs
-            println!("Agent logs (placeholder)");
+            let config = load_config(config_path)?;
+            let settings = &config.monitoring.audit;
+            let Some(path) = &settings.path else {
+                anyhow::bail!("No audit file configured; set monitoring.audit.path");
+            };
+            let sink = JsonlAuditSink::open(
+                expand_path(path),
+                settings.max_file_bytes,
+                settings.max_files,
+            )?;
+
+            let entries: Vec<AuditEntry> = sink
+                .entries()?
+                .into_iter()
+                .filter(|entry| entry.agent_id.as_deref() == Some(id.as_str()))
+                .collect();
+            let skip = entries.len().saturating_sub(lines);
+            let mut last_shown = entries.last().map(|entry| entry.timestamp);
+            for entry in &entries[skip..] {
+                print_audit_entry(entry);
+            }
+
+            while follow {
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                for entry in sink.entries()? {
+                    if entry.agent_id.as_deref() != Some(id.as_str())
+                        || last_shown.is_some_and(|shown| entry.timestamp <= shown)
+                    {
+                        continue;
+                    }
+                    print_audit_entry(&entry);
+                    last_shown = Some(entry.timestamp);
+                }
+            }
         }
     }
     Ok(())
 }
 
+/// Agent started by `agent run`
+struct RunSpec {
+    id: String,
+    agent_type: String,
+    wallet: PathBuf,
+    strategy: Option<PathBuf>,
+    interval: u64,
+    dry_run: bool,
+    /// Directory holding the agent workspaces
+    data_dir: PathBuf,
+    /// Spec file the agent was described in, passed on to daemons
+    spec_file: Option<PathBuf>,
+    agent_spec: Option<AgentSpec>,
+}
+
+/// Summarize a validated agent spec
+fn print_agent_spec(spec: &AgentSpec) {
+    if let Some(name) = &spec.name {
+        println!("Name:      {}", name);
+    }
+    println!("Type:      {}", spec.agent.type_name());
+    match &spec.agent {
+        AgentKind::Deterministic { strategy } => match spec.strategy() {
+            Some(strategy) => println!("Strategy:  {:?}", strategy),
+            // A recipient domain is only resolved when the agent runs
+            None => println!("Strategy:  {:?}", strategy),
+        },
+        AgentKind::Llm { model } => println!("Model:     {}", model.model),
+    }
+    println!("Interval:  {}s", spec.poll_interval_seconds);
+    if spec.dry_run {
+        println!("Mode:      dry run (nothing is sent)");
+    }
+    let limits = spec.limits();
+    println!(
+        "Limits:    {} SOL per transaction, {} transactions per hour",
+        limits.max_sol_per_transaction, limits.max_transactions_per_hour
+    );
+    if !spec.watchlist.is_empty() {
+        println!("Watchlist: {}", spec.watchlist.join(", "));
+    }
+}
+
+/// Open the agent registry kept next to the configuration file
+fn open_registry(config_path: &std::path::Path) -> Result<AgentRegistry> {
+    let config = expand_path(config_path);
+    let dir = config.parent().map_or_else(|| PathBuf::from("."), |p| p.to_path_buf());
+    Ok(AgentRegistry::open(dir.join(REGISTRY_DIR))?)
+}
+
+/// Store of agent state next to the configuration file
+fn open_state_store(config_path: &std::path::Path) -> Result<FileStateStore> {
+    let config = expand_path(config_path);
+    let dir = config.parent().map_or_else(|| PathBuf::from("."), |p| p.to_path_buf());
+    Ok(FileStateStore::new(AgentWorkspaces::open(dir)?))
+}
+
+/// Print the registry fields shown by `--detailed` and `status`
+fn print_registry_details(entry: &RegistryEntry) {
+    if let Some(pid) = entry.pid {
+        println!("  pid:            {}", pid);
+    }
+    println!("  started:        {}", entry.started_at.format("%Y-%m-%d %H:%M:%S"));
+    println!("  last heartbeat: {}", entry.last_heartbeat.format("%Y-%m-%d %H:%M:%S"));
+    if let Some(strategy) = &entry.strategy {
+        println!("  strategy:       {}", strategy);
+    }
+    if let Some(error) = &entry.last_error {
+        println!("  last error:     {}", error);
+    }
+}
+
+/// Print the outcome statistics shown by `status`
+fn print_agent_stats(stats: &agent_wallet_core::AgentStats) {
+    let now = chrono::Utc::now();
+    let summary = stats.summary(now);
+    println!("Statistics:");
+    println!("  executed:       {}", summary.executed);
+    println!("  failed:         {}", summary.failed);
+    println!("  success rate:   {:.0}%", summary.success_rate * 100.0);
+    println!("  spent:          {}", Lamports(summary.lamports_spent));
+    println!("  fees:           {}", Lamports(summary.fee_lamports));
+    for (kind, executed) in &summary.actions_today {
+        println!("  today {:<16} {}", kind, executed);
+    }
+    if !stats.recent.is_empty() {
+        println!("Recent outcomes:");
+    }
+    for outcome in stats.recent.iter().rev() {
+        let result = match (&outcome.signature, &outcome.error) {
+            (Some(signature), _) => signature.to_string(),
+            (None, Some(error)) => format!("failed: {}", error),
+            (None, None) => "failed".to_string(),
+        };
+        println!(
+            "  {} {:<20} {}",
+            outcome.at.format("%Y-%m-%d %H:%M:%S"),
+            outcome.action,
+            result
+        );
+    }
+}
+
+/// Wallet passphrase from the environment, stdin or an interactive prompt
+fn read_passphrase(from_stdin: bool) -> Result<Zeroizing<String>> {
+    if from_stdin {
+        let mut line = Zeroizing::new(String::new());
+        std::io::stdin().read_line(&mut line)?;
+        return Ok(Zeroizing::new(line.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    Ok(Zeroizing::new(
+        dialoguer::Password::new()
+            .with_prompt("Wallet passphrase")
+            .interact()?,
+    ))
+}
+
+/// Passphrase for a new wallet from the environment or a confirmed prompt
+fn read_new_passphrase() -> Result<Zeroizing<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    let passphrase = Zeroizing::new(
+        dialoguer::Password::new()
+            .with_prompt("New wallet passphrase")
+            .with_confirmation("Repeat passphrase", "Passphrases do not match")
+            .interact()?,
+    );
+    if passphrase.is_empty() {
+        anyhow::bail!("The passphrase must not be empty");
+    }
+    Ok(passphrase)
+}
+
+/// Re-run `agent run` as a detached process logging into the registry
+fn spawn_daemon(
+    spec: &RunSpec,
+    config_path: &std::path::Path,
+    passphrase: &Zeroizing<String>,
+) -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let registry = open_registry(config_path)?;
+    let log_path = registry.dir().join(format!("{}.log", spec.id));
+    let log = std::fs::OpenOptions::new().create(true).append(true).open(&log_path)?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("--config")
+        .arg(config_path)
+        .args(["agent", "run", "--type", &spec.agent_type, "--id", &spec.id])
+        .arg("--wallet")
+        .arg(&spec.wallet)
+        .args(["--interval", &spec.interval.to_string(), "--passphrase-stdin"])
+        .arg("--data-dir")
+        .arg(expand_path(&spec.data_dir))
+        .stdin(Stdio::piped())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    if let Some(strategy) = &spec.strategy {
+        command.arg("--strategy").arg(strategy);
+    }
+    if let Some(file) = &spec.spec_file {
+        command.arg("--spec").arg(expand_path(file));
+    }
+    if spec.dry_run {
+        command.arg("--dry-run");
+    }
+    #[cfg(unix)]
+    {
+        // Leave the terminal's process group so the agent outlives the shell
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", passphrase.as_str())?;
+    }
+    println!("Started agent {} (pid {})", spec.id, child.id());
+    println!("Logs: {}", log_path.display());
+    Ok(())
+}
+
+/// Run an agent in the foreground until it is stopped
+async fn run_agent(
+    spec: RunSpec,
+    config_path: &std::path::Path,
+    registry: &AgentRegistry,
+    passphrase: &Zeroizing<String>,
+) -> Result<()> {
+    use std::sync::Arc;
+
+    let mode = if spec.dry_run { RunMode::DryRun } else { RunMode::Live };
+    let mut config = load_config(config_path)?;
+    config.dry_run |= mode == RunMode::DryRun;
+    if let Some(agent_spec) = spec.agent_spec.as_ref().filter(|s| !s.watchlist.is_empty()) {
+        config.agent.context.watchlist = agent_spec.watchlist.clone();
+    }
+    let (wallet_name, wallet) = open_wallet_with_config(&spec.wallet, config, passphrase).await?;
+    let wallet = Arc::new(wallet);
+
+    let (agent, strategy_json): (Arc<dyn DynAgent>, serde_json::Value) = match &spec.agent_spec {
+        Some(agent_spec) => {
+            let mut named = agent_spec.clone();
+            named.name = Some(spec.id.clone());
+            // The registry keeps the domain so it is resolved again on restart
+            named.resolve_recipients(&wallet).await?;
+            if let Some(strategy) = named.strategy() {
+                strategy.check_recipient(&wallet.public_key())?;
+            }
+            (Arc::from(named.to_agent()?), serde_json::to_value(agent_spec)?)
+        }
+        None => {
+            let strategy_json: serde_json::Value = match &spec.strategy {
+                Some(path) => {
+                    serde_json::from_str(&std::fs::read_to_string(expand_path(path))?)?
+                }
+                None => {
+                    anyhow::bail!("A strategy configuration (--strategy or --spec) is required")
+                }
+            };
+            let agent: Arc<dyn DynAgent> = match spec.agent_type.as_str() {
+                "deterministic" => {
+                    let strategy: DeterministicStrategy =
+                        serde_json::from_value(strategy_json.clone())?;
+                    strategy.check_recipient(&wallet.public_key())?;
+                    let agent = DeterministicAgent::try_new(strategy, &AgentLimits::default())
+                        .map_err(|e| anyhow::anyhow!("Invalid strategy: {}", e))?;
+                    Arc::new(agent.with_name(spec.id.clone()))
+                }
+                other => anyhow::bail!("Unsupported agent type '{}'", other),
+            };
+            (agent, strategy_json)
+        }
+    };
+
+    let audit_sink = wallet.audit_sink().await;
+    let webhooks = wallet.config().monitoring.webhooks.clone();
+    let approval = wallet.config().agent.approval.clone();
+    let shutdown = CancellationToken::new();
+    cancel_on_signal(shutdown.clone());
+    let mut metrics_server = match MetricsServer::start_with_shutdown(
+        &wallet.config().monitoring.metrics,
+        Metrics::global(),
+        &shutdown,
+    )
+    .await
+    {
+        Ok(server) => server,
+        Err(e) => {
+            warn!("Metrics are not exported: {}", e);
+            None
+        }
+    };
+    let rpc = wallet.rpc_client();
+    let subscriptions = wallet.subscriptions();
+
+    // Statistics carry over from earlier runs under the same id
+    let stats = registry.get(&spec.id)?.map(|entry| entry.stats).unwrap_or_default();
+    registry.register(
+        RegistryEntry::new(spec.id.clone(), spec.agent_type.clone(), wallet_name)
+            .with_strategy(strategy_json)
+            .with_dry_run(wallet.run_mode() == RunMode::DryRun),
+    )?;
+    // Strategy state carries over too, so a restart does not fire early
+    let mut runner = AgentRunner::new(spec.id.clone(), agent, wallet)
+        .with_stats(stats)
+        .with_state_store(Arc::new(open_state_store(config_path)?))
+        .with_run_mode(mode)
+        .with_shutdown(shutdown.clone());
+    if let Some(limit) = spec.agent_spec.as_ref().and_then(AgentSpec::rate_limit) {
+        runner = runner.with_rate_limit(limit);
+    }
+    if let Some(sink) = audit_sink {
+        runner = runner.with_audit_sink(sink);
+    }
+    if !webhooks.urls.is_empty() {
+        runner = runner.with_notifier(Arc::new(WebhookNotifier::new(&webhooks)?));
+    }
+    if let Some(threshold) = webhooks.balance_threshold_sol {
+        runner = runner.with_balance_alert(threshold);
+    }
+    if let Some(policy) = approval {
+        let workspace = AgentWorkspaces::open(expand_path(&spec.data_dir))?.register(&spec.id)?;
+        let gate = ApprovalGate::new(policy, ApprovalStore::open(workspace)?)?;
+        runner = runner.with_approval_gate(gate);
+    }
+    if let Err(e) = runner.start().await {
+        registry.mark_stopped(&spec.id, Some(e.to_string()))?;
+        return Err(e.into());
+    }
+    if runner.run_mode() == RunMode::DryRun {
+        info!("Agent {} is a dry run; no transaction will be sent", spec.id);
+    }
+    info!("Agent {} running, deciding every {}s", spec.id, spec.interval);
+
+    let mut decide = tokio::time::interval(std::time::Duration::from_secs(spec.interval.max(1)));
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut decisions = 0u64;
+    let mut last_error = None;
+    let exit_error = loop {
+        tokio::select! {
+            () = shutdown.cancelled() => {
+                info!("Interrupted, stopping agent {}", spec.id);
+                break None;
+            }
+            _ = heartbeat.tick() => {}
+            _ = decide.tick() => {
+                match runner.tick().await {
+                    Ok(DecisionOutcome::Failed { reason }) => {
+                        decisions += 1;
+                        last_error = Some(reason);
+                    }
+                    Ok(DecisionOutcome::TimedOut { timeout_ms }) => {
+                        warn!("Agent {} decision timed out after {}ms", spec.id, timeout_ms);
+                        last_error = Some(format!("decision timed out after {}ms", timeout_ms));
+                    }
+                    Ok(_) => decisions += 1,
+                    Err(e) => {
+                        error!("Agent {} tick failed: {}", spec.id, e);
+                        last_error = Some(e.to_string());
+                    }
+                }
+                if runner.status() == AgentStatus::Error {
+                    break last_error.take().or_else(|| Some("agent entered error state".to_string()));
+                }
+            }
+        }
+        if registry.stop_requested(&spec.id)? {
+            info!("Stop requested, stopping agent {}", spec.id);
+            break None;
+        }
+        registry.heartbeat(&spec.id, decisions, last_error.take())?;
+        registry.record_stats(&spec.id, runner.stats())?;
+    };
+
+    // Settle transactions still in flight before anything else stops
+    if let Err(e) = runner.shutdown(DEFAULT_SHUTDOWN_TIMEOUT).await {
+        warn!("Agent {} did not shut down cleanly: {}", spec.id, e);
+    }
+    if let Some(server) = &mut metrics_server {
+        if let Err(e) = server.shutdown(DEFAULT_SHUTDOWN_TIMEOUT).await {
+            warn!("{}", e);
+        }
+    }
+    if let Some(subscriptions) = subscriptions {
+        if let Err(e) = subscriptions.shutdown(DEFAULT_SHUTDOWN_TIMEOUT).await {
+            warn!("{}", e);
+        }
+    }
+    if let Err(e) = rpc.shutdown(DEFAULT_SHUTDOWN_TIMEOUT).await {
+        warn!("{}", e);
+    }
+    registry.heartbeat(&spec.id, decisions, None)?;
+    registry.record_stats(&spec.id, runner.stats())?;
+    registry.mark_stopped(&spec.id, exit_error.clone())?;
+    match exit_error {
+        Some(reason) => anyhow::bail!("Agent {} stopped with an error: {}", spec.id, reason),
+        None => {
+            info!("Agent {} stopped after {} decisions", spec.id, decisions);
+            Ok(())
+        }
+    }
+}
+
+/// Cancel `shutdown` on Ctrl-C, or on SIGTERM on Unix
+fn cancel_on_signal(shutdown: CancellationToken) {
+    tokio::spawn(async move {
+        tokio::select! {
+            () = shutdown_requested() => {
+                info!("Shutdown requested, finishing work in flight");
+                shutdown.cancel();
+            }
+            () = shutdown.cancelled() => {}
+        }
+    });
+}
+
+/// Wait for the process to be asked to stop
+async fn shutdown_requested() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("SIGTERM is not handled: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("Ctrl-C is not handled: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Print one audit record on a line
+fn print_audit_entry(entry: &AuditEntry) {
+    let phase = match entry.phase {
+        AuditPhase::Intent => "intent".to_string(),
+        AuditPhase::Outcome => entry
+            .outcome
+            .map_or("outcome".to_string(), |outcome| format!("{:?}", outcome).to_lowercase()),
+    };
+    let action = entry.action.as_ref().map_or(String::new(), |action| action.description());
+    print!(
+        "{} {} {:<11} {}",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        entry.id,
+        phase,
+        action
+    );
+    if let Some(signature) = entry.signature {
+        print!(" sig {}", signature);
+    }
+    if let Some(fee) = entry.fee_lamports {
+        print!(" fee {}", fee);
+    }
+    if let Some(error) = &entry.error {
+        print!(" error: {}", error);
+    }
+    if entry.dry_run {
+        print!(" [dry run]");
+    }
+    println!();
+}
+
+/// Open the persisted dead-letter queue of an agent
+fn open_dead_letters(id: &str, data_dir: &std::path::Path) -> Result<DeadLetterQueue> {
+    let workspace = AgentWorkspaces::open(expand_path(data_dir))?.open_workspace(id)?;
+    Ok(DeadLetterQueue::open(workspace, DeadLetterConfig::default())?)
+}
+
+/// Print parked actions with their validation issues
+fn print_dead_letters(letters: &[DeadLetter]) {
+    if letters.is_empty() {
+        println!("No dead-lettered actions");
+        return;
+    }
+    println!("Dead-lettered actions:");
+    for letter in letters {
+        println!("  {} parked {} after {} failures", letter.digest, letter.parked_at, letter.failures);
+        println!("    action: {}", letter.action);
+        for issue in &letter.issues {
+            println!("    issue:  {}", issue);
+        }
+    }
+}
+
 /// Handle transaction commands
-async fn handle_transaction_command(cmd: TransactionCommands) -> Result<()> {
+async fn handle_transaction_command(
+    cmd: TransactionCommands,
+    config_path: &std::path::Path,
+) -> Result<()> {
     match cmd {
         TransactionCommands::Transfer {
             wallet,
             to,
             amount,
             memo,
+            nonce_account,
+            allow_program_destination,
             yes,
         } => {
-            info!("Transferring {} SOL to {}", amount, to);
+            // Fails early, with a suggestion, for anything but an address or .sol domain
+            let recipient: agent_wallet_core::Recipient = to.parse()?;
+            let nonce_account: Option<solana_sdk::pubkey::Pubkey> =
+                nonce_account.map(|account| account.parse()).transpose()?;
+            let lamports = parse_sol_amount(&amount)?;
+            info!("Transferring {} to {}", format_sol(lamports), recipient);
             info!("Wallet: {}", wallet.display());
-            if let Some(memo_text) = memo {
+            if let Some(memo_text) = &memo {
                 info!("Memo: {}", memo_text);
             }
+
+            let passphrase = read_passphrase(false)?;
+            let (_, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+            let to = wallet.resolve_recipient(&to).await?;
+            if let Some(domain) = recipient.domain() {
+                println!("{} resolves to {}", domain, to);
+            }
+            let destination = DestinationOptions {
+                allow_program_destination,
+                ..Default::default()
+            };
             if !yes {
-                info!("Confirmation would be prompted here");
+                // Show the recipient and what the transfer does before asking
+                let check = wallet
+                    .validate_destination(
+                        &to,
+                        &DestinationTransfer::Sol { lamports },
+                        &destination,
+                    )
+                    .await?;
+                println!("Destination: {}", check.info);
+                for warning in &check.warnings {
+                    println!("Warning: {}", warning);
+                }
+                let action = AgentAction::TransferSol {
+                    to,
+                    amount: lamports,
+                    memo: memo.clone(),
+                };
+                print_preview(&wallet.preview_action(&action).await?);
+                if !dialoguer::Confirm::new()
+                    .with_prompt(format!("Send {} to {}?", format_sol(lamports), to))
+                    .interact()?
+                {
+                    println!("Transfer cancelled");
+                    return Ok(());
+                }
             }
-            // TODO: Implement SOL transfer
-            info!("Transfer completed (placeholder implementation)");
+            let options = TransactionOptions {
+                // The wallet key is the nonce authority
+                nonce: nonce_account.map(|account| NonceInfo::new(account, wallet.public_key())),
+                destination,
+                ..Default::default()
+            };
+            let signature = wallet
+                .transfer_sol_with_options(recipient, lamports, memo, &options)
+                .await?;
+            println!("Transfer sent: {}", signature);
+        }
+        TransactionCommands::Pay {
+            wallet,
+            uri,
+            strict,
+            yes,
+        } => {
+            use agent_wallet_core::TransferRequest;
+
+            let request = if strict {
+                TransferRequest::parse_strict(&uri)?
+            } else {
+                TransferRequest::parse(&uri)?
+            };
+            let Some(amount) = &request.amount else {
+                anyhow::bail!("The request has no amount; pay it with `tx transfer` instead");
+            };
+            let asset = match &request.spl_token {
+                Some(mint) => format!("of token {}", mint),
+                None => "SOL".to_string(),
+            };
+            println!("Pay {} {} to {}", amount, asset, request.recipient);
+            if let Some(label) = &request.label {
+                println!("Label: {}", label);
+            }
+            if let Some(message) = &request.message {
+                println!("Message: {}", message);
+            }
+            if let Some(memo) = &request.memo {
+                println!("Memo: {}", memo);
+            }
+            for reference in &request.references {
+                println!("Reference: {}", reference);
+            }
+            if !yes
+                && !dialoguer::Confirm::new()
+                    .with_prompt("Send this payment?")
+                    .interact()?
+            {
+                println!("Payment cancelled");
+                return Ok(());
+            }
+
+            let passphrase = read_passphrase(false)?;
+            let (_, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+            let signature = wallet.pay(&request).await?;
+            println!("Payment sent: {}", signature);
         }
         TransactionCommands::History {
             wallet,
@@ -446,50 +2163,411 @@ async fn handle_transaction_command(cmd: TransactionCommands) -> Result<()> {
             // TODO: Implement transaction history
             println!("Transaction history (placeholder)");
         }
-        TransactionCommands::Status { signature } => {
-            info!("Getting status for transaction: {}", signature);
-            // TODO: Implement transaction status
-            println!("Transaction status (placeholder)");
+        TransactionCommands::Status {
+            signature,
+            wait,
+            finalized,
+            timeout,
+        } => {
+            use agent_wallet_core::rpc::poll_for_confirmation;
+            use agent_wallet_core::{Error as CoreError, RpcClient, RpcClientConfig};
+            use solana_sdk::commitment_config::CommitmentConfig;
+            use solana_sdk::signature::Signature;
+
+            let signature: Signature = signature
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid signature '{}': {}", signature, e))?;
+            let config = load_config(config_path)?;
+            let rpc = RpcClient::new(RpcClientConfig::from_settings(&config.rpc)).await?;
+
+            if wait {
+                let commitment = if finalized {
+                    CommitmentConfig::finalized()
+                } else {
+                    CommitmentConfig::confirmed()
+                };
+                info!("Waiting for {} to reach {:?}", signature, commitment.commitment);
+                // A failed transaction has landed too; its error is printed below
+                if let Err(e @ CoreError::ConfirmationTimeout { .. }) = poll_for_confirmation(
+                    &rpc,
+                    &signature,
+                    commitment,
+                    std::time::Duration::from_secs(timeout),
+                    std::time::Duration::from_millis(500),
+                )
+                .await
+                {
+                    return Err(e.into());
+                }
+            }
+
+            let status = rpc
+                .get_signature_statuses(&[signature])
+                .await?
+                .into_iter()
+                .next()
+                .flatten();
+            let Some(status) = status else {
+                println!(
+                    "Transaction {} not found: not processed yet, or its blockhash expired",
+                    signature
+                );
+                return Ok(());
+            };
+            println!("Status:    {:?}", status.confirmation_status());
+            match rpc.get_transaction(&signature).await {
+                Ok(details) => print!("{}", details),
+                Err(e) => {
+                    // Not every node serves the transaction yet; the status still does
+                    info!("Transaction details unavailable: {}", e);
+                    println!("Slot:      {}", status.slot);
+                    if let Some(err) = &status.err {
+                        println!("Result:    failed: {}", err);
+                    }
+                }
+            }
+        }
+        TransactionCommands::Simulate {
+            input,
+            wallet,
+            json,
+        } => {
+            let input = read_input(&input)?;
+            let value: serde_json::Value = serde_json::from_str(&input)?;
+
+            // Envelopes carry a transaction; anything else is an action
+            if value.get("transaction").is_some() {
+                let envelope = TransactionEnvelope::from_json(&input)?;
+                let transaction = envelope.transaction()?;
+                if let Some(description) = &envelope.description {
+                    info!("Simulating {}", description);
+                }
+
+                let passphrase = read_passphrase(false)?;
+                let (_, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+                let report = wallet.simulation_report(&transaction).await?;
+                if json {
+                    println!("{}", report.to_json()?);
+                } else {
+                    print!("{}", report);
+                }
+            } else {
+                let action: AgentAction = serde_json::from_value(value)?;
+                info!("Simulating {}", action.description());
+
+                let passphrase = read_passphrase(false)?;
+                let (_, wallet) = open_wallet(&wallet, config_path, &passphrase).await?;
+                let preview = wallet.preview_action(&action).await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&preview)?);
+                } else {
+                    print_preview(&preview);
+                }
+            }
+        }
+        TransactionCommands::Sign {
+            transaction,
+            keypair,
+            partial,
+        } => {
+            let mut transaction = read_transaction(&transaction)?;
+            let keypair = read_keypair(&keypair)?;
+            multisig::partial_sign(&mut transaction, &keypair)?;
+
+            let missing = multisig::missing_signers(&transaction);
+            if !partial && !missing.is_empty() {
+                anyhow::bail!(
+                    "Transaction still needs {} signature(s); pass --partial to hand it on",
+                    missing.len()
+                );
+            }
+            print_transaction(&transaction)?;
+        }
+        TransactionCommands::Merge { transactions } => {
+            let mut copies = transactions.iter().map(|t| read_transaction(t));
+            let mut merged = match copies.next() {
+                Some(first) => first?,
+                None => anyhow::bail!("No transactions to merge"),
+            };
+            for copy in copies {
+                multisig::merge_signatures(&mut merged, &copy?)?;
+            }
+            print_transaction(&merged)?;
         }
-        TransactionCommands::Simulate { transaction } => {
-            info!("Simulating transaction: {}", transaction);
-            // TODO: Implement transaction simulation
-            println!("Transaction simulation (placeholder)");
+        TransactionCommands::Build {
+            action,
+            from,
+            nonce_account,
+            output,
+        } => {
+            use agent_wallet_core::{
+                AgentContext, RpcClient, RpcClientConfig, RpcProvider, TransactionBuilder,
+            };
+
+            let action: AgentAction = serde_json::from_str(&read_input(&action)?)?;
+            let from: solana_sdk::pubkey::Pubkey = from.parse()?;
+            let nonce_account: Option<solana_sdk::pubkey::Pubkey> =
+                nonce_account.map(|account| account.parse()).transpose()?;
+            let config = load_config(config_path)?;
+            let rpc = RpcClient::new(RpcClientConfig::from_settings(&config.rpc)).await?;
+
+            let mut options = TransactionOptions::default();
+            // A nonce transaction signs over the stored nonce and never
+            // expires; otherwise the blockhash lasts the validity window
+            let (blockhash, expiry_slot) = match nonce_account {
+                Some(account) => {
+                    options.nonce = Some(NonceInfo::new(account, from));
+                    (rpc.get_nonce_account(&account).await?.blockhash, None)
+                }
+                None => {
+                    let blockhash = rpc.get_latest_blockhash().await?;
+                    let slot = rpc.get_slot().await?;
+                    (blockhash, Some(slot + options.blockhash_validity_slots))
+                }
+            };
+
+            let mut context = AgentContext::new(from);
+            context.address_policy = config.agent.address_policy.clone();
+            context.spending_limits.token_limits = config.agent.limits.token_limits();
+            context.spending_limits.unlisted_tokens = config.agent.limits.unlisted_tokens;
+            let transaction =
+                TransactionBuilder::new().build_unsigned(&action, &context, &options, blockhash)?;
+            let envelope = UnsignedEnvelope::new(&transaction, expiry_slot)
+                .with_description(action.description())
+                .with_action(action);
+            write_output(&envelope.to_json()?, output.as_deref())?;
+            match expiry_slot {
+                Some(slot) => eprintln!("Sign and broadcast before slot {}", slot),
+                None => eprintln!("Envelope uses a durable nonce and does not expire"),
+            }
+        }
+        TransactionCommands::SignOffline {
+            envelope,
+            wallet,
+            output,
+            yes,
+        } => {
+            let envelope = UnsignedEnvelope::from_json(&read_input(&envelope)?)?;
+            if !yes {
+                let description = envelope
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| "an undescribed transaction".to_string());
+                if !dialoguer::Confirm::new()
+                    .with_prompt(format!("Sign {}?", description))
+                    .interact()?
+                {
+                    println!("Signing cancelled");
+                    return Ok(());
+                }
+            }
+
+            let passphrase = read_passphrase(false)?;
+            let wallet = open_wallet_offline(&wallet, config_path, &passphrase).await?;
+            let signed = wallet.sign_offline(&envelope).await?;
+            write_output(&signed.to_json()?, output.as_deref())?;
+        }
+        TransactionCommands::Broadcast { envelope } => {
+            use agent_wallet_core::{RpcClient, RpcClientConfig};
+
+            let envelope = SignedEnvelope::from_json(&read_input(&envelope)?)?;
+            let config = load_config(config_path)?;
+            let rpc = RpcClient::new(RpcClientConfig::from_settings(&config.rpc)).await?;
+            let signature = rpc.broadcast_envelope(&envelope).await?;
+            println!("Transaction sent: {}", signature);
         }
     }
     Ok(())
 }
 
 /// Handle configuration commands
-async fn handle_config_command(cmd: ConfigCommands) -> Result<()> {
+async fn handle_config_command(cmd: ConfigCommands, config_path: &std::path::Path) -> Result<()> {
     match cmd {
         ConfigCommands::Init { force } => {
-            info!("Initializing configuration");
-            if force {
-                info!("Force overwrite enabled");
+            let path = expand_path(config_path);
+            if path.exists() && !force {
+                anyhow::bail!("{} already exists; pass --force to overwrite it", path.display());
             }
-            // TODO: Implement config initialization
-            info!("Configuration initialized (placeholder implementation)");
+            save_config(&WalletConfig::default(), &path)?;
+            println!("Wrote default configuration to {}", path.display());
         }
-        ConfigCommands::Show { json, yaml } => {
-            info!("Showing configuration");
+        ConfigCommands::Show { json, yaml: _ } => {
+            let config = load_config(config_path)?;
             if json {
-                info!("Output format: JSON");
-            } else if yaml {
-                info!("Output format: YAML");
+                println!("{}", serde_json::to_string_pretty(&config)?);
+            } else {
+                print!("{}", serde_yaml::to_string(&config)?);
             }
-            // TODO: Implement config display
-            println!("Configuration (placeholder)");
         }
         ConfigCommands::Set { key, value } => {
-            info!("Setting config key '{}' to '{}'", key, value);
-            // TODO: Implement config setting
-            info!("Configuration updated (placeholder implementation)");
+            // Environment overrides are not written back to the file
+            let mut config = load_config_file(config_path)?;
+            if key.starts_with("policy.") {
+                set_policy_key(&mut config.agent.address_policy, &key, &value)?;
+            } else {
+                config.set_key(&key, &value)?;
+            }
+
+            let path = expand_path(config_path);
+            save_config(&config, &path)?;
+            println!("Set {} in {}", key, path.display());
         }
         ConfigCommands::Get { key } => {
-            info!("Getting config key: {}", key);
-            // TODO: Implement config getting
-            println!("Config value (placeholder)");
+            match load_config(config_path)?.get_key(&key)? {
+                serde_yaml::Value::String(value) => println!("{}", value),
+                serde_yaml::Value::Null => println!("(unset)"),
+                value => print!("{}", serde_yaml::to_string(&value)?),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle dApp commands
+async fn handle_dapp_command(cmd: DappCommands, config_path: &std::path::Path) -> Result<()> {
+    use std::sync::Arc;
+
+    use agent_wallet_core::{RpcClient, RpcClientConfig};
+    use agent_wallet_dapp::{PoolCache, PoolProtocol, TokenPair};
+
+    let config = load_config(config_path)?;
+    let rpc = RpcClient::new(RpcClientConfig::from_settings(&config.rpc)).await?;
+    let cache = PoolCache::open_default(Arc::new(rpc));
+
+    match cmd {
+        DappCommands::Pools(PoolCommands::Refresh { protocol }) => {
+            let protocol = protocol.map(|p| p.parse::<PoolProtocol>()).transpose()?;
+            info!(
+                "Refreshing {} pools",
+                protocol.map_or("all".to_string(), |p| p.to_string())
+            );
+            let count = cache.refresh(protocol).await?;
+            println!("Cached {} pools in {}", count, cache.path().display());
+        }
+        DappCommands::Pools(PoolCommands::List { pair, protocol }) => {
+            let protocol = protocol.map(|p| p.parse::<PoolProtocol>()).transpose()?;
+            let pools = match pair {
+                Some(pair) => cache.find_pools(&pair.parse::<TokenPair>()?).await?,
+                None => cache.pools(protocol).await,
+            };
+
+            let mut shown = 0;
+            for pool in pools
+                .iter()
+                .filter(|pool| protocol.map_or(true, |p| p == pool.protocol))
+            {
+                println!(
+                    "{:<8} {}  {} / {}  fee {:.2} bps  liquidity {:?}  seen {}",
+                    pool.protocol,
+                    pool.address,
+                    pool.mint_a,
+                    pool.mint_b,
+                    pool.fee_bps(),
+                    pool.liquidity,
+                    pool.last_seen.format("%Y-%m-%d %H:%M")
+                );
+                shown += 1;
+            }
+            if shown == 0 {
+                println!("No cached pools (run `dapp pools refresh`)");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle address book commands
+fn handle_contacts_command(cmd: ContactCommands) -> Result<()> {
+    use agent_wallet_core::{AddressBook, Contact, IntegrityPolicy};
+    use solana_sdk::pubkey::Pubkey;
+
+    match cmd {
+        ContactCommands::List { book, signer } => {
+            let signer: Pubkey = signer.parse()?;
+            let book = AddressBook::load(expand_path(&book), &signer)?;
+            if let Some(reason) = book.integrity_error() {
+                warn!("Whitelist disabled: {}", reason);
+            }
+            for contact in book.contacts() {
+                let marker = if contact.whitelisted { "*" } else { " " };
+                println!("{} {:<20} {}", marker, contact.name, contact.address);
+            }
+            for change in book.pending() {
+                println!(
+                    "pending {} {} -> {} ({} approvals)",
+                    change.id,
+                    change.contact.name,
+                    change.contact.address,
+                    change.approvals.len()
+                );
+            }
+        }
+        ContactCommands::Init {
+            approvers,
+            quorum,
+            book,
+            signer,
+        } => {
+            let signer = read_keypair(&signer)?;
+            let approvers = approvers
+                .iter()
+                .map(|a| a.parse())
+                .collect::<std::result::Result<Vec<Pubkey>, _>>()?;
+            let mut book = AddressBook::load(expand_path(&book), &signer.public_key())?;
+            // A book without a policy yet is what this command fixes
+            if book.integrity_enabled() {
+                if let Some(reason) = book.integrity_error() {
+                    anyhow::bail!("Refusing to modify address book: {}", reason);
+                }
+            }
+
+            book.enable_integrity(IntegrityPolicy::new(approvers, quorum))?;
+            book.save(&signer)?;
+            println!("Integrity mode enabled (quorum {})", quorum);
+        }
+        ContactCommands::Propose {
+            name,
+            address,
+            note,
+            book,
+            keypair,
+            signer,
+        } => {
+            let signer = read_keypair(&signer)?;
+            let proposer = read_keypair(&keypair)?;
+            let mut book = AddressBook::load(expand_path(&book), &signer.public_key())?;
+            if let Some(reason) = book.integrity_error() {
+                anyhow::bail!("Refusing to modify address book: {}", reason);
+            }
+
+            let mut contact = Contact::whitelisted(name, address.parse()?);
+            if let Some(note) = note {
+                contact = contact.with_note(note);
+            }
+            let id = book.propose(contact, &proposer)?;
+            book.save(&signer)?;
+            println!("Proposed change {} (awaiting approval)", id);
+        }
+        ContactCommands::Approve {
+            id,
+            book,
+            keypair,
+            signer,
+        } => {
+            let signer = read_keypair(&signer)?;
+            let approver = read_keypair(&keypair)?;
+            let mut book = AddressBook::load(expand_path(&book), &signer.public_key())?;
+            if let Some(reason) = book.integrity_error() {
+                anyhow::bail!("Refusing to modify address book: {}", reason);
+            }
+
+            let activated = book.approve(&id.parse()?, &approver)?;
+            book.save(&signer)?;
+            if activated {
+                println!("Change {} approved and activated", id);
+            } else {
+                println!("Approval recorded for {}; quorum not reached yet", id);
+            }
         }
     }
     Ok(())
@@ -0,0 +1,884 @@
+//! HTTP API of the agent wallet service
+//!
+//! Requests authenticate with a bearer token. Each token carries a set of
+//! capabilities and may be scoped to specific agents, so a webhook sender
+//! can be allowed to trigger one agent and nothing else.
+//!
+//! Routes:
+//! - `POST /agents/{id}/trigger`: queue a decision tick carrying the JSON body
+//! - `GET /agents/{id}/decisions?cursor=N&limit=M`: audit records after `N`
+//! - `GET /agents/{id}/decisions/stream?cursor=N`: server-sent events
+//! - `GET /agents/{id}/decisions/ws?cursor=N`: WebSocket
+//! - `GET /agents/{id}/stats`: the agent's outcome statistics from the registry
+//! - `POST /approvals/{token}`: answer an approval request; the body is
+//!   `{"decision": "approve"}` or `{"decision": "reject"}`
+//! - `GET /metrics`: Prometheus metrics, when the service is given
+//!   [`Metrics`]; unauthenticated, like any scrape target
+//!
+//! The two streaming routes replay the audit records after the cursor and
+//! then follow the log live. SSE clients may also resume with the standard
+//! `Last-Event-ID` header; every event's id is its record's sequence number.
+//! Without a cursor only new records are sent. A client that falls more
+//! than the log's stream buffer behind is disconnected: WebSocket clients
+//! get a close frame with code [`SLOW_CONSUMER_CLOSE_CODE`], SSE clients a
+//! final `close` event carrying the same code. Records are redacted with
+//! the service's [`RedactionPolicy`] before they leave the process.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::IntoFuture;
+use std::sync::Arc;
+
+use agent_wallet_agent::approval::{ApprovalDecision, ApprovalStore};
+use agent_wallet_agent::audit::{AuditLog, AuditRecord, RedactionPolicy};
+use agent_wallet_agent::registry::AgentRegistry;
+use agent_wallet_agent::trigger::{TriggerHandle, TriggerPayload};
+use agent_wallet_agent::{AgentError, AgentId};
+use agent_wallet_core::metrics::Metrics;
+use agent_wallet_core::shutdown::{CancellationToken, DEFAULT_SHUTDOWN_TIMEOUT};
+use axum::body::Bytes;
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{info, warn};
+
+/// Upper bound on request bodies; agents enforce their own smaller limits
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Header naming the event source of a trigger
+const TRIGGER_SOURCE_HEADER: &str = "x-trigger-source";
+
+/// Header SSE clients use to resume after the last event they received
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// Records returned by `GET /agents/{id}/decisions` when no limit is given
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page `GET /agents/{id}/decisions` returns
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Close code sent to stream clients that fell too far behind
+pub const SLOW_CONSUMER_CLOSE_CODE: u16 = 4008;
+
+/// Close reason sent with [`SLOW_CONSUMER_CLOSE_CODE`]
+const SLOW_CONSUMER_REASON: &str = "slow consumer";
+
+/// Operations an API token may perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Fire external triggers
+    Trigger,
+    /// Read and stream audit records and read statistics
+    ReadAudit,
+    /// Answer approval requests of held-back actions
+    Approve,
+}
+
+/// Bearer token accepted by the service
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Secret presented in the `Authorization` header
+    pub token: String,
+    /// Granted capabilities
+    pub capabilities: Vec<Capability>,
+    /// Agents the token applies to; empty means all agents
+    #[serde(default)]
+    pub agents: Vec<AgentId>,
+}
+
+impl ApiToken {
+    /// Whether the token grants `capability` on `agent_id`
+    fn allows(&self, capability: Capability, agent_id: &str) -> bool {
+        self.capabilities.contains(&capability)
+            && (self.agents.is_empty() || self.agents.iter().any(|id| id == agent_id))
+    }
+}
+
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("token", &"[redacted]")
+            .field("capabilities", &self.capabilities)
+            .field("agents", &self.agents)
+            .finish()
+    }
+}
+
+/// Shared state of the HTTP service
+#[derive(Clone, Default)]
+pub struct ServiceState {
+    tokens: Arc<Vec<ApiToken>>,
+    triggers: Arc<RwLock<HashMap<AgentId, TriggerHandle>>>,
+    audit_logs: Arc<RwLock<HashMap<AgentId, AuditLog>>>,
+    approvals: Arc<RwLock<HashMap<AgentId, ApprovalStore>>>,
+    redaction: RedactionPolicy,
+    registry: Option<Arc<AgentRegistry>>,
+    metrics: Option<Metrics>,
+}
+
+impl ServiceState {
+    /// Create state accepting the given tokens
+    pub fn new(tokens: Vec<ApiToken>) -> Self {
+        Self {
+            tokens: Arc::new(tokens),
+            ..Self::default()
+        }
+    }
+
+    /// Redact audit records with `policy` instead of the default policy
+    pub fn with_redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction = policy;
+        self
+    }
+
+    /// Serve agent statistics from `registry`
+    pub fn with_registry(mut self, registry: AgentRegistry) -> Self {
+        self.registry = Some(Arc::new(registry));
+        self
+    }
+
+    /// Export `metrics` at `GET /metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Serve an agent's audit log
+    pub async fn register_audit_log(&self, log: AuditLog) {
+        self.audit_logs
+            .write()
+            .await
+            .insert(log.agent_id().clone(), log);
+    }
+
+    /// Answer an agent's approval requests from `store`
+    pub async fn register_approvals(&self, agent_id: AgentId, store: ApprovalStore) {
+        self.approvals.write().await.insert(agent_id, store);
+    }
+
+    /// Route triggers for an agent to its runner
+    pub async fn register_trigger(&self, handle: TriggerHandle) {
+        self.triggers
+            .write()
+            .await
+            .insert(handle.agent_id().clone(), handle);
+    }
+
+    /// Token presented in the request, if it is known
+    fn authenticate(&self, headers: &HeaderMap) -> Option<&ApiToken> {
+        let presented = headers
+            .get(header::AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")?;
+        self.tokens
+            .iter()
+            .find(|t| bool::from(t.token.as_bytes().ct_eq(presented.as_bytes())))
+    }
+
+    /// Check that the request may use `capability` on `agent_id`
+    fn authorize(
+        &self,
+        headers: &HeaderMap,
+        capability: Capability,
+        agent_id: &str,
+    ) -> Result<(), (StatusCode, Json<Value>)> {
+        let Some(token) = self.authenticate(headers) else {
+            return Err(error(
+                StatusCode::UNAUTHORIZED,
+                "Missing or unknown API token",
+            ));
+        };
+        if !token.allows(capability, agent_id) {
+            return Err(error(
+                StatusCode::FORBIDDEN,
+                format!("Token may not access agent {}", agent_id),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Audit log of an agent, if it is served
+    async fn audit_log(&self, agent_id: &str) -> Result<AuditLog, (StatusCode, Json<Value>)> {
+        self.audit_logs
+            .read()
+            .await
+            .get(agent_id)
+            .cloned()
+            .ok_or_else(|| {
+                error(
+                    StatusCode::NOT_FOUND,
+                    format!("Agent {} has no audit log", agent_id),
+                )
+            })
+    }
+}
+
+/// Build the service router
+pub fn router(state: ServiceState) -> Router {
+    let metrics = state.metrics.clone();
+    let router = Router::new()
+        .route("/agents/{id}/trigger", post(trigger_agent))
+        .route("/agents/{id}/decisions", get(list_decisions))
+        .route("/agents/{id}/decisions/stream", get(stream_decisions_sse))
+        .route("/agents/{id}/decisions/ws", get(stream_decisions_ws))
+        .route("/agents/{id}/stats", get(agent_stats))
+        .route("/approvals/{token}", post(answer_approval))
+        .layer(DefaultBodyLimit::max(MAX_REQUEST_BYTES))
+        .with_state(state);
+    match metrics {
+        Some(metrics) => router.merge(metrics.router()),
+        None => router,
+    }
+}
+
+/// Serve the API until `shutdown` is cancelled
+///
+/// Requests in progress are finished first. Streaming clients keep their
+/// connection open, so they are given [`DEFAULT_SHUTDOWN_TIMEOUT`] before
+/// the server stops regardless.
+pub async fn serve(
+    addr: &str,
+    state: ServiceState,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(
+        "Agent wallet service listening on {}",
+        listener.local_addr()?
+    );
+    let server = axum::serve(listener, router(state))
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .into_future();
+    let deadline = async {
+        shutdown.cancelled().await;
+        tokio::time::sleep(DEFAULT_SHUTDOWN_TIMEOUT).await;
+    };
+    tokio::select! {
+        served = server => served?,
+        () = deadline => warn!(
+            "Connections still open {:?} after shutdown began, closing them",
+            DEFAULT_SHUTDOWN_TIMEOUT
+        ),
+    }
+    Ok(())
+}
+
+/// `POST /agents/{id}/trigger`
+async fn trigger_agent(
+    State(state): State<ServiceState>,
+    Path(agent_id): Path<AgentId>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    if let Err(rejection) = state.authorize(&headers, Capability::Trigger, &agent_id) {
+        return rejection;
+    }
+
+    let Some(handle) = state.triggers.read().await.get(&agent_id).cloned() else {
+        return error(
+            StatusCode::NOT_FOUND,
+            format!("Agent {} does not accept triggers", agent_id),
+        );
+    };
+    if body.len() > handle.config().max_payload_bytes {
+        return error(StatusCode::PAYLOAD_TOO_LARGE, "Trigger payload too large");
+    }
+    let data: Value = match serde_json::from_slice(&body) {
+        Ok(data) => data,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)),
+    };
+
+    let source = headers
+        .get(TRIGGER_SOURCE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("http");
+    match handle.fire(TriggerPayload::new(source, data)) {
+        Ok(()) => (StatusCode::ACCEPTED, Json(json!({ "queued": true }))),
+        Err(e) => {
+            warn!("Rejected trigger for agent {}: {}", agent_id, e);
+            let status = match e {
+                AgentError::LimitExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+                AgentError::InvalidTrigger(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            error(status, e.to_string())
+        }
+    }
+}
+
+/// Body of `POST /approvals/{token}`
+#[derive(Debug, Deserialize)]
+struct ApprovalBody {
+    /// Whether the action is approved or rejected
+    decision: ApprovalDecision,
+}
+
+/// `POST /approvals/{token}`
+///
+/// Answering again with the same decision succeeds without changing
+/// anything; contradicting an earlier answer is a conflict. Only the
+/// requests of agents the caller may approve for are looked up, so tokens
+/// of other agents are reported unknown.
+async fn answer_approval(
+    State(state): State<ServiceState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    let Some(api_token) = state.authenticate(&headers) else {
+        return error(StatusCode::UNAUTHORIZED, "Missing or unknown API token");
+    };
+    if !api_token.capabilities.contains(&Capability::Approve) {
+        return error(StatusCode::FORBIDDEN, "Token may not answer approvals");
+    }
+    let decision = match serde_json::from_slice::<ApprovalBody>(&body) {
+        Ok(body) => body.decision,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid body: {}", e)),
+    };
+
+    let now = chrono::Utc::now();
+    let stores: Vec<(AgentId, ApprovalStore)> = state
+        .approvals
+        .read()
+        .await
+        .iter()
+        .filter(|(agent_id, _)| api_token.allows(Capability::Approve, agent_id))
+        .map(|(agent_id, store)| (agent_id.clone(), store.clone()))
+        .collect();
+    let mut found = None;
+    for (agent_id, store) in stores {
+        match store.get(&token, now) {
+            Ok(Some(_)) => {
+                found = Some(store);
+                break;
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read approvals of agent {}: {}", agent_id, e),
+        }
+    }
+    let Some(store) = found else {
+        return error(StatusCode::NOT_FOUND, "Unknown approval token");
+    };
+
+    match store.resolve(&token, decision, now) {
+        Ok(request) => (
+            StatusCode::OK,
+            Json(json!({
+                "agent_id": request.agent_id,
+                "action": request.description,
+                "status": request.status,
+            })),
+        ),
+        Err(AgentError::InvalidState(message)) => error(StatusCode::CONFLICT, message),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Query parameters of the audit routes
+#[derive(Debug, Default, Deserialize)]
+struct CursorQuery {
+    /// Sequence number of the last record the client has seen
+    cursor: Option<u64>,
+    /// Maximum number of records to return
+    limit: Option<usize>,
+}
+
+/// `GET /agents/{id}/decisions`
+async fn list_decisions(
+    State(state): State<ServiceState>,
+    Path(agent_id): Path<AgentId>,
+    headers: HeaderMap,
+    Query(query): Query<CursorQuery>,
+) -> (StatusCode, Json<Value>) {
+    if let Err(rejection) = state.authorize(&headers, Capability::ReadAudit, &agent_id) {
+        return rejection;
+    }
+    let log = match state.audit_log(&agent_id).await {
+        Ok(log) => log,
+        Err(rejection) => return rejection,
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    match log.since(query.cursor) {
+        Ok(records) => {
+            let records: Vec<AuditRecord> = records
+                .iter()
+                .take(limit)
+                .map(|record| state.redaction.apply(record))
+                .collect();
+            (
+                StatusCode::OK,
+                Json(json!({ "records": records, "last_sequence": log.last_sequence() })),
+            )
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// `GET /agents/{id}/stats`
+async fn agent_stats(
+    State(state): State<ServiceState>,
+    Path(agent_id): Path<AgentId>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<Value>) {
+    if let Err(rejection) = state.authorize(&headers, Capability::ReadAudit, &agent_id) {
+        return rejection;
+    }
+    let Some(registry) = &state.registry else {
+        return error(StatusCode::NOT_FOUND, "No agent registry is served");
+    };
+    match registry.get(&agent_id) {
+        Ok(Some(entry)) => (
+            StatusCode::OK,
+            Json(
+                json!({ "stats": entry.stats, "summary": entry.stats.summary(chrono::Utc::now()) }),
+            ),
+        ),
+        Ok(None) => error(
+            StatusCode::NOT_FOUND,
+            format!("Agent {} is not registered", agent_id),
+        ),
+        Err(AgentError::Config(message)) => error(StatusCode::BAD_REQUEST, message),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// `GET /agents/{id}/decisions/stream`
+async fn stream_decisions_sse(
+    State(state): State<ServiceState>,
+    Path(agent_id): Path<AgentId>,
+    headers: HeaderMap,
+    Query(query): Query<CursorQuery>,
+) -> Response {
+    let cursor = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .or(query.cursor);
+    let records = match open_stream(&state, &headers, &agent_id, cursor).await {
+        Ok(records) => records,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let events = records.map(|item| match item {
+        StreamItem::Record(record) => Event::default()
+            .id(record.sequence.to_string())
+            .event("decision")
+            .json_data(&record),
+        StreamItem::SlowConsumer => Ok(Event::default().event("close").data(
+            json!({ "code": SLOW_CONSUMER_CLOSE_CODE, "reason": SLOW_CONSUMER_REASON }).to_string(),
+        )),
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// `GET /agents/{id}/decisions/ws`
+async fn stream_decisions_ws(
+    State(state): State<ServiceState>,
+    Path(agent_id): Path<AgentId>,
+    headers: HeaderMap,
+    Query(query): Query<CursorQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let records = match open_stream(&state, &headers, &agent_id, query.cursor).await {
+        Ok(records) => records,
+        Err(rejection) => return rejection.into_response(),
+    };
+    upgrade.on_upgrade(move |socket| forward_records(socket, records))
+}
+
+/// Send streamed records over a WebSocket until either side stops
+async fn forward_records(mut socket: WebSocket, records: impl Stream<Item = StreamItem>) {
+    let mut records = std::pin::pin!(records);
+    while let Some(item) = records.next().await {
+        let message = match item {
+            StreamItem::Record(record) => match serde_json::to_string(&record) {
+                Ok(text) => Message::Text(text.into()),
+                Err(e) => {
+                    warn!("Failed to encode audit record: {}", e);
+                    continue;
+                }
+            },
+            StreamItem::SlowConsumer => {
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: SLOW_CONSUMER_CLOSE_CODE,
+                        reason: SLOW_CONSUMER_REASON.into(),
+                    })))
+                    .await;
+                return;
+            }
+        };
+        if socket.send(message).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Item of an audit stream
+enum StreamItem {
+    /// Redacted record to deliver
+    Record(AuditRecord),
+    /// The client fell behind and is being disconnected
+    SlowConsumer,
+}
+
+/// State threaded through an audit stream
+struct AuditStream {
+    backlog: VecDeque<AuditRecord>,
+    receiver: broadcast::Receiver<AuditRecord>,
+    last_sent: u64,
+    redaction: RedactionPolicy,
+    finished: bool,
+}
+
+/// Authorize a stream request and start replaying from `cursor`
+async fn open_stream(
+    state: &ServiceState,
+    headers: &HeaderMap,
+    agent_id: &str,
+    cursor: Option<u64>,
+) -> Result<impl Stream<Item = StreamItem>, (StatusCode, Json<Value>)> {
+    state.authorize(headers, Capability::ReadAudit, agent_id)?;
+    let log = state.audit_log(agent_id).await?;
+
+    // Subscribe before reading the backlog so no record falls in between
+    let start = cursor.unwrap_or_else(|| log.last_sequence());
+    let receiver = log.subscribe();
+    let backlog = match cursor {
+        Some(cursor) => log
+            .since(Some(cursor))
+            .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => Vec::new(),
+    };
+
+    let stream = AuditStream {
+        backlog: backlog.into(),
+        receiver,
+        last_sent: start,
+        redaction: state.redaction,
+        finished: false,
+    };
+    Ok(stream::unfold(stream, |mut stream| async move {
+        if stream.finished {
+            return None;
+        }
+        if let Some(record) = stream.backlog.pop_front() {
+            stream.last_sent = record.sequence;
+            let item = StreamItem::Record(stream.redaction.apply(&record));
+            return Some((item, stream));
+        }
+        loop {
+            match stream.receiver.recv().await {
+                Ok(record) if record.sequence <= stream.last_sent => continue,
+                Ok(record) => {
+                    stream.last_sent = record.sequence;
+                    let item = StreamItem::Record(stream.redaction.apply(&record));
+                    return Some((item, stream));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Disconnecting audit stream that fell {} records behind",
+                        skipped
+                    );
+                    stream.finished = true;
+                    return Some((StreamItem::SlowConsumer, stream));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }))
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": message.into() })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_wallet_agent::audit::AuditConfig;
+    use agent_wallet_agent::decision::{DecisionOutcome, DecisionRecord};
+    use anyhow::{anyhow, Result};
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "reader-secret";
+
+    fn state() -> ServiceState {
+        ServiceState::new(vec![ApiToken {
+            token: TOKEN.to_string(),
+            capabilities: vec![Capability::ReadAudit],
+            agents: vec!["alpha".to_string()],
+        }])
+    }
+
+    fn decision() -> DecisionRecord {
+        DecisionRecord {
+            agent_id: "alpha".to_string(),
+            timestamp: chrono::Utc::now(),
+            action: None,
+            outcome: DecisionOutcome::NoAction,
+            trigger: None,
+        }
+    }
+
+    async fn open_sse(state: ServiceState, uri: &str) -> Result<Body> {
+        let request = Request::get(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+            .body(Body::empty())?;
+        let response = router(state).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        Ok(response.into_body())
+    }
+
+    /// Next SSE event of the body as `(event, id, data)`, skipping keep-alives
+    async fn next_event(body: &mut Body) -> Result<Option<(String, String, String)>> {
+        while let Some(frame) = body.frame().await {
+            let Ok(data) = frame?.into_data() else {
+                continue;
+            };
+            let text = String::from_utf8(data.to_vec())?;
+            let (mut event, mut id, mut payload) = (String::new(), String::new(), String::new());
+            for line in text.lines() {
+                if let Some(value) = line.strip_prefix("event: ") {
+                    event = value.to_string();
+                } else if let Some(value) = line.strip_prefix("id: ") {
+                    id = value.to_string();
+                } else if let Some(value) = line.strip_prefix("data: ") {
+                    payload = value.to_string();
+                }
+            }
+            if !event.is_empty() {
+                return Ok(Some((event, id, payload)));
+            }
+        }
+        Ok(None)
+    }
+
+    #[tokio::test]
+    async fn test_stream_replays_backlog_then_follows_live() -> Result<()> {
+        let state = state();
+        let log = AuditLog::in_memory("alpha", AuditConfig::default());
+        state.register_audit_log(log.clone()).await;
+        for _ in 0..3 {
+            log.append(decision())?;
+        }
+
+        let mut body = open_sse(state, "/agents/alpha/decisions/stream?cursor=1").await?;
+        log.append(decision())?;
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let (event, id, data) = next_event(&mut body)
+                .await?
+                .ok_or_else(|| anyhow!("stream ended early"))?;
+            assert_eq!(event, "decision");
+            let record: AuditRecord = serde_json::from_str(&data)?;
+            assert_eq!(record.sequence.to_string(), id);
+            ids.push(id);
+        }
+        assert_eq!(ids, vec!["2", "3", "4"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_is_disconnected() -> Result<()> {
+        let state = state();
+        let config = AuditConfig {
+            stream_buffer: 4,
+            ..AuditConfig::default()
+        };
+        let log = AuditLog::in_memory("alpha", config);
+        state.register_audit_log(log.clone()).await;
+
+        // Nothing reads the stream while more records than its buffer arrive
+        let mut body = open_sse(state, "/agents/alpha/decisions/stream").await?;
+        for _ in 0..10 {
+            log.append(decision())?;
+        }
+
+        let (event, _, data) = next_event(&mut body)
+            .await?
+            .ok_or_else(|| anyhow!("stream ended without a close event"))?;
+        assert_eq!(event, "close");
+        let close: Value = serde_json::from_str(&data)?;
+        assert_eq!(close["code"], json!(SLOW_CONSUMER_CLOSE_CODE));
+        assert!(next_event(&mut body).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stats_served_from_registry() -> Result<()> {
+        use agent_wallet_agent::registry::RegistryEntry;
+        use agent_wallet_core::{types::AgentAction, AgentStats, Lamports};
+
+        let dir = tempfile::tempdir()?;
+        let registry = AgentRegistry::open(dir.path())?;
+        registry.register(RegistryEntry::new("alpha", "deterministic", "main"))?;
+        let mut stats = AgentStats::new();
+        let action = AgentAction::TransferSol {
+            to: solana_sdk::pubkey::Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        stats.record_failed(&action, "Insufficient funds", chrono::Utc::now());
+        registry.record_stats("alpha", &stats)?;
+        let state = state().with_registry(registry);
+
+        let get = |uri: &str| {
+            Request::get(uri)
+                .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+                .body(Body::empty())
+        };
+        let response = router(state.clone())
+            .oneshot(get("/agents/alpha/stats")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value =
+            serde_json::from_slice(&response.into_body().collect().await?.to_bytes())?;
+        assert_eq!(body["summary"]["failed"], json!(1));
+        assert_eq!(
+            body["stats"]["by_action"]["transfer_sol"]["failed"],
+            json!(1)
+        );
+
+        // The token is scoped to alpha
+        let response = router(state).oneshot(get("/agents/beta/stats")?).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_mounted_on_the_service() -> Result<()> {
+        let metrics = Metrics::global().clone();
+        metrics.record_transaction("transfer_sol", true);
+        metrics.record_decision("alpha", "no_action", std::time::Duration::ZERO);
+
+        let request = Request::get("/metrics").body(Body::empty())?;
+        let response = router(state()).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::get("/metrics").body(Body::empty())?;
+        let response = router(state().with_metrics(metrics))
+            .oneshot(request)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = String::from_utf8(response.into_body().collect().await?.to_bytes().to_vec())?;
+        assert!(body.contains("agent_wallet_transactions_total"));
+        assert!(body.contains("agent_wallet_agent_decisions_total"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_approvals_are_answered_once() -> Result<()> {
+        use agent_wallet_agent::approval::{ApprovalGate, ApprovalStatus};
+        use agent_wallet_core::config::ApprovalPolicy;
+        use agent_wallet_core::{types::AgentAction, Lamports};
+
+        let state = ServiceState::new(vec![ApiToken {
+            token: TOKEN.to_string(),
+            capabilities: vec![Capability::Approve],
+            agents: vec!["alpha".to_string()],
+        }]);
+        let store = ApprovalStore::new();
+        state
+            .register_approvals("alpha".to_string(), store.clone())
+            .await;
+        let gate = ApprovalGate::new(ApprovalPolicy::default(), store.clone())?;
+        let request = gate.request(
+            &"alpha".to_string(),
+            &solana_sdk::pubkey::Pubkey::new_unique(),
+            uuid::Uuid::new_v4(),
+            &AgentAction::TransferSol {
+                to: solana_sdk::pubkey::Pubkey::new_unique(),
+                amount: Lamports::new(5_000_000_000),
+                memo: None,
+            },
+            Some(5.0),
+            None,
+        )?;
+
+        let answer = |token: &str, body: &'static str| {
+            Request::post(format!("/approvals/{}", token))
+                .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+                .body(Body::from(body))
+        };
+        let approve = r#"{"decision": "approve"}"#;
+        let response = router(state.clone())
+            .oneshot(answer(&request.token, "")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        for _ in 0..2 {
+            let response = router(state.clone())
+                .oneshot(answer(&request.token, approve)?)
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let answered = store
+            .get(&request.token, chrono::Utc::now())?
+            .ok_or_else(|| anyhow!("request vanished"))?;
+        assert_eq!(answered.status, ApprovalStatus::Approved);
+
+        let response = router(state.clone())
+            .oneshot(answer(&request.token, r#"{"decision": "reject"}"#)?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = router(state).oneshot(answer("unknown", approve)?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // A token scoped to another agent cannot tell the request exists
+        let other = ServiceState::new(vec![ApiToken {
+            token: TOKEN.to_string(),
+            capabilities: vec![Capability::Approve],
+            agents: vec!["beta".to_string()],
+        }]);
+        other.register_approvals("alpha".to_string(), store).await;
+        let response = router(other)
+            .oneshot(answer(&request.token, approve)?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_requires_read_audit_capability() -> Result<()> {
+        let state = ServiceState::new(vec![ApiToken {
+            token: TOKEN.to_string(),
+            capabilities: vec![Capability::Trigger],
+            agents: Vec::new(),
+        }]);
+        state
+            .register_audit_log(AuditLog::in_memory("alpha", AuditConfig::default()))
+            .await;
+        let request = Request::get("/agents/alpha/decisions/stream")
+            .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+            .body(Body::empty())?;
+        let response = router(state).oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_redacts_token() {
+        let debug = format!("{:?}", state().tokens[0]);
+        assert!(!debug.contains(TOKEN));
+        assert!(debug.contains("[redacted]"));
+    }
+}
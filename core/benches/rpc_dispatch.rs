@@ -0,0 +1,87 @@
+//! Cost of calling an [`RpcProvider`] statically versus through [`DynRpcProvider`]
+//!
+//! The static path polls the provider's future in place; the dynamic path
+//! boxes it once per call.
+
+use std::sync::Arc;
+
+use agent_wallet_core::rpc::{DynRpcProvider, RpcProvider};
+use agent_wallet_core::{Error, Result};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use futures::executor::block_on;
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{
+    account::Account, hash::Hash, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+use solana_transaction_status::TransactionStatus;
+
+/// Provider answering balances from memory
+struct FixedBalance(u64);
+
+impl RpcProvider for FixedBalance {
+    async fn get_balance(&self, _pubkey: &Pubkey) -> Result<u64> {
+        Ok(self.0)
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        Err(Error::AccountNotFound(pubkey.to_string()))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        Ok(vec![None; pubkeys.len()])
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(Hash::default())
+    }
+
+    async fn send_transaction(&self, _transaction: &Transaction) -> Result<Signature> {
+        Err(Error::rpc("not supported"))
+    }
+
+    async fn simulate_transaction(
+        &self,
+        _transaction: &Transaction,
+    ) -> Result<RpcSimulateTransactionResult> {
+        Err(Error::rpc("not supported"))
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<TransactionStatus>>> {
+        Ok(vec![None; signatures.len()])
+    }
+
+    async fn get_program_accounts(
+        &self,
+        _program_id: &Pubkey,
+        _config: Option<RpcProgramAccountsConfig>,
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        Ok(Vec::new())
+    }
+}
+
+async fn static_balance<P: RpcProvider>(provider: &P, pubkey: &Pubkey) -> Result<u64> {
+    // Both traits are in scope here, so name the static one
+    RpcProvider::get_balance(provider, pubkey).await
+}
+
+fn get_balance(c: &mut Criterion) {
+    let pubkey = Pubkey::new_unique();
+    let provider = FixedBalance(1_000_000_000);
+    let shared: Arc<dyn DynRpcProvider> = Arc::new(FixedBalance(1_000_000_000));
+
+    let mut group = c.benchmark_group("get_balance");
+    group.bench_function("static", |b| {
+        b.iter(|| block_on(static_balance(black_box(&provider), &pubkey)))
+    });
+    group.bench_function("dyn", |b| {
+        b.iter(|| block_on(black_box(&shared).get_balance(&pubkey)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, get_balance);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+//! Cost of building a transfer from scratch versus patching a prepared template
+//!
+//! The full build assembles and validates the transaction for every action;
+//! the templated build only patches the amount and blockhash of a message
+//! prepared once and re-checks the spending limits.
+
+use agent_wallet_core::template::{ActionTemplate, PreparedTemplate};
+use agent_wallet_core::transaction::{TransactionBuilder, TransactionOptions};
+use agent_wallet_core::types::{AgentContext, PermissionLevel, UnlistedTokenPolicy};
+use agent_wallet_core::Result;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use solana_sdk::{hash::Hash, pubkey::Pubkey};
+
+fn context() -> AgentContext {
+    let mut context = AgentContext::new(Pubkey::new_unique());
+    context.permission_level = PermissionLevel::Advanced;
+    // The template moves a fresh mint without limits of its own
+    context.spending_limits.unlisted_tokens = UnlistedTokenPolicy::AllowUnlimited;
+    context
+}
+
+fn token_transfer(c: &mut Criterion) -> Result<()> {
+    let mut builder = TransactionBuilder::new();
+    let context = context();
+    let options = TransactionOptions::default();
+    let template = ActionTemplate::TransferToken {
+        mint: Pubkey::new_unique(),
+        to: Pubkey::new_unique(),
+        memo: Some("benchmark".to_string()),
+    };
+    let prepared = PreparedTemplate::prepare(&mut builder, template.clone(), &context, &options)?;
+    let action = template.instantiate(42_000);
+
+    let mut group = c.benchmark_group("token_transfer");
+    group.bench_function("full", |b| {
+        b.iter(|| {
+            let transaction = builder.build_from_action(black_box(&action), &context, &options)?;
+            let validation = builder.validate_transaction(&transaction, &context, &options);
+            Ok::<_, agent_wallet_core::Error>(validation.is_valid)
+        })
+    });
+    group.bench_function("templated", |b| {
+        b.iter(|| prepared.instantiate(&builder, black_box(&action), &context, Hash::default()))
+    });
+    group.finish();
+    Ok(())
+}
+
+fn build(c: &mut Criterion) {
+    if let Err(e) = token_transfer(c) {
+        panic!("failed to prepare the template: {}", e);
+    }
+}
+
+criterion_group!(benches, build);
+criterion_main!(benches);
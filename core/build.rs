@@ -0,0 +1,47 @@
+//! Generates the C header of the `ffi` feature
+//!
+//! With the feature enabled, cbindgen writes `agent_wallet.h` to `OUT_DIR`,
+//! and also to the directory named by `AGENT_WALLET_HEADER_DIR` when it is
+//! set, so packaging scripts can pick the header up from a stable path.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    ffi::generate_header();
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use std::env;
+    use std::path::PathBuf;
+
+    /// File name of the generated header
+    const HEADER: &str = "agent_wallet.h";
+
+    pub fn generate_header() {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-env-changed=AGENT_WALLET_HEADER_DIR");
+
+        let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+        let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+
+        let config = cbindgen::Config {
+            language: cbindgen::Language::C,
+            include_guard: Some("AGENT_WALLET_H".to_string()),
+            header: Some(
+                "/* Generated by cbindgen from agent-wallet-core. Do not edit. */".to_string(),
+            ),
+            usize_is_size_t: true,
+            ..Default::default()
+        };
+        let bindings = cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(PathBuf::from(&crate_dir).join("src/ffi.rs"))
+            .generate()
+            .expect("src/ffi.rs can be parsed by cbindgen");
+
+        bindings.write_to_file(out_dir.join(HEADER));
+        if let Some(dir) = env::var_os("AGENT_WALLET_HEADER_DIR") {
+            bindings.write_to_file(PathBuf::from(dir).join(HEADER));
+        }
+    }
+}
@@ -0,0 +1,698 @@
+//! Address book with two-person integrity for whitelisted recipients
+//!
+//! When the recipient whitelist is sourced from the address book, anyone able
+//! to edit the book could otherwise bypass spending controls. With integrity
+//! mode enabled, whitelist-eligible entries can only be added or modified
+//! through a proposal that a quorum of approvers (excluding the proposer)
+//! signs off on, and the whole file is signed with the wallet or operator key.
+//! A book that fails verification still loads for display, but every
+//! whitelist query fails closed with [`Error::WhitelistIntegrity`].
+//!
+//! Whether integrity is required comes from the caller, never from the file:
+//! a book loaded against a trusted signer must carry an integrity policy, so
+//! stripping the policy from the file does not turn verification off.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::address_book::{AddressBook, Contact, IntegrityPolicy};
+//! use agent_wallet_core::SecureKeypair;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let operator = SecureKeypair::generate();
+//! let (alice, bob) = (SecureKeypair::generate(), SecureKeypair::generate());
+//!
+//! let mut book = AddressBook::new("contacts.json");
+//! book.enable_integrity(IntegrityPolicy::new(vec![alice.public_key(), bob.public_key()], 1))?;
+//!
+//! let id = book.propose(Contact::whitelisted("exchange", Pubkey::new_unique()), &alice)?;
+//! book.approve(&id, &bob)?;
+//! book.save(&operator)?;
+//!
+//! let book = AddressBook::load("contacts.json", &operator.public_key())?;
+//! assert_eq!(book.whitelist()?.len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use uuid::Uuid;
+
+use crate::config::AddressBookSettings;
+use crate::error::{Error, Result};
+use crate::keypair::SecureKeypair;
+
+/// Current address book file format version
+pub const ADDRESS_BOOK_VERSION: u32 = 1;
+
+/// A named address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    /// Unique, human-readable name
+    pub name: String,
+    /// Address of the contact
+    pub address: Pubkey,
+    /// Whether the contact may receive funds under whitelist enforcement
+    pub whitelisted: bool,
+    /// Optional free-form note
+    pub note: Option<String>,
+}
+
+impl Contact {
+    /// Create a contact that is not whitelist-eligible
+    pub fn new(name: impl Into<String>, address: Pubkey) -> Self {
+        Self {
+            name: name.into(),
+            address,
+            whitelisted: false,
+            note: None,
+        }
+    }
+
+    /// Create a whitelist-eligible contact
+    pub fn whitelisted(name: impl Into<String>, address: Pubkey) -> Self {
+        Self {
+            whitelisted: true,
+            ..Self::new(name, address)
+        }
+    }
+
+    /// Attach a note
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+}
+
+/// Approvers and quorum required for whitelist-eligible changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityPolicy {
+    /// Keys allowed to approve changes
+    pub approvers: Vec<Pubkey>,
+    /// Number of distinct approvals (not counting the proposer) to activate
+    pub quorum: usize,
+}
+
+impl IntegrityPolicy {
+    /// Create a policy
+    pub fn new(approvers: Vec<Pubkey>, quorum: usize) -> Self {
+        Self { approvers, quorum }
+    }
+}
+
+/// A signed approval of a proposed change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Approval {
+    /// Approving key
+    pub approver: Pubkey,
+    /// Signature over the change payload
+    pub signature: Signature,
+    /// When the approval was given
+    pub approved_at: DateTime<Utc>,
+}
+
+/// A proposed addition or modification of a whitelist-eligible contact
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContactChange {
+    /// Change identifier
+    pub id: Uuid,
+    /// Contact as it will read once activated
+    pub contact: Contact,
+    /// Proposing key
+    pub proposed_by: Pubkey,
+    /// Proposer's signature over the change payload
+    pub proposer_signature: Signature,
+    /// When the change was proposed
+    pub proposed_at: DateTime<Utc>,
+    /// Approvals collected so far
+    pub approvals: Vec<Approval>,
+}
+
+impl ContactChange {
+    /// Bytes signed by the proposer and approvers
+    fn payload(id: &Uuid, contact: &Contact) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&(id, contact))?)
+    }
+
+    /// Approvals with a valid signature from a distinct policy approver other
+    /// than the proposer
+    fn valid_approvals(&self, policy: &IntegrityPolicy) -> Result<usize> {
+        let payload = Self::payload(&self.id, &self.contact)?;
+        if !self
+            .proposer_signature
+            .verify(self.proposed_by.as_ref(), &payload)
+        {
+            return Ok(0);
+        }
+
+        let approvers: HashSet<Pubkey> = self
+            .approvals
+            .iter()
+            .filter(|approval| {
+                approval.approver != self.proposed_by
+                    && policy.approvers.contains(&approval.approver)
+                    && approval
+                        .signature
+                        .verify(approval.approver.as_ref(), &payload)
+            })
+            .map(|approval| approval.approver)
+            .collect();
+        Ok(approvers.len())
+    }
+}
+
+/// An active contact with the change record that activated it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContactEntry {
+    /// The contact
+    pub contact: Contact,
+    /// Approved change that activated a whitelisted contact (integrity mode)
+    pub activation: Option<ContactChange>,
+}
+
+/// Signed portion of the address book file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AddressBookBody {
+    version: u32,
+    integrity: Option<IntegrityPolicy>,
+    contacts: Vec<ContactEntry>,
+    pending: Vec<ContactChange>,
+}
+
+/// File signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BookSignature {
+    signer: Pubkey,
+    signature: Signature,
+}
+
+/// On-disk address book format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AddressBookFile {
+    #[serde(flatten)]
+    body: AddressBookBody,
+    signature: Option<BookSignature>,
+}
+
+/// Address book persisted as JSON
+#[derive(Debug)]
+pub struct AddressBook {
+    /// File location
+    path: PathBuf,
+    /// Book contents
+    body: AddressBookBody,
+    /// Why the book failed verification at load, if it did
+    integrity_error: Option<String>,
+}
+
+impl AddressBook {
+    /// Create an empty address book stored at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            body: AddressBookBody {
+                version: ADDRESS_BOOK_VERSION,
+                ..Default::default()
+            },
+            integrity_error: None,
+        }
+    }
+
+    /// Load an address book, verifying it against the trusted signer
+    ///
+    /// The book must be in integrity mode and signed by `trusted_signer`.
+    /// Verification failures do not abort loading; they make whitelist
+    /// queries fail closed. A missing file yields an empty book.
+    pub fn load(path: impl Into<PathBuf>, trusted_signer: &Pubkey) -> Result<Self> {
+        Self::read(path.into(), Some(trusted_signer))
+    }
+
+    /// Load an address book without a trusted signer
+    ///
+    /// A book in integrity mode cannot be verified this way, so its
+    /// whitelist fails closed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        Self::read(path.into(), None)
+    }
+
+    /// Load the address book described by the wallet configuration
+    pub fn from_settings(settings: &AddressBookSettings) -> Result<Self> {
+        match &settings.trusted_signer {
+            Some(signer) => {
+                let signer: Pubkey = signer.parse().map_err(|_| {
+                    Error::config(format!("Invalid address book signer '{}'", signer))
+                })?;
+                Self::load(&settings.path, &signer)
+            }
+            None => Self::open(&settings.path),
+        }
+    }
+
+    fn read(path: PathBuf, trusted_signer: Option<&Pubkey>) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(path));
+        }
+
+        let file: AddressBookFile = serde_json::from_slice(&fs::read(&path)?)?;
+        if file.body.version != ADDRESS_BOOK_VERSION {
+            return Err(Error::storage(format!(
+                "Unsupported address book version {}",
+                file.body.version
+            )));
+        }
+
+        let mut book = Self {
+            path,
+            body: file.body,
+            integrity_error: None,
+        };
+        book.integrity_error = book.verify(file.signature.as_ref(), trusted_signer)?;
+        Ok(book)
+    }
+
+    /// Check the file signature and every whitelisted entry's approvals
+    ///
+    /// With a trusted signer the book must be in integrity mode.
+    fn verify(
+        &self,
+        signature: Option<&BookSignature>,
+        trusted_signer: Option<&Pubkey>,
+    ) -> Result<Option<String>> {
+        let (policy, trusted_signer) = match (&self.body.integrity, trusted_signer) {
+            (Some(policy), Some(trusted_signer)) => (policy, trusted_signer),
+            (None, None) => return Ok(None),
+            (None, Some(_)) => {
+                return Ok(Some(
+                    "address book has no integrity policy but a trusted signer is configured"
+                        .to_string(),
+                ))
+            }
+            (Some(_), None) => {
+                return Ok(Some(
+                    "no trusted signer is configured to verify the address book".to_string(),
+                ))
+            }
+        };
+
+        let Some(signature) = signature else {
+            return Ok(Some("address book is not signed".to_string()));
+        };
+        if signature.signer != *trusted_signer {
+            return Ok(Some(format!(
+                "address book signed by untrusted key {}",
+                signature.signer
+            )));
+        }
+        let payload = serde_json::to_vec(&self.body)?;
+        if !signature
+            .signature
+            .verify(signature.signer.as_ref(), &payload)
+        {
+            return Ok(Some(
+                "address book signature does not match its contents".to_string(),
+            ));
+        }
+
+        for entry in self.body.contacts.iter().filter(|e| e.contact.whitelisted) {
+            let approved = match &entry.activation {
+                Some(change) if change.contact == entry.contact => {
+                    change.valid_approvals(policy)? >= policy.quorum
+                }
+                _ => false,
+            };
+            if !approved {
+                return Ok(Some(format!(
+                    "whitelisted contact '{}' was not activated by an approved change",
+                    entry.contact.name
+                )));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// File location
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether integrity mode is enabled
+    pub fn integrity_enabled(&self) -> bool {
+        self.body.integrity.is_some()
+    }
+
+    /// Reason the book failed verification at load, if any
+    pub fn integrity_error(&self) -> Option<&str> {
+        self.integrity_error.as_deref()
+    }
+
+    /// Enable integrity mode
+    ///
+    /// Existing whitelisted contacts lose their eligibility and must be
+    /// proposed again under the new policy.
+    pub fn enable_integrity(&mut self, policy: IntegrityPolicy) -> Result<()> {
+        if policy.quorum == 0 || policy.quorum > policy.approvers.len() {
+            return Err(Error::validation(format!(
+                "Quorum must be between 1 and the number of approvers ({})",
+                policy.approvers.len()
+            )));
+        }
+
+        for entry in &mut self.body.contacts {
+            if entry.activation.is_none() {
+                entry.contact.whitelisted = false;
+            }
+        }
+        self.body.integrity = Some(policy);
+        Ok(())
+    }
+
+    /// All active contacts
+    pub fn contacts(&self) -> impl Iterator<Item = &Contact> {
+        self.body.contacts.iter().map(|entry| &entry.contact)
+    }
+
+    /// Changes awaiting approval
+    pub fn pending(&self) -> &[ContactChange] {
+        &self.body.pending
+    }
+
+    /// Look up a contact by name
+    pub fn get(&self, name: &str) -> Option<&Contact> {
+        self.contacts().find(|contact| contact.name == name)
+    }
+
+    /// Add or replace a contact directly
+    ///
+    /// In integrity mode whitelist-eligible contacts, and changes to existing
+    /// whitelisted contacts, must go through [`AddressBook::propose`].
+    pub fn upsert(&mut self, contact: Contact) -> Result<()> {
+        if self.integrity_enabled() {
+            let touches_whitelisted = self.get(&contact.name).map_or(false, |c| c.whitelisted);
+            if contact.whitelisted || touches_whitelisted {
+                return Err(Error::permission_denied(format!(
+                    "Contact '{}' is whitelist-eligible and must be proposed and approved",
+                    contact.name
+                )));
+            }
+        }
+
+        self.activate(ContactEntry {
+            contact,
+            activation: None,
+        });
+        Ok(())
+    }
+
+    /// Remove a contact; removing never widens the whitelist
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.body.contacts.len();
+        self.body
+            .contacts
+            .retain(|entry| entry.contact.name != name);
+        before != self.body.contacts.len()
+    }
+
+    /// Propose adding or modifying a whitelist-eligible contact
+    pub fn propose(&mut self, contact: Contact, proposer: &SecureKeypair) -> Result<Uuid> {
+        if !self.integrity_enabled() {
+            return Err(Error::config(
+                "Address book integrity mode is not enabled; add contacts directly",
+            ));
+        }
+
+        let id = Uuid::new_v4();
+        let payload = ContactChange::payload(&id, &contact)?;
+        self.body.pending.push(ContactChange {
+            id,
+            contact,
+            proposed_by: proposer.public_key(),
+            proposer_signature: proposer.sign(&payload),
+            proposed_at: Utc::now(),
+            approvals: Vec::new(),
+        });
+        Ok(id)
+    }
+
+    /// Approve a pending change, activating it once the quorum is reached
+    ///
+    /// Returns whether the change was activated.
+    pub fn approve(&mut self, id: &Uuid, approver: &SecureKeypair) -> Result<bool> {
+        let policy = self
+            .body
+            .integrity
+            .clone()
+            .ok_or_else(|| Error::config("Address book integrity mode is not enabled"))?;
+        let approver_key = approver.public_key();
+        if !policy.approvers.contains(&approver_key) {
+            return Err(Error::permission_denied(format!(
+                "{} is not an address book approver",
+                approver_key
+            )));
+        }
+
+        let index = self
+            .body
+            .pending
+            .iter()
+            .position(|change| change.id == *id)
+            .ok_or_else(|| Error::validation(format!("No pending contact change {}", id)))?;
+        let change = &mut self.body.pending[index];
+        if change.proposed_by == approver_key {
+            return Err(Error::permission_denied(
+                "The proposer of a change cannot approve it",
+            ));
+        }
+
+        if !change.approvals.iter().any(|a| a.approver == approver_key) {
+            let payload = ContactChange::payload(&change.id, &change.contact)?;
+            change.approvals.push(Approval {
+                approver: approver_key,
+                signature: approver.sign(&payload),
+                approved_at: Utc::now(),
+            });
+        }
+
+        if change.valid_approvals(&policy)? < policy.quorum {
+            return Ok(false);
+        }
+
+        let change = self.body.pending.remove(index);
+        self.activate(ContactEntry {
+            contact: change.contact.clone(),
+            activation: Some(change),
+        });
+        Ok(true)
+    }
+
+    /// Insert or replace the entry with the same name
+    fn activate(&mut self, entry: ContactEntry) {
+        match self
+            .body
+            .contacts
+            .iter_mut()
+            .find(|existing| existing.contact.name == entry.contact.name)
+        {
+            Some(existing) => *existing = entry,
+            None => self.body.contacts.push(entry),
+        }
+    }
+
+    /// Sign the book with the wallet or operator key and write it to disk
+    pub fn save(&mut self, signer: &SecureKeypair) -> Result<()> {
+        let payload = serde_json::to_vec(&self.body)?;
+        let file = AddressBookFile {
+            body: self.body.clone(),
+            signature: Some(BookSignature {
+                signer: signer.public_key(),
+                signature: signer.sign(&payload),
+            }),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&file)?)?;
+        self.integrity_error = None;
+        Ok(())
+    }
+
+    /// Addresses eligible to receive funds
+    ///
+    /// Fails closed when the book did not pass verification.
+    pub fn whitelist(&self) -> Result<HashSet<Pubkey>> {
+        if let Some(reason) = &self.integrity_error {
+            return Err(Error::WhitelistIntegrity(reason.clone()));
+        }
+
+        Ok(self
+            .contacts()
+            .filter(|contact| contact.whitelisted)
+            .map(|contact| contact.address)
+            .collect())
+    }
+
+    /// Ensure a recipient is whitelisted
+    pub fn check_recipient(&self, recipient: &Pubkey) -> Result<()> {
+        if self.whitelist()?.contains(recipient) {
+            Ok(())
+        } else {
+            Err(Error::permission_denied(format!(
+                "Recipient {} is not in the address book whitelist",
+                recipient
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    struct Keys {
+        operator: SecureKeypair,
+        alice: SecureKeypair,
+        bob: SecureKeypair,
+    }
+
+    fn keys() -> Keys {
+        Keys {
+            operator: SecureKeypair::generate(),
+            alice: SecureKeypair::generate(),
+            bob: SecureKeypair::generate(),
+        }
+    }
+
+    fn protected_book(path: &Path, keys: &Keys) -> Result<AddressBook> {
+        let mut book = AddressBook::new(path);
+        book.enable_integrity(IntegrityPolicy::new(
+            vec![keys.alice.public_key(), keys.bob.public_key()],
+            1,
+        ))?;
+        Ok(book)
+    }
+
+    #[test]
+    fn test_approved_contact_is_whitelisted_after_reload() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("contacts.json");
+        let keys = keys();
+        let exchange = Pubkey::new_unique();
+
+        let mut book = protected_book(&path, &keys)?;
+        let id = book.propose(Contact::whitelisted("exchange", exchange), &keys.alice)?;
+        assert!(book.whitelist()?.is_empty());
+
+        // Two-person rule: the proposer cannot approve their own change
+        assert!(book.approve(&id, &keys.alice).is_err());
+        assert!(book.approve(&id, &keys.bob)?);
+        book.save(&keys.operator)?;
+
+        let loaded = AddressBook::load(&path, &keys.operator.public_key())?;
+        assert!(loaded.integrity_error().is_none());
+        loaded.check_recipient(&exchange)?;
+        assert!(loaded.check_recipient(&Pubkey::new_unique()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_direct_file_edit_fails_closed() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("contacts.json");
+        let keys = keys();
+
+        let mut book = protected_book(&path, &keys)?;
+        book.upsert(Contact::new("friend", Pubkey::new_unique()))?;
+        book.save(&keys.operator)?;
+
+        // Flip the entry to whitelisted by editing the JSON by hand
+        let mut raw: serde_json::Value = serde_json::from_slice(&fs::read(&path)?)?;
+        raw["contacts"][0]["contact"]["whitelisted"] = serde_json::Value::Bool(true);
+        fs::write(&path, serde_json::to_vec(&raw)?)?;
+
+        let tampered = AddressBook::load(&path, &keys.operator.public_key())?;
+        assert!(matches!(
+            tampered.whitelist(),
+            Err(Error::WhitelistIntegrity(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resigned_unapproved_whitelist_entry_fails_closed() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("contacts.json");
+        let keys = keys();
+
+        // Someone holding the signing key but bypassing the approval workflow
+        let mut book = protected_book(&path, &keys)?;
+        book.body.contacts.push(ContactEntry {
+            contact: Contact::whitelisted("attacker", Pubkey::new_unique()),
+            activation: None,
+        });
+        book.save(&keys.operator)?;
+
+        let loaded = AddressBook::load(&path, &keys.operator.public_key())?;
+        assert!(loaded.whitelist().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stripped_integrity_policy_fails_closed() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("contacts.json");
+        let keys = keys();
+        protected_book(&path, &keys)?.save(&keys.operator)?;
+
+        // Drop the policy and whitelist a contact as if integrity were never on
+        let mut raw: serde_json::Value = serde_json::from_slice(&fs::read(&path)?)?;
+        let Some(fields) = raw.as_object_mut() else {
+            panic!("address book is not a JSON object");
+        };
+        fields.remove("integrity");
+        fields.remove("signature");
+        raw["contacts"] = serde_json::json!([{
+            "contact": Contact::whitelisted("attacker", Pubkey::new_unique()),
+            "activation": null,
+        }]);
+        fs::write(&path, serde_json::to_vec(&raw)?)?;
+
+        let settings = AddressBookSettings {
+            path: path.clone(),
+            trusted_signer: Some(keys.operator.public_key().to_string()),
+        };
+        let stripped = AddressBook::from_settings(&settings)?;
+        assert!(!stripped.integrity_enabled());
+        assert!(matches!(
+            stripped.whitelist(),
+            Err(Error::WhitelistIntegrity(_))
+        ));
+
+        // Without a trusted signer nothing is required of the file
+        let open = AddressBook::open(&path)?;
+        assert_eq!(open.whitelist()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_mode_blocks_direct_whitelist_upsert() -> Result<()> {
+        let dir = tempdir()?;
+        let keys = keys();
+        let mut book = protected_book(&dir.path().join("contacts.json"), &keys)?;
+
+        assert!(book
+            .upsert(Contact::whitelisted("exchange", Pubkey::new_unique()))
+            .is_err());
+        book.upsert(Contact::new("friend", Pubkey::new_unique()))?;
+        assert_eq!(book.contacts().count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,506 @@
+//! Append-only audit trail of executed actions
+//!
+//! The wallet and agent runners report every action they execute to an
+//! [`AuditSink`]. Each action produces two [`AuditEntry`] records sharing
+//! one id: an [`AuditPhase::Intent`] written before the transaction is
+//! broadcast, and an [`AuditPhase::Outcome`] written once it confirmed,
+//! failed or timed out. An intent without a matching outcome marks an
+//! action whose fate is unknown, typically because the process died.
+//! Records of dry runs carry the `dry_run` flag and end with
+//! [`AuditOutcome::Simulated`]; nothing was broadcast for them.
+//!
+//! [`JsonlAuditSink`] appends records as JSON lines and rotates the file
+//! once it reaches a size limit, keeping a fixed number of rotated files
+//! (`audit.jsonl.1` being the most recent). [`MemoryAuditSink`] keeps
+//! records in memory for tests.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::audit::{AuditEntry, AuditOutcome, AuditSink, JsonlAuditSink};
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::AgentAction;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # fn main() -> agent_wallet_core::Result<()> {
+//! let sink = JsonlAuditSink::open("/var/log/agent-wallet/audit.jsonl", 10 << 20, 5)?;
+//! let action = AgentAction::TransferSol {
+//!     to: Pubkey::new_unique(),
+//!     amount: Lamports::new(1_000),
+//!     memo: None,
+//! };
+//!
+//! let intent = AuditEntry::intent(Pubkey::new_unique(), action).with_agent("trader-1");
+//! sink.record(&intent)?;
+//! sink.record(&intent.outcome(AuditOutcome::Confirmed))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::sync::lock;
+use crate::types::AgentAction;
+
+/// Stage of an action an audit record describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditPhase {
+    /// About to be broadcast
+    Intent,
+    /// Settled, or given up on
+    Outcome,
+}
+
+/// How an audited action ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// The agent decided not to act
+    NoAction,
+    /// Broadcast without waiting for confirmation
+    Sent,
+    /// The transaction reached the requested commitment
+    Confirmed,
+    /// Confirmation timed out; the transaction may still land
+    Unconfirmed,
+    /// The action was rejected or the transaction failed
+    Failed,
+    /// Dry run: the transaction was simulated and deliberately not sent
+    Simulated,
+}
+
+/// One record of the audit trail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Id shared by an action's intent and outcome
+    pub id: Uuid,
+    /// Stage the record describes
+    pub phase: AuditPhase,
+    /// When the record was written
+    pub timestamp: DateTime<Utc>,
+    /// Agent that decided the action, if any
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// Wallet executing the action
+    pub wallet: Pubkey,
+    /// The action
+    #[serde(default)]
+    pub action: Option<AgentAction>,
+    /// How the action ended, on outcome records
+    #[serde(default)]
+    pub outcome: Option<AuditOutcome>,
+    /// Transaction signature, once signed
+    #[serde(default)]
+    pub signature: Option<Signature>,
+    /// Estimated transaction fee in lamports
+    #[serde(default)]
+    pub fee_lamports: Option<u64>,
+    /// Error that failed the action
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Whether the record belongs to a dry run that sends nothing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// Warnings the transaction policy raised before the send
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Recipient as the caller named it, when it was a `.sol` domain
+    ///
+    /// The resolved address is the one in the action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient_name: Option<String>,
+    /// How the transaction reached the network, on outcome records of sent
+    /// actions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission: Option<SubmissionPath>,
+}
+
+/// Route a sent transaction took to the network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionPath {
+    /// Broadcast through the RPC node
+    Rpc,
+    /// Landed as part of a Jito bundle
+    JitoBundle {
+        /// Id the block engine gave the bundle
+        bundle_id: String,
+    },
+    /// Broadcast through the RPC node after the Jito bundle failed
+    RpcFallback {
+        /// Why the bundle was given up on
+        reason: String,
+    },
+}
+
+impl AuditEntry {
+    /// Intent record of a new action
+    pub fn intent(wallet: Pubkey, action: AgentAction) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            phase: AuditPhase::Intent,
+            timestamp: Utc::now(),
+            agent_id: None,
+            wallet,
+            action: Some(action),
+            outcome: None,
+            signature: None,
+            fee_lamports: None,
+            error: None,
+            dry_run: false,
+            warnings: Vec::new(),
+            recipient_name: None,
+            submission: None,
+        }
+    }
+
+    /// Outcome record linked to this intent
+    pub fn outcome(&self, outcome: AuditOutcome) -> Self {
+        Self {
+            phase: AuditPhase::Outcome,
+            timestamp: Utc::now(),
+            outcome: Some(outcome),
+            ..self.clone()
+        }
+    }
+
+    /// Use `id` for the entry, e.g. to match the decision it records
+    pub fn with_id(mut self, id: Uuid) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Attribute the action to an agent
+    pub fn with_agent(mut self, agent_id: impl Into<String>) -> Self {
+        self.agent_id = Some(agent_id.into());
+        self
+    }
+
+    /// Record the transaction signature
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Record the transaction fee
+    pub fn with_fee(mut self, fee_lamports: u64) -> Self {
+        self.fee_lamports = Some(fee_lamports);
+        self
+    }
+
+    /// Record the transaction policy's warnings
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Record the name the recipient was given as, if any
+    pub fn with_recipient_name(mut self, recipient_name: Option<String>) -> Self {
+        self.recipient_name = recipient_name;
+        self
+    }
+
+    /// Record how the transaction reached the network
+    pub fn with_submission(mut self, submission: SubmissionPath) -> Self {
+        self.submission = Some(submission);
+        self
+    }
+
+    /// Flag the record as part of a dry run
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Record the error that failed the action
+    pub fn with_error(mut self, error: impl ToString) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// Destination of audit records
+pub trait AuditSink: Send + Sync {
+    /// Durably record an entry
+    fn record(&self, entry: &AuditEntry) -> Result<()>;
+
+    /// Write out anything still buffered, e.g. before shutting down
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Audit records kept in memory
+#[derive(Debug, Default)]
+pub struct MemoryAuditSink {
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl MemoryAuditSink {
+    /// Create an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records written so far, oldest first
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        lock(&self.entries).clone()
+    }
+}
+
+impl AuditSink for MemoryAuditSink {
+    fn record(&self, entry: &AuditEntry) -> Result<()> {
+        lock(&self.entries).push(entry.clone());
+        Ok(())
+    }
+}
+
+struct OpenFile {
+    file: File,
+    len: u64,
+}
+
+/// JSON-lines audit file with size-based rotation
+pub struct JsonlAuditSink {
+    path: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: Mutex<OpenFile>,
+}
+
+impl JsonlAuditSink {
+    /// Append to `path`, rotating it once it would exceed `max_file_bytes`
+    ///
+    /// Up to `max_files` rotated files are kept beside the active one.
+    pub fn open(path: impl AsRef<Path>, max_file_bytes: u64, max_files: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| io_error("create", dir, e))?;
+        }
+        let file = open_append(&path)?;
+        Ok(Self {
+            path,
+            max_file_bytes,
+            max_files,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Path of the active file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Existing audit files, oldest first
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = (1..=self.max_files)
+            .rev()
+            .map(|n| self.rotated(n))
+            .filter(|path| path.exists())
+            .collect();
+        if self.path.exists() {
+            files.push(self.path.clone());
+        }
+        files
+    }
+
+    /// Every readable record across the rotated files, oldest first
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let _guard = lock(&self.file);
+        let mut entries = Vec::new();
+        for path in self.files() {
+            let contents = fs::read_to_string(&path).map_err(|e| io_error("read", &path, e))?;
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                entries.push(serde_json::from_str(line)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// Shift rotated files up by one and start a fresh active file
+    fn rotate(&self, open: &mut OpenFile) -> Result<()> {
+        open.file
+            .flush()
+            .map_err(|e| io_error("flush", &self.path, e))?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path).map_err(|e| io_error("remove", &self.path, e))?;
+        } else {
+            let oldest = self.rotated(self.max_files);
+            if oldest.exists() {
+                fs::remove_file(&oldest).map_err(|e| io_error("remove", &oldest, e))?;
+            }
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(n + 1))
+                        .map_err(|e| io_error("rotate", &from, e))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))
+                .map_err(|e| io_error("rotate", &self.path, e))?;
+        }
+        *open = open_append(&self.path)?;
+        Ok(())
+    }
+}
+
+impl AuditSink for JsonlAuditSink {
+    fn record(&self, entry: &AuditEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut open = lock(&self.file);
+        if open.len > 0 && open.len + line.len() as u64 > self.max_file_bytes {
+            self.rotate(&mut open)?;
+        }
+        open.file
+            .write_all(&line)
+            .and_then(|()| open.file.sync_data())
+            .map_err(|e| io_error("append to", &self.path, e))?;
+        open.len += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        let open = lock(&self.file);
+        open.file
+            .sync_all()
+            .map_err(|e| io_error("flush", &self.path, e))
+    }
+}
+
+fn open_append(path: &Path) -> Result<OpenFile> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| io_error("open", path, e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| io_error("inspect", path, e))?
+        .len();
+    Ok(OpenFile { file, len })
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> Error {
+    Error::storage(format!(
+        "Failed to {} audit file {}: {}",
+        action,
+        path.display(),
+        e
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol::Lamports;
+    use tempfile::tempdir;
+
+    fn transfer() -> AgentAction {
+        AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(5_000),
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_outcome_is_linked_to_intent() -> Result<()> {
+        let sink = MemoryAuditSink::new();
+        let intent = AuditEntry::intent(Pubkey::new_unique(), transfer())
+            .with_agent("trader-1")
+            .with_fee(5_000);
+        sink.record(&intent)?;
+        sink.record(
+            &intent
+                .outcome(AuditOutcome::Failed)
+                .with_error("blockhash expired"),
+        )?;
+
+        let entries = sink.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, entries[1].id);
+        assert_eq!(entries[1].phase, AuditPhase::Outcome);
+        assert_eq!(entries[1].agent_id.as_deref(), Some("trader-1"));
+        assert_eq!(entries[1].error.as_deref(), Some("blockhash expired"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_account_actions_round_trip() -> Result<()> {
+        let dir = tempdir()?;
+        let sink = JsonlAuditSink::open(dir.path().join("audit.jsonl"), u64::MAX, 1)?;
+        let wallet = Pubkey::new_unique();
+        let actions = vec![
+            AgentAction::CreateTokenAccount {
+                mint: Pubkey::new_unique(),
+            },
+            AgentAction::CloseTokenAccount {
+                account: Pubkey::new_unique(),
+                destination: wallet,
+            },
+        ];
+        for action in &actions {
+            let json = serde_json::to_string(action)?;
+            let parsed: AgentAction = serde_json::from_str(&json)?;
+            assert_eq!(serde_json::to_string(&parsed)?, json);
+            sink.record(&AuditEntry::intent(wallet, action.clone()))?;
+        }
+
+        let logged: Vec<String> = sink
+            .entries()?
+            .iter()
+            .filter_map(|entry| entry.action.as_ref().map(AgentAction::description))
+            .collect();
+        let expected: Vec<String> = actions.iter().map(AgentAction::description).collect();
+        assert_eq!(logged, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_rotates_and_keeps_order() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("audit.jsonl");
+        let wallet = Pubkey::new_unique();
+
+        let line_len = serde_json::to_vec(&AuditEntry::intent(wallet, transfer()))?.len() as u64;
+        // Two records per file, two rotated files kept
+        let sink = JsonlAuditSink::open(&path, 2 * line_len + 2, 2)?;
+        let mut ids = Vec::new();
+        for _ in 0..7 {
+            let entry = AuditEntry::intent(wallet, transfer());
+            ids.push(entry.id);
+            sink.record(&entry)?;
+        }
+
+        assert_eq!(sink.files().len(), 3);
+        assert!(!sink.rotated(3).exists());
+        let kept: Vec<Uuid> = sink.entries()?.into_iter().map(|e| e.id).collect();
+        assert_eq!(kept, ids[2..].to_vec());
+
+        // Reopening resumes the active file's size
+        drop(sink);
+        let reopened = JsonlAuditSink::open(&path, 2 * line_len + 2, 2)?;
+        reopened.record(&AuditEntry::intent(wallet, transfer()))?;
+        assert_eq!(reopened.entries()?.len(), 5);
+        Ok(())
+    }
+}
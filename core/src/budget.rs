@@ -0,0 +1,594 @@
+//! Cancellation-safe spend accounting
+//!
+//! Every budgeted action holds a [`BudgetReservation`] from the moment it is
+//! prepared. The reservation counts against the daily budget while the action
+//! is in flight, is committed only once the transaction is confirmed, and is
+//! released when dropped: on failure, on cancellation of the future holding
+//! it, or on a panic. Two concurrent actions can therefore never both spend
+//! the last of the budget, and an abandoned action never keeps budget locked.
+//!
+//! A reservation whose transaction was already submitted is the exception:
+//! the transaction may still land, so dropping it keeps the budget held until
+//! [`BudgetLedger::reconcile`] resolves it. Pending reservations are persisted
+//! next to an idempotency journal recording each submission and its outcome,
+//! and reconciliation at startup settles them from the journal:
+//!
+//! | Journal     | Outcome                                   |
+//! |-------------|-------------------------------------------|
+//! | no entry    | released, unless it was already signed    |
+//! | `submitted` | may have landed; committed to be safe     |
+//! | `confirmed` | committed                                 |
+//! | `failed`    | released                                  |
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::Arc;
+//! use agent_wallet_core::budget::BudgetLedger;
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::types::AgentContext;
+//! use solana_sdk::pubkey::Pubkey;
+//! use solana_sdk::signature::Signature;
+//!
+//! # fn main() -> agent_wallet_core::Result<()> {
+//! let ledger = Arc::new(BudgetLedger::in_memory());
+//! let context = AgentContext::new(Pubkey::new_unique());
+//!
+//! let mut reservation = ledger.reserve(&context, Lamports::new(1_000))?;
+//! reservation.mark_sent(&Signature::default())?;
+//! assert_eq!(reservation.commit()?, Lamports::new(1_000));
+//! assert_eq!(ledger.reserved(), Lamports::ZERO);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Signature;
+use tracing::warn;
+
+use crate::error::{Error, Result};
+use crate::sol::Lamports;
+use crate::sync::lock;
+use crate::types::AgentContext;
+
+/// Progress of a submission in the idempotency journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// Signed and about to be sent; outcome unknown
+    Submitted,
+    /// Reached the requested commitment
+    Confirmed,
+    /// Rejected or failed on chain
+    Failed,
+}
+
+/// One line of the idempotency journal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Idempotency key of the action, shared with its reservation
+    pub key: String,
+    /// Transaction signature, once signed
+    pub signature: Option<Signature>,
+    /// Progress at the time of writing
+    pub status: JournalStatus,
+    /// When the entry was written
+    pub at: DateTime<Utc>,
+}
+
+/// Append-only JSONL journal of submitted actions
+#[derive(Debug, Clone)]
+pub struct IdempotencyJournal {
+    path: PathBuf,
+}
+
+impl IdempotencyJournal {
+    /// Journal stored at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Path of the journal file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry
+    pub fn append(&self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)
+            .map_err(|e| Error::serialization(format!("Invalid journal entry: {}", e)))?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| Error::storage(format!("Failed to open journal: {}", e)))?;
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .map_err(|e| Error::storage(format!("Failed to write journal: {}", e)))
+    }
+
+    /// Latest status recorded for each key
+    ///
+    /// A missing journal has no entries. A torn last line, left by a crash
+    /// mid-write, is ignored.
+    pub fn latest(&self) -> Result<HashMap<String, JournalStatus>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(Error::storage(format!("Failed to read journal: {}", e))),
+        };
+
+        let mut latest = HashMap::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<JournalEntry>(line) {
+                Ok(entry) => {
+                    latest.insert(entry.key, entry.status);
+                }
+                Err(e) => warn!("Skipping unreadable journal line: {}", e),
+            }
+        }
+        Ok(latest)
+    }
+}
+
+/// Persisted record of a reservation that has not been settled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingReservation {
+    /// Idempotency key of the reserved action
+    pub key: String,
+    /// Lamports held against the budget
+    pub lamports: u64,
+    /// When the reservation was made
+    pub created_at: DateTime<Utc>,
+    /// Signature of the submitted transaction
+    pub signature: Option<Signature>,
+}
+
+/// Outcome of settling persisted reservations
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// Reservations whose spend is now deducted
+    pub committed: Vec<PendingReservation>,
+    /// Reservations given back to the budget
+    pub released: Vec<PendingReservation>,
+}
+
+impl Reconciliation {
+    /// Total lamports to deduct from the budget
+    pub fn committed_lamports(&self) -> Lamports {
+        Lamports::new(
+            self.committed
+                .iter()
+                .map(|r| r.lamports)
+                .fold(0, u64::saturating_add),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Table {
+    pending: BTreeMap<String, PendingReservation>,
+}
+
+/// Reservations held against an agent's daily budget
+#[derive(Debug)]
+pub struct BudgetLedger {
+    /// File holding the pending reservations table, if persisted
+    path: Option<PathBuf>,
+    journal: Option<IdempotencyJournal>,
+    table: StdMutex<Table>,
+}
+
+impl BudgetLedger {
+    /// Ledger kept only in memory
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            journal: None,
+            table: StdMutex::new(Table::default()),
+        }
+    }
+
+    /// Ledger persisting pending reservations to `path` and submissions to `journal`
+    ///
+    /// Reservations left by a previous run are loaded but not settled; call
+    /// [`BudgetLedger::reconcile`] before reserving.
+    pub fn open(path: impl Into<PathBuf>, journal: IdempotencyJournal) -> Result<Self> {
+        let path = path.into();
+        let table = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| Error::serialization(format!("Invalid reservations table: {}", e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Table::default(),
+            Err(e) => {
+                return Err(Error::storage(format!(
+                    "Failed to read reservations table: {}",
+                    e
+                )))
+            }
+        };
+
+        Ok(Self {
+            path: Some(path),
+            journal: Some(journal),
+            table: StdMutex::new(table),
+        })
+    }
+
+    /// Lamports currently held by unsettled reservations
+    pub fn reserved(&self) -> Lamports {
+        Lamports::new(
+            lock(&self.table)
+                .pending
+                .values()
+                .map(|r| r.lamports)
+                .fold(0, u64::saturating_add),
+        )
+    }
+
+    /// Unsettled reservations, oldest first
+    pub fn pending(&self) -> Vec<PendingReservation> {
+        let mut pending: Vec<_> = lock(&self.table).pending.values().cloned().collect();
+        pending.sort_by_key(|r| r.created_at);
+        pending
+    }
+
+    /// Hold `amount` against the budget of `context`
+    ///
+    /// Fails with `LimitExceeded` if the amount breaks the per-transaction
+    /// limit or does not fit in the remaining budget minus what other
+    /// reservations already hold.
+    pub fn reserve(
+        self: &Arc<Self>,
+        context: &AgentContext,
+        amount: Lamports,
+    ) -> Result<BudgetReservation> {
+        context.is_action_allowed(amount)?;
+
+        let mut table = lock(&self.table);
+        let held = table
+            .pending
+            .values()
+            .map(|r| r.lamports)
+            .fold(0, u64::saturating_add);
+        let remaining = context
+            .spending_limits
+            .remaining_daily_budget_lamports
+            .as_u64();
+        let available = remaining.saturating_sub(held);
+        if amount.as_u64() > available {
+            return Err(Error::LimitExceeded(format!(
+                "Transaction amount {} exceeds the {} left after {} reserved by actions in flight",
+                amount,
+                Lamports::new(available),
+                Lamports::new(held)
+            )));
+        }
+
+        let key = uuid::Uuid::new_v4().to_string();
+        table.pending.insert(
+            key.clone(),
+            PendingReservation {
+                key: key.clone(),
+                lamports: amount.as_u64(),
+                created_at: Utc::now(),
+                signature: None,
+            },
+        );
+        self.persist(&table)?;
+
+        Ok(BudgetReservation {
+            ledger: Arc::clone(self),
+            key,
+            lamports: amount,
+            sent: false,
+            settled: false,
+        })
+    }
+
+    /// Settle every persisted reservation from the idempotency journal
+    ///
+    /// Committed reservations must be deducted from the budget by the caller.
+    pub fn reconcile(&self) -> Result<Reconciliation> {
+        let statuses = match &self.journal {
+            Some(journal) => journal.latest()?,
+            None => HashMap::new(),
+        };
+
+        let mut table = lock(&self.table);
+        let mut outcome = Reconciliation::default();
+        for (key, reservation) in std::mem::take(&mut table.pending) {
+            match statuses.get(&key) {
+                // Either landed or may still land: count it rather than risk
+                // spending the same budget twice
+                Some(JournalStatus::Confirmed) | Some(JournalStatus::Submitted) => {
+                    outcome.committed.push(reservation)
+                }
+                None if reservation.signature.is_some() => outcome.committed.push(reservation),
+                Some(JournalStatus::Failed) | None => outcome.released.push(reservation),
+            }
+        }
+        self.persist(&table)?;
+        Ok(outcome)
+    }
+
+    fn record(&self, key: &str, signature: Option<Signature>, status: JournalStatus) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.append(&JournalEntry {
+                key: key.to_string(),
+                signature,
+                status,
+                at: Utc::now(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn persist(&self, table: &Table) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let bytes = serde_json::to_vec_pretty(table)
+            .map_err(|e| Error::serialization(format!("Invalid reservations table: {}", e)))?;
+
+        // Write then rename so a crash never leaves a torn table
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)
+            .and_then(|()| fs::rename(&tmp, path))
+            .map_err(|e| Error::storage(format!("Failed to write reservations table: {}", e)))
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        let mut table = lock(&self.table);
+        table.pending.remove(key);
+        self.persist(&table)
+    }
+}
+
+/// Budget held for one action until it is confirmed or abandoned
+///
+/// Dropping an unsent reservation releases it. Dropping one whose
+/// transaction was submitted keeps it held for [`BudgetLedger::reconcile`].
+#[derive(Debug)]
+#[must_use = "dropping a reservation releases it"]
+pub struct BudgetReservation {
+    ledger: Arc<BudgetLedger>,
+    key: String,
+    lamports: Lamports,
+    sent: bool,
+    settled: bool,
+}
+
+impl BudgetReservation {
+    /// Idempotency key of the reserved action
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Lamports held
+    pub fn lamports(&self) -> Lamports {
+        self.lamports
+    }
+
+    /// Record that the transaction is about to be sent
+    ///
+    /// Call before sending: from here on a crash or cancellation leaves the
+    /// reservation for reconciliation instead of releasing it.
+    pub fn mark_sent(&mut self, signature: &Signature) -> Result<()> {
+        {
+            let mut table = lock(&self.ledger.table);
+            if let Some(reservation) = table.pending.get_mut(&self.key) {
+                reservation.signature = Some(*signature);
+            }
+            self.ledger.persist(&table)?;
+        }
+        self.ledger
+            .record(&self.key, Some(*signature), JournalStatus::Submitted)?;
+        self.sent = true;
+        Ok(())
+    }
+
+    /// Settle the reservation as spent, returning the amount to deduct
+    pub fn commit(mut self) -> Result<Lamports> {
+        self.ledger
+            .record(&self.key, None, JournalStatus::Confirmed)?;
+        self.ledger.remove(&self.key)?;
+        self.settled = true;
+        Ok(self.lamports)
+    }
+
+    /// Give the reservation back because the transaction failed
+    pub fn release(mut self) -> Result<()> {
+        if self.sent {
+            self.ledger.record(&self.key, None, JournalStatus::Failed)?;
+        }
+        self.ledger.remove(&self.key)?;
+        self.settled = true;
+        Ok(())
+    }
+}
+
+impl Drop for BudgetReservation {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        if self.sent {
+            warn!(
+                "Reservation {} dropped after submission; holding {} until reconciled",
+                self.key, self.lamports
+            );
+            return;
+        }
+        if let Err(e) = self.ledger.remove(&self.key) {
+            warn!("Failed to release reservation {}: {}", self.key, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol::LAMPORTS_PER_SOL;
+    use futures::FutureExt;
+    use solana_sdk::pubkey::Pubkey;
+    use tempfile::tempdir;
+
+    fn context(remaining_sol: u64) -> AgentContext {
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        let remaining = Lamports::new(remaining_sol * LAMPORTS_PER_SOL);
+        context.spending_limits.per_transaction_limit_lamports = remaining;
+        context.spending_limits.remaining_daily_budget_lamports = remaining;
+        context
+    }
+
+    fn pending(key: &str, lamports: u64, signature: Option<Signature>) -> PendingReservation {
+        PendingReservation {
+            key: key.to_string(),
+            lamports,
+            created_at: Utc::now(),
+            signature,
+        }
+    }
+
+    fn submitted(journal: &IdempotencyJournal, key: &str, status: JournalStatus) -> Result<()> {
+        journal.append(&JournalEntry {
+            key: key.to_string(),
+            signature: Some(Signature::default()),
+            status,
+            at: Utc::now(),
+        })
+    }
+
+    #[test]
+    fn test_commit_after_confirmation() -> Result<()> {
+        let dir = tempdir().map_err(|e| Error::storage(e.to_string()))?;
+        let journal = IdempotencyJournal::new(dir.path().join("journal.jsonl"));
+        let ledger = Arc::new(BudgetLedger::open(
+            dir.path().join("reservations.json"),
+            journal.clone(),
+        )?);
+
+        let mut reservation = ledger.reserve(&context(1), Lamports::new(400_000_000))?;
+        assert_eq!(ledger.reserved(), Lamports::new(400_000_000));
+        reservation.mark_sent(&Signature::default())?;
+        assert_eq!(ledger.pending()[0].signature, Some(Signature::default()));
+
+        let key = reservation.key().to_string();
+        assert_eq!(reservation.commit()?, Lamports::new(400_000_000));
+        assert_eq!(ledger.reserved(), Lamports::ZERO);
+        assert_eq!(journal.latest()?.get(&key), Some(&JournalStatus::Confirmed));
+
+        // Nothing left for the next start to settle
+        let reopened = BudgetLedger::open(dir.path().join("reservations.json"), journal)?;
+        assert!(reopened.pending().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reservations_share_the_budget() -> Result<()> {
+        let ledger = Arc::new(BudgetLedger::in_memory());
+        let context = context(1);
+
+        let first = ledger.reserve(&context, Lamports::new(700_000_000))?;
+        assert!(matches!(
+            ledger.reserve(&context, Lamports::new(400_000_000)),
+            Err(Error::LimitExceeded(_))
+        ));
+
+        first.release()?;
+        let _second = ledger.reserve(&context, Lamports::new(400_000_000))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_before_send_releases() -> Result<()> {
+        let dir = tempdir().map_err(|e| Error::storage(e.to_string()))?;
+        let path = dir.path().join("reservations.json");
+        let ledger = Arc::new(BudgetLedger::open(
+            &path,
+            IdempotencyJournal::new(dir.path().join("journal.jsonl")),
+        )?);
+        let context = context(1);
+
+        // An action future cancelled while waiting, before it sent anything
+        let action = async {
+            let _reservation = ledger.reserve(&context, Lamports::new(500_000_000))?;
+            std::future::pending::<()>().await;
+            Ok::<_, Error>(())
+        };
+        assert!(action.now_or_never().is_none());
+
+        assert_eq!(ledger.reserved(), Lamports::ZERO);
+        let reopened = BudgetLedger::open(
+            &path,
+            IdempotencyJournal::new(dir.path().join("journal.jsonl")),
+        )?;
+        assert!(reopened.pending().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_after_send_holds_budget() -> Result<()> {
+        let ledger = Arc::new(BudgetLedger::in_memory());
+        let mut reservation = ledger.reserve(&context(1), Lamports::new(500_000_000))?;
+        reservation.mark_sent(&Signature::default())?;
+        drop(reservation);
+
+        assert_eq!(ledger.reserved(), Lamports::new(500_000_000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconcile_crash_after_send() -> Result<()> {
+        let dir = tempdir().map_err(|e| Error::storage(e.to_string()))?;
+        let path = dir.path().join("reservations.json");
+        let journal = IdempotencyJournal::new(dir.path().join("journal.jsonl"));
+
+        // Table and journal as a crash left them
+        let mut table = Table::default();
+        for reservation in [
+            pending("in-doubt", 100, Some(Signature::default())),
+            pending("unsent", 200, None),
+            pending("confirmed", 300, Some(Signature::default())),
+            pending("failed", 400, Some(Signature::default())),
+        ] {
+            table.pending.insert(reservation.key.clone(), reservation);
+        }
+        fs::write(
+            &path,
+            serde_json::to_vec(&table).map_err(|e| Error::serialization(e.to_string()))?,
+        )
+        .map_err(|e| Error::storage(e.to_string()))?;
+        submitted(&journal, "in-doubt", JournalStatus::Submitted)?;
+        submitted(&journal, "confirmed", JournalStatus::Submitted)?;
+        submitted(&journal, "confirmed", JournalStatus::Confirmed)?;
+        submitted(&journal, "failed", JournalStatus::Submitted)?;
+        submitted(&journal, "failed", JournalStatus::Failed)?;
+
+        let ledger = BudgetLedger::open(&path, journal.clone())?;
+        assert_eq!(ledger.reserved(), Lamports::new(1_000));
+
+        let outcome = ledger.reconcile()?;
+        let mut committed: Vec<_> = outcome.committed.iter().map(|r| r.key.as_str()).collect();
+        committed.sort_unstable();
+        let mut released: Vec<_> = outcome.released.iter().map(|r| r.key.as_str()).collect();
+        released.sort_unstable();
+        assert_eq!(committed, vec!["confirmed", "in-doubt"]);
+        assert_eq!(released, vec!["failed", "unsent"]);
+        assert_eq!(outcome.committed_lamports(), Lamports::new(400));
+
+        // Settled for good: a second start has nothing to do
+        assert_eq!(ledger.reserved(), Lamports::ZERO);
+        let reopened = BudgetLedger::open(&path, journal)?;
+        assert_eq!(reopened.reconcile()?, Reconciliation::default());
+        Ok(())
+    }
+}
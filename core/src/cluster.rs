@@ -0,0 +1,111 @@
+//! Identify the Solana cluster an RPC endpoint serves
+//!
+//! Every cluster has a fixed genesis hash, so comparing the endpoint's
+//! `getGenesisHash` against the public clusters tells mainnet from devnet
+//! and testnet whatever the URL looks like. Anything else, such as a local
+//! validator, is [`Cluster::Custom`]. Safety checks that must never run
+//! against real funds, like the devnet faucet, use this.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::cluster::Cluster;
+//! use agent_wallet_core::rpc::{RpcClient, RpcClientConfig};
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let rpc = RpcClient::new(RpcClientConfig::single_endpoint("https://api.devnet.solana.com")).await?;
+//! assert_eq!(rpc.get_cluster().await?, Cluster::Devnet);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+
+/// Genesis hash of mainnet-beta
+pub const MAINNET_BETA_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+
+/// Genesis hash of devnet
+pub const DEVNET_GENESIS_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+
+/// Genesis hash of testnet
+pub const TESTNET_GENESIS_HASH: &str = "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY";
+
+/// Solana cluster, as identified by its genesis hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cluster {
+    /// The production cluster; real funds
+    MainnetBeta,
+    /// Public development cluster with a faucet
+    Devnet,
+    /// Public test cluster for validators
+    Testnet,
+    /// Any other cluster, e.g. a local test validator
+    Custom,
+}
+
+impl Cluster {
+    /// Cluster whose genesis block has `hash`
+    pub fn from_genesis_hash(hash: &Hash) -> Self {
+        match hash.to_string().as_str() {
+            MAINNET_BETA_GENESIS_HASH => Self::MainnetBeta,
+            DEVNET_GENESIS_HASH => Self::Devnet,
+            TESTNET_GENESIS_HASH => Self::Testnet,
+            _ => Self::Custom,
+        }
+    }
+
+    /// Whether the cluster holds real funds
+    pub fn is_mainnet(&self) -> bool {
+        matches!(self, Self::MainnetBeta)
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MainnetBeta => "mainnet-beta",
+            Self::Devnet => "devnet",
+            Self::Testnet => "testnet",
+            Self::Custom => "custom",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, Result};
+
+    fn hash(base58: &str) -> Result<Hash> {
+        base58
+            .parse()
+            .map_err(|e| Error::validation(format!("{}: {}", base58, e)))
+    }
+
+    #[test]
+    fn test_cluster_from_genesis_hash() -> Result<()> {
+        assert_eq!(
+            Cluster::from_genesis_hash(&hash(MAINNET_BETA_GENESIS_HASH)?),
+            Cluster::MainnetBeta
+        );
+        assert_eq!(
+            Cluster::from_genesis_hash(&hash(DEVNET_GENESIS_HASH)?),
+            Cluster::Devnet
+        );
+        assert_eq!(
+            Cluster::from_genesis_hash(&hash(TESTNET_GENESIS_HASH)?),
+            Cluster::Testnet
+        );
+        assert_eq!(
+            Cluster::from_genesis_hash(&Hash::new_unique()),
+            Cluster::Custom
+        );
+        assert!(Cluster::MainnetBeta.is_mainnet());
+        assert!(!Cluster::Custom.is_mainnet());
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+//! Market data from CoinGecko's simple-price API
+//!
+//! A [`CoinGeckoProvider`] quotes a whole watchlist with one request to
+//! `/simple/price`, mapping symbols and mint addresses to CoinGecko coin
+//! ids. Calls go through an internal [`TokenBucket`] sized for the public
+//! free tier, so a fast refresh loop is refused locally instead of being
+//! throttled by CoinGecko; the context then falls back to its other
+//! providers.
+//!
+//! Only available in builds with the `coingecko` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::coingecko::{CoinGeckoConfig, CoinGeckoProvider};
+//! use agent_wallet_core::market::MarketDataProvider;
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let provider = CoinGeckoProvider::new(CoinGeckoConfig::default())?.with_id("JUP", "jupiter");
+//! let prices = provider.get_prices(&["SOL".to_string(), "JUP".to_string()]).await?;
+//! for (symbol, point) in &prices {
+//!     println!("{} at ${} ({:?}% over 24h)", symbol, point.price, point.change_24h);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::market::{MarketDataProvider, PricePoint};
+use crate::rate_limit::TokenBucket;
+
+/// Base URL of the public API
+pub const DEFAULT_COINGECKO_URL: &str = "https://api.coingecko.com/api/v3";
+
+/// Calls per minute allowed on the free tier
+pub const FREE_TIER_CALLS_PER_MINUTE: u32 = 30;
+
+/// Default time allowed for one request
+pub const DEFAULT_COINGECKO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Path of the simple-price endpoint below the base URL
+const SIMPLE_PRICE_PATH: &str = "/simple/price";
+
+/// Header carrying a demo API key
+const API_KEY_HEADER: &str = "x-cg-demo-api-key";
+
+/// Coin ids of the symbols and mints quoted without configuration
+const DEFAULT_IDS: [(&str, &str); 6] = [
+    ("SOL", "solana"),
+    ("So11111111111111111111111111111111111111112", "solana"),
+    ("USDC", "usd-coin"),
+    ("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "usd-coin"),
+    ("USDT", "tether"),
+    ("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", "tether"),
+];
+
+/// Where prices are requested and how often
+#[derive(Debug, Clone)]
+pub struct CoinGeckoConfig {
+    /// Base URL of the API
+    pub base_url: String,
+    /// Demo API key sent with every request, if any
+    pub api_key: Option<String>,
+    /// Calls allowed per minute
+    pub calls_per_minute: u32,
+    /// Time allowed for one request
+    pub timeout: Duration,
+}
+
+impl CoinGeckoConfig {
+    /// Send requests to `base_url` instead of the public API
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Authenticate with a demo API key
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+}
+
+impl Default for CoinGeckoConfig {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_COINGECKO_URL.to_string(),
+            api_key: None,
+            calls_per_minute: FREE_TIER_CALLS_PER_MINUTE,
+            timeout: DEFAULT_COINGECKO_TIMEOUT,
+        }
+    }
+}
+
+/// Quote of one coin as the simple-price endpoint returns it
+#[derive(Deserialize)]
+struct SimplePrice {
+    usd: Option<f64>,
+    usd_24h_change: Option<f64>,
+    last_updated_at: Option<i64>,
+}
+
+/// [`MarketDataProvider`] backed by CoinGecko
+pub struct CoinGeckoProvider {
+    config: CoinGeckoConfig,
+    client: reqwest::Client,
+    ids: HashMap<String, String>,
+    limiter: TokenBucket,
+}
+
+impl CoinGeckoProvider {
+    /// Provider knowing SOL, USDC and USDT by symbol and mint
+    pub fn new(config: CoinGeckoConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| Error::config(format!("Failed to build CoinGecko client: {}", e)))?;
+        let ids = DEFAULT_IDS
+            .iter()
+            .map(|(symbol, id)| (symbol.to_string(), id.to_string()))
+            .collect();
+        let limiter = TokenBucket::per_minute(config.calls_per_minute);
+        Ok(Self {
+            config,
+            client,
+            ids,
+            limiter,
+        })
+    }
+
+    /// Quote `symbol` (or a mint address) as the coin `id`
+    pub fn with_id(mut self, symbol: impl Into<String>, id: impl Into<String>) -> Self {
+        self.ids.insert(symbol.into(), id.into());
+        self
+    }
+
+    /// CoinGecko coin id of `symbol`, if known
+    pub fn coin_id(&self, symbol: &str) -> Option<&str> {
+        self.ids.get(symbol).map(String::as_str)
+    }
+
+    /// Quote the known `symbols` with one request
+    ///
+    /// Fails with [`Error::RateLimitExceeded`] without a request once the
+    /// configured calls per minute are used.
+    pub async fn quote(&self, symbols: &[String]) -> Result<HashMap<String, PricePoint>> {
+        let mut wanted: HashMap<&str, Vec<&String>> = HashMap::new();
+        for symbol in symbols {
+            if let Some(id) = self.coin_id(symbol) {
+                wanted.entry(id).or_default().push(symbol);
+            }
+        }
+        if wanted.is_empty() {
+            return Ok(HashMap::new());
+        }
+        self.limiter.check_and_record(Instant::now())?;
+
+        let mut ids: Vec<&str> = wanted.keys().copied().collect();
+        ids.sort_unstable();
+        let url = format!(
+            "{}{}",
+            self.config.base_url.trim_end_matches('/'),
+            SIMPLE_PRICE_PATH
+        );
+        let mut request = self.client.get(url).query(&[
+            ("ids", ids.join(",").as_str()),
+            ("vs_currencies", "usd"),
+            ("include_24hr_change", "true"),
+            ("include_last_updated_at", "true"),
+        ]);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        let response = request.send().await.map_err(request_error)?;
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(Error::RateLimitExceeded(
+                "CoinGecko throttled the request".to_string(),
+            ));
+        }
+        if !status.is_success() {
+            let reason = response.text().await.unwrap_or_default();
+            return Err(Error::Network(format!(
+                "CoinGecko returned {}: {}",
+                status,
+                reason.trim()
+            )));
+        }
+        let quotes: HashMap<String, SimplePrice> = response.json().await.map_err(request_error)?;
+
+        let now = Utc::now();
+        let mut prices = HashMap::new();
+        for (id, quote) in quotes {
+            let (Some(price), Some(symbols)) = (quote.usd, wanted.get(id.as_str())) else {
+                continue;
+            };
+            let point = PricePoint {
+                price,
+                change_24h: quote.usd_24h_change,
+                timestamp: quote
+                    .last_updated_at
+                    .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+                    .unwrap_or(now),
+            };
+            for symbol in symbols {
+                prices.insert((*symbol).clone(), point);
+            }
+        }
+        Ok(prices)
+    }
+}
+
+impl MarketDataProvider for CoinGeckoProvider {
+    fn name(&self) -> &str {
+        "coingecko"
+    }
+
+    fn get_prices(
+        &self,
+        symbols: &[String],
+    ) -> impl Future<Output = Result<HashMap<String, PricePoint>>> + Send {
+        self.quote(symbols)
+    }
+}
+
+fn request_error(error: reqwest::Error) -> Error {
+    if error.is_timeout() {
+        Error::Timeout(format!("CoinGecko did not answer: {}", error))
+    } else {
+        Error::Network(format!("CoinGecko request failed: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn provider(server: &MockServer, calls_per_minute: u32) -> Result<CoinGeckoProvider> {
+        let config = CoinGeckoConfig {
+            calls_per_minute,
+            ..CoinGeckoConfig::default()
+        };
+        CoinGeckoProvider::new(config.with_base_url(server.uri()))
+    }
+
+    #[tokio::test]
+    async fn test_simple_price_quotes_symbols_and_mints() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(SIMPLE_PRICE_PATH))
+            .and(query_param("ids", "solana,usd-coin"))
+            .and(query_param("vs_currencies", "usd"))
+            .and(query_param("include_24hr_change", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "solana": { "usd": 151.5, "usd_24h_change": -2.25, "last_updated_at": 1_700_000_000 },
+                "usd-coin": { "usd": 1.0 },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string();
+        let symbols = vec!["SOL".to_string(), usdc.clone(), "BONK".to_string()];
+        let prices = provider(&server, FREE_TIER_CALLS_PER_MINUTE)?
+            .quote(&symbols)
+            .await?;
+
+        assert_eq!(prices.len(), 2);
+        let Some(sol) = prices.get("SOL") else {
+            panic!("SOL was not quoted");
+        };
+        assert_eq!(sol.price, 151.5);
+        assert_eq!(sol.change_24h, Some(-2.25));
+        assert_eq!(sol.timestamp.timestamp(), 1_700_000_000);
+        assert_eq!(prices.get(&usdc).map(|point| point.price), Some(1.0));
+        assert_eq!(prices.get(&usdc).and_then(|point| point.change_24h), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_key_is_sent_in_its_header() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(SIMPLE_PRICE_PATH))
+            .and(header(API_KEY_HEADER, "demo-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "solana": { "usd": 150.0 },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = CoinGeckoConfig::default()
+            .with_base_url(server.uri())
+            .with_api_key("demo-key");
+        let prices = CoinGeckoProvider::new(config)?
+            .quote(&["SOL".to_string()])
+            .await?;
+        assert_eq!(prices.get("SOL").map(|point| point.price), Some(150.0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_calls_past_the_rate_limit_are_refused_locally() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(SIMPLE_PRICE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "solana": { "usd": 150.0 },
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let provider = provider(&server, 2)?;
+        let symbols = vec!["SOL".to_string()];
+        provider.quote(&symbols).await?;
+        provider.quote(&symbols).await?;
+        let refused = provider.quote(&symbols).await;
+        assert!(matches!(refused, Err(Error::RateLimitExceeded(_))));
+
+        // Unknown symbols need no request and use no allowance
+        assert!(provider.quote(&["BONK".to_string()]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_throttling_and_server_errors_fail_the_call() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(SIMPLE_PRICE_PATH))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(SIMPLE_PRICE_PATH))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let provider = provider(&server, FREE_TIER_CALLS_PER_MINUTE)?;
+        let symbols = vec!["SOL".to_string()];
+        let throttled = provider.quote(&symbols).await;
+        assert!(matches!(throttled, Err(Error::RateLimitExceeded(_))));
+        let failed = provider.quote(&symbols).await;
+        assert!(matches!(failed, Err(Error::Network(_))));
+        Ok(())
+    }
+}
@@ -22,13 +22,36 @@
 //! // Load configuration from file
 //! let config = WalletConfig::from_file("config.yaml")?;
 //! ```
+//!
+//! # Keys and environment overrides
+//!
+//! Single settings are addressed by dotted keys following the serialized
+//! layout, e.g. `rpc.timeout_seconds` or `rpc.endpoints.0.url`; see
+//! [`WalletConfig::get_key`] and [`WalletConfig::set_key`]. Environment
+//! variables named [`ENV_OVERRIDE_PREFIX`] followed by the key's sections
+//! joined with `__` override the file:
+//! `AGENT_WALLET__AGENT__LIMITS__DAILY_SPEND_LIMIT_SOL=2.5` sets
+//! `agent.limits.daily_spend_limit_sol`.
+//!
+//! RPC auth tokens may name environment variables as `${VAR}`, so secrets
+//! stay out of configuration files; they are resolved when the RPC client
+//! connects.
 
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use crate::encryption::KdfAlgorithm;
 use crate::error::{Error, Result};
-use crate::types::PermissionLevel;
+use crate::policy::AddressPolicy;
+use crate::protocols::ProtocolSettings;
+use crate::read_cache::{DEFAULT_ACCOUNT_CACHE_TTL, DEFAULT_BLOCKHASH_CACHE_SLOTS};
+use crate::rotation::KeyRotationPolicy;
+use crate::types::{pubkey_map, PermissionLevel, TokenLimit, UnlistedTokenPolicy};
 
 /// Main configuration structure for the wallet
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +65,23 @@ pub struct WalletConfig {
     pub rpc: RpcSettings,
     /// Monitoring and observability configuration
     pub monitoring: MonitoringSettings,
+    /// Rehearse actions without broadcasting them, see [`RunMode::DryRun`]
+    pub dry_run: bool,
+}
+
+/// Whether a wallet broadcasts the actions it executes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    /// Sign and broadcast transactions
+    #[default]
+    Live,
+    /// Build, validate, sign and simulate transactions but never send them
+    ///
+    /// Spends are deducted from an in-memory shadow budget, so a dry run
+    /// exhausts its daily limits like a live run would without touching the
+    /// wallet's persisted budget.
+    DryRun,
 }
 
 /// Wallet-specific settings
@@ -52,10 +92,14 @@ pub struct WalletSettings {
     pub encryption: EncryptionSettings,
     /// Storage configuration
     pub storage: StorageSettings,
+    /// When the wallet key should be replaced
+    pub key_rotation: KeyRotationPolicy,
+    /// Re-encrypt the key in memory after this many seconds without signing
+    pub key_idle_lock_seconds: Option<u64>,
 }
 
 /// Encryption algorithm configuration
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum EncryptionAlgorithm {
     /// AES-256-GCM encryption (default)
@@ -64,14 +108,30 @@ pub enum EncryptionAlgorithm {
     Ring,
 }
 
+impl From<EncryptionAlgorithm> for crate::encryption::EncryptionAlgorithm {
+    fn from(algorithm: EncryptionAlgorithm) -> Self {
+        match algorithm {
+            EncryptionAlgorithm::Aes256Gcm => Self::Aes256Gcm,
+            EncryptionAlgorithm::Ring => Self::Ring,
+        }
+    }
+}
+
 /// Encryption settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct EncryptionSettings {
     /// Encryption algorithm to use
     pub algorithm: EncryptionAlgorithm,
-    /// Number of PBKDF2 iterations for key derivation
-    pub kdf_iterations: u32,
+    /// Key derivation function for new wallet files (Argon2id by default)
+    pub kdf: KdfAlgorithm,
+    /// Deprecated and ignored: PBKDF2 iterations are set in `kdf`
+    ///
+    /// Still accepted so older configuration files load, with a warning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf_iterations: Option<u32>,
+    /// Also bind new wallet files to a secret in the OS keychain
+    pub use_os_keystore: bool,
 }
 
 /// Storage settings
@@ -84,6 +144,45 @@ pub struct StorageSettings {
     pub backup_path: PathBuf,
     /// Maximum number of wallet versions to keep
     pub max_versions: usize,
+    /// Backend wallets are stored in
+    pub backend: StorageBackend,
+}
+
+/// Environment variable the env backend reads by default
+pub const DEFAULT_WALLET_ENV_VAR: &str = "AGENT_WALLET_BLOB";
+
+/// Prefix of environment variables overriding single configuration keys
+pub const ENV_OVERRIDE_PREFIX: &str = "AGENT_WALLET__";
+
+/// Where wallets are stored
+///
+/// See [`crate::store`] for how each backend behaves.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// Encrypted wallet files under `path`, with backups under `backup_path`
+    #[default]
+    File,
+    /// Wallets held in process memory, lost when the process exits
+    Memory {
+        /// Stores opened with the same namespace share their wallets
+        #[serde(default)]
+        namespace: String,
+    },
+    /// One read-only wallet, a base64-encoded wallet file from the
+    /// environment or a mounted secret file
+    Env {
+        /// Environment variable holding the blob
+        #[serde(default = "default_wallet_env_var")]
+        variable: String,
+        /// Secret file holding the blob, read when the variable is unset
+        #[serde(default)]
+        secret_file: Option<PathBuf>,
+    },
+}
+
+fn default_wallet_env_var() -> String {
+    DEFAULT_WALLET_ENV_VAR.to_string()
 }
 
 /// Agent-specific settings
@@ -96,6 +195,90 @@ pub struct AgentSettings {
     pub limits: AgentLimits,
     /// Default permission level for new agents
     pub default_permission_level: PermissionLevel,
+    /// Least permission an agent needs to have a sponsor pay its fees
+    pub fee_payer_permission: PermissionLevel,
+    /// How the agent context is refreshed between decisions
+    pub context: ContextSettings,
+    /// Destinations transactions may send funds to
+    pub address_policy: AddressPolicy,
+    /// Protocols agents may interact with; empty allows none
+    pub allowed_protocols: Vec<ProtocolSettings>,
+    /// Human sign-off for large actions (off when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalPolicy>,
+    /// Address book restricting transfer recipients (off when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address_book: Option<AddressBookSettings>,
+}
+
+/// Address book whose whitelisted contacts are the only transfer recipients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBookSettings {
+    /// Address book file
+    pub path: PathBuf,
+    /// Base58 key the book must be signed with
+    ///
+    /// When set, the book must also carry an integrity policy; a book
+    /// without one fails verification.
+    #[serde(default)]
+    pub trusted_signer: Option<String>,
+}
+
+/// When an agent's action waits for a human to approve it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApprovalPolicy {
+    /// Actions worth less than this many SOL run without approval
+    pub auto_approve_below_sol: f64,
+    /// Webhooks asked to approve larger actions
+    pub approvers: Vec<ApproverEndpoint>,
+    /// Seconds a request waits for an answer
+    pub timeout_secs: u64,
+    /// What becomes of a request nobody answered in time
+    pub on_timeout: ApprovalTimeoutAction,
+}
+
+/// Webhook receiving approval requests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApproverEndpoint {
+    /// URL each request is POSTed to
+    pub url: String,
+    /// Shared secret signing each body with HMAC-SHA256
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// Resolution of an approval request that expired unanswered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalTimeoutAction {
+    /// Drop the action
+    #[default]
+    Reject,
+    /// Execute the action anyway
+    Approve,
+}
+
+/// Agent context refresh settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextSettings {
+    /// Symbols or mint addresses whose prices feed the context
+    pub watchlist: Vec<String>,
+    /// Seconds a fetched SOL balance is reused
+    pub balance_ttl_seconds: u64,
+    /// Seconds fetched token balances are reused
+    pub token_balances_ttl_seconds: u64,
+    /// Seconds fetched transaction history is reused
+    pub history_ttl_seconds: u64,
+    /// Seconds fetched prices are reused
+    pub price_ttl_seconds: u64,
+    /// Age in seconds past which a quote is dropped instead of used
+    pub max_price_age_seconds: u64,
+    /// Number of recent transactions kept in the context
+    pub history_limit: usize,
+    /// Number of recent prices per symbol used for market conditions
+    pub price_window: usize,
 }
 
 /// Sandbox execution settings
@@ -124,6 +307,37 @@ pub struct AgentLimits {
     pub max_transaction_size: usize,
     /// Maximum number of signatures per transaction
     pub max_signatures: u8,
+    /// SOL kept in the wallet for fees; outgoing transfers may not spend it
+    pub min_sol_reserve: f64,
+    /// Limits of tokens with a budget of their own, by mint
+    #[serde(with = "pubkey_map")]
+    pub token_limits: HashMap<Pubkey, TokenLimitSettings>,
+    /// How transfers of tokens without an entry in `token_limits` are treated
+    pub unlisted_tokens: UnlistedTokenPolicy,
+}
+
+impl AgentLimits {
+    /// Token limits with the whole daily amount available
+    pub fn token_limits(&self) -> HashMap<Pubkey, TokenLimit> {
+        self.token_limits
+            .iter()
+            .map(|(mint, limit)| {
+                (
+                    *mint,
+                    TokenLimit::new(limit.daily_limit, limit.per_tx_limit),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Limits of one token, in its base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenLimitSettings {
+    /// Amount that may leave the wallet per day
+    pub daily_limit: u64,
+    /// Amount that may leave the wallet in one transaction
+    pub per_tx_limit: u64,
 }
 
 /// RPC client settings
@@ -140,17 +354,72 @@ pub struct RpcSettings {
     pub use_websocket: bool,
     /// Websocket endpoint (if different from HTTP)
     pub websocket_url: Option<String>,
+    /// Cache hot reads for a moment (off when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_cache: Option<ReadCacheSettings>,
+}
+
+/// Short-lived cache of balances, accounts and the blockhash
+///
+/// See [`crate::read_cache`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadCacheSettings {
+    /// Slots the latest blockhash is reused for
+    pub blockhash_slots: u64,
+    /// How long balances and account data are reused, in milliseconds
+    pub account_ttl_ms: u64,
+}
+
+impl Default for ReadCacheSettings {
+    fn default() -> Self {
+        Self {
+            blockhash_slots: DEFAULT_BLOCKHASH_CACHE_SLOTS,
+            account_ttl_ms: DEFAULT_ACCOUNT_CACHE_TTL.as_millis() as u64,
+        }
+    }
 }
 
 /// RPC endpoint with priority
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Debug and Display output never include the auth token or credentials
+/// embedded in the URL.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RpcEndpoint {
     /// RPC URL
     pub url: String,
     /// Priority (lower number = higher priority)
     pub priority: u32,
-    /// Optional authentication token
+    /// Optional authentication token; `${VAR}` is replaced by the variable
     pub auth_token: Option<String>,
+    /// How the token is sent, inferred from the URL when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_style: Option<AuthStyle>,
+}
+
+/// How an RPC endpoint's auth token is sent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStyle {
+    /// As the query parameter with this name, e.g. `api-key` for Helius
+    QueryParam(String),
+    /// In the header with this name, e.g. `x-api-key`; an `Authorization`
+    /// header carries `Bearer <token>`
+    Header(String),
+}
+
+impl AuthStyle {
+    /// Style the provider behind `url` expects
+    ///
+    /// Helius takes an `api-key` query parameter; everything else gets a
+    /// bearer token.
+    pub fn for_url(url: &str) -> Self {
+        if crate::rpc::log_safe_url(url).contains("helius") {
+            Self::QueryParam("api-key".to_string())
+        } else {
+            Self::Header("Authorization".to_string())
+        }
+    }
 }
 
 /// Solana commitment level
@@ -173,6 +442,10 @@ pub struct MonitoringSettings {
     pub metrics: MetricsSettings,
     /// Logging configuration
     pub logging: LoggingSettings,
+    /// Audit trail of executed actions
+    pub audit: AuditSettings,
+    /// Webhook notifications of agent events
+    pub webhooks: WebhookSettings,
 }
 
 /// Metrics collection settings
@@ -199,6 +472,62 @@ pub struct LoggingSettings {
     pub log_to_file: bool,
     /// Log file path (if logging to file)
     pub log_file: Option<PathBuf>,
+    /// How often the log file is rotated
+    pub rotation: LogRotation,
+}
+
+/// Audit trail settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditSettings {
+    /// JSON-lines file receiving audit records; auditing is off if unset
+    pub path: Option<PathBuf>,
+    /// Size at which the audit file is rotated
+    pub max_file_bytes: u64,
+    /// Number of rotated audit files to keep
+    pub max_files: usize,
+}
+
+/// Webhook notification settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookSettings {
+    /// URLs every notification is POSTed to; notifications are off if empty
+    pub urls: Vec<String>,
+    /// Shared secret signing each body with HMAC-SHA256
+    pub secret: Option<String>,
+    /// Which events are sent
+    pub events: WebhookEvents,
+    /// SOL balance below which an agent reports `balance_below_threshold`
+    pub balance_threshold_sol: Option<f64>,
+    /// Delivery attempts per URL
+    pub max_attempts: u32,
+    /// Backoff before the second attempt in milliseconds, doubling after
+    pub backoff_base_ms: u64,
+    /// Timeout of a single delivery attempt in seconds
+    pub timeout_seconds: u64,
+}
+
+/// Per-event switches of webhook notifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookEvents {
+    /// An agent's action was executed or rehearsed
+    pub decision_executed: bool,
+    /// An action was refused by a spending limit
+    pub spending_limit_hit: bool,
+    /// An agent failed to decide or its action failed
+    pub agent_errored: bool,
+    /// A sent transaction was not confirmed in time
+    pub confirmation_timeout: bool,
+    /// The wallet balance fell below `balance_threshold_sol`
+    pub balance_below_threshold: bool,
+    /// The wallet fell below its SOL reserve and needs funding
+    pub top_up_requested: bool,
+    /// The wallet key crossed a threshold of its rotation policy
+    pub key_rotation_needed: bool,
+    /// An action is waiting for a human to approve it
+    pub approval_requested: bool,
 }
 
 /// Log level
@@ -227,6 +556,19 @@ pub enum LogFormat {
     Json,
 }
 
+/// Log file rotation schedule
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Never rotate
+    Never,
+    /// Start a new file every hour
+    Hourly,
+    /// Start a new file every day (default)
+    #[default]
+    Daily,
+}
+
 // Default implementations
 
 impl Default for WalletConfig {
@@ -236,6 +578,7 @@ impl Default for WalletConfig {
             agent: AgentSettings::default(),
             rpc: RpcSettings::default(),
             monitoring: MonitoringSettings::default(),
+            dry_run: false,
         }
     }
 }
@@ -245,6 +588,8 @@ impl Default for WalletSettings {
         Self {
             encryption: EncryptionSettings::default(),
             storage: StorageSettings::default(),
+            key_rotation: KeyRotationPolicy::default(),
+            key_idle_lock_seconds: None,
         }
     }
 }
@@ -253,7 +598,9 @@ impl Default for EncryptionSettings {
     fn default() -> Self {
         Self {
             algorithm: EncryptionAlgorithm::Aes256Gcm,
-            kdf_iterations: 100_000,
+            kdf: KdfAlgorithm::default(),
+            kdf_iterations: None,
+            use_os_keystore: false,
         }
     }
 }
@@ -266,6 +613,7 @@ impl Default for StorageSettings {
             path: home_dir.join(".agent-wallet/wallets"),
             backup_path: home_dir.join(".agent-wallet/backups"),
             max_versions: 10,
+            backend: StorageBackend::File,
         }
     }
 }
@@ -276,6 +624,38 @@ impl Default for AgentSettings {
             sandbox: SandboxSettings::default(),
             limits: AgentLimits::default(),
             default_permission_level: PermissionLevel::Basic,
+            fee_payer_permission: PermissionLevel::Administrator,
+            context: ContextSettings::default(),
+            address_policy: AddressPolicy::default(),
+            allowed_protocols: Vec::new(),
+            approval: None,
+            address_book: None,
+        }
+    }
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self {
+            auto_approve_below_sol: 1.0,
+            approvers: Vec::new(),
+            timeout_secs: 15 * 60,
+            on_timeout: ApprovalTimeoutAction::Reject,
+        }
+    }
+}
+
+impl Default for ContextSettings {
+    fn default() -> Self {
+        Self {
+            watchlist: Vec::new(),
+            balance_ttl_seconds: 10,
+            token_balances_ttl_seconds: 30,
+            history_ttl_seconds: 30,
+            price_ttl_seconds: 15,
+            max_price_age_seconds: 300,
+            history_limit: 20,
+            price_window: 30,
         }
     }
 }
@@ -298,6 +678,9 @@ impl Default for AgentLimits {
             max_transactions_per_minute: 10,
             max_transaction_size: 1232, // Solana transaction size limit
             max_signatures: 20,         // Solana max signatures per transaction
+            min_sol_reserve: 0.0,
+            token_limits: HashMap::new(),
+            unlisted_tokens: UnlistedTokenPolicy::Deny,
         }
     }
 }
@@ -310,6 +693,7 @@ impl Default for RpcSettings {
             commitment: CommitmentLevel::Confirmed,
             use_websocket: true,
             websocket_url: None,
+            read_cache: None,
         }
     }
 }
@@ -319,6 +703,37 @@ impl Default for MonitoringSettings {
         Self {
             metrics: MetricsSettings::default(),
             logging: LoggingSettings::default(),
+            audit: AuditSettings::default(),
+            webhooks: WebhookSettings::default(),
+        }
+    }
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: None,
+            events: WebhookEvents::default(),
+            balance_threshold_sol: None,
+            max_attempts: 3,
+            backoff_base_ms: 500,
+            timeout_seconds: 10,
+        }
+    }
+}
+
+impl Default for WebhookEvents {
+    fn default() -> Self {
+        Self {
+            decision_executed: true,
+            spending_limit_hit: true,
+            agent_errored: true,
+            confirmation_timeout: true,
+            balance_below_threshold: true,
+            top_up_requested: true,
+            key_rotation_needed: true,
+            approval_requested: true,
         }
     }
 }
@@ -333,6 +748,16 @@ impl Default for MetricsSettings {
     }
 }
 
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
 impl Default for LoggingSettings {
     fn default() -> Self {
         Self {
@@ -340,6 +765,7 @@ impl Default for LoggingSettings {
             format: LogFormat::Text,
             log_to_file: false,
             log_file: None,
+            rotation: LogRotation::Daily,
         }
     }
 }
@@ -357,13 +783,24 @@ impl WalletConfig {
         WalletConfigBuilder::new()
     }
 
+    /// Whether wallets opened with this configuration broadcast actions
+    pub fn run_mode(&self) -> RunMode {
+        if self.dry_run {
+            RunMode::DryRun
+        } else {
+            RunMode::Live
+        }
+    }
+
     /// Load configuration from a YAML file
     pub fn from_yaml_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = std::fs::read_to_string(path.as_ref())
             .map_err(|e| Error::config(format!("Failed to read config file: {}", e)))?;
 
-        serde_yaml::from_str(&content)
-            .map_err(|e| Error::config(format!("Failed to parse YAML config: {}", e)))
+        let config: Self = serde_yaml::from_str(&content)
+            .map_err(|e| Error::config(format!("Failed to parse YAML config: {}", e)))?;
+        config.warn_deprecated();
+        Ok(config)
     }
 
     /// Load configuration from a JSON file
@@ -371,8 +808,22 @@ impl WalletConfig {
         let content = std::fs::read_to_string(path.as_ref())
             .map_err(|e| Error::config(format!("Failed to read config file: {}", e)))?;
 
-        serde_json::from_str(&content)
-            .map_err(|e| Error::config(format!("Failed to parse JSON config: {}", e)))
+        let config: Self = serde_json::from_str(&content)
+            .map_err(|e| Error::config(format!("Failed to parse JSON config: {}", e)))?;
+        config.warn_deprecated();
+        Ok(config)
+    }
+
+    /// Warn about settings that are still accepted but have no effect
+    fn warn_deprecated(&self) {
+        if let Some(iterations) = self.wallet.encryption.kdf_iterations {
+            tracing::warn!(
+                "wallet.encryption.kdf_iterations ({}) is deprecated and ignored; \
+                 set wallet.encryption.kdf to {{ name: pbkdf2, iterations: {} }} to keep PBKDF2",
+                iterations,
+                iterations
+            );
+        }
     }
 
     /// Load configuration from a file (auto-detects format by extension)
@@ -412,6 +863,99 @@ impl WalletConfig {
             .map_err(|e| Error::config(format!("Failed to write config file: {}", e)))
     }
 
+    /// Load the effective configuration: `path` if it exists, else defaults,
+    /// with environment overrides applied on top
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config = if path.as_ref().exists() {
+            Self::from_file(path)?
+        } else {
+            Self::default()
+        };
+        config.with_env_overrides()
+    }
+
+    /// Apply the `AGENT_WALLET__*` variables of the process environment
+    pub fn with_env_overrides(self) -> Result<Self> {
+        let vars = std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        });
+        self.with_overrides(vars)
+    }
+
+    /// Apply overrides given as `(variable, value)` pairs
+    ///
+    /// Variables without [`ENV_OVERRIDE_PREFIX`] are ignored; the rest name
+    /// a key with `__` between sections, in any case.
+    pub fn with_overrides<I>(mut self, vars: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, value) in vars {
+            let Some(sections) = name.strip_prefix(ENV_OVERRIDE_PREFIX) else {
+                continue;
+            };
+            let key = sections.to_lowercase().replace("__", ".");
+            self.set_key(&key, &value).map_err(|e| match e {
+                Error::Config(message) => Error::config(format!("{}: {}", name, message)),
+                other => other,
+            })?;
+        }
+        Ok(self)
+    }
+
+    /// Value of a dotted key such as `rpc.timeout_seconds`
+    ///
+    /// Sections may index lists, as in `rpc.endpoints.0.url`.
+    pub fn get_key(&self, key: &str) -> Result<Value> {
+        let root = self.to_value()?;
+        let mut value = &root;
+        for section in key_sections(key)? {
+            value = child(value, section)
+                .ok_or_else(|| Error::config(format!("Unknown configuration key '{}'", key)))?;
+        }
+        Ok(value.clone())
+    }
+
+    /// Set a dotted key from its textual value
+    ///
+    /// The value must fit the key: numeric keys only take numbers, flags
+    /// only `true` or `false`, and enum keys only their variants. The
+    /// configuration is unchanged when the write is refused.
+    pub fn set_key(&mut self, key: &str, value: &str) -> Result<()> {
+        let unknown = || Error::config(format!("Unknown configuration key '{}'", key));
+        let mut root = self.to_value()?;
+        let mut slot = &mut root;
+        let sections = key_sections(key)?;
+        let last = sections.len() - 1;
+        for (index, section) in sections.into_iter().enumerate() {
+            // The last section may add an entry to a map such as
+            // `agent.limits.token_limits`
+            if index == last {
+                if let Value::Mapping(mapping) = slot {
+                    if !mapping.contains_key(section) {
+                        mapping.insert(Value::from(section), Value::Null);
+                    }
+                }
+            }
+            slot = child_mut(slot, section).ok_or_else(unknown)?;
+        }
+        *slot = typed_value(slot, key, value)?;
+
+        let updated: WalletConfig = serde_yaml::from_value(root)
+            .map_err(|e| Error::config(format!("Invalid value '{}' for {}: {}", value, key, e)))?;
+        // A field the configuration does not have is dropped when it is read
+        if updated.get_key(key).is_err() {
+            return Err(unknown());
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    fn to_value(&self) -> Result<Value> {
+        serde_yaml::to_value(self)
+            .map_err(|e| Error::config(format!("Failed to serialize config: {}", e)))
+    }
+
     /// Get the request timeout as Duration
     pub fn request_timeout(&self) -> Duration {
         Duration::from_secs(self.rpc.timeout_seconds)
@@ -445,6 +989,7 @@ impl RpcEndpoint {
             url: url.into(),
             priority: 1,
             auth_token: None,
+            auth_style: None,
         }
     }
 
@@ -454,6 +999,7 @@ impl RpcEndpoint {
             url: url.into(),
             priority,
             auth_token: None,
+            auth_style: None,
         }
     }
 
@@ -462,6 +1008,74 @@ impl RpcEndpoint {
         self.auth_token = Some(token.into());
         self
     }
+
+    /// Send the authentication token as `style` asks
+    pub fn with_auth_style(mut self, style: AuthStyle) -> Self {
+        self.auth_style = Some(style);
+        self
+    }
+
+    /// How the authentication token is sent
+    pub fn auth_style(&self) -> AuthStyle {
+        self.auth_style
+            .clone()
+            .unwrap_or_else(|| AuthStyle::for_url(&self.url))
+    }
+
+    /// Authentication token with `${VAR}` references replaced from the
+    /// environment
+    ///
+    /// Fails with `Config` naming the variable, never the token, if a
+    /// referenced variable is unset.
+    pub fn resolve_auth_token(&self) -> Result<Option<String>> {
+        self.auth_token
+            .as_deref()
+            .map(|token| interpolate_env(token, |name| std::env::var(name).ok()))
+            .transpose()
+    }
+}
+
+impl fmt::Debug for RpcEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpcEndpoint")
+            .field("url", &crate::rpc::log_safe_url(&self.url))
+            .field("priority", &self.priority)
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "[redacted]"),
+            )
+            .field("auth_style", &self.auth_style)
+            .finish()
+    }
+}
+
+impl fmt::Display for RpcEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&crate::rpc::log_safe_url(&self.url))
+    }
+}
+
+/// `value` with every `${NAME}` replaced by `lookup(NAME)`
+fn interpolate_env(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            return Err(Error::config("Unterminated ${ in RPC auth token"));
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let variable = lookup(name).ok_or_else(|| {
+            Error::config(format!(
+                "RPC auth token refers to unset environment variable {}",
+                name
+            ))
+        })?;
+        resolved.push_str(&rest[..start]);
+        resolved.push_str(&variable);
+        rest = &rest[start + 3 + len..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
 }
 
 impl CommitmentLevel {
@@ -519,15 +1133,33 @@ impl WalletConfigBuilder {
         self
     }
 
+    /// Set the storage backend
+    pub fn with_storage_backend(mut self, backend: StorageBackend) -> Self {
+        self.config.wallet.storage.backend = backend;
+        self
+    }
+
     /// Set the encryption algorithm
     pub fn with_encryption_algorithm(mut self, algorithm: EncryptionAlgorithm) -> Self {
         self.config.wallet.encryption.algorithm = algorithm;
         self
     }
 
-    /// Set the KDF iterations
+    /// Derive keys of new wallet files with `kdf`
+    pub fn with_kdf(mut self, kdf: KdfAlgorithm) -> Self {
+        self.config.wallet.encryption.kdf = kdf;
+        self
+    }
+
+    /// Derive keys of new wallet files with PBKDF2 over this many iterations
     pub fn with_kdf_iterations(mut self, iterations: u32) -> Self {
-        self.config.wallet.encryption.kdf_iterations = iterations;
+        self.config.wallet.encryption.kdf = KdfAlgorithm::Pbkdf2 { iterations };
+        self
+    }
+
+    /// Set when the wallet key should be rotated
+    pub fn with_key_rotation(mut self, policy: KeyRotationPolicy) -> Self {
+        self.config.wallet.key_rotation = policy;
         self
     }
 
@@ -549,6 +1181,12 @@ impl WalletConfigBuilder {
         self
     }
 
+    /// Allow agents to interact with a protocol
+    pub fn with_allowed_protocol(mut self, protocol: ProtocolSettings) -> Self {
+        self.config.agent.allowed_protocols.push(protocol);
+        self
+    }
+
     /// Set maximum transactions per minute
     pub fn with_max_transactions_per_minute(mut self, max: u32) -> Self {
         self.config.agent.limits.max_transactions_per_minute = max;
@@ -591,6 +1229,12 @@ impl WalletConfigBuilder {
         self
     }
 
+    /// Rehearse actions without broadcasting them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
     /// Build the final configuration
     pub fn build(self) -> WalletConfig {
         self.config
@@ -603,6 +1247,68 @@ impl Default for WalletConfigBuilder {
     }
 }
 
+/// Sections of a dotted key
+fn key_sections(key: &str) -> Result<Vec<&str>> {
+    let sections: Vec<&str> = key.split('.').collect();
+    if sections.iter().any(|section| section.is_empty()) {
+        return Err(Error::config(format!(
+            "Invalid configuration key '{}'",
+            key
+        )));
+    }
+    Ok(sections)
+}
+
+/// Field or list element `section` of a serialized value
+fn child<'a>(value: &'a Value, section: &str) -> Option<&'a Value> {
+    match value {
+        Value::Mapping(mapping) => mapping.get(section),
+        Value::Sequence(items) => items.get(section.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+/// Mutable field or list element `section` of a serialized value
+fn child_mut<'a>(value: &'a mut Value, section: &str) -> Option<&'a mut Value> {
+    match value {
+        Value::Mapping(mapping) => mapping.get_mut(section),
+        Value::Sequence(items) => items.get_mut(section.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+/// Parse `text` as a value of the same kind as `current`
+fn typed_value(current: &Value, key: &str, text: &str) -> Result<Value> {
+    let mismatch =
+        |expected: &str| Error::config(format!("{} expects {}, got '{}'", key, expected, text));
+    let parsed = || -> Result<Value> {
+        serde_yaml::from_str(text).map_err(|e| Error::config(format!("{}: {}", key, e)))
+    };
+    match current {
+        // Enum variants serialize as strings too; deserializing the whole
+        // configuration afterwards checks them
+        Value::String(_) => Ok(Value::String(text.to_string())),
+        Value::Bool(_) => text
+            .parse()
+            .map(Value::Bool)
+            .map_err(|_| mismatch("true or false")),
+        Value::Number(_) => match parsed()? {
+            number @ Value::Number(_) => Ok(number),
+            _ => Err(mismatch("a number")),
+        },
+        Value::Sequence(_) => match parsed()? {
+            list @ Value::Sequence(_) => Ok(list),
+            _ => Err(mismatch("a list")),
+        },
+        Value::Mapping(_) => match parsed()? {
+            mapping @ Value::Mapping(_) => Ok(mapping),
+            _ => Err(mismatch("a mapping")),
+        },
+        // Unset optional keys take whatever the text parses as
+        Value::Null | Value::Tagged(_) => parsed(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -616,7 +1322,11 @@ mod tests {
             config.wallet.encryption.algorithm,
             EncryptionAlgorithm::Aes256Gcm
         );
-        assert_eq!(config.wallet.encryption.kdf_iterations, 100_000);
+        assert_eq!(config.wallet.encryption.kdf_iterations, None);
+        assert!(matches!(
+            config.wallet.encryption.kdf,
+            KdfAlgorithm::Argon2id { .. }
+        ));
         assert_eq!(config.agent.limits.daily_spend_limit_sol, 10.0);
         assert_eq!(config.agent.limits.max_transactions_per_minute, 10);
         assert_eq!(config.rpc.timeout_seconds, 30);
@@ -643,6 +1353,45 @@ mod tests {
             .any(|e| e.url == "https://custom.rpc.url"));
     }
 
+    #[test]
+    fn test_rpc_auth_token_interpolation() -> Result<()> {
+        let lookup = |name: &str| (name == "HELIUS_KEY").then(|| "s3cret".to_string());
+        assert_eq!(interpolate_env("${HELIUS_KEY}", lookup)?, "s3cret");
+        assert_eq!(
+            interpolate_env("key-${HELIUS_KEY}-x", lookup)?,
+            "key-s3cret-x"
+        );
+        assert_eq!(interpolate_env("plain", lookup)?, "plain");
+
+        let Err(Error::Config(message)) = interpolate_env("${MISSING_KEY}", lookup) else {
+            panic!("unset variable was accepted");
+        };
+        assert!(message.contains("MISSING_KEY"));
+        assert!(interpolate_env("${HELIUS_KEY", lookup).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rpc_endpoint_auth_parsing_and_redaction() -> Result<()> {
+        let endpoint: RpcEndpoint = serde_json::from_str(
+            r#"{"url": "https://rpc.example.com", "priority": 0,
+                "auth_token": "s3cret", "auth_style": {"header": "x-api-key"}}"#,
+        )?;
+        assert_eq!(
+            endpoint.auth_style(),
+            AuthStyle::Header("x-api-key".to_string())
+        );
+        assert!(!format!("{:?}", endpoint).contains("s3cret"));
+        assert!(!endpoint.to_string().contains("s3cret"));
+
+        let helius = RpcEndpoint::new("https://mainnet.helius-rpc.com");
+        assert_eq!(
+            helius.auth_style(),
+            AuthStyle::QueryParam("api-key".to_string())
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = WalletConfig::default();
@@ -660,6 +1409,23 @@ mod tests {
         assert!(json.contains("\"rpc\""));
     }
 
+    #[test]
+    fn test_deprecated_kdf_iterations_are_ignored() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "wallet:\n  encryption:\n    kdf_iterations: 1000\n")?;
+
+        let config = WalletConfig::from_file(&path)?;
+        assert_eq!(config.wallet.encryption.kdf_iterations, Some(1_000));
+        assert_eq!(config.wallet.encryption.kdf, KdfAlgorithm::default());
+
+        // Configuration written back no longer mentions them
+        let yaml = serde_yaml::to_string(&WalletConfig::default())
+            .map_err(|e| Error::config(e.to_string()))?;
+        assert!(!yaml.contains("kdf_iterations"));
+        Ok(())
+    }
+
     #[test]
     fn test_config_file_io() -> Result<()> {
         let temp_dir = tempdir().unwrap();
@@ -678,6 +1444,183 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_storage_backend_round_trip() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let backends = [
+            StorageBackend::File,
+            StorageBackend::Memory {
+                namespace: "ci".to_string(),
+            },
+            StorageBackend::Env {
+                variable: "CI_WALLET".to_string(),
+                secret_file: Some(PathBuf::from("/run/secrets/wallet")),
+            },
+        ];
+        for backend in backends {
+            let config = WalletConfig::builder()
+                .with_storage_backend(backend.clone())
+                .build();
+            let yaml_path = temp_dir.path().join("config.yaml");
+            config.save_to_yaml_file(&yaml_path)?;
+            assert_eq!(
+                WalletConfig::from_file(&yaml_path)?.wallet.storage.backend,
+                backend
+            );
+            let json_path = temp_dir.path().join("config.json");
+            config.save_to_json_file(&json_path)?;
+            assert_eq!(
+                WalletConfig::from_file(&json_path)?.wallet.storage.backend,
+                backend
+            );
+        }
+
+        // Older configs without a backend keep using files; env fields default
+        let storage: StorageSettings =
+            serde_yaml::from_str("max_versions: 3").map_err(|e| Error::config(e.to_string()))?;
+        assert_eq!(storage.backend, StorageBackend::File);
+        let storage: StorageSettings = serde_yaml::from_str("backend:\n  type: env")
+            .map_err(|e| Error::config(e.to_string()))?;
+        assert_eq!(
+            storage.backend,
+            StorageBackend::Env {
+                variable: DEFAULT_WALLET_ENV_VAR.to_string(),
+                secret_file: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dotted_keys_read_and_write_nested_values() -> Result<()> {
+        let mut config = WalletConfig::default();
+        assert_eq!(config.get_key("rpc.timeout_seconds")?, Value::from(30));
+
+        config.set_key("rpc.timeout_seconds", "45")?;
+        config.set_key("agent.limits.daily_spend_limit_sol", "2.5")?;
+        config.set_key("rpc.endpoints.0.url", "https://rpc.example.com")?;
+        config.set_key("dry_run", "true")?;
+        assert_eq!(config.rpc.timeout_seconds, 45);
+        assert_eq!(config.agent.limits.daily_spend_limit_sol, 2.5);
+        assert_eq!(config.rpc.endpoints[0].url, "https://rpc.example.com");
+        assert!(config.dry_run);
+
+        // Enums take their variants, including inside tagged enums
+        config.set_key("rpc.commitment", "finalized")?;
+        config.set_key("wallet.encryption.algorithm", "ring")?;
+        config.set_key("wallet.storage.backend.type", "memory")?;
+        assert_eq!(config.rpc.commitment, CommitmentLevel::Finalized);
+        assert_eq!(
+            config.wallet.encryption.algorithm,
+            EncryptionAlgorithm::Ring
+        );
+        assert_eq!(
+            config.wallet.storage.backend,
+            StorageBackend::Memory {
+                namespace: String::new()
+            }
+        );
+        assert_eq!(
+            config.get_key("wallet.storage.backend.type")?,
+            Value::from("memory")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_limits_are_keyed_by_mint() -> Result<()> {
+        let mut config = WalletConfig::default();
+        let mint = Pubkey::new_unique();
+        let key = format!("agent.limits.token_limits.{}", mint);
+        config.set_key(&key, "{daily_limit: 5000000, per_tx_limit: 1000000}")?;
+        config.set_key(&format!("{}.per_tx_limit", key), "2000000")?;
+        config.set_key("agent.limits.unlisted_tokens", "convert_to_sol")?;
+        assert_eq!(
+            config.agent.limits.token_limits.get(&mint),
+            Some(&TokenLimitSettings {
+                daily_limit: 5_000_000,
+                per_tx_limit: 2_000_000
+            })
+        );
+        assert_eq!(
+            config.agent.limits.unlisted_tokens,
+            UnlistedTokenPolicy::ConvertToSol
+        );
+        assert_eq!(
+            config.agent.limits.token_limits()[&mint].remaining,
+            5_000_000
+        );
+
+        // Mints are written as base58 and must parse back
+        let yaml = serde_yaml::to_string(&config).map_err(|e| Error::config(e.to_string()))?;
+        assert!(yaml.contains(&mint.to_string()));
+        assert!(config
+            .set_key(
+                "agent.limits.token_limits.not-a-mint",
+                "{daily_limit: 1, per_tx_limit: 1}"
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_refused_writes_leave_config_unchanged() -> Result<()> {
+        let mut config = WalletConfig::default();
+        let refused = [
+            ("rpc.timeout_seconds", "soon"),
+            ("rpc.timeout_seconds", "1.5"),
+            ("dry_run", "yes"),
+            ("rpc.commitment", "eventually"),
+            ("wallet.storage.backend.type", "cloud"),
+            ("rpc.endpoints", "https://one.example.com"),
+            ("rpc.timeout", "5"),
+            ("rpc.endpoints.9.url", "https://nine.example.com"),
+            ("rpc..timeout_seconds", "5"),
+            ("rpc.timeout_seconds.value", "5"),
+        ];
+        for (key, value) in refused {
+            assert!(
+                matches!(config.set_key(key, value), Err(Error::Config(_))),
+                "{} = {} was accepted",
+                key,
+                value
+            );
+        }
+        assert_eq!(config.rpc.timeout_seconds, 30);
+        assert_eq!(config.rpc.commitment, CommitmentLevel::Confirmed);
+        assert!(config.get_key("monitoring.nonexistent").is_err());
+        assert!(config.get_key("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_overrides_name_keys_by_sections() -> Result<()> {
+        let vars = [
+            ("AGENT_WALLET__RPC__TIMEOUT_SECONDS", "60"),
+            ("AGENT_WALLET__AGENT__LIMITS__DAILY_SPEND_LIMIT_SOL", "1.5"),
+            ("AGENT_WALLET_BLOB", "not an override"),
+            ("PATH", "/usr/bin"),
+        ];
+        let config = WalletConfig::default().with_overrides(
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        )?;
+        assert_eq!(config.rpc.timeout_seconds, 60);
+        assert_eq!(config.agent.limits.daily_spend_limit_sol, 1.5);
+
+        let bad = [(
+            "AGENT_WALLET__RPC__COMMITMENT".to_string(),
+            "whenever".to_string(),
+        )];
+        match WalletConfig::default().with_overrides(bad) {
+            Err(Error::Config(message)) => {
+                assert!(message.contains("AGENT_WALLET__RPC__COMMITMENT"))
+            }
+            other => return Err(Error::config(format!("Unexpected {:?}", other))),
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_rpc_endpoint_priority() {
         let mut config = WalletConfig::default();
@@ -0,0 +1,819 @@
+//! Incremental agent context refresh
+//!
+//! The [`ContextBuilder`] fills the network-backed fields of an
+//! [`AgentContext`]: the SOL balance, token balances discovered through the
+//! [`TokenManager`], recent transactions, and prices for a watchlist from a
+//! [`PriceOracle`](crate::oracle::PriceOracle) and any number of
+//! [`MarketDataProvider`](crate::market::MarketDataProvider)s, with market
+//! conditions derived from the recent prices. Each field has its own TTL; a
+//! refresh only refetches fields older than theirs, so an agent deciding
+//! every few seconds does not query the RPC node on every tick.
+//!
+//! When several sources quote a symbol, the freshest quote wins. A source
+//! that fails only loses its own quotes, and quotes older than the maximum
+//! price age are dropped and listed in `AgentContext::stale_price_feeds`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use agent_wallet_core::context::ContextBuilder;
+//! use agent_wallet_core::oracle::DynPriceOracle;
+//! use agent_wallet_core::{AgentContext, DynRpcProvider, TokenManager};
+//! use solana_sdk::commitment_config::CommitmentConfig;
+//! use tokio::sync::RwLock;
+//!
+//! # async fn example(
+//! #     rpc: Arc<dyn DynRpcProvider>,
+//! #     oracle: Arc<dyn DynPriceOracle>,
+//! #     context: AgentContext,
+//! # ) -> agent_wallet_core::Result<()> {
+//! let tokens = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+//! let builder = ContextBuilder::new(rpc, Arc::new(RwLock::new(tokens)))
+//!     .with_price_oracle(oracle, vec!["SOL".to_string()]);
+//!
+//! // Only fields past their TTL are fetched
+//! let context = builder.build(&context).await?;
+//! println!(
+//!     "SOL at {:?}, trend {:?}",
+//!     context.price_feeds.get("SOL"),
+//!     context.market_conditions.trend
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::TransactionConfirmationStatus;
+use tokio::sync::{Mutex, RwLock};
+use tracing::warn;
+
+use crate::config::ContextSettings;
+use crate::error::Result;
+use crate::market::{DynMarketDataProvider, PricePoint};
+use crate::memo::parse_attributed_memo;
+use crate::oracle::DynPriceOracle;
+use crate::rpc::DynRpcProvider;
+use crate::sol::Lamports;
+use crate::token::TokenManager;
+use crate::types::{AgentContext, MarketTrend, OracleData, TransactionRecord, TransactionStatus};
+
+/// Number of latest prices in the short moving average
+const SHORT_WINDOW: usize = 5;
+/// Relative gap between the moving averages below which the trend is neutral
+const TREND_THRESHOLD: f64 = 0.01;
+
+/// How long each fetched part of the context is reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextTtls {
+    /// SOL balance
+    pub balance: Duration,
+    /// Token balances
+    pub token_balances: Duration,
+    /// Transaction history
+    pub transaction_history: Duration,
+    /// Price feeds and market conditions
+    pub price_feeds: Duration,
+}
+
+impl ContextTtls {
+    /// TTLs from the agent context settings
+    pub fn from_settings(settings: &ContextSettings) -> Self {
+        Self {
+            balance: Duration::from_secs(settings.balance_ttl_seconds),
+            token_balances: Duration::from_secs(settings.token_balances_ttl_seconds),
+            transaction_history: Duration::from_secs(settings.history_ttl_seconds),
+            price_feeds: Duration::from_secs(settings.price_ttl_seconds),
+        }
+    }
+}
+
+impl Default for ContextTtls {
+    fn default() -> Self {
+        Self::from_settings(&ContextSettings::default())
+    }
+}
+
+/// Fields fetched by one refresh
+///
+/// Fields that were fresh enough, or whose source failed, are left out and
+/// keep their previous value when the update is applied.
+#[derive(Debug, Clone)]
+pub struct ContextUpdate {
+    fetched_at: DateTime<Utc>,
+    balance: Option<f64>,
+    token_balances: Option<HashMap<Pubkey, u64>>,
+    transaction_history: Option<Vec<TransactionRecord>>,
+    prices: Option<PriceUpdate>,
+}
+
+/// Quotes of one price refresh and what they say about the market
+#[derive(Debug, Clone)]
+struct PriceUpdate {
+    feeds: HashMap<String, f64>,
+    /// Symbols whose prices are too old to keep
+    stale: Vec<String>,
+    oracle_data: Option<OracleData>,
+    volatility: Option<f64>,
+    trend: Option<MarketTrend>,
+}
+
+impl ContextUpdate {
+    fn new(fetched_at: DateTime<Utc>) -> Self {
+        Self {
+            fetched_at,
+            balance: None,
+            token_balances: None,
+            transaction_history: None,
+            prices: None,
+        }
+    }
+
+    /// Whether nothing was fetched
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.token_balances.is_none()
+            && self.transaction_history.is_none()
+            && self.prices.is_none()
+    }
+
+    /// Write the fetched fields into `context` and mark them fresh
+    pub fn apply(self, context: &mut AgentContext) {
+        let fetched_at = Some(self.fetched_at);
+        if let Some(balance) = self.balance {
+            context.wallet_balance = balance;
+            context.freshness.balance = fetched_at;
+        }
+        if let Some(token_balances) = self.token_balances {
+            context.token_balances = token_balances;
+            context.stale_token_mints.clear();
+            context.freshness.token_balances = fetched_at;
+        }
+        if let Some(history) = self.transaction_history {
+            context.transaction_history = history;
+            context.freshness.transaction_history = fetched_at;
+        }
+        if let Some(prices) = self.prices {
+            context
+                .price_feeds
+                .retain(|symbol, _| !prices.stale.contains(symbol));
+            context.price_feeds.extend(prices.feeds);
+            context.stale_price_feeds = prices.stale;
+            if let Some(oracle_data) = prices.oracle_data {
+                context.oracle_data = Some(oracle_data);
+            }
+            if let Some(volatility) = prices.volatility {
+                context.market_conditions.volatility = volatility;
+            }
+            if let Some(trend) = prices.trend {
+                context.market_conditions.trend = trend;
+            }
+            context.freshness.price_feeds = fetched_at;
+        }
+    }
+}
+
+/// Refreshes the network-backed fields of agent contexts
+pub struct ContextBuilder {
+    rpc_client: Arc<dyn DynRpcProvider>,
+    token_manager: Arc<RwLock<TokenManager>>,
+    oracle: Option<Arc<dyn DynPriceOracle>>,
+    providers: Vec<Arc<dyn DynMarketDataProvider>>,
+    watchlist: Vec<String>,
+    ttls: ContextTtls,
+    max_price_age: Duration,
+    history_limit: usize,
+    price_window: usize,
+    /// Recent prices per symbol, oldest first
+    price_history: Mutex<HashMap<String, VecDeque<f64>>>,
+    /// Publish time of the latest quote kept per symbol
+    last_quoted: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl ContextBuilder {
+    /// Builder with the default settings and no price oracle
+    pub fn new(
+        rpc_client: Arc<dyn DynRpcProvider>,
+        token_manager: Arc<RwLock<TokenManager>>,
+    ) -> Self {
+        Self::from_settings(rpc_client, token_manager, &ContextSettings::default())
+    }
+
+    /// Builder following the agent context settings
+    ///
+    /// The watchlist is only polled once a price oracle or market data
+    /// providers are set.
+    pub fn from_settings(
+        rpc_client: Arc<dyn DynRpcProvider>,
+        token_manager: Arc<RwLock<TokenManager>>,
+        settings: &ContextSettings,
+    ) -> Self {
+        Self {
+            rpc_client,
+            token_manager,
+            oracle: None,
+            providers: Vec::new(),
+            watchlist: settings.watchlist.clone(),
+            ttls: ContextTtls::from_settings(settings),
+            max_price_age: Duration::from_secs(settings.max_price_age_seconds),
+            history_limit: settings.history_limit,
+            price_window: settings.price_window.max(2),
+            price_history: Mutex::new(HashMap::new()),
+            last_quoted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Quote `watchlist` from `oracle`
+    pub fn with_price_oracle(
+        mut self,
+        oracle: Arc<dyn DynPriceOracle>,
+        watchlist: Vec<String>,
+    ) -> Self {
+        self.oracle = Some(oracle);
+        self.watchlist = watchlist;
+        self
+    }
+
+    /// Also quote the watchlist from `providers`
+    ///
+    /// Every provider is asked on each price refresh; a tie between quotes
+    /// published at the same time goes to the oracle, then to the provider
+    /// listed first.
+    pub fn with_market_data(mut self, providers: Vec<Arc<dyn DynMarketDataProvider>>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// Quote `watchlist` instead of the configured one
+    pub fn with_watchlist(mut self, watchlist: Vec<String>) -> Self {
+        self.watchlist = watchlist;
+        self
+    }
+
+    /// Drop quotes published more than `max_age` ago
+    pub fn with_max_price_age(mut self, max_age: Duration) -> Self {
+        self.max_price_age = max_age;
+        self
+    }
+
+    /// Oracle quoting the watchlist, if any
+    pub fn price_oracle(&self) -> Option<Arc<dyn DynPriceOracle>> {
+        self.oracle.clone()
+    }
+
+    /// Market data providers quoting the watchlist
+    pub fn market_data(&self) -> &[Arc<dyn DynMarketDataProvider>] {
+        &self.providers
+    }
+
+    /// Set how long each fetched field is reused
+    pub fn with_ttls(mut self, ttls: ContextTtls) -> Self {
+        self.ttls = ttls;
+        self
+    }
+
+    /// How long each fetched field is reused
+    pub fn ttls(&self) -> ContextTtls {
+        self.ttls
+    }
+
+    /// Symbols quoted on every price refresh
+    pub fn watchlist(&self) -> &[String] {
+        &self.watchlist
+    }
+
+    /// Fetch the fields of `previous` that are older than their TTL
+    ///
+    /// Fails only if the SOL balance is due and cannot be read; other
+    /// sources that fail are logged and retried on the next refresh.
+    pub async fn refresh(&self, previous: &AgentContext) -> Result<ContextUpdate> {
+        let now = Utc::now();
+        let wallet = previous.wallet_pubkey;
+        let freshness = &previous.freshness;
+        let mut update = ContextUpdate::new(now);
+
+        if is_due(freshness.balance, self.ttls.balance, now) {
+            let lamports = self.rpc_client.get_balance(&wallet).await?;
+            update.balance = Some(Lamports::new(lamports).to_sol_f64());
+        }
+
+        if is_due(freshness.token_balances, self.ttls.token_balances, now) {
+            let accounts = self
+                .token_manager
+                .read()
+                .await
+                .get_wallet_token_accounts(&wallet)
+                .await;
+            match accounts {
+                Ok(accounts) => {
+                    let mut totals: HashMap<Pubkey, u64> = HashMap::new();
+                    for info in accounts {
+                        *totals.entry(info.mint).or_insert(0) += info.balance;
+                    }
+                    update.token_balances = Some(totals);
+                }
+                Err(e) => warn!("Keeping previous token balances of {}: {}", wallet, e),
+            }
+        }
+
+        if is_due(
+            freshness.transaction_history,
+            self.ttls.transaction_history,
+            now,
+        ) {
+            match self
+                .rpc_client
+                .get_signatures_for_address(&wallet, self.history_limit)
+                .await
+            {
+                Ok(statuses) => {
+                    update.transaction_history =
+                        Some(statuses.iter().filter_map(transaction_record).collect());
+                }
+                Err(e) => warn!("Keeping previous transaction history of {}: {}", wallet, e),
+            }
+        }
+
+        let has_price_sources = self.oracle.is_some() || !self.providers.is_empty();
+        if has_price_sources
+            && !self.watchlist.is_empty()
+            && is_due(freshness.price_feeds, self.ttls.price_feeds, now)
+        {
+            update.prices = self.fetch_prices(now).await;
+        }
+
+        Ok(update)
+    }
+
+    /// Copy of `previous` with every stale field refreshed
+    pub async fn build(&self, previous: &AgentContext) -> Result<AgentContext> {
+        let mut context = previous.clone();
+        self.refresh(previous).await?.apply(&mut context);
+        Ok(context)
+    }
+
+    /// Quote the watchlist from every source and update the price windows
+    ///
+    /// Quotes older than the maximum price age are dropped, as are earlier
+    /// quotes of symbols no source has quoted since; both are reported as
+    /// stale. `None` if nothing was quoted and nothing went stale.
+    async fn fetch_prices(&self, now: DateTime<Utc>) -> Option<PriceUpdate> {
+        let mut quotes: HashMap<String, OracleData> = HashMap::new();
+        if let Some(oracle) = &self.oracle {
+            for symbol in &self.watchlist {
+                match oracle.get_price(symbol).await {
+                    Ok(data) => merge_quote(&mut quotes, symbol, data),
+                    Err(e) => warn!("Failed to quote {}: {}", symbol, e),
+                }
+            }
+        }
+        for provider in &self.providers {
+            match provider.get_prices(&self.watchlist).await {
+                Ok(points) => {
+                    for (symbol, point) in points {
+                        if self.watchlist.contains(&symbol) {
+                            merge_quote(&mut quotes, &symbol, quote_data(provider.name(), &point));
+                        }
+                    }
+                }
+                Err(e) => warn!("Skipping prices from {}: {}", provider.name(), e),
+            }
+        }
+
+        let mut stale = Vec::new();
+        let mut last_quoted = self.last_quoted.lock().await;
+        for symbol in &self.watchlist {
+            match quotes.get(symbol).map(|quote| quote.timestamp) {
+                Some(published) if is_older_than(published, self.max_price_age, now) => {
+                    warn!(
+                        "Dropping price of {}: latest quote was published at {}",
+                        symbol, published
+                    );
+                    quotes.remove(symbol);
+                    stale.push(symbol.clone());
+                }
+                Some(published) => {
+                    last_quoted.insert(symbol.clone(), published);
+                }
+                None => {
+                    if let Some(published) = last_quoted.get(symbol) {
+                        if is_older_than(*published, self.max_price_age, now) {
+                            warn!(
+                                "Dropping price of {}: not quoted since {}",
+                                symbol, published
+                            );
+                            stale.push(symbol.clone());
+                        }
+                    }
+                }
+            }
+        }
+        drop(last_quoted);
+        if quotes.is_empty() && stale.is_empty() {
+            return None;
+        }
+
+        // The first symbol of the watchlist is the reference asset
+        let oracle_data = self
+            .watchlist
+            .first()
+            .and_then(|symbol| quotes.get(symbol))
+            .cloned();
+        let feeds: HashMap<String, f64> = quotes
+            .into_iter()
+            .map(|(symbol, quote)| (symbol, quote.price))
+            .collect();
+
+        let mut history = self.price_history.lock().await;
+        for (symbol, price) in &feeds {
+            let window = history.entry(symbol.clone()).or_default();
+            window.push_back(*price);
+            while window.len() > self.price_window {
+                window.pop_front();
+            }
+        }
+
+        let windows: Vec<&VecDeque<f64>> = self
+            .watchlist
+            .iter()
+            .filter_map(|symbol| history.get(symbol))
+            .collect();
+        let volatilities: Vec<f64> = windows.iter().copied().filter_map(volatility).collect();
+        let volatility = (!volatilities.is_empty())
+            .then(|| volatilities.iter().sum::<f64>() / volatilities.len() as f64);
+        let trends: Vec<MarketTrend> = windows.iter().copied().filter_map(trend).collect();
+
+        Some(PriceUpdate {
+            feeds,
+            stale,
+            oracle_data,
+            volatility,
+            trend: overall_trend(&trends),
+        })
+    }
+}
+
+/// Whether a field fetched at `fetched_at` is older than `ttl`
+fn is_due(fetched_at: Option<DateTime<Utc>>, ttl: Duration, now: DateTime<Utc>) -> bool {
+    let Some(fetched_at) = fetched_at else {
+        return true;
+    };
+    // A timestamp in the future means the clock moved; refetch
+    match now.signed_duration_since(fetched_at).to_std() {
+        Ok(age) => age >= ttl,
+        Err(_) => true,
+    }
+}
+
+/// Whether something published at `published` is older than `max_age`
+///
+/// Timestamps ahead of the local clock count as fresh.
+fn is_older_than(published: DateTime<Utc>, max_age: Duration, now: DateTime<Utc>) -> bool {
+    now.signed_duration_since(published)
+        .to_std()
+        .is_ok_and(|age| age > max_age)
+}
+
+/// Keep `quote` for `symbol` unless an equally fresh or fresher one is kept
+fn merge_quote(quotes: &mut HashMap<String, OracleData>, symbol: &str, quote: OracleData) {
+    match quotes.get(symbol) {
+        Some(kept) if kept.timestamp >= quote.timestamp => {}
+        _ => {
+            quotes.insert(symbol.to_string(), quote);
+        }
+    }
+}
+
+/// Oracle data of a market data provider's quote
+fn quote_data(source: &str, point: &PricePoint) -> OracleData {
+    OracleData {
+        source: source.to_string(),
+        price: point.price,
+        confidence: 0.0,
+        timestamp: point.timestamp,
+        ema: None,
+    }
+}
+
+/// Standard deviation of the prices relative to their mean, capped at 1
+fn volatility(prices: &VecDeque<f64>) -> Option<f64> {
+    if prices.len() < 2 {
+        return None;
+    }
+    let mean = prices.iter().sum::<f64>() / prices.len() as f64;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = prices.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / prices.len() as f64;
+    Some((variance.sqrt() / mean).min(1.0))
+}
+
+/// Trend from the short moving average crossing the whole window's average
+fn trend(prices: &VecDeque<f64>) -> Option<MarketTrend> {
+    if prices.len() <= SHORT_WINDOW {
+        return None;
+    }
+    let long = prices.iter().sum::<f64>() / prices.len() as f64;
+    let short = prices.iter().rev().take(SHORT_WINDOW).sum::<f64>() / SHORT_WINDOW as f64;
+    Some(if short > long * (1.0 + TREND_THRESHOLD) {
+        MarketTrend::Bullish
+    } else if short < long * (1.0 - TREND_THRESHOLD) {
+        MarketTrend::Bearish
+    } else {
+        MarketTrend::Neutral
+    })
+}
+
+/// Majority of the per-symbol trends
+fn overall_trend(trends: &[MarketTrend]) -> Option<MarketTrend> {
+    if trends.is_empty() {
+        return None;
+    }
+    let score: i32 = trends
+        .iter()
+        .map(|trend| match trend {
+            MarketTrend::Bullish => 1,
+            MarketTrend::Bearish => -1,
+            MarketTrend::Neutral => 0,
+        })
+        .sum();
+    Some(match score.signum() {
+        1 => MarketTrend::Bullish,
+        -1 => MarketTrend::Bearish,
+        _ => MarketTrend::Neutral,
+    })
+}
+
+/// History entry for a signature listed by `getSignaturesForAddress`
+///
+/// Only what the listing tells is filled in: amounts, destination and fee
+/// need the full transaction.
+fn transaction_record(
+    status: &RpcConfirmedTransactionStatusWithSignature,
+) -> Option<TransactionRecord> {
+    let signature = status.signature.parse().ok()?;
+    let timestamp = status
+        .block_time
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .unwrap_or_else(Utc::now);
+    let transaction_status = match (&status.err, &status.confirmation_status) {
+        (Some(_), _) => TransactionStatus::Failed,
+        (None, Some(TransactionConfirmationStatus::Processed)) => TransactionStatus::Pending,
+        (None, Some(_)) => TransactionStatus::Confirmed,
+        (None, None) => TransactionStatus::Unknown,
+    };
+    Some(TransactionRecord {
+        signature,
+        timestamp,
+        action_type: "transaction".to_string(),
+        amount: None,
+        token_mint: None,
+        destination: None,
+        status: transaction_status,
+        fee: 0,
+        memo: status.memo.clone(),
+        attribution: status.memo.as_deref().and_then(parse_attributed_memo),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::market::MarketDataProvider;
+    use crate::oracle::PriceOracle;
+    use crate::rpc::mock::MockRpc;
+    use solana_sdk::{
+        commitment_config::CommitmentConfig, signature::Signature, transaction::TransactionError,
+    };
+    use std::future::Future;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Oracle quoting a price one dollar higher on every call
+    #[derive(Default)]
+    struct RisingOracle {
+        calls: AtomicU64,
+    }
+
+    impl PriceOracle for RisingOracle {
+        fn get_price(&self, symbol: &str) -> impl Future<Output = Result<OracleData>> + Send {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(Ok(OracleData {
+                source: format!("rising:{}", symbol),
+                price: 100.0 + call as f64,
+                confidence: 0.1,
+                timestamp: Utc::now(),
+                ema: None,
+            }))
+        }
+    }
+
+    /// Provider answering with fixed quotes, or failing without any
+    struct FixedProvider {
+        name: &'static str,
+        quotes: Option<Vec<(&'static str, f64, DateTime<Utc>)>>,
+    }
+
+    impl MarketDataProvider for FixedProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn get_prices(
+            &self,
+            _symbols: &[String],
+        ) -> impl Future<Output = Result<HashMap<String, PricePoint>>> + Send {
+            let result = match &self.quotes {
+                Some(quotes) => Ok(quotes
+                    .iter()
+                    .map(|(symbol, price, timestamp)| {
+                        let point = PricePoint {
+                            price: *price,
+                            change_24h: None,
+                            timestamp: *timestamp,
+                        };
+                        (symbol.to_string(), point)
+                    })
+                    .collect()),
+                None => Err(Error::network(format!("{} is down", self.name))),
+            };
+            std::future::ready(result)
+        }
+    }
+
+    fn listed(
+        signature: Signature,
+        err: Option<TransactionError>,
+    ) -> RpcConfirmedTransactionStatusWithSignature {
+        RpcConfirmedTransactionStatusWithSignature {
+            signature: signature.to_string(),
+            slot: 10,
+            err,
+            memo: None,
+            block_time: Some(1_700_000_000),
+            confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+        }
+    }
+
+    /// Wallet with 2 SOL, 250 tokens of `mint` and two past transactions
+    fn funded_rpc(wallet: Pubkey, mint: Pubkey) -> Result<Arc<MockRpc>> {
+        let rpc = Arc::new(MockRpc::new());
+        rpc.set_balance(wallet, 2_000_000_000);
+        rpc.set_token_account(Pubkey::new_unique(), mint, wallet, 250)?;
+        rpc.set_history(
+            wallet,
+            vec![
+                listed(Signature::new_unique(), None),
+                listed(
+                    Signature::new_unique(),
+                    Some(TransactionError::InsufficientFundsForFee),
+                ),
+            ],
+        );
+        Ok(rpc)
+    }
+
+    fn builder(rpc: Arc<MockRpc>, oracle: Arc<RisingOracle>) -> ContextBuilder {
+        let tokens = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        ContextBuilder::new(rpc, Arc::new(RwLock::new(tokens)))
+            .with_price_oracle(oracle, vec!["SOL".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_refresh_populates_every_field() -> Result<()> {
+        let (wallet, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let rpc = funded_rpc(wallet, mint)?;
+        let oracle = Arc::new(RisingOracle::default());
+        let builder = builder(rpc, oracle).with_ttls(ContextTtls {
+            balance: Duration::ZERO,
+            token_balances: Duration::ZERO,
+            transaction_history: Duration::ZERO,
+            price_feeds: Duration::ZERO,
+        });
+
+        let mut context = AgentContext::new(wallet);
+        for _ in 0..10 {
+            context = builder.build(&context).await?;
+        }
+
+        assert!((context.wallet_balance - 2.0).abs() < 1e-9);
+        assert_eq!(context.token_balances.get(&mint), Some(&250));
+        let statuses: Vec<TransactionStatus> = context
+            .transaction_history
+            .iter()
+            .map(|r| r.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![TransactionStatus::Confirmed, TransactionStatus::Failed]
+        );
+        assert_eq!(context.price_feeds.get("SOL"), Some(&109.0));
+        assert_eq!(
+            context.oracle_data.as_ref().map(|d| d.source.as_str()),
+            Some("rising:SOL")
+        );
+
+        // Prices rose steadily: the short average is above the long one
+        assert_eq!(context.market_conditions.trend, MarketTrend::Bullish);
+        assert!(context.market_conditions.volatility > 0.0);
+        assert!(context.market_conditions.volatility < 0.1);
+        assert!(context.freshness.balance.is_some());
+        assert!(context.freshness.token_balances.is_some());
+        assert!(context.freshness.transaction_history.is_some());
+        assert!(context.freshness.price_feeds.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_skips_fields_within_ttl() -> Result<()> {
+        let (wallet, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let rpc = funded_rpc(wallet, mint)?;
+        let oracle = Arc::new(RisingOracle::default());
+        let builder = builder(rpc.clone(), oracle.clone());
+
+        let context = builder.build(&AgentContext::new(wallet)).await?;
+        assert_eq!(rpc.history_calls(), 1);
+        assert_eq!(oracle.calls.load(Ordering::SeqCst), 1);
+        let discovery_calls = rpc.program_account_calls();
+
+        // Everything is fresh: nothing is fetched again
+        assert!(builder.refresh(&context).await?.is_empty());
+        assert_eq!(rpc.history_calls(), 1);
+        assert_eq!(rpc.program_account_calls(), discovery_calls);
+        assert_eq!(oracle.calls.load(Ordering::SeqCst), 1);
+
+        // Only the field past its TTL is refetched
+        let mut aged = context.clone();
+        aged.freshness.transaction_history = Some(Utc::now() - chrono::Duration::minutes(5));
+        rpc.set_history(wallet, vec![listed(Signature::new_unique(), None)]);
+        let refreshed = builder.build(&aged).await?;
+        assert_eq!(rpc.history_calls(), 2);
+        assert_eq!(refreshed.transaction_history.len(), 1);
+        assert_eq!(rpc.program_account_calls(), discovery_calls);
+        assert_eq!(oracle.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(refreshed.freshness.balance, context.freshness.balance);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_market_data_keeps_the_freshest_quote() -> Result<()> {
+        let (wallet, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let rpc = funded_rpc(wallet, mint)?;
+        let now = Utc::now();
+        let seconds_ago = |seconds| now - chrono::Duration::seconds(seconds);
+        let slow = FixedProvider {
+            name: "slow",
+            quotes: Some(vec![
+                ("SOL", 100.0, seconds_ago(60)),
+                ("USDC", 1.0, seconds_ago(30)),
+                ("BONK", 0.00002, seconds_ago(3_600)),
+                ("JUP", 0.9, seconds_ago(10)),
+            ]),
+        };
+        let down = FixedProvider {
+            name: "down",
+            quotes: None,
+        };
+        let fast = FixedProvider {
+            name: "fast",
+            quotes: Some(vec![
+                ("SOL", 101.0, seconds_ago(5)),
+                ("USDC", 0.99, seconds_ago(120)),
+            ]),
+        };
+        let providers: Vec<Arc<dyn DynMarketDataProvider>> =
+            vec![Arc::new(slow), Arc::new(down), Arc::new(fast)];
+        let tokens = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let builder = ContextBuilder::new(rpc, Arc::new(RwLock::new(tokens)))
+            .with_watchlist(vec![
+                "SOL".to_string(),
+                "USDC".to_string(),
+                "BONK".to_string(),
+            ])
+            .with_market_data(providers);
+
+        let mut previous = AgentContext::new(wallet);
+        previous.price_feeds.insert("BONK".to_string(), 0.00003);
+        let context = builder.build(&previous).await?;
+
+        // The failing provider costs nothing; the freshest quote wins
+        assert_eq!(context.price_feeds.get("SOL"), Some(&101.0));
+        assert_eq!(context.price_feeds.get("USDC"), Some(&1.0));
+        // Quotes outside the watchlist are ignored
+        assert!(!context.price_feeds.contains_key("JUP"));
+        // An hour-old quote is dropped along with the earlier price
+        assert!(!context.price_feeds.contains_key("BONK"));
+        assert_eq!(context.stale_price_feeds, vec!["BONK".to_string()]);
+        let oracle_data = context.oracle_data.as_ref();
+        assert_eq!(oracle_data.map(|d| d.source.as_str()), Some("fast"));
+        assert!(context.freshness.price_feeds.is_some());
+        Ok(())
+    }
+}
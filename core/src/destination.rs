@@ -0,0 +1,364 @@
+//! Safety checks on the recipient of a transfer
+//!
+//! A transfer to a mistyped or wrong address cannot be undone.
+//! [`validate_destination`] looks the recipient up on-chain before a
+//! transfer is built:
+//!
+//! - Program ids (the system and token programs, loaders, sysvars and any
+//!   executable account) are refused as destinations unless
+//!   [`DestinationOptions::allow_program_destination`] is set.
+//! - SOL sent to an account that does not exist yet, in an amount below the
+//!   rent-exempt minimum, would not survive as an account. Depending on
+//!   [`DestinationOptions::unfunded`] this is a warning or an error.
+//! - A token transfer addressed to a token account, rather than to the wallet
+//!   owning it, credits that account directly instead of deriving an
+//!   associated token account of the token account. A token account of a
+//!   different mint is refused.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::destination::{DestinationOptions, DestinationTransfer};
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::Wallet;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(wallet: &Wallet, to: Pubkey) -> agent_wallet_core::Result<()> {
+//! let check = wallet
+//!     .validate_destination(
+//!         &to,
+//!         &DestinationTransfer::Sol {
+//!             lamports: Lamports::new(1_000),
+//!         },
+//!         &DestinationOptions::default(),
+//!     )
+//!     .await?;
+//! println!("{}", check.info);
+//! for warning in &check.warnings {
+//!     println!("warning: {}", warning);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use solana_sdk::{
+    account::Account, bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable, compute_budget,
+    pubkey::Pubkey, stake, system_program, sysvar, vote,
+};
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::sol::Lamports;
+use crate::token::{
+    parse_token_account, METAPLEX_METADATA_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+/// What to do about SOL sent to a new account below the rent-exempt minimum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnfundedDestination {
+    /// Send anyway and report a warning
+    #[default]
+    Warn,
+    /// Refuse the transfer
+    Reject,
+}
+
+/// How strictly [`validate_destination`] treats a recipient
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DestinationOptions {
+    /// Accept program ids as destinations
+    pub allow_program_destination: bool,
+    /// Outcome of sending too little SOL to an account that does not exist
+    pub unfunded: UnfundedDestination,
+}
+
+/// The transfer a destination receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DestinationTransfer {
+    /// SOL
+    Sol {
+        /// Amount sent
+        lamports: Lamports,
+    },
+    /// Tokens of `mint`
+    Token {
+        /// Mint of the tokens sent
+        mint: Pubkey,
+    },
+}
+
+/// On-chain state of a destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationInfo {
+    /// Address looked up
+    pub address: Pubkey,
+    /// Whether the account exists
+    pub exists: bool,
+    /// Lamports the account holds
+    pub lamports: u64,
+    /// Program owning the account, if it exists
+    pub owner: Option<Pubkey>,
+    /// Whether the address is a program id
+    pub is_program: bool,
+}
+
+impl fmt::Display for DestinationInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.exists {
+            return write!(f, "{}: does not exist yet", self.address);
+        }
+        write!(
+            f,
+            "{}: exists, {} SOL",
+            self.address,
+            Lamports::new(self.lamports).to_sol_display()
+        )?;
+        if self.is_program {
+            write!(f, ", is a program")?;
+        }
+        if let Some(owner) = self.owner.filter(|owner| *owner != system_program::id()) {
+            write!(f, ", owned by {}", owner)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of a destination check that passed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationCheck {
+    /// On-chain state of the destination
+    pub info: DestinationInfo,
+    /// Token account to credit directly, when the destination is one
+    pub token_account: Option<Pubkey>,
+    /// Problems that did not stop the transfer
+    pub warnings: Vec<String>,
+}
+
+/// Whether `address` is a well-known program or sysvar id
+pub fn is_well_known_program(address: &Pubkey) -> bool {
+    [
+        system_program::id(),
+        TOKEN_PROGRAM_ID,
+        TOKEN_2022_PROGRAM_ID,
+        spl_associated_token_account::id(),
+        spl_memo::id(),
+        METAPLEX_METADATA_PROGRAM_ID,
+        stake::program::id(),
+        vote::program::id(),
+        compute_budget::id(),
+        bpf_loader::id(),
+        bpf_loader_deprecated::id(),
+        bpf_loader_upgradeable::id(),
+    ]
+    .contains(address)
+        || sysvar::is_sysvar_id(address)
+}
+
+/// Check `destination` before `transfer` is sent to it
+///
+/// Fails with a validation error for a refused destination.
+pub async fn validate_destination(
+    rpc: &dyn DynRpcProvider,
+    destination: &Pubkey,
+    transfer: &DestinationTransfer,
+    options: &DestinationOptions,
+) -> Result<DestinationCheck> {
+    let account = rpc.get_account_optional(destination).await?;
+    let info = DestinationInfo {
+        address: *destination,
+        exists: account.is_some(),
+        lamports: account.as_ref().map_or(0, |account| account.lamports),
+        owner: account.as_ref().map(|account| account.owner),
+        is_program: is_well_known_program(destination)
+            || account.as_ref().is_some_and(|account| account.executable),
+    };
+
+    if info.is_program && !options.allow_program_destination {
+        return Err(Error::validation(format!(
+            "Destination {} is a program id; funds sent to it cannot be recovered",
+            destination
+        )));
+    }
+
+    let mut check = DestinationCheck {
+        info,
+        token_account: None,
+        warnings: Vec::new(),
+    };
+    match (transfer, &account) {
+        (DestinationTransfer::Sol { lamports }, None) => {
+            let minimum = Lamports::new(rpc.get_minimum_balance_for_rent_exemption(0).await?);
+            if *lamports < minimum {
+                let problem = format!(
+                    "Destination {} does not exist and {} is below the rent-exempt minimum of \
+                     {}; the funds would be lost",
+                    destination, lamports, minimum
+                );
+                match options.unfunded {
+                    UnfundedDestination::Warn => check.warnings.push(problem),
+                    UnfundedDestination::Reject => return Err(Error::validation(problem)),
+                }
+            }
+        }
+        (DestinationTransfer::Token { mint }, Some(account)) if is_token_owned(account) => {
+            let token_account = parse_token_account(destination, account).map_err(|_| {
+                Error::validation(format!(
+                    "Destination {} is a token program account but not a token account",
+                    destination
+                ))
+            })?;
+            if token_account.mint != *mint {
+                return Err(Error::validation(format!(
+                    "Destination {} is a token account of mint {}, not {}",
+                    destination, token_account.mint, mint
+                )));
+            }
+            check.token_account = Some(*destination);
+            check.warnings.push(format!(
+                "Destination {} is a token account owned by {}; crediting it directly",
+                destination, token_account.owner
+            ));
+        }
+        _ => {}
+    }
+    Ok(check)
+}
+
+/// Whether either token program owns `account`
+fn is_token_owned(account: &Account) -> bool {
+    account.owner == TOKEN_PROGRAM_ID || account.owner == TOKEN_2022_PROGRAM_ID
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+
+    #[tokio::test]
+    async fn test_program_destinations_are_refused() -> Result<()> {
+        let rpc = MockRpc::new();
+        let transfer = DestinationTransfer::Sol {
+            lamports: Lamports::new(1_000_000),
+        };
+        let strict = DestinationOptions::default();
+
+        for program in [system_program::id(), TOKEN_PROGRAM_ID, sysvar::clock::id()] {
+            let result = validate_destination(&rpc, &program, &transfer, &strict).await;
+            assert!(matches!(result, Err(Error::Validation(_))));
+        }
+
+        // Any executable account is a program
+        let deployed = Pubkey::new_unique();
+        let mut account = Account::new(1_000_000, 36, &bpf_loader_upgradeable::id());
+        account.executable = true;
+        rpc.set_account(deployed, account);
+        let result = validate_destination(&rpc, &deployed, &transfer, &strict).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        let allowed = DestinationOptions {
+            allow_program_destination: true,
+            ..Default::default()
+        };
+        let check = validate_destination(&rpc, &deployed, &transfer, &allowed).await?;
+        assert!(check.info.is_program);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unfunded_destination_warns_or_rejects() -> Result<()> {
+        let rpc = MockRpc::new();
+        let fresh = Pubkey::new_unique();
+        let dust = DestinationTransfer::Sol {
+            lamports: Lamports::new(1_000),
+        };
+
+        let check =
+            validate_destination(&rpc, &fresh, &dust, &DestinationOptions::default()).await?;
+        assert!(!check.info.exists);
+        assert_eq!(check.warnings.len(), 1);
+        assert!(check.warnings[0].contains("rent-exempt"));
+
+        let strict = DestinationOptions {
+            unfunded: UnfundedDestination::Reject,
+            ..Default::default()
+        };
+        let result = validate_destination(&rpc, &fresh, &dust, &strict).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Enough to open the account, or an account that already exists
+        let funded = DestinationTransfer::Sol {
+            lamports: Lamports::from_sol_str("1")?,
+        };
+        assert!(validate_destination(&rpc, &fresh, &funded, &strict)
+            .await?
+            .warnings
+            .is_empty());
+        rpc.set_account(fresh, Account::new(5_000_000, 0, &system_program::id()));
+        let check = validate_destination(&rpc, &fresh, &dust, &strict).await?;
+        assert!(check.warnings.is_empty());
+        assert_eq!(check.info.lamports, 5_000_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_account_destinations_are_credited_directly() -> Result<()> {
+        let rpc = MockRpc::new();
+        let (mint, owner, address) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        rpc.set_token_account(address, mint, owner, 10)?;
+        let options = DestinationOptions::default();
+
+        let check = validate_destination(
+            &rpc,
+            &address,
+            &DestinationTransfer::Token { mint },
+            &options,
+        )
+        .await?;
+        assert_eq!(check.token_account, Some(address));
+        assert!(check.warnings[0].contains(&owner.to_string()));
+
+        // A token account of another mint is a mistake
+        let other = DestinationTransfer::Token {
+            mint: Pubkey::new_unique(),
+        };
+        let result = validate_destination(&rpc, &address, &other, &options).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        // Wallet owners get their associated account derived as usual
+        let wallet = Pubkey::new_unique();
+        rpc.set_account(wallet, Account::new(1_000_000, 0, &system_program::id()));
+        let check = validate_destination(
+            &rpc,
+            &wallet,
+            &DestinationTransfer::Token { mint },
+            &options,
+        )
+        .await?;
+        assert_eq!(check.token_account, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lookup_failures_propagate() -> Result<()> {
+        let rpc = MockRpc::new();
+        let destination = Pubkey::new_unique();
+        rpc.fail_account(destination);
+        let result = validate_destination(
+            &rpc,
+            &destination,
+            &DestinationTransfer::Sol {
+                lamports: Lamports::new(1),
+            },
+            &DestinationOptions::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Rpc(_))));
+        Ok(())
+    }
+}
@@ -7,7 +7,9 @@
 //! # Features
 //!
 //! - **AES-256-GCM**: Authenticated encryption with AES-GCM
+//! - **Argon2id**: Memory-hard password-based key derivation (default)
 //! - **PBKDF2**: Password-based key derivation with configurable iterations
+//! - **Keystore Binding**: Keys that also need a secret from the OS keychain
 //! - **Secure Random**: Cryptographically secure random number generation
 //! - **Zeroization**: Automatic zeroization of sensitive data in memory
 //! - **Multiple Backends**: Support for AES-GCM and Ring encryption
@@ -41,7 +43,9 @@ use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
+use argon2::{Argon2, Params};
 use base64::{engine::general_purpose::STANDARD, Engine};
+use hkdf::Hkdf;
 use pbkdf2::pbkdf2_hmac;
 use ring::{
     aead::{self, Aad, LessSafeKey, UnboundKey, NONCE_LEN},
@@ -78,6 +82,60 @@ impl fmt::Display for EncryptionAlgorithm {
     }
 }
 
+/// Key derivation function turning a passphrase into an encryption key
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "name", rename_all = "kebab-case")]
+pub enum KdfAlgorithm {
+    /// PBKDF2 with HMAC-SHA256, the only KDF before format version 3
+    Pbkdf2 {
+        /// Number of iterations
+        iterations: u32,
+    },
+    /// Argon2id (default)
+    Argon2id {
+        /// Memory in KiB
+        m_cost: u32,
+        /// Number of passes over the memory
+        t_cost: u32,
+        /// Degree of parallelism
+        p_cost: u32,
+    },
+}
+
+impl KdfAlgorithm {
+    /// Iteration count recorded next to the KDF for older readers
+    pub fn iterations(&self) -> u32 {
+        match *self {
+            KdfAlgorithm::Pbkdf2 { iterations } => iterations,
+            KdfAlgorithm::Argon2id { t_cost, .. } => t_cost,
+        }
+    }
+}
+
+impl Default for KdfAlgorithm {
+    /// Argon2id with the OWASP recommended 19 MiB, two passes and one lane
+    fn default() -> Self {
+        Self::Argon2id {
+            m_cost: 19_456,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+impl fmt::Display for KdfAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KdfAlgorithm::Pbkdf2 { iterations } => write!(f, "pbkdf2 ({} iterations)", iterations),
+            KdfAlgorithm::Argon2id { m_cost, t_cost, p_cost } => write!(
+                f,
+                "argon2id (m={} KiB, t={}, p={})",
+                m_cost, t_cost, p_cost
+            ),
+        }
+    }
+}
+
 /// Encrypted data structure with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncryptedData {
@@ -91,13 +149,25 @@ pub struct EncryptedData {
     pub algorithm: EncryptionAlgorithm,
     /// Key derivation function iterations
     pub kdf_iterations: u32,
+    /// Key derivation function, absent before format version 3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<KdfAlgorithm>,
+    /// Whether the key also mixes in an OS keystore secret (version 3 on)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keystore_bound: bool,
     /// Version of the encryption format
     pub version: u8,
 }
 
 impl EncryptedData {
-    /// Current encryption format version
-    pub const CURRENT_VERSION: u8 = 1;
+    /// Current encryption format version, naming its KDF and keystore binding
+    pub const CURRENT_VERSION: u8 = 3;
+
+    /// Original format version, keyed with PBKDF2 over `kdf_iterations`
+    pub const LEGACY_VERSION: u8 = 1;
+
+    /// Format version 2, like version 1 but also mixing in an OS keystore secret
+    pub const KEYSTORE_BOUND_VERSION: u8 = 2;
 
     /// Create new encrypted data
     pub fn new(
@@ -113,6 +183,8 @@ impl EncryptedData {
             salt: STANDARD.encode(salt),
             algorithm,
             kdf_iterations,
+            kdf: None,
+            keystore_bound: false,
             version: Self::CURRENT_VERSION,
         }
     }
@@ -138,9 +210,29 @@ impl EncryptedData {
             .map_err(|e| Error::encryption(format!("Failed to decode salt: {}", e)))
     }
 
+    /// Key derivation function the key was derived with
+    ///
+    /// Data that names none predates format version 3 and used PBKDF2.
+    pub fn kdf(&self) -> KdfAlgorithm {
+        self.kdf.unwrap_or(KdfAlgorithm::Pbkdf2 {
+            iterations: self.kdf_iterations,
+        })
+    }
+
+    /// Whether decrypting needs the secret from the OS keystore
+    pub fn is_keystore_bound(&self) -> bool {
+        self.version == Self::KEYSTORE_BOUND_VERSION || self.keystore_bound
+    }
+
     /// Validate the encrypted data structure
     pub fn validate(&self) -> Result<()> {
-        if self.version != Self::CURRENT_VERSION {
+        if ![
+            Self::LEGACY_VERSION,
+            Self::KEYSTORE_BOUND_VERSION,
+            Self::CURRENT_VERSION,
+        ]
+        .contains(&self.version)
+        {
             return Err(Error::encryption(format!(
                 "Unsupported encryption version: {} (current: {})",
                 self.version,
@@ -180,6 +272,62 @@ impl KeyDerivation {
         key
     }
 
+    /// Derive an encryption key using Argon2id
+    ///
+    /// # Arguments
+    /// * `passphrase` - The passphrase to derive key from
+    /// * `salt` - The salt, at least 8 bytes
+    /// * `m_cost`, `t_cost`, `p_cost` - Memory in KiB, passes and lanes
+    ///
+    /// # Returns
+    /// A 32-byte encryption key, or an error for out-of-range parameters
+    pub fn argon2id(
+        passphrase: &Zeroizing<String>,
+        salt: &[u8],
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    ) -> Result<Zeroizing<[u8; 32]>> {
+        let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+            .map_err(|e| Error::encryption(format!("Invalid Argon2id parameters: {}", e)))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+            .map_err(|e| Error::encryption(format!("Argon2id key derivation failed: {}", e)))?;
+        Ok(key)
+    }
+
+    /// Derive an encryption key with the given KDF
+    pub fn derive(
+        passphrase: &Zeroizing<String>,
+        salt: &[u8],
+        kdf: &KdfAlgorithm,
+    ) -> Result<Zeroizing<[u8; 32]>> {
+        match *kdf {
+            KdfAlgorithm::Pbkdf2 { iterations } => Ok(Self::pbkdf2(passphrase, salt, iterations)),
+            KdfAlgorithm::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => Self::argon2id(passphrase, salt, m_cost, t_cost, p_cost),
+        }
+    }
+
+    /// Bind a passphrase-derived key to a keystore secret
+    ///
+    /// HKDF-SHA256 over the key followed by the secret, so the result can
+    /// only be derived with both.
+    pub fn bind_to_secret(key: &Zeroizing<[u8; 32]>, secret: &[u8]) -> Zeroizing<[u8; 32]> {
+        let mut material = Zeroizing::new(Vec::with_capacity(key.len() + secret.len()));
+        material.extend_from_slice(&**key);
+        material.extend_from_slice(secret);
+
+        let mut bound = Zeroizing::new([0u8; 32]);
+        // 32 bytes is far below the HKDF-SHA256 output limit
+        let _ = Hkdf::<Sha256>::new(None, &material).expand(b"agent-wallet keystore binding", &mut *bound);
+        bound
+    }
+
     /// Generate a random salt
     pub fn generate_salt() -> Zeroizing<[u8; 16]> {
         let mut salt = Zeroizing::new([0u8; 16]);
@@ -397,43 +545,92 @@ pub mod utils {
         plaintext: &[u8],
         passphrase: &Zeroizing<String>,
         algorithm: EncryptionAlgorithm,
-        kdf_iterations: u32,
+        kdf: KdfAlgorithm,
     ) -> Result<EncryptedData> {
         // Generate random salt
         let salt = KeyDerivation::generate_salt();
 
         // Derive key from passphrase
-        let key = KeyDerivation::pbkdf2(passphrase, &*salt, kdf_iterations);
+        let key = KeyDerivation::derive(passphrase, &*salt, &kdf)?;
 
         // Create encryption service and encrypt
         let encryption = EncryptionService::new(algorithm);
         let mut encrypted = encryption.encrypt(plaintext, &key)?;
 
-        // Update metadata
-        encrypted.kdf_iterations = kdf_iterations;
+        // Update metadata; the key was derived with this salt, not the
+        // one the cipher generated
+        encrypted.salt = STANDARD.encode(&*salt);
+        encrypted.kdf_iterations = kdf.iterations();
+        encrypted.kdf = Some(kdf);
 
         Ok(encrypted)
     }
 
-    /// Decrypt data with a passphrase (derives key automatically)
+    /// Decrypt data with a passphrase (derives key with the KDF it names)
     pub fn decrypt_with_passphrase(
         encrypted: &EncryptedData,
         passphrase: &Zeroizing<String>,
     ) -> Result<Zeroizing<Vec<u8>>> {
         // Validate encrypted data
         encrypted.validate()?;
+        if encrypted.is_keystore_bound() {
+            return Err(Error::encryption(
+                "Data is bound to an OS keystore secret and needs it to decrypt",
+            ));
+        }
 
         // Get salt
         let salt = encrypted.salt_bytes()?;
 
         // Derive key from passphrase
-        let key = KeyDerivation::pbkdf2(passphrase, &salt, encrypted.kdf_iterations);
+        let key = KeyDerivation::derive(passphrase, &salt, &encrypted.kdf())?;
 
         // Create encryption service and decrypt
         let encryption = EncryptionService::new(encrypted.algorithm);
         encryption.decrypt(encrypted, &key)
     }
 
+    /// Encrypt data under a passphrase and an OS keystore secret
+    pub fn encrypt_with_keystore_secret(
+        plaintext: &[u8],
+        passphrase: &Zeroizing<String>,
+        secret: &[u8],
+        algorithm: EncryptionAlgorithm,
+        kdf: KdfAlgorithm,
+    ) -> Result<EncryptedData> {
+        let salt = KeyDerivation::generate_salt();
+        let key = KeyDerivation::derive(passphrase, &*salt, &kdf)?;
+        let key = KeyDerivation::bind_to_secret(&key, secret);
+
+        let encryption = EncryptionService::new(algorithm);
+        let mut encrypted = encryption.encrypt(plaintext, &key)?;
+        encrypted.salt = STANDARD.encode(&*salt);
+        encrypted.kdf_iterations = kdf.iterations();
+        encrypted.kdf = Some(kdf);
+        encrypted.keystore_bound = true;
+
+        Ok(encrypted)
+    }
+
+    /// Decrypt data encrypted under a passphrase and an OS keystore secret
+    pub fn decrypt_with_keystore_secret(
+        encrypted: &EncryptedData,
+        passphrase: &Zeroizing<String>,
+        secret: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        encrypted.validate()?;
+        if !encrypted.is_keystore_bound() {
+            return decrypt_with_passphrase(encrypted, passphrase);
+        }
+
+        let salt = encrypted.salt_bytes()?;
+        let key = KeyDerivation::derive(passphrase, &salt, &encrypted.kdf())?;
+        let key = KeyDerivation::bind_to_secret(&key, secret);
+
+        let encryption = EncryptionService::new(encrypted.algorithm);
+        encryption.decrypt(encrypted, &key)
+    }
+
     /// Generate a new random passphrase
     pub fn generate_passphrase(length: usize) -> Zeroizing<String> {
         use rand::distributions::{Alphanumeric, DistString};
@@ -447,6 +644,13 @@ pub mod utils {
 mod tests {
     use super::*;
 
+    /// Argon2id cheap enough for tests
+    const TEST_ARGON2ID: KdfAlgorithm = KdfAlgorithm::Argon2id {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
     #[test]
     fn test_key_derivation() {
         let passphrase = Zeroizing::new("test-passphrase".to_string());
@@ -529,8 +733,11 @@ mod tests {
             plaintext,
             &passphrase,
             EncryptionAlgorithm::Aes256Gcm,
-            100_000,
+            KdfAlgorithm::Pbkdf2 {
+                iterations: 100_000,
+            },
         )?;
+        assert_eq!(encrypted.version, EncryptedData::CURRENT_VERSION);
 
         // Decrypt with same passphrase
         let decrypted = utils::decrypt_with_passphrase(&encrypted, &passphrase)?;
@@ -543,6 +750,97 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_keystore_bound_encryption() -> Result<()> {
+        let passphrase = Zeroizing::new("super-secret-passphrase".to_string());
+        let secret = [7u8; 32];
+        let plaintext = b"Very sensitive data";
+
+        let encrypted = utils::encrypt_with_keystore_secret(
+            plaintext,
+            &passphrase,
+            &secret,
+            EncryptionAlgorithm::Aes256Gcm,
+            TEST_ARGON2ID,
+        )?;
+        assert!(encrypted.is_keystore_bound());
+        encrypted.validate()?;
+
+        let decrypted = utils::decrypt_with_keystore_secret(&encrypted, &passphrase, &secret)?;
+        assert_eq!(plaintext, decrypted.as_slice());
+
+        // The passphrase alone, or with another secret, opens nothing
+        assert!(utils::decrypt_with_passphrase(&encrypted, &passphrase).is_err());
+        assert!(utils::decrypt_with_keystore_secret(&encrypted, &passphrase, &[8u8; 32]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_argon2id_passphrase_encryption() -> Result<()> {
+        let passphrase = Zeroizing::new("super-secret-passphrase".to_string());
+        let plaintext = b"Very sensitive data";
+
+        let encrypted = utils::encrypt_with_passphrase(
+            plaintext,
+            &passphrase,
+            EncryptionAlgorithm::Ring,
+            TEST_ARGON2ID,
+        )?;
+        assert_eq!(encrypted.kdf(), TEST_ARGON2ID);
+
+        // The KDF survives serialization and decides how the key is derived
+        let json = serde_json::to_string(&encrypted)
+            .map_err(|e| Error::serialization(e.to_string()))?;
+        let stored: EncryptedData =
+            serde_json::from_str(&json).map_err(|e| Error::serialization(e.to_string()))?;
+        let decrypted = utils::decrypt_with_passphrase(&stored, &passphrase)?;
+        assert_eq!(plaintext, decrypted.as_slice());
+
+        let wrong_passphrase = Zeroizing::new("wrong-passphrase".to_string());
+        assert!(matches!(
+            utils::decrypt_with_passphrase(&stored, &wrong_passphrase),
+            Err(Error::Encryption(_))
+        ));
+
+        // Parameters Argon2id rejects fail instead of deriving a weak key
+        let invalid = KdfAlgorithm::Argon2id {
+            m_cost: 1,
+            t_cost: 0,
+            p_cost: 1,
+        };
+        assert!(KeyDerivation::derive(&passphrase, &[0u8; 16], &invalid).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_1_data_keeps_decrypting() -> Result<()> {
+        // Written by format version 1: PBKDF2 over 1,000 iterations, no KDF field
+        let fixture = r#"{
+            "ciphertext": "ked2BUCAIs82SUKM4TN99AUR7yi2g6iukX5/kumjwssSOViJrp1CS4YaqQ==",
+            "nonce": "ZGVmZ2hpamtsbW5v",
+            "salt": "AAECAwQFBgcICQoLDA0ODw==",
+            "algorithm": "aes256-gcm",
+            "kdf_iterations": 1000,
+            "version": 1
+        }"#;
+        let encrypted: EncryptedData =
+            serde_json::from_str(fixture).map_err(|e| Error::serialization(e.to_string()))?;
+        assert_eq!(encrypted.kdf(), KdfAlgorithm::Pbkdf2 { iterations: 1_000 });
+        assert!(!encrypted.is_keystore_bound());
+
+        let passphrase = Zeroizing::new("fixture passphrase".to_string());
+        let decrypted = utils::decrypt_with_passphrase(&encrypted, &passphrase)?;
+        assert_eq!(decrypted.as_slice(), b"written by format version 1");
+
+        let wrong_passphrase = Zeroizing::new("wrong-passphrase".to_string());
+        assert!(matches!(
+            utils::decrypt_with_passphrase(&encrypted, &wrong_passphrase),
+            Err(Error::Encryption(_))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_encrypted_data_validation() {
         let valid_data = EncryptedData {
@@ -551,6 +849,8 @@ mod tests {
             salt: STANDARD.encode(b"salt"),
             algorithm: EncryptionAlgorithm::Aes256Gcm,
             kdf_iterations: 100_000,
+            kdf: None,
+            keystore_bound: false,
             version: EncryptedData::CURRENT_VERSION,
         };
 
@@ -563,6 +863,8 @@ mod tests {
             salt: STANDARD.encode(b"salt"),
             algorithm: EncryptionAlgorithm::Aes256Gcm,
             kdf_iterations: 100_000,
+            kdf: None,
+            keystore_bound: false,
             version: EncryptedData::CURRENT_VERSION,
         };
 
@@ -575,6 +877,8 @@ mod tests {
             salt: STANDARD.encode(b"salt"),
             algorithm: EncryptionAlgorithm::Aes256Gcm,
             kdf_iterations: 100_000,
+            kdf: None,
+            keystore_bound: false,
             version: 255,
         };
 
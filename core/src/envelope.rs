@@ -0,0 +1,516 @@
+//! Portable transaction envelopes and simulation reports
+//!
+//! A [`TransactionEnvelope`] carries a base64-encoded wire transaction,
+//! signed or not, with optional metadata describing it. It is the JSON form
+//! in which transactions are handed to `agent-wallet tx simulate` or posted
+//! to a service, and read back with [`TransactionEnvelope::from_json`].
+//!
+//! Offline signing moves a transaction between machines in two more
+//! envelopes. An [`UnsignedEnvelope`] carries the message built online, the
+//! keys that must sign it and the slot its blockhash expires at; an
+//! air-gapped wallet signs it with
+//! [`Wallet::sign_offline`](crate::wallet::Wallet::sign_offline) into a
+//! [`SignedEnvelope`], which is broadcast back online with
+//! [`SignedEnvelope::broadcast`].
+//!
+//! A [`SimulationReport`] combines the simulation of an envelope's
+//! transaction with its validation against the wallet's transaction policy.
+//! It prints as a human-readable report and serializes as the matching
+//! response body.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::envelope::TransactionEnvelope;
+//! use solana_sdk::{pubkey::Pubkey, system_instruction, transaction::Transaction};
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let payer = Pubkey::new_unique();
+//! let instruction = system_instruction::transfer(&payer, &Pubkey::new_unique(), 1_000);
+//! let transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
+//!
+//! let json = TransactionEnvelope::new(&transaction)?
+//!     .with_description("Pay the treasury")
+//!     .to_json()?;
+//! let envelope = TransactionEnvelope::from_json(&json)?;
+//! assert_eq!(envelope.transaction()?, transaction);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    clock::Slot, message::Message, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+
+use crate::error::{Error, Result};
+use crate::multisig;
+use crate::policy::pubkey_strings;
+use crate::rpc::DynRpcProvider;
+use crate::transaction::{SimulationResult, ValidationResult};
+use crate::types::AgentAction;
+
+/// Envelope format version written by this library
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// A wire transaction with optional metadata, as portable JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionEnvelope {
+    /// Envelope format version
+    pub version: u32,
+    /// Base64-encoded wire transaction
+    pub transaction: String,
+    /// What the transaction does, for whoever reviews it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Action the transaction was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<AgentAction>,
+    /// When the envelope was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl TransactionEnvelope {
+    /// Envelope of a transaction, signed or not
+    pub fn new(transaction: &Transaction) -> Result<Self> {
+        Ok(Self {
+            version: ENVELOPE_VERSION,
+            transaction: multisig::encode_transaction(transaction)?,
+            description: None,
+            action: None,
+            created_at: Some(Utc::now()),
+        })
+    }
+
+    /// Describe what the transaction does
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Record the action the transaction was built from
+    pub fn with_action(mut self, action: AgentAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Decode the wire transaction
+    pub fn transaction(&self) -> Result<Transaction> {
+        multisig::decode_transaction(&self.transaction)
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse an envelope, checking its version and transaction
+    pub fn from_json(json: &str) -> Result<Self> {
+        let envelope: Self = serde_json::from_str(json)?;
+        check_version(envelope.version)?;
+        envelope.transaction()?;
+        Ok(envelope)
+    }
+}
+
+/// An unsigned message to be signed on another machine
+///
+/// The message is base64 of its wire bytes, the exact bytes each signer
+/// signs. `expiry_slot` is the last slot its blockhash is expected to be
+/// valid at; it is `None` for durable nonce transactions, which do not
+/// expire.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsignedEnvelope {
+    /// Envelope format version
+    pub version: u32,
+    /// Base64-encoded message
+    pub message: String,
+    /// Keys that must sign, fee payer first
+    #[serde(with = "pubkey_strings")]
+    pub signers: Vec<Pubkey>,
+    /// Last slot the blockhash is expected to be valid at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry_slot: Option<Slot>,
+    /// What the transaction does, for whoever signs it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Action the transaction was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<AgentAction>,
+}
+
+impl UnsignedEnvelope {
+    /// Envelope of a transaction's message; its signatures are dropped
+    pub fn new(transaction: &Transaction, expiry_slot: Option<Slot>) -> Self {
+        let message = &transaction.message;
+        let required = usize::from(message.header.num_required_signatures);
+        Self {
+            version: ENVELOPE_VERSION,
+            message: STANDARD.encode(message.serialize()),
+            signers: message.account_keys[..required].to_vec(),
+            expiry_slot,
+            description: None,
+            action: None,
+        }
+    }
+
+    /// Describe what the transaction does
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Record the action the transaction was built from
+    pub fn with_action(mut self, action: AgentAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Decode the message, checking it needs exactly the listed signers
+    pub fn message(&self) -> Result<Message> {
+        let bytes = STANDARD
+            .decode(self.message.trim())
+            .map_err(|e| Error::serialization(format!("Invalid base64 message: {}", e)))?;
+        let message: Message = bincode::deserialize(&bytes)?;
+        let required = usize::from(message.header.num_required_signatures);
+        if message.account_keys.get(..required) != Some(self.signers.as_slice()) {
+            return Err(Error::validation(
+                "Envelope signers do not match the message's required signers",
+            ));
+        }
+        Ok(message)
+    }
+
+    /// The unsigned transaction
+    pub fn transaction(&self) -> Result<Transaction> {
+        Ok(Transaction::new_unsigned(self.message()?))
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse an envelope, checking its version and message
+    pub fn from_json(json: &str) -> Result<Self> {
+        let envelope: Self = serde_json::from_str(json)?;
+        check_version(envelope.version)?;
+        envelope.message()?;
+        Ok(envelope)
+    }
+}
+
+/// A transaction signed offline, ready to broadcast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedEnvelope {
+    /// Envelope format version
+    pub version: u32,
+    /// Base64-encoded wire transaction
+    pub transaction: String,
+    /// Last slot the blockhash is expected to be valid at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry_slot: Option<Slot>,
+}
+
+impl SignedEnvelope {
+    /// Envelope of a signed transaction
+    pub fn new(transaction: &Transaction, expiry_slot: Option<Slot>) -> Result<Self> {
+        Ok(Self {
+            version: ENVELOPE_VERSION,
+            transaction: multisig::encode_transaction(transaction)?,
+            expiry_slot,
+        })
+    }
+
+    /// Decode the wire transaction
+    pub fn transaction(&self) -> Result<Transaction> {
+        multisig::decode_transaction(&self.transaction)
+    }
+
+    /// Decode the transaction and verify every required signature
+    pub fn verified_transaction(&self) -> Result<Transaction> {
+        let transaction = self.transaction()?;
+        let required = usize::from(transaction.message.header.num_required_signatures);
+        if transaction.signatures.len() != required {
+            return Err(Error::validation(format!(
+                "Transaction carries {} signatures, its message requires {}",
+                transaction.signatures.len(),
+                required
+            )));
+        }
+        let missing = multisig::missing_signers(&transaction);
+        if let Some(signer) = missing.first() {
+            return Err(Error::validation(format!(
+                "Transaction is missing the signature of {}",
+                signer
+            )));
+        }
+        transaction
+            .verify()
+            .map_err(|e| Error::validation(format!("Invalid signature: {}", e)))?;
+        Ok(transaction)
+    }
+
+    /// Send the transaction, unless its blockhash has expired
+    ///
+    /// Providers that cannot report the current slot leave expiry to the
+    /// cluster.
+    pub async fn broadcast(&self, rpc: &dyn DynRpcProvider) -> Result<Signature> {
+        let transaction = self.verified_transaction()?;
+        if let Some(expiry_slot) = self.expiry_slot {
+            if let Ok(slot) = rpc.get_slot().await {
+                if slot > expiry_slot {
+                    return Err(Error::BlockhashExpired(format!(
+                        "Blockhash expired at slot {}, the cluster is at slot {}",
+                        expiry_slot, slot
+                    )));
+                }
+            }
+        }
+        rpc.send_transaction(&transaction).await
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse an envelope, checking its version and transaction
+    pub fn from_json(json: &str) -> Result<Self> {
+        let envelope: Self = serde_json::from_str(json)?;
+        check_version(envelope.version)?;
+        envelope.transaction()?;
+        Ok(envelope)
+    }
+}
+
+/// Refuse envelope versions this library does not know
+fn check_version(version: u32) -> Result<()> {
+    if version == 0 || version > ENVELOPE_VERSION {
+        return Err(Error::serialization(format!(
+            "Unsupported transaction envelope version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+/// Simulation and validation of one transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationReport {
+    /// Outcome of the simulation
+    pub simulation: SimulationResult,
+    /// Outcome of validation against the transaction policy
+    pub validation: ValidationResult,
+}
+
+impl SimulationReport {
+    /// Report of a simulation and the matching validation
+    pub fn new(simulation: SimulationResult, validation: ValidationResult) -> Self {
+        Self {
+            simulation,
+            validation,
+        }
+    }
+
+    /// Whether the transaction both simulates and validates
+    pub fn is_ok(&self) -> bool {
+        self.simulation.success && self.validation.is_valid
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let simulation = &self.simulation;
+        match &simulation.error {
+            None => writeln!(f, "Simulation succeeded")?,
+            Some(error) => writeln!(f, "Simulation failed: {}", error)?,
+        }
+        match simulation.compute_units_consumed {
+            Some(units) => writeln!(f, "  Compute units: {}", units)?,
+            None => writeln!(f, "  Compute units: unknown")?,
+        }
+        writeln!(f, "  Fee:           {} lamports", simulation.fee)?;
+        for change in &simulation.balance_changes {
+            writeln!(f, "  {}: {:+} lamports", change.account, change.delta())?;
+        }
+        for line in &simulation.logs {
+            writeln!(f, "  log: {}", line)?;
+        }
+
+        let validation = &self.validation;
+        let verdict = if validation.is_valid {
+            "passed"
+        } else {
+            "failed"
+        };
+        writeln!(
+            f,
+            "Validation {} ({} bytes)",
+            verdict, validation.transaction_size
+        )?;
+        for error in &validation.errors {
+            writeln!(f, "  Error: {}", error)?;
+        }
+        if let (false, Some(required)) = (validation.is_valid, &validation.required_breakdown) {
+            writeln!(
+                f,
+                "  Requires:      {} lamports ({} transfers + {} fees + {} rent + {} reserve)",
+                required.total(),
+                required.transfers,
+                required.fees,
+                required.rent,
+                required.reserve
+            )?;
+        }
+        for warning in &validation.warnings {
+            writeln!(f, "  Warning: {}", warning)?;
+        }
+        if validation.missing_signatures > 0 {
+            writeln!(f, "  Missing signatures: {}", validation.missing_signatures)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preview::BalanceChange;
+    use crate::sol::Lamports;
+    use crate::transaction::RequiredBreakdown;
+    use solana_sdk::{pubkey::Pubkey, system_instruction};
+
+    #[test]
+    fn test_envelope_round_trip() -> Result<()> {
+        let payer = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let instruction = system_instruction::transfer(&payer, &to, 1_000);
+        let transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
+        let action = AgentAction::TransferSol {
+            to,
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+
+        let envelope = TransactionEnvelope::new(&transaction)?
+            .with_description("Pay")
+            .with_action(action);
+        let parsed = TransactionEnvelope::from_json(&envelope.to_json()?)?;
+        assert_eq!(parsed.transaction()?, transaction);
+        assert_eq!(parsed.description.as_deref(), Some("Pay"));
+        assert!(matches!(
+            parsed.action,
+            Some(AgentAction::TransferSol {
+                amount: Lamports(1_000),
+                ..
+            })
+        ));
+        assert_eq!(parsed.created_at, envelope.created_at);
+
+        // Metadata is optional
+        let bare = format!(
+            r#"{{"version":1,"transaction":"{}"}}"#,
+            envelope.transaction
+        );
+        assert_eq!(
+            TransactionEnvelope::from_json(&bare)?.transaction()?,
+            transaction
+        );
+
+        // Unknown versions and undecodable transactions are refused
+        let future = format!(
+            r#"{{"version":2,"transaction":"{}"}}"#,
+            envelope.transaction
+        );
+        assert!(TransactionEnvelope::from_json(&future).is_err());
+        assert!(TransactionEnvelope::from_json(r#"{"version":1,"transaction":"AAAA"}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_rendering() {
+        let simulation = SimulationResult {
+            success: true,
+            logs: vec![
+                "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+                "Program 11111111111111111111111111111111 success".to_string(),
+            ],
+            compute_units_consumed: Some(150),
+            return_data: None,
+            error: None,
+            accounts_modified: Vec::new(),
+            balance_changes: vec![
+                BalanceChange {
+                    account: Pubkey::new_from_array([1; 32]),
+                    pre_lamports: 2_000_000,
+                    post_lamports: 995_000,
+                },
+                BalanceChange {
+                    account: Pubkey::new_from_array([2; 32]),
+                    pre_lamports: 0,
+                    post_lamports: 1_000_000,
+                },
+            ],
+            fee: 5_000,
+        };
+        let mut validation = ValidationResult::valid();
+        validation.transaction_size = 215;
+        validation.missing_signatures = 1;
+        validation.add_warning("Fee of 5000 lamports exceeds the 1000 lamports sent".to_string());
+
+        let report = SimulationReport::new(simulation, validation);
+        assert!(report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "\
+Simulation succeeded
+  Compute units: 150
+  Fee:           5000 lamports
+  4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi: -1005000 lamports
+  8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR: +1000000 lamports
+  log: Program 11111111111111111111111111111111 invoke [1]
+  log: Program 11111111111111111111111111111111 success
+Validation passed (215 bytes)
+  Warning: Fee of 5000 lamports exceeds the 1000 lamports sent
+  Missing signatures: 1
+"
+        );
+
+        let mut failed = report.clone();
+        failed.simulation.success = false;
+        failed.simulation.error = Some("InsufficientFundsForFee".to_string());
+        failed.simulation.compute_units_consumed = None;
+        failed.simulation.balance_changes.clear();
+        failed.simulation.logs.clear();
+        failed.validation = ValidationResult::invalid(vec!["Too big".to_string()]);
+        failed.validation.required_breakdown = Some(RequiredBreakdown {
+            transfers: 1_000_000,
+            fees: 5_000,
+            rent: 2_039_280,
+            reserve: 0,
+        });
+        assert!(!failed.is_ok());
+        assert_eq!(
+            failed.to_string(),
+            "\
+Simulation failed: InsufficientFundsForFee
+  Compute units: unknown
+  Fee:           5000 lamports
+Validation failed (0 bytes)
+  Error: Too big
+  Requires:      3044280 lamports (1000000 transfers + 5000 fees + 2039280 rent + 0 reserve)
+"
+        );
+    }
+}
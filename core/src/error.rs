@@ -6,6 +6,8 @@
 
 use std::fmt;
 
+use crate::retry::RpcErrorClass;
+
 /// Result type alias for the wallet operations
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -44,10 +46,40 @@ pub enum Error {
     #[error("Transaction validation failed: {0}")]
     TransactionValidation(String),
 
+    /// The transaction's blockhash expired before the cluster accepted it
+    ///
+    /// Nothing was executed; signing again with a fresh blockhash recovers.
+    #[error("Blockhash expired: {0}")]
+    BlockhashExpired(String),
+
+    /// Transaction was sent but did not reach the requested commitment in time
+    #[error("Transaction {signature} not confirmed in time (last status: {last_status:?})")]
+    ConfirmationTimeout {
+        signature: solana_sdk::signature::Signature,
+        last_status: Option<solana_transaction_status::TransactionConfirmationStatus>,
+    },
+
     /// Insufficient funds for transaction
     #[error("Insufficient funds: required {required}, available {available}")]
     InsufficientFunds { required: u64, available: u64 },
 
+    /// OS keychain entry a wallet file is bound to is missing or unreachable
+    #[error("Keystore unavailable: {0}")]
+    KeystoreUnavailable(String),
+
+    /// Transfer would take the balance below the minimum SOL reserve
+    #[error("Transfer would leave {post_balance} lamports, below the {reserve} lamport reserve")]
+    ReserveBreached { reserve: u64, post_balance: u64 },
+
+    /// Stake is still deactivating and cannot be withdrawn before the epoch ends
+    #[error("Stake account {stake_account} is cooling down ({active} lamports still active)")]
+    StakeCooldown {
+        /// The deactivating stake account
+        stake_account: solana_sdk::pubkey::Pubkey,
+        /// Lamports that have not finished deactivating
+        active: u64,
+    },
+
     /// Wallet creation/loading error
     #[error("Wallet error: {0}")]
     Wallet(String),
@@ -56,6 +88,17 @@ pub enum Error {
     #[error("Storage error: {0}")]
     Storage(String),
 
+    /// The wallet file was written by someone else since it was loaded
+    #[error("Wallet '{name}' changed on disk (loaded revision {expected}, found {found})")]
+    StorageConflict {
+        /// Wallet name
+        name: String,
+        /// Revision the wallet had when it was loaded or last saved
+        expected: u64,
+        /// Revision currently on disk
+        found: u64,
+    },
+
     /// Wallet not found
     #[error("Wallet not found: {0}")]
     WalletNotFound(String),
@@ -111,6 +154,10 @@ pub enum Error {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// Address book failed its integrity check; whitelist enforcement fails closed
+    #[error("Address book integrity check failed: {0}")]
+    WhitelistIntegrity(String),
+
     /// Permission denied
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
@@ -134,6 +181,13 @@ pub enum Error {
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    /// A signer returned a signature that does not verify against its key
+    #[error("Signature does not verify against the expected signer {expected}")]
+    SignerMismatch {
+        /// Public key the signature was expected from
+        expected: solana_sdk::pubkey::Pubkey,
+    },
+
     /// IO error
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -267,17 +321,30 @@ impl Error {
         matches!(self, Self::RateLimitExceeded(_))
     }
 
-    /// Check if error is recoverable (can retry)
-    pub fn is_recoverable(&self) -> bool {
+    /// Retry class of the error
+    ///
+    /// Solana client errors are classified by [`RpcErrorClass::of`]. Network
+    /// failures, timeouts and exhausted RPC retries are retryable, rate
+    /// limits are rate limited and everything else is fatal.
+    pub fn classify(&self) -> RpcErrorClass {
         match self {
+            Self::SolanaRpc(e) => RpcErrorClass::of(e),
+            Self::RateLimitExceeded(_) => RpcErrorClass::RateLimited { retry_after: None },
+            Self::BlockhashExpired(_) => RpcErrorClass::BlockhashExpired,
             Self::Network(_)
             | Self::Timeout(_)
             | Self::Rpc(_)
-            | Self::SolanaRpc(_)
-            | Self::RateLimitExceeded(_) => true,
-            _ => false,
+            | Self::ConfirmationTimeout { .. } => RpcErrorClass::Retryable,
+            _ => RpcErrorClass::Fatal,
         }
     }
+
+    /// Check if error is recoverable (can retry)
+    ///
+    /// An expired blockhash is recoverable by signing again.
+    pub fn is_recoverable(&self) -> bool {
+        self.classify() != RpcErrorClass::Fatal
+    }
 }
 
 impl From<solana_sdk::signature::ParseSignatureError> for Error {
@@ -303,3 +370,9 @@ impl From<base64::DecodeError> for Error {
         Self::Serialization(format!("Base64 decoding error: {}", err))
     }
 }
+
+impl From<prometheus::Error> for Error {
+    fn from(err: prometheus::Error) -> Self {
+        Self::Config(format!("Metrics error: {}", err))
+    }
+}
@@ -0,0 +1,348 @@
+//! Priority fee escalation for congested networks
+//!
+//! A transaction priced too low for the current congestion is dropped
+//! without ever failing. [`Wallet::send_with_escalation`] sends it, waits,
+//! and when it has not landed rebuilds the same instructions at a higher
+//! compute unit price, following an [`EscalationPolicy`] and bounded by
+//! [`TransactionOptions::max_priority_fee_lamports`]. A transaction can
+//! land until its blockhash expires, so without a durable nonce the next
+//! broadcast waits for the previous blockhash to expire and for every
+//! earlier signature to stay unseen; a transaction that lands late is never
+//! paid for twice by this path.
+//!
+//! [`Wallet::send_with_escalation`]: crate::Wallet::send_with_escalation
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use agent_wallet_core::escalation::EscalationPolicy;
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::transaction::TransactionOptions;
+//! use agent_wallet_core::types::AgentAction;
+//! use agent_wallet_core::Wallet;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(wallet: &Wallet, to: Pubkey) -> agent_wallet_core::Result<()> {
+//! let action = AgentAction::TransferSol {
+//!     to,
+//!     amount: Lamports::new(1_000_000),
+//!     memo: None,
+//! };
+//! let options = TransactionOptions {
+//!     max_priority_fee_lamports: Some(50_000),
+//!     ..Default::default()
+//! };
+//! let policy = EscalationPolicy::new(vec![1, 2, 5]).with_wait(Duration::from_secs(10));
+//!
+//! let report = wallet.send_with_escalation(&action, &options, &policy).await?;
+//! println!("{} landed after {} broadcasts", report.landed, report.signatures.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, message::Message, signature::Signature,
+};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::transaction::TransactionOptions;
+
+/// Compute unit limit assumed when the options do not set one
+const DEFAULT_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+/// When and how much to raise the compute unit price
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscalationPolicy {
+    /// Multiplier applied to the starting price, one per attempt
+    pub multipliers: Vec<u64>,
+    /// How long each broadcast may take to land before the next one
+    pub wait: Duration,
+    /// Interval between signature status polls
+    pub poll_interval: Duration,
+    /// How long to wait for the previous blockhash to expire before a
+    /// rebroadcast; escalation stops if it has not
+    pub expiry_wait: Duration,
+    /// Starting price in micro-lamports when the network reports no fees
+    pub min_compute_unit_price: u64,
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self {
+            multipliers: vec![1, 2, 5],
+            wait: Duration::from_secs(15),
+            poll_interval: Duration::from_millis(500),
+            // A blockhash stays valid for 150 blocks, about a minute
+            expiry_wait: Duration::from_secs(90),
+            min_compute_unit_price: 1_000,
+        }
+    }
+}
+
+impl EscalationPolicy {
+    /// Policy following the given multiplier schedule
+    pub fn new(multipliers: Vec<u64>) -> Self {
+        Self {
+            multipliers,
+            ..Default::default()
+        }
+    }
+
+    /// Set how long each broadcast may take to land
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Set the interval between signature status polls
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Set how long to wait for the previous blockhash to expire
+    pub fn with_expiry_wait(mut self, expiry_wait: Duration) -> Self {
+        self.expiry_wait = expiry_wait;
+        self
+    }
+
+    /// Maximum number of broadcasts
+    pub fn attempts(&self) -> usize {
+        self.multipliers.len()
+    }
+
+    /// Compute unit price of an attempt, capped by the options' fee bound
+    pub fn price_for(&self, attempt: usize, base: u64, options: &TransactionOptions) -> u64 {
+        let multiplier = self.multipliers.get(attempt).copied().unwrap_or(1);
+        let price = base.saturating_mul(multiplier);
+        match max_compute_unit_price(options) {
+            Some(cap) => price.min(cap),
+            None => price,
+        }
+    }
+}
+
+/// Highest compute unit price keeping the priority fee within its bound
+///
+/// `None` when the options do not bound the priority fee.
+pub fn max_compute_unit_price(options: &TransactionOptions) -> Option<u64> {
+    let max_fee = options.max_priority_fee_lamports? as u128;
+    let limit = options
+        .compute_unit_limit
+        .map_or(DEFAULT_COMPUTE_UNIT_LIMIT, u64::from)
+        .max(1) as u128;
+    let price = max_fee * 1_000_000 / limit;
+    Some(u64::try_from(price).unwrap_or(u64::MAX))
+}
+
+/// Outcome of a send that may have been rebroadcast
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscalationReport {
+    /// Every signature broadcast, in order
+    pub signatures: Vec<Signature>,
+    /// Compute unit price of each broadcast, in micro-lamports
+    pub compute_unit_prices: Vec<u64>,
+    /// The signature that landed
+    pub landed: Signature,
+}
+
+impl EscalationReport {
+    /// Broadcasts that were replaced by a later one or landed too late
+    pub fn superseded(&self) -> impl Iterator<Item = &Signature> {
+        self.signatures.iter().filter(|s| **s != self.landed)
+    }
+}
+
+/// How waiting for an earlier broadcast's blockhash to expire ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// One of the earlier signatures was processed
+    Landed(Signature),
+    /// The blockhash expired with none of them seen, so none can land
+    Expired,
+    /// The blockhash was still valid when the wait ran out
+    Pending,
+}
+
+/// Starting compute unit price for a message, from recent network fees
+///
+/// Takes the median of the nonzero fees recently paid to write-lock the
+/// message's accounts, and the policy's minimum when there are none or the
+/// query fails.
+pub async fn starting_price(
+    provider: &dyn DynRpcProvider,
+    message: &Message,
+    policy: &EscalationPolicy,
+) -> u64 {
+    let mut fees: Vec<u64> = match provider
+        .get_recent_prioritization_fees(&message.account_keys)
+        .await
+    {
+        Ok(fees) => fees
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .filter(|fee| *fee > 0)
+            .collect(),
+        Err(e) => {
+            debug!("Prioritization fee query failed, using the minimum: {}", e);
+            Vec::new()
+        }
+    };
+    fees.sort_unstable();
+    fees.get(fees.len() / 2)
+        .copied()
+        .unwrap_or(0)
+        .max(policy.min_compute_unit_price)
+}
+
+/// First of `signatures` the network has seen, at any commitment
+///
+/// A transaction that was processed, even one that failed, counts: sending
+/// a replacement for it could execute the action twice.
+pub async fn find_landed(
+    provider: &dyn DynRpcProvider,
+    signatures: &[Signature],
+) -> Result<Option<Signature>> {
+    if signatures.is_empty() {
+        return Ok(None);
+    }
+    let statuses = provider.get_signature_statuses(signatures).await?;
+    Ok(signatures
+        .iter()
+        .zip(statuses)
+        .find_map(|(signature, status)| status.map(|_| *signature)))
+}
+
+/// Poll until `blockhash` expires or one of `signatures` is processed
+///
+/// The blockhash is checked before the statuses, so [`Expiry::Expired`]
+/// means no earlier transaction can still land. A provider that cannot
+/// tell whether the blockhash expired never reports it as expired.
+pub async fn wait_for_expiry(
+    provider: &dyn DynRpcProvider,
+    signatures: &[Signature],
+    blockhash: &Hash,
+    wait: Duration,
+    interval: Duration,
+) -> Result<Expiry> {
+    let deadline = tokio::time::Instant::now() + wait;
+
+    loop {
+        let expired = match provider.is_blockhash_valid(blockhash).await {
+            Ok(valid) => !valid,
+            Err(e) => {
+                debug!("Blockhash validity check failed: {}", e);
+                false
+            }
+        };
+        if let Some(signature) = find_landed(provider, signatures).await? {
+            return Ok(Expiry::Landed(signature));
+        }
+        if expired {
+            return Ok(Expiry::Expired);
+        }
+
+        if tokio::time::Instant::now() + interval > deadline {
+            return Ok(Expiry::Pending);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Poll until one of `signatures` reaches `commitment` or fails
+///
+/// Returns `None` if none did within `wait`. Transient RPC errors are
+/// retried until then.
+pub async fn wait_for_any(
+    provider: &dyn DynRpcProvider,
+    signatures: &[Signature],
+    commitment: CommitmentConfig,
+    wait: Duration,
+    interval: Duration,
+) -> Result<Option<Signature>> {
+    if signatures.is_empty() {
+        return Err(Error::validation("No signatures to wait for"));
+    }
+    let deadline = tokio::time::Instant::now() + wait;
+
+    loop {
+        match provider.get_signature_statuses(signatures).await {
+            Ok(statuses) => {
+                let landed = signatures
+                    .iter()
+                    .zip(statuses)
+                    .find_map(|(signature, status)| {
+                        status
+                            .filter(|s| s.err.is_some() || s.satisfies_commitment(commitment))
+                            .map(|_| *signature)
+                    });
+                if landed.is_some() {
+                    return Ok(landed);
+                }
+            }
+            Err(e) => debug!("Signature status poll failed: {}", e),
+        }
+
+        if tokio::time::Instant::now() + interval > deadline {
+            return Ok(None);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+    use solana_sdk::{pubkey::Pubkey, system_instruction};
+
+    #[test]
+    fn test_price_schedule_is_capped_by_max_fee() {
+        let policy = EscalationPolicy::default();
+        let unbounded = TransactionOptions::default();
+        let prices: Vec<u64> = (0..policy.attempts())
+            .map(|attempt| policy.price_for(attempt, 10_000, &unbounded))
+            .collect();
+        assert_eq!(prices, vec![10_000, 20_000, 50_000]);
+
+        // 5_000 lamports over 200_000 units allows 25_000 micro-lamports per unit
+        let bounded = TransactionOptions {
+            max_priority_fee_lamports: Some(5_000),
+            ..Default::default()
+        };
+        assert_eq!(max_compute_unit_price(&bounded), Some(25_000));
+        let prices: Vec<u64> = (0..policy.attempts())
+            .map(|attempt| policy.price_for(attempt, 10_000, &bounded))
+            .collect();
+        assert_eq!(prices, vec![10_000, 20_000, 25_000]);
+    }
+
+    #[tokio::test]
+    async fn test_starting_price_uses_median_recent_fee() -> Result<()> {
+        let payer = Pubkey::new_unique();
+        let message = Message::new(
+            &[system_instruction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer),
+        );
+        let policy = EscalationPolicy::default();
+        let rpc = MockRpc::new();
+
+        // An idle network falls back to the minimum
+        assert_eq!(starting_price(&rpc, &message, &policy).await, 1_000);
+
+        // Slots without priority fees do not drag the median down
+        rpc.set_prioritization_fees(vec![0, 0, 3_000, 8_000, 0, 5_000]);
+        assert_eq!(starting_price(&rpc, &message, &policy).await, 5_000);
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! Sponsored transactions paid by a designated fee wallet
+//!
+//! A transaction's fee is paid by the first account of its message, which
+//! must also sign first. Setting [`TransactionOptions::fee_payer`] builds
+//! the message that way; a [`FeePayerService`] supplies the sponsor's
+//! signature, whether its key is a second wallet loaded in this process, a
+//! raw [`SecureKeypair`] or a [`RemoteSigner`](crate::signer::RemoteSigner).
+//! [`Wallet::sign_and_send_with_fee_payer`] signs with both and sends.
+//!
+//! The transaction policy only lets agents with
+//! `agent.fee_payer_permission` (Administrator by default) have someone
+//! else pay, so a deployment decides which agents may be sponsored.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::fee_payer::FeePayerService;
+//! use agent_wallet_core::{SecureKeypair, TransactionOptions, Wallet};
+//! use solana_sdk::{message::Message, pubkey::Pubkey, system_instruction, transaction::Transaction};
+//!
+//! # async fn example(wallet: Wallet, sponsor_key: SecureKeypair) -> agent_wallet_core::Result<()> {
+//! let sponsor = FeePayerService::from_keypair(sponsor_key);
+//! let transfer = system_instruction::transfer(&wallet.public_key(), &Pubkey::new_unique(), 1_000);
+//! let mut transaction =
+//!     Transaction::new_unsigned(Message::new(&[transfer], Some(&sponsor.pubkey())));
+//! let signature = wallet.sign_and_send_with_fee_payer(&mut transaction, &sponsor).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use crate::error::Result;
+use crate::keypair::SecureKeypair;
+use crate::signer::{DynTransactionSigner, TransactionSigner};
+use crate::transaction::TransactionOptions;
+use crate::wallet::Wallet;
+
+/// Signer of the fees of transactions it sponsors
+#[derive(Clone)]
+pub struct FeePayerService {
+    pubkey: Pubkey,
+    signer: Arc<dyn DynTransactionSigner>,
+}
+
+impl FeePayerService {
+    /// Sponsor signing with any signer, remote ones included
+    pub async fn new(signer: Arc<dyn DynTransactionSigner>) -> Self {
+        Self {
+            pubkey: signer.pubkey().await,
+            signer,
+        }
+    }
+
+    /// Sponsor signing with a keypair held in memory
+    pub fn from_keypair(keypair: SecureKeypair) -> Self {
+        Self {
+            pubkey: keypair.public_key(),
+            signer: Arc::new(keypair),
+        }
+    }
+
+    /// Sponsor signing with another wallet loaded in this process
+    pub fn from_wallet(wallet: &Wallet) -> Self {
+        Self {
+            pubkey: wallet.public_key(),
+            signer: wallet.signer(),
+        }
+    }
+
+    /// Address paying the fees
+    pub fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    /// `options` with this sponsor as the fee payer
+    pub fn sponsor(&self, options: &TransactionOptions) -> TransactionOptions {
+        TransactionOptions {
+            fee_payer: Some(self.pubkey),
+            ..options.clone()
+        }
+    }
+}
+
+impl TransactionSigner for FeePayerService {
+    fn pubkey(&self) -> impl Future<Output = Pubkey> + Send {
+        std::future::ready(self.pubkey)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> impl Future<Output = Result<Signature>> + Send {
+        self.signer.sign_message(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::sign_transaction;
+    use crate::sol::Lamports;
+    use crate::transaction::TransactionBuilder;
+    use crate::types::{AgentAction, AgentContext};
+    use solana_sdk::hash::Hash;
+
+    #[tokio::test]
+    async fn test_sponsor_signs_first() -> Result<()> {
+        let owner = SecureKeypair::generate();
+        let sponsor = FeePayerService::from_keypair(SecureKeypair::generate());
+        let context = AgentContext::new(owner.public_key());
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+
+        let options = sponsor.sponsor(&TransactionOptions::default());
+        let mut transaction = TransactionBuilder::new().build_unsigned(
+            &action,
+            &context,
+            &options,
+            Hash::new_unique(),
+        )?;
+        assert_eq!(
+            &transaction.message.account_keys[..2],
+            &[sponsor.pubkey(), owner.public_key()]
+        );
+
+        // Either order of signing fills the sponsor's slot first
+        let owner_signature = sign_transaction(&mut transaction, &owner).await?;
+        let sponsor_signature = sign_transaction(&mut transaction, &sponsor).await?;
+        assert_eq!(
+            transaction.signatures,
+            vec![sponsor_signature, owner_signature]
+        );
+        assert!(transaction.verify().is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+//! Priority fee and compute unit limit estimation
+//!
+//! With [`PriorityFeeStrategy::Auto`] the wallet prices a transaction from
+//! the network instead of taking the compute unit price and limit from
+//! [`TransactionOptions`]. A [`FeeEstimator`] pays a percentile (p75 by
+//! default) of the prioritization fees recently paid to lock the accounts
+//! the transaction touches, and sizes the compute unit limit from the units
+//! a simulation consumed plus a safety margin instead of reserving the
+//! blanket 200k units. Both are bounded by
+//! [`TransactionOptions::max_total_fee_lamports`]: when fees spike the price
+//! is lowered to fit rather than the wallet paying whatever the market asks.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::fees::{FeeEstimator, PriorityFeeStrategy};
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::transaction::TransactionOptions;
+//! use agent_wallet_core::types::AgentAction;
+//! use agent_wallet_core::Wallet;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(wallet: &Wallet, to: Pubkey) -> agent_wallet_core::Result<()> {
+//! let action = AgentAction::TransferSol {
+//!     to,
+//!     amount: Lamports::new(1_000_000),
+//!     memo: None,
+//! };
+//! let options = TransactionOptions {
+//!     priority_fee_strategy: PriorityFeeStrategy::Auto(FeeEstimator::new().with_percentile(90)),
+//!     max_total_fee_lamports: Some(50_000),
+//!     ..Default::default()
+//! };
+//! wallet.execute_action(&action, &options).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use solana_sdk::{hash::Hash, transaction::Transaction};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::transaction::TransactionOptions;
+
+/// Base fee charged per signature, in lamports
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Most compute units a transaction may request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Micro-lamports in a lamport, the unit of compute unit prices
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// How the compute unit price and limit of a transaction are chosen
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PriorityFeeStrategy {
+    /// Use the price and limit set in the options
+    #[default]
+    Fixed,
+    /// Estimate both from recent network fees and a simulation
+    Auto(FeeEstimator),
+}
+
+impl PriorityFeeStrategy {
+    /// Automatic estimation with the default estimator
+    pub fn auto() -> Self {
+        Self::Auto(FeeEstimator::default())
+    }
+}
+
+/// Chooses compute unit prices and limits from network data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeEstimator {
+    /// Percentile of recent fees to pay, 0 to 100
+    pub percentile: u8,
+    /// Compute units reserved on top of the simulated usage, in percent
+    pub margin_percent: u32,
+    /// Fewest compute units ever requested
+    pub min_compute_unit_limit: u32,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        Self {
+            percentile: 75,
+            margin_percent: 10,
+            min_compute_unit_limit: 1_000,
+        }
+    }
+}
+
+/// Compute unit price and limit chosen for a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// Price in micro-lamports per compute unit
+    pub compute_unit_price: u64,
+    /// Compute units requested
+    pub compute_unit_limit: u32,
+}
+
+impl FeeEstimate {
+    /// Priority fee the estimate costs, in lamports, rounded up as the runtime does
+    pub fn priority_fee_lamports(&self) -> u64 {
+        let micro_lamports =
+            u128::from(self.compute_unit_price) * u128::from(self.compute_unit_limit);
+        let lamports = micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT);
+        u64::try_from(lamports).unwrap_or(u64::MAX)
+    }
+
+    /// Options fixed to this estimate's price and limit
+    pub fn apply(&self, options: &TransactionOptions) -> TransactionOptions {
+        TransactionOptions {
+            compute_unit_price: Some(self.compute_unit_price),
+            compute_unit_limit: Some(self.compute_unit_limit),
+            priority_fee: None,
+            priority_fee_strategy: PriorityFeeStrategy::Fixed,
+            ..options.clone()
+        }
+    }
+}
+
+impl FeeEstimator {
+    /// Estimator paying the 75th percentile with a 10% compute margin
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the percentile of recent fees to pay, capped at 100
+    pub fn with_percentile(mut self, percentile: u8) -> Self {
+        self.percentile = percentile.min(100);
+        self
+    }
+
+    /// Set the compute units reserved on top of the simulated usage, in percent
+    pub fn with_margin_percent(mut self, margin_percent: u32) -> Self {
+        self.margin_percent = margin_percent;
+        self
+    }
+
+    /// Fee at the estimator's percentile of `fees`, by nearest rank
+    ///
+    /// Slots without competition report 0 and count as samples. Returns 0
+    /// when there are no samples.
+    pub fn price_from_samples(&self, fees: &[u64]) -> u64 {
+        if fees.is_empty() {
+            return 0;
+        }
+        let mut sorted = fees.to_vec();
+        sorted.sort_unstable();
+        let percentile = usize::from(self.percentile.min(100));
+        let rank = (percentile * sorted.len()).div_ceil(100).max(1);
+        sorted[rank - 1]
+    }
+
+    /// Compute unit limit covering `units_consumed` plus the safety margin
+    pub fn limit_from_units(&self, units_consumed: u64) -> u32 {
+        let with_margin =
+            u128::from(units_consumed) * u128::from(100 + u64::from(self.margin_percent)) / 100;
+        let limit = u32::try_from(with_margin).unwrap_or(MAX_COMPUTE_UNIT_LIMIT);
+        limit.clamp(
+            self.min_compute_unit_limit.min(MAX_COMPUTE_UNIT_LIMIT),
+            MAX_COMPUTE_UNIT_LIMIT,
+        )
+    }
+
+    /// Choose a price and limit from fee samples and simulated usage
+    ///
+    /// Without a simulated usage the options' limit is kept. The price is
+    /// lowered until the fees of `signatures` signatures plus the priority
+    /// fee fit `options.max_total_fee_lamports`; when the signature fees
+    /// alone exceed it, fails with `Error::LimitExceeded`.
+    pub fn estimate(
+        &self,
+        fees: &[u64],
+        units_consumed: Option<u64>,
+        signatures: usize,
+        options: &TransactionOptions,
+    ) -> Result<FeeEstimate> {
+        let compute_unit_limit = match units_consumed {
+            Some(units) => self.limit_from_units(units),
+            None => options
+                .compute_unit_limit
+                .unwrap_or(MAX_COMPUTE_UNIT_LIMIT)
+                .min(MAX_COMPUTE_UNIT_LIMIT),
+        };
+        let mut compute_unit_price = self.price_from_samples(fees);
+
+        if let Some(max_total) = options.max_total_fee_lamports {
+            let base_fee = LAMPORTS_PER_SIGNATURE.saturating_mul(signatures as u64);
+            let budget = max_total.checked_sub(base_fee).ok_or_else(|| {
+                Error::LimitExceeded(format!(
+                    "Signature fees of {} lamports exceed the {} lamport fee cap",
+                    base_fee, max_total
+                ))
+            })?;
+            let max_price = u128::from(budget) * MICRO_LAMPORTS_PER_LAMPORT
+                / u128::from(compute_unit_limit.max(1));
+            let max_price = u64::try_from(max_price).unwrap_or(u64::MAX);
+            if compute_unit_price > max_price {
+                debug!(
+                    "Capping compute unit price {} at {} to stay within {} lamports",
+                    compute_unit_price, max_price, max_total
+                );
+                compute_unit_price = max_price;
+            }
+        }
+
+        Ok(FeeEstimate {
+            compute_unit_price,
+            compute_unit_limit,
+        })
+    }
+
+    /// Estimate the price and limit of `transaction` from the network
+    ///
+    /// Fees are sampled for the accounts the transaction touches; a failed
+    /// fee query counts as no samples. The transaction is simulated as built,
+    /// with a recent blockhash if it has none yet, and fails with
+    /// `Error::TransactionSimulation` if it would fail.
+    pub async fn estimate_transaction(
+        &self,
+        provider: &dyn DynRpcProvider,
+        transaction: &Transaction,
+        options: &TransactionOptions,
+    ) -> Result<FeeEstimate> {
+        let fees: Vec<u64> = match provider
+            .get_recent_prioritization_fees(&transaction.message.account_keys)
+            .await
+        {
+            Ok(fees) => fees.into_iter().map(|fee| fee.prioritization_fee).collect(),
+            Err(e) => {
+                debug!(
+                    "Prioritization fee query failed, estimating without fees: {}",
+                    e
+                );
+                Vec::new()
+            }
+        };
+
+        let mut simulated = transaction.clone();
+        if simulated.message.recent_blockhash == Hash::default() {
+            simulated.message.recent_blockhash = provider.get_latest_blockhash().await?;
+        }
+        let simulation = provider.simulate_transaction(&simulated).await?;
+        if let Some(err) = simulation.err {
+            return Err(Error::TransactionSimulation(format!(
+                "Simulation for fee estimation failed: {}",
+                err
+            )));
+        }
+
+        let signatures = usize::from(transaction.message.header.num_required_signatures);
+        self.estimate(&fees, simulation.units_consumed, signatures, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_sdk::{message::Message, pubkey::Pubkey, system_instruction};
+
+    fn simulation(units_consumed: Option<u64>) -> RpcSimulateTransactionResult {
+        RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(Vec::new()),
+            accounts: None,
+            units_consumed,
+            return_data: None,
+            inner_instructions: None,
+        }
+    }
+
+    #[test]
+    fn test_percentile_of_fee_samples() {
+        let fees = [0, 100, 0, 5_000, 300, 200, 0, 10_000];
+        let estimator = FeeEstimator::new();
+        // Sorted: 0 0 0 100 200 300 5000 10000; rank 6 of 8
+        assert_eq!(estimator.price_from_samples(&fees), 300);
+        assert_eq!(
+            estimator
+                .clone()
+                .with_percentile(50)
+                .price_from_samples(&fees),
+            100
+        );
+        assert_eq!(
+            estimator
+                .clone()
+                .with_percentile(100)
+                .price_from_samples(&fees),
+            10_000
+        );
+        assert_eq!(
+            estimator
+                .clone()
+                .with_percentile(0)
+                .price_from_samples(&fees),
+            0
+        );
+        assert_eq!(estimator.price_from_samples(&[]), 0);
+        assert_eq!(estimator.price_from_samples(&[42]), 42);
+    }
+
+    #[test]
+    fn test_limit_follows_simulated_units() {
+        let estimator = FeeEstimator::new();
+        assert_eq!(estimator.limit_from_units(450), 1_000);
+        assert_eq!(estimator.limit_from_units(30_000), 33_000);
+        assert_eq!(
+            estimator.limit_from_units(u64::from(MAX_COMPUTE_UNIT_LIMIT)),
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+        assert_eq!(
+            estimator.with_margin_percent(50).limit_from_units(30_000),
+            45_000
+        );
+    }
+
+    #[test]
+    fn test_fee_cap_lowers_the_price() -> Result<()> {
+        let estimator = FeeEstimator::new();
+        let spike = [2_000_000; 10];
+
+        // Uncapped: the spike is paid in full
+        let uncapped =
+            estimator.estimate(&spike, Some(30_000), 1, &TransactionOptions::default())?;
+        assert_eq!(
+            uncapped,
+            FeeEstimate {
+                compute_unit_price: 2_000_000,
+                compute_unit_limit: 33_000,
+            }
+        );
+        assert_eq!(uncapped.priority_fee_lamports(), 66_000);
+
+        // Capped at 20k lamports: 15k of priority fee over 33k units
+        let options = TransactionOptions {
+            max_total_fee_lamports: Some(20_000),
+            ..Default::default()
+        };
+        let capped = estimator.estimate(&spike, Some(30_000), 1, &options)?;
+        assert_eq!(capped.compute_unit_price, 454_545);
+        assert!(LAMPORTS_PER_SIGNATURE + capped.priority_fee_lamports() <= 20_000);
+
+        // A quiet market is not raised to the cap
+        let quiet = estimator.estimate(&[0, 10, 20], Some(30_000), 1, &options)?;
+        assert_eq!(quiet.compute_unit_price, 20);
+
+        // Signature fees alone over the cap
+        let tiny = TransactionOptions {
+            max_total_fee_lamports: Some(9_000),
+            ..Default::default()
+        };
+        assert!(matches!(
+            estimator.estimate(&spike, Some(30_000), 2, &tiny),
+            Err(Error::LimitExceeded(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_estimate_from_network_sets_options() -> Result<()> {
+        let rpc = MockRpc::new();
+        rpc.set_prioritization_fees(vec![0, 1_000, 4_000, 2_000]);
+        rpc.set_simulation(simulation(Some(12_000)));
+
+        let payer = Pubkey::new_unique();
+        let transaction = Transaction::new_unsigned(Message::new(
+            &[system_instruction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer),
+        ));
+        let options = TransactionOptions {
+            priority_fee_strategy: PriorityFeeStrategy::auto(),
+            ..Default::default()
+        };
+        let estimate = FeeEstimator::new()
+            .estimate_transaction(&rpc, &transaction, &options)
+            .await?;
+        assert_eq!(
+            estimate,
+            FeeEstimate {
+                compute_unit_price: 2_000,
+                compute_unit_limit: 13_200,
+            }
+        );
+
+        let applied = estimate.apply(&options);
+        assert_eq!(applied.compute_unit_price, Some(2_000));
+        assert_eq!(applied.compute_unit_limit, Some(13_200));
+        assert_eq!(applied.priority_fee_strategy, PriorityFeeStrategy::Fixed);
+
+        // Without simulated usage the options' limit is kept
+        rpc.set_simulation(simulation(None));
+        let estimate = FeeEstimator::new()
+            .estimate_transaction(&rpc, &transaction, &options)
+            .await?;
+        assert_eq!(estimate.compute_unit_limit, 200_000);
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+//! Minimal C ABI over [`Wallet`]
+//!
+//! Built with the `ffi` feature, which also has the build script generate a
+//! C header, `agent_wallet.h`, with cbindgen (see `build.rs`). Link against
+//! the shared library the crate is also built as:
+//!
+//! ```text
+//! cargo build -p agent-wallet-core --release --features ffi
+//! ```
+//!
+//! # Conventions
+//!
+//! - Strings are UTF-8 and passed as a pointer with an explicit byte length;
+//!   nothing is NUL-terminated. A null pointer is only accepted with a zero
+//!   length.
+//! - Functions return [`AW_OK`] or a negative `AW_ERR_*` status, except
+//!   [`aw_wallet_create`], which returns a null handle on failure. The
+//!   reason is kept per thread and read with [`aw_last_error_message`]; every
+//!   call clears the reason left by the previous one.
+//! - Outputs are written to a caller-owned buffer of a given capacity. The
+//!   byte length of the output is stored in `written` whether or not it fit,
+//!   and [`AW_ERR_BUFFER_TOO_SMALL`] is returned without writing anything if
+//!   it did not. [`AW_PUBKEY_MAX_LEN`] and [`AW_SIGNATURE_MAX_LEN`] always fit.
+//! - Calls block until done; the async wallet runs on a runtime embedded in
+//!   the library and started on first use.
+//! - A handle may be shared across threads, but must not be used once it was
+//!   passed to [`aw_wallet_free`].
+//!
+//! Panics are caught at the boundary and reported as [`AW_ERR_PANIC`]. This
+//! relies on unwinding: a library built with `panic = "abort"`, as the
+//! workspace's release profile is, aborts the process instead.
+//!
+//! Passphrases are copied into [`Zeroizing`] buffers, so the library keeps no
+//! copy once a call returns; clearing the caller's own buffer is up to the
+//! caller.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::str::FromStr;
+
+use once_cell::sync::OnceCell;
+use solana_sdk::pubkey::Pubkey;
+use tokio::runtime::Runtime;
+use zeroize::Zeroizing;
+
+use crate::config::WalletConfig;
+use crate::error::Error;
+use crate::sol::Lamports;
+use crate::wallet::Wallet;
+
+/// The call succeeded
+pub const AW_OK: i32 = 0;
+/// A required pointer was null
+pub const AW_ERR_NULL_POINTER: i32 = -1;
+/// An argument was not valid UTF-8, JSON or base58, or was otherwise malformed
+pub const AW_ERR_INVALID_ARGUMENT: i32 = -2;
+/// The output buffer cannot hold the output
+pub const AW_ERR_BUFFER_TOO_SMALL: i32 = -3;
+/// The wallet refused or failed the operation
+pub const AW_ERR_WALLET: i32 = -4;
+/// The library panicked; the handle should not be used again
+pub const AW_ERR_PANIC: i32 = -5;
+
+/// Longest base58 public key, in bytes
+pub const AW_PUBKEY_MAX_LEN: usize = 44;
+/// Longest base58 transaction signature, in bytes
+pub const AW_SIGNATURE_MAX_LEN: usize = 88;
+
+/// Opaque handle to an open wallet
+pub struct AwWallet {
+    wallet: Wallet,
+}
+
+static RUNTIME: OnceCell<Runtime> = OnceCell::new();
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Error of a call, with the status it returns
+struct FfiError {
+    status: i32,
+    message: String,
+}
+
+impl FfiError {
+    fn null(name: &str) -> Self {
+        Self {
+            status: AW_ERR_NULL_POINTER,
+            message: format!("{} is null", name),
+        }
+    }
+
+    fn invalid(message: String) -> Self {
+        Self {
+            status: AW_ERR_INVALID_ARGUMENT,
+            message,
+        }
+    }
+}
+
+impl From<Error> for FfiError {
+    fn from(err: Error) -> Self {
+        Self {
+            status: AW_ERR_WALLET,
+            message: err.to_string(),
+        }
+    }
+}
+
+type FfiResult<T> = std::result::Result<T, FfiError>;
+
+/// The embedded runtime, started on first use
+fn runtime() -> FfiResult<&'static Runtime> {
+    RUNTIME.get_or_try_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("agent-wallet-ffi")
+            .build()
+            .map_err(|e| Error::config(format!("Failed to start runtime: {}", e)).into())
+    })
+}
+
+/// Run `call`, turning its error or panic into a status and last error
+fn guard(call: impl FnOnce() -> FfiResult<()>) -> i32 {
+    LAST_ERROR.with(|last| last.borrow_mut().take());
+    let (status, message) = match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => return AW_OK,
+        Ok(Err(err)) => (err.status, err.message),
+        Err(panic) => (AW_ERR_PANIC, format!("panic: {}", panic_message(&*panic))),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Borrow a UTF-8 string argument
+///
+/// # Safety
+/// `ptr` must be null or valid for reads of `len` bytes for `'a`.
+unsafe fn str_arg<'a>(ptr: *const u8, len: usize, name: &str) -> FfiResult<&'a str> {
+    if len == 0 {
+        return Ok("");
+    }
+    if ptr.is_null() {
+        return Err(FfiError::null(name));
+    }
+    let bytes = std::slice::from_raw_parts(ptr, len);
+    std::str::from_utf8(bytes)
+        .map_err(|e| FfiError::invalid(format!("{} is not valid UTF-8: {}", name, e)))
+}
+
+/// Borrow the wallet behind a handle
+///
+/// # Safety
+/// `handle` must be null or a live handle from [`aw_wallet_create`].
+unsafe fn wallet_arg<'a>(handle: *const AwWallet) -> FfiResult<&'a Wallet> {
+    handle
+        .as_ref()
+        .map(|handle| &handle.wallet)
+        .ok_or_else(|| FfiError::null("handle"))
+}
+
+/// Copy `output` into a caller's buffer, storing its length in `written`
+///
+/// # Safety
+/// `out` must be null or valid for writes of `capacity` bytes, and `written`
+/// null or valid for a write.
+unsafe fn write_output(
+    output: &[u8],
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+    name: &str,
+) -> FfiResult<()> {
+    if let Some(written) = written.as_mut() {
+        *written = output.len();
+    }
+    if out.is_null() {
+        return Err(FfiError::null(name));
+    }
+    if output.len() > capacity {
+        return Err(FfiError {
+            status: AW_ERR_BUFFER_TOO_SMALL,
+            message: format!(
+                "{} holds {} bytes, {} are needed",
+                name,
+                capacity,
+                output.len()
+            ),
+        });
+    }
+    ptr::copy_nonoverlapping(output.as_ptr(), out, output.len());
+    Ok(())
+}
+
+/// Create a wallet and return a handle to it, or null on failure
+///
+/// `config_json` is a [`WalletConfig`] as JSON; keys it leaves out take
+/// their defaults, and an empty string selects the default configuration.
+///
+/// # Safety
+/// Each pointer must be null or valid for reads of its length in bytes.
+#[no_mangle]
+pub unsafe extern "C" fn aw_wallet_create(
+    name: *const u8,
+    name_len: usize,
+    passphrase: *const u8,
+    passphrase_len: usize,
+    config_json: *const u8,
+    config_json_len: usize,
+) -> *mut AwWallet {
+    let mut handle = ptr::null_mut();
+    guard(|| {
+        let name = str_arg(name, name_len, "name")?;
+        if name.is_empty() {
+            return Err(FfiError::invalid("name is empty".to_string()));
+        }
+        let passphrase =
+            Zeroizing::new(str_arg(passphrase, passphrase_len, "passphrase")?.to_owned());
+        let config_json = str_arg(config_json, config_json_len, "config_json")?;
+        let config = if config_json.is_empty() {
+            WalletConfig::default()
+        } else {
+            serde_json::from_str(config_json).map_err(|e| {
+                FfiError::invalid(format!("config_json is not a wallet config: {}", e))
+            })?
+        };
+
+        let wallet = runtime()?.block_on(Wallet::create(name, &passphrase, config))?;
+        handle = Box::into_raw(Box::new(AwWallet { wallet }));
+        Ok(())
+    });
+    handle
+}
+
+/// Write the wallet's base58 public key to `out_buf`
+///
+/// # Safety
+/// `handle` must be null or a live handle; `out_buf` must be null or valid
+/// for writes of `out_len` bytes; `written` must be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn aw_wallet_pubkey(
+    handle: *const AwWallet,
+    out_buf: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> i32 {
+    guard(|| {
+        let wallet = wallet_arg(handle)?;
+        let pubkey = wallet.public_key().to_string();
+        write_output(pubkey.as_bytes(), out_buf, out_len, written, "out_buf")
+    })
+}
+
+/// Transfer `lamports` to the base58 address `to` and write the signature
+///
+/// Nothing is sent if `out_sig_buf` is null or shorter than
+/// [`AW_SIGNATURE_MAX_LEN`], so a signature is never lost for want of room.
+///
+/// # Safety
+/// `handle` must be null or a live handle; `to` must be null or valid for
+/// reads of `to_len` bytes; `out_sig_buf` must be null or valid for writes of
+/// `out_sig_len` bytes; `written` must be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn aw_wallet_transfer_sol(
+    handle: *const AwWallet,
+    to: *const u8,
+    to_len: usize,
+    lamports: u64,
+    out_sig_buf: *mut u8,
+    out_sig_len: usize,
+    written: *mut usize,
+) -> i32 {
+    guard(|| {
+        let wallet = wallet_arg(handle)?;
+        let to = str_arg(to, to_len, "to")?;
+        let to = Pubkey::from_str(to)
+            .map_err(|e| FfiError::invalid(format!("to is not a base58 address: {}", e)))?;
+        if out_sig_buf.is_null() {
+            return Err(FfiError::null("out_sig_buf"));
+        }
+        if out_sig_len < AW_SIGNATURE_MAX_LEN {
+            return Err(FfiError {
+                status: AW_ERR_BUFFER_TOO_SMALL,
+                message: format!(
+                    "out_sig_buf holds {} bytes, {} are needed",
+                    out_sig_len, AW_SIGNATURE_MAX_LEN
+                ),
+            });
+        }
+
+        let signature =
+            runtime()?.block_on(wallet.transfer_sol(to, Lamports::new(lamports), None))?;
+        write_output(
+            signature.to_string().as_bytes(),
+            out_sig_buf,
+            out_sig_len,
+            written,
+            "out_sig_buf",
+        )
+    })
+}
+
+/// Close a wallet and release its handle; a null handle is ignored
+///
+/// # Safety
+/// `handle` must be null or a live handle, and is dangling once this returns.
+#[no_mangle]
+pub unsafe extern "C" fn aw_wallet_free(handle: *mut AwWallet) {
+    guard(|| {
+        if handle.is_null() {
+            return Ok(());
+        }
+        let handle = Box::from_raw(handle);
+        // Wallet components may hold runtime resources, like timers, that
+        // have to be released within the runtime
+        let _runtime = runtime()?.enter();
+        drop(handle);
+        Ok(())
+    });
+}
+
+/// Copy the reason the last call on this thread failed into `out_buf`
+///
+/// Returns the length of the message in bytes, or 0 if the last call
+/// succeeded. The message is only copied if `out_len` is at least that long,
+/// so a caller can size its buffer from a first call with a null `out_buf`.
+///
+/// # Safety
+/// `out_buf` must be null or valid for writes of `out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn aw_last_error_message(out_buf: *mut u8, out_len: usize) -> usize {
+    catch_unwind(AssertUnwindSafe(|| {
+        LAST_ERROR.with(|last| match last.borrow().as_deref() {
+            Some(message) => {
+                if !out_buf.is_null() && message.len() <= out_len {
+                    ptr::copy_nonoverlapping(message.as_ptr(), out_buf, message.len());
+                }
+                message.len()
+            }
+            None => 0,
+        })
+    }))
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{RpcEndpoint, WalletConfigBuilder};
+    use crate::error::Result;
+    use tempfile::tempdir;
+
+    fn last_error() -> String {
+        let mut buf = [0u8; 512];
+        let len = unsafe { aw_last_error_message(buf.as_mut_ptr(), buf.len()) };
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn test_errors_are_reported_through_the_last_error_message() {
+        let mut buf = [0u8; AW_PUBKEY_MAX_LEN];
+        let mut written = 0;
+        let status =
+            unsafe { aw_wallet_pubkey(ptr::null(), buf.as_mut_ptr(), buf.len(), &mut written) };
+        assert_eq!(status, AW_ERR_NULL_POINTER);
+
+        // The message's length comes back even when it does not fit
+        let len = unsafe { aw_last_error_message(ptr::null_mut(), 0) };
+        assert_eq!(len, "handle is null".len());
+        let mut short = [0u8; 4];
+        assert_eq!(
+            unsafe { aw_last_error_message(short.as_mut_ptr(), short.len()) },
+            len
+        );
+        assert_eq!(short, [0u8; 4]);
+        assert_eq!(last_error(), "handle is null");
+
+        // A successful call clears it
+        unsafe { aw_wallet_free(ptr::null_mut()) };
+        assert_eq!(unsafe { aw_last_error_message(ptr::null_mut(), 0) }, 0);
+    }
+
+    #[test]
+    fn test_create_rejects_malformed_arguments() {
+        let passphrase = b"correct horse battery staple";
+        let cases: [(&[u8], &[u8], &str); 3] = [
+            (b"\xff\xfe", b"", "name is not valid UTF-8"),
+            (b"", b"", "name is empty"),
+            (
+                b"agent",
+                b"{\"wallet\": 1}",
+                "config_json is not a wallet config",
+            ),
+        ];
+        for (name, config, expected) in cases {
+            let handle = unsafe {
+                aw_wallet_create(
+                    name.as_ptr(),
+                    name.len(),
+                    passphrase.as_ptr(),
+                    passphrase.len(),
+                    config.as_ptr(),
+                    config.len(),
+                )
+            };
+            assert!(handle.is_null());
+            assert!(last_error().contains(expected), "{}", last_error());
+        }
+
+        // A null pointer with a length is refused rather than read
+        let handle =
+            unsafe { aw_wallet_create(b"agent".as_ptr(), 5, ptr::null(), 8, ptr::null(), 0) };
+        assert!(handle.is_null());
+        assert_eq!(last_error(), "passphrase is null");
+    }
+
+    #[test]
+    fn test_wallet_handle_round_trip() -> Result<()> {
+        let dir = tempdir()?;
+        let mut config = WalletConfigBuilder::new()
+            .with_storage_path(dir.path().join("wallets"))
+            .with_backup_path(dir.path().join("backups"))
+            .build();
+        // Nothing listens here, so no call can reach a real cluster
+        config.rpc.endpoints = vec![RpcEndpoint::new("http://127.0.0.1:9")];
+        let config = serde_json::to_vec(&config)?;
+        let name = b"ffi-agent";
+        let passphrase = b"correct horse battery staple";
+
+        let handle = unsafe {
+            aw_wallet_create(
+                name.as_ptr(),
+                name.len(),
+                passphrase.as_ptr(),
+                passphrase.len(),
+                config.as_ptr(),
+                config.len(),
+            )
+        };
+        assert!(!handle.is_null(), "{}", last_error());
+
+        // Too small a buffer is left untouched but learns the length needed
+        let mut short = [0u8; 8];
+        let mut written = 0;
+        let status =
+            unsafe { aw_wallet_pubkey(handle, short.as_mut_ptr(), short.len(), &mut written) };
+        assert_eq!(status, AW_ERR_BUFFER_TOO_SMALL);
+        assert_eq!(short, [0u8; 8]);
+
+        let mut buf = [0u8; AW_PUBKEY_MAX_LEN];
+        let status = unsafe { aw_wallet_pubkey(handle, buf.as_mut_ptr(), buf.len(), &mut written) };
+        assert_eq!(status, AW_OK);
+        let pubkey =
+            std::str::from_utf8(&buf[..written]).map_err(|e| Error::validation(e.to_string()))?;
+        let wallet = unsafe { wallet_arg(handle) }.map_err(|e| Error::validation(e.message))?;
+        assert_eq!(pubkey, wallet.public_key().to_string());
+
+        // Malformed recipients and signature buffers fail before anything is sent
+        let mut sig = [0u8; AW_SIGNATURE_MAX_LEN];
+        let to = b"not-base58!";
+        let status = unsafe {
+            aw_wallet_transfer_sol(
+                handle,
+                to.as_ptr(),
+                to.len(),
+                1,
+                sig.as_mut_ptr(),
+                sig.len(),
+                &mut written,
+            )
+        };
+        assert_eq!(status, AW_ERR_INVALID_ARGUMENT);
+        assert!(last_error().starts_with("to is not a base58 address"));
+
+        let to = Pubkey::new_unique().to_string();
+        let status = unsafe {
+            aw_wallet_transfer_sol(
+                handle,
+                to.as_ptr(),
+                to.len(),
+                1,
+                sig.as_mut_ptr(),
+                10,
+                &mut written,
+            )
+        };
+        assert_eq!(status, AW_ERR_BUFFER_TOO_SMALL);
+
+        unsafe { aw_wallet_free(handle) };
+        Ok(())
+    }
+
+    #[test]
+    fn test_panics_do_not_cross_the_boundary() {
+        let status = guard(|| panic!("boom"));
+        assert_eq!(status, AW_ERR_PANIC);
+        assert_eq!(last_error(), "panic: boom");
+    }
+}
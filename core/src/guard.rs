@@ -0,0 +1,138 @@
+//! Minimum SOL reserve
+//!
+//! A wallet that spends its last lamports can no longer pay fees, and every
+//! later action fails. A [`BalanceGuard`] keeps `agent.limits.min_sol_reserve`
+//! out of reach of outgoing transfers: a transfer whose amount, fee and rent
+//! would take the balance below the reserve fails with `ReserveBreached`
+//! before it is signed.
+//!
+//! The reserve is also published in the agent context, so strategies see the
+//! balance they can actually spend.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::guard::BalanceGuard;
+//! use agent_wallet_core::sol::Lamports;
+//!
+//! let guard = BalanceGuard::new(Lamports::new(10_000_000));
+//! let balance = Lamports::new(50_000_000);
+//!
+//! assert_eq!(guard.spendable(balance), Lamports::new(40_000_000));
+//! // 40_000_000 lamports transferred plus a 5_000 lamport fee breach the reserve
+//! assert!(guard.check(balance, Lamports::new(40_005_000)).is_err());
+//! assert!(guard.check(balance, Lamports::new(39_995_000)).is_ok());
+//! ```
+
+use crate::config::AgentLimits;
+use crate::error::{Error, Result};
+use crate::sol::Lamports;
+
+/// Keeps a minimum SOL balance out of reach of outgoing transfers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BalanceGuard {
+    reserve: Lamports,
+}
+
+impl BalanceGuard {
+    /// Guard keeping `reserve` in the wallet
+    pub fn new(reserve: Lamports) -> Self {
+        Self { reserve }
+    }
+
+    /// Guard keeping the configured `min_sol_reserve`
+    pub fn from_limits(limits: &AgentLimits) -> Result<Self> {
+        let reserve = Lamports::from_sol_f64_rounded(limits.min_sol_reserve).map_err(|e| {
+            Error::config(format!(
+                "Invalid agent.limits.min_sol_reserve {}: {}",
+                limits.min_sol_reserve, e
+            ))
+        })?;
+        Ok(Self::new(reserve))
+    }
+
+    /// Balance that must stay in the wallet
+    pub fn reserve(&self) -> Lamports {
+        self.reserve
+    }
+
+    /// Part of `balance` above the reserve
+    pub fn spendable(&self, balance: Lamports) -> Lamports {
+        balance.saturating_sub(self.reserve)
+    }
+
+    /// Check that spending `total` out of `balance` keeps the reserve
+    ///
+    /// `total` is everything the transaction takes from the wallet: the
+    /// amount transferred plus the estimated fee and rent.
+    pub fn check(&self, balance: Lamports, total: Lamports) -> Result<()> {
+        let post_balance = balance.saturating_sub(total);
+        if post_balance < self.reserve {
+            return Err(Error::ReserveBreached {
+                reserve: self.reserve.as_u64(),
+                post_balance: post_balance.as_u64(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Lamports that bring `balance` back up to `target`, if it fell below the reserve
+    ///
+    /// A target under the reserve tops up to the reserve itself.
+    pub fn top_up_amount(&self, balance: Lamports, target: Lamports) -> Option<Lamports> {
+        if balance >= self.reserve {
+            return None;
+        }
+        Some(target.max(self.reserve).saturating_sub(balance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_includes_fee() -> Result<()> {
+        let guard = BalanceGuard::new(Lamports::new(1_000_000));
+        let balance = Lamports::new(3_000_000);
+        let transfer = 2_000_000;
+        let fee = 5_000;
+
+        // The transfer alone would leave exactly the reserve
+        guard.check(balance, Lamports::new(transfer))?;
+        let error = guard.check(balance, Lamports::new(transfer + fee)).err();
+        assert!(matches!(
+            error,
+            Some(Error::ReserveBreached {
+                reserve: 1_000_000,
+                post_balance: 995_000,
+            })
+        ));
+
+        assert_eq!(guard.spendable(balance), Lamports::new(2_000_000));
+        assert_eq!(guard.spendable(Lamports::new(500)), Lamports::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_up_amount() {
+        let guard = BalanceGuard::new(Lamports::new(1_000_000));
+
+        assert_eq!(
+            guard.top_up_amount(Lamports::new(1_000_000), Lamports::new(5_000_000)),
+            None
+        );
+        assert_eq!(
+            guard.top_up_amount(Lamports::new(400_000), Lamports::new(5_000_000)),
+            Some(Lamports::new(4_600_000))
+        );
+        assert_eq!(
+            guard.top_up_amount(Lamports::new(400_000), Lamports::ZERO),
+            Some(Lamports::new(600_000))
+        );
+        assert_eq!(
+            BalanceGuard::default().top_up_amount(Lamports::ZERO, Lamports::new(1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,483 @@
+//! MEV-protected submission through Jito bundles
+//!
+//! Transactions broadcast through a public RPC node sit in the open until a
+//! leader picks them up, which leaves swaps open to sandwiching. A
+//! [`JitoClient`] instead hands them to a Jito block engine as a bundle: the
+//! transactions execute in order and all-or-nothing, followed by a small
+//! tip transfer to one of the [`JITO_TIP_ACCOUNTS`] that pays for the
+//! bundle's inclusion.
+//!
+//! The wallet submits through Jito when
+//! [`TransactionOptions::submission`](crate::transaction::TransactionOptions::submission)
+//! is [`SubmissionMode::JitoBundle`](crate::transaction::SubmissionMode::JitoBundle),
+//! and only in builds with the `jito` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::jito::{JitoClient, JitoConfig};
+//! use agent_wallet_core::SecureKeypair;
+//! use solana_sdk::transaction::Transaction;
+//!
+//! # async fn example(swap: Transaction, payer: SecureKeypair) -> agent_wallet_core::Result<()> {
+//! let client = JitoClient::new(JitoConfig::new("https://mainnet.block-engine.jito.wtf", 10_000))?;
+//! let blockhash = swap.message.recent_blockhash;
+//! let bundle_id = client.submit(vec![swap], &payer, blockhash).await?;
+//! let slot = client.wait_for_bundle(&bundle_id).await?;
+//! println!("Bundle {} landed in slot {}", bundle_id, slot);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    clock::Slot, hash::Hash, pubkey, pubkey::Pubkey, system_instruction, transaction::Transaction,
+};
+
+use crate::error::{Error, Result};
+use crate::signer::{sign_transaction, DynTransactionSigner};
+
+/// Accounts the block engine accepts tips at
+pub const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
+    pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+/// Most transactions a bundle may hold, the tip included
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+/// Default time a bundle is given to land
+pub const DEFAULT_BUNDLE_DEADLINE: Duration = Duration::from_secs(30);
+
+/// Default time between bundle status polls
+pub const DEFAULT_BUNDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default time allowed for one request to the block engine
+pub const DEFAULT_BLOCK_ENGINE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Path of the bundle API below the block engine URL
+const BUNDLES_PATH: &str = "/api/v1/bundles";
+
+/// Where bundles are sent and what they tip
+#[derive(Debug, Clone)]
+pub struct JitoConfig {
+    /// Base URL of the block engine
+    pub block_engine_url: String,
+    /// Lamports tipped per bundle
+    pub tip_lamports: u64,
+    /// Account the tip is paid to; one of [`JITO_TIP_ACCOUNTS`] at random if unset
+    pub tip_account: Option<Pubkey>,
+    /// How long a bundle is polled for before giving up on it
+    pub deadline: Duration,
+    /// Time between status polls
+    pub poll_interval: Duration,
+    /// Time allowed for one request to the block engine
+    pub timeout: Duration,
+}
+
+impl JitoConfig {
+    /// Config for the block engine at `block_engine_url`, tipping `tip_lamports`
+    pub fn new(block_engine_url: impl Into<String>, tip_lamports: u64) -> Self {
+        Self {
+            block_engine_url: block_engine_url.into(),
+            tip_lamports,
+            tip_account: None,
+            deadline: DEFAULT_BUNDLE_DEADLINE,
+            poll_interval: DEFAULT_BUNDLE_POLL_INTERVAL,
+            timeout: DEFAULT_BLOCK_ENGINE_TIMEOUT,
+        }
+    }
+
+    /// Always tip `account`
+    pub fn with_tip_account(mut self, account: Pubkey) -> Self {
+        self.tip_account = Some(account);
+        self
+    }
+
+    /// Poll bundles for up to `deadline`, every `poll_interval`
+    pub fn with_polling(mut self, deadline: Duration, poll_interval: Duration) -> Self {
+        self.deadline = deadline;
+        self.poll_interval = poll_interval;
+        self
+    }
+}
+
+/// Where a submitted bundle stands, as the block engine reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BundleStatus {
+    /// Not yet landed, or not yet known to the block engine
+    Pending,
+    /// Landed in the given slot
+    Landed(Slot),
+    /// Simulated with an error or failed to be included
+    Failed,
+    /// Rejected as malformed, or too old to be tracked
+    Invalid,
+}
+
+#[derive(Serialize)]
+struct JsonRpcRequest<'a, P> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: P,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct InflightStatuses {
+    value: Option<Vec<InflightStatus>>,
+}
+
+#[derive(Deserialize)]
+struct InflightStatus {
+    status: String,
+    landed_slot: Option<Slot>,
+}
+
+/// Client of a Jito block engine's bundle API
+pub struct JitoClient {
+    config: JitoConfig,
+    client: reqwest::Client,
+}
+
+impl JitoClient {
+    /// Create a client for the configured block engine
+    pub fn new(config: JitoConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| Error::config(format!("Failed to build block engine client: {}", e)))?;
+        Ok(Self { config, client })
+    }
+
+    /// The client's configuration
+    pub fn config(&self) -> &JitoConfig {
+        &self.config
+    }
+
+    /// Account the next tip is paid to
+    pub fn tip_account(&self) -> Pubkey {
+        self.config
+            .tip_account
+            .unwrap_or_else(|| JITO_TIP_ACCOUNTS[rand::random::<usize>() % JITO_TIP_ACCOUNTS.len()])
+    }
+
+    /// Unsigned transaction paying the tip from `payer`
+    pub fn tip_transaction(&self, payer: &Pubkey, blockhash: Hash) -> Transaction {
+        let tip =
+            system_instruction::transfer(payer, &self.tip_account(), self.config.tip_lamports);
+        let mut transaction = Transaction::new_with_payer(&[tip], Some(payer));
+        transaction.message.recent_blockhash = blockhash;
+        transaction
+    }
+
+    /// Bundle signed `transactions` with a tip signed by `tipper`, and send it
+    ///
+    /// The tip goes last so it is only paid if every transaction before it
+    /// executes. Returns the bundle id.
+    pub async fn submit(
+        &self,
+        transactions: Vec<Transaction>,
+        tipper: &dyn DynTransactionSigner,
+        blockhash: Hash,
+    ) -> Result<String> {
+        if transactions.is_empty() || transactions.len() >= MAX_BUNDLE_TRANSACTIONS {
+            return Err(Error::validation(format!(
+                "A bundle holds 1 to {} transactions besides the tip, not {}",
+                MAX_BUNDLE_TRANSACTIONS - 1,
+                transactions.len()
+            )));
+        }
+        let mut tip = self.tip_transaction(&tipper.pubkey().await, blockhash);
+        sign_transaction(&mut tip, tipper).await?;
+
+        let mut bundle = transactions;
+        bundle.push(tip);
+        self.send_bundle(&bundle).await
+    }
+
+    /// Send fully signed transactions as one bundle, returning its id
+    pub async fn send_bundle(&self, bundle: &[Transaction]) -> Result<String> {
+        let encoded = bundle
+            .iter()
+            .map(|transaction| Ok(STANDARD.encode(bincode::serialize(transaction)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let params = (encoded, serde_json::json!({ "encoding": "base64" }));
+        self.call("sendBundle", params).await
+    }
+
+    /// Where the bundle `bundle_id` stands
+    pub async fn bundle_status(&self, bundle_id: &str) -> Result<BundleStatus> {
+        let statuses: InflightStatuses = self
+            .call("getInflightBundleStatuses", [[bundle_id]])
+            .await?;
+        let Some(status) = statuses.value.and_then(|value| value.into_iter().next()) else {
+            return Ok(BundleStatus::Pending);
+        };
+        Ok(match (status.status.as_str(), status.landed_slot) {
+            ("Landed", Some(slot)) => BundleStatus::Landed(slot),
+            ("Failed", _) => BundleStatus::Failed,
+            ("Invalid", _) => BundleStatus::Invalid,
+            _ => BundleStatus::Pending,
+        })
+    }
+
+    /// Poll the bundle until it lands, returning the slot it landed in
+    ///
+    /// Fails once the bundle failed or turned invalid, and with
+    /// [`Error::Timeout`] when it is still pending at the deadline.
+    pub async fn wait_for_bundle(&self, bundle_id: &str) -> Result<Slot> {
+        let deadline = tokio::time::Instant::now() + self.config.deadline;
+        loop {
+            match self.bundle_status(bundle_id).await? {
+                BundleStatus::Landed(slot) => return Ok(slot),
+                BundleStatus::Pending => {}
+                status => {
+                    return Err(Error::transaction(format!(
+                        "Bundle {} did not land: {:?}",
+                        bundle_id, status
+                    )))
+                }
+            }
+            if tokio::time::Instant::now() + self.config.poll_interval > deadline {
+                return Err(Error::Timeout(format!(
+                    "Bundle {} did not land within {:?}",
+                    bundle_id, self.config.deadline
+                )));
+            }
+            tokio::time::sleep(self.config.poll_interval).await;
+        }
+    }
+
+    async fn call<P: Serialize, T: DeserializeOwned>(&self, method: &str, params: P) -> Result<T> {
+        let url = format!(
+            "{}{}",
+            self.config.block_engine_url.trim_end_matches('/'),
+            BUNDLES_PATH
+        );
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0",
+            id: 1,
+            method,
+            params,
+        };
+        let response = self
+            .client
+            .post(url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response.text().await.unwrap_or_default();
+            return Err(Error::Network(format!(
+                "Block engine returned {}: {}",
+                status,
+                reason.trim()
+            )));
+        }
+        let response: JsonRpcResponse<T> = response.json().await.map_err(request_error)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(Error::Network(format!(
+                "Block engine refused {}: {} ({})",
+                method, error.message, error.code
+            ))),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(Error::Network(format!(
+                "Block engine answered {} without a result",
+                method
+            ))),
+        }
+    }
+}
+
+fn request_error(error: reqwest::Error) -> Error {
+    if error.is_timeout() {
+        Error::Timeout(format!("Block engine did not answer: {}", error))
+    } else {
+        Error::Network(format!("Block engine request failed: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypair::SecureKeypair;
+    use solana_sdk::system_program;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn transfer(from: &Pubkey, blockhash: Hash) -> Transaction {
+        let mut transaction = Transaction::new_with_payer(
+            &[system_instruction::transfer(from, &Pubkey::new_unique(), 1)],
+            Some(from),
+        );
+        transaction.message.recent_blockhash = blockhash;
+        transaction
+    }
+
+    fn statuses(status: &str, landed_slot: Option<Slot>) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "context": { "slot": 100 },
+                "value": [{ "bundle_id": "b1", "status": status, "landed_slot": landed_slot }],
+            },
+        }))
+    }
+
+    #[test]
+    fn test_tip_transaction_pays_a_tip_account() -> Result<()> {
+        let payer = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+        let client = JitoClient::new(JitoConfig::new("http://localhost", 10_000))?;
+
+        let tip = client.tip_transaction(&payer, blockhash);
+        assert_eq!(tip.message.recent_blockhash, blockhash);
+        assert_eq!(tip.message.account_keys[0], payer);
+        let instruction = &tip.message.instructions[0];
+        assert_eq!(
+            tip.message.account_keys[instruction.program_id_index as usize],
+            system_program::id()
+        );
+        let tip_account = tip.message.account_keys[instruction.accounts[1] as usize];
+        assert!(JITO_TIP_ACCOUNTS.contains(&tip_account));
+        assert_eq!(
+            instruction.data,
+            system_instruction::transfer(&payer, &tip_account, 10_000).data
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bundle_carries_the_transactions_then_the_tip() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(BUNDLES_PATH))
+            .and(body_partial_json(
+                serde_json::json!({ "method": "sendBundle" }),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "b1" }),
+                ),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let payer = SecureKeypair::generate();
+        let tip_account = JITO_TIP_ACCOUNTS[3];
+        let client =
+            JitoClient::new(JitoConfig::new(server.uri(), 5_000).with_tip_account(tip_account))?;
+        let blockhash = Hash::new_unique();
+        let mut swap = transfer(&payer.public_key(), blockhash);
+        sign_transaction(&mut swap, &payer).await?;
+
+        let bundle_id = client.submit(vec![swap.clone()], &payer, blockhash).await?;
+        assert_eq!(bundle_id, "b1");
+
+        let requests = server.received_requests().await.unwrap_or_default();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body)?;
+        assert_eq!(body["params"][1]["encoding"], "base64");
+        let Some(encoded) = body["params"][0].as_array() else {
+            panic!("bundle is not a list of transactions");
+        };
+        let bundle = encoded
+            .iter()
+            .map(|transaction| {
+                let bytes = STANDARD
+                    .decode(transaction.as_str().unwrap_or_default())
+                    .map_err(|e| Error::serialization(e.to_string()))?;
+                Ok(bincode::deserialize::<Transaction>(&bytes)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(bundle.len(), 2);
+        assert_eq!(bundle[0], swap);
+        assert!(bundle[1].verify().is_ok());
+        assert!(bundle[1].message.account_keys.contains(&tip_account));
+
+        // Bundles are capped, the tip included
+        let full = vec![swap; MAX_BUNDLE_TRANSACTIONS];
+        assert!(client.submit(full, &payer, blockhash).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bundle_status_is_polled_until_landed() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({ "method": "getInflightBundleStatuses" }),
+            ))
+            .respond_with(statuses("Pending", None))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(statuses("Landed", Some(1_234)))
+            .mount(&server)
+            .await;
+
+        let client = JitoClient::new(
+            JitoConfig::new(server.uri(), 5_000)
+                .with_polling(Duration::from_secs(5), Duration::from_millis(10)),
+        )?;
+        assert_eq!(client.wait_for_bundle("b1").await?, 1_234);
+        assert_eq!(server.received_requests().await.map(|r| r.len()), Some(3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bundle_that_never_lands_times_out() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(statuses("Pending", None))
+            .mount(&server)
+            .await;
+        let client = JitoClient::new(
+            JitoConfig::new(server.uri(), 5_000)
+                .with_polling(Duration::from_millis(50), Duration::from_millis(10)),
+        )?;
+        assert!(matches!(
+            client.wait_for_bundle("b1").await,
+            Err(Error::Timeout(_))
+        ));
+
+        let failed = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(statuses("Failed", None))
+            .mount(&failed)
+            .await;
+        let client = JitoClient::new(JitoConfig::new(failed.uri(), 5_000))?;
+        assert!(matches!(
+            client.wait_for_bundle("b1").await,
+            Err(Error::Transaction(_))
+        ));
+        Ok(())
+    }
+}
@@ -28,8 +28,11 @@
 //! assert_eq!(loaded.public_key(), public_key);
 //! ```
 
-use std::fmt;
+use std::fmt::{self, Write as _};
 use std::ops::Deref;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
 
 use aes_gcm::aead::OsRng;
 use rand::rngs::OsRng as RandOsRng;
@@ -52,6 +55,51 @@ use crate::error::{Error, Result};
 pub struct SecureKeypair {
     /// The underlying Solana keypair, wrapped in Zeroizing for memory protection
     inner: Zeroizing<SolanaKeypair>,
+    /// Signatures made with the key, shared by every clone
+    usage: Arc<KeyUsage>,
+}
+
+/// Count of the signatures made with a key
+///
+/// Counting starts at zero when the key is loaded; [`KeyUsage::resume`]
+/// carries over the signatures of earlier sessions.
+#[derive(Debug, Default)]
+pub struct KeyUsage {
+    /// Number of signatures
+    signatures: AtomicU64,
+    /// Unix timestamp of the latest signature, 0 if there was none
+    last_used: AtomicI64,
+}
+
+impl KeyUsage {
+    /// Count one signature made now
+    pub fn record(&self) {
+        self.signatures.fetch_add(1, Ordering::Relaxed);
+        self.last_used
+            .store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Add the signatures made in earlier sessions, the latest at `last_used`
+    pub fn resume(&self, signatures: u64, last_used: Option<chrono::DateTime<chrono::Utc>>) {
+        self.signatures.fetch_add(signatures, Ordering::Relaxed);
+        if let Some(last_used) = last_used {
+            self.last_used
+                .fetch_max(last_used.timestamp(), Ordering::Relaxed);
+        }
+    }
+
+    /// Number of signatures made
+    pub fn signatures(&self) -> u64 {
+        self.signatures.load(Ordering::Relaxed)
+    }
+
+    /// When the latest signature was made, if any
+    pub fn last_used(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self.last_used.load(Ordering::Relaxed) {
+            0 => None,
+            timestamp => chrono::DateTime::from_timestamp(timestamp, 0),
+        }
+    }
 }
 
 /// Encrypted keypair data for secure storage
@@ -125,6 +173,7 @@ impl SecureKeypair {
 
         Self {
             inner: Zeroizing::new(keypair),
+            usage: Arc::default(),
         }
     }
 
@@ -132,6 +181,7 @@ impl SecureKeypair {
     pub fn from_keypair(keypair: SolanaKeypair) -> Self {
         Self {
             inner: Zeroizing::new(keypair),
+            usage: Arc::default(),
         }
     }
 
@@ -152,6 +202,58 @@ impl SecureKeypair {
         Self::from_bytes(&bytes)
     }
 
+    /// Parse the 64-element byte array JSON written by `solana-keygen`
+    ///
+    /// The second half of the array must be the public key derived from the
+    /// first; a mismatch means the file is corrupt or hand-edited.
+    pub fn from_solana_json(json: &str) -> Result<Self> {
+        let bytes: Zeroizing<Vec<u8>> = Zeroizing::new(
+            serde_json::from_str(json)
+                .map_err(|e| Error::Keypair(format!("Invalid keypair JSON: {}", e)))?,
+        );
+        if bytes.len() != 64 {
+            return Err(Error::Keypair(format!(
+                "Keypair JSON holds {} bytes (expected 64)",
+                bytes.len()
+            )));
+        }
+
+        let keypair = Self::from_bytes(&bytes)?;
+        if bytes[32..] != keypair.public_key().to_bytes() {
+            return Err(Error::Keypair(
+                "Keypair JSON public key does not match its secret key".to_string(),
+            ));
+        }
+        Ok(keypair)
+    }
+
+    /// Read a Solana CLI keypair file such as `~/.config/solana/id.json`
+    pub fn from_solana_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = Zeroizing::new(std::fs::read_to_string(path).map_err(|e| {
+            Error::Keypair(format!("Failed to read {}: {}", path.display(), e))
+        })?);
+        Self::from_solana_json(&json)
+    }
+
+    /// The keypair as the 64-element byte array JSON used by the Solana CLI
+    ///
+    /// The string holds the unencrypted secret key and is zeroized on drop.
+    pub fn to_solana_json(&self) -> Zeroizing<String> {
+        let bytes = self.private_key_bytes();
+        // Sized up front so the secret is never copied by a reallocation
+        let mut json = Zeroizing::new(String::with_capacity(64 * 4 + 2));
+        json.push('[');
+        for (i, byte) in bytes.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{}", byte);
+        }
+        json.push(']');
+        json
+    }
+
     /// Get the public key
     pub fn public_key(&self) -> Pubkey {
         self.inner.public_key()
@@ -240,11 +342,17 @@ impl SecureKeypair {
         Self::from_bytes(&key_bytes)
     }
 
-    /// Sign a message with this keypair
+    /// Sign a message with this keypair, counting the signature
     pub fn sign(&self, message: &[u8]) -> Signature {
+        self.usage.record();
         self.inner.sign_message(message)
     }
 
+    /// Signatures made with this key, shared with its clones
+    pub fn usage(&self) -> &Arc<KeyUsage> {
+        &self.usage
+    }
+
     /// Verify a signature with this keypair's public key
     pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
         signature.verify(self.public_key().as_ref(), message)
@@ -399,6 +507,20 @@ impl KeypairManager {
         self.metadata.iter().collect()
     }
 
+    /// Copy the signatures made with each keypair into its metadata
+    ///
+    /// `usage_count` then counts the signatures made since the keypair was
+    /// added, and `last_used` is the time of the latest one.
+    pub fn refresh_usage(&mut self) {
+        for (keypair, metadata) in self.keypairs.iter().zip(self.metadata.iter_mut()) {
+            let usage = keypair.usage();
+            metadata.usage_count = usage.signatures();
+            if let Some(last_used) = usage.last_used() {
+                metadata.last_used = last_used;
+            }
+        }
+    }
+
     /// Update keypair metadata
     pub fn update_metadata(
         &mut self,
@@ -492,6 +614,29 @@ mod tests {
         assert_ne!(keypair1.public_key(), Pubkey::default());
     }
 
+    #[test]
+    fn test_signatures_are_counted() {
+        let keypair = SecureKeypair::generate();
+        let clone = keypair.clone();
+        assert_eq!(keypair.usage().signatures(), 0);
+        assert!(keypair.usage().last_used().is_none());
+
+        keypair.sign(b"one");
+        // Signing through the Signer trait and through a clone counts too
+        Signer::sign_message(&clone, b"two");
+        assert_eq!(keypair.usage().signatures(), 2);
+        assert!(keypair.usage().last_used().is_some());
+
+        let mut manager = KeypairManager::new();
+        manager.add_keypair(keypair.clone(), None, None);
+        keypair.sign(b"three");
+        manager.refresh_usage();
+        assert_eq!(
+            manager.get_metadata(&keypair.public_key()).map(|meta| meta.usage_count),
+            Some(3)
+        );
+    }
+
     #[test]
     fn test_keypair_from_bytes() -> Result<()> {
         // Generate a keypair
@@ -525,6 +670,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_solana_json_round_trip() -> Result<()> {
+        let keypair = SecureKeypair::generate();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("id.json");
+        std::fs::write(&path, keypair.to_solana_json().as_bytes())?;
+
+        // The file is readable by the Solana SDK itself
+        let solana = solana_sdk::signature::read_keypair_file(&path)
+            .map_err(|e| Error::Keypair(e.to_string()))?;
+        assert_eq!(solana.pubkey(), keypair.public_key());
+
+        let imported = SecureKeypair::from_solana_json_file(&path)?;
+        assert_eq!(imported.public_key(), keypair.public_key());
+
+        // A file whose public half belongs to another key is rejected
+        let mut bytes = *keypair.private_key_bytes();
+        bytes[32..].copy_from_slice(&SecureKeypair::generate().public_key().to_bytes());
+        let tampered = serde_json::to_string(&bytes.to_vec())?;
+        assert!(SecureKeypair::from_solana_json(&tampered).is_err());
+        assert!(SecureKeypair::from_solana_json("[1,2,3]").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_keypair_signing() {
         let keypair = SecureKeypair::generate();
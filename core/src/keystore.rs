@@ -0,0 +1,181 @@
+//! Wallet secrets held in the OS keychain
+//!
+//! With `wallet.encryption.use_os_keystore` enabled, a wallet file is
+//! encrypted under a key derived from both the passphrase and a random
+//! 32-byte secret kept in the OS keychain: the macOS Keychain, the Windows
+//! Credential Manager or the Secret Service on Linux. A phished passphrase
+//! alone no longer opens a copied wallet file, and loading a file on a
+//! machine without its keychain entry fails with
+//! [`Error::KeystoreUnavailable`]. Entries are keyed by the wallet's
+//! storage location as well as its name, so same-named wallets in two
+//! directories keep separate secrets.
+//!
+//! [`Wallet::export_recovery`](crate::Wallet::export_recovery) writes a
+//! passphrase-only copy of a keystore-bound wallet for backups.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::keystore::{generate_secret, Keystore, OsKeystore};
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let keystore = OsKeystore::default();
+//! keystore.set_secret("trader", &generate_secret())?;
+//! assert!(keystore.get_secret("trader")?.is_some());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+use crate::sync::lock;
+
+/// Keychain service wallet secrets are stored under
+pub const KEYSTORE_SERVICE: &str = "agent-wallet";
+
+/// Length of a keystore secret in bytes
+pub const SECRET_LEN: usize = 32;
+
+/// Store of per-wallet secrets outside the wallet file
+pub trait Keystore: Send + Sync {
+    /// Secret stored for `account`, if any
+    ///
+    /// Fails with [`Error::KeystoreUnavailable`] if the keystore cannot be
+    /// reached at all.
+    fn get_secret(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>>;
+
+    /// Store `secret` for `account`, replacing any previous one
+    fn set_secret(&self, account: &str, secret: &[u8]) -> Result<()>;
+
+    /// Remove the secret of `account`; removing a missing one succeeds
+    fn delete_secret(&self, account: &str) -> Result<()>;
+}
+
+/// Keystore backed by the operating system's keychain
+#[derive(Debug, Clone)]
+pub struct OsKeystore {
+    service: String,
+}
+
+impl OsKeystore {
+    /// Keychain entries under `service`
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+        }
+    }
+
+    fn entry(&self, account: &str) -> Result<keyring::Entry> {
+        keyring::Entry::new(&self.service, account).map_err(|e| unavailable(account, e))
+    }
+}
+
+impl Default for OsKeystore {
+    fn default() -> Self {
+        Self::new(KEYSTORE_SERVICE)
+    }
+}
+
+impl Keystore for OsKeystore {
+    fn get_secret(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let encoded = match self.entry(account)?.get_password() {
+            Ok(encoded) => Zeroizing::new(encoded),
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(e) => return Err(unavailable(account, e)),
+        };
+        let secret = hex::decode(encoded.as_bytes()).map_err(|e| {
+            Error::KeystoreUnavailable(format!("Keychain entry of '{}' is corrupt: {}", account, e))
+        })?;
+        Ok(Some(Zeroizing::new(secret)))
+    }
+
+    fn set_secret(&self, account: &str, secret: &[u8]) -> Result<()> {
+        let encoded = Zeroizing::new(hex::encode(secret));
+        self.entry(account)?
+            .set_password(&encoded)
+            .map_err(|e| unavailable(account, e))
+    }
+
+    fn delete_secret(&self, account: &str) -> Result<()> {
+        match self.entry(account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(unavailable(account, e)),
+        }
+    }
+}
+
+/// Keystore held in process memory, for tests
+#[derive(Debug, Default)]
+pub struct MemoryKeystore {
+    secrets: Mutex<HashMap<String, Zeroizing<Vec<u8>>>>,
+}
+
+impl MemoryKeystore {
+    /// Empty keystore
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn secrets(&self) -> std::sync::MutexGuard<'_, HashMap<String, Zeroizing<Vec<u8>>>> {
+        lock(&self.secrets)
+    }
+}
+
+impl Keystore for MemoryKeystore {
+    fn get_secret(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        Ok(self.secrets().get(account).cloned())
+    }
+
+    fn set_secret(&self, account: &str, secret: &[u8]) -> Result<()> {
+        self.secrets()
+            .insert(account.to_string(), Zeroizing::new(secret.to_vec()));
+        Ok(())
+    }
+
+    fn delete_secret(&self, account: &str) -> Result<()> {
+        self.secrets().remove(account);
+        Ok(())
+    }
+}
+
+/// New random keystore secret
+pub fn generate_secret() -> Zeroizing<[u8; SECRET_LEN]> {
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut *secret);
+    secret
+}
+
+fn unavailable(account: &str, error: keyring::Error) -> Error {
+    Error::KeystoreUnavailable(format!(
+        "Keychain entry of '{}' is not accessible: {}",
+        account, error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_keystore_round_trip() -> Result<()> {
+        let keystore = MemoryKeystore::new();
+        assert!(keystore.get_secret("trader")?.is_none());
+
+        let secret = generate_secret();
+        keystore.set_secret("trader", &*secret)?;
+        assert_eq!(
+            keystore.get_secret("trader")?.as_deref().map(Vec::as_slice),
+            Some(&secret[..])
+        );
+        assert_ne!(*generate_secret(), *secret);
+
+        keystore.delete_secret("trader")?;
+        keystore.delete_secret("trader")?;
+        assert!(keystore.get_secret("trader")?.is_none());
+        Ok(())
+    }
+}
@@ -16,7 +16,7 @@
 //! # Quick Start
 //!
 //! ```no_run
-//! use agent_wallet_core::{Wallet, WalletConfig};
+//! use agent_wallet_core::{Lamports, Wallet, WalletConfig};
 //! use zeroize::Zeroizing;
 //!
 //! #[tokio::main]
@@ -33,7 +33,7 @@
 //!     // Transfer SOL
 //!     let signature = wallet.transfer_sol(
 //!         &Pubkey::new_unique(),
-//!         0.1,
+//!         Lamports::from_sol_str("0.1")?,
 //!         Some("Test transfer".to_string()),
 //!     ).await?;
 //!
@@ -51,28 +51,129 @@
 #![warn(clippy::unwrap_used)]
 #![warn(clippy::expect_used)]
 
+pub mod address_book;
+pub mod audit;
+pub mod budget;
+pub mod cluster;
 pub mod config;
+pub mod context;
+pub mod destination;
 pub mod encryption;
+pub mod envelope;
 pub mod error;
+pub mod escalation;
+pub mod fee_payer;
+pub mod fees;
+pub mod guard;
 pub mod keypair;
+pub mod keystore;
+pub mod locked_keypair;
+pub mod logging;
+pub mod manager;
+pub mod market;
+pub mod memo;
+pub mod metrics;
+pub mod multisig;
+pub mod nonce;
+pub mod oracle;
+pub mod policy;
+pub mod preview;
+pub mod program_accounts;
+pub mod protocols;
+pub mod rate_limit;
+pub mod read_cache;
+pub mod rent;
+pub mod retry;
+pub mod rotation;
 pub mod rpc;
+pub mod shutdown;
+pub mod signer;
+pub mod sns;
+pub mod sol;
+pub mod solana_pay;
+pub mod stake;
+pub mod stats;
 pub mod storage;
+pub mod store;
+pub(crate) mod sync;
+pub mod template;
 pub mod token;
 pub mod transaction;
+pub mod tx_details;
+pub mod tx_policy;
 pub mod types;
 pub mod wallet;
+pub mod watch;
+
+#[cfg(feature = "coingecko")]
+pub mod coingecko;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "jito")]
+pub mod jito;
 
 // Re-exports for convenience
-pub use config::WalletConfig;
-pub use encryption::{EncryptedData, EncryptionService};
+pub use address_book::{AddressBook, Contact, IntegrityPolicy};
+pub use audit::{AuditEntry, AuditSink, JsonlAuditSink};
+pub use budget::{BudgetLedger, BudgetReservation, IdempotencyJournal};
+pub use cluster::Cluster;
+pub use config::{AuthStyle, RpcEndpoint, RunMode, WalletConfig};
+pub use context::ContextBuilder;
+pub use destination::{DestinationCheck, DestinationOptions, DestinationTransfer};
+pub use encryption::{EncryptedData, EncryptionService, KdfAlgorithm};
+pub use envelope::{SignedEnvelope, SimulationReport, TransactionEnvelope, UnsignedEnvelope};
 pub use error::{Error, Result};
+pub use escalation::{EscalationPolicy, EscalationReport};
+pub use fee_payer::FeePayerService;
+pub use fees::{FeeEstimate, FeeEstimator, PriorityFeeStrategy};
+pub use guard::BalanceGuard;
 pub use keypair::{EncryptedKeypair, KeypairManager, SecureKeypair};
-pub use rpc::{RpcClient, RpcClientConfig};
-pub use storage::{StorageService, WalletStorage};
-pub use token::{TokenAccountInfo, TokenInfo, TokenManager, TokenMetadataInfo};
-pub use transaction::{SimulationResult, TransactionBuilder, TransactionOptions, ValidationResult};
-pub use types::{AgentAction, AgentContext, PermissionLevel, WalletInfo};
-pub use wallet::{Wallet, WalletBuilder};
+pub use locked_keypair::LockedKeypair;
+pub use manager::{SharedComponents, WalletManager};
+pub use market::{DynMarketDataProvider, MarketDataProvider, PricePoint};
+pub use memo::{MemoAttribution, MemoMode};
+pub use metrics::{Metrics, MetricsServer};
+pub use multisig::MultisigConfig;
+pub use nonce::NonceInfo;
+pub use oracle::{DynPriceOracle, PriceOracle};
+pub use policy::{AddressPolicy, AddressPolicyMode};
+pub use preview::{BalanceChange, TokenDelta, TransferPreview};
+pub use program_accounts::{AccountFilter, ProgramAccountsQuery};
+pub use protocols::ProtocolSettings;
+pub use read_cache::{BlockhashCache, ReadCacheConfig};
+pub use rent::RentCalculator;
+pub use retry::RpcErrorClass;
+pub use rotation::{KeyRotationPolicy, RotationReason, RotationReport};
+pub use rpc::{
+    DynRpcProvider, EndpointReport, OfflineRpc, RpcClient, RpcClientConfig, RpcProvider,
+    SubscriptionClient,
+};
+pub use shutdown::{CancellationToken, ShutdownSignal};
+pub use signer::{DynTransactionSigner, RemoteSigner, RemoteSignerConfig, TransactionSigner};
+pub use sns::{NameResolver, Recipient};
+pub use sol::{Lamports, TokenAmount};
+pub use solana_pay::TransferRequest;
+pub use stats::{AgentStats, StatsSummary};
+pub use storage::{BackupInfo, PurgeReport, StorageService, WalletStorage};
+pub use store::{EnvStore, MemoryStore, WalletStore};
+pub use template::{ActionTemplate, PreparedTemplate, TemplateSet};
+pub use token::{RefreshReport, TokenAccountInfo, TokenInfo, TokenManager, TokenMetadataInfo};
+pub use transaction::{
+    ActionReceipt, BatchTransferReport, ConfirmationStrategy, PreparedAction, SimulationResult,
+    SubmissionMode, TransactionBuilder, TransactionOptions, ValidationResult,
+};
+pub use tx_details::TransactionDetails;
+pub use tx_policy::{PolicyInput, PolicyRule, TransactionPolicy};
+pub use types::{
+    AgentAction, AgentContext, PermissionLevel, TokenBalanceSummary, TriggerPayload, WalletInfo,
+};
+pub use wallet::{CreateReport, DeleteReport, Wallet, WalletBuilder};
+pub use watch::{BalanceEvent, BalanceWatcher};
+
+#[cfg(feature = "coingecko")]
+pub use coingecko::{CoinGeckoConfig, CoinGeckoProvider};
+#[cfg(feature = "jito")]
+pub use jito::{JitoClient, JitoConfig};
 
 // Type aliases for compatibility with architecture documentation
 /// Secure keypair type
@@ -0,0 +1,409 @@
+//! Keypair that re-encrypts itself in memory while idle
+//!
+//! A [`SecureKeypair`] holds the decrypted key for as long as the wallet is
+//! open. A [`LockedKeypair`] narrows that window for long-running daemons:
+//! once no signature was made for its idle timeout it encrypts the key
+//! bytes with a random session key and wipes the plaintext. The next
+//! signing call decrypts them again transparently; the time this takes is
+//! recorded in the `agent_wallet_key_unlock_seconds` histogram.
+//!
+//! The session key lives next to the ciphertext, so this is defense in
+//! depth against memory dumps and swap, not a second passphrase. With the
+//! `mlock` feature on unix, the pages holding key bytes and the session key
+//! are also locked into RAM so they are never written to swap. If
+//! `RLIMIT_MEMLOCK` is too low the key still works, unlocked, and a warning
+//! is logged.
+//!
+//! Wallets use a `LockedKeypair` when `wallet.key_idle_lock_seconds` is set.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use agent_wallet_core::keypair::SecureKeypair;
+//! use agent_wallet_core::locked_keypair::LockedKeypair;
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let key = Arc::new(LockedKeypair::new(
+//!     SecureKeypair::generate(),
+//!     Duration::from_secs(300),
+//! ));
+//! let _task = key.spawn_idle_lock();
+//! let signature = key.sign(b"hello")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature, signer::Signer};
+use tokio::task::JoinHandle;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::encryption::{EncryptedData, EncryptionService};
+use crate::error::{Error, Result};
+use crate::keypair::{KeyUsage, SecureKeypair};
+use crate::metrics::Metrics;
+use crate::sync::lock;
+
+/// Bytes of an ed25519 keypair
+const KEYPAIR_BYTES: usize = 64;
+
+/// Shortest interval between idle checks of the background task
+const MIN_IDLE_CHECK: Duration = Duration::from_millis(100);
+
+/// Keypair encrypted in memory after an idle timeout
+pub struct LockedKeypair {
+    public_key: Pubkey,
+    idle_lock: Duration,
+    state: Mutex<KeyState>,
+    usage: Arc<KeyUsage>,
+    metrics: Metrics,
+}
+
+/// Whether the key bytes are in the clear
+enum KeyState {
+    Unlocked {
+        key: PinnedBytes<KEYPAIR_BYTES>,
+        last_used: Instant,
+    },
+    Locked {
+        sealed: EncryptedData,
+        session_key: PinnedBytes<32>,
+    },
+}
+
+impl LockedKeypair {
+    /// Hold `keypair`, locking it after `idle_lock` without a signature
+    ///
+    /// Signatures keep counting in the keypair's [`KeyUsage`].
+    pub fn new(keypair: SecureKeypair, idle_lock: Duration) -> Self {
+        Self::new_at(keypair, idle_lock, Instant::now())
+    }
+
+    /// Like [`LockedKeypair::new`], idle from `now` on
+    pub fn new_at(keypair: SecureKeypair, idle_lock: Duration, now: Instant) -> Self {
+        Self {
+            public_key: keypair.public_key(),
+            idle_lock,
+            state: Mutex::new(KeyState::Unlocked {
+                key: PinnedBytes::new(*keypair.private_key_bytes()),
+                last_used: now,
+            }),
+            usage: keypair.usage().clone(),
+            metrics: Metrics::global().clone(),
+        }
+    }
+
+    /// Record unlock latency in `metrics` instead of [`Metrics::global`]
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Public key of the held keypair
+    pub fn public_key(&self) -> Pubkey {
+        self.public_key
+    }
+
+    /// Signatures made with the key
+    pub fn usage(&self) -> &Arc<KeyUsage> {
+        &self.usage
+    }
+
+    /// Idle time after which the key is locked
+    pub fn idle_lock(&self) -> Duration {
+        self.idle_lock
+    }
+
+    /// Whether the key bytes are currently encrypted
+    pub fn is_locked(&self) -> bool {
+        matches!(*self.state(), KeyState::Locked { .. })
+    }
+
+    /// Sign `message`, unlocking the key if needed
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        self.sign_at(message, Instant::now())
+    }
+
+    /// Sign `message` at `now`
+    pub fn sign_at(&self, message: &[u8], now: Instant) -> Result<Signature> {
+        let mut state = self.state();
+        if let KeyState::Locked {
+            sealed,
+            session_key,
+        } = &*state
+        {
+            let started = Instant::now();
+            let key = unseal(sealed, session_key)?;
+            *state = KeyState::Unlocked {
+                key,
+                last_used: now,
+            };
+            self.metrics.record_key_unlock(started.elapsed());
+            tracing::debug!("Unlocked key {}", self.public_key);
+        }
+        let KeyState::Unlocked { key, last_used } = &mut *state else {
+            return Err(Error::State(format!(
+                "Key {} did not unlock",
+                self.public_key
+            )));
+        };
+        let keypair = SecureKeypair::from_bytes(key.as_slice())?;
+        let signature = keypair.as_inner().sign_message(message);
+        *last_used = now;
+        self.usage.record();
+        Ok(signature)
+    }
+
+    /// Encrypt the key bytes now, whether idle or not
+    pub fn lock(&self) -> Result<()> {
+        let mut state = self.state();
+        if let KeyState::Unlocked { key, .. } = &*state {
+            let session_key = PinnedBytes::new(*EncryptionService::generate_key());
+            let sealed = EncryptionService::new_aes_gcm()
+                .encrypt(key.as_slice(), &Zeroizing::new(*session_key.array()))?;
+            // Dropping the unlocked state wipes the plaintext
+            *state = KeyState::Locked {
+                sealed,
+                session_key,
+            };
+            tracing::debug!("Locked idle key {}", self.public_key);
+        }
+        Ok(())
+    }
+
+    /// Lock the key if it was last used `idle_lock` or longer before `now`
+    ///
+    /// Returns whether the key is locked afterwards.
+    pub fn lock_if_idle(&self, now: Instant) -> Result<bool> {
+        let idle = match &*self.state() {
+            KeyState::Locked { .. } => return Ok(true),
+            KeyState::Unlocked { last_used, .. } => {
+                now.saturating_duration_since(*last_used) >= self.idle_lock
+            }
+        };
+        if idle {
+            self.lock()?;
+        }
+        Ok(idle)
+    }
+
+    /// Lock the key in the background once it has been idle long enough
+    ///
+    /// The task ends when the key is dropped.
+    pub fn spawn_idle_lock(self: &Arc<Self>) -> JoinHandle<()> {
+        let key: Weak<Self> = Arc::downgrade(self);
+        let period = (self.idle_lock / 4).max(MIN_IDLE_CHECK);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let Some(key) = key.upgrade() else {
+                    return;
+                };
+                if let Err(e) = key.lock_if_idle(Instant::now()) {
+                    tracing::warn!("Failed to lock idle key {}: {}", key.public_key, e);
+                }
+            }
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, KeyState> {
+        lock(&self.state)
+    }
+}
+
+impl fmt::Debug for LockedKeypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedKeypair")
+            .field("public_key", &self.public_key)
+            .field("locked", &self.is_locked())
+            .finish()
+    }
+}
+
+impl crate::signer::TransactionSigner for LockedKeypair {
+    fn pubkey(&self) -> impl std::future::Future<Output = Pubkey> + Send {
+        std::future::ready(self.public_key)
+    }
+
+    fn sign_message(
+        &self,
+        message: &[u8],
+    ) -> impl std::future::Future<Output = Result<Signature>> + Send {
+        std::future::ready(self.sign(message))
+    }
+}
+
+/// Decrypt sealed key bytes with the session key
+fn unseal(
+    sealed: &EncryptedData,
+    session_key: &PinnedBytes<32>,
+) -> Result<PinnedBytes<KEYPAIR_BYTES>> {
+    let plaintext =
+        EncryptionService::new_aes_gcm().decrypt(sealed, &Zeroizing::new(*session_key.array()))?;
+    let bytes: [u8; KEYPAIR_BYTES] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::encryption("Sealed key has the wrong length"))?;
+    Ok(PinnedBytes::new(bytes))
+}
+
+/// Heap bytes locked into RAM where supported, wiped when dropped
+struct PinnedBytes<const N: usize> {
+    bytes: Box<[u8; N]>,
+    locked: bool,
+}
+
+impl<const N: usize> PinnedBytes<N> {
+    /// Move `bytes` to the heap and try to lock them; the source is wiped
+    fn new(mut bytes: [u8; N]) -> Self {
+        let mut pinned = Self {
+            bytes: Box::new(bytes),
+            locked: false,
+        };
+        bytes.zeroize();
+        pinned.locked = memory_lock::lock(pinned.bytes.as_mut_ptr(), N);
+        pinned
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..]
+    }
+
+    fn array(&self) -> &[u8; N] {
+        &self.bytes
+    }
+}
+
+impl<const N: usize> Drop for PinnedBytes<N> {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            memory_lock::unlock(self.bytes.as_mut_ptr(), N);
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "mlock"))]
+mod memory_lock {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Whether the warning about failed locks was logged
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    /// Lock `len` bytes at `ptr` into RAM, warning once if that fails
+    pub(super) fn lock(ptr: *mut u8, len: usize) -> bool {
+        // SAFETY: `ptr` points to a live heap allocation of `len` bytes
+        let locked = unsafe { memsec::mlock(ptr, len) };
+        if !locked && !WARNED.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                "Could not lock key memory into RAM (RLIMIT_MEMLOCK too low?); \
+                 key bytes may be swapped to disk"
+            );
+        }
+        locked
+    }
+
+    /// Unlock memory locked with [`lock`]
+    pub(super) fn unlock(ptr: *mut u8, len: usize) {
+        // SAFETY: `ptr` is the live allocation `lock` succeeded on
+        unsafe {
+            memsec::munlock(ptr, len);
+        }
+    }
+}
+
+#[cfg(not(all(unix, feature = "mlock")))]
+mod memory_lock {
+    /// Memory locking is unavailable; nothing is locked
+    pub(super) fn lock(_ptr: *mut u8, _len: usize) -> bool {
+        false
+    }
+
+    pub(super) fn unlock(_ptr: *mut u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::Registry;
+
+    fn key(idle: Duration, now: Instant) -> Result<(SecureKeypair, LockedKeypair)> {
+        let keypair = SecureKeypair::generate();
+        let locked = LockedKeypair::new_at(keypair.clone(), idle, now)
+            .with_metrics(Metrics::new(Registry::new())?);
+        Ok((keypair, locked))
+    }
+
+    #[test]
+    fn test_idle_key_locks_and_unlocks_on_demand() -> Result<()> {
+        let start = Instant::now();
+        let idle = Duration::from_secs(60);
+        let (keypair, locked) = key(idle, start)?;
+
+        // Recently used keys stay unlocked
+        assert!(!locked.lock_if_idle(start + Duration::from_secs(59))?);
+        assert!(!locked.is_locked());
+
+        assert!(locked.lock_if_idle(start + idle)?);
+        assert!(locked.is_locked());
+
+        // Signing unlocks and restarts the idle timer
+        let at = start + Duration::from_secs(90);
+        let signature = locked.sign_at(b"message", at)?;
+        assert!(keypair.verify(b"message", &signature));
+        assert!(!locked.is_locked());
+        assert!(!locked.lock_if_idle(at + Duration::from_secs(30))?);
+        assert!(locked.lock_if_idle(at + idle)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_signatures_survive_lock_cycles() -> Result<()> {
+        let start = Instant::now();
+        let idle = Duration::from_secs(1);
+        let (keypair, locked) = key(idle, start)?;
+
+        let mut now = start;
+        for round in 0..5u8 {
+            now += idle;
+            assert!(locked.lock_if_idle(now)?);
+            let message = [round; 32];
+            let signature = locked.sign_at(&message, now)?;
+            assert_eq!(signature, keypair.sign(&message));
+        }
+        // The locked key shares the usage counter of the keypair it wraps
+        assert_eq!(locked.usage().signatures(), 10);
+        assert_eq!(locked.public_key(), keypair.public_key());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlock_latency_is_recorded() -> Result<()> {
+        let start = Instant::now();
+        let metrics = Metrics::new(Registry::new())?;
+        let locked = LockedKeypair::new_at(SecureKeypair::generate(), Duration::ZERO, start)
+            .with_metrics(metrics.clone());
+        locked.lock()?;
+        locked.sign_at(b"message", start)?;
+        assert!(metrics
+            .render()?
+            .contains("agent_wallet_key_unlock_seconds_count 1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_output_hides_key_bytes() -> Result<()> {
+        let (keypair, locked) = key(Duration::from_secs(1), Instant::now())?;
+        let debug = format!("{:?}", locked);
+        assert!(debug.contains(&keypair.public_key().to_string()));
+        assert!(!debug.contains(keypair.private_key_base58().as_str()));
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Tracing setup shared by the CLI and the service
+//!
+//! [`init_logging`] installs the global `tracing` subscriber described by
+//! [`LoggingSettings`]: plain text or JSON lines, at the configured level,
+//! to stderr or to a log file rotated on the configured schedule. A
+//! `RUST_LOG` filter in the environment overrides the level.
+//!
+//! Key operations run inside spans whose fields tie log lines together:
+//!
+//! - `wallet.transfer`: wallet name, action, amount and destination of
+//!   every action a wallet sends
+//! - `rpc.request`: method and endpoint of each RPC request
+//! - `agent.decide`: agent id and decision id of each tick; the decision id
+//!   is also the id of the tick's audit intent, so logs correlate with the
+//!   audit trail
+//!
+//! Span fields never carry passphrases, private keys or auth tokens.
+//! Types holding key material only show their public parts when formatted.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::config::{LogFormat, LoggingSettings};
+//! use agent_wallet_core::logging::init_logging;
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let settings = LoggingSettings {
+//!     format: LogFormat::Json,
+//!     ..Default::default()
+//! };
+//! init_logging(&settings)?;
+//! tracing::info!("Logging as JSON lines");
+//! # Ok(())
+//! # }
+//! ```
+
+use tracing::Subscriber;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{writer::BoxMakeWriter, MakeWriter},
+    EnvFilter,
+};
+
+use crate::config::{LogFormat, LogLevel, LogRotation, LoggingSettings};
+use crate::error::{Error, Result};
+
+/// Install the global subscriber described by `settings`
+///
+/// Fails if file logging is enabled without a log file, or if a global
+/// subscriber is already installed.
+pub fn init_logging(settings: &LoggingSettings) -> Result<()> {
+    let writer = if settings.log_to_file {
+        let path = settings.log_file.as_ref().ok_or_else(|| {
+            Error::config("logging.log_to_file is set but logging.log_file is not")
+        })?;
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::config(format!("Invalid log file {}", path.display())))?;
+        let directory = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| std::path::Path::new("."));
+        std::fs::create_dir_all(directory)?;
+        BoxMakeWriter::new(RollingFileAppender::new(
+            rotation(&settings.rotation),
+            directory,
+            file_name,
+        ))
+    } else {
+        BoxMakeWriter::new(std::io::stderr)
+    };
+
+    tracing::subscriber::set_global_default(subscriber(settings, writer))
+        .map_err(|e| Error::config(format!("Logging is already initialized: {}", e)))
+}
+
+/// Subscriber formatting events as `settings` asks, into `writer`
+fn subscriber<W>(settings: &LoggingSettings, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::builder()
+        .with_default_directive(level_filter(&settings.level).into())
+        .from_env_lossy();
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(!settings.log_to_file);
+    match settings.format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().with_current_span(true).finish()),
+    }
+}
+
+/// Most verbose level `level` lets through
+fn level_filter(level: &LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
+
+/// Rotation schedule of the log file
+fn rotation(rotation: &LogRotation) -> Rotation {
+    match rotation {
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RpcEndpoint;
+    use crate::keypair::SecureKeypair;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer collecting everything logged into a shared buffer
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .map_err(|_| io::Error::other("poisoned"))?
+                .extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Capture {
+        fn contents(&self) -> String {
+            self.0
+                .lock()
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default()
+        }
+    }
+
+    #[test]
+    fn test_spans_never_leak_private_keys() -> Result<()> {
+        let keypair = SecureKeypair::generate();
+        for format in [LogFormat::Text, LogFormat::Json] {
+            let settings = LoggingSettings {
+                format,
+                ..Default::default()
+            };
+            let capture = Capture::default();
+            let writer = capture.clone();
+            let subscriber = subscriber(&settings, move || writer.clone());
+
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!(
+                    "wallet.transfer",
+                    signer = ?keypair,
+                    destination = %keypair.public_key()
+                );
+                let _entered = span.enter();
+                tracing::info!(keypair = ?keypair, "Signing transfer");
+            });
+
+            let logged = capture.contents();
+            assert!(logged.contains("wallet.transfer"));
+            assert!(logged.contains(&keypair.public_key().to_string()));
+            assert!(!logged.contains(keypair.private_key_base58().as_str()));
+            // Nor the 32-byte secret half on its own
+            let secret = bs58::encode(&keypair.private_key_bytes()[..32]).into_string();
+            assert!(!logged.contains(&secret));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rpc_auth_tokens_stay_out_of_logs() {
+        let endpoint =
+            RpcEndpoint::new("https://rpc.example.com/?api-key=in-url").with_auth_token("s3cret");
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = subscriber(&LoggingSettings::default(), move || writer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(endpoint = ?endpoint, "Connecting");
+            tracing::info!("Routing RPC traffic to {}", endpoint);
+        });
+
+        let logged = capture.contents();
+        assert!(logged.contains("rpc.example.com"));
+        assert!(!logged.contains("s3cret"));
+        assert!(!logged.contains("in-url"));
+    }
+
+    #[test]
+    fn test_file_logging_needs_a_file() {
+        let settings = LoggingSettings {
+            log_to_file: true,
+            log_file: None,
+            ..Default::default()
+        };
+        assert!(matches!(init_logging(&settings), Err(Error::Config(_))));
+    }
+}
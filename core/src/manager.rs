@@ -0,0 +1,476 @@
+//! Many wallets on one set of RPC and token components
+//!
+//! Every [`Wallet::create`] or [`Wallet::load`] connects its own
+//! [`RpcClient`] and [`TokenManager`], so twenty agents mean twenty
+//! connection pools and twenty metric registries. A [`WalletManager`]
+//! connects once, on first use, and opens every wallet on those
+//! [`SharedComponents`].
+//!
+//! Open wallets are cached by name and closed again once they have been
+//! idle for the manager's idle timeout and nobody holds them any more.
+//! Balances of every stored wallet come from batched `getMultipleAccounts`
+//! calls without unlocking a single key.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::manager::WalletManager;
+//! use agent_wallet_core::WalletConfig;
+//! use zeroize::Zeroizing;
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let manager = WalletManager::new(WalletConfig::default());
+//! let passphrase = Zeroizing::new("secure-passphrase".to_string());
+//! let trader = manager.get_or_load("trader", &passphrase).await?;
+//! let sweeper = manager.get_or_load("sweeper", &passphrase).await?;
+//!
+//! for info in manager.balances_for_all().await? {
+//!     println!("{}: {} lamports", info.name, info.balance_lamports);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::sync::{Mutex, OnceCell, RwLock};
+use zeroize::Zeroizing;
+
+use crate::config::{RpcSettings, WalletConfig};
+use crate::error::Result;
+use crate::keystore::{Keystore, OsKeystore};
+use crate::rpc::{DynRpcProvider, OfflineRpc, RpcClient, RpcClientConfig};
+use crate::token::{TokenManager, MAX_MULTIPLE_ACCOUNTS};
+use crate::types::WalletInfo;
+use crate::wallet::Wallet;
+
+/// How long an unused wallet stays open by default
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// RPC provider, token manager and keychain wallets can share
+#[derive(Clone)]
+pub struct SharedComponents {
+    rpc_client: Arc<dyn DynRpcProvider>,
+    token_manager: Arc<RwLock<TokenManager>>,
+    keystore: Arc<dyn Keystore>,
+}
+
+impl SharedComponents {
+    /// Components on an existing provider
+    pub fn new(rpc_client: Arc<dyn DynRpcProvider>, commitment: CommitmentConfig) -> Self {
+        let token_manager = TokenManager::with_provider(rpc_client.clone(), commitment);
+        Self {
+            rpc_client,
+            token_manager: Arc::new(RwLock::new(token_manager)),
+            keystore: Arc::new(OsKeystore::default()),
+        }
+    }
+
+    /// Keep the secrets of keystore-bound wallets in `keystore`
+    ///
+    /// Defaults to the OS keychain.
+    pub fn with_keystore(mut self, keystore: Arc<dyn Keystore>) -> Self {
+        self.keystore = keystore;
+        self
+    }
+
+    /// Components on a new [`RpcClient`] for the configured endpoints
+    pub async fn connect(config: &WalletConfig) -> Result<Self> {
+        Self::connect_with(&RpcClientFactory, config).await
+    }
+
+    /// Components on a provider made by `factory`
+    pub async fn connect_with(factory: &dyn DynRpcFactory, config: &WalletConfig) -> Result<Self> {
+        let rpc_client = factory.connect(&config.rpc).await?;
+        Ok(Self::new(
+            rpc_client,
+            config.rpc.commitment.to_solana_commitment(),
+        ))
+    }
+
+    /// Components on an [`OfflineRpc`] that refuses every call
+    ///
+    /// For air-gapped machines that only sign; see [`Wallet::load_offline`].
+    pub fn offline(config: &WalletConfig) -> Self {
+        Self::new(
+            Arc::new(OfflineRpc),
+            config.rpc.commitment.to_solana_commitment(),
+        )
+    }
+
+    /// The shared RPC provider
+    pub fn rpc_client(&self) -> Arc<dyn DynRpcProvider> {
+        self.rpc_client.clone()
+    }
+
+    /// The shared token manager
+    pub fn token_manager(&self) -> Arc<RwLock<TokenManager>> {
+        self.token_manager.clone()
+    }
+
+    /// Keychain holding the secrets of keystore-bound wallets
+    pub fn keystore(&self) -> Arc<dyn Keystore> {
+        self.keystore.clone()
+    }
+}
+
+/// Connects the RPC provider of a [`WalletManager`]
+pub trait RpcFactory: Send + Sync {
+    /// Provider for the endpoints in `settings`
+    fn connect(
+        &self,
+        settings: &RpcSettings,
+    ) -> impl Future<Output = Result<Arc<dyn DynRpcProvider>>> + Send;
+}
+
+/// Object-safe form of [`RpcFactory`], implemented for every factory
+pub trait DynRpcFactory: Send + Sync {
+    /// Provider for the endpoints in `settings`
+    fn connect<'a>(
+        &'a self,
+        settings: &'a RpcSettings,
+    ) -> BoxFuture<'a, Result<Arc<dyn DynRpcProvider>>>;
+}
+
+impl<F: RpcFactory> DynRpcFactory for F {
+    fn connect<'a>(
+        &'a self,
+        settings: &'a RpcSettings,
+    ) -> BoxFuture<'a, Result<Arc<dyn DynRpcProvider>>> {
+        Box::pin(RpcFactory::connect(self, settings))
+    }
+}
+
+/// Factory connecting an [`RpcClient`]
+pub struct RpcClientFactory;
+
+impl RpcFactory for RpcClientFactory {
+    async fn connect(&self, settings: &RpcSettings) -> Result<Arc<dyn DynRpcProvider>> {
+        let client = RpcClient::new(RpcClientConfig::from_settings(settings)).await?;
+        Ok(Arc::new(client))
+    }
+}
+
+/// A cached wallet and when it was last handed out
+struct OpenWallet {
+    wallet: Arc<Wallet>,
+    last_used: Instant,
+}
+
+/// Opens wallets on demand, all on one set of [`SharedComponents`]
+pub struct WalletManager {
+    config: WalletConfig,
+    factory: Arc<dyn DynRpcFactory>,
+    shared: OnceCell<SharedComponents>,
+    open: Mutex<HashMap<String, OpenWallet>>,
+    idle_timeout: Duration,
+}
+
+impl WalletManager {
+    /// Manager opening wallets with `config` on an [`RpcClient`]
+    pub fn new(config: WalletConfig) -> Self {
+        Self::with_factory(config, Arc::new(RpcClientFactory))
+    }
+
+    /// Manager connecting its provider through `factory`
+    pub fn with_factory(config: WalletConfig, factory: Arc<dyn DynRpcFactory>) -> Self {
+        Self {
+            config,
+            factory,
+            shared: OnceCell::new(),
+            open: Mutex::new(HashMap::new()),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Close wallets unused for longer than `idle_timeout`
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Configuration every wallet is opened with
+    pub fn config(&self) -> &WalletConfig {
+        &self.config
+    }
+
+    /// The shared components, connecting them on first use
+    pub async fn shared(&self) -> Result<&SharedComponents> {
+        self.shared
+            .get_or_try_init(|| SharedComponents::connect_with(self.factory.as_ref(), &self.config))
+            .await
+    }
+
+    /// The open wallet named `name`, loading it from storage if needed
+    pub async fn get_or_load(
+        &self,
+        name: &str,
+        passphrase: &Zeroizing<String>,
+    ) -> Result<Arc<Wallet>> {
+        let shared = self.shared().await?;
+        let mut open = self.open.lock().await;
+        self.evict_idle_locked(&mut open);
+        if let Some(entry) = open.get_mut(name) {
+            entry.last_used = Instant::now();
+            return Ok(entry.wallet.clone());
+        }
+
+        let wallet = Arc::new(
+            Wallet::load_with_shared(name, passphrase, self.config.clone(), shared).await?,
+        );
+        open.insert(name.to_string(), OpenWallet::new(wallet.clone()));
+        Ok(wallet)
+    }
+
+    /// Create a wallet and keep it open
+    pub async fn create(&self, name: &str, passphrase: &Zeroizing<String>) -> Result<Arc<Wallet>> {
+        let shared = self.shared().await?;
+        let wallet = Arc::new(
+            Wallet::create_with_shared(name, passphrase, self.config.clone(), shared).await?,
+        );
+        let mut open = self.open.lock().await;
+        self.evict_idle_locked(&mut open);
+        open.insert(name.to_string(), OpenWallet::new(wallet.clone()));
+        Ok(wallet)
+    }
+
+    /// The wallet named `name` if it is open
+    pub async fn get(&self, name: &str) -> Option<Arc<Wallet>> {
+        let mut open = self.open.lock().await;
+        let entry = open.get_mut(name)?;
+        entry.last_used = Instant::now();
+        Some(entry.wallet.clone())
+    }
+
+    /// Names of the open wallets, sorted
+    pub async fn open_wallets(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.open.lock().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Close the wallet named `name`; returns whether it was open
+    ///
+    /// Callers still holding the wallet keep using it until they drop it.
+    pub async fn close(&self, name: &str) -> bool {
+        self.open.lock().await.remove(name).is_some()
+    }
+
+    /// Close wallets idle for longer than the idle timeout, returning their names
+    ///
+    /// Wallets still held outside the manager stay open.
+    pub async fn evict_idle(&self) -> Vec<String> {
+        let mut open = self.open.lock().await;
+        self.evict_idle_locked(&mut open)
+    }
+
+    fn evict_idle_locked(&self, open: &mut HashMap<String, OpenWallet>) -> Vec<String> {
+        let idle: Vec<String> = open
+            .iter()
+            .filter(|(_, entry)| {
+                entry.last_used.elapsed() >= self.idle_timeout
+                    && Arc::strong_count(&entry.wallet) == 1
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &idle {
+            open.remove(name);
+            tracing::debug!("Closed idle wallet '{}'", name);
+        }
+        idle
+    }
+
+    /// Every stored wallet with its SOL balance
+    ///
+    /// Balances come from `getMultipleAccounts`, one call per 100 wallets;
+    /// no wallet is unlocked. Wallets whose account does not exist yet
+    /// report zero.
+    pub async fn balances_for_all(&self) -> Result<Vec<WalletInfo>> {
+        let rpc_client = self.shared().await?.rpc_client();
+        Wallet::list_wallets_with(&self.config, rpc_client.as_ref(), true).await
+    }
+}
+
+/// Fill in the SOL balance of every wallet, one `getMultipleAccounts` call
+/// per 100 wallets
+pub(crate) async fn hydrate_balances(
+    rpc: &dyn DynRpcProvider,
+    wallets: &mut [WalletInfo],
+) -> Result<()> {
+    for chunk in wallets.chunks_mut(MAX_MULTIPLE_ACCOUNTS) {
+        let keys: Vec<_> = chunk.iter().map(|info| info.public_key).collect();
+        let accounts = rpc.get_multiple_accounts(&keys).await?;
+        for (info, account) in chunk.iter_mut().zip(accounts) {
+            info.balance_lamports = account.map_or(0, |account| account.lamports);
+        }
+    }
+    Ok(())
+}
+
+impl OpenWallet {
+    fn new(wallet: Arc<Wallet>) -> Self {
+        Self {
+            wallet,
+            last_used: Instant::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{StorageBackend, StorageSettings};
+    use crate::rpc::mock::MockRpc;
+    use solana_sdk::account::Account;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+
+    /// Factory handing out one mock and counting its connections
+    struct CountingFactory {
+        rpc: Arc<MockRpc>,
+        connections: AtomicUsize,
+    }
+
+    impl RpcFactory for CountingFactory {
+        async fn connect(&self, _settings: &RpcSettings) -> Result<Arc<dyn DynRpcProvider>> {
+            self.connections.fetch_add(1, Ordering::SeqCst);
+            Ok(self.rpc.clone())
+        }
+    }
+
+    fn manager(dir: &std::path::Path, namespace: &str) -> (WalletManager, Arc<CountingFactory>) {
+        let mut config = WalletConfig::default();
+        config.wallet.storage = StorageSettings {
+            path: dir.join("wallets"),
+            backup_path: dir.join("backups"),
+            max_versions: 3,
+            backend: StorageBackend::Memory {
+                namespace: namespace.to_string(),
+            },
+        };
+        let factory = Arc::new(CountingFactory {
+            rpc: Arc::new(MockRpc::new()),
+            connections: AtomicUsize::new(0),
+        });
+        (
+            WalletManager::with_factory(config, factory.clone()),
+            factory,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_wallets_share_one_rpc_client() -> Result<()> {
+        let dir = tempdir()?;
+        let passphrase = Zeroizing::new("manager-passphrase".to_string());
+        let names = ["alpha", "beta", "gamma", "delta"];
+        {
+            let (creator, _) = manager(dir.path(), "shared-client");
+            for name in names {
+                creator.create(name, &passphrase).await?;
+            }
+        }
+
+        let (manager, factory) = manager(dir.path(), "shared-client");
+        assert_eq!(factory.connections.load(Ordering::SeqCst), 0);
+        let mut wallets = Vec::new();
+        for name in names {
+            wallets.push(manager.get_or_load(name, &passphrase).await?);
+        }
+        let again = manager.get_or_load("alpha", &passphrase).await?;
+        assert!(Arc::ptr_eq(&again, &wallets[0]));
+        assert_eq!(factory.connections.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            manager.open_wallets().await,
+            vec!["alpha", "beta", "delta", "gamma"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_wallets_close_once_released() -> Result<()> {
+        let dir = tempdir()?;
+        let passphrase = Zeroizing::new("manager-passphrase".to_string());
+        let (manager, _) = manager(dir.path(), "idle");
+        let manager = manager.with_idle_timeout(Duration::ZERO);
+        let held = manager.create("held", &passphrase).await?;
+        drop(manager.create("released", &passphrase).await?);
+
+        assert_eq!(manager.evict_idle().await, vec!["released".to_string()]);
+        assert_eq!(manager.open_wallets().await, vec!["held"]);
+        drop(held);
+        assert_eq!(manager.evict_idle().await, vec!["held".to_string()]);
+
+        // Closed wallets load again on demand
+        manager.get_or_load("released", &passphrase).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_balances_for_all_is_one_batched_call() -> Result<()> {
+        let dir = tempdir()?;
+        let passphrase = Zeroizing::new("manager-passphrase".to_string());
+        let (manager, factory) = manager(dir.path(), "balances");
+        let funded = manager.create("funded", &passphrase).await?;
+        manager.create("empty", &passphrase).await?;
+        factory.rpc.set_account(
+            funded.public_key(),
+            Account {
+                lamports: 2_500_000_000,
+                data: Vec::new(),
+                owner: solana_sdk::system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let calls = factory.rpc.batch_calls();
+        let mut balances: Vec<(String, u64)> = manager
+            .balances_for_all()
+            .await?
+            .into_iter()
+            .map(|info| (info.name, info.balance_lamports))
+            .collect();
+        balances.sort();
+        assert_eq!(
+            balances,
+            vec![
+                ("empty".to_string(), 0),
+                ("funded".to_string(), 2_500_000_000)
+            ]
+        );
+        assert_eq!(factory.rpc.batch_calls(), calls + 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_listing_without_hydration_makes_no_rpc_calls() -> Result<()> {
+        let dir = tempdir()?;
+        let passphrase = Zeroizing::new("manager-passphrase".to_string());
+        let (manager, factory) = manager(dir.path(), "unhydrated");
+        manager.create("funded", &passphrase).await?;
+
+        let calls = (
+            factory.rpc.batch_calls(),
+            factory.rpc.history_calls(),
+            factory.rpc.program_account_calls(),
+        );
+        let listed =
+            Wallet::list_wallets_with(manager.config(), factory.rpc.as_ref(), false).await?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].balance_lamports, 0);
+        assert_eq!(
+            calls,
+            (
+                factory.rpc.batch_calls(),
+                factory.rpc.history_calls(),
+                factory.rpc.program_account_calls(),
+            )
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+//! Market data providers feeding the agent context's price feeds
+//!
+//! A [`MarketDataProvider`] quotes a batch of symbols (or mint addresses) in
+//! USD, each as a [`PricePoint`] stamped with its publish time. The
+//! [`ContextBuilder`](crate::context::ContextBuilder) polls every configured
+//! provider for the watchlist and keeps the freshest quote per symbol, so
+//! one provider failing only costs the symbols nobody else quotes.
+//!
+//! Any [`PriceOracle`] becomes a provider through [`OracleProvider`]; the
+//! [`PythOracle`] reads Pyth's on-chain price accounts this way. The
+//! CoinGecko provider lives in [`coingecko`](crate::coingecko) behind the
+//! `coingecko` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use agent_wallet_core::market::{OracleProvider, PythOracle};
+//! use agent_wallet_core::DynRpcProvider;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(
+//! #     rpc: Arc<dyn DynRpcProvider>,
+//! #     sol_feed: Pubkey,
+//! #     wrapped_sol: Pubkey,
+//! # ) -> agent_wallet_core::Result<()> {
+//! let pyth = PythOracle::new(rpc)
+//!     .with_feed("SOL", sol_feed)
+//!     .with_mint(wrapped_sol, "SOL");
+//! let provider = OracleProvider::new("pyth", Arc::new(pyth));
+//!
+//! let prices = provider.quote(&["SOL".to_string()]).await?;
+//! println!("SOL at {:?}", prices.get("SOL").map(|point| point.price));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+use crate::error::{Error, Result};
+use crate::oracle::{DynPriceOracle, PriceOracle};
+use crate::rpc::DynRpcProvider;
+use crate::types::OracleData;
+
+/// Program owning Pyth's pull-oracle price update accounts
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+/// Anchor discriminator of a `PriceUpdateV2` account
+const PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// One USD quote of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PricePoint {
+    /// Price in USD
+    pub price: f64,
+    /// Change over the last 24 hours in percent, if the source reports it
+    pub change_24h: Option<f64>,
+    /// When the source published the price
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<&OracleData> for PricePoint {
+    fn from(data: &OracleData) -> Self {
+        Self {
+            price: data.price,
+            change_24h: None,
+            timestamp: data.timestamp,
+        }
+    }
+}
+
+/// Source of USD prices for batches of symbols or mint addresses
+pub trait MarketDataProvider: Send + Sync {
+    /// Name of the source, used in logs and as the quote's source
+    fn name(&self) -> &str;
+
+    /// Prices of those `symbols` the source knows, keyed as requested
+    ///
+    /// Symbols the source does not quote are left out; an error means the
+    /// source could not be asked at all.
+    fn get_prices(
+        &self,
+        symbols: &[String],
+    ) -> impl Future<Output = Result<HashMap<String, PricePoint>>> + Send;
+}
+
+/// Object-safe form of [`MarketDataProvider`], implemented for every provider
+pub trait DynMarketDataProvider: Send + Sync {
+    /// Name of the source, used in logs and as the quote's source
+    fn name(&self) -> &str;
+
+    /// Prices of those `symbols` the source knows, keyed as requested
+    fn get_prices<'a>(
+        &'a self,
+        symbols: &'a [String],
+    ) -> BoxFuture<'a, Result<HashMap<String, PricePoint>>>;
+}
+
+impl<P: MarketDataProvider> DynMarketDataProvider for P {
+    fn name(&self) -> &str {
+        MarketDataProvider::name(self)
+    }
+
+    fn get_prices<'a>(
+        &'a self,
+        symbols: &'a [String],
+    ) -> BoxFuture<'a, Result<HashMap<String, PricePoint>>> {
+        Box::pin(MarketDataProvider::get_prices(self, symbols))
+    }
+}
+
+/// [`MarketDataProvider`] quoting symbol by symbol from a [`PriceOracle`]
+pub struct OracleProvider {
+    name: String,
+    oracle: Arc<dyn DynPriceOracle>,
+}
+
+impl OracleProvider {
+    /// Provider named `name` asking `oracle` for each symbol
+    pub fn new(name: impl Into<String>, oracle: Arc<dyn DynPriceOracle>) -> Self {
+        Self {
+            name: name.into(),
+            oracle,
+        }
+    }
+
+    /// Quote `symbols`, skipping those the oracle fails on
+    ///
+    /// Fails only if symbols were asked for and none could be quoted.
+    pub async fn quote(&self, symbols: &[String]) -> Result<HashMap<String, PricePoint>> {
+        let mut prices = HashMap::new();
+        let mut last_error = None;
+        for symbol in symbols {
+            match self.oracle.get_price(symbol).await {
+                Ok(data) => {
+                    prices.insert(symbol.clone(), PricePoint::from(&data));
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if prices.is_empty() => Err(e),
+            _ => Ok(prices),
+        }
+    }
+}
+
+impl MarketDataProvider for OracleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_prices(
+        &self,
+        symbols: &[String],
+    ) -> impl Future<Output = Result<HashMap<String, PricePoint>>> + Send {
+        self.quote(symbols)
+    }
+}
+
+/// [`PriceOracle`] reading Pyth price update accounts on chain
+///
+/// Each symbol is mapped to the price account of its feed; mint addresses
+/// can be mapped to a symbol so watchlists keyed by mint resolve to the
+/// same feed.
+pub struct PythOracle {
+    rpc_client: Arc<dyn DynRpcProvider>,
+    feeds: HashMap<String, Pubkey>,
+    mints: HashMap<String, String>,
+}
+
+impl PythOracle {
+    /// Oracle without feeds, reading accounts through `rpc_client`
+    pub fn new(rpc_client: Arc<dyn DynRpcProvider>) -> Self {
+        Self {
+            rpc_client,
+            feeds: HashMap::new(),
+            mints: HashMap::new(),
+        }
+    }
+
+    /// Quote `symbol` from the price update account `price_account`
+    pub fn with_feed(mut self, symbol: impl Into<String>, price_account: Pubkey) -> Self {
+        self.feeds.insert(symbol.into(), price_account);
+        self
+    }
+
+    /// Quote `mint` with the feed of `symbol`
+    pub fn with_mint(mut self, mint: Pubkey, symbol: impl Into<String>) -> Self {
+        self.mints.insert(mint.to_string(), symbol.into());
+        self
+    }
+
+    /// Price account quoting `symbol`, following mint aliases
+    pub fn price_account(&self, symbol: &str) -> Option<Pubkey> {
+        let symbol = self.mints.get(symbol).map_or(symbol, String::as_str);
+        self.feeds.get(symbol).copied()
+    }
+
+    async fn read_price(&self, symbol: &str) -> Result<OracleData> {
+        let account = self.price_account(symbol).ok_or_else(|| {
+            Error::validation(format!("No Pyth price feed configured for {}", symbol))
+        })?;
+        let data = self.rpc_client.get_account(&account).await?;
+        if data.owner != PYTH_RECEIVER_PROGRAM_ID {
+            return Err(Error::validation(format!(
+                "Account {} is owned by {}, not the Pyth receiver",
+                account, data.owner
+            )));
+        }
+        let update = decode_price_update(&data.data)?;
+        Ok(update.to_oracle_data())
+    }
+}
+
+impl PriceOracle for PythOracle {
+    fn get_price(&self, symbol: &str) -> impl Future<Output = Result<OracleData>> + Send {
+        self.read_price(symbol)
+    }
+}
+
+/// Price message of a Pyth `PriceUpdateV2` account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PythPrice {
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+    ema_price: i64,
+}
+
+impl PythPrice {
+    fn scale(&self, value: f64) -> f64 {
+        value * 10f64.powi(self.exponent)
+    }
+
+    fn to_oracle_data(self) -> OracleData {
+        OracleData {
+            source: "pyth".to_string(),
+            price: self.scale(self.price as f64),
+            confidence: self.scale(self.conf as f64),
+            timestamp: DateTime::from_timestamp(self.publish_time, 0).unwrap_or_default(),
+            ema: Some(self.scale(self.ema_price as f64)),
+        }
+    }
+}
+
+/// Decode the price message of a `PriceUpdateV2` account
+fn decode_price_update(data: &[u8]) -> Result<PythPrice> {
+    let invalid = || Error::serialization("Malformed Pyth price update account");
+    if data.get(..8) != Some(&PRICE_UPDATE_DISCRIMINATOR[..]) {
+        return Err(invalid());
+    }
+    // Discriminator, then the write authority
+    let mut offset = 8 + 32;
+    // Verification level: `Partial { num_signatures: u8 }` or `Full`
+    offset += match data.get(offset) {
+        Some(0) => 2,
+        Some(1) => 1,
+        _ => return Err(invalid()),
+    };
+    // Feed id, then the price fields
+    offset += 32;
+    let field = |at: usize| -> Result<[u8; 8]> {
+        data.get(at..at + 8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(invalid)
+    };
+    let price = i64::from_le_bytes(field(offset)?);
+    let conf = u64::from_le_bytes(field(offset + 8)?);
+    let exponent = data
+        .get(offset + 16..offset + 20)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(i32::from_le_bytes)
+        .ok_or_else(invalid)?;
+    let publish_time = i64::from_le_bytes(field(offset + 20)?);
+    // The previous publish time sits between the publish time and the EMA
+    let ema_price = i64::from_le_bytes(field(offset + 36)?);
+    Ok(PythPrice {
+        price,
+        conf,
+        exponent,
+        publish_time,
+        ema_price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+    use solana_sdk::account::Account;
+
+    /// `PriceUpdateV2` account data with full verification
+    fn price_update(price: i64, conf: u64, exponent: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.push(1);
+        data.extend_from_slice(&[7u8; 32]);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&exponent.to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&(publish_time - 1).to_le_bytes());
+        data.extend_from_slice(&(price - 100).to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data
+    }
+
+    fn pyth_account(data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner: PYTH_RECEIVER_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pyth_oracle_reads_price_updates() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let feed = Pubkey::new_unique();
+        rpc.set_account(
+            feed,
+            pyth_account(price_update(15_025_000_000, 5_000_000, -8, 1_700_000_000)),
+        );
+        let mint = Pubkey::new_unique();
+        let oracle = PythOracle::new(rpc.clone())
+            .with_feed("SOL", feed)
+            .with_mint(mint, "SOL");
+
+        let data = PriceOracle::get_price(&oracle, &mint.to_string()).await?;
+        assert_eq!(data.source, "pyth");
+        assert!((data.price - 150.25).abs() < 1e-9);
+        assert!((data.confidence - 0.05).abs() < 1e-9);
+        assert_eq!(data.timestamp.timestamp(), 1_700_000_000);
+        assert!(data.ema.is_some_and(|ema| (ema - 150.249_999).abs() < 1e-9));
+
+        // Unknown symbols and foreign accounts are refused
+        assert!(PriceOracle::get_price(&oracle, "BONK").await.is_err());
+        let mut foreign = pyth_account(price_update(1, 1, 0, 1_700_000_000));
+        foreign.owner = Pubkey::new_unique();
+        rpc.set_account(feed, foreign);
+        assert!(PriceOracle::get_price(&oracle, "SOL").await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_price_update_handles_partial_verification() -> Result<()> {
+        let mut data = price_update(2_000, 10, -2, 1_700_000_000);
+        // Partial verification carries its signature count
+        data[40] = 0;
+        data.insert(41, 3);
+        let price = decode_price_update(&data)?;
+        assert_eq!(price.price, 2_000);
+        assert_eq!(price.exponent, -2);
+        assert_eq!(price.publish_time, 1_700_000_000);
+
+        assert!(decode_price_update(&data[..60]).is_err());
+        assert!(decode_price_update(&[0u8; 200]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oracle_provider_skips_failed_symbols() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let feed = Pubkey::new_unique();
+        rpc.set_account(
+            feed,
+            pyth_account(price_update(100_000, 0, -3, 1_700_000_000)),
+        );
+        let oracle = PythOracle::new(rpc).with_feed("SOL", feed);
+        let provider = OracleProvider::new("pyth", Arc::new(oracle));
+
+        let symbols = vec!["SOL".to_string(), "BONK".to_string()];
+        let prices = provider.quote(&symbols).await?;
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices.get("SOL").map(|point| point.price), Some(100.0));
+        assert_eq!(prices.get("SOL").and_then(|point| point.change_24h), None);
+
+        // Nothing quoted at all is an error
+        assert!(provider.quote(&["BONK".to_string()]).await.is_err());
+        Ok(())
+    }
+}
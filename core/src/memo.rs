@@ -0,0 +1,289 @@
+//! Memo instructions with optional agent attribution
+//!
+//! In [`MemoMode::Plain`] a transfer's memo is written as given. In
+//! [`MemoMode::Attributed`] it is wrapped in a compact key=value header
+//! naming the agent, the decision and the crate version, so a transaction
+//! read back from the chain can be traced to the decision that sent it:
+//!
+//! ```text
+//! aw1;agent=rebalancer;decision=7f0c…;v=0.1.0;memo=weekly rebalance
+//! ```
+//!
+//! The user memo comes last and may contain anything. Memos are capped at
+//! [`TransactionOptions::memo_max_bytes`](crate::transaction::TransactionOptions::memo_max_bytes);
+//! an attributed memo over the cap loses the end of the user memo first,
+//! then the end of the agent id.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::memo::{compose_memo, parse_attributed_memo, MemoAttribution, MemoMode};
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let attribution = MemoAttribution::new("rebalancer", uuid::Uuid::new_v4());
+//! let memo = compose_memo(Some("weekly"), MemoMode::Attributed, Some(&attribution), 256)?;
+//! let parsed = memo.as_deref().and_then(parse_attributed_memo);
+//! assert_eq!(parsed, Some(attribution));
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// Default cap on memo length in bytes
+pub const DEFAULT_MEMO_MAX_BYTES: usize = 256;
+
+/// Marker and format version starting every attributed memo
+const ATTRIBUTED_PREFIX: &str = "aw1;";
+
+/// How a transaction's memo is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoMode {
+    /// The memo exactly as given
+    #[default]
+    Plain,
+    /// The memo wrapped with the agent id, decision id and crate version
+    ///
+    /// Without an attribution to write, memos are written as in `Plain`.
+    Attributed,
+}
+
+/// Which agent decision produced a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoAttribution {
+    /// Agent that decided on the transaction
+    pub agent_id: String,
+    /// Decision the transaction executes
+    pub decision_id: Uuid,
+    /// Version of the wallet crate that built it
+    pub version: String,
+}
+
+impl MemoAttribution {
+    /// Attribution for a decision made with this version of the crate
+    pub fn new(agent_id: impl Into<String>, decision_id: Uuid) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            decision_id,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Header preceding the user memo, with the agent id cut to `agent_len` bytes
+    fn header(&self, agent_len: usize) -> String {
+        // `;` separates fields, so it cannot appear in the agent id
+        let agent = truncate(&self.agent_id, agent_len).replace(';', "_");
+        format!(
+            "{}agent={};decision={};v={}",
+            ATTRIBUTED_PREFIX, agent, self.decision_id, self.version
+        )
+    }
+}
+
+/// Memo text to write for `memo` in `mode`, `None` if there is nothing to write
+///
+/// Plain memos are returned unchanged and refused if longer than
+/// `max_bytes`. Attributed memos are always written, truncated to fit.
+pub fn compose_memo(
+    memo: Option<&str>,
+    mode: MemoMode,
+    attribution: Option<&MemoAttribution>,
+    max_bytes: usize,
+) -> Result<Option<String>> {
+    let memo = memo.filter(|memo| !memo.is_empty());
+    let attribution = match (mode, attribution) {
+        (MemoMode::Attributed, Some(attribution)) => attribution,
+        _ => {
+            if let Some(memo) = memo {
+                check_length(memo.as_bytes(), max_bytes)?;
+            }
+            return Ok(memo.map(str::to_string));
+        }
+    };
+
+    let mut header = attribution.header(attribution.agent_id.len());
+    if header.len() > max_bytes {
+        let excess = header.len() - max_bytes;
+        let agent_len = attribution.agent_id.len().saturating_sub(excess);
+        header = attribution.header(agent_len);
+        if header.len() > max_bytes {
+            return Err(Error::validation(format!(
+                "Attributed memos need more than {} bytes",
+                max_bytes
+            )));
+        }
+    }
+
+    const MEMO_FIELD: &str = ";memo=";
+    Ok(Some(match memo {
+        Some(memo) if header.len() + MEMO_FIELD.len() < max_bytes => {
+            let room = max_bytes - header.len() - MEMO_FIELD.len();
+            format!("{}{}{}", header, MEMO_FIELD, truncate(memo, room))
+        }
+        _ => header,
+    }))
+}
+
+/// Memo instruction signed by `signer`
+///
+/// Refuses memos that are not UTF-8 or longer than `max_bytes`.
+pub fn memo_instruction(memo: &[u8], signer: &Pubkey, max_bytes: usize) -> Result<Instruction> {
+    std::str::from_utf8(memo)
+        .map_err(|e| Error::validation(format!("Memo is not valid UTF-8: {}", e)))?;
+    check_length(memo, max_bytes)?;
+    Ok(spl_memo::build_memo(memo, &[signer]))
+}
+
+/// Attribution written into a memo by [`compose_memo`], if any
+///
+/// Accepts memos as `getSignaturesForAddress` reports them, prefixed with
+/// their length in brackets.
+pub fn parse_attributed_memo(memo: &str) -> Option<MemoAttribution> {
+    let start = memo.find(ATTRIBUTED_PREFIX)?;
+    let fields = &memo[start + ATTRIBUTED_PREFIX.len()..];
+    // The user memo is last and may contain separators of its own
+    let fields = fields.split(";memo=").next()?;
+
+    let (mut agent_id, mut decision_id, mut version) = (None, None, None);
+    for field in fields.split(';') {
+        match field.split_once('=')? {
+            ("agent", value) => agent_id = Some(value.to_string()),
+            ("decision", value) => decision_id = value.parse().ok(),
+            ("v", value) => version = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some(MemoAttribution {
+        agent_id: agent_id?,
+        decision_id: decision_id?,
+        version: version?,
+    })
+}
+
+fn check_length(memo: &[u8], max_bytes: usize) -> Result<()> {
+    if memo.len() > max_bytes {
+        return Err(Error::validation(format!(
+            "Memo is {} bytes, more than the {} allowed",
+            memo.len(),
+            max_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// Longest prefix of `text` of at most `max_bytes` ending on a character boundary
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribution() -> MemoAttribution {
+        MemoAttribution::new("rebalancer", Uuid::new_v4())
+    }
+
+    #[test]
+    fn test_attributed_memo_round_trip() -> Result<()> {
+        let attribution = attribution();
+        let Some(memo) = compose_memo(
+            Some("weekly; rebalance=yes"),
+            MemoMode::Attributed,
+            Some(&attribution),
+            DEFAULT_MEMO_MAX_BYTES,
+        )?
+        else {
+            panic!("attributed memo was not written");
+        };
+        assert!(memo.ends_with(";memo=weekly; rebalance=yes"));
+        assert_eq!(parse_attributed_memo(&memo), Some(attribution.clone()));
+
+        // As reported by getSignaturesForAddress
+        let listed = format!("[{}] {}", memo.len(), memo);
+        assert_eq!(parse_attributed_memo(&listed), Some(attribution.clone()));
+
+        // Attributed even without a user memo
+        let bare = compose_memo(None, MemoMode::Attributed, Some(&attribution), 256)?;
+        assert_eq!(
+            bare.as_deref().and_then(parse_attributed_memo),
+            Some(attribution)
+        );
+        assert_eq!(parse_attributed_memo("just a note"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_attributed_memo_truncates_user_part_first() -> Result<()> {
+        let attribution = attribution();
+        let header_len = attribution.header(attribution.agent_id.len()).len();
+
+        let long = "é".repeat(100);
+        let Some(memo) = compose_memo(
+            Some(&long),
+            MemoMode::Attributed,
+            Some(&attribution),
+            header_len + 12,
+        )?
+        else {
+            panic!("attributed memo was not written");
+        };
+        assert!(memo.len() <= header_len + 12);
+        assert!(memo.ends_with(";memo=ééé"));
+        assert_eq!(parse_attributed_memo(&memo), Some(attribution.clone()));
+
+        // Then the agent id
+        let Some(memo) = compose_memo(
+            Some(&long),
+            MemoMode::Attributed,
+            Some(&attribution),
+            header_len - 4,
+        )?
+        else {
+            panic!("attributed memo was not written");
+        };
+        let Some(parsed) = parse_attributed_memo(&memo) else {
+            panic!("truncated memo does not parse");
+        };
+        assert_eq!(parsed.agent_id, "rebala");
+        assert_eq!(parsed.decision_id, attribution.decision_id);
+
+        // Never the decision id
+        assert!(compose_memo(None, MemoMode::Attributed, Some(&attribution), 40).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_memo_is_unchanged() -> Result<()> {
+        let signer = Pubkey::new_unique();
+        let memo = compose_memo(
+            Some("invoice 42"),
+            MemoMode::Plain,
+            Some(&attribution()),
+            256,
+        )?;
+        assert_eq!(memo.as_deref(), Some("invoice 42"));
+        assert_eq!(compose_memo(Some(""), MemoMode::Plain, None, 256)?, None);
+
+        let built = memo_instruction(b"invoice 42", &signer, 256)?;
+        assert_eq!(built, spl_memo::build_memo(b"invoice 42", &[&signer]));
+
+        assert!(compose_memo(Some("too long"), MemoMode::Plain, None, 4).is_err());
+        assert!(memo_instruction(&[0xff, 0xfe], &signer, 256).is_err());
+        assert!(memo_instruction(b"too long", &signer, 4).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+//! Prometheus metrics shared by every crate of the workspace
+//!
+//! [`Metrics::global`] is the process-wide set of metrics. Its registry is
+//! the one RPC clients built from settings register with, so a single
+//! `/metrics` endpoint exports everything:
+//!
+//! - `agent_wallet_wallet_balance_sol`: SOL balance per wallet
+//! - `agent_wallet_transactions_total`: transactions by action and status
+//!   (`sent` or `failed`)
+//! - `agent_wallet_agent_decisions_total`: decisions by agent and outcome
+//! - `agent_wallet_spending_limit_remaining_lamports`: daily budget left
+//!   per wallet
+//! - `agent_wallet_decision_duration_seconds`: decision latency per agent
+//! - `agent_wallet_key_unlock_seconds`: time to unlock idle-locked keys
+//!
+//! [`MetricsServer`] serves the registry on its own port when
+//! [`MetricsSettings::enabled`] is set. Processes already running an HTTP
+//! server mount [`Metrics::router`] on it instead. A server started with a
+//! shutdown token finishes the scrapes in progress once it is cancelled.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::config::MetricsSettings;
+//! use agent_wallet_core::metrics::{Metrics, MetricsServer};
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let metrics = Metrics::global();
+//! let server = MetricsServer::start(&MetricsSettings::default(), metrics).await?;
+//! metrics.record_transaction("transfer_sol", true);
+//! if let Some(server) = &server {
+//!     println!("Metrics on http://{}/metrics", server.local_addr());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::MetricsSettings;
+use crate::error::{Error, Result};
+use crate::shutdown::{stop_task, CancellationToken};
+use crate::sol::Lamports;
+
+/// Metrics of the whole process
+static GLOBAL: Lazy<Metrics> = Lazy::new(|| {
+    Metrics::new(Registry::new()).expect("metric definitions are valid and registered once")
+});
+
+/// Wallet and agent metrics, registered with one registry
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    wallet_balance: GaugeVec,
+    transactions: IntCounterVec,
+    decisions: IntCounterVec,
+    spending_remaining: IntGaugeVec,
+    decision_duration: HistogramVec,
+    key_unlock_duration: Histogram,
+}
+
+impl Metrics {
+    /// Metrics of the whole process
+    pub fn global() -> &'static Metrics {
+        &GLOBAL
+    }
+
+    /// Register a fresh set of metrics with `registry`
+    ///
+    /// Fails if `registry` already holds metrics of the same names.
+    pub fn new(registry: Registry) -> Result<Self> {
+        let wallet_balance = GaugeVec::new(
+            Opts::new("agent_wallet_wallet_balance_sol", "Wallet balance in SOL"),
+            &["wallet"],
+        )?;
+        let transactions = IntCounterVec::new(
+            Opts::new(
+                "agent_wallet_transactions_total",
+                "Transactions sent or failed, by action type",
+            ),
+            &["action", "status"],
+        )?;
+        let decisions = IntCounterVec::new(
+            Opts::new(
+                "agent_wallet_agent_decisions_total",
+                "Agent decisions by outcome",
+            ),
+            &["agent_id", "outcome"],
+        )?;
+        let spending_remaining = IntGaugeVec::new(
+            Opts::new(
+                "agent_wallet_spending_limit_remaining_lamports",
+                "Daily spending budget left, in lamports",
+            ),
+            &["wallet"],
+        )?;
+        let decision_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "agent_wallet_decision_duration_seconds",
+                "Time from the start of a tick to its outcome, in seconds",
+            ),
+            &["agent_id"],
+        )?;
+        let key_unlock_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "agent_wallet_key_unlock_seconds",
+                "Time to decrypt an idle-locked key before signing, in seconds",
+            )
+            .buckets(prometheus::exponential_buckets(0.00001, 4.0, 10)?),
+        )?;
+
+        registry.register(Box::new(wallet_balance.clone()))?;
+        registry.register(Box::new(transactions.clone()))?;
+        registry.register(Box::new(decisions.clone()))?;
+        registry.register(Box::new(spending_remaining.clone()))?;
+        registry.register(Box::new(decision_duration.clone()))?;
+        registry.register(Box::new(key_unlock_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            wallet_balance,
+            transactions,
+            decisions,
+            spending_remaining,
+            decision_duration,
+            key_unlock_duration,
+        })
+    }
+
+    /// Registry every metric is registered with
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Record the SOL balance of `wallet`
+    pub fn set_wallet_balance(&self, wallet: &str, balance_sol: f64) {
+        self.wallet_balance
+            .with_label_values(&[wallet])
+            .set(balance_sol);
+    }
+
+    /// Count a transaction of `action` that was sent or failed
+    pub fn record_transaction(&self, action: &str, sent: bool) {
+        let status = if sent { "sent" } else { "failed" };
+        self.transactions.with_label_values(&[action, status]).inc();
+    }
+
+    /// Count a decision of `agent_id` and record how long it took
+    pub fn record_decision(&self, agent_id: &str, outcome: &str, duration: Duration) {
+        self.decisions.with_label_values(&[agent_id, outcome]).inc();
+        self.decision_duration
+            .with_label_values(&[agent_id])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Record the daily budget `wallet` has left
+    pub fn set_spending_remaining(&self, wallet: &str, remaining: Lamports) {
+        self.spending_remaining
+            .with_label_values(&[wallet])
+            .set(i64::try_from(remaining.as_u64()).unwrap_or(i64::MAX));
+    }
+
+    /// Record how long unlocking an idle-locked key took
+    pub fn record_key_unlock(&self, duration: Duration) {
+        self.key_unlock_duration.observe(duration.as_secs_f64());
+    }
+
+    /// Everything in the registry, in the Prometheus text format
+    pub fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| Error::serialization(e.to_string()))
+    }
+
+    /// Router serving the registry at `GET /metrics`
+    pub fn router(&self) -> Router {
+        let metrics = self.clone();
+        Router::new().route(
+            "/metrics",
+            get(move || {
+                let metrics = metrics.clone();
+                async move { scrape(&metrics) }
+            }),
+        )
+    }
+}
+
+/// Response to a scrape of `metrics`
+fn scrape(metrics: &Metrics) -> impl IntoResponse {
+    match metrics.render() {
+        Ok(body) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            warn!("Failed to render metrics: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// HTTP server exporting a registry at `/metrics`
+///
+/// The server stops when dropped.
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+    cancel: CancellationToken,
+    task: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Serve `metrics` on `0.0.0.0:{port}` if `settings` enable metrics
+    pub async fn start(settings: &MetricsSettings, metrics: &Metrics) -> Result<Option<Self>> {
+        Self::start_with_shutdown(settings, metrics, &CancellationToken::new()).await
+    }
+
+    /// Like [`MetricsServer::start`], stopping gracefully once `shutdown` is cancelled
+    pub async fn start_with_shutdown(
+        settings: &MetricsSettings,
+        metrics: &Metrics,
+        shutdown: &CancellationToken,
+    ) -> Result<Option<Self>> {
+        if !settings.enabled {
+            return Ok(None);
+        }
+        let addr = SocketAddr::from(([0, 0, 0, 0], settings.port));
+        Self::bind_with_shutdown(addr, metrics, shutdown)
+            .await
+            .map(Some)
+    }
+
+    /// Serve `metrics` on `addr`
+    pub async fn bind(addr: SocketAddr, metrics: &Metrics) -> Result<Self> {
+        Self::bind_with_shutdown(addr, metrics, &CancellationToken::new()).await
+    }
+
+    /// Serve `metrics` on `addr` until `shutdown` is cancelled
+    ///
+    /// Scrapes in progress are finished before the server stops.
+    pub async fn bind_with_shutdown(
+        addr: SocketAddr,
+        metrics: &Metrics,
+        shutdown: &CancellationToken,
+    ) -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        info!("Serving metrics on http://{}/metrics", local_addr);
+        let router = metrics.router();
+        let cancel = shutdown.child_token();
+        let stopped = cancel.clone().cancelled_owned();
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router)
+                .with_graceful_shutdown(stopped)
+                .await
+            {
+                warn!("Metrics server stopped: {}", e);
+            }
+        });
+        Ok(Self {
+            local_addr,
+            cancel,
+            task: Some(task),
+        })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop serving, waiting up to `timeout` for scrapes in progress
+    pub async fn shutdown(&mut self, timeout: Duration) -> Result<()> {
+        self.cancel.cancel();
+        match self.task.take() {
+            Some(task) => stop_task(task, timeout, "Metrics server").await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scrape_reports_recorded_activity() -> Result<()> {
+        let metrics = Metrics::new(Registry::new())?;
+        let settings = MetricsSettings {
+            enabled: true,
+            port: 0,
+            ..Default::default()
+        };
+        let server = MetricsServer::start(&settings, &metrics)
+            .await?
+            .ok_or_else(|| Error::config("metrics server not started"))?;
+
+        metrics.set_wallet_balance("main", 1.5);
+        metrics.record_transaction("transfer_sol", true);
+        metrics.record_transaction("transfer_sol", false);
+        metrics.record_decision("trader-1", "executed", Duration::from_millis(20));
+        metrics.set_spending_remaining("main", Lamports::new(250_000_000));
+
+        let url = format!("http://127.0.0.1:{}/metrics", server.local_addr().port());
+        let response = reqwest::get(&url)
+            .await
+            .map_err(|e| Error::rpc(e.to_string()))?;
+        assert!(response.status().is_success());
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Error::rpc(e.to_string()))?;
+
+        for family in [
+            "agent_wallet_wallet_balance_sol{wallet=\"main\"} 1.5",
+            "agent_wallet_transactions_total{action=\"transfer_sol\",status=\"sent\"} 1",
+            "agent_wallet_transactions_total{action=\"transfer_sol\",status=\"failed\"} 1",
+            "agent_wallet_agent_decisions_total{agent_id=\"trader-1\",outcome=\"executed\"} 1",
+            "agent_wallet_spending_limit_remaining_lamports{wallet=\"main\"} 250000000",
+            "agent_wallet_decision_duration_seconds_count{agent_id=\"trader-1\"} 1",
+        ] {
+            assert!(body.contains(family), "{} missing from:\n{}", family, body);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disabled_metrics_start_no_server() -> Result<()> {
+        let metrics = Metrics::new(Registry::new())?;
+        let settings = MetricsSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(MetricsServer::start(&settings, &metrics).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_server_stops_listening() -> Result<()> {
+        let metrics = Metrics::new(Registry::new())?;
+        let token = CancellationToken::new();
+        let mut server = MetricsServer::bind_with_shutdown(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            &metrics,
+            &token,
+        )
+        .await?;
+        let url = format!("http://{}/metrics", server.local_addr());
+
+        token.cancel();
+        server.shutdown(Duration::from_secs(5)).await?;
+        assert!(reqwest::get(&url).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_metrics_register_once_per_registry() -> Result<()> {
+        let registry = Registry::new();
+        Metrics::new(registry.clone())?;
+        assert!(Metrics::new(registry).is_err());
+        Ok(())
+    }
+}
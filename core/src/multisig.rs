@@ -0,0 +1,345 @@
+//! Multi-signature wallets and partial signing
+//!
+//! A multisig wallet names additional co-signer keys and a threshold of
+//! signatures (the wallet's own key included) every transaction needs.
+//! Co-signers are attached to a transaction as signers of a memo
+//! instruction, so the runtime rejects it until each of them has signed.
+//!
+//! Signing happens in rounds: each party partially signs the transaction,
+//! passes it on as base64, and the signatures are merged before sending.
+//! None of the helpers here need network access.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::keypair::SecureKeypair;
+//! use agent_wallet_core::multisig::{self, MultisigConfig};
+//! use solana_sdk::transaction::Transaction;
+//!
+//! # fn main() -> agent_wallet_core::Result<()> {
+//! # let mut transaction: Transaction = unimplemented!();
+//! let cosigner = SecureKeypair::generate();
+//! let config = MultisigConfig::new(vec![cosigner.public_key()], 2)?;
+//!
+//! // Co-signer signs the transaction handed to it as base64
+//! let mut shared = multisig::decode_transaction(&multisig::encode_transaction(&transaction)?)?;
+//! let signature = multisig::partial_sign(&mut shared, &cosigner)?;
+//!
+//! // The proposer merges the signature back
+//! multisig::add_signature(&mut transaction, signature, &cosigner.public_key())?;
+//! assert!(multisig::missing_signers(&transaction).is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    instruction::Instruction, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+
+use crate::error::{Error, Result};
+use crate::keypair::SecureKeypair;
+use crate::storage::WalletMetadata;
+
+/// Key under which the multisig configuration is kept in wallet metadata
+pub const METADATA_KEY: &str = "multisig";
+
+/// Memo text of the instruction that carries co-signers
+pub const COSIGNER_MEMO: &str = "agent-wallet multisig approval";
+
+/// Co-signers and signature threshold of a multisig wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigConfig {
+    /// Additional signer keys
+    pub cosigners: Vec<Pubkey>,
+    /// Signatures required, including the wallet's own
+    pub threshold: u8,
+}
+
+impl MultisigConfig {
+    /// Create a configuration, validating the threshold
+    pub fn new(cosigners: Vec<Pubkey>, threshold: u8) -> Result<Self> {
+        let config = Self {
+            cosigners,
+            threshold,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the threshold and co-signer list
+    pub fn validate(&self) -> Result<()> {
+        let total = self.cosigners.len() + 1;
+        if self.threshold < 2 || self.threshold as usize > total {
+            return Err(Error::config(format!(
+                "Multisig threshold must be between 2 and {} signers",
+                total
+            )));
+        }
+        let mut unique = self.cosigners.clone();
+        unique.sort();
+        unique.dedup();
+        if unique.len() != self.cosigners.len() {
+            return Err(Error::config("Multisig co-signers must be distinct"));
+        }
+        Ok(())
+    }
+
+    /// Number of co-signatures needed besides the wallet's own
+    pub fn required_cosigners(&self) -> usize {
+        self.threshold as usize - 1
+    }
+
+    /// Check that a chosen set of co-signers satisfies the threshold
+    pub fn check_cosigners(&self, chosen: &[Pubkey]) -> Result<()> {
+        if let Some(unknown) = chosen.iter().find(|key| !self.cosigners.contains(key)) {
+            return Err(Error::permission_denied(format!(
+                "{} is not a co-signer of this wallet",
+                unknown
+            )));
+        }
+        if chosen.len() < self.required_cosigners() {
+            return Err(Error::permission_denied(format!(
+                "Multisig wallet requires {} co-signers, {} given",
+                self.required_cosigners(),
+                chosen.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Read the configuration stored in wallet metadata, if any
+    pub fn from_metadata(metadata: &WalletMetadata) -> Result<Option<Self>> {
+        metadata
+            .custom_data
+            .get(METADATA_KEY)
+            .map(|json| {
+                let config: Self = serde_json::from_str(json)?;
+                config.validate()?;
+                Ok(config)
+            })
+            .transpose()
+    }
+
+    /// Store the configuration in wallet metadata
+    pub fn store_in(&self, metadata: &mut WalletMetadata) -> Result<()> {
+        metadata
+            .custom_data
+            .insert(METADATA_KEY.to_string(), serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+/// Memo instruction that makes the given co-signers required signers
+pub fn cosigner_instruction(cosigners: &[Pubkey]) -> Instruction {
+    let signers: Vec<&Pubkey> = cosigners.iter().collect();
+    spl_memo::build_memo(COSIGNER_MEMO.as_bytes(), &signers)
+}
+
+/// Required signers that have not signed yet
+pub fn missing_signers(transaction: &Transaction) -> Vec<Pubkey> {
+    let required = transaction.message.header.num_required_signatures as usize;
+    transaction
+        .message
+        .account_keys
+        .iter()
+        .take(required)
+        .zip(&transaction.signatures)
+        .filter(|(_, signature)| **signature == Signature::default())
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+/// Whether any required signer has signed already
+pub fn has_signatures(transaction: &Transaction) -> bool {
+    transaction
+        .signatures
+        .iter()
+        .any(|signature| *signature != Signature::default())
+}
+
+/// Sign with one key, keeping the blockhash and the other signatures
+pub fn partial_sign(transaction: &mut Transaction, keypair: &SecureKeypair) -> Result<Signature> {
+    let index = signer_index(transaction, &keypair.public_key())?;
+    let blockhash = transaction.message.recent_blockhash;
+    transaction
+        .try_partial_sign(&[keypair.as_inner()], blockhash)
+        .map_err(|e| Error::transaction(format!("Failed to sign transaction: {}", e)))?;
+    Ok(transaction.signatures[index])
+}
+
+/// Insert a signature produced elsewhere after checking it
+pub fn add_signature(
+    transaction: &mut Transaction,
+    signature: Signature,
+    pubkey: &Pubkey,
+) -> Result<()> {
+    let index = signer_index(transaction, pubkey)?;
+    if !signature.verify(pubkey.as_ref(), &transaction.message_data()) {
+        return Err(Error::validation(format!(
+            "Signature does not match the transaction for {}",
+            pubkey
+        )));
+    }
+    transaction.signatures[index] = signature;
+    Ok(())
+}
+
+/// Copy every signature present in `other` into `transaction`
+///
+/// Both must carry the same message. Returns the number of signatures added.
+pub fn merge_signatures(transaction: &mut Transaction, other: &Transaction) -> Result<usize> {
+    if transaction.message != other.message {
+        return Err(Error::validation(
+            "Cannot merge signatures of different transactions",
+        ));
+    }
+
+    let mut added = 0;
+    for (index, signature) in other.signatures.iter().enumerate() {
+        if *signature != Signature::default() && transaction.signatures[index] != *signature {
+            let pubkey = transaction.message.account_keys[index];
+            add_signature(transaction, *signature, &pubkey)?;
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Serialize a transaction as base64 for handing to another signer
+pub fn encode_transaction(transaction: &Transaction) -> Result<String> {
+    Ok(STANDARD.encode(bincode::serialize(transaction)?))
+}
+
+/// Parse a base64-encoded transaction
+pub fn decode_transaction(encoded: &str) -> Result<Transaction> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| Error::serialization(format!("Invalid base64 transaction: {}", e)))?;
+    Ok(bincode::deserialize(&bytes)?)
+}
+
+/// Position of a required signer in the transaction
+pub(crate) fn signer_index(transaction: &Transaction, pubkey: &Pubkey) -> Result<usize> {
+    let required = transaction.message.header.num_required_signatures as usize;
+    transaction.message.account_keys[..required]
+        .iter()
+        .position(|key| key == pubkey)
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "{} is not a required signer of the transaction",
+                pubkey
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol::Lamports;
+    use crate::transaction::{TransactionBuilder, TransactionOptions};
+    use crate::types::{AgentAction, AgentContext};
+    use solana_sdk::hash::Hash;
+
+    /// Build a transfer that needs the wallet and the chosen co-signers
+    fn build_transfer(wallet: &SecureKeypair, cosigners: &[Pubkey]) -> Result<Transaction> {
+        let mut builder = TransactionBuilder::new();
+        let context = AgentContext::new(wallet.public_key());
+        let options = TransactionOptions {
+            cosigners: cosigners.to_vec(),
+            ..Default::default()
+        };
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+
+        let mut transaction = builder.build_from_action(&action, &context, &options)?;
+        transaction.message.recent_blockhash = Hash::new_unique();
+        Ok(transaction)
+    }
+
+    #[test]
+    fn test_two_of_three_offline_flow() -> Result<()> {
+        let wallet = SecureKeypair::generate();
+        let alice = SecureKeypair::generate();
+        let bob = SecureKeypair::generate();
+        let config = MultisigConfig::new(vec![alice.public_key(), bob.public_key()], 2)?;
+
+        // The wallet picks Bob as its co-signer for this transaction
+        let chosen = vec![bob.public_key()];
+        config.check_cosigners(&chosen)?;
+        let mut transaction = build_transfer(&wallet, &chosen)?;
+
+        let builder = TransactionBuilder::new();
+        let context = AgentContext::new(wallet.public_key());
+        let options = TransactionOptions::default();
+        assert_eq!(
+            builder
+                .validate_transaction(&transaction, &context, &options)
+                .missing_signatures,
+            2
+        );
+
+        partial_sign(&mut transaction, &wallet)?;
+        assert_eq!(missing_signers(&transaction), vec![bob.public_key()]);
+        assert_eq!(
+            builder
+                .validate_transaction(&transaction, &context, &options)
+                .missing_signatures,
+            1
+        );
+
+        // Hand off as base64; Bob signs his copy and returns the signature
+        let mut bobs_copy = decode_transaction(&encode_transaction(&transaction)?)?;
+        let bob_signature = partial_sign(&mut bobs_copy, &bob)?;
+        add_signature(&mut transaction, bob_signature, &bob.public_key())?;
+
+        assert!(missing_signers(&transaction).is_empty());
+        assert_eq!(transaction, bobs_copy);
+        transaction
+            .verify()
+            .map_err(|e| Error::transaction(e.to_string()))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_and_reject_foreign_signatures() -> Result<()> {
+        let wallet = SecureKeypair::generate();
+        let alice = SecureKeypair::generate();
+        let outsider = SecureKeypair::generate();
+        let mut transaction = build_transfer(&wallet, &[alice.public_key()])?;
+
+        let mut alices_copy = transaction.clone();
+        partial_sign(&mut alices_copy, &alice)?;
+        partial_sign(&mut transaction, &wallet)?;
+        assert_eq!(merge_signatures(&mut transaction, &alices_copy)?, 1);
+        assert!(missing_signers(&transaction).is_empty());
+
+        // Keys that are not required signers, and forged signatures, are rejected
+        assert!(partial_sign(&mut transaction.clone(), &outsider).is_err());
+        let forged = outsider.sign(&transaction.message_data());
+        assert!(add_signature(&mut transaction, forged, &alice.public_key()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let cosigner = Pubkey::new_unique();
+        assert!(MultisigConfig::new(vec![cosigner], 2).is_ok());
+        assert!(MultisigConfig::new(vec![cosigner], 3).is_err());
+        assert!(MultisigConfig::new(vec![cosigner], 1).is_err());
+        assert!(MultisigConfig::new(vec![cosigner, cosigner], 2).is_err());
+
+        let config = MultisigConfig {
+            cosigners: vec![cosigner, Pubkey::new_unique()],
+            threshold: 3,
+        };
+        assert!(config.check_cosigners(&[cosigner]).is_err());
+        assert!(config
+            .check_cosigners(&[cosigner, Pubkey::new_unique()])
+            .is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Durable nonce accounts for delayed transactions
+//!
+//! A transaction normally expires once its recent blockhash is older than
+//! ~150 slots (60-90 seconds). A transaction using a durable nonce instead
+//! starts with an `AdvanceNonceAccount` instruction and carries the nonce
+//! stored in that account as its blockhash, so it stays valid until the
+//! nonce is advanced. Agents that queue decisions for later execution set
+//! [`TransactionOptions::nonce`](crate::transaction::TransactionOptions::nonce).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::nonce::NonceInfo;
+//! use agent_wallet_core::transaction::TransactionOptions;
+//! use agent_wallet_core::Wallet;
+//!
+//! # async fn example(wallet: &Wallet) -> agent_wallet_core::Result<()> {
+//! // The wallet pays the rent and becomes the nonce authority
+//! let nonce: NonceInfo = wallet.create_nonce_account(None).await?;
+//!
+//! let options = TransactionOptions {
+//!     nonce: Some(nonce),
+//!     ..Default::default()
+//! };
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+    system_instruction::{self, SystemInstruction},
+    system_program,
+};
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+
+/// Nonce account a transaction advances instead of using a recent blockhash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceInfo {
+    /// Nonce account address
+    pub account: Pubkey,
+    /// Key allowed to advance the nonce; it must sign the transaction
+    pub authority: Pubkey,
+}
+
+impl NonceInfo {
+    /// Nonce account `account` advanced by `authority`
+    pub fn new(account: Pubkey, authority: Pubkey) -> Self {
+        Self { account, authority }
+    }
+
+    /// Instruction advancing the nonce, which must come first in a message
+    pub fn advance_instruction(&self) -> Instruction {
+        system_instruction::advance_nonce_account(&self.account, &self.authority)
+    }
+}
+
+/// Contents of an initialized nonce account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceData {
+    /// Key allowed to advance the nonce
+    pub authority: Pubkey,
+    /// Stored nonce, used as the blockhash of transactions
+    pub blockhash: Hash,
+    /// Fee per signature when the nonce was stored
+    pub lamports_per_signature: u64,
+}
+
+/// Parse the data of a nonce account
+///
+/// Fails for uninitialized accounts and data that is not a nonce state.
+pub fn parse_nonce_data(data: &[u8]) -> Result<NonceData> {
+    let versions: Versions = bincode::deserialize(data)
+        .map_err(|e| Error::serialization(format!("Not a nonce account: {}", e)))?;
+    match versions.state() {
+        State::Initialized(data) => Ok(NonceData {
+            authority: data.authority,
+            blockhash: data.blockhash(),
+            lamports_per_signature: data.get_lamports_per_signature(),
+        }),
+        State::Uninitialized => Err(Error::validation("Nonce account is not initialized")),
+    }
+}
+
+/// Parse a fetched nonce account, checking it is owned by the system program
+pub fn parse_nonce_account(pubkey: &Pubkey, account: &Account) -> Result<NonceData> {
+    if account.owner != system_program::id() {
+        return Err(Error::validation(format!(
+            "{} is owned by {}, not the system program",
+            pubkey, account.owner
+        )));
+    }
+    parse_nonce_data(&account.data)
+}
+
+/// Nonce account a message advances, if it uses a durable nonce
+///
+/// Only a leading `AdvanceNonceAccount` instruction makes a durable nonce
+/// transaction; such transactions must never get a fresh blockhash.
+pub fn nonce_account_of(message: &Message) -> Option<Pubkey> {
+    let instruction = message.instructions.first()?;
+    let program = message
+        .account_keys
+        .get(instruction.program_id_index as usize)?;
+    if !system_program::check_id(program) {
+        return None;
+    }
+    match bincode::deserialize(&instruction.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => {
+            let index = *instruction.accounts.first()?;
+            message.account_keys.get(index as usize).copied()
+        }
+        _ => None,
+    }
+}
+
+/// Blockhash a message should be signed with
+///
+/// The stored nonce for durable nonce messages, the latest blockhash
+/// otherwise.
+pub async fn blockhash_for(message: &Message, rpc_client: &dyn DynRpcProvider) -> Result<Hash> {
+    match nonce_account_of(message) {
+        Some(account) => Ok(rpc_client.get_nonce_account(&account).await?.blockhash),
+        None => rpc_client.get_latest_blockhash().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account data of an initialized nonce account (current version)
+    fn fixture(authority: &Pubkey, nonce: [u8; 32], lamports_per_signature: u64) -> Vec<u8> {
+        let mut data = Vec::with_capacity(State::size());
+        data.extend_from_slice(&1u32.to_le_bytes()); // Versions::Current
+        data.extend_from_slice(&1u32.to_le_bytes()); // State::Initialized
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&lamports_per_signature.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_nonce_account_fixture() -> Result<()> {
+        let authority = Pubkey::new_unique();
+        let data = fixture(&authority, [7u8; 32], 5_000);
+        assert_eq!(data.len(), State::size());
+
+        let account = Account {
+            lamports: 1_447_680,
+            data,
+            owner: system_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        let parsed = parse_nonce_account(&Pubkey::new_unique(), &account)?;
+        assert_eq!(
+            parsed,
+            NonceData {
+                authority,
+                blockhash: Hash::new_from_array([7u8; 32]),
+                lamports_per_signature: 5_000,
+            }
+        );
+
+        // Uninitialized, truncated and foreign accounts are rejected
+        let mut uninitialized = fixture(&authority, [7u8; 32], 5_000);
+        uninitialized[4..8].copy_from_slice(&0u32.to_le_bytes());
+        assert!(parse_nonce_data(&uninitialized[..8]).is_err());
+        assert!(parse_nonce_data(&account.data[..40]).is_err());
+        let foreign = Account {
+            owner: Pubkey::new_unique(),
+            ..account
+        };
+        assert!(parse_nonce_account(&Pubkey::new_unique(), &foreign).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nonce_account_of_message() {
+        let payer = Pubkey::new_unique();
+        let nonce = NonceInfo::new(Pubkey::new_unique(), payer);
+        let transfer = system_instruction::transfer(&payer, &Pubkey::new_unique(), 1);
+
+        let message = Message::new(
+            &[nonce.advance_instruction(), transfer.clone()],
+            Some(&payer),
+        );
+        assert_eq!(nonce_account_of(&message), Some(nonce.account));
+
+        // Advancing anywhere but first does not make a nonce transaction
+        let message = Message::new(
+            &[transfer.clone(), nonce.advance_instruction()],
+            Some(&payer),
+        );
+        assert_eq!(nonce_account_of(&message), None);
+        let message = Message::new(&[transfer], Some(&payer));
+        assert_eq!(nonce_account_of(&message), None);
+    }
+}
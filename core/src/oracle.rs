@@ -0,0 +1,54 @@
+//! Price oracles feeding the agent context
+//!
+//! A [`PriceOracle`] quotes symbols (or mint addresses) in USD. The
+//! [`ContextBuilder`](crate::context::ContextBuilder) polls one for the
+//! configured watchlist and derives market conditions from the quotes.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::future::Future;
+//!
+//! use agent_wallet_core::oracle::PriceOracle;
+//! use agent_wallet_core::types::OracleData;
+//! use agent_wallet_core::Result;
+//!
+//! struct FixedOracle;
+//!
+//! impl PriceOracle for FixedOracle {
+//!     fn get_price(&self, _symbol: &str) -> impl Future<Output = Result<OracleData>> + Send {
+//!         std::future::ready(Ok(OracleData {
+//!             source: "fixed".to_string(),
+//!             price: 150.0,
+//!             confidence: 0.0,
+//!             timestamp: chrono::Utc::now(),
+//!             ema: None,
+//!         }))
+//!     }
+//! }
+//! ```
+
+use std::future::Future;
+
+use futures::future::BoxFuture;
+
+use crate::error::Result;
+use crate::types::OracleData;
+
+/// Source of USD prices for symbols or mint addresses
+pub trait PriceOracle: Send + Sync {
+    /// Current price of `symbol`
+    fn get_price(&self, symbol: &str) -> impl Future<Output = Result<OracleData>> + Send;
+}
+
+/// Object-safe form of [`PriceOracle`], implemented for every oracle
+pub trait DynPriceOracle: Send + Sync {
+    /// Current price of `symbol`
+    fn get_price<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, Result<OracleData>>;
+}
+
+impl<O: PriceOracle> DynPriceOracle for O {
+    fn get_price<'a>(&'a self, symbol: &'a str) -> BoxFuture<'a, Result<OracleData>> {
+        Box::pin(PriceOracle::get_price(self, symbol))
+    }
+}
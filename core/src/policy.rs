@@ -0,0 +1,278 @@
+//! Destination policies enforced on every transaction the wallet builds
+//!
+//! An [`AddressPolicy`] decides which accounts a transaction may write to
+//! besides the wallet's own. It looks at the accounts each instruction
+//! actually writes, not at what an action or a memo claims, so a transfer
+//! hidden behind an innocent-looking memo is caught as well. Denied
+//! addresses are always refused; in [`AddressPolicyMode::AllowlistOnly`]
+//! every writable account must also be allowlisted.
+//!
+//! Token transfers write to associated token accounts rather than to their
+//! owners, so the associated token accounts of listed owners count as the
+//! owners themselves. Other program accounts a transaction writes to (pool
+//! vaults, stake or nonce accounts) have to be allowlisted explicitly.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::policy::AddressPolicy;
+//! use agent_wallet_core::Wallet;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(wallet: &Wallet, treasury: Pubkey) -> agent_wallet_core::Result<()> {
+//! // Opened with `agent.default_permission_level: Administrator`
+//! wallet
+//!     .set_address_policy(AddressPolicy::allowlist_only(vec![treasury]))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{message::Message, pubkey::Pubkey};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::error::{Error, Result};
+use crate::token::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// Which destinations an [`AddressPolicy`] lets through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressPolicyMode {
+    /// Any address that is not denylisted
+    #[default]
+    AllowAll,
+    /// Only allowlisted addresses
+    AllowlistOnly,
+}
+
+/// Allowlist and denylist of the accounts transactions may write to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressPolicy {
+    /// Whether only allowlisted addresses are accepted
+    pub mode: AddressPolicyMode,
+    /// Addresses accepted in allowlist-only mode
+    #[serde(with = "pubkey_strings")]
+    pub allowlist: Vec<Pubkey>,
+    /// Addresses that are always refused
+    #[serde(with = "pubkey_strings")]
+    pub denylist: Vec<Pubkey>,
+}
+
+impl AddressPolicy {
+    /// Policy accepting only the given addresses
+    pub fn allowlist_only(allowlist: Vec<Pubkey>) -> Self {
+        Self {
+            mode: AddressPolicyMode::AllowlistOnly,
+            allowlist,
+            denylist: Vec::new(),
+        }
+    }
+
+    /// Add addresses that are always refused
+    pub fn with_denylist(mut self, denylist: Vec<Pubkey>) -> Self {
+        self.denylist = denylist;
+        self
+    }
+
+    /// Whether the policy accepts every address
+    pub fn is_open(&self) -> bool {
+        self.mode == AddressPolicyMode::AllowAll && self.denylist.is_empty()
+    }
+
+    /// Check a declared destination such as the `to` of a transfer
+    pub fn check_destination(&self, address: &Pubkey) -> Result<()> {
+        if self.denylist.contains(address) {
+            return Err(Error::permission_denied(format!(
+                "Destination {} is denylisted",
+                address
+            )));
+        }
+        if self.mode == AddressPolicyMode::AllowlistOnly && !self.allowlist.contains(address) {
+            return Err(Error::permission_denied(format!(
+                "Destination {} is not allowlisted",
+                address
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check every account a message writes to without signing for it
+    ///
+    /// `wallet` and its associated token accounts are always accepted.
+    /// Fails with `Error::PermissionDenied` naming the first offending
+    /// account.
+    pub fn check_message(&self, message: &Message, wallet: &Pubkey) -> Result<()> {
+        if self.is_open() {
+            return Ok(());
+        }
+        for address in writable_non_signers(message) {
+            if address == wallet || is_token_account_of(address, &[*wallet], message) {
+                continue;
+            }
+
+            let denied = self.denylist.contains(address)
+                || is_token_account_of(address, &self.denylist, message);
+            if denied {
+                return Err(Error::permission_denied(format!(
+                    "Transaction writes to denylisted account {}",
+                    address
+                )));
+            }
+
+            let allowed = self.mode == AddressPolicyMode::AllowAll
+                || self.allowlist.contains(address)
+                || is_token_account_of(address, &self.allowlist, message);
+            if !allowed {
+                return Err(Error::permission_denied(format!(
+                    "Transaction writes to account {} which is not allowlisted",
+                    address
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reasons a message violates the policy, for validation reports
+    pub fn violations(&self, message: &Message, wallet: &Pubkey) -> Vec<String> {
+        match self.check_message(message, wallet) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![e.to_string()],
+        }
+    }
+}
+
+/// Accounts a message writes to that do not sign it
+fn writable_non_signers(message: &Message) -> impl Iterator<Item = &Pubkey> {
+    let signed = usize::from(message.header.num_required_signatures);
+    let writable_end = message
+        .account_keys
+        .len()
+        .saturating_sub(usize::from(message.header.num_readonly_unsigned_accounts));
+    message
+        .account_keys
+        .get(signed.min(writable_end)..writable_end)
+        .unwrap_or_default()
+        .iter()
+}
+
+/// Whether `address` is an associated token account of one of `owners`
+///
+/// Candidate mints are the message's account keys, under both token programs.
+fn is_token_account_of(address: &Pubkey, owners: &[Pubkey], message: &Message) -> bool {
+    owners.iter().any(|owner| {
+        message.account_keys.iter().any(|mint| {
+            [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID]
+                .iter()
+                .any(|program| {
+                    get_associated_token_address_with_program_id(owner, mint, program) == *address
+                })
+        })
+    })
+}
+
+/// Serde for lists of addresses as base58 strings, as config files write them
+pub(crate) mod pubkey_strings {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(keys: &[Pubkey], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(keys.iter().map(Pubkey::to_string))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Pubkey>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|key| key.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::system_instruction;
+    use spl_associated_token_account::get_associated_token_address;
+
+    #[test]
+    fn test_memo_cannot_disguise_the_real_recipient() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let alice = Pubkey::new_unique();
+        let mallory = Pubkey::new_unique();
+        let policy = AddressPolicy::allowlist_only(vec![alice]);
+
+        // The memo names alice, the lamports go to mallory
+        let memo = format!("Payment to {}", alice);
+        let sneaky = Message::new(
+            &[
+                spl_memo::build_memo(memo.as_bytes(), &[&wallet]),
+                system_instruction::transfer(&wallet, &mallory, 1_000),
+            ],
+            Some(&wallet),
+        );
+        match policy.check_message(&sneaky, &wallet) {
+            Err(Error::PermissionDenied(message)) => {
+                assert!(message.contains(&mallory.to_string()))
+            }
+            other => return Err(Error::validation(format!("Unexpected {:?}", other))),
+        }
+
+        let honest = Message::new(
+            &[
+                spl_memo::build_memo(memo.as_bytes(), &[&wallet]),
+                system_instruction::transfer(&wallet, &alice, 1_000),
+            ],
+            Some(&wallet),
+        );
+        policy.check_message(&honest, &wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_accounts_follow_their_owners() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let alice = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let transfer = |to: &Pubkey| -> Result<Message> {
+            let instruction = spl_token::instruction::transfer_checked(
+                &TOKEN_PROGRAM_ID,
+                &get_associated_token_address(&wallet, &mint),
+                &mint,
+                &get_associated_token_address(to, &mint),
+                &wallet,
+                &[],
+                100,
+                6,
+            )
+            .map_err(|e| Error::token(e.to_string()))?;
+            Ok(Message::new(&[instruction], Some(&wallet)))
+        };
+
+        AddressPolicy::allowlist_only(vec![alice]).check_message(&transfer(&alice)?, &wallet)?;
+
+        // Denying the owner denies its token accounts too
+        let deny_alice = AddressPolicy::default().with_denylist(vec![alice]);
+        assert!(matches!(
+            deny_alice.check_message(&transfer(&alice)?, &wallet),
+            Err(Error::PermissionDenied(_))
+        ));
+        deny_alice.check_message(&transfer(&Pubkey::new_unique())?, &wallet)?;
+        assert!(deny_alice.check_destination(&alice).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_reads_base58_lists() -> Result<()> {
+        let alice = Pubkey::new_unique();
+        let json = format!(
+            r#"{{"mode": "allowlist_only", "allowlist": ["{}"]}}"#,
+            alice
+        );
+        let policy: AddressPolicy = serde_json::from_str(&json)?;
+        assert_eq!(policy, AddressPolicy::allowlist_only(vec![alice]));
+        Ok(())
+    }
+}
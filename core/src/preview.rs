@@ -0,0 +1,374 @@
+//! Balance-change previews from transaction simulations
+//!
+//! Before an action is sent the wallet simulates its transaction and asks
+//! the node for the post-simulation state of the wallet, the action's
+//! recipients and every account the transaction writes to. Comparing that
+//! state with the accounts as they are now gives a [`TransferPreview`]: the
+//! change of the wallet's SOL balance and of every token balance the wallet
+//! or a recipient holds, so agents and users can see what an action does
+//! before it is signed and sent.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::types::AgentAction;
+//! use agent_wallet_core::Wallet;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(wallet: &Wallet, to: Pubkey) -> agent_wallet_core::Result<()> {
+//! let action = AgentAction::TransferSol {
+//!     to,
+//!     amount: Lamports::new(1_000_000),
+//!     memo: None,
+//! };
+//! let preview = wallet.preview_action(&action).await?;
+//! println!(
+//!     "SOL change: {} lamports, fee: {} lamports",
+//!     preview.lamports_delta, preview.fee
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{account::Account, message::Message, pubkey::Pubkey, transaction::Transaction};
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::token::parse_token_account;
+use crate::types::AgentAction;
+
+/// What a transaction would do to the balances of the wallet and its recipients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferPreview {
+    /// Change of the wallet's SOL balance in lamports, as simulated
+    pub lamports_delta: i64,
+    /// Changes of token balances held by the wallet or a recipient
+    pub token_deltas: Vec<TokenDelta>,
+    /// Estimated transaction fee in lamports
+    pub fee: u64,
+    /// Program logs of the simulation
+    pub logs: Vec<String>,
+    /// Warnings the transaction policy raised for the transaction
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// SOL balance of one account before and after a simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    /// Account address
+    pub account: Pubkey,
+    /// Lamports before the transaction
+    pub pre_lamports: u64,
+    /// Lamports after the simulated transaction
+    pub post_lamports: u64,
+}
+
+impl BalanceChange {
+    /// Change in lamports
+    pub fn delta(&self) -> i128 {
+        i128::from(self.post_lamports) - i128::from(self.pre_lamports)
+    }
+}
+
+/// Change of one token account's balance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenDelta {
+    /// Token account address
+    pub account: Pubkey,
+    /// Token mint address
+    pub mint: Pubkey,
+    /// Owner of the token account
+    pub owner: Pubkey,
+    /// Change in token base units
+    pub delta: i128,
+}
+
+impl TransferPreview {
+    /// Compute the deltas between account states before and after a simulation
+    ///
+    /// `before` and `after` line up with `addresses`; missing accounts hold
+    /// nothing. `after` is `None` when the node returned no post-simulation
+    /// state, in which case every balance counts as unchanged. Only token
+    /// accounts owned by `wallet` or one of `parties` are reported.
+    pub fn from_states(
+        wallet: &Pubkey,
+        parties: &[Pubkey],
+        addresses: &[Pubkey],
+        before: &[Option<Account>],
+        after: Option<&[Option<Account>]>,
+        fee: u64,
+        logs: Vec<String>,
+    ) -> Self {
+        let lamports = |account: Option<&Account>| {
+            account.map_or(0, |a| i64::try_from(a.lamports).unwrap_or(i64::MAX))
+        };
+        let token_balance = |address: &Pubkey, account: Option<&Account>| {
+            account.and_then(|a| parse_token_account(address, a).ok())
+        };
+
+        let mut lamports_delta = 0;
+        let mut token_deltas = Vec::new();
+        for (index, address) in addresses.iter().enumerate() {
+            let pre = before.get(index).and_then(Option::as_ref);
+            let post = match after {
+                Some(after) => after.get(index).and_then(Option::as_ref),
+                None => pre,
+            };
+
+            if address == wallet {
+                lamports_delta = lamports(post) - lamports(pre);
+                continue;
+            }
+
+            let pre_token = token_balance(address, pre);
+            let post_token = token_balance(address, post);
+            let info = match post_token.as_ref().or(pre_token.as_ref()) {
+                Some(info) => info,
+                None => continue,
+            };
+            if info.owner != *wallet && !parties.contains(&info.owner) {
+                continue;
+            }
+            let delta = post_token.as_ref().map_or(0, |t| i128::from(t.balance))
+                - pre_token.as_ref().map_or(0, |t| i128::from(t.balance));
+            if delta != 0 {
+                token_deltas.push(TokenDelta {
+                    account: *address,
+                    mint: info.mint,
+                    owner: info.owner,
+                    delta,
+                });
+            }
+        }
+
+        Self {
+            lamports_delta,
+            token_deltas,
+            fee,
+            logs,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Change of `owner`'s balance of `mint` across all its token accounts
+    pub fn token_delta(&self, owner: &Pubkey, mint: &Pubkey) -> i128 {
+        self.token_deltas
+            .iter()
+            .filter(|d| d.owner == *owner && d.mint == *mint)
+            .map(|d| d.delta)
+            .sum()
+    }
+}
+
+/// Recipients of an action's transfers
+pub fn recipients(action: &AgentAction) -> Vec<Pubkey> {
+    match action {
+        AgentAction::TransferSol { to, .. }
+        | AgentAction::TransferToken { to, .. }
+        | AgentAction::MintToken { to, .. } => vec![*to],
+        AgentAction::BatchTransfer { transfers, .. } => {
+            transfers.iter().map(|(to, _)| *to).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Addresses a preview captures: the wallet, the recipients, then every
+/// other account the message writes to
+pub fn preview_addresses(wallet: &Pubkey, parties: &[Pubkey], message: &Message) -> Vec<Pubkey> {
+    let mut addresses = vec![*wallet];
+    for address in parties.iter().chain(writable_keys(message)) {
+        if !addresses.contains(address) {
+            addresses.push(*address);
+        }
+    }
+    addresses
+}
+
+/// Accounts a message locks for writing, from its header
+fn writable_keys(message: &Message) -> impl Iterator<Item = &Pubkey> {
+    let header = &message.header;
+    let signed = usize::from(header.num_required_signatures);
+    let writable_signed = signed.saturating_sub(usize::from(header.num_readonly_signed_accounts));
+    let writable_unsigned = message
+        .account_keys
+        .len()
+        .saturating_sub(usize::from(header.num_readonly_unsigned_accounts));
+    message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(move |(index, _)| {
+            *index < writable_signed || (*index >= signed && *index < writable_unsigned)
+        })
+        .map(|(_, key)| key)
+}
+
+/// Simulate `transaction` and preview the balance changes of `action`
+///
+/// Fails with [`Error::TransactionSimulation`], carrying the simulation's
+/// error and logs, when the transaction would fail.
+pub async fn simulate(
+    provider: &dyn DynRpcProvider,
+    wallet: &Pubkey,
+    action: &AgentAction,
+    transaction: &Transaction,
+    fee: u64,
+) -> Result<TransferPreview> {
+    let parties = recipients(action);
+    let addresses = preview_addresses(wallet, &parties, &transaction.message);
+    let before = provider.get_multiple_accounts(&addresses).await?;
+    let simulation = provider
+        .simulate_transaction_with_accounts(transaction, &addresses)
+        .await?;
+
+    let logs = simulation.logs.unwrap_or_default();
+    if let Some(err) = simulation.err {
+        let mut message = format!("Simulation failed: {}", err);
+        for line in &logs {
+            message.push('\n');
+            message.push_str(line);
+        }
+        return Err(Error::TransactionSimulation(message));
+    }
+
+    let after: Option<Vec<Option<Account>>> = simulation.accounts.map(|accounts| {
+        accounts
+            .into_iter()
+            .map(|account| account.and_then(|a| a.decode::<Account>()))
+            .collect()
+    });
+    Ok(TransferPreview::from_states(
+        wallet,
+        &parties,
+        &addresses,
+        &before,
+        after.as_deref(),
+        fee,
+        logs,
+    ))
+}
+
+/// Simulate `transaction` and capture the SOL balance changes of the accounts it writes
+///
+/// A failed simulation is returned as is, with no balance changes. Unsigned
+/// transactions are accepted.
+pub async fn simulate_balances(
+    provider: &dyn DynRpcProvider,
+    transaction: &Transaction,
+) -> Result<(RpcSimulateTransactionResult, Vec<BalanceChange>)> {
+    let addresses: Vec<Pubkey> = writable_keys(&transaction.message).copied().collect();
+    let before = provider.get_multiple_accounts(&addresses).await?;
+    let simulation = provider
+        .simulate_transaction_with_accounts(transaction, &addresses)
+        .await?;
+
+    let after = match (&simulation.err, &simulation.accounts) {
+        (None, Some(after)) => after,
+        _ => return Ok((simulation, Vec::new())),
+    };
+    let lamports = |account: Option<&Account>| account.map_or(0, |a| a.lamports);
+    let changes = addresses
+        .iter()
+        .zip(&before)
+        .zip(after)
+        .filter_map(|((account, pre), post)| {
+            let post = post.as_ref().and_then(|a| a.decode::<Account>());
+            let change = BalanceChange {
+                account: *account,
+                pre_lamports: lamports(pre.as_ref()),
+                post_lamports: lamports(post.as_ref()),
+            };
+            (change.delta() != 0).then_some(change)
+        })
+        .collect();
+    Ok((simulation, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::token_account;
+    use solana_sdk::{system_instruction, system_program};
+
+    fn system_account(lamports: u64) -> Account {
+        Account::new(lamports, 0, &system_program::id())
+    }
+
+    #[test]
+    fn test_deltas_from_account_states() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let (source, destination, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let addresses = [wallet, recipient, source, destination, other];
+
+        let before = vec![
+            Some(system_account(10_000_000)),
+            Some(system_account(1_000)),
+            Some(token_account(mint, wallet, 500)?),
+            None,
+            Some(token_account(mint, stranger, 7)?),
+        ];
+        // The recipient's token account is created by the transaction
+        let after = vec![
+            Some(system_account(7_955_720)),
+            Some(system_account(1_000)),
+            Some(token_account(mint, wallet, 380)?),
+            Some(token_account(mint, recipient, 120)?),
+            Some(token_account(mint, stranger, 9)?),
+        ];
+
+        let preview = TransferPreview::from_states(
+            &wallet,
+            &[recipient],
+            &addresses,
+            &before,
+            Some(&after),
+            5_000,
+            vec!["Program log: Transfer".to_string()],
+        );
+        assert_eq!(preview.lamports_delta, -2_044_280);
+        assert_eq!(preview.fee, 5_000);
+        assert_eq!(preview.token_delta(&wallet, &mint), -120);
+        assert_eq!(preview.token_delta(&recipient, &mint), 120);
+        // Accounts of unrelated owners are not reported
+        assert_eq!(preview.token_deltas.len(), 2);
+
+        // Without post-simulation state nothing appears to change
+        let unchanged = TransferPreview::from_states(
+            &wallet,
+            &[recipient],
+            &addresses,
+            &before,
+            None,
+            0,
+            vec![],
+        );
+        assert_eq!(unchanged.lamports_delta, 0);
+        assert!(unchanged.token_deltas.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_addresses_cover_writable_accounts() {
+        let wallet = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let message = Message::new(
+            &[system_instruction::transfer(&wallet, &recipient, 1)],
+            Some(&wallet),
+        );
+        let addresses = preview_addresses(&wallet, &[recipient], &message);
+        // The system program is read-only and not captured
+        assert_eq!(addresses, vec![wallet, recipient]);
+    }
+}
@@ -0,0 +1,366 @@
+//! Typed `getProgramAccounts` queries
+//!
+//! [`ProgramAccountsQuery`] builds the filters of a program account scan from
+//! typed parts instead of hand-encoded [`RpcFilterType`]s, and fetches the
+//! matching accounts from any [`DynRpcProvider`].
+//!
+//! Nodes cap the size of a `getProgramAccounts` response, and scans of busy
+//! programs run into the cap, either as an error or as a timeout. When that
+//! happens, [`ProgramAccountsQuery::fetch`] asks again for the keys only,
+//! with a zero-length `dataSlice`, and hydrates the accounts with batched
+//! `getMultipleAccounts` calls. Accounts that changed between the two steps
+//! are checked against the filters again, and dropped if they no longer
+//! match.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::program_accounts::{token_accounts_by_owner_filter, ProgramAccountsQuery};
+//! use agent_wallet_core::rpc::{RpcClient, RpcClientConfig};
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(owner: Pubkey) -> agent_wallet_core::Result<()> {
+//! let rpc = RpcClient::new(RpcClientConfig::single_endpoint("https://api.devnet.solana.com")).await?;
+//! let accounts = ProgramAccountsQuery::new(spl_token::id())
+//!     .data_size(165)
+//!     .filter(token_accounts_by_owner_filter(&owner))
+//!     .fetch(&rpc)
+//!     .await?;
+//! println!("{} token accounts", accounts.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::token::MAX_MULTIPLE_ACCOUNTS;
+
+/// Offset of the owner in the token account layout, shared by both programs
+pub const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+
+/// Filter on the data of program accounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountFilter {
+    /// Accounts holding exactly this many bytes of data
+    DataSize(u64),
+    /// Accounts whose data holds `bytes` at `offset`
+    Memcmp {
+        /// Offset into the account data
+        offset: usize,
+        /// Bytes to compare
+        bytes: Vec<u8>,
+    },
+}
+
+impl AccountFilter {
+    /// Whether account data passes the filter
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::DataSize(size) => data.len() as u64 == *size,
+            Self::Memcmp { offset, bytes } => offset
+                .checked_add(bytes.len())
+                .and_then(|end| data.get(*offset..end))
+                .is_some_and(|window| window == bytes.as_slice()),
+        }
+    }
+
+    /// The filter as sent to the node, with memcmp bytes in base58
+    pub fn to_rpc_filter(&self) -> RpcFilterType {
+        match self {
+            Self::DataSize(size) => RpcFilterType::DataSize(*size),
+            Self::Memcmp { offset, bytes } => {
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(*offset, bytes))
+            }
+        }
+    }
+}
+
+/// Filter for SPL Token and Token-2022 accounts owned by `owner`
+pub fn token_accounts_by_owner_filter(owner: &Pubkey) -> AccountFilter {
+    AccountFilter::Memcmp {
+        offset: TOKEN_ACCOUNT_OWNER_OFFSET,
+        bytes: owner.to_bytes().to_vec(),
+    }
+}
+
+/// Builder of a `getProgramAccounts` request
+#[derive(Debug, Clone)]
+pub struct ProgramAccountsQuery {
+    program_id: Pubkey,
+    filters: Vec<AccountFilter>,
+    commitment: Option<CommitmentConfig>,
+    with_context: bool,
+}
+
+impl ProgramAccountsQuery {
+    /// Query the accounts owned by `program_id`
+    pub fn new(program_id: Pubkey) -> Self {
+        Self {
+            program_id,
+            filters: Vec::new(),
+            commitment: None,
+            with_context: false,
+        }
+    }
+
+    /// Keep accounts holding exactly `size` bytes of data
+    pub fn data_size(self, size: u64) -> Self {
+        self.filter(AccountFilter::DataSize(size))
+    }
+
+    /// Keep accounts whose data holds `bytes` at `offset`
+    pub fn memcmp(self, offset: usize, bytes: impl Into<Vec<u8>>) -> Self {
+        self.filter(AccountFilter::Memcmp {
+            offset,
+            bytes: bytes.into(),
+        })
+    }
+
+    /// Keep accounts holding `pubkey` at `offset`
+    pub fn memcmp_pubkey(self, offset: usize, pubkey: &Pubkey) -> Self {
+        self.memcmp(offset, pubkey.to_bytes())
+    }
+
+    /// Add a filter
+    pub fn filter(mut self, filter: AccountFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Commitment the node reads the accounts at
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Ask for the response along with the slot it was read at
+    pub fn with_context(mut self, with_context: bool) -> Self {
+        self.with_context = with_context;
+        self
+    }
+
+    /// Program whose accounts are queried
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// Filters accounts must pass
+    pub fn filters(&self) -> &[AccountFilter] {
+        &self.filters
+    }
+
+    /// Whether account data passes every filter
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.filters.iter().all(|filter| filter.matches(data))
+    }
+
+    /// Request configuration for the full accounts
+    pub fn to_config(&self) -> RpcProgramAccountsConfig {
+        self.config(None)
+    }
+
+    /// Request configuration for the keys of the matching accounts only
+    pub fn to_keys_only_config(&self) -> RpcProgramAccountsConfig {
+        self.config(Some(UiDataSliceConfig {
+            offset: 0,
+            length: 0,
+        }))
+    }
+
+    fn config(&self, data_slice: Option<UiDataSliceConfig>) -> RpcProgramAccountsConfig {
+        RpcProgramAccountsConfig {
+            filters: (!self.filters.is_empty()).then(|| {
+                self.filters
+                    .iter()
+                    .map(AccountFilter::to_rpc_filter)
+                    .collect()
+            }),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice,
+                commitment: self.commitment,
+                ..Default::default()
+            },
+            with_context: self.with_context.then_some(true),
+        }
+    }
+
+    /// Fetch the matching accounts
+    ///
+    /// Falls back to listing the keys and hydrating them in batches when the
+    /// node refuses or times out on the full response.
+    pub async fn fetch(&self, rpc: &dyn DynRpcProvider) -> Result<Vec<(Pubkey, Account)>> {
+        match rpc
+            .get_program_accounts(&self.program_id, Some(self.to_config()))
+            .await
+        {
+            Ok(accounts) => Ok(accounts),
+            Err(e) if is_oversized_response(&e) => {
+                debug!(
+                    "Program accounts of {} too large to fetch at once ({}), hydrating in batches",
+                    self.program_id, e
+                );
+                self.fetch_chunked(rpc).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetch the matching accounts by listing their keys, then hydrating them
+    /// with batched `getMultipleAccounts` calls
+    pub async fn fetch_chunked(&self, rpc: &dyn DynRpcProvider) -> Result<Vec<(Pubkey, Account)>> {
+        let keys: Vec<Pubkey> = rpc
+            .get_program_accounts(&self.program_id, Some(self.to_keys_only_config()))
+            .await?
+            .into_iter()
+            .map(|(pubkey, _)| pubkey)
+            .collect();
+
+        let mut accounts = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let fetched = rpc.get_multiple_accounts(chunk).await?;
+            for (pubkey, account) in chunk.iter().zip(fetched) {
+                // Closed, reassigned or rewritten since the keys were listed
+                match account {
+                    Some(account)
+                        if account.owner == self.program_id && self.matches(&account.data) =>
+                    {
+                        accounts.push((*pubkey, account))
+                    }
+                    _ => debug!(
+                        "Dropping program account {} changed while hydrating",
+                        pubkey
+                    ),
+                }
+            }
+        }
+        Ok(accounts)
+    }
+}
+
+/// Whether a failed `getProgramAccounts` call hit the node's response cap
+fn is_oversized_response(error: &Error) -> bool {
+    if matches!(error, Error::Timeout(_)) {
+        return true;
+    }
+    let message = error.to_string().to_lowercase();
+    ["too large", "exceed", "timed out", "timeout"]
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_filter_encoding_matches_fixtures() -> Result<()> {
+        let owner = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+            .map_err(|e| Error::validation(e.to_string()))?;
+        let query = ProgramAccountsQuery::new(spl_token::id())
+            .data_size(165)
+            .filter(token_accounts_by_owner_filter(&owner))
+            .memcmp(0, vec![1, 2, 3]);
+
+        let config = serde_json::to_value(query.to_config())?;
+        assert_eq!(
+            config["filters"],
+            serde_json::json!([
+                { "dataSize": 165 },
+                {
+                    "memcmp": {
+                        "offset": 32,
+                        "bytes": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                        "encoding": "base58"
+                    }
+                },
+                { "memcmp": { "offset": 0, "bytes": "Ldp", "encoding": "base58" } }
+            ])
+        );
+        assert_eq!(config["encoding"], "base64");
+        assert!(config["dataSlice"].is_null());
+
+        let keys_only = serde_json::to_value(query.to_keys_only_config())?;
+        assert_eq!(
+            keys_only["dataSlice"],
+            serde_json::json!({ "offset": 0, "length": 0 })
+        );
+
+        let mut data = vec![0u8; 165];
+        data[..3].copy_from_slice(&[1, 2, 3]);
+        data[32..64].copy_from_slice(owner.as_ref());
+        assert!(query.matches(&data));
+        assert!(!query.matches(&data[..164]));
+        data[40] ^= 1;
+        assert!(!query.matches(&data));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oversized_scan_is_hydrated_in_batches() -> Result<()> {
+        let program_id = Pubkey::new_unique();
+        let rpc = MockRpc::new();
+        for i in 0..5_000u32 {
+            // One account was resized after the node listed it
+            let size = if i == 0 { 80 } else { 64 };
+            let mut data = vec![0u8; size];
+            data[..4].copy_from_slice(&i.to_le_bytes());
+            rpc.set_account(
+                Pubkey::new_unique(),
+                Account {
+                    lamports: 1,
+                    data,
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+        rpc.limit_program_accounts(1_000);
+
+        let accounts = ProgramAccountsQuery::new(program_id)
+            .data_size(64)
+            .fetch(&rpc)
+            .await?;
+
+        // The full scan was refused, the keys listed, and 5,000 keys hydrated
+        assert_eq!(rpc.program_account_calls(), 2);
+        assert_eq!(rpc.batch_calls(), 50);
+        assert_eq!(accounts.len(), 4_999);
+        assert!(accounts.iter().all(|(_, account)| account.data.len() == 64));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_small_scan_is_fetched_at_once() -> Result<()> {
+        let program_id = Pubkey::new_unique();
+        let rpc = MockRpc::new();
+        rpc.set_account(
+            Pubkey::new_unique(),
+            Account {
+                lamports: 1,
+                data: vec![0u8; 64],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        rpc.limit_program_accounts(1_000);
+
+        let accounts = ProgramAccountsQuery::new(program_id).fetch(&rpc).await?;
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(rpc.program_account_calls(), 1);
+        assert_eq!(rpc.batch_calls(), 0);
+        Ok(())
+    }
+}
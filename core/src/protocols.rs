@@ -0,0 +1,339 @@
+//! Protocols agents may interact with
+//!
+//! An agent context carries the list of protocols its agent may use, taken
+//! from `agent.allowed_protocols`. [`check_action`] enforces it on every
+//! action the wallet builds:
+//!
+//! - [`AgentAction::ProtocolInteraction`] must name an allowed protocol,
+//!   by name or program id, that supports the requested action.
+//! - Swaps and liquidity actions need an allowed protocol supporting them.
+//!
+//! [`check_programs`] enforces it on transactions built outside the wallet,
+//! such as routed swaps: every program they invoke must be an allowed
+//! protocol, except the [`NATIVE_PROGRAMS`] the wallet uses itself.
+//!
+//! The list is empty by default, so no protocol interaction, swap or
+//! liquidity action is allowed until one is declared. Configs can name
+//! well-known programs symbolically, as `raydium-amm-v4` or
+//! `orca-whirlpool`, instead of by raw program id.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::protocols::{ProtocolSettings, RAYDIUM_AMM_V4, RAYDIUM_AMM_V4_PROGRAM_ID};
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let protocol = ProtocolSettings::new(RAYDIUM_AMM_V4)
+//!     .with_risk_level(0.3)
+//!     .with_supported_actions(vec!["swap_tokens".to_string()])
+//!     .to_protocol()?;
+//! assert_eq!(protocol.address, RAYDIUM_AMM_V4_PROGRAM_ID);
+//! assert!(protocol.supports("swap_tokens"));
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{compute_budget, message::Message, pubkey, pubkey::Pubkey, stake, system_program};
+
+use crate::error::{Error, Result};
+use crate::types::{AgentAction, Protocol};
+
+/// Raydium AMM v4 program
+pub const RAYDIUM_AMM_V4_PROGRAM_ID: Pubkey =
+    pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+
+/// Orca Whirlpool program
+pub const ORCA_WHIRLPOOL_PROGRAM_ID: Pubkey =
+    pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+
+/// Symbolic name of the Raydium AMM v4 program
+pub const RAYDIUM_AMM_V4: &str = "raydium-amm-v4";
+
+/// Symbolic name of the Orca Whirlpool program
+pub const ORCA_WHIRLPOOL: &str = "orca-whirlpool";
+
+/// Programs configs may name symbolically
+pub const KNOWN_PROTOCOLS: &[(&str, Pubkey)] = &[
+    (RAYDIUM_AMM_V4, RAYDIUM_AMM_V4_PROGRAM_ID),
+    (ORCA_WHIRLPOOL, ORCA_WHIRLPOOL_PROGRAM_ID),
+];
+
+/// Programs the wallet's own transactions use, allowed without an entry
+pub const NATIVE_PROGRAMS: &[Pubkey] = &[
+    system_program::ID,
+    compute_budget::ID,
+    stake::program::ID,
+    spl_token::ID,
+    spl_token_2022::ID,
+    spl_associated_token_account::ID,
+    spl_memo::ID,
+];
+
+/// Program id of a symbolic protocol name or a base58 program id
+pub fn resolve_program(program: &str) -> Result<Pubkey> {
+    if let Some((_, address)) = KNOWN_PROTOCOLS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(program))
+    {
+        return Ok(*address);
+    }
+    program.parse().map_err(|_| {
+        Error::config(format!(
+            "Unknown protocol '{}': expected one of {} or a program id",
+            program,
+            KNOWN_PROTOCOLS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    })
+}
+
+/// An allowed protocol, as written in config files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProtocolSettings {
+    /// Symbolic name such as `raydium-amm-v4`, or a program id
+    pub program: String,
+    /// Risk level (0-1 scale)
+    #[serde(default)]
+    pub risk_level: f64,
+    /// Actions the protocol may be used for; empty allows every action
+    #[serde(default)]
+    pub supported_actions: Vec<String>,
+}
+
+impl ProtocolSettings {
+    /// Allow `program` for every action, at risk level 0
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            risk_level: 0.0,
+            supported_actions: Vec::new(),
+        }
+    }
+
+    /// Set the risk level (0-1 scale)
+    pub fn with_risk_level(mut self, risk_level: f64) -> Self {
+        self.risk_level = risk_level;
+        self
+    }
+
+    /// Restrict the protocol to these actions
+    pub fn with_supported_actions(mut self, actions: Vec<String>) -> Self {
+        self.supported_actions = actions;
+        self
+    }
+
+    /// Resolve the program and check the risk level
+    pub fn to_protocol(&self) -> Result<Protocol> {
+        if !(0.0..=1.0).contains(&self.risk_level) {
+            return Err(Error::config(format!(
+                "Risk level of protocol '{}' must be between 0 and 1, got {}",
+                self.program, self.risk_level
+            )));
+        }
+        let address = resolve_program(&self.program)?;
+        Ok(Protocol::new(self.program.clone(), address)
+            .with_risk_level(self.risk_level)
+            .with_supported_actions(self.supported_actions.clone()))
+    }
+}
+
+/// Resolve every configured protocol
+pub fn resolve_all(settings: &[ProtocolSettings]) -> Result<Vec<Protocol>> {
+    settings.iter().map(ProtocolSettings::to_protocol).collect()
+}
+
+/// Check an action against the allowed protocols
+///
+/// Fails with `Error::PermissionDenied` naming the program that is not
+/// allowed. Actions that use no protocol always pass.
+pub fn check_action(action: &AgentAction, allowed: &[Protocol]) -> Result<()> {
+    match action {
+        AgentAction::ProtocolInteraction {
+            protocol,
+            action: requested,
+            ..
+        } => {
+            let program = resolve_program(protocol).ok();
+            let found = allowed.iter().find(|candidate| {
+                candidate.name.eq_ignore_ascii_case(protocol)
+                    || program.is_some_and(|program| program == candidate.address)
+            });
+            let Some(found) = found else {
+                return Err(Error::permission_denied(format!(
+                    "Program {} is not an allowed protocol",
+                    program.map_or_else(|| protocol.clone(), |program| program.to_string())
+                )));
+            };
+            if !found.supports(requested) {
+                return Err(Error::permission_denied(format!(
+                    "Program {} ({}) is not allowed to {}",
+                    found.address, found.name, requested
+                )));
+            }
+            Ok(())
+        }
+        AgentAction::SwapTokens { .. }
+        | AgentAction::ProvideLiquidity { .. }
+        | AgentAction::RemoveLiquidity { .. } => {
+            let kind = action.kind();
+            if !allowed.iter().any(|protocol| protocol.supports(kind)) {
+                return Err(Error::permission_denied(format!(
+                    "No allowed protocol supports {}",
+                    kind
+                )));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check the programs a transaction invokes against the allowed protocols
+///
+/// Fails with `Error::PermissionDenied` naming the first program that is
+/// neither native nor an allowed protocol.
+pub fn check_programs(message: &Message, allowed: &[Protocol]) -> Result<()> {
+    for instruction in &message.instructions {
+        let Some(program) = message
+            .account_keys
+            .get(usize::from(instruction.program_id_index))
+        else {
+            continue;
+        };
+        if NATIVE_PROGRAMS.contains(program)
+            || allowed.iter().any(|protocol| protocol.address == *program)
+        {
+            continue;
+        }
+        return Err(Error::permission_denied(format!(
+            "Program {} is not an allowed protocol",
+            program
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sol::Lamports;
+
+    fn interaction(protocol: &str, action: &str) -> AgentAction {
+        AgentAction::ProtocolInteraction {
+            protocol: protocol.to_string(),
+            action: action.to_string(),
+            parameters: "{}".to_string(),
+        }
+    }
+
+    fn swap() -> AgentAction {
+        AgentAction::SwapTokens {
+            input_mint: Pubkey::new_unique(),
+            output_mint: Pubkey::new_unique(),
+            amount: 1_000,
+            min_output_amount: 900,
+        }
+    }
+
+    #[test]
+    fn test_allowed_protocol_passes() -> Result<()> {
+        let allowed = resolve_all(&[ProtocolSettings::new(RAYDIUM_AMM_V4)
+            .with_risk_level(0.4)
+            .with_supported_actions(vec!["swap".to_string(), "swap_tokens".to_string()])])?;
+
+        // By symbolic name, by program id and by any casing of the name
+        check_action(&interaction(RAYDIUM_AMM_V4, "swap"), &allowed)?;
+        check_action(
+            &interaction(&RAYDIUM_AMM_V4_PROGRAM_ID.to_string(), "swap"),
+            &allowed,
+        )?;
+        check_action(&interaction("Raydium-AMM-v4", "swap"), &allowed)?;
+        check_action(&swap(), &allowed)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_other_protocols_and_actions_are_denied() -> Result<()> {
+        let allowed =
+            resolve_all(&[ProtocolSettings::new(ORCA_WHIRLPOOL)
+                .with_supported_actions(vec!["swap".to_string()])])?;
+
+        let error = check_action(&interaction(RAYDIUM_AMM_V4, "swap"), &allowed).err();
+        assert!(matches!(
+            error,
+            Some(Error::PermissionDenied(ref message))
+                if message.contains(&RAYDIUM_AMM_V4_PROGRAM_ID.to_string())
+        ));
+        assert!(matches!(
+            check_action(&interaction(ORCA_WHIRLPOOL, "withdraw"), &allowed),
+            Err(Error::PermissionDenied(_))
+        ));
+        // The only allowed protocol does not support swap_tokens
+        assert!(check_action(&swap(), &allowed).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_programs_must_be_allowed() -> Result<()> {
+        use solana_sdk::instruction::{AccountMeta, Instruction};
+        use solana_sdk::system_instruction;
+
+        let payer = Pubkey::new_unique();
+        let swap = Instruction::new_with_bytes(
+            RAYDIUM_AMM_V4_PROGRAM_ID,
+            &[9],
+            vec![AccountMeta::new(payer, true)],
+        );
+        let message = Message::new(
+            &[
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 1),
+                swap,
+            ],
+            Some(&payer),
+        );
+
+        assert!(matches!(
+            check_programs(&message, &[]),
+            Err(Error::PermissionDenied(ref message))
+                if message.contains(&RAYDIUM_AMM_V4_PROGRAM_ID.to_string())
+        ));
+        let allowed = resolve_all(&[ProtocolSettings::new(RAYDIUM_AMM_V4)])?;
+        check_programs(&message, &allowed)?;
+
+        // Native programs alone need no entry
+        let transfer = Message::new(
+            &[system_instruction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer),
+        );
+        check_programs(&transfer, &[])
+    }
+
+    #[test]
+    fn test_empty_list_denies_every_protocol_action() {
+        for action in [interaction(RAYDIUM_AMM_V4, "swap"), swap()] {
+            assert!(matches!(
+                check_action(&action, &[]),
+                Err(Error::PermissionDenied(_))
+            ));
+        }
+        let transfer = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1),
+            memo: None,
+        };
+        assert!(check_action(&transfer, &[]).is_ok());
+        assert!(resolve_program("serum-v3").is_err());
+        assert!(ProtocolSettings::new(ORCA_WHIRLPOOL)
+            .with_risk_level(1.5)
+            .to_protocol()
+            .is_err());
+    }
+}
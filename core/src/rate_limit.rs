@@ -0,0 +1,204 @@
+//! Token-bucket rate limiting
+//!
+//! A [`TokenBucket`] holds up to `capacity` tokens and refills them evenly
+//! over its window, so a full bucket allows a burst of `capacity` operations
+//! and then one operation per `window / capacity`. Every check takes the
+//! current instant as an argument, which keeps the limiter deterministic
+//! under simulated time.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Instant;
+//! use agent_wallet_core::rate_limit::TokenBucket;
+//!
+//! let bucket = TokenBucket::per_minute(2);
+//! let now = Instant::now();
+//! assert!(bucket.check_and_record(now).is_ok());
+//! assert!(bucket.check_and_record(now).is_ok());
+//! assert!(bucket.check_and_record(now).is_err());
+//! ```
+
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::sync::lock;
+
+/// Snapshot of a limiter's capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitState {
+    /// Operations allowed right now
+    pub remaining: u32,
+    /// Earliest instant the next operation is allowed; `None` if never
+    pub next_allowed: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: u32,
+    last_refill: Option<Instant>,
+}
+
+/// Thread-safe token bucket
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: u32,
+    window: Duration,
+    bucket: StdMutex<Bucket>,
+}
+
+impl TokenBucket {
+    /// Allow bursts of `capacity` operations, refilled evenly over `window`
+    pub fn new(capacity: u32, window: Duration) -> Self {
+        Self {
+            capacity,
+            window,
+            bucket: StdMutex::new(Bucket {
+                tokens: capacity,
+                last_refill: None,
+            }),
+        }
+    }
+
+    /// Allow `capacity` operations per minute
+    pub fn per_minute(capacity: u32) -> Self {
+        Self::new(capacity, Duration::from_secs(60))
+    }
+
+    /// Maximum burst size
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Take a token for an operation at `now`
+    ///
+    /// Fails with [`Error::RateLimitExceeded`] when the bucket is empty; the
+    /// operation is not recorded in that case.
+    pub fn check_and_record(&self, now: Instant) -> Result<()> {
+        let mut bucket = lock(&self.bucket);
+        self.refill(&mut bucket, now);
+        if bucket.tokens > 0 {
+            bucket.tokens -= 1;
+            return Ok(());
+        }
+
+        let retry = match self.next_allowed(&bucket, now) {
+            Some(at) => format!("retry in {:?}", at.saturating_duration_since(now)),
+            None => "no operations are allowed".to_string(),
+        };
+        Err(Error::RateLimitExceeded(format!(
+            "{} operations per {:?} used, {}",
+            self.capacity, self.window, retry
+        )))
+    }
+
+    /// Remaining capacity and next allowed time at `now`
+    pub fn state(&self, now: Instant) -> RateLimitState {
+        let mut bucket = lock(&self.bucket);
+        self.refill(&mut bucket, now);
+        RateLimitState {
+            remaining: bucket.tokens,
+            next_allowed: self.next_allowed(&bucket, now),
+        }
+    }
+
+    /// Time between two refilled tokens
+    fn interval(&self) -> Duration {
+        self.window / self.capacity.max(1)
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let Some(last_refill) = bucket.last_refill else {
+            bucket.last_refill = Some(now);
+            return;
+        };
+        if bucket.tokens >= self.capacity {
+            bucket.last_refill = Some(now);
+            return;
+        }
+
+        let interval = self.interval();
+        let elapsed = now.saturating_duration_since(last_refill);
+        let earned = elapsed.as_nanos() / interval.as_nanos().max(1);
+        let earned = u32::try_from(earned).unwrap_or(u32::MAX);
+        if earned == 0 {
+            return;
+        }
+        bucket.tokens = bucket.tokens.saturating_add(earned).min(self.capacity);
+        bucket.last_refill = if bucket.tokens == self.capacity {
+            Some(now)
+        } else {
+            Some(last_refill + interval * earned)
+        };
+    }
+
+    fn next_allowed(&self, bucket: &Bucket, now: Instant) -> Option<Instant> {
+        if self.capacity == 0 {
+            return None;
+        }
+        if bucket.tokens > 0 {
+            return Some(now);
+        }
+        Some(bucket.last_refill.unwrap_or(now) + self.interval())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_reject() {
+        let bucket = TokenBucket::new(3, Duration::from_secs(3));
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(bucket.check_and_record(now).is_ok());
+        }
+        assert!(matches!(
+            bucket.check_and_record(now),
+            Err(Error::RateLimitExceeded(_))
+        ));
+        assert_eq!(
+            bucket.state(now),
+            RateLimitState {
+                remaining: 0,
+                next_allowed: Some(now + Duration::from_secs(1)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_refill_over_simulated_time() {
+        let bucket = TokenBucket::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        assert!(bucket.check_and_record(start).is_ok());
+        assert!(bucket.check_and_record(start).is_ok());
+        assert!(bucket.check_and_record(start).is_err());
+
+        // One token every five seconds
+        let later = start + Duration::from_secs(4);
+        assert!(bucket.check_and_record(later).is_err());
+        let later = start + Duration::from_secs(5);
+        assert!(bucket.check_and_record(later).is_ok());
+        assert!(bucket.check_and_record(later).is_err());
+
+        // Idle time refills to capacity, not beyond
+        let later = start + Duration::from_secs(600);
+        assert_eq!(bucket.state(later).remaining, 2);
+        assert!(bucket.check_and_record(later).is_ok());
+        assert!(bucket.check_and_record(later).is_ok());
+        assert!(bucket.check_and_record(later).is_err());
+    }
+
+    #[test]
+    fn test_zero_capacity_never_allows() {
+        let bucket = TokenBucket::per_minute(0);
+        let now = Instant::now();
+        assert!(bucket.check_and_record(now).is_err());
+        assert_eq!(
+            bucket.state(now + Duration::from_secs(3600)).next_allowed,
+            None
+        );
+    }
+}
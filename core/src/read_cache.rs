@@ -0,0 +1,568 @@
+//! Short-lived cache of hot RPC reads
+//!
+//! Many agents on one [`RpcClient`](crate::rpc::RpcClient) ask for the same
+//! balances and blockhash every tick. With
+//! [`RpcClientConfig::read_cache`](crate::rpc::RpcClientConfig::read_cache)
+//! set, the client answers them from a read-through cache:
+//!
+//! - the latest blockhash is reused for [`ReadCacheConfig::blockhash_slots`]
+//!   slots, from a [`BlockhashCache`] that the wallet's
+//!   [`TransactionBuilder`](crate::transaction::TransactionBuilder) shares
+//! - balances and account data are reused for [`ReadCacheConfig::account_ttl`],
+//!   keyed by account and commitment
+//! - identical reads that are already in flight are coalesced: callers
+//!   arriving while a request is outstanding wait for its answer instead of
+//!   sending their own
+//!
+//! Sending a transaction invalidates the cached balances and accounts of
+//! every account it references, including reads still in flight. Followers
+//! of a coalesced read that fails get the leader's error as
+//! [`Error::Rpc`].
+//!
+//! Signing never uses the cache: transactions are signed on a blockhash
+//! fetched for them, so identical transactions do not share a signature.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::read_cache::BlockhashCache;
+//! use solana_sdk::hash::Hash;
+//!
+//! let cache = BlockhashCache::new();
+//! let blockhash = Hash::new_unique();
+//! cache.store(blockhash, 100);
+//!
+//! cache.observe_slot(103);
+//! assert_eq!(cache.get(4), Some(blockhash));
+//! cache.observe_slot(104);
+//! assert_eq!(cache.get(4), None);
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash as StdHash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use solana_sdk::{
+    account::Account,
+    clock::{Slot, DEFAULT_MS_PER_SLOT},
+    commitment_config::CommitmentLevel,
+    hash::Hash,
+    pubkey::Pubkey,
+};
+use tokio::sync::oneshot;
+
+use crate::config::ReadCacheSettings;
+use crate::error::{Error, Result};
+use crate::sync::lock;
+
+/// Slots a cached blockhash is reused for, by default
+pub const DEFAULT_BLOCKHASH_CACHE_SLOTS: u64 = 4;
+
+/// How long balances and account data are reused, by default
+pub const DEFAULT_ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// Cached entries kept before expired ones are swept
+const SWEEP_THRESHOLD: usize = 1_024;
+
+/// How long cached reads of an [`RpcClient`](crate::rpc::RpcClient) are reused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadCacheConfig {
+    /// Slots the latest blockhash is reused for
+    pub blockhash_slots: u64,
+    /// How long balances and account data are reused
+    pub account_ttl: Duration,
+}
+
+impl ReadCacheConfig {
+    /// Configuration from the `rpc.read_cache` settings
+    pub fn from_settings(settings: &ReadCacheSettings) -> Self {
+        Self {
+            blockhash_slots: settings.blockhash_slots,
+            account_ttl: Duration::from_millis(settings.account_ttl_ms),
+        }
+    }
+}
+
+impl Default for ReadCacheConfig {
+    fn default() -> Self {
+        Self {
+            blockhash_slots: DEFAULT_BLOCKHASH_CACHE_SLOTS,
+            account_ttl: DEFAULT_ACCOUNT_CACHE_TTL,
+        }
+    }
+}
+
+/// A recent blockhash with the slot it was fetched at
+///
+/// Clones share one cache. A blockhash ages by the slots observed since it
+/// was fetched, or by the time passed at the cluster's target slot time
+/// when that is further along, so it also expires when no slots are
+/// reported.
+#[derive(Debug, Clone, Default)]
+pub struct BlockhashCache {
+    state: Arc<Mutex<BlockhashState>>,
+}
+
+#[derive(Debug, Default)]
+struct BlockhashState {
+    entry: Option<CachedBlockhash>,
+    latest_slot: Option<Slot>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedBlockhash {
+    blockhash: Hash,
+    slot: Slot,
+    fetched_at: Instant,
+}
+
+impl BlockhashCache {
+    /// Empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache `blockhash`, fetched at `slot`
+    pub fn store(&self, blockhash: Hash, slot: Slot) {
+        let mut state = lock(&self.state);
+        state.entry = Some(CachedBlockhash {
+            blockhash,
+            slot,
+            fetched_at: Instant::now(),
+        });
+        state.latest_slot = Some(state.latest_slot.map_or(slot, |latest| latest.max(slot)));
+    }
+
+    /// Record a slot the node has reached, aging the cached blockhash
+    pub fn observe_slot(&self, slot: Slot) {
+        let mut state = lock(&self.state);
+        state.latest_slot = Some(state.latest_slot.map_or(slot, |latest| latest.max(slot)));
+    }
+
+    /// Latest slot observed
+    pub fn latest_slot(&self) -> Option<Slot> {
+        lock(&self.state).latest_slot
+    }
+
+    /// The cached blockhash, if it is younger than `validity_slots`
+    pub fn get(&self, validity_slots: u64) -> Option<Hash> {
+        let state = lock(&self.state);
+        let entry = state.entry?;
+        let observed = state
+            .latest_slot
+            .map_or(0, |latest| latest.saturating_sub(entry.slot));
+        let elapsed = (entry.fetched_at.elapsed().as_millis() / u128::from(DEFAULT_MS_PER_SLOT))
+            .try_into()
+            .unwrap_or(u64::MAX);
+        (observed.max(elapsed) < validity_slots).then_some(entry.blockhash)
+    }
+
+    /// Forget the cached blockhash, e.g. after the cluster reported it expired
+    pub fn invalidate(&self) {
+        lock(&self.state).entry = None;
+    }
+}
+
+/// How a cached read was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CacheOutcome {
+    /// From the cache
+    Hit,
+    /// By a request of its own
+    Miss,
+    /// By a request another caller had in flight
+    Coalesced,
+}
+
+impl CacheOutcome {
+    /// Label of the outcome in the RPC metrics
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Miss => "miss",
+            Self::Coalesced => "coalesced",
+        }
+    }
+}
+
+/// Values that expire after a time to live
+#[derive(Debug)]
+struct TtlMap<K, V> {
+    entries: Mutex<HashMap<K, (V, Instant)>>,
+}
+
+impl<K: Eq + StdHash, V: Clone> TtlMap<K, V> {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &K, ttl: Duration) -> Option<V> {
+        match lock(&self.entries).get(key) {
+            Some((value, fetched_at)) if fetched_at.elapsed() < ttl => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, key: K, value: V, fetched_at: Instant, ttl: Duration) {
+        let mut entries = lock(&self.entries);
+        if entries.len() >= SWEEP_THRESHOLD {
+            entries.retain(|_, (_, fetched_at)| fetched_at.elapsed() < ttl);
+        }
+        entries.insert(key, (value, fetched_at));
+    }
+
+    fn remove(&self, matches: impl Fn(&K) -> bool) {
+        lock(&self.entries).retain(|key, _| !matches(key));
+    }
+}
+
+type Waiter<V> = oneshot::Sender<std::result::Result<V, String>>;
+
+/// Requests in flight, each with the callers waiting on it
+#[derive(Debug)]
+struct Singleflight<K, V> {
+    flights: Mutex<HashMap<K, (u64, Vec<Waiter<V>>)>>,
+    next_id: AtomicU64,
+}
+
+/// Leadership of a flight; dropping it unfinished lets the waiters fetch themselves
+struct Flight<'a, K: Eq + StdHash, V> {
+    singleflight: &'a Singleflight<K, V>,
+    key: Option<K>,
+    id: u64,
+}
+
+impl<K: Eq + StdHash, V> Flight<'_, K, V> {
+    /// End the flight, handing back its waiters
+    fn land(mut self) -> Vec<Waiter<V>> {
+        self.key
+            .take()
+            .map(|key| self.singleflight.remove(key, self.id))
+            .unwrap_or_default()
+    }
+}
+
+impl<K: Eq + StdHash, V> Drop for Flight<'_, K, V> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.singleflight.remove(key, self.id);
+        }
+    }
+}
+
+impl<K: Eq + StdHash + Clone, V: Clone> Singleflight<K, V> {
+    fn new() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Run `fetch` unless an identical request is in flight, then share its answer
+    async fn run<F>(&self, key: K, fetch: F) -> Result<(V, CacheOutcome)>
+    where
+        F: Future<Output = Result<V>>,
+    {
+        let joined = {
+            let mut flights = lock(&self.flights);
+            match flights.get_mut(&key) {
+                Some((_, waiters)) => {
+                    let (sender, receiver) = oneshot::channel();
+                    waiters.push(sender);
+                    Ok(receiver)
+                }
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    flights.insert(key.clone(), (id, Vec::new()));
+                    Err(id)
+                }
+            }
+        };
+
+        let flight = match joined {
+            Ok(receiver) => match receiver.await {
+                Ok(Ok(value)) => return Ok((value, CacheOutcome::Coalesced)),
+                Ok(Err(message)) => return Err(Error::rpc(message)),
+                // The leader was cancelled or its flight abandoned; fetch alone
+                Err(_) => return fetch.await.map(|value| (value, CacheOutcome::Miss)),
+            },
+            Err(id) => Flight {
+                singleflight: self,
+                key: Some(key),
+                id,
+            },
+        };
+
+        let outcome = fetch.await;
+        let shared = match &outcome {
+            Ok(value) => Ok(value.clone()),
+            Err(err) => Err(err.to_string()),
+        };
+        for waiter in flight.land() {
+            let _ = waiter.send(shared.clone());
+        }
+        outcome.map(|value| (value, CacheOutcome::Miss))
+    }
+}
+
+impl<K: Eq + StdHash, V> Singleflight<K, V> {
+    /// Remove flight `id` of `key`, if it was not replaced since
+    fn remove(&self, key: K, id: u64) -> Vec<Waiter<V>> {
+        let mut flights = lock(&self.flights);
+        match flights.get(&key) {
+            Some((current, _)) if *current == id => flights
+                .remove(&key)
+                .map(|(_, waiters)| waiters)
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Abandon the flights of keys matching `matches`
+    ///
+    /// Callers waiting on them fetch for themselves, and the leaders' answers
+    /// are no longer shared.
+    fn detach(&self, matches: impl Fn(&K) -> bool) {
+        lock(&self.flights).retain(|key, _| !matches(key));
+    }
+}
+
+/// Account read, keyed by the account and the commitment it was read at
+pub(crate) type AccountKey = (Pubkey, CommitmentLevel);
+
+/// Read-through cache of an [`RpcClient`](crate::rpc::RpcClient)
+pub(crate) struct ReadCache {
+    config: ReadCacheConfig,
+    blockhash: BlockhashCache,
+    blockhash_flights: Singleflight<CommitmentLevel, (Hash, Slot)>,
+    balances: TtlMap<AccountKey, u64>,
+    balance_flights: Singleflight<AccountKey, u64>,
+    accounts: TtlMap<AccountKey, Option<Account>>,
+    account_flights: Singleflight<AccountKey, Option<Account>>,
+    /// When each account was last written by a transaction sent through the client
+    writes: Mutex<HashMap<Pubkey, Instant>>,
+}
+
+impl ReadCache {
+    pub(crate) fn new(config: ReadCacheConfig) -> Self {
+        Self {
+            config,
+            blockhash: BlockhashCache::new(),
+            blockhash_flights: Singleflight::new(),
+            balances: TtlMap::new(),
+            balance_flights: Singleflight::new(),
+            accounts: TtlMap::new(),
+            account_flights: Singleflight::new(),
+            writes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Blockhash cache shared with transaction builders
+    pub(crate) fn blockhash_cache(&self) -> BlockhashCache {
+        self.blockhash.clone()
+    }
+
+    /// The latest blockhash, fetched with `fetch` when the cached one is too old
+    pub(crate) async fn blockhash<F>(
+        &self,
+        commitment: CommitmentLevel,
+        fetch: F,
+    ) -> Result<(Hash, CacheOutcome)>
+    where
+        F: Future<Output = Result<(Hash, Slot)>>,
+    {
+        if let Some(blockhash) = self.blockhash.get(self.config.blockhash_slots) {
+            return Ok((blockhash, CacheOutcome::Hit));
+        }
+        let ((blockhash, slot), outcome) = self.blockhash_flights.run(commitment, fetch).await?;
+        if outcome == CacheOutcome::Miss {
+            self.blockhash.store(blockhash, slot);
+        }
+        Ok((blockhash, outcome))
+    }
+
+    /// An account's balance, fetched with `fetch` when not cached
+    pub(crate) async fn balance<F>(&self, key: AccountKey, fetch: F) -> Result<(u64, CacheOutcome)>
+    where
+        F: Future<Output = Result<u64>>,
+    {
+        let (value, outcome, requested_at) = read_through(
+            &self.balances,
+            &self.balance_flights,
+            key,
+            self.config.account_ttl,
+            fetch,
+        )
+        .await?;
+        if outcome == CacheOutcome::Miss && !self.written_since(&key.0, requested_at) {
+            self.balances
+                .insert(key, value, requested_at, self.config.account_ttl);
+        }
+        Ok((value, outcome))
+    }
+
+    /// An account, `None` if it does not exist, fetched with `fetch` when not cached
+    pub(crate) async fn account<F>(
+        &self,
+        key: AccountKey,
+        fetch: F,
+    ) -> Result<(Option<Account>, CacheOutcome)>
+    where
+        F: Future<Output = Result<Option<Account>>>,
+    {
+        let (value, outcome, requested_at) = read_through(
+            &self.accounts,
+            &self.account_flights,
+            key,
+            self.config.account_ttl,
+            fetch,
+        )
+        .await?;
+        if outcome == CacheOutcome::Miss && !self.written_since(&key.0, requested_at) {
+            self.accounts
+                .insert(key, value.clone(), requested_at, self.config.account_ttl);
+        }
+        Ok((value, outcome))
+    }
+
+    /// Forget what is cached or in flight about `pubkeys`, e.g. after a write
+    ///
+    /// Reads that were requested before are answered but not cached.
+    pub(crate) fn invalidate_accounts(&self, pubkeys: &[Pubkey]) {
+        let now = Instant::now();
+        {
+            let mut writes = lock(&self.writes);
+            if writes.len() >= SWEEP_THRESHOLD {
+                let ttl = self.config.account_ttl;
+                writes.retain(|_, written_at| written_at.elapsed() < ttl);
+            }
+            for pubkey in pubkeys {
+                writes.insert(*pubkey, now);
+            }
+        }
+        let matches = |(pubkey, _): &AccountKey| pubkeys.contains(pubkey);
+        self.balances.remove(matches);
+        self.accounts.remove(matches);
+        self.balance_flights.detach(matches);
+        self.account_flights.detach(matches);
+    }
+
+    /// Whether `pubkey` was written at or after `requested_at`
+    fn written_since(&self, pubkey: &Pubkey, requested_at: Instant) -> bool {
+        lock(&self.writes)
+            .get(pubkey)
+            .is_some_and(|written_at| *written_at >= requested_at)
+    }
+}
+
+/// Answer from `entries`, or from one request per key shared through `flights`
+///
+/// Returns when the answer was requested, for the caller to cache it.
+async fn read_through<K, V, F>(
+    entries: &TtlMap<K, V>,
+    flights: &Singleflight<K, V>,
+    key: K,
+    ttl: Duration,
+    fetch: F,
+) -> Result<(V, CacheOutcome, Instant)>
+where
+    K: Eq + StdHash + Clone,
+    V: Clone,
+    F: Future<Output = Result<V>>,
+{
+    let requested_at = Instant::now();
+    if let Some(value) = entries.get(&key, ttl) {
+        return Ok((value, CacheOutcome::Hit, requested_at));
+    }
+    let (value, outcome) = flights.run(key, fetch).await?;
+    Ok((value, outcome, requested_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blockhash_ages_by_slots_and_time() {
+        let cache = BlockhashCache::new();
+        assert_eq!(cache.get(u64::MAX), None);
+
+        let blockhash = Hash::new_unique();
+        cache.store(blockhash, 1_000);
+        assert_eq!(cache.latest_slot(), Some(1_000));
+        assert_eq!(cache.get(1), Some(blockhash));
+
+        // Clones share the cache, and older slots never rejuvenate it
+        cache.clone().observe_slot(1_010);
+        cache.observe_slot(1_000);
+        assert_eq!(cache.get(10), None);
+        assert_eq!(cache.get(11), Some(blockhash));
+
+        cache.invalidate();
+        assert_eq!(cache.get(u64::MAX), None);
+    }
+
+    #[tokio::test]
+    async fn test_invalidation_drops_reads_in_flight() -> Result<()> {
+        let cache = ReadCache::new(ReadCacheConfig::default());
+        let sender = Pubkey::new_unique();
+        let key = (sender, CommitmentLevel::Confirmed);
+
+        // A read that started before the write is answered but not cached
+        let (balance, outcome) = cache
+            .balance(key, async {
+                cache.invalidate_accounts(&[sender]);
+                Ok(5)
+            })
+            .await?;
+        assert_eq!((balance, outcome), (5, CacheOutcome::Miss));
+
+        let (balance, outcome) = cache.balance(key, async { Ok(4) }).await?;
+        assert_eq!((balance, outcome), (4, CacheOutcome::Miss));
+        let (balance, outcome) = cache.balance(key, async { Ok(3) }).await?;
+        assert_eq!((balance, outcome), (4, CacheOutcome::Hit));
+
+        cache.invalidate_accounts(&[sender]);
+        let (balance, _) = cache.balance(key, async { Ok(3) }).await?;
+        assert_eq!(balance, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_flight_is_shared_and_not_cached() -> Result<()> {
+        let cache = ReadCache::new(ReadCacheConfig::default());
+        let key = (Pubkey::new_unique(), CommitmentLevel::Confirmed);
+        let (release, released) = oneshot::channel::<()>();
+
+        let leader = cache.balance(key, async {
+            let _ = released.await;
+            Err(Error::rpc("node unavailable"))
+        });
+        tokio::pin!(leader);
+        assert!(futures::poll!(&mut leader).is_pending());
+
+        let follower = cache.balance(key, async { Ok(1) });
+        let releaser = async {
+            // Fail the leader once the follower waits on it
+            while !matches!(
+                lock(&cache.balance_flights.flights).get(&key),
+                Some((_, waiters)) if !waiters.is_empty()
+            ) {
+                tokio::task::yield_now().await;
+            }
+            let _ = release.send(());
+        };
+        let (leader, follower, ()) = tokio::join!(leader, follower, releaser);
+        assert!(leader.is_err());
+        assert!(follower.is_err());
+
+        let (balance, outcome) = cache.balance(key, async { Ok(2) }).await?;
+        assert_eq!((balance, outcome), (2, CacheOutcome::Miss));
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Rent-exemption costs of account creation
+//!
+//! Creating an account locks its rent-exempt minimum in the new account.
+//! That balance is not a fee: it comes back when the account is closed. The
+//! [`RentCalculator`] caches the minimum per data size and itemizes the
+//! accounts a transaction creates, so planning can report rent separately
+//! from fees and transfer amounts and budgets can treat it as reclaimable.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_core::rent::RentCalculator;
+//! use agent_wallet_core::rpc::{DynRpcProvider, RpcClient, RpcClientConfig};
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let rpc: Arc<dyn DynRpcProvider> = Arc::new(RpcClient::new(RpcClientConfig::default()).await?);
+//! let rent = RentCalculator::new(rpc);
+//! let token_account_rent = rent.minimum_balance(165).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::{program_pack::Pack, system_program};
+use spl_token_2022::extension::ExtensionType;
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::sync::lock;
+
+/// Account created by an instruction, before its rent is known
+enum Creation {
+    /// Funded with an explicit amount for an account of the given size
+    Funded(Pubkey, u64, u64),
+    /// Associated token account of the given size, unless it already exists
+    TokenAccount(Pubkey, usize),
+}
+
+/// Cached rent-exemption minimums and account-creation itemization
+pub struct RentCalculator {
+    rpc: Arc<dyn DynRpcProvider>,
+    cache: StdMutex<HashMap<usize, u64>>,
+}
+
+impl RentCalculator {
+    /// Create a calculator querying `rpc`
+    pub fn new(rpc: Arc<dyn DynRpcProvider>) -> Self {
+        Self {
+            rpc,
+            cache: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Rent-exempt minimum balance for an account of `data_len` bytes
+    pub async fn minimum_balance(&self, data_len: usize) -> Result<u64> {
+        if let Some(lamports) = lock(&self.cache).get(&data_len) {
+            return Ok(*lamports);
+        }
+        let lamports = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await?;
+        lock(&self.cache).insert(data_len, lamports);
+        Ok(lamports)
+    }
+
+    /// Rent locked in each account the message creates, in instruction order
+    ///
+    /// System `CreateAccount` instructions are charged the lamports they fund
+    /// the account with, up to the rent-exempt minimum for its size.
+    /// Associated token accounts are charged the minimum for a token account,
+    /// and only if they don't exist yet, since the idempotent create is a
+    /// no-op for existing accounts.
+    pub async fn itemize(&self, message: &Message) -> Result<Vec<(Pubkey, u64)>> {
+        let mut creations = Vec::new();
+        for instruction in &message.instructions {
+            let key = |position: usize| -> Result<Pubkey> {
+                instruction
+                    .accounts
+                    .get(position)
+                    .and_then(|index| message.account_keys.get(*index as usize))
+                    .copied()
+                    .ok_or_else(|| Error::transaction("Instruction is missing an account"))
+            };
+            let program_id = message
+                .account_keys
+                .get(instruction.program_id_index as usize)
+                .ok_or_else(|| Error::transaction("Instruction program is out of range"))?;
+
+            if *program_id == system_program::id() {
+                match bincode::deserialize(&instruction.data) {
+                    Ok(SystemInstruction::CreateAccount {
+                        lamports, space, ..
+                    })
+                    | Ok(SystemInstruction::CreateAccountWithSeed {
+                        lamports, space, ..
+                    }) => {
+                        creations.push(Creation::Funded(key(1)?, lamports, space));
+                    }
+                    _ => {}
+                }
+            } else if *program_id == spl_associated_token_account::id()
+                && matches!(instruction.data.as_slice(), [] | [0] | [1])
+            {
+                let token_program = key(5)?;
+                creations.push(Creation::TokenAccount(
+                    key(1)?,
+                    token_account_len(&token_program)?,
+                ));
+            }
+        }
+
+        let token_accounts: Vec<Pubkey> = creations
+            .iter()
+            .filter_map(|creation| match creation {
+                Creation::TokenAccount(address, _) => Some(*address),
+                Creation::Funded(..) => None,
+            })
+            .collect();
+        let existing: Vec<Pubkey> = if token_accounts.is_empty() {
+            Vec::new()
+        } else {
+            let accounts = self.rpc.get_multiple_accounts(&token_accounts).await?;
+            token_accounts
+                .into_iter()
+                .zip(accounts)
+                .filter(|(_, account)| account.is_some())
+                .map(|(address, _)| address)
+                .collect()
+        };
+
+        let mut costs = Vec::with_capacity(creations.len());
+        for creation in creations {
+            match creation {
+                // Funding beyond the rent-exempt minimum, such as delegated
+                // stake, is not rent; `sol_transferred` counts it as a
+                // transfer into the account
+                Creation::Funded(address, lamports, space) => {
+                    let minimum = self.minimum_balance(space as usize).await?;
+                    costs.push((address, lamports.min(minimum)));
+                }
+                Creation::TokenAccount(address, len) => {
+                    if !existing.contains(&address) {
+                        costs.push((address, self.minimum_balance(len).await?));
+                    }
+                }
+            }
+        }
+        Ok(costs)
+    }
+}
+
+/// Size of a new associated token account for a token program
+///
+/// Token-2022 accounts carry the immutable-owner extension; extensions
+/// required by the mint are not known here and not included.
+fn token_account_len(token_program: &Pubkey) -> Result<usize> {
+    if *token_program == spl_token_2022::id() {
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&[
+            ExtensionType::ImmutableOwner,
+        ])
+        .map_err(|e| Error::token(format!("Failed to size token account: {}", e)))
+    } else {
+        Ok(spl_token::state::Account::LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+    use solana_sdk::account::Account;
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use solana_sdk::rent::Rent;
+    use solana_sdk::system_instruction;
+    use spl_associated_token_account::get_associated_token_address;
+    use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+    fn token_transfer(payer: &Pubkey, to: &Pubkey, mint: &Pubkey) -> Result<Message> {
+        let instructions = vec![
+            create_associated_token_account_idempotent(payer, to, mint, &spl_token::id()),
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &get_associated_token_address(payer, mint),
+                &get_associated_token_address(to, mint),
+                payer,
+                &[],
+                10,
+            )
+            .map_err(|e| Error::token(e.to_string()))?,
+        ];
+        Ok(Message::new(&instructions, Some(payer)))
+    }
+
+    #[tokio::test]
+    async fn test_token_transfer_itemizes_new_token_account() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let rent = RentCalculator::new(rpc.clone());
+        let (payer, to, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let message = token_transfer(&payer, &to, &mint)?;
+        let destination = get_associated_token_address(&to, &mint);
+
+        let expected = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+        assert_eq!(rent.itemize(&message).await?, vec![(destination, expected)]);
+        assert_eq!(rent.itemize(&message).await?, vec![(destination, expected)]);
+        assert_eq!(rpc.rent_queries(), 1);
+
+        // An existing account costs nothing to "create" again
+        rpc.set_account(
+            destination,
+            Account::new(expected, spl_token::state::Account::LEN, &spl_token::id()),
+        );
+        assert!(rent.itemize(&message).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_counter_initialization_itemizes_counter_account() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let rent = RentCalculator::new(rpc);
+        let (payer, counter, program) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let counter_rent = rent.minimum_balance(8).await?;
+        let instructions = vec![
+            system_instruction::create_account(&payer, &counter, counter_rent, 8, &program),
+            Instruction::new_with_bytes(
+                program,
+                &[0],
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(payer, true),
+                ],
+            ),
+            system_instruction::transfer(&payer, &counter, 1_000),
+        ];
+        let message = Message::new(&instructions, Some(&payer));
+
+        assert_eq!(rent.itemize(&message).await?, vec![(counter, counter_rent)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+//! Classification of RPC failures and the delays between retries
+//!
+//! Not every RPC error is worth retrying. [`RpcErrorClass::of`] sorts a
+//! client error into one of four classes the failover loop of
+//! [`RpcClient`](crate::rpc::RpcClient) acts on: fatal errors (invalid
+//! params, failed preflight simulations) are returned at once, rate-limited
+//! requests wait for the server's retry-after when it sends one, other
+//! transient failures back off exponentially with jitter, and an expired
+//! blockhash goes back to the caller, which has to sign again.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::retry::{backoff_delay, RpcErrorClass};
+//! use agent_wallet_core::Error;
+//!
+//! fn next_step(error: &Error) -> &'static str {
+//!     match error.classify() {
+//!         RpcErrorClass::Fatal => "give up",
+//!         RpcErrorClass::BlockhashExpired => "sign again with a fresh blockhash",
+//!         RpcErrorClass::Retryable | RpcErrorClass::RateLimited { .. } => "retry",
+//!     }
+//! }
+//!
+//! // Ceiling of 800 ms at attempt 3: waits between 400 and 800 ms
+//! let delay = backoff_delay(3, 100, 5_000, rand::random());
+//! ```
+
+use std::time::Duration;
+
+use solana_client::{
+    client_error::ClientError as SolanaClientError,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::transaction::TransactionError;
+
+/// JSON-RPC: the request is not a valid request object
+const JSON_RPC_INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC: the method does not exist
+const JSON_RPC_METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC: invalid method parameters
+const JSON_RPC_INVALID_PARAMS: i64 = -32602;
+/// Solana: `sendTransaction` preflight simulation failed
+const SEND_TRANSACTION_PREFLIGHT_FAILURE: i64 = -32002;
+/// Solana: the node is unhealthy or behind the cluster
+const NODE_UNHEALTHY: i64 = -32005;
+/// Rate limit reported in a JSON-RPC error instead of an HTTP status
+const TOO_MANY_REQUESTS: i64 = 429;
+
+/// How the failover loop treats a failed RPC request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcErrorClass {
+    /// Transient failure, retried with exponential backoff
+    Retryable,
+    /// The endpoint throttled the request
+    RateLimited {
+        /// Wait the server asked for, when it sent one
+        retry_after: Option<Duration>,
+    },
+    /// Sending the same request again cannot succeed
+    Fatal,
+    /// The transaction's blockhash expired; it must be signed again
+    BlockhashExpired,
+}
+
+impl RpcErrorClass {
+    /// Classify a Solana client error
+    pub fn of(error: &SolanaClientError) -> Self {
+        match error {
+            SolanaClientError::Io(_)
+            | SolanaClientError::EmptyResponse
+            | SolanaClientError::Custom(_) => Self::Retryable,
+            SolanaClientError::Reqwest(e) => match e.status() {
+                Some(status) if status.as_u16() == 429 => Self::RateLimited { retry_after: None },
+                Some(status) if status.is_client_error() => Self::Fatal,
+                _ => Self::Retryable,
+            },
+            SolanaClientError::RpcError(e) => Self::of_rpc_error(e),
+            SolanaClientError::TooManyRequests => Self::RateLimited { retry_after: None },
+            SolanaClientError::TransactionError(e) => Self::of_transaction_error(e),
+            SolanaClientError::SerdeJson(_)
+            | SolanaClientError::SerdeYaml(_)
+            | SolanaClientError::InvalidParams(_)
+            | SolanaClientError::DecodeError(_) => Self::Fatal,
+        }
+    }
+
+    /// Classify an error returned by the RPC server
+    ///
+    /// Unknown server error codes are assumed transient.
+    pub fn of_rpc_error(error: &RpcError) -> Self {
+        match error {
+            RpcError::RpcResponseError {
+                code,
+                message,
+                data,
+            } => match *code {
+                TOO_MANY_REQUESTS => Self::RateLimited {
+                    retry_after: parse_retry_after(message),
+                },
+                NODE_UNHEALTHY => Self::Retryable,
+                SEND_TRANSACTION_PREFLIGHT_FAILURE => match data {
+                    RpcResponseErrorData::SendTransactionPreflightFailure(result) => result
+                        .err
+                        .as_ref()
+                        .map_or(Self::Fatal, Self::of_transaction_error),
+                    _ if message.contains("Blockhash not found") => Self::BlockhashExpired,
+                    _ => Self::Fatal,
+                },
+                JSON_RPC_INVALID_REQUEST | JSON_RPC_METHOD_NOT_FOUND | JSON_RPC_INVALID_PARAMS => {
+                    Self::Fatal
+                }
+                _ => Self::Retryable,
+            },
+            RpcError::RpcRequestError(_) => Self::Retryable,
+            RpcError::ParseError(_) | RpcError::ForUser(_) => Self::Fatal,
+        }
+    }
+
+    /// Classify a transaction rejected by the cluster
+    pub fn of_transaction_error(error: &TransactionError) -> Self {
+        match error {
+            TransactionError::BlockhashNotFound => Self::BlockhashExpired,
+            _ => Self::Fatal,
+        }
+    }
+
+    /// Whether the failover loop sends the same request again
+    pub fn is_retried(&self) -> bool {
+        matches!(self, Self::Retryable | Self::RateLimited { .. })
+    }
+}
+
+/// Delay before retry `attempt`, counting from 0
+///
+/// The ceiling doubles from `base_ms` with every attempt up to `max_ms`. The
+/// delay is half the ceiling plus `jitter` (0 to 1) of the other half, so
+/// clients failing together spread out without ever retrying immediately.
+pub fn backoff_delay(attempt: u32, base_ms: u64, max_ms: u64, jitter: f64) -> Duration {
+    let ceiling = base_ms
+        .saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX))
+        .min(max_ms);
+    let half = ceiling / 2;
+    let spread = (half as f64 * jitter.clamp(0.0, 1.0)).round() as u64;
+    Duration::from_millis(ceiling - half + spread)
+}
+
+/// Wait requested in a rate limit message ("retry after 2", "Retry-After: 500ms")
+fn parse_retry_after(message: &str) -> Option<Duration> {
+    let lower = message.to_ascii_lowercase();
+    let start = ["retry-after", "retry after"]
+        .iter()
+        .find_map(|key| lower.find(key).map(|index| index + key.len()))?;
+    let rest = lower[start..].trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let value: u64 = rest[..digits].parse().ok()?;
+    if rest[digits..].starts_with("ms") {
+        Some(Duration::from_millis(value))
+    } else {
+        Some(Duration::from_secs(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn response_error(code: i64, message: &str) -> SolanaClientError {
+        SolanaClientError::RpcError(RpcError::RpcResponseError {
+            code,
+            message: message.to_string(),
+            data: RpcResponseErrorData::Empty,
+        })
+    }
+
+    #[test]
+    fn test_client_errors_map_to_classes() {
+        let matrix = [
+            (
+                SolanaClientError::Io(io::Error::new(io::ErrorKind::ConnectionReset, "reset")),
+                RpcErrorClass::Retryable,
+            ),
+            (SolanaClientError::EmptyResponse, RpcErrorClass::Retryable),
+            (
+                SolanaClientError::TooManyRequests,
+                RpcErrorClass::RateLimited { retry_after: None },
+            ),
+            (
+                SolanaClientError::InvalidParams("missing pubkey".to_string()),
+                RpcErrorClass::Fatal,
+            ),
+            (
+                SolanaClientError::TransactionError(TransactionError::BlockhashNotFound),
+                RpcErrorClass::BlockhashExpired,
+            ),
+            (
+                SolanaClientError::TransactionError(TransactionError::AlreadyProcessed),
+                RpcErrorClass::Fatal,
+            ),
+            (
+                response_error(NODE_UNHEALTHY, "Node is behind by 42 slots"),
+                RpcErrorClass::Retryable,
+            ),
+            (
+                response_error(TOO_MANY_REQUESTS, "Too many requests, retry after 2"),
+                RpcErrorClass::RateLimited {
+                    retry_after: Some(Duration::from_secs(2)),
+                },
+            ),
+            (
+                response_error(TOO_MANY_REQUESTS, "Too many requests"),
+                RpcErrorClass::RateLimited { retry_after: None },
+            ),
+            (
+                response_error(JSON_RPC_INVALID_PARAMS, "Invalid params"),
+                RpcErrorClass::Fatal,
+            ),
+            (
+                response_error(
+                    SEND_TRANSACTION_PREFLIGHT_FAILURE,
+                    "Transaction simulation failed: Error processing Instruction 0",
+                ),
+                RpcErrorClass::Fatal,
+            ),
+            (
+                response_error(
+                    SEND_TRANSACTION_PREFLIGHT_FAILURE,
+                    "Transaction simulation failed: Blockhash not found",
+                ),
+                RpcErrorClass::BlockhashExpired,
+            ),
+            (
+                response_error(-32004, "Block not available for slot 1"),
+                RpcErrorClass::Retryable,
+            ),
+            (
+                SolanaClientError::RpcError(RpcError::RpcRequestError("timed out".to_string())),
+                RpcErrorClass::Retryable,
+            ),
+            (
+                SolanaClientError::RpcError(RpcError::ForUser("bad input".to_string())),
+                RpcErrorClass::Fatal,
+            ),
+        ];
+
+        for (error, expected) in matrix {
+            assert_eq!(RpcErrorClass::of(&error), expected, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_retry_after_parsing() {
+        assert_eq!(
+            parse_retry_after("Retry-After: 500ms"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            parse_retry_after("rate limited; retry after 3 seconds"),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(parse_retry_after("retry after a while"), None);
+        assert_eq!(parse_retry_after("slow down"), None);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let ceilings: Vec<u64> = (0..6)
+            .map(|attempt| backoff_delay(attempt, 100, 2_000, 1.0).as_millis() as u64)
+            .collect();
+        assert_eq!(ceilings, vec![100, 200, 400, 800, 1_600, 2_000]);
+
+        // Jitter only spreads the upper half
+        assert_eq!(
+            backoff_delay(3, 100, 2_000, 0.0),
+            Duration::from_millis(400)
+        );
+        assert_eq!(
+            backoff_delay(3, 100, 2_000, 0.5),
+            Duration::from_millis(600)
+        );
+        assert_eq!(
+            backoff_delay(200, 100, 2_000, 0.0),
+            Duration::from_millis(1_000)
+        );
+    }
+}
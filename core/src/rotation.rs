@@ -0,0 +1,445 @@
+//! Key usage tracking and rotation
+//!
+//! Every signature made with a wallet key is counted, and the count is kept
+//! in the wallet file along with when the key was created. A
+//! [`KeyRotationPolicy`] in `wallet.key_rotation` caps the signatures a key
+//! may make and how old it may get; once either threshold is crossed,
+//! [`Wallet::needs_rotation`](crate::Wallet::needs_rotation) reports why.
+//!
+//! [`Wallet::rotate_key`](crate::Wallet::rotate_key) then generates a new
+//! key and sweeps everything the old one holds to it, following the
+//! [`SweepPlan`] built by [`plan_sweep`]: each token balance moves to the new
+//! key's associated account, the emptied token accounts are closed with
+//! their rent going to the new key, and the remaining SOL follows last, less
+//! fees and the rent of the accounts created on the way. The wallet file is
+//! re-encrypted under the new key in one write that archives the old
+//! encrypted key and marks its sweep as pending; the mark is cleared once
+//! every sweep transaction confirmed. A rotation interrupted in between is
+//! finished by [`Wallet::resume_rotation`](crate::Wallet::resume_rotation).
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::rotation::{KeyRecord, KeyRotationPolicy};
+//!
+//! let policy = KeyRotationPolicy {
+//!     max_signatures: Some(10_000),
+//!     max_age_days: Some(90),
+//! };
+//! let mut record = KeyRecord::new(chrono::Utc::now());
+//! assert!(policy.check(&record, chrono::Utc::now()).is_none());
+//!
+//! record.signatures = 10_000;
+//! assert!(policy.check(&record, chrono::Utc::now()).is_some());
+//! ```
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    instruction::Instruction, pubkey::Pubkey, signature::Signature, system_instruction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::instruction::{close_account, transfer_checked};
+
+use crate::error::{Error, Result};
+use crate::keypair::EncryptedKeypair;
+use crate::token::TokenAccountInfo;
+
+/// Token accounts moved per sweep transaction, keeping each under the size limit
+pub const TOKENS_PER_SWEEP_TRANSACTION: usize = 4;
+
+/// Size of the largest associated token account a sweep creates, a
+/// Token-2022 account with the immutable-owner extension
+pub const SWEEP_TOKEN_ACCOUNT_LEN: usize = 170;
+
+/// When a wallet key should be replaced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyRotationPolicy {
+    /// Signatures a key may make before it is rotated
+    pub max_signatures: Option<u64>,
+    /// Days a key may be in use before it is rotated
+    pub max_age_days: Option<u32>,
+}
+
+impl KeyRotationPolicy {
+    /// Whether any threshold is set
+    pub fn is_enabled(&self) -> bool {
+        self.max_signatures.is_some() || self.max_age_days.is_some()
+    }
+
+    /// Why the key described by `record` should be rotated at `now`, if it should
+    pub fn check(&self, record: &KeyRecord, now: DateTime<Utc>) -> Option<RotationReason> {
+        if let Some(max) = self.max_signatures {
+            if record.signatures >= max {
+                return Some(RotationReason::Signatures {
+                    count: record.signatures,
+                    max,
+                });
+            }
+        }
+        if let Some(max_days) = self.max_age_days {
+            let days = now.signed_duration_since(record.created_at).num_days();
+            if days >= i64::from(max_days) {
+                return Some(RotationReason::Age { days, max_days });
+            }
+        }
+        None
+    }
+}
+
+/// Threshold of a [`KeyRotationPolicy`] a key crossed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationReason {
+    /// The key made too many signatures
+    Signatures {
+        /// Signatures made
+        count: u64,
+        /// Signatures allowed
+        max: u64,
+    },
+    /// The key is too old
+    Age {
+        /// Days since the key was created
+        days: i64,
+        /// Days allowed
+        max_days: u32,
+    },
+}
+
+impl fmt::Display for RotationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Signatures { count, max } => {
+                write!(f, "key made {} signatures, policy allows {}", count, max)
+            }
+            Self::Age { days, max_days } => {
+                write!(f, "key is {} days old, policy allows {}", days, max_days)
+            }
+        }
+    }
+}
+
+/// Usage of the current wallet key and the keys it replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRecord {
+    /// When the current key was created
+    pub created_at: DateTime<Utc>,
+    /// Signatures the current key made
+    #[serde(default)]
+    pub signatures: u64,
+    /// When the current key last signed
+    #[serde(default)]
+    pub last_used: Option<DateTime<Utc>>,
+    /// Keys the wallet used before, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archived: Vec<ArchivedKey>,
+    /// Archived key whose funds have not all moved to the current key yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_sweep: Option<Pubkey>,
+}
+
+impl KeyRecord {
+    /// Record of a key created at `created_at` that never signed
+    pub fn new(created_at: DateTime<Utc>) -> Self {
+        Self {
+            created_at,
+            signatures: 0,
+            last_used: None,
+            archived: Vec::new(),
+            pending_sweep: None,
+        }
+    }
+}
+
+/// A retired wallet key, kept encrypted under the passphrase for recovery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedKey {
+    /// Public key of the retired key
+    pub public_key: Pubkey,
+    /// The key, encrypted under the wallet passphrase
+    pub encrypted_keypair: EncryptedKeypair,
+    /// When the key was created
+    pub created_at: DateTime<Utc>,
+    /// When it was replaced
+    pub retired_at: DateTime<Utc>,
+    /// Signatures it made
+    pub signatures: u64,
+}
+
+/// A token account of the old key and the decimals of its mint
+#[derive(Debug, Clone)]
+pub struct SweepToken {
+    /// Account to empty and close
+    pub account: TokenAccountInfo,
+    /// Decimals of the account's mint
+    pub decimals: u8,
+}
+
+/// Transactions moving everything from an old key to a new one
+#[derive(Debug, Clone)]
+pub struct SweepPlan {
+    /// Instructions of each transaction, in the order they must be sent
+    pub transactions: Vec<Vec<Instruction>>,
+    /// Lamports the last transaction moves to the new key
+    pub sol_lamports: u64,
+}
+
+/// Plan the sweep of `old`'s SOL and token balances to `new`
+///
+/// `fee_per_transaction` is paid by `old` for every transaction, and
+/// `token_account_rent` for every associated account created for `new`.
+/// Wrapped SOL and empty accounts are closed straight to `new`; frozen
+/// accounts cannot move and are left behind. An empty `old` needs no
+/// transaction at all. Fails with `InsufficientFunds` if the SOL balance
+/// does not cover the fees and rent.
+pub fn plan_sweep(
+    old: &Pubkey,
+    new: &Pubkey,
+    sol_balance: u64,
+    fee_per_transaction: u64,
+    token_account_rent: u64,
+    tokens: &[SweepToken],
+) -> Result<SweepPlan> {
+    let mut transactions = Vec::new();
+    let mut created = 0u64;
+    let movable: Vec<&SweepToken> = tokens
+        .iter()
+        .filter(|token| !token.account.is_frozen)
+        .collect();
+    for chunk in movable.chunks(TOKENS_PER_SWEEP_TRANSACTION) {
+        let mut instructions = Vec::new();
+        for token in chunk {
+            let account = &token.account;
+            if account.balance > 0 && !account.is_native {
+                let destination = get_associated_token_address_with_program_id(
+                    new,
+                    &account.mint,
+                    &account.program_id,
+                );
+                instructions.push(create_associated_token_account_idempotent(
+                    old,
+                    new,
+                    &account.mint,
+                    &account.program_id,
+                ));
+                instructions.push(
+                    transfer_checked(
+                        &account.program_id,
+                        &account.address,
+                        &account.mint,
+                        &destination,
+                        old,
+                        &[],
+                        account.balance,
+                        token.decimals,
+                    )
+                    .map_err(|e| Error::token(e.to_string()))?,
+                );
+                created += 1;
+            }
+            instructions.push(
+                close_account(&account.program_id, &account.address, new, old, &[])
+                    .map_err(|e| Error::token(e.to_string()))?,
+            );
+        }
+        transactions.push(instructions);
+    }
+    if transactions.is_empty() {
+        if sol_balance == 0 {
+            return Ok(SweepPlan {
+                transactions,
+                sol_lamports: 0,
+            });
+        }
+        transactions.push(Vec::new());
+    }
+
+    let fees = fee_per_transaction.saturating_mul(transactions.len() as u64);
+    let required = fees.saturating_add(token_account_rent.saturating_mul(created));
+    let sol_lamports = sol_balance
+        .checked_sub(required)
+        .ok_or(Error::InsufficientFunds {
+            required,
+            available: sol_balance,
+        })?;
+    if sol_lamports > 0 {
+        if let Some(last) = transactions.last_mut() {
+            last.push(system_instruction::transfer(old, new, sol_lamports));
+        }
+    }
+    Ok(SweepPlan {
+        transactions,
+        sol_lamports,
+    })
+}
+
+/// Outcome of a key rotation
+#[derive(Debug, Clone)]
+pub struct RotationReport {
+    /// Key that was retired
+    pub old_public_key: Pubkey,
+    /// Key now holding the wallet's funds
+    pub new_public_key: Pubkey,
+    /// Signatures of the sweep transactions, in the order they were sent
+    pub signatures: Vec<Signature>,
+    /// Lamports moved to the new key besides token balances
+    pub swept_lamports: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::{NATIVE_MINT, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+    use chrono::Duration;
+
+    #[test]
+    fn test_thresholds() {
+        let now = Utc::now();
+        let policy = KeyRotationPolicy {
+            max_signatures: Some(100),
+            max_age_days: Some(30),
+        };
+        let mut record = KeyRecord::new(now - Duration::days(29));
+        record.signatures = 99;
+        assert!(policy.check(&record, now).is_none());
+
+        record.signatures = 100;
+        assert_eq!(
+            policy.check(&record, now),
+            Some(RotationReason::Signatures {
+                count: 100,
+                max: 100
+            })
+        );
+
+        record.signatures = 0;
+        record.created_at = now - Duration::days(30);
+        assert_eq!(
+            policy.check(&record, now),
+            Some(RotationReason::Age {
+                days: 30,
+                max_days: 30
+            })
+        );
+
+        // Without thresholds a key never needs rotating
+        let disabled = KeyRotationPolicy::default();
+        assert!(!disabled.is_enabled());
+        record.signatures = u64::MAX;
+        assert!(disabled.check(&record, now).is_none());
+    }
+
+    #[test]
+    fn test_sweep_moves_tokens_then_sol() -> Result<()> {
+        let old = Pubkey::new_unique();
+        let new = Pubkey::new_unique();
+        let token = |mint: Pubkey, balance: u64, program_id: Pubkey| SweepToken {
+            account: TokenAccountInfo::new(
+                mint,
+                get_associated_token_address_with_program_id(&old, &mint, &program_id),
+                old,
+                balance,
+                program_id,
+            ),
+            decimals: 6,
+        };
+        let usdc = token(Pubkey::new_unique(), 2_500_000, TOKEN_PROGRAM_ID);
+        let pyusd = token(Pubkey::new_unique(), 7, TOKEN_2022_PROGRAM_ID);
+        let empty = token(Pubkey::new_unique(), 0, TOKEN_PROGRAM_ID);
+        let mut wsol = token(NATIVE_MINT, 1_000_000, TOKEN_PROGRAM_ID);
+        wsol.account.is_native = true;
+        let extra: Vec<SweepToken> = (0..2)
+            .map(|_| token(Pubkey::new_unique(), 1, TOKEN_PROGRAM_ID))
+            .collect();
+        let mut tokens = vec![usdc.clone(), pyusd, empty, wsol];
+        tokens.extend(extra);
+
+        let plan = plan_sweep(&old, &new, 10_000_000, 5_000, 2_039_280, &tokens)?;
+        // Six accounts fill two transactions; four of them need a new account
+        assert_eq!(plan.transactions.len(), 2);
+        assert_eq!(plan.sol_lamports, 10_000_000 - 2 * 5_000 - 4 * 2_039_280);
+
+        let first = &plan.transactions[0];
+        let usdc_destination = get_associated_token_address_with_program_id(
+            &new,
+            &usdc.account.mint,
+            &TOKEN_PROGRAM_ID,
+        );
+        assert_eq!(
+            first[0],
+            create_associated_token_account_idempotent(
+                &old,
+                &new,
+                &usdc.account.mint,
+                &TOKEN_PROGRAM_ID
+            )
+        );
+        assert_eq!(
+            first[1],
+            transfer_checked(
+                &TOKEN_PROGRAM_ID,
+                &usdc.account.address,
+                &usdc.account.mint,
+                &usdc_destination,
+                &old,
+                &[],
+                2_500_000,
+                6,
+            )
+            .map_err(|e| Error::token(e.to_string()))?
+        );
+        assert_eq!(
+            first[2],
+            close_account(&TOKEN_PROGRAM_ID, &usdc.account.address, &new, &old, &[])
+                .map_err(|e| Error::token(e.to_string()))?
+        );
+        // Token-2022 moves under its own program; empty and wrapped SOL
+        // accounts are only closed
+        assert_eq!(first[3].program_id, TOKEN_2022_PROGRAM_ID);
+        assert_eq!(first.len(), 3 + 3 + 1 + 1);
+
+        // SOL goes last
+        let last = plan.transactions[1].last();
+        assert_eq!(
+            last,
+            Some(&system_instruction::transfer(&old, &new, plan.sol_lamports))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_needs_funds_for_fees_and_rent() -> Result<()> {
+        let old = Pubkey::new_unique();
+        let new = Pubkey::new_unique();
+
+        // A SOL-only wallet sweeps in one transaction
+        let plan = plan_sweep(&old, &new, 1_000_000, 5_000, 2_039_280, &[])?;
+        assert_eq!(plan.transactions.len(), 1);
+        assert_eq!(plan.sol_lamports, 995_000);
+
+        // An emptied wallet has nothing left to sweep
+        let plan = plan_sweep(&old, &new, 0, 5_000, 2_039_280, &[])?;
+        assert!(plan.transactions.is_empty());
+
+        let mint = Pubkey::new_unique();
+        let tokens = [SweepToken {
+            account: TokenAccountInfo::new(mint, Pubkey::new_unique(), old, 5, TOKEN_PROGRAM_ID),
+            decimals: 0,
+        }];
+        assert!(matches!(
+            plan_sweep(&old, &new, 1_000_000, 5_000, 2_039_280, &tokens),
+            Err(Error::InsufficientFunds {
+                required: 2_044_280,
+                available: 1_000_000
+            })
+        ));
+        Ok(())
+    }
+}
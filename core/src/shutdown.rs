@@ -0,0 +1,124 @@
+//! Cooperative shutdown of long-lived components
+//!
+//! Components that run in the background, such as the RPC health prober,
+//! the websocket [`SubscriptionClient`](crate::rpc::SubscriptionClient) and
+//! the [`MetricsServer`](crate::metrics::MetricsServer), accept a shared
+//! [`CancellationToken`] and stop once it is cancelled. Work that must not
+//! be cut short, like waiting for a transaction that was already sent, runs
+//! under a [`ShutdownSignal`], which gives it a bounded drain period after
+//! cancellation instead of abandoning it outright.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use agent_wallet_core::shutdown::{CancellationToken, ShutdownSignal};
+//!
+//! # async fn example() {
+//! let token = CancellationToken::new();
+//! let signal = ShutdownSignal::new(token.clone(), Duration::from_millis(10));
+//! token.cancel();
+//!
+//! // Work that outlasts the drain period is cut short
+//! let waited = signal.drain(tokio::time::sleep(Duration::from_secs(60))).await;
+//! assert!(waited.is_none());
+//! # }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+pub use tokio_util::sync::CancellationToken;
+
+use crate::error::{Error, Result};
+
+/// How long in-flight work may run on after shutdown begins, by default
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Cancellation token with the time work may take to wind down after it
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    token: CancellationToken,
+    drain: Duration,
+}
+
+impl ShutdownSignal {
+    /// Signal cancelled through `token`, draining work for up to `drain`
+    pub fn new(token: CancellationToken, drain: Duration) -> Self {
+        Self { token, drain }
+    }
+
+    /// Token that starts the shutdown
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Time work may run on once the token is cancelled
+    pub fn drain_timeout(&self) -> Duration {
+        self.drain
+    }
+
+    /// Whether the shutdown has begun
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Run `work` to completion, or until the drain period after cancellation ends
+    ///
+    /// Returns `None` if the work was cut short.
+    pub async fn drain<F: Future>(&self, work: F) -> Option<F::Output> {
+        tokio::pin!(work);
+        tokio::select! {
+            output = &mut work => return Some(output),
+            () = self.token.cancelled() => {}
+        }
+        tokio::time::timeout(self.drain, work).await.ok()
+    }
+}
+
+/// Wait up to `timeout` for a cancelled task to finish, aborting it if it does not
+pub(crate) async fn stop_task(task: JoinHandle<()>, timeout: Duration, name: &str) -> Result<()> {
+    let abort = task.abort_handle();
+    match tokio::time::timeout(timeout, task).await {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            abort.abort();
+            Err(Error::Timeout(format!(
+                "{} did not stop within {:?}",
+                name, timeout
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_finishes_work_within_the_timeout() {
+        let token = CancellationToken::new();
+        let signal = ShutdownSignal::new(token.clone(), Duration::from_secs(5));
+
+        // Not cancelled: the work runs to completion
+        assert_eq!(signal.drain(async { 1 }).await, Some(1));
+
+        // Cancelled mid-way: the work still gets to finish
+        let (drained, ()) = tokio::join!(
+            signal.drain(async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                2
+            }),
+            async { token.cancel() }
+        );
+        assert_eq!(drained, Some(2));
+        assert!(signal.is_shutting_down());
+
+        let short = ShutdownSignal::new(token, Duration::from_millis(10));
+        let cut = short
+            .drain(tokio::time::sleep(Duration::from_secs(60)))
+            .await;
+        assert_eq!(cut, None);
+    }
+}
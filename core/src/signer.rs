@@ -0,0 +1,346 @@
+//! Transaction signers, local and remote
+//!
+//! Signing goes through the [`TransactionSigner`] trait so the private key
+//! does not have to live in the wallet process. A [`SecureKeypair`] signs
+//! in memory; a [`RemoteSigner`] hands the message bytes to an external
+//! signing service (an HSM front-end, a hardware wallet bridge) and checks
+//! the returned signature against the expected public key before a
+//! transaction carrying it is accepted.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use agent_wallet_core::signer::{DynTransactionSigner, RemoteSigner, RemoteSignerConfig};
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(pubkey: Pubkey) -> agent_wallet_core::Result<()> {
+//! let config = RemoteSignerConfig::new("https://signer.internal/sign", pubkey)
+//!     .with_auth_token("secret")
+//!     .with_timeout(Duration::from_secs(5));
+//! let signer: Arc<dyn DynTransactionSigner> = Arc::new(RemoteSigner::new(config)?);
+//!
+//! let signature = signer.sign_message(b"hello").await?;
+//! assert!(signature.verify(pubkey.as_ref(), b"hello"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::Transaction};
+
+use crate::error::{Error, Result};
+use crate::keypair::SecureKeypair;
+use crate::multisig;
+
+/// Default time allowed for a remote signing request
+pub const DEFAULT_REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that can sign transaction messages for one public key
+pub trait TransactionSigner: Send + Sync {
+    /// Public key the signatures verify against
+    fn pubkey(&self) -> impl Future<Output = Pubkey> + Send;
+
+    /// Sign the serialized message bytes
+    fn sign_message(&self, message: &[u8]) -> impl Future<Output = Result<Signature>> + Send;
+}
+
+/// Object-safe form of [`TransactionSigner`], implemented for every signer
+pub trait DynTransactionSigner: Send + Sync {
+    /// Public key the signatures verify against
+    fn pubkey(&self) -> BoxFuture<'_, Pubkey>;
+
+    /// Sign the serialized message bytes
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, Result<Signature>>;
+}
+
+impl<S: TransactionSigner> DynTransactionSigner for S {
+    fn pubkey(&self) -> BoxFuture<'_, Pubkey> {
+        Box::pin(TransactionSigner::pubkey(self))
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, Result<Signature>> {
+        Box::pin(TransactionSigner::sign_message(self, message))
+    }
+}
+
+impl TransactionSigner for SecureKeypair {
+    fn pubkey(&self) -> impl Future<Output = Pubkey> + Send {
+        std::future::ready(self.public_key())
+    }
+
+    fn sign_message(&self, message: &[u8]) -> impl Future<Output = Result<Signature>> + Send {
+        std::future::ready(Ok(self.sign(message)))
+    }
+}
+
+/// Where and how to reach a remote signing service
+#[derive(Clone)]
+pub struct RemoteSignerConfig {
+    /// Endpoint the signing requests are POSTed to
+    pub url: String,
+    /// Public key the service signs for
+    pub pubkey: Pubkey,
+    /// Bearer token sent in the `Authorization` header
+    pub auth_token: Option<String>,
+    /// Time allowed for one signing request
+    pub timeout: Duration,
+}
+
+impl RemoteSignerConfig {
+    /// Config for a service at `url` signing for `pubkey`
+    pub fn new(url: impl Into<String>, pubkey: Pubkey) -> Self {
+        Self {
+            url: url.into(),
+            pubkey,
+            auth_token: None,
+            timeout: DEFAULT_REMOTE_SIGNER_TIMEOUT,
+        }
+    }
+
+    /// Authenticate requests with a bearer token
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Set the time allowed for one signing request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Body of a signing request
+#[derive(Debug, Serialize, Deserialize)]
+struct SignRequest {
+    /// Base58 public key expected to sign
+    pubkey: String,
+    /// Base64 message bytes
+    message: String,
+}
+
+/// Body of a signing response
+#[derive(Debug, Serialize, Deserialize)]
+struct SignResponse {
+    /// Base58 signature
+    signature: String,
+}
+
+/// Signer that delegates to an external signing service over HTTP
+///
+/// The service receives `{"pubkey": <base58>, "message": <base64>}` and
+/// answers `{"signature": <base58>}`. Signatures that do not verify
+/// against the configured public key are rejected with
+/// [`Error::SignerMismatch`].
+pub struct RemoteSigner {
+    config: RemoteSignerConfig,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    /// Create a signer for the configured service
+    pub fn new(config: RemoteSignerConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| Error::config(format!("Failed to build signer client: {}", e)))?;
+        Ok(Self { config, client })
+    }
+
+    /// The signer's configuration
+    pub fn config(&self) -> &RemoteSignerConfig {
+        &self.config
+    }
+
+    async fn request_signature(&self, message: &[u8]) -> Result<Signature> {
+        let body = SignRequest {
+            pubkey: self.config.pubkey.to_string(),
+            message: STANDARD.encode(message),
+        };
+        let mut request = self.client.post(&self.config.url).json(&body);
+        if let Some(token) = &self.config.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await.map_err(request_error)?;
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response.text().await.unwrap_or_default();
+            return Err(Error::Network(format!(
+                "Signing service returned {}: {}",
+                status,
+                reason.trim()
+            )));
+        }
+        let response: SignResponse = response.json().await.map_err(request_error)?;
+
+        let signature: Signature = response.signature.parse()?;
+        if !signature.verify(self.config.pubkey.as_ref(), message) {
+            return Err(Error::SignerMismatch {
+                expected: self.config.pubkey,
+            });
+        }
+        Ok(signature)
+    }
+}
+
+impl TransactionSigner for RemoteSigner {
+    fn pubkey(&self) -> impl Future<Output = Pubkey> + Send {
+        std::future::ready(self.config.pubkey)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> impl Future<Output = Result<Signature>> + Send {
+        self.request_signature(message)
+    }
+}
+
+fn request_error(error: reqwest::Error) -> Error {
+    if error.is_timeout() {
+        Error::Timeout(format!("Signing service did not answer: {}", error))
+    } else {
+        Error::Network(format!("Signing request failed: {}", error))
+    }
+}
+
+/// Add the signer's signature to a transaction, keeping its blockhash
+///
+/// Other signers' signatures are left in place, so this works for both
+/// single-signer and partially signed transactions.
+pub async fn sign_transaction(
+    transaction: &mut Transaction,
+    signer: &dyn DynTransactionSigner,
+) -> Result<Signature> {
+    let pubkey = signer.pubkey().await;
+    let index = multisig::signer_index(transaction, &pubkey)?;
+    if transaction.signatures.len() != transaction.message.header.num_required_signatures as usize {
+        transaction.signatures =
+            vec![Signature::default(); transaction.message.header.num_required_signatures as usize];
+    }
+
+    let message = transaction.message_data();
+    let signature = signer.sign_message(&message).await?;
+    if !signature.verify(pubkey.as_ref(), &message) {
+        return Err(Error::SignerMismatch { expected: pubkey });
+    }
+    transaction.signatures[index] = signature;
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{hash::Hash, system_instruction};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    /// Mock signing service that signs with `keypair`
+    async fn signing_service(keypair: SecureKeypair) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/sign"))
+            .and(header("authorization", "Bearer token"))
+            .respond_with(move |request: &Request| {
+                let body: SignRequest = match serde_json::from_slice(&request.body) {
+                    Ok(body) => body,
+                    Err(_) => return ResponseTemplate::new(400),
+                };
+                match STANDARD.decode(body.message) {
+                    Ok(message) => ResponseTemplate::new(200).set_body_json(SignResponse {
+                        signature: keypair.sign(&message).to_string(),
+                    }),
+                    Err(_) => ResponseTemplate::new(400),
+                }
+            })
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn transfer(from: &Pubkey) -> Transaction {
+        let mut transaction = Transaction::new_with_payer(
+            &[system_instruction::transfer(from, &Pubkey::new_unique(), 1)],
+            Some(from),
+        );
+        transaction.message.recent_blockhash = Hash::new_unique();
+        transaction
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_signs_transaction() -> Result<()> {
+        let keypair = SecureKeypair::generate();
+        let pubkey = keypair.public_key();
+        let server = signing_service(keypair).await;
+        let signer = RemoteSigner::new(
+            RemoteSignerConfig::new(format!("{}/sign", server.uri()), pubkey)
+                .with_auth_token("token"),
+        )?;
+
+        let mut transaction = transfer(&pubkey);
+        let signature = sign_transaction(&mut transaction, &signer).await?;
+        assert_eq!(transaction.signatures, vec![signature]);
+        assert!(transaction.verify().is_ok());
+
+        // Without the token the service refuses
+        let anonymous = RemoteSigner::new(RemoteSignerConfig::new(
+            format!("{}/sign", server.uri()),
+            pubkey,
+        ))?;
+        assert!(matches!(
+            anonymous.sign_message(b"hello").await,
+            Err(Error::Network(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_rejects_signature_for_other_key() -> Result<()> {
+        let expected = SecureKeypair::generate().public_key();
+        let server = signing_service(SecureKeypair::generate()).await;
+        let signer = RemoteSigner::new(
+            RemoteSignerConfig::new(format!("{}/sign", server.uri()), expected)
+                .with_auth_token("token"),
+        )?;
+
+        let mut transaction = transfer(&expected);
+        let result = sign_transaction(&mut transaction, &signer).await;
+        assert!(matches!(result, Err(Error::SignerMismatch { expected: key }) if key == expected));
+        assert_eq!(transaction.signatures, vec![Signature::default()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_times_out() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+        let signer = RemoteSigner::new(
+            RemoteSignerConfig::new(server.uri(), Pubkey::new_unique())
+                .with_timeout(Duration::from_millis(100)),
+        )?;
+
+        let result = signer.sign_message(b"hello").await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_signer_matches_keypair() -> Result<()> {
+        let keypair = SecureKeypair::generate();
+        let mut transaction = transfer(&keypair.public_key());
+
+        let signature = sign_transaction(&mut transaction, &keypair).await?;
+        assert_eq!(signature, keypair.sign(&transaction.message_data()));
+        assert!(transaction.verify().is_ok());
+        Ok(())
+    }
+}
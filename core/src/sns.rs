@@ -0,0 +1,368 @@
+//! Solana Name Service recipients
+//!
+//! Recipients can be written as a base58 address or as a `.sol` domain
+//! (`alice.sol`, or `pay.alice.sol` for a subdomain). A domain is resolved
+//! to the owner of its name record: the record's address is derived from
+//! the hashed name under the `.sol` root, and its registry header carries
+//! the owner. The [`NameResolver`] caches resolutions for a while, so an
+//! agent paying the same domain every minute does not fetch the record
+//! every time; a domain that is not registered is never cached.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_core::rpc::{DynRpcProvider, RpcClient, RpcClientConfig};
+//! use agent_wallet_core::sns::NameResolver;
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! let rpc: Arc<dyn DynRpcProvider> = Arc::new(RpcClient::new(RpcClientConfig::default()).await?);
+//! let names = NameResolver::new(rpc);
+//! let owner = names.resolve_recipient("bonfida.sol").await?;
+//! println!("bonfida.sol -> {}", owner);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+use crate::sync::lock;
+
+/// SPL Name Service program
+pub const NAME_PROGRAM_ID: Pubkey = pubkey!("namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX");
+
+/// Name record of the `.sol` top-level domain, parent of every domain
+pub const SOL_TLD_AUTHORITY: Pubkey = pubkey!("58PwtjSDuFHuUkYjH9BYnnQKHfwo9reZhC2zMJv9JPkx");
+
+/// How long a resolved domain is trusted before it is fetched again
+pub const DEFAULT_NAME_TTL: Duration = Duration::from_secs(300);
+
+/// Prefix hashed in front of every name
+const HASH_PREFIX: &str = "SPL Name Service";
+
+/// Size of the registry header: parent, owner and class
+const HEADER_LEN: usize = 96;
+
+/// Recipient as a user writes it: an address or a `.sol` domain
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Recipient {
+    /// Base58 address
+    Address(Pubkey),
+    /// `.sol` domain, resolved to its owner when used
+    Domain(String),
+}
+
+impl Recipient {
+    /// The address, if no resolution is needed
+    pub fn address(&self) -> Option<Pubkey> {
+        match self {
+            Recipient::Address(address) => Some(*address),
+            Recipient::Domain(_) => None,
+        }
+    }
+
+    /// The domain, if this recipient is one
+    pub fn domain(&self) -> Option<&str> {
+        match self {
+            Recipient::Address(_) => None,
+            Recipient::Domain(domain) => Some(domain),
+        }
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if let Ok(address) = Pubkey::from_str(input) {
+            return Ok(Recipient::Address(address));
+        }
+        if input.ends_with(".sol") {
+            name_labels(input)?;
+            return Ok(Recipient::Domain(input.to_string()));
+        }
+        let hint = if !input.is_empty()
+            && input
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            format!("did you mean '{}.sol'?", input)
+        } else {
+            "expected a base58 address or a name ending in .sol".to_string()
+        };
+        Err(Error::InvalidAddress(format!(
+            "'{}' is not an address or a .sol domain; {}",
+            input, hint
+        )))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recipient::Address(address) => write!(f, "{}", address),
+            Recipient::Domain(domain) => f.write_str(domain),
+        }
+    }
+}
+
+impl From<Pubkey> for Recipient {
+    fn from(address: Pubkey) -> Self {
+        Recipient::Address(address)
+    }
+}
+
+impl From<&Pubkey> for Recipient {
+    fn from(address: &Pubkey) -> Self {
+        Recipient::Address(*address)
+    }
+}
+
+impl TryFrom<String> for Recipient {
+    type Error = Error;
+
+    fn try_from(input: String) -> Result<Self> {
+        input.parse()
+    }
+}
+
+impl From<Recipient> for String {
+    fn from(recipient: Recipient) -> Self {
+        recipient.to_string()
+    }
+}
+
+/// Labels of a `.sol` domain below the top level, outermost first
+fn name_labels(domain: &str) -> Result<Vec<&str>> {
+    let labels: Vec<&str> = domain
+        .strip_suffix(".sol")
+        .unwrap_or(domain)
+        .split('.')
+        .collect();
+    if labels.len() > 2 || labels.iter().any(|label| label.is_empty()) {
+        return Err(Error::InvalidAddress(format!(
+            "'{}' is not a valid .sol domain; expected name.sol or sub.name.sol",
+            domain
+        )));
+    }
+    Ok(labels.into_iter().rev().collect())
+}
+
+/// Address of the name record of `name` under `parent`
+fn name_account(name: &str, parent: &Pubkey) -> Pubkey {
+    let hashed = Sha256::digest(format!("{}{}", HASH_PREFIX, name).as_bytes());
+    let class = Pubkey::default();
+    Pubkey::find_program_address(
+        &[hashed.as_slice(), class.as_ref(), parent.as_ref()],
+        &NAME_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Address of the name record of a `.sol` domain or subdomain
+pub fn domain_account(domain: &str) -> Result<Pubkey> {
+    let labels = name_labels(domain)?;
+    let mut account = SOL_TLD_AUTHORITY;
+    for (depth, label) in labels.into_iter().enumerate() {
+        account = if depth == 0 {
+            name_account(label, &account)
+        } else {
+            // Subdomain names carry a leading zero byte
+            name_account(&format!("\0{}", label), &account)
+        };
+    }
+    Ok(account)
+}
+
+/// Resolves `.sol` domains to their owners, caching results for a TTL
+pub struct NameResolver {
+    rpc: Arc<dyn DynRpcProvider>,
+    ttl: Duration,
+    cache: StdMutex<HashMap<String, (Pubkey, Instant)>>,
+}
+
+impl NameResolver {
+    /// Create a resolver querying `rpc`
+    pub fn new(rpc: Arc<dyn DynRpcProvider>) -> Self {
+        Self {
+            rpc,
+            ttl: DEFAULT_NAME_TTL,
+            cache: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keep resolutions for `ttl` instead of [`DEFAULT_NAME_TTL`]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Address of a base58 address or `.sol` domain
+    ///
+    /// Anything else fails with [`Error::InvalidAddress`] and a suggestion.
+    pub async fn resolve_recipient(&self, input: &str) -> Result<Pubkey> {
+        self.resolve(&input.parse()?).await
+    }
+
+    /// Address of a recipient, resolving domains to their owners
+    pub async fn resolve(&self, recipient: &Recipient) -> Result<Pubkey> {
+        self.resolve_at(recipient, Instant::now()).await
+    }
+
+    /// [`NameResolver::resolve`] as of `now`
+    pub async fn resolve_at(&self, recipient: &Recipient, now: Instant) -> Result<Pubkey> {
+        let domain = match recipient {
+            Recipient::Address(address) => return Ok(*address),
+            Recipient::Domain(domain) => domain,
+        };
+        if let Some((owner, resolved_at)) = lock(&self.cache).get(domain) {
+            if now.saturating_duration_since(*resolved_at) < self.ttl {
+                return Ok(*owner);
+            }
+        }
+
+        let account = self
+            .rpc
+            .get_account_optional(&domain_account(domain)?)
+            .await?;
+        // A record that was never registered or was released has no owner
+        let owner = account
+            .filter(|account| account.owner == NAME_PROGRAM_ID && account.data.len() >= HEADER_LEN)
+            .and_then(|account| Pubkey::try_from(&account.data[32..64]).ok())
+            .filter(|owner| *owner != Pubkey::default());
+        let Some(owner) = owner else {
+            lock(&self.cache).remove(domain);
+            return Err(Error::InvalidAddress(format!(
+                "{} is not a registered .sol domain",
+                domain
+            )));
+        };
+
+        lock(&self.cache).insert(domain.clone(), (owner, now));
+        Ok(owner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockRpc;
+    use solana_sdk::account::Account;
+
+    /// Registry state of a name record owned by `owner`
+    fn name_record(owner: &Pubkey) -> Account {
+        let mut data = vec![0u8; HEADER_LEN + 32];
+        data[..32].copy_from_slice(SOL_TLD_AUTHORITY.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner: NAME_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_recipient_parsing() -> Result<()> {
+        let address = Pubkey::new_unique();
+        assert_eq!(
+            address.to_string().parse::<Recipient>()?,
+            Recipient::Address(address)
+        );
+        assert_eq!(
+            " pay.alice.sol ".parse::<Recipient>()?,
+            Recipient::Domain("pay.alice.sol".to_string())
+        );
+
+        let Err(Error::InvalidAddress(message)) = "alice".parse::<Recipient>() else {
+            panic!("a bare name is not a recipient");
+        };
+        assert!(message.contains("alice.sol"));
+        assert!(matches!(
+            "a.b.c.sol".parse::<Recipient>(),
+            Err(Error::InvalidAddress(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_known_domain_account() -> Result<()> {
+        // Name record of bonfida.sol on mainnet
+        assert_eq!(
+            domain_account("bonfida.sol")?,
+            pubkey!("Crf8hzfthWGbGbLTVCiqRqV5MVnbpHB1L9KQMd6gsinb")
+        );
+        assert_ne!(
+            domain_account("dex.bonfida.sol")?,
+            domain_account("bonfida.sol")?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolution_is_cached_until_ttl() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let names = NameResolver::new(rpc.clone()).with_ttl(Duration::from_secs(60));
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let account = domain_account("alice.sol")?;
+        rpc.set_account(account, name_record(&first));
+
+        let alice = Recipient::Domain("alice.sol".to_string());
+        let start = Instant::now();
+        assert_eq!(names.resolve_at(&alice, start).await?, first);
+
+        // The domain changed hands, but the cached owner is still fresh
+        rpc.set_account(account, name_record(&second));
+        assert_eq!(
+            names
+                .resolve_at(&alice, start + Duration::from_secs(30))
+                .await?,
+            first
+        );
+        assert_eq!(
+            names
+                .resolve_at(&alice, start + Duration::from_secs(61))
+                .await?,
+            second
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_domain_is_invalid_address() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let names = NameResolver::new(rpc.clone()).with_ttl(Duration::from_secs(60));
+
+        let Err(Error::InvalidAddress(message)) = names.resolve_recipient("nobody.sol").await
+        else {
+            panic!("an unregistered domain must not resolve");
+        };
+        assert!(message.contains("nobody.sol"));
+
+        // A stale cache entry for a domain that lapsed is not served
+        let account = domain_account("lapsed.sol")?;
+        rpc.set_account(account, name_record(&Pubkey::new_unique()));
+        let lapsed = Recipient::Domain("lapsed.sol".to_string());
+        let start = Instant::now();
+        names.resolve_at(&lapsed, start).await?;
+        rpc.set_account(account, name_record(&Pubkey::default()));
+        assert!(matches!(
+            names.resolve_at(&lapsed, start + Duration::from_secs(61)).await,
+            Err(Error::InvalidAddress(message)) if message.contains("lapsed.sol")
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+//! Lamport, SOL and token amount conversions
+//!
+//! Every conversion between SOL and lamports goes through [`Lamports`] so
+//! that the amount checked against balances and budgets is the amount placed
+//! into the instruction. [`TokenAmount`] does the same for SPL token amounts,
+//! which carry their mint's decimals.
+//!
+//! # Rounding
+//!
+//! `f64` SOL amounts are converted with [`Lamports::from_sol_f64_rounded`],
+//! which rounds to the nearest lamport, halves away from zero. Negative,
+//! non-finite and out-of-range inputs are rejected rather than clamped.
+//! Decimal strings are parsed exactly with [`Lamports::from_sol_str`] and
+//! reject digits below one lamport.
+//!
+//! # Arithmetic
+//!
+//! Sums and differences of amounts use `try_add` and `try_sub`, which fail
+//! with [`Error::InvalidAmount`] instead of wrapping or saturating, so an
+//! overflowing total can never slip under a limit.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::sol::Lamports;
+//!
+//! # fn main() -> agent_wallet_core::Result<()> {
+//! let amount = Lamports::from_sol_str("0.1")?;
+//! assert_eq!(amount, Lamports::from_sol_f64_rounded(0.1)?);
+//! assert_eq!(amount.as_u64(), 100_000_000);
+//! assert_eq!(amount.to_sol_display(), "0.1");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Lamports in one SOL
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// Decimal places of SOL
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Most decimals an amount can have; `10^38` still fits in a `u128`
+const MAX_DECIMALS: u8 = 38;
+
+/// An amount of lamports
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Lamports(pub u64);
+
+impl Lamports {
+    /// Zero lamports
+    pub const ZERO: Lamports = Lamports(0);
+
+    /// Wrap a lamport amount
+    pub const fn new(lamports: u64) -> Self {
+        Self(lamports)
+    }
+
+    /// Amount in lamports
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Parse a decimal SOL amount such as `"1.25"` without float math
+    pub fn from_sol_str(sol: &str) -> Result<Self> {
+        parse_decimal(sol, SOL_DECIMALS, "SOL").map(Self)
+    }
+
+    /// Convert an `f64` SOL amount, rounding to the nearest lamport
+    pub fn from_sol_f64_rounded(sol: f64) -> Result<Self> {
+        if !sol.is_finite() || sol < 0.0 {
+            return Err(Error::InvalidAmount(format!(
+                "SOL amount must be a non-negative number, got {}",
+                sol
+            )));
+        }
+
+        let lamports = (sol * LAMPORTS_PER_SOL as f64).round();
+        if lamports >= u64::MAX as f64 {
+            return Err(Error::InvalidAmount(format!(
+                "SOL amount {} is too large",
+                sol
+            )));
+        }
+        Ok(Self(lamports as u64))
+    }
+
+    /// Amount in SOL as `f64`, for display and estimates only
+    pub fn to_sol_f64(self) -> f64 {
+        self.0 as f64 / LAMPORTS_PER_SOL as f64
+    }
+
+    /// Exact decimal SOL representation without trailing zeros
+    pub fn to_sol_display(self) -> String {
+        format_decimal(self.0, SOL_DECIMALS)
+    }
+
+    /// Add, returning `None` on overflow
+    pub fn checked_add(self, other: Lamports) -> Option<Lamports> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Subtract, returning `None` if `other` is larger
+    pub fn checked_sub(self, other: Lamports) -> Option<Lamports> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Add, failing with `Error::InvalidAmount` on overflow
+    pub fn try_add(self, other: Lamports) -> Result<Lamports> {
+        self.checked_add(other)
+            .ok_or_else(|| Error::InvalidAmount(format!("{} plus {} overflows", self, other)))
+    }
+
+    /// Subtract, failing with `Error::InvalidAmount` if `other` is larger
+    pub fn try_sub(self, other: Lamports) -> Result<Lamports> {
+        self.checked_sub(other)
+            .ok_or_else(|| Error::InvalidAmount(format!("Cannot subtract {} from {}", other, self)))
+    }
+
+    /// Subtract, stopping at zero
+    pub fn saturating_sub(self, other: Lamports) -> Lamports {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
+impl FromStr for Lamports {
+    type Err = Error;
+
+    /// Parse a decimal SOL amount, see [`Lamports::from_sol_str`]
+    fn from_str(sol: &str) -> Result<Self> {
+        Self::from_sol_str(sol)
+    }
+}
+
+impl From<u64> for Lamports {
+    fn from(lamports: u64) -> Self {
+        Self(lamports)
+    }
+}
+
+impl From<Lamports> for u64 {
+    fn from(lamports: Lamports) -> Self {
+        lamports.0
+    }
+}
+
+impl fmt::Display for Lamports {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} SOL", self.to_sol_display())
+    }
+}
+
+/// An amount of an SPL token in base units, with its mint's decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TokenAmount {
+    /// Amount in base units
+    pub raw: u64,
+    /// Decimals of the mint
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    /// Wrap an amount in base units
+    pub const fn new(raw: u64, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    /// Parse a decimal amount such as `"12.5"` for a mint with `decimals`
+    pub fn from_ui_str(amount: &str, decimals: u8) -> Result<Self> {
+        parse_decimal(amount, decimals, "token").map(|raw| Self { raw, decimals })
+    }
+
+    /// Exact decimal representation without trailing zeros
+    pub fn to_ui_string(self) -> String {
+        format_decimal(self.raw, self.decimals)
+    }
+
+    /// Amount in whole tokens as `f64`, for display and estimates only
+    pub fn to_ui_f64(self) -> f64 {
+        self.raw as f64 / 10f64.powi(i32::from(self.decimals))
+    }
+
+    /// Add, failing on overflow or if the decimals differ
+    pub fn try_add(self, other: TokenAmount) -> Result<TokenAmount> {
+        self.check_decimals(other)?;
+        self.raw
+            .checked_add(other.raw)
+            .map(|raw| Self { raw, ..self })
+            .ok_or_else(|| Error::InvalidAmount(format!("{} plus {} overflows", self, other)))
+    }
+
+    /// Subtract, failing if `other` is larger or the decimals differ
+    pub fn try_sub(self, other: TokenAmount) -> Result<TokenAmount> {
+        self.check_decimals(other)?;
+        self.raw
+            .checked_sub(other.raw)
+            .map(|raw| Self { raw, ..self })
+            .ok_or_else(|| Error::InvalidAmount(format!("Cannot subtract {} from {}", other, self)))
+    }
+
+    fn check_decimals(self, other: TokenAmount) -> Result<()> {
+        if self.decimals != other.decimals {
+            return Err(Error::InvalidAmount(format!(
+                "Cannot combine amounts with {} and {} decimals",
+                self.decimals, other.decimals
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_ui_string())
+    }
+}
+
+/// Parse a non-negative decimal string into base units with `decimals` decimals
+fn parse_decimal(amount: &str, decimals: u8, unit: &str) -> Result<u64> {
+    let invalid = || Error::InvalidAmount(format!("Invalid {} amount: '{}'", unit, amount));
+    let too_large = || Error::InvalidAmount(format!("{} amount '{}' is too large", unit, amount));
+    if decimals > MAX_DECIMALS {
+        return Err(Error::InvalidAmount(format!(
+            "Amounts with {} decimals are not supported",
+            decimals
+        )));
+    }
+
+    let trimmed = amount.trim();
+    let (whole, fraction) = trimmed.split_once('.').unwrap_or((trimmed, ""));
+    if (whole.is_empty() && fraction.is_empty())
+        || !whole.bytes().all(|b| b.is_ascii_digit())
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fraction.len() > usize::from(decimals) {
+        return Err(Error::InvalidAmount(format!(
+            "{} amount '{}' has more than {} decimals",
+            unit, amount, decimals
+        )));
+    }
+
+    // Only digits are left, so parsing can only fail on overflow
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| too_large())?
+    };
+    let fraction: u128 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = usize::from(decimals))
+            .parse()
+            .map_err(|_| invalid())?
+    };
+
+    whole
+        .checked_mul(10u128.pow(u32::from(decimals)))
+        .and_then(|raw| raw.checked_add(fraction))
+        .and_then(|raw| u64::try_from(raw).ok())
+        .ok_or_else(too_large)
+}
+
+/// Format base units with `decimals` decimals, without trailing zeros
+fn format_decimal(raw: u64, decimals: u8) -> String {
+    let decimals = usize::from(decimals);
+    let digits = format!("{:0>width$}", raw, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() -> Result<()> {
+        assert_eq!(Lamports::from_sol_str("1")?, Lamports(LAMPORTS_PER_SOL));
+        assert_eq!(Lamports::from_sol_str("0.000000001")?, Lamports(1));
+        assert_eq!(Lamports::from_sol_str(".5")?, Lamports(500_000_000));
+        assert_eq!(
+            Lamports::from_sol_str("2.")?,
+            Lamports(2 * LAMPORTS_PER_SOL)
+        );
+        for invalid in ["", ".", "-1", "1.0000000001", "1e3", "1.2.3", "18446744074"] {
+            assert!(Lamports::from_sol_str(invalid).is_err(), "{}", invalid);
+        }
+
+        assert_eq!(Lamports(1_500_000_000).to_sol_display(), "1.5");
+        assert_eq!(Lamports(1).to_sol_display(), "0.000000001");
+        assert_eq!(Lamports(3 * LAMPORTS_PER_SOL).to_string(), "3 SOL");
+        for lamports in [0, 1, 999_999_999, 1_000_000_001, u64::MAX] {
+            let display = Lamports(lamports).to_sol_display();
+            assert_eq!(Lamports::from_sol_str(&display)?, Lamports(lamports));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_f64_rounding_rule() -> Result<()> {
+        // Nearest lamport
+        assert_eq!(Lamports::from_sol_f64_rounded(0.1)?, Lamports(100_000_000));
+        assert_eq!(Lamports::from_sol_f64_rounded(0.3)?, Lamports(300_000_000));
+        assert_eq!(
+            Lamports::from_sol_f64_rounded(1.0000000004)?,
+            Lamports(1_000_000_000)
+        );
+        assert_eq!(
+            Lamports::from_sol_f64_rounded(1.0000000006)?,
+            Lamports(1_000_000_001)
+        );
+        assert_eq!(
+            Lamports::from_sol_f64_rounded(0.1 + 0.2)?,
+            Lamports::from_sol_str("0.3")?
+        );
+
+        for invalid in [-0.1, f64::NAN, f64::INFINITY, 1e12] {
+            assert!(Lamports::from_sol_f64_rounded(invalid).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_arithmetic() -> Result<()> {
+        assert_eq!(Lamports(2).checked_add(Lamports(3)), Some(Lamports(5)));
+        assert_eq!(Lamports(u64::MAX).checked_add(Lamports(1)), None);
+        assert_eq!(Lamports(3).checked_sub(Lamports(2)), Some(Lamports(1)));
+        assert_eq!(Lamports(2).checked_sub(Lamports(3)), None);
+
+        assert_eq!(Lamports(2).try_add(Lamports(3))?, Lamports(5));
+        assert!(matches!(
+            Lamports(u64::MAX).try_add(Lamports(1)),
+            Err(Error::InvalidAmount(_))
+        ));
+        assert!(Lamports(2).try_sub(Lamports(3)).is_err());
+        assert_eq!(Lamports(2).saturating_sub(Lamports(3)), Lamports::ZERO);
+        assert_eq!("1.5".parse::<Lamports>()?, Lamports(1_500_000_000));
+
+        let usdc = TokenAmount::from_ui_str("12.5", 6)?;
+        assert_eq!(usdc, TokenAmount::new(12_500_000, 6));
+        assert_eq!(usdc.to_string(), "12.5");
+        assert_eq!(usdc.try_add(TokenAmount::new(1, 6))?.raw, 12_500_001);
+        assert!(usdc.try_add(TokenAmount::new(1, 9)).is_err());
+        assert!(usdc.try_sub(TokenAmount::new(12_500_001, 6)).is_err());
+        assert!(TokenAmount::from_ui_str("0.0000001", 6).is_err());
+        assert_eq!(TokenAmount::from_ui_str("42", 0)?.to_string(), "42");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_format_round_trip_over_full_range() -> Result<()> {
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let edges = [
+            0,
+            1,
+            9,
+            10,
+            LAMPORTS_PER_SOL - 1,
+            LAMPORTS_PER_SOL,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+
+        for i in 0..200_000u64 {
+            // xorshift64 over all of u64, with the edges mixed in
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let raw = if i < edges.len() as u64 {
+                edges[i as usize]
+            } else {
+                state
+            };
+
+            let lamports = Lamports(raw);
+            assert_eq!(lamports.to_sol_display().parse::<Lamports>()?, lamports);
+
+            let decimals = (state % u64::from(MAX_DECIMALS + 1)) as u8;
+            let token = TokenAmount::new(raw, decimals);
+            assert_eq!(
+                TokenAmount::from_ui_str(&token.to_ui_string(), decimals)?,
+                token
+            );
+        }
+
+        // One past the range is rejected rather than wrapped
+        assert!(Lamports::from_sol_str("18446744073.709551616").is_err());
+        assert!(TokenAmount::from_ui_str("18446744073709551616", 0).is_err());
+        Ok(())
+    }
+}
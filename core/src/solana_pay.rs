@@ -0,0 +1,380 @@
+//! Solana Pay transfer requests
+//!
+//! Merchants hand out `solana:` URIs naming a recipient, an amount, an
+//! optional SPL token and reference keys:
+//!
+//! ```text
+//! solana:<recipient>?amount=<amount>&spl-token=<mint>&reference=<key>&label=<label>&message=<message>&memo=<memo>
+//! ```
+//!
+//! [`TransferRequest::parse`] validates such a URI and [`Wallet::pay`](crate::Wallet::pay)
+//! executes it. The reference keys are added to the transfer instruction as
+//! read-only accounts, so the merchant finds the payment with
+//! `getSignaturesForAddress` on the reference. Transaction requests
+//! (`solana:https://…`) are not supported.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::solana_pay::TransferRequest;
+//!
+//! # fn example() -> agent_wallet_core::Result<()> {
+//! let request = TransferRequest::parse(
+//!     "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?amount=1&label=Michael",
+//! )?;
+//! assert_eq!(request.label.as_deref(), Some("Michael"));
+//! assert_eq!(request.lamports()?.as_u64(), 1_000_000_000);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::{Error, Result};
+use crate::sol::{Lamports, TokenAmount};
+
+/// URI scheme of Solana Pay requests
+pub const SCHEME: &str = "solana";
+
+/// A Solana Pay transfer request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferRequest {
+    /// Wallet receiving the payment; for tokens, the owner of the token account
+    pub recipient: Pubkey,
+    /// Decimal amount in SOL or whole tokens, `None` if the payer chooses
+    pub amount: Option<String>,
+    /// Mint of the token to pay in, `None` for SOL
+    pub spl_token: Option<Pubkey>,
+    /// Keys added to the transfer so the merchant can find it
+    pub references: Vec<Pubkey>,
+    /// Who the request is from
+    pub label: Option<String>,
+    /// What the payment is for
+    pub message: Option<String>,
+    /// Memo written on chain with the transfer
+    pub memo: Option<String>,
+}
+
+impl TransferRequest {
+    /// Request for `amount` SOL to `recipient`
+    pub fn sol(recipient: Pubkey, amount: Lamports) -> Self {
+        Self {
+            amount: Some(amount.to_sol_display()),
+            ..Self::for_recipient(recipient)
+        }
+    }
+
+    /// Request for `amount` of the token `mint` to `recipient`
+    pub fn token(recipient: Pubkey, mint: Pubkey, amount: TokenAmount) -> Self {
+        Self {
+            amount: Some(amount.to_ui_string()),
+            spl_token: Some(mint),
+            ..Self::for_recipient(recipient)
+        }
+    }
+
+    fn for_recipient(recipient: Pubkey) -> Self {
+        Self {
+            recipient,
+            amount: None,
+            spl_token: None,
+            references: Vec::new(),
+            label: None,
+            message: None,
+            memo: None,
+        }
+    }
+
+    /// Parse a transfer request URI, ignoring query parameters it does not know
+    pub fn parse(uri: &str) -> Result<Self> {
+        Self::parse_with(uri, false)
+    }
+
+    /// Parse a transfer request URI, refusing query parameters it does not know
+    pub fn parse_strict(uri: &str) -> Result<Self> {
+        Self::parse_with(uri, true)
+    }
+
+    fn parse_with(uri: &str, strict: bool) -> Result<Self> {
+        let rest = uri
+            .split_once(':')
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(SCHEME))
+            .map(|(_, rest)| rest)
+            .ok_or_else(|| Error::validation(format!("Not a Solana Pay URI: '{}'", uri)))?;
+        let (recipient, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let recipient = percent_decode(recipient)?;
+        if recipient.starts_with("https:") {
+            return Err(Error::NotSupported(
+                "Solana Pay transaction requests are not supported".to_string(),
+            ));
+        }
+        let mut request = Self::for_recipient(recipient.parse().map_err(|_| {
+            Error::InvalidAddress(format!("Invalid payment recipient '{}'", recipient))
+        })?);
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = percent_decode(key)?;
+            let value = percent_decode(value)?;
+            match key.as_str() {
+                "amount" => set_once(&mut request.amount, &key, validate_amount(value)?)?,
+                "spl-token" => set_once(&mut request.spl_token, &key, parse_key(&key, &value)?)?,
+                "reference" => request.references.push(parse_key(&key, &value)?),
+                "label" => set_once(&mut request.label, &key, value)?,
+                "message" => set_once(&mut request.message, &key, value)?,
+                "memo" => set_once(&mut request.memo, &key, value)?,
+                _ if strict => {
+                    return Err(Error::validation(format!(
+                        "Unknown Solana Pay parameter '{}'",
+                        key
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        // SOL amounts can be checked for precision now; token amounts need the mint
+        if request.spl_token.is_none() && request.amount.is_some() {
+            request.lamports()?;
+        }
+        Ok(request)
+    }
+
+    /// The SOL amount requested
+    ///
+    /// Fails if the request is for a token, has no amount or has more
+    /// decimals than SOL.
+    pub fn lamports(&self) -> Result<Lamports> {
+        if let Some(mint) = &self.spl_token {
+            return Err(Error::validation(format!(
+                "The payment is in token {}, not SOL",
+                mint
+            )));
+        }
+        Lamports::from_sol_str(self.required_amount()?)
+    }
+
+    /// The token amount requested, for a mint with `decimals`
+    ///
+    /// Fails if the request has no amount or more decimals than the mint.
+    pub fn token_amount(&self, decimals: u8) -> Result<TokenAmount> {
+        TokenAmount::from_ui_str(self.required_amount()?, decimals)
+    }
+
+    fn required_amount(&self) -> Result<&str> {
+        self.amount
+            .as_deref()
+            .ok_or_else(|| Error::validation("The payment request has no amount"))
+    }
+
+    /// Render the request as a `solana:` URI
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!("{}:{}", SCHEME, self.recipient);
+        let mut params: Vec<(&str, String)> = Vec::new();
+        if let Some(amount) = &self.amount {
+            params.push(("amount", amount.clone()));
+        }
+        if let Some(mint) = &self.spl_token {
+            params.push(("spl-token", mint.to_string()));
+        }
+        for reference in &self.references {
+            params.push(("reference", reference.to_string()));
+        }
+        for (key, value) in [
+            ("label", &self.label),
+            ("message", &self.message),
+            ("memo", &self.memo),
+        ] {
+            if let Some(value) = value {
+                params.push((key, value.clone()));
+            }
+        }
+        for (i, (key, value)) in params.iter().enumerate() {
+            uri.push(if i == 0 { '?' } else { '&' });
+            uri.push_str(key);
+            uri.push('=');
+            uri.push_str(&percent_encode(value));
+        }
+        uri
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, key: &str, value: T) -> Result<()> {
+    if slot.is_some() {
+        return Err(Error::validation(format!(
+            "Solana Pay parameter '{}' is given twice",
+            key
+        )));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn parse_key(key: &str, value: &str) -> Result<Pubkey> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidAddress(format!("Invalid Solana Pay {} '{}'", key, value)))
+}
+
+/// Check the amount grammar: digits with an optional fraction, above zero
+///
+/// A fraction needs a leading digit (`0.5`, not `.5`) and exponents are
+/// not allowed.
+fn validate_amount(amount: String) -> Result<String> {
+    let (whole, fraction) = match amount.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (amount.as_str(), None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !digits(whole) || !fraction.into_iter().all(digits) {
+        return Err(Error::InvalidAmount(format!(
+            "Invalid Solana Pay amount '{}'",
+            amount
+        )));
+    }
+    if amount.bytes().all(|b| b == b'0' || b == b'.') {
+        return Err(Error::InvalidAmount(
+            "Solana Pay amount must be greater than zero".to_string(),
+        ));
+    }
+    Ok(amount)
+}
+
+fn percent_decode(text: &str) -> Result<String> {
+    let invalid = || Error::validation(format!("Invalid percent-encoding in '{}'", text));
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .ok_or_else(invalid)?;
+                let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
+
+/// Percent-encode everything but unreserved characters, like `encodeURIComponent`
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            // Writing to a String cannot fail
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Examples from the Solana Pay specification
+    const SPEC_SOL: &str = "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?amount=1&label=Michael&message=Thanks%20for%20all%20the%20fish&memo=OrderId12345";
+    const SPEC_USDC: &str = "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?amount=0.01&spl-token=EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const SPEC_NO_AMOUNT: &str = "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?label=Michael";
+    const SPEC_RECIPIENT_ONLY: &str = "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN";
+
+    #[test]
+    fn test_spec_examples() -> Result<()> {
+        let request = TransferRequest::parse(SPEC_SOL)?;
+        assert_eq!(
+            request.recipient.to_string(),
+            "mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN"
+        );
+        assert_eq!(request.lamports()?, Lamports::from_sol_str("1")?);
+        assert_eq!(request.label.as_deref(), Some("Michael"));
+        assert_eq!(request.message.as_deref(), Some("Thanks for all the fish"));
+        assert_eq!(request.memo.as_deref(), Some("OrderId12345"));
+        assert_eq!(request.to_uri(), SPEC_SOL);
+
+        let request = TransferRequest::parse(SPEC_USDC)?;
+        assert_eq!(
+            request.spl_token.map(|mint| mint.to_string()).as_deref(),
+            Some("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+        );
+        assert_eq!(request.token_amount(6)?, TokenAmount::new(10_000, 6));
+        assert!(request.lamports().is_err());
+        assert_eq!(request.to_uri(), SPEC_USDC);
+
+        let request = TransferRequest::parse(SPEC_NO_AMOUNT)?;
+        assert_eq!(request.amount, None);
+        assert!(request.lamports().is_err());
+        assert_eq!(request.to_uri(), SPEC_NO_AMOUNT);
+
+        let request = TransferRequest::parse(SPEC_RECIPIENT_ONLY)?;
+        assert_eq!(request.to_uri(), SPEC_RECIPIENT_ONLY);
+        Ok(())
+    }
+
+    #[test]
+    fn test_references_round_trip() -> Result<()> {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut request = TransferRequest::sol(Pubkey::new_unique(), Lamports::new(1_500));
+        request.references = vec![first, second];
+        request.memo = Some("order #7 & co".to_string());
+
+        let uri = request.to_uri();
+        assert!(uri.contains("memo=order%20%237%20%26%20co"));
+        assert_eq!(TransferRequest::parse_strict(&uri)?, request);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_validation() -> Result<()> {
+        let recipient = "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN";
+        for amount in ["-1", "0", "0.0", ".5", "1.", "1e3", "1,5", "0x10"] {
+            let uri = format!("{}?amount={}", recipient, amount);
+            assert!(TransferRequest::parse(&uri).is_err(), "{}", amount);
+        }
+        // More decimals than SOL, or than the mint
+        let uri = format!("{}?amount=0.0000000001", recipient);
+        assert!(TransferRequest::parse(&uri).is_err());
+        let request = TransferRequest::parse(&format!(
+            "{}?amount=0.0001&spl-token=EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            recipient
+        ))?;
+        assert!(request.token_amount(4).is_ok());
+        assert!(request.token_amount(3).is_err());
+
+        let unknown = format!("{}?amount=1&tip=5", recipient);
+        assert!(TransferRequest::parse(&unknown).is_ok());
+        assert!(TransferRequest::parse_strict(&unknown).is_err());
+
+        for invalid in [
+            "bitcoin:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN",
+            "solana:not-a-key",
+            "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?amount=1&amount=2",
+            "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?reference=nope",
+            "solana:mvines9iiHiQTysrwkJjGf2gb9Ex9jXJX8ns3qwf2kN?label=%E2%28",
+        ] {
+            assert!(TransferRequest::parse(invalid).is_err(), "{}", invalid);
+        }
+        assert!(matches!(
+            TransferRequest::parse("solana:https%3A%2F%2Fexample.com%2Fpay"),
+            Err(Error::NotSupported(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+//! Native SOL staking through the stake program
+//!
+//! Staking creates a stake account at an address derived from the wallet
+//! key and a seed index (`stake:<index>`), so no extra keypair has to be
+//! stored, and delegates it to a validator vote account in the same
+//! transaction. Unstaking takes two transactions an epoch apart: the first
+//! deactivates the stake, the second withdraws it once the cooldown is over.
+//! Withdrawing earlier fails with [`Error::StakeCooldown`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::{Lamports, Wallet};
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(wallet: &Wallet, validator: Pubkey) -> agent_wallet_core::Result<()> {
+//! // Delegate 2 SOL; the stake account's rent reserve comes on top
+//! let stake_account = wallet
+//!     .stake_sol(&validator, Lamports::from_sol_str("2")?)
+//!     .await?;
+//!
+//! // Deactivates now; called again after the epoch ends it withdraws
+//! wallet.unstake_sol(&stake_account).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use solana_client::rpc_response::{RpcStakeActivation, StakeActivationState};
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    stake::{
+        self, instruction as stake_instruction,
+        state::{Authorized, Lockup, StakeStateV2},
+    },
+};
+
+use crate::error::{Error, Result};
+use crate::rpc::DynRpcProvider;
+
+/// Prefix of the seeds stake account addresses are derived with
+pub const STAKE_SEED_PREFIX: &str = "stake:";
+
+/// Seed indices searched for the wallet's stake accounts
+pub const MAX_STAKE_ACCOUNTS: u32 = 32;
+
+/// Seed of the stake account with the given index
+pub fn stake_seed(index: u32) -> String {
+    format!("{}{}", STAKE_SEED_PREFIX, index)
+}
+
+/// Address of the wallet's stake account with the given seed index
+pub fn stake_account_address(wallet: &Pubkey, index: u32) -> Result<Pubkey> {
+    Pubkey::create_with_seed(wallet, &stake_seed(index), &stake::program::id())
+        .map_err(|e| Error::validation(format!("Invalid stake account seed: {}", e)))
+}
+
+/// Rent-exempt reserve of a stake account, added on top of the delegated amount
+pub fn stake_account_rent() -> u64 {
+    Rent::default().minimum_balance(StakeStateV2::size_of())
+}
+
+/// Instructions creating stake account `index` and delegating `lamports` to `vote_account`
+///
+/// The wallet funds the account, pays its rent reserve and becomes both
+/// the stake and the withdraw authority.
+pub fn stake_instructions(
+    wallet: &Pubkey,
+    vote_account: &Pubkey,
+    index: u32,
+    lamports: u64,
+) -> Result<Vec<Instruction>> {
+    if lamports == 0 {
+        return Err(Error::InvalidAmount(
+            "Stake amount must be greater than zero".to_string(),
+        ));
+    }
+    let funding = lamports
+        .checked_add(stake_account_rent())
+        .ok_or_else(|| Error::InvalidAmount("Stake amount overflows".to_string()))?;
+    Ok(
+        stake_instruction::create_account_with_seed_and_delegate_stake(
+            wallet,
+            &stake_account_address(wallet, index)?,
+            wallet,
+            &stake_seed(index),
+            vote_account,
+            &Authorized::auto(wallet),
+            &Lockup::default(),
+            funding,
+        ),
+    )
+}
+
+/// Instructions for the next unstake step of a stake account
+///
+/// Active or activating stake is deactivated; inactive stake has `lamports`
+/// withdrawn to the wallet. Stake still cooling down cannot be withdrawn
+/// and fails with [`Error::StakeCooldown`].
+pub fn unstake_instructions(
+    wallet: &Pubkey,
+    stake_account: &Pubkey,
+    lamports: u64,
+    activation: &RpcStakeActivation,
+) -> Result<Vec<Instruction>> {
+    match activation.state {
+        StakeActivationState::Activating | StakeActivationState::Active => {
+            Ok(vec![stake_instruction::deactivate_stake(
+                stake_account,
+                wallet,
+            )])
+        }
+        StakeActivationState::Deactivating => Err(Error::StakeCooldown {
+            stake_account: *stake_account,
+            active: activation.active,
+        }),
+        StakeActivationState::Inactive => {
+            if lamports == 0 {
+                return Err(Error::InvalidAmount(
+                    "Withdraw amount must be greater than zero".to_string(),
+                ));
+            }
+            Ok(vec![stake_instruction::withdraw(
+                stake_account,
+                wallet,
+                wallet,
+                lamports,
+                None,
+            )])
+        }
+    }
+}
+
+/// Vote account a stake account is delegated to, if any
+pub fn delegated_vote_account(account: &Account) -> Option<Pubkey> {
+    if account.owner != stake::program::id() {
+        return None;
+    }
+    let state: StakeStateV2 = bincode::deserialize(&account.data).ok()?;
+    state.delegation().map(|delegation| delegation.voter_pubkey)
+}
+
+/// The wallet's existing stake accounts with their seed indices
+pub async fn stake_accounts(
+    provider: &dyn DynRpcProvider,
+    wallet: &Pubkey,
+) -> Result<Vec<(u32, Pubkey, Account)>> {
+    let addresses = (0..MAX_STAKE_ACCOUNTS)
+        .map(|index| stake_account_address(wallet, index))
+        .collect::<Result<Vec<_>>>()?;
+    let accounts = provider.get_multiple_accounts(&addresses).await?;
+    Ok((0..MAX_STAKE_ACCOUNTS)
+        .zip(addresses)
+        .zip(accounts)
+        .filter_map(|((index, address), account)| account.map(|a| (index, address, a)))
+        .collect())
+}
+
+/// First seed index without a stake account
+pub async fn next_stake_index(provider: &dyn DynRpcProvider, wallet: &Pubkey) -> Result<u32> {
+    let used: Vec<u32> = stake_accounts(provider, wallet)
+        .await?
+        .into_iter()
+        .map(|(index, _, _)| index)
+        .collect();
+    (0..MAX_STAKE_ACCOUNTS)
+        .find(|index| !used.contains(index))
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "All {} stake account seeds are in use; withdraw some stake first",
+                MAX_STAKE_ACCOUNTS
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::stake::instruction::StakeInstruction;
+    use solana_sdk::system_instruction::SystemInstruction;
+    use solana_sdk::system_program;
+
+    fn activation(state: StakeActivationState, active: u64) -> RpcStakeActivation {
+        RpcStakeActivation {
+            state,
+            active,
+            inactive: 0,
+        }
+    }
+
+    #[test]
+    fn test_stake_instructions_create_and_delegate() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let validator = Pubkey::new_unique();
+        let instructions = stake_instructions(&wallet, &validator, 3, 2_000_000_000)?;
+        let stake_account = stake_account_address(&wallet, 3)?;
+        assert_ne!(stake_account, stake_account_address(&wallet, 4)?);
+
+        // Create with seed, initialize, delegate
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].program_id, system_program::id());
+        match bincode::deserialize(&instructions[0].data)? {
+            SystemInstruction::CreateAccountWithSeed {
+                base,
+                seed,
+                lamports,
+                space,
+                owner,
+            } => {
+                assert_eq!(base, wallet);
+                assert_eq!(seed, "stake:3");
+                assert_eq!(lamports, 2_000_000_000 + stake_account_rent());
+                assert_eq!(space, StakeStateV2::size_of() as u64);
+                assert_eq!(owner, stake::program::id());
+            }
+            other => return Err(Error::validation(format!("Unexpected {:?}", other))),
+        }
+        let delegate = &instructions[2];
+        assert_eq!(delegate.program_id, stake::program::id());
+        assert!(matches!(
+            bincode::deserialize(&delegate.data)?,
+            StakeInstruction::DelegateStake
+        ));
+        assert_eq!(delegate.accounts[0].pubkey, stake_account);
+        assert_eq!(delegate.accounts[1].pubkey, validator);
+
+        assert!(stake_instructions(&wallet, &validator, 0, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unstake_follows_activation_state() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let stake_account = stake_account_address(&wallet, 0)?;
+
+        let deactivate = unstake_instructions(
+            &wallet,
+            &stake_account,
+            1_000,
+            &activation(StakeActivationState::Active, 1_000),
+        )?;
+        assert!(matches!(
+            bincode::deserialize(&deactivate[0].data)?,
+            StakeInstruction::Deactivate
+        ));
+
+        // Withdrawing during the cooldown epoch is refused before signing
+        let cooling = unstake_instructions(
+            &wallet,
+            &stake_account,
+            1_000,
+            &activation(StakeActivationState::Deactivating, 800),
+        );
+        assert!(matches!(
+            cooling,
+            Err(Error::StakeCooldown { stake_account: account, active: 800 })
+                if account == stake_account
+        ));
+
+        let withdraw = unstake_instructions(
+            &wallet,
+            &stake_account,
+            1_000,
+            &activation(StakeActivationState::Inactive, 0),
+        )?;
+        assert!(matches!(
+            bincode::deserialize(&withdraw[0].data)?,
+            StakeInstruction::Withdraw(1_000)
+        ));
+        assert_eq!(withdraw[0].accounts[1].pubkey, wallet);
+        Ok(())
+    }
+}
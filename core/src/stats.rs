@@ -0,0 +1,484 @@
+//! Per-agent execution statistics
+//!
+//! An [`AgentStats`] accumulates what an agent's decisions did: how many
+//! actions of each kind ran or failed, the lamports they moved and the fees
+//! they paid, in total and for the current UTC day, plus the last few
+//! outcomes with their signatures. The success rate is taken over a rolling
+//! window of recent outcomes, so an agent that recovers is not held back by
+//! failures from long ago and a string of new failures shows at once.
+//!
+//! A compact [`StatsSummary`] travels in the
+//! [`AgentContext`](crate::types::AgentContext), letting strategies and LLM
+//! prompts condition on their own recent performance.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::stats::AgentStats;
+//! use agent_wallet_core::types::AgentAction;
+//! use chrono::Utc;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! let mut stats = AgentStats::new();
+//! let action = AgentAction::TransferSol {
+//!     to: Pubkey::new_unique(),
+//!     amount: Lamports::new(1_000_000),
+//!     memo: None,
+//! };
+//! stats.record_failed(&action, "Insufficient funds", Utc::now());
+//!
+//! let today = stats.today_for("transfer_sol", Utc::now());
+//! println!("{} failed transfers today, success rate {:.2}", today.failed, stats.success_rate());
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Signature;
+
+use crate::transaction::ActionReceipt;
+use crate::types::AgentAction;
+
+/// Outcomes the rolling success rate is taken over
+pub const DEFAULT_SUCCESS_WINDOW: usize = 20;
+
+/// Outcomes kept in [`AgentStats::recent`]
+pub const DEFAULT_RECENT_OUTCOMES: usize = 20;
+
+/// Success rate over the last few outcomes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollingSuccessRate {
+    /// Number of outcomes considered
+    window: usize,
+    /// Most recent outcomes, oldest first; `true` for success
+    outcomes: VecDeque<bool>,
+}
+
+impl Default for RollingSuccessRate {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUCCESS_WINDOW)
+    }
+}
+
+impl RollingSuccessRate {
+    /// Rate over the last `window` outcomes, at least one
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            outcomes: VecDeque::new(),
+        }
+    }
+
+    /// Record an outcome, forgetting the oldest one beyond the window
+    pub fn record(&mut self, success: bool) {
+        if self.outcomes.len() >= self.window {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(success);
+    }
+
+    /// Share of successes in the window (0-1), 1 before any outcome
+    pub fn rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 1.0;
+        }
+        let successes = self.outcomes.iter().filter(|success| **success).count();
+        successes as f64 / self.outcomes.len() as f64
+    }
+
+    /// Number of outcomes in the window
+    pub fn len(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    /// Whether no outcome was recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.is_empty()
+    }
+}
+
+/// Counters of one kind of action
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionStats {
+    /// Actions sent successfully
+    pub executed: u64,
+    /// Actions that failed
+    pub failed: u64,
+    /// Value counted against the spending budget, in lamports
+    pub lamports_spent: u64,
+    /// Transaction fees paid, in lamports
+    pub fee_lamports: u64,
+}
+
+impl ActionStats {
+    fn record_executed(&mut self, lamports_spent: u64, fee_lamports: u64) {
+        self.executed += 1;
+        self.lamports_spent = self.lamports_spent.saturating_add(lamports_spent);
+        self.fee_lamports = self.fee_lamports.saturating_add(fee_lamports);
+    }
+
+    fn record_failed(&mut self) {
+        self.failed += 1;
+    }
+}
+
+/// One recent decision outcome
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentOutcome {
+    /// When the outcome was recorded
+    pub at: DateTime<Utc>,
+    /// Kind of action, as returned by [`AgentAction::kind`]
+    pub action: String,
+    /// Signature of the sent transaction, if it was sent
+    pub signature: Option<Signature>,
+    /// Value counted against the spending budget, in lamports
+    pub lamports_spent: u64,
+    /// Transaction fee paid, in lamports
+    pub fee_lamports: u64,
+    /// Why the action failed, if it did
+    pub error: Option<String>,
+}
+
+impl RecentOutcome {
+    /// Whether the action was executed
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Counters of the actions of one UTC day
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayStats {
+    /// The day counted, `None` before the first action
+    pub date: Option<NaiveDate>,
+    /// Counters by kind of action
+    pub by_action: BTreeMap<String, ActionStats>,
+}
+
+/// Aggregated outcomes of an agent's decisions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentStats {
+    /// Decisions recorded, including those to do nothing
+    pub decisions: u64,
+    /// Decisions to do nothing
+    pub no_action: u64,
+    /// Counters over all actions
+    pub totals: ActionStats,
+    /// Counters by kind of action
+    pub by_action: BTreeMap<String, ActionStats>,
+    /// Counters of the current UTC day
+    pub today: DayStats,
+    /// Last outcomes of actions, oldest first
+    pub recent: VecDeque<RecentOutcome>,
+    /// Number of outcomes kept in `recent`
+    pub recent_capacity: usize,
+    /// Success rate over the latest outcomes
+    pub success: RollingSuccessRate,
+}
+
+impl Default for AgentStats {
+    fn default() -> Self {
+        Self {
+            decisions: 0,
+            no_action: 0,
+            totals: ActionStats::default(),
+            by_action: BTreeMap::new(),
+            today: DayStats::default(),
+            recent: VecDeque::new(),
+            recent_capacity: DEFAULT_RECENT_OUTCOMES,
+            success: RollingSuccessRate::default(),
+        }
+    }
+}
+
+impl AgentStats {
+    /// Empty statistics with the default windows
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `capacity` recent outcomes and take the success rate over `window`
+    pub fn with_windows(mut self, capacity: usize, window: usize) -> Self {
+        self.recent_capacity = capacity;
+        self.success = RollingSuccessRate::new(window);
+        self
+    }
+
+    /// Record a decision to do nothing
+    pub fn record_no_action(&mut self) {
+        self.decisions += 1;
+        self.no_action += 1;
+    }
+
+    /// Record an action that was sent, with what it cost
+    pub fn record_executed(
+        &mut self,
+        action: &AgentAction,
+        receipt: &ActionReceipt,
+        now: DateTime<Utc>,
+    ) {
+        let (spent, fee) = (receipt.transfer_lamports, receipt.fee_lamports);
+        self.decisions += 1;
+        self.totals.record_executed(spent, fee);
+        self.by_action
+            .entry(action.kind().to_string())
+            .or_default()
+            .record_executed(spent, fee);
+        self.day(now)
+            .entry(action.kind().to_string())
+            .or_default()
+            .record_executed(spent, fee);
+        self.success.record(true);
+        self.push_recent(RecentOutcome {
+            at: now,
+            action: action.kind().to_string(),
+            signature: Some(receipt.signature),
+            lamports_spent: spent,
+            fee_lamports: fee,
+            error: None,
+        });
+    }
+
+    /// Record an action that failed
+    pub fn record_failed(&mut self, action: &AgentAction, reason: &str, now: DateTime<Utc>) {
+        self.decisions += 1;
+        self.totals.record_failed();
+        self.by_action
+            .entry(action.kind().to_string())
+            .or_default()
+            .record_failed();
+        self.day(now)
+            .entry(action.kind().to_string())
+            .or_default()
+            .record_failed();
+        self.success.record(false);
+        self.push_recent(RecentOutcome {
+            at: now,
+            action: action.kind().to_string(),
+            signature: None,
+            lamports_spent: 0,
+            fee_lamports: 0,
+            error: Some(reason.to_string()),
+        });
+    }
+
+    /// Success rate over the latest outcomes (0-1)
+    pub fn success_rate(&self) -> f64 {
+        self.success.rate()
+    }
+
+    /// Counters of one kind of action on the UTC day of `now`
+    pub fn today_for(&self, kind: &str, now: DateTime<Utc>) -> ActionStats {
+        if self.today.date != Some(now.date_naive()) {
+            return ActionStats::default();
+        }
+        self.today.by_action.get(kind).copied().unwrap_or_default()
+    }
+
+    /// Compact view for the agent's context
+    pub fn summary(&self, now: DateTime<Utc>) -> StatsSummary {
+        let actions_today = if self.today.date == Some(now.date_naive()) {
+            self.today
+                .by_action
+                .iter()
+                .map(|(kind, stats)| (kind.clone(), stats.executed))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+        StatsSummary {
+            decisions: self.decisions,
+            executed: self.totals.executed,
+            failed: self.totals.failed,
+            success_rate: self.success_rate(),
+            lamports_spent: self.totals.lamports_spent,
+            fee_lamports: self.totals.fee_lamports,
+            actions_today,
+            last_error: self
+                .recent
+                .iter()
+                .rev()
+                .find_map(|outcome| outcome.error.clone()),
+        }
+    }
+
+    /// Counters of the day of `now`, starting a new day when it changed
+    fn day(&mut self, now: DateTime<Utc>) -> &mut BTreeMap<String, ActionStats> {
+        let date = now.date_naive();
+        if self.today.date != Some(date) {
+            self.today = DayStats {
+                date: Some(date),
+                by_action: BTreeMap::new(),
+            };
+        }
+        &mut self.today.by_action
+    }
+
+    fn push_recent(&mut self, outcome: RecentOutcome) {
+        while self.recent.len() >= self.recent_capacity.max(1) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(outcome);
+    }
+}
+
+/// Summary of an agent's own performance, handed to its decisions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsSummary {
+    /// Decisions made, including those to do nothing
+    pub decisions: u64,
+    /// Actions sent successfully
+    pub executed: u64,
+    /// Actions that failed
+    pub failed: u64,
+    /// Success rate over the latest outcomes (0-1)
+    pub success_rate: f64,
+    /// Value of all executed actions, in lamports
+    pub lamports_spent: u64,
+    /// Fees of all executed actions, in lamports
+    pub fee_lamports: u64,
+    /// Actions executed today by kind
+    pub actions_today: BTreeMap<String, u64>,
+    /// Most recent failure, if any
+    pub last_error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, Result};
+    use crate::sol::Lamports;
+    use chrono::TimeZone;
+    use solana_sdk::pubkey::Pubkey;
+
+    fn at(day: u32, hour: u32) -> Result<DateTime<Utc>> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, 0, 0)
+            .single()
+            .ok_or_else(|| Error::validation("Invalid test date"))
+    }
+
+    fn receipt(transfer_lamports: u64, fee_lamports: u64) -> ActionReceipt {
+        ActionReceipt {
+            signature: Signature::new_unique(),
+            transfer_lamports,
+            fee_lamports,
+            rent_costs: Vec::new(),
+            simulated: None,
+        }
+    }
+
+    fn swap() -> AgentAction {
+        AgentAction::SwapTokens {
+            input_mint: Pubkey::new_unique(),
+            output_mint: Pubkey::new_unique(),
+            amount: 1_000,
+            min_output_amount: 900,
+        }
+    }
+
+    fn transfer() -> AgentAction {
+        AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(50_000),
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_outcomes_aggregate_by_kind_and_day() -> Result<()> {
+        let monday = at(2, 23)?;
+        let tuesday = at(3, 1)?;
+        let mut stats = AgentStats::new();
+
+        stats.record_executed(&swap(), &receipt(1_000_000, 5_000), monday);
+        stats.record_executed(&swap(), &receipt(2_000_000, 7_000), monday);
+        stats.record_failed(&swap(), "slippage exceeded", monday);
+        stats.record_no_action();
+        stats.record_executed(&transfer(), &receipt(50_000, 5_000), tuesday);
+        stats.record_executed(&swap(), &receipt(500_000, 6_000), tuesday);
+
+        assert_eq!(stats.decisions, 6);
+        assert_eq!(stats.no_action, 1);
+        assert_eq!(
+            stats.totals,
+            ActionStats {
+                executed: 4,
+                failed: 1,
+                lamports_spent: 3_550_000,
+                fee_lamports: 23_000,
+            }
+        );
+        assert_eq!(
+            stats.by_action["swap_tokens"],
+            ActionStats {
+                executed: 3,
+                failed: 1,
+                lamports_spent: 3_500_000,
+                fee_lamports: 18_000,
+            }
+        );
+
+        // The day rolled over at midnight UTC
+        let swaps_today = stats.today_for("swap_tokens", tuesday);
+        assert_eq!((swaps_today.executed, swaps_today.fee_lamports), (1, 6_000));
+        assert_eq!(
+            stats.today_for("swap_tokens", monday),
+            ActionStats::default()
+        );
+
+        let summary = stats.summary(tuesday);
+        assert_eq!(summary.decisions, 6);
+        assert_eq!(summary.actions_today.get("transfer_sol"), Some(&1));
+        assert_eq!(summary.last_error.as_deref(), Some("slippage exceeded"));
+        assert!((summary.success_rate - 0.8).abs() < 1e-9);
+        assert!(stats
+            .recent
+            .iter()
+            .filter(|o| o.is_success())
+            .all(|o| o.signature.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_success_rate_rolls_over_recent_outcomes() {
+        let now = Utc::now();
+        let mut stats = AgentStats::new().with_windows(3, 4);
+        assert_eq!(stats.success_rate(), 1.0);
+
+        for _ in 0..10 {
+            stats.record_failed(&transfer(), "rpc timeout", now);
+        }
+        assert_eq!(stats.success_rate(), 0.0);
+
+        // Recovery shows as soon as the failures leave the window
+        for _ in 0..3 {
+            stats.record_executed(&transfer(), &receipt(1, 1), now);
+        }
+        assert!((stats.success_rate() - 0.75).abs() < 1e-9);
+        stats.record_executed(&transfer(), &receipt(1, 1), now);
+        assert_eq!(stats.success_rate(), 1.0);
+
+        // Only the last three outcomes are kept
+        assert_eq!(stats.recent.len(), 3);
+        assert!(stats.recent.iter().all(RecentOutcome::is_success));
+        assert_eq!(stats.totals.failed, 10);
+    }
+
+    #[test]
+    fn test_stats_round_trip_through_json() -> Result<()> {
+        let mut stats = AgentStats::new();
+        stats.record_executed(&swap(), &receipt(10, 5_000), Utc::now());
+        stats.record_failed(&transfer(), "denied", Utc::now());
+
+        let restored: AgentStats = serde_json::from_str(&serde_json::to_string(&stats)?)?;
+        assert_eq!(restored, stats);
+
+        // Entries written before stats existed load as empty stats
+        let empty: AgentStats = serde_json::from_str("{}")?;
+        assert_eq!(empty, AgentStats::new());
+        Ok(())
+    }
+}
@@ -13,27 +13,43 @@
 //!   "salt": "base64_encoded_salt",
 //!   "algorithm": "aes-256-gcm",
 //!   "kdf_iterations": 100000,
+//!   "kdf": { "name": "argon2id", "m_cost": 19456, "t_cost": 2, "p_cost": 1 },
 //!   "metadata": {
 //!     "created_at": "2024-01-01T00:00:00Z",
 //!     "last_accessed": "2024-01-01T00:00:00Z",
 //!     "public_key": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
-//!     "name": "my-agent-wallet"
+//!     "name": "my-agent-wallet",
+//!     "revision": 3
 //!   }
 //! }
 //! ```
+//!
+//! # Concurrent Access
+//!
+//! Several processes may open the same wallet directory. Every
+//! read-modify-write of a wallet file holds an exclusive advisory lock on
+//! `<name>.lock` next to it, and files are always replaced by renaming a
+//! complete temporary file, so readers never see a partial write. Each write
+//! bumps the wallet's `revision`; a service that loaded or saved a wallet
+//! refuses to overwrite it once another writer has moved the revision on,
+//! failing with [`Error::StorageConflict`] instead.
 
 use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
 use zeroize::{Zeroize, Zeroizing};
 
 use crate::config::StorageSettings;
 use crate::encryption::{EncryptedData, EncryptionAlgorithm};
 use crate::error::{Error, Result};
+use crate::rotation::KeyRecord;
 use crate::types::WalletInfo;
 
 /// Wallet storage data structure (on-disk format)
@@ -68,6 +84,12 @@ pub struct WalletMetadata {
     pub tags: Vec<String>,
     /// Custom metadata key-value pairs
     pub custom_data: HashMap<String, String>,
+    /// Number of times the wallet file was written, for conflict detection
+    #[serde(default)]
+    pub revision: u64,
+    /// Usage of the wallet key and the keys it replaced
+    #[serde(default)]
+    pub key: Option<KeyRecord>,
 }
 
 /// Wallet data that gets encrypted and stored
@@ -127,12 +149,52 @@ pub struct AgentConfig {
     pub last_decision: Option<DateTime<Utc>>,
 }
 
+/// A stored backup of a wallet file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// Wallet name
+    pub name: String,
+    /// Version identifier, the timestamp in the backup's file name
+    pub version: String,
+    /// When the backup was taken
+    pub timestamp: DateTime<Utc>,
+    /// Backup file path
+    pub path: PathBuf,
+    /// File size in bytes
+    pub size: u64,
+}
+
+/// Files removed when a wallet was purged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeReport {
+    /// Wallet files removed, including leftovers of interrupted writes
+    pub files_removed: usize,
+    /// Backups of the wallet removed
+    pub backups_removed: usize,
+}
+
+/// Format of the timestamp in backup file names
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S";
+
+/// Exclusive advisory lock on one wallet, released when dropped
+///
+/// The lock is held on a separate `.lock` file because writes replace the
+/// wallet file itself by renaming.
+struct WalletLock {
+    file: fs::File,
+}
+
+impl Drop for WalletLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
 /// Storage service for managing encrypted wallet files
 pub struct StorageService {
     /// Storage settings
     settings: StorageSettings,
-    /// In-memory cache of loaded wallet metadata
-    #[allow(dead_code)]
+    /// Metadata of the wallets this service loaded or saved, as last seen
     wallet_cache: HashMap<String, WalletMetadata>,
 }
 
@@ -155,6 +217,10 @@ impl StorageService {
     }
 
     /// Save a wallet to storage
+    ///
+    /// A wallet this service loaded or saved before is only overwritten if
+    /// its revision on disk is still the one seen then; otherwise this fails
+    /// with [`Error::StorageConflict`] and the file is left as it is.
     pub fn save_wallet(
         &mut self,
         name: &str,
@@ -162,7 +228,19 @@ impl StorageService {
         public_key: Pubkey,
         description: Option<&str>,
     ) -> Result<()> {
+        let _lock = self.lock_wallet(name)?;
         let now = Utc::now();
+        let (revision, key) = match self.read_storage(name) {
+            Ok(existing) => {
+                self.check_revision(name, existing.metadata.revision)?;
+                (
+                    existing.metadata.revision + 1,
+                    kept_key_record(&existing.metadata, &public_key, now),
+                )
+            }
+            Err(Error::WalletNotFound(_)) => (1, KeyRecord::new(now)),
+            Err(e) => return Err(e),
+        };
 
         // Create metadata
         let metadata = WalletMetadata {
@@ -175,6 +253,8 @@ impl StorageService {
             description: description.map(|s| s.to_string()),
             tags: Vec::new(),
             custom_data: HashMap::new(),
+            revision,
+            key: Some(key),
         };
 
         // Create wallet storage
@@ -184,20 +264,8 @@ impl StorageService {
             metadata,
         };
 
-        // Serialize to JSON
-        let json_data = serde_json::to_string_pretty(&wallet_storage)
-            .map_err(|e| Error::serialization(format!("Failed to serialize wallet: {}", e)))?;
-
-        // Determine file path
-        let file_path = self.wallet_file_path(name);
-
         // Write to file atomically
-        let temp_path = file_path.with_extension("tmp");
-        fs::write(&temp_path, &json_data)
-            .map_err(|e| Error::storage(format!("Failed to write wallet file: {}", e)))?;
-
-        fs::rename(&temp_path, &file_path)
-            .map_err(|e| Error::storage(format!("Failed to rename wallet file: {}", e)))?;
+        self.write_storage(name, &wallet_storage)?;
 
         // Create backup
         self.backup_wallet(name)?;
@@ -209,42 +277,170 @@ impl StorageService {
     }
 
     /// Load a wallet from storage
+    ///
+    /// Records the access in the file's `last_accessed` timestamp; use
+    /// [`StorageService::load_wallet_readonly`] for reads that must not write.
+    /// Touching the timestamp does not change the wallet's revision.
     pub fn load_wallet(&mut self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        let _lock = self.lock_wallet(name)?;
+        let mut wallet_storage = self.read_storage(name)?;
+
+        // Update last accessed timestamp
+        wallet_storage.metadata.last_accessed = Utc::now();
+        self.write_storage(name, &wallet_storage)?;
+
+        // Update cache
+        self.wallet_cache.insert(name.to_string(), wallet_storage.metadata.clone());
+
+        Ok((wallet_storage.encrypted_data, wallet_storage.metadata))
+    }
+
+    /// Load a wallet without writing to its file
+    ///
+    /// The loaded revision is remembered like with
+    /// [`StorageService::load_wallet`], so a later save still detects
+    /// concurrent changes.
+    pub fn load_wallet_readonly(&mut self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        let wallet_storage = self.read_storage(name)?;
+        self.wallet_cache.insert(name.to_string(), wallet_storage.metadata.clone());
+        Ok((wallet_storage.encrypted_data, wallet_storage.metadata))
+    }
+
+    /// Read a stored wallet without touching the file
+    pub fn read_wallet(&self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        let wallet_storage = self.read_storage(name)?;
+        Ok((wallet_storage.encrypted_data, wallet_storage.metadata))
+    }
+
+    /// Replace the encrypted data of a stored wallet, keeping its metadata
+    ///
+    /// The current file is backed up first and then replaced atomically.
+    /// Fails with [`Error::StorageConflict`] if the wallet changed on disk
+    /// since this service loaded or saved it.
+    pub fn replace_encrypted_data(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+    ) -> Result<()> {
+        let _lock = self.lock_wallet(name)?;
+        let mut wallet_storage = self.read_storage(name)?;
+        self.check_revision(name, wallet_storage.metadata.revision)?;
+        self.backup_wallet(name)?;
+
+        wallet_storage.encrypted_data = encrypted_data;
+        wallet_storage.metadata.last_modified = Utc::now();
+        wallet_storage.metadata.revision += 1;
+        self.write_storage(name, &wallet_storage)?;
+
+        self.wallet_cache.insert(name.to_string(), wallet_storage.metadata.clone());
+        Ok(())
+    }
+
+    /// Replace the encrypted data and metadata of a stored wallet in one write
+    pub fn replace_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        metadata: &WalletMetadata,
+    ) -> Result<()> {
+        let _lock = self.lock_wallet(name)?;
+        let mut wallet_storage = self.read_storage(name)?;
+        let revision = wallet_storage.metadata.revision;
+        self.check_revision(name, revision)?;
+        self.backup_wallet(name)?;
+
+        wallet_storage.encrypted_data = encrypted_data;
+        wallet_storage.metadata = metadata.clone();
+        wallet_storage.metadata.last_modified = Utc::now();
+        wallet_storage.metadata.revision = revision + 1;
+        self.write_storage(name, &wallet_storage)?;
+
+        self.wallet_cache.insert(name.to_string(), wallet_storage.metadata.clone());
+        Ok(())
+    }
+
+    /// Read and parse a wallet file
+    fn read_storage(&self, name: &str) -> Result<WalletStorage> {
         let file_path = self.wallet_file_path(name);
+        let json_data = fs::read_to_string(&file_path).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::WalletNotFound(name.to_string())
+            } else {
+                Error::storage(format!("Failed to read wallet file: {}", e))
+            }
+        })?;
+        serde_json::from_str(&json_data)
+            .map_err(|e| Error::serialization(format!("Failed to parse wallet file: {}", e)))
+    }
 
-        // Read file
-        let json_data = fs::read_to_string(&file_path)
-            .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    Error::WalletNotFound(name.to_string())
-                } else {
-                    Error::storage(format!("Failed to read wallet file: {}", e))
-                }
-            })?;
+    /// Serialize a wallet and replace its file atomically
+    fn write_storage(&self, name: &str, wallet_storage: &WalletStorage) -> Result<()> {
+        let json_data = serde_json::to_string_pretty(wallet_storage)
+            .map_err(|e| Error::serialization(format!("Failed to serialize wallet: {}", e)))?;
 
-        // Deserialize
-        let wallet_storage: WalletStorage = serde_json::from_str(&json_data)
-            .map_err(|e| Error::serialization(format!("Failed to parse wallet file: {}", e)))?;
+        let file_path = self.wallet_file_path(name);
+        let temp_path = file_path.with_extension("tmp");
+        fs::write(&temp_path, &json_data)
+            .map_err(|e| Error::storage(format!("Failed to write wallet file: {}", e)))?;
+        fs::rename(&temp_path, &file_path)
+            .map_err(|e| Error::storage(format!("Failed to rename wallet file: {}", e)))?;
+        Ok(())
+    }
 
-        // Update last accessed timestamp
-        let mut updated_storage = wallet_storage.clone();
-        updated_storage.metadata.last_accessed = Utc::now();
+    /// Take the exclusive lock guarding writes to a wallet
+    ///
+    /// Blocks until other processes holding the lock release it.
+    fn lock_wallet(&self, name: &str) -> Result<WalletLock> {
+        let lock_path = self.settings.path.join(format!("{}.lock", name));
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| Error::storage(format!("Failed to open wallet lock: {}", e)))?;
+        FileExt::lock_exclusive(&file)
+            .map_err(|e| Error::storage(format!("Failed to lock wallet: {}", e)))?;
+        Ok(WalletLock { file })
+    }
+
+    /// Fail if a wallet's revision on disk moved on since this service saw it
+    ///
+    /// Wallets this service never loaded or saved are not checked.
+    fn check_revision(&self, name: &str, on_disk: u64) -> Result<()> {
+        match self.wallet_cache.get(name) {
+            Some(seen) if seen.revision != on_disk => Err(Error::StorageConflict {
+                name: name.to_string(),
+                expected: seen.revision,
+                found: on_disk,
+            }),
+            _ => Ok(()),
+        }
+    }
 
-        // Save updated metadata
-        let updated_json = serde_json::to_string_pretty(&updated_storage)
-            .map_err(|e| Error::serialization(format!("Failed to serialize updated wallet: {}", e)))?;
+    /// Replace the metadata of a stored wallet, keeping its encrypted data
+    ///
+    /// Fails with [`Error::StorageConflict`] if the wallet changed on disk
+    /// since this service loaded or saved it.
+    pub fn update_metadata(&mut self, name: &str, metadata: &WalletMetadata) -> Result<()> {
+        let _lock = self.lock_wallet(name)?;
+        let mut wallet_storage = self.read_storage(name)?;
+        let revision = wallet_storage.metadata.revision;
+        self.check_revision(name, revision)?;
 
-        fs::write(&file_path, &updated_json)
-            .map_err(|e| Error::storage(format!("Failed to update wallet metadata: {}", e)))?;
+        wallet_storage.metadata = metadata.clone();
+        wallet_storage.metadata.last_modified = Utc::now();
+        wallet_storage.metadata.revision = revision + 1;
 
-        // Update cache
-        self.wallet_cache.insert(name.to_string(), updated_storage.metadata.clone());
+        // Write to file atomically
+        self.write_storage(name, &wallet_storage)?;
 
-        Ok((wallet_storage.encrypted_data, updated_storage.metadata))
+        self.wallet_cache.insert(name.to_string(), wallet_storage.metadata);
+
+        Ok(())
     }
 
     /// Delete a wallet from storage
     pub fn delete_wallet(&mut self, name: &str) -> Result<()> {
+        let _lock = self.lock_wallet(name)?;
         let file_path = self.wallet_file_path(name);
 
         // Check if file exists
@@ -262,13 +458,39 @@ impl StorageService {
         // Remove from cache
         self.wallet_cache.remove(name);
 
-        // Also delete backup if it exists
-        let backup_path = self.backup_file_path(name);
-        if backup_path.exists() {
-            let _ = fs::remove_file(&backup_path);
+        Ok(())
+    }
+
+    /// Delete a wallet and every backup of it, zeroing each file first
+    ///
+    /// Unlike [`StorageService::delete_wallet`], no backup is kept. The
+    /// overwrite is best effort: journaling or copy-on-write file systems
+    /// and SSDs may still hold the old blocks.
+    pub fn purge_wallet(&mut self, name: &str) -> Result<PurgeReport> {
+        let _lock = self.lock_wallet(name)?;
+        let file_path = self.wallet_file_path(name);
+        if !file_path.exists() {
+            return Err(Error::WalletNotFound(name.to_string()));
         }
+        let backups = self.list_backups(name)?;
 
-        Ok(())
+        let mut report = PurgeReport::default();
+        shred_file(&file_path)?;
+        report.files_removed += 1;
+        self.wallet_cache.remove(name);
+
+        // A write interrupted before its rename leaves the key behind too
+        let temp_path = file_path.with_extension("tmp");
+        if temp_path.exists() {
+            shred_file(&temp_path)?;
+            report.files_removed += 1;
+        }
+
+        for backup in &backups {
+            shred_file(&backup.path)?;
+            report.backups_removed += 1;
+        }
+        Ok(report)
     }
 
     /// List all wallets in storage
@@ -297,8 +519,10 @@ impl StorageService {
                                 last_accessed: metadata.last_accessed,
                                 balance_lamports: 0, // Will be populated by wallet
                                 transaction_count: 0, // Will be populated by wallet
+                                transaction_count_truncated: false,
                                 permission_level: crate::types::PermissionLevel::Basic,
                                 is_active: true,
+                                token_accounts: Vec::new(),
                             };
                             wallets.push(wallet_info);
                         }
@@ -315,6 +539,8 @@ impl StorageService {
     }
 
     /// Backup a wallet
+    ///
+    /// Older backups of the wallet beyond `max_versions` are deleted.
     pub fn backup_wallet(&self, name: &str) -> Result<()> {
         let source_path = self.wallet_file_path(name);
         let backup_path = self.backup_file_path(name);
@@ -327,34 +553,121 @@ impl StorageService {
         fs::copy(&source_path, &backup_path)
             .map_err(|e| Error::storage(format!("Failed to backup wallet: {}", e)))?;
 
-        Ok(())
+        self.prune_backups(name)
     }
 
-    /// Restore a wallet from backup
-    pub fn restore_wallet(&self, name: &str) -> Result<()> {
-        let backup_path = self.backup_file_path(name);
-        let target_path = self.wallet_file_path(name);
+    /// List the backups of a wallet, oldest first
+    pub fn list_backups(&self, name: &str) -> Result<Vec<BackupInfo>> {
+        let entries = fs::read_dir(&self.settings.backup_path)
+            .map_err(|e| Error::storage(format!("Failed to read backup directory: {}", e)))?;
+        let prefix = format!("{}_", name);
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| {
+                Error::storage(format!("Failed to read directory entry: {}", e))
+            })?;
 
-        if !backup_path.exists() {
-            return Err(Error::storage(format!("No backup found for wallet: {}", name)));
+            let path = entry.path();
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            // Backups of other wallets sharing the prefix fail to parse
+            let version = match path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|stem| stem.strip_prefix(&prefix))
+            {
+                Some(version) => version.to_string(),
+                None => continue,
+            };
+            let timestamp = match parse_backup_version(&version) {
+                Some(timestamp) => timestamp,
+                None => continue,
+            };
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            backups.push(BackupInfo {
+                name: name.to_string(),
+                version,
+                timestamp,
+                path,
+                size,
+            });
         }
 
-        // Copy backup to main location
-        fs::copy(&backup_path, &target_path)
+        backups.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(backups)
+    }
+
+    /// Restore a wallet from its newest backup
+    pub fn restore_wallet(&self, name: &str) -> Result<BackupInfo> {
+        let backup = self
+            .list_backups(name)?
+            .pop()
+            .ok_or_else(|| Error::storage(format!("No backup found for wallet: {}", name)))?;
+        self.restore_backup(&backup)?;
+        Ok(backup)
+    }
+
+    /// Restore a wallet from the backup with the given version
+    ///
+    /// Versions are the timestamps listed by [`StorageService::list_backups`].
+    pub fn restore_wallet_version(&self, name: &str, version: &str) -> Result<BackupInfo> {
+        let backup = self
+            .list_backups(name)?
+            .into_iter()
+            .find(|backup| backup.version == version)
+            .ok_or_else(|| {
+                Error::storage(format!("No backup {} found for wallet: {}", version, name))
+            })?;
+        self.restore_backup(&backup)?;
+        Ok(backup)
+    }
+
+    /// Copy a backup over the wallet file atomically
+    fn restore_backup(&self, backup: &BackupInfo) -> Result<()> {
+        let _lock = self.lock_wallet(&backup.name)?;
+        let target_path = self.wallet_file_path(&backup.name);
+        let temp_path = target_path.with_extension("tmp");
+        fs::copy(&backup.path, &temp_path)
             .map_err(|e| Error::storage(format!("Failed to restore wallet: {}", e)))?;
+        fs::rename(&temp_path, &target_path)
+            .map_err(|e| Error::storage(format!("Failed to restore wallet: {}", e)))?;
+        Ok(())
+    }
 
+    /// Delete the oldest backups of a wallet beyond `max_versions`
+    ///
+    /// A `max_versions` of 0 keeps every backup.
+    pub fn prune_backups(&self, name: &str) -> Result<()> {
+        if self.settings.max_versions == 0 {
+            return Ok(());
+        }
+        let backups = self.list_backups(name)?;
+        let excess = backups.len().saturating_sub(self.settings.max_versions);
+        for backup in &backups[..excess] {
+            fs::remove_file(&backup.path)
+                .map_err(|e| Error::storage(format!("Failed to delete old backup: {}", e)))?;
+        }
         Ok(())
     }
 
     /// Get wallet file path
-    fn wallet_file_path(&self, name: &str) -> PathBuf {
+    pub(crate) fn wallet_file_path(&self, name: &str) -> PathBuf {
         self.settings.path.join(format!("{}.json", name))
     }
 
     /// Get backup file path
+    ///
+    /// Microseconds keep backups taken within the same second apart.
     fn backup_file_path(&self, name: &str) -> PathBuf {
-        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        self.settings.backup_path.join(format!("{}_{}.json", name, timestamp))
+        let now = Utc::now();
+        let version = format!(
+            "{}_{:06}",
+            now.format(BACKUP_TIMESTAMP_FORMAT),
+            now.timestamp_subsec_micros()
+        );
+        self.settings.backup_path.join(format!("{}_{}.json", name, version))
     }
 
     /// Load only wallet metadata (without encrypted data)
@@ -469,6 +782,63 @@ impl StorageService {
     }
 }
 
+/// Overwrite a file with zeros, then unlink it
+///
+/// A failed overwrite is logged and the file is removed regardless.
+fn shred_file(path: &Path) -> Result<()> {
+    if let Err(e) = zero_file(path) {
+        warn!("Failed to overwrite {} before deleting it: {}", path.display(), e);
+    }
+    fs::remove_file(path)
+        .map_err(|e| Error::storage(format!("Failed to delete {}: {}", path.display(), e)))
+}
+
+/// Overwrite the contents of a file with zeros in place
+fn zero_file(path: &Path) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 4096];
+    let mut remaining = file.metadata()?.len();
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+}
+
+/// Key record to keep when a wallet is saved again with `public_key`
+///
+/// Usage carries over while the key stays the same; a new key starts afresh
+/// but keeps the archive of retired keys.
+pub(crate) fn kept_key_record(
+    existing: &WalletMetadata,
+    public_key: &Pubkey,
+    now: DateTime<Utc>,
+) -> KeyRecord {
+    match &existing.key {
+        Some(record) if existing.public_key == *public_key => record.clone(),
+        Some(record) => KeyRecord {
+            archived: record.archived.clone(),
+            pending_sweep: record.pending_sweep,
+            ..KeyRecord::new(now)
+        },
+        None => KeyRecord::new(now),
+    }
+}
+
+/// Parse the timestamp of a backup version
+///
+/// Accepts `YYYYMMDD_HHMMSS` with an optional `_micros` suffix.
+fn parse_backup_version(version: &str) -> Option<DateTime<Utc>> {
+    let (seconds, micros) = match version.get(15..) {
+        Some("") => (version, 0),
+        Some(rest) => (&version[..15], rest.strip_prefix('_')?.parse::<u32>().ok()?),
+        None => return None,
+    };
+    let timestamp = NaiveDateTime::parse_from_str(seconds, BACKUP_TIMESTAMP_FORMAT).ok()?;
+    Some(timestamp.and_utc() + Duration::microseconds(i64::from(micros)))
+}
+
 /// Storage statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageStats {
@@ -530,6 +900,7 @@ pub mod utils {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::StorageBackend;
     use tempfile::tempdir;
 
     #[test]
@@ -541,6 +912,7 @@ mod tests {
             path: temp_dir.path().to_path_buf(),
             backup_path: backup_dir.path().to_path_buf(),
             max_versions: 10,
+            backend: StorageBackend::File,
         };
 
         let storage = StorageService::new(settings)?;
@@ -571,6 +943,8 @@ mod tests {
                 map.insert("owner".to_string(), "test-user".to_string());
                 map
             },
+            revision: 4,
+            key: None,
         };
 
         // Serialize and deserialize
@@ -581,6 +955,14 @@ mod tests {
         assert_eq!(metadata.public_key, deserialized.public_key);
         assert_eq!(metadata.description, deserialized.description);
         assert_eq!(metadata.tags.len(), deserialized.tags.len());
+        assert_eq!(deserialized.revision, 4);
+
+        // Files written before revisions existed start at 0
+        let mut legacy = serde_json::to_value(&metadata)?;
+        if let Some(fields) = legacy.as_object_mut() {
+            fields.remove("revision");
+        }
+        assert_eq!(serde_json::from_value::<WalletMetadata>(legacy)?.revision, 0);
 
         Ok(())
     }
@@ -593,6 +975,8 @@ mod tests {
             salt: "salt".to_string(),
             algorithm: EncryptionAlgorithm::Aes256Gcm,
             kdf_iterations: 100_000,
+            kdf: None,
+            keystore_bound: false,
             version: 1,
         };
 
@@ -609,6 +993,8 @@ mod tests {
             description: None,
             tags: Vec::new(),
             custom_data: HashMap::new(),
+            revision: 0,
+            key: None,
         };
 
         let wallet_storage = WalletStorage {
@@ -640,4 +1026,205 @@ mod tests {
         // The zeroize should have cleared the memory
         // (This is a compile-time check, not runtime)
     }
+
+    fn encrypted(ciphertext: &str) -> EncryptedData {
+        EncryptedData {
+            ciphertext: ciphertext.to_string(),
+            nonce: "nonce".to_string(),
+            salt: "salt".to_string(),
+            algorithm: EncryptionAlgorithm::Aes256Gcm,
+            kdf_iterations: 100_000,
+            kdf: None,
+            keystore_bound: false,
+            version: 1,
+        }
+    }
+
+    fn storage_with_versions(dir: &Path, max_versions: usize) -> Result<StorageService> {
+        StorageService::new(StorageSettings {
+            path: dir.join("wallets"),
+            backup_path: dir.join("backups"),
+            max_versions,
+            backend: StorageBackend::File,
+        })
+    }
+
+    #[test]
+    fn test_backups_are_pruned_per_wallet() -> Result<()> {
+        let dir = tempdir()?;
+        let mut storage = storage_with_versions(dir.path(), 3)?;
+        let public_key = Pubkey::new_unique();
+
+        storage.save_wallet("agent", encrypted("v0"), public_key, None)?;
+        storage.save_wallet("agent_two", encrypted("other"), public_key, None)?;
+        for version in 1..=4 {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            storage.replace_encrypted_data("agent", encrypted(&format!("v{}", version)))?;
+        }
+
+        // Five backups of "agent" were taken; the oldest two are gone
+        let backups = storage.list_backups("agent")?;
+        assert_eq!(backups.len(), 3);
+        assert!(backups.windows(2).all(|pair| pair[0].timestamp < pair[1].timestamp));
+        // A wallet whose name extends another's keeps its own backups
+        assert_eq!(storage.list_backups("agent_two")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_picks_newest_or_requested_backup() -> Result<()> {
+        let dir = tempdir()?;
+        let mut storage = storage_with_versions(dir.path(), 10)?;
+        assert!(storage.restore_wallet("agent").is_err());
+
+        storage.save_wallet("agent", encrypted("first"), Pubkey::new_unique(), None)?;
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        storage.replace_encrypted_data("agent", encrypted("second"))?;
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        storage.replace_encrypted_data("agent", encrypted("third"))?;
+
+        // The newest backup was taken just before "third" was written
+        let restored = storage.restore_wallet("agent")?;
+        assert_eq!(storage.read_wallet("agent")?.0.ciphertext, "second");
+
+        let oldest = storage.list_backups("agent")?.remove(0);
+        assert_ne!(oldest.version, restored.version);
+        storage.restore_wallet_version("agent", &oldest.version)?;
+        assert_eq!(storage.read_wallet("agent")?.0.ciphertext, "first");
+
+        assert!(storage.restore_wallet_version("agent", "20000101_000000").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_purge_removes_the_wallet_and_all_its_backups() -> Result<()> {
+        let dir = tempdir()?;
+        let mut storage = storage_with_versions(dir.path(), 10)?;
+        let public_key = Pubkey::new_unique();
+
+        storage.save_wallet("agent", encrypted("v0"), public_key, None)?;
+        storage.save_wallet("agent_two", encrypted("other"), public_key, None)?;
+        for version in 1..=3 {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            storage.replace_encrypted_data("agent", encrypted(&format!("v{}", version)))?;
+        }
+        // Every save took a backup
+        let backups = storage.list_backups("agent")?;
+        assert_eq!(backups.len(), 4);
+
+        let report = storage.purge_wallet("agent")?;
+        assert_eq!(report, PurgeReport { files_removed: 1, backups_removed: 4 });
+        assert!(!storage.wallet_exists("agent"));
+        assert!(storage.list_backups("agent")?.is_empty());
+        assert!(backups.iter().all(|backup| !backup.path.exists()));
+        // No backup of the purged wallet was taken on the way out
+        assert!(storage.restore_wallet("agent").is_err());
+
+        // A wallet whose name extends the purged one's is untouched
+        assert!(storage.wallet_exists("agent_two"));
+        assert_eq!(storage.list_backups("agent_two")?.len(), 1);
+        assert!(matches!(storage.purge_wallet("agent"), Err(Error::WalletNotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_file_keeps_the_length() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("secret.json");
+        let contents = "k".repeat(10_000);
+        fs::write(&path, &contents)?;
+
+        zero_file(&path)?;
+        let zeroed = fs::read(&path)?;
+        assert_eq!(zeroed.len(), contents.len());
+        assert!(zeroed.iter().all(|byte| *byte == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_versions_parse() {
+        assert!(parse_backup_version("20240102_030405").is_some());
+        assert_eq!(
+            parse_backup_version("20240102_030405_000250")
+                .zip(parse_backup_version("20240102_030405"))
+                .map(|(micros, seconds)| (micros - seconds).num_microseconds()),
+            Some(Some(250))
+        );
+        assert!(parse_backup_version("two_20240102_030405").is_none());
+        assert!(parse_backup_version("20240102_030405_x").is_none());
+    }
+
+    #[test]
+    fn test_readonly_load_never_writes() -> Result<()> {
+        let dir = tempdir()?;
+        let mut storage = storage_with_versions(dir.path(), 0)?;
+        storage.save_wallet("agent", encrypted("secret"), Pubkey::new_unique(), None)?;
+        let path = storage.wallet_file_path("agent");
+        let before = fs::read(&path)?;
+
+        let (data, metadata) = storage.load_wallet_readonly("agent")?;
+        assert_eq!(data.ciphertext, "secret");
+        assert_eq!(metadata.revision, 1);
+        assert_eq!(fs::read(&path)?, before);
+
+        // A touching load rewrites the timestamp but keeps the revision
+        let (_, touched) = storage.load_wallet("agent")?;
+        assert_eq!(touched.revision, 1);
+        assert_ne!(fs::read(&path)?, before);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_saves_conflict_instead_of_overwriting() -> Result<()> {
+        let dir = tempdir()?;
+        let public_key = Pubkey::new_unique();
+        storage_with_versions(dir.path(), 0)?.save_wallet(
+            "agent",
+            encrypted("original"),
+            public_key,
+            None,
+        )?;
+
+        // Both tasks load revision 1 before either of them saves
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let tasks: Vec<_> = ["first", "second"]
+            .into_iter()
+            .map(|description| {
+                let barrier = barrier.clone();
+                let path = dir.path().to_path_buf();
+                tokio::task::spawn_blocking(move || -> Result<()> {
+                    let mut storage = storage_with_versions(&path, 0)?;
+                    let loaded = storage.load_wallet_readonly("agent");
+                    barrier.wait();
+                    loaded?;
+                    storage.save_wallet(
+                        "agent",
+                        encrypted(description),
+                        public_key,
+                        Some(description),
+                    )
+                })
+            })
+            .collect();
+
+        let (mut saved, mut conflicts) = (0, 0);
+        for task in tasks {
+            match task.await.map_err(|e| Error::storage(e.to_string()))? {
+                Ok(()) => saved += 1,
+                Err(Error::StorageConflict {
+                    expected: 1,
+                    found: 2,
+                    ..
+                }) => conflicts += 1,
+                Err(e) => return Err(e),
+            }
+        }
+        assert_eq!((saved, conflicts), (1, 1));
+
+        // The file parses and holds the winner's save, not a mix of both
+        let (data, metadata) = storage_with_versions(dir.path(), 0)?.read_wallet("agent")?;
+        assert_eq!(metadata.revision, 2);
+        assert_eq!(metadata.description.as_deref(), Some(data.ciphertext.as_str()));
+        Ok(())
+    }
 }
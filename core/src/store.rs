@@ -0,0 +1,624 @@
+//! Pluggable backends wallets are stored in
+//!
+//! Wallets persist their encrypted key through a [`WalletStore`].
+//! [`open_store`] opens the backend selected by
+//! [`StorageSettings::backend`](crate::config::StorageSettings::backend):
+//!
+//! - `file`: encrypted wallet files on disk ([`StorageService`]), the default.
+//! - `memory`: wallets held in process memory ([`MemoryStore`]), for tests
+//!   and ephemeral agents. Stores opened with the same namespace share their
+//!   wallets, so a wallet created through one can be loaded through another.
+//! - `env`: a single read-only wallet ([`EnvStore`]) whose wallet file comes
+//!   base64-encoded from an environment variable or a mounted secret file,
+//!   for containers and CI where the filesystem is ephemeral or read-only.
+//!   Saving, updating or deleting fails with [`Error::NotSupported`].
+//!
+//! # Precedence
+//!
+//! The env backend reads its environment variable first and only falls back
+//! to the secret file when the variable is unset. Wallet files under the
+//! storage `path` are never consulted while the env backend is configured,
+//! even when a file for the same wallet exists there.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::config::{StorageBackend, WalletConfig};
+//! use agent_wallet_core::Wallet;
+//! use zeroize::Zeroizing;
+//!
+//! # async fn example() -> agent_wallet_core::Result<()> {
+//! // The container is started with AGENT_WALLET_BLOB="$(base64 -w0 trader.json)"
+//! let config = WalletConfig::builder()
+//!     .with_storage_backend(StorageBackend::Env {
+//!         variable: "AGENT_WALLET_BLOB".to_string(),
+//!         secret_file: Some("/run/secrets/agent-wallet".into()),
+//!     })
+//!     .build();
+//! let passphrase = Zeroizing::new("secure-passphrase".to_string());
+//! let wallet = Wallet::load("trader", &passphrase, config).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::env::{self, VarError};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::config::{StorageBackend, StorageSettings};
+use crate::encryption::EncryptedData;
+use crate::error::{Error, Result};
+use crate::rotation::KeyRecord;
+use crate::storage::{kept_key_record, PurgeReport, StorageService, WalletMetadata, WalletStorage};
+use crate::sync::lock;
+use crate::types::{PermissionLevel, WalletInfo};
+
+/// Storage of encrypted wallets by name
+pub trait WalletStore: Send + Sync {
+    /// Save a wallet, replacing a stored wallet of the same name
+    fn save_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        public_key: Pubkey,
+        description: Option<&str>,
+    ) -> Result<()>;
+
+    /// Load a wallet, recording the access where the backend can
+    fn load_wallet(&mut self, name: &str) -> Result<(EncryptedData, WalletMetadata)>;
+
+    /// Read a wallet without recording the access
+    fn read_wallet(&self, name: &str) -> Result<(EncryptedData, WalletMetadata)>;
+
+    /// Replace the encrypted data of a stored wallet, keeping its metadata
+    fn replace_encrypted_data(&mut self, name: &str, encrypted_data: EncryptedData) -> Result<()>;
+
+    /// Replace the metadata of a stored wallet, keeping its encrypted data
+    fn update_metadata(&mut self, name: &str, metadata: &WalletMetadata) -> Result<()>;
+
+    /// Replace the encrypted data and metadata of a stored wallet in one write
+    fn replace_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        metadata: &WalletMetadata,
+    ) -> Result<()>;
+
+    /// Delete a wallet
+    fn delete_wallet(&mut self, name: &str) -> Result<()>;
+
+    /// Delete a wallet without keeping any copy of it
+    ///
+    /// File backends zero the wallet file and every backup before removing
+    /// them; backends without files only delete the wallet.
+    fn purge_wallet(&mut self, name: &str) -> Result<PurgeReport> {
+        self.delete_wallet(name)?;
+        Ok(PurgeReport::default())
+    }
+
+    /// List the stored wallets
+    fn list_wallets(&self) -> Result<Vec<WalletInfo>>;
+
+    /// Check if a wallet is stored
+    fn wallet_exists(&self, name: &str) -> bool;
+
+    /// File a wallet is stored in, for backends that store files
+    fn wallet_path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Open the backend selected in `settings`
+pub fn open_store(settings: &StorageSettings) -> Result<Box<dyn WalletStore>> {
+    Ok(match &settings.backend {
+        StorageBackend::File => Box::new(StorageService::new(settings.clone())?),
+        StorageBackend::Memory { namespace } => Box::new(MemoryStore::shared(namespace)),
+        StorageBackend::Env {
+            variable,
+            secret_file,
+        } => Box::new(EnvStore::new(variable.clone(), secret_file.clone())),
+    })
+}
+
+impl WalletStore for StorageService {
+    fn save_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        public_key: Pubkey,
+        description: Option<&str>,
+    ) -> Result<()> {
+        StorageService::save_wallet(self, name, encrypted_data, public_key, description)
+    }
+
+    fn load_wallet(&mut self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        StorageService::load_wallet(self, name)
+    }
+
+    fn read_wallet(&self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        StorageService::read_wallet(self, name)
+    }
+
+    fn replace_encrypted_data(&mut self, name: &str, encrypted_data: EncryptedData) -> Result<()> {
+        StorageService::replace_encrypted_data(self, name, encrypted_data)
+    }
+
+    fn update_metadata(&mut self, name: &str, metadata: &WalletMetadata) -> Result<()> {
+        StorageService::update_metadata(self, name, metadata)
+    }
+
+    fn replace_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        metadata: &WalletMetadata,
+    ) -> Result<()> {
+        StorageService::replace_wallet(self, name, encrypted_data, metadata)
+    }
+
+    fn delete_wallet(&mut self, name: &str) -> Result<()> {
+        StorageService::delete_wallet(self, name)
+    }
+
+    fn purge_wallet(&mut self, name: &str) -> Result<PurgeReport> {
+        StorageService::purge_wallet(self, name)
+    }
+
+    fn list_wallets(&self) -> Result<Vec<WalletInfo>> {
+        StorageService::list_wallets(self)
+    }
+
+    fn wallet_exists(&self, name: &str) -> bool {
+        StorageService::wallet_exists(self, name)
+    }
+
+    fn wallet_path(&self, name: &str) -> Option<PathBuf> {
+        Some(self.wallet_file_path(name))
+    }
+}
+
+/// Summary of a stored wallet for listings
+fn wallet_info(metadata: &WalletMetadata) -> WalletInfo {
+    WalletInfo {
+        name: metadata.name.clone(),
+        public_key: metadata.public_key,
+        created_at: metadata.created_at,
+        last_accessed: metadata.last_accessed,
+        balance_lamports: 0,
+        transaction_count: 0,
+        transaction_count_truncated: false,
+        permission_level: PermissionLevel::Basic,
+        is_active: true,
+        token_accounts: Vec::new(),
+    }
+}
+
+type MemoryWallets = Arc<Mutex<HashMap<String, WalletStorage>>>;
+
+/// Wallets of every shared memory store, by namespace
+static NAMESPACES: Lazy<Mutex<HashMap<String, MemoryWallets>>> = Lazy::new(Default::default);
+
+/// Wallets kept in process memory
+///
+/// Clones share their wallets.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    wallets: MemoryWallets,
+}
+
+impl MemoryStore {
+    /// Create an empty store of its own
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the store of `namespace`, shared by every store opened with it
+    pub fn shared(namespace: &str) -> Self {
+        let wallets = lock(&NAMESPACES)
+            .entry(namespace.to_string())
+            .or_default()
+            .clone();
+        Self { wallets }
+    }
+
+    /// Apply `update` to a stored wallet and bump its revision
+    fn modify(&self, name: &str, update: impl FnOnce(&mut WalletStorage)) -> Result<()> {
+        let mut wallets = lock(&self.wallets);
+        let wallet_storage = wallets
+            .get_mut(name)
+            .ok_or_else(|| Error::WalletNotFound(name.to_string()))?;
+        let revision = wallet_storage.metadata.revision;
+        update(wallet_storage);
+        wallet_storage.metadata.last_modified = Utc::now();
+        wallet_storage.metadata.revision = revision + 1;
+        Ok(())
+    }
+}
+
+impl WalletStore for MemoryStore {
+    fn save_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        public_key: Pubkey,
+        description: Option<&str>,
+    ) -> Result<()> {
+        let mut wallets = lock(&self.wallets);
+        let now = Utc::now();
+        let (revision, key) = wallets
+            .get(name)
+            .map_or((1, KeyRecord::new(now)), |existing| {
+                (
+                    existing.metadata.revision + 1,
+                    kept_key_record(&existing.metadata, &public_key, now),
+                )
+            });
+        let metadata = WalletMetadata {
+            name: name.to_string(),
+            public_key,
+            created_at: now,
+            last_accessed: now,
+            last_modified: now,
+            wallet_version: 1,
+            description: description.map(str::to_string),
+            tags: Vec::new(),
+            custom_data: HashMap::new(),
+            revision,
+            key: Some(key),
+        };
+        wallets.insert(
+            name.to_string(),
+            WalletStorage {
+                version: "1.0".to_string(),
+                encrypted_data,
+                metadata,
+            },
+        );
+        Ok(())
+    }
+
+    fn load_wallet(&mut self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        let mut wallets = lock(&self.wallets);
+        let wallet_storage = wallets
+            .get_mut(name)
+            .ok_or_else(|| Error::WalletNotFound(name.to_string()))?;
+        wallet_storage.metadata.last_accessed = Utc::now();
+        Ok((
+            wallet_storage.encrypted_data.clone(),
+            wallet_storage.metadata.clone(),
+        ))
+    }
+
+    fn read_wallet(&self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        lock(&self.wallets)
+            .get(name)
+            .map(|w| (w.encrypted_data.clone(), w.metadata.clone()))
+            .ok_or_else(|| Error::WalletNotFound(name.to_string()))
+    }
+
+    fn replace_encrypted_data(&mut self, name: &str, encrypted_data: EncryptedData) -> Result<()> {
+        self.modify(name, |w| w.encrypted_data = encrypted_data)
+    }
+
+    fn update_metadata(&mut self, name: &str, metadata: &WalletMetadata) -> Result<()> {
+        self.modify(name, |w| w.metadata = metadata.clone())
+    }
+
+    fn replace_wallet(
+        &mut self,
+        name: &str,
+        encrypted_data: EncryptedData,
+        metadata: &WalletMetadata,
+    ) -> Result<()> {
+        self.modify(name, |w| {
+            w.encrypted_data = encrypted_data;
+            w.metadata = metadata.clone();
+        })
+    }
+
+    fn delete_wallet(&mut self, name: &str) -> Result<()> {
+        lock(&self.wallets)
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| Error::WalletNotFound(name.to_string()))
+    }
+
+    fn list_wallets(&self) -> Result<Vec<WalletInfo>> {
+        let mut wallets: Vec<WalletInfo> = lock(&self.wallets)
+            .values()
+            .map(|w| wallet_info(&w.metadata))
+            .collect();
+        wallets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(wallets)
+    }
+
+    fn wallet_exists(&self, name: &str) -> bool {
+        lock(&self.wallets).contains_key(name)
+    }
+}
+
+/// One read-only wallet from an environment variable or a secret file
+///
+/// Both hold the wallet's file as written by the file backend,
+/// base64-encoded (see [`encode_wallet_blob`]). The blob is read on every
+/// access, so a rotated secret is picked up without restarting.
+#[derive(Debug, Clone)]
+pub struct EnvStore {
+    variable: String,
+    secret_file: Option<PathBuf>,
+}
+
+impl EnvStore {
+    /// Create a store reading `variable`, falling back to `secret_file`
+    pub fn new(variable: impl Into<String>, secret_file: Option<PathBuf>) -> Self {
+        Self {
+            variable: variable.into(),
+            secret_file,
+        }
+    }
+
+    /// The blob's wallet, or `None` when neither source is present
+    fn stored(&self) -> Result<Option<WalletStorage>> {
+        let blob = match env::var(&self.variable) {
+            Ok(blob) => blob,
+            Err(VarError::NotPresent) => match &self.secret_file {
+                Some(path) => match fs::read_to_string(path) {
+                    Ok(blob) => blob,
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                    Err(e) => {
+                        return Err(Error::storage(format!(
+                            "Failed to read wallet secret {}: {}",
+                            path.display(),
+                            e
+                        )))
+                    }
+                },
+                None => return Ok(None),
+            },
+            Err(e) => {
+                return Err(Error::storage(format!(
+                    "Failed to read {}: {}",
+                    self.variable, e
+                )))
+            }
+        };
+        decode_wallet_blob(&blob).map(Some)
+    }
+
+    /// The blob's wallet if it is the one named `name`
+    fn wallet(&self, name: &str) -> Result<WalletStorage> {
+        match self.stored()? {
+            Some(wallet_storage) if wallet_storage.metadata.name == name => Ok(wallet_storage),
+            _ => Err(Error::WalletNotFound(name.to_string())),
+        }
+    }
+
+    fn read_only(&self, operation: &str, name: &str) -> Error {
+        Error::NotSupported(format!(
+            "Cannot {} wallet '{}': the env wallet store is read-only",
+            operation, name
+        ))
+    }
+}
+
+impl WalletStore for EnvStore {
+    fn save_wallet(
+        &mut self,
+        name: &str,
+        _encrypted_data: EncryptedData,
+        _public_key: Pubkey,
+        _description: Option<&str>,
+    ) -> Result<()> {
+        Err(self.read_only("save", name))
+    }
+
+    fn load_wallet(&mut self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        self.read_wallet(name)
+    }
+
+    fn read_wallet(&self, name: &str) -> Result<(EncryptedData, WalletMetadata)> {
+        let wallet_storage = self.wallet(name)?;
+        Ok((wallet_storage.encrypted_data, wallet_storage.metadata))
+    }
+
+    fn replace_encrypted_data(&mut self, name: &str, _encrypted_data: EncryptedData) -> Result<()> {
+        Err(self.read_only("re-encrypt", name))
+    }
+
+    fn update_metadata(&mut self, name: &str, _metadata: &WalletMetadata) -> Result<()> {
+        Err(self.read_only("update", name))
+    }
+
+    fn replace_wallet(
+        &mut self,
+        name: &str,
+        _encrypted_data: EncryptedData,
+        _metadata: &WalletMetadata,
+    ) -> Result<()> {
+        Err(self.read_only("replace", name))
+    }
+
+    fn delete_wallet(&mut self, name: &str) -> Result<()> {
+        Err(self.read_only("delete", name))
+    }
+
+    fn list_wallets(&self) -> Result<Vec<WalletInfo>> {
+        Ok(self
+            .stored()?
+            .map(|w| wallet_info(&w.metadata))
+            .into_iter()
+            .collect())
+    }
+
+    fn wallet_exists(&self, name: &str) -> bool {
+        self.wallet(name).is_ok()
+    }
+}
+
+/// Encode a wallet file as the blob the env backend reads
+pub fn encode_wallet_blob(wallet_storage: &WalletStorage) -> Result<String> {
+    Ok(STANDARD.encode(serde_json::to_vec(wallet_storage)?))
+}
+
+/// Decode a base64-encoded wallet file
+///
+/// Surrounding whitespace, such as the newline ending a secret file, is
+/// ignored.
+pub fn decode_wallet_blob(blob: &str) -> Result<WalletStorage> {
+    let json = STANDARD.decode(blob.trim())?;
+    serde_json::from_slice(&json)
+        .map_err(|e| Error::serialization(format!("Failed to parse wallet blob: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::EncryptionAlgorithm;
+    use tempfile::tempdir;
+
+    fn encrypted(ciphertext: &str) -> EncryptedData {
+        EncryptedData {
+            ciphertext: ciphertext.to_string(),
+            nonce: "nonce".to_string(),
+            salt: "salt".to_string(),
+            algorithm: EncryptionAlgorithm::Aes256Gcm,
+            kdf_iterations: 100_000,
+            kdf: None,
+            keystore_bound: false,
+            version: 1,
+        }
+    }
+
+    fn blob_of(name: &str, ciphertext: &str) -> Result<String> {
+        let mut store = MemoryStore::new();
+        store.save_wallet(name, encrypted(ciphertext), Pubkey::new_unique(), None)?;
+        let (encrypted_data, metadata) = store.read_wallet(name)?;
+        encode_wallet_blob(&WalletStorage {
+            version: "1.0".to_string(),
+            encrypted_data,
+            metadata,
+        })
+    }
+
+    #[test]
+    fn test_file_backend_behind_the_trait() -> Result<()> {
+        let dir = tempdir()?;
+        let mut store = open_store(&StorageSettings {
+            path: dir.path().join("wallets"),
+            backup_path: dir.path().join("backups"),
+            max_versions: 3,
+            backend: StorageBackend::File,
+        })?;
+
+        store.save_wallet("agent", encrypted("secret"), Pubkey::new_unique(), None)?;
+        assert!(dir.path().join("wallets/agent.json").exists());
+        assert_eq!(store.load_wallet("agent")?.0.ciphertext, "secret");
+        assert_eq!(store.list_wallets()?.len(), 1);
+        store.delete_wallet("agent")?;
+        assert!(!store.wallet_exists("agent"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_backend_shares_namespaces() -> Result<()> {
+        let backend = StorageBackend::Memory {
+            namespace: "test_memory_backend_shares_namespaces".to_string(),
+        };
+        let settings = StorageSettings {
+            backend,
+            ..StorageSettings::default()
+        };
+        let public_key = Pubkey::new_unique();
+
+        let mut creator = open_store(&settings)?;
+        creator.save_wallet("agent", encrypted("v1"), public_key, Some("ephemeral"))?;
+
+        // A store opened later with the same namespace sees the wallet
+        let mut loader = open_store(&settings)?;
+        let (data, metadata) = loader.load_wallet("agent")?;
+        assert_eq!(data.ciphertext, "v1");
+        assert_eq!(metadata.public_key, public_key);
+        assert_eq!(metadata.revision, 1);
+
+        loader.replace_encrypted_data("agent", encrypted("v2"))?;
+        let (data, metadata) = creator.read_wallet("agent")?;
+        assert_eq!(data.ciphertext, "v2");
+        assert_eq!(metadata.revision, 2);
+        assert_eq!(metadata.description.as_deref(), Some("ephemeral"));
+
+        // Other namespaces and private stores are isolated
+        assert!(!MemoryStore::new().wallet_exists("agent"));
+        assert!(!MemoryStore::shared("elsewhere").wallet_exists("agent"));
+
+        loader.delete_wallet("agent")?;
+        assert!(creator.list_wallets()?.is_empty());
+        assert!(matches!(
+            creator.delete_wallet("agent"),
+            Err(Error::WalletNotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_backend_reads_blob_and_refuses_writes() -> Result<()> {
+        let variable = "AGENT_WALLET_TEST_ENV_BACKEND";
+        env::set_var(variable, blob_of("ci-agent", "from-env")?);
+        let mut store = open_store(&StorageSettings {
+            backend: StorageBackend::Env {
+                variable: variable.to_string(),
+                secret_file: None,
+            },
+            ..StorageSettings::default()
+        })?;
+
+        assert_eq!(store.load_wallet("ci-agent")?.0.ciphertext, "from-env");
+        assert_eq!(store.list_wallets()?.len(), 1);
+        assert!(matches!(
+            store.read_wallet("other"),
+            Err(Error::WalletNotFound(_))
+        ));
+        assert!(matches!(
+            store.save_wallet("ci-agent", encrypted("new"), Pubkey::new_unique(), None),
+            Err(Error::NotSupported(_))
+        ));
+        assert!(matches!(
+            store.delete_wallet("ci-agent"),
+            Err(Error::NotSupported(_))
+        ));
+        env::remove_var(variable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_variable_takes_precedence_over_secret_file() -> Result<()> {
+        let dir = tempdir()?;
+        let secret_file = dir.path().join("wallet.b64");
+        fs::write(
+            &secret_file,
+            format!("{}\n", blob_of("ci-agent", "from-file")?),
+        )?;
+        let variable = "AGENT_WALLET_TEST_ENV_PRECEDENCE";
+        let store = EnvStore::new(variable, Some(secret_file));
+
+        // The secret file is read while the variable is unset
+        assert_eq!(store.read_wallet("ci-agent")?.0.ciphertext, "from-file");
+
+        env::set_var(variable, blob_of("ci-agent", "from-env")?);
+        assert_eq!(store.read_wallet("ci-agent")?.0.ciphertext, "from-env");
+        env::remove_var(variable);
+
+        // Neither source present: no wallets
+        let empty = EnvStore::new(variable, Some(dir.path().join("missing")));
+        assert!(empty.list_wallets()?.is_empty());
+        assert!(!empty.wallet_exists("ci-agent"));
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+//! Synchronization helpers shared across the crate
+//!
+//! State behind a `std::sync::Mutex` here is only ever updated in place by
+//! short critical sections, so a holder panicking leaves it consistent.
+//! [`lock`] therefore recovers a poisoned mutex instead of propagating the
+//! panic to every later caller.
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Lock a mutex, recovering its state if a holder panicked
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
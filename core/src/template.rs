@@ -0,0 +1,503 @@
+//! Precomputed transaction templates
+//!
+//! Deterministic strategies emit the same shape of action on every tick: the
+//! same recipient, mint and memo, with only the amount changing. An
+//! [`ActionTemplate`] describes that shape so the transaction can be built and
+//! validated once up front. At decision time only the amount and the blockhash
+//! are patched into the prepared message, and only the amount-dependent
+//! constraints (spending limits) are checked again.
+//!
+//! Actions that do not match a prepared template go through the regular
+//! [`TransactionBuilder::build_from_action`] path.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::template::{ActionTemplate, TemplateSet};
+//! use agent_wallet_core::transaction::{TransactionBuilder, TransactionOptions};
+//! use agent_wallet_core::types::{AgentAction, AgentContext};
+//! use solana_sdk::{hash::Hash, pubkey::Pubkey};
+//!
+//! # fn main() -> agent_wallet_core::Result<()> {
+//! let mut builder = TransactionBuilder::new();
+//! let context = AgentContext::new(Pubkey::new_unique());
+//! let options = TransactionOptions::default();
+//! let recipient = Pubkey::new_unique();
+//!
+//! let templates = TemplateSet::prepare(
+//!     &mut builder,
+//!     vec![ActionTemplate::TransferSol { to: recipient, memo: None }],
+//!     &context,
+//!     &options,
+//! )?;
+//!
+//! let action = AgentAction::TransferSol {
+//!     to: recipient,
+//!     amount: Lamports::new(1_000),
+//!     memo: None,
+//! };
+//! if let Some(prepared) = templates.find(&action, &context, &options) {
+//!     let transaction = prepared.instantiate(&builder, &action, &context, Hash::default())?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{hash::Hash, message::Message, pubkey::Pubkey, transaction::Transaction};
+
+use crate::error::{Error, Result};
+use crate::memo::{MemoAttribution, MemoMode};
+use crate::nonce::NonceInfo;
+use crate::sol::Lamports;
+use crate::transaction::{TransactionBuilder, TransactionOptions};
+use crate::tx_policy::{PolicyInput, TransactionPolicy};
+use crate::types::{AgentAction, AgentContext, PermissionLevel};
+
+/// Amounts used to locate the amount field in a prepared message.
+///
+/// They differ only in the lowest byte, so the first differing byte of the
+/// two builds is the start of the little-endian amount.
+const PROBE_AMOUNTS: (u64, u64) = (1, 2);
+
+/// Fixed shape of an action whose amount varies between decisions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionTemplate {
+    /// SOL transfer to a fixed recipient
+    TransferSol {
+        /// Destination address
+        to: Pubkey,
+        /// Optional memo
+        memo: Option<String>,
+    },
+    /// SPL token transfer of a fixed mint to a fixed recipient
+    TransferToken {
+        /// Token mint address
+        mint: Pubkey,
+        /// Destination address
+        to: Pubkey,
+        /// Optional memo
+        memo: Option<String>,
+    },
+}
+
+impl ActionTemplate {
+    /// Derive the template an action fits, if its kind supports templating
+    pub fn from_action(action: &AgentAction) -> Option<Self> {
+        match action {
+            AgentAction::TransferSol { to, memo, .. } => Some(ActionTemplate::TransferSol {
+                to: *to,
+                memo: memo.clone(),
+            }),
+            AgentAction::TransferToken { mint, to, memo, .. } => {
+                Some(ActionTemplate::TransferToken {
+                    mint: *mint,
+                    to: *to,
+                    memo: memo.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether an action has exactly this shape
+    pub fn matches(&self, action: &AgentAction) -> bool {
+        Self::from_action(action).as_ref() == Some(self)
+    }
+
+    /// Build the action for a concrete amount
+    pub fn instantiate(&self, amount: u64) -> AgentAction {
+        match self {
+            ActionTemplate::TransferSol { to, memo } => AgentAction::TransferSol {
+                to: *to,
+                amount: Lamports::new(amount),
+                memo: memo.clone(),
+            },
+            ActionTemplate::TransferToken { mint, to, memo } => AgentAction::TransferToken {
+                mint: *mint,
+                to: *to,
+                amount,
+                memo: memo.clone(),
+            },
+        }
+    }
+
+    /// Amount carried by an action of this template's kind
+    fn amount_of(action: &AgentAction) -> Option<u64> {
+        match action {
+            AgentAction::TransferSol { amount, .. } => Some(amount.as_u64()),
+            AgentAction::TransferToken { amount, .. } => Some(*amount),
+            _ => None,
+        }
+    }
+}
+
+/// Location of the little-endian amount inside the prepared message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AmountSlot {
+    instruction: usize,
+    offset: usize,
+}
+
+/// Inputs the prepared message depends on besides the template itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PreparedFor {
+    wallet_pubkey: Pubkey,
+    permission_level: PermissionLevel,
+    fee_payer: Option<Pubkey>,
+    compute_unit_limit: Option<u32>,
+    compute_unit_price: Option<u64>,
+    cosigners: Vec<Pubkey>,
+    nonce: Option<NonceInfo>,
+    memo_mode: MemoMode,
+    memo_attribution: Option<MemoAttribution>,
+    payment_references: Vec<Pubkey>,
+}
+
+impl PreparedFor {
+    fn new(context: &AgentContext, options: &TransactionOptions) -> Self {
+        Self {
+            wallet_pubkey: context.wallet_pubkey,
+            permission_level: context.permission_level,
+            fee_payer: options.fee_payer,
+            compute_unit_limit: options.compute_unit_limit,
+            compute_unit_price: options.compute_unit_price,
+            cosigners: options.cosigners.clone(),
+            nonce: options.nonce,
+            memo_mode: options.memo_mode,
+            memo_attribution: options.memo_attribution.clone(),
+            payment_references: options.payment_references.clone(),
+        }
+    }
+}
+
+/// A template whose transaction was built and validated ahead of time
+#[derive(Debug, Clone)]
+pub struct PreparedTemplate {
+    template: ActionTemplate,
+    message: Message,
+    amount_slot: AmountSlot,
+    prepared_for: PreparedFor,
+}
+
+impl PreparedTemplate {
+    /// Build and validate the transaction for a template
+    ///
+    /// The template is built twice with different probe amounts to find where
+    /// the amount lives in the message. Templates whose messages differ in
+    /// anything but that amount cannot be patched and are rejected.
+    pub fn prepare(
+        builder: &mut TransactionBuilder,
+        template: ActionTemplate,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Result<Self> {
+        let (probe_a, probe_b) = PROBE_AMOUNTS;
+        let first = builder.build_from_action(&template.instantiate(probe_a), context, options)?;
+        let second = builder.build_from_action(&template.instantiate(probe_b), context, options)?;
+
+        TransactionPolicy::from_options(options).enforce(&PolicyInput::new(&first, context))?;
+
+        let amount_slot = locate_amount(&first.message, &second.message).ok_or_else(|| {
+            Error::NotSupported(format!(
+                "Transaction for {:?} cannot be patched by amount",
+                template
+            ))
+        })?;
+
+        Ok(Self {
+            template,
+            message: first.message,
+            amount_slot,
+            prepared_for: PreparedFor::new(context, options),
+        })
+    }
+
+    /// Template this transaction was prepared from
+    pub fn template(&self) -> &ActionTemplate {
+        &self.template
+    }
+
+    /// Whether the prepared message is still valid for this context and options
+    pub fn is_valid_for(&self, context: &AgentContext, options: &TransactionOptions) -> bool {
+        self.prepared_for == PreparedFor::new(context, options)
+    }
+
+    /// Produce the unsigned transaction for an action matching the template
+    ///
+    /// Only the spending limits are re-checked since everything else was
+    /// validated when the template was prepared.
+    pub fn instantiate(
+        &self,
+        builder: &TransactionBuilder,
+        action: &AgentAction,
+        context: &AgentContext,
+        recent_blockhash: Hash,
+    ) -> Result<Transaction> {
+        let amount = ActionTemplate::amount_of(action)
+            .filter(|_| self.template.matches(action))
+            .ok_or_else(|| {
+                Error::validation(format!(
+                    "Action does not match template {:?}",
+                    self.template
+                ))
+            })?;
+
+        builder.validate_spending_limits(action, context)?;
+
+        let mut message = self.message.clone();
+        let AmountSlot {
+            instruction,
+            offset,
+        } = self.amount_slot;
+        message.instructions[instruction].data[offset..offset + 8]
+            .copy_from_slice(&amount.to_le_bytes());
+        message.recent_blockhash = recent_blockhash;
+
+        Ok(Transaction::new_unsigned(message))
+    }
+}
+
+/// Find the single amount field that differs between two probe builds
+fn locate_amount(first: &Message, second: &Message) -> Option<AmountSlot> {
+    if first.header != second.header
+        || first.account_keys != second.account_keys
+        || first.instructions.len() != second.instructions.len()
+    {
+        return None;
+    }
+
+    let (probe_a, probe_b) = PROBE_AMOUNTS;
+    let mut slot = None;
+    for (index, (a, b)) in first
+        .instructions
+        .iter()
+        .zip(&second.instructions)
+        .enumerate()
+    {
+        if a.program_id_index != b.program_id_index
+            || a.accounts != b.accounts
+            || a.data.len() != b.data.len()
+        {
+            return None;
+        }
+        if a.data == b.data {
+            continue;
+        }
+        if slot.is_some() {
+            return None;
+        }
+
+        let offset = a.data.iter().zip(&b.data).position(|(x, y)| x != y)?;
+        let end = offset + 8;
+        if end > a.data.len()
+            || a.data[offset..end] != probe_a.to_le_bytes()
+            || b.data[offset..end] != probe_b.to_le_bytes()
+            || a.data[end..] != b.data[end..]
+        {
+            return None;
+        }
+        slot = Some(AmountSlot {
+            instruction: index,
+            offset,
+        });
+    }
+    slot
+}
+
+/// Prepared templates for one agent
+#[derive(Debug, Clone, Default)]
+pub struct TemplateSet {
+    prepared: Vec<PreparedTemplate>,
+}
+
+impl TemplateSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepare every template, failing if any of them does not validate
+    pub fn prepare(
+        builder: &mut TransactionBuilder,
+        templates: impl IntoIterator<Item = ActionTemplate>,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Result<Self> {
+        let prepared = templates
+            .into_iter()
+            .map(|template| PreparedTemplate::prepare(builder, template, context, options))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { prepared })
+    }
+
+    /// Prepared template matching an action that is still valid for the context
+    pub fn find(
+        &self,
+        action: &AgentAction,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Option<&PreparedTemplate> {
+        self.prepared.iter().find(|prepared| {
+            prepared.template.matches(action) && prepared.is_valid_for(context, options)
+        })
+    }
+
+    /// Number of prepared templates
+    pub fn len(&self) -> usize {
+        self.prepared.len()
+    }
+
+    /// Whether no templates are prepared
+    pub fn is_empty(&self) -> bool {
+        self.prepared.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::UnlistedTokenPolicy;
+
+    fn context() -> AgentContext {
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Advanced;
+        // Templates here move fresh mints without limits of their own
+        context.spending_limits.unlisted_tokens = UnlistedTokenPolicy::AllowUnlimited;
+        context
+    }
+
+    fn assert_same_as_fresh_build(template: ActionTemplate, amount: u64) -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let context = context();
+        let options = TransactionOptions::default();
+        let blockhash = Hash::new_unique();
+
+        let prepared =
+            PreparedTemplate::prepare(&mut builder, template.clone(), &context, &options)?;
+        let action = template.instantiate(amount);
+        let patched = prepared.instantiate(&builder, &action, &context, blockhash)?;
+
+        let mut fresh = builder.build_from_action(&action, &context, &options)?;
+        fresh.message.recent_blockhash = blockhash;
+
+        assert_eq!(bincode::serialize(&patched)?, bincode::serialize(&fresh)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_patched_sol_transfer_matches_fresh_build() -> Result<()> {
+        let to = Pubkey::new_unique();
+        assert_same_as_fresh_build(ActionTemplate::TransferSol { to, memo: None }, 123_456_789)?;
+        assert_same_as_fresh_build(
+            ActionTemplate::TransferSol {
+                to,
+                memo: Some("hourly".to_string()),
+            },
+            987_654_321,
+        )
+    }
+
+    #[test]
+    fn test_patched_token_transfer_matches_fresh_build() -> Result<()> {
+        assert_same_as_fresh_build(
+            ActionTemplate::TransferToken {
+                mint: Pubkey::new_unique(),
+                to: Pubkey::new_unique(),
+                memo: Some("dca".to_string()),
+            },
+            42_000,
+        )
+    }
+
+    #[test]
+    fn test_find_falls_back_for_other_shapes() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let mut context = context();
+        let options = TransactionOptions::default();
+        let to = Pubkey::new_unique();
+
+        let set = TemplateSet::prepare(
+            &mut builder,
+            vec![ActionTemplate::TransferSol { to, memo: None }],
+            &context,
+            &options,
+        )?;
+
+        let templated = AgentAction::TransferSol {
+            to,
+            amount: Lamports::new(5),
+            memo: None,
+        };
+        assert!(set.find(&templated, &context, &options).is_some());
+
+        let other_recipient = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(5),
+            memo: None,
+        };
+        assert!(set.find(&other_recipient, &context, &options).is_none());
+
+        context.permission_level = PermissionLevel::Full;
+        assert!(set.find(&templated, &context, &options).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_falls_back_when_memo_attribution_changes() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let context = context();
+        let options = TransactionOptions::default();
+        let to = Pubkey::new_unique();
+
+        let set = TemplateSet::prepare(
+            &mut builder,
+            vec![ActionTemplate::TransferSol {
+                to,
+                memo: Some("hourly".to_string()),
+            }],
+            &context,
+            &options,
+        )?;
+        let action = AgentAction::TransferSol {
+            to,
+            amount: Lamports::new(5),
+            memo: Some("hourly".to_string()),
+        };
+        assert!(set.find(&action, &context, &options).is_some());
+
+        let attributed = TransactionOptions {
+            memo_mode: MemoMode::Attributed,
+            memo_attribution: Some(MemoAttribution::new("dca-bot", uuid::Uuid::new_v4())),
+            ..TransactionOptions::default()
+        };
+        assert!(set.find(&action, &context, &attributed).is_none());
+
+        let other_decision = TransactionOptions {
+            memo_attribution: Some(MemoAttribution::new("dca-bot", uuid::Uuid::new_v4())),
+            ..attributed.clone()
+        };
+        assert!(set.find(&action, &context, &other_decision).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_patched_amount_still_checks_spending_limits() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let context = context();
+        let options = TransactionOptions::default();
+        let template = ActionTemplate::TransferSol {
+            to: Pubkey::new_unique(),
+            memo: None,
+        };
+
+        let prepared =
+            PreparedTemplate::prepare(&mut builder, template.clone(), &context, &options)?;
+        let too_much = template.instantiate(50_000_000_000);
+        assert!(prepared
+            .instantiate(&builder, &too_much, &context, Hash::default())
+            .is_err());
+        Ok(())
+    }
+}
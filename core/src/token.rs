@@ -46,35 +46,51 @@
 
 use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use solana_sdk::{
+    account::Account,
     commitment_config::CommitmentConfig,
     instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Signature, Signer},
+    system_instruction,
     transaction::Transaction,
 };
 use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::{create_associated_token_account, create_associated_token_account_idempotent},
 };
 use spl_token::{
     instruction::{
         approve, approve_checked, burn, burn_checked, close_account, initialize_account,
         initialize_account2, initialize_account3, initialize_mint, mint_to, mint_to_checked,
-        revoke, set_authority, transfer, transfer_checked,
+        revoke, set_authority, sync_native, transfer,
     },
     state::{Account as TokenAccountState, Mint},
 };
 use spl_token_2022 as token_2022;
+use spl_token_2022::extension::{
+    metadata_pointer::MetadataPointer,
+    transfer_fee::{
+        instruction::transfer_checked_with_fee, TransferFee as ProgramTransferFee,
+        TransferFeeConfig,
+    },
+    BaseStateWithExtensions, StateWithExtensions,
+};
 use spl_token_metadata_interface::state::TokenMetadata;
 use tokio::sync::RwLock;
+use tracing::debug;
 use zeroize::{Zeroize, Zeroizing};
 
 use crate::error::{Error, Result};
-use crate::rpc::RpcClient;
+use crate::program_accounts::{token_accounts_by_owner_filter, ProgramAccountsQuery};
+use crate::rpc::{DynRpcProvider, RpcClient};
 use crate::types::PermissionLevel;
 
 /// Token program identifier (standard SPL Token)
@@ -82,6 +98,25 @@ pub const TOKEN_PROGRAM_ID: Pubkey = spl_token::ID;
 /// Token-2022 program identifier
 pub const TOKEN_2022_PROGRAM_ID: Pubkey = token_2022::ID;
 
+/// Metaplex Token Metadata program identifier
+pub const METAPLEX_METADATA_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Mint of wrapped SOL
+pub const NATIVE_MINT: Pubkey = spl_token::native_mint::ID;
+
+/// Rent-exempt minimum of a 165-byte SPL token account at the default rent
+pub const TOKEN_ACCOUNT_RENT_LAMPORTS: u64 = 2_039_280;
+
+/// How long fetched mint information stays cached by default
+pub const DEFAULT_TOKEN_INFO_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of accounts served by a single `getMultipleAccounts` call
+pub(crate) const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Basis points in a whole, the denominator of transfer fee rates
+const BASIS_POINTS_PER_WHOLE: u128 = 10_000;
+
 /// Token metadata information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenMetadataInfo {
@@ -157,7 +192,8 @@ impl TokenAccountInfo {
 
     /// Check if this is an associated token account
     pub fn is_associated(&self) -> bool {
-        get_associated_token_address(&self.owner, &self.mint) == self.address
+        get_associated_token_address_with_program_id(&self.owner, &self.mint, &self.program_id)
+            == self.address
     }
 }
 
@@ -170,8 +206,17 @@ pub struct TokenInfo {
     pub metadata: Option<TokenMetadataInfo>,
     /// Token program ID
     pub program_id: Pubkey,
+    /// Number of decimals, as stored in the mint
+    pub decimals: u8,
+    /// Mint authority, if supply can still change
+    pub mint_authority: Option<Pubkey>,
+    /// Freeze authority, if accounts can be frozen
+    pub freeze_authority: Option<Pubkey>,
     /// Total supply
     pub total_supply: u64,
+    /// Token-2022 transfer fee configuration, if the mint charges one
+    #[serde(default)]
+    pub transfer_fee: Option<TransferFeeSchedule>,
     /// Is token initialized
     pub is_initialized: bool,
     /// Current holders count (approximate)
@@ -180,6 +225,107 @@ pub struct TokenInfo {
     pub last_updated: chrono::DateTime<chrono::Utc>,
 }
 
+/// Token-2022 transfer fee in effect from an epoch on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferFee {
+    /// First epoch the fee applies to
+    pub epoch: u64,
+    /// Fee rate in basis points of the transferred amount
+    pub basis_points: u16,
+    /// Largest fee charged on one transfer, in base units
+    pub maximum_fee: u64,
+}
+
+impl TransferFee {
+    /// Fee withheld from a transfer of `amount`, rounded up as the program does
+    pub fn fee_for(&self, amount: u64) -> u64 {
+        if self.basis_points == 0 || amount == 0 {
+            return 0;
+        }
+        let fee =
+            (u128::from(amount) * u128::from(self.basis_points)).div_ceil(BASIS_POINTS_PER_WHOLE);
+        u64::try_from(fee).unwrap_or(u64::MAX).min(self.maximum_fee)
+    }
+
+    /// Smallest amount to send for the recipient to receive `net` after the fee
+    pub fn gross_for(&self, net: u64) -> Result<u64> {
+        let overflow = || Error::InvalidAmount(format!("Transfer of {} plus fees overflows", net));
+        if self.basis_points == 0 || net == 0 {
+            return Ok(net);
+        }
+        let rate = u128::from(self.basis_points);
+        if rate >= BASIS_POINTS_PER_WHOLE {
+            return net.checked_add(self.maximum_fee).ok_or_else(overflow);
+        }
+        let raw =
+            (u128::from(net) * BASIS_POINTS_PER_WHOLE).div_ceil(BASIS_POINTS_PER_WHOLE - rate);
+        if raw - u128::from(net) >= u128::from(self.maximum_fee) {
+            return net.checked_add(self.maximum_fee).ok_or_else(overflow);
+        }
+        u64::try_from(raw).map_err(|_| overflow())
+    }
+}
+
+impl From<&ProgramTransferFee> for TransferFee {
+    fn from(fee: &ProgramTransferFee) -> Self {
+        Self {
+            epoch: u64::from(fee.epoch),
+            basis_points: u16::from(fee.transfer_fee_basis_points),
+            maximum_fee: u64::from(fee.maximum_fee),
+        }
+    }
+}
+
+/// Transfer fee configuration of a Token-2022 mint
+///
+/// A fee change is scheduled by the mint's fee authority and takes effect
+/// at `newer.epoch`; until then `older` applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferFeeSchedule {
+    /// Fee in effect before `newer.epoch`
+    pub older: TransferFee,
+    /// Fee in effect from `newer.epoch` on
+    pub newer: TransferFee,
+}
+
+impl TransferFeeSchedule {
+    /// Fee in effect during `epoch`
+    pub fn fee_at(&self, epoch: u64) -> TransferFee {
+        if epoch >= self.newer.epoch {
+            self.newer
+        } else {
+            self.older
+        }
+    }
+
+    /// Whether the fee charged depends on the current epoch
+    pub fn depends_on_epoch(&self) -> bool {
+        (self.older.basis_points, self.older.maximum_fee)
+            != (self.newer.basis_points, self.newer.maximum_fee)
+    }
+}
+
+impl From<&TransferFeeConfig> for TransferFeeSchedule {
+    fn from(config: &TransferFeeConfig) -> Self {
+        Self {
+            older: TransferFee::from(&config.older_transfer_fee),
+            newer: TransferFee::from(&config.newer_transfer_fee),
+        }
+    }
+}
+
+/// What a transfer of a mint with a transfer fee delivers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferFeeHandling {
+    /// Send the requested amount; the recipient receives it minus the fee
+    #[default]
+    DeductFromAmount,
+    /// Send the requested amount plus the fee, so the recipient receives
+    /// exactly the requested amount
+    GrossUp,
+}
+
 /// Token transfer parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenTransferParams {
@@ -210,6 +356,12 @@ pub struct TokenOperationResult {
     pub operation_type: TokenOperationType,
     /// Amount involved (if applicable)
     pub amount: Option<u64>,
+    /// Token-2022 transfer fee withheld from the recipient (if any)
+    #[serde(default)]
+    pub transfer_fee: Option<u64>,
+    /// Amount the recipient receives after any transfer fee (transfers only)
+    #[serde(default)]
+    pub net_amount: Option<u64>,
     /// Source account (if applicable)
     pub source: Option<Pubkey>,
     /// Destination account (if applicable)
@@ -241,6 +393,10 @@ pub enum TokenOperationType {
     CloseAccount,
     /// Set authority
     SetAuthority,
+    /// SOL wrapped into the native mint
+    Wrap,
+    /// Wrapped SOL account closed back to SOL
+    Unwrap,
 }
 
 impl fmt::Display for TokenOperationType {
@@ -254,18 +410,42 @@ impl fmt::Display for TokenOperationType {
             TokenOperationType::CreateAccount => write!(f, "create_account"),
             TokenOperationType::CloseAccount => write!(f, "close_account"),
             TokenOperationType::SetAuthority => write!(f, "set_authority"),
+            TokenOperationType::Wrap => write!(f, "wrap"),
+            TokenOperationType::Unwrap => write!(f, "unwrap"),
         }
     }
 }
 
+/// Outcome of a bulk token balance refresh
+#[derive(Debug)]
+pub struct RefreshReport {
+    /// Accounts refreshed successfully (already written to the cache)
+    pub updated: Vec<TokenAccountInfo>,
+    /// Accounts that could not be refreshed, with the cause
+    pub failed: Vec<(Pubkey, Error)>,
+    /// Mints of the failed accounts, whose cached balances are now stale
+    pub stale_mints: Vec<Pubkey>,
+    /// Time spent on the refresh
+    pub duration: Duration,
+}
+
+impl RefreshReport {
+    /// Check whether every tracked account was refreshed
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
 /// Token manager for handling multiple tokens and operations
 pub struct TokenManager {
-    /// RPC client for blockchain operations
-    rpc_client: Arc<RwLock<RpcClient>>,
+    /// RPC provider for blockchain operations
+    rpc_client: Arc<dyn DynRpcProvider>,
     /// Token cache
     token_cache: RwLock<HashMap<Pubkey, TokenInfo>>,
     /// Account cache
     account_cache: RwLock<HashMap<Pubkey, TokenAccountInfo>>,
+    /// How long cached token information is served before refetching
+    token_info_ttl: Duration,
     /// Default commitment level
     commitment: CommitmentConfig,
 }
@@ -273,31 +453,47 @@ pub struct TokenManager {
 impl TokenManager {
     /// Create a new token manager
     pub fn new(rpc_client: RpcClient) -> Self {
-        Self {
-            rpc_client: Arc::new(RwLock::new(rpc_client)),
-            token_cache: RwLock::new(HashMap::new()),
-            account_cache: RwLock::new(HashMap::new()),
-            commitment: CommitmentConfig::confirmed(),
-        }
+        Self::with_provider(Arc::new(rpc_client), CommitmentConfig::confirmed())
     }
 
     /// Create a new token manager with custom commitment
     pub fn new_with_commitment(rpc_client: RpcClient, commitment: CommitmentConfig) -> Self {
+        Self::with_provider(Arc::new(rpc_client), commitment)
+    }
+
+    /// Create a token manager on top of a shared RPC provider
+    pub fn with_provider(
+        rpc_client: Arc<dyn DynRpcProvider>,
+        commitment: CommitmentConfig,
+    ) -> Self {
         Self {
-            rpc_client: Arc::new(RwLock::new(rpc_client)),
+            rpc_client,
             token_cache: RwLock::new(HashMap::new()),
             account_cache: RwLock::new(HashMap::new()),
+            token_info_ttl: DEFAULT_TOKEN_INFO_TTL,
             commitment,
         }
     }
 
+    /// Set how long fetched token information stays cached
+    pub fn with_token_info_ttl(mut self, ttl: Duration) -> Self {
+        self.token_info_ttl = ttl;
+        self
+    }
+
     /// Get token information
     pub async fn get_token_info(&self, mint: &Pubkey) -> Result<TokenInfo> {
         // Check cache first
         {
             let cache = self.token_cache.read().await;
             if let Some(info) = cache.get(mint) {
-                return Ok(info.clone());
+                let age = chrono::Utc::now()
+                    .signed_duration_since(info.last_updated)
+                    .to_std()
+                    .unwrap_or_default();
+                if age < self.token_info_ttl {
+                    return Ok(info.clone());
+                }
             }
         }
 
@@ -315,10 +511,9 @@ impl TokenManager {
 
     /// Fetch token information from blockchain
     async fn fetch_token_info(&self, mint: &Pubkey) -> Result<TokenInfo> {
-        let rpc_client = self.rpc_client.read().await;
-
         // Try to get account data
-        let account = rpc_client
+        let account = self
+            .rpc_client
             .get_account(mint)
             .await
             .map_err(|e| Error::Token(format!("Failed to fetch token account: {}", e)))?;
@@ -336,17 +531,34 @@ impl TokenManager {
         };
 
         // Parse mint data
-        let mint_data = Mint::unpack(&account.data)
-            .map_err(|e| Error::InvalidTokenMint(format!("Failed to parse mint data: {}", e)))?;
+        let mint_data = parse_mint(&program_id, &account.data)?;
 
-        // Try to fetch metadata
-        let metadata = self.fetch_token_metadata(mint, &program_id).await.ok();
+        // Name, symbol and URI are optional; a mint without them is still usable
+        let metadata = self
+            .fetch_token_metadata(mint, &mint_data)
+            .await
+            .map(|decoded| TokenMetadataInfo {
+                name: decoded.name,
+                symbol: decoded.symbol,
+                decimals: mint_data.decimals,
+                uri: (!decoded.uri.is_empty()).then_some(decoded.uri),
+                additional_metadata: decoded.additional_metadata,
+                update_authority: decoded.update_authority,
+                mint_authority: mint_data.mint_authority,
+                freeze_authority: mint_data.freeze_authority,
+                is_mint_authority_mutable: mint_data.mint_authority.is_some(),
+                is_freeze_authority_mutable: mint_data.freeze_authority.is_some(),
+            });
 
         Ok(TokenInfo {
             mint: *mint,
             metadata,
             program_id,
+            decimals: mint_data.decimals,
+            mint_authority: mint_data.mint_authority,
+            freeze_authority: mint_data.freeze_authority,
             total_supply: mint_data.supply,
+            transfer_fee: mint_data.transfer_fee,
             is_initialized: mint_data.is_initialized,
             holders_count: None, // Would require additional queries
             last_updated: chrono::Utc::now(),
@@ -354,27 +566,46 @@ impl TokenManager {
     }
 
     /// Fetch token metadata
+    ///
+    /// Token-2022 mints carrying their own `TokenMetadata` extension are read
+    /// in place. Otherwise the metadata pointer target (if any) or the Metaplex
+    /// metadata PDA is fetched and decoded.
     async fn fetch_token_metadata(
         &self,
         mint: &Pubkey,
-        program_id: &Pubkey,
-    ) -> Result<TokenMetadataInfo> {
-        // This is a simplified implementation
-        // In production, you would query the token metadata account
-
-        // For now, return basic metadata
-        Ok(TokenMetadataInfo {
-            name: "Unknown Token".to_string(),
-            symbol: "UNK".to_string(),
-            decimals: 9, // Default
-            uri: None,
-            additional_metadata: HashMap::new(),
-            update_authority: None,
-            mint_authority: None,
-            freeze_authority: None,
-            is_mint_authority_mutable: false,
-            is_freeze_authority_mutable: false,
-        })
+        mint_data: &ParsedMint,
+    ) -> Option<DecodedMetadata> {
+        let pointer = mint_data.metadata_pointer.filter(|address| address != mint);
+        if pointer.is_none() {
+            if let Some(embedded) = &mint_data.embedded_metadata {
+                return Some(embedded.clone());
+            }
+        }
+
+        let address = pointer.unwrap_or_else(|| metaplex_metadata_address(mint));
+        let account = match self.rpc_client.get_account(&address).await {
+            Ok(account) => account,
+            Err(e) => {
+                debug!("No metadata account {} for mint {}: {}", address, mint, e);
+                return None;
+            }
+        };
+
+        if account.owner != METAPLEX_METADATA_PROGRAM_ID {
+            debug!(
+                "Metadata account {} for mint {} is owned by unsupported program {}",
+                address, mint, account.owner
+            );
+            return None;
+        }
+
+        match decode_metaplex_metadata(&account.data) {
+            Ok(decoded) => Some(decoded),
+            Err(e) => {
+                debug!("Failed to decode metadata for mint {}: {}", mint, e);
+                None
+            }
+        }
     }
 
     /// Get token account information
@@ -401,46 +632,96 @@ impl TokenManager {
 
     /// Fetch token account information from blockchain
     async fn fetch_token_account_info(&self, token_account: &Pubkey) -> Result<TokenAccountInfo> {
-        let rpc_client = self.rpc_client.read().await;
-
         // Get account data
-        let account = rpc_client
-            .get_account(token_account)
+        let account = self
+            .rpc_client
+            .get_account_optional(token_account)
             .await
-            .map_err(|e| Error::Token(format!("Failed to fetch token account: {}", e)))?;
+            .map_err(|e| Error::Token(format!("Failed to fetch token account: {}", e)))?
+            .ok_or_else(|| {
+                Error::TokenAccountNotFound(format!("Token account not found: {}", token_account))
+            })?;
 
-        // Parse token account data
-        let token_account_state = TokenAccountState::unpack(&account.data)
-            .map_err(|e| Error::Token(format!("Failed to parse token account data: {}", e)))?;
+        parse_token_account(token_account, &account)
+    }
 
-        // Determine program ID
-        let program_id = if account.owner == TOKEN_PROGRAM_ID {
-            TOKEN_PROGRAM_ID
-        } else if account.owner == TOKEN_2022_PROGRAM_ID {
-            TOKEN_2022_PROGRAM_ID
-        } else {
-            return Err(Error::TokenAccountNotFound(format!(
-                "Account {} is not a token account",
-                token_account
-            )));
+    /// Refresh every cached token account owned by `wallet`
+    ///
+    /// Accounts are read in `getMultipleAccounts` batches. Accounts a batch
+    /// could not serve (the whole call failed, or no data came back) are
+    /// retried individually with at most `concurrency` requests in flight.
+    /// One bad account never aborts the refresh: it is recorded in the report
+    /// and its previously cached entry is left untouched.
+    pub async fn refresh_all_balances(&self, wallet: &Pubkey, concurrency: usize) -> RefreshReport {
+        let start_time = Instant::now();
+
+        // Snapshot tracked accounts (address -> mint) for this wallet
+        let tracked: HashMap<Pubkey, Pubkey> = {
+            let cache = self.account_cache.read().await;
+            cache
+                .values()
+                .filter(|info| info.owner == *wallet)
+                .map(|info| (info.address, info.mint))
+                .collect()
         };
+        let addresses: Vec<Pubkey> = tracked.keys().copied().collect();
+
+        let mut updated = Vec::new();
+        let mut failed = Vec::new();
+        let mut retry = Vec::new();
+
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            match self.rpc_client.get_multiple_accounts(chunk).await {
+                Ok(accounts) => {
+                    for (address, account) in chunk.iter().zip(accounts) {
+                        match account {
+                            Some(account) => match parse_token_account(address, &account) {
+                                Ok(info) => updated.push(info),
+                                Err(e) => failed.push((*address, e)),
+                            },
+                            None => retry.push(*address),
+                        }
+                    }
+                }
+                Err(_) => retry.extend_from_slice(chunk),
+            }
+        }
 
-        Ok(TokenAccountInfo {
-            mint: token_account_state.mint,
-            address: *token_account,
-            owner: token_account_state.owner,
-            balance: token_account_state.amount,
-            delegated_amount: if token_account_state.delegate.is_some() {
-                Some(token_account_state.delegated_amount)
-            } else {
-                None
-            },
-            delegate: token_account_state.delegate,
-            is_frozen: token_account_state.is_frozen(),
-            is_native: token_account_state.is_native(),
-            last_updated_slot: 0, // Would need slot information
-            program_id,
-        })
+        // Fall back to individual fetches with bounded concurrency
+        let results: Vec<(Pubkey, Result<TokenAccountInfo>)> = stream::iter(retry)
+            .map(|address| async move { (address, self.fetch_token_account_info(&address).await) })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+
+        for (address, result) in results {
+            match result {
+                Ok(info) => updated.push(info),
+                Err(e) => failed.push((address, e)),
+            }
+        }
+
+        // Only successful reads replace cached entries
+        {
+            let mut cache = self.account_cache.write().await;
+            for info in &updated {
+                cache.insert(info.address, info.clone());
+            }
+        }
+
+        let mut stale_mints: Vec<Pubkey> = failed
+            .iter()
+            .filter_map(|(address, _)| tracked.get(address).copied())
+            .collect();
+        stale_mints.sort();
+        stale_mints.dedup();
+
+        RefreshReport {
+            updated,
+            failed,
+            stale_mints,
+            duration: start_time.elapsed(),
+        }
     }
 
     /// Get token balance for a wallet
@@ -463,7 +744,7 @@ impl TokenManager {
         payer: &Pubkey,
         signer: &impl Signer,
     ) -> Result<TokenOperationResult> {
-        let rpc_client = self.rpc_client.read().await;
+        let rpc_client = &self.rpc_client;
 
         // Get recent blockhash
         let recent_blockhash = rpc_client.get_latest_blockhash().await?;
@@ -486,6 +767,8 @@ impl TokenManager {
             signature,
             operation_type: TokenOperationType::CreateAccount,
             amount: None,
+            transfer_fee: None,
+            net_amount: None,
             source: Some(*payer),
             destination: Some(get_associated_token_address(wallet, mint)),
             timestamp: chrono::Utc::now(),
@@ -494,7 +777,81 @@ impl TokenManager {
         })
     }
 
+    /// Wrap `amount` lamports into `wallet`'s wrapped SOL account
+    ///
+    /// The account is created if it does not exist yet; lamports already
+    /// wrapped stay there, so the balance grows by `amount`.
+    pub async fn wrap_sol(
+        &self,
+        wallet: &Pubkey,
+        amount: u64,
+        signer: &impl Signer,
+    ) -> Result<TokenOperationResult> {
+        let instructions = wrap_sol_instructions(wallet, amount)?;
+        let recent_blockhash = self.rpc_client.get_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(wallet),
+            &[signer],
+            recent_blockhash,
+        );
+        let signature = self.rpc_client.send_transaction(&transaction).await?;
+
+        let wsol_account = get_associated_token_address(wallet, &NATIVE_MINT);
+        self.clear_account_cache(&wsol_account).await;
+        Ok(TokenOperationResult {
+            signature,
+            operation_type: TokenOperationType::Wrap,
+            amount: Some(amount),
+            transfer_fee: None,
+            net_amount: None,
+            source: Some(*wallet),
+            destination: Some(wsol_account),
+            timestamp: chrono::Utc::now(),
+            success: true,
+            error: None,
+        })
+    }
+
+    /// Close `wallet`'s wrapped SOL account, returning its lamports as SOL
+    pub async fn unwrap_sol(
+        &self,
+        wallet: &Pubkey,
+        signer: &impl Signer,
+    ) -> Result<TokenOperationResult> {
+        let wsol_account = get_associated_token_address(wallet, &NATIVE_MINT);
+        let amount = self.get_balance(&NATIVE_MINT, wallet).await?;
+        let recent_blockhash = self.rpc_client.get_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            &[unwrap_sol_instruction(wallet)?],
+            Some(wallet),
+            &[signer],
+            recent_blockhash,
+        );
+        let signature = self.rpc_client.send_transaction(&transaction).await?;
+
+        self.clear_account_cache(&wsol_account).await;
+        Ok(TokenOperationResult {
+            signature,
+            operation_type: TokenOperationType::Unwrap,
+            amount: Some(amount),
+            transfer_fee: None,
+            net_amount: None,
+            source: Some(wsol_account),
+            destination: Some(*wallet),
+            timestamp: chrono::Utc::now(),
+            success: true,
+            error: None,
+        })
+    }
+
     /// Transfer tokens
+    ///
+    /// Instructions and associated token accounts use the mint's own token
+    /// program. For Token-2022 mints with a transfer fee the fee of the
+    /// current epoch is stated in a `TransferCheckedWithFee` instruction and
+    /// `options.fee_handling` decides whether `amount` is what is sent or
+    /// what the recipient receives; the result reports both.
     pub async fn transfer(
         &self,
         mint: &Pubkey,
@@ -504,34 +861,45 @@ impl TokenManager {
         owner: &impl Signer,
         options: Option<TokenTransferOptions>,
     ) -> Result<TokenOperationResult> {
-        let rpc_client = self.rpc_client.read().await;
+        let rpc_client = &self.rpc_client;
 
         // Get token info to determine program and decimals
         let token_info = self.get_token_info(mint).await?;
-        let decimals = token_info
-            .metadata
-            .as_ref()
-            .map(|m| m.decimals)
-            .unwrap_or(9);
+        let decimals = token_info.decimals;
+        let options = options.unwrap_or_default();
+
+        // Amount to send and fee withheld from it
+        let (amount, withheld) = match &token_info.transfer_fee {
+            Some(schedule) => {
+                let fee = self.active_transfer_fee(schedule).await?;
+                let gross = match options.fee_handling {
+                    TransferFeeHandling::DeductFromAmount => amount,
+                    TransferFeeHandling::GrossUp => fee.gross_for(amount)?,
+                };
+                (gross, Some(fee.fee_for(gross)))
+            }
+            None => (amount, None),
+        };
 
         // Get source and destination accounts
-        let source_ata = get_associated_token_address(from, mint);
-        let dest_ata = get_associated_token_address(to, mint);
+        let source_ata =
+            get_associated_token_address_with_program_id(from, mint, &token_info.program_id);
+        let dest_ata =
+            get_associated_token_address_with_program_id(to, mint, &token_info.program_id);
 
-        // Check if destination account exists, create if not
-        let create_dest_account = match rpc_client.get_account(&dest_ata).await {
-            Ok(_) => false,
-            Err(_) => true,
-        };
+        // Check if destination account exists, create if not. Failed
+        // lookups abort the transfer rather than pass for a missing account
+        let create_dest_account = rpc_client.get_account_optional(&dest_ata).await?.is_none();
 
         // Get recent blockhash
         let recent_blockhash = rpc_client.get_latest_blockhash().await?;
 
         let mut instructions = Vec::new();
 
-        // Create destination account if needed
+        // Create destination account if needed; the idempotent form still
+        // succeeds if someone else creates it before this transaction lands
         if create_dest_account {
-            instructions.push(create_associated_token_account(
+            instructions.push(create_associated_token_account_idempotent(
                 from, // payer
                 to,   // owner
                 mint, // mint
@@ -540,8 +908,22 @@ impl TokenManager {
         }
 
         // Add transfer instruction
-        let transfer_instruction = if options.as_ref().map(|o| o.use_checked).unwrap_or(false) {
-            transfer_checked(
+        let transfer_instruction = if let Some(fee) = withheld {
+            // Fee-bearing mints only accept transfers stating the expected fee
+            transfer_checked_with_fee(
+                &token_info.program_id,
+                &source_ata,
+                mint,
+                &dest_ata,
+                from,
+                &[], // additional signers
+                amount,
+                decimals,
+                fee,
+            )?
+        } else if options.use_checked {
+            // The Token-2022 builder accepts both token program ids
+            token_2022::instruction::transfer_checked(
                 &token_info.program_id,
                 &source_ata,
                 mint,
@@ -579,6 +961,8 @@ impl TokenManager {
             signature,
             operation_type: TokenOperationType::Transfer,
             amount: Some(amount),
+            transfer_fee: withheld,
+            net_amount: Some(amount - withheld.unwrap_or(0)),
             source: Some(source_ata),
             destination: Some(dest_ata),
             timestamp: chrono::Utc::now(),
@@ -587,19 +971,298 @@ impl TokenManager {
         })
     }
 
+    /// Transfer fee a mint charges in the current epoch
+    ///
+    /// The clock sysvar is only read when a scheduled fee change makes the
+    /// epoch matter.
+    async fn active_transfer_fee(&self, schedule: &TransferFeeSchedule) -> Result<TransferFee> {
+        if !schedule.depends_on_epoch() {
+            return Ok(schedule.newer);
+        }
+        let account = self
+            .rpc_client
+            .get_account(&solana_sdk::sysvar::clock::id())
+            .await?;
+        let clock: solana_sdk::clock::Clock = bincode::deserialize(&account.data)
+            .map_err(|e| Error::rpc(format!("Failed to decode the clock sysvar: {}", e)))?;
+        Ok(schedule.fee_at(clock.epoch))
+    }
+
+    /// Mint `amount` new tokens into `recipient_wallet`'s associated token account
+    ///
+    /// The mint is read fresh from the chain: `mint_authority` must be its
+    /// current mint authority, and fixed-supply mints are refused before
+    /// anything is sent. The recipient's account is created first if it does
+    /// not exist yet, paid for by the mint authority.
+    pub async fn mint_to(
+        &self,
+        mint: &Pubkey,
+        recipient_wallet: &Pubkey,
+        amount: u64,
+        mint_authority: &impl Signer,
+    ) -> Result<TokenOperationResult> {
+        if amount == 0 {
+            return Err(Error::InvalidAmount(
+                "Mint amount must be greater than zero".to_string(),
+            ));
+        }
+
+        // The authority may have changed since the mint was cached
+        self.clear_token_cache(mint).await;
+        let token_info = self.get_token_info(mint).await?;
+        let authority = mint_authority.pubkey();
+        check_mint_authority(&token_info, &authority)?;
+
+        let dest_ata = get_associated_token_address_with_program_id(
+            recipient_wallet,
+            mint,
+            &token_info.program_id,
+        );
+        let mut instructions = Vec::new();
+        if self
+            .rpc_client
+            .get_account_optional(&dest_ata)
+            .await?
+            .is_none()
+        {
+            instructions.push(create_associated_token_account_idempotent(
+                &authority,
+                recipient_wallet,
+                mint,
+                &token_info.program_id,
+            ));
+        }
+        // The Token-2022 builder accepts both token program ids
+        instructions.push(
+            token_2022::instruction::mint_to_checked(
+                &token_info.program_id,
+                mint,
+                &dest_ata,
+                &authority,
+                &[], // additional signers
+                amount,
+                token_info.decimals,
+            )
+            .map_err(|e| Error::token(e.to_string()))?,
+        );
+
+        let signature = self.sign_and_send(&instructions, mint_authority).await?;
+
+        // The supply changed along with the recipient's balance
+        self.clear_token_cache(mint).await;
+        self.clear_account_cache(&dest_ata).await;
+        Ok(TokenOperationResult {
+            signature,
+            operation_type: TokenOperationType::Mint,
+            amount: Some(amount),
+            transfer_fee: None,
+            net_amount: None,
+            source: None,
+            destination: Some(dest_ata),
+            timestamp: chrono::Utc::now(),
+            success: true,
+            error: None,
+        })
+    }
+
+    /// Burn `amount` tokens from `owner_wallet`'s associated token account
+    ///
+    /// The account balance is read fresh and must cover `amount`; nothing is
+    /// sent otherwise.
+    pub async fn burn(
+        &self,
+        mint: &Pubkey,
+        owner_wallet: &Pubkey,
+        amount: u64,
+        owner: &impl Signer,
+    ) -> Result<TokenOperationResult> {
+        check_signer(owner, owner_wallet)?;
+        if amount == 0 {
+            return Err(Error::InvalidAmount(
+                "Burn amount must be greater than zero".to_string(),
+            ));
+        }
+
+        let token_info = self.get_token_info(mint).await?;
+        let source_ata = get_associated_token_address_with_program_id(
+            owner_wallet,
+            mint,
+            &token_info.program_id,
+        );
+
+        // Check the balance before burning
+        self.clear_account_cache(&source_ata).await;
+        let balance = match self.get_token_account_info(&source_ata).await {
+            Ok(info) => info.balance,
+            Err(Error::TokenAccountNotFound(_)) => 0,
+            Err(e) => return Err(e),
+        };
+        if amount > balance {
+            return Err(Error::InsufficientFunds {
+                required: amount,
+                available: balance,
+            });
+        }
+
+        let instruction = token_2022::instruction::burn_checked(
+            &token_info.program_id,
+            &source_ata,
+            mint,
+            owner_wallet,
+            &[], // additional signers
+            amount,
+            token_info.decimals,
+        )
+        .map_err(|e| Error::token(e.to_string()))?;
+        let signature = self.sign_and_send(&[instruction], owner).await?;
+
+        self.clear_token_cache(mint).await;
+        self.clear_account_cache(&source_ata).await;
+        Ok(TokenOperationResult {
+            signature,
+            operation_type: TokenOperationType::Burn,
+            amount: Some(amount),
+            transfer_fee: None,
+            net_amount: None,
+            source: Some(source_ata),
+            destination: None,
+            timestamp: chrono::Utc::now(),
+            success: true,
+            error: None,
+        })
+    }
+
+    /// Allow `delegate` to move up to `amount` of `owner`'s tokens
+    ///
+    /// Replaces any delegation already set on `owner`'s associated token
+    /// account.
+    pub async fn approve_delegate(
+        &self,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        delegate: &Pubkey,
+        amount: u64,
+        owner_signer: &impl Signer,
+    ) -> Result<TokenOperationResult> {
+        check_signer(owner_signer, owner)?;
+
+        let token_info = self.get_token_info(mint).await?;
+        let source_ata =
+            get_associated_token_address_with_program_id(owner, mint, &token_info.program_id);
+        let instruction = token_2022::instruction::approve_checked(
+            &token_info.program_id,
+            &source_ata,
+            mint,
+            delegate,
+            owner,
+            &[], // additional signers
+            amount,
+            token_info.decimals,
+        )
+        .map_err(|e| Error::token(e.to_string()))?;
+        let signature = self.sign_and_send(&[instruction], owner_signer).await?;
+
+        self.clear_account_cache(&source_ata).await;
+        Ok(TokenOperationResult {
+            signature,
+            operation_type: TokenOperationType::Approve,
+            amount: Some(amount),
+            transfer_fee: None,
+            net_amount: None,
+            source: Some(source_ata),
+            destination: Some(*delegate),
+            timestamp: chrono::Utc::now(),
+            success: true,
+            error: None,
+        })
+    }
+
+    /// Remove any delegation from `owner`'s associated token account
+    pub async fn revoke_delegate(
+        &self,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        owner_signer: &impl Signer,
+    ) -> Result<TokenOperationResult> {
+        check_signer(owner_signer, owner)?;
+
+        let token_info = self.get_token_info(mint).await?;
+        let source_ata =
+            get_associated_token_address_with_program_id(owner, mint, &token_info.program_id);
+        let instruction = token_2022::instruction::revoke(
+            &token_info.program_id,
+            &source_ata,
+            owner,
+            &[], // additional signers
+        )
+        .map_err(|e| Error::token(e.to_string()))?;
+        let signature = self.sign_and_send(&[instruction], owner_signer).await?;
+
+        self.clear_account_cache(&source_ata).await;
+        Ok(TokenOperationResult {
+            signature,
+            operation_type: TokenOperationType::Revoke,
+            amount: None,
+            transfer_fee: None,
+            net_amount: None,
+            source: Some(source_ata),
+            destination: None,
+            timestamp: chrono::Utc::now(),
+            success: true,
+            error: None,
+        })
+    }
+
+    /// Sign `instructions` with `signer`, who also pays the fee, and send them
+    async fn sign_and_send(
+        &self,
+        instructions: &[Instruction],
+        signer: &impl Signer,
+    ) -> Result<Signature> {
+        let recent_blockhash = self.rpc_client.get_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signer.pubkey()),
+            &[signer],
+            recent_blockhash,
+        );
+        self.rpc_client.send_transaction(&transaction).await
+    }
+
     /// Get all token accounts for a wallet
+    ///
+    /// Discovers SPL Token and Token-2022 accounts owned by `wallet` with
+    /// `getProgramAccounts` and caches them, so later balance refreshes
+    /// track them too. Accounts that fail to parse are skipped.
     pub async fn get_wallet_token_accounts(
         &self,
         wallet: &Pubkey,
     ) -> Result<Vec<TokenAccountInfo>> {
-        let rpc_client = self.rpc_client.read().await;
+        let mut found = Vec::new();
+        for program_id in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+            let mut query = ProgramAccountsQuery::new(program_id)
+                .filter(token_accounts_by_owner_filter(wallet))
+                .commitment(self.commitment);
+            // Token-2022 accounts grow with their extensions
+            if program_id == TOKEN_PROGRAM_ID {
+                query = query.data_size(TokenAccountState::LEN as u64);
+            }
 
-        // This is a simplified implementation
-        // In production, you would use getTokenAccountsByOwner or getProgramAccounts
+            let accounts = query.fetch(self.rpc_client.as_ref()).await?;
+            for (address, account) in accounts {
+                match parse_token_account(&address, &account) {
+                    Ok(info) if info.owner == *wallet => found.push(info),
+                    Ok(_) => {}
+                    Err(e) => debug!("Skipping token account {}: {}", address, e),
+                }
+            }
+        }
 
-        // For now, return empty vector
-        // TODO: Implement proper token account discovery
-        Ok(Vec::new())
+        let mut cache = self.account_cache.write().await;
+        for info in &found {
+            cache.insert(info.address, info.clone());
+        }
+        Ok(found)
     }
 
     /// Clear cache for a specific token
@@ -637,6 +1300,259 @@ impl TokenManager {
     }
 }
 
+/// Parse a raw account owned by either token program
+pub(crate) fn parse_token_account(address: &Pubkey, account: &Account) -> Result<TokenAccountInfo> {
+    let parse_error = |e: solana_sdk::program_error::ProgramError| {
+        Error::Token(format!("Failed to parse token account data: {}", e))
+    };
+
+    let mut info = if account.owner == TOKEN_PROGRAM_ID {
+        let state = TokenAccountState::unpack(&account.data).map_err(parse_error)?;
+        let mut info = TokenAccountInfo::new(
+            state.mint,
+            *address,
+            state.owner,
+            state.amount,
+            TOKEN_PROGRAM_ID,
+        );
+        info.delegate = state.delegate.into();
+        info.delegated_amount = info.delegate.map(|_| state.delegated_amount);
+        info.is_frozen = state.is_frozen();
+        info.is_native = state.is_native();
+        info
+    } else if account.owner == TOKEN_2022_PROGRAM_ID {
+        // Token-2022 accounts may carry extensions after the base layout
+        let state = StateWithExtensions::<token_2022::state::Account>::unpack(&account.data)
+            .map_err(parse_error)?
+            .base;
+        let mut info = TokenAccountInfo::new(
+            state.mint,
+            *address,
+            state.owner,
+            state.amount,
+            TOKEN_2022_PROGRAM_ID,
+        );
+        info.delegate = state.delegate.into();
+        info.delegated_amount = info.delegate.map(|_| state.delegated_amount);
+        info.is_frozen = state.is_frozen();
+        info.is_native = state.is_native();
+        info
+    } else {
+        return Err(Error::TokenAccountNotFound(format!(
+            "Account {} is not a token account",
+            address
+        )));
+    };
+
+    info.last_updated_slot = 0; // Would need slot information
+    Ok(info)
+}
+
+/// Mint fields shared by SPL Token and Token-2022 mints
+struct ParsedMint {
+    supply: u64,
+    decimals: u8,
+    is_initialized: bool,
+    mint_authority: Option<Pubkey>,
+    freeze_authority: Option<Pubkey>,
+    /// Token-2022 metadata pointer target
+    metadata_pointer: Option<Pubkey>,
+    /// Token-2022 metadata stored in the mint itself
+    embedded_metadata: Option<DecodedMetadata>,
+    /// Token-2022 transfer fee configuration
+    transfer_fee: Option<TransferFeeSchedule>,
+}
+
+/// Name, symbol and URI read from a metadata source
+#[derive(Debug, Clone)]
+struct DecodedMetadata {
+    name: String,
+    symbol: String,
+    uri: String,
+    update_authority: Option<Pubkey>,
+    additional_metadata: HashMap<String, String>,
+}
+
+impl From<TokenMetadata> for DecodedMetadata {
+    fn from(metadata: TokenMetadata) -> Self {
+        Self {
+            name: metadata.name,
+            symbol: metadata.symbol,
+            uri: metadata.uri,
+            update_authority: metadata.update_authority.into(),
+            additional_metadata: metadata.additional_metadata.into_iter().collect(),
+        }
+    }
+}
+
+/// Parse mint data owned by either token program
+fn parse_mint(program_id: &Pubkey, data: &[u8]) -> Result<ParsedMint> {
+    let parse_error = |e: solana_sdk::program_error::ProgramError| {
+        Error::InvalidTokenMint(format!("Failed to parse mint data: {}", e))
+    };
+
+    if *program_id == TOKEN_PROGRAM_ID {
+        let mint = Mint::unpack(data).map_err(parse_error)?;
+        return Ok(ParsedMint {
+            supply: mint.supply,
+            decimals: mint.decimals,
+            is_initialized: mint.is_initialized,
+            mint_authority: mint.mint_authority.into(),
+            freeze_authority: mint.freeze_authority.into(),
+            metadata_pointer: None,
+            embedded_metadata: None,
+            transfer_fee: None,
+        });
+    }
+
+    // Token-2022 mints may carry extensions after the base layout
+    let state =
+        StateWithExtensions::<token_2022::state::Mint>::unpack(data).map_err(parse_error)?;
+    let metadata_pointer = state
+        .get_extension::<MetadataPointer>()
+        .ok()
+        .and_then(|pointer| Option::<Pubkey>::from(pointer.metadata_address));
+    let embedded_metadata = state
+        .get_variable_len_extension::<TokenMetadata>()
+        .ok()
+        .map(DecodedMetadata::from);
+    let transfer_fee = state
+        .get_extension::<TransferFeeConfig>()
+        .ok()
+        .map(TransferFeeSchedule::from);
+
+    Ok(ParsedMint {
+        supply: state.base.supply,
+        decimals: state.base.decimals,
+        is_initialized: state.base.is_initialized,
+        mint_authority: state.base.mint_authority.into(),
+        freeze_authority: state.base.freeze_authority.into(),
+        metadata_pointer,
+        embedded_metadata,
+        transfer_fee,
+    })
+}
+
+/// Check that `authority` can mint new supply of the token described by `info`
+///
+/// Fails for fixed-supply mints, whose mint authority was removed, and for
+/// any key other than the current mint authority.
+pub fn check_mint_authority(info: &TokenInfo, authority: &Pubkey) -> Result<()> {
+    match info.mint_authority {
+        None => Err(Error::InvalidTokenMint(format!(
+            "Mint {} has a fixed supply: its mint authority was removed",
+            info.mint
+        ))),
+        Some(expected) if expected != *authority => Err(Error::permission_denied(format!(
+            "{} is not the mint authority of {} (the authority is {})",
+            authority, info.mint, expected
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Check that `signer` holds the key of `owner`
+fn check_signer(signer: &impl Signer, owner: &Pubkey) -> Result<()> {
+    let key = signer.pubkey();
+    if key != *owner {
+        return Err(Error::permission_denied(format!(
+            "Signer {} is not the owner {}",
+            key, owner
+        )));
+    }
+    Ok(())
+}
+
+/// Derive the Metaplex metadata account of a mint
+pub fn metaplex_metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            METAPLEX_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &METAPLEX_METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Instructions wrapping `lamports` into `owner`'s wrapped SOL account
+///
+/// Creates the associated account idempotently, transfers the lamports
+/// into it and syncs its token balance with `sync_native`.
+pub fn wrap_sol_instructions(owner: &Pubkey, lamports: u64) -> Result<Vec<Instruction>> {
+    if lamports == 0 {
+        return Err(Error::InvalidAmount(
+            "Wrap amount must be greater than zero".to_string(),
+        ));
+    }
+    let wsol_account = get_associated_token_address(owner, &NATIVE_MINT);
+    Ok(vec![
+        create_associated_token_account_idempotent(owner, owner, &NATIVE_MINT, &TOKEN_PROGRAM_ID),
+        system_instruction::transfer(owner, &wsol_account, lamports),
+        sync_native(&TOKEN_PROGRAM_ID, &wsol_account).map_err(|e| Error::token(e.to_string()))?,
+    ])
+}
+
+/// Instruction closing `owner`'s wrapped SOL account back to `owner`
+///
+/// The whole balance, rent reserve included, is returned as SOL.
+pub fn unwrap_sol_instruction(owner: &Pubkey) -> Result<Instruction> {
+    let wsol_account = get_associated_token_address(owner, &NATIVE_MINT);
+    close_account(&TOKEN_PROGRAM_ID, &wsol_account, owner, owner, &[])
+        .map_err(|e| Error::token(e.to_string()))
+}
+
+/// Lamports that must be wrapped to bring a wrapped SOL balance up to `target`
+pub fn wrap_shortfall(current: u64, target: u64) -> u64 {
+    target.saturating_sub(current)
+}
+
+/// Decode the leading fields of a Metaplex `Metadata` account
+///
+/// Layout: key (1), update authority (32), mint (32), then borsh strings for
+/// name, symbol and URI, which Metaplex pads with NUL bytes.
+fn decode_metaplex_metadata(data: &[u8]) -> Result<DecodedMetadata> {
+    const METADATA_V1_KEY: u8 = 4;
+
+    fn read_string(data: &[u8], offset: &mut usize) -> Result<String> {
+        let len_bytes: [u8; 4] = data
+            .get(*offset..*offset + 4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| Error::token("Truncated metadata string length"))?;
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        let bytes = data
+            .get(*offset + 4..*offset + 4 + len)
+            .ok_or_else(|| Error::token("Truncated metadata string"))?;
+        *offset += 4 + len;
+        Ok(String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string())
+    }
+
+    if data.first() != Some(&METADATA_V1_KEY) {
+        return Err(Error::token("Account is not a Metaplex metadata account"));
+    }
+    let update_authority = data
+        .get(1..33)
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(Pubkey::new_from_array)
+        .ok_or_else(|| Error::token("Truncated metadata account"))?;
+
+    let mut offset = 1 + 32 + 32;
+    let name = read_string(data, &mut offset)?;
+    let symbol = read_string(data, &mut offset)?;
+    let uri = read_string(data, &mut offset)?;
+
+    Ok(DecodedMetadata {
+        name,
+        symbol,
+        uri,
+        update_authority: Some(update_authority),
+        additional_metadata: HashMap::new(),
+    })
+}
+
 /// Token transfer options
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenTransferOptions {
@@ -650,6 +1566,9 @@ pub struct TokenTransferOptions {
     pub compute_unit_limit: Option<u32>,
     /// Skip preflight checks
     pub skip_preflight: bool,
+    /// What transfers of mints with a transfer fee deliver
+    #[serde(default)]
+    pub fee_handling: TransferFeeHandling,
 }
 
 impl Default for TokenTransferOptions {
@@ -660,23 +1579,29 @@ impl Default for TokenTransferOptions {
             priority_fee: None,
             compute_unit_limit: None,
             skip_preflight: false,
+            fee_handling: TransferFeeHandling::DeductFromAmount,
         }
     }
 }
 
 /// Token operations trait for higher-level operations
-#[async_trait]
+///
+/// Not object safe; implementations write plain `async fn`s.
 pub trait TokenOperations {
     /// Check if a wallet has sufficient token balance
-    async fn has_sufficient_balance(
+    fn has_sufficient_balance(
         &self,
         mint: &Pubkey,
         wallet: &Pubkey,
         required_amount: u64,
-    ) -> Result<bool>;
+    ) -> impl Future<Output = Result<bool>> + Send;
 
     /// Get token value in SOL (requires price oracle)
-    async fn get_token_value_in_sol(&self, mint: &Pubkey, amount: u64) -> Result<f64>;
+    fn get_token_value_in_sol(
+        &self,
+        mint: &Pubkey,
+        amount: u64,
+    ) -> impl Future<Output = Result<f64>> + Send;
 
     /// Validate token transfer parameters
     fn validate_transfer_params(
@@ -686,7 +1611,6 @@ pub trait TokenOperations {
     ) -> Result<()>;
 }
 
-#[async_trait]
 impl TokenOperations for TokenManager {
     async fn has_sufficient_balance(
         &self,
@@ -737,6 +1661,7 @@ impl TokenOperations for TokenManager {
 /// Utility functions for token operations
 pub mod utils {
     use super::*;
+    use crate::sol::{Lamports, TokenAmount, SOL_DECIMALS};
 
     /// Convert lamports to token amount based on decimals
     pub fn lamports_to_token_amount(lamports: u64, decimals: u8) -> f64 {
@@ -748,14 +1673,94 @@ pub mod utils {
         (amount * 10_f64.powi(decimals as i32)).round() as u64
     }
 
-    /// Format token amount with symbol
+    /// Format an amount in base units exactly, with thousands separators and symbol
+    ///
+    /// `1234500000` with 6 decimals and `USDC` is `1,234.5 USDC`; trailing
+    /// zeros of the fraction are dropped, digits never are.
     pub fn format_token_amount(amount: u64, decimals: u8, symbol: Option<&str>) -> String {
-        let formatted_amount = lamports_to_token_amount(amount, decimals);
+        let exact = TokenAmount::new(amount, decimals).to_ui_string();
+        let (whole, fraction) = exact.split_once('.').unwrap_or((&exact, ""));
+        let mut formatted = group_thousands(whole);
+        if !fraction.is_empty() {
+            formatted.push('.');
+            formatted.push_str(fraction);
+        }
         if let Some(sym) = symbol {
-            format!("{} {}", formatted_amount, sym)
-        } else {
-            format!("{}", formatted_amount)
+            formatted.push(' ');
+            formatted.push_str(sym);
         }
+        formatted
+    }
+
+    /// Format a SOL amount like [`format_token_amount`], e.g. `1,000.25 SOL`
+    pub fn format_sol(lamports: Lamports) -> String {
+        format_token_amount(lamports.as_u64(), SOL_DECIMALS, Some("SOL"))
+    }
+
+    /// Parse a SOL amount as typed by a user
+    ///
+    /// Takes decimal SOL with an optional `sol` suffix (`0.1`, `1.5sol`,
+    /// `1.5 SOL`) or whole lamports with a `lamports` suffix (`2500lamports`).
+    /// `_` may group digits, as in `100_000`. Amounts finer than a lamport
+    /// are refused rather than rounded.
+    pub fn parse_sol_amount(input: &str) -> Result<Lamports> {
+        let lower = input.trim().to_ascii_lowercase();
+        if let Some(lamports) = lower.strip_suffix("lamports") {
+            let digits = strip_digit_separators(lamports.trim_end(), input)?;
+            if digits.contains('.') {
+                return Err(Error::InvalidAmount(format!(
+                    "Lamport amounts are whole numbers: '{}'",
+                    input
+                )));
+            }
+            return TokenAmount::from_ui_str(&digits, 0).map(|amount| Lamports::new(amount.raw));
+        }
+        let sol = lower.strip_suffix("sol").unwrap_or(&lower).trim_end();
+        Lamports::from_sol_str(&strip_digit_separators(sol, input)?)
+    }
+
+    /// Parse an amount of a token with `decimals` as typed by a user
+    ///
+    /// Takes decimal whole tokens, followed by the token's `symbol` if one
+    /// is given (`12.5`, `12.5 USDC`); `_` may group digits. Amounts finer
+    /// than the mint's base unit are refused.
+    pub fn parse_token_amount(
+        input: &str,
+        decimals: u8,
+        symbol: Option<&str>,
+    ) -> Result<TokenAmount> {
+        let lower = input.trim().to_ascii_lowercase();
+        let amount = symbol
+            .map(str::to_ascii_lowercase)
+            .and_then(|symbol| lower.strip_suffix(symbol.as_str()).map(str::to_string))
+            .unwrap_or_else(|| lower.clone());
+        TokenAmount::from_ui_str(&strip_digit_separators(amount.trim_end(), input)?, decimals)
+    }
+
+    /// Remove `_` digit separators, each of which must sit between two digits
+    fn strip_digit_separators(amount: &str, input: &str) -> Result<String> {
+        let bytes = amount.as_bytes();
+        for (i, byte) in bytes.iter().enumerate() {
+            let between_digits = i > 0
+                && bytes[i - 1].is_ascii_digit()
+                && bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+            if *byte == b'_' && !between_digits {
+                return Err(Error::InvalidAmount(format!("Invalid amount: '{}'", input)));
+            }
+        }
+        Ok(amount.replace('_', ""))
+    }
+
+    /// Insert `,` between groups of three digits
+    fn group_thousands(digits: &str) -> String {
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        grouped
     }
 
     /// Check if a pubkey is a valid token program ID
@@ -764,23 +1769,23 @@ pub mod utils {
     }
 
     /// Get associated token account address with specified program
+    ///
+    /// The token program is one of the derivation seeds, so the same owner
+    /// and mint give different addresses under SPL Token and Token-2022.
     pub fn get_associated_token_address_with_program(
         wallet: &Pubkey,
         mint: &Pubkey,
         program_id: &Pubkey,
     ) -> Pubkey {
-        if program_id == &TOKEN_PROGRAM_ID {
-            get_associated_token_address(wallet, mint)
-        } else {
-            // Token-2022 uses the same derivation
-            get_associated_token_address(wallet, mint)
-        }
+        get_associated_token_address_with_program_id(wallet, mint, program_id)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rpc::mock::{mint_account, MockRpc};
+    use crate::sol::{Lamports, TokenAmount, LAMPORTS_PER_SOL};
     use solana_sdk::signature::Keypair;
 
     #[test]
@@ -827,6 +1832,72 @@ mod tests {
         assert_eq!(formatted_no_symbol, "1.5");
     }
 
+    #[test]
+    fn test_utils_format_amounts_by_decimals() {
+        // No decimals
+        assert_eq!(
+            utils::format_token_amount(1_234_567, 0, Some("PTS")),
+            "1,234,567 PTS"
+        );
+        assert_eq!(utils::format_token_amount(999, 0, None), "999");
+        // USDC-like, 6 decimals
+        assert_eq!(
+            utils::format_token_amount(1_234_500_000, 6, Some("USDC")),
+            "1,234.5 USDC"
+        );
+        assert_eq!(utils::format_token_amount(1, 6, None), "0.000001");
+        assert_eq!(utils::format_token_amount(1_000_000, 6, None), "1");
+        // SOL, 9 decimals, exact beyond f64 precision
+        assert_eq!(
+            utils::format_sol(Lamports::new(12_345_678_901_234_567_891)),
+            "12,345,678,901.234567891 SOL"
+        );
+        assert_eq!(utils::format_sol(Lamports::ZERO), "0 SOL");
+    }
+
+    #[test]
+    fn test_utils_parse_amount_suffixes() -> Result<()> {
+        let sol = |lamports| Lamports::new(lamports);
+        assert_eq!(utils::parse_sol_amount("0.1")?, sol(100_000_000));
+        assert_eq!(utils::parse_sol_amount("1.5sol")?, sol(1_500_000_000));
+        assert_eq!(utils::parse_sol_amount(" 1.5 SOL ")?, sol(1_500_000_000));
+        assert_eq!(
+            utils::parse_sol_amount("100_000")?,
+            sol(100_000 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(utils::parse_sol_amount("2500lamports")?, sol(2_500));
+        assert_eq!(utils::parse_sol_amount("1_000 lamports")?, sol(1_000));
+        for invalid in [
+            "", "sol", "lamports", "_1", "1_", "1__0", "1._5", "1.5 usdc", "-1", "1e9",
+        ] {
+            assert!(utils::parse_sol_amount(invalid).is_err(), "{}", invalid);
+        }
+
+        assert_eq!(
+            utils::parse_token_amount("1_250.5 usdc", 6, Some("USDC"))?,
+            TokenAmount::new(1_250_500_000, 6)
+        );
+        assert_eq!(
+            utils::parse_token_amount("42", 0, None)?,
+            TokenAmount::new(42, 0)
+        );
+        assert!(utils::parse_token_amount("12.5 USDC", 6, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_utils_parse_amount_rejects_excess_precision() {
+        // Finer than one lamport, or than the mint's base unit
+        assert!(matches!(
+            utils::parse_sol_amount("0.0000000001"),
+            Err(Error::InvalidAmount(_))
+        ));
+        assert!(utils::parse_sol_amount("1.5lamports").is_err());
+        assert!(utils::parse_token_amount("0.0000001", 6, None).is_err());
+        assert!(utils::parse_token_amount("1.5", 0, None).is_err());
+        assert!(utils::parse_token_amount("0.000000001", 9, None).is_ok());
+    }
+
     #[test]
     fn test_utils_is_token_program_id() {
         assert!(utils::is_token_program_id(&TOKEN_PROGRAM_ID));
@@ -856,5 +1927,752 @@ mod tests {
         assert!(options.priority_fee.is_none());
         assert!(options.compute_unit_limit.is_none());
         assert!(!options.skip_preflight);
+        assert_eq!(options.fee_handling, TransferFeeHandling::DeductFromAmount);
+    }
+
+    fn fee(epoch: u64, basis_points: u16, maximum_fee: u64) -> TransferFee {
+        TransferFee {
+            epoch,
+            basis_points,
+            maximum_fee,
+        }
+    }
+
+    #[test]
+    fn test_transfer_fee_math() -> Result<()> {
+        // 1% capped at 1_000 base units
+        let one_percent = fee(0, 100, 1_000);
+        assert_eq!(one_percent.fee_for(10_000), 100);
+        // Rounded up like the program
+        assert_eq!(one_percent.fee_for(10_001), 101);
+        assert_eq!(one_percent.fee_for(1), 1);
+        assert_eq!(one_percent.fee_for(1_000_000_000), 1_000);
+        assert_eq!(one_percent.gross_for(9_900)?, 10_000);
+        assert_eq!(one_percent.gross_for(10_000)?, 10_102);
+        assert_eq!(one_percent.gross_for(1_000_000_000)?, 1_000_001_000);
+
+        let free = fee(0, 0, 1_000);
+        assert_eq!(free.fee_for(u64::MAX), 0);
+        assert_eq!(free.gross_for(u64::MAX)?, u64::MAX);
+
+        // A 100% rate takes everything up to the cap
+        let confiscatory = fee(0, 10_000, 500);
+        assert_eq!(confiscatory.fee_for(300), 300);
+        assert_eq!(confiscatory.fee_for(800), 500);
+        assert_eq!(confiscatory.gross_for(300)?, 800);
+
+        assert!(matches!(
+            one_percent.gross_for(u64::MAX),
+            Err(Error::InvalidAmount(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gross_up_delivers_exactly_the_net_amount() -> Result<()> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for _ in 0..100_000 {
+            // xorshift64: rates up to 100%, caps and amounts of any size
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let rate = fee(0, (state % 10_001) as u16, state >> 20);
+            let net = state >> (state % 64);
+
+            let gross = match rate.gross_for(net) {
+                Ok(gross) => gross,
+                Err(Error::InvalidAmount(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            assert_eq!(gross - rate.fee_for(gross), net, "{:?} net {}", rate, net);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fee_schedule_follows_the_epoch() {
+        let schedule = TransferFeeSchedule {
+            older: fee(0, 50, 1_000),
+            newer: fee(10, 100, 1_000),
+        };
+        assert!(schedule.depends_on_epoch());
+        assert_eq!(schedule.fee_at(9).basis_points, 50);
+        assert_eq!(schedule.fee_at(10).basis_points, 100);
+
+        let unchanged = TransferFeeSchedule {
+            older: fee(0, 100, 1_000),
+            newer: fee(10, 100, 1_000),
+        };
+        assert!(!unchanged.depends_on_epoch());
+    }
+
+    #[test]
+    fn test_associated_addresses_depend_on_the_token_program() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let classic =
+            utils::get_associated_token_address_with_program(&owner, &mint, &TOKEN_PROGRAM_ID);
+        let token_2022 =
+            utils::get_associated_token_address_with_program(&owner, &mint, &TOKEN_2022_PROGRAM_ID);
+        assert_eq!(classic, get_associated_token_address(&owner, &mint));
+        assert_ne!(classic, token_2022);
+        assert_eq!(
+            token_2022,
+            get_associated_token_address_with_program_id(&owner, &mint, &TOKEN_2022_PROGRAM_ID)
+        );
+
+        let info = TokenAccountInfo::new(mint, token_2022, owner, 0, TOKEN_2022_PROGRAM_ID);
+        assert!(info.is_associated());
+        let misfiled = TokenAccountInfo::new(mint, token_2022, owner, 0, TOKEN_PROGRAM_ID);
+        assert!(!misfiled.is_associated());
+    }
+
+    /// Track `count` accounts for `wallet` in a fresh manager backed by `rpc`
+    async fn tracked_manager(
+        rpc: Arc<MockRpc>,
+        wallet: Pubkey,
+        count: usize,
+    ) -> Result<(TokenManager, Vec<(Pubkey, Pubkey)>)> {
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let mut accounts = Vec::new();
+        for _ in 0..count {
+            let (address, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+            rpc.set_token_account(address, mint, wallet, 100)?;
+            manager.get_token_account_info(&address).await?;
+            accounts.push((address, mint));
+        }
+        Ok((manager, accounts))
+    }
+
+    #[tokio::test]
+    async fn test_wallet_token_accounts_are_discovered_and_tracked() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = Pubkey::new_unique();
+        let (mine, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_token_account(mine, mint, wallet, 250)?;
+        rpc.set_token_account(Pubkey::new_unique(), mint, Pubkey::new_unique(), 999)?;
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+
+        let accounts = manager.get_wallet_token_accounts(&wallet).await?;
+        assert_eq!(accounts.len(), 1);
+        assert_eq!((accounts[0].address, accounts[0].balance), (mine, 250));
+
+        // Discovered accounts are refreshed with the tracked ones
+        let report = manager.refresh_all_balances(&wallet, 4).await;
+        assert_eq!(report.updated.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_all_balances_reports_partial_failures() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = Pubkey::new_unique();
+        let (manager, accounts) = tracked_manager(rpc.clone(), wallet, 6).await?;
+
+        // Balances move on-chain; two accounts start failing
+        for (address, mint) in &accounts {
+            rpc.set_token_account(*address, *mint, wallet, 500)?;
+        }
+        let (bad_a, bad_mint_a) = accounts[1];
+        let (bad_b, bad_mint_b) = accounts[4];
+        rpc.fail_account(bad_a);
+        rpc.fail_account(bad_b);
+
+        let report = manager.refresh_all_balances(&wallet, 2).await;
+
+        assert!(!report.is_complete());
+        assert_eq!(report.updated.len(), 4);
+        assert!(report.updated.iter().all(|info| info.balance == 500));
+        assert_eq!(report.failed.len(), 2);
+        let mut expected_stale = vec![bad_mint_a, bad_mint_b];
+        expected_stale.sort();
+        assert_eq!(report.stale_mints, expected_stale);
+
+        // Failed accounts keep their previous cached balance
+        assert_eq!(manager.get_token_account_info(&bad_a).await?.balance, 100);
+        assert_eq!(
+            manager
+                .get_token_account_info(&accounts[0].0)
+                .await?
+                .balance,
+            500
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_all_balances_falls_back_when_batch_fails() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = Pubkey::new_unique();
+        let (manager, accounts) = tracked_manager(rpc.clone(), wallet, 3).await?;
+
+        // Accounts owned by other wallets are ignored
+        let other = Pubkey::new_unique();
+        let foreign = Pubkey::new_unique();
+        rpc.set_token_account(foreign, Pubkey::new_unique(), other, 1)?;
+        manager.get_token_account_info(&foreign).await?;
+
+        rpc.fail_batches();
+        let report = manager.refresh_all_balances(&wallet, 4).await;
+
+        assert!(report.is_complete());
+        assert_eq!(report.updated.len(), accounts.len());
+        assert_eq!(rpc.batch_calls(), 1);
+
+        Ok(())
+    }
+
+    /// Metaplex metadata account with NUL-padded strings, as stored on chain
+    fn metaplex_account(name: &str, symbol: &str, uri: &str) -> Account {
+        let mut data = vec![4u8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        for (value, padded_len) in [(name, 32), (symbol, 10), (uri, 200)] {
+            let mut bytes = value.as_bytes().to_vec();
+            bytes.resize(padded_len, 0);
+            data.extend_from_slice(&(padded_len as u32).to_le_bytes());
+            data.extend_from_slice(&bytes);
+        }
+        // seller fee, no creators, primary sale, is mutable
+        data.extend_from_slice(&[0, 0, 0, 0, 1]);
+
+        Account {
+            lamports: 5_616_720,
+            data,
+            owner: METAPLEX_METADATA_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_checked_uses_mint_decimals() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let mint = Pubkey::new_unique();
+        rpc.set_account(mint, mint_account(6, Some(Pubkey::new_unique()))?);
+        rpc.set_account(
+            metaplex_metadata_address(&mint),
+            metaplex_account("USD Coin", "USDC", ""),
+        );
+
+        let info = manager.get_token_info(&mint).await?;
+        assert_eq!(info.decimals, 6);
+        let metadata = info
+            .metadata
+            .ok_or_else(|| Error::token("metadata missing"))?;
+        assert_eq!(metadata.name, "USD Coin");
+        assert_eq!(metadata.symbol, "USDC");
+        assert_eq!(metadata.decimals, 6);
+        assert!(metadata.uri.is_none());
+
+        manager
+            .transfer(
+                &mint,
+                &owner.pubkey(),
+                &Pubkey::new_unique(),
+                1_500_000,
+                &owner,
+                None,
+            )
+            .await?;
+
+        let sent = rpc.sent_transactions();
+        let message = &sent[0].message;
+        let transfer = message
+            .instructions
+            .iter()
+            .find(|ix| message.account_keys[ix.program_id_index as usize] == TOKEN_PROGRAM_ID)
+            .ok_or_else(|| Error::token("transfer instruction missing"))?;
+        // TransferChecked: tag 12, amount (u64 LE), decimals
+        assert_eq!(transfer.data[0], 12);
+        assert_eq!(transfer.data[1..9], 1_500_000u64.to_le_bytes());
+        assert_eq!(transfer.data[9], 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_creates_missing_destination_account() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let mint = Pubkey::new_unique();
+        rpc.set_account(mint, mint_account(6, Some(Pubkey::new_unique()))?);
+
+        // No destination account: the transfer creates it first
+        manager
+            .transfer(
+                &mint,
+                &owner.pubkey(),
+                &Pubkey::new_unique(),
+                10,
+                &owner,
+                None,
+            )
+            .await?;
+
+        let sent = rpc.sent_transactions();
+        let message = &sent[0].message;
+        let create = message
+            .instructions
+            .first()
+            .ok_or_else(|| Error::token("no instructions"))?;
+        assert_eq!(
+            message.account_keys[create.program_id_index as usize],
+            spl_associated_token_account::id()
+        );
+        // CreateIdempotent
+        assert_eq!(create.data, vec![1]);
+        assert_eq!(message.instructions.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_propagates_destination_lookup_errors() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let (mint, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(mint, mint_account(6, Some(Pubkey::new_unique()))?);
+        let dest_ata =
+            get_associated_token_address_with_program_id(&recipient, &mint, &TOKEN_PROGRAM_ID);
+        rpc.time_out_account(dest_ata);
+
+        let result = manager
+            .transfer(&mint, &owner.pubkey(), &recipient, 10, &owner, None)
+            .await;
+
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert!(rpc.sent_transactions().is_empty());
+
+        // Token account reads report the timeout, not a missing account
+        let result = manager.get_token_account_info(&dest_ata).await;
+        assert!(matches!(result, Err(Error::Token(_))));
+        Ok(())
+    }
+
+    /// Instruction of the token program in the only transaction sent
+    fn sent_token_instruction(rpc: &MockRpc) -> Result<(Vec<u8>, Vec<Pubkey>)> {
+        let sent = rpc.sent_transactions();
+        let message = &sent
+            .last()
+            .ok_or_else(|| Error::token("no transaction sent"))?
+            .message;
+        let instruction = message
+            .instructions
+            .iter()
+            .find(|ix| message.account_keys[ix.program_id_index as usize] == TOKEN_PROGRAM_ID)
+            .ok_or_else(|| Error::token("token instruction missing"))?;
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|index| message.account_keys[*index as usize])
+            .collect();
+        Ok((instruction.data.clone(), accounts))
+    }
+
+    #[tokio::test]
+    async fn test_mint_to_checked_uses_mint_decimals() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let authority = Keypair::new();
+        let (mint, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(mint, mint_account(9, Some(authority.pubkey()))?);
+
+        let result = manager
+            .mint_to(&mint, &recipient, 2_000_000_000, &authority)
+            .await?;
+
+        let dest_ata = get_associated_token_address(&recipient, &mint);
+        assert_eq!(result.operation_type, TokenOperationType::Mint);
+        assert_eq!(result.destination, Some(dest_ata));
+        // The missing recipient account is created first
+        assert_eq!(rpc.sent_transactions()[0].message.instructions.len(), 2);
+        let (data, accounts) = sent_token_instruction(&rpc)?;
+        // MintToChecked: tag 14, amount (u64 LE), decimals
+        assert_eq!(data[0], 14);
+        assert_eq!(data[1..9], 2_000_000_000u64.to_le_bytes());
+        assert_eq!(data[9], 9);
+        assert_eq!(accounts, vec![mint, dest_ata, authority.pubkey()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_to_checks_the_mint_authority() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let (authority, impostor) = (Keypair::new(), Keypair::new());
+        let (mint, fixed) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(mint, mint_account(6, Some(authority.pubkey()))?);
+        rpc.set_account(fixed, mint_account(6, None)?);
+
+        let result = manager
+            .mint_to(&mint, &Pubkey::new_unique(), 10, &impostor)
+            .await;
+        let Err(Error::PermissionDenied(message)) = result else {
+            panic!("expected a permission error, got {:?}", result);
+        };
+        assert!(message.contains(&authority.pubkey().to_string()));
+
+        let result = manager
+            .mint_to(&fixed, &Pubkey::new_unique(), 10, &authority)
+            .await;
+        let Err(Error::InvalidTokenMint(message)) = result else {
+            panic!("expected a fixed-supply error, got {:?}", result);
+        };
+        assert!(message.contains("fixed supply"));
+
+        assert!(rpc.sent_transactions().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_burn_checks_the_balance_first() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let mint = Pubkey::new_unique();
+        let source = get_associated_token_address(&owner.pubkey(), &mint);
+        rpc.set_account(mint, mint_account(6, Some(Pubkey::new_unique()))?);
+        rpc.set_token_account(source, mint, owner.pubkey(), 100)?;
+
+        let result = manager.burn(&mint, &owner.pubkey(), 150, &owner).await;
+        assert!(matches!(
+            result,
+            Err(Error::InsufficientFunds {
+                required: 150,
+                available: 100
+            })
+        ));
+        let result = manager.burn(&mint, &Pubkey::new_unique(), 10, &owner).await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+        assert!(rpc.sent_transactions().is_empty());
+
+        let result = manager.burn(&mint, &owner.pubkey(), 40, &owner).await?;
+        assert_eq!(result.operation_type, TokenOperationType::Burn);
+        let (data, accounts) = sent_token_instruction(&rpc)?;
+        // BurnChecked: tag 15, amount (u64 LE), decimals
+        assert_eq!(data[0], 15);
+        assert_eq!(data[1..9], 40u64.to_le_bytes());
+        assert_eq!(data[9], 6);
+        assert_eq!(accounts, vec![source, mint, owner.pubkey()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_approve_and_revoke_delegate() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let (mint, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let source = get_associated_token_address(&owner.pubkey(), &mint);
+        rpc.set_account(mint, mint_account(6, Some(Pubkey::new_unique()))?);
+
+        let result = manager
+            .approve_delegate(&mint, &owner.pubkey(), &delegate, 500, &owner)
+            .await?;
+        assert_eq!(result.operation_type, TokenOperationType::Approve);
+        let (data, accounts) = sent_token_instruction(&rpc)?;
+        // ApproveChecked: tag 13, amount (u64 LE), decimals
+        assert_eq!(data[0], 13);
+        assert_eq!(data[1..9], 500u64.to_le_bytes());
+        assert_eq!(data[9], 6);
+        assert_eq!(accounts, vec![source, mint, delegate, owner.pubkey()]);
+
+        manager
+            .revoke_delegate(&mint, &owner.pubkey(), &owner)
+            .await?;
+        let (data, accounts) = sent_token_instruction(&rpc)?;
+        // Revoke: tag 5
+        assert_eq!(data, vec![5]);
+        assert_eq!(accounts, vec![source, owner.pubkey()]);
+
+        let result = manager.revoke_delegate(&mint, &delegate, &owner).await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+        Ok(())
+    }
+
+    /// Token-2022 mint with a transfer fee changing to `newer` at its epoch
+    fn fee_mint_account(decimals: u8, older: TransferFee, newer: TransferFee) -> Result<Account> {
+        use spl_token_2022::extension::{ExtensionType, StateWithExtensionsMut};
+
+        let to_error = |e: solana_sdk::program_error::ProgramError| Error::token(e.to_string());
+        let to_program = |fee: TransferFee| ProgramTransferFee {
+            epoch: fee.epoch.into(),
+            transfer_fee_basis_points: fee.basis_points.into(),
+            maximum_fee: fee.maximum_fee.into(),
+        };
+
+        let space = ExtensionType::try_calculate_account_len::<token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .map_err(to_error)?;
+        let mut data = vec![0u8; space];
+        {
+            let mut state =
+                StateWithExtensionsMut::<token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                    .map_err(to_error)?;
+            state.base = token_2022::state::Mint {
+                decimals,
+                is_initialized: true,
+                ..Default::default()
+            };
+            state.pack_base();
+            state.init_account_type().map_err(to_error)?;
+            let config = state
+                .init_extension::<TransferFeeConfig>(true)
+                .map_err(to_error)?;
+            config.older_transfer_fee = to_program(older);
+            config.newer_transfer_fee = to_program(newer);
+        }
+
+        Ok(Account {
+            lamports: 1,
+            data,
+            owner: TOKEN_2022_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_token_2022_transfer_states_the_fee() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        // 0.5% until epoch 10, then 1% capped at 1_000; the cluster is at epoch 12
+        rpc.set_account(
+            mint,
+            fee_mint_account(6, fee(0, 50, 1_000), fee(10, 100, 1_000))?,
+        );
+        let clock = solana_sdk::clock::Clock {
+            epoch: 12,
+            ..Default::default()
+        };
+        rpc.set_account(
+            solana_sdk::sysvar::clock::id(),
+            Account {
+                lamports: 1,
+                data: bincode::serialize(&clock)?,
+                owner: solana_sdk::sysvar::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let info = manager.get_token_info(&mint).await?;
+        assert_eq!(info.program_id, TOKEN_2022_PROGRAM_ID);
+        assert_eq!(
+            info.transfer_fee
+                .map(|schedule| schedule.newer.basis_points),
+            Some(100)
+        );
+
+        let options = TokenTransferOptions {
+            fee_handling: TransferFeeHandling::GrossUp,
+            ..Default::default()
+        };
+        let result = manager
+            .transfer(
+                &mint,
+                &owner.pubkey(),
+                &recipient,
+                10_000,
+                &owner,
+                Some(options),
+            )
+            .await?;
+        assert_eq!(result.amount, Some(10_102));
+        assert_eq!(result.transfer_fee, Some(102));
+        assert_eq!(result.net_amount, Some(10_000));
+        assert_eq!(
+            result.destination,
+            Some(get_associated_token_address_with_program_id(
+                &recipient,
+                &mint,
+                &TOKEN_2022_PROGRAM_ID
+            ))
+        );
+
+        let expected = transfer_checked_with_fee(
+            &TOKEN_2022_PROGRAM_ID,
+            &get_associated_token_address_with_program_id(
+                &owner.pubkey(),
+                &mint,
+                &TOKEN_2022_PROGRAM_ID,
+            ),
+            &mint,
+            &get_associated_token_address_with_program_id(
+                &recipient,
+                &mint,
+                &TOKEN_2022_PROGRAM_ID,
+            ),
+            &owner.pubkey(),
+            &[],
+            10_102,
+            6,
+            102,
+        )?;
+        let sent = rpc.sent_transactions();
+        let message = &sent[0].message;
+        let transfer = message
+            .instructions
+            .iter()
+            .find(|ix| message.account_keys[ix.program_id_index as usize] == TOKEN_2022_PROGRAM_ID)
+            .ok_or_else(|| Error::token("transfer instruction missing"))?;
+        assert_eq!(transfer.data, expected.data);
+
+        // Deducting from the amount sends it as is
+        let result = manager
+            .transfer(&mint, &owner.pubkey(), &recipient, 10_000, &owner, None)
+            .await?;
+        assert_eq!(result.amount, Some(10_000));
+        assert_eq!(result.transfer_fee, Some(100));
+        assert_eq!(result.net_amount, Some(9_900));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_2022_embedded_metadata() -> Result<()> {
+        use spl_token_2022::extension::{ExtensionType, StateWithExtensionsMut};
+
+        let to_error = |e: solana_sdk::program_error::ProgramError| Error::token(e.to_string());
+        let mint = Pubkey::new_unique();
+        let metadata = TokenMetadata {
+            mint,
+            name: "Agent Token".to_string(),
+            symbol: "AGT".to_string(),
+            uri: "https://example.com/agt.json".to_string(),
+            additional_metadata: vec![("tier".to_string(), "gold".to_string())],
+            ..Default::default()
+        };
+
+        let space = ExtensionType::try_calculate_account_len::<token_2022::state::Mint>(&[
+            ExtensionType::MetadataPointer,
+        ])
+        .map_err(to_error)?
+            + metadata.tlv_size_of().map_err(to_error)?;
+        let mut data = vec![0u8; space];
+        {
+            let mut state =
+                StateWithExtensionsMut::<token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                    .map_err(to_error)?;
+            state.base = token_2022::state::Mint {
+                decimals: 6,
+                is_initialized: true,
+                ..Default::default()
+            };
+            state.pack_base();
+            state.init_account_type().map_err(to_error)?;
+            let pointer = state
+                .init_extension::<MetadataPointer>(true)
+                .map_err(to_error)?;
+            pointer.metadata_address = Some(mint).try_into().map_err(to_error)?;
+            state
+                .init_variable_len_extension(&metadata, false)
+                .map_err(to_error)?;
+        }
+
+        let rpc = Arc::new(MockRpc::new());
+        rpc.set_account(
+            mint,
+            Account {
+                lamports: 1,
+                data,
+                owner: TOKEN_2022_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let manager = TokenManager::with_provider(rpc, CommitmentConfig::confirmed());
+
+        let info = manager.get_token_info(&mint).await?;
+        assert_eq!(info.program_id, TOKEN_2022_PROGRAM_ID);
+        assert_eq!(info.decimals, 6);
+        assert!(info.mint_authority.is_none());
+        let metadata = info
+            .metadata
+            .ok_or_else(|| Error::token("metadata missing"))?;
+        assert_eq!(metadata.symbol, "AGT");
+        assert_eq!(
+            metadata.uri.as_deref(),
+            Some("https://example.com/agt.json")
+        );
+        assert_eq!(
+            metadata.additional_metadata.get("tier").map(String::as_str),
+            Some("gold")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wrap_sol_creates_transfers_and_syncs() -> Result<()> {
+        use solana_sdk::system_instruction::SystemInstruction;
+        use spl_token::instruction::TokenInstruction;
+
+        let rpc = Arc::new(MockRpc::new());
+        let manager = TokenManager::with_provider(rpc.clone(), CommitmentConfig::confirmed());
+        let owner = Keypair::new();
+        let wsol_account = get_associated_token_address(&owner.pubkey(), &NATIVE_MINT);
+
+        let result = manager.wrap_sol(&owner.pubkey(), 250_000, &owner).await?;
+        assert_eq!(result.operation_type, TokenOperationType::Wrap);
+        assert_eq!(result.destination, Some(wsol_account));
+
+        let sent = rpc.sent_transactions();
+        let message = &sent[0].message;
+        let programs: Vec<Pubkey> = message
+            .instructions
+            .iter()
+            .map(|ix| message.account_keys[ix.program_id_index as usize])
+            .collect();
+        assert_eq!(
+            programs,
+            vec![
+                spl_associated_token_account::id(),
+                solana_sdk::system_program::id(),
+                TOKEN_PROGRAM_ID,
+            ]
+        );
+        // CreateIdempotent, so an existing account does not fail the wrap
+        assert_eq!(message.instructions[0].data, vec![1]);
+        assert!(matches!(
+            bincode::deserialize(&message.instructions[1].data)?,
+            SystemInstruction::Transfer { lamports: 250_000 }
+        ));
+        assert_eq!(
+            message.account_keys[message.instructions[1].accounts[1] as usize],
+            wsol_account
+        );
+        assert!(matches!(
+            TokenInstruction::unpack(&message.instructions[2].data),
+            Ok(TokenInstruction::SyncNative)
+        ));
+
+        let unwrap = unwrap_sol_instruction(&owner.pubkey())?;
+        assert!(matches!(
+            TokenInstruction::unpack(&unwrap.data),
+            Ok(TokenInstruction::CloseAccount)
+        ));
+        assert_eq!(unwrap.accounts[0].pubkey, wsol_account);
+        assert_eq!(unwrap.accounts[1].pubkey, owner.pubkey());
+
+        assert!(wrap_sol_instructions(&owner.pubkey(), 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_shortfall() {
+        assert_eq!(wrap_shortfall(0, 1_000), 1_000);
+        assert_eq!(wrap_shortfall(400, 1_000), 600);
+        assert_eq!(wrap_shortfall(1_000, 1_000), 0);
+        // A larger balance never wraps or unwraps
+        assert_eq!(wrap_shortfall(5_000, 1_000), 0);
     }
 }
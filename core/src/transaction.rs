@@ -20,6 +20,7 @@
 //! use agent_wallet_core::types::{AgentAction, AgentContext};
 //! use agent_wallet_core::keypair::SecureKeypair;
 //! use agent_wallet_core::rpc::RpcClient;
+//! use agent_wallet_core::sol::Lamports;
 //! use solana_sdk::pubkey::Pubkey;
 //!
 //! #[tokio::main]
@@ -30,7 +31,7 @@
 //!     // Create agent action
 //!     let action = AgentAction::TransferSol {
 //!         to: Pubkey::new_unique(),
-//!         amount: 1_000_000, // 0.001 SOL
+//!         amount: Lamports::new(1_000_000), // 0.001 SOL
 //!         memo: Some("Test transfer".to_string()),
 //!     };
 //!
@@ -53,29 +54,153 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_response::RpcStakeActivation;
 use solana_sdk::{
     account::Account,
+    clock::Slot,
     commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     hash::Hash,
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, CompiledInstruction, Instruction},
     message::Message,
-    native_token::lamports_to_sol,
     pubkey::Pubkey,
     signature::{Signature, Signer},
     signer::keypair::Keypair,
-    system_instruction,
+    system_instruction::{self, SystemInstruction},
+    system_program,
     transaction::{Transaction, VersionedTransaction},
 };
-use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
 use spl_token::instruction as token_instruction;
 
+use crate::destination::DestinationOptions;
 use crate::error::{Error, Result};
-use crate::keypair::SecureKeypair;
-use crate::rpc::RpcClient;
+use crate::fees::PriorityFeeStrategy;
+use crate::memo::{self, MemoAttribution, MemoMode, DEFAULT_MEMO_MAX_BYTES};
+use crate::nonce::{self, NonceInfo};
+use crate::preview::{BalanceChange, TransferPreview};
+use crate::read_cache::BlockhashCache;
+use crate::rpc::DynRpcProvider;
+use crate::shutdown::ShutdownSignal;
+use crate::signer::DynTransactionSigner;
+use crate::sol::{Lamports, LAMPORTS_PER_SOL};
+use crate::stake;
+use crate::token::TOKEN_ACCOUNT_RENT_LAMPORTS;
+use crate::tx_policy::{PolicyInput, TransactionPolicy};
 use crate::types::{AgentAction, AgentContext, PermissionLevel};
 
+/// Most instructions an action may turn into, before compute budget ones
+const MAX_ACTION_INSTRUCTIONS: usize = 20;
+
+/// Add `references` to the last instruction, the transfer, as read-only keys
+///
+/// Programs ignore accounts they do not expect, so the transfer is
+/// unchanged but shows up in the references' signature history.
+fn with_references(mut instructions: Vec<Instruction>, references: &[Pubkey]) -> Vec<Instruction> {
+    if let Some(transfer) = instructions.last_mut() {
+        transfer.accounts.extend(
+            references
+                .iter()
+                .map(|reference| AccountMeta::new_readonly(*reference, false)),
+        );
+    }
+    instructions
+}
+
+/// Decimals of a mint as recorded in the context, for checked instructions
+///
+/// The wallet records them while validating the action.
+fn known_decimals(context: &AgentContext, mint: &Pubkey) -> Result<u8> {
+    context
+        .token_decimals
+        .get(mint)
+        .copied()
+        .ok_or_else(|| Error::validation(format!("Decimals of token {} are not known", mint)))
+}
+
+/// Lamports the system transfers in `message` move out of `from`
+///
+/// Accounts `from` creates count with what they are funded beyond the rent
+/// charged for them in `rent`, as itemized by
+/// [`RentCalculator::itemize`](crate::rent::RentCalculator::itemize), so
+/// the rent-exempt minimum is not counted twice.
+pub fn sol_transferred(message: &Message, from: &Pubkey, rent: &[(Pubkey, u64)]) -> u64 {
+    system_transfers(message)
+        .filter(|transfer| transfer.source == *from)
+        .map(|transfer| {
+            if !transfer.creates {
+                return transfer.lamports;
+            }
+            let charged = rent
+                .iter()
+                .filter(|(address, _)| *address == transfer.destination)
+                .map(|(_, lamports)| *lamports)
+                .fold(0u64, u64::saturating_add);
+            transfer.lamports.saturating_sub(charged)
+        })
+        .fold(0u64, u64::saturating_add)
+}
+
+/// Lamports the system transfers in `message` move from `from` to `to`
+///
+/// Accounts created by the message are left out.
+pub fn sol_transferred_to(message: &Message, from: &Pubkey, to: &Pubkey) -> u64 {
+    system_transfers(message)
+        .filter(|transfer| {
+            !transfer.creates && transfer.source == *from && transfer.destination == *to
+        })
+        .map(|transfer| transfer.lamports)
+        .fold(0u64, u64::saturating_add)
+}
+
+/// Lamports a system instruction moves between two accounts
+struct SystemTransfer {
+    source: Pubkey,
+    destination: Pubkey,
+    lamports: u64,
+    /// Whether the instruction creates the destination account
+    creates: bool,
+}
+
+/// Each system transfer or account creation in `message`
+fn system_transfers(message: &Message) -> impl Iterator<Item = SystemTransfer> + '_ {
+    let key = |instruction: &CompiledInstruction, position: usize| {
+        let index = instruction.accounts.get(position)?;
+        message.account_keys.get(usize::from(*index)).copied()
+    };
+    message
+        .instructions
+        .iter()
+        .filter(|instruction| {
+            message
+                .account_keys
+                .get(usize::from(instruction.program_id_index))
+                == Some(&system_program::id())
+        })
+        .filter_map(move |instruction| {
+            // The funding account comes first in every one of them
+            let (lamports, destination, creates) = match bincode::deserialize(&instruction.data)
+                .ok()?
+            {
+                SystemInstruction::Transfer { lamports } => (lamports, 1, false),
+                SystemInstruction::TransferWithSeed { lamports, .. } => (lamports, 2, false),
+                SystemInstruction::CreateAccount { lamports, .. }
+                | SystemInstruction::CreateAccountWithSeed { lamports, .. } => (lamports, 1, true),
+                _ => return None,
+            };
+            Some(SystemTransfer {
+                source: key(instruction, 0)?,
+                destination: key(instruction, destination)?,
+                lamports,
+                creates,
+            })
+        })
+}
+
 /// Transaction building and validation options
 #[derive(Debug, Clone)]
 pub struct TransactionOptions {
@@ -85,6 +210,13 @@ pub struct TransactionOptions {
     pub compute_unit_limit: Option<u32>,
     /// Compute unit price in micro-lamports
     pub compute_unit_price: Option<u64>,
+    /// Whether the price and limit above are used as set or estimated
+    pub priority_fee_strategy: PriorityFeeStrategy,
+    /// Upper bound on the whole fee in lamports, signatures included
+    ///
+    /// Automatic estimation lowers the price to fit; a transaction whose
+    /// fee still exceeds it is refused before signing.
+    pub max_total_fee_lamports: Option<u64>,
     /// Skip preflight checks
     pub skip_preflight: bool,
     /// Commitment level for simulation
@@ -97,8 +229,133 @@ pub struct TransactionOptions {
     pub fee_payer: Option<Pubkey>,
     /// Recent blockhash validity duration (in slots)
     pub blockhash_validity_slots: u64,
+    /// How often a send rejected for an expired blockhash is re-signed and retried
+    pub max_retries: u32,
     /// Whether to add memo instruction
     pub include_memo: bool,
+    /// Whether transfer memos are written as given or with agent attribution
+    pub memo_mode: MemoMode,
+    /// Agent decision written into memos in [`MemoMode::Attributed`]
+    ///
+    /// Set by the agent runner for each decision.
+    pub memo_attribution: Option<MemoAttribution>,
+    /// Longest memo in bytes
+    pub memo_max_bytes: usize,
+    /// How long to wait for the transaction to land after sending
+    pub confirmation: ConfirmationStrategy,
+    /// Co-signers that must sign alongside the wallet (multisig wallets)
+    pub cosigners: Vec<Pubkey>,
+    /// Durable nonce to use instead of a recent blockhash
+    pub nonce: Option<NonceInfo>,
+    /// Upper bound on the priority fee in lamports when escalating fees
+    pub max_priority_fee_lamports: Option<u64>,
+    /// Seed index of the stake account a stake action creates
+    ///
+    /// The wallet picks the first unused index when unset; the builder
+    /// alone falls back to 0.
+    pub stake_seed_index: Option<u32>,
+    /// Activation state of the stake account an unstake action targets
+    ///
+    /// Fetched by the wallet when unset; building an unstake without it fails.
+    pub stake_activation: Option<RpcStakeActivation>,
+    /// Simulate the signed transaction and abort if it would fail
+    pub simulate_before_send: bool,
+    /// Checks `Wallet::transfer_sol` and `Wallet::transfer_token` run on
+    /// the recipient
+    pub destination: DestinationOptions,
+    /// Token account a token transfer credits instead of the recipient's
+    /// associated token account
+    ///
+    /// Set by the wallet when the recipient turns out to be a token account.
+    pub token_destination: Option<Pubkey>,
+    /// `.sol` domain the recipient was resolved from, recorded in the audit log
+    ///
+    /// Set by the wallet when a transfer is addressed to a domain.
+    pub recipient_name: Option<String>,
+    /// Read-only keys added to a transfer instruction, such as Solana Pay
+    /// references a merchant looks the payment up by
+    pub payment_references: Vec<Pubkey>,
+    /// Shutdown that cuts the wait for confirmation short
+    ///
+    /// Once it begins, a sent transaction is awaited for its drain period
+    /// only and then recorded as unconfirmed. Set by the agent runner.
+    pub shutdown: Option<ShutdownSignal>,
+    /// Where the signed transaction is broadcast
+    pub submission: SubmissionMode,
+}
+
+/// How a signed transaction reaches the network
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionMode {
+    /// Broadcast through the wallet's RPC node
+    #[default]
+    Rpc,
+    /// Sent to a Jito block engine as a tipped bundle, out of sight of the
+    /// public mempool
+    ///
+    /// The tip is paid by the wallet on top of the fee and counts against
+    /// the spending budget. Needs the `jito` feature; see the `jito`
+    /// module.
+    JitoBundle {
+        /// Base URL of the block engine
+        block_engine_url: String,
+        /// Lamports tipped per bundle
+        tip_lamports: u64,
+        /// Broadcast through the RPC node instead when the bundle is
+        /// refused or fails
+        fallback_to_rpc: bool,
+    },
+}
+
+impl SubmissionMode {
+    /// Lamports the wallet pays on top of the fee to submit this way
+    pub fn tip_lamports(&self) -> u64 {
+        match self {
+            SubmissionMode::Rpc => 0,
+            SubmissionMode::JitoBundle { tip_lamports, .. } => *tip_lamports,
+        }
+    }
+}
+
+/// Confirmation behaviour after a transaction is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationStrategy {
+    /// Return as soon as the transaction is accepted by the RPC node
+    None,
+    /// Wait until the transaction reaches `confirmed` commitment
+    Confirmed {
+        /// Maximum time to wait
+        timeout: Duration,
+    },
+    /// Wait until the transaction reaches `finalized` commitment
+    Finalized {
+        /// Maximum time to wait
+        timeout: Duration,
+    },
+}
+
+impl ConfirmationStrategy {
+    /// Get the commitment and timeout to wait for, if any
+    pub fn requirement(&self) -> Option<(CommitmentConfig, Duration)> {
+        match self {
+            ConfirmationStrategy::None => None,
+            ConfirmationStrategy::Confirmed { timeout } => {
+                Some((CommitmentConfig::confirmed(), *timeout))
+            }
+            ConfirmationStrategy::Finalized { timeout } => {
+                Some((CommitmentConfig::finalized(), *timeout))
+            }
+        }
+    }
+}
+
+impl Default for ConfirmationStrategy {
+    fn default() -> Self {
+        ConfirmationStrategy::Confirmed {
+            timeout: Duration::from_secs(60),
+        }
+    }
 }
 
 impl Default for TransactionOptions {
@@ -107,19 +364,38 @@ impl Default for TransactionOptions {
             priority_fee: None,
             compute_unit_limit: Some(200_000), // Default compute unit limit
             compute_unit_price: None,
+            priority_fee_strategy: PriorityFeeStrategy::Fixed,
+            max_total_fee_lamports: None,
             skip_preflight: false,
             commitment: CommitmentConfig::confirmed(),
             max_transaction_size: 1232, // Solana max transaction size
             max_signatures: 20,         // Solana max signatures per transaction
             fee_payer: None,
             blockhash_validity_slots: 150, // ~1 minute at 400ms slots
+            max_retries: 3,
             include_memo: true,
+            memo_mode: MemoMode::Plain,
+            memo_attribution: None,
+            memo_max_bytes: DEFAULT_MEMO_MAX_BYTES,
+            confirmation: ConfirmationStrategy::default(),
+            cosigners: Vec::new(),
+            nonce: None,
+            max_priority_fee_lamports: None,
+            stake_seed_index: None,
+            stake_activation: None,
+            simulate_before_send: true,
+            destination: DestinationOptions::default(),
+            token_destination: None,
+            recipient_name: None,
+            payment_references: Vec::new(),
+            shutdown: None,
+            submission: SubmissionMode::Rpc,
         }
     }
 }
 
 /// Transaction validation result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     /// Whether the transaction is valid
     pub is_valid: bool,
@@ -133,6 +409,38 @@ pub struct ValidationResult {
     pub estimated_compute_units: u32,
     /// Transaction size in bytes
     pub transaction_size: usize,
+    /// Required signatures that are still missing
+    pub missing_signatures: usize,
+    /// What the wallet must hold for the transaction, when its balance was checked
+    #[serde(default)]
+    pub required_breakdown: Option<RequiredBreakdown>,
+}
+
+/// Lamports a transaction needs the wallet to hold, itemized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequiredBreakdown {
+    /// SOL transferred out of the wallet
+    pub transfers: u64,
+    /// Fee, when the wallet pays it
+    pub fees: u64,
+    /// Rent-exempt minimums of the accounts the transaction creates
+    pub rent: u64,
+    /// Minimum balance that must remain afterwards
+    pub reserve: u64,
+}
+
+impl RequiredBreakdown {
+    /// Lamports the transaction takes from the wallet
+    pub fn spent(&self) -> u64 {
+        self.transfers
+            .saturating_add(self.fees)
+            .saturating_add(self.rent)
+    }
+
+    /// Balance the wallet needs: everything spent plus the reserve
+    pub fn total(&self) -> u64 {
+        self.spent().saturating_add(self.reserve)
+    }
 }
 
 impl ValidationResult {
@@ -145,6 +453,8 @@ impl ValidationResult {
             estimated_fee: 0,
             estimated_compute_units: 0,
             transaction_size: 0,
+            missing_signatures: 0,
+            required_breakdown: None,
         }
     }
 
@@ -157,6 +467,8 @@ impl ValidationResult {
             estimated_fee: 0,
             estimated_compute_units: 0,
             transaction_size: 0,
+            missing_signatures: 0,
+            required_breakdown: None,
         }
     }
 
@@ -173,7 +485,7 @@ impl ValidationResult {
 }
 
 /// Transaction simulation result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationResult {
     /// Whether simulation succeeded
     pub success: bool,
@@ -187,30 +499,160 @@ pub struct SimulationResult {
     pub error: Option<String>,
     /// Accounts modified during simulation
     pub accounts_modified: Vec<Pubkey>,
+    /// SOL balance changes of the accounts the transaction writes
+    #[serde(default)]
+    pub balance_changes: Vec<BalanceChange>,
     /// Transaction fee
     pub fee: u64,
 }
 
+/// Itemized plan of an action, built before it is signed
+///
+/// Rent locked in new accounts is listed per account and kept apart from the
+/// fee and the transfer amount: fees are gone once paid, while rent comes
+/// back when the account is closed.
+#[derive(Debug, Clone)]
+pub struct PreparedAction {
+    /// Action the transaction carries out
+    pub action: AgentAction,
+    /// Unsigned transaction for the action
+    pub transaction: Transaction,
+    /// Value of the action counted against the spending budget, in lamports
+    pub transfer_lamports: u64,
+    /// Estimated transaction fee in lamports
+    pub fee_lamports: u64,
+    /// Rent-exempt minimum locked in each account the transaction creates
+    pub rent_costs: Vec<(Pubkey, u64)>,
+    /// Lamports released by the accounts the transaction closes
+    pub closed_lamports: u64,
+    /// Warnings the transaction policy raised; they do not block the send
+    pub warnings: Vec<String>,
+}
+
+impl PreparedAction {
+    /// Total rent locked in new accounts
+    pub fn rent_lamports(&self) -> u64 {
+        self.rent_costs.iter().map(|(_, lamports)| lamports).sum()
+    }
+
+    /// Lamports leaving the wallet for fees and rent, plus SOL transferred or staked
+    pub fn total_lamports(&self) -> u64 {
+        let transferred = match &self.action {
+            AgentAction::TransferSol { amount, .. } | AgentAction::StakeTokens { amount, .. } => {
+                amount.as_u64()
+            }
+            AgentAction::BatchTransfer {
+                transfers,
+                mint: None,
+                ..
+            } => AgentAction::batch_total(transfers).unwrap_or(u64::MAX),
+            _ => 0,
+        };
+        transferred
+            .saturating_add(self.fee_lamports)
+            .saturating_add(self.rent_lamports())
+    }
+}
+
+/// Itemized cost of an executed action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionReceipt {
+    /// Transaction signature
+    pub signature: Signature,
+    /// Value of the action counted against the spending budget, in lamports
+    pub transfer_lamports: u64,
+    /// Estimated transaction fee in lamports
+    pub fee_lamports: u64,
+    /// Rent-exempt minimum locked in each account the transaction created
+    pub rent_costs: Vec<(Pubkey, u64)>,
+    /// Simulated balance changes of a dry run; the transaction was not sent
+    #[serde(default)]
+    pub simulated: Option<TransferPreview>,
+}
+
+impl ActionReceipt {
+    /// Whether the action was only rehearsed and never broadcast
+    pub fn is_dry_run(&self) -> bool {
+        self.simulated.is_some()
+    }
+}
+
+impl From<(Signature, PreparedAction)> for ActionReceipt {
+    fn from((signature, prepared): (Signature, PreparedAction)) -> Self {
+        Self {
+            signature,
+            transfer_lamports: prepared.transfer_lamports,
+            fee_lamports: prepared.fee_lamports,
+            rent_costs: prepared.rent_costs,
+            simulated: None,
+        }
+    }
+}
+
+/// Outcome of a batch transfer sent as several transactions
+///
+/// Transactions are sent in order and the batch stops at the first failure,
+/// so recipients are either paid, in the failed transaction, or never sent.
+#[derive(Debug)]
+pub struct BatchTransferReport {
+    /// Receipts of the transactions that succeeded, in order
+    pub receipts: Vec<ActionReceipt>,
+    /// Recipients paid by those transactions
+    pub succeeded: Vec<(Pubkey, u64)>,
+    /// Recipients of the transaction that failed; it may still land if it
+    /// timed out waiting for confirmation
+    pub failed: Vec<(Pubkey, u64)>,
+    /// Recipients whose transactions were never sent
+    pub unsent: Vec<(Pubkey, u64)>,
+    /// Why the batch stopped
+    pub error: Option<Error>,
+}
+
+impl BatchTransferReport {
+    /// Whether every recipient was paid
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
 /// Transaction builder for converting agent actions to Solana transactions
 pub struct TransactionBuilder {
-    /// Recent blockhash cache
-    blockhash_cache: Option<(Hash, u64)>,
-    /// Cache timestamp
-    cache_timestamp: Option<std::time::Instant>,
-    /// Maximum cache age
-    max_cache_age: Duration,
+    /// Recent blockhash and the slot it was fetched at
+    blockhash_cache: BlockhashCache,
 }
 
 impl TransactionBuilder {
     /// Create a new transaction builder
     pub fn new() -> Self {
+        Self::with_blockhash_cache(BlockhashCache::new())
+    }
+
+    /// Create a transaction builder sharing `cache`, e.g. an RPC client's
+    ///
+    /// See [`DynRpcProvider::blockhash_cache`].
+    pub fn with_blockhash_cache(cache: BlockhashCache) -> Self {
         Self {
-            blockhash_cache: None,
-            cache_timestamp: None,
-            max_cache_age: Duration::from_secs(30), // Cache for 30 seconds
+            blockhash_cache: cache,
         }
     }
 
+    /// Create a transaction builder sharing `rpc_client`'s blockhash cache, if it keeps one
+    pub fn for_provider(rpc_client: &dyn DynRpcProvider) -> Self {
+        rpc_client
+            .blockhash_cache()
+            .map_or_else(Self::new, Self::with_blockhash_cache)
+    }
+
+    /// Record a slot the node has reached, aging the cached blockhash
+    pub fn observe_slot(&mut self, slot: Slot) {
+        self.blockhash_cache.observe_slot(slot);
+    }
+
+    /// Forget the cached blockhash, e.g. after the cluster reported it expired
+    pub fn invalidate_blockhash(&mut self) {
+        self.blockhash_cache.invalidate();
+    }
+
     /// Build a transaction from an agent action
     pub fn build_from_action(
         &mut self,
@@ -225,16 +667,166 @@ impl TransactionBuilder {
         self.validate_spending_limits(action, context)?;
 
         // Convert action to instructions
-        let instructions = self.action_to_instructions(action, context)?;
+        let instructions = self.action_to_instructions(action, context, options)?;
 
         // Check instruction count
-        if instructions.len() > 20 {
+        if instructions.len() > MAX_ACTION_INSTRUCTIONS {
             // Arbitrary limit for safety
             return Err(Error::validation(
                 "Too many instructions in transaction".to_string(),
             ));
         }
 
+        let transaction = self.assemble(instructions, context, options)?;
+
+        // Validate transaction size
+        self.validate_transaction_size(&transaction, options)?;
+
+        Ok(transaction)
+    }
+
+    /// Build an unsigned transaction on an explicit blockhash
+    ///
+    /// Runs the checks of [`TransactionBuilder::build_from_action`] without
+    /// any RPC call, so the transaction can be built on one machine and
+    /// signed on another. For durable nonce transactions `recent_blockhash`
+    /// is the nonce stored in the nonce account.
+    pub fn build_unsigned(
+        &mut self,
+        action: &AgentAction,
+        context: &AgentContext,
+        options: &TransactionOptions,
+        recent_blockhash: Hash,
+    ) -> Result<Transaction> {
+        let transaction = self.build_from_action(action, context, options)?;
+        let mut message = transaction.message;
+        message.recent_blockhash = recent_blockhash;
+        Ok(Transaction::new_unsigned(message))
+    }
+
+    /// Build the transactions for an action, splitting batches to fit
+    ///
+    /// A [`AgentAction::BatchTransfer`] that does not fit one transaction is
+    /// packed into as few as stay under `options.max_transaction_size`; any
+    /// other action builds into a single transaction. Permission and
+    /// spending limits are checked once, against the whole batch.
+    pub fn build_batch_from_action(
+        &mut self,
+        action: &AgentAction,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Result<Vec<Transaction>> {
+        self.split_batch(action, context, options)?
+            .iter()
+            .map(|chunk| {
+                let instructions = self.action_to_instructions(chunk, context, options)?;
+                self.assemble(instructions, context, options)
+            })
+            .collect()
+    }
+
+    /// Split an action into actions that each fit one transaction
+    ///
+    /// Only batch transfers are split, keeping the order of the recipients.
+    /// Fails if the whole batch breaks a limit or a single transfer cannot
+    /// fit a transaction on its own.
+    pub fn split_batch(
+        &mut self,
+        action: &AgentAction,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Result<Vec<AgentAction>> {
+        self.validate_permission(action, context)?;
+        self.validate_spending_limits(action, context)?;
+
+        let (transfers, mint, memo) = match action {
+            AgentAction::BatchTransfer {
+                transfers,
+                mint,
+                memo,
+            } => (transfers, mint, memo),
+            other => return Ok(vec![other.clone()]),
+        };
+        if transfers.is_empty() {
+            return Err(Error::validation("Batch transfer has no recipients"));
+        }
+
+        let chunk_action = |transfers: Vec<(Pubkey, u64)>| AgentAction::BatchTransfer {
+            transfers,
+            mint: *mint,
+            memo: memo.clone(),
+        };
+        let mut chunks = Vec::new();
+        let mut current = chunk_action(Vec::with_capacity(transfers.len()));
+        for &transfer in transfers {
+            if let AgentAction::BatchTransfer { transfers, .. } = &mut current {
+                transfers.push(transfer);
+            }
+            if self.fits_one_transaction(&current, context, options)? {
+                continue;
+            }
+
+            // Close the chunk without this transfer and start the next with it
+            let mut full = std::mem::replace(&mut current, chunk_action(vec![transfer]));
+            if let AgentAction::BatchTransfer { transfers, .. } = &mut full {
+                transfers.pop();
+                if transfers.is_empty() {
+                    return Err(Error::validation(format!(
+                        "Transfer to {} does not fit in a transaction",
+                        transfer.0
+                    )));
+                }
+            }
+            chunks.push(full);
+            if !self.fits_one_transaction(&current, context, options)? {
+                return Err(Error::validation(format!(
+                    "Transfer to {} does not fit in a transaction",
+                    transfer.0
+                )));
+            }
+        }
+        chunks.push(current);
+
+        Ok(chunks)
+    }
+
+    /// Whether an action builds into a single transaction within the limits
+    fn fits_one_transaction(
+        &self,
+        action: &AgentAction,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Result<bool> {
+        let instructions = self.action_to_instructions(action, context, options)?;
+        if instructions.len() > MAX_ACTION_INSTRUCTIONS {
+            return Ok(false);
+        }
+        let transaction = self.assemble(instructions, context, options)?;
+        let size = bincode::serialized_size(&transaction)
+            .map_err(|e| Error::serialization(e.to_string()))? as usize;
+        Ok(size <= options.max_transaction_size)
+    }
+
+    /// Turn action instructions into an unsigned transaction
+    fn assemble(
+        &self,
+        mut instructions: Vec<Instruction>,
+        context: &AgentContext,
+        options: &TransactionOptions,
+    ) -> Result<Transaction> {
+        // Require the chosen co-signers and prepend compute budget instructions
+        if !instructions.is_empty() {
+            if !options.cosigners.is_empty() {
+                instructions.push(crate::multisig::cosigner_instruction(&options.cosigners));
+            }
+            self.add_priority_fee_instructions(&mut instructions, options);
+
+            // The nonce must be advanced by the very first instruction
+            if let Some(nonce) = &options.nonce {
+                instructions.insert(0, nonce.advance_instruction());
+            }
+        }
+
         // Get fee payer
         let fee_payer = options
             .fee_payer
@@ -245,73 +837,62 @@ impl TransactionBuilder {
         let message = Message::new_with_blockhash(
             &instructions,
             Some(&fee_payer),
-            &self.get_cached_blockhash()?, // Will be updated with real blockhash before signing
+            // Will be updated with real blockhash before signing
+            &self.get_cached_blockhash(options.blockhash_validity_slots),
         );
 
         // Create transaction
-        let transaction = Transaction::new_unsigned(message);
-
-        // Validate transaction size
-        self.validate_transaction_size(&transaction, options)?;
-
-        Ok(transaction)
+        Ok(Transaction::new_unsigned(message))
     }
 
     /// Validate a transaction against agent context and options
+    ///
+    /// Reports every violation of the [`TransactionPolicy`] the send path
+    /// enforces, along with its warnings and the estimated fee.
     pub fn validate_transaction(
         &self,
         transaction: &Transaction,
         context: &AgentContext,
         options: &TransactionOptions,
     ) -> ValidationResult {
-        let mut result = ValidationResult::valid();
-
-        // Check transaction size
-        let transaction_size = bincode::serialized_size(transaction).unwrap_or(0) as usize;
-        result.transaction_size = transaction_size;
-
-        if transaction_size > options.max_transaction_size {
-            result.add_error(format!(
-                "Transaction size {} bytes exceeds maximum {} bytes",
-                transaction_size, options.max_transaction_size
-            ));
-        }
-
-        // Check signature count
-        let signature_count = transaction.signatures.len();
-        if signature_count > options.max_signatures as usize {
-            result.add_error(format!(
-                "Transaction has {} signatures, maximum is {}",
-                signature_count, options.max_signatures
-            ));
-        }
-
-        // Check fee payer permission
-        if let Some(fee_payer) = transaction.message.fee_payer() {
-            if fee_payer != &context.get_wallet_pubkey() {
-                // Only allow fee payer changes for administrators
-                if !context
-                    .permission_level
-                    .can_perform(PermissionLevel::Administrator)
-                {
-                    result
-                        .add_error("Only administrators can specify custom fee payers".to_string());
-                }
-            }
-        }
+        let policy = TransactionPolicy::from_options(options);
+        self.validate_with_policy(transaction, context, options, &policy)
+    }
 
-        // Estimate fee (simplified)
-        result.estimated_fee = self.estimate_transaction_fee(transaction, options);
-        result.estimated_compute_units = self.estimate_compute_units(transaction);
+    /// Validate a transaction against a policy other than the options' own
+    pub fn validate_with_policy(
+        &self,
+        transaction: &Transaction,
+        context: &AgentContext,
+        options: &TransactionOptions,
+        policy: &TransactionPolicy,
+    ) -> ValidationResult {
+        self.validate_input(PolicyInput::new(transaction, context), options, policy)
+    }
 
+    /// Validate a transaction with what is known of its cost, such as the balance
+    ///
+    /// The estimated fee replaces the input's.
+    pub fn validate_input(
+        &self,
+        input: PolicyInput<'_>,
+        options: &TransactionOptions,
+        policy: &TransactionPolicy,
+    ) -> ValidationResult {
+        let estimated_fee = self.estimate_transaction_fee(input.transaction, options);
+        let mut result = policy.report(&input.with_fee(estimated_fee));
+        result.estimated_fee = estimated_fee;
+        result.estimated_compute_units = self.estimate_compute_units(input.transaction);
         result
     }
 
-    /// Sign a transaction with a secure keypair
-    pub fn sign_transaction(
+    /// Sign a transaction with any signer, local or remote
+    ///
+    /// Existing signatures are cleared since the blockhash changes.
+    pub async fn sign_transaction(
         &self,
         transaction: &mut Transaction,
-        keypair: &SecureKeypair,
+        signer: &dyn DynTransactionSigner,
         recent_blockhash: Hash,
     ) -> Result<Signature> {
         // Update transaction with recent blockhash
@@ -320,58 +901,66 @@ impl TransactionBuilder {
         *transaction = Transaction::new_unsigned(message);
 
         // Sign transaction
-        let signature = keypair.sign_transaction(transaction);
-        transaction.signatures = vec![signature];
-
-        Ok(signature)
+        crate::signer::sign_transaction(transaction, signer).await
     }
 
     /// Prepare transaction for sending (update blockhash, sign)
+    ///
+    /// Durable nonce transactions are signed with the nonce stored in their
+    /// nonce account rather than a fresh blockhash.
     pub async fn prepare_transaction(
         &mut self,
         transaction: &mut Transaction,
-        keypair: &SecureKeypair,
-        rpc_client: &RpcClient,
+        signer: &dyn DynTransactionSigner,
+        rpc_client: &dyn DynRpcProvider,
     ) -> Result<Signature> {
-        // Get fresh blockhash
-        let recent_blockhash = rpc_client.get_latest_blockhash().await?;
-
-        // Update blockhash cache
-        self.blockhash_cache = Some((recent_blockhash, 0)); // Slot 0 placeholder
-        self.cache_timestamp = Some(std::time::Instant::now());
+        let recent_blockhash = match nonce::nonce_account_of(&transaction.message) {
+            Some(account) => rpc_client.get_nonce_account(&account).await?.blockhash,
+            None => {
+                // Always sign with a fresh blockhash so identical transactions
+                // never share a signature
+                let recent_blockhash = rpc_client.get_fresh_blockhash().await?;
+
+                // Cache it with its slot; without a slot it cannot be aged
+                if let Ok(slot) = rpc_client.get_slot().await {
+                    self.blockhash_cache.store(recent_blockhash, slot);
+                }
+                recent_blockhash
+            }
+        };
 
         // Sign transaction
-        self.sign_transaction(transaction, keypair, recent_blockhash)
+        self.sign_transaction(transaction, signer, recent_blockhash)
+            .await
     }
 
     /// Simulate a transaction using RPC
+    ///
+    /// Unsigned transactions are accepted; signatures are not verified.
     pub async fn simulate_transaction(
         &self,
         transaction: &Transaction,
-        rpc_client: &RpcClient,
+        rpc_client: &dyn DynRpcProvider,
         options: &TransactionOptions,
     ) -> Result<SimulationResult> {
-        let simulation = rpc_client.simulate_transaction(transaction).await?;
-
-        let mut result = SimulationResult {
-            success: simulation.value.err.is_none(),
-            logs: simulation.value.logs.unwrap_or_default(),
-            compute_units_consumed: simulation.value.units_consumed,
-            return_data: simulation.value.return_data.map(|rd| rd.data),
-            error: simulation.value.err.map(|e| format!("{:?}", e)),
-            accounts_modified: Vec::new(),
-            fee: simulation.value.fee.unwrap_or(0),
-        };
-
-        // Extract accounts modified from logs (simplified)
-        for log in &result.logs {
-            if log.contains("Program") && log.contains("succeeded") {
-                // Parse program log to extract modified accounts
-                // This is simplified - actual implementation would parse more carefully
-            }
-        }
-
-        Ok(result)
+        let (simulation, balance_changes) =
+            crate::preview::simulate_balances(rpc_client, transaction).await?;
+
+        Ok(SimulationResult {
+            success: simulation.err.is_none(),
+            logs: simulation.logs.unwrap_or_default(),
+            compute_units_consumed: simulation.units_consumed,
+            return_data: simulation
+                .return_data
+                .and_then(|rd| STANDARD.decode(rd.data.0).ok()),
+            error: simulation.err.map(|e| format!("{:?}", e)),
+            accounts_modified: balance_changes
+                .iter()
+                .map(|change| change.account)
+                .collect(),
+            balance_changes,
+            fee: self.estimate_transaction_fee(transaction, options),
+        })
     }
 
     /// Convert agent action to Solana instructions
@@ -379,23 +968,123 @@ impl TransactionBuilder {
         &self,
         action: &AgentAction,
         context: &AgentContext,
+        options: &TransactionOptions,
     ) -> Result<Vec<Instruction>> {
+        let owner = context.get_wallet_pubkey();
+        let memo_instruction = |memo: &Option<String>| -> Result<Option<Instruction>> {
+            memo::compose_memo(
+                memo.as_deref(),
+                options.memo_mode,
+                options.memo_attribution.as_ref(),
+                options.memo_max_bytes,
+            )?
+            .map(|text| memo::memo_instruction(text.as_bytes(), &owner, options.memo_max_bytes))
+            .transpose()
+        };
+
         match action {
-            AgentAction::TransferSol { to, amount, memo } => {
-                self.build_transfer_sol_instructions(context.get_wallet_pubkey(), to, *amount, memo)
-            }
+            AgentAction::TransferSol { to, amount, memo } => self
+                .build_transfer_sol_instructions(
+                    &context.get_wallet_pubkey(),
+                    to,
+                    amount.as_u64(),
+                    memo_instruction(memo)?,
+                )
+                .map(|instructions| with_references(instructions, &options.payment_references)),
             AgentAction::TransferToken {
                 mint,
                 to,
                 amount,
                 memo,
-            } => self.build_transfer_token_instructions(
-                context.get_wallet_pubkey(),
+            } => self
+                .build_transfer_token_instructions(
+                    &context.get_wallet_pubkey(),
+                    mint,
+                    to,
+                    options.token_destination.as_ref(),
+                    *amount,
+                    memo_instruction(memo)?,
+                )
+                .map(|instructions| with_references(instructions, &options.payment_references)),
+            AgentAction::BatchTransfer {
+                transfers,
                 mint,
-                to,
-                *amount,
                 memo,
+            } => self.build_batch_transfer_instructions(
+                &context.get_wallet_pubkey(),
+                transfers,
+                mint.as_ref(),
+                memo_instruction(memo)?,
             ),
+            AgentAction::StakeTokens {
+                staking_pool,
+                amount,
+            } => stake::stake_instructions(
+                &context.get_wallet_pubkey(),
+                staking_pool,
+                options.stake_seed_index.unwrap_or(0),
+                amount.as_u64(),
+            ),
+            AgentAction::UnstakeTokens {
+                staking_pool,
+                amount,
+            } => {
+                let activation = options.stake_activation.as_ref().ok_or_else(|| {
+                    Error::validation(format!(
+                        "Activation state of stake account {} is unknown",
+                        staking_pool
+                    ))
+                })?;
+                stake::unstake_instructions(
+                    &context.get_wallet_pubkey(),
+                    staking_pool,
+                    amount.as_u64(),
+                    activation,
+                )
+            }
+            AgentAction::CreateTokenAccount { mint } => {
+                let owner = context.get_wallet_pubkey();
+                Ok(vec![create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
+                    mint,
+                    &spl_token::id(),
+                )])
+            }
+            AgentAction::CloseTokenAccount {
+                account,
+                destination,
+            } => Ok(vec![token_instruction::close_account(
+                &spl_token::id(),
+                account,
+                destination,
+                &context.get_wallet_pubkey(),
+                &[],
+            )?]),
+            AgentAction::MintToken { mint, to, amount } => {
+                let destination = get_associated_token_address(to, mint);
+                Ok(vec![
+                    create_associated_token_account_idempotent(&owner, to, mint, &spl_token::id()),
+                    token_instruction::mint_to_checked(
+                        &spl_token::id(),
+                        mint,
+                        &destination,
+                        &owner,
+                        &[],
+                        *amount,
+                        known_decimals(context, mint)?,
+                    )?,
+                ])
+            }
+            AgentAction::BurnToken { mint, amount } => Ok(vec![token_instruction::burn_checked(
+                &spl_token::id(),
+                &get_associated_token_address(&owner, mint),
+                mint,
+                &owner,
+                &[],
+                *amount,
+                known_decimals(context, mint)?,
+            )?]),
             AgentAction::NoOp => Ok(Vec::new()),
             _ => Err(Error::NotSupported(
                 "Action type not yet implemented".to_string(),
@@ -409,19 +1098,12 @@ impl TransactionBuilder {
         from: &Pubkey,
         to: &Pubkey,
         amount: u64,
-        memo: &Option<String>,
+        memo: Option<Instruction>,
     ) -> Result<Vec<Instruction>> {
         let mut instructions = Vec::new();
 
         // Add memo instruction if provided
-        if let Some(memo_text) = memo {
-            if !memo_text.is_empty() {
-                instructions.push(spl_memo::build_memo(
-                    memo_text.as_bytes(),
-                    &[from], // Signer for memo
-                ));
-            }
-        }
+        instructions.extend(memo);
 
         // Add transfer instruction
         instructions.push(system_instruction::transfer(from, to, amount));
@@ -435,33 +1117,32 @@ impl TransactionBuilder {
         owner: &Pubkey,
         mint: &Pubkey,
         to: &Pubkey,
+        token_destination: Option<&Pubkey>,
         amount: u64,
-        memo: &Option<String>,
+        memo: Option<Instruction>,
     ) -> Result<Vec<Instruction>> {
         let mut instructions = Vec::new();
 
         // Get associated token accounts
         let source_token_account = get_associated_token_address(owner, mint);
-        let destination_token_account = get_associated_token_address(to, mint);
+        let destination_token_account = token_destination
+            .copied()
+            .unwrap_or_else(|| get_associated_token_address(to, mint));
 
         // Add memo instruction if provided
-        if let Some(memo_text) = memo {
-            if !memo_text.is_empty() {
-                instructions.push(spl_memo::build_memo(
-                    memo_text.as_bytes(),
-                    &[owner], // Signer for memo
-                ));
-            }
-        }
+        instructions.extend(memo);
 
-        // Create destination token account if it doesn't exist
-        instructions.push(
-            spl_associated_token_account::create_associated_token_account(
+        // Create destination token account if it doesn't exist; the idempotent
+        // variant succeeds when the account is already there. A token account
+        // given as the destination exists already.
+        if token_destination.is_none() {
+            instructions.push(create_associated_token_account_idempotent(
                 owner, // payer
                 to,    // owner
                 mint,  // mint
-            ),
-        );
+                &spl_token::id(),
+            ));
+        }
 
         // Add transfer instruction
         instructions.push(token_instruction::transfer(
@@ -476,6 +1157,45 @@ impl TransactionBuilder {
         Ok(instructions)
     }
 
+    /// Build instructions paying every recipient of a batch
+    ///
+    /// The memo comes first, once; token legs create the recipient's
+    /// associated account if needed before transferring.
+    fn build_batch_transfer_instructions(
+        &self,
+        owner: &Pubkey,
+        transfers: &[(Pubkey, u64)],
+        mint: Option<&Pubkey>,
+        memo: Option<Instruction>,
+    ) -> Result<Vec<Instruction>> {
+        let mut instructions = Vec::new();
+        instructions.extend(memo);
+
+        for (to, amount) in transfers {
+            match mint {
+                None => instructions.push(system_instruction::transfer(owner, to, *amount)),
+                Some(mint) => {
+                    instructions.push(create_associated_token_account_idempotent(
+                        owner,
+                        to,
+                        mint,
+                        &spl_token::id(),
+                    ));
+                    instructions.push(token_instruction::transfer(
+                        &spl_token::id(),
+                        &get_associated_token_address(owner, mint),
+                        &get_associated_token_address(to, mint),
+                        owner,
+                        &[],
+                        *amount,
+                    )?);
+                }
+            }
+        }
+
+        Ok(instructions)
+    }
+
     /// Validate agent permission for action
     fn validate_permission(&self, action: &AgentAction, context: &AgentContext) -> Result<()> {
         let required_permission = action.required_permission();
@@ -487,42 +1207,68 @@ impl TransactionBuilder {
             });
         }
 
-        Ok(())
+        context.is_protocol_allowed(action)
     }
 
     /// Validate spending limits for action
-    fn validate_spending_limits(&self, action: &AgentAction, context: &AgentContext) -> Result<()> {
+    pub(crate) fn validate_spending_limits(
+        &self,
+        action: &AgentAction,
+        context: &AgentContext,
+    ) -> Result<()> {
         match action {
-            AgentAction::TransferSol { amount, .. } => {
-                let sol_amount = lamports_to_sol(*amount);
-                context.is_action_allowed(sol_amount)
+            AgentAction::TransferSol { amount, .. } => context.is_action_allowed(*amount),
+            AgentAction::TransferToken { mint, amount, .. } => {
+                context.is_token_action_allowed(mint, *amount).map(|_| ())
+            }
+            // The whole batch counts as one transaction
+            AgentAction::BatchTransfer {
+                transfers,
+                mint: None,
+                ..
+            } => context.is_action_allowed(Lamports::new(AgentAction::batch_total(transfers)?)),
+            AgentAction::BatchTransfer {
+                transfers,
+                mint: Some(mint),
+                ..
+            } => context
+                .is_token_action_allowed(mint, AgentAction::batch_total(transfers)?)
+                .map(|_| ()),
+            AgentAction::SwapTokens {
+                input_mint, amount, ..
+            } => context
+                .is_token_action_allowed(input_mint, *amount)
+                .map(|_| ()),
+            // Staked SOL leaves the wallet like a transfer; unstaking brings it back
+            AgentAction::StakeTokens { amount, .. } => context.is_action_allowed(*amount),
+            // Rent locked in a new account counts as spent; closing refunds it
+            AgentAction::CreateTokenAccount { .. } => {
+                context.is_action_allowed(Lamports::new(TOKEN_ACCOUNT_RENT_LAMPORTS))
             }
-            AgentAction::TransferToken { amount, .. } => {
-                // For tokens, we need to check value in SOL equivalent
-                // This is simplified - would need price feed integration
-                let estimated_sol_value = (*amount as f64) / 1_000_000_000.0; // Assume 1:1 for now
-                context.is_action_allowed(estimated_sol_value)
+            // Burned tokens leave the wallet like a transfer; minting spends nothing
+            AgentAction::BurnToken { mint, amount } => {
+                context.is_token_action_allowed(mint, *amount).map(|_| ())
             }
-            AgentAction::NoOp => Ok(()),
+            AgentAction::UnstakeTokens { .. }
+            | AgentAction::CloseTokenAccount { .. }
+            | AgentAction::MintToken { .. }
+            | AgentAction::NoOp => Ok(()),
             _ => {
                 // For other actions, check a default minimum
-                context.is_action_allowed(0.1) // 0.1 SOL default check
+                context.is_action_allowed(Lamports::new(LAMPORTS_PER_SOL / 10)) // 0.1 SOL default check
             }
         }
     }
 
     /// Get cached blockhash or generate placeholder
-    fn get_cached_blockhash(&self) -> Result<Hash> {
-        if let Some((blockhash, _)) = self.blockhash_cache {
-            if let Some(timestamp) = self.cache_timestamp {
-                if timestamp.elapsed() < self.max_cache_age {
-                    return Ok(blockhash);
-                }
-            }
-        }
-
-        // Return placeholder - will be updated with real blockhash before signing
-        Ok(Hash::new_from_array([0u8; 32]))
+    ///
+    /// The cached blockhash is used until the node has advanced
+    /// `validity_slots` past the slot it was fetched at.
+    fn get_cached_blockhash(&self, validity_slots: u64) -> Hash {
+        // Placeholder otherwise - updated with a real blockhash before signing
+        self.blockhash_cache
+            .get(validity_slots)
+            .unwrap_or_else(|| Hash::new_from_array([0u8; 32]))
     }
 
     /// Validate transaction size
@@ -544,7 +1290,7 @@ impl TransactionBuilder {
     }
 
     /// Estimate transaction fee
-    fn estimate_transaction_fee(
+    pub(crate) fn estimate_transaction_fee(
         &self,
         transaction: &Transaction,
         options: &TransactionOptions,
@@ -575,29 +1321,27 @@ impl TransactionBuilder {
     }
 
     /// Add priority fee instructions if needed
+    ///
+    /// Prepends `SetComputeUnitLimit` and `SetComputeUnitPrice` as requested
+    /// by the options. `compute_unit_price` takes precedence over
+    /// `priority_fee`, which is the same per-unit price under its older name.
     pub fn add_priority_fee_instructions(
         &self,
         instructions: &mut Vec<Instruction>,
         options: &TransactionOptions,
     ) {
-        if let (Some(compute_unit_limit), Some(compute_unit_price)) =
-            (options.compute_unit_limit, options.compute_unit_price)
-        {
-            // Add compute budget instructions
-            instructions.insert(
-                0,
-                ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit),
-            );
-            instructions.insert(
-                1,
-                ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price),
-            );
-        } else if let Some(compute_unit_limit) = options.compute_unit_limit {
-            instructions.insert(
-                0,
-                ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit),
-            );
+        let mut budget_instructions = Vec::with_capacity(2);
+        if let Some(compute_unit_limit) = options.compute_unit_limit {
+            budget_instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(
+                compute_unit_limit,
+            ));
         }
+        if let Some(compute_unit_price) = options.compute_unit_price.or(options.priority_fee) {
+            budget_instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
+                compute_unit_price,
+            ));
+        }
+        instructions.splice(0..0, budget_instructions);
     }
 }
 
@@ -615,9 +1359,7 @@ pub trait AgentContextExt {
 
 impl AgentContextExt for AgentContext {
     fn get_wallet_pubkey(&self) -> Pubkey {
-        // In real implementation, this would come from the wallet
-        // For now, return a placeholder
-        Pubkey::new_from_array([0u8; 32])
+        self.wallet_pubkey
     }
 }
 
@@ -644,13 +1386,48 @@ impl PermissionLevelExt for PermissionLevel {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{TokenLimit, UnlistedTokenPolicy};
     use solana_sdk::signature::Keypair;
 
     #[test]
     fn test_transaction_builder_creation() {
         let builder = TransactionBuilder::new();
-        assert!(builder.blockhash_cache.is_none());
-        assert!(builder.cache_timestamp.is_none());
+        assert!(builder.blockhash_cache.get(u64::MAX).is_none());
+        assert!(builder.blockhash_cache.latest_slot().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cached_blockhash_expires_by_slot() -> Result<()> {
+        let rpc = crate::rpc::mock::MockRpc::default();
+        rpc.set_slot(1_000);
+        let keypair = crate::keypair::SecureKeypair::generate();
+        let payer = keypair.public_key();
+        let mut builder = TransactionBuilder::new();
+        let mut transaction = Transaction::new_unsigned(Message::new(
+            &[system_instruction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer),
+        ));
+        builder
+            .prepare_transaction(&mut transaction, &keypair, &rpc)
+            .await?;
+        let fetched = transaction.message.recent_blockhash;
+
+        builder.observe_slot(1_149);
+        assert_eq!(builder.get_cached_blockhash(150), fetched);
+        builder.observe_slot(1_150);
+        assert_ne!(builder.get_cached_blockhash(150), fetched);
+
+        // Older slots never make an expired blockhash valid again
+        builder.observe_slot(1_000);
+        assert_ne!(builder.get_cached_blockhash(150), fetched);
+        assert_eq!(builder.get_cached_blockhash(u64::MAX), fetched);
+        builder.invalidate_blockhash();
+        assert_ne!(builder.get_cached_blockhash(u64::MAX), fetched);
+        Ok(())
     }
 
     #[test]
@@ -686,7 +1463,7 @@ mod tests {
         context.permission_level = PermissionLevel::Advanced;
         let action = AgentAction::TransferSol {
             to: Pubkey::new_unique(),
-            amount: 1000,
+            amount: Lamports::new(1000),
             memo: None,
         };
 
@@ -696,6 +1473,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_protocol_interactions_need_an_allowed_protocol() -> Result<()> {
+        let builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Full;
+        let action = AgentAction::ProtocolInteraction {
+            protocol: crate::protocols::ORCA_WHIRLPOOL.to_string(),
+            action: "swap".to_string(),
+            parameters: "{}".to_string(),
+        };
+
+        // Full permission alone is not enough
+        assert!(matches!(
+            builder.validate_permission(&action, &context),
+            Err(Error::PermissionDenied(_))
+        ));
+
+        let context = context.with_allowed_protocol(crate::types::Protocol::new(
+            "orca",
+            crate::protocols::ORCA_WHIRLPOOL_PROGRAM_ID,
+        ));
+        builder.validate_permission(&action, &context)?;
+        Ok(())
+    }
+
     #[test]
     fn test_transaction_options_default() {
         let options = TransactionOptions::default();
@@ -716,21 +1518,247 @@ mod tests {
         // Test SOL transfer
         let action = AgentAction::TransferSol {
             to: Pubkey::new_unique(),
-            amount: 1_000_000,
+            amount: Lamports::new(1_000_000),
             memo: Some("Test".to_string()),
         };
 
-        let instructions = builder.action_to_instructions(&action, &context)?;
+        let instructions =
+            builder.action_to_instructions(&action, &context, &TransactionOptions::default())?;
         assert!(!instructions.is_empty());
 
         // Test NoOp
         let action = AgentAction::NoOp;
-        let instructions = builder.action_to_instructions(&action, &context)?;
+        let instructions =
+            builder.action_to_instructions(&action, &context, &TransactionOptions::default())?;
         assert!(instructions.is_empty());
 
         Ok(())
     }
 
+    #[test]
+    fn test_stake_actions_build_and_count_against_budget() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Advanced;
+        context.spending_limits.per_transaction_limit_lamports = Lamports::from_sol_str("5")?;
+        let validator = Pubkey::new_unique();
+        let options = TransactionOptions {
+            stake_seed_index: Some(2),
+            ..Default::default()
+        };
+
+        let stake = AgentAction::StakeTokens {
+            staking_pool: validator,
+            amount: Lamports::new(2_000_000_000),
+        };
+        let transaction = builder.build_from_action(&stake, &context, &options)?;
+        let stake_account = stake::stake_account_address(&context.get_wallet_pubkey(), 2)?;
+        assert!(transaction.message.account_keys.contains(&stake_account));
+
+        // Staked SOL is spent like a transfer
+        context.spending_limits.per_transaction_limit_lamports = Lamports::from_sol_str("1")?;
+        assert!(builder
+            .build_from_action(&stake, &context, &options)
+            .is_err());
+
+        // Unstaking needs the activation state and spends nothing
+        let unstake = AgentAction::UnstakeTokens {
+            staking_pool: stake_account,
+            amount: Lamports::new(2_000_000_000),
+        };
+        assert!(matches!(
+            builder.build_from_action(&unstake, &context, &options),
+            Err(Error::Validation(_))
+        ));
+        let options = TransactionOptions {
+            stake_activation: Some(RpcStakeActivation {
+                state: solana_client::rpc_response::StakeActivationState::Active,
+                active: 2_000_000_000,
+                inactive: 0,
+            }),
+            ..Default::default()
+        };
+        builder.build_from_action(&unstake, &context, &options)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_account_actions_build_and_budget_rent() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Advanced;
+        let owner = context.get_wallet_pubkey();
+        let mint = Pubkey::new_unique();
+        let options = TransactionOptions::default();
+
+        let create = AgentAction::CreateTokenAccount { mint };
+        let instructions = builder.action_to_instructions(&create, &context, &options)?;
+        assert_eq!(
+            instructions,
+            vec![create_associated_token_account_idempotent(
+                &owner,
+                &owner,
+                &mint,
+                &spl_token::id()
+            )]
+        );
+
+        let account = get_associated_token_address(&owner, &mint);
+        let destination = Pubkey::new_unique();
+        let close = AgentAction::CloseTokenAccount {
+            account,
+            destination,
+        };
+        let instructions = builder.action_to_instructions(&close, &context, &options)?;
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(instructions[0].program_id, spl_token::id());
+        let accounts: Vec<Pubkey> = instructions[0].accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(accounts, vec![account, destination, owner]);
+        assert!(instructions[0].accounts[2].is_signer);
+
+        // Both need Advanced permission
+        context.permission_level = PermissionLevel::Basic;
+        assert!(builder.validate_permission(&create, &context).is_err());
+        assert!(builder.validate_permission(&close, &context).is_err());
+
+        // Rent of the new account counts against the budget; closing spends nothing
+        context.permission_level = PermissionLevel::Advanced;
+        context.spending_limits.per_transaction_limit_lamports =
+            Lamports::new(TOKEN_ACCOUNT_RENT_LAMPORTS - 1);
+        assert!(builder
+            .build_from_action(&create, &context, &options)
+            .is_err());
+        builder.build_from_action(&close, &context, &options)?;
+        context.spending_limits.per_transaction_limit_lamports =
+            Lamports::new(TOKEN_ACCOUNT_RENT_LAMPORTS);
+        builder.build_from_action(&create, &context, &options)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mint_and_burn_actions_use_recorded_decimals() -> Result<()> {
+        let builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Full;
+        let owner = context.get_wallet_pubkey();
+        let (mint, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let options = TransactionOptions::default();
+        let mint_action = AgentAction::MintToken {
+            mint,
+            to,
+            amount: 1_000,
+        };
+        let burn = AgentAction::BurnToken { mint, amount: 250 };
+
+        // Checked instructions cannot be built without the mint's decimals
+        assert!(builder
+            .action_to_instructions(&mint_action, &context, &options)
+            .is_err());
+        context.token_decimals.insert(mint, 6);
+
+        let instructions = builder.action_to_instructions(&mint_action, &context, &options)?;
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(
+            instructions[1],
+            token_instruction::mint_to_checked(
+                &spl_token::id(),
+                &mint,
+                &get_associated_token_address(&to, &mint),
+                &owner,
+                &[],
+                1_000,
+                6
+            )?
+        );
+        let instructions = builder.action_to_instructions(&burn, &context, &options)?;
+        assert_eq!(
+            instructions,
+            vec![token_instruction::burn_checked(
+                &spl_token::id(),
+                &get_associated_token_address(&owner, &mint),
+                &mint,
+                &owner,
+                &[],
+                250,
+                6
+            )?]
+        );
+
+        // Both need Full permission
+        context.permission_level = PermissionLevel::Advanced;
+        assert!(builder.validate_permission(&mint_action, &context).is_err());
+        assert!(builder.validate_permission(&burn, &context).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_budget_checks_the_instruction_amount() -> Result<()> {
+        let builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+
+        for _ in 0..1_000_000 {
+            // xorshift64: uniform SOL amounts in [0, 1000)
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let sol = (state >> 11) as f64 / (1u64 << 53) as f64 * 1000.0;
+
+            // The wallet converts once and both paths read the action amount
+            let lamports = Lamports::from_sol_f64_rounded(sol)?;
+            let action = AgentAction::TransferSol {
+                to: Pubkey::new_unique(),
+                amount: lamports,
+                memo: None,
+            };
+
+            let instructions = builder.action_to_instructions(
+                &action,
+                &context,
+                &TransactionOptions::default(),
+            )?;
+            let transferred = match bincode::deserialize(&instructions[0].data)? {
+                solana_sdk::system_instruction::SystemInstruction::Transfer { lamports } => {
+                    lamports
+                }
+                other => {
+                    return Err(Error::transaction(format!(
+                        "unexpected instruction {:?}",
+                        other
+                    )))
+                }
+            };
+            assert_eq!(transferred, lamports.as_u64());
+
+            // A limit of exactly the requested amount allows it, one lamport less does not
+            context.spending_limits.per_transaction_limit_lamports = lamports;
+            context.spending_limits.remaining_daily_budget_lamports = lamports;
+            assert!(builder.validate_spending_limits(&action, &context).is_ok());
+
+            if let Some(below) = lamports.checked_sub(Lamports::new(1)) {
+                context.spending_limits.per_transaction_limit_lamports = below;
+                assert!(builder.validate_spending_limits(&action, &context).is_err());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_confirmation_strategy_requirement() {
+        assert!(ConfirmationStrategy::None.requirement().is_none());
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            ConfirmationStrategy::Finalized { timeout }.requirement(),
+            Some((CommitmentConfig::finalized(), timeout))
+        );
+
+        assert!(matches!(
+            TransactionOptions::default().confirmation,
+            ConfirmationStrategy::Confirmed { .. }
+        ));
+    }
+
     #[test]
     fn test_fee_estimation() {
         let builder = TransactionBuilder::new();
@@ -750,4 +1778,459 @@ mod tests {
         // Base fee for 1 signature = 5000 lamports
         assert_eq!(fee, 5000);
     }
+
+    #[test]
+    fn test_token_transfer_instruction_order() -> Result<()> {
+        use spl_associated_token_account::instruction::AssociatedTokenAccountInstruction;
+
+        let mut builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Advanced;
+        let mint = Pubkey::new_unique();
+        context
+            .spending_limits
+            .token_limits
+            .insert(mint, TokenLimit::new(1_000_000, 10_000));
+        let action = AgentAction::TransferToken {
+            mint,
+            to: Pubkey::new_unique(),
+            amount: 1_000,
+            memo: Some("invoice".to_string()),
+        };
+        let options = TransactionOptions {
+            compute_unit_limit: Some(100_000),
+            compute_unit_price: Some(5_000),
+            ..Default::default()
+        };
+
+        let transaction = builder.build_from_action(&action, &context, &options)?;
+        let message = &transaction.message;
+        let programs: Vec<Pubkey> = message
+            .instructions
+            .iter()
+            .map(|ix| message.account_keys[ix.program_id_index as usize])
+            .collect();
+
+        assert_eq!(
+            programs,
+            vec![
+                solana_sdk::compute_budget::id(),
+                solana_sdk::compute_budget::id(),
+                spl_memo::id(),
+                spl_associated_token_account::id(),
+                spl_token::id(),
+            ]
+        );
+        assert_eq!(
+            message.instructions[0].data,
+            ComputeBudgetInstruction::set_compute_unit_limit(100_000).data
+        );
+        assert_eq!(
+            message.instructions[1].data,
+            ComputeBudgetInstruction::set_compute_unit_price(5_000).data
+        );
+
+        // CreateIdempotent, not Create, so an existing destination ATA is fine
+        assert_eq!(
+            message.instructions[3].data,
+            vec![AssociatedTokenAccountInstruction::CreateIdempotent as u8]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_memo_modes() -> Result<()> {
+        use crate::memo::{parse_attributed_memo, MemoAttribution, MemoMode};
+
+        let mut builder = TransactionBuilder::new();
+        let owner = Pubkey::new_unique();
+        let mut context = AgentContext::new(owner);
+        context.permission_level = PermissionLevel::Advanced;
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: Some("invoice".to_string()),
+        };
+        let memo_data = |transaction: &Transaction| {
+            let message = &transaction.message;
+            message
+                .instructions
+                .iter()
+                .find(|ix| message.account_keys[ix.program_id_index as usize] == spl_memo::id())
+                .map(|ix| ix.data.clone())
+        };
+
+        // Plain writes exactly the memo given
+        let plain = builder.build_from_action(&action, &context, &TransactionOptions::default())?;
+        assert_eq!(
+            memo_data(&plain),
+            Some(spl_memo::build_memo(b"invoice", &[&owner]).data)
+        );
+
+        let attribution = MemoAttribution::new("trader", uuid::Uuid::new_v4());
+        let options = TransactionOptions {
+            memo_mode: MemoMode::Attributed,
+            memo_attribution: Some(attribution.clone()),
+            ..Default::default()
+        };
+        let attributed = builder.build_from_action(&action, &context, &options)?;
+        let Some(data) = memo_data(&attributed) else {
+            panic!("attributed transfer has no memo");
+        };
+        let text = String::from_utf8_lossy(&data);
+        assert!(text.ends_with(";memo=invoice"));
+        assert_eq!(parse_attributed_memo(&text), Some(attribution));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nonce_transaction_advances_first_and_uses_stored_nonce() -> Result<()> {
+        use crate::rpc::mock::MockRpc;
+        use solana_sdk::system_instruction::SystemInstruction;
+
+        let keypair = crate::keypair::SecureKeypair::generate();
+        let mut builder = TransactionBuilder::new();
+        let context = AgentContext::new(keypair.public_key());
+        let nonce = NonceInfo::new(Pubkey::new_unique(), keypair.public_key());
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: Some("later".to_string()),
+        };
+        let options = TransactionOptions {
+            compute_unit_price: Some(5_000),
+            nonce: Some(nonce),
+            ..Default::default()
+        };
+
+        let mut transaction = builder.build_from_action(&action, &context, &options)?;
+        let message = &transaction.message;
+        let programs: Vec<Pubkey> = message
+            .instructions
+            .iter()
+            .map(|ix| message.account_keys[ix.program_id_index as usize])
+            .collect();
+        assert_eq!(
+            programs,
+            vec![
+                solana_sdk::system_program::id(),
+                solana_sdk::compute_budget::id(),
+                solana_sdk::compute_budget::id(),
+                spl_memo::id(),
+                solana_sdk::system_program::id(),
+            ]
+        );
+        assert!(matches!(
+            bincode::deserialize(&message.instructions[0].data)?,
+            SystemInstruction::AdvanceNonceAccount
+        ));
+        assert_eq!(nonce::nonce_account_of(message), Some(nonce.account));
+
+        // Signing uses the nonce stored in the account, not a fresh blockhash
+        let stored = Hash::new_unique();
+        let state = solana_sdk::nonce::state::Versions::new(
+            solana_sdk::nonce::state::State::Initialized(solana_sdk::nonce::state::Data::new(
+                nonce.authority,
+                solana_sdk::nonce::state::DurableNonce::from_blockhash(&stored),
+                5_000,
+            )),
+        );
+        let data = bincode::serialize(&state)?;
+        let expected = nonce::parse_nonce_data(&data)?.blockhash;
+        let rpc = MockRpc::new();
+        rpc.set_account(
+            nonce.account,
+            Account {
+                lamports: 1_447_680,
+                data,
+                owner: solana_sdk::system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        builder
+            .prepare_transaction(&mut transaction, &keypair, &rpc)
+            .await?;
+        assert_eq!(transaction.message.recent_blockhash, expected);
+        assert!(transaction.verify().is_ok());
+        Ok(())
+    }
+
+    /// Recipients with distinct keys and amounts
+    fn synthetic_recipients(count: usize) -> Vec<(Pubkey, u64)> {
+        (0..count)
+            .map(|i| (Pubkey::new_unique(), 1_000 + i as u64))
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_transfer_splits_at_size_limit() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let context = AgentContext::new(Pubkey::new_unique());
+        let options = TransactionOptions::default();
+        let recipients = synthetic_recipients(100);
+        let action = AgentAction::BatchTransfer {
+            transfers: recipients.clone(),
+            mint: None,
+            memo: Some("payroll".to_string()),
+        };
+
+        let chunks = builder.split_batch(&action, &context, &options)?;
+        let transactions = builder.build_batch_from_action(&action, &context, &options)?;
+        assert!(chunks.len() > 1);
+        assert_eq!(transactions.len(), chunks.len());
+
+        let mut packed = Vec::new();
+        for (i, (chunk, transaction)) in chunks.iter().zip(&transactions).enumerate() {
+            let size = bincode::serialized_size(transaction)? as usize;
+            assert!(size <= options.max_transaction_size);
+
+            let AgentAction::BatchTransfer { transfers, .. } = chunk else {
+                return Err(Error::validation("chunk is not a batch transfer"));
+            };
+            packed.extend(transfers.iter().copied());
+
+            // Each full chunk is closed exactly at the boundary
+            if let Some(next) = recipients.get(packed.len()) {
+                let mut grown = transfers.clone();
+                grown.push(*next);
+                let grown = AgentAction::BatchTransfer {
+                    transfers: grown,
+                    mint: None,
+                    memo: Some("payroll".to_string()),
+                };
+                assert!(
+                    !builder.fits_one_transaction(&grown, &context, &options)?,
+                    "chunk {} could have held another transfer",
+                    i
+                );
+            }
+        }
+        assert_eq!(packed, recipients);
+
+        // A batch that fits stays in one transaction
+        let small = AgentAction::BatchTransfer {
+            transfers: synthetic_recipients(3),
+            mint: None,
+            memo: None,
+        };
+        assert_eq!(
+            builder
+                .build_batch_from_action(&small, &context, &options)?
+                .len(),
+            1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_sums_cannot_bypass_limits() -> Result<()> {
+        let builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        let transfer = |amount: Lamports| AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount,
+            memo: None,
+        };
+
+        // 0.1 + 0.2 is 0.30000000000000004 as f64, but exactly 0.3 in lamports
+        let limit = Lamports::from_sol_str("0.3")?;
+        context.spending_limits.per_transaction_limit_lamports = limit;
+        let sum = Lamports::from_sol_str("0.1")?.try_add(Lamports::from_sol_str("0.2")?)?;
+        assert_eq!(sum, limit);
+        builder.validate_spending_limits(&transfer(sum), &context)?;
+        let over = sum.try_add(Lamports::new(1))?;
+        assert!(builder
+            .validate_spending_limits(&transfer(over), &context)
+            .is_err());
+
+        // Ten deductions of 0.1 SOL drain a 1 SOL budget without leaving dust
+        context.spending_limits.per_transaction_limit_lamports = Lamports::from_sol_str("1")?;
+        context.spending_limits.remaining_daily_budget_lamports = Lamports::from_sol_str("1")?;
+        for _ in 0..10 {
+            context.deduct_from_budget(Lamports::from_sol_str("0.1")?);
+        }
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::ZERO
+        );
+        assert!(builder
+            .validate_spending_limits(&transfer(Lamports::new(1)), &context)
+            .is_err());
+
+        // Two random amounts summed to a limit: the sum passes, one lamport more does not
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        for _ in 0..100_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let a = Lamports::new(state % (100 * LAMPORTS_PER_SOL));
+            let b = Lamports::new((state >> 17) % (100 * LAMPORTS_PER_SOL));
+            let total = a.try_add(b)?;
+            context.spending_limits.per_transaction_limit_lamports = total;
+            context.spending_limits.remaining_daily_budget_lamports = total;
+            builder.validate_spending_limits(&transfer(total), &context)?;
+            let over = total.try_add(Lamports::new(1))?;
+            assert!(builder
+                .validate_spending_limits(&transfer(over), &context)
+                .is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_transfer_limits_apply_to_total() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.spending_limits.per_transaction_limit_lamports = Lamports::from_sol_str("1")?;
+        let half = Lamports::from_sol_f64_rounded(0.6)?.as_u64();
+        let action = AgentAction::BatchTransfer {
+            transfers: vec![(Pubkey::new_unique(), half), (Pubkey::new_unique(), half)],
+            mint: None,
+            memo: None,
+        };
+
+        // Each leg is under the per-transaction limit, the batch is not
+        let result =
+            builder.build_batch_from_action(&action, &context, &TransactionOptions::default());
+        assert!(matches!(result, Err(Error::LimitExceeded(_))));
+
+        // Token batches need the permission of token transfers
+        let tokens = AgentAction::BatchTransfer {
+            transfers: synthetic_recipients(2),
+            mint: Some(Pubkey::new_unique()),
+            memo: None,
+        };
+        context.permission_level = PermissionLevel::Basic;
+        assert!(builder.validate_permission(&tokens, &context).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_limits_apply_per_mint() -> Result<()> {
+        let builder = TransactionBuilder::new();
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        context.permission_level = PermissionLevel::Advanced;
+        let (usdc, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        context
+            .spending_limits
+            .token_limits
+            .insert(usdc, TokenLimit::new(5_000_000, 2_000_000));
+        let transfer = |mint: Pubkey, amount: u64| AgentAction::TransferToken {
+            mint,
+            to: Pubkey::new_unique(),
+            amount,
+            memo: None,
+        };
+
+        // A listed mint has its own limits and leaves the SOL budget alone
+        let sol_budget = context.spending_limits.remaining_daily_budget_lamports;
+        assert_eq!(
+            context.is_token_action_allowed(&usdc, 2_000_000)?,
+            Lamports::ZERO
+        );
+        assert!(matches!(
+            builder.validate_spending_limits(&transfer(usdc, 2_000_001), &context),
+            Err(Error::LimitExceeded(_))
+        ));
+        for _ in 0..2 {
+            context.deduct_token_from_budget(&usdc, 2_000_000);
+        }
+        assert!(builder
+            .validate_spending_limits(&transfer(usdc, 1_000_001), &context)
+            .is_err());
+        builder.validate_spending_limits(&transfer(usdc, 1_000_000), &context)?;
+        let swap = AgentAction::SwapTokens {
+            input_mint: usdc,
+            output_mint: other,
+            amount: 1_500_000,
+            min_output_amount: 1,
+        };
+        assert!(builder.validate_spending_limits(&swap, &context).is_err());
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            sol_budget
+        );
+
+        // Unlisted mints are denied by default
+        assert!(matches!(
+            builder.validate_spending_limits(&transfer(other, 1), &context),
+            Err(Error::PermissionDenied(_))
+        ));
+        context.spending_limits.unlisted_tokens = UnlistedTokenPolicy::AllowUnlimited;
+        builder.validate_spending_limits(&transfer(other, u64::MAX), &context)?;
+
+        // Converted through the price feeds they are charged to the SOL budget
+        context.spending_limits.unlisted_tokens = UnlistedTokenPolicy::ConvertToSol;
+        assert!(context.is_token_action_allowed(&other, 1_000_000).is_err());
+        context.token_decimals.insert(other, 6);
+        context.price_feeds.insert(other.to_string(), 2.0);
+        context.price_feeds.insert("SOL".to_string(), 100.0);
+        assert_eq!(
+            context.is_token_action_allowed(&other, 25_000_000)?,
+            Lamports::from_sol_str("0.5")?
+        );
+        // 100 SOL worth breaks the 1 SOL per-transaction limit
+        assert!(matches!(
+            builder.validate_spending_limits(&transfer(other, 5_000_000_000), &context),
+            Err(Error::LimitExceeded(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_daily_reset_restores_sol_and_token_budgets() -> Result<()> {
+        let mut context = AgentContext::new(Pubkey::new_unique());
+        let mint = Pubkey::new_unique();
+        context
+            .spending_limits
+            .token_limits
+            .insert(mint, TokenLimit::new(1_000, 1_000));
+        context.deduct_from_budget(Lamports::from_sol_str("4")?);
+        context.deduct_token_from_budget(&mint, 900);
+
+        // Within the day nothing comes back
+        context.reset_daily_budget_if_needed();
+        assert_eq!(context.spending_limits.token_limits[&mint].remaining, 100);
+
+        context.spending_limits.last_reset = chrono::Utc::now() - chrono::Duration::days(1);
+        context.reset_daily_budget_if_needed();
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            context.spending_limits.daily_limit_lamports
+        );
+        assert_eq!(context.spending_limits.token_limits[&mint].remaining, 1_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_fee_without_compute_limit() -> Result<()> {
+        let mut builder = TransactionBuilder::new();
+        let context = AgentContext::new(Pubkey::new_unique());
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        let options = TransactionOptions {
+            compute_unit_limit: None,
+            priority_fee: Some(1_000),
+            ..Default::default()
+        };
+
+        let transaction = builder.build_from_action(&action, &context, &options)?;
+        let instructions = &transaction.message.instructions;
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(
+            instructions[0].data,
+            ComputeBudgetInstruction::set_compute_unit_price(1_000).data
+        );
+
+        // No budget instructions for an empty transaction
+        let transaction = builder.build_from_action(&AgentAction::NoOp, &context, &options)?;
+        assert!(transaction.message.instructions.is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,295 @@
+//! Details of landed transactions
+//!
+//! [`TransactionDetails`] is the part of a `getTransaction` response the
+//! wallet and the CLI report on: where and when the transaction landed, its
+//! fee, its error if it failed, its logs and the SOL balances of the
+//! accounts it touched. [`TransactionDetails::from_encoded`] parses it from
+//! any encoding of legacy and versioned transactions; account keys loaded
+//! from address lookup tables follow the static keys, so balances line up
+//! with [`TransactionDetails::account_keys`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::rpc::{RpcClient, RpcClientConfig};
+//! use solana_sdk::signature::Signature;
+//!
+//! # async fn example(rpc: RpcClient, signature: Signature) -> agent_wallet_core::Result<()> {
+//! let details = rpc.get_transaction(&signature).await?;
+//! println!("{}", details);
+//! for change in details.balance_changes() {
+//!     println!("{}: {:+}", change.account, change.delta());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    EncodedTransaction, TransactionVersion, UiMessage,
+};
+
+use crate::error::{Error, Result};
+use crate::preview::BalanceChange;
+
+/// A landed transaction, as reported by `getTransaction`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionDetails {
+    /// First signature of the transaction
+    pub signature: Signature,
+    /// Slot the transaction landed in
+    pub slot: Slot,
+    /// Estimated production time of the block, if known
+    pub block_time: Option<DateTime<Utc>>,
+    /// Fee paid, in lamports
+    pub fee: u64,
+    /// Why the transaction failed, `None` if it succeeded
+    pub error: Option<String>,
+    /// Program logs
+    pub logs: Vec<String>,
+    /// Accounts of the transaction, loaded addresses last
+    pub account_keys: Vec<Pubkey>,
+    /// Lamports of each account before the transaction
+    pub pre_balances: Vec<u64>,
+    /// Lamports of each account after the transaction
+    pub post_balances: Vec<u64>,
+    /// Message version, `None` for legacy transactions
+    pub version: Option<u8>,
+}
+
+impl TransactionDetails {
+    /// Parse a `getTransaction` response
+    ///
+    /// Fails if the response carries no status metadata or its transaction
+    /// cannot be decoded.
+    pub fn from_encoded(encoded: &EncodedConfirmedTransactionWithStatusMeta) -> Result<Self> {
+        let with_meta = &encoded.transaction;
+        let meta = with_meta
+            .meta
+            .as_ref()
+            .ok_or_else(|| Error::rpc("Transaction response carries no status metadata"))?;
+
+        let (signature, mut account_keys, needs_loaded) = match &with_meta.transaction {
+            EncodedTransaction::Json(transaction) => {
+                let signature = first_signature(&transaction.signatures)?;
+                match &transaction.message {
+                    UiMessage::Raw(message) => {
+                        (signature, parse_keys(&message.account_keys)?, true)
+                    }
+                    // Parsed messages already list the loaded addresses
+                    UiMessage::Parsed(message) => {
+                        let keys: Vec<&String> =
+                            message.account_keys.iter().map(|key| &key.pubkey).collect();
+                        (signature, parse_keys(keys)?, false)
+                    }
+                }
+            }
+            EncodedTransaction::Accounts(list) => {
+                let keys: Vec<&String> = list.account_keys.iter().map(|key| &key.pubkey).collect();
+                (first_signature(&list.signatures)?, parse_keys(keys)?, false)
+            }
+            other => {
+                let transaction = other
+                    .decode()
+                    .ok_or_else(|| Error::serialization("Failed to decode transaction"))?;
+                let signature = transaction
+                    .signatures
+                    .first()
+                    .copied()
+                    .ok_or_else(|| Error::serialization("Transaction carries no signature"))?;
+                (
+                    signature,
+                    transaction.message.static_account_keys().to_vec(),
+                    true,
+                )
+            }
+        };
+        if needs_loaded {
+            if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+                account_keys.extend(parse_keys(&loaded.writable)?);
+                account_keys.extend(parse_keys(&loaded.readonly)?);
+            }
+        }
+
+        let logs = match &meta.log_messages {
+            OptionSerializer::Some(logs) => logs.clone(),
+            _ => Vec::new(),
+        };
+        let version = match &with_meta.version {
+            Some(TransactionVersion::Number(number)) => Some(*number),
+            _ => None,
+        };
+
+        Ok(Self {
+            signature,
+            slot: encoded.slot,
+            block_time: encoded
+                .block_time
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+            fee: meta.fee,
+            error: meta.err.as_ref().map(|err| err.to_string()),
+            logs,
+            account_keys,
+            pre_balances: meta.pre_balances.clone(),
+            post_balances: meta.post_balances.clone(),
+            version,
+        })
+    }
+
+    /// Whether the transaction succeeded
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// SOL balance changes of the accounts the transaction touched
+    pub fn balance_changes(&self) -> Vec<BalanceChange> {
+        self.account_keys
+            .iter()
+            .zip(self.pre_balances.iter().zip(&self.post_balances))
+            .filter(|(_, (pre, post))| pre != post)
+            .map(|(account, (pre, post))| BalanceChange {
+                account: *account,
+                pre_lamports: *pre,
+                post_lamports: *post,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TransactionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Signature: {}", self.signature)?;
+        match &self.error {
+            None => writeln!(f, "Result:    success")?,
+            Some(error) => writeln!(f, "Result:    failed: {}", error)?,
+        }
+        writeln!(f, "Slot:      {}", self.slot)?;
+        if let Some(block_time) = self.block_time {
+            writeln!(
+                f,
+                "Time:      {}",
+                block_time.format("%Y-%m-%d %H:%M:%S UTC")
+            )?;
+        }
+        writeln!(f, "Fee:       {} lamports", self.fee)?;
+        match self.version {
+            Some(version) => writeln!(f, "Version:   {}", version)?,
+            None => writeln!(f, "Version:   legacy")?,
+        }
+        Ok(())
+    }
+}
+
+/// First of a list of base58 signatures
+fn first_signature(signatures: &[String]) -> Result<Signature> {
+    let signature = signatures
+        .first()
+        .ok_or_else(|| Error::serialization("Transaction carries no signature"))?;
+    signature
+        .parse()
+        .map_err(|e| Error::serialization(format!("Invalid signature {}: {}", signature, e)))
+}
+
+/// Parse base58 account keys
+fn parse_keys<I, S>(keys: I) -> Result<Vec<Pubkey>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    keys.into_iter()
+        .map(|key| {
+            let key = key.as_ref();
+            key.parse()
+                .map_err(|e| Error::serialization(format!("Invalid account key {}: {}", key, e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY_JSON: &str = include_str!("../tests/fixtures/transaction_legacy.json");
+    const V0_JSON: &str = include_str!("../tests/fixtures/transaction_v0.json");
+
+    fn parse(json: &str) -> Result<TransactionDetails> {
+        let encoded: EncodedConfirmedTransactionWithStatusMeta = serde_json::from_str(json)?;
+        TransactionDetails::from_encoded(&encoded)
+    }
+
+    fn key(base58: &str) -> Result<Pubkey> {
+        base58
+            .parse()
+            .map_err(|_| Error::serialization("bad fixture key"))
+    }
+
+    #[test]
+    fn test_legacy_json_transaction() -> Result<()> {
+        let details = parse(LEGACY_JSON)?;
+        assert!(details.is_success());
+        assert_eq!(details.slot, 312_457_723);
+        assert_eq!(details.fee, 5_000);
+        assert_eq!(details.version, None);
+        assert_eq!(
+            details.block_time.map(|time| time.timestamp()),
+            Some(1_718_030_563)
+        );
+        assert_eq!(details.logs.len(), 2);
+        assert_eq!(details.account_keys.len(), 3);
+
+        let payer = key("92vBXvpoy6Vh5z5dtQR71ee8wseDY3idM8TByDztC6v1")?;
+        let recipient = key("EnACmmKAR6dgAtPDp8AN965bZK183ePqLAGxag1v7YNA")?;
+        assert_eq!(
+            details.balance_changes(),
+            vec![
+                BalanceChange {
+                    account: payer,
+                    pre_lamports: 2_000_000_000,
+                    post_lamports: 1_998_995_000,
+                },
+                BalanceChange {
+                    account: recipient,
+                    pre_lamports: 0,
+                    post_lamports: 1_000_000,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_versioned_binary_transaction() -> Result<()> {
+        let details = parse(V0_JSON)?;
+        assert_eq!(details.version, Some(0));
+        assert!(!details.is_success());
+        assert!(details
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("Instruction 0")));
+        assert_eq!(details.logs.len(), 3);
+
+        // The recipient comes from the lookup table, after the static keys
+        let recipient = key("EnACmmKAR6dgAtPDp8AN965bZK183ePqLAGxag1v7YNA")?;
+        assert_eq!(details.account_keys.len(), 3);
+        assert_eq!(details.account_keys[2], recipient);
+        assert_eq!(
+            details.signature.to_string(),
+            "2dg13SEjq2sHTdQUwCyFJ4TrComWtnDprNVPrtbvN9SmQPPu33cdsQVLDPjaYgebQr2LSRsVerQuDh2RuQur3GLM"
+        );
+        assert!(details.to_string().contains("Version:   0"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_metadata_is_an_error() -> Result<()> {
+        let mut value: serde_json::Value = serde_json::from_str(LEGACY_JSON)?;
+        value["meta"] = serde_json::Value::Null;
+        assert!(parse(&value.to_string()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,567 @@
+//! Checks every transaction passes before it is signed
+//!
+//! A [`TransactionPolicy`] owns the pre-send rules: transaction size,
+//! signature and instruction counts, fee payer permission, spending limits,
+//! the address policy and whether the balance covers everything the
+//! transaction takes, itemized as a [`RequiredBreakdown`]. The same rules
+//! back two callers, so a transaction that validates is one the wallet will
+//! send:
+//!
+//! - [`TransactionPolicy::report`] lists every violation and warning in a
+//!   [`ValidationResult`], as `validate_transaction` returns it.
+//! - [`TransactionPolicy::enforce`] fails with the first violation's own
+//!   error, as the send path needs it, and returns the warnings otherwise.
+//!
+//! Warnings never block a send. The wallet records them in the audit log
+//! and the CLI prints them before asking for confirmation.
+//!
+//! # Example
+//!
+//! ```
+//! use agent_wallet_core::sol::Lamports;
+//! use agent_wallet_core::transaction::TransactionOptions;
+//! use agent_wallet_core::tx_policy::{PolicyInput, TransactionPolicy};
+//! use agent_wallet_core::types::AgentContext;
+//! use solana_sdk::{pubkey::Pubkey, system_instruction, transaction::Transaction};
+//!
+//! let wallet = Pubkey::new_unique();
+//! let context = AgentContext::new(wallet);
+//! let instruction = system_instruction::transfer(&wallet, &Pubkey::new_unique(), 1_000);
+//! let transaction = Transaction::new_with_payer(&[instruction], Some(&wallet));
+//!
+//! let policy = TransactionPolicy::from_options(&TransactionOptions::default());
+//! let input = PolicyInput::new(&transaction, &context)
+//!     .with_spend(Lamports::new(1_000))
+//!     .with_fee(5_000)
+//!     .with_balance(1_000_000, 1_000);
+//! assert!(policy.report(&input).is_valid);
+//! assert!(policy.enforce(&input).is_ok());
+//! ```
+
+use solana_sdk::transaction::Transaction;
+
+use crate::error::{Error, Result};
+use crate::sol::Lamports;
+use crate::transaction::{RequiredBreakdown, TransactionOptions, ValidationResult};
+use crate::types::{AgentContext, PermissionLevel};
+
+/// Most instructions a transaction may carry, Solana's instruction trace limit
+pub const DEFAULT_MAX_INSTRUCTIONS: usize = 64;
+
+/// Share of the maximum size above which a transaction draws a warning, in percent
+const SIZE_WARNING_PERCENT: usize = 90;
+
+/// Share of the remaining daily budget above which a spend draws a warning, in percent
+const BUDGET_WARNING_PERCENT: u64 = 50;
+
+/// One pre-send rule of a [`TransactionPolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PolicyRule {
+    /// Serialized size within `max_transaction_size`
+    Size,
+    /// Signature count within `max_signatures`
+    Signatures,
+    /// Instruction count within `max_instructions`
+    InstructionCount,
+    /// Only agents with `fee_payer_permission` may have someone else pay the fee
+    Permission,
+    /// Spend within the per-transaction limit and the remaining daily budget
+    SpendingLimit,
+    /// Every written account passes the address policy
+    AddressPolicy,
+    /// Balance covers the fee, transfers and rent, keeping the reserve
+    FeeVsBalance,
+}
+
+impl PolicyRule {
+    /// Every rule, in the order they are checked
+    pub const ALL: [PolicyRule; 7] = [
+        PolicyRule::Size,
+        PolicyRule::Signatures,
+        PolicyRule::InstructionCount,
+        PolicyRule::Permission,
+        PolicyRule::SpendingLimit,
+        PolicyRule::AddressPolicy,
+        PolicyRule::FeeVsBalance,
+    ];
+}
+
+/// Transaction under check and what is known about its cost
+///
+/// Rules whose inputs are unknown pass: without a balance, fee versus
+/// balance is not checked.
+#[derive(Debug, Clone, Copy)]
+pub struct PolicyInput<'a> {
+    /// Transaction to check
+    pub transaction: &'a Transaction,
+    /// Agent context supplying permission, limits and address policy
+    pub context: &'a AgentContext,
+    /// Value counted against the spending limits
+    pub spend: Lamports,
+    /// Estimated fee in lamports
+    pub fee_lamports: u64,
+    /// Wallet balance in lamports, if fetched
+    pub balance: Option<u64>,
+    /// Lamports leaving the wallet besides the fee: SOL transferred and rent
+    pub outgoing_lamports: u64,
+    /// Part of the outgoing lamports locked as rent in created accounts
+    pub rent_lamports: u64,
+    /// Balance that must remain in the wallet afterwards
+    pub reserve_lamports: u64,
+}
+
+impl<'a> PolicyInput<'a> {
+    /// Input spending nothing, with no fee or balance known
+    pub fn new(transaction: &'a Transaction, context: &'a AgentContext) -> Self {
+        Self {
+            transaction,
+            context,
+            spend: Lamports::ZERO,
+            fee_lamports: 0,
+            balance: None,
+            outgoing_lamports: 0,
+            rent_lamports: 0,
+            reserve_lamports: 0,
+        }
+    }
+
+    /// Value counted against the spending limits
+    pub fn with_spend(mut self, spend: Lamports) -> Self {
+        self.spend = spend;
+        self
+    }
+
+    /// Estimated fee in lamports
+    pub fn with_fee(mut self, fee_lamports: u64) -> Self {
+        self.fee_lamports = fee_lamports;
+        self
+    }
+
+    /// Wallet balance and the lamports leaving it besides the fee
+    pub fn with_balance(mut self, balance: u64, outgoing_lamports: u64) -> Self {
+        self.balance = Some(balance);
+        self.outgoing_lamports = outgoing_lamports;
+        self
+    }
+
+    /// Part of the outgoing lamports that is rent of created accounts
+    pub fn with_rent(mut self, rent_lamports: u64) -> Self {
+        self.rent_lamports = rent_lamports;
+        self
+    }
+
+    /// Balance that must remain in the wallet afterwards
+    pub fn with_reserve(mut self, reserve_lamports: u64) -> Self {
+        self.reserve_lamports = reserve_lamports;
+        self
+    }
+
+    /// What the wallet must hold for the transaction
+    ///
+    /// The fee counts only when the wallet pays it rather than a sponsor.
+    pub fn required(&self) -> RequiredBreakdown {
+        let wallet = self.context.get_wallet_pubkey();
+        let sponsored = matches!(
+            self.transaction.message.fee_payer(),
+            Some(payer) if *payer != wallet
+        );
+        RequiredBreakdown {
+            transfers: self.outgoing_lamports.saturating_sub(self.rent_lamports),
+            fees: if sponsored { 0 } else { self.fee_lamports },
+            rent: self.rent_lamports,
+            reserve: self.reserve_lamports,
+        }
+    }
+}
+
+/// Pre-send rules shared by validation reports and the send path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionPolicy {
+    /// Maximum serialized size in bytes
+    pub max_transaction_size: usize,
+    /// Maximum signatures per transaction
+    pub max_signatures: u8,
+    /// Maximum instructions per transaction
+    pub max_instructions: usize,
+    /// Least permission allowed to have a sponsor pay the fee
+    pub fee_payer_permission: PermissionLevel,
+}
+
+impl Default for TransactionPolicy {
+    fn default() -> Self {
+        Self::from_options(&TransactionOptions::default())
+    }
+}
+
+impl TransactionPolicy {
+    /// Policy with the size and signature limits of `options`
+    pub fn from_options(options: &TransactionOptions) -> Self {
+        Self {
+            max_transaction_size: options.max_transaction_size,
+            max_signatures: options.max_signatures,
+            max_instructions: DEFAULT_MAX_INSTRUCTIONS,
+            fee_payer_permission: PermissionLevel::Administrator,
+        }
+    }
+
+    /// Set the maximum instructions per transaction
+    pub fn with_max_instructions(mut self, max_instructions: usize) -> Self {
+        self.max_instructions = max_instructions;
+        self
+    }
+
+    /// Let agents at `level` or above use a fee payer other than the wallet
+    pub fn with_fee_payer_permission(mut self, level: PermissionLevel) -> Self {
+        self.fee_payer_permission = level;
+        self
+    }
+
+    /// Every rule the input violates, with the error it fails with
+    pub fn violations(&self, input: &PolicyInput<'_>) -> Vec<(PolicyRule, Error)> {
+        self.run(input).0
+    }
+
+    /// Check every rule and report all violations and warnings
+    ///
+    /// Fills in the transaction size and the signatures still missing; the
+    /// fee and compute estimates are left to the caller.
+    pub fn report(&self, input: &PolicyInput<'_>) -> ValidationResult {
+        let (violations, warnings) = self.run(input);
+        let mut result = ValidationResult::valid();
+        result.transaction_size = transaction_size(input.transaction);
+        result.required_breakdown = input.balance.map(|_| input.required());
+        result.missing_signatures = crate::multisig::missing_signers(input.transaction).len();
+        for (_, error) in violations {
+            result.add_error(error.to_string());
+        }
+        for warning in warnings {
+            result.add_warning(warning);
+        }
+        result
+    }
+
+    /// Check every rule and fail with the first violation
+    ///
+    /// Returns the warnings of a transaction that passes.
+    pub fn enforce(&self, input: &PolicyInput<'_>) -> Result<Vec<String>> {
+        let (violations, warnings) = self.run(input);
+        match violations.into_iter().next() {
+            Some((_, error)) => Err(error),
+            None => Ok(warnings),
+        }
+    }
+
+    /// Violations in rule order, then warnings
+    fn run(&self, input: &PolicyInput<'_>) -> (Vec<(PolicyRule, Error)>, Vec<String>) {
+        let mut violations = Vec::new();
+        let mut warnings = Vec::new();
+        for rule in PolicyRule::ALL {
+            if let Err(error) = self.check(rule, input, &mut warnings) {
+                violations.push((rule, error));
+            }
+        }
+        (violations, warnings)
+    }
+
+    /// Check a single rule, noting any warning it raises
+    fn check(
+        &self,
+        rule: PolicyRule,
+        input: &PolicyInput<'_>,
+        warnings: &mut Vec<String>,
+    ) -> Result<()> {
+        let transaction = input.transaction;
+        let context = input.context;
+        match rule {
+            PolicyRule::Size => {
+                let size = transaction_size(transaction);
+                if size > self.max_transaction_size {
+                    return Err(Error::TransactionValidation(format!(
+                        "Transaction size {} bytes exceeds maximum {} bytes",
+                        size, self.max_transaction_size
+                    )));
+                }
+                if size * 100 > self.max_transaction_size * SIZE_WARNING_PERCENT {
+                    warnings.push(format!(
+                        "Transaction size {} bytes is close to the {} byte maximum",
+                        size, self.max_transaction_size
+                    ));
+                }
+            }
+            PolicyRule::Signatures => {
+                let count = transaction.signatures.len();
+                if count > usize::from(self.max_signatures) {
+                    return Err(Error::TransactionValidation(format!(
+                        "Transaction has {} signatures, maximum is {}",
+                        count, self.max_signatures
+                    )));
+                }
+            }
+            PolicyRule::InstructionCount => {
+                let count = transaction.message.instructions.len();
+                if count > self.max_instructions {
+                    return Err(Error::TransactionValidation(format!(
+                        "Transaction has {} instructions, maximum is {}",
+                        count, self.max_instructions
+                    )));
+                }
+            }
+            PolicyRule::Permission => {
+                let custom_payer = transaction
+                    .message
+                    .fee_payer()
+                    .is_some_and(|payer| payer != &context.get_wallet_pubkey());
+                if custom_payer
+                    && !context
+                        .permission_level
+                        .can_perform(self.fee_payer_permission)
+                {
+                    return Err(Error::permission_denied(format!(
+                        "Custom fee payers require {} permission (have {})",
+                        self.fee_payer_permission, context.permission_level
+                    )));
+                }
+            }
+            PolicyRule::SpendingLimit => {
+                context.is_action_allowed(input.spend)?;
+                let remaining = context.spending_limits.remaining_daily_budget_lamports;
+                if input.spend.as_u64().saturating_mul(100)
+                    > remaining.as_u64().saturating_mul(BUDGET_WARNING_PERCENT)
+                {
+                    warnings.push(format!(
+                        "Spending {} of the {} left in today's budget",
+                        input.spend, remaining
+                    ));
+                }
+            }
+            PolicyRule::AddressPolicy => context.is_message_allowed(&transaction.message)?,
+            PolicyRule::FeeVsBalance => {
+                if let Some(balance) = input.balance {
+                    let required = input.required();
+                    if required.spent() > balance {
+                        return Err(Error::InsufficientFunds {
+                            required: required.total(),
+                            available: balance,
+                        });
+                    }
+                    let post_balance = balance - required.spent();
+                    if post_balance < required.reserve {
+                        return Err(Error::ReserveBreached {
+                            reserve: required.reserve,
+                            post_balance,
+                        });
+                    }
+                }
+                if input.spend > Lamports::ZERO && input.fee_lamports > input.spend.as_u64() {
+                    warnings.push(format!(
+                        "Fee of {} lamports exceeds the {} lamports sent",
+                        input.fee_lamports,
+                        input.spend.as_u64()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serialized size of a transaction in bytes
+fn transaction_size(transaction: &Transaction) -> usize {
+    bincode::serialized_size(transaction).unwrap_or(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::AddressPolicy;
+    use solana_sdk::{
+        message::Message, pubkey::Pubkey, system_instruction, transaction::Transaction,
+    };
+
+    /// Transaction of `legs` small transfers from `wallet`, paid by `payer`
+    fn transfers(wallet: &Pubkey, payer: &Pubkey, legs: usize) -> Transaction {
+        let instructions: Vec<_> = (0..legs)
+            .map(|_| system_instruction::transfer(wallet, &Pubkey::new_unique(), 1_000))
+            .collect();
+        Transaction::new_unsigned(Message::new(&instructions, Some(payer)))
+    }
+
+    #[test]
+    fn test_each_rule_in_isolation() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let context = AgentContext::new(wallet);
+        let mut restricted = AgentContext::new(wallet);
+        restricted.address_policy = AddressPolicy::allowlist_only(Vec::new());
+
+        let single = transfers(&wallet, &wallet, 1);
+        let double = transfers(&wallet, &wallet, 2);
+        let sponsored = transfers(&wallet, &Pubkey::new_unique(), 1);
+        let policy = TransactionPolicy::default();
+
+        let cases = [
+            (
+                PolicyRule::Size,
+                TransactionPolicy {
+                    max_transaction_size: 100,
+                    ..policy
+                },
+                PolicyInput::new(&single, &context),
+            ),
+            (
+                PolicyRule::Signatures,
+                TransactionPolicy {
+                    max_signatures: 0,
+                    ..policy
+                },
+                PolicyInput::new(&single, &context),
+            ),
+            (
+                PolicyRule::InstructionCount,
+                policy.with_max_instructions(1),
+                PolicyInput::new(&double, &context),
+            ),
+            (
+                PolicyRule::Permission,
+                policy,
+                PolicyInput::new(&sponsored, &context),
+            ),
+            (
+                PolicyRule::SpendingLimit,
+                policy,
+                PolicyInput::new(&single, &context).with_spend(Lamports::new(2_000_000_000)),
+            ),
+            (
+                PolicyRule::AddressPolicy,
+                policy,
+                PolicyInput::new(&single, &restricted),
+            ),
+            (
+                PolicyRule::FeeVsBalance,
+                policy,
+                PolicyInput::new(&single, &context)
+                    .with_fee(5_000)
+                    .with_balance(5_000, 1_000),
+            ),
+        ];
+        assert_eq!(cases.len(), PolicyRule::ALL.len());
+
+        for (rule, policy, input) in &cases {
+            let broken: Vec<_> = policy
+                .violations(input)
+                .into_iter()
+                .map(|(rule, _)| rule)
+                .collect();
+            assert_eq!(broken, vec![*rule], "{:?}", rule);
+
+            let report = policy.report(input);
+            assert!(!report.is_valid);
+            assert_eq!(report.errors.len(), 1);
+            assert!(policy.enforce(input).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fee_payer_permission_is_configurable() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let context = AgentContext::new(wallet);
+        let sponsored = transfers(&wallet, &Pubkey::new_unique(), 1);
+        let input = PolicyInput::new(&sponsored, &context);
+        assert_eq!(context.permission_level, PermissionLevel::Basic);
+
+        assert!(matches!(
+            TransactionPolicy::default().enforce(&input),
+            Err(Error::PermissionDenied(_))
+        ));
+        let sponsoring =
+            TransactionPolicy::default().with_fee_payer_permission(PermissionLevel::Basic);
+        assert!(sponsoring.report(&input).is_valid);
+
+        let mut read_only = context.clone();
+        read_only.permission_level = PermissionLevel::ReadOnly;
+        assert!(sponsoring
+            .enforce(&PolicyInput::new(&sponsored, &read_only))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_enforce_returns_the_rule_error() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let context = AgentContext::new(wallet);
+        let transaction = transfers(&wallet, &wallet, 1);
+        let policy = TransactionPolicy::default();
+
+        let input = PolicyInput::new(&transaction, &context)
+            .with_fee(5_000)
+            .with_balance(4_000, 0);
+        assert!(matches!(
+            policy.enforce(&input),
+            Err(Error::InsufficientFunds {
+                required: 5_000,
+                available: 4_000,
+            })
+        ));
+
+        // Affordable, but dipping into the reserve
+        let input = PolicyInput::new(&transaction, &context)
+            .with_fee(5_000)
+            .with_balance(1_000_000, 900_000)
+            .with_reserve(200_000);
+        assert!(matches!(
+            policy.enforce(&input),
+            Err(Error::ReserveBreached {
+                reserve: 200_000,
+                ..
+            })
+        ));
+        assert_eq!(
+            policy.report(&input).required_breakdown,
+            Some(RequiredBreakdown {
+                transfers: 900_000,
+                fees: 5_000,
+                rent: 0,
+                reserve: 200_000,
+            })
+        );
+
+        let input =
+            PolicyInput::new(&transaction, &context).with_spend(Lamports::new(2_000_000_000));
+        assert!(matches!(
+            policy.enforce(&input),
+            Err(Error::LimitExceeded(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_warnings_do_not_block() -> Result<()> {
+        let wallet = Pubkey::new_unique();
+        let context = AgentContext::new(wallet);
+        let transaction = transfers(&wallet, &wallet, 1);
+        let policy = TransactionPolicy::default();
+
+        // A 100 lamport transfer paying a 5000 lamport fee
+        let input = PolicyInput::new(&transaction, &context)
+            .with_spend(Lamports::new(100))
+            .with_fee(5_000)
+            .with_balance(1_000_000, 100);
+        let warnings = policy.enforce(&input)?;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(policy.report(&input).warnings, warnings);
+
+        // Most of the remaining daily budget in one go
+        let input = PolicyInput::new(&transaction, &context)
+            .with_spend(Lamports::new(1_000_000_000))
+            .with_fee(5_000);
+        let mut spent = context.clone();
+        spent.deduct_from_budget(Lamports::new(9_000_000_000));
+        assert!(policy.enforce(&input)?.is_empty());
+        let input = PolicyInput {
+            context: &spent,
+            ..input
+        };
+        assert_eq!(policy.enforce(&input)?.len(), 1);
+        Ok(())
+    }
+}
@@ -9,9 +9,12 @@ use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use solana_sdk::{message::Message, pubkey::Pubkey, signature::Signature};
 
 use crate::error::Error;
+use crate::policy::AddressPolicy;
+use crate::sol::{Lamports, LAMPORTS_PER_SOL};
+use crate::stats::{RollingSuccessRate, StatsSummary};
 
 /// Permission levels for agents and operations
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -59,8 +62,8 @@ pub enum AgentAction {
     TransferSol {
         /// Destination address
         to: Pubkey,
-        /// Amount in lamports
-        amount: u64,
+        /// Amount to send
+        amount: Lamports,
         /// Optional memo
         memo: Option<String>,
     },
@@ -75,6 +78,18 @@ pub enum AgentAction {
         /// Optional memo
         memo: Option<String>,
     },
+    /// Transfer SOL or one SPL token to many recipients at once
+    ///
+    /// Built into as few transactions as fit the size limit; spending limits
+    /// apply to the total of all transfers.
+    BatchTransfer {
+        /// Destination addresses and amounts (lamports or token base units)
+        transfers: Vec<(Pubkey, u64)>,
+        /// Token mint, or `None` for SOL
+        mint: Option<Pubkey>,
+        /// Optional memo, repeated in every transaction of the batch
+        memo: Option<String>,
+    },
     /// Swap tokens using a DEX
     SwapTokens {
         /// Input token mint
@@ -102,18 +117,54 @@ pub enum AgentAction {
         /// LP token amount to remove
         lp_token_amount: u64,
     },
-    /// Stake tokens
+    /// Stake SOL with a validator through a new stake account
     StakeTokens {
-        /// Staking program or pool
+        /// Validator vote account to delegate to
         staking_pool: Pubkey,
-        /// Token amount to stake
-        amount: u64,
+        /// Amount to delegate, not counting the stake account's rent reserve
+        amount: Lamports,
     },
-    /// Unstake tokens
+    /// Deactivate stake, or withdraw it once deactivated
     UnstakeTokens {
-        /// Staking program or pool
+        /// Stake account, or the vote account the wallet's stake is delegated to
         staking_pool: Pubkey,
-        /// Amount to unstake
+        /// Amount to withdraw once the stake is inactive
+        amount: Lamports,
+    },
+    /// Create the wallet's associated token account for a mint
+    ///
+    /// Idempotent: succeeds without change if the account already exists.
+    /// The account's rent counts against the spending budget.
+    CreateTokenAccount {
+        /// Token mint address
+        mint: Pubkey,
+    },
+    /// Close an empty token account owned by the wallet
+    ///
+    /// The account's rent is returned to `destination` and credited back
+    /// to the spending budget.
+    CloseTokenAccount {
+        /// Token account to close
+        account: Pubkey,
+        /// Address receiving the account's lamports
+        destination: Pubkey,
+    },
+    /// Mint new tokens of a mint whose mint authority is the wallet
+    ///
+    /// The recipient's associated token account is created if missing.
+    MintToken {
+        /// Token mint address
+        mint: Pubkey,
+        /// Wallet receiving the new tokens
+        to: Pubkey,
+        /// Amount in token base units
+        amount: u64,
+    },
+    /// Burn tokens from the wallet's associated token account
+    BurnToken {
+        /// Token mint address
+        mint: Pubkey,
+        /// Amount in token base units
         amount: u64,
     },
     /// Custom protocol interaction
@@ -135,27 +186,73 @@ impl AgentAction {
         match self {
             AgentAction::TransferSol { .. } => PermissionLevel::Basic,
             AgentAction::TransferToken { .. } => PermissionLevel::Advanced,
+            AgentAction::BatchTransfer { mint: None, .. } => PermissionLevel::Basic,
+            AgentAction::BatchTransfer { mint: Some(_), .. } => PermissionLevel::Advanced,
             AgentAction::SwapTokens { .. } => PermissionLevel::Advanced,
             AgentAction::ProvideLiquidity { .. } => PermissionLevel::Advanced,
             AgentAction::RemoveLiquidity { .. } => PermissionLevel::Advanced,
             AgentAction::StakeTokens { .. } => PermissionLevel::Advanced,
             AgentAction::UnstakeTokens { .. } => PermissionLevel::Advanced,
+            AgentAction::CreateTokenAccount { .. } => PermissionLevel::Advanced,
+            AgentAction::CloseTokenAccount { .. } => PermissionLevel::Advanced,
+            AgentAction::MintToken { .. } => PermissionLevel::Full,
+            AgentAction::BurnToken { .. } => PermissionLevel::Full,
             AgentAction::ProtocolInteraction { .. } => PermissionLevel::Full,
             AgentAction::NoOp => PermissionLevel::ReadOnly,
         }
     }
 
+    /// Short snake_case name of the kind of action, as used in statistics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AgentAction::TransferSol { .. } => "transfer_sol",
+            AgentAction::TransferToken { .. } => "transfer_token",
+            AgentAction::BatchTransfer { .. } => "batch_transfer",
+            AgentAction::SwapTokens { .. } => "swap_tokens",
+            AgentAction::ProvideLiquidity { .. } => "provide_liquidity",
+            AgentAction::RemoveLiquidity { .. } => "remove_liquidity",
+            AgentAction::StakeTokens { .. } => "stake_tokens",
+            AgentAction::UnstakeTokens { .. } => "unstake_tokens",
+            AgentAction::CreateTokenAccount { .. } => "create_token_account",
+            AgentAction::CloseTokenAccount { .. } => "close_token_account",
+            AgentAction::MintToken { .. } => "mint_token",
+            AgentAction::BurnToken { .. } => "burn_token",
+            AgentAction::ProtocolInteraction { .. } => "protocol_interaction",
+            AgentAction::NoOp => "no_op",
+        }
+    }
+
     /// Get a brief description of the action
     pub fn description(&self) -> String {
         match self {
             AgentAction::TransferSol { to, amount, .. } => {
-                format!("Transfer {} lamports to {}", amount, to)
+                format!("Transfer {} lamports to {}", amount.as_u64(), to)
             }
             AgentAction::TransferToken {
                 mint, to, amount, ..
             } => {
                 format!("Transfer {} of token {} to {}", amount, mint, to)
             }
+            AgentAction::BatchTransfer {
+                transfers, mint, ..
+            } => {
+                let total = transfers
+                    .iter()
+                    .fold(0u64, |total, (_, amount)| total.saturating_add(*amount));
+                match mint {
+                    Some(mint) => format!(
+                        "Transfer {} of token {} to {} recipients",
+                        total,
+                        mint,
+                        transfers.len()
+                    ),
+                    None => format!(
+                        "Transfer {} lamports to {} recipients",
+                        total,
+                        transfers.len()
+                    ),
+                }
+            }
             AgentAction::SwapTokens {
                 input_mint,
                 output_mint,
@@ -178,14 +275,64 @@ impl AgentAction {
             } => {
                 format!("Remove {} LP tokens", lp_token_amount)
             }
-            AgentAction::StakeTokens { amount, .. } => format!("Stake {} tokens", amount),
-            AgentAction::UnstakeTokens { amount, .. } => format!("Unstake {} tokens", amount),
+            AgentAction::StakeTokens {
+                staking_pool,
+                amount,
+            } => format!("Stake {} lamports with {}", amount.as_u64(), staking_pool),
+            AgentAction::UnstakeTokens {
+                staking_pool,
+                amount,
+            } => format!("Unstake {} lamports from {}", amount.as_u64(), staking_pool),
+            AgentAction::CreateTokenAccount { mint } => {
+                format!("Create token account for mint {}", mint)
+            }
+            AgentAction::CloseTokenAccount {
+                account,
+                destination,
+            } => format!("Close token account {} to {}", account, destination),
+            AgentAction::MintToken { mint, to, amount } => {
+                format!("Mint {} of token {} to {}", amount, mint, to)
+            }
+            AgentAction::BurnToken { mint, amount } => {
+                format!("Burn {} of token {}", amount, mint)
+            }
             AgentAction::ProtocolInteraction {
                 protocol, action, ..
             } => format!("Interact with {}: {}", protocol, action),
             AgentAction::NoOp => "No operation".to_string(),
         }
     }
+
+    /// Sum of the amounts of a batch of transfers
+    pub fn batch_total(transfers: &[(Pubkey, u64)]) -> Result<u64, Error> {
+        transfers.iter().try_fold(0u64, |total, (_, amount)| {
+            total
+                .checked_add(*amount)
+                .ok_or_else(|| Error::InvalidAmount("Batch transfer total overflows".to_string()))
+        })
+    }
+
+    /// Token mint and amount the action spends, if it spends a token
+    pub fn token_spend(&self) -> Option<(Pubkey, u64)> {
+        match self {
+            AgentAction::TransferToken { mint, amount, .. } => Some((*mint, *amount)),
+            AgentAction::BatchTransfer {
+                transfers,
+                mint: Some(mint),
+                ..
+            } => Some((
+                *mint,
+                transfers
+                    .iter()
+                    .fold(0u64, |total, (_, amount)| total.saturating_add(*amount)),
+            )),
+            AgentAction::SwapTokens {
+                input_mint, amount, ..
+            } => Some((*input_mint, *amount)),
+            AgentAction::BurnToken { mint, amount } => Some((*mint, *amount)),
+            _ => None,
+        }
+    }
 }
 
 /// Market conditions for decision-making
@@ -239,18 +386,103 @@ pub struct OracleData {
 }
 
 /// Spending limits for agents
+///
+/// Limits saved by older versions under the `_sol` names, as `f64` SOL,
+/// are read into the lamport fields.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpendingLimits {
-    /// Daily spending limit in SOL
-    pub daily_limit_sol: f64,
+    /// Daily spending limit
+    #[serde(
+        alias = "daily_limit_sol",
+        deserialize_with = "legacy_sol::deserialize"
+    )]
+    pub daily_limit_lamports: Lamports,
     /// Daily spending limit in USD equivalent
     pub daily_limit_usd: f64,
-    /// Per-transaction limit in SOL
-    pub per_transaction_limit_sol: f64,
-    /// Remaining daily budget in SOL
-    pub remaining_daily_budget_sol: f64,
+    /// Per-transaction limit
+    #[serde(
+        alias = "per_transaction_limit_sol",
+        deserialize_with = "legacy_sol::deserialize"
+    )]
+    pub per_transaction_limit_lamports: Lamports,
+    /// Remaining daily budget
+    #[serde(
+        alias = "remaining_daily_budget_sol",
+        deserialize_with = "legacy_sol::deserialize"
+    )]
+    pub remaining_daily_budget_lamports: Lamports,
     /// Last reset timestamp
     pub last_reset: DateTime<Utc>,
+    /// Rent locked in accounts the agent created, in lamports
+    ///
+    /// Rent is reclaimable by closing the accounts, so it is tracked apart
+    /// from the daily budget instead of being deducted from it.
+    #[serde(default)]
+    pub reclaimable_rent_lamports: u64,
+    /// Balance kept for fees that outgoing transfers may not spend, in lamports
+    #[serde(default)]
+    pub min_sol_reserve_lamports: u64,
+    /// Limits of tokens with a budget of their own, by mint
+    #[serde(default, with = "pubkey_map")]
+    pub token_limits: HashMap<Pubkey, TokenLimit>,
+    /// How tokens without an entry in `token_limits` are treated
+    #[serde(default)]
+    pub unlisted_tokens: UnlistedTokenPolicy,
+}
+
+/// Daily and per-transaction limits of one token, in its base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenLimit {
+    /// Amount that may leave the wallet per day
+    pub daily_limit: u64,
+    /// Amount that may leave the wallet in one transaction
+    pub per_tx_limit: u64,
+    /// Amount left for today
+    pub remaining: u64,
+}
+
+impl TokenLimit {
+    /// Limits with the whole daily amount still available
+    pub fn new(daily_limit: u64, per_tx_limit: u64) -> Self {
+        Self {
+            daily_limit,
+            per_tx_limit,
+            remaining: daily_limit,
+        }
+    }
+}
+
+/// How tokens without a [`TokenLimit`] are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlistedTokenPolicy {
+    /// Refuse to move them
+    #[default]
+    Deny,
+    /// Move any amount
+    AllowUnlimited,
+    /// Value them in SOL through the price feeds and charge the SOL budget
+    ConvertToSol,
+}
+
+impl SpendingLimits {
+    /// Daily spending limit in SOL
+    #[deprecated(note = "use `daily_limit_lamports`")]
+    pub fn daily_limit_sol(&self) -> f64 {
+        self.daily_limit_lamports.to_sol_f64()
+    }
+
+    /// Per-transaction limit in SOL
+    #[deprecated(note = "use `per_transaction_limit_lamports`")]
+    pub fn per_transaction_limit_sol(&self) -> f64 {
+        self.per_transaction_limit_lamports.to_sol_f64()
+    }
+
+    /// Remaining daily budget in SOL
+    #[deprecated(note = "use `remaining_daily_budget_lamports`")]
+    pub fn remaining_daily_budget_sol(&self) -> f64 {
+        self.remaining_daily_budget_lamports.to_sol_f64()
+    }
 }
 
 /// Protocol information
@@ -268,6 +500,40 @@ pub struct Protocol {
     pub risk_level: f64,
 }
 
+impl Protocol {
+    /// Protocol at `address`, allowed for every action at risk level 0
+    pub fn new(name: impl Into<String>, address: Pubkey) -> Self {
+        Self {
+            name: name.into(),
+            address,
+            version: String::new(),
+            supported_actions: Vec::new(),
+            risk_level: 0.0,
+        }
+    }
+
+    /// Set the risk level (0-1 scale)
+    pub fn with_risk_level(mut self, risk_level: f64) -> Self {
+        self.risk_level = risk_level;
+        self
+    }
+
+    /// Restrict the protocol to these actions
+    pub fn with_supported_actions(mut self, actions: Vec<String>) -> Self {
+        self.supported_actions = actions;
+        self
+    }
+
+    /// Whether the protocol may be used for `action`
+    pub fn supports(&self, action: &str) -> bool {
+        self.supported_actions.is_empty()
+            || self
+                .supported_actions
+                .iter()
+                .any(|supported| supported == action)
+    }
+}
+
 /// Transaction record for history tracking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRecord {
@@ -289,6 +555,9 @@ pub struct TransactionRecord {
     pub fee: u64,
     /// Optional memo
     pub memo: Option<String>,
+    /// Agent decision named in an attributed memo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<crate::memo::MemoAttribution>,
 }
 
 /// Transaction status
@@ -334,26 +603,66 @@ pub struct WalletInfo {
     pub balance_lamports: u64,
     /// Number of transactions
     pub transaction_count: u64,
+    /// Whether the wallet has more transactions than were counted
+    #[serde(default)]
+    pub transaction_count_truncated: bool,
     /// Permission level
     pub permission_level: PermissionLevel,
     /// Whether wallet is active
     pub is_active: bool,
+    /// Token accounts the wallet owns
+    #[serde(default)]
+    pub token_accounts: Vec<TokenBalanceSummary>,
+}
+
+impl WalletInfo {
+    /// Transaction count for display, with a `+` when it was capped
+    pub fn transaction_count_label(&self) -> String {
+        if self.transaction_count_truncated {
+            format!("{}+", self.transaction_count)
+        } else {
+            self.transaction_count.to_string()
+        }
+    }
+}
+
+/// Balance of one token account of a wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenBalanceSummary {
+    /// Token mint
+    pub mint: Pubkey,
+    /// Token symbol, if the mint has metadata
+    pub symbol: Option<String>,
+    /// Balance in whole tokens; `None` if the mint could not be read
+    pub ui_amount: Option<f64>,
 }
 
 /// Agent context for decision-making
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentContext {
     // Wallet state
+    /// Public key of the wallet the agent operates
+    #[serde(default)]
+    pub wallet_pubkey: Pubkey,
     /// Current SOL balance
     pub wallet_balance: f64,
     /// Token balances (mint -> balance in base units)
     pub token_balances: HashMap<Pubkey, u64>,
+    /// Mints whose balances could not be refreshed and may be outdated
+    #[serde(default)]
+    pub stale_token_mints: Vec<Pubkey>,
+    /// Decimals of the mints the wallet has looked up
+    #[serde(default, with = "pubkey_map")]
+    pub token_decimals: HashMap<Pubkey, u8>,
     /// Recent transaction history
     pub transaction_history: Vec<TransactionRecord>,
 
     // Market data
     /// Price feeds (symbol -> price)
     pub price_feeds: HashMap<String, f64>,
+    /// Symbols whose last quote was too old and was dropped from the feeds
+    #[serde(default)]
+    pub stale_price_feeds: Vec<String>,
     /// Current market conditions
     pub market_conditions: MarketConditions,
     /// Optional oracle data
@@ -370,10 +679,16 @@ pub struct AgentContext {
     // Agent state
     /// Number of decisions made
     pub decision_count: u64,
-    /// Success rate (0-1 scale)
+    /// Success rate over the latest outcomes (0-1 scale)
     pub success_rate: f64,
+    /// Latest outcomes the success rate is taken over
+    #[serde(default)]
+    pub recent_outcomes: RollingSuccessRate,
     /// Recent errors
     pub recent_errors: Vec<AgentError>,
+    /// The agent's own statistics, filled in by its runner
+    #[serde(default)]
+    pub performance: Option<StatsSummary>,
 
     // Configuration
     /// Spending limits
@@ -382,6 +697,62 @@ pub struct AgentContext {
     pub allowed_protocols: Vec<Protocol>,
     /// Current permission level
     pub permission_level: PermissionLevel,
+    /// Destinations transactions may send funds to
+    #[serde(default)]
+    pub address_policy: AddressPolicy,
+
+    // Event data
+    /// External event that triggered this decision, if any
+    #[serde(default)]
+    pub trigger: Option<TriggerPayload>,
+
+    /// When the network-backed fields were last refreshed
+    #[serde(default)]
+    pub freshness: ContextFreshness,
+}
+
+/// When each network-backed part of an [`AgentContext`] was last fetched
+///
+/// `None` means never; such fields hold their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextFreshness {
+    /// SOL balance
+    pub balance: Option<DateTime<Utc>>,
+    /// Token balances
+    pub token_balances: Option<DateTime<Utc>>,
+    /// Transaction history
+    pub transaction_history: Option<DateTime<Utc>>,
+    /// Price feeds and the market conditions derived from them
+    pub price_feeds: Option<DateTime<Utc>>,
+}
+
+/// External event delivered to an agent to trigger a decision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriggerPayload {
+    /// Where the event came from (e.g. "http", "tradingview")
+    pub source: String,
+    /// Event data as sent by the source
+    pub data: serde_json::Value,
+    /// When the event was received
+    pub received_at: DateTime<Utc>,
+}
+
+impl TriggerPayload {
+    /// Create a payload received now
+    pub fn new(source: impl Into<String>, data: serde_json::Value) -> Self {
+        Self {
+            source: source.into(),
+            data,
+            received_at: Utc::now(),
+        }
+    }
+
+    /// Size of the serialized event data in bytes
+    pub fn size(&self) -> usize {
+        serde_json::to_vec(&self.data)
+            .map(|bytes| bytes.len())
+            .unwrap_or(usize::MAX)
+    }
 }
 
 impl AgentContext {
@@ -389,11 +760,15 @@ impl AgentContext {
     pub fn new(public_key: Pubkey) -> Self {
         let now = Utc::now();
         Self {
+            wallet_pubkey: public_key,
             wallet_balance: 0.0,
             token_balances: HashMap::new(),
+            stale_token_mints: Vec::new(),
+            token_decimals: HashMap::new(),
             transaction_history: Vec::new(),
 
             price_feeds: HashMap::new(),
+            stale_price_feeds: Vec::new(),
             market_conditions: MarketConditions {
                 volatility: 0.5,
                 trend: MarketTrend::Neutral,
@@ -408,20 +783,37 @@ impl AgentContext {
 
             decision_count: 0,
             success_rate: 1.0,
+            recent_outcomes: RollingSuccessRate::default(),
             recent_errors: Vec::new(),
+            performance: None,
 
             spending_limits: SpendingLimits {
-                daily_limit_sol: 10.0,
+                daily_limit_lamports: Lamports::new(10 * LAMPORTS_PER_SOL),
                 daily_limit_usd: 100.0,
-                per_transaction_limit_sol: 1.0,
-                remaining_daily_budget_sol: 10.0,
+                per_transaction_limit_lamports: Lamports::new(LAMPORTS_PER_SOL),
+                remaining_daily_budget_lamports: Lamports::new(10 * LAMPORTS_PER_SOL),
                 last_reset: now,
+                reclaimable_rent_lamports: 0,
+                min_sol_reserve_lamports: 0,
+                token_limits: HashMap::new(),
+                unlisted_tokens: UnlistedTokenPolicy::Deny,
             },
             allowed_protocols: Vec::new(),
             permission_level: PermissionLevel::Basic,
+            address_policy: AddressPolicy::default(),
+
+            trigger: None,
+
+            freshness: ContextFreshness::default(),
         }
     }
 
+    /// SOL balance above the minimum reserve, which transfers may spend
+    pub fn spendable_balance(&self) -> f64 {
+        let reserve = Lamports::new(self.spending_limits.min_sol_reserve_lamports).to_sol_f64();
+        (self.wallet_balance - reserve).max(0.0)
+    }
+
     /// Update the timestamp and recalculate time since last action
     pub fn update_timestamp(&mut self) {
         let now = Utc::now();
@@ -439,8 +831,8 @@ impl AgentContext {
     /// Record a successful action
     pub fn record_success(&mut self) {
         self.decision_count += 1;
-        // Update success rate with exponential moving average
-        self.success_rate = (self.success_rate * 0.95) + (1.0 * 0.05);
+        self.recent_outcomes.record(true);
+        self.success_rate = self.recent_outcomes.rate();
         self.last_action_time = Some(Utc::now());
         self.update_timestamp();
     }
@@ -448,8 +840,8 @@ impl AgentContext {
     /// Record a failed action with error
     pub fn record_failure(&mut self, error: Error, context: String) {
         self.decision_count += 1;
-        // Update success rate with exponential moving average
-        self.success_rate = (self.success_rate * 0.95) + (0.0 * 0.05);
+        self.recent_outcomes.record(false);
+        self.success_rate = self.recent_outcomes.rate();
 
         let agent_error = AgentError {
             message: error.to_string(),
@@ -470,38 +862,156 @@ impl AgentContext {
         self.update_timestamp();
     }
 
-    /// Check if an action is allowed based on spending limits
-    pub fn is_action_allowed(&self, sol_amount: f64) -> Result<(), Error> {
-        // Check per-transaction limit
-        if sol_amount > self.spending_limits.per_transaction_limit_sol {
+    /// Check an amount leaving the wallet against the spending limits
+    ///
+    /// An amount exactly at a limit is allowed.
+    pub fn is_action_allowed(&self, amount: Lamports) -> Result<(), Error> {
+        let limits = &self.spending_limits;
+        if amount > limits.per_transaction_limit_lamports {
             return Err(Error::LimitExceeded(format!(
-                "Transaction amount {} SOL exceeds per-transaction limit {} SOL",
-                sol_amount, self.spending_limits.per_transaction_limit_sol
+                "Transaction amount {} exceeds per-transaction limit {}",
+                amount, limits.per_transaction_limit_lamports
             )));
         }
-
-        // Check daily budget
-        if sol_amount > self.spending_limits.remaining_daily_budget_sol {
+        if amount > limits.remaining_daily_budget_lamports {
             return Err(Error::InsufficientFunds {
-                required: (sol_amount * 1_000_000_000.0) as u64,
-                available: (self.spending_limits.remaining_daily_budget_sol * 1_000_000_000.0)
-                    as u64,
+                required: amount.as_u64(),
+                available: limits.remaining_daily_budget_lamports.as_u64(),
             });
         }
-
         Ok(())
     }
 
-    /// Deduct from daily budget
-    pub fn deduct_from_budget(&mut self, sol_amount: f64) {
-        self.spending_limits.remaining_daily_budget_sol -= sol_amount;
+    /// Check a token amount leaving the wallet against the spending limits
+    ///
+    /// Mints listed in `token_limits` are checked against their own limits
+    /// and cost the SOL budget nothing. Other mints follow
+    /// `unlisted_tokens`; when they are converted, the returned value is
+    /// what they cost the SOL budget.
+    pub fn is_token_action_allowed(&self, mint: &Pubkey, amount: u64) -> Result<Lamports, Error> {
+        let limits = &self.spending_limits;
+        if let Some(limit) = limits.token_limits.get(mint) {
+            if amount > limit.per_tx_limit {
+                return Err(Error::LimitExceeded(format!(
+                    "Amount {} of token {} exceeds its per-transaction limit {}",
+                    amount, mint, limit.per_tx_limit
+                )));
+            }
+            if amount > limit.remaining {
+                return Err(Error::LimitExceeded(format!(
+                    "Amount {} of token {} exceeds the {} left of its daily limit",
+                    amount, mint, limit.remaining
+                )));
+            }
+            return Ok(Lamports::ZERO);
+        }
 
-        // Ensure it doesn't go below zero
-        if self.spending_limits.remaining_daily_budget_sol < 0.0 {
-            self.spending_limits.remaining_daily_budget_sol = 0.0;
+        match limits.unlisted_tokens {
+            UnlistedTokenPolicy::Deny => Err(Error::permission_denied(format!(
+                "Token {} has no spending limit and unlisted tokens are denied",
+                mint
+            ))),
+            UnlistedTokenPolicy::AllowUnlimited => Ok(Lamports::ZERO),
+            UnlistedTokenPolicy::ConvertToSol => {
+                let value = self.token_sol_value(mint, amount)?;
+                self.is_action_allowed(value)?;
+                Ok(value)
+            }
         }
     }
 
+    /// Value of a token amount in lamports, from the USD price feeds
+    ///
+    /// Needs the feed of the mint's address, the `SOL` feed and the mint's
+    /// decimals in `token_decimals`.
+    pub fn token_sol_value(&self, mint: &Pubkey, amount: u64) -> Result<Lamports, Error> {
+        let decimals = self
+            .token_decimals
+            .get(mint)
+            .ok_or_else(|| Error::validation(format!("Decimals of token {} are unknown", mint)))?;
+        let price = |symbol: &str| {
+            self.price_feeds
+                .get(symbol)
+                .copied()
+                .filter(|price| *price > 0.0)
+                .ok_or_else(|| Error::validation(format!("No price feed for {}", symbol)))
+        };
+        let token_usd = price(&mint.to_string())?;
+        let sol_usd = price("SOL")?;
+        let tokens = amount as f64 / 10f64.powi(i32::from(*decimals));
+        Lamports::from_sol_f64_rounded(tokens * token_usd / sol_usd)
+    }
+
+    /// Check a declared destination against the address policy
+    pub fn is_destination_allowed(&self, address: &Pubkey) -> Result<(), Error> {
+        self.address_policy.check_destination(address)
+    }
+
+    /// Allow agents to use `protocol`
+    pub fn with_allowed_protocol(mut self, protocol: Protocol) -> Self {
+        self.allowed_protocols.push(protocol);
+        self
+    }
+
+    /// Check an action against the allowed protocols
+    ///
+    /// Fails with `Error::PermissionDenied` naming the program.
+    pub fn is_protocol_allowed(&self, action: &AgentAction) -> Result<(), Error> {
+        crate::protocols::check_action(action, &self.allowed_protocols)
+    }
+
+    /// Check every account a message writes to against the address policy
+    ///
+    /// Fails with `Error::PermissionDenied` naming the offending account.
+    pub fn is_message_allowed(&self, message: &Message) -> Result<(), Error> {
+        self.address_policy
+            .check_message(message, &self.wallet_pubkey)
+    }
+
+    /// Deduct a spent amount from the daily budget, stopping at zero
+    pub fn deduct_from_budget(&mut self, amount: Lamports) {
+        let limits = &mut self.spending_limits;
+        limits.remaining_daily_budget_lamports = limits
+            .remaining_daily_budget_lamports
+            .saturating_sub(amount);
+    }
+
+    /// Credit a refunded amount back to the daily budget, up to the daily limit
+    pub fn credit_budget(&mut self, amount: Lamports) {
+        let limits = &mut self.spending_limits;
+        limits.remaining_daily_budget_lamports = limits
+            .remaining_daily_budget_lamports
+            .checked_add(amount)
+            .unwrap_or(limits.daily_limit_lamports)
+            .min(limits.daily_limit_lamports);
+    }
+
+    /// Deduct a spent token amount from the daily limit of its mint
+    ///
+    /// Unlisted mints have no token budget; what they cost in SOL is
+    /// deducted with [`AgentContext::deduct_from_budget`].
+    pub fn deduct_token_from_budget(&mut self, mint: &Pubkey, amount: u64) {
+        if let Some(limit) = self.spending_limits.token_limits.get_mut(mint) {
+            limit.remaining = limit.remaining.saturating_sub(amount);
+        }
+    }
+
+    /// Record rent locked in newly created accounts
+    pub fn record_rent(&mut self, rent: Lamports) {
+        self.spending_limits.reclaimable_rent_lamports = self
+            .spending_limits
+            .reclaimable_rent_lamports
+            .saturating_add(rent.as_u64());
+    }
+
+    /// Record rent returned by closing accounts
+    pub fn release_rent(&mut self, rent: Lamports) {
+        self.spending_limits.reclaimable_rent_lamports = self
+            .spending_limits
+            .reclaimable_rent_lamports
+            .saturating_sub(rent.as_u64());
+    }
+
     /// Reset daily budget if it's a new day
     pub fn reset_daily_budget_if_needed(&mut self) {
         let now = Utc::now();
@@ -510,7 +1020,11 @@ impl AgentContext {
             .num_days();
 
         if days_since_reset >= 1 {
-            self.spending_limits.remaining_daily_budget_sol = self.spending_limits.daily_limit_sol;
+            self.spending_limits.remaining_daily_budget_lamports =
+                self.spending_limits.daily_limit_lamports;
+            for limit in self.spending_limits.token_limits.values_mut() {
+                limit.remaining = limit.daily_limit;
+            }
             self.spending_limits.last_reset = now;
         }
     }
@@ -532,6 +1046,13 @@ pub enum AgentStatus {
     Error,
 }
 
+/// Shortest interval a periodic strategy may act on by default, in seconds
+pub const DEFAULT_MIN_INTERVAL_SECONDS: u64 = 10;
+
+fn default_min_interval_seconds() -> u64 {
+    DEFAULT_MIN_INTERVAL_SECONDS
+}
+
 /// Agent limits configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentLimits {
@@ -545,6 +1066,9 @@ pub struct AgentLimits {
     pub max_transaction_size: usize,
     /// Allowed protocols
     pub allowed_protocols: Vec<String>,
+    /// Shortest interval a periodic strategy may act on, in seconds
+    #[serde(default = "default_min_interval_seconds")]
+    pub min_interval_seconds: u64,
 }
 
 impl Default for AgentLimits {
@@ -555,6 +1079,93 @@ impl Default for AgentLimits {
             max_transactions_per_hour: 60,
             max_transaction_size: 1232, // Solana transaction size limit
             allowed_protocols: Vec::new(),
+            min_interval_seconds: DEFAULT_MIN_INTERVAL_SECONDS,
+        }
+    }
+}
+
+/// Maps keyed by pubkeys, serialized with base58 keys
+/// Lamport amounts that older versions stored as `f64` SOL
+///
+/// Integers are lamports; floats are SOL and are rounded to the nearest
+/// lamport.
+mod legacy_sol {
+    use serde::{de::Error as _, Deserialize, Deserializer};
+
+    use crate::sol::Lamports;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Lamports(u64),
+        Sol(f64),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Lamports, D::Error> {
+        match Amount::deserialize(deserializer)? {
+            Amount::Lamports(lamports) => Ok(Lamports::new(lamports)),
+            Amount::Sol(sol) => Lamports::from_sol_f64_rounded(sol).map_err(D::Error::custom),
         }
     }
 }
+
+pub(crate) mod pubkey_map {
+    use std::collections::HashMap;
+
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer, V: Serialize>(
+        map: &HashMap<Pubkey, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(key, value)| (key.to_string(), value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<Pubkey, V>, D::Error> {
+        HashMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| Ok((key.parse().map_err(D::Error::custom)?, value)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spending_limits_saved_in_sol_still_load() -> Result<(), Error> {
+        let saved = r#"{
+            "daily_limit_sol": 10.0,
+            "daily_limit_usd": 100.0,
+            "per_transaction_limit_sol": 1.5,
+            "remaining_daily_budget_sol": 0.1,
+            "last_reset": "2024-01-01T00:00:00Z"
+        }"#;
+        let limits: SpendingLimits = serde_json::from_str(saved)?;
+        assert_eq!(
+            limits.daily_limit_lamports,
+            Lamports::new(10 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(
+            limits.per_transaction_limit_lamports,
+            Lamports::from_sol_str("1.5")?
+        );
+        assert_eq!(
+            limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("0.1")?
+        );
+        assert_eq!(limits.unlisted_tokens, UnlistedTokenPolicy::Deny);
+
+        // Limits saved now round-trip in lamports
+        let reloaded: SpendingLimits = serde_json::from_str(&serde_json::to_string(&limits)?)?;
+        assert_eq!(
+            reloaded.remaining_daily_budget_lamports,
+            Lamports::new(100_000_000)
+        );
+        Ok(())
+    }
+}
@@ -33,47 +33,130 @@
 //! }
 //! ```
 
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    message::Message,
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Signature, Signer},
     transaction::Transaction,
 };
 use tokio::sync::{Mutex, RwLock};
+use tracing::Instrument;
 use zeroize::Zeroizing;
 
-use crate::config::{WalletConfig, WalletSettings};
+use crate::address_book::AddressBook;
+use crate::audit::{AuditEntry, AuditOutcome, AuditSink, JsonlAuditSink, SubmissionPath};
+use crate::budget::{BudgetLedger, BudgetReservation, IdempotencyJournal, Reconciliation};
+use crate::config::{
+    EncryptionSettings, RunMode, StorageBackend, StorageSettings, WalletConfig, WalletSettings,
+};
+use crate::context::ContextBuilder;
+use crate::destination::{self, DestinationCheck, DestinationOptions, DestinationTransfer};
 use crate::encryption::{EncryptedData, EncryptionService};
+use crate::envelope::{SignedEnvelope, SimulationReport, UnsignedEnvelope};
 use crate::error::{Error, Result};
-use crate::keypair::{EncryptedKeypair, SecureKeypair};
-use crate::rpc::RpcClient;
-use crate::storage::{StorageService, WalletData, WalletMetadata, WalletStorage};
-use crate::token::{TokenManager, TokenOperationResult};
+use crate::escalation::{self, EscalationPolicy, EscalationReport, Expiry};
+use crate::fees::PriorityFeeStrategy;
+use crate::fees::LAMPORTS_PER_SIGNATURE;
+use crate::guard::BalanceGuard;
+#[cfg(feature = "jito")]
+use crate::jito::{JitoClient, JitoConfig};
+use crate::keypair::{EncryptedKeypair, KeyUsage, SecureKeypair};
+use crate::keystore::{self, Keystore};
+use crate::locked_keypair::LockedKeypair;
+use crate::manager::{hydrate_balances, SharedComponents};
+use crate::market::DynMarketDataProvider;
+use crate::metrics::Metrics;
+use crate::multisig::{self, MultisigConfig};
+use crate::nonce::{self, NonceInfo};
+use crate::oracle::DynPriceOracle;
+use crate::policy::AddressPolicy;
+use crate::preview::{self, TransferPreview};
+use crate::protocols;
+use crate::rate_limit::TokenBucket;
+use crate::rent::RentCalculator;
+use crate::rotation::{
+    self, ArchivedKey, KeyRecord, RotationReason, RotationReport, SweepPlan, SweepToken,
+};
+use crate::rpc::{poll_for_confirmation, DynRpcProvider, OfflineRpc, SubscriptionClient};
+use crate::signer::{self, DynTransactionSigner};
+use crate::sns::{NameResolver, Recipient};
+use crate::sol::Lamports;
+use crate::solana_pay::TransferRequest;
+use crate::stake;
+use crate::storage::{WalletData, WalletMetadata, WalletStorage};
+use crate::store::{open_store, WalletStore};
+use crate::template::{ActionTemplate, TemplateSet};
+use crate::token::{self, RefreshReport, TokenManager, TokenOperationResult};
 use crate::transaction::{
-    SimulationResult, TransactionBuilder, TransactionOptions, ValidationResult,
+    sol_transferred, sol_transferred_to, ActionReceipt, BatchTransferReport, PreparedAction,
+    SimulationResult, SubmissionMode, TransactionBuilder, TransactionOptions, ValidationResult,
 };
-use crate::types::{AgentContext, PermissionLevel, WalletInfo};
+use crate::tx_policy::{PolicyInput, TransactionPolicy};
+use crate::types::{
+    AgentAction, AgentContext, PermissionLevel, TokenBalanceSummary, UnlistedTokenPolicy,
+    WalletInfo,
+};
+
+/// Interval between signature status polls while waiting for confirmation
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time allowed for each sweep transaction of a key rotation to confirm
+const ROTATION_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Most signatures [`Wallet::get_info`] counts; more are shown as `1000+`
+pub const INFO_TRANSACTION_COUNT_CAP: usize = 1_000;
+
+/// State a new wallet was created in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateReport {
+    /// Public key of the new wallet
+    pub pubkey: Pubkey,
+    /// Whether the wallet already held SOL, as far as the balance could be read
+    pub funded: bool,
+    /// File the wallet was saved to, for backends that store files
+    pub storage_path: Option<PathBuf>,
+}
+
+/// What deleting a wallet removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteReport {
+    /// Wallet files removed, for backends that store files
+    pub files_removed: usize,
+    /// Backups of the wallet removed
+    pub backups_removed: usize,
+    /// SOL balance of the wallet when it was deleted; 0 if it could not be read
+    pub final_balance_lamports: u64,
+}
 
 /// Main wallet structure
 pub struct Wallet {
     /// Wallet name/identifier
     name: String,
-    /// Secure keypair for signing operations
-    keypair: Arc<RwLock<SecureKeypair>>,
+    /// Wallet public key
+    public_key: Pubkey,
+    /// Signer holding the wallet key, in memory or behind a signing service
+    signer: Arc<dyn DynTransactionSigner>,
     /// Encrypted keypair data for storage
     encrypted_keypair: Arc<RwLock<Option<EncryptedKeypair>>>,
-    /// RPC client for blockchain operations
-    rpc_client: Arc<RwLock<RpcClient>>,
-    /// Storage service for wallet persistence
-    storage_service: Arc<RwLock<StorageService>>,
+    /// RPC provider for blockchain operations (shared with the token manager)
+    rpc_client: Arc<dyn DynRpcProvider>,
+    /// Websocket subscriptions (when enabled in the RPC settings)
+    subscriptions: Option<Arc<SubscriptionClient>>,
+    /// Store the wallet is persisted in
+    storage_service: Arc<RwLock<Box<dyn WalletStore>>>,
     /// Token manager for token operations
     token_manager: Arc<RwLock<TokenManager>>,
+    /// Refreshes the agent context from the network
+    context_builder: Arc<RwLock<ContextBuilder>>,
     /// Transaction builder for creating transactions
     transaction_builder: Arc<Mutex<TransactionBuilder>>,
     /// Wallet configuration
@@ -82,6 +165,26 @@ pub struct Wallet {
     metadata: Arc<RwLock<WalletMetadata>>,
     /// Agent context for decision-making
     agent_context: Arc<RwLock<AgentContext>>,
+    /// Address book whose whitelisted contacts are the only allowed recipients
+    recipient_whitelist: Arc<RwLock<Option<AddressBook>>>,
+    /// Prepared transaction templates for the fast build path
+    templates: Arc<RwLock<TemplateSet>>,
+    /// Cached rent-exemption minimums for planning account creation
+    rent: Arc<RentCalculator>,
+    /// Cached `.sol` domain resolutions for transfer recipients
+    names: Arc<NameResolver>,
+    /// Limit on transactions sent per minute, independent of agent runners
+    rate_limiter: Arc<TokenBucket>,
+    /// Co-signers and threshold, for multisig wallets
+    multisig: Option<MultisigConfig>,
+    /// Budget held by actions in flight
+    budget: Arc<BudgetLedger>,
+    /// Destination of the audit trail of executed actions
+    audit_sink: Arc<RwLock<Option<Arc<dyn AuditSink>>>>,
+    /// Keychain holding the secrets of keystore-bound wallet files
+    keystore: Arc<dyn Keystore>,
+    /// Signatures made with the wallet key, when it is held locally
+    key_usage: Option<Arc<KeyUsage>>,
     /// Whether wallet is loaded and ready
     is_loaded: bool,
 }
@@ -89,6 +192,9 @@ pub struct Wallet {
 impl Wallet {
     /// Create a new wallet with a given name and passphrase
     ///
+    /// Fails with `Error::Wallet` if a wallet named `name` already exists;
+    /// see [`Wallet::create_with_report`] to replace it.
+    ///
     /// # Arguments
     /// * `name` - Wallet name/identifier
     /// * `passphrase` - Passphrase for encrypting the wallet
@@ -100,29 +206,110 @@ impl Wallet {
         name: impl Into<String>,
         passphrase: &Zeroizing<String>,
         config: WalletConfig,
+    ) -> Result<Self> {
+        let shared = SharedComponents::connect(&config).await?;
+        Self::create_with_shared(name, passphrase, config, &shared).await
+    }
+
+    /// Create a new wallet on RPC and token components shared with other wallets
+    ///
+    /// See [`WalletManager`](crate::manager::WalletManager), which keeps one
+    /// set of components for every wallet it opens.
+    pub async fn create_with_shared(
+        name: impl Into<String>,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+        shared: &SharedComponents,
+    ) -> Result<Self> {
+        let (wallet, _) = Self::create_with_keypair(
+            name.into(),
+            SecureKeypair::generate(),
+            passphrase,
+            config,
+            shared,
+            false,
+        )
+        .await?;
+        Ok(wallet)
+    }
+
+    /// Create a new wallet and report the state it was created in
+    ///
+    /// With `overwrite`, a stored wallet of the same name is replaced;
+    /// otherwise creation fails with `Error::Wallet`. Creation is atomic: if
+    /// it fails once the wallet is saved, the save is undone, and a replaced
+    /// wallet is put back. The initial balance is read on a best-effort
+    /// basis, so an unreachable cluster leaves the wallet unfunded in the
+    /// report rather than failing creation.
+    pub async fn create_with_report(
+        name: impl Into<String>,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+        overwrite: bool,
+    ) -> Result<(Self, CreateReport)> {
+        let shared = SharedComponents::connect(&config).await?;
+        Self::create_with_keypair(
+            name.into(),
+            SecureKeypair::generate(),
+            passphrase,
+            config,
+            &shared,
+            overwrite,
+        )
+        .await
+    }
+
+    /// Create a wallet holding an existing keypair
+    ///
+    /// Used to bring in keys from `solana-keygen` (see
+    /// [`SecureKeypair::from_solana_json_file`]). Fails if a wallet named
+    /// `name` already exists, so an import never replaces a stored key.
+    pub async fn import(
+        name: impl Into<String>,
+        keypair: SecureKeypair,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
     ) -> Result<Self> {
         let name = name.into();
-        let start_time = std::time::Instant::now();
+        // Checked before connecting, so a clash fails without network access
+        stored_wallet(open_store(&config.wallet.storage)?.as_ref(), &name, false)?;
+        let shared = SharedComponents::connect(&config).await?;
+        let (wallet, _) =
+            Self::create_with_keypair(name, keypair, passphrase, config, &shared, false).await?;
+        Ok(wallet)
+    }
 
-        // Generate secure keypair
-        let keypair = SecureKeypair::generate();
+    async fn create_with_keypair(
+        name: String,
+        keypair: SecureKeypair,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+        shared: &SharedComponents,
+        overwrite: bool,
+    ) -> Result<(Self, CreateReport)> {
+        let start_time = std::time::Instant::now();
         let public_key = keypair.public_key();
+        let rpc_client = shared.rpc_client();
+        let token_manager = shared.token_manager();
 
-        // Create RPC client
-        let rpc_config = crate::rpc::RpcClientConfig::from_settings(&config.rpc);
-        let rpc_client = RpcClient::new(rpc_config).await?;
-
-        // Create storage service
-        let storage_service = StorageService::new(config.wallet.storage.clone())?;
+        // Open the configured wallet store, refusing to replace a wallet by accident
+        let mut storage_service = open_store(&config.wallet.storage)?;
+        let mut rollback = CreateRollback {
+            name: name.clone(),
+            account: keystore_account(&config.wallet.storage, &name),
+            previous: stored_wallet(storage_service.as_ref(), &name, overwrite)?,
+            previous_secret: None,
+            secret_set: false,
+        };
 
-        // Create token manager
-        let token_manager = TokenManager::new_with_commitment(
+        let context_builder = ContextBuilder::from_settings(
             rpc_client.clone(),
-            config.rpc.commitment.to_solana_commitment(),
+            token_manager.clone(),
+            &config.agent.context,
         );
 
-        // Create transaction builder
-        let transaction_builder = TransactionBuilder::new();
+        // Create transaction builder, on the RPC client's blockhash cache
+        let transaction_builder = TransactionBuilder::for_provider(rpc_client.as_ref());
 
         // Create wallet metadata
         let now = Utc::now();
@@ -136,10 +323,21 @@ impl Wallet {
             description: None,
             tags: Vec::new(),
             custom_data: HashMap::new(),
+            revision: 0,
+            key: Some(KeyRecord::new(now)),
         };
 
         // Create agent context
-        let agent_context = AgentContext::new(public_key);
+        let mut agent_context = AgentContext::new(public_key);
+        agent_context.address_policy = config.agent.address_policy.clone();
+        agent_context.allowed_protocols = protocols::resolve_all(&config.agent.allowed_protocols)?;
+        let recipient_whitelist = open_recipient_whitelist(&config)?;
+        agent_context.spending_limits.min_sol_reserve_lamports =
+            BalanceGuard::from_limits(&config.agent.limits)?
+                .reserve()
+                .as_u64();
+        agent_context.spending_limits.token_limits = config.agent.limits.token_limits();
+        agent_context.spending_limits.unlisted_tokens = config.agent.limits.unlisted_tokens;
 
         // Encrypt keypair for storage
         let encrypted_keypair = keypair.encrypt(passphrase)?;
@@ -150,44 +348,124 @@ impl Wallet {
                 .map_err(|e| Error::serialization(format!("Failed to serialize keypair: {}", e)))?,
         );
 
-        // Encrypt wallet data
-        let encrypted_data = crate::encryption::utils::encrypt_with_passphrase(
+        // Encrypt wallet data, bound to a new keychain secret if configured
+        let keystore = shared.keystore();
+        if config.wallet.encryption.use_os_keystore && rollback.previous.is_some() {
+            rollback.previous_secret = keystore.get_secret(&rollback.account)?;
+        }
+        let secret = new_keystore_secret(
+            keystore.as_ref(),
+            &rollback.account,
+            &config.wallet.encryption,
+        )?;
+        rollback.secret_set = secret.is_some();
+        let sealed = seal(
             &crate::storage::utils::serialize_wallet_data(&wallet_data)?,
             passphrase,
-            config.wallet.encryption.algorithm.into(),
-            config.wallet.encryption.kdf_iterations,
-        )?;
+            &config.wallet.encryption,
+            secret.as_deref().map(Vec::as_slice),
+        );
 
-        // Save wallet to storage
-        storage_service
-            .save_wallet(&name, encrypted_data, public_key, None)
-            .await?;
+        // Save wallet to storage; any failure from here on undoes the save
+        let persisted = sealed.and_then(|encrypted_data| {
+            storage_service.save_wallet(&name, encrypted_data, public_key, None)?;
+            Ok((open_budget(&config, &name)?, open_audit_sink(&config)?))
+        });
+        let (budget, audit_sink) = match persisted {
+            Ok(persisted) => persisted,
+            Err(e) => {
+                if let Err(undo) = rollback.undo(storage_service.as_mut(), keystore.as_ref()) {
+                    tracing::error!("Failed to roll back wallet '{}': {}", name, undo);
+                }
+                return Err(e);
+            }
+        };
+        let storage_path = storage_service.wallet_path(&name);
 
+        let key_usage = keypair.usage().clone();
         let wallet = Self {
             name: name.clone(),
-            keypair: Arc::new(RwLock::new(keypair)),
+            public_key,
+            signer: key_signer(keypair, &config.wallet),
             encrypted_keypair: Arc::new(RwLock::new(Some(encrypted_keypair))),
-            rpc_client: Arc::new(RwLock::new(rpc_client)),
+            rent: Arc::new(RentCalculator::new(rpc_client.clone())),
+            names: Arc::new(NameResolver::new(rpc_client.clone())),
+            rate_limiter: Arc::new(TokenBucket::per_minute(
+                config.agent.limits.max_transactions_per_minute,
+            )),
+            rpc_client,
+            subscriptions: SubscriptionClient::from_settings(&config.rpc).map(Arc::new),
             storage_service: Arc::new(RwLock::new(storage_service)),
-            token_manager: Arc::new(RwLock::new(token_manager)),
+            token_manager,
+            context_builder: Arc::new(RwLock::new(context_builder)),
             transaction_builder: Arc::new(Mutex::new(transaction_builder)),
             config,
             metadata: Arc::new(RwLock::new(metadata)),
             agent_context: Arc::new(RwLock::new(agent_context)),
+            recipient_whitelist: Arc::new(RwLock::new(recipient_whitelist)),
+            templates: Arc::new(RwLock::new(TemplateSet::new())),
+            multisig: None,
+            budget: Arc::new(budget),
+            audit_sink: Arc::new(RwLock::new(audit_sink)),
+            keystore,
+            key_usage: Some(key_usage),
             is_loaded: true,
         };
 
-        // Update agent context with initial balance
-        wallet.update_agent_context().await?;
+        // The initial balance is best-effort: the wallet is stored and usable
+        let funded = match wallet.update_agent_context().await {
+            Ok(()) => wallet.agent_context.read().await.wallet_balance > 0.0,
+            Err(e) => {
+                tracing::warn!(
+                    "Wallet '{}' created without its initial balance: {}",
+                    name,
+                    e
+                );
+                false
+            }
+        };
 
         let duration = start_time.elapsed();
-        log::info!(
+        tracing::info!(
             "Wallet '{}' created in {:?}. Public key: {}",
             name,
             duration,
             public_key
         );
 
+        let report = CreateReport {
+            pubkey: public_key,
+            funded,
+            storage_path,
+        };
+        Ok((wallet, report))
+    }
+
+    /// Create a multisig wallet
+    ///
+    /// Every transaction of the wallet then needs `multisig.threshold`
+    /// signatures: the wallet's own plus co-signatures collected with
+    /// [`Wallet::build_partially_signed`] and [`Wallet::add_signature`].
+    pub async fn create_multisig(
+        name: impl Into<String>,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+        multisig: MultisigConfig,
+    ) -> Result<Self> {
+        multisig.validate()?;
+        let mut wallet = Self::create(name, passphrase, config).await?;
+
+        {
+            let mut metadata = wallet.metadata.write().await;
+            multisig.store_in(&mut metadata)?;
+            wallet
+                .storage_service
+                .write()
+                .await
+                .update_metadata(&wallet.name, &metadata)?;
+        }
+        wallet.multisig = Some(multisig);
+
         Ok(wallet)
     }
 
@@ -204,72 +482,252 @@ impl Wallet {
         name: impl Into<String>,
         passphrase: &Zeroizing<String>,
         config: WalletConfig,
+    ) -> Result<Self> {
+        let shared = SharedComponents::connect(&config).await?;
+        Self::load_with_shared(name, passphrase, config, &shared).await
+    }
+
+    /// Load an existing wallet on RPC and token components shared with other wallets
+    pub async fn load_with_shared(
+        name: impl Into<String>,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+        shared: &SharedComponents,
     ) -> Result<Self> {
         let name = name.into();
         let start_time = std::time::Instant::now();
+        let wallet = Self::unlock(name.clone(), passphrase, config, shared)?;
 
-        // Create storage service
-        let storage_service = StorageService::new(config.wallet.storage.clone())?;
+        // Update agent context with current state
+        wallet.update_agent_context().await?;
 
-        // Load wallet from storage
-        let (encrypted_data, metadata) = storage_service.load_wallet(&name).await?;
+        // Settle spends a previous run left in flight
+        let settled = wallet.reconcile_budget().await?;
+        if !settled.committed.is_empty() || !settled.released.is_empty() {
+            tracing::info!(
+                "Wallet '{}' settled in-flight spends: {} committed ({}), {} released",
+                name,
+                settled.committed.len(),
+                settled.committed_lamports(),
+                settled.released.len()
+            );
+        }
+
+        let duration = start_time.elapsed();
+        tracing::info!(
+            "Wallet '{}' loaded in {:?}. Public key: {}",
+            name,
+            duration,
+            wallet.public_key()
+        );
 
-        // Decrypt wallet data
-        let decrypted_data =
-            crate::encryption::utils::decrypt_with_passphrase(&encrypted_data, passphrase)?;
+        Ok(wallet)
+    }
 
-        // Deserialize wallet data
-        let wallet_data = crate::storage::utils::deserialize_wallet_data(&decrypted_data)?;
+    /// Load an existing wallet on a machine without network access
+    ///
+    /// No RPC client is constructed: every cluster call fails with
+    /// [`Error::NotSupported`], so the wallet can only sign, e.g. with
+    /// [`Wallet::sign_offline`]. The agent context is not refreshed and
+    /// in-flight spends are left for the next online load to settle.
+    pub async fn load_offline(
+        name: impl Into<String>,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+    ) -> Result<Self> {
+        let shared = SharedComponents::offline(&config);
+        let mut wallet = Self::unlock(name.into(), passphrase, config, &shared)?;
+        wallet.subscriptions = None;
+        tracing::info!(
+            "Wallet '{}' loaded offline. Public key: {}",
+            wallet.name,
+            wallet.public_key()
+        );
+        Ok(wallet)
+    }
 
-        // Deserialize encrypted keypair
-        let encrypted_keypair: EncryptedKeypair =
-            bincode::deserialize(&wallet_data.encrypted_private_key).map_err(|e| {
-                Error::serialization(format!("Failed to deserialize keypair: {}", e))
-            })?;
+    /// Decrypt a stored wallet onto shared components, without any RPC call
+    fn unlock(
+        name: String,
+        passphrase: &Zeroizing<String>,
+        config: WalletConfig,
+        shared: &SharedComponents,
+    ) -> Result<Self> {
+        // Open the configured wallet store
+        let mut storage_service = open_store(&config.wallet.storage)?;
 
-        // Decrypt keypair
-        let keypair = SecureKeypair::decrypt(&encrypted_keypair, passphrase)?;
+        // Load wallet from storage
+        let (encrypted_data, mut metadata) = storage_service.load_wallet(&name)?;
 
-        // Create RPC client
-        let rpc_config = crate::rpc::RpcClientConfig::from_settings(&config.rpc);
-        let rpc_client = RpcClient::new(rpc_config).await?;
+        // Decrypt wallet data and keypair; a keystore-bound file also needs
+        // this machine's keychain entry
+        let secret = bound_secret(
+            shared.keystore().as_ref(),
+            &config.wallet.storage,
+            &name,
+            &encrypted_data,
+        )?;
+        let (_, encrypted_keypair, keypair) = unlock_with_secret(
+            &encrypted_data,
+            passphrase,
+            secret.as_deref().map(Vec::as_slice),
+        )?;
 
-        // Create token manager
-        let token_manager = TokenManager::new_with_commitment(
+        let rpc_client = shared.rpc_client();
+        let token_manager = shared.token_manager();
+        let context_builder = ContextBuilder::from_settings(
             rpc_client.clone(),
-            config.rpc.commitment.to_solana_commitment(),
+            token_manager.clone(),
+            &config.agent.context,
         );
 
-        // Create transaction builder
-        let transaction_builder = TransactionBuilder::new();
+        // Create transaction builder, on the RPC client's blockhash cache
+        let transaction_builder = TransactionBuilder::for_provider(rpc_client.as_ref());
+
+        // Multisig wallets keep their co-signers in the metadata
+        let multisig = MultisigConfig::from_metadata(&metadata)?;
 
         // Create agent context
         let mut agent_context = AgentContext::new(metadata.public_key);
-        agent_context.permission_level = PermissionLevel::Basic; // Default
+        agent_context.permission_level = config.agent.default_permission_level;
+        agent_context.address_policy = config.agent.address_policy.clone();
+        agent_context.allowed_protocols = protocols::resolve_all(&config.agent.allowed_protocols)?;
+        let recipient_whitelist = open_recipient_whitelist(&config)?;
+        agent_context.spending_limits.min_sol_reserve_lamports =
+            BalanceGuard::from_limits(&config.agent.limits)?
+                .reserve()
+                .as_u64();
+        agent_context.spending_limits.token_limits = config.agent.limits.token_limits();
+        agent_context.spending_limits.unlisted_tokens = config.agent.limits.unlisted_tokens;
+        let budget = open_budget(&config, &name)?;
+        let audit_sink = open_audit_sink(&config)?;
 
-        let wallet = Self {
-            name: name.clone(),
-            keypair: Arc::new(RwLock::new(keypair)),
+        // Keep counting signatures where earlier sessions left off; wallets
+        // written before keys were tracked count from their creation
+        let record = metadata
+            .key
+            .get_or_insert_with(|| KeyRecord::new(metadata.created_at));
+        keypair.usage().resume(record.signatures, record.last_used);
+        let key_usage = keypair.usage().clone();
+
+        Ok(Self {
+            name,
+            public_key: keypair.public_key(),
+            signer: key_signer(keypair, &config.wallet),
             encrypted_keypair: Arc::new(RwLock::new(Some(encrypted_keypair))),
-            rpc_client: Arc::new(RwLock::new(rpc_client)),
+            rent: Arc::new(RentCalculator::new(rpc_client.clone())),
+            names: Arc::new(NameResolver::new(rpc_client.clone())),
+            rate_limiter: Arc::new(TokenBucket::per_minute(
+                config.agent.limits.max_transactions_per_minute,
+            )),
+            rpc_client,
+            subscriptions: SubscriptionClient::from_settings(&config.rpc).map(Arc::new),
             storage_service: Arc::new(RwLock::new(storage_service)),
-            token_manager: Arc::new(RwLock::new(token_manager)),
+            token_manager,
+            context_builder: Arc::new(RwLock::new(context_builder)),
             transaction_builder: Arc::new(Mutex::new(transaction_builder)),
             config,
             metadata: Arc::new(RwLock::new(metadata)),
             agent_context: Arc::new(RwLock::new(agent_context)),
+            recipient_whitelist: Arc::new(RwLock::new(recipient_whitelist)),
+            templates: Arc::new(RwLock::new(TemplateSet::new())),
+            multisig,
+            budget: Arc::new(budget),
+            audit_sink: Arc::new(RwLock::new(audit_sink)),
+            keystore: shared.keystore(),
+            key_usage: Some(key_usage),
+            is_loaded: true,
+        })
+    }
+
+    /// Open a wallet whose key lives outside the process
+    ///
+    /// Every signature comes from `signer`, e.g. a
+    /// [`RemoteSigner`](crate::signer::RemoteSigner). No key is stored, so
+    /// [`Wallet::save`] and [`Wallet::change_passphrase`] are unavailable.
+    pub async fn with_signer(
+        name: impl Into<String>,
+        signer: Arc<dyn DynTransactionSigner>,
+        config: WalletConfig,
+    ) -> Result<Self> {
+        let name = name.into();
+        let public_key = signer.pubkey().await;
+
+        let shared = SharedComponents::connect(&config).await?;
+        let rpc_client = shared.rpc_client();
+        let token_manager = shared.token_manager();
+        let storage_service = open_store(&config.wallet.storage)?;
+        let context_builder = ContextBuilder::from_settings(
+            rpc_client.clone(),
+            token_manager.clone(),
+            &config.agent.context,
+        );
+
+        let now = Utc::now();
+        let metadata = WalletMetadata {
+            name: name.clone(),
+            public_key,
+            created_at: now,
+            last_accessed: now,
+            last_modified: now,
+            wallet_version: 1,
+            description: None,
+            tags: Vec::new(),
+            custom_data: HashMap::new(),
+            revision: 0,
+            key: None,
+        };
+        let mut agent_context = AgentContext::new(public_key);
+        agent_context.address_policy = config.agent.address_policy.clone();
+        agent_context.allowed_protocols = protocols::resolve_all(&config.agent.allowed_protocols)?;
+        let recipient_whitelist = open_recipient_whitelist(&config)?;
+        agent_context.spending_limits.min_sol_reserve_lamports =
+            BalanceGuard::from_limits(&config.agent.limits)?
+                .reserve()
+                .as_u64();
+        agent_context.spending_limits.token_limits = config.agent.limits.token_limits();
+        agent_context.spending_limits.unlisted_tokens = config.agent.limits.unlisted_tokens;
+        let budget = open_budget(&config, &name)?;
+        let audit_sink = open_audit_sink(&config)?;
+
+        let wallet = Self {
+            name: name.clone(),
+            public_key,
+            signer,
+            encrypted_keypair: Arc::new(RwLock::new(None)),
+            rent: Arc::new(RentCalculator::new(rpc_client.clone())),
+            names: Arc::new(NameResolver::new(rpc_client.clone())),
+            rate_limiter: Arc::new(TokenBucket::per_minute(
+                config.agent.limits.max_transactions_per_minute,
+            )),
+            transaction_builder: Arc::new(Mutex::new(TransactionBuilder::for_provider(
+                rpc_client.as_ref(),
+            ))),
+            rpc_client,
+            subscriptions: SubscriptionClient::from_settings(&config.rpc).map(Arc::new),
+            storage_service: Arc::new(RwLock::new(storage_service)),
+            token_manager,
+            context_builder: Arc::new(RwLock::new(context_builder)),
+            config,
+            metadata: Arc::new(RwLock::new(metadata)),
+            agent_context: Arc::new(RwLock::new(agent_context)),
+            recipient_whitelist: Arc::new(RwLock::new(recipient_whitelist)),
+            templates: Arc::new(RwLock::new(TemplateSet::new())),
+            multisig: None,
+            budget: Arc::new(budget),
+            audit_sink: Arc::new(RwLock::new(audit_sink)),
+            keystore: shared.keystore(),
+            key_usage: None,
             is_loaded: true,
         };
 
-        // Update agent context with current state
         wallet.update_agent_context().await?;
+        wallet.reconcile_budget().await?;
 
-        let duration = start_time.elapsed();
-        log::info!(
-            "Wallet '{}' loaded in {:?}. Public key: {}",
+        tracing::info!(
+            "Wallet '{}' opened with an external signer. Public key: {}",
             name,
-            duration,
-            wallet.public_key()
+            public_key
         );
 
         Ok(wallet)
@@ -305,423 +763,6210 @@ impl Wallet {
         ))
     }
 
-    /// Get wallet public key
-    pub fn public_key(&self) -> Pubkey {
-        // This is a bit awkward because we need to lock, but public key should be immutable
-        // In practice, we might want to store public key separately
-        let keypair = self.keypair.blocking_read();
-        keypair.public_key()
-    }
+    /// Re-encrypt the stored wallet under a new passphrase
+    ///
+    /// The wallet file is backed up, then replaced atomically with the same
+    /// key and wallet data encrypted under `new` with a fresh salt and the
+    /// configured KDF iterations. Fails with `Encryption` and leaves the file
+    /// untouched if `old` does not decrypt it.
+    pub async fn change_passphrase(
+        &self,
+        old: &Zeroizing<String>,
+        new: &Zeroizing<String>,
+    ) -> Result<()> {
+        let mut storage_service = self.storage_service.write().await;
+        let (encrypted_data, metadata) = storage_service.read_wallet(&self.name)?;
 
-    /// Get wallet name
-    pub fn name(&self) -> &str {
-        &self.name
-    }
+        // A keystore-bound file stays bound to the same keychain secret
+        let secret = bound_secret(
+            self.keystore.as_ref(),
+            &self.config.wallet.storage,
+            &self.name,
+            &encrypted_data,
+        )?;
+        let secret = secret.as_deref().map(Vec::as_slice);
+        let (mut wallet_data, _, keypair) = unlock_with_secret(&encrypted_data, old, secret)?;
+        if keypair.public_key() != metadata.public_key || keypair.public_key() != self.public_key {
+            return Err(Error::State(format!(
+                "Stored key of wallet '{}' does not match its public key",
+                self.name
+            )));
+        }
 
-    /// Get wallet balance in SOL
-    pub async fn get_balance(&self) -> Result<f64> {
-        let rpc_client = self.rpc_client.read().await;
-        let pubkey = self.public_key();
+        let encrypted_keypair = keypair.encrypt(new)?;
+        wallet_data.encrypted_private_key = bincode::serialize(&encrypted_keypair)
+            .map_err(|e| Error::serialization(format!("Failed to serialize keypair: {}", e)))?;
+        let reencrypted = seal(
+            &crate::storage::utils::serialize_wallet_data(&wallet_data)?,
+            new,
+            &self.config.wallet.encryption,
+            secret,
+        )?;
+
+        // Never replace the file with something the new passphrase cannot open
+        let (_, _, check) = unlock_with_secret(&reencrypted, new, secret)?;
+        if check.public_key() != metadata.public_key {
+            return Err(Error::encryption(format!(
+                "Re-encrypted wallet '{}' does not decrypt to its public key",
+                self.name
+            )));
+        }
 
-        let balance_lamports: u64 = rpc_client.get_balance(&pubkey).await?;
-        Ok(balance_lamports as f64 / 1_000_000_000.0) // Convert lamports to SOL
+        storage_service.replace_encrypted_data(&self.name, reencrypted)?;
+        *self.encrypted_keypair.write().await = Some(encrypted_keypair);
+        tracing::info!("Changed passphrase of wallet '{}'", self.name);
+        Ok(())
     }
 
-    /// Get token balance for a specific mint
-    pub async fn get_token_balance(&self, mint: &Pubkey) -> Result<u64> {
-        let token_manager = self.token_manager.read().await;
-        let pubkey = self.public_key();
+    /// Re-encrypt the stored wallet in the current format and configured KDF
+    ///
+    /// Files written before format version 3, or keyed with another KDF or
+    /// algorithm than `wallet.encryption` names (Argon2id by default), are
+    /// backed up and replaced atomically under the same passphrase and
+    /// keychain binding. Returns whether the file was rewritten. Fails with
+    /// `Encryption` and leaves the file untouched if `passphrase` does not
+    /// decrypt it.
+    pub async fn upgrade_encryption(&self, passphrase: &Zeroizing<String>) -> Result<bool> {
+        let mut storage_service = self.storage_service.write().await;
+        let (encrypted_data, metadata) = storage_service.read_wallet(&self.name)?;
 
-        token_manager.get_balance(mint, &pubkey).await
-    }
+        let secret = bound_secret(
+            self.keystore.as_ref(),
+            &self.config.wallet.storage,
+            &self.name,
+            &encrypted_data,
+        )?;
+        let secret = secret.as_deref().map(Vec::as_slice);
+        let (wallet_data, _, keypair) = unlock_with_secret(&encrypted_data, passphrase, secret)?;
+        if keypair.public_key() != metadata.public_key || keypair.public_key() != self.public_key {
+            return Err(Error::State(format!(
+                "Stored key of wallet '{}' does not match its public key",
+                self.name
+            )));
+        }
 
-    /// Transfer SOL to another address
-    pub async fn transfer_sol(
-        &self,
-        to: &Pubkey,
-        amount: f64, // Amount in SOL
-        memo: Option<String>,
-    ) -> Result<Signature> {
-        // Convert SOL to lamports
-        let amount_lamports = (amount * 1_000_000_000.0).round() as u64;
+        let settings = &self.config.wallet.encryption;
+        let algorithm: crate::encryption::EncryptionAlgorithm = settings.algorithm.into();
+        if encrypted_data.version == EncryptedData::CURRENT_VERSION
+            && encrypted_data.kdf() == settings.kdf
+            && encrypted_data.algorithm == algorithm
+        {
+            return Ok(false);
+        }
+        let upgraded = seal(
+            &crate::storage::utils::serialize_wallet_data(&wallet_data)?,
+            passphrase,
+            settings,
+            secret,
+        )?;
 
-        if amount_lamports == 0 {
-            return Err(Error::InvalidAmount(
-                "Transfer amount must be greater than zero".to_string(),
-            ));
+        // Never replace the file with something the passphrase cannot open
+        let (_, _, check) = unlock_with_secret(&upgraded, passphrase, secret)?;
+        if check.public_key() != metadata.public_key {
+            return Err(Error::encryption(format!(
+                "Re-encrypted wallet '{}' does not decrypt to its public key",
+                self.name
+            )));
         }
 
-        // Check balance
-        let balance = self.get_balance().await?;
-        let balance_lamports = (balance * 1_000_000_000.0).round() as u64;
+        storage_service.replace_encrypted_data(&self.name, upgraded)?;
+        tracing::info!(
+            "Upgraded encryption of wallet '{}' from version {} ({}) to version {} ({})",
+            self.name,
+            encrypted_data.version,
+            encrypted_data.kdf(),
+            EncryptedData::CURRENT_VERSION,
+            settings.kdf
+        );
+        Ok(true)
+    }
 
-        if amount_lamports > balance_lamports {
-            return Err(Error::InsufficientFunds {
-                required: amount_lamports,
-                available: balance_lamports,
-            });
+    /// Why the wallet key should be rotated under `wallet.key_rotation`, if it should
+    ///
+    /// Only keys held by the wallet are tracked; wallets opened with an
+    /// external signer never report a rotation.
+    pub async fn needs_rotation(&self) -> Option<RotationReason> {
+        let usage = self.key_usage.as_ref()?;
+        let policy = &self.config.wallet.key_rotation;
+        if !policy.is_enabled() {
+            return None;
         }
+        let mut record = self.metadata.read().await.key.clone()?;
+        record.signatures = usage.signatures();
+        policy.check(&record, Utc::now())
+    }
 
-        // Create agent action for validation
-        let action = crate::types::AgentAction::TransferSol {
-            to: *to,
-            amount: amount_lamports,
-            memo,
+    /// Replace the wallet key with a new one and move the funds to it
+    ///
+    /// A new key is generated and the sweep of the old key's token balances
+    /// and SOL is planned (see [`rotation::plan_sweep`]). Before any funds
+    /// move, one write rewrites the wallet file under `passphrase` with the
+    /// new key, archives the old encrypted key in its metadata and marks its
+    /// sweep as pending. Each sweep transaction is signed by the old key and
+    /// confirmed before the next is sent, and the mark is cleared once all
+    /// of them were. If one fails, or the process stops, what has not moved
+    /// yet stays on the old key and [`Wallet::resume_rotation`] finishes the
+    /// sweep.
+    pub async fn rotate_key(&mut self, passphrase: &Zeroizing<String>) -> Result<RotationReport> {
+        self.ensure_live("Key rotation")?;
+        let Some(usage) = self.key_usage.clone() else {
+            return Err(Error::NotSupported(
+                "Key rotation needs a wallet whose key is held locally".to_string(),
+            ));
         };
+        let old = self.public_key;
 
-        // Validate against agent context
-        let agent_context = self.agent_context.read().await;
-        agent_context.is_action_allowed(amount)?;
+        let mut storage_service = self.storage_service.write().await;
+        let (encrypted_data, mut metadata) = storage_service.read_wallet(&self.name)?;
+        let secret = bound_secret(
+            self.keystore.as_ref(),
+            &self.config.wallet.storage,
+            &self.name,
+            &encrypted_data,
+        )?;
+        let secret = secret.as_deref().map(Vec::as_slice);
+        let (mut wallet_data, old_encrypted, old_keypair) =
+            unlock_with_secret(&encrypted_data, passphrase, secret)?;
+        if old_keypair.public_key() != metadata.public_key || old_keypair.public_key() != old {
+            return Err(Error::State(format!(
+                "Stored key of wallet '{}' does not match its public key",
+                self.name
+            )));
+        }
 
-        // Build transaction
-        let mut transaction_builder = self.transaction_builder.lock().await;
-        let options = TransactionOptions::default();
-        let mut transaction =
-            transaction_builder.build_from_action(&action, &agent_context, &options)?;
-
-        // Validate transaction
-        let validation =
-            transaction_builder.validate_transaction(&transaction, &agent_context, &options);
-        if !validation.is_valid {
-            return Err(Error::TransactionValidation(format!(
-                "Transaction validation failed: {:?}",
-                validation.errors
+        // Plan first, so a wallet that cannot pay for its sweep keeps its key
+        let new_keypair = SecureKeypair::generate();
+        let new = new_keypair.public_key();
+        let plan = self.plan_key_sweep(&old, &new).await?;
+
+        let new_encrypted = new_keypair.encrypt(passphrase)?;
+        wallet_data.encrypted_private_key = bincode::serialize(&new_encrypted)
+            .map_err(|e| Error::serialization(format!("Failed to serialize keypair: {}", e)))?;
+        let reencrypted = seal(
+            &crate::storage::utils::serialize_wallet_data(&wallet_data)?,
+            passphrase,
+            &self.config.wallet.encryption,
+            secret,
+        )?;
+        let (_, _, check) = unlock_with_secret(&reencrypted, passphrase, secret)?;
+        if check.public_key() != new {
+            return Err(Error::encryption(format!(
+                "Re-encrypted wallet '{}' does not decrypt to its new key",
+                self.name
             )));
         }
 
-        // Prepare and sign transaction
-        let keypair = self.keypair.read().await;
-        let rpc_client = self.rpc_client.read().await;
-        let signature = transaction_builder
-            .prepare_transaction(&mut transaction, &keypair, &rpc_client)
-            .await?;
+        // Archive the old key before replacing it, so an interrupted rotation
+        // can always recover it
+        let now = Utc::now();
+        let previous = metadata
+            .key
+            .take()
+            .unwrap_or_else(|| KeyRecord::new(metadata.created_at));
+        let mut archived = previous.archived;
+        archived.push(ArchivedKey {
+            public_key: old,
+            encrypted_keypair: old_encrypted,
+            created_at: previous.created_at,
+            retired_at: now,
+            signatures: usage.signatures(),
+        });
+        metadata.public_key = new;
+        metadata.key = Some(KeyRecord {
+            archived,
+            pending_sweep: Some(old),
+            ..KeyRecord::new(now)
+        });
+        storage_service.replace_wallet(&self.name, reencrypted, &metadata)?;
+        drop(storage_service);
 
-        // Send transaction
-        rpc_client.send_transaction(&transaction).await?;
+        self.key_usage = Some(new_keypair.usage().clone());
+        self.public_key = new;
+        self.signer = key_signer(new_keypair, &self.config.wallet);
+        *self.encrypted_keypair.write().await = Some(new_encrypted);
+        *self.metadata.write().await = metadata;
+        self.agent_context.write().await.wallet_pubkey = new;
+        // Templates were prepared for the old key
+        *self.templates.write().await = TemplateSet::new();
+        tracing::info!(
+            "Wallet '{}' rotated its key from {} to {}",
+            self.name,
+            old,
+            new
+        );
 
-        // Update agent context
-        let mut agent_context = self.agent_context.write().await;
-        agent_context.deduct_from_budget(amount);
-        agent_context.record_success();
+        let signatures = self.finish_sweep(&old_keypair, &plan).await?;
+        Ok(RotationReport {
+            old_public_key: old,
+            new_public_key: new,
+            signatures,
+            swept_lamports: plan.sol_lamports,
+        })
+    }
 
-        Ok(signature)
+    /// Finish the sweep of a rotation that failed or was interrupted
+    ///
+    /// The retired key whose sweep is still pending is decrypted from the
+    /// archive with `passphrase`, and whatever it still holds moves to the
+    /// current key as in [`Wallet::rotate_key`]. Fails with
+    /// `Error::Validation` when no sweep is pending.
+    pub async fn resume_rotation(
+        &mut self,
+        passphrase: &Zeroizing<String>,
+    ) -> Result<RotationReport> {
+        self.ensure_live("Key rotation")?;
+        let (_, metadata) = self.storage_service.read().await.read_wallet(&self.name)?;
+        let pending = metadata
+            .key
+            .as_ref()
+            .and_then(|key| key.pending_sweep.map(|old| (key, old)));
+        let Some((key, old)) = pending else {
+            return Err(Error::validation(format!(
+                "Wallet '{}' has no interrupted key rotation",
+                self.name
+            )));
+        };
+        let archived = key
+            .archived
+            .iter()
+            .find(|archived| archived.public_key == old)
+            .ok_or_else(|| {
+                Error::State(format!(
+                    "Wallet '{}' has no archived key {} to sweep",
+                    self.name, old
+                ))
+            })?;
+        let old_keypair = SecureKeypair::decrypt(&archived.encrypted_keypair, passphrase)?;
+        if old_keypair.public_key() != old {
+            return Err(Error::State(format!(
+                "Archived key {} of wallet '{}' does not match its public key",
+                old, self.name
+            )));
+        }
+
+        let new = self.public_key;
+        let plan = self.plan_key_sweep(&old, &new).await?;
+        let signatures = self.finish_sweep(&old_keypair, &plan).await?;
+        Ok(RotationReport {
+            old_public_key: old,
+            new_public_key: new,
+            signatures,
+            swept_lamports: plan.sol_lamports,
+        })
     }
 
-    /// Transfer tokens to another address
-    pub async fn transfer_token(
+    /// Send a planned sweep from a retired key to the current one, then
+    /// clear the pending sweep in the wallet file
+    async fn finish_sweep(
         &self,
-        mint: &Pubkey,
-        to: &Pubkey,
-        amount: u64,
-        memo: Option<String>,
-    ) -> Result<Signature> {
-        if amount == 0 {
-            return Err(Error::InvalidAmount(
-                "Transfer amount must be greater than zero".to_string(),
-            ));
+        old_keypair: &SecureKeypair,
+        plan: &SweepPlan,
+    ) -> Result<Vec<Signature>> {
+        let (old, new) = (old_keypair.public_key(), self.public_key);
+        let mut signatures = Vec::new();
+        for instructions in &plan.transactions {
+            if instructions.is_empty() {
+                continue;
+            }
+            let signature = self
+                .send_sweep(instructions, old_keypair)
+                .await
+                .map_err(|e| {
+                    Error::transaction(format!(
+                        "Sweep from {} to {} stopped after {} of {} transactions: {}; \
+                         the rest remains on the archived key until resume_rotation",
+                        old,
+                        new,
+                        signatures.len(),
+                        plan.transactions.len(),
+                        e
+                    ))
+                })?;
+            signatures.push(signature);
         }
 
-        // Check token balance
-        let balance = self.get_token_balance(mint).await?;
-        if amount > balance {
-            return Err(Error::InsufficientFunds {
-                required: amount,
-                available: balance,
-            });
+        {
+            let mut storage_service = self.storage_service.write().await;
+            let (_, mut metadata) = storage_service.read_wallet(&self.name)?;
+            if let Some(key) = metadata.key.as_mut() {
+                key.pending_sweep = None;
+            }
+            storage_service.update_metadata(&self.name, &metadata)?;
+            *self.metadata.write().await = metadata;
         }
+        self.update_agent_context().await?;
 
-        // Create agent action for validation
-        let action = crate::types::AgentAction::TransferToken {
-            mint: *mint,
-            to: *to,
-            amount,
-            memo,
-        };
-
-        // Validate against agent context
-        let agent_context = self.agent_context.read().await;
-
-        // For tokens, we need to estimate SOL value
-        // This is simplified - would need price feed integration
-        let estimated_sol_value = amount as f64 / 1_000_000_000.0; // Assume 9 decimals
-        agent_context.is_action_allowed(estimated_sol_value)?;
+        tracing::info!(
+            "Wallet '{}' swept {} lamports and its tokens to {} in {} transactions",
+            self.name,
+            plan.sol_lamports,
+            new,
+            signatures.len()
+        );
+        Ok(signatures)
+    }
 
-        // Build transaction
-        let mut transaction_builder = self.transaction_builder.lock().await;
-        let options = TransactionOptions::default();
-        let mut transaction =
-            transaction_builder.build_from_action(&action, &agent_context, &options)?;
-
-        // Validate transaction
-        let validation =
-            transaction_builder.validate_transaction(&transaction, &agent_context, &options);
-        if !validation.is_valid {
-            return Err(Error::TransactionValidation(format!(
-                "Transaction validation failed: {:?}",
-                validation.errors
-            )));
+    /// Plan moving everything `old` holds to `new`
+    async fn plan_key_sweep(&self, old: &Pubkey, new: &Pubkey) -> Result<SweepPlan> {
+        let mut tokens = Vec::new();
+        {
+            let token_manager = self.token_manager.read().await;
+            for account in token_manager.get_wallet_token_accounts(old).await? {
+                let decimals = token_manager.get_token_info(&account.mint).await?.decimals;
+                tokens.push(SweepToken { account, decimals });
+            }
         }
-
-        // Prepare and sign transaction
-        let keypair = self.keypair.read().await;
-        let rpc_client = self.rpc_client.read().await;
-        let signature = transaction_builder
-            .prepare_transaction(&mut transaction, &keypair, &rpc_client)
+        let balance = self.rpc_client.get_balance(old).await?;
+        let token_account_rent = self
+            .rent
+            .minimum_balance(rotation::SWEEP_TOKEN_ACCOUNT_LEN)
             .await?;
-
-        // Send transaction
-        rpc_client.send_transaction(&transaction).await?;
-
-        // Update agent context
-        let mut agent_context = self.agent_context.write().await;
-        agent_context.deduct_from_budget(estimated_sol_value);
-        agent_context.record_success();
-
-        Ok(signature)
+        rotation::plan_sweep(
+            old,
+            new,
+            balance,
+            LAMPORTS_PER_SIGNATURE,
+            token_account_rent,
+            &tokens,
+        )
     }
 
-    /// Sign a transaction (does not send it)
-    pub async fn sign_transaction(&self, transaction: &mut Transaction) -> Result<Signature> {
-        let keypair = self.keypair.read().await;
-        let rpc_client = self.rpc_client.read().await;
-        let mut transaction_builder = self.transaction_builder.lock().await;
+    /// Send one sweep transaction signed by the old key and wait for it
+    async fn send_sweep(
+        &self,
+        instructions: &[solana_sdk::instruction::Instruction],
+        old_keypair: &SecureKeypair,
+    ) -> Result<Signature> {
+        let message = solana_sdk::message::Message::new_with_blockhash(
+            instructions,
+            Some(&old_keypair.public_key()),
+            &self.rpc_client.get_latest_blockhash().await?,
+        );
+        let mut transaction = Transaction::new_unsigned(message);
+        signer::sign_transaction(&mut transaction, old_keypair).await?;
+        let signature = self.rpc_client.send_transaction(&transaction).await?;
+        self.wait_for_confirmation(
+            &signature,
+            CommitmentConfig::confirmed(),
+            ROTATION_CONFIRMATION_TIMEOUT,
+        )
+        .await?;
+        Ok(signature)
+    }
+
+    /// Write the wallet's keypair to `path` in Solana CLI JSON format
+    ///
+    /// The file holds the unencrypted secret key, so this requires the wallet
+    /// passphrase and `PermissionLevel::Administrator`, which only the
+    /// configuration the wallet was opened with can grant
+    /// (`agent.default_permission_level`). An existing file is only replaced
+    /// when `force` is set. On Unix the file is readable by its owner only.
+    pub async fn export_keypair(
+        &self,
+        passphrase: &Zeroizing<String>,
+        path: impl AsRef<Path>,
+        force: bool,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let level = self.agent_context.read().await.permission_level;
+        if !level.can_perform(PermissionLevel::Administrator) {
+            return Err(Error::permission_denied(format!(
+                "Exporting the keypair requires Administrator permission (have {})",
+                level
+            )));
+        }
+        if !force && path.exists() {
+            return Err(Error::validation(format!(
+                "{} already exists; refusing to overwrite it",
+                path.display()
+            )));
+        }
+
+        let (encrypted_data, metadata) =
+            self.storage_service.read().await.read_wallet(&self.name)?;
+        let secret = bound_secret(
+            self.keystore.as_ref(),
+            &self.config.wallet.storage,
+            &self.name,
+            &encrypted_data,
+        )?;
+        let (_, _, keypair) = unlock_with_secret(
+            &encrypted_data,
+            passphrase,
+            secret.as_deref().map(Vec::as_slice),
+        )?;
+        if keypair.public_key() != metadata.public_key || keypair.public_key() != self.public_key {
+            return Err(Error::State(format!(
+                "Stored key of wallet '{}' does not match its public key",
+                self.name
+            )));
+        }
+
+        write_secret_file(path, keypair.to_solana_json().as_bytes(), force)?;
+        tracing::warn!(
+            "Exported the unencrypted keypair of wallet '{}' to {}",
+            self.name,
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Write a passphrase-only copy of the wallet file to `path`, for backups
+    ///
+    /// A keystore-bound wallet file only opens on the machine holding its
+    /// keychain entry. The recovery copy holds the same key and wallet data
+    /// encrypted under the passphrase alone, in the wallet file format, so it
+    /// can be restored anywhere by placing it in a wallet directory. An
+    /// existing file is only replaced when `force` is set; on Unix the file
+    /// is created readable by its owner only.
+    pub async fn export_recovery(
+        &self,
+        passphrase: &Zeroizing<String>,
+        path: impl AsRef<Path>,
+        force: bool,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        if !force && path.exists() {
+            return Err(Error::validation(format!(
+                "{} already exists; refusing to overwrite it",
+                path.display()
+            )));
+        }
+
+        let (encrypted_data, metadata) =
+            self.storage_service.read().await.read_wallet(&self.name)?;
+        let secret = bound_secret(
+            self.keystore.as_ref(),
+            &self.config.wallet.storage,
+            &self.name,
+            &encrypted_data,
+        )?;
+        let (wallet_data, _, keypair) = unlock_with_secret(
+            &encrypted_data,
+            passphrase,
+            secret.as_deref().map(Vec::as_slice),
+        )?;
+        if keypair.public_key() != metadata.public_key {
+            return Err(Error::State(format!(
+                "Stored key of wallet '{}' does not match its public key",
+                self.name
+            )));
+        }
+
+        let recovery = WalletStorage {
+            version: "1.0".to_string(),
+            encrypted_data: seal(
+                &crate::storage::utils::serialize_wallet_data(&wallet_data)?,
+                passphrase,
+                &self.config.wallet.encryption,
+                None,
+            )?,
+            metadata,
+        };
+        let json = serde_json::to_vec_pretty(&recovery)?;
+        write_secret_file(path, &json, force)?;
+        tracing::info!(
+            "Exported a passphrase-only recovery copy of wallet '{}' to {}",
+            self.name,
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Destination policy enforced on every transaction
+    pub async fn address_policy(&self) -> AddressPolicy {
+        self.agent_context.read().await.address_policy.clone()
+    }
+
+    /// Replace the destination policy
+    ///
+    /// Requires `PermissionLevel::Administrator`, so an agent cannot widen
+    /// the set of addresses it may pay.
+    pub async fn set_address_policy(&self, policy: AddressPolicy) -> Result<()> {
+        let mut agent_context = self.agent_context.write().await;
+        if !agent_context
+            .permission_level
+            .can_perform(PermissionLevel::Administrator)
+        {
+            return Err(Error::permission_denied(
+                "Changing the address policy requires administrator permission",
+            ));
+        }
+        agent_context.address_policy = policy;
+        tracing::info!("Updated the address policy of wallet '{}'", self.name);
+        Ok(())
+    }
+
+    /// Get wallet public key
+    pub fn public_key(&self) -> Pubkey {
+        self.public_key
+    }
+
+    /// Signer producing the wallet's signatures
+    pub fn signer(&self) -> Arc<dyn DynTransactionSigner> {
+        self.signer.clone()
+    }
+
+    /// Multisig configuration, if this is a multisig wallet
+    pub fn multisig(&self) -> Option<&MultisigConfig> {
+        self.multisig.as_ref()
+    }
+
+    /// Get wallet name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get wallet balance in SOL
+    pub async fn get_balance(&self) -> Result<f64> {
+        let balance_lamports: u64 = self.rpc_client.get_balance(&self.public_key).await?;
+        Ok(Lamports::new(balance_lamports).to_sol_f64())
+    }
+
+    /// Fund the wallet from the cluster's faucet and wait for the airdrop
+    ///
+    /// Refused with [`Error::PermissionDenied`] on mainnet-beta, which is
+    /// told apart by its genesis hash rather than the URL. A faucet that
+    /// rate-limits the request fails with [`Error::RateLimitExceeded`].
+    pub async fn request_airdrop(&self, amount: Lamports) -> Result<Signature> {
+        let cluster = self.rpc_client.get_cluster().await?;
+        if cluster.is_mainnet() {
+            return Err(Error::permission_denied(
+                "Airdrops are not available on mainnet-beta",
+            ));
+        }
+        self.rpc_client
+            .request_airdrop(&self.public_key, amount.as_u64())
+            .await
+    }
+
+    /// Get token balance for a specific mint
+    pub async fn get_token_balance(&self, mint: &Pubkey) -> Result<u64> {
+        let token_manager = self.token_manager.read().await;
+        let pubkey = self.public_key();
+
+        token_manager.get_balance(mint, &pubkey).await
+    }
+
+    /// Refresh all tracked token balances and sync them into the agent context
+    ///
+    /// Mints whose accounts could not be refreshed keep their last known
+    /// balance and are listed in `AgentContext::stale_token_mints`.
+    pub async fn refresh_token_balances(&self, concurrency: usize) -> RefreshReport {
+        let pubkey = self.metadata.read().await.public_key;
+        let report = {
+            let token_manager = self.token_manager.read().await;
+            token_manager
+                .refresh_all_balances(&pubkey, concurrency)
+                .await
+        };
+
+        // A mint with any failed account keeps its previous total; a sum of
+        // only the accounts that answered would understate it
+        let mut totals: HashMap<Pubkey, u64> = HashMap::new();
+        for info in &report.updated {
+            if !report.stale_mints.contains(&info.mint) {
+                *totals.entry(info.mint).or_insert(0) += info.balance;
+            }
+        }
+
+        let mut agent_context = self.agent_context.write().await;
+        agent_context.token_balances.extend(totals);
+        agent_context.stale_token_mints = report.stale_mints.clone();
+        agent_context.update_timestamp();
+
+        report
+    }
+
+    /// Address of a base58 address or `.sol` domain
+    ///
+    /// Domains resolve to the owner of their name record and are cached for
+    /// a few minutes; an unregistered domain fails with
+    /// [`Error::InvalidAddress`] naming it.
+    pub async fn resolve_recipient(&self, input: &str) -> Result<Pubkey> {
+        self.names.resolve_recipient(input).await
+    }
+
+    /// Transfer SOL to an address or `.sol` domain
+    pub async fn transfer_sol(
+        &self,
+        to: impl Into<Recipient>,
+        amount: Lamports,
+        memo: Option<String>,
+    ) -> Result<Signature> {
+        self.transfer_sol_with_options(to, amount, memo, &TransactionOptions::default())
+            .await
+    }
+
+    /// Transfer an amount given in SOL as a float
+    #[deprecated(note = "use `transfer_sol` with `Lamports::from_sol_str`")]
+    pub async fn transfer_sol_f64(
+        &self,
+        to: &Pubkey,
+        amount: f64,
+        memo: Option<String>,
+    ) -> Result<Signature> {
+        self.transfer_sol(to, Lamports::from_sol_f64_rounded(amount)?, memo)
+            .await
+    }
+
+    /// Transfer SOL to an address or `.sol` domain with explicit transaction
+    /// options
+    ///
+    /// When `options.confirmation` requests a commitment, this blocks until the
+    /// transaction reaches it and only then records the success. A domain is
+    /// resolved first and both it and the address are audited; the
+    /// recipient is then checked with [`Wallet::validate_destination`].
+    pub async fn transfer_sol_with_options(
+        &self,
+        to: impl Into<Recipient>,
+        amount: Lamports,
+        memo: Option<String>,
+        options: &TransactionOptions,
+    ) -> Result<Signature> {
+        let recipient = to.into();
+        let to = self.names.resolve(&recipient).await?;
+        let named;
+        let options = match recipient.domain() {
+            Some(domain) => {
+                named = TransactionOptions {
+                    recipient_name: Some(domain.to_string()),
+                    ..options.clone()
+                };
+                &named
+            }
+            None => options,
+        };
+        self.validate_destination(
+            &to,
+            &DestinationTransfer::Sol { lamports: amount },
+            &options.destination,
+        )
+        .await?;
+        let action = AgentAction::TransferSol { to, amount, memo };
+        self.execute_action(&action, options).await
+    }
+
+    /// Pay many recipients, packing the transfers into few transactions
+    ///
+    /// See [`Wallet::execute_batch_transfer`].
+    pub async fn batch_transfer_sol(
+        &self,
+        recipients: &[(Pubkey, Lamports)],
+        memo: Option<String>,
+    ) -> Result<BatchTransferReport> {
+        self.batch_transfer_sol_with_options(recipients, memo, &TransactionOptions::default())
+            .await
+    }
+
+    /// Pay many recipients amounts given in SOL as floats
+    #[deprecated(note = "use `batch_transfer_sol` with `Lamports` amounts")]
+    pub async fn batch_transfer_sol_f64(
+        &self,
+        recipients: &[(Pubkey, f64)],
+        memo: Option<String>,
+    ) -> Result<BatchTransferReport> {
+        let recipients = recipients
+            .iter()
+            .map(|(to, amount)| Ok((*to, Lamports::from_sol_f64_rounded(*amount)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.batch_transfer_sol(&recipients, memo).await
+    }
+
+    /// Pay many recipients with explicit transaction options
+    pub async fn batch_transfer_sol_with_options(
+        &self,
+        recipients: &[(Pubkey, Lamports)],
+        memo: Option<String>,
+        options: &TransactionOptions,
+    ) -> Result<BatchTransferReport> {
+        let transfers = recipients
+            .iter()
+            .map(|(to, amount)| (*to, amount.as_u64()))
+            .collect();
+
+        let action = AgentAction::BatchTransfer {
+            transfers,
+            mint: None,
+            memo,
+        };
+        self.execute_batch_transfer(&action, options).await
+    }
+
+    /// Execute a batch transfer, sending as many transactions as it takes
+    ///
+    /// The whole batch is checked against the balance and spending limits
+    /// before anything is sent. Transactions then go out one at a time and
+    /// the first failure stops the batch; the report tells which recipients
+    /// were paid. An error is only returned if nothing was sent.
+    pub async fn execute_batch_transfer(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<BatchTransferReport> {
+        if !matches!(action, AgentAction::BatchTransfer { .. }) {
+            return Err(Error::NotSupported(format!(
+                "Not a batch transfer: {}",
+                action.description()
+            )));
+        }
+
+        let sol_value = self.preflight(action).await?;
+        let chunks = {
+            let agent_context = self.agent_context.read().await;
+            agent_context.is_action_allowed(sol_value)?;
+            self.transaction_builder
+                .lock()
+                .await
+                .split_batch(action, &agent_context, options)?
+        };
+
+        let mut report = BatchTransferReport {
+            receipts: Vec::new(),
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+            unsent: Vec::new(),
+            error: None,
+        };
+        let mut chunks = chunks.into_iter();
+        while let Some(chunk) = chunks.next() {
+            match self.execute_action_with_receipt(&chunk, options).await {
+                Ok(receipt) => {
+                    report.receipts.push(receipt);
+                    report.succeeded.extend(batch_legs(&chunk));
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Batch transfer of wallet '{}' stopped after {} of {} transactions: {}",
+                        self.name,
+                        report.receipts.len(),
+                        report.receipts.len() + 1 + chunks.len(),
+                        e
+                    );
+                    report.failed = batch_legs(&chunk).to_vec();
+                    report.unsent = chunks.flat_map(|c| batch_legs(&c).to_vec()).collect();
+                    report.error = Some(e);
+                    break;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Transfer tokens to another address
+    pub async fn transfer_token(
+        &self,
+        mint: &Pubkey,
+        to: &Pubkey,
+        amount: u64,
+        memo: Option<String>,
+    ) -> Result<Signature> {
+        self.transfer_token_with_options(mint, to, amount, memo, &TransactionOptions::default())
+            .await
+    }
+
+    /// Transfer tokens to another address with explicit transaction options
+    ///
+    /// The recipient is checked with [`Wallet::validate_destination`] first;
+    /// a recipient that is itself a token account of `mint` is credited
+    /// directly.
+    pub async fn transfer_token_with_options(
+        &self,
+        mint: &Pubkey,
+        to: &Pubkey,
+        amount: u64,
+        memo: Option<String>,
+        options: &TransactionOptions,
+    ) -> Result<Signature> {
+        let check = self
+            .validate_destination(
+                to,
+                &DestinationTransfer::Token { mint: *mint },
+                &options.destination,
+            )
+            .await?;
+        let options = match check.token_account {
+            Some(account) => Cow::Owned(TransactionOptions {
+                token_destination: Some(account),
+                ..options.clone()
+            }),
+            None => Cow::Borrowed(options),
+        };
+        let action = AgentAction::TransferToken {
+            mint: *mint,
+            to: *to,
+            amount,
+            memo,
+        };
+        self.execute_action(&action, &options).await
+    }
+
+    /// Pay a Solana Pay transfer request
+    ///
+    /// The request must name an amount; token amounts are checked against
+    /// the mint's decimals. Its references are added to the transfer so the
+    /// merchant can find it, and its memo is written with the transfer.
+    pub async fn pay(&self, request: &TransferRequest) -> Result<Signature> {
+        let options = TransactionOptions {
+            payment_references: request.references.clone(),
+            ..Default::default()
+        };
+        match &request.spl_token {
+            None => {
+                self.transfer_sol_with_options(
+                    request.recipient,
+                    request.lamports()?,
+                    request.memo.clone(),
+                    &options,
+                )
+                .await
+            }
+            Some(mint) => {
+                let decimals = self
+                    .token_manager
+                    .read()
+                    .await
+                    .get_token_info(mint)
+                    .await?
+                    .decimals;
+                let amount = request.token_amount(decimals)?;
+                self.transfer_token_with_options(
+                    mint,
+                    &request.recipient,
+                    amount.raw,
+                    request.memo.clone(),
+                    &options,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Look up the recipient of a transfer and refuse unsafe ones
+    ///
+    /// See [`destination`](crate::destination) for the checks; warnings are
+    /// logged and returned with the destination's on-chain state.
+    pub async fn validate_destination(
+        &self,
+        to: &Pubkey,
+        transfer: &DestinationTransfer,
+        options: &DestinationOptions,
+    ) -> Result<DestinationCheck> {
+        let check =
+            destination::validate_destination(self.rpc_client.as_ref(), to, transfer, options)
+                .await?;
+        for warning in &check.warnings {
+            tracing::warn!("Wallet '{}': {}", self.name, warning);
+        }
+        Ok(check)
+    }
+
+    /// Execute a transfer action decided by an agent
+    ///
+    /// Actions matching a template registered with [`Wallet::prepare_templates`]
+    /// skip rebuilding the transaction from scratch.
+    pub async fn execute_action(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<Signature> {
+        self.execute_action_with_receipt(action, options)
+            .await
+            .map(|receipt| receipt.signature)
+    }
+
+    /// Execute an action and itemize what it cost
+    ///
+    /// The receipt lists rent locked in new accounts separately from the fee
+    /// and the transfer amount. Rent is recorded as reclaimable in the agent
+    /// context instead of being deducted from the daily budget.
+    ///
+    /// Sends are limited to `agent.limits.max_transactions_per_minute`; an
+    /// action over the limit fails with `RateLimitExceeded` before signing.
+    ///
+    /// In [`RunMode::DryRun`] the signed transaction is always simulated and
+    /// never sent; the receipt carries the simulated balance changes and the
+    /// spend comes out of the wallet's shadow budget.
+    pub async fn execute_action_with_receipt(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<ActionReceipt> {
+        let span = transfer_span(&self.name, action);
+        let result = self.send_action(action, options).instrument(span).await;
+        match &result {
+            Ok(receipt) if receipt.is_dry_run() => {}
+            Ok(_) => Metrics::global().record_transaction(action.kind(), true),
+            Err(_) => Metrics::global().record_transaction(action.kind(), false),
+        }
+        result
+    }
+
+    /// Sign and send an action, see [`Wallet::execute_action_with_receipt`]
+    async fn send_action(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<ActionReceipt> {
+        if let Some(multisig) = &self.multisig {
+            return Err(Error::permission_denied(format!(
+                "Multisig wallet requires {} co-signatures; use build_partially_signed",
+                multisig.required_cosigners()
+            )));
+        }
 
-        // Get recent blockhash
-        let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+        let (mut prepared, mut reservation) = self.prepare_reserved(action, options).await?;
+        self.check_rate_limit(action).await?;
 
         // Sign transaction
-        transaction_builder.sign_transaction(transaction, &keypair, recent_blockhash)
+        let signature = {
+            let mut transaction_builder = self.transaction_builder.lock().await;
+            transaction_builder
+                .prepare_transaction(
+                    &mut prepared.transaction,
+                    self.signer.as_ref(),
+                    self.rpc_client.as_ref(),
+                )
+                .await?
+        };
+
+        if self.config.dry_run {
+            return self
+                .rehearse(action, options, prepared, reservation, signature)
+                .await;
+        }
+
+        // A transaction that would fail is never audited or sent
+        if options.simulate_before_send {
+            if let Err(e) = preview::simulate(
+                self.rpc_client.as_ref(),
+                &self.public_key,
+                action,
+                &prepared.transaction,
+                prepared.fee_lamports,
+            )
+            .await
+            {
+                reservation.release()?;
+                return Err(e);
+            }
+        }
+
+        // Audit the intent before anything can reach the network
+        let mut signature = signature;
+        let mut intent = AuditEntry::intent(self.public_key, action.clone())
+            .with_signature(signature)
+            .with_fee(prepared.fee_lamports)
+            .with_warnings(prepared.warnings.clone())
+            .with_recipient_name(options.recipient_name.clone());
+        self.audit(&intent).await?;
+
+        // Journal the submission first so a crash mid-send is never forgotten
+        reservation.mark_sent(&signature)?;
+
+        // Send transaction; with preflight on, an error means it was rejected.
+        // A blockhash that expired between signing and sending executed
+        // nothing, so the transaction is signed again and resent.
+        let mut retries = 0;
+        let submission = loop {
+            let e = match self.submit(&prepared.transaction, options).await {
+                Ok(submission) => break submission,
+                Err(e) => e,
+            };
+            self.audit_outcome(&intent, AuditOutcome::Failed, Some(&e))
+                .await;
+            if !Self::can_resign(&e, &prepared.transaction) || retries >= options.max_retries {
+                reservation.release()?;
+                return Err(e);
+            }
+            retries += 1;
+            tracing::warn!(
+                "Blockhash of {} expired before it landed; re-signing (retry {} of {})",
+                signature,
+                retries,
+                options.max_retries
+            );
+            signature = match self.resign(&mut prepared.transaction).await {
+                Ok(signature) => signature,
+                Err(e) => {
+                    reservation.release()?;
+                    return Err(e);
+                }
+            };
+            intent = AuditEntry::intent(self.public_key, action.clone())
+                .with_signature(signature)
+                .with_fee(prepared.fee_lamports)
+                .with_warnings(prepared.warnings.clone())
+                .with_recipient_name(options.recipient_name.clone());
+            self.audit(&intent).await?;
+            reservation.mark_sent(&signature)?;
+        };
+        let intent = intent.with_submission(submission);
+
+        // Update agent context once the transaction has landed
+        let rent = Lamports::new(prepared.rent_lamports());
+        let confirmed = self
+            .confirm_and_record(&signature, options, rent, Some(reservation))
+            .await;
+        self.deduct_token_spend(action, &confirmed).await;
+        self.credit_closed_account(&prepared, &confirmed).await;
+        match &confirmed {
+            Ok(()) if options.confirmation.requirement().is_none() => {
+                self.audit_outcome(&intent, AuditOutcome::Sent, None).await
+            }
+            Ok(()) => {
+                self.audit_outcome(&intent, AuditOutcome::Confirmed, None)
+                    .await
+            }
+            Err(e @ Error::ConfirmationTimeout { .. }) => {
+                self.audit_outcome(&intent, AuditOutcome::Unconfirmed, Some(e))
+                    .await
+            }
+            Err(e) => {
+                self.audit_outcome(&intent, AuditOutcome::Failed, Some(e))
+                    .await
+            }
+        }
+        confirmed?;
+
+        Ok(ActionReceipt::from((signature, prepared)))
     }
 
-    /// Sign and send a transaction
-    pub async fn sign_and_send(&self, transaction: &mut Transaction) -> Result<Signature> {
-        let signature = self.sign_transaction(transaction).await?;
-        let rpc_client = self.rpc_client.read().await;
+    /// Broadcast a signed transaction the way `options.submission` asks
+    ///
+    /// A Jito bundle is awaited until it lands. Should the block engine
+    /// refuse or fail it and fallback be enabled, the same transaction is
+    /// broadcast through the RPC node; it carries the same signature, so it
+    /// can execute at most once. A bundle still pending at the deadline may
+    /// yet land, so it is never fallen back from: the signature is confirmed
+    /// like any other sent transaction.
+    async fn submit(
+        &self,
+        transaction: &Transaction,
+        options: &TransactionOptions,
+    ) -> Result<SubmissionPath> {
+        let SubmissionMode::JitoBundle {
+            block_engine_url,
+            tip_lamports,
+            fallback_to_rpc,
+        } = &options.submission
+        else {
+            self.rpc_client.send_transaction(transaction).await?;
+            return Ok(SubmissionPath::Rpc);
+        };
+
+        let bundled = self
+            .submit_bundle(transaction, block_engine_url, *tip_lamports)
+            .await;
+        match bundled {
+            Ok(bundle_id) => Ok(SubmissionPath::JitoBundle { bundle_id }),
+            Err(e) if *fallback_to_rpc => {
+                tracing::warn!(
+                    "Jito bundle of {} failed, broadcasting through RPC: {}",
+                    transaction.signatures[0],
+                    e
+                );
+                self.rpc_client.send_transaction(transaction).await?;
+                Ok(SubmissionPath::RpcFallback {
+                    reason: e.to_string(),
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Send `transaction` as a tipped Jito bundle and wait for it to land
+    ///
+    /// A bundle that is still pending at the deadline is returned as sent.
+    #[cfg(feature = "jito")]
+    async fn submit_bundle(
+        &self,
+        transaction: &Transaction,
+        block_engine_url: &str,
+        tip_lamports: u64,
+    ) -> Result<String> {
+        let client = JitoClient::new(JitoConfig::new(block_engine_url, tip_lamports))?;
+        // A durable nonce is no recent blockhash the tip could use
+        let blockhash = match nonce::nonce_account_of(&transaction.message) {
+            Some(_) => self.rpc_client.get_latest_blockhash().await?,
+            None => transaction.message.recent_blockhash,
+        };
+        let bundle_id = client
+            .submit(vec![transaction.clone()], self.signer.as_ref(), blockhash)
+            .await?;
+        match client.wait_for_bundle(&bundle_id).await {
+            Ok(_) => Ok(bundle_id),
+            Err(Error::Timeout(reason)) => {
+                tracing::warn!(
+                    "{}; confirming {} instead",
+                    reason,
+                    transaction.signatures[0]
+                );
+                Ok(bundle_id)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Jito bundles are only sent by builds with the `jito` feature
+    #[cfg(not(feature = "jito"))]
+    async fn submit_bundle(
+        &self,
+        _transaction: &Transaction,
+        _block_engine_url: &str,
+        _tip_lamports: u64,
+    ) -> Result<String> {
+        Err(Error::NotSupported(
+            "Jito bundles need the `jito` feature".to_string(),
+        ))
+    }
+
+    /// Whether a rejected send can be recovered by signing with a new blockhash
+    ///
+    /// Durable nonce transactions do not use a recent blockhash, so their
+    /// failures are never retried this way.
+    fn can_resign(error: &Error, transaction: &Transaction) -> bool {
+        matches!(error, Error::BlockhashExpired(_))
+            && nonce::nonce_account_of(&transaction.message).is_none()
+    }
+
+    /// Sign a transaction again with a freshly fetched blockhash
+    async fn resign(&self, transaction: &mut Transaction) -> Result<Signature> {
+        let mut transaction_builder = self.transaction_builder.lock().await;
+        transaction_builder.invalidate_blockhash();
+        transaction_builder
+            .prepare_transaction(transaction, self.signer.as_ref(), self.rpc_client.as_ref())
+            .await
+    }
+
+    /// Finish a dry run: simulate instead of sending and spend the shadow budget
+    async fn rehearse(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+        prepared: PreparedAction,
+        reservation: BudgetReservation,
+        signature: Signature,
+    ) -> Result<ActionReceipt> {
+        let simulated = match preview::simulate(
+            self.rpc_client.as_ref(),
+            &self.public_key,
+            action,
+            &prepared.transaction,
+            prepared.fee_lamports,
+        )
+        .await
+        {
+            Ok(simulated) => simulated,
+            Err(e) => {
+                reservation.release()?;
+                return Err(e);
+            }
+        };
+
+        let intent = AuditEntry::intent(self.public_key, action.clone())
+            .with_signature(signature)
+            .with_fee(prepared.fee_lamports)
+            .with_warnings(prepared.warnings.clone())
+            .with_recipient_name(options.recipient_name.clone())
+            .with_dry_run(true);
+        self.audit(&intent).await?;
+
+        // The ledger of a dry-run wallet is in memory, so this only
+        // decrements the shadow budget
+        let rent = Lamports::new(prepared.rent_lamports());
+        self.record_outcome(&signature, Ok(()), rent, Some(reservation))
+            .await?;
+        self.deduct_token_spend(action, &Ok(())).await;
+        self.credit_closed_account(&prepared, &Ok(())).await;
+        self.audit_outcome(&intent, AuditOutcome::Simulated, None)
+            .await;
+        tracing::info!(
+            "Dry run: wallet '{}' would have sent {} ({})",
+            self.name,
+            signature,
+            action.description()
+        );
+
+        let mut receipt = ActionReceipt::from((signature, prepared));
+        receipt.simulated = Some(simulated);
+        Ok(receipt)
+    }
+
+    /// Refuse an operation that can only broadcast when rehearsing
+    fn ensure_live(&self, operation: &str) -> Result<()> {
+        if self.config.dry_run {
+            return Err(Error::NotSupported(format!(
+                "{} broadcasts transactions and is not available in dry-run mode",
+                operation
+            )));
+        }
+        Ok(())
+    }
+
+    /// Send an action, raising its priority fee until it lands
+    ///
+    /// Each broadcast waits `policy.wait` for any transaction sent so far to
+    /// reach the commitment requested by `options.confirmation` (`confirmed`
+    /// when it requests none). If none did, the same instructions are rebuilt
+    /// at the policy's next compute unit price, signed with a fresh blockhash
+    /// and rebroadcast. The starting price is `options.compute_unit_price`
+    /// when set, otherwise the median of recent prioritization fees.
+    ///
+    /// An earlier transaction can still land until its blockhash expires, so
+    /// a rebroadcast first waits up to `policy.expiry_wait` for the previous
+    /// blockhash to expire, and escalation stops as soon as an earlier
+    /// signature was seen or the blockhash outlived the wait. The report and
+    /// the audit trail list every signature for reconciliation. With a
+    /// durable nonce every broadcast advances the same nonce, so at most one
+    /// of them can land and rebroadcasts only check the earlier signatures.
+    pub async fn send_with_escalation(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+        policy: &EscalationPolicy,
+    ) -> Result<EscalationReport> {
+        self.ensure_live("Escalating sends")?;
+        if let Some(multisig) = &self.multisig {
+            return Err(Error::permission_denied(format!(
+                "Multisig wallet requires {} co-signatures; use build_partially_signed",
+                multisig.required_cosigners()
+            )));
+        }
+        if policy.attempts() == 0 {
+            return Err(Error::config("Escalation policy allows no broadcasts"));
+        }
+        let (action, options) = self.resolve_stake(action, options).await?;
+        let (action, options) = (action.as_ref(), options.as_ref());
+
+        let (prepared, mut reservation) = self.prepare_reserved(action, options).await?;
+        self.check_rate_limit(action).await?;
+
+        let base_price = match options.compute_unit_price.or(options.priority_fee) {
+            Some(price) => price,
+            None => {
+                escalation::starting_price(
+                    self.rpc_client.as_ref(),
+                    &prepared.transaction.message,
+                    policy,
+                )
+                .await
+            }
+        };
+        let commitment = options
+            .confirmation
+            .requirement()
+            .map_or_else(CommitmentConfig::confirmed, |(commitment, _)| commitment);
+
+        let mut signatures = Vec::new();
+        let mut prices = Vec::new();
+        let mut intents = Vec::new();
+        let mut landed = None;
+        let mut last_blockhash = None;
+        for attempt in 0..policy.attempts() {
+            // Never rebroadcast while an earlier transaction can still land
+            match last_blockhash.filter(|_| options.nonce.is_none()) {
+                Some(blockhash) => {
+                    match escalation::wait_for_expiry(
+                        self.rpc_client.as_ref(),
+                        &signatures,
+                        &blockhash,
+                        policy.expiry_wait,
+                        policy.poll_interval,
+                    )
+                    .await?
+                    {
+                        Expiry::Expired => {}
+                        Expiry::Landed(signature) => {
+                            landed = Some(signature);
+                            break;
+                        }
+                        Expiry::Pending => {
+                            tracing::warn!(
+                                "Wallet '{}' stopped escalating: blockhash {} has not expired",
+                                self.name,
+                                blockhash
+                            );
+                            break;
+                        }
+                    }
+                }
+                None if attempt > 0 => {
+                    landed = escalation::find_landed(self.rpc_client.as_ref(), &signatures).await?;
+                    if landed.is_some() {
+                        break;
+                    }
+                }
+                None => {}
+            }
+
+            let price = policy.price_for(attempt, base_price, options);
+            let attempt_options = TransactionOptions {
+                compute_unit_price: Some(price),
+                priority_fee: None,
+                ..options.clone()
+            };
+            let (mut transaction, fee_lamports) = {
+                let agent_context = self.agent_context.read().await;
+                let mut transaction_builder = self.transaction_builder.lock().await;
+                let transaction = transaction_builder.build_from_action(
+                    action,
+                    &agent_context,
+                    &attempt_options,
+                )?;
+                let fee =
+                    transaction_builder.estimate_transaction_fee(&transaction, &attempt_options);
+                (transaction, fee)
+            };
+            let signature = self
+                .transaction_builder
+                .lock()
+                .await
+                .prepare_transaction(
+                    &mut transaction,
+                    self.signer.as_ref(),
+                    self.rpc_client.as_ref(),
+                )
+                .await?;
+
+            if attempt == 0 && options.simulate_before_send {
+                if let Err(e) = preview::simulate(
+                    self.rpc_client.as_ref(),
+                    &self.public_key,
+                    action,
+                    &transaction,
+                    fee_lamports,
+                )
+                .await
+                {
+                    reservation.release()?;
+                    return Err(e);
+                }
+            }
+
+            let intent = AuditEntry::intent(self.public_key, action.clone())
+                .with_signature(signature)
+                .with_fee(fee_lamports)
+                .with_warnings(prepared.warnings.clone())
+                .with_recipient_name(options.recipient_name.clone());
+            self.audit(&intent).await?;
+            reservation.mark_sent(&signature)?;
+
+            if let Err(e) = self.rpc_client.send_transaction(&transaction).await {
+                self.audit_outcome(&intent, AuditOutcome::Failed, Some(&e))
+                    .await;
+                let Some(previous) = signatures.last() else {
+                    reservation.release()?;
+                    return Err(e);
+                };
+                // An earlier broadcast may still land; settle on those
+                reservation.mark_sent(previous)?;
+                tracing::warn!(
+                    "Rebroadcast {} of wallet '{}' was rejected: {}",
+                    attempt,
+                    self.name,
+                    e
+                );
+                break;
+            }
+            tracing::info!(
+                "Wallet '{}' broadcast {} at {} micro-lamports per compute unit",
+                self.name,
+                signature,
+                price
+            );
+            signatures.push(signature);
+            prices.push(price);
+            intents.push(intent);
+            last_blockhash = Some(transaction.message.recent_blockhash);
+
+            landed = escalation::wait_for_any(
+                self.rpc_client.as_ref(),
+                &signatures,
+                commitment,
+                policy.wait,
+                policy.poll_interval,
+            )
+            .await?;
+            if landed.is_some() {
+                break;
+            }
+        }
+
+        // A processed transaction gets one more wait to reach the commitment
+        let (settled, outcome) = match landed {
+            Some(signature) => (
+                signature,
+                self.wait_for_confirmation(&signature, commitment, policy.wait)
+                    .await,
+            ),
+            None => {
+                let signature = signatures.last().copied().unwrap_or_default();
+                let timeout = Error::ConfirmationTimeout {
+                    signature,
+                    last_status: None,
+                };
+                (signature, Err(timeout))
+            }
+        };
+        let rent = Lamports::new(prepared.rent_lamports());
+        let recorded = self
+            .record_outcome(&settled, outcome, rent, Some(reservation))
+            .await;
+        self.deduct_token_spend(action, &recorded).await;
+        self.credit_closed_account(&prepared, &recorded).await;
+
+        for (signature, intent) in signatures.iter().zip(&intents) {
+            match &recorded {
+                Ok(()) if *signature == settled => {
+                    self.audit_outcome(intent, AuditOutcome::Confirmed, None)
+                        .await
+                }
+                Err(e @ Error::ConfirmationTimeout { .. }) => {
+                    self.audit_outcome(intent, AuditOutcome::Unconfirmed, Some(e))
+                        .await
+                }
+                Err(e) if *signature == settled => {
+                    self.audit_outcome(intent, AuditOutcome::Failed, Some(e))
+                        .await
+                }
+                // Superseded broadcasts may still land until their blockhash expires
+                _ => {
+                    self.audit_outcome(intent, AuditOutcome::Unconfirmed, None)
+                        .await
+                }
+            }
+        }
+        recorded?;
+
+        Ok(EscalationReport {
+            signatures,
+            compute_unit_prices: prices,
+            landed: settled,
+        })
+    }
+
+    /// Count a send against the wallet's rate limit
+    ///
+    /// Defense in depth against agents that bypass their runner's limit.
+    async fn check_rate_limit(&self, action: &AgentAction) -> Result<()> {
+        if let Err(Error::RateLimitExceeded(reason)) = self
+            .rate_limiter
+            .check_and_record(std::time::Instant::now())
+        {
+            self.agent_context.write().await.record_failure(
+                Error::RateLimitExceeded(reason.clone()),
+                action.description(),
+            );
+            return Err(Error::RateLimitExceeded(reason));
+        }
+        Ok(())
+    }
+
+    /// Fill in the chain state a stake action is built against
+    ///
+    /// Stake actions get the first unused stake account seed. Unstake
+    /// actions addressing a vote account are pointed at the wallet's stake
+    /// account delegated to it, and get that account's activation state.
+    /// Anything already set in the options is kept.
+    async fn resolve_stake<'a>(
+        &self,
+        action: &'a AgentAction,
+        options: &'a TransactionOptions,
+    ) -> Result<(Cow<'a, AgentAction>, Cow<'a, TransactionOptions>)> {
+        match action {
+            AgentAction::StakeTokens { .. } if options.stake_seed_index.is_none() => {
+                let index =
+                    stake::next_stake_index(self.rpc_client.as_ref(), &self.public_key).await?;
+                let options = TransactionOptions {
+                    stake_seed_index: Some(index),
+                    ..options.clone()
+                };
+                Ok((Cow::Borrowed(action), Cow::Owned(options)))
+            }
+            AgentAction::UnstakeTokens {
+                staking_pool,
+                amount,
+            } if options.stake_activation.is_none() => {
+                let account = self.rpc_client.get_account(staking_pool).await?;
+                let stake_account = if account.owner == solana_sdk::vote::program::id() {
+                    stake::stake_accounts(self.rpc_client.as_ref(), &self.public_key)
+                        .await?
+                        .into_iter()
+                        .find(|(_, _, account)| {
+                            stake::delegated_vote_account(account) == Some(*staking_pool)
+                        })
+                        .map(|(_, address, _)| address)
+                        .ok_or_else(|| {
+                            Error::validation(format!(
+                                "No stake account of the wallet is delegated to {}",
+                                staking_pool
+                            ))
+                        })?
+                } else {
+                    *staking_pool
+                };
+                let activation = self.rpc_client.get_stake_activation(&stake_account).await?;
+                let action = AgentAction::UnstakeTokens {
+                    staking_pool: stake_account,
+                    amount: *amount,
+                };
+                let options = TransactionOptions {
+                    stake_activation: Some(activation),
+                    ..options.clone()
+                };
+                Ok((Cow::Owned(action), Cow::Owned(options)))
+            }
+            _ => Ok((Cow::Borrowed(action), Cow::Borrowed(options))),
+        }
+    }
+
+    /// Stake SOL with a validator, returning the new stake account
+    ///
+    /// The stake account is derived from the wallet key and the first unused
+    /// seed index, funded with `amount` plus its rent reserve and delegated
+    /// to `validator`'s vote account. The staked amount counts against the
+    /// spending limits like a transfer.
+    pub async fn stake_sol(&self, validator: &Pubkey, amount: Lamports) -> Result<Pubkey> {
+        let index = stake::next_stake_index(self.rpc_client.as_ref(), &self.public_key).await?;
+        let action = AgentAction::StakeTokens {
+            staking_pool: *validator,
+            amount,
+        };
+        let options = TransactionOptions {
+            stake_seed_index: Some(index),
+            ..Default::default()
+        };
+        self.execute_action(&action, &options).await?;
+        stake::stake_account_address(&self.public_key, index)
+    }
+
+    /// Stake an amount given in SOL as a float
+    #[deprecated(note = "use `stake_sol` with `Lamports::from_sol_str`")]
+    pub async fn stake_sol_f64(&self, validator: &Pubkey, amount: f64) -> Result<Pubkey> {
+        self.stake_sol(validator, Lamports::from_sol_f64_rounded(amount)?)
+            .await
+    }
+
+    /// Take the next unstake step for a stake account
+    ///
+    /// Active stake is deactivated; once the cooldown epoch has passed a
+    /// second call withdraws the whole account to the wallet. Calling it
+    /// while the stake is still deactivating fails with
+    /// [`Error::StakeCooldown`] before anything is signed.
+    pub async fn unstake_sol(&self, stake_account: &Pubkey) -> Result<Signature> {
+        let activation = self.rpc_client.get_stake_activation(stake_account).await?;
+        let action = AgentAction::UnstakeTokens {
+            staking_pool: *stake_account,
+            amount: Lamports::new(self.rpc_client.get_balance(stake_account).await?),
+        };
+        let options = TransactionOptions {
+            stake_activation: Some(activation),
+            ..Default::default()
+        };
+        self.execute_action(&action, &options).await
+    }
+
+    /// Make sure the wallet's wrapped SOL account holds at least `lamports`
+    ///
+    /// Only the shortfall from the current wrapped balance is wrapped, and
+    /// `None` is returned when nothing had to be. Wrapped SOL remains the
+    /// wallet's own, so wrapping does not count against the spending limits.
+    pub async fn ensure_wrapped_sol(&self, lamports: u64) -> Result<Option<Signature>> {
+        let current = self.get_token_balance(&token::NATIVE_MINT).await?;
+        let shortfall = token::wrap_shortfall(current, lamports);
+        if shortfall == 0 {
+            return Ok(None);
+        }
+        let available = self.rpc_client.get_balance(&self.public_key).await?;
+        if available < shortfall {
+            return Err(Error::InsufficientFunds {
+                required: shortfall,
+                available,
+            });
+        }
+
+        let instructions = token::wrap_sol_instructions(&self.public_key, shortfall)?;
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&self.public_key));
+        let signature = self.sign_and_send(&mut transaction).await?;
+
+        let wsol_account = spl_associated_token_account::get_associated_token_address(
+            &self.public_key,
+            &token::NATIVE_MINT,
+        );
+        self.token_manager
+            .read()
+            .await
+            .clear_account_cache(&wsol_account)
+            .await;
+        tracing::info!(
+            "Wallet '{}' wrapped {} lamports ({})",
+            self.name,
+            shortfall,
+            signature
+        );
+        Ok(Some(signature))
+    }
+
+    /// Validate an action and itemize its transfer amount, fee and rent
+    ///
+    /// The built transaction must pass the [`TransactionPolicy`]; its
+    /// warnings come back in [`PreparedAction::warnings`]. Fails with
+    /// `InsufficientFunds` if the wallet cannot cover the SOL transferred
+    /// plus the fee and the rent of every account created.
+    pub async fn prepare_action(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<PreparedAction> {
+        let (action, options) = self.resolve_stake(action, options).await?;
+        let (action, options) = (action.as_ref(), options.as_ref());
+        let sol_value = self.preflight(action).await?;
+        let mut transaction = self.build_checked(action, options).await?;
+
+        // Price the built transaction from the network, then build it at that price
+        let estimated;
+        let options = match &options.priority_fee_strategy {
+            PriorityFeeStrategy::Auto(estimator) => {
+                let estimate = estimator
+                    .estimate_transaction(self.rpc_client.as_ref(), &transaction, options)
+                    .await?;
+                estimated = estimate.apply(options);
+                transaction = self.build_checked(action, &estimated).await?;
+                &estimated
+            }
+            PriorityFeeStrategy::Fixed => options,
+        };
+
+        let fee_lamports = self
+            .transaction_builder
+            .lock()
+            .await
+            .estimate_transaction_fee(&transaction, options);
+        if let Some(max_total) = options.max_total_fee_lamports {
+            if fee_lamports > max_total {
+                return Err(Error::LimitExceeded(format!(
+                    "Fee of {} lamports exceeds the {} lamport cap",
+                    fee_lamports, max_total
+                )));
+            }
+        }
+        let rent_costs = self.rent.itemize(&transaction.message).await?;
+        let closed_lamports = match action {
+            AgentAction::CloseTokenAccount { account, .. } => {
+                self.rpc_client.get_balance(account).await?
+            }
+            _ => 0,
+        };
+        // A bundle tip leaves the wallet like a transfer and is held to the same limits
+        let tip_lamports = options.submission.tip_lamports();
+        let spend = Lamports::new(sol_value.as_u64().saturating_add(tip_lamports));
+        let mut prepared = PreparedAction {
+            action: action.clone(),
+            transaction,
+            transfer_lamports: spend.as_u64(),
+            fee_lamports,
+            rent_costs,
+            closed_lamports,
+            warnings: Vec::new(),
+        };
+
+        let balance = self.rpc_client.get_balance(&self.public_key).await?;
+        let reserve = self.reserve_for(prepared.transfer_lamports)?;
+        prepared.warnings = {
+            let agent_context = self.agent_context.read().await;
+            let input = PolicyInput::new(&prepared.transaction, &agent_context)
+                .with_spend(spend)
+                .with_fee(fee_lamports)
+                .with_balance(
+                    balance,
+                    prepared
+                        .total_lamports()
+                        .saturating_sub(fee_lamports)
+                        .saturating_add(tip_lamports),
+                )
+                .with_rent(prepared.rent_lamports())
+                .with_reserve(reserve);
+            self.policy(options).enforce(&input)?
+        };
+        Ok(prepared)
+    }
+
+    /// Reserve a transaction transferring `transfer_lamports` must keep in the wallet
+    ///
+    /// Only transactions sending SOL are held to the reserve; fee and rent
+    /// count against it along with the amount sent.
+    fn reserve_for(&self, transfer_lamports: u64) -> Result<u64> {
+        if transfer_lamports == 0 {
+            return Ok(0);
+        }
+        Ok(BalanceGuard::from_limits(&self.config.agent.limits)?
+            .reserve()
+            .as_u64())
+    }
+
+    /// Prepare an action and hold its spend against the budget
+    ///
+    /// The reservation is released if the returned guard is dropped before
+    /// the transaction is sent, including when the calling future is cancelled.
+    async fn prepare_reserved(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<(PreparedAction, BudgetReservation)> {
+        let prepared = self.prepare_action(action, options).await?;
+        let reservation = {
+            let agent_context = self.agent_context.read().await;
+            self.budget
+                .reserve(&agent_context, Lamports::new(prepared.transfer_lamports))?
+        };
+        Ok((prepared, reservation))
+    }
+
+    /// Lamports `message` transfers out of the wallet that count as spent
+    ///
+    /// SOL wrapped into the wallet's own wrapped SOL account stays the
+    /// wallet's and is not counted.
+    fn sol_spent(&self, message: &Message, rent: &[(Pubkey, u64)]) -> u64 {
+        let wsol_account = spl_associated_token_account::get_associated_token_address(
+            &self.public_key,
+            &token::NATIVE_MINT,
+        );
+        sol_transferred(message, &self.public_key, rent).saturating_sub(sol_transferred_to(
+            message,
+            &self.public_key,
+            &wsol_account,
+        ))
+    }
+
+    /// Check a transaction built outside the wallet and hold what it spends
+    ///
+    /// Every program it invokes must be an allowed protocol or one the
+    /// wallet uses itself. The SOL the transaction transfers out of the
+    /// wallet, including what it funds accounts with beyond their rent,
+    /// counts as its spend, and must be covered by the balance along with the
+    /// fee and the rent of created accounts. Returns the
+    /// reservation and that rent.
+    async fn reserve_external(
+        &self,
+        transaction: &Transaction,
+        options: &TransactionOptions,
+    ) -> Result<(BudgetReservation, Lamports)> {
+        let rent_costs = self.rent.itemize(&transaction.message).await?;
+        let rent: u64 = rent_costs.iter().map(|(_, lamports)| lamports).sum();
+        let transfers = sol_transferred(&transaction.message, &self.public_key, &rent_costs);
+        let spend = self.sol_spent(&transaction.message, &rent_costs);
+        let balance = self.rpc_client.get_balance(&self.public_key).await?;
+        let reserve = self.reserve_for(spend)?;
+        let fee_lamports = self
+            .transaction_builder
+            .lock()
+            .await
+            .estimate_transaction_fee(transaction, options);
+
+        let agent_context = self.agent_context.read().await;
+        protocols::check_programs(&transaction.message, &agent_context.allowed_protocols)?;
+        let input = PolicyInput::new(transaction, &agent_context)
+            .with_spend(Lamports::new(spend))
+            .with_fee(fee_lamports)
+            .with_balance(balance, transfers.saturating_add(rent))
+            .with_rent(rent)
+            .with_reserve(reserve);
+        self.policy(options).enforce(&input)?;
+        let reservation = self.budget.reserve(&agent_context, Lamports::new(spend))?;
+        Ok((reservation, Lamports::new(rent)))
+    }
+
+    /// Build a multisig transaction for an action and sign it with the wallet key
+    ///
+    /// `cosigners` are the co-signers chosen for this transaction; they must
+    /// meet the wallet's threshold. The returned transaction carries the
+    /// wallet's signature and can be passed on with
+    /// [`multisig::encode_transaction`].
+    pub async fn build_partially_signed(
+        &self,
+        action: &AgentAction,
+        cosigners: &[Pubkey],
+        options: &TransactionOptions,
+    ) -> Result<Transaction> {
+        let config = self
+            .multisig
+            .as_ref()
+            .ok_or_else(|| Error::config("Wallet is not a multisig wallet"))?;
+        config.check_cosigners(cosigners)?;
+
+        let (action, options) = self.resolve_stake(action, options).await?;
+        let action = action.as_ref();
+        let sol_value = self.preflight(action).await?;
+        let options = TransactionOptions {
+            cosigners: cosigners.to_vec(),
+            ..options.into_owned()
+        };
+        let mut transaction = self.build_checked(action, &options).await?;
+        {
+            let fee_lamports = self
+                .transaction_builder
+                .lock()
+                .await
+                .estimate_transaction_fee(&transaction, &options);
+            let agent_context = self.agent_context.read().await;
+            let input = PolicyInput::new(&transaction, &agent_context)
+                .with_spend(sol_value)
+                .with_fee(fee_lamports);
+            self.policy(&options).enforce(&input)?;
+        }
+        transaction.message.recent_blockhash =
+            nonce::blockhash_for(&transaction.message, self.rpc_client.as_ref()).await?;
+
+        signer::sign_transaction(&mut transaction, self.signer.as_ref()).await?;
+        Ok(transaction)
+    }
+
+    /// Check amount and balance for an action and estimate its value in lamports
+    async fn preflight(&self, action: &AgentAction) -> Result<Lamports> {
+        let sol_value = match action {
+            AgentAction::TransferSol { amount, .. } => {
+                if *amount == Lamports::ZERO {
+                    return Err(Error::InvalidAmount(
+                        "Transfer amount must be greater than zero".to_string(),
+                    ));
+                }
+
+                // Check balance
+                let balance_lamports = self.rpc_client.get_balance(&self.public_key).await?;
+
+                if amount.as_u64() > balance_lamports {
+                    return Err(Error::InsufficientFunds {
+                        required: amount.as_u64(),
+                        available: balance_lamports,
+                    });
+                }
+
+                *amount
+            }
+            AgentAction::TransferToken { mint, amount, .. } => {
+                if *amount == 0 {
+                    return Err(Error::InvalidAmount(
+                        "Transfer amount must be greater than zero".to_string(),
+                    ));
+                }
+
+                // Check token balance
+                let balance = self.get_token_balance(mint).await?;
+                if *amount > balance {
+                    return Err(Error::InsufficientFunds {
+                        required: *amount,
+                        available: balance,
+                    });
+                }
+
+                self.token_budget_value(mint, *amount).await?
+            }
+            AgentAction::BatchTransfer {
+                transfers, mint, ..
+            } => {
+                if transfers.is_empty() || transfers.iter().any(|(_, amount)| *amount == 0) {
+                    return Err(Error::InvalidAmount(
+                        "Every batch transfer amount must be greater than zero".to_string(),
+                    ));
+                }
+
+                let total = AgentAction::batch_total(transfers)?;
+                let available = match mint {
+                    Some(mint) => self.get_token_balance(mint).await?,
+                    None => self.rpc_client.get_balance(&self.public_key).await?,
+                };
+                if total > available {
+                    return Err(Error::InsufficientFunds {
+                        required: total,
+                        available,
+                    });
+                }
+
+                match mint {
+                    Some(mint) => self.token_budget_value(mint, total).await?,
+                    None => Lamports::new(total),
+                }
+            }
+            AgentAction::StakeTokens {
+                staking_pool,
+                amount,
+            } => {
+                if *amount == Lamports::ZERO {
+                    return Err(Error::InvalidAmount(
+                        "Stake amount must be greater than zero".to_string(),
+                    ));
+                }
+                let vote_account = self.rpc_client.get_account(staking_pool).await?;
+                if vote_account.owner != solana_sdk::vote::program::id() {
+                    return Err(Error::validation(format!(
+                        "{} is not a validator vote account",
+                        staking_pool
+                    )));
+                }
+
+                // The stake account's rent reserve is itemized with the fee
+                let balance = self.rpc_client.get_balance(&self.public_key).await?;
+                if amount.as_u64() > balance {
+                    return Err(Error::InsufficientFunds {
+                        required: amount.as_u64(),
+                        available: balance,
+                    });
+                }
+
+                // Staked SOL counts against the budget like a transfer
+                *amount
+            }
+            // Unstaking returns SOL to the wallet and spends nothing
+            AgentAction::UnstakeTokens { .. } => Lamports::ZERO,
+            // Rent of the new account is spent; an existing account costs nothing
+            AgentAction::CreateTokenAccount { mint } => {
+                let account = spl_associated_token_account::get_associated_token_address(
+                    &self.public_key,
+                    mint,
+                );
+                match self.rpc_client.get_account_optional(&account).await? {
+                    Some(_) => Lamports::ZERO,
+                    None => Lamports::new(
+                        self.rent
+                            .minimum_balance(spl_token::state::Account::LEN)
+                            .await?,
+                    ),
+                }
+            }
+            AgentAction::CloseTokenAccount { account, .. } => {
+                self.check_closable(account).await?;
+                Lamports::ZERO
+            }
+            // New supply costs nothing, but only the mint authority may create it
+            AgentAction::MintToken { mint, amount, .. } => {
+                if *amount == 0 {
+                    return Err(Error::InvalidAmount(
+                        "Mint amount must be greater than zero".to_string(),
+                    ));
+                }
+                let info = self.spl_mint_info(mint).await?;
+                token::check_mint_authority(&info, &self.public_key)?;
+                Lamports::ZERO
+            }
+            // Burned tokens leave the wallet like a transfer
+            AgentAction::BurnToken { mint, amount } => {
+                if *amount == 0 {
+                    return Err(Error::InvalidAmount(
+                        "Burn amount must be greater than zero".to_string(),
+                    ));
+                }
+                let balance = self.get_token_balance(mint).await?;
+                if *amount > balance {
+                    return Err(Error::InsufficientFunds {
+                        required: *amount,
+                        available: balance,
+                    });
+                }
+                self.spl_mint_info(mint).await?;
+                self.token_budget_value(mint, *amount).await?
+            }
+            other => {
+                return Err(Error::NotSupported(format!(
+                    "Wallet cannot execute action: {}",
+                    other.description()
+                )))
+            }
+        };
+        Ok(sol_value)
+    }
+
+    /// Check that a token account belongs to the wallet and holds no tokens
+    ///
+    /// Closing fails on chain for a non-empty account, except wrapped SOL
+    /// whose balance is returned with the rent.
+    async fn check_closable(&self, account: &Pubkey) -> Result<()> {
+        let token_manager = self.token_manager.read().await;
+        token_manager.clear_account_cache(account).await;
+        let info = token_manager.get_token_account_info(account).await?;
+        if info.owner != self.public_key {
+            return Err(Error::permission_denied(format!(
+                "Token account {} is owned by {}, not the wallet",
+                account, info.owner
+            )));
+        }
+        if info.balance > 0 && !info.is_native {
+            return Err(Error::validation(format!(
+                "Token account {} still holds {} of token {}",
+                account, info.balance, info.mint
+            )));
+        }
+        Ok(())
+    }
+
+    /// Read a mint fresh and record its decimals for checked instructions
+    ///
+    /// Minting and burning through an action are built for SPL Token mints;
+    /// Token-2022 mints go through [`TokenManager`] directly.
+    async fn spl_mint_info(&self, mint: &Pubkey) -> Result<token::TokenInfo> {
+        let info = {
+            let token_manager = self.token_manager.read().await;
+            token_manager.clear_token_cache(mint).await;
+            token_manager.get_token_info(mint).await?
+        };
+        if info.program_id != token::TOKEN_PROGRAM_ID {
+            return Err(Error::NotSupported(format!(
+                "Mint {} belongs to program {}, not SPL Token",
+                mint, info.program_id
+            )));
+        }
+        self.agent_context
+            .write()
+            .await
+            .token_decimals
+            .insert(*mint, info.decimals);
+        Ok(info)
+    }
+
+    /// Check a token amount against the spending limits and value it for the SOL budget
+    ///
+    /// Looks up the mint's decimals first when an unlisted mint is valued
+    /// through the price feeds.
+    async fn token_budget_value(&self, mint: &Pubkey, amount: u64) -> Result<Lamports> {
+        let needs_decimals = {
+            let agent_context = self.agent_context.read().await;
+            let limits = &agent_context.spending_limits;
+            limits.unlisted_tokens == UnlistedTokenPolicy::ConvertToSol
+                && !limits.token_limits.contains_key(mint)
+                && !agent_context.token_decimals.contains_key(mint)
+        };
+        if needs_decimals {
+            let decimals = self
+                .token_manager
+                .read()
+                .await
+                .get_token_info(mint)
+                .await?
+                .decimals;
+            self.agent_context
+                .write()
+                .await
+                .token_decimals
+                .insert(*mint, decimals);
+        }
+        self.agent_context
+            .read()
+            .await
+            .is_token_action_allowed(mint, amount)
+    }
+
+    /// Pre-build and validate transactions for templated actions
+    ///
+    /// Replaces any previously prepared templates. Call again whenever the
+    /// agent is reconfigured; templates prepared for a different permission
+    /// level or fee settings are ignored and the full build path is used.
+    pub async fn prepare_templates(
+        &self,
+        templates: Vec<ActionTemplate>,
+        options: &TransactionOptions,
+    ) -> Result<usize> {
+        let agent_context = self.agent_context.read().await;
+        let mut transaction_builder = self.transaction_builder.lock().await;
+        let prepared =
+            TemplateSet::prepare(&mut transaction_builder, templates, &agent_context, options)?;
+        let count = prepared.len();
+        *self.templates.write().await = prepared;
+        Ok(count)
+    }
+
+    /// Check an action's declared recipients and build its unsigned transaction
+    ///
+    /// The transaction itself still has to pass the [`TransactionPolicy`].
+    async fn build_checked(
+        &self,
+        action: &AgentAction,
+        options: &TransactionOptions,
+    ) -> Result<Transaction> {
+        // Rent of a closed account sent anywhere but the wallet is a transfer
+        let declared = match action {
+            AgentAction::TransferSol { to, .. }
+            | AgentAction::TransferToken { to, .. }
+            | AgentAction::MintToken { to, .. } => Some(to),
+            AgentAction::CloseTokenAccount { destination, .. }
+                if *destination != self.public_key =>
+            {
+                Some(destination)
+            }
+            _ => None,
+        };
+
+        // Enforce the address book whitelist, failing closed if it was tampered with
+        if let Some(book) = self.recipient_whitelist.read().await.as_ref() {
+            if let Some(to) = declared {
+                book.check_recipient(to)?;
+            }
+            for (to, _) in batch_legs(action) {
+                book.check_recipient(to)?;
+            }
+        }
+
+        // Declared destinations; the accounts actually written are checked by the policy
+        let agent_context = self.agent_context.read().await;
+        if let Some(to) = declared {
+            agent_context.is_destination_allowed(to)?;
+        }
+        for (to, _) in batch_legs(action) {
+            agent_context.is_destination_allowed(to)?;
+        }
+
+        // Build transaction, patching a prepared template when one matches
+        let mut transaction_builder = self.transaction_builder.lock().await;
+        let templates = self.templates.read().await;
+        match templates.find(action, &agent_context, options) {
+            Some(prepared) => prepared.instantiate(
+                &transaction_builder,
+                action,
+                &agent_context,
+                Default::default(),
+            ),
+            None => transaction_builder.build_from_action(action, &agent_context, options),
+        }
+    }
+
+    /// Wait for a sent transaction as requested and record the outcome
+    ///
+    /// Success is only recorded, and the reservation committed, once the
+    /// transaction reached the requested commitment. A timed-out transaction
+    /// may still land, so its reservation stays held until reconciled; a
+    /// failed one is released. Rent of created accounts is recorded as
+    /// reclaimable rather than deducted.
+    async fn confirm_and_record(
+        &self,
+        signature: &Signature,
+        options: &TransactionOptions,
+        rent: Lamports,
+        reservation: Option<BudgetReservation>,
+    ) -> Result<()> {
+        let outcome = match (options.confirmation.requirement(), &options.shutdown) {
+            (Some((commitment, timeout)), None) => {
+                self.wait_for_confirmation(signature, commitment, timeout)
+                    .await
+            }
+            // Cut short by shutdown, the transaction may still land
+            (Some((commitment, timeout)), Some(shutdown)) => shutdown
+                .drain(self.wait_for_confirmation(signature, commitment, timeout))
+                .await
+                .unwrap_or(Err(Error::ConfirmationTimeout {
+                    signature: *signature,
+                    last_status: None,
+                })),
+            (None, _) => Ok(()),
+        };
+        let recorded = self
+            .record_outcome(signature, outcome, rent, reservation)
+            .await;
+        self.persist_key_usage().await;
+        recorded
+    }
+
+    /// Deduct the token an action spent from its per-token limit
+    ///
+    /// Like the SOL budget, a transaction that timed out may still have
+    /// landed and counts as spent.
+    async fn deduct_token_spend(&self, action: &AgentAction, outcome: &Result<()>) {
+        let Some((mint, amount)) = action.token_spend() else {
+            return;
+        };
+        if matches!(outcome, Ok(()) | Err(Error::ConfirmationTimeout { .. })) {
+            self.agent_context
+                .write()
+                .await
+                .deduct_token_from_budget(&mint, amount);
+        }
+    }
+
+    /// Credit the rent of a closed token account back to the budget
+    ///
+    /// Only lamports returned to the wallet itself are credited, and only
+    /// once the close succeeded.
+    async fn credit_closed_account(&self, prepared: &PreparedAction, outcome: &Result<()>) {
+        let AgentAction::CloseTokenAccount { destination, .. } = &prepared.action else {
+            return;
+        };
+        if outcome.is_err() || prepared.closed_lamports == 0 {
+            return;
+        }
+        let refund = Lamports::new(prepared.closed_lamports);
+        let mut agent_context = self.agent_context.write().await;
+        agent_context.release_rent(refund);
+        if *destination == self.public_key {
+            agent_context.credit_budget(refund);
+        }
+    }
+
+    /// Write the signatures made with the wallet key to its file
+    ///
+    /// Best effort: a failed write is logged and retried after the next
+    /// transaction.
+    async fn persist_key_usage(&self) {
+        let Some(usage) = &self.key_usage else {
+            return;
+        };
+        let mut metadata = self.metadata.write().await;
+        let Some(record) = metadata.key.as_mut() else {
+            return;
+        };
+        if record.signatures == usage.signatures() {
+            return;
+        }
+        record.signatures = usage.signatures();
+        record.last_used = usage.last_used();
+        if let Err(e) = self
+            .storage_service
+            .write()
+            .await
+            .update_metadata(&self.name, &metadata)
+        {
+            tracing::debug!(
+                "Could not record key usage of wallet '{}': {}",
+                self.name,
+                e
+            );
+        }
+    }
+
+    /// Record how a sent transaction ended and settle its reservation
+    async fn record_outcome(
+        &self,
+        signature: &Signature,
+        outcome: Result<()>,
+        rent: Lamports,
+        reservation: Option<BudgetReservation>,
+    ) -> Result<()> {
+        let mut agent_context = self.agent_context.write().await;
+        match outcome {
+            Ok(()) => {
+                if let Some(reservation) = reservation {
+                    agent_context.deduct_from_budget(reservation.commit()?);
+                }
+                agent_context.record_rent(rent);
+                agent_context.record_success();
+                Ok(())
+            }
+            Err(e) => {
+                let recorded = match &e {
+                    Error::ConfirmationTimeout {
+                        signature,
+                        last_status,
+                    } => {
+                        // Dropping the sent reservation keeps it held
+                        drop(reservation);
+                        agent_context.record_rent(rent);
+                        Error::ConfirmationTimeout {
+                            signature: *signature,
+                            last_status: last_status.clone(),
+                        }
+                    }
+                    other => {
+                        if let Some(reservation) = reservation {
+                            reservation.release()?;
+                        }
+                        Error::transaction(other.to_string())
+                    }
+                };
+                agent_context.record_failure(recorded, format!("Confirming {}", signature));
+                Err(e)
+            }
+        }
+    }
+
+    /// Write a record to the audit sink, if one is configured
+    async fn audit(&self, entry: &AuditEntry) -> Result<()> {
+        match self.audit_sink.read().await.as_ref() {
+            Some(sink) => sink.record(entry),
+            None => Ok(()),
+        }
+    }
+
+    /// Record how an audited action ended
+    ///
+    /// The transaction was already broadcast, so a failing sink is logged
+    /// rather than failing the action.
+    async fn audit_outcome(
+        &self,
+        intent: &AuditEntry,
+        outcome: AuditOutcome,
+        error: Option<&Error>,
+    ) {
+        let mut entry = intent.outcome(outcome);
+        if let Some(error) = error {
+            entry = entry.with_error(error);
+        }
+        if let Err(e) = self.audit(&entry).await {
+            tracing::warn!("Failed to audit outcome of action {}: {}", intent.id, e);
+        }
+    }
+
+    /// Send the audit trail of executed actions to `sink`
+    ///
+    /// Pass `None` to stop auditing.
+    pub async fn set_audit_sink(&self, sink: Option<Arc<dyn AuditSink>>) {
+        *self.audit_sink.write().await = sink;
+    }
+
+    /// Sink receiving the audit trail, if any
+    pub async fn audit_sink(&self) -> Option<Arc<dyn AuditSink>> {
+        self.audit_sink.read().await.clone()
+    }
+
+    /// Restrict transfer recipients to the whitelisted contacts of an address book
+    ///
+    /// Pass `None` to lift the restriction.
+    pub async fn set_recipient_whitelist(&self, address_book: Option<AddressBook>) {
+        *self.recipient_whitelist.write().await = address_book;
+    }
+
+    /// Sign a transaction (does not send it)
+    ///
+    /// An unsigned transaction gets a fresh blockhash first. A transaction
+    /// another signer already signed keeps its blockhash so their signature
+    /// stays valid. Transactions requiring other signers come back partially
+    /// signed; [`ValidationResult::missing_signatures`] tells how many remain.
+    pub async fn sign_transaction(&self, transaction: &mut Transaction) -> Result<Signature> {
+        if !multisig::has_signatures(transaction) {
+            // Get recent blockhash, or the stored nonce for nonce transactions
+            let mut message = transaction.message.clone();
+            message.recent_blockhash =
+                nonce::blockhash_for(&message, self.rpc_client.as_ref()).await?;
+            *transaction = Transaction::new_unsigned(message);
+        }
+
+        signer::sign_transaction(transaction, self.signer.as_ref()).await
+    }
+
+    /// Sign a transaction built on another machine
+    ///
+    /// Makes no RPC call, so it works on a wallet opened with
+    /// [`Wallet::load_offline`]. The transaction must pass the wallet's
+    /// transaction policy, address policy included, before the wallet key
+    /// signs it; other required signatures are left to their own signers.
+    pub async fn sign_offline(&self, envelope: &UnsignedEnvelope) -> Result<SignedEnvelope> {
+        let mut transaction = envelope.transaction()?;
+        {
+            let agent_context = self.agent_context.read().await;
+            let input = PolicyInput::new(&transaction, &agent_context);
+            self.policy(&TransactionOptions::default())
+                .enforce(&input)?;
+        }
+
+        let signature = signer::sign_transaction(&mut transaction, self.signer.as_ref()).await?;
+        self.persist_key_usage().await;
+        tracing::info!("Wallet '{}' signed {} offline", self.name, signature);
+        SignedEnvelope::new(&transaction, envelope.expiry_slot)
+    }
+
+    /// Insert a co-signer's signature into a partially signed transaction
+    ///
+    /// The signature is verified against the transaction before it is added.
+    pub fn add_signature(
+        transaction: &mut Transaction,
+        signature: Signature,
+        pubkey: &Pubkey,
+    ) -> Result<()> {
+        multisig::add_signature(transaction, signature, pubkey)
+    }
+
+    /// Create a durable nonce account with the wallet as its authority
+    ///
+    /// `rent_payer` funds the rent-exempt balance and the fee; the wallet
+    /// pays when it is `None`. Pass the returned [`NonceInfo`] in
+    /// [`TransactionOptions::nonce`] to build transactions that do not expire.
+    pub async fn create_nonce_account(
+        &self,
+        rent_payer: Option<&dyn DynTransactionSigner>,
+    ) -> Result<NonceInfo> {
+        let payer = match rent_payer {
+            Some(payer) => payer,
+            None => self.signer.as_ref(),
+        };
+        let payer_pubkey = payer.pubkey().await;
+        let nonce_keypair = SecureKeypair::generate();
+        let nonce = NonceInfo::new(nonce_keypair.public_key(), self.public_key);
+
+        let lamports = self
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(solana_sdk::nonce::state::State::size())
+            .await?;
+        let instructions = solana_sdk::system_instruction::create_nonce_account(
+            &payer_pubkey,
+            &nonce.account,
+            &nonce.authority,
+            lamports,
+        );
+        let message = solana_sdk::message::Message::new_with_blockhash(
+            &instructions,
+            Some(&payer_pubkey),
+            &self.rpc_client.get_latest_blockhash().await?,
+        );
+        let mut transaction = Transaction::new_unsigned(message);
+        signer::sign_transaction(&mut transaction, payer).await?;
+        signer::sign_transaction(&mut transaction, &nonce_keypair).await?;
+
+        // Creating the account transfers nothing, so no spend is reserved
+        self.ensure_live("Creating nonce accounts")?;
+        self.check_fully_signed(&transaction)?;
+        let signature = transaction.signatures[0];
+        self.rpc_client.send_transaction(&transaction).await?;
+        self.confirm_and_record(
+            &signature,
+            &TransactionOptions::default(),
+            Lamports::ZERO,
+            None,
+        )
+        .await?;
+        tracing::info!(
+            "Wallet '{}' created nonce account {} ({})",
+            self.name,
+            nonce.account,
+            signature
+        );
+        Ok(nonce)
+    }
+
+    /// Send a transaction that already carries every required signature
+    ///
+    /// The SOL it transfers out of the wallet must pass the transaction
+    /// policy and is held against the budget until the outcome is recorded.
+    pub async fn send_signed_transaction(
+        &self,
+        transaction: &Transaction,
+        options: &TransactionOptions,
+    ) -> Result<Signature> {
+        self.ensure_live("Sending signed transactions")?;
+        self.check_fully_signed(transaction)?;
+        let signature = transaction.signatures[0];
+        let (mut reservation, rent) = self.reserve_external(transaction, options).await?;
+
+        reservation.mark_sent(&signature)?;
+        if let Err(e) = self.rpc_client.send_transaction(transaction).await {
+            reservation.release()?;
+            return Err(e);
+        }
+        self.confirm_and_record(&signature, options, rent, Some(reservation))
+            .await?;
+
+        Ok(signature)
+    }
+
+    /// Make sure a transaction is completely signed and meets the multisig threshold
+    fn check_fully_signed(&self, transaction: &Transaction) -> Result<()> {
+        let missing = multisig::missing_signers(transaction);
+        if !missing.is_empty() {
+            return Err(Error::transaction(format!(
+                "Transaction is missing signatures from {:?}",
+                missing
+            )));
+        }
+
+        if let Some(config) = &self.multisig {
+            let required = transaction.message.header.num_required_signatures as usize;
+            let cosigners: Vec<Pubkey> = transaction.message.account_keys[..required]
+                .iter()
+                .filter(|key| config.cosigners.contains(key))
+                .copied()
+                .collect();
+            config.check_cosigners(&cosigners)?;
+        }
+
+        transaction
+            .verify()
+            .map_err(|e| Error::transaction(format!("Invalid transaction signatures: {}", e)))
+    }
+
+    /// Sign and send a transaction
+    ///
+    /// See [`Wallet::sign_and_send_with_options`].
+    pub async fn sign_and_send(&self, transaction: &mut Transaction) -> Result<Signature> {
+        self.sign_and_send_with_options(transaction, &TransactionOptions::default())
+            .await
+    }
+
+    /// Sign and send a transaction, waiting for confirmation as requested
+    ///
+    /// Like [`Wallet::send_signed_transaction`], the transaction must pass
+    /// the transaction policy, and the SOL it transfers out of the wallet is
+    /// held against the budget until the outcome is recorded.
+    pub async fn sign_and_send_with_options(
+        &self,
+        transaction: &mut Transaction,
+        options: &TransactionOptions,
+    ) -> Result<Signature> {
+        self.ensure_live("Signing and sending transactions")?;
+        let (mut reservation, rent) = self.reserve_external(transaction, options).await?;
+        // Co-signed transactions cannot be signed again by the wallet alone
+        let resignable = !multisig::has_signatures(transaction);
+        let mut signature = self.sign_transaction(transaction).await?;
+        self.check_fully_signed(transaction)?;
+        reservation.mark_sent(&signature)?;
+
+        // Send transaction, re-signing it if its blockhash expired
+        let mut retries = 0;
+        while let Err(e) = self.rpc_client.send_transaction(transaction).await {
+            if !resignable || !Self::can_resign(&e, transaction) || retries >= options.max_retries {
+                reservation.release()?;
+                return Err(e);
+            }
+            retries += 1;
+            tracing::warn!(
+                "Blockhash of {} expired before it landed; re-signing (retry {} of {})",
+                signature,
+                retries,
+                options.max_retries
+            );
+            transaction.signatures.clear();
+            signature = match self.sign_transaction(transaction).await {
+                Ok(signature) => signature,
+                Err(e) => {
+                    reservation.release()?;
+                    return Err(e);
+                }
+            };
+            reservation.mark_sent(&signature)?;
+        }
+
+        // Update agent context once the transaction has landed
+        self.confirm_and_record(&signature, options, rent, Some(reservation))
+            .await?;
+
+        Ok(signature)
+    }
+
+    /// Sign a transaction whose fee `fee_payer` sponsors and send it
+    ///
+    /// The message must name the sponsor as its fee payer, e.g. built with
+    /// [`FeePayerService::sponsor`](crate::fee_payer::FeePayerService::sponsor)
+    /// options, so its signature comes first. The sponsor only pays the fee:
+    /// a message whose instructions use the sponsor's account is refused, so
+    /// its signature cannot authorize a transfer of its own funds. The
+    /// transaction gets a fresh blockhash, must pass the wallet's transaction
+    /// policy, budget and balance checks like
+    /// [`Wallet::send_signed_transaction`], and is signed by the sponsor and
+    /// the wallet. Returns the transaction id, the sponsor's signature.
+    pub async fn sign_and_send_with_fee_payer(
+        &self,
+        transaction: &mut Transaction,
+        fee_payer: &dyn DynTransactionSigner,
+    ) -> Result<Signature> {
+        self.ensure_live("Sending sponsored transactions")?;
+        let sponsor = fee_payer.pubkey().await;
+        if transaction.message.account_keys.first() != Some(&sponsor) {
+            return Err(Error::validation(format!(
+                "Fee payer of the transaction is not the sponsor {}",
+                sponsor
+            )));
+        }
+        // The fee payer is the first account key
+        let message = &transaction.message;
+        let uses_sponsor = message.instructions.iter().any(|instruction| {
+            instruction.program_id_index == 0 || instruction.accounts.contains(&0)
+        });
+        if uses_sponsor {
+            return Err(Error::permission_denied(format!(
+                "Sponsor {} may only pay the fee, not take part in the instructions",
+                sponsor
+            )));
+        }
+
+        let options = TransactionOptions::default();
+        let mut message = transaction.message.clone();
+        message.recent_blockhash = nonce::blockhash_for(&message, self.rpc_client.as_ref()).await?;
+        *transaction = Transaction::new_unsigned(message);
+        let (mut reservation, rent) = self.reserve_external(transaction, &options).await?;
+        let signature = signer::sign_transaction(transaction, fee_payer).await?;
+        signer::sign_transaction(transaction, self.signer.as_ref()).await?;
+        self.check_fully_signed(transaction)?;
+
+        reservation.mark_sent(&signature)?;
+        if let Err(e) = self.rpc_client.send_transaction(transaction).await {
+            reservation.release()?;
+            return Err(e);
+        }
+        self.confirm_and_record(&signature, &options, rent, Some(reservation))
+            .await?;
+        tracing::info!(
+            "Wallet '{}' sent {} with fees paid by {}",
+            self.name,
+            signature,
+            sponsor
+        );
+        Ok(signature)
+    }
+
+    /// Wait until a signature reaches the given commitment
+    ///
+    /// Uses the websocket subscription when one is configured, after one
+    /// status check for signatures that confirmed before the subscription,
+    /// and falls back to polling `getSignatureStatuses` for the remaining
+    /// time if the subscription is unavailable or does not report in time.
+    pub async fn wait_for_confirmation(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+        timeout: Duration,
+    ) -> Result<()> {
+        let started = std::time::Instant::now();
+
+        if let Some(subscriptions) = &self.subscriptions {
+            // A signature confirmed before we subscribe is never notified
+            match poll_for_confirmation(
+                self.rpc_client.as_ref(),
+                signature,
+                commitment,
+                Duration::ZERO,
+                CONFIRMATION_POLL_INTERVAL,
+            )
+            .await
+            {
+                Err(Error::ConfirmationTimeout { .. }) => {}
+                settled => return settled,
+            }
+            if let Ok(receiver) =
+                subscriptions.subscribe_signature_with_commitment(*signature, commitment)
+            {
+                if let Ok(Ok(result)) = tokio::time::timeout(timeout, receiver).await {
+                    return result.map_err(|err| {
+                        Error::transaction(format!("Transaction {} failed: {}", signature, err))
+                    });
+                }
+            }
+        }
+
+        // The websocket may have missed a confirmation that landed before we
+        // subscribed, so always finish with at least one status poll
+        let remaining = timeout.saturating_sub(started.elapsed());
+        poll_for_confirmation(
+            self.rpc_client.as_ref(),
+            signature,
+            commitment,
+            remaining,
+            CONFIRMATION_POLL_INTERVAL,
+        )
+        .await
+    }
+
+    /// Settle reservations left unsettled from the idempotency journal
+    ///
+    /// Runs on load. Committed spends are deducted from the agent's budget.
+    pub async fn reconcile_budget(&self) -> Result<Reconciliation> {
+        let mut agent_context = self.agent_context.write().await;
+        let settled = self.budget.reconcile()?;
+        agent_context.deduct_from_budget(settled.committed_lamports());
+        Ok(settled)
+    }
+
+    /// Budget held by actions in flight
+    pub fn budget(&self) -> Arc<BudgetLedger> {
+        self.budget.clone()
+    }
+
+    /// Get websocket subscriptions, if enabled
+    pub fn subscriptions(&self) -> Option<Arc<SubscriptionClient>> {
+        self.subscriptions.clone()
+    }
+
+    /// Keep the context's SOL balance current from account notifications
+    ///
+    /// Returns `None` when no websocket is configured. The task runs until
+    /// the handle is aborted or the subscription closes.
+    pub fn watch_balance(&self) -> Result<Option<tokio::task::JoinHandle<()>>> {
+        let Some(subscriptions) = &self.subscriptions else {
+            return Ok(None);
+        };
+        let mut accounts = subscriptions.subscribe_account(self.public_key)?;
+        let agent_context = self.agent_context.clone();
+        Ok(Some(tokio::spawn(async move {
+            while let Some(account) = futures::StreamExt::next(&mut accounts).await {
+                let mut agent_context = agent_context.write().await;
+                agent_context.wallet_balance = Lamports::new(account.lamports).to_sol_f64();
+                agent_context.freshness.balance = Some(Utc::now());
+            }
+        })))
+    }
+
+    /// Simulate a transaction
+    pub async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<SimulationResult> {
+        let transaction_builder = self.transaction_builder.lock().await;
+        let options = TransactionOptions::default();
+
+        transaction_builder
+            .simulate_transaction(transaction, self.rpc_client.as_ref(), &options)
+            .await
+    }
+
+    /// Simulate a transaction and validate it against the transaction policy
+    ///
+    /// The transaction may be unsigned. A failing simulation is reported,
+    /// not returned as an error.
+    pub async fn simulation_report(&self, transaction: &Transaction) -> Result<SimulationReport> {
+        let simulation = self.simulate_transaction(transaction).await?;
+        let validation = self.validate_transaction(transaction).await?;
+        Ok(SimulationReport::new(simulation, validation))
+    }
+
+    /// Simulate an action and preview the balance changes it would cause
+    ///
+    /// Builds the action's transaction like a send would, without signing,
+    /// sending or reserving budget. Fails with `Error::TransactionSimulation`
+    /// when the transaction would fail. The preview carries the warnings of
+    /// the transaction policy.
+    pub async fn preview_action(&self, action: &AgentAction) -> Result<TransferPreview> {
+        let prepared = self
+            .prepare_action(action, &TransactionOptions::default())
+            .await?;
+        let preview = preview::simulate(
+            self.rpc_client.as_ref(),
+            &self.public_key,
+            &prepared.action,
+            &prepared.transaction,
+            prepared.fee_lamports,
+        )
+        .await?;
+        Ok(TransferPreview {
+            warnings: prepared.warnings,
+            ..preview
+        })
+    }
+
+    /// Validate a transaction, checking the wallet can pay for it
+    ///
+    /// The balance must cover the SOL the transaction transfers out of the
+    /// wallet, its fee unless a sponsor pays it and the rent of every account
+    /// it creates, keeping the configured reserve when SOL is sent. The
+    /// result itemizes that requirement in
+    /// [`ValidationResult::required_breakdown`].
+    pub async fn validate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<ValidationResult> {
+        let rent_costs = self.rent.itemize(&transaction.message).await?;
+        let rent: u64 = rent_costs.iter().map(|(_, lamports)| lamports).sum();
+        let transfers = sol_transferred(&transaction.message, &self.public_key, &rent_costs);
+        let spend = self.sol_spent(&transaction.message, &rent_costs);
+        let balance = self.rpc_client.get_balance(&self.public_key).await?;
+        let reserve = self.reserve_for(spend)?;
+
+        let transaction_builder = self.transaction_builder.lock().await;
+        let agent_context = self.agent_context.read().await;
+        let options = TransactionOptions::default();
+        let input = PolicyInput::new(transaction, &agent_context)
+            .with_spend(Lamports::new(spend))
+            .with_balance(balance, transfers.saturating_add(rent))
+            .with_rent(rent)
+            .with_reserve(reserve);
+        Ok(transaction_builder.validate_input(input, &options, &self.policy(&options)))
+    }
+
+    /// Transaction policy for `options` with this wallet's configuration
+    ///
+    /// Who may have a sponsor pay the fee is set by `agent.fee_payer_permission`.
+    fn policy(&self, options: &TransactionOptions) -> TransactionPolicy {
+        TransactionPolicy::from_options(options)
+            .with_fee_payer_permission(self.config.agent.fee_payer_permission)
+    }
+
+    /// Get wallet information
+    ///
+    /// Counts up to [`INFO_TRANSACTION_COUNT_CAP`] transactions from the
+    /// wallet's signature history, flagging the count as truncated when the
+    /// cap is reached, and summarizes the token accounts found by discovery.
+    pub async fn get_info(&self) -> Result<WalletInfo> {
+        let balance_lamports = self.rpc_client.get_balance(&self.public_key).await?;
+        let signatures = self
+            .rpc_client
+            .get_signatures_for_address(&self.public_key, INFO_TRANSACTION_COUNT_CAP)
+            .await?;
+        let token_accounts = self.token_balance_summaries().await?;
+        let permission_level = self.agent_context.read().await.permission_level;
+
+        let metadata = self.metadata.read().await;
+        Ok(WalletInfo {
+            name: metadata.name.clone(),
+            public_key: metadata.public_key,
+            created_at: metadata.created_at,
+            last_accessed: metadata.last_accessed,
+            balance_lamports,
+            transaction_count: signatures.len() as u64,
+            transaction_count_truncated: signatures.len() >= INFO_TRANSACTION_COUNT_CAP,
+            permission_level,
+            is_active: self.is_loaded,
+            token_accounts,
+        })
+    }
+
+    /// Balances of the wallet's token accounts, in whole tokens
+    ///
+    /// Accounts of a mint that cannot be read are listed without an amount.
+    async fn token_balance_summaries(&self) -> Result<Vec<TokenBalanceSummary>> {
+        let token_manager = self.token_manager.read().await;
+        let accounts = token_manager
+            .get_wallet_token_accounts(&self.public_key)
+            .await?;
+
+        let mut summaries = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            let summary = match token_manager.get_token_info(&account.mint).await {
+                Ok(info) => TokenBalanceSummary {
+                    mint: account.mint,
+                    symbol: info
+                        .metadata
+                        .map(|metadata| metadata.symbol.trim().to_string())
+                        .filter(|symbol| !symbol.is_empty()),
+                    ui_amount: Some(account.balance as f64 / 10f64.powi(i32::from(info.decimals))),
+                },
+                Err(e) => {
+                    tracing::debug!("Mint {} unavailable for wallet info: {}", account.mint, e);
+                    TokenBalanceSummary {
+                        mint: account.mint,
+                        symbol: None,
+                        ui_amount: None,
+                    }
+                }
+            };
+            summaries.push(summary);
+        }
+        Ok(summaries)
+    }
+
+    /// Get agent context
+    pub async fn get_agent_context(&self) -> Result<AgentContext> {
+        let agent_context = self.agent_context.read().await;
+        Ok(agent_context.clone())
+    }
+
+    /// Refresh the agent context from the network and return it
+    ///
+    /// Only fields older than their TTL in `agent.context` are fetched, so
+    /// this is cheap enough to call before every decision.
+    pub async fn refresh_agent_context(&self) -> Result<AgentContext> {
+        self.update_agent_context().await?;
+        self.get_agent_context().await
+    }
+
+    /// Quote prices for the configured watchlist from `oracle`
+    ///
+    /// Replaces the context builder, so collected price history restarts.
+    /// Market data providers already set are kept.
+    pub async fn set_price_oracle(&self, oracle: Arc<dyn DynPriceOracle>) {
+        let mut current = self.context_builder.write().await;
+        let settings = &self.config.agent.context;
+        *current = ContextBuilder::from_settings(
+            self.rpc_client.clone(),
+            self.token_manager.clone(),
+            settings,
+        )
+        .with_price_oracle(oracle, settings.watchlist.clone())
+        .with_market_data(current.market_data().to_vec());
+    }
+
+    /// Quote prices for the configured watchlist from `providers` as well
+    ///
+    /// Replaces the context builder, so collected price history restarts.
+    /// The price oracle already set is kept.
+    pub async fn set_market_data_providers(&self, providers: Vec<Arc<dyn DynMarketDataProvider>>) {
+        let mut current = self.context_builder.write().await;
+        let mut builder = ContextBuilder::from_settings(
+            self.rpc_client.clone(),
+            self.token_manager.clone(),
+            &self.config.agent.context,
+        );
+        if let Some(oracle) = current.price_oracle() {
+            builder =
+                builder.with_price_oracle(oracle, self.config.agent.context.watchlist.clone());
+        }
+        *current = builder.with_market_data(providers);
+    }
+
+    /// Update agent context with current wallet state
+    async fn update_agent_context(&self) -> Result<()> {
+        // Fetch without holding the context so sends can record outcomes
+        let previous = self.agent_context.read().await.clone();
+        let update = self.context_builder.read().await.refresh(&previous).await?;
+
+        let mut agent_context = self.agent_context.write().await;
+        update.apply(&mut agent_context);
+
+        // Update timestamp
+        agent_context.update_timestamp();
+
+        // Reset daily budget if needed
+        agent_context.reset_daily_budget_if_needed();
+
+        Ok(())
+    }
+
+    /// List all wallets in storage
+    ///
+    /// With `hydrate`, connects to the configured RPC endpoints and fills in
+    /// every balance with batched `getMultipleAccounts` calls; otherwise
+    /// balances are left at zero and nothing goes over the network.
+    pub async fn list_wallets(config: &WalletConfig, hydrate: bool) -> Result<Vec<WalletInfo>> {
+        if !hydrate {
+            return Self::list_wallets_with(config, &OfflineRpc, false).await;
+        }
+        let shared = SharedComponents::connect(config).await?;
+        Self::list_wallets_with(config, shared.rpc_client().as_ref(), true).await
+    }
+
+    /// List all wallets in storage, looking balances up on `rpc` with `hydrate`
+    pub async fn list_wallets_with(
+        config: &WalletConfig,
+        rpc: &dyn DynRpcProvider,
+        hydrate: bool,
+    ) -> Result<Vec<WalletInfo>> {
+        let mut wallets = open_store(&config.wallet.storage)?.list_wallets()?;
+        for info in &mut wallets {
+            info.permission_level = config.agent.default_permission_level;
+        }
+        if hydrate {
+            hydrate_balances(rpc, &mut wallets).await?;
+        }
+        Ok(wallets)
+    }
+
+    /// Delete a wallet from storage, leaving no copy of its key behind
+    ///
+    /// Refuses with `Error::Wallet` while the wallet still holds SOL or
+    /// tokens, unless `force` is set. The wallet file and every backup of
+    /// it are zeroed before they are removed, and a keychain secret the
+    /// file was bound to is deleted as well.
+    pub async fn delete(
+        name: impl Into<String>,
+        config: &WalletConfig,
+        force: bool,
+    ) -> Result<DeleteReport> {
+        let name = name.into();
+        let shared = match SharedComponents::connect(config).await {
+            Ok(shared) => shared,
+            Err(e) if force => {
+                tracing::warn!(
+                    "Deleting wallet '{}' without a cluster connection: {}",
+                    name,
+                    e
+                );
+                SharedComponents::offline(config)
+            }
+            Err(e) => return Err(e),
+        };
+        Self::delete_with(name, config, &shared, force).await
+    }
+
+    /// Delete a wallet like [`Wallet::delete`], checking funds on `shared`
+    pub async fn delete_with(
+        name: impl Into<String>,
+        config: &WalletConfig,
+        shared: &SharedComponents,
+        force: bool,
+    ) -> Result<DeleteReport> {
+        let name = name.into();
+        let mut store = open_store(&config.wallet.storage)?;
+        let (encrypted_data, metadata) = store.read_wallet(&name)?;
+
+        let final_balance_lamports = match held_funds(shared, &metadata.public_key).await {
+            Ok((lamports, funded_accounts)) => {
+                if (lamports > 0 || funded_accounts > 0) && !force {
+                    return Err(Error::wallet(format!(
+                        "wallet still holds funds: '{}' has {} lamports and {} token accounts \
+                         with a balance; pass force to delete it anyway",
+                        name, lamports, funded_accounts
+                    )));
+                }
+                lamports
+            }
+            Err(e) if force => {
+                tracing::warn!("Deleting wallet '{}' with an unknown balance: {}", name, e);
+                0
+            }
+            Err(e) => return Err(e),
+        };
+
+        let account = keystore_account(&config.wallet.storage, &name);
+        let purged = store.purge_wallet(&name)?;
+        if encrypted_data.is_keystore_bound() {
+            if let Err(e) = shared.keystore().delete_secret(&account) {
+                tracing::warn!("Failed to delete the keychain secret of '{}': {}", name, e);
+            }
+        }
+        tracing::info!(
+            "Wallet '{}' deleted with {} backups",
+            name,
+            purged.backups_removed
+        );
+        Ok(DeleteReport {
+            files_removed: purged.files_removed,
+            backups_removed: purged.backups_removed,
+            final_balance_lamports,
+        })
+    }
+
+    /// Check if wallet exists in storage
+    pub async fn exists(name: impl Into<String>, config: &WalletConfig) -> Result<bool> {
+        let name = name.into();
+        Ok(open_store(&config.wallet.storage)?.wallet_exists(&name))
+    }
+
+    /// Get RPC client for direct access (advanced usage)
+    pub fn rpc_client(&self) -> Arc<dyn DynRpcProvider> {
+        self.rpc_client.clone()
+    }
+
+    /// Get token manager for direct access (advanced usage)
+    pub fn token_manager(&self) -> Arc<RwLock<TokenManager>> {
+        self.token_manager.clone()
+    }
+
+    /// Get transaction builder for direct access (advanced usage)
+    pub fn transaction_builder(&self) -> Arc<Mutex<TransactionBuilder>> {
+        self.transaction_builder.clone()
+    }
+
+    /// Get wallet configuration
+    pub fn config(&self) -> &WalletConfig {
+        &self.config
+    }
+
+    /// Whether the wallet broadcasts actions or only rehearses them
+    pub fn run_mode(&self) -> RunMode {
+        self.config.run_mode()
+    }
+
+    /// Check if wallet is loaded
+    pub fn is_loaded(&self) -> bool {
+        self.is_loaded
+    }
+}
+
+impl Drop for Wallet {
+    fn drop(&mut self) {
+        if self.is_loaded {
+            tracing::debug!("Wallet '{}' dropped", self.name);
+        }
+    }
+}
+
+// Implement Signer trait for compatibility with Solana ecosystem
+impl Signer for Wallet {
+    fn try_pubkey(&self) -> std::result::Result<Pubkey, solana_sdk::signer::SignerError> {
+        Ok(self.public_key())
+    }
+
+    fn try_sign_message(
+        &self,
+        message: &[u8],
+    ) -> std::result::Result<Signature, solana_sdk::signer::SignerError> {
+        futures::executor::block_on(self.signer.sign_message(message))
+            .map_err(|e| solana_sdk::signer::SignerError::Custom(e.to_string()))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Wallet builder for creating wallets with custom configuration
+pub struct WalletBuilder {
+    name: Option<String>,
+    config: WalletConfig,
+    multisig: Option<MultisigConfig>,
+}
+
+impl WalletBuilder {
+    /// Create a new wallet builder
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            config: WalletConfig::default(),
+            multisig: None,
+        }
+    }
+
+    /// Set wallet name
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set wallet configuration
+    pub fn config(mut self, config: WalletConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Make the wallet a multisig wallet
+    pub fn multisig(mut self, multisig: MultisigConfig) -> Self {
+        self.multisig = Some(multisig);
+        self
+    }
+
+    /// Build and create the wallet
+    pub async fn create(self, passphrase: &Zeroizing<String>) -> Result<Wallet> {
+        let name = self
+            .name
+            .ok_or_else(|| Error::config("Wallet name is required"))?;
+        match self.multisig {
+            Some(multisig) => {
+                Wallet::create_multisig(name, passphrase, self.config, multisig).await
+            }
+            None => Wallet::create(name, passphrase, self.config).await,
+        }
+    }
+
+    /// Build and load an existing wallet
+    pub async fn load(self, passphrase: &Zeroizing<String>) -> Result<Wallet> {
+        let name = self
+            .name
+            .ok_or_else(|| Error::config("Wallet name is required"))?;
+        Wallet::load(name, passphrase, self.config).await
+    }
+}
+
+impl Default for WalletBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Recipients and amounts of a batch transfer, empty for other actions
+fn batch_legs(action: &AgentAction) -> &[(Pubkey, u64)] {
+    match action {
+        AgentAction::BatchTransfer { transfers, .. } => transfers,
+        _ => &[],
+    }
+}
+
+/// Span of an action a wallet sends, with its amount and destination
+fn transfer_span(wallet: &str, action: &AgentAction) -> tracing::Span {
+    let (amount, destination) = match action {
+        AgentAction::TransferSol { to, amount, .. } => (Some(amount.as_u64()), Some(*to)),
+        AgentAction::TransferToken { to, amount, .. } => (Some(*amount), Some(*to)),
+        AgentAction::BatchTransfer { transfers, .. } => (
+            Some(
+                transfers
+                    .iter()
+                    .fold(0u64, |total, (_, amount)| total.saturating_add(*amount)),
+            ),
+            None,
+        ),
+        AgentAction::MintToken { to, amount, .. } => (Some(*amount), Some(*to)),
+        AgentAction::SwapTokens { amount, .. } | AgentAction::BurnToken { amount, .. } => {
+            (Some(*amount), None)
+        }
+        _ => (None, None),
+    };
+    tracing::info_span!(
+        "wallet.transfer",
+        wallet = %wallet,
+        action = action.kind(),
+        amount,
+        destination = destination.map(tracing::field::display),
+    )
+}
+
+/// Decrypt stored wallet data and the keypair inside it
+fn unlock(
+    encrypted_data: &EncryptedData,
+    passphrase: &Zeroizing<String>,
+) -> Result<(WalletData, EncryptedKeypair, SecureKeypair)> {
+    unlock_with_secret(encrypted_data, passphrase, None)
+}
+
+/// Decrypt stored wallet data, bound to `secret` if given, and its keypair
+///
+/// The keypair inside is always encrypted under the passphrase alone.
+fn unlock_with_secret(
+    encrypted_data: &EncryptedData,
+    passphrase: &Zeroizing<String>,
+    secret: Option<&[u8]>,
+) -> Result<(WalletData, EncryptedKeypair, SecureKeypair)> {
+    let decrypted_data = match secret {
+        Some(secret) => crate::encryption::utils::decrypt_with_keystore_secret(
+            encrypted_data,
+            passphrase,
+            secret,
+        )?,
+        None => crate::encryption::utils::decrypt_with_passphrase(encrypted_data, passphrase)?,
+    };
+    let wallet_data = crate::storage::utils::deserialize_wallet_data(&decrypted_data)?;
+    let encrypted_keypair: EncryptedKeypair =
+        bincode::deserialize(&wallet_data.encrypted_private_key)
+            .map_err(|e| Error::serialization(format!("Failed to deserialize keypair: {}", e)))?;
+    let keypair = SecureKeypair::decrypt(&encrypted_keypair, passphrase)?;
+    Ok((wallet_data, encrypted_keypair, keypair))
+}
+
+/// Encrypt wallet data under `passphrase`, bound to `secret` if given
+fn seal(
+    plaintext: &[u8],
+    passphrase: &Zeroizing<String>,
+    settings: &EncryptionSettings,
+    secret: Option<&[u8]>,
+) -> Result<EncryptedData> {
+    match secret {
+        Some(secret) => crate::encryption::utils::encrypt_with_keystore_secret(
+            plaintext,
+            passphrase,
+            secret,
+            settings.algorithm.into(),
+            settings.kdf,
+        ),
+        None => crate::encryption::utils::encrypt_with_passphrase(
+            plaintext,
+            passphrase,
+            settings.algorithm.into(),
+            settings.kdf,
+        ),
+    }
+}
+
+/// Keychain account of wallet `name` in the store configured by `storage`
+///
+/// Wallets of the same name in different stores get separate entries, so
+/// creating one never replaces the secret of the other.
+fn keystore_account(storage: &StorageSettings, name: &str) -> String {
+    let location = match &storage.backend {
+        StorageBackend::File => std::fs::canonicalize(&storage.path)
+            .unwrap_or_else(|_| storage.path.clone())
+            .display()
+            .to_string(),
+        StorageBackend::Memory { namespace } => format!("memory:{}", namespace),
+        StorageBackend::Env { variable, .. } => format!("env:{}", variable),
+    };
+    format!("{}/{}", location, name)
+}
+
+/// Store a new keychain secret under `account`, if the settings ask for one
+fn new_keystore_secret(
+    keystore: &dyn Keystore,
+    account: &str,
+    settings: &EncryptionSettings,
+) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    if !settings.use_os_keystore {
+        return Ok(None);
+    }
+    let secret = keystore::generate_secret();
+    keystore.set_secret(account, &*secret)?;
+    Ok(Some(Zeroizing::new(secret.to_vec())))
+}
+
+/// Keychain secret stored wallet data is bound to, if it is keystore-bound
+///
+/// Fails with `KeystoreUnavailable` when the entry is missing, e.g. for a
+/// wallet file copied from another machine.
+fn bound_secret(
+    keystore: &dyn Keystore,
+    storage: &StorageSettings,
+    name: &str,
+    encrypted_data: &EncryptedData,
+) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    if !encrypted_data.is_keystore_bound() {
+        return Ok(None);
+    }
+    match keystore.get_secret(&keystore_account(storage, name))? {
+        Some(secret) => Ok(Some(secret)),
+        None => Err(Error::KeystoreUnavailable(format!(
+            "Wallet '{}' is bound to an OS keychain entry that does not exist on this machine; \
+             restore it from a recovery copy made with export-recovery",
+            name
+        ))),
+    }
+}
+
+/// Write secret material to a file readable by its owner only on Unix
+///
+/// Fails if the file exists unless `overwrite` is set. An overwritten file
+/// keeps its inode, so its mode is narrowed explicitly.
+fn write_secret_file(path: &Path, contents: &[u8], overwrite: bool) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| Error::storage(format!("Failed to create {}: {}", path.display(), e)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| Error::storage(format!("Failed to restrict {}: {}", path.display(), e)))?;
+    }
+    file.write_all(contents)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| Error::storage(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Open the persisted budget ledger of a wallet
+///
+/// Pending reservations and the idempotency journal live next to the
+/// wallet file as `<name>.reservations.json` and `<name>.journal.jsonl`.
+/// Dry runs keep a shadow ledger in memory instead, leaving both untouched.
+fn open_budget(config: &WalletConfig, name: &str) -> Result<BudgetLedger> {
+    if config.dry_run {
+        return Ok(BudgetLedger::in_memory());
+    }
+    let dir = &config.wallet.storage.path;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| Error::storage(format!("Failed to create {}: {}", dir.display(), e)))?;
+    BudgetLedger::open(
+        dir.join(format!("{}.reservations.json", name)),
+        IdempotencyJournal::new(dir.join(format!("{}.journal.jsonl", name))),
+    )
+}
+
+/// Lamports held by `owner` and the number of its token accounts with a balance
+///
+/// Fails if either cannot be read, since the wallet may still hold funds.
+async fn held_funds(shared: &SharedComponents, owner: &Pubkey) -> Result<(u64, usize)> {
+    let lamports = shared.rpc_client().get_balance(owner).await?;
+    let funded_accounts = shared
+        .token_manager()
+        .read()
+        .await
+        .get_wallet_token_accounts(owner)
+        .await?
+        .iter()
+        .filter(|account| account.balance > 0)
+        .count();
+    Ok((lamports, funded_accounts))
+}
+
+/// Stored wallet named `name`, which creating a wallet would replace
+///
+/// Fails with `Error::Wallet` if one exists and `overwrite` is not set.
+fn stored_wallet(
+    store: &dyn WalletStore,
+    name: &str,
+    overwrite: bool,
+) -> Result<Option<(EncryptedData, WalletMetadata)>> {
+    if !store.wallet_exists(name) {
+        return Ok(None);
+    }
+    if !overwrite {
+        return Err(Error::wallet(format!("Wallet '{}' already exists", name)));
+    }
+    store.read_wallet(name).map(Some)
+}
+
+/// What creating a wallet changed, to undo if creation fails
+struct CreateRollback {
+    name: String,
+    /// Keychain account of the wallet
+    account: String,
+    /// Stored wallet the new one replaces
+    previous: Option<(EncryptedData, WalletMetadata)>,
+    /// Keychain secret of the replaced wallet
+    previous_secret: Option<Zeroizing<Vec<u8>>>,
+    /// Whether a keychain secret was stored for the new wallet
+    secret_set: bool,
+}
+
+impl CreateRollback {
+    /// Put back the replaced wallet, or remove the new one
+    fn undo(&self, store: &mut dyn WalletStore, keystore: &dyn Keystore) -> Result<()> {
+        match &self.previous {
+            Some((encrypted_data, metadata)) => {
+                store.replace_encrypted_data(&self.name, encrypted_data.clone())?;
+                store.update_metadata(&self.name, metadata)?;
+            }
+            // Purged rather than deleted so no backup of the new key is kept
+            None => match store.purge_wallet(&self.name) {
+                Ok(_) | Err(Error::WalletNotFound(_)) => {}
+                Err(e) => return Err(e),
+            },
+        }
+        if self.secret_set {
+            match &self.previous_secret {
+                Some(secret) => keystore.set_secret(&self.account, secret)?,
+                None => keystore.delete_secret(&self.account)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Open the audit file configured in the monitoring settings, if any
+fn open_audit_sink(config: &WalletConfig) -> Result<Option<Arc<dyn AuditSink>>> {
+    let settings = &config.monitoring.audit;
+    let Some(path) = &settings.path else {
+        return Ok(None);
+    };
+    let sink = JsonlAuditSink::open(path, settings.max_file_bytes, settings.max_files)?;
+    Ok(Some(Arc::new(sink)))
+}
+
+/// Address book restricting recipients, when one is configured
+fn open_recipient_whitelist(config: &WalletConfig) -> Result<Option<AddressBook>> {
+    config
+        .agent
+        .address_book
+        .as_ref()
+        .map(AddressBook::from_settings)
+        .transpose()
+}
+
+/// Signer for an unlocked wallet key
+///
+/// With `key_idle_lock_seconds` set the key is held by a [`LockedKeypair`]
+/// that re-encrypts itself while idle, checked by a background task when a
+/// runtime is available.
+fn key_signer(keypair: SecureKeypair, settings: &WalletSettings) -> Arc<dyn DynTransactionSigner> {
+    let Some(seconds) = settings.key_idle_lock_seconds else {
+        return Arc::new(keypair);
+    };
+    let locked = Arc::new(LockedKeypair::new(keypair, Duration::from_secs(seconds)));
+    if tokio::runtime::Handle::try_current().is_ok() {
+        locked.spawn_idle_lock();
+    }
+    locked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditPhase, MemoryAuditSink};
+    use crate::encryption::KdfAlgorithm;
+    use crate::keystore::MemoryKeystore;
+    use crate::manager::SharedComponents;
+    use crate::rpc::mock::{mint_account, status, token_account, MockRpc};
+    use crate::sol::LAMPORTS_PER_SOL;
+    use crate::transaction::ConfirmationStrategy;
+    use crate::types::TokenLimit;
+    use solana_transaction_status::TransactionConfirmationStatus;
+    use tempfile::tempdir;
+
+    // Note: These tests are mostly compile-time checks
+    // Real tests would require a running Solana RPC endpoint
+
+    #[test]
+    fn test_wallet_builder_creation() {
+        let builder = WalletBuilder::new();
+        assert!(builder.name.is_none());
+    }
+
+    #[test]
+    fn test_wallet_builder_with_name() {
+        let builder = WalletBuilder::new().name("test-wallet");
+        assert_eq!(builder.name, Some("test-wallet".to_string()));
+    }
+
+    /// Build a loaded wallet backed by a mock RPC and temporary storage
+    fn mock_wallet(rpc: Arc<MockRpc>, dir: &std::path::Path) -> Result<Wallet> {
+        mock_wallet_with_signer(rpc, dir, Arc::new(SecureKeypair::generate()))
+    }
+
+    /// Give the wallet's agent `level`, as its configuration would
+    async fn grant(wallet: &Wallet, level: PermissionLevel) {
+        wallet.agent_context.write().await.permission_level = level;
+    }
+
+    /// Build a mock wallet that signs through `signer`
+    fn mock_wallet_with_signer(
+        rpc: Arc<MockRpc>,
+        dir: &std::path::Path,
+        signer: Arc<dyn DynTransactionSigner>,
+    ) -> Result<Wallet> {
+        let public_key = futures::executor::block_on(signer.pubkey());
+        let now = Utc::now();
+        let mut config = WalletConfig::default();
+        config.wallet.storage = crate::config::StorageSettings {
+            path: dir.join("wallets"),
+            backup_path: dir.join("backups"),
+            max_versions: 3,
+            backend: crate::config::StorageBackend::File,
+        };
+        let commitment = config.rpc.commitment.to_solana_commitment();
+        let rpc_client: Arc<dyn DynRpcProvider> = rpc;
+        let token_manager = Arc::new(RwLock::new(TokenManager::with_provider(
+            rpc_client.clone(),
+            commitment,
+        )));
+
+        Ok(Wallet {
+            name: "mock".to_string(),
+            public_key,
+            signer,
+            encrypted_keypair: Arc::new(RwLock::new(None)),
+            rent: Arc::new(RentCalculator::new(rpc_client.clone())),
+            names: Arc::new(NameResolver::new(rpc_client.clone())),
+            rate_limiter: Arc::new(TokenBucket::per_minute(
+                config.agent.limits.max_transactions_per_minute,
+            )),
+            rpc_client: rpc_client.clone(),
+            subscriptions: None,
+            storage_service: Arc::new(RwLock::new(open_store(&config.wallet.storage)?)),
+            token_manager: token_manager.clone(),
+            context_builder: Arc::new(RwLock::new(ContextBuilder::new(
+                rpc_client.clone(),
+                token_manager,
+            ))),
+            transaction_builder: Arc::new(Mutex::new(TransactionBuilder::new())),
+            config,
+            metadata: Arc::new(RwLock::new(WalletMetadata {
+                name: "mock".to_string(),
+                public_key,
+                created_at: now,
+                last_accessed: now,
+                last_modified: now,
+                wallet_version: 1,
+                description: None,
+                tags: Vec::new(),
+                custom_data: HashMap::new(),
+                revision: 0,
+                key: None,
+            })),
+            agent_context: Arc::new(RwLock::new(AgentContext::new(public_key))),
+            recipient_whitelist: Arc::new(RwLock::new(None)),
+            templates: Arc::new(RwLock::new(TemplateSet::new())),
+            multisig: None,
+            budget: Arc::new(BudgetLedger::in_memory()),
+            audit_sink: Arc::new(RwLock::new(None)),
+            keystore: Arc::new(MemoryKeystore::new()),
+            key_usage: None,
+            is_loaded: true,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_confirmation_checks_status_before_subscribing() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let signature = Signature::new_unique();
+        rpc.script_statuses(
+            signature,
+            vec![Some(status(TransactionConfirmationStatus::Finalized))],
+        );
+        let mut wallet = mock_wallet(rpc.clone(), dir.path())?;
+        // Nothing listens here, so the subscription never reports
+        wallet.subscriptions = Some(Arc::new(SubscriptionClient::new(
+            "ws://127.0.0.1:9",
+            CommitmentConfig::confirmed(),
+        )));
+
+        let wait = wallet.wait_for_confirmation(
+            &signature,
+            CommitmentConfig::confirmed(),
+            Duration::from_secs(60),
+        );
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .map_err(|_| Error::Timeout("waited on the subscription".to_string()))??;
+        assert_eq!(rpc.status_polls(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_records_success_after_confirmation() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![
+            Some(status(TransactionConfirmationStatus::Processed)),
+            Some(status(TransactionConfirmationStatus::Processed)),
+            Some(status(TransactionConfirmationStatus::Confirmed)),
+        ]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::Confirmed {
+                timeout: Duration::from_secs(10),
+            },
+            ..Default::default()
+        };
+        wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.5")?,
+                None,
+                &options,
+            )
+            .await?;
+
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        assert_eq!(rpc.status_polls(), 3);
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(context.decision_count, 1);
+        assert!(context.recent_errors.is_empty());
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("9.5")?
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pay_solana_pay_request_with_references() -> Result<()> {
+        use crate::solana_pay::TransferRequest;
+        use solana_sdk::system_program;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let (recipient, reference) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let request = TransferRequest::parse(&format!(
+            "solana:{}?amount=0.25&reference={}&memo=order-17",
+            recipient, reference
+        ))?;
+        wallet.pay(&request).await?;
+
+        let sent = rpc.sent_transactions();
+        let [transaction] = sent.as_slice() else {
+            panic!("expected one transaction, got {}", sent.len());
+        };
+        let message = &transaction.message;
+        let transfer = message
+            .instructions
+            .iter()
+            .find(|ix| message.account_keys[ix.program_id_index as usize] == system_program::id())
+            .ok_or_else(|| Error::transaction("no transfer instruction"))?;
+        let accounts: Vec<Pubkey> = transfer
+            .accounts
+            .iter()
+            .map(|&index| message.account_keys[index as usize])
+            .collect();
+        assert_eq!(accounts, vec![wallet.public_key(), recipient, reference]);
+        let reference_index = message
+            .account_keys
+            .iter()
+            .position(|key| *key == reference)
+            .ok_or_else(|| Error::transaction("reference missing"))?;
+        assert!(!message.is_maybe_writable(reference_index, None));
+        assert!(!message.is_signer(reference_index));
+
+        // Without an amount there is nothing to pay
+        let open = TransferRequest::parse(&format!("solana:{}", recipient))?;
+        assert!(wallet.pay(&open).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_airdrop_refused_on_mainnet() -> Result<()> {
+        use crate::cluster::{DEVNET_GENESIS_HASH, MAINNET_BETA_GENESIS_HASH};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        let one_sol = Lamports::from_sol_str("1")?;
+
+        let mainnet = MAINNET_BETA_GENESIS_HASH
+            .parse()
+            .map_err(|_| Error::validation("bad genesis hash"))?;
+        rpc.set_genesis_hash(mainnet);
+        assert!(matches!(
+            wallet.request_airdrop(one_sol).await,
+            Err(Error::PermissionDenied(_))
+        ));
+        assert!(rpc.airdrops().is_empty());
+
+        let devnet = DEVNET_GENESIS_HASH
+            .parse()
+            .map_err(|_| Error::validation("bad genesis hash"))?;
+        rpc.set_genesis_hash(devnet);
+        wallet.request_airdrop(one_sol).await?;
+        assert_eq!(
+            rpc.airdrops(),
+            vec![(wallet.public_key(), one_sol.as_u64())]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "jito")]
+    #[tokio::test]
+    async fn test_jito_submission_falls_back_to_rpc() -> Result<()> {
+        use crate::audit::AuditPhase;
+        use wiremock::matchers::{body_partial_json, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
+
+        let landing = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({ "method": "sendBundle" }),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "b1" }),
+                ),
+            )
+            .mount(&landing)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": { "value": [{ "bundle_id": "b1", "status": "Landed", "landed_slot": 7 }] },
+            })))
+            .mount(&landing)
+            .await;
+        let down = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&down)
+            .await;
+
+        let jito = |url: String, fallback_to_rpc: bool| TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            submission: SubmissionMode::JitoBundle {
+                block_engine_url: url,
+                tip_lamports: 10_000,
+                fallback_to_rpc,
+            },
+            ..Default::default()
+        };
+        let last_submission = || {
+            sink.entries()
+                .into_iter()
+                .filter(|entry| entry.phase == AuditPhase::Outcome)
+                .last()
+                .and_then(|entry| entry.submission)
+        };
+        let to = Pubkey::new_unique();
+
+        // A landed bundle never touches the RPC node
+        wallet
+            .transfer_sol_with_options(to, Lamports::new(1_000), None, &jito(landing.uri(), false))
+            .await?;
+        assert!(rpc.sent_transactions().is_empty());
+        assert_eq!(
+            last_submission(),
+            Some(SubmissionPath::JitoBundle {
+                bundle_id: "b1".to_string()
+            })
+        );
+        // The tip counts against the budget along with the transfer
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("10")?.saturating_sub(Lamports::new(11_000))
+        );
+
+        // A failing block engine is fallen back from only when allowed
+        let result = wallet
+            .transfer_sol_with_options(to, Lamports::new(1_000), None, &jito(down.uri(), false))
+            .await;
+        assert!(matches!(result, Err(Error::Network(_))));
+        assert!(rpc.sent_transactions().is_empty());
+
+        wallet
+            .transfer_sol_with_options(to, Lamports::new(1_000), None, &jito(down.uri(), true))
+            .await?;
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        assert!(matches!(
+            last_submission(),
+            Some(SubmissionPath::RpcFallback { reason }) if reason.contains("503")
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_to_domain_audits_name_and_address() -> Result<()> {
+        use crate::sns::{self, NAME_PROGRAM_ID};
+        use solana_sdk::account::Account;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
+
+        let owner = Pubkey::new_unique();
+        let mut data = vec![0u8; 96];
+        data[32..64].copy_from_slice(owner.as_ref());
+        rpc.set_account(
+            sns::domain_account("alice.sol")?,
+            Account {
+                lamports: 1_000_000,
+                data,
+                owner: NAME_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let alice: Recipient = "alice.sol".parse()?;
+        wallet
+            .transfer_sol_with_options(alice, Lamports::new(1_000_000), None, &options)
+            .await?;
+
+        let intent = &sink.entries()[0];
+        assert_eq!(intent.recipient_name.as_deref(), Some("alice.sol"));
+        assert!(matches!(
+            intent.action,
+            Some(AgentAction::TransferSol { to, .. }) if to == owner
+        ));
+
+        let missing: Recipient = "nobody.sol".parse()?;
+        assert!(matches!(
+            wallet
+                .transfer_sol_with_options(missing, Lamports::new(1_000_000), None, &options)
+                .await,
+            Err(Error::InvalidAddress(message)) if message.contains("nobody.sol")
+        ));
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sponsor_pays_fee_of_empty_wallet() -> Result<()> {
+        use crate::fee_payer::FeePayerService;
+        use solana_sdk::{message::Message, system_instruction};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet = mock_wallet(rpc.clone(), dir.path())?;
+        let sponsor_wallet = mock_wallet(rpc.clone(), &dir.path().join("sponsor"))?;
+        let sponsor = FeePayerService::from_wallet(&sponsor_wallet);
+        rpc.set_balance(sponsor.pubkey(), LAMPORTS_PER_SOL);
+        rpc.set_balance(wallet.public_key(), 1_000);
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+
+        // The wallet holds only what it sends; only the sponsor can pay the fee
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        let context = wallet.get_agent_context().await?;
+        let mut transaction = wallet.transaction_builder.lock().await.build_from_action(
+            &action,
+            &context,
+            &sponsor.sponsor(&TransactionOptions::default()),
+        )?;
+        assert_eq!(transaction.message.account_keys[0], sponsor.pubkey());
+        assert_eq!(transaction.message.header.num_required_signatures, 2);
+
+        // Basic agents may only be sponsored where the deployment allows it
+        assert!(!wallet.validate_transaction(&transaction).await?.is_valid);
+        wallet.config.agent.fee_payer_permission = PermissionLevel::Basic;
+        let validation = wallet.validate_transaction(&transaction).await?;
+        assert!(validation.is_valid);
+        assert_eq!(
+            validation.required_breakdown.map(|required| required.fees),
+            Some(0)
+        );
+
+        let signature = wallet
+            .sign_and_send_with_fee_payer(&mut transaction, &sponsor)
+            .await?;
+        let sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signatures[0], signature);
+        let message = sent[0].message_data();
+        assert!(sent[0].signatures[0].verify(sponsor.pubkey().as_ref(), &message));
+        assert!(sent[0].signatures[1].verify(wallet.public_key().as_ref(), &message));
+        assert!(sent[0].verify().is_ok());
+
+        // A transaction the sponsor does not pay for is refused
+        let mut unsponsored = wallet.transaction_builder.lock().await.build_from_action(
+            &action,
+            &context,
+            &TransactionOptions::default(),
+        )?;
+        assert!(wallet
+            .sign_and_send_with_fee_payer(&mut unsponsored, &sponsor)
+            .await
+            .is_err());
+
+        // The sponsor's signature only pays the fee, never a transfer of its own funds
+        let mut drain = Transaction::new_unsigned(Message::new(
+            &[
+                system_instruction::transfer(&wallet.public_key(), &Pubkey::new_unique(), 1),
+                system_instruction::transfer(&sponsor.pubkey(), &wallet.public_key(), 1_000),
+            ],
+            Some(&sponsor.pubkey()),
+        ));
+        assert!(matches!(
+            wallet
+                .sign_and_send_with_fee_payer(&mut drain, &sponsor)
+                .await,
+            Err(Error::PermissionDenied(_))
+        ));
+        assert_eq!(rpc.sent_transactions().len(), 1);
+
+        // What the wallet sends is held against its budget
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("10")?.saturating_sub(Lamports::new(1_000))
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_send_is_held_to_the_spending_limits() -> Result<()> {
+        use solana_sdk::system_instruction;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 20 * LAMPORTS_PER_SOL);
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+        let transfer = |lamports: u64| {
+            Transaction::new_with_payer(
+                &[system_instruction::transfer(
+                    &wallet.public_key(),
+                    &Pubkey::new_unique(),
+                    lamports,
+                )],
+                Some(&wallet.public_key()),
+            )
+        };
+
+        // Over the per-transaction limit: refused before signing
+        let mut too_much = transfer(2 * LAMPORTS_PER_SOL);
+        assert!(wallet.sign_and_send(&mut too_much).await.is_err());
+        assert!(rpc.sent_transactions().is_empty());
+
+        wallet
+            .sign_and_send(&mut transfer(LAMPORTS_PER_SOL / 2))
+            .await?;
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("9.5")?
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_funding_beyond_rent_counts_as_spend() -> Result<()> {
+        use solana_sdk::{rent::Rent, system_instruction, system_program};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 20 * LAMPORTS_PER_SOL);
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+        let create = |seed: &str, lamports: u64| -> Result<Transaction> {
+            let address =
+                Pubkey::create_with_seed(&wallet.public_key(), seed, &system_program::id())
+                    .map_err(|e| Error::validation(e.to_string()))?;
+            Ok(Transaction::new_with_payer(
+                &[system_instruction::create_account_with_seed(
+                    &wallet.public_key(),
+                    &address,
+                    &wallet.public_key(),
+                    seed,
+                    lamports,
+                    0,
+                    &system_program::id(),
+                )],
+                Some(&wallet.public_key()),
+            ))
+        };
+
+        // An account funded with more than the per-transaction limit
+        let mut oversized = create("oversized", 2 * LAMPORTS_PER_SOL)?;
+        assert!(wallet.sign_and_send(&mut oversized).await.is_err());
+        assert!(rpc.sent_transactions().is_empty());
+
+        // Only the funding beyond the rent-exempt minimum is spent
+        wallet
+            .sign_and_send(&mut create("funded", LAMPORTS_PER_SOL / 2)?)
+            .await?;
+        let rent = Rent::default().minimum_balance(0);
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::new(10 * LAMPORTS_PER_SOL - (LAMPORTS_PER_SOL / 2 - rent))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_routed_swap_to_a_disallowed_program_is_refused() -> Result<()> {
+        use crate::protocols::{ProtocolSettings, ORCA_WHIRLPOOL, RAYDIUM_AMM_V4_PROGRAM_ID};
+        use solana_sdk::instruction::{AccountMeta, Instruction};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), LAMPORTS_PER_SOL);
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+        wallet.agent_context.write().await.allowed_protocols =
+            protocols::resolve_all(&[ProtocolSettings::new(ORCA_WHIRLPOOL)])?;
+
+        // What a router filling the swap on Raydium hands back
+        let swap = Instruction::new_with_bytes(
+            RAYDIUM_AMM_V4_PROGRAM_ID,
+            &[9],
+            vec![AccountMeta::new(wallet.public_key(), true)],
+        );
+        let mut routed = Transaction::new_with_payer(&[swap], Some(&wallet.public_key()));
+        assert!(matches!(
+            wallet.sign_and_send(&mut routed).await,
+            Err(Error::PermissionDenied(_))
+        ));
+        assert!(rpc.sent_transactions().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_blockhash_is_re_signed_and_resent() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
+        rpc.fail_sends(vec![Error::BlockhashExpired(
+            "Blockhash not found".to_string(),
+        )]);
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000_000),
+            memo: None,
+        };
+        let receipt = wallet
+            .execute_action_with_receipt(&action, &options)
+            .await?;
+
+        let rejected = rpc.rejected_transactions();
+        let sent = rpc.sent_transactions();
+        assert_eq!((rejected.len(), sent.len()), (1, 1));
+        assert_ne!(
+            rejected[0].message.recent_blockhash,
+            sent[0].message.recent_blockhash
+        );
+        assert_eq!(sent[0].signatures[0], receipt.signature);
+        assert!(sent[0].verify().is_ok());
+
+        let outcomes: Vec<Option<AuditOutcome>> =
+            sink.entries().iter().map(|e| e.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                None,
+                Some(AuditOutcome::Failed),
+                None,
+                Some(AuditOutcome::Sent)
+            ]
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+
+        // Retries are bounded; other errors are never retried
+        let expired = || Error::BlockhashExpired("Blockhash not found".to_string());
+        rpc.fail_sends(vec![expired(), expired()]);
+        let once = TransactionOptions {
+            max_retries: 1,
+            ..options.clone()
+        };
+        assert!(matches!(
+            wallet.execute_action_with_receipt(&action, &once).await,
+            Err(Error::BlockhashExpired(_))
+        ));
+        rpc.fail_sends(vec![Error::rpc("node is behind")]);
+        assert!(wallet
+            .execute_action_with_receipt(&action, &options)
+            .await
+            .is_err());
+        assert_eq!(rpc.rejected_transactions().len(), 4);
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_priority_fee_prices_from_network() -> Result<()> {
+        use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.set_prioritization_fees(vec![0, 1_000, 4_000, 2_000]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let options = TransactionOptions {
+            priority_fee_strategy: PriorityFeeStrategy::auto(),
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let amount = Lamports::from_sol_str("0.5")?;
+        wallet
+            .transfer_sol_with_options(&Pubkey::new_unique(), amount, None, &options)
+            .await?;
+
+        // p75 of the recent fees; the mock simulation consumes 450 units
+        let sent = rpc.sent_transactions();
+        let message = &sent[0].message;
+        let budget_data: Vec<Vec<u8>> = message
+            .instructions
+            .iter()
+            .filter(|ix| message.account_keys[ix.program_id_index as usize] == compute_budget::id())
+            .map(|ix| ix.data.clone())
+            .collect();
+        assert!(budget_data.contains(&ComputeBudgetInstruction::set_compute_unit_price(2_000).data));
+        assert!(budget_data.contains(&ComputeBudgetInstruction::set_compute_unit_limit(1_000).data));
+
+        // A fee over the cap is refused before anything is sent
+        let capped = TransactionOptions {
+            max_total_fee_lamports: Some(4_000),
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let result = wallet
+            .transfer_sol_with_options(&Pubkey::new_unique(), amount, None, &capped)
+            .await;
+        assert!(matches!(result, Err(Error::LimitExceeded(_))));
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_breaching_reserve_is_rejected() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet = mock_wallet(rpc.clone(), dir.path())?;
+        wallet.config.agent.limits.min_sol_reserve = 0.5;
+        rpc.set_balance(wallet.public_key(), LAMPORTS_PER_SOL);
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+
+        // Exactly half a SOL leaves the reserve minus the fee
+        let result = wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.5")?,
+                None,
+                &options,
+            )
+            .await;
+        let Err(Error::ReserveBreached {
+            reserve,
+            post_balance,
+        }) = result
+        else {
+            return Err(Error::validation("expected the reserve to be breached"));
+        };
+        assert_eq!(reserve, LAMPORTS_PER_SOL / 2);
+        assert!(post_balance < reserve);
+        assert!(rpc.sent_transactions().is_empty());
+
+        wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.4")?,
+                None,
+                &options,
+            )
+            .await?;
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_never_sends_but_spends_shadow_budget() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet = mock_wallet(rpc.clone(), dir.path())?;
+        wallet.config.dry_run = true;
+        assert_eq!(wallet.run_mode(), RunMode::DryRun);
+        rpc.set_balance(wallet.public_key(), 100 * LAMPORTS_PER_SOL);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
+        wallet
+            .agent_context
+            .write()
+            .await
+            .spending_limits
+            .remaining_daily_budget_lamports = Lamports::from_sol_str("1.5")?;
+
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::from_sol_str("1")?,
+            memo: None,
+        };
+        let receipt = wallet
+            .execute_action_with_receipt(&action, &TransactionOptions::default())
+            .await?;
+        assert!(receipt.is_dry_run());
+        assert_eq!(
+            receipt.transfer_lamports,
+            Lamports::from_sol_str("1")?.as_u64()
+        );
+
+        // The shadow budget runs out like a live one would
+        let exhausted = wallet
+            .execute_action_with_receipt(&action, &TransactionOptions::default())
+            .await;
+        assert!(matches!(
+            exhausted,
+            Err(Error::InsufficientFunds { .. } | Error::LimitExceeded(_))
+        ));
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("0.5")?
+        );
+
+        // Nothing reached the network, and every audit record says so
+        assert!(rpc.sent_transactions().is_empty());
+        let mut raw = Transaction::new_with_payer(&[], Some(&wallet.public_key()));
+        assert!(matches!(
+            wallet.sign_and_send(&mut raw).await,
+            Err(Error::NotSupported(_))
+        ));
+        let entries = sink.entries();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.dry_run));
+        assert_eq!(entries[1].outcome, Some(AuditOutcome::Simulated));
+        assert_eq!(entries[1].signature, Some(receipt.signature));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_policy_warnings_are_audited() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
+
+        // Sending 1_000 lamports costs more in fees than it moves
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        let prepared = wallet.prepare_action(&action, &options).await?;
+        assert_eq!(prepared.warnings.len(), 1);
+
+        wallet.execute_action(&action, &options).await?;
+        let entries = sink.entries();
+        assert_eq!(entries[0].phase, AuditPhase::Intent);
+        assert_eq!(entries[0].warnings, prepared.warnings);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_is_audited_before_send_and_after_confirmation() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Processed))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
+
+        let unconfirmed = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let signature = wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.5")?,
+                None,
+                &unconfirmed,
+            )
+            .await?;
+        let timing_out = TransactionOptions {
+            confirmation: ConfirmationStrategy::Finalized {
+                timeout: Duration::from_millis(50),
+            },
+            ..Default::default()
+        };
+        let timed_out = wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.1")?,
+                None,
+                &timing_out,
+            )
+            .await;
+        assert!(timed_out.is_err());
+
+        let entries = sink.entries();
+        let phases: Vec<AuditPhase> = entries.iter().map(|e| e.phase).collect();
+        assert_eq!(
+            phases,
+            vec![
+                AuditPhase::Intent,
+                AuditPhase::Outcome,
+                AuditPhase::Intent,
+                AuditPhase::Outcome
+            ]
+        );
+        assert_eq!(entries[0].id, entries[1].id);
+        assert_ne!(entries[1].id, entries[2].id);
+        assert_eq!(entries[0].signature, Some(signature));
+        assert!(entries[0].fee_lamports.is_some());
+        assert_eq!(entries[1].outcome, Some(AuditOutcome::Sent));
+        assert_eq!(entries[3].outcome, Some(AuditOutcome::Unconfirmed));
+        assert!(entries[3].error.is_some());
+        Ok(())
+    }
+
+    /// Persist a mock wallet the way `Wallet::create` does
+    async fn store(
+        wallet: &Wallet,
+        keypair: &SecureKeypair,
+        passphrase: &Zeroizing<String>,
+    ) -> Result<()> {
+        let encrypted_keypair = keypair.encrypt(passphrase)?;
+        let wallet_data = crate::storage::utils::create_wallet_data_from_key(
+            bincode::serialize(&encrypted_keypair)
+                .map_err(|e| Error::serialization(e.to_string()))?,
+        );
+        let settings = &wallet.config.wallet.encryption;
+        let account = keystore_account(&wallet.config.wallet.storage, wallet.name());
+        let secret = new_keystore_secret(wallet.keystore.as_ref(), &account, settings)?;
+        let encrypted_data = seal(
+            &crate::storage::utils::serialize_wallet_data(&wallet_data)?,
+            passphrase,
+            settings,
+            secret.as_deref().map(Vec::as_slice),
+        )?;
+        wallet.storage_service.write().await.save_wallet(
+            wallet.name(),
+            encrypted_data,
+            wallet.public_key(),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_change_passphrase_reencrypts_stored_wallet() -> Result<()> {
+        let dir = tempdir()?;
+        let keypair = SecureKeypair::generate();
+        let wallet = mock_wallet_with_signer(
+            Arc::new(MockRpc::new()),
+            dir.path(),
+            Arc::new(keypair.clone()),
+        )?;
+        let old = Zeroizing::new("old passphrase".to_string());
+        let new = Zeroizing::new("new passphrase".to_string());
+        store(&wallet, &keypair, &old).await?;
+        let file = dir.path().join("wallets").join("mock.json");
+
+        // A wrong passphrase leaves the file untouched
+        let before = std::fs::read(&file)?;
+        let wrong = Zeroizing::new("wrong passphrase".to_string());
+        let result = wallet.change_passphrase(&wrong, &new).await;
+        assert!(matches!(result, Err(Error::Encryption(_))));
+        assert_eq!(std::fs::read(&file)?, before);
+
+        wallet.change_passphrase(&old, &new).await?;
+        let (encrypted, metadata) = wallet.storage_service.read().await.read_wallet("mock")?;
+        let (_, _, keypair) = unlock(&encrypted, &new)?;
+        assert_eq!(keypair.public_key(), wallet.public_key());
+        assert_eq!(metadata.public_key, wallet.public_key());
+        assert!(matches!(
+            unlock(&encrypted, &old),
+            Err(Error::Encryption(_))
+        ));
+
+        // Backups hold the file as it was under the old passphrase
+        for backup in std::fs::read_dir(dir.path().join("backups"))? {
+            let stored: WalletStorage = serde_json::from_slice(&std::fs::read(backup?.path())?)?;
+            unlock(&stored.encrypted_data, &old)?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_encryption_from_version_1() -> Result<()> {
+        let dir = tempdir()?;
+        let keypair = SecureKeypair::generate();
+        let mut wallet = mock_wallet_with_signer(
+            Arc::new(MockRpc::new()),
+            dir.path(),
+            Arc::new(keypair.clone()),
+        )?;
+        wallet.config.wallet.encryption.kdf = KdfAlgorithm::Pbkdf2 { iterations: 1_000 };
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        store(&wallet, &keypair, &passphrase).await?;
+
+        // Rewrite the file as format version 1 wrote it
+        let mut storage = wallet.storage_service.write().await;
+        let (mut legacy, _) = storage.read_wallet("mock")?;
+        legacy.version = EncryptedData::LEGACY_VERSION;
+        legacy.kdf = None;
+        storage.replace_encrypted_data("mock", legacy)?;
+        drop(storage);
+        let file = dir.path().join("wallets").join("mock.json");
+        let before = std::fs::read(&file)?;
+
+        let argon2id = KdfAlgorithm::Argon2id {
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        wallet.config.wallet.encryption.kdf = argon2id;
+        let wrong = Zeroizing::new("wrong passphrase".to_string());
+        let result = wallet.upgrade_encryption(&wrong).await;
+        assert!(matches!(result, Err(Error::Encryption(_))));
+        assert_eq!(std::fs::read(&file)?, before);
+
+        assert!(wallet.upgrade_encryption(&passphrase).await?);
+        let (encrypted, _) = wallet.storage_service.read().await.read_wallet("mock")?;
+        assert_eq!(encrypted.version, EncryptedData::CURRENT_VERSION);
+        assert_eq!(encrypted.kdf(), argon2id);
+        let (_, _, upgraded) = unlock(&encrypted, &passphrase)?;
+        assert_eq!(upgraded.public_key(), keypair.public_key());
+        assert!(matches!(
+            unlock(&encrypted, &wrong),
+            Err(Error::Encryption(_))
+        ));
+
+        // An up-to-date file is left alone
+        let after = std::fs::read(&file)?;
+        assert!(!wallet.upgrade_encryption(&passphrase).await?);
+        assert_eq!(std::fs::read(&file)?, after);
+
+        // The newest backup keeps the version 1 file, still readable
+        let backup = wallet
+            .storage_service
+            .read()
+            .await
+            .list_backups("mock")?
+            .pop()
+            .ok_or_else(|| Error::storage("no backup"))?;
+        let stored: WalletStorage = serde_json::from_slice(&std::fs::read(&backup.path)?)?;
+        assert_eq!(stored.encrypted_data.version, EncryptedData::LEGACY_VERSION);
+        unlock(&stored.encrypted_data, &passphrase)?;
+        Ok(())
+    }
+
+    /// File-backed config under `dir` with a fast KDF
+    fn create_config(dir: &std::path::Path) -> WalletConfig {
+        let mut config = WalletConfig::default();
+        config.wallet.storage.path = dir.join("wallets");
+        config.wallet.storage.backup_path = dir.join("backups");
+        config.wallet.encryption.kdf = KdfAlgorithm::Pbkdf2 { iterations: 1_000 };
+        config.rpc.use_websocket = false;
+        config
+    }
+
+    #[tokio::test]
+    async fn test_create_survives_an_unreachable_cluster() -> Result<()> {
+        let dir = tempdir()?;
+        let config = create_config(dir.path());
+        let rpc = Arc::new(MockRpc::new());
+        let shared =
+            SharedComponents::new(rpc.clone(), config.rpc.commitment.to_solana_commitment());
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        let keypair = SecureKeypair::generate();
+        rpc.fail_account(keypair.public_key());
+
+        let (wallet, report) = Wallet::create_with_keypair(
+            "offline".to_string(),
+            keypair.clone(),
+            &passphrase,
+            config.clone(),
+            &shared,
+            false,
+        )
+        .await?;
+        assert_eq!(report.pubkey, keypair.public_key());
+        assert!(!report.funded);
+        assert_eq!(wallet.agent_context.read().await.wallet_balance, 0.0);
+        let path = report
+            .storage_path
+            .ok_or_else(|| Error::storage("no storage path"))?;
+        assert!(path.exists());
+
+        // The name is taken unless overwriting is asked for
+        let result = Wallet::create_with_keypair(
+            "offline".to_string(),
+            SecureKeypair::generate(),
+            &passphrase,
+            config.clone(),
+            &shared,
+            false,
+        )
+        .await;
+        let Err(Error::Wallet(message)) = result else {
+            panic!("expected a duplicate name error");
+        };
+        assert!(message.contains("already exists"));
+
+        let replacement = SecureKeypair::generate();
+        rpc.set_balance(replacement.public_key(), LAMPORTS_PER_SOL);
+        let (_, report) = Wallet::create_with_keypair(
+            "offline".to_string(),
+            replacement.clone(),
+            &passphrase,
+            config.clone(),
+            &shared,
+            true,
+        )
+        .await?;
+        assert!(report.funded);
+        let (_, metadata) = open_store(&config.wallet.storage)?.read_wallet("offline")?;
+        assert_eq!(metadata.public_key, replacement.public_key());
+        Ok(())
+    }
+
+    /// Names of the files in the backup directory of `config`
+    fn backup_files(config: &WalletConfig) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&config.wallet.storage.backup_path)? {
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        Ok(names)
+    }
+
+    #[tokio::test]
+    async fn test_delete_refuses_a_funded_wallet_unless_forced() -> Result<()> {
+        let dir = tempdir()?;
+        let config = create_config(dir.path());
+        let rpc = Arc::new(MockRpc::new());
+        let shared =
+            SharedComponents::new(rpc.clone(), config.rpc.commitment.to_solana_commitment());
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        let keypair = SecureKeypair::generate();
+        rpc.set_balance(keypair.public_key(), LAMPORTS_PER_SOL);
+        Wallet::create_with_keypair(
+            "funded".to_string(),
+            keypair,
+            &passphrase,
+            config.clone(),
+            &shared,
+            false,
+        )
+        .await?;
+        let backups = backup_files(&config)?;
+        assert!(!backups.is_empty());
+
+        let result = Wallet::delete_with("funded", &config, &shared, false).await;
+        let Err(Error::Wallet(message)) = result else {
+            panic!("expected the funds check to refuse the deletion");
+        };
+        assert!(message.contains("still holds funds"));
+        assert!(open_store(&config.wallet.storage)?.wallet_exists("funded"));
+        assert_eq!(backup_files(&config)?, backups);
+
+        let report = Wallet::delete_with("funded", &config, &shared, true).await?;
+        assert_eq!(report.final_balance_lamports, LAMPORTS_PER_SOL);
+        assert_eq!(report.files_removed, 1);
+        assert_eq!(report.backups_removed, backups.len());
+        assert!(!open_store(&config.wallet.storage)?.wallet_exists("funded"));
+        assert!(backup_files(&config)?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_refuses_unlisted_token_accounts_unless_forced() -> Result<()> {
+        let dir = tempdir()?;
+        let config = create_config(dir.path());
+        let rpc = Arc::new(MockRpc::new());
+        let shared =
+            SharedComponents::new(rpc.clone(), config.rpc.commitment.to_solana_commitment());
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        let keypair = SecureKeypair::generate();
+        let owner = keypair.public_key();
+        Wallet::create_with_keypair(
+            "tokens".to_string(),
+            keypair,
+            &passphrase,
+            config.clone(),
+            &shared,
+            false,
+        )
+        .await?;
+
+        // The token account exists but cannot be read back
+        rpc.set_token_account(Pubkey::new_unique(), Pubkey::new_unique(), owner, 5)?;
+        rpc.limit_program_accounts(0);
+        rpc.fail_batches();
+
+        let result = Wallet::delete_with("tokens", &config, &shared, false).await;
+        assert!(result.is_err());
+        assert!(open_store(&config.wallet.storage)?.wallet_exists("tokens"));
+
+        Wallet::delete_with("tokens", &config, &shared, true).await?;
+        assert!(!open_store(&config.wallet.storage)?.wallet_exists("tokens"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_every_backup_of_the_wallet() -> Result<()> {
+        let dir = tempdir()?;
+        let config = create_config(dir.path());
+        let shared = SharedComponents::new(
+            Arc::new(MockRpc::new()),
+            config.rpc.commitment.to_solana_commitment(),
+        );
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        for name in ["agent", "agent_two"] {
+            Wallet::create_with_keypair(
+                name.to_string(),
+                SecureKeypair::generate(),
+                &passphrase,
+                config.clone(),
+                &shared,
+                false,
+            )
+            .await?;
+        }
+        let mut store = open_store(&config.wallet.storage)?;
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(2));
+            let (encrypted_data, _) = store.read_wallet("agent")?;
+            store.replace_encrypted_data("agent", encrypted_data)?;
+        }
+        let storage = crate::storage::StorageService::new(config.wallet.storage.clone())?;
+        let backups = storage.list_backups("agent")?;
+        let other_backups = storage.list_backups("agent_two")?;
+        assert!(backups.len() > 3);
+
+        let report = Wallet::delete_with("agent", &config, &shared, false).await?;
+        assert_eq!(report.final_balance_lamports, 0);
+        assert_eq!(report.backups_removed, backups.len());
+        assert!(backups.iter().all(|backup| !backup.path.exists()));
+        assert!(storage.list_backups("agent")?.is_empty());
+        assert!(!dir.path().join("wallets").join("agent.json").exists());
+
+        // The wallet sharing the name's prefix keeps its file and backups
+        assert!(storage.wallet_exists("agent_two"));
+        assert_eq!(storage.list_backups("agent_two")?, other_backups);
+        let result = Wallet::delete_with("agent", &config, &shared, true).await;
+        assert!(matches!(result, Err(Error::WalletNotFound(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_create_leaves_no_wallet_behind() -> Result<()> {
+        let dir = tempdir()?;
+        let mut config = create_config(dir.path());
+        let shared = SharedComponents::new(
+            Arc::new(MockRpc::new()),
+            config.rpc.commitment.to_solana_commitment(),
+        );
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        let original = SecureKeypair::generate();
+        Wallet::create_with_keypair(
+            "kept".to_string(),
+            original.clone(),
+            &passphrase,
+            config.clone(),
+            &shared,
+            false,
+        )
+        .await?;
+
+        // A directory cannot be opened as the audit file, after the wallet is saved
+        config.monitoring.audit.path = Some(dir.path().to_path_buf());
+        let result = Wallet::create_with_keypair(
+            "fresh".to_string(),
+            SecureKeypair::generate(),
+            &passphrase,
+            config.clone(),
+            &shared,
+            false,
+        )
+        .await;
+        assert!(result.is_err());
+        let store = open_store(&config.wallet.storage)?;
+        assert!(!store.wallet_exists("fresh"));
+        assert!(!dir.path().join("wallets").join("fresh.json").exists());
+        assert!(std::fs::read_dir(dir.path().join("backups"))?
+            .next()
+            .is_none());
+
+        // A failed overwrite puts the replaced wallet back
+        let result = Wallet::create_with_keypair(
+            "kept".to_string(),
+            SecureKeypair::generate(),
+            &passphrase,
+            config.clone(),
+            &shared,
+            true,
+        )
+        .await;
+        assert!(result.is_err());
+        let (_, metadata) = open_store(&config.wallet.storage)?.read_wallet("kept")?;
+        assert_eq!(metadata.public_key, original.public_key());
+
+        // Once the cause is fixed, creating under the same name works
+        config.monitoring.audit.path = None;
+        Wallet::create_with_keypair(
+            "fresh".to_string(),
+            SecureKeypair::generate(),
+            &passphrase,
+            config,
+            &shared,
+            false,
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keystore_bound_wallet_needs_keychain_entry() -> Result<()> {
+        let dir = tempdir()?;
+        let keypair = SecureKeypair::generate();
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet =
+            mock_wallet_with_signer(rpc.clone(), dir.path(), Arc::new(keypair.clone()))?;
+        wallet.config.wallet.encryption.use_os_keystore = true;
+        wallet.config.wallet.encryption.kdf = KdfAlgorithm::Pbkdf2 { iterations: 1_000 };
+        let keystore = Arc::new(MemoryKeystore::new());
+        wallet.keystore = keystore.clone();
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        store(&wallet, &keypair, &passphrase).await?;
+
+        // The passphrase alone does not open the file
+        let (encrypted, _) = wallet.storage_service.read().await.read_wallet("mock")?;
+        assert!(encrypted.is_keystore_bound());
+        assert!(unlock(&encrypted, &passphrase).is_err());
+
+        let commitment = wallet.config.rpc.commitment.to_solana_commitment();
+        let shared = SharedComponents::new(rpc, commitment).with_keystore(keystore.clone());
+        let loaded =
+            Wallet::load_with_shared("mock", &passphrase, wallet.config.clone(), &shared).await?;
+        assert_eq!(loaded.public_key(), keypair.public_key());
+
+        // The recovery copy opens with the passphrase alone
+        let path = dir.path().join("recovery.json");
+        wallet.export_recovery(&passphrase, &path, false).await?;
+        let recovery: WalletStorage = serde_json::from_slice(&std::fs::read(&path)?)?;
+        assert!(!recovery.encrypted_data.is_keystore_bound());
+        let (_, _, recovered) = unlock(&recovery.encrypted_data, &passphrase)?;
+        assert_eq!(recovered.public_key(), keypair.public_key());
+
+        // On a machine without the keychain entry, loading says so
+        keystore.delete_secret(&keystore_account(&wallet.config.wallet.storage, "mock"))?;
+        let result =
+            Wallet::load_with_shared("mock", &passphrase, wallet.config.clone(), &shared).await;
+        assert!(matches!(result, Err(Error::KeystoreUnavailable(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_rotation_sweep_is_resumed() -> Result<()> {
+        let dir = tempdir()?;
+        let keypair = SecureKeypair::generate();
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+        let mut wallet =
+            mock_wallet_with_signer(rpc.clone(), dir.path(), Arc::new(keypair.clone()))?;
+        wallet.config.wallet.encryption.kdf = KdfAlgorithm::Pbkdf2 { iterations: 1_000 };
+        wallet.key_usage = Some(keypair.usage().clone());
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        store(&wallet, &keypair, &passphrase).await?;
+        let old = keypair.public_key();
+        rpc.set_balance(old, 1_000_000_000);
+
+        // The key switches in one write that records the sweep it still owes
+        rpc.fail_sends(vec![Error::rpc("sweep dropped")]);
+        let result = wallet.rotate_key(&passphrase).await;
+        assert!(matches!(result, Err(Error::Transaction(_))));
+        let new = wallet.public_key();
+        assert_ne!(new, old);
+        let (_, metadata) = wallet.storage_service.read().await.read_wallet("mock")?;
+        assert_eq!(metadata.public_key, new);
+        let Some(key) = metadata.key else {
+            panic!("rotated wallet has no key record");
+        };
+        assert_eq!(key.pending_sweep, Some(old));
+        assert!(key
+            .archived
+            .iter()
+            .any(|archived| archived.public_key == old));
+
+        let report = wallet.resume_rotation(&passphrase).await?;
+        assert_eq!((report.old_public_key, report.new_public_key), (old, new));
+        assert_eq!(report.signatures.len(), 1);
+        assert_eq!(
+            report.swept_lamports,
+            1_000_000_000 - LAMPORTS_PER_SIGNATURE
+        );
+        let sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].message.account_keys[0], old);
+
+        // Once swept, there is nothing left to resume
+        let (_, metadata) = wallet.storage_service.read().await.read_wallet("mock")?;
+        assert!(metadata.key.is_some_and(|key| key.pending_sweep.is_none()));
+        assert!(matches!(
+            wallet.resume_rotation(&passphrase).await,
+            Err(Error::Validation(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_same_named_wallets_in_two_stores_keep_their_keychain_secrets() -> Result<()> {
+        let (first_dir, second_dir) = (tempdir()?, tempdir()?);
+        let keystore = Arc::new(MemoryKeystore::new());
+        let shared = SharedComponents::new(Arc::new(MockRpc::new()), CommitmentConfig::confirmed())
+            .with_keystore(keystore.clone());
+        let passphrase = Zeroizing::new("passphrase".to_string());
+
+        let mut created = Vec::new();
+        for dir in [first_dir.path(), second_dir.path()] {
+            let mut config = create_config(dir);
+            config.wallet.encryption.use_os_keystore = true;
+            let keypair = SecureKeypair::generate();
+            Wallet::create_with_keypair(
+                "trader".to_string(),
+                keypair.clone(),
+                &passphrase,
+                config.clone(),
+                &shared,
+                false,
+            )
+            .await?;
+            created.push((config, keypair.public_key()));
+        }
+
+        // Creating the second wallet did not replace the first one's secret
+        for (config, public_key) in created {
+            let loaded = Wallet::load_with_shared("trader", &passphrase, config, &shared).await?;
+            assert_eq!(loaded.public_key(), public_key);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing_round_trip() -> Result<()> {
+        use solana_sdk::hash::Hash;
+
+        let dir = tempdir()?;
+        let keypair = SecureKeypair::generate();
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet =
+            mock_wallet_with_signer(rpc.clone(), dir.path(), Arc::new(keypair.clone()))?;
+        wallet.config.wallet.encryption.kdf = KdfAlgorithm::Pbkdf2 { iterations: 1_000 };
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        store(&wallet, &keypair, &passphrase).await?;
+
+        // Online: build on an explicit blockhash, knowing only the public key
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000_000),
+            memo: None,
+        };
+        let blockhash = Hash::new_unique();
+        let transaction = TransactionBuilder::new().build_unsigned(
+            &action,
+            &AgentContext::new(keypair.public_key()),
+            &TransactionOptions::default(),
+            blockhash,
+        )?;
+        let unsigned = UnsignedEnvelope::new(&transaction, Some(150)).with_action(action);
+        assert_eq!(unsigned.signers, vec![keypair.public_key()]);
+
+        // Air-gapped: the wallet loads and signs without reaching a cluster
+        let offline = Wallet::load_offline("mock", &passphrase, wallet.config.clone()).await?;
+        assert!(matches!(
+            offline.rpc_client.get_latest_blockhash().await,
+            Err(Error::NotSupported(_))
+        ));
+        let signed = offline
+            .sign_offline(&UnsignedEnvelope::from_json(&unsigned.to_json()?)?)
+            .await?;
+        let signed = SignedEnvelope::from_json(&signed.to_json()?)?;
+
+        // The signature covers exactly the message that was built
+        let transaction = signed.transaction()?;
+        assert_eq!(transaction.message, unsigned.message()?);
+        assert_eq!(transaction.message.recent_blockhash, blockhash);
+        assert!(transaction.signatures[0]
+            .verify(keypair.public_key().as_ref(), &transaction.message_data()));
+
+        // Online again: a tampered copy is refused, the signed one is sent
+        let mut tampered = transaction.clone();
+        tampered.message.recent_blockhash = Hash::new_unique();
+        let tampered = SignedEnvelope::new(&tampered, signed.expiry_slot)?;
+        assert!(tampered.broadcast(rpc.as_ref()).await.is_err());
+        assert_eq!(
+            signed.broadcast(rpc.as_ref()).await?,
+            transaction.signatures[0]
+        );
+        assert_eq!(rpc.sent_transactions(), vec![transaction]);
+
+        // Past its expiry slot the envelope is not broadcast
+        rpc.set_slot(151);
+        assert!(matches!(
+            signed.broadcast(rpc.as_ref()).await,
+            Err(Error::BlockhashExpired(_))
+        ));
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_keypair_round_trips_through_solana_sdk() -> Result<()> {
+        let dir = tempdir()?;
+        let keypair = SecureKeypair::generate();
+        let wallet = mock_wallet_with_signer(
+            Arc::new(MockRpc::new()),
+            dir.path(),
+            Arc::new(keypair.clone()),
+        )?;
+        let passphrase = Zeroizing::new("passphrase".to_string());
+        store(&wallet, &keypair, &passphrase).await?;
+        let path = dir.path().join("id.json");
+
+        // Agents below Administrator cannot export
+        let result = wallet.export_keypair(&passphrase, &path, false).await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+        assert!(!path.exists());
+
+        grant(&wallet, PermissionLevel::Administrator).await;
+        wallet.export_keypair(&passphrase, &path, false).await?;
+        let solana = solana_sdk::signature::read_keypair_file(&path)
+            .map_err(|e| Error::Keypair(e.to_string()))?;
+        assert_eq!(solana.pubkey(), wallet.public_key());
+        let imported = SecureKeypair::from_solana_json_file(&path)?;
+        assert_eq!(imported.public_key(), wallet.public_key());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // An existing file is kept unless the export is forced
+        std::fs::write(&path, b"keep")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        }
+        let result = wallet.export_keypair(&passphrase, &path, false).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert_eq!(std::fs::read(&path)?, b"keep");
+        wallet.export_keypair(&passphrase, &path, true).await?;
+        assert_eq!(
+            SecureKeypair::from_solana_json_file(&path)?.public_key(),
+            wallet.public_key()
+        );
+        // A forced overwrite does not keep the old file's looser mode
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A wrong passphrase exports nothing, even for an administrator
+        std::fs::remove_file(&path)?;
+        let wrong = Zeroizing::new("wrong".to_string());
+        assert!(wallet.export_keypair(&wrong, &path, false).await.is_err());
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stake_needs_the_balance_for_amount_rent_and_fee() -> Result<()> {
+        use solana_sdk::account::Account;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        grant(&wallet, PermissionLevel::Advanced).await;
+        let validator = Pubkey::new_unique();
+        rpc.set_account(
+            validator,
+            Account::new(1, 3762, &solana_sdk::vote::program::id()),
+        );
+
+        // The amount alone fits, but not with the stake account's rent and the fee
+        let amount = Lamports::from_sol_str("0.5")?;
+        rpc.set_balance(wallet.public_key(), amount.as_u64() + 1);
+        let result = wallet.stake_sol(&validator, amount).await;
+        assert!(matches!(result, Err(Error::InsufficientFunds { .. })));
+        assert!(rpc.sent_transactions().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stake_and_unstake_follow_activation_state() -> Result<()> {
+        use solana_client::rpc_response::{RpcStakeActivation, StakeActivationState};
+        use solana_sdk::account::Account;
+        use solana_sdk::stake::state::{Delegation, Meta, Stake, StakeFlags, StakeStateV2};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Finalized))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        grant(&wallet, PermissionLevel::Advanced).await;
+        rpc.set_balance(wallet.public_key(), 5_000_000_000);
+        let validator = Pubkey::new_unique();
+        rpc.set_account(
+            validator,
+            Account::new(1, 3762, &solana_sdk::vote::program::id()),
+        );
+
+        // Seed 0 is taken, so the new stake account uses seed 1
+        let taken = stake::stake_account_address(&wallet.public_key(), 0)?;
+        rpc.set_account(taken, Account::new(1, 0, &solana_sdk::stake::program::id()));
+        let stake_account = wallet
+            .stake_sol(&validator, Lamports::from_sol_str("0.5")?)
+            .await?;
+        assert_eq!(
+            stake_account,
+            stake::stake_account_address(&wallet.public_key(), 1)?
+        );
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        assert!(rpc.sent_transactions()[0]
+            .message
+            .account_keys
+            .contains(&stake_account));
+        assert_eq!(
+            wallet
+                .get_agent_context()
+                .await?
+                .spending_limits
+                .remaining_daily_budget_lamports,
+            Lamports::from_sol_str("9.5")?
+        );
+
+        // Withdrawing during the cooldown epoch fails before anything is signed
+        rpc.set_stake_activation(
+            stake_account,
+            RpcStakeActivation {
+                state: StakeActivationState::Deactivating,
+                active: 500_000_000,
+                inactive: 0,
+            },
+        );
+        let result = wallet.unstake_sol(&stake_account).await;
+        assert!(matches!(
+            result,
+            Err(Error::StakeCooldown { stake_account: account, active: 500_000_000 })
+                if account == stake_account
+        ));
+        assert_eq!(rpc.sent_transactions().len(), 1);
+
+        // An agent unstaking by vote account reaches the delegated stake account
+        let mut data = bincode::serialize(&StakeStateV2::Stake(
+            Meta::default(),
+            Stake {
+                delegation: Delegation::new(&validator, 500_000_000, 0),
+                credits_observed: 0,
+            },
+            StakeFlags::empty(),
+        ))?;
+        data.resize(StakeStateV2::size_of(), 0);
+        rpc.set_account(
+            stake_account,
+            Account {
+                lamports: 502_282_880,
+                data,
+                owner: solana_sdk::stake::program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        rpc.set_balance(stake_account, 502_282_880);
+        rpc.set_stake_activation(
+            stake_account,
+            RpcStakeActivation {
+                state: StakeActivationState::Inactive,
+                active: 0,
+                inactive: 500_000_000,
+            },
+        );
+        let action = AgentAction::UnstakeTokens {
+            staking_pool: validator,
+            amount: Lamports::new(502_282_880),
+        };
+        wallet
+            .execute_action(&action, &TransactionOptions::default())
+            .await?;
+        let withdraw = &rpc.sent_transactions()[1];
+        assert!(withdraw.message.account_keys.contains(&stake_account));
+        assert!(withdraw
+            .message
+            .account_keys
+            .contains(&solana_sdk::stake::program::id()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_transfer_stops_at_first_failed_chunk() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        // Two sends go through, the third chunk hits the rate limit
+        wallet.rate_limiter = Arc::new(TokenBucket::per_minute(2));
+
+        let amount = Lamports::from_sol_str("0.001")?;
+        let recipients: Vec<(Pubkey, Lamports)> =
+            (0..60).map(|_| (Pubkey::new_unique(), amount)).collect();
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let report = wallet
+            .batch_transfer_sol_with_options(&recipients, None, &options)
+            .await?;
+
+        assert!(!report.is_complete());
+        assert!(matches!(report.error, Some(Error::RateLimitExceeded(_))));
+        assert_eq!(report.receipts.len(), 2);
+        assert_eq!(rpc.sent_transactions().len(), 2);
+        assert!(!report.failed.is_empty());
+
+        // Every recipient is accounted for exactly once, in order
+        let accounted: Vec<Pubkey> = report
+            .succeeded
+            .iter()
+            .chain(&report.failed)
+            .chain(&report.unsent)
+            .map(|(to, _)| *to)
+            .collect();
+        let expected: Vec<Pubkey> = recipients.iter().map(|(to, _)| *to).collect();
+        assert_eq!(accounted, expected);
+        Ok(())
+    }
+
+    /// Signer claiming one key but signing with another
+    struct ImpostorSigner {
+        claimed: Pubkey,
+        actual: SecureKeypair,
+    }
+
+    impl crate::signer::TransactionSigner for ImpostorSigner {
+        fn pubkey(&self) -> impl std::future::Future<Output = Pubkey> + Send {
+            std::future::ready(self.claimed)
+        }
+
+        fn sign_message(
+            &self,
+            message: &[u8],
+        ) -> impl std::future::Future<Output = Result<Signature>> + Send {
+            std::future::ready(Ok(self.actual.sign(message)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signature_from_wrong_key_is_never_sent() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let signer = Arc::new(ImpostorSigner {
+            claimed: Pubkey::new_unique(),
+            actual: SecureKeypair::generate(),
+        });
+        let wallet = mock_wallet_with_signer(rpc.clone(), dir.path(), signer)?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let result = wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.5")?,
+                None,
+                &options,
+            )
+            .await;
+        let key = wallet.public_key();
+        assert!(matches!(result, Err(Error::SignerMismatch { expected }) if expected == key));
+        assert!(rpc.sent_transactions().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_templated_transfer_matches_full_build() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(250_000_000),
+            memo: Some("periodic".to_string()),
+        };
+
+        wallet.execute_action(&action, &options).await?;
+        let template = ActionTemplate::from_action(&action)
+            .ok_or_else(|| Error::validation("transfer should be templatable"))?;
+        let prepared = wallet.prepare_templates(vec![template], &options).await?;
+        assert_eq!(prepared, 1);
+        wallet.execute_action(&action, &options).await?;
+
+        let mut sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 2);
+        for transaction in &mut sent {
+            transaction.message.recent_blockhash = Default::default();
+        }
+        assert_eq!(sent[0].message, sent[1].message);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_payment_references_bypass_prepared_templates() -> Result<()> {
+        use crate::solana_pay::TransferRequest;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Confirmed))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let (recipient, reference) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let template = ActionTemplate::TransferSol {
+            to: recipient,
+            memo: Some("order-17".to_string()),
+        };
+        let prepared = wallet
+            .prepare_templates(vec![template], &TransactionOptions::default())
+            .await?;
+        assert_eq!(prepared, 1);
+
+        let request = TransferRequest::parse(&format!(
+            "solana:{}?amount=0.25&reference={}&memo=order-17",
+            recipient, reference
+        ))?;
+        wallet.pay(&request).await?;
+
+        let sent = rpc.sent_transactions();
+        let [transaction] = sent.as_slice() else {
+            panic!("expected one transaction, got {}", sent.len());
+        };
+        assert!(transaction.message.account_keys.contains(&reference));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multisig_wallet_collects_cosignature_before_sending() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let mut wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let alice = SecureKeypair::generate();
+        let bob = SecureKeypair::generate();
+        wallet.multisig = Some(MultisigConfig::new(
+            vec![alice.public_key(), bob.public_key()],
+            2,
+        )?);
 
-        // Send transaction
-        rpc_client.send_transaction(transaction).await?;
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(100_000_000),
+            memo: None,
+        };
 
-        // Update agent context
-        let mut agent_context = self.agent_context.write().await;
-        agent_context.record_success();
+        // Sending directly is refused
+        assert!(matches!(
+            wallet.execute_action(&action, &options).await,
+            Err(Error::PermissionDenied(_))
+        ));
 
-        Ok(signature)
-    }
+        let mut transaction = wallet
+            .build_partially_signed(&action, &[alice.public_key()], &options)
+            .await?;
+        assert!(wallet
+            .send_signed_transaction(&transaction, &options)
+            .await
+            .is_err());
 
-    /// Simulate a transaction
-    pub async fn simulate_transaction(
-        &self,
-        transaction: &Transaction,
-    ) -> Result<SimulationResult> {
-        let rpc_client = self.rpc_client.read().await;
-        let transaction_builder = self.transaction_builder.lock().await;
-        let options = TransactionOptions::default();
+        let mut alices_copy =
+            multisig::decode_transaction(&multisig::encode_transaction(&transaction)?)?;
+        let signature = multisig::partial_sign(&mut alices_copy, &alice)?;
+        Wallet::add_signature(&mut transaction, signature, &alice.public_key())?;
 
-        transaction_builder
-            .simulate_transaction(transaction, &rpc_client, &options)
+        // The transfer is checked against the budget left when it is sent
+        let budget = wallet
+            .agent_context
+            .read()
+            .await
+            .spending_limits
+            .remaining_daily_budget_lamports;
+        wallet
+            .agent_context
+            .write()
             .await
+            .spending_limits
+            .remaining_daily_budget_lamports = Lamports::new(50_000_000);
+        assert!(wallet
+            .send_signed_transaction(&transaction, &options)
+            .await
+            .is_err());
+        assert!(rpc.sent_transactions().is_empty());
+        wallet
+            .agent_context
+            .write()
+            .await
+            .spending_limits
+            .remaining_daily_budget_lamports = budget;
+
+        wallet
+            .send_signed_transaction(&transaction, &options)
+            .await?;
+        assert_eq!(rpc.sent_transactions(), vec![transaction]);
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            budget.saturating_sub(Lamports::new(100_000_000))
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+        Ok(())
     }
 
-    /// Validate a transaction
-    pub async fn validate_transaction(
-        &self,
-        transaction: &Transaction,
-    ) -> Result<ValidationResult> {
-        let transaction_builder = self.transaction_builder.lock().await;
-        let agent_context = self.agent_context.read().await;
-        let options = TransactionOptions::default();
+    #[tokio::test]
+    async fn test_transfer_confirmation_timeout_records_failure() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Processed))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::Finalized {
+                timeout: Duration::from_millis(50),
+            },
+            ..Default::default()
+        };
+        let result = wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.5")?,
+                None,
+                &options,
+            )
+            .await;
 
-        Ok(transaction_builder.validate_transaction(transaction, &agent_context, &options))
+        assert!(matches!(
+            result,
+            Err(Error::ConfirmationTimeout {
+                last_status: Some(TransactionConfirmationStatus::Processed),
+                ..
+            })
+        ));
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(context.decision_count, 1);
+        assert_eq!(context.recent_errors.len(), 1);
+        assert!(context.success_rate < 1.0);
+        Ok(())
     }
 
-    /// Get wallet information
-    pub async fn get_info(&self) -> Result<WalletInfo> {
-        let metadata = self.metadata.read().await;
-        let balance = self.get_balance().await?;
-        let balance_lamports = (balance * 1_000_000_000.0).round() as u64;
+    #[tokio::test]
+    async fn test_timed_out_transfer_holds_budget_until_reconciled() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Processed))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
 
-        // Get transaction count (simplified - would need to query history)
-        let transaction_count = 0;
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::Finalized {
+                timeout: Duration::from_millis(50),
+            },
+            ..Default::default()
+        };
+        assert!(wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.5")?,
+                None,
+                &options
+            )
+            .await
+            .is_err());
 
-        Ok(WalletInfo {
-            name: metadata.name.clone(),
-            public_key: metadata.public_key,
-            created_at: metadata.created_at,
-            last_accessed: metadata.last_accessed,
-            balance_lamports,
-            transaction_count,
-            permission_level: PermissionLevel::Basic, // Default
-            is_active: self.is_loaded,
-        })
+        // Not charged yet, but not available to other actions either
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("10")?
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::new(500_000_000));
+
+        let settled = wallet.reconcile_budget().await?;
+        assert_eq!(settled.committed_lamports(), Lamports::new(500_000_000));
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("9.5")?
+        );
+        Ok(())
     }
 
-    /// Get agent context
-    pub async fn get_agent_context(&self) -> Result<AgentContext> {
-        let agent_context = self.agent_context.read().await;
-        Ok(agent_context.clone())
+    /// Escalation options confirming at `confirmed` with priority fees of at most 1_000 lamports
+    fn escalation_options() -> TransactionOptions {
+        TransactionOptions {
+            confirmation: ConfirmationStrategy::Confirmed {
+                timeout: Duration::from_secs(10),
+            },
+            max_priority_fee_lamports: Some(1_000),
+            ..Default::default()
+        }
     }
 
-    /// Update agent context with current wallet state
-    async fn update_agent_context(&self) -> Result<()> {
-        let mut agent_context = self.agent_context.write().await;
+    #[tokio::test]
+    async fn test_escalation_reports_late_confirmation_of_first_broadcast() -> Result<()> {
+        use solana_sdk::compute_budget::ComputeBudgetInstruction;
 
-        // Update wallet balance
-        let balance = self.get_balance().await?;
-        agent_context.wallet_balance = balance;
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.set_prioritization_fees(vec![0, 4_000, 3_000, 6_000]);
+        // Unseen for two polls, then confirmed: after the rebroadcast went out
+        rpc.script_default_statuses(vec![
+            None,
+            None,
+            Some(status(TransactionConfirmationStatus::Confirmed)),
+        ]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let sink = Arc::new(MemoryAuditSink::new());
+        wallet.set_audit_sink(Some(sink.clone())).await;
 
-        // Update token balances
-        // This is simplified - would need to fetch all token accounts
-        agent_context.token_balances.clear();
+        // A zero wait polls once per broadcast, making the schedule deterministic
+        let policy = EscalationPolicy::default().with_wait(Duration::ZERO);
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(500_000_000),
+            memo: None,
+        };
+        let report = wallet
+            .send_with_escalation(&action, &escalation_options(), &policy)
+            .await?;
 
-        // Update timestamp
-        agent_context.update_timestamp();
+        // Median fee 4_000 doubled to 8_000, capped at 1_000 lamports / 200_000 units
+        assert_eq!(report.compute_unit_prices, vec![4_000, 5_000]);
+        assert_eq!(report.signatures.len(), 2);
+        assert_eq!(report.landed, report.signatures[0]);
+        assert_eq!(
+            report.superseded().collect::<Vec<_>>(),
+            vec![&report.signatures[1]]
+        );
 
-        // Reset daily budget if needed
-        agent_context.reset_daily_budget_if_needed();
+        let sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 2);
+        assert_ne!(
+            sent[0].message.recent_blockhash,
+            sent[1].message.recent_blockhash
+        );
+        let price = ComputeBudgetInstruction::set_compute_unit_price(5_000).data;
+        assert!(sent[1]
+            .message
+            .instructions
+            .iter()
+            .any(|instruction| instruction.data == price));
 
+        // Both broadcasts are audited; only the landed one is confirmed
+        let outcomes: Vec<_> = sink
+            .entries()
+            .into_iter()
+            .filter(|e| e.phase == AuditPhase::Outcome)
+            .map(|e| (e.signature, e.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (Some(report.signatures[0]), Some(AuditOutcome::Confirmed)),
+                (Some(report.signatures[1]), Some(AuditOutcome::Unconfirmed)),
+            ]
+        );
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("9.5")?
+        );
+        assert_eq!(wallet.budget().reserved(), Lamports::ZERO);
         Ok(())
     }
 
-    /// List all wallets in storage
-    pub async fn list_wallets(config: &WalletConfig) -> Result<Vec<WalletInfo>> {
-        let storage_service = StorageService::new(config.wallet.storage.clone())?;
-        storage_service.list_wallets().await
-    }
+    #[tokio::test]
+    async fn test_escalation_does_not_rebroadcast_processed_transaction() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        // Processed just after the first wait ran out, confirmed on the next poll
+        rpc.script_default_statuses(vec![
+            None,
+            Some(status(TransactionConfirmationStatus::Processed)),
+            Some(status(TransactionConfirmationStatus::Confirmed)),
+        ]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
 
-    /// Delete wallet from storage
-    pub async fn delete(name: impl Into<String>, config: &WalletConfig) -> Result<()> {
-        let name = name.into();
-        let storage_service = StorageService::new(config.wallet.storage.clone())?;
-        storage_service.delete_wallet(&name).await
-    }
+        let policy = EscalationPolicy::default().with_wait(Duration::ZERO);
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(500_000_000),
+            memo: None,
+        };
+        let report = wallet
+            .send_with_escalation(&action, &escalation_options(), &policy)
+            .await?;
 
-    /// Check if wallet exists in storage
-    pub async fn exists(name: impl Into<String>, config: &WalletConfig) -> Result<bool> {
-        let name = name.into();
-        let storage_service = StorageService::new(config.wallet.storage.clone())?;
-        Ok(storage_service.wallet_exists(&name))
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        assert_eq!(report.signatures, vec![report.landed]);
+        assert_eq!(
+            report.compute_unit_prices,
+            vec![policy.min_compute_unit_price]
+        );
+        Ok(())
     }
 
-    /// Get RPC client for direct access (advanced usage)
-    pub fn rpc_client(&self) -> Arc<RwLock<RpcClient>> {
-        self.rpc_client.clone()
-    }
+    #[tokio::test]
+    async fn test_escalation_waits_for_blockhash_expiry_before_rebroadcast() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.keep_blockhashes_valid();
+        // Unseen when the first wait runs out, processed while its blockhash is still valid
+        rpc.script_default_statuses(vec![
+            None,
+            None,
+            Some(status(TransactionConfirmationStatus::Processed)),
+            Some(status(TransactionConfirmationStatus::Confirmed)),
+        ]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
 
-    /// Get token manager for direct access (advanced usage)
-    pub fn token_manager(&self) -> Arc<RwLock<TokenManager>> {
-        self.token_manager.clone()
-    }
+        let policy = EscalationPolicy::default()
+            .with_wait(Duration::ZERO)
+            .with_poll_interval(Duration::from_millis(1))
+            .with_expiry_wait(Duration::from_secs(5));
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(500_000_000),
+            memo: None,
+        };
+        let report = wallet
+            .send_with_escalation(&action, &escalation_options(), &policy)
+            .await?;
 
-    /// Get transaction builder for direct access (advanced usage)
-    pub fn transaction_builder(&self) -> Arc<Mutex<TransactionBuilder>> {
-        self.transaction_builder.clone()
+        // The first broadcast landed late and was never sent again
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        assert_eq!(report.signatures, vec![report.landed]);
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("9.5")?
+        );
+        Ok(())
     }
 
-    /// Get wallet configuration
-    pub fn config(&self) -> &WalletConfig {
-        &self.config
-    }
+    #[tokio::test]
+    async fn test_tampered_address_book_blocks_transfers() -> Result<()> {
+        use crate::address_book::{AddressBook, Contact, IntegrityPolicy};
 
-    /// Check if wallet is loaded
-    pub fn is_loaded(&self) -> bool {
-        self.is_loaded
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+
+        let operator = SecureKeypair::generate();
+        let approver = SecureKeypair::generate();
+        let path = dir.path().join("contacts.json");
+        let mut book = AddressBook::new(&path);
+        book.enable_integrity(IntegrityPolicy::new(vec![approver.public_key()], 1))?;
+        book.save(&operator)?;
+
+        // Smuggle a whitelisted contact into the signed file
+        let recipient = Pubkey::new_unique();
+        let mut raw: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+        raw["contacts"] = serde_json::json!([{
+            "contact": Contact::whitelisted("attacker", recipient),
+            "activation": null,
+        }]);
+        std::fs::write(&path, serde_json::to_vec(&raw)?)?;
+
+        let book = AddressBook::load(&path, &operator.public_key())?;
+        wallet.set_recipient_whitelist(Some(book)).await;
+
+        let result = wallet
+            .transfer_sol(&recipient, Lamports::from_sol_str("0.1")?, None)
+            .await;
+        assert!(matches!(result, Err(Error::WhitelistIntegrity(_))));
+        assert!(rpc.sent_transactions().is_empty());
+        Ok(())
     }
-}
 
-impl Drop for Wallet {
-    fn drop(&mut self) {
-        if self.is_loaded {
-            log::debug!("Wallet '{}' dropped", self.name);
+    #[tokio::test]
+    async fn test_refresh_token_balances_keeps_totals_of_stale_mints() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        let owner = wallet.public_key();
+        let (split, whole) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (first, second, third) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        rpc.set_token_account(first, split, owner, 100)?;
+        rpc.set_token_account(second, split, owner, 100)?;
+        rpc.set_token_account(third, whole, owner, 100)?;
+        {
+            let token_manager = wallet.token_manager.read().await;
+            for address in [first, second, third] {
+                token_manager.get_token_account_info(&address).await?;
+            }
         }
+        let report = wallet.refresh_token_balances(2).await;
+        assert!(report.is_complete());
+        assert_eq!(
+            wallet.agent_context.read().await.token_balances.get(&split),
+            Some(&200)
+        );
+
+        // One of the two accounts of `split` fails: its total is not halved
+        rpc.set_token_account(first, split, owner, 500)?;
+        rpc.set_token_account(third, whole, owner, 500)?;
+        rpc.fail_account(second);
+        let report = wallet.refresh_token_balances(2).await;
+        assert_eq!(report.stale_mints, vec![split]);
+
+        let context = wallet.agent_context.read().await;
+        assert_eq!(context.token_balances.get(&split), Some(&200));
+        assert_eq!(context.token_balances.get(&whole), Some(&500));
+        assert_eq!(context.stale_token_mints, vec![split]);
+        Ok(())
     }
-}
 
-// Implement Signer trait for compatibility with Solana ecosystem
-impl Signer for Wallet {
-    fn try_pubkey(&self) -> std::result::Result<Pubkey, solana_sdk::signer::SignerError> {
-        Ok(self.public_key())
+    #[tokio::test]
+    async fn test_token_transfer_receipt_itemizes_new_token_account_rent() -> Result<()> {
+        use spl_associated_token_account::get_associated_token_address;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 10_000_000);
+
+        let (mint, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_token_account(
+            get_associated_token_address(&wallet.public_key(), &mint),
+            mint,
+            wallet.public_key(),
+            1_000,
+        )?;
+
+        wallet
+            .agent_context
+            .write()
+            .await
+            .spending_limits
+            .token_limits
+            .insert(mint, TokenLimit::new(800, 600));
+
+        let action = AgentAction::TransferToken {
+            mint,
+            to: recipient,
+            amount: 500,
+            memo: None,
+        };
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let receipt = wallet
+            .execute_action_with_receipt(&action, &options)
+            .await?;
+
+        let rent = rpc
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .await?;
+        assert_eq!(
+            receipt.rent_costs,
+            vec![(get_associated_token_address(&recipient, &mint), rent)]
+        );
+        assert_eq!(receipt.transfer_lamports, 0);
+        assert_eq!(receipt.fee_lamports, 5_000);
+
+        // Rent is reclaimable and does not eat into the daily budget; the
+        // tokens come out of their own limit
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(context.spending_limits.reclaimable_rent_lamports, rent);
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::new(10 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(
+            context
+                .spending_limits
+                .token_limits
+                .get(&mint)
+                .map(|limit| limit.remaining),
+            Some(300)
+        );
+
+        // A second transfer of 500 would overdraw the remaining 300
+        let result = wallet.execute_action_with_receipt(&action, &options).await;
+        assert!(matches!(result, Err(Error::LimitExceeded(_))));
+        Ok(())
     }
 
-    fn try_sign_message(
-        &self,
-        message: &[u8],
-    ) -> std::result::Result<Signature, solana_sdk::signer::SignerError> {
-        let keypair = self.keypair.blocking_read();
-        Ok(keypair.sign(message))
+    #[tokio::test]
+    async fn test_validation_requires_rent_only_for_new_token_accounts() -> Result<()> {
+        use spl_associated_token_account::get_associated_token_address;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 10_000_000);
+
+        let (mint, fresh, existing) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        rpc.set_token_account(
+            get_associated_token_address(&wallet.public_key(), &mint),
+            mint,
+            wallet.public_key(),
+            1_000,
+        )?;
+        rpc.set_token_account(
+            get_associated_token_address(&existing, &mint),
+            mint,
+            existing,
+            1_000,
+        )?;
+        wallet
+            .agent_context
+            .write()
+            .await
+            .spending_limits
+            .token_limits
+            .insert(mint, TokenLimit::new(800, 600));
+
+        let context = wallet.get_agent_context().await?;
+        let validate = |to: Pubkey| {
+            let (wallet, context) = (&wallet, &context);
+            async move {
+                let action = AgentAction::TransferToken {
+                    mint,
+                    to,
+                    amount: 500,
+                    memo: None,
+                };
+                let transaction = wallet.transaction_builder.lock().await.build_from_action(
+                    &action,
+                    context,
+                    &TransactionOptions::default(),
+                )?;
+                wallet.validate_transaction(&transaction).await
+            }
+        };
+
+        // A fresh recipient needs a token account, paid for by the wallet
+        let rent = rpc
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .await?;
+        let validation = validate(fresh).await?;
+        assert!(validation.is_valid);
+        let required = validation.required_breakdown.unwrap_or_default();
+        assert_eq!(required.rent, rent);
+        assert_eq!(required.transfers, 0);
+        assert_eq!(required.fees, 5_000);
+
+        // An existing one does not
+        let validation = validate(existing).await?;
+        assert!(validation.is_valid);
+        let required = validation.required_breakdown.unwrap_or_default();
+        assert_eq!(required.rent, 0);
+        assert_eq!(required.total(), 5_000);
+
+        // Without the rent, the fresh recipient cannot be paid
+        rpc.set_balance(wallet.public_key(), rent);
+        let validation = validate(fresh).await?;
+        assert!(!validation.is_valid);
+        assert_eq!(
+            validation
+                .required_breakdown
+                .map(|required| required.spent()),
+            Some(rent + 5_000)
+        );
+        assert!(validate(existing).await?.is_valid);
+        Ok(())
     }
 
-    fn is_interactive(&self) -> bool {
-        false
+    #[tokio::test]
+    async fn test_token_transfer_to_a_token_account_credits_it_directly() -> Result<()> {
+        use spl_associated_token_account::get_associated_token_address;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 10_000_000);
+        let (mint, vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_token_account(
+            get_associated_token_address(&wallet.public_key(), &mint),
+            mint,
+            wallet.public_key(),
+            1_000,
+        )?;
+        rpc.set_token_account(vault, mint, Pubkey::new_unique(), 1_000)?;
+        wallet
+            .agent_context
+            .write()
+            .await
+            .spending_limits
+            .token_limits
+            .insert(mint, TokenLimit::new(800, 800));
+
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        wallet
+            .transfer_token_with_options(&mint, &vault, 500, None, &options)
+            .await?;
+
+        // No associated account of the token account is derived or created
+        let sent = rpc.sent_transactions();
+        let keys = &sent[0].message.account_keys;
+        assert!(keys.contains(&vault));
+        assert!(!keys.contains(&spl_associated_token_account::id()));
+        assert!(!keys.contains(&get_associated_token_address(&vault, &mint)));
+
+        // Program ids are never valid recipients
+        let result = wallet
+            .transfer_token_with_options(&mint, &spl_token::id(), 1, None, &options)
+            .await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        Ok(())
     }
-}
 
-/// Wallet builder for creating wallets with custom configuration
-pub struct WalletBuilder {
-    name: Option<String>,
-    config: WalletConfig,
-}
+    #[tokio::test]
+    async fn test_transfers_beyond_rate_limit_are_rejected() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
 
-impl WalletBuilder {
-    /// Create a new wallet builder
-    pub fn new() -> Self {
-        Self {
-            name: None,
-            config: WalletConfig::default(),
+        let options = TransactionOptions {
+            confirmation: ConfirmationStrategy::None,
+            ..Default::default()
+        };
+        let limit = wallet.config.agent.limits.max_transactions_per_minute as usize;
+        for _ in 0..limit {
+            wallet
+                .transfer_sol_with_options(
+                    &Pubkey::new_unique(),
+                    Lamports::from_sol_str("0.01")?,
+                    None,
+                    &options,
+                )
+                .await?;
         }
+        let result = wallet
+            .transfer_sol_with_options(
+                &Pubkey::new_unique(),
+                Lamports::from_sol_str("0.01")?,
+                None,
+                &options,
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::RateLimitExceeded(_))));
+        assert_eq!(rpc.sent_transactions().len(), limit);
+        assert_eq!(wallet.get_agent_context().await?.recent_errors.len(), 1);
+        Ok(())
     }
 
-    /// Set wallet name
-    pub fn name(mut self, name: impl Into<String>) -> Self {
-        self.name = Some(name.into());
-        self
+    #[tokio::test]
+    async fn test_ensure_wrapped_sol_wraps_only_the_shortfall() -> Result<()> {
+        use solana_sdk::system_instruction::SystemInstruction;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Finalized))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 5_000_000_000);
+
+        let wsol_account = spl_associated_token_account::get_associated_token_address(
+            &wallet.public_key(),
+            &token::NATIVE_MINT,
+        );
+        let mut wrapped = token_account(token::NATIVE_MINT, wallet.public_key(), 400_000)?;
+        wrapped.lamports += 400_000;
+        rpc.set_account(wsol_account, wrapped);
+
+        // Enough is already wrapped: nothing is sent
+        assert!(wallet.ensure_wrapped_sol(300_000).await?.is_none());
+        assert!(rpc.sent_transactions().is_empty());
+
+        // Only the missing 600_000 lamports are moved in
+        assert!(wallet.ensure_wrapped_sol(1_000_000).await?.is_some());
+        let sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert!(matches!(
+            bincode::deserialize(&sent[0].message.instructions[1].data)?,
+            SystemInstruction::Transfer { lamports: 600_000 }
+        ));
+
+        assert!(matches!(
+            wallet.ensure_wrapped_sol(10_000_000_000).await,
+            Err(Error::InsufficientFunds { .. })
+        ));
+        Ok(())
     }
 
-    /// Set wallet configuration
-    pub fn config(mut self, config: WalletConfig) -> Self {
-        self.config = config;
-        self
+    #[tokio::test]
+    async fn test_preview_action_reports_balance_changes() -> Result<()> {
+        use solana_account_decoder::{UiAccount, UiAccountEncoding};
+        use solana_client::rpc_response::RpcSimulateTransactionResult;
+        use solana_sdk::{account::Account, system_program};
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        let recipient = Pubkey::new_unique();
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        rpc.set_account(
+            wallet.public_key(),
+            Account::new(2_000_000_000, 0, &system_program::id()),
+        );
+
+        let encode = |address: &Pubkey, lamports: u64| {
+            UiAccount::encode(
+                address,
+                &Account::new(lamports, 0, &system_program::id()),
+                UiAccountEncoding::Base64,
+                None,
+                None,
+            )
+        };
+        rpc.set_simulation(RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(vec![
+                "Program 11111111111111111111111111111111 success".to_string()
+            ]),
+            accounts: Some(vec![
+                Some(encode(&wallet.public_key(), 1_998_995_000)),
+                Some(encode(&recipient, 1_000_000)),
+            ]),
+            units_consumed: Some(150),
+            return_data: None,
+            inner_instructions: None,
+        });
+
+        let action = AgentAction::TransferSol {
+            to: recipient,
+            amount: Lamports::new(1_000_000),
+            memo: None,
+        };
+        let preview = wallet.preview_action(&action).await?;
+        assert_eq!(
+            rpc.simulated_accounts(),
+            vec![wallet.public_key(), recipient]
+        );
+        // The transfer plus the fee leave the wallet
+        assert_eq!(preview.lamports_delta, -1_005_000);
+        assert_eq!(preview.fee, 5_000);
+        assert!(preview.token_deltas.is_empty());
+        assert_eq!(preview.logs.len(), 1);
+        assert!(rpc.sent_transactions().is_empty());
+        Ok(())
     }
 
-    /// Build and create the wallet
-    pub async fn create(self, passphrase: &Zeroizing<String>) -> Result<Wallet> {
-        let name = self
-            .name
-            .ok_or_else(|| Error::config("Wallet name is required"))?;
-        Wallet::create(name, passphrase, self.config).await
+    #[tokio::test]
+    async fn test_failed_simulation_aborts_send() -> Result<()> {
+        use solana_client::rpc_response::RpcSimulateTransactionResult;
+        use solana_sdk::transaction::TransactionError;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Finalized))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        rpc.set_simulation(RpcSimulateTransactionResult {
+            err: Some(TransactionError::InsufficientFundsForRent { account_index: 1 }),
+            logs: Some(vec![
+                "Program log: recipient below rent exemption".to_string()
+            ]),
+            accounts: None,
+            units_consumed: Some(150),
+            return_data: None,
+            inner_instructions: None,
+        });
+
+        let action = AgentAction::TransferSol {
+            to: Pubkey::new_unique(),
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        let result = wallet
+            .execute_action(&action, &TransactionOptions::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::TransactionSimulation(message)) if message.contains("below rent exemption")
+        ));
+        assert!(rpc.sent_transactions().is_empty());
+        let context = wallet.get_agent_context().await?;
+        assert_eq!(
+            context.spending_limits.remaining_daily_budget_lamports,
+            Lamports::from_sol_str("10")?
+        );
+
+        // Opting out sends without simulating
+        let options = TransactionOptions {
+            simulate_before_send: false,
+            ..Default::default()
+        };
+        wallet.execute_action(&action, &options).await?;
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        Ok(())
     }
 
-    /// Build and load an existing wallet
-    pub async fn load(self, passphrase: &Zeroizing<String>) -> Result<Wallet> {
-        let name = self
-            .name
-            .ok_or_else(|| Error::config("Wallet name is required"))?;
-        Wallet::load(name, passphrase, self.config).await
+    #[tokio::test]
+    async fn test_address_policy_blocks_unlisted_destinations() -> Result<()> {
+        use crate::policy::AddressPolicy;
+
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        rpc.script_default_statuses(vec![Some(status(TransactionConfirmationStatus::Finalized))]);
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 2_000_000_000);
+        let (treasury, stranger) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let policy = AddressPolicy::allowlist_only(vec![treasury]);
+
+        // Agents cannot change their own policy
+        assert!(matches!(
+            wallet.set_address_policy(policy.clone()).await,
+            Err(Error::PermissionDenied(_))
+        ));
+        grant(&wallet, PermissionLevel::Administrator).await;
+        wallet.set_address_policy(policy).await?;
+
+        let pay = |to| AgentAction::TransferSol {
+            to,
+            amount: Lamports::new(1_000),
+            memo: None,
+        };
+        match wallet
+            .execute_action(&pay(stranger), &TransactionOptions::default())
+            .await
+        {
+            Err(Error::PermissionDenied(message)) => {
+                assert!(message.contains(&stranger.to_string()))
+            }
+            other => return Err(Error::validation(format!("Unexpected {:?}", other))),
+        }
+        assert!(rpc.sent_transactions().is_empty());
+
+        wallet
+            .execute_action(&pay(treasury), &TransactionOptions::default())
+            .await?;
+        assert_eq!(rpc.sent_transactions().len(), 1);
+        Ok(())
     }
-}
 
-impl Default for WalletBuilder {
-    fn default() -> Self {
-        Self::new()
+    fn history_entry() -> solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature {
+        solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature {
+            signature: Signature::new_unique().to_string(),
+            slot: 10,
+            err: None,
+            memo: None,
+            block_time: Some(1_700_000_000),
+            confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+    #[tokio::test]
+    async fn test_get_info_reports_history_tokens_and_permission() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_balance(wallet.public_key(), 1_500_000_000);
+        rpc.set_history(
+            wallet.public_key(),
+            (0..3).map(|_| history_entry()).collect(),
+        );
+        grant(&wallet, PermissionLevel::Advanced).await;
 
-    // Note: These tests are mostly compile-time checks
-    // Real tests would require a running Solana RPC endpoint
+        // 2.5 tokens of a 6-decimal mint, and a balance of an unreadable mint
+        let (mint, unknown_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(mint, mint_account(6, None)?);
+        for (mint, amount) in [(mint, 2_500_000), (unknown_mint, 7)] {
+            rpc.set_token_account(Pubkey::new_unique(), mint, wallet.public_key(), amount)?;
+        }
 
-    #[test]
-    fn test_wallet_builder_creation() {
-        let builder = WalletBuilder::new();
-        assert!(builder.name.is_none());
+        let info = wallet.get_info().await?;
+        assert_eq!(info.balance_lamports, 1_500_000_000);
+        assert_eq!(info.transaction_count, 3);
+        assert!(!info.transaction_count_truncated);
+        assert_eq!(info.transaction_count_label(), "3");
+        assert_eq!(info.permission_level, PermissionLevel::Advanced);
+
+        let mut tokens = info.token_accounts.clone();
+        tokens.sort_by_key(|summary| summary.ui_amount.is_none());
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].mint, mint);
+        assert_eq!(tokens[0].ui_amount, Some(2.5));
+        assert_eq!(tokens[1].mint, unknown_mint);
+        assert_eq!(tokens[1].ui_amount, None);
+        Ok(())
     }
 
-    #[test]
-    fn test_wallet_builder_with_name() {
-        let builder = WalletBuilder::new().name("test-wallet");
-        assert_eq!(builder.name, Some("test-wallet".to_string()));
+    #[tokio::test]
+    async fn test_get_info_caps_transaction_count() -> Result<()> {
+        let dir = tempdir()?;
+        let rpc = Arc::new(MockRpc::new());
+        let wallet = mock_wallet(rpc.clone(), dir.path())?;
+        rpc.set_history(
+            wallet.public_key(),
+            (0..INFO_TRANSACTION_COUNT_CAP + 5)
+                .map(|_| history_entry())
+                .collect(),
+        );
+
+        let info = wallet.get_info().await?;
+        assert_eq!(info.transaction_count, INFO_TRANSACTION_COUNT_CAP as u64);
+        assert!(info.transaction_count_truncated);
+        assert_eq!(info.transaction_count_label(), "1000+");
+        assert_eq!(rpc.history_calls(), 1);
+        Ok(())
     }
 }
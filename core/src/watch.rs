@@ -0,0 +1,626 @@
+//! Streaming balance changes of a wallet
+//!
+//! A [`BalanceWatcher`] follows the SOL account of a wallet and every token
+//! account it owns, yielding a [`BalanceEvent`] whenever a balance moves, a
+//! token account is opened or one is closed. With [`AccountSubscriptions`]
+//! (such as the websocket [`SubscriptionClient`]) changes arrive as they are
+//! notified; activity of the wallet triggers a rescan of its token accounts,
+//! so a freshly created associated account is subscribed to as soon as it
+//! appears. Without subscriptions the watcher polls at a fixed interval.
+//!
+//! The stream is plain [`Stream`], so it can be printed by the CLI or
+//! forwarded as server-sent events alike.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use agent_wallet_core::rpc::{RpcClient, SubscriptionClient};
+//! use agent_wallet_core::watch::BalanceWatcher;
+//! use futures::StreamExt;
+//! use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+//!
+//! # async fn example(rpc: RpcClient, wallet: Pubkey) -> agent_wallet_core::Result<()> {
+//! let subscriptions =
+//!     SubscriptionClient::new("wss://api.devnet.solana.com", CommitmentConfig::confirmed());
+//! let watcher = BalanceWatcher::new(wallet, Arc::new(rpc))
+//!     .with_subscriptions(Arc::new(subscriptions));
+//!
+//! let mut events = Box::pin(watcher.watch().await?);
+//! while let Some(event) = events.next().await {
+//!     println!("{}", event);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, BoxStream, SelectAll, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
+};
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::rpc::{DynRpcProvider, SubscriptionClient};
+use crate::token::{parse_token_account, TokenManager};
+
+/// How often a watcher without subscriptions polls by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What happened to a watched account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceEventKind {
+    /// The balance moved
+    Changed,
+    /// A token account of the wallet appeared
+    Opened,
+    /// A token account of the wallet was closed
+    Closed,
+}
+
+/// A change of the SOL or a token balance of a watched wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceEvent {
+    /// Account whose balance changed
+    pub account: Pubkey,
+    /// Mint of the token account, `None` for the wallet's SOL
+    pub mint: Option<Pubkey>,
+    /// What happened
+    pub kind: BalanceEventKind,
+    /// Balance before, in lamports or token base units
+    pub previous: u64,
+    /// Balance after, in lamports or token base units
+    pub balance: u64,
+    /// Latest transaction touching the account, if it could be resolved
+    pub signature: Option<Signature>,
+}
+
+impl BalanceEvent {
+    /// Signed change of the balance
+    pub fn delta(&self) -> i128 {
+        i128::from(self.balance) - i128::from(self.previous)
+    }
+}
+
+impl fmt::Display for BalanceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mint {
+            None => write!(f, "SOL")?,
+            Some(mint) => write!(f, "token {} ({})", mint, self.account)?,
+        }
+        match self.kind {
+            BalanceEventKind::Changed => {}
+            BalanceEventKind::Opened => write!(f, " opened")?,
+            BalanceEventKind::Closed => write!(f, " closed")?,
+        }
+        write!(f, ": {:+} -> {}", self.delta(), self.balance)?;
+        if let Some(signature) = &self.signature {
+            write!(f, " ({})", signature)?;
+        }
+        Ok(())
+    }
+}
+
+/// Source of account change notifications
+///
+/// Implemented by [`SubscriptionClient`]; tests drive the watcher with
+/// scripted streams.
+pub trait AccountSubscriptions: Send + Sync {
+    /// Stream the new state of an account each time it changes
+    fn subscribe_account(&self, pubkey: Pubkey) -> Result<BoxStream<'static, Account>>;
+
+    /// Stream the signatures of transactions mentioning an address
+    fn subscribe_activity(&self, address: Pubkey) -> Result<BoxStream<'static, Signature>>;
+}
+
+impl AccountSubscriptions for SubscriptionClient {
+    fn subscribe_account(&self, pubkey: Pubkey) -> Result<BoxStream<'static, Account>> {
+        Ok(SubscriptionClient::subscribe_account(self, pubkey)?.boxed())
+    }
+
+    fn subscribe_activity(&self, address: Pubkey) -> Result<BoxStream<'static, Signature>> {
+        Ok(self
+            .subscribe_logs(address)?
+            .filter_map(|logs| async move { logs.signature.parse().ok() })
+            .boxed())
+    }
+}
+
+/// Watches the SOL and token balances of a wallet
+pub struct BalanceWatcher {
+    owner: Pubkey,
+    rpc: Arc<dyn DynRpcProvider>,
+    subscriptions: Option<Arc<dyn AccountSubscriptions>>,
+    poll_interval: Duration,
+    commitment: CommitmentConfig,
+}
+
+impl BalanceWatcher {
+    /// Watch the wallet `owner`, polling through `rpc`
+    pub fn new(owner: Pubkey, rpc: Arc<dyn DynRpcProvider>) -> Self {
+        Self {
+            owner,
+            rpc,
+            subscriptions: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            commitment: CommitmentConfig::confirmed(),
+        }
+    }
+
+    /// Follow changes through subscriptions instead of polling
+    pub fn with_subscriptions(mut self, subscriptions: Arc<dyn AccountSubscriptions>) -> Self {
+        self.subscriptions = Some(subscriptions);
+        self
+    }
+
+    /// Set how often to poll when there are no subscriptions
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set the commitment token accounts are discovered at
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Wallet being watched
+    pub fn owner(&self) -> Pubkey {
+        self.owner
+    }
+
+    /// Snapshot the current balances and stream their changes
+    ///
+    /// If subscribing fails the watcher falls back to polling. The stream
+    /// ends only when every subscription does.
+    pub async fn watch(self) -> Result<impl Stream<Item = BalanceEvent>> {
+        let tokens = TokenManager::with_provider(self.rpc.clone(), self.commitment);
+        let mut tracked = HashMap::new();
+        tracked.insert(
+            self.owner,
+            Tracked {
+                mint: None,
+                balance: self.rpc.get_balance(&self.owner).await?,
+            },
+        );
+        for info in tokens.get_wallet_token_accounts(&self.owner).await? {
+            tracked.insert(
+                info.address,
+                Tracked {
+                    mint: Some(info.mint),
+                    balance: info.balance,
+                },
+            );
+        }
+
+        let mut state = WatchState {
+            owner: self.owner,
+            rpc: self.rpc,
+            tokens,
+            subscriptions: self.subscriptions,
+            poll_interval: self.poll_interval,
+            tracked,
+            subscribed: HashSet::new(),
+            updates: SelectAll::new(),
+            pending: VecDeque::new(),
+        };
+        state.subscribe_all();
+
+        Ok(stream::unfold(state, |mut state| async move {
+            let event = state.next_event().await?;
+            Some((event, state))
+        }))
+    }
+}
+
+/// Last known balance of a watched account
+#[derive(Debug, Clone, Copy)]
+struct Tracked {
+    mint: Option<Pubkey>,
+    balance: u64,
+}
+
+/// Notification feeding the watcher
+enum Update {
+    /// New state of an account, `None` if it no longer exists
+    Account(Pubkey, Option<Account>),
+    /// A transaction mentioned the wallet
+    Activity,
+}
+
+/// Balances and subscriptions behind a watch stream
+struct WatchState {
+    owner: Pubkey,
+    rpc: Arc<dyn DynRpcProvider>,
+    tokens: TokenManager,
+    subscriptions: Option<Arc<dyn AccountSubscriptions>>,
+    poll_interval: Duration,
+    tracked: HashMap<Pubkey, Tracked>,
+    subscribed: HashSet<Pubkey>,
+    updates: SelectAll<BoxStream<'static, Update>>,
+    pending: VecDeque<BalanceEvent>,
+}
+
+impl WatchState {
+    /// Subscribe to the wallet's activity and every tracked account
+    fn subscribe_all(&mut self) {
+        let Some(subscriptions) = self.subscriptions.clone() else {
+            return;
+        };
+        let accounts: Vec<Pubkey> = self.tracked.keys().copied().collect();
+        let subscribed = subscriptions
+            .subscribe_activity(self.owner)
+            .map(|activity| {
+                self.updates
+                    .push(activity.map(|_| Update::Activity).boxed())
+            })
+            .and_then(|_| {
+                accounts
+                    .into_iter()
+                    .try_for_each(|account| self.subscribe(account))
+            });
+        if let Err(e) = subscribed {
+            self.fall_back_to_polling(e);
+        }
+    }
+
+    /// Drop every subscription and poll from now on
+    fn fall_back_to_polling(&mut self, error: Error) {
+        warn!(
+            "Subscribing to wallet {} failed, polling instead: {}",
+            self.owner, error
+        );
+        self.subscriptions = None;
+        self.updates = SelectAll::new();
+        self.subscribed.clear();
+    }
+
+    /// Subscribe to an account unless already subscribed
+    fn subscribe(&mut self, account: Pubkey) -> Result<()> {
+        let Some(subscriptions) = &self.subscriptions else {
+            return Ok(());
+        };
+        if self.subscribed.insert(account) {
+            let changes = subscriptions.subscribe_account(account)?;
+            self.updates.push(
+                changes
+                    .map(move |state| Update::Account(account, Some(state)))
+                    .boxed(),
+            );
+        }
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Option<BalanceEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            if self.subscriptions.is_some() {
+                match self.updates.next().await? {
+                    Update::Account(account, state) => self.apply(account, state).await,
+                    Update::Activity => self.rescan().await,
+                }
+            } else {
+                tokio::time::sleep(self.poll_interval).await;
+                self.poll().await;
+            }
+        }
+    }
+
+    /// Fetch every tracked account, then look for new token accounts
+    async fn poll(&mut self) {
+        let accounts: Vec<Pubkey> = self.tracked.keys().copied().collect();
+        match self.rpc.get_multiple_accounts(&accounts).await {
+            Ok(states) => {
+                for (account, state) in accounts.into_iter().zip(states) {
+                    self.apply(account, state).await;
+                }
+            }
+            Err(e) => warn!("Polling wallet {} failed: {}", self.owner, e),
+        }
+        self.rescan().await;
+    }
+
+    /// Track and subscribe to token accounts of the wallet not seen before
+    async fn rescan(&mut self) {
+        let found = match self.tokens.get_wallet_token_accounts(&self.owner).await {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("Scanning token accounts of {} failed: {}", self.owner, e);
+                return;
+            }
+        };
+        for info in found {
+            if self.tracked.contains_key(&info.address) {
+                continue;
+            }
+            if let Err(e) = self.subscribe(info.address) {
+                warn!(
+                    "Subscribing to token account {} failed: {}",
+                    info.address, e
+                );
+            }
+            self.record(
+                info.address,
+                Some(info.mint),
+                BalanceEventKind::Opened,
+                0,
+                info.balance,
+            )
+            .await;
+        }
+    }
+
+    /// Compare the new state of an account with its tracked balance
+    async fn apply(&mut self, account: Pubkey, state: Option<Account>) {
+        if account == self.owner {
+            let balance = state.map_or(0, |state| state.lamports);
+            let previous = self.tracked.get(&account).map_or(0, |t| t.balance);
+            if balance != previous {
+                self.record(account, None, BalanceEventKind::Changed, previous, balance)
+                    .await;
+            }
+            return;
+        }
+
+        // Closed accounts go back to the system program with no lamports
+        let info = state
+            .filter(|state| state.lamports > 0)
+            .and_then(|state| parse_token_account(&account, &state).ok())
+            .filter(|info| info.owner == self.owner);
+        match (self.tracked.get(&account).copied(), info) {
+            (Some(tracked), Some(info)) if tracked.balance != info.balance => {
+                self.record(
+                    account,
+                    Some(info.mint),
+                    BalanceEventKind::Changed,
+                    tracked.balance,
+                    info.balance,
+                )
+                .await;
+            }
+            (Some(tracked), None) => {
+                self.tracked.remove(&account);
+                self.record(
+                    account,
+                    tracked.mint,
+                    BalanceEventKind::Closed,
+                    tracked.balance,
+                    0,
+                )
+                .await;
+            }
+            // An account closed earlier and opened again at the same address
+            (None, Some(info)) => {
+                self.record(
+                    account,
+                    Some(info.mint),
+                    BalanceEventKind::Opened,
+                    0,
+                    info.balance,
+                )
+                .await;
+            }
+            _ => {}
+        }
+    }
+
+    /// Queue an event, tracking the new balance of open accounts
+    async fn record(
+        &mut self,
+        account: Pubkey,
+        mint: Option<Pubkey>,
+        kind: BalanceEventKind,
+        previous: u64,
+        balance: u64,
+    ) {
+        if kind != BalanceEventKind::Closed {
+            self.tracked.insert(account, Tracked { mint, balance });
+        }
+        let signature = self.latest_signature(&account).await;
+        self.pending.push_back(BalanceEvent {
+            account,
+            mint,
+            kind,
+            previous,
+            balance,
+            signature,
+        });
+    }
+
+    /// Latest transaction touching an account, if the node reports one
+    async fn latest_signature(&self, account: &Pubkey) -> Option<Signature> {
+        match self.rpc.get_signatures_for_address(account, 1).await {
+            Ok(history) => history
+                .first()
+                .and_then(|entry| entry.signature.parse().ok()),
+            Err(e) => {
+                debug!("Resolving the signature for {} failed: {}", account, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::channel::mpsc::{unbounded, UnboundedSender};
+    use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+    use solana_sdk::system_program;
+
+    use super::*;
+    use crate::rpc::mock::{token_account, MockRpc};
+
+    /// Subscriptions fed by the test through channels
+    #[derive(Default)]
+    struct MockSubscriptions {
+        accounts: Mutex<HashMap<Pubkey, UnboundedSender<Account>>>,
+        activity: Mutex<Option<UnboundedSender<Signature>>>,
+    }
+
+    impl MockSubscriptions {
+        fn push_account(&self, pubkey: Pubkey, account: Account) -> Result<()> {
+            let accounts = self.accounts.lock().map_err(|_| Error::rpc("poisoned"))?;
+            let sender = accounts
+                .get(&pubkey)
+                .ok_or_else(|| Error::rpc(format!("{} is not subscribed", pubkey)))?;
+            sender
+                .unbounded_send(account)
+                .map_err(|e| Error::rpc(e.to_string()))
+        }
+
+        fn push_activity(&self, signature: Signature) -> Result<()> {
+            let activity = self.activity.lock().map_err(|_| Error::rpc("poisoned"))?;
+            activity
+                .as_ref()
+                .ok_or_else(|| Error::rpc("activity is not subscribed"))?
+                .unbounded_send(signature)
+                .map_err(|e| Error::rpc(e.to_string()))
+        }
+
+        fn is_subscribed(&self, pubkey: &Pubkey) -> bool {
+            self.accounts
+                .lock()
+                .is_ok_and(|accounts| accounts.contains_key(pubkey))
+        }
+    }
+
+    impl AccountSubscriptions for MockSubscriptions {
+        fn subscribe_account(&self, pubkey: Pubkey) -> Result<BoxStream<'static, Account>> {
+            let (sender, receiver) = unbounded();
+            self.accounts
+                .lock()
+                .map_err(|_| Error::rpc("poisoned"))?
+                .insert(pubkey, sender);
+            Ok(receiver.boxed())
+        }
+
+        fn subscribe_activity(&self, _address: Pubkey) -> Result<BoxStream<'static, Signature>> {
+            let (sender, receiver) = unbounded();
+            *self.activity.lock().map_err(|_| Error::rpc("poisoned"))? = Some(sender);
+            Ok(receiver.boxed())
+        }
+    }
+
+    fn sol_account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            owner: system_program::id(),
+            ..Account::default()
+        }
+    }
+
+    fn history(signature: &Signature) -> Vec<RpcConfirmedTransactionStatusWithSignature> {
+        vec![RpcConfirmedTransactionStatusWithSignature {
+            signature: signature.to_string(),
+            slot: 10,
+            err: None,
+            memo: None,
+            block_time: None,
+            confirmation_status: None,
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_changes_become_events() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let owner = Pubkey::new_unique();
+        let (usdc, usdc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_balance(owner, 1_000_000_000);
+        rpc.set_token_account(usdc, usdc_mint, owner, 100)?;
+        let payment = Signature::new_unique();
+        rpc.set_history(owner, history(&payment));
+
+        let subscriptions = Arc::new(MockSubscriptions::default());
+        let watcher =
+            BalanceWatcher::new(owner, rpc.clone()).with_subscriptions(subscriptions.clone());
+        let mut events = Box::pin(watcher.watch().await?);
+        assert!(subscriptions.is_subscribed(&owner));
+        assert!(subscriptions.is_subscribed(&usdc));
+
+        // A notification without a balance change yields nothing
+        subscriptions.push_account(owner, sol_account(1_000_000_000))?;
+        subscriptions.push_account(owner, sol_account(400_000_000))?;
+        assert_eq!(
+            events.next().await,
+            Some(BalanceEvent {
+                account: owner,
+                mint: None,
+                kind: BalanceEventKind::Changed,
+                previous: 1_000_000_000,
+                balance: 400_000_000,
+                signature: Some(payment),
+            })
+        );
+
+        // A new associated account shows up after wallet activity
+        let (bonk, bonk_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_token_account(bonk, bonk_mint, owner, 5_000)?;
+        subscriptions.push_activity(Signature::new_unique())?;
+        let opened = events.next().await;
+        assert_eq!(
+            opened
+                .as_ref()
+                .map(|e| (e.account, e.mint, e.kind, e.previous, e.balance)),
+            Some((bonk, Some(bonk_mint), BalanceEventKind::Opened, 0, 5_000))
+        );
+        assert!(subscriptions.is_subscribed(&bonk));
+
+        subscriptions.push_account(bonk, token_account(bonk_mint, owner, 7_500)?)?;
+        let changed = events.next().await;
+        assert_eq!(
+            changed.as_ref().map(|e| (e.kind, e.delta())),
+            Some((BalanceEventKind::Changed, 2_500))
+        );
+
+        // Closing hands the account back to the system program
+        subscriptions.push_account(usdc, sol_account(0))?;
+        assert_eq!(
+            events
+                .next()
+                .await
+                .map(|e| (e.account, e.kind, e.previous, e.balance)),
+            Some((usdc, BalanceEventKind::Closed, 100, 0))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_polling_without_subscriptions() -> Result<()> {
+        let rpc = Arc::new(MockRpc::new());
+        let owner = Pubkey::new_unique();
+        rpc.set_balance(owner, 50);
+        rpc.set_account(owner, sol_account(50));
+
+        let watcher =
+            BalanceWatcher::new(owner, rpc.clone()).with_poll_interval(Duration::from_millis(1));
+        let mut events = Box::pin(watcher.watch().await?);
+
+        let (usdc, usdc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(owner, sol_account(80));
+        rpc.set_token_account(usdc, usdc_mint, owner, 9)?;
+        let first = events.next().await;
+        let second = events.next().await;
+        assert_eq!(
+            first.map(|e| (e.mint, e.kind, e.delta())),
+            Some((None, BalanceEventKind::Changed, 30))
+        );
+        assert_eq!(
+            second.map(|e| (e.account, e.kind, e.balance)),
+            Some((usdc, BalanceEventKind::Opened, 9))
+        );
+        Ok(())
+    }
+}
@@ -82,6 +82,9 @@ async fn main() -> Result<()> {
     println!("        .with_rpc_url(\"https://api.devnet.solana.com\");");
     println!("    let wallet = Wallet::load(\"wallet.json\", config)?;");
     println!("    ");
+    println!("    // Pay for fees from the devnet faucet (refused on mainnet)");
+    println!("    wallet.request_airdrop(Lamports::from_sol_str(\"1\")?).await?;");
+    println!("    ");
     println!("    // Create counter client");
     println!("    let counter = CounterClient::new(program_id, wallet.rpc_client());");
     println!("    ");
@@ -179,7 +182,7 @@ async fn main() -> Result<()> {
     println!("1. Deploy a counter program to devnet");
     println!("2. Create a counter account");
     println!("3. Update the program_id and counter_account values");
-    println!("4. Use a real wallet with SOL for transaction fees");
+    println!("4. Fund the wallet with `wallet airdrop <name> 1` or `Wallet::request_airdrop`");
 
     Ok(())
 }
@@ -0,0 +1,50 @@
+//! Shared building blocks for protocol clients
+//!
+//! Every DEX client implements [`ProtocolClient`], which lets callers such as
+//! the [`DexRouter`](crate::protocol::DexRouter) quote and build swaps without
+//! knowing which protocol fills them. Transactions for custom programs are
+//! assembled with the core [`TransactionBuilder`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_dapp::common::ProtocolClient;
+//! use agent_wallet_dapp::protocol::SwapParams;
+//!
+//! # async fn example(client: &dyn ProtocolClient) -> agent_wallet_dapp::Result<()> {
+//! let quote = client
+//!     .quote_swap(&SwapParams::new("SOL", "USDC", 1.0))
+//!     .await?;
+//! println!("{} would return {}", client.protocol(), quote.expected_out);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+
+use crate::error::Result;
+use crate::protocol::{DexProtocol, ProtocolAction, SwapParams, SwapQuote};
+
+pub use agent_wallet_core::TransactionBuilder;
+
+/// Client of a DeFi protocol that can quote and build swaps
+#[async_trait]
+pub trait ProtocolClient: Send + Sync {
+    /// Protocol served by this client
+    fn protocol(&self) -> DexProtocol;
+
+    /// Actions this client can build transactions for
+    fn supported_actions(&self) -> Vec<ProtocolAction>;
+
+    /// Quote a swap at the current on-chain state
+    async fn quote_swap(&self, params: &SwapParams) -> Result<SwapQuote>;
+
+    /// Build an unsigned swap transaction for `owner`, returning its quote
+    async fn build_swap(
+        &self,
+        owner: &Pubkey,
+        params: &SwapParams,
+    ) -> Result<(Transaction, SwapQuote)>;
+}
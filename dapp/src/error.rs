@@ -0,0 +1,93 @@
+//! Error types for the dApp and DeFi protocol clients
+//!
+//! Protocol clients surface their own failures through [`DappError`] and wrap
+//! errors coming from the core wallet library so callers only deal with a
+//! single error type.
+
+/// Result type alias for dApp operations
+pub type Result<T> = std::result::Result<T, DappError>;
+
+/// Error type for dApp and protocol client operations
+#[derive(Debug, thiserror::Error)]
+pub enum DappError {
+    /// Error from the core wallet library (RPC, signing, ...)
+    #[error("Wallet error: {0}")]
+    Wallet(#[from] agent_wallet_core::Error),
+
+    /// Protocol-specific failure
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    /// No pool exists for the requested pair
+    #[error("Pool not found: {0}")]
+    PoolNotFound(String),
+
+    /// On-chain pool account could not be decoded
+    #[error("Invalid pool data: {0}")]
+    InvalidPoolData(String),
+
+    /// A swap would cross into a tick array that has not been initialized
+    #[error("Tick array {address} starting at tick {start_tick_index} is not initialized")]
+    UninitializedTickArray {
+        /// Address of the missing tick array
+        address: solana_sdk::pubkey::Pubkey,
+        /// First tick index the array would cover
+        start_tick_index: i32,
+    },
+
+    /// An on-chain program rejected an instruction with a custom error code
+    #[error("Program {program_id} failed with error {code}: {message}")]
+    Program {
+        /// Program that returned the error
+        program_id: solana_sdk::pubkey::Pubkey,
+        /// Custom program error code
+        code: u32,
+        /// What the code means to the client
+        message: String,
+    },
+
+    /// Invalid parameters supplied by the caller
+    #[error("Invalid parameters: {0}")]
+    InvalidParams(String),
+
+    /// Local cache could not be read or written
+    #[error("Cache error: {0}")]
+    Cache(String),
+
+    /// Serialization/deserialization failed
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+}
+
+impl DappError {
+    /// Create a protocol error
+    pub fn protocol(msg: impl Into<String>) -> Self {
+        Self::Protocol(msg.into())
+    }
+
+    /// Create an invalid pool data error
+    pub fn invalid_pool_data(msg: impl Into<String>) -> Self {
+        Self::InvalidPoolData(msg.into())
+    }
+
+    /// Create an invalid parameters error
+    pub fn invalid_params(msg: impl Into<String>) -> Self {
+        Self::InvalidParams(msg.into())
+    }
+
+    /// Create a cache error
+    pub fn cache(msg: impl Into<String>) -> Self {
+        Self::Cache(msg.into())
+    }
+
+    /// Create a serialization error
+    pub fn serialization(msg: impl Into<String>) -> Self {
+        Self::Serialization(msg.into())
+    }
+}
+
+impl From<solana_sdk::pubkey::ParsePubkeyError> for DappError {
+    fn from(err: solana_sdk::pubkey::ParsePubkeyError) -> Self {
+        Self::InvalidParams(format!("Invalid public key: {}", err))
+    }
+}
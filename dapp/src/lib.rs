@@ -8,8 +8,10 @@
 //!
 //! - **Test Program Client**: Simple counter program for testing and development
 //! - **Raydium Integration**: Token swaps and liquidity pool operations
-//! - **Orca Integration**: Alternative DEX with whirlpool support
+//! - **Orca Integration**: Whirlpool quotes and swaps across tick arrays
 //! - **Protocol Abstraction**: Unified interface for multiple DeFi protocols
+//! - **DEX Routing**: Swaps filled by whichever protocol quotes the best output
+//! - **Pool Metadata Cache**: On-disk cache of discovered Raydium/Orca pools
 //! - **Transaction Building**: Helper functions for constructing protocol-specific transactions
 //!
 //! # Quick Start
@@ -28,18 +30,15 @@
 //!     // Create Raydium client
 //!     let raydium = RaydiumClient::new(wallet.rpc_client());
 //!
-//!     // Perform a swap
-//!     let swap_params = SwapParams {
-//!         input_token: "SOL".to_string(),
-//!         output_token: "USDC".to_string(),
-//!         amount: 1.0,
-//!         slippage_bps: 50, // 0.5%
-//!     };
-//!
-//!     let transaction = raydium.create_swap_transaction(&swap_params).await?;
+//!     // Quote and build a swap
+//!     let swap_params = SwapParams::new("SOL", "USDC", 1.0).with_slippage(0.5);
+//!     let (mut transaction, quote) = raydium
+//!         .create_swap_transaction(&wallet.public_key(), &swap_params)
+//!         .await?;
+//!     println!("Expecting {} (at least {})", quote.expected_out, quote.min_out);
 //!
 //!     // Sign and send with wallet
-//!     let signature = wallet.sign_and_send(transaction).await?;
+//!     let signature = wallet.sign_and_send(&mut transaction).await?;
 //!     println!("Swap transaction sent: {}", signature);
 //!
 //!     Ok(())
@@ -55,7 +54,8 @@
 //! ```no_run
 //! use agent_wallet_dapp::test_program::CounterClient;
 //!
-//! let counter = CounterClient::new(program_id, rpc_client);
+//! let mut counter = CounterClient::new(program_id, wallet.rpc_client());
+//! counter.initialize(&wallet).await?;
 //! counter.increment(&wallet).await?;
 //! let count = counter.get_count().await?;
 //! ```
@@ -77,13 +77,15 @@
 //!
 //! ## Orca
 //!
-//! Alternative DEX with concentrated liquidity:
+//! Concentrated liquidity swaps through whirlpools:
 //!
 //! ```no_run
 //! use agent_wallet_dapp::orca::{OrcaClient, WhirlpoolParams};
 //!
-//! // Interact with whirlpool
-//! let whirlpool = WhirlpoolParams::new(pool_address, 100.0, 200.0);
+//! // Quote selling token A, then build the swap
+//! let quote = orca.get_quote(whirlpool, 1_000_000, true).await?;
+//! let params = WhirlpoolParams::new(wallet.public_key()).with_slippage(0.5);
+//! let transaction = orca.create_swap_transaction(&params, &quote)?;
 //! ```
 //!
 //! # Safety Features
@@ -104,6 +106,7 @@
 
 pub mod common;
 pub mod error;
+pub mod pools;
 pub mod protocol;
 
 #[cfg(feature = "test-program")]
@@ -118,32 +121,35 @@ pub mod orca;
 // Re-exports for convenience
 pub use common::{ProtocolClient, TransactionBuilder};
 pub use error::{DappError, Result};
-pub use protocol::{DexProtocol, ProtocolAction, ProtocolParams};
+pub use pools::{LiquidityBucket, PoolCache, PoolMetadata, PoolProtocol, TokenPair};
+pub use protocol::{
+    DexProtocol, DexRouter, ProtocolAction, ProtocolParams, RoutedSwap, SwapParams, SwapQuote,
+};
 
 #[cfg(feature = "test-program")]
-pub use test_program::{CounterClient, CounterInstruction};
+pub use test_program::{CounterClient, CounterError, CounterInstruction, CounterRpc};
 
 #[cfg(feature = "raydium")]
-pub use raydium::{LiquidityParams, RaydiumClient, SwapParams};
+pub use raydium::{LiquidityParams, RaydiumClient, RaydiumPool};
 
 #[cfg(feature = "orca")]
-pub use orca::{OrcaClient, WhirlpoolParams};
+pub use orca::{OrcaClient, Whirlpool, WhirlpoolParams, WhirlpoolQuote};
 
 /// Prelude module for easy importing of common types
 pub mod prelude {
     pub use super::{
-        DappError, DexProtocol, ProtocolAction, ProtocolClient, ProtocolParams, Result,
-        TransactionBuilder,
+        DappError, DexProtocol, DexRouter, ProtocolAction, ProtocolClient, ProtocolParams,
+        Result, RoutedSwap, SwapParams, SwapQuote, TransactionBuilder,
     };
 
     #[cfg(feature = "test-program")]
     pub use super::{CounterClient, CounterInstruction};
 
     #[cfg(feature = "raydium")]
-    pub use super::{LiquidityParams, RaydiumClient, SwapParams};
+    pub use super::{LiquidityParams, RaydiumClient};
 
     #[cfg(feature = "orca")]
-    pub use super::{OrcaClient, WhirlpoolParams};
+    pub use super::{OrcaClient, WhirlpoolParams, WhirlpoolQuote};
 
     // Re-export commonly used Solana types
     pub use solana_sdk::{
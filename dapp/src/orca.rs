@@ -0,0 +1,1219 @@
+//! Orca Whirlpool swaps
+//!
+//! [`OrcaClient`] quotes a swap by fetching the whirlpool together with the
+//! three tick arrays a swap instruction may reference, then stepping through
+//! the concentrated liquidity curve tick by tick with the same fixed-point
+//! math the program uses. The quote carries the accounts it was computed
+//! against, so the transaction built from it references exactly the tick
+//! arrays and oracle the estimate assumed.
+//!
+//! A swap that would run into a tick array nobody has initialized fails with
+//! [`DappError::UninitializedTickArray`] at quote time instead of producing a
+//! transaction the program is bound to reject.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_dapp::orca::{OrcaClient, WhirlpoolParams};
+//! use agent_wallet_core::{Wallet, WalletConfig};
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(whirlpool: Pubkey) -> Result<(), Box<dyn std::error::Error>> {
+//! let wallet = Wallet::load("wallet.json", WalletConfig::new())?;
+//! let orca = OrcaClient::new(wallet.rpc_client());
+//!
+//! // Sell 0.1 of token A for token B
+//! let quote = orca.get_quote(whirlpool, 100_000_000, true).await?;
+//! println!("Expecting {} (fee {})", quote.estimated_amount_out, quote.fee_amount);
+//!
+//! let params = WhirlpoolParams::new(wallet.public_key()).with_slippage(0.5);
+//! let mut transaction = orca.create_swap_transaction(&params, &quote)?;
+//! wallet.sign_and_send(&mut transaction).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use agent_wallet_core::token::{unwrap_sol_instruction, wrap_sol_instructions, NATIVE_MINT};
+use agent_wallet_core::{DynRpcProvider, ProgramAccountsQuery};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use tracing::debug;
+
+use crate::common::ProtocolClient;
+use crate::error::{DappError, Result};
+use crate::pools::{
+    read_bytes, read_i32, read_pubkey, read_u128, read_u16, whirlpool_layout,
+    ORCA_WHIRLPOOL_PROGRAM_ID,
+};
+use crate::protocol::{ui_to_raw, DexProtocol, ProtocolAction, SwapParams, SwapQuote};
+use crate::DEFAULT_SLIPPAGE_BPS;
+
+/// Anchor discriminator of the `swap` instruction
+const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+/// Seed of tick array PDAs
+const TICK_ARRAY_SEED: &[u8] = b"tick_array";
+
+/// Seed of oracle PDAs
+const ORACLE_SEED: &[u8] = b"oracle";
+
+/// Ticks stored in one tick array
+pub const TICK_ARRAY_SIZE: i32 = 88;
+
+/// Lowest tick a whirlpool price can reach
+pub const MIN_TICK_INDEX: i32 = -443_636;
+
+/// Highest tick a whirlpool price can reach
+pub const MAX_TICK_INDEX: i32 = 443_636;
+
+/// Square root price at [`MIN_TICK_INDEX`], in Q64.64
+pub const MIN_SQRT_PRICE: u128 = 4_295_048_016;
+
+/// Square root price at [`MAX_TICK_INDEX`], in Q64.64
+pub const MAX_SQRT_PRICE: u128 = 79_226_673_515_401_279_992_447_579_055;
+
+/// Denominator of whirlpool fee rates, which are in hundredths of a basis point
+const FEE_RATE_DENOMINATOR: u128 = 1_000_000;
+
+/// Basis points in one
+const BPS: u128 = 10_000;
+
+/// Offset of the decimals in an SPL token mint
+const MINT_DECIMALS: usize = 44;
+
+/// One in Q64.64
+const Q64: u128 = 1 << 64;
+
+/// `2^64 / sqrt(1.0001)^(2^k)` for bits `k` of a tick index, in Q64.64
+const TICK_RATIOS: [u128; 19] = [
+    18_445_821_805_675_392_311,
+    18_444_899_583_751_176_498,
+    18_443_055_278_223_354_162,
+    18_439_367_220_385_604_838,
+    18_431_993_317_065_449_817,
+    18_417_254_355_718_160_513,
+    18_387_811_781_193_591_352,
+    18_329_067_761_203_520_168,
+    18_212_142_134_806_087_854,
+    17_980_523_815_641_551_639,
+    17_526_086_738_831_147_013,
+    16_651_378_430_235_024_244,
+    15_030_750_278_693_429_944,
+    12_247_334_978_882_834_399,
+    8_131_365_268_884_726_200,
+    3_584_323_654_723_342_297,
+    696_457_651_847_595_233,
+    26_294_789_957_452_057,
+    37_481_735_321_082,
+];
+
+/// `TickArray` account layout: discriminator, start index, ticks, whirlpool
+mod tick_array_layout {
+    pub const SIZE: usize = 9988;
+    pub const START_TICK_INDEX: usize = 8;
+    pub const TICKS: usize = 12;
+    pub const TICK_SIZE: usize = 113;
+    pub const TICK_LIQUIDITY_NET: usize = 1;
+    pub const TICK_LIQUIDITY_GROSS: usize = 17;
+}
+
+/// Parameters for executing a quoted swap
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhirlpoolParams {
+    /// Wallet selling the input and paying for the transaction
+    pub owner: Pubkey,
+    /// Accepted shortfall from the estimated output, in basis points
+    pub slippage_bps: u16,
+    /// Minimum output in raw units, overriding the slippage-derived minimum
+    #[serde(default)]
+    pub min_output_amount: Option<u64>,
+    /// Close the wrapped SOL account after a swap selling or buying SOL
+    #[serde(default)]
+    pub auto_unwrap: bool,
+}
+
+impl WhirlpoolParams {
+    /// Swap from `owner`'s token accounts with default slippage
+    pub fn new(owner: Pubkey) -> Self {
+        Self {
+            owner,
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            min_output_amount: None,
+            auto_unwrap: false,
+        }
+    }
+
+    /// Set the slippage tolerance in percent
+    pub fn with_slippage(mut self, percent: f64) -> Self {
+        self.slippage_bps = (percent * 100.0).round().clamp(0.0, u16::MAX as f64) as u16;
+        self
+    }
+
+    /// Require at least `amount` raw units of output
+    pub fn with_min_output(mut self, amount: u64) -> Self {
+        self.min_output_amount = Some(amount);
+        self
+    }
+
+    /// Unwrap SOL left in the wrapped SOL account after the swap
+    pub fn with_auto_unwrap(mut self) -> Self {
+        self.auto_unwrap = true;
+        self
+    }
+}
+
+/// Decoded Orca whirlpool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Whirlpool {
+    /// Whirlpool account
+    pub address: Pubkey,
+    /// Distance between initializable ticks
+    pub tick_spacing: u16,
+    /// Swap fee in hundredths of a basis point; this is the pool's fee tier
+    pub fee_rate: u16,
+    /// Liquidity active at the current price
+    pub liquidity: u128,
+    /// Square root of the price of A in B, in Q64.64
+    pub sqrt_price: u128,
+    /// Tick containing the current price
+    pub tick_current_index: i32,
+    /// Token A mint
+    pub token_mint_a: Pubkey,
+    /// Vault holding token A
+    pub token_vault_a: Pubkey,
+    /// Token B mint
+    pub token_mint_b: Pubkey,
+    /// Vault holding token B
+    pub token_vault_b: Pubkey,
+}
+
+impl Whirlpool {
+    /// Decode a `Whirlpool` account
+    pub fn decode(address: Pubkey, account: &Account) -> Result<Self> {
+        let data = account.data.as_slice();
+        if data.len() != whirlpool_layout::SIZE {
+            return Err(DappError::invalid_pool_data(format!(
+                "unexpected account size {}",
+                data.len()
+            )));
+        }
+
+        let tick_spacing = read_u16(data, whirlpool_layout::TICK_SPACING)?;
+        if tick_spacing == 0 {
+            return Err(DappError::invalid_pool_data("zero tick spacing"));
+        }
+        let sqrt_price = read_u128(data, whirlpool_layout::SQRT_PRICE)?;
+        if !(MIN_SQRT_PRICE..=MAX_SQRT_PRICE).contains(&sqrt_price) {
+            return Err(DappError::invalid_pool_data(format!(
+                "sqrt price {} out of range",
+                sqrt_price
+            )));
+        }
+
+        Ok(Self {
+            address,
+            tick_spacing,
+            fee_rate: read_u16(data, whirlpool_layout::FEE_RATE)?,
+            liquidity: read_u128(data, whirlpool_layout::LIQUIDITY)?,
+            sqrt_price,
+            tick_current_index: read_i32(data, whirlpool_layout::TICK_CURRENT_INDEX)?,
+            token_mint_a: read_pubkey(data, whirlpool_layout::TOKEN_MINT_A)?,
+            token_vault_a: read_pubkey(data, whirlpool_layout::TOKEN_VAULT_A)?,
+            token_mint_b: read_pubkey(data, whirlpool_layout::TOKEN_MINT_B)?,
+            token_vault_b: read_pubkey(data, whirlpool_layout::TOKEN_VAULT_B)?,
+        })
+    }
+
+    /// Ticks covered by one tick array
+    fn ticks_per_array(&self) -> i32 {
+        i32::from(self.tick_spacing) * TICK_ARRAY_SIZE
+    }
+
+    /// Start indexes of the three tick arrays a swap in this direction uses
+    ///
+    /// A swap selling B starts from the array of the next tick up, since a
+    /// price sitting exactly on an array's first tick trades into the array
+    /// above it.
+    pub fn tick_array_start_indexes(&self, a_to_b: bool) -> [i32; 3] {
+        let span = self.ticks_per_array();
+        let shift = if a_to_b {
+            0
+        } else {
+            i32::from(self.tick_spacing)
+        };
+        let first = (self.tick_current_index + shift).div_euclid(span) * span;
+        let step = if a_to_b { -span } else { span };
+        [first, first + step, first + 2 * step]
+    }
+
+    /// Tick array PDA starting at `start_tick_index`
+    pub fn tick_array_address(&self, start_tick_index: i32) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                TICK_ARRAY_SEED,
+                self.address.as_ref(),
+                start_tick_index.to_string().as_bytes(),
+            ],
+            &ORCA_WHIRLPOOL_PROGRAM_ID,
+        )
+        .0
+    }
+
+    /// Oracle PDA of the whirlpool
+    pub fn oracle_address(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[ORACLE_SEED, self.address.as_ref()],
+            &ORCA_WHIRLPOOL_PROGRAM_ID,
+        )
+        .0
+    }
+
+    /// Quote selling `amount_in` against already fetched tick arrays
+    ///
+    /// `tick_arrays` are the three arrays of [`Whirlpool::tick_array_start_indexes`]
+    /// in order, `None` where the account does not exist.
+    pub fn quote(
+        &self,
+        tick_arrays: [TickArraySlot; 3],
+        amount_in: u64,
+        a_to_b: bool,
+    ) -> Result<WhirlpoolQuote> {
+        if amount_in == 0 {
+            return Err(DappError::invalid_params("Swap amount must be positive"));
+        }
+        let sqrt_price_limit = if a_to_b {
+            MIN_SQRT_PRICE
+        } else {
+            MAX_SQRT_PRICE
+        };
+
+        let mut remaining = u128::from(amount_in);
+        let mut amount_out = 0u128;
+        let mut fee_amount = 0u128;
+        let mut sqrt_price = self.sqrt_price;
+        let mut tick_current = self.tick_current_index;
+        let mut liquidity = self.liquidity;
+
+        while remaining > 0 && sqrt_price != sqrt_price_limit {
+            let (next_tick, liquidity_net) = self.next_tick(&tick_arrays, tick_current, a_to_b)?;
+            let target = sqrt_price_from_tick(next_tick)?;
+            let target = if a_to_b {
+                target.max(sqrt_price_limit)
+            } else {
+                target.min(sqrt_price_limit)
+            };
+
+            let step = swap_step(
+                remaining,
+                self.fee_rate,
+                liquidity,
+                sqrt_price,
+                target,
+                a_to_b,
+            )?;
+            remaining = remaining.saturating_sub(step.amount_in + step.fee_amount);
+            amount_out += step.amount_out;
+            fee_amount += step.fee_amount;
+
+            if step.next_sqrt_price == target {
+                if let Some(net) = liquidity_net {
+                    liquidity = cross_tick(liquidity, net, a_to_b)?;
+                }
+                tick_current = if a_to_b { next_tick - 1 } else { next_tick };
+            }
+            sqrt_price = step.next_sqrt_price;
+        }
+
+        let [first, second, third] = tick_arrays;
+        Ok(WhirlpoolQuote {
+            pool: self.clone(),
+            a_to_b,
+            amount_in,
+            consumed_amount_in: to_u64(u128::from(amount_in) - remaining)?,
+            estimated_amount_out: to_u64(amount_out)?,
+            fee_amount: to_u64(fee_amount)?,
+            end_sqrt_price: sqrt_price,
+            tick_arrays: [first.address, second.address, third.address],
+            oracle: self.oracle_address(),
+        })
+    }
+
+    /// Next tick the price moves to from `tick_current`
+    ///
+    /// Returns the nearest initialized tick with its net liquidity, or the
+    /// edge of the current tick array when the array has none left so the
+    /// search continues in the next array.
+    fn next_tick(
+        &self,
+        tick_arrays: &[TickArraySlot; 3],
+        tick_current: i32,
+        a_to_b: bool,
+    ) -> Result<(i32, Option<i128>)> {
+        let spacing = i32::from(self.tick_spacing);
+        let span = self.ticks_per_array();
+        let mut tick = tick_current.div_euclid(spacing) * spacing;
+        if !a_to_b {
+            tick += spacing;
+        }
+        let start = tick.div_euclid(span) * span;
+        let slot = tick_arrays
+            .iter()
+            .find(|slot| slot.start_tick_index == start)
+            .ok_or_else(|| {
+                DappError::protocol(format!(
+                    "Swap on {} needs the tick array starting at {}, beyond the three a swap can reference; reduce the amount",
+                    self.address, start
+                ))
+            })?;
+        let array = slot
+            .array
+            .as_ref()
+            .ok_or(DappError::UninitializedTickArray {
+                address: slot.address,
+                start_tick_index: start,
+            })?;
+
+        loop {
+            if tick < MIN_TICK_INDEX {
+                return Ok((MIN_TICK_INDEX, None));
+            }
+            if tick > MAX_TICK_INDEX {
+                return Ok((MAX_TICK_INDEX, None));
+            }
+            let index = usize::try_from((tick - start) / spacing)
+                .map_err(|_| DappError::invalid_pool_data("tick outside its array"))?;
+            if let Some(net) = array.ticks.get(index).and_then(Tick::liquidity_net) {
+                return Ok((tick, Some(net)));
+            }
+            if a_to_b {
+                if tick == start {
+                    return Ok((tick, None));
+                }
+                tick -= spacing;
+            } else {
+                if tick == start + span - spacing {
+                    return Ok((tick, None));
+                }
+                tick += spacing;
+            }
+        }
+    }
+}
+
+/// One tick of a tick array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tick {
+    /// Whether a position bounds its range at this tick
+    pub initialized: bool,
+    /// Liquidity added when the price crosses this tick upwards
+    pub liquidity_net: i128,
+    /// Total liquidity of positions bounded at this tick
+    pub liquidity_gross: u128,
+}
+
+impl Tick {
+    fn liquidity_net(&self) -> Option<i128> {
+        self.initialized.then_some(self.liquidity_net)
+    }
+}
+
+/// Decoded `TickArray` account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickArray {
+    /// Tick array account
+    pub address: Pubkey,
+    /// Index of the first tick covered
+    pub start_tick_index: i32,
+    /// Ticks in ascending order, one per tick spacing
+    pub ticks: Vec<Tick>,
+}
+
+impl TickArray {
+    /// Decode a `TickArray` account
+    pub fn decode(address: Pubkey, account: &Account) -> Result<Self> {
+        let data = account.data.as_slice();
+        if data.len() != tick_array_layout::SIZE {
+            return Err(DappError::invalid_pool_data(format!(
+                "unexpected tick array size {}",
+                data.len()
+            )));
+        }
+
+        let ticks = (0..TICK_ARRAY_SIZE as usize)
+            .map(|i| {
+                let offset = tick_array_layout::TICKS + i * tick_array_layout::TICK_SIZE;
+                Ok(Tick {
+                    initialized: data[offset] != 0,
+                    liquidity_net: read_bytes(data, offset + tick_array_layout::TICK_LIQUIDITY_NET)
+                        .map(i128::from_le_bytes)?,
+                    liquidity_gross: read_u128(
+                        data,
+                        offset + tick_array_layout::TICK_LIQUIDITY_GROSS,
+                    )?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            address,
+            start_tick_index: read_i32(data, tick_array_layout::START_TICK_INDEX)?,
+            ticks,
+        })
+    }
+}
+
+/// Tick array a swap references, with its contents if it exists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickArraySlot {
+    /// Tick array PDA
+    pub address: Pubkey,
+    /// Index of the first tick the array covers
+    pub start_tick_index: i32,
+    /// Decoded array, `None` if the account is not initialized
+    pub array: Option<TickArray>,
+}
+
+/// Expected outcome of a whirlpool swap, in raw token units
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhirlpoolQuote {
+    /// Whirlpool the quote was computed against
+    pub pool: Whirlpool,
+    /// Whether token A is sold for token B
+    pub a_to_b: bool,
+    /// Amount offered
+    pub amount_in: u64,
+    /// Amount the swap consumes; less than offered if the price limit is hit
+    pub consumed_amount_in: u64,
+    /// Output at the current pool state
+    pub estimated_amount_out: u64,
+    /// Fee taken from the input at the pool's fee rate
+    pub fee_amount: u64,
+    /// Square root price after the swap, in Q64.64
+    pub end_sqrt_price: u128,
+    /// Tick arrays the swap instruction references
+    pub tick_arrays: [Pubkey; 3],
+    /// Oracle of the whirlpool
+    pub oracle: Pubkey,
+}
+
+impl WhirlpoolQuote {
+    /// Input and output mints
+    pub fn mints(&self) -> (Pubkey, Pubkey) {
+        if self.a_to_b {
+            (self.pool.token_mint_a, self.pool.token_mint_b)
+        } else {
+            (self.pool.token_mint_b, self.pool.token_mint_a)
+        }
+    }
+
+    /// Shortfall of the output from the starting price, in basis points
+    pub fn price_impact_bps(&self) -> Result<u32> {
+        let traded = u128::from(self.consumed_amount_in - self.fee_amount);
+        let sqrt_price = self.pool.sqrt_price;
+        let spot_out = if self.a_to_b {
+            mul_div(
+                mul_div(traded, sqrt_price, Q64, false)?,
+                sqrt_price,
+                Q64,
+                false,
+            )?
+        } else {
+            mul_div(
+                mul_div(traded, Q64, sqrt_price, false)?,
+                Q64,
+                sqrt_price,
+                false,
+            )?
+        };
+        if spot_out == 0 {
+            return Ok(0);
+        }
+        let shortfall = spot_out.saturating_sub(u128::from(self.estimated_amount_out));
+        Ok(u32::try_from(shortfall * BPS / spot_out).unwrap_or(u32::MAX))
+    }
+
+    /// Protocol-neutral summary of the quote under `params`' limits
+    pub fn swap_quote(&self, params: &WhirlpoolParams) -> Result<SwapQuote> {
+        Ok(SwapQuote {
+            expected_out: self.estimated_amount_out,
+            min_out: self.min_out(params)?,
+            price_impact_bps: self.price_impact_bps()?,
+            fee: self.fee_amount,
+        })
+    }
+
+    /// Output below which the swap fails on chain
+    pub fn min_out(&self, params: &WhirlpoolParams) -> Result<u64> {
+        if let Some(min_out) = params.min_output_amount {
+            return Ok(min_out);
+        }
+        let slippage_bps = u128::from(params.slippage_bps);
+        if slippage_bps > BPS {
+            return Err(DappError::invalid_params(format!(
+                "Slippage of {} bps exceeds 100%",
+                slippage_bps
+            )));
+        }
+        to_u64(u128::from(self.estimated_amount_out) * (BPS - slippage_bps) / BPS)
+    }
+}
+
+/// Build a whirlpool `swap` instruction selling an exact input amount
+pub fn swap_instruction(
+    quote: &WhirlpoolQuote,
+    owner: &Pubkey,
+    owner_account_a: &Pubkey,
+    owner_account_b: &Pubkey,
+    min_out: u64,
+) -> Instruction {
+    let sqrt_price_limit = if quote.a_to_b {
+        MIN_SQRT_PRICE
+    } else {
+        MAX_SQRT_PRICE
+    };
+    let mut data = Vec::with_capacity(42);
+    data.extend_from_slice(&SWAP_DISCRIMINATOR);
+    data.extend_from_slice(&quote.amount_in.to_le_bytes());
+    data.extend_from_slice(&min_out.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
+    data.push(1); // amount_specified_is_input
+    data.push(u8::from(quote.a_to_b));
+
+    let pool = &quote.pool;
+    Instruction {
+        program_id: ORCA_WHIRLPOOL_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(pool.address, false),
+            AccountMeta::new(*owner_account_a, false),
+            AccountMeta::new(pool.token_vault_a, false),
+            AccountMeta::new(*owner_account_b, false),
+            AccountMeta::new(pool.token_vault_b, false),
+            AccountMeta::new(quote.tick_arrays[0], false),
+            AccountMeta::new(quote.tick_arrays[1], false),
+            AccountMeta::new(quote.tick_arrays[2], false),
+            AccountMeta::new(quote.oracle, false),
+        ],
+        data,
+    }
+}
+
+/// Client for Orca whirlpools
+pub struct OrcaClient {
+    rpc: Arc<dyn DynRpcProvider>,
+}
+
+impl OrcaClient {
+    /// Program the client trades against
+    pub const PROGRAM_ID: Pubkey = ORCA_WHIRLPOOL_PROGRAM_ID;
+
+    /// Name configs use for the program in `agent.allowed_protocols`
+    pub const PROTOCOL_NAME: &'static str = agent_wallet_core::protocols::ORCA_WHIRLPOOL;
+
+    /// Create a client querying `rpc`
+    pub fn new(rpc: Arc<dyn DynRpcProvider>) -> Self {
+        Self { rpc }
+    }
+
+    /// Fetch and decode a whirlpool
+    pub async fn get_whirlpool(&self, whirlpool: &Pubkey) -> Result<Whirlpool> {
+        Whirlpool::decode(*whirlpool, &self.rpc.get_account(whirlpool).await?)
+    }
+
+    /// Quote selling `amount_in` raw units of token A (`a_to_b`) or token B
+    pub async fn get_quote(
+        &self,
+        whirlpool: Pubkey,
+        amount_in: u64,
+        a_to_b: bool,
+    ) -> Result<WhirlpoolQuote> {
+        let pool = self.get_whirlpool(&whirlpool).await?;
+        self.quote_whirlpool(&pool, amount_in, a_to_b).await
+    }
+
+    /// Quote a swap on an already decoded whirlpool, fetching its tick arrays
+    pub async fn quote_whirlpool(
+        &self,
+        pool: &Whirlpool,
+        amount_in: u64,
+        a_to_b: bool,
+    ) -> Result<WhirlpoolQuote> {
+        let starts = pool.tick_array_start_indexes(a_to_b);
+        let addresses = starts.map(|start| pool.tick_array_address(start));
+        let accounts = self.rpc.get_multiple_accounts(&addresses).await?;
+
+        let mut slots = Vec::with_capacity(3);
+        for ((start, address), account) in starts.into_iter().zip(addresses).zip(accounts) {
+            let array = account
+                .map(|account| TickArray::decode(address, &account))
+                .transpose()?;
+            slots.push(TickArraySlot {
+                address,
+                start_tick_index: start,
+                array,
+            });
+        }
+        let slots: [TickArraySlot; 3] = slots
+            .try_into()
+            .map_err(|_| DappError::protocol("RPC returned the wrong number of tick arrays"))?;
+
+        let quote = pool.quote(slots, amount_in, a_to_b)?;
+        debug!(
+            "Orca quote on {}: {} in, {} out, {} fee",
+            pool.address, quote.consumed_amount_in, quote.estimated_amount_out, quote.fee_amount
+        );
+        Ok(quote)
+    }
+
+    /// Whirlpools trading two mints in either orientation, across fee tiers
+    pub async fn find_whirlpools(
+        &self,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+    ) -> Result<Vec<Whirlpool>> {
+        let mut pools = Vec::new();
+        for (a, b) in [(mint_a, mint_b), (mint_b, mint_a)] {
+            let accounts = ProgramAccountsQuery::new(ORCA_WHIRLPOOL_PROGRAM_ID)
+                .data_size(whirlpool_layout::SIZE as u64)
+                .memcmp_pubkey(whirlpool_layout::TOKEN_MINT_A, a)
+                .memcmp_pubkey(whirlpool_layout::TOKEN_MINT_B, b)
+                .fetch(self.rpc.as_ref())
+                .await?;
+            for (address, account) in accounts {
+                match Whirlpool::decode(address, &account) {
+                    Ok(pool) => pools.push(pool),
+                    Err(e) => debug!("Skipping Orca account {}: {}", address, e),
+                }
+            }
+        }
+        Ok(pools)
+    }
+
+    /// Best quote for a swap over every whirlpool of its pair
+    ///
+    /// Pairs usually have one whirlpool per fee tier; the one returning the
+    /// most output wins. Pools that cannot be quoted are skipped, and the
+    /// last of their errors is returned if none can.
+    pub async fn best_quote(&self, params: &SwapParams) -> Result<WhirlpoolQuote> {
+        let (input_mint, output_mint) = params.mints()?;
+        let pools = self.find_whirlpools(&input_mint, &output_mint).await?;
+        if pools.is_empty() {
+            return Err(DappError::PoolNotFound(format!(
+                "{}/{}",
+                input_mint, output_mint
+            )));
+        }
+        let amount_in = match params.raw_amount {
+            Some(amount) => amount,
+            None => ui_to_raw(params.amount, self.mint_decimals(&input_mint).await?)?,
+        };
+
+        let mut quotes = Vec::new();
+        let mut last_error = None;
+        for pool in &pools {
+            let a_to_b = pool.token_mint_a == input_mint;
+            match self.quote_whirlpool(pool, amount_in, a_to_b).await {
+                Ok(quote) => quotes.push(quote),
+                Err(e) => {
+                    debug!("Skipping whirlpool {}: {}", pool.address, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let best = quotes
+            .into_iter()
+            .max_by_key(|quote| quote.estimated_amount_out);
+        match (best, last_error) {
+            (Some(quote), _) => Ok(quote),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(DappError::PoolNotFound(format!(
+                "{}/{}",
+                input_mint, output_mint
+            ))),
+        }
+    }
+
+    /// Decimals of a token mint
+    async fn mint_decimals(&self, mint: &Pubkey) -> Result<u8> {
+        let account = self.rpc.get_account(mint).await?;
+        read_bytes::<1>(&account.data, MINT_DECIMALS).map(|[decimals]| decimals)
+    }
+
+    /// Build an unsigned transaction executing `quote` for `params.owner`
+    ///
+    /// The owner's token account for the output mint is created if needed.
+    /// Selling SOL wraps the amount into the owner's wrapped SOL account
+    /// first. Bought SOL and leftover wrapped SOL stay wrapped unless
+    /// [`WhirlpoolParams::auto_unwrap`] is set, which closes the wrapped SOL
+    /// account after the swap.
+    pub fn create_swap_transaction(
+        &self,
+        params: &WhirlpoolParams,
+        quote: &WhirlpoolQuote,
+    ) -> Result<Transaction> {
+        let owner = &params.owner;
+        let min_out = quote.min_out(params)?;
+        let (input_mint, output_mint) = quote.mints();
+        let owner_account_a = get_associated_token_address(owner, &quote.pool.token_mint_a);
+        let owner_account_b = get_associated_token_address(owner, &quote.pool.token_mint_b);
+
+        let mut instructions = Vec::new();
+        if input_mint == NATIVE_MINT {
+            instructions.extend(wrap_sol_instructions(owner, quote.amount_in)?);
+        }
+        instructions.push(create_associated_token_account_idempotent(
+            owner,
+            owner,
+            &output_mint,
+            &spl_token::id(),
+        ));
+        instructions.push(swap_instruction(
+            quote,
+            owner,
+            &owner_account_a,
+            &owner_account_b,
+            min_out,
+        ));
+        if params.auto_unwrap && (input_mint == NATIVE_MINT || output_mint == NATIVE_MINT) {
+            instructions.push(unwrap_sol_instruction(owner)?);
+        }
+
+        debug!(
+            "Orca swap through {}: {} in, {} expected, {} minimum",
+            quote.pool.address, quote.amount_in, quote.estimated_amount_out, min_out
+        );
+        Ok(Transaction::new_with_payer(&instructions, Some(owner)))
+    }
+}
+
+#[async_trait]
+impl ProtocolClient for OrcaClient {
+    fn protocol(&self) -> DexProtocol {
+        DexProtocol::Orca
+    }
+
+    fn supported_actions(&self) -> Vec<ProtocolAction> {
+        vec![ProtocolAction::Swap]
+    }
+
+    async fn quote_swap(&self, params: &SwapParams) -> Result<SwapQuote> {
+        let quote = self.best_quote(params).await?;
+        quote.swap_quote(&whirlpool_params(&Pubkey::default(), params))
+    }
+
+    async fn build_swap(
+        &self,
+        owner: &Pubkey,
+        params: &SwapParams,
+    ) -> Result<(Transaction, SwapQuote)> {
+        let quote = self.best_quote(params).await?;
+        let params = whirlpool_params(owner, params);
+        let transaction = self.create_swap_transaction(&params, &quote)?;
+        Ok((transaction, quote.swap_quote(&params)?))
+    }
+}
+
+/// Whirlpool parameters carrying a generic swap's limits
+fn whirlpool_params(owner: &Pubkey, params: &SwapParams) -> WhirlpoolParams {
+    WhirlpoolParams {
+        owner: *owner,
+        slippage_bps: params.slippage_bps,
+        min_output_amount: params.min_output_amount,
+        auto_unwrap: params.auto_unwrap,
+    }
+}
+
+/// Square root price at `tick`, in Q64.64
+pub fn sqrt_price_from_tick(tick: i32) -> Result<u128> {
+    if !(MIN_TICK_INDEX..=MAX_TICK_INDEX).contains(&tick) {
+        return Err(DappError::invalid_params(format!(
+            "Tick {} out of range",
+            tick
+        )));
+    }
+    let abs = tick.unsigned_abs();
+    let mut ratio = if abs & 1 != 0 { TICK_RATIOS[0] } else { Q64 };
+    for (bit, factor) in TICK_RATIOS.iter().enumerate().skip(1) {
+        if abs & (1 << bit) != 0 {
+            ratio = mul_shift_64(ratio, *factor);
+        }
+    }
+    Ok(if tick > 0 { u128::MAX / ratio } else { ratio })
+}
+
+/// Result of swapping within one tick range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SwapStep {
+    next_sqrt_price: u128,
+    amount_in: u128,
+    amount_out: u128,
+    fee_amount: u128,
+}
+
+/// Swap as much of `remaining` as fits between `sqrt_price` and `target`
+fn swap_step(
+    remaining: u128,
+    fee_rate: u16,
+    liquidity: u128,
+    sqrt_price: u128,
+    target: u128,
+    a_to_b: bool,
+) -> Result<SwapStep> {
+    let fee_rate = u128::from(fee_rate);
+    let amount_less_fee = mul_div(
+        remaining,
+        FEE_RATE_DENOMINATOR - fee_rate,
+        FEE_RATE_DENOMINATOR,
+        false,
+    )?;
+    let to_target = if a_to_b {
+        delta_a(target, sqrt_price, liquidity, true)?
+    } else {
+        delta_b(sqrt_price, target, liquidity, true)?
+    };
+
+    let (next_sqrt_price, amount_in, fee_amount) = if amount_less_fee >= to_target {
+        let fee = mul_div(to_target, fee_rate, FEE_RATE_DENOMINATOR - fee_rate, true)?;
+        (target, to_target, fee)
+    } else {
+        let next = next_sqrt_price_from_input(sqrt_price, liquidity, amount_less_fee, a_to_b)?;
+        (next, amount_less_fee, remaining - amount_less_fee)
+    };
+    let amount_out = if a_to_b {
+        delta_b(next_sqrt_price, sqrt_price, liquidity, false)?
+    } else {
+        delta_a(sqrt_price, next_sqrt_price, liquidity, false)?
+    };
+
+    Ok(SwapStep {
+        next_sqrt_price,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+/// Token A between two square root prices: `L * (upper - lower) / (upper * lower)`
+fn delta_a(lower: u128, upper: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let scaled = mul_div(liquidity, upper - lower, upper, round_up)?;
+    mul_div(scaled, Q64, lower, round_up)
+}
+
+/// Token B between two square root prices: `L * (upper - lower)`
+fn delta_b(lower: u128, upper: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    mul_div(liquidity, upper - lower, Q64, round_up)
+}
+
+/// Square root price after adding `amount` of the input token
+fn next_sqrt_price_from_input(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u128,
+    a_to_b: bool,
+) -> Result<u128> {
+    if a_to_b {
+        // Rounding up keeps the price from moving further than paid for
+        let denominator = liquidity
+            .checked_add(mul_div(amount, sqrt_price, Q64, false)?)
+            .ok_or_else(overflow)?;
+        mul_div(liquidity, sqrt_price, denominator, true)
+    } else {
+        sqrt_price
+            .checked_add(mul_div(amount, Q64, liquidity, false)?)
+            .ok_or_else(overflow)
+    }
+}
+
+/// Active liquidity after crossing a tick with `liquidity_net`
+fn cross_tick(liquidity: u128, liquidity_net: i128, a_to_b: bool) -> Result<u128> {
+    let net = liquidity_net.unsigned_abs();
+    // Moving down removes what moving up adds
+    let adds = (liquidity_net > 0) != a_to_b;
+    let liquidity = if adds {
+        liquidity.checked_add(net)
+    } else {
+        liquidity.checked_sub(net)
+    };
+    liquidity.ok_or_else(|| DappError::invalid_pool_data("tick liquidity out of range"))
+}
+
+/// `a * b / denominator` with a 256-bit intermediate product
+fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> Result<u128> {
+    if denominator == 0 {
+        return Err(DappError::protocol("Whirlpool math divided by zero"));
+    }
+    let (high, low) = full_mul(a, b);
+    if high >= denominator {
+        return Err(overflow());
+    }
+
+    // Long division of (high, low) by the denominator, one bit at a time
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+
+    if round_up && remainder != 0 {
+        quotient.checked_add(1).ok_or_else(overflow)
+    } else {
+        Ok(quotient)
+    }
+}
+
+/// Full product of two `u128` as `(high, low)` halves
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (low_high & MASK) + (high_low & MASK);
+    let low = (low_low & MASK) | (middle << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+/// `a * b >> 64` for Q64.64 factors below one
+fn mul_shift_64(a: u128, b: u128) -> u128 {
+    let (high, low) = full_mul(a, b);
+    (high << 64) | (low >> 64)
+}
+
+fn overflow() -> DappError {
+    DappError::protocol("Whirlpool math overflow")
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| DappError::protocol(format!("Amount {} overflows", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    /// Whirlpool at tick 0 with 0.3% fees and a tick spacing of 64
+    fn pool() -> Whirlpool {
+        Whirlpool {
+            address: Pubkey::new_unique(),
+            tick_spacing: 64,
+            fee_rate: 3000,
+            liquidity: LIQUIDITY,
+            sqrt_price: Q64,
+            tick_current_index: 0,
+            token_mint_a: Pubkey::new_unique(),
+            token_vault_a: Pubkey::new_unique(),
+            token_mint_b: Pubkey::new_unique(),
+            token_vault_b: Pubkey::new_unique(),
+        }
+    }
+
+    /// Slots for the pool's tick arrays; `ticks` lists initialized ticks and
+    /// their net liquidity, `missing` the start indexes left uninitialized
+    fn slots(
+        pool: &Whirlpool,
+        a_to_b: bool,
+        ticks: &[(i32, i128)],
+        missing: &[i32],
+    ) -> [TickArraySlot; 3] {
+        let spacing = i32::from(pool.tick_spacing);
+        pool.tick_array_start_indexes(a_to_b).map(|start| {
+            let mut array = vec![Tick::default(); TICK_ARRAY_SIZE as usize];
+            for (tick, net) in ticks {
+                let offset = tick - start;
+                if (0..spacing * TICK_ARRAY_SIZE).contains(&offset) {
+                    array[(offset / spacing) as usize] = Tick {
+                        initialized: true,
+                        liquidity_net: *net,
+                        liquidity_gross: net.unsigned_abs(),
+                    };
+                }
+            }
+            TickArraySlot {
+                address: pool.tick_array_address(start),
+                start_tick_index: start,
+                array: (!missing.contains(&start)).then(|| TickArray {
+                    address: pool.tick_array_address(start),
+                    start_tick_index: start,
+                    ticks: array,
+                }),
+            }
+        })
+    }
+
+    #[test]
+    fn test_sqrt_price_from_tick() -> Result<()> {
+        for (tick, expected) in [
+            (0, 18_446_744_073_709_551_616),
+            (1, 18_447_666_387_855_959_851),
+            (-1, 18_445_821_805_675_392_311),
+            (64, 18_505_865_242_158_250_042),
+            (-64, 18_387_811_781_193_591_352),
+            (22_880, 57_906_560_509_842_916_966),
+            (-22_880, 5_876_404_399_171_618_955),
+            (MIN_TICK_INDEX, MIN_SQRT_PRICE),
+        ] {
+            assert_eq!(sqrt_price_from_tick(tick)?, expected, "tick {}", tick);
+        }
+        assert!(sqrt_price_from_tick(MAX_TICK_INDEX)? >= MAX_SQRT_PRICE);
+        assert!(sqrt_price_from_tick(MAX_TICK_INDEX + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mul_div_uses_full_width_product() -> Result<()> {
+        let (a, b, denominator) = ((1 << 100) + 3, (1 << 90) + 7, (1 << 64) + 11);
+        assert_eq!(
+            mul_div(a, b, denominator, false)?,
+            85_070_591_730_234_615_815_115_106_136_478_449_694
+        );
+        assert_eq!(
+            mul_div(a, b, denominator, true)?,
+            85_070_591_730_234_615_815_115_106_136_478_449_695
+        );
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX, false)?, u128::MAX);
+        assert!(mul_div(u128::MAX, 2, 1, false).is_err());
+        assert!(mul_div(1, 1, 0, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tick_array_start_indexes() {
+        let mut pool = pool();
+        assert_eq!(pool.tick_array_start_indexes(true), [0, -5632, -11264]);
+        assert_eq!(pool.tick_array_start_indexes(false), [0, 5632, 11264]);
+
+        pool.tick_current_index = -1;
+        assert_eq!(pool.tick_array_start_indexes(true), [-5632, -11264, -16896]);
+
+        // The next tick up already lies in the following array
+        pool.tick_current_index = 5600;
+        assert_eq!(pool.tick_array_start_indexes(false), [5632, 11264, 16896]);
+    }
+
+    #[test]
+    fn test_quote_within_one_tick_range() -> Result<()> {
+        let pool = pool();
+        let ticks = [(-128, 500_000_000_000), (128, -500_000_000_000)];
+
+        let quote = pool.quote(slots(&pool, true, &ticks, &[]), 1_000_000, true)?;
+        assert_eq!(quote.consumed_amount_in, 1_000_000);
+        assert_eq!(quote.estimated_amount_out, 996_999);
+        assert_eq!(quote.fee_amount, 3_000);
+        assert_eq!(quote.end_sqrt_price, 18_446_725_682_324_046_339);
+        assert_eq!(quote.price_impact_bps()?, 0);
+
+        let quote = pool.quote(slots(&pool, false, &ticks, &[]), 1_000_000, false)?;
+        assert_eq!(quote.estimated_amount_out, 996_999);
+        assert_eq!(quote.fee_amount, 3_000);
+        assert_eq!(quote.end_sqrt_price, 18_446_762_465_113_393_104);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_crosses_initialized_tick() -> Result<()> {
+        let pool = pool();
+        let slots = slots(&pool, true, &[(-128, 500_000_000_000)], &[]);
+
+        let quote = pool.quote(slots, 20_000_000_000, true)?;
+        assert_eq!(quote.consumed_amount_in, 20_000_000_000);
+        assert_eq!(quote.estimated_amount_out, 19_377_866_604);
+        assert_eq!(quote.fee_amount, 60_000_001);
+        assert_eq!(quote.end_sqrt_price, 17_849_503_294_313_510_453);
+        assert!(quote.end_sqrt_price < sqrt_price_from_tick(-128)?);
+        assert_eq!(quote.price_impact_bps()?, 281);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_into_uninitialized_tick_array_fails() -> Result<()> {
+        let pool = pool();
+        let slots = slots(&pool, false, &[(128, -500_000_000_000)], &[5632, 11264]);
+        let missing = slots[1].address;
+
+        match pool.quote(slots.clone(), 1_000_000_000_000_000, false) {
+            Err(DappError::UninitializedTickArray {
+                address,
+                start_tick_index,
+            }) => {
+                assert_eq!(address, missing);
+                assert_eq!(start_tick_index, 5632);
+            }
+            other => {
+                return Err(DappError::protocol(format!(
+                    "expected an uninitialized tick array error, got {:?}",
+                    other
+                )))
+            }
+        }
+
+        // Swaps staying inside the first array do not need the others
+        assert!(pool.quote(slots, 1_000_000, false).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_instruction_accounts_and_data() -> Result<()> {
+        let pool = pool();
+        let quote = pool.quote(slots(&pool, true, &[], &[]), 1_000_000, true)?;
+        let (owner, account_a, account_b) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let params = WhirlpoolParams::new(owner).with_slippage(1.0);
+        let min_out = quote.min_out(&params)?;
+        assert_eq!(min_out, quote.estimated_amount_out * 99 / 100);
+
+        let instruction = swap_instruction(&quote, &owner, &account_a, &account_b, min_out);
+        assert_eq!(instruction.program_id, ORCA_WHIRLPOOL_PROGRAM_ID);
+        let expected = [
+            (spl_token::id(), false, false),
+            (owner, false, true),
+            (pool.address, true, false),
+            (account_a, true, false),
+            (pool.token_vault_a, true, false),
+            (account_b, true, false),
+            (pool.token_vault_b, true, false),
+            (quote.tick_arrays[0], true, false),
+            (quote.tick_arrays[1], true, false),
+            (quote.tick_arrays[2], true, false),
+            (pool.oracle_address(), true, false),
+        ];
+        let actual: Vec<_> = instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable, meta.is_signer))
+            .collect();
+        assert_eq!(actual, expected);
+
+        assert_eq!(instruction.data.len(), 42);
+        assert_eq!(instruction.data[..8], SWAP_DISCRIMINATOR);
+        assert_eq!(instruction.data[8..16], 1_000_000u64.to_le_bytes());
+        assert_eq!(instruction.data[16..24], min_out.to_le_bytes());
+        assert_eq!(instruction.data[24..40], MIN_SQRT_PRICE.to_le_bytes());
+        assert_eq!(instruction.data[40..], [1, 1]);
+        Ok(())
+    }
+}
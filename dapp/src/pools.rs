@@ -0,0 +1,924 @@
+//! Memoized protocol pool metadata
+//!
+//! Discovering Raydium and Orca pools requires `getProgramAccounts` scans over
+//! the whole program, which is slow (and often rate limited) on public RPC
+//! nodes while the result rarely changes. [`PoolCache`] keeps the discovered
+//! pool metadata in a versioned JSON file under the config directory, loads it
+//! on construction and only rescans a protocol when a requested pair is
+//! missing or the cached snapshot is older than the configured TTL.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use agent_wallet_core::{RpcClient, RpcClientConfig};
+//! use agent_wallet_dapp::pools::{PoolCache, TokenPair};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let rpc = RpcClient::new(RpcClientConfig::single_endpoint("https://api.mainnet-beta.solana.com")).await?;
+//! let cache = PoolCache::open_default(Arc::new(rpc));
+//!
+//! let pair: TokenPair = "SOL/USDC".parse()?;
+//! for pool in cache.find_pools(&pair).await? {
+//!     println!("{} {} ({} bps)", pool.protocol, pool.address, pool.fee_bps());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_wallet_core::{DynRpcProvider, ProgramAccountsQuery};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{account::Account, pubkey, pubkey::Pubkey};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info, warn};
+
+use crate::error::{DappError, Result};
+
+/// Version of the on-disk cache format; files with another version are rebuilt
+pub const POOL_CACHE_VERSION: u32 = 1;
+
+/// Default age after which a protocol's cached pools are rescanned
+pub const DEFAULT_POOL_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Minimum delay between two lazy rescans of the same protocol, so lookups of
+/// a pair that has no pool do not trigger a scan every time
+pub const MIN_LAZY_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+pub use agent_wallet_core::protocols::{ORCA_WHIRLPOOL_PROGRAM_ID, RAYDIUM_AMM_V4_PROGRAM_ID};
+
+/// Well-known token symbols accepted in pair strings
+const KNOWN_TOKENS: &[(&str, Pubkey)] = &[
+    (
+        "SOL",
+        pubkey!("So11111111111111111111111111111111111111112"),
+    ),
+    (
+        "USDC",
+        pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+    ),
+    (
+        "USDT",
+        pubkey!("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB"),
+    ),
+];
+
+/// Raydium AMM v4 `AmmInfo` layout
+pub(crate) mod raydium_layout {
+    pub const SIZE: usize = 752;
+    pub const NONCE: usize = 8;
+    pub const COIN_DECIMALS: usize = 32;
+    pub const PC_DECIMALS: usize = 40;
+    pub const SWAP_FEE_NUMERATOR: usize = 176;
+    pub const SWAP_FEE_DENOMINATOR: usize = 184;
+    pub const NEED_TAKE_PNL_COIN: usize = 192;
+    pub const NEED_TAKE_PNL_PC: usize = 200;
+    pub const COIN_VAULT: usize = 336;
+    pub const PC_VAULT: usize = 368;
+    pub const COIN_MINT: usize = 400;
+    pub const PC_MINT: usize = 432;
+    pub const OPEN_ORDERS: usize = 496;
+    pub const MARKET: usize = 528;
+    pub const MARKET_PROGRAM: usize = 560;
+    pub const TARGET_ORDERS: usize = 592;
+    pub const LP_AMOUNT: usize = 720;
+}
+
+/// Orca `Whirlpool` account layout
+pub(crate) mod whirlpool_layout {
+    pub const SIZE: usize = 653;
+    pub const TICK_SPACING: usize = 41;
+    pub const FEE_RATE: usize = 45;
+    pub const LIQUIDITY: usize = 49;
+    pub const SQRT_PRICE: usize = 65;
+    pub const TICK_CURRENT_INDEX: usize = 81;
+    pub const TOKEN_MINT_A: usize = 101;
+    pub const TOKEN_VAULT_A: usize = 133;
+    pub const TOKEN_MINT_B: usize = 181;
+    pub const TOKEN_VAULT_B: usize = 213;
+}
+
+/// Protocols whose pools can be discovered and cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolProtocol {
+    /// Raydium AMM v4 constant-product pools
+    Raydium,
+    /// Orca Whirlpool concentrated-liquidity pools
+    Orca,
+}
+
+impl PoolProtocol {
+    /// All supported protocols
+    pub const ALL: [PoolProtocol; 2] = [PoolProtocol::Raydium, PoolProtocol::Orca];
+
+    /// Program owning the protocol's pool accounts
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            Self::Raydium => RAYDIUM_AMM_V4_PROGRAM_ID,
+            Self::Orca => ORCA_WHIRLPOOL_PROGRAM_ID,
+        }
+    }
+
+    /// Size of a pool state account, used as the scan filter
+    fn pool_account_size(&self) -> usize {
+        match self {
+            Self::Raydium => raydium_layout::SIZE,
+            Self::Orca => whirlpool_layout::SIZE,
+        }
+    }
+
+    /// Protocol name as used in the cache file and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Raydium => "raydium",
+            Self::Orca => "orca",
+        }
+    }
+}
+
+impl fmt::Display for PoolProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PoolProtocol {
+    type Err = DappError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "raydium" => Ok(Self::Raydium),
+            "orca" => Ok(Self::Orca),
+            other => Err(DappError::invalid_params(format!(
+                "Unknown protocol '{}', expected raydium or orca",
+                other
+            ))),
+        }
+    }
+}
+
+/// Coarse liquidity indicator recorded when the pool was last scanned
+///
+/// Buckets are orders of magnitude of the raw on-chain liquidity value (LP
+/// supply for Raydium, virtual liquidity for Orca) and are only meant to rank
+/// candidate pools, not to quote against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiquidityBucket {
+    /// No liquidity
+    Empty,
+    /// Below 10^6 raw units
+    Low,
+    /// Below 10^10 raw units
+    Medium,
+    /// 10^10 raw units or more
+    High,
+}
+
+impl LiquidityBucket {
+    /// Bucket a raw liquidity value
+    pub fn from_raw(liquidity: u128) -> Self {
+        match liquidity {
+            0 => Self::Empty,
+            1..=999_999 => Self::Low,
+            1_000_000..=9_999_999_999 => Self::Medium,
+            _ => Self::High,
+        }
+    }
+}
+
+/// Cached metadata for a single pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolMetadata {
+    /// Protocol the pool belongs to
+    pub protocol: PoolProtocol,
+    /// Pool state account
+    pub address: Pubkey,
+    /// First token mint (coin mint / token A)
+    pub mint_a: Pubkey,
+    /// Second token mint (pc mint / token B)
+    pub mint_b: Pubkey,
+    /// Swap fee in parts per million
+    pub fee_ppm: u32,
+    /// Liquidity bucket at the time of the last scan
+    pub liquidity: LiquidityBucket,
+    /// When the pool was last seen on chain
+    pub last_seen: DateTime<Utc>,
+}
+
+impl PoolMetadata {
+    /// Swap fee in basis points
+    pub fn fee_bps(&self) -> f64 {
+        self.fee_ppm as f64 / 100.0
+    }
+
+    /// Whether the pool trades the given pair, in either direction
+    pub fn matches(&self, pair: &TokenPair) -> bool {
+        (self.mint_a == pair.base && self.mint_b == pair.quote)
+            || (self.mint_a == pair.quote && self.mint_b == pair.base)
+    }
+}
+
+/// A token pair such as `SOL/USDC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenPair {
+    /// Base token mint
+    pub base: Pubkey,
+    /// Quote token mint
+    pub quote: Pubkey,
+}
+
+impl TokenPair {
+    /// Create a pair from two mints
+    pub fn new(base: Pubkey, quote: Pubkey) -> Self {
+        Self { base, quote }
+    }
+}
+
+impl FromStr for TokenPair {
+    type Err = DappError;
+
+    /// Parse `BASE/QUOTE` where each side is a known symbol or a mint address
+    fn from_str(s: &str) -> Result<Self> {
+        let (base, quote) = s.split_once('/').ok_or_else(|| {
+            DappError::invalid_params(format!("Invalid pair '{}', expected BASE/QUOTE", s))
+        })?;
+        Ok(Self::new(resolve_token(base)?, resolve_token(quote)?))
+    }
+}
+
+impl fmt::Display for TokenPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            token_label(&self.base),
+            token_label(&self.quote)
+        )
+    }
+}
+
+/// Resolve a token symbol or mint address
+pub(crate) fn resolve_token(token: &str) -> Result<Pubkey> {
+    let token = token.trim();
+    KNOWN_TOKENS
+        .iter()
+        .find(|(symbol, _)| symbol.eq_ignore_ascii_case(token))
+        .map(|(_, mint)| Ok(*mint))
+        .unwrap_or_else(|| Ok(Pubkey::from_str(token)?))
+}
+
+/// Symbol of a well-known mint, or the mint address itself
+fn token_label(mint: &Pubkey) -> String {
+    KNOWN_TOKENS
+        .iter()
+        .find(|(_, known)| known == mint)
+        .map(|(symbol, _)| symbol.to_string())
+        .unwrap_or_else(|| mint.to_string())
+}
+
+/// Cached pools of a single protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProtocolSnapshot {
+    refreshed_at: DateTime<Utc>,
+    pools: Vec<PoolMetadata>,
+}
+
+/// On-disk cache file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PoolCacheFile {
+    version: u32,
+    protocols: BTreeMap<PoolProtocol, ProtocolSnapshot>,
+}
+
+impl Default for PoolCacheFile {
+    fn default() -> Self {
+        Self {
+            version: POOL_CACHE_VERSION,
+            protocols: BTreeMap::new(),
+        }
+    }
+}
+
+/// Persistent cache of discovered protocol pools
+pub struct PoolCache {
+    /// RPC provider used for program account scans
+    rpc: Arc<dyn DynRpcProvider>,
+    /// Cache file location
+    path: PathBuf,
+    /// Age after which a protocol snapshot is rescanned
+    ttl: Duration,
+    /// In-memory copy of the cache file
+    state: RwLock<PoolCacheFile>,
+    /// Serializes scans so concurrent lookups don't rescan the same program
+    refresh_lock: Mutex<()>,
+}
+
+impl PoolCache {
+    /// Open the cache stored at `path`
+    ///
+    /// A missing, corrupted or outdated cache file is not an error: the cache
+    /// starts empty and is rebuilt on the next lookup.
+    pub fn new(rpc: Arc<dyn DynRpcProvider>, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let state = Self::read_file(&path);
+
+        Self {
+            rpc,
+            path,
+            ttl: DEFAULT_POOL_CACHE_TTL,
+            state: RwLock::new(state),
+            refresh_lock: Mutex::new(()),
+        }
+    }
+
+    /// Open the cache at its default location in the config directory
+    pub fn open_default(rpc: Arc<dyn DynRpcProvider>) -> Self {
+        Self::new(rpc, Self::default_path())
+    }
+
+    /// Default cache file location (`~/.agent-wallet/cache/pools.json`)
+    pub fn default_path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".agent-wallet/cache/pools.json")
+    }
+
+    /// Set the age after which cached pools are rescanned
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Cache file location
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// When a protocol was last scanned, if ever
+    pub async fn refreshed_at(&self, protocol: PoolProtocol) -> Option<DateTime<Utc>> {
+        let state = self.state.read().await;
+        state
+            .protocols
+            .get(&protocol)
+            .map(|snapshot| snapshot.refreshed_at)
+    }
+
+    /// All cached pools, optionally restricted to one protocol (no RPC calls)
+    pub async fn pools(&self, protocol: Option<PoolProtocol>) -> Vec<PoolMetadata> {
+        let state = self.state.read().await;
+        state
+            .protocols
+            .iter()
+            .filter(|(cached, _)| protocol.map_or(true, |p| p == **cached))
+            .flat_map(|(_, snapshot)| snapshot.pools.iter().cloned())
+            .collect()
+    }
+
+    /// Find the pools trading a pair, scanning on chain only when needed
+    ///
+    /// Protocols are rescanned when the pair is missing from the cache (at
+    /// most once per [`MIN_LAZY_REFRESH_INTERVAL`]) or when their snapshot is
+    /// older than the TTL. A protocol whose scan fails keeps its cached pools
+    /// and does not stop the others from being scanned; the lookup only fails
+    /// with the scan error when no protocol has a pool for the pair. Results
+    /// are sorted by liquidity, deepest first.
+    pub async fn find_pools(&self, pair: &TokenPair) -> Result<Vec<PoolMetadata>> {
+        let cached = self.matching(pair).await;
+        let now = Utc::now();
+
+        let mut to_refresh = Vec::new();
+        for protocol in PoolProtocol::ALL {
+            let age = self
+                .refreshed_at(protocol)
+                .await
+                .and_then(|at| now.signed_duration_since(at).to_std().ok());
+            let needs_refresh = match age {
+                None => true,
+                Some(age) if age >= self.ttl => true,
+                Some(age) => cached.is_empty() && age >= MIN_LAZY_REFRESH_INTERVAL,
+            };
+            if needs_refresh {
+                to_refresh.push(protocol);
+            }
+        }
+
+        if to_refresh.is_empty() {
+            debug!("Pool cache hit for {}", pair);
+        }
+        let mut failures = Vec::new();
+        for protocol in to_refresh {
+            // Stale metadata is still useful when the node refuses the scan
+            if let Err(e) = self.refresh_protocol(protocol).await {
+                warn!(
+                    "Keeping stale {} pools after failed refresh: {}",
+                    protocol, e
+                );
+                failures.push(e);
+            }
+        }
+
+        let mut pools = self.matching(pair).await;
+        if pools.is_empty() {
+            return Err(failures
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| DappError::PoolNotFound(pair.to_string())));
+        }
+        pools.sort_by(|a, b| b.liquidity.cmp(&a.liquidity));
+        Ok(pools)
+    }
+
+    /// Rescan one protocol, or all of them, and persist the result
+    ///
+    /// Returns the number of pools discovered.
+    pub async fn refresh(&self, protocol: Option<PoolProtocol>) -> Result<usize> {
+        let protocols = match protocol {
+            Some(protocol) => vec![protocol],
+            None => PoolProtocol::ALL.to_vec(),
+        };
+
+        let mut discovered = 0;
+        for protocol in protocols {
+            discovered += self.refresh_protocol(protocol).await?;
+        }
+        Ok(discovered)
+    }
+
+    /// Cached pools matching a pair
+    async fn matching(&self, pair: &TokenPair) -> Vec<PoolMetadata> {
+        let state = self.state.read().await;
+        state
+            .protocols
+            .values()
+            .flat_map(|snapshot| snapshot.pools.iter())
+            .filter(|pool| pool.matches(pair))
+            .cloned()
+            .collect()
+    }
+
+    /// Scan a protocol's program accounts and replace its snapshot
+    async fn refresh_protocol(&self, protocol: PoolProtocol) -> Result<usize> {
+        let _guard = self.refresh_lock.lock().await;
+
+        let started = std::time::Instant::now();
+        let pools = discover_pools(self.rpc.as_ref(), protocol).await?;
+        let count = pools.len();
+        info!(
+            "Discovered {} {} pools in {:?}",
+            count,
+            protocol,
+            started.elapsed()
+        );
+
+        let mut state = self.state.write().await;
+        state.protocols.insert(
+            protocol,
+            ProtocolSnapshot {
+                refreshed_at: Utc::now(),
+                pools,
+            },
+        );
+        self.write_file(&state)?;
+
+        Ok(count)
+    }
+
+    /// Read the cache file, falling back to an empty cache
+    fn read_file(path: &Path) -> PoolCacheFile {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return PoolCacheFile::default(),
+            Err(e) => {
+                warn!("Failed to read pool cache {}: {}", path.display(), e);
+                return PoolCacheFile::default();
+            }
+        };
+
+        match serde_json::from_slice::<PoolCacheFile>(&contents) {
+            Ok(file) if file.version == POOL_CACHE_VERSION => file,
+            Ok(file) => {
+                warn!(
+                    "Pool cache {} has version {} (expected {}), rebuilding",
+                    path.display(),
+                    file.version,
+                    POOL_CACHE_VERSION
+                );
+                PoolCacheFile::default()
+            }
+            Err(e) => {
+                warn!(
+                    "Pool cache {} is corrupted, rebuilding: {}",
+                    path.display(),
+                    e
+                );
+                PoolCacheFile::default()
+            }
+        }
+    }
+
+    /// Atomically replace the cache file
+    fn write_file(&self, file: &PoolCacheFile) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                DappError::cache(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+
+        let contents = serde_json::to_vec_pretty(file)
+            .map_err(|e| DappError::serialization(format!("Failed to encode pool cache: {}", e)))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, contents).map_err(|e| {
+            DappError::cache(format!("Failed to write {}: {}", temp_path.display(), e))
+        })?;
+        fs::rename(&temp_path, &self.path).map_err(|e| {
+            DappError::cache(format!("Failed to replace {}: {}", self.path.display(), e))
+        })
+    }
+}
+
+/// Scan the program accounts of a protocol and decode its pools
+async fn discover_pools(
+    rpc: &dyn DynRpcProvider,
+    protocol: PoolProtocol,
+) -> Result<Vec<PoolMetadata>> {
+    let accounts = ProgramAccountsQuery::new(protocol.program_id())
+        .data_size(protocol.pool_account_size() as u64)
+        .fetch(rpc)
+        .await?;
+
+    let now = Utc::now();
+    let mut pools = Vec::with_capacity(accounts.len());
+    for (address, account) in accounts {
+        match decode_pool(protocol, address, &account, now) {
+            Ok(pool) => pools.push(pool),
+            Err(e) => debug!("Skipping {} account {}: {}", protocol, address, e),
+        }
+    }
+    Ok(pools)
+}
+
+/// Decode a pool state account
+fn decode_pool(
+    protocol: PoolProtocol,
+    address: Pubkey,
+    account: &Account,
+    seen_at: DateTime<Utc>,
+) -> Result<PoolMetadata> {
+    let data = account.data.as_slice();
+    if data.len() != protocol.pool_account_size() {
+        return Err(DappError::invalid_pool_data(format!(
+            "unexpected account size {}",
+            data.len()
+        )));
+    }
+
+    let (mint_a, mint_b, fee_ppm, liquidity) = match protocol {
+        PoolProtocol::Raydium => {
+            let numerator = read_u64(data, raydium_layout::SWAP_FEE_NUMERATOR)?;
+            let denominator = read_u64(data, raydium_layout::SWAP_FEE_DENOMINATOR)?;
+            if denominator == 0 {
+                return Err(DappError::invalid_pool_data("zero fee denominator"));
+            }
+            let fee_ppm = (numerator as u128 * 1_000_000 / denominator as u128) as u32;
+            (
+                read_pubkey(data, raydium_layout::COIN_MINT)?,
+                read_pubkey(data, raydium_layout::PC_MINT)?,
+                fee_ppm,
+                read_u64(data, raydium_layout::LP_AMOUNT)? as u128,
+            )
+        }
+        PoolProtocol::Orca => (
+            read_pubkey(data, whirlpool_layout::TOKEN_MINT_A)?,
+            read_pubkey(data, whirlpool_layout::TOKEN_MINT_B)?,
+            // Whirlpool fee rates are stored in hundredths of a basis point
+            read_u16(data, whirlpool_layout::FEE_RATE)? as u32,
+            read_u128(data, whirlpool_layout::LIQUIDITY)?,
+        ),
+    };
+
+    if mint_a == Pubkey::default() || mint_b == Pubkey::default() {
+        return Err(DappError::invalid_pool_data("uninitialized pool"));
+    }
+
+    Ok(PoolMetadata {
+        protocol,
+        address,
+        mint_a,
+        mint_b,
+        fee_ppm,
+        liquidity: LiquidityBucket::from_raw(liquidity),
+        last_seen: seen_at,
+    })
+}
+
+/// Borrow `N` bytes at `offset`
+pub(crate) fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| DappError::invalid_pool_data(format!("truncated at offset {}", offset)))
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+pub(crate) fn read_i32(data: &[u8], offset: usize) -> Result<i32> {
+    read_bytes(data, offset).map(i32::from_le_bytes)
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+pub(crate) fn read_u128(data: &[u8], offset: usize) -> Result<u128> {
+    read_bytes(data, offset).map(u128::from_le_bytes)
+}
+
+pub(crate) fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    read_bytes(data, offset).map(Pubkey::new_from_array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use agent_wallet_core::Error as CoreError;
+    use agent_wallet_core::RpcProvider;
+    use solana_client::rpc_config::RpcProgramAccountsConfig;
+    use solana_client::rpc_response::RpcSimulateTransactionResult;
+    use solana_sdk::{hash::Hash, signature::Signature, transaction::Transaction};
+    use solana_transaction_status::TransactionStatus;
+    use tempfile::tempdir;
+
+    /// RPC double serving fixed program accounts and counting scans
+    #[derive(Default)]
+    struct ScanRpc {
+        program_accounts: HashMap<Pubkey, Vec<(Pubkey, Account)>>,
+        /// Programs whose scans fail
+        refused: Vec<Pubkey>,
+        scans: AtomicUsize,
+    }
+
+    impl ScanRpc {
+        fn scans(&self) -> usize {
+            self.scans.load(Ordering::SeqCst)
+        }
+    }
+
+    fn unsupported<T>() -> agent_wallet_core::Result<T> {
+        Err(CoreError::rpc("not supported by ScanRpc"))
+    }
+
+    impl RpcProvider for ScanRpc {
+        async fn get_balance(&self, _pubkey: &Pubkey) -> agent_wallet_core::Result<u64> {
+            unsupported()
+        }
+
+        async fn get_account(&self, _pubkey: &Pubkey) -> agent_wallet_core::Result<Account> {
+            unsupported()
+        }
+
+        async fn get_multiple_accounts(
+            &self,
+            _pubkeys: &[Pubkey],
+        ) -> agent_wallet_core::Result<Vec<Option<Account>>> {
+            unsupported()
+        }
+
+        async fn get_latest_blockhash(&self) -> agent_wallet_core::Result<Hash> {
+            unsupported()
+        }
+
+        async fn send_transaction(
+            &self,
+            _transaction: &Transaction,
+        ) -> agent_wallet_core::Result<Signature> {
+            unsupported()
+        }
+
+        async fn simulate_transaction(
+            &self,
+            _transaction: &Transaction,
+        ) -> agent_wallet_core::Result<RpcSimulateTransactionResult> {
+            unsupported()
+        }
+
+        async fn get_signature_statuses(
+            &self,
+            _signatures: &[Signature],
+        ) -> agent_wallet_core::Result<Vec<Option<TransactionStatus>>> {
+            unsupported()
+        }
+
+        async fn get_program_accounts(
+            &self,
+            program_id: &Pubkey,
+            _config: Option<RpcProgramAccountsConfig>,
+        ) -> agent_wallet_core::Result<Vec<(Pubkey, Account)>> {
+            self.scans.fetch_add(1, Ordering::SeqCst);
+            if self.refused.contains(program_id) {
+                return unsupported();
+            }
+            Ok(self
+                .program_accounts
+                .get(program_id)
+                .cloned()
+                .unwrap_or_default())
+        }
+    }
+
+    fn raydium_pool(coin_mint: Pubkey, pc_mint: Pubkey, lp_amount: u64) -> Account {
+        let mut data = vec![0u8; raydium_layout::SIZE];
+        data[raydium_layout::SWAP_FEE_NUMERATOR..][..8].copy_from_slice(&25u64.to_le_bytes());
+        data[raydium_layout::SWAP_FEE_DENOMINATOR..][..8].copy_from_slice(&10_000u64.to_le_bytes());
+        data[raydium_layout::COIN_MINT..][..32].copy_from_slice(coin_mint.as_ref());
+        data[raydium_layout::PC_MINT..][..32].copy_from_slice(pc_mint.as_ref());
+        data[raydium_layout::LP_AMOUNT..][..8].copy_from_slice(&lp_amount.to_le_bytes());
+        Account {
+            lamports: 1,
+            data,
+            owner: RAYDIUM_AMM_V4_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn whirlpool(mint_a: Pubkey, mint_b: Pubkey, fee_rate: u16, liquidity: u128) -> Account {
+        let mut data = vec![0u8; whirlpool_layout::SIZE];
+        data[whirlpool_layout::FEE_RATE..][..2].copy_from_slice(&fee_rate.to_le_bytes());
+        data[whirlpool_layout::LIQUIDITY..][..16].copy_from_slice(&liquidity.to_le_bytes());
+        data[whirlpool_layout::TOKEN_MINT_A..][..32].copy_from_slice(mint_a.as_ref());
+        data[whirlpool_layout::TOKEN_MINT_B..][..32].copy_from_slice(mint_b.as_ref());
+        Account {
+            lamports: 1,
+            data,
+            owner: ORCA_WHIRLPOOL_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    /// Scan RPC with one SOL/USDC pool on each protocol
+    fn sol_usdc_rpc() -> Result<Arc<ScanRpc>> {
+        let pair: TokenPair = "SOL/USDC".parse()?;
+        let mut rpc = ScanRpc::default();
+        rpc.program_accounts.insert(
+            RAYDIUM_AMM_V4_PROGRAM_ID,
+            vec![(
+                Pubkey::new_unique(),
+                raydium_pool(pair.base, pair.quote, 5_000),
+            )],
+        );
+        rpc.program_accounts.insert(
+            ORCA_WHIRLPOOL_PROGRAM_ID,
+            vec![(
+                Pubkey::new_unique(),
+                whirlpool(pair.quote, pair.base, 3000, 50_000_000_000),
+            )],
+        );
+        Ok(Arc::new(rpc))
+    }
+
+    #[test]
+    fn test_token_pair_parsing() -> Result<()> {
+        let pair: TokenPair = "sol/USDC".parse()?;
+        assert_eq!(pair.to_string(), "SOL/USDC");
+
+        let mint = Pubkey::new_unique();
+        let pair: TokenPair = format!("{}/USDT", mint).parse()?;
+        assert_eq!(pair.base, mint);
+
+        assert!("SOL-USDC".parse::<TokenPair>().is_err());
+        assert!("SOL/NOTAMINT".parse::<TokenPair>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_pool_fee_and_liquidity() -> Result<()> {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let now = Utc::now();
+
+        let raydium = decode_pool(
+            PoolProtocol::Raydium,
+            Pubkey::new_unique(),
+            &raydium_pool(a, b, 5_000),
+            now,
+        )?;
+        assert_eq!((raydium.mint_a, raydium.mint_b), (a, b));
+        assert_eq!(raydium.fee_ppm, 2_500);
+        assert_eq!(raydium.liquidity, LiquidityBucket::Low);
+
+        let orca = decode_pool(
+            PoolProtocol::Orca,
+            Pubkey::new_unique(),
+            &whirlpool(a, b, 3000, 50_000_000_000),
+            now,
+        )?;
+        assert_eq!(orca.fee_bps(), 30.0);
+        assert_eq!(orca.liquidity, LiquidityBucket::High);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_avoids_program_account_scan() -> Result<()> {
+        let dir = tempdir().map_err(|e| DappError::cache(e.to_string()))?;
+        let path = dir.path().join("pools.json");
+        let pair: TokenPair = "SOL/USDC".parse()?;
+
+        let first_rpc = sol_usdc_rpc()?;
+        let cache = PoolCache::new(first_rpc.clone(), &path);
+        assert_eq!(cache.find_pools(&pair).await?.len(), 2);
+        assert_eq!(first_rpc.scans(), 2);
+
+        // A new cache loaded from disk answers without touching the RPC
+        let second_rpc = sol_usdc_rpc()?;
+        let reloaded = PoolCache::new(second_rpc.clone(), &path);
+        let pools = reloaded.find_pools(&pair).await?;
+        assert_eq!(second_rpc.scans(), 0);
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0].protocol, PoolProtocol::Orca);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_cache_is_rebuilt() -> Result<()> {
+        let dir = tempdir().map_err(|e| DappError::cache(e.to_string()))?;
+        let path = dir.path().join("pools.json");
+        fs::write(&path, b"{\"version\": 1, \"protocols\": [garbage")
+            .map_err(|e| DappError::cache(e.to_string()))?;
+
+        let rpc = sol_usdc_rpc()?;
+        let cache = PoolCache::new(rpc.clone(), &path);
+        assert!(cache.pools(None).await.is_empty());
+
+        let pools = cache.find_pools(&"USDC/SOL".parse()?).await?;
+        assert_eq!(pools.len(), 2);
+        assert_eq!(rpc.scans(), 2);
+
+        let contents = fs::read(&path).map_err(|e| DappError::cache(e.to_string()))?;
+        let rebuilt: PoolCacheFile = serde_json::from_slice(&contents)
+            .map_err(|e| DappError::serialization(e.to_string()))?;
+        assert_eq!(rebuilt.version, POOL_CACHE_VERSION);
+        assert_eq!(rebuilt.protocols.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_pair_after_fresh_scan_is_not_rescanned() -> Result<()> {
+        let dir = tempdir().map_err(|e| DappError::cache(e.to_string()))?;
+        let rpc = sol_usdc_rpc()?;
+        let cache = PoolCache::new(rpc.clone(), dir.path().join("pools.json"));
+
+        let unknown = TokenPair::new(Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(matches!(
+            cache.find_pools(&unknown).await,
+            Err(DappError::PoolNotFound(_))
+        ));
+        assert!(cache.find_pools(&unknown).await.is_err());
+        assert_eq!(rpc.scans(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_protocol_scan_does_not_hide_other_pools() -> Result<()> {
+        let dir = tempdir().map_err(|e| DappError::cache(e.to_string()))?;
+        let pair: TokenPair = "SOL/USDC".parse()?;
+        let mut rpc = ScanRpc::default();
+        rpc.refused.push(RAYDIUM_AMM_V4_PROGRAM_ID);
+        rpc.program_accounts.insert(
+            ORCA_WHIRLPOOL_PROGRAM_ID,
+            vec![(
+                Pubkey::new_unique(),
+                whirlpool(pair.base, pair.quote, 3000, 50_000_000_000),
+            )],
+        );
+        let rpc = Arc::new(rpc);
+        let cache = PoolCache::new(rpc.clone(), dir.path().join("pools.json"));
+
+        let pools = cache.find_pools(&pair).await?;
+        assert_eq!(rpc.scans(), 2);
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].protocol, PoolProtocol::Orca);
+        assert!(cache.refreshed_at(PoolProtocol::Raydium).await.is_none());
+
+        // With no pool anywhere, the scan failure is what gets reported
+        let unknown = TokenPair::new(Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(matches!(
+            cache.find_pools(&unknown).await,
+            Err(DappError::Wallet(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,463 @@
+//! Protocol-agnostic swaps and routing
+//!
+//! [`SwapParams`] and [`SwapQuote`] describe a swap independently of the DEX
+//! that fills it. A [`DexRouter`] holds several [`ProtocolClient`]s, asks each
+//! of them for a quote and builds the transaction with the one offering the
+//! highest output, so an agent emitting [`AgentAction::SwapTokens`] does not
+//! need to know which protocol is used.
+//!
+//! Protocols that fail to quote are skipped; their errors are kept on the
+//! [`RoutedSwap`] for diagnostics.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_dapp::protocol::{DexRouter, SwapParams};
+//! use agent_wallet_core::{Wallet, WalletConfig};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let wallet = Wallet::load("wallet.json", WalletConfig::new())?;
+//! let router = DexRouter::with_default_clients(wallet.rpc_client(), wallet.public_key());
+//!
+//! let mut routed = router
+//!     .route(SwapParams::new("SOL", "USDC", 1.0).with_slippage(0.5))
+//!     .await?;
+//! println!("Filled by {}: {} expected", routed.protocol, routed.quote.expected_out);
+//! wallet.sign_and_send(&mut routed.transaction).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use agent_wallet_core::{AgentAction, DynRpcProvider};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+use tracing::debug;
+
+use crate::common::ProtocolClient;
+use crate::error::{DappError, Result};
+use crate::pools::resolve_token;
+use crate::DEFAULT_SLIPPAGE_BPS;
+
+/// DeFi protocol a client talks to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DexProtocol {
+    /// Raydium AMM v4
+    Raydium,
+    /// Orca whirlpools
+    Orca,
+    /// Any other protocol, by name
+    Custom(String),
+}
+
+impl DexProtocol {
+    /// Protocol name
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Raydium => "raydium",
+            Self::Orca => "orca",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for DexProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Kind of operation a protocol client supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtocolAction {
+    /// Exchange one token for another
+    Swap,
+    /// Deposit into a liquidity pool
+    ProvideLiquidity,
+    /// Withdraw from a liquidity pool
+    RemoveLiquidity,
+}
+
+/// Parameters of a protocol operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ProtocolParams {
+    /// Swap parameters
+    Swap(SwapParams),
+}
+
+impl ProtocolParams {
+    /// Kind of operation described
+    pub fn action(&self) -> ProtocolAction {
+        match self {
+            Self::Swap(_) => ProtocolAction::Swap,
+        }
+    }
+}
+
+/// Parameters of a swap
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapParams {
+    /// Token sold, as a symbol or mint address
+    pub input_token: String,
+    /// Token bought, as a symbol or mint address
+    pub output_token: String,
+    /// Amount sold, in UI units of the input token
+    pub amount: f64,
+    /// Amount sold in raw units, overriding `amount`
+    #[serde(default)]
+    pub raw_amount: Option<u64>,
+    /// Accepted shortfall from the expected output, in basis points
+    pub slippage_bps: u16,
+    /// Minimum output in raw units, overriding the slippage-derived minimum
+    #[serde(default)]
+    pub min_output_amount: Option<u64>,
+    /// Close the wrapped SOL account after a swap selling or buying SOL
+    #[serde(default)]
+    pub auto_unwrap: bool,
+}
+
+impl SwapParams {
+    /// Swap `amount` of `input_token` for `output_token` with default slippage
+    pub fn new(
+        input_token: impl Into<String>,
+        output_token: impl Into<String>,
+        amount: f64,
+    ) -> Self {
+        Self {
+            input_token: input_token.into(),
+            output_token: output_token.into(),
+            amount,
+            raw_amount: None,
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            min_output_amount: None,
+            auto_unwrap: false,
+        }
+    }
+
+    /// Swap `amount` raw units of `input_mint` for `output_mint`
+    pub fn raw(input_mint: &Pubkey, output_mint: &Pubkey, amount: u64) -> Self {
+        let mut params = Self::new(input_mint.to_string(), output_mint.to_string(), 0.0);
+        params.raw_amount = Some(amount);
+        params
+    }
+
+    /// Set the slippage tolerance in percent
+    pub fn with_slippage(mut self, percent: f64) -> Self {
+        self.slippage_bps = (percent * 100.0).round().clamp(0.0, u16::MAX as f64) as u16;
+        self
+    }
+
+    /// Require at least `amount` raw units of output
+    pub fn with_min_output(mut self, amount: u64) -> Self {
+        self.min_output_amount = Some(amount);
+        self
+    }
+
+    /// Unwrap SOL left in the wrapped SOL account after the swap
+    pub fn with_auto_unwrap(mut self) -> Self {
+        self.auto_unwrap = true;
+        self
+    }
+
+    /// Input and output mints
+    pub fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        let input = resolve_token(&self.input_token)?;
+        let output = resolve_token(&self.output_token)?;
+        if input == output {
+            return Err(DappError::invalid_params("Cannot swap a token for itself"));
+        }
+        Ok((input, output))
+    }
+}
+
+impl TryFrom<&AgentAction> for SwapParams {
+    type Error = DappError;
+
+    fn try_from(action: &AgentAction) -> Result<Self> {
+        match action {
+            AgentAction::SwapTokens {
+                input_mint,
+                output_mint,
+                amount,
+                min_output_amount,
+            } => {
+                if *amount == 0 {
+                    return Err(DappError::invalid_params("Swap amount must be positive"));
+                }
+                let params = Self::raw(input_mint, output_mint, *amount);
+                Ok(if *min_output_amount > 0 {
+                    params.with_min_output(*min_output_amount)
+                } else {
+                    params
+                })
+            }
+            other => Err(DappError::invalid_params(format!(
+                "Not a swap: {}",
+                other.description()
+            ))),
+        }
+    }
+}
+
+/// Expected outcome of a swap, in raw token units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapQuote {
+    /// Output at the current pool state
+    pub expected_out: u64,
+    /// Output below which the swap fails on chain
+    pub min_out: u64,
+    /// Shortfall of the expected output from the spot price, in basis points
+    pub price_impact_bps: u32,
+    /// Pool fee taken from the input
+    pub fee: u64,
+}
+
+/// Swap routed through the protocol offering the best output
+#[derive(Debug, Clone)]
+pub struct RoutedSwap {
+    /// Protocol filling the swap
+    pub protocol: DexProtocol,
+    /// Quote the transaction was built from
+    pub quote: SwapQuote,
+    /// Unsigned transaction with the router's owner as fee payer
+    pub transaction: Transaction,
+    /// Protocols skipped because they failed, with their errors
+    pub skipped: Vec<(DexProtocol, String)>,
+}
+
+/// Routes swaps to whichever registered protocol quotes the highest output
+pub struct DexRouter {
+    owner: Pubkey,
+    clients: Vec<Arc<dyn ProtocolClient>>,
+}
+
+impl DexRouter {
+    /// Router building transactions for `owner`, with no protocols yet
+    pub fn new(owner: Pubkey) -> Self {
+        Self {
+            owner,
+            clients: Vec::new(),
+        }
+    }
+
+    /// Router with a client for every protocol compiled into this crate
+    #[cfg_attr(
+        not(any(feature = "raydium", feature = "orca")),
+        allow(unused_mut, unused_variables)
+    )]
+    pub fn with_default_clients(rpc: Arc<dyn DynRpcProvider>, owner: Pubkey) -> Self {
+        let mut router = Self::new(owner);
+        #[cfg(feature = "raydium")]
+        router
+            .clients
+            .push(Arc::new(crate::raydium::RaydiumClient::new(Arc::clone(
+                &rpc,
+            ))));
+        #[cfg(feature = "orca")]
+        router
+            .clients
+            .push(Arc::new(crate::orca::OrcaClient::new(Arc::clone(&rpc))));
+        router
+    }
+
+    /// Register a protocol client
+    pub fn with_client(mut self, client: Arc<dyn ProtocolClient>) -> Self {
+        self.clients.push(client);
+        self
+    }
+
+    /// Protocols registered, in registration order
+    pub fn protocols(&self) -> Vec<DexProtocol> {
+        self.clients
+            .iter()
+            .map(|client| client.protocol())
+            .collect()
+    }
+
+    /// Quote with every protocol and build the swap with the best one
+    ///
+    /// Should building the best quote fail, the next best is tried. Fails only
+    /// when no protocol can both quote and build the swap.
+    pub async fn route(&self, params: SwapParams) -> Result<RoutedSwap> {
+        let mut skipped = Vec::new();
+        let mut quoted = Vec::new();
+        for client in self
+            .clients
+            .iter()
+            .filter(|client| client.supported_actions().contains(&ProtocolAction::Swap))
+        {
+            match client.quote_swap(&params).await {
+                Ok(quote) => quoted.push((client, quote)),
+                Err(e) => {
+                    debug!("{} could not quote: {}", client.protocol(), e);
+                    skipped.push((client.protocol(), e.to_string()));
+                }
+            }
+        }
+
+        // Highest output first; registration order breaks ties
+        quoted.sort_by(|(_, a), (_, b)| b.expected_out.cmp(&a.expected_out));
+        for (client, _) in quoted {
+            match client.build_swap(&self.owner, &params).await {
+                Ok((transaction, quote)) => {
+                    return Ok(RoutedSwap {
+                        protocol: client.protocol(),
+                        quote,
+                        transaction,
+                        skipped,
+                    })
+                }
+                Err(e) => {
+                    debug!("{} could not build the swap: {}", client.protocol(), e);
+                    skipped.push((client.protocol(), e.to_string()));
+                }
+            }
+        }
+
+        let reasons = skipped
+            .iter()
+            .map(|(protocol, error)| format!("{}: {}", protocol, error))
+            .collect::<Vec<_>>();
+        Err(DappError::protocol(format!(
+            "No protocol can swap {} for {}{}",
+            params.input_token,
+            params.output_token,
+            if reasons.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", reasons.join("; "))
+            }
+        )))
+    }
+
+    /// Route an [`AgentAction::SwapTokens`]
+    pub async fn route_action(&self, action: &AgentAction) -> Result<RoutedSwap> {
+        self.route(SwapParams::try_from(action)?).await
+    }
+}
+
+/// Convert a UI amount of a token with `decimals` to raw units
+pub(crate) fn ui_to_raw(amount: f64, decimals: u8) -> Result<u64> {
+    let raw = (amount * 10f64.powi(i32::from(decimals))).round();
+    if !raw.is_finite() || raw <= 0.0 || raw > u64::MAX as f64 {
+        return Err(DappError::invalid_params(format!(
+            "Invalid swap amount {}",
+            amount
+        )));
+    }
+    Ok(raw as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    struct MockClient {
+        protocol: DexProtocol,
+        expected_out: Option<u64>,
+    }
+
+    impl MockClient {
+        fn quote(&self) -> Result<SwapQuote> {
+            let expected_out = self
+                .expected_out
+                .ok_or_else(|| DappError::PoolNotFound("no pool".to_string()))?;
+            Ok(SwapQuote {
+                expected_out,
+                min_out: expected_out * 99 / 100,
+                price_impact_bps: 10,
+                fee: 25,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ProtocolClient for MockClient {
+        fn protocol(&self) -> DexProtocol {
+            self.protocol.clone()
+        }
+
+        fn supported_actions(&self) -> Vec<ProtocolAction> {
+            vec![ProtocolAction::Swap]
+        }
+
+        async fn quote_swap(&self, _params: &SwapParams) -> Result<SwapQuote> {
+            self.quote()
+        }
+
+        async fn build_swap(
+            &self,
+            owner: &Pubkey,
+            _params: &SwapParams,
+        ) -> Result<(Transaction, SwapQuote)> {
+            Ok((Transaction::new_with_payer(&[], Some(owner)), self.quote()?))
+        }
+    }
+
+    fn mock(name: &str, expected_out: Option<u64>) -> Arc<dyn ProtocolClient> {
+        Arc::new(MockClient {
+            protocol: DexProtocol::Custom(name.to_string()),
+            expected_out,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_route_picks_highest_output() -> Result<()> {
+        let owner = Pubkey::new_unique();
+        let router = DexRouter::new(owner)
+            .with_client(mock("low", Some(9_800)))
+            .with_client(mock("broken", None))
+            .with_client(mock("high", Some(9_900)));
+
+        let routed = router.route(SwapParams::new("SOL", "USDC", 1.0)).await?;
+        assert_eq!(routed.protocol, DexProtocol::Custom("high".to_string()));
+        assert_eq!(routed.quote.expected_out, 9_900);
+        assert_eq!(
+            routed.transaction.message.account_keys.first(),
+            Some(&owner)
+        );
+        assert_eq!(routed.skipped.len(), 1);
+        assert_eq!(
+            routed.skipped[0].0,
+            DexProtocol::Custom("broken".to_string())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route_fails_when_no_protocol_quotes() -> Result<()> {
+        let router = DexRouter::new(Pubkey::new_unique()).with_client(mock("broken", None));
+        match router.route(SwapParams::new("SOL", "USDC", 1.0)).await {
+            Err(DappError::Protocol(message)) => assert!(message.contains("broken")),
+            other => {
+                return Err(DappError::protocol(format!(
+                    "expected a routing error, got {:?}",
+                    other.map(|routed| routed.protocol)
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_params_from_agent_action() -> Result<()> {
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let params = SwapParams::try_from(&AgentAction::SwapTokens {
+            input_mint,
+            output_mint,
+            amount: 5_000,
+            min_output_amount: 4_900,
+        })?;
+        assert_eq!(params.raw_amount, Some(5_000));
+        assert_eq!(params.min_output_amount, Some(4_900));
+        assert_eq!(params.mints()?, (input_mint, output_mint));
+        Ok(())
+    }
+}
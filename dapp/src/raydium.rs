@@ -0,0 +1,720 @@
+//! Raydium AMM v4 swaps
+//!
+//! [`RaydiumClient`] locates the AMM v4 pool for a mint pair with a filtered
+//! `getProgramAccounts` scan, decodes the pool and its OpenBook market to
+//! collect every account the swap touches, and quotes the trade with the
+//! constant-product formula against the current vault balances. The quote
+//! is returned next to the unsigned transaction so an agent can inspect the
+//! expected output and price impact before deciding to sign.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_dapp::raydium::{RaydiumClient, SwapParams};
+//! use agent_wallet_core::{Wallet, WalletConfig};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let wallet = Wallet::load("wallet.json", WalletConfig::new())?;
+//! let raydium = RaydiumClient::new(wallet.rpc_client());
+//!
+//! let params = SwapParams::new("SOL", "USDC", 1.0).with_slippage(0.5);
+//! let (mut transaction, quote) = raydium
+//!     .create_swap_transaction(&wallet.public_key(), &params)
+//!     .await?;
+//! if quote.price_impact_bps < 100 {
+//!     wallet.sign_and_send(&mut transaction).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use agent_wallet_core::token::{unwrap_sol_instruction, wrap_sol_instructions, NATIVE_MINT};
+use agent_wallet_core::{DynRpcProvider, ProgramAccountsQuery};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use tracing::debug;
+
+use crate::common::ProtocolClient;
+use crate::error::{DappError, Result};
+use crate::pools::{raydium_layout, read_pubkey, read_u64, RAYDIUM_AMM_V4_PROGRAM_ID};
+use crate::protocol::{ui_to_raw, DexProtocol, ProtocolAction};
+pub use crate::protocol::{SwapParams, SwapQuote};
+
+/// Instruction tag of the AMM v4 `SwapBaseIn` instruction
+const SWAP_BASE_IN: u8 = 9;
+
+/// Seed of the AMM authority PDA
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Basis points in one
+const BPS: u128 = 10_000;
+
+/// OpenBook `MarketState` layout, including the 5-byte account head
+mod market_layout {
+    pub const SIZE: usize = 388;
+    pub const VAULT_SIGNER_NONCE: usize = 45;
+    pub const COIN_VAULT: usize = 117;
+    pub const PC_VAULT: usize = 165;
+    pub const EVENT_QUEUE: usize = 253;
+    pub const BIDS: usize = 285;
+    pub const ASKS: usize = 317;
+}
+
+/// Parameters of a liquidity deposit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiquidityParams {
+    /// Pool state account
+    pub pool: Pubkey,
+    /// Amount of the pool's coin token, in UI units
+    pub coin_amount: f64,
+    /// Amount of the pool's pc token, in UI units
+    pub pc_amount: f64,
+}
+
+impl LiquidityParams {
+    /// Deposit into `pool`
+    pub fn new(pool: Pubkey, coin_amount: f64, pc_amount: f64) -> Self {
+        Self {
+            pool,
+            coin_amount,
+            pc_amount,
+        }
+    }
+}
+
+/// Decoded Raydium AMM v4 pool with the accounts a swap needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RaydiumPool {
+    /// Pool state account
+    pub address: Pubkey,
+    /// PDA owning the pool vaults
+    pub authority: Pubkey,
+    /// Pool open orders on the market
+    pub open_orders: Pubkey,
+    /// Pool target orders
+    pub target_orders: Pubkey,
+    /// Vault holding the coin token
+    pub coin_vault: Pubkey,
+    /// Vault holding the pc token
+    pub pc_vault: Pubkey,
+    /// Coin token mint
+    pub coin_mint: Pubkey,
+    /// Pc token mint
+    pub pc_mint: Pubkey,
+    /// Coin token decimals
+    pub coin_decimals: u8,
+    /// Pc token decimals
+    pub pc_decimals: u8,
+    /// OpenBook market of the pool
+    pub market: Pubkey,
+    /// Program owning the market
+    pub market_program: Pubkey,
+    /// Coin owed to the protocol, excluded from the reserves
+    pub need_take_pnl_coin: u64,
+    /// Pc owed to the protocol, excluded from the reserves
+    pub need_take_pnl_pc: u64,
+    /// Swap fee numerator
+    pub swap_fee_numerator: u64,
+    /// Swap fee denominator
+    pub swap_fee_denominator: u64,
+    /// LP token supply
+    pub lp_amount: u64,
+}
+
+impl RaydiumPool {
+    /// Decode an `AmmInfo` account
+    pub fn decode(address: Pubkey, account: &Account) -> Result<Self> {
+        let data = account.data.as_slice();
+        if data.len() != raydium_layout::SIZE {
+            return Err(DappError::invalid_pool_data(format!(
+                "unexpected account size {}",
+                data.len()
+            )));
+        }
+
+        let nonce = read_u64(data, raydium_layout::NONCE)?;
+        let nonce = u8::try_from(nonce)
+            .map_err(|_| DappError::invalid_pool_data(format!("invalid nonce {}", nonce)))?;
+        let authority =
+            Pubkey::create_program_address(&[AMM_AUTHORITY_SEED, &[nonce]], &account.owner)
+                .map_err(|e| DappError::invalid_pool_data(format!("invalid authority: {}", e)))?;
+
+        let swap_fee_denominator = read_u64(data, raydium_layout::SWAP_FEE_DENOMINATOR)?;
+        if swap_fee_denominator == 0 {
+            return Err(DappError::invalid_pool_data("zero fee denominator"));
+        }
+
+        Ok(Self {
+            address,
+            authority,
+            open_orders: read_pubkey(data, raydium_layout::OPEN_ORDERS)?,
+            target_orders: read_pubkey(data, raydium_layout::TARGET_ORDERS)?,
+            coin_vault: read_pubkey(data, raydium_layout::COIN_VAULT)?,
+            pc_vault: read_pubkey(data, raydium_layout::PC_VAULT)?,
+            coin_mint: read_pubkey(data, raydium_layout::COIN_MINT)?,
+            pc_mint: read_pubkey(data, raydium_layout::PC_MINT)?,
+            coin_decimals: read_decimals(data, raydium_layout::COIN_DECIMALS)?,
+            pc_decimals: read_decimals(data, raydium_layout::PC_DECIMALS)?,
+            market: read_pubkey(data, raydium_layout::MARKET)?,
+            market_program: read_pubkey(data, raydium_layout::MARKET_PROGRAM)?,
+            need_take_pnl_coin: read_u64(data, raydium_layout::NEED_TAKE_PNL_COIN)?,
+            need_take_pnl_pc: read_u64(data, raydium_layout::NEED_TAKE_PNL_PC)?,
+            swap_fee_numerator: read_u64(data, raydium_layout::SWAP_FEE_NUMERATOR)?,
+            swap_fee_denominator,
+            lp_amount: read_u64(data, raydium_layout::LP_AMOUNT)?,
+        })
+    }
+
+    /// Whether selling `mint` sells the pool's coin token
+    fn sells_coin(&self, mint: &Pubkey) -> Result<bool> {
+        if *mint == self.coin_mint {
+            Ok(true)
+        } else if *mint == self.pc_mint {
+            Ok(false)
+        } else {
+            Err(DappError::invalid_params(format!(
+                "Pool {} does not trade {}",
+                self.address, mint
+            )))
+        }
+    }
+
+    /// Quote selling `amount_in` raw units of `input_mint`
+    ///
+    /// The minimum output is the expected output less `slippage_bps`, unless
+    /// `min_output` overrides it.
+    pub fn quote(
+        &self,
+        reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+        slippage_bps: u16,
+        min_output: Option<u64>,
+    ) -> Result<SwapQuote> {
+        if u128::from(slippage_bps) > BPS {
+            return Err(DappError::invalid_params(format!(
+                "Slippage of {} bps exceeds 100%",
+                slippage_bps
+            )));
+        }
+        let (reserve_in, reserve_out) = if self.sells_coin(input_mint)? {
+            (reserves.coin, reserves.pc)
+        } else {
+            (reserves.pc, reserves.coin)
+        };
+        if reserve_in == 0 || reserve_out == 0 {
+            return Err(DappError::protocol(format!(
+                "Pool {} has no liquidity",
+                self.address
+            )));
+        }
+
+        let (amount_in, reserve_in, reserve_out) = (
+            u128::from(amount_in),
+            u128::from(reserve_in),
+            u128::from(reserve_out),
+        );
+        let numerator = u128::from(self.swap_fee_numerator);
+        let denominator = u128::from(self.swap_fee_denominator);
+        // The program rounds the fee up
+        let fee = (amount_in * numerator).div_ceil(denominator);
+        let amount_in_after_fee = amount_in.saturating_sub(fee);
+
+        let expected_out = reserve_out * amount_in_after_fee / (reserve_in + amount_in_after_fee);
+        if expected_out == 0 {
+            return Err(DappError::invalid_params(
+                "Swap amount is too small to produce any output",
+            ));
+        }
+        let spot_out = amount_in_after_fee * reserve_out / reserve_in;
+        let price_impact_bps = (spot_out - expected_out) * BPS / spot_out;
+        let min_out = match min_output {
+            Some(min_out) => u128::from(min_out),
+            None => expected_out * (BPS - u128::from(slippage_bps)) / BPS,
+        };
+
+        Ok(SwapQuote {
+            expected_out: to_u64(expected_out)?,
+            min_out: to_u64(min_out)?,
+            price_impact_bps: u32::try_from(price_impact_bps).unwrap_or(u32::MAX),
+            fee: to_u64(fee)?,
+        })
+    }
+}
+
+/// Accounts of the OpenBook market a pool trades through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketAccounts {
+    /// Market state account
+    pub address: Pubkey,
+    /// Program owning the market
+    pub program: Pubkey,
+    /// Bids slab
+    pub bids: Pubkey,
+    /// Asks slab
+    pub asks: Pubkey,
+    /// Event queue
+    pub event_queue: Pubkey,
+    /// Market coin vault
+    pub coin_vault: Pubkey,
+    /// Market pc vault
+    pub pc_vault: Pubkey,
+    /// PDA owning the market vaults
+    pub vault_signer: Pubkey,
+}
+
+impl MarketAccounts {
+    /// Decode a `MarketState` account
+    pub fn decode(address: Pubkey, account: &Account) -> Result<Self> {
+        let data = account.data.as_slice();
+        if data.len() != market_layout::SIZE {
+            return Err(DappError::invalid_pool_data(format!(
+                "unexpected market account size {}",
+                data.len()
+            )));
+        }
+
+        let nonce = read_u64(data, market_layout::VAULT_SIGNER_NONCE)?;
+        let vault_signer = Pubkey::create_program_address(
+            &[address.as_ref(), &nonce.to_le_bytes()],
+            &account.owner,
+        )
+        .map_err(|e| DappError::invalid_pool_data(format!("invalid vault signer: {}", e)))?;
+
+        Ok(Self {
+            address,
+            program: account.owner,
+            bids: read_pubkey(data, market_layout::BIDS)?,
+            asks: read_pubkey(data, market_layout::ASKS)?,
+            event_queue: read_pubkey(data, market_layout::EVENT_QUEUE)?,
+            coin_vault: read_pubkey(data, market_layout::COIN_VAULT)?,
+            pc_vault: read_pubkey(data, market_layout::PC_VAULT)?,
+            vault_signer,
+        })
+    }
+}
+
+/// Tradable pool balances, in raw units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolReserves {
+    /// Coin token reserve
+    pub coin: u64,
+    /// Pc token reserve
+    pub pc: u64,
+}
+
+/// Build a `SwapBaseIn` instruction
+///
+/// `user_source` and `user_destination` are the owner's token accounts for
+/// the input and output mints.
+pub fn swap_instruction(
+    pool: &RaydiumPool,
+    market: &MarketAccounts,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    owner: &Pubkey,
+    amount_in: u64,
+    min_out: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(17);
+    data.push(SWAP_BASE_IN);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_out.to_le_bytes());
+
+    Instruction {
+        program_id: RAYDIUM_AMM_V4_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(pool.address, false),
+            AccountMeta::new_readonly(pool.authority, false),
+            AccountMeta::new(pool.open_orders, false),
+            AccountMeta::new(pool.target_orders, false),
+            AccountMeta::new(pool.coin_vault, false),
+            AccountMeta::new(pool.pc_vault, false),
+            AccountMeta::new_readonly(market.program, false),
+            AccountMeta::new(market.address, false),
+            AccountMeta::new(market.bids, false),
+            AccountMeta::new(market.asks, false),
+            AccountMeta::new(market.event_queue, false),
+            AccountMeta::new(market.coin_vault, false),
+            AccountMeta::new(market.pc_vault, false),
+            AccountMeta::new_readonly(market.vault_signer, false),
+            AccountMeta::new(*user_source, false),
+            AccountMeta::new(*user_destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    }
+}
+
+/// Client for Raydium AMM v4 pools
+pub struct RaydiumClient {
+    rpc: Arc<dyn DynRpcProvider>,
+}
+
+impl RaydiumClient {
+    /// Program the client trades against
+    pub const PROGRAM_ID: Pubkey = RAYDIUM_AMM_V4_PROGRAM_ID;
+
+    /// Name configs use for the program in `agent.allowed_protocols`
+    pub const PROTOCOL_NAME: &'static str = agent_wallet_core::protocols::RAYDIUM_AMM_V4;
+
+    /// Create a client querying `rpc`
+    pub fn new(rpc: Arc<dyn DynRpcProvider>) -> Self {
+        Self { rpc }
+    }
+
+    /// Find the deepest pool trading two mints, in either orientation
+    pub async fn find_pool(&self, mint_a: &Pubkey, mint_b: &Pubkey) -> Result<RaydiumPool> {
+        let mut pools = Vec::new();
+        for (coin, pc) in [(mint_a, mint_b), (mint_b, mint_a)] {
+            let accounts = ProgramAccountsQuery::new(RAYDIUM_AMM_V4_PROGRAM_ID)
+                .data_size(raydium_layout::SIZE as u64)
+                .memcmp_pubkey(raydium_layout::COIN_MINT, coin)
+                .memcmp_pubkey(raydium_layout::PC_MINT, pc)
+                .fetch(self.rpc.as_ref())
+                .await?;
+            for (address, account) in accounts {
+                match RaydiumPool::decode(address, &account) {
+                    Ok(pool) => pools.push(pool),
+                    Err(e) => debug!("Skipping Raydium account {}: {}", address, e),
+                }
+            }
+        }
+
+        pools
+            .into_iter()
+            .max_by_key(|pool| pool.lp_amount)
+            .ok_or_else(|| DappError::PoolNotFound(format!("{}/{}", mint_a, mint_b)))
+    }
+
+    /// Current tradable reserves of a pool
+    pub async fn reserves(&self, pool: &RaydiumPool) -> Result<PoolReserves> {
+        let accounts = self
+            .rpc
+            .get_multiple_accounts(&[pool.coin_vault, pool.pc_vault])
+            .await?;
+        let amount = |account: Option<&Option<Account>>, vault: &Pubkey| -> Result<u64> {
+            let account = account.and_then(Option::as_ref).ok_or_else(|| {
+                DappError::invalid_pool_data(format!("vault {} not found", vault))
+            })?;
+            spl_token::state::Account::unpack(&account.data)
+                .map(|token_account| token_account.amount)
+                .map_err(|e| DappError::invalid_pool_data(format!("vault {}: {}", vault, e)))
+        };
+
+        Ok(PoolReserves {
+            coin: amount(accounts.first(), &pool.coin_vault)?
+                .saturating_sub(pool.need_take_pnl_coin),
+            pc: amount(accounts.get(1), &pool.pc_vault)?.saturating_sub(pool.need_take_pnl_pc),
+        })
+    }
+
+    /// Quote a swap without building a transaction
+    pub async fn quote(&self, params: &SwapParams) -> Result<(RaydiumPool, SwapQuote)> {
+        let (input_mint, output_mint) = params.mints()?;
+        let pool = self.find_pool(&input_mint, &output_mint).await?;
+        let reserves = self.reserves(&pool).await?;
+        let amount_in = input_amount(&pool, &input_mint, params)?;
+        let quote = pool.quote(
+            &reserves,
+            &input_mint,
+            amount_in,
+            params.slippage_bps,
+            params.min_output_amount,
+        )?;
+        Ok((pool, quote))
+    }
+
+    /// Build an unsigned swap transaction for `owner` and quote it
+    ///
+    /// The owner's token account for the output mint is created if needed.
+    /// Selling SOL wraps the amount into the owner's wrapped SOL account
+    /// first. Bought SOL and leftover wrapped SOL stay wrapped unless
+    /// [`SwapParams::auto_unwrap`] is set, which closes the wrapped SOL
+    /// account after the swap.
+    pub async fn create_swap_transaction(
+        &self,
+        owner: &Pubkey,
+        params: &SwapParams,
+    ) -> Result<(Transaction, SwapQuote)> {
+        let (input_mint, output_mint) = params.mints()?;
+        let (pool, quote) = self.quote(params).await?;
+        let market =
+            MarketAccounts::decode(pool.market, &self.rpc.get_account(&pool.market).await?)?;
+        let amount_in = input_amount(&pool, &input_mint, params)?;
+
+        let source = get_associated_token_address(owner, &input_mint);
+        let destination = get_associated_token_address(owner, &output_mint);
+        let mut instructions = Vec::new();
+        if input_mint == NATIVE_MINT {
+            instructions.extend(wrap_sol_instructions(owner, amount_in)?);
+        }
+        instructions.push(create_associated_token_account_idempotent(
+            owner,
+            owner,
+            &output_mint,
+            &spl_token::id(),
+        ));
+        instructions.push(swap_instruction(
+            &pool,
+            &market,
+            &source,
+            &destination,
+            owner,
+            amount_in,
+            quote.min_out,
+        ));
+        if params.auto_unwrap && (input_mint == NATIVE_MINT || output_mint == NATIVE_MINT) {
+            instructions.push(unwrap_sol_instruction(owner)?);
+        }
+
+        debug!(
+            "Raydium swap through {}: {} in, {} expected, {} minimum",
+            pool.address, amount_in, quote.expected_out, quote.min_out
+        );
+        Ok((
+            Transaction::new_with_payer(&instructions, Some(owner)),
+            quote,
+        ))
+    }
+}
+
+#[async_trait]
+impl ProtocolClient for RaydiumClient {
+    fn protocol(&self) -> DexProtocol {
+        DexProtocol::Raydium
+    }
+
+    fn supported_actions(&self) -> Vec<ProtocolAction> {
+        vec![ProtocolAction::Swap]
+    }
+
+    async fn quote_swap(&self, params: &SwapParams) -> Result<SwapQuote> {
+        self.quote(params).await.map(|(_, quote)| quote)
+    }
+
+    async fn build_swap(
+        &self,
+        owner: &Pubkey,
+        params: &SwapParams,
+    ) -> Result<(Transaction, SwapQuote)> {
+        self.create_swap_transaction(owner, params).await
+    }
+}
+
+/// Raw input amount of a swap through `pool`
+fn input_amount(pool: &RaydiumPool, input_mint: &Pubkey, params: &SwapParams) -> Result<u64> {
+    match params.raw_amount {
+        Some(amount) => Ok(amount),
+        None => raw_input_amount(pool, input_mint, params.amount),
+    }
+}
+
+/// Convert a UI amount of the input token to raw units
+fn raw_input_amount(pool: &RaydiumPool, input_mint: &Pubkey, amount: f64) -> Result<u64> {
+    let decimals = if pool.sells_coin(input_mint)? {
+        pool.coin_decimals
+    } else {
+        pool.pc_decimals
+    };
+    ui_to_raw(amount, decimals)
+}
+
+fn read_decimals(data: &[u8], offset: usize) -> Result<u8> {
+    let decimals = read_u64(data, offset)?;
+    u8::try_from(decimals)
+        .map_err(|_| DappError::invalid_pool_data(format!("invalid decimals {}", decimals)))
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| DappError::protocol(format!("Amount {} overflows", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixture pool and market accounts with unique addresses
+    fn fixture() -> Result<(RaydiumPool, MarketAccounts)> {
+        let (_, nonce) =
+            Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &RAYDIUM_AMM_V4_PROGRAM_ID);
+        let market_program = Pubkey::new_unique();
+        let market_address = Pubkey::new_unique();
+        let coin_mint = Pubkey::new_unique();
+        let pc_mint = Pubkey::new_unique();
+
+        let mut data = vec![0u8; raydium_layout::SIZE];
+        for (offset, value) in [
+            (raydium_layout::NONCE, u64::from(nonce)),
+            (raydium_layout::COIN_DECIMALS, 9),
+            (raydium_layout::PC_DECIMALS, 6),
+            (raydium_layout::SWAP_FEE_NUMERATOR, 25),
+            (raydium_layout::SWAP_FEE_DENOMINATOR, 10_000),
+            (raydium_layout::LP_AMOUNT, 1_000),
+        ] {
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        for (offset, key) in [
+            (raydium_layout::COIN_VAULT, Pubkey::new_unique()),
+            (raydium_layout::PC_VAULT, Pubkey::new_unique()),
+            (raydium_layout::COIN_MINT, coin_mint),
+            (raydium_layout::PC_MINT, pc_mint),
+            (raydium_layout::OPEN_ORDERS, Pubkey::new_unique()),
+            (raydium_layout::MARKET, market_address),
+            (raydium_layout::MARKET_PROGRAM, market_program),
+            (raydium_layout::TARGET_ORDERS, Pubkey::new_unique()),
+        ] {
+            data[offset..offset + 32].copy_from_slice(key.as_ref());
+        }
+        let pool_account = Account {
+            lamports: 1,
+            data,
+            owner: RAYDIUM_AMM_V4_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        // Vault signer nonces are the first that yield an off-curve address
+        let vault_nonce = (0u64..)
+            .find(|nonce| {
+                Pubkey::create_program_address(
+                    &[market_address.as_ref(), &nonce.to_le_bytes()],
+                    &market_program,
+                )
+                .is_ok()
+            })
+            .unwrap_or_default();
+        let mut data = vec![0u8; market_layout::SIZE];
+        data[market_layout::VAULT_SIGNER_NONCE..][..8].copy_from_slice(&vault_nonce.to_le_bytes());
+        for offset in [
+            market_layout::COIN_VAULT,
+            market_layout::PC_VAULT,
+            market_layout::EVENT_QUEUE,
+            market_layout::BIDS,
+            market_layout::ASKS,
+        ] {
+            data[offset..offset + 32].copy_from_slice(Pubkey::new_unique().as_ref());
+        }
+        let market_account = Account {
+            lamports: 1,
+            data,
+            owner: market_program,
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        Ok((
+            RaydiumPool::decode(Pubkey::new_unique(), &pool_account)?,
+            MarketAccounts::decode(market_address, &market_account)?,
+        ))
+    }
+
+    #[test]
+    fn test_constant_product_quote() -> Result<()> {
+        let (pool, _) = fixture()?;
+        let reserves = PoolReserves {
+            coin: 1_000_000_000,
+            pc: 1_000_000_000,
+        };
+
+        let quote = pool.quote(&reserves, &pool.coin_mint, 10_000_000, 50, None)?;
+        assert_eq!(
+            quote,
+            SwapQuote {
+                expected_out: 9_876_482,
+                min_out: 9_827_099,
+                price_impact_bps: 98,
+                fee: 25_000,
+            }
+        );
+
+        // An explicit minimum replaces the slippage-derived one
+        let quote = pool.quote(&reserves, &pool.pc_mint, 10_000_000, 50, Some(9_000_000))?;
+        assert_eq!(quote.min_out, 9_000_000);
+        assert_eq!(quote.expected_out, 9_876_482);
+
+        assert!(pool
+            .quote(&reserves, &Pubkey::new_unique(), 10_000_000, 50, None)
+            .is_err());
+        assert!(pool
+            .quote(&reserves, &pool.coin_mint, 10_000_000, 10_001, None)
+            .is_err());
+        assert!(pool.quote(&reserves, &pool.coin_mint, 1, 50, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimals_scale_input_amount() -> Result<()> {
+        let (pool, _) = fixture()?;
+        assert_eq!(
+            raw_input_amount(&pool, &pool.coin_mint, 1.5)?,
+            1_500_000_000
+        );
+        assert_eq!(raw_input_amount(&pool, &pool.pc_mint, 1.5)?, 1_500_000);
+        assert!(raw_input_amount(&pool, &pool.pc_mint, -1.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_instruction_account_order() -> Result<()> {
+        let (pool, market) = fixture()?;
+        let (source, destination, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let instruction =
+            swap_instruction(&pool, &market, &source, &destination, &owner, 1_000, 990);
+        assert_eq!(instruction.program_id, RAYDIUM_AMM_V4_PROGRAM_ID);
+        let expected = [
+            (spl_token::id(), false),
+            (pool.address, true),
+            (pool.authority, false),
+            (pool.open_orders, true),
+            (pool.target_orders, true),
+            (pool.coin_vault, true),
+            (pool.pc_vault, true),
+            (market.program, false),
+            (market.address, true),
+            (market.bids, true),
+            (market.asks, true),
+            (market.event_queue, true),
+            (market.coin_vault, true),
+            (market.pc_vault, true),
+            (market.vault_signer, false),
+            (source, true),
+            (destination, true),
+            (owner, false),
+        ];
+        let actual: Vec<(Pubkey, bool)> = instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable))
+            .collect();
+        assert_eq!(actual, expected);
+        let signers: Vec<Pubkey> = instruction
+            .accounts
+            .iter()
+            .filter(|meta| meta.is_signer)
+            .map(|meta| meta.pubkey)
+            .collect();
+        assert_eq!(signers, vec![owner]);
+
+        let mut data = vec![SWAP_BASE_IN];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&990u64.to_le_bytes());
+        assert_eq!(instruction.data, data);
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+//! Client for the counter test program
+//!
+//! The counter program keeps a single borsh-encoded `u64` per counter account
+//! and understands three instructions: increment, decrement and set. It is
+//! deployed by `scripts/deploy-test-program.sh` (or preloaded into a local
+//! validator by `scripts/start-devnet.sh`) and gives contributors one small
+//! program to exercise wallet signing, RPC failover and a dApp client end to
+//! end.
+//!
+//! [`CounterClient`] reads through any [`CounterRpc`]: the wallet's shared
+//! provider from [`Wallet::rpc_client`], the enhanced core
+//! [`RpcClient`](agent_wallet_core::RpcClient), or a plain Solana
+//! nonblocking RPC client. Transactions are always signed by the wallet,
+//! whose `agent.allowed_protocols` must list the counter program.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use agent_wallet_core::{Wallet, WalletConfig};
+//! use agent_wallet_dapp::test_program::CounterClient;
+//! use solana_sdk::pubkey::Pubkey;
+//!
+//! # async fn example(program_id: Pubkey) -> Result<(), Box<dyn std::error::Error>> {
+//! let wallet = Wallet::load("wallet.json", WalletConfig::new())?;
+//! let mut counter = CounterClient::new(program_id, wallet.rpc_client());
+//!
+//! let address = counter.initialize(&wallet).await?;
+//! counter.increment(&wallet).await?;
+//! println!("Counter {} is at {}", address, counter.get_count().await?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use agent_wallet_core::{
+    DynRpcProvider, Error as CoreError, RpcClient, TransactionOptions, Wallet,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_client::nonblocking::rpc_client::RpcClient as SolanaRpcClient;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use tracing::debug;
+
+use crate::error::{DappError, Result};
+
+/// Size in bytes of a counter account
+pub const COUNTER_ACCOUNT_SIZE: usize = 8;
+
+/// Instructions understood by the counter program
+///
+/// Borsh encodes these as the variant index followed by its payload, so
+/// increment is `[0]`, decrement `[1]` and set `[2]` plus a little-endian
+/// `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum CounterInstruction {
+    /// Add one to the counter
+    Increment,
+    /// Subtract one from the counter, failing at zero
+    Decrement,
+    /// Overwrite the counter
+    Set(u64),
+}
+
+impl CounterInstruction {
+    /// Instruction name used in logs and errors
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Increment => "increment",
+            Self::Decrement => "decrement",
+            Self::Set(_) => "set",
+        }
+    }
+}
+
+/// On-chain state of a counter account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CounterAccount {
+    /// Current value
+    pub count: u64,
+}
+
+impl CounterAccount {
+    /// Decode counter account data
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        Self::try_from_slice(data)
+            .map_err(|e| DappError::serialization(format!("Invalid counter account: {}", e)))
+    }
+}
+
+/// Custom errors returned by the counter program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterError {
+    /// Decrement of a counter already at zero
+    Underflow,
+    /// Increment of a counter already at `u64::MAX`
+    Overflow,
+}
+
+impl CounterError {
+    /// Map a custom program error code to a counter error
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(Self::Underflow),
+            1 => Some(Self::Overflow),
+            _ => None,
+        }
+    }
+
+    /// Custom program error code
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::Underflow => 0,
+            Self::Overflow => 1,
+        }
+    }
+}
+
+impl fmt::Display for CounterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Underflow => write!(f, "counter underflow"),
+            Self::Overflow => write!(f, "counter overflow"),
+        }
+    }
+}
+
+/// RPC calls the counter client needs
+///
+/// Implemented for the wallet's shared provider, the enhanced core
+/// [`RpcClient`] and the plain Solana nonblocking client.
+pub trait CounterRpc: Send + Sync {
+    /// Data of an account, or `None` if it does not exist
+    fn get_account_data(
+        &self,
+        address: &Pubkey,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>>> + Send;
+
+    /// Minimum balance for an account of `data_len` bytes to be rent exempt
+    fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Latest blockhash
+    fn get_latest_blockhash(&self) -> impl Future<Output = Result<Hash>> + Send;
+}
+
+impl CounterRpc for dyn DynRpcProvider {
+    async fn get_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        match self.get_account(address).await {
+            Ok(account) => Ok(Some(account.data)),
+            Err(CoreError::AccountNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(DynRpcProvider::get_minimum_balance_for_rent_exemption(self, data_len).await?)
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(DynRpcProvider::get_latest_blockhash(self).await?)
+    }
+}
+
+impl CounterRpc for RpcClient {
+    async fn get_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        match self.get_account(address).await {
+            Ok(account) => Ok(Some(account.data)),
+            Err(CoreError::AccountNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(RpcClient::get_minimum_balance_for_rent_exemption(self, data_len).await?)
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(RpcClient::get_latest_blockhash(self).await?)
+    }
+}
+
+impl CounterRpc for SolanaRpcClient {
+    async fn get_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let response = self
+            .get_account_with_commitment(address, self.commitment())
+            .await
+            .map_err(CoreError::from)?;
+        Ok(response.value.map(|account| account.data))
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(
+            SolanaRpcClient::get_minimum_balance_for_rent_exemption(self, data_len)
+                .await
+                .map_err(CoreError::from)?,
+        )
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(SolanaRpcClient::get_latest_blockhash(self)
+            .await
+            .map_err(CoreError::from)?)
+    }
+}
+
+/// Client for one counter account of the counter program
+pub struct CounterClient<R: ?Sized = dyn DynRpcProvider> {
+    program_id: Pubkey,
+    rpc: Arc<R>,
+    counter: Option<Pubkey>,
+}
+
+impl<R: CounterRpc + ?Sized> CounterClient<R> {
+    /// Create a client for the counter program deployed at `program_id`
+    pub fn new(program_id: Pubkey, rpc: Arc<R>) -> Self {
+        Self {
+            program_id,
+            rpc,
+            counter: None,
+        }
+    }
+
+    /// Use an existing counter account
+    pub fn with_counter(mut self, counter: Pubkey) -> Self {
+        self.counter = Some(counter);
+        self
+    }
+
+    /// Program the client talks to
+    pub fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    /// Counter account, once initialized or set
+    pub fn counter(&self) -> Option<Pubkey> {
+        self.counter
+    }
+
+    /// Create a rent-exempt counter account owned by the program
+    ///
+    /// `payer` funds the account and signs alongside the fresh counter
+    /// keypair. The new account becomes this client's counter.
+    pub async fn initialize(&mut self, payer: &Wallet) -> Result<Pubkey> {
+        let counter = Keypair::new();
+        let lamports = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(COUNTER_ACCOUNT_SIZE)
+            .await?;
+        let create = system_instruction::create_account(
+            &payer.public_key(),
+            &counter.pubkey(),
+            lamports,
+            COUNTER_ACCOUNT_SIZE as u64,
+            &self.program_id,
+        );
+
+        let mut transaction = Transaction::new_with_payer(&[create], Some(&payer.public_key()));
+        let blockhash = self.rpc.get_latest_blockhash().await?;
+        transaction
+            .try_partial_sign(&[&counter], blockhash)
+            .map_err(|e| DappError::protocol(format!("Failed to sign counter account: {}", e)))?;
+
+        // The wallet keeps the blockhash of an already partially signed transaction
+        payer.sign_transaction(&mut transaction).await?;
+        let signature = payer
+            .send_signed_transaction(&transaction, &TransactionOptions::default())
+            .await?;
+        debug!(
+            counter = %counter.pubkey(),
+            %signature,
+            lamports,
+            "Counter account created"
+        );
+
+        self.counter = Some(counter.pubkey());
+        Ok(counter.pubkey())
+    }
+
+    /// Add one to the counter
+    pub async fn increment(&self, wallet: &Wallet) -> Result<Signature> {
+        self.send(wallet, CounterInstruction::Increment).await
+    }
+
+    /// Subtract one from the counter
+    ///
+    /// Fails with [`DappError::Program`] carrying [`CounterError::Underflow`]'s
+    /// code when the counter is already at zero.
+    pub async fn decrement(&self, wallet: &Wallet) -> Result<Signature> {
+        self.send(wallet, CounterInstruction::Decrement).await
+    }
+
+    /// Current value of the counter
+    pub async fn get_count(&self) -> Result<u64> {
+        let counter = self.counter_address()?;
+        let data =
+            self.rpc.get_account_data(&counter).await?.ok_or_else(|| {
+                DappError::protocol(format!("Counter {} does not exist", counter))
+            })?;
+        Ok(CounterAccount::decode(&data)?.count)
+    }
+
+    /// Build a counter instruction for `authority`
+    pub fn instruction(
+        &self,
+        authority: &Pubkey,
+        instruction: CounterInstruction,
+    ) -> Result<Instruction> {
+        let data = borsh::to_vec(&instruction)
+            .map_err(|e| DappError::serialization(format!("Invalid instruction: {}", e)))?;
+        Ok(Instruction::new_with_bytes(
+            self.program_id,
+            &data,
+            vec![
+                AccountMeta::new(self.counter_address()?, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+        ))
+    }
+
+    async fn send(&self, wallet: &Wallet, instruction: CounterInstruction) -> Result<Signature> {
+        let ix = self.instruction(&wallet.public_key(), instruction)?;
+        let mut transaction = Transaction::new_with_payer(&[ix], Some(&wallet.public_key()));
+
+        wallet
+            .sign_and_send(&mut transaction)
+            .await
+            .map_err(|e| decode_program_error(self.program_id, instruction, e))
+    }
+
+    fn counter_address(&self) -> Result<Pubkey> {
+        self.counter
+            .ok_or_else(|| DappError::invalid_params("Counter account is not initialized"))
+    }
+}
+
+/// Turn a custom program error into [`DappError::Program`]
+///
+/// Errors that did not come from the counter program pass through unchanged.
+fn decode_program_error(
+    program_id: Pubkey,
+    instruction: CounterInstruction,
+    error: CoreError,
+) -> DappError {
+    let code = match &error {
+        CoreError::SolanaRpc(client_error) => match client_error.get_transaction_error() {
+            Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => code,
+            _ => return error.into(),
+        },
+        _ => return error.into(),
+    };
+
+    let reason = CounterError::from_code(code)
+        .map(|e| e.to_string())
+        .unwrap_or_else(|| "unknown error".to_string());
+    DappError::Program {
+        program_id,
+        code,
+        message: format!("{} failed: {}", instruction.name(), reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::client_error::ClientError;
+
+    /// Provider answering from a fixed account
+    struct Fixed(Option<Vec<u8>>);
+
+    impl CounterRpc for Fixed {
+        async fn get_account_data(&self, _address: &Pubkey) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.clone())
+        }
+
+        async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+            Ok(solana_sdk::rent::Rent::default().minimum_balance(data_len))
+        }
+
+        async fn get_latest_blockhash(&self) -> Result<Hash> {
+            Ok(Hash::default())
+        }
+    }
+
+    #[test]
+    fn test_instruction_encoding() -> Result<()> {
+        let program_id = Pubkey::new_unique();
+        let counter = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let client = CounterClient::new(program_id, Arc::new(Fixed(None))).with_counter(counter);
+
+        let ix = client.instruction(&authority, CounterInstruction::Increment)?;
+        assert_eq!(ix.program_id, program_id);
+        assert_eq!(ix.data, vec![0]);
+        assert_eq!(ix.accounts[0], AccountMeta::new(counter, false));
+        assert_eq!(ix.accounts[1], AccountMeta::new_readonly(authority, true));
+
+        let ix = client.instruction(&authority, CounterInstruction::Decrement)?;
+        assert_eq!(ix.data, vec![1]);
+
+        let ix = client.instruction(&authority, CounterInstruction::Set(258))?;
+        assert_eq!(ix.data, vec![2, 2, 1, 0, 0, 0, 0, 0, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_instruction_requires_counter() {
+        let client = CounterClient::new(Pubkey::new_unique(), Arc::new(Fixed(None)));
+        assert!(matches!(
+            client.instruction(&Pubkey::new_unique(), CounterInstruction::Increment),
+            Err(DappError::InvalidParams(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_count() -> Result<()> {
+        let data = 42u64.to_le_bytes().to_vec();
+        let client = CounterClient::new(Pubkey::new_unique(), Arc::new(Fixed(Some(data))))
+            .with_counter(Pubkey::new_unique());
+        assert_eq!(client.get_count().await?, 42);
+
+        let missing = CounterClient::new(Pubkey::new_unique(), Arc::new(Fixed(None)))
+            .with_counter(Pubkey::new_unique());
+        assert!(matches!(
+            missing.get_count().await,
+            Err(DappError::Protocol(_))
+        ));
+
+        let truncated = CounterClient::new(Pubkey::new_unique(), Arc::new(Fixed(Some(vec![1]))))
+            .with_counter(Pubkey::new_unique());
+        assert!(matches!(
+            truncated.get_count().await,
+            Err(DappError::Serialization(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_underflow() -> Result<()> {
+        let program_id = Pubkey::new_unique();
+        let error = CoreError::SolanaRpc(ClientError::from(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(0),
+        )));
+
+        let DappError::Program {
+            program_id: failed,
+            code,
+            message,
+        } = decode_program_error(program_id, CounterInstruction::Decrement, error)
+        else {
+            return Err(DappError::protocol("expected a program error"));
+        };
+        assert_eq!(failed, program_id);
+        assert_eq!(CounterError::from_code(code), Some(CounterError::Underflow));
+        assert_eq!(message, "decrement failed: counter underflow");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_passes_other_errors() {
+        let error = CoreError::rpc("connection refused");
+        assert!(matches!(
+            decode_program_error(Pubkey::new_unique(), CounterInstruction::Increment, error),
+            DappError::Wallet(_)
+        ));
+    }
+}
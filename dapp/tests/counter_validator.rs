@@ -0,0 +1,83 @@
+//! End-to-end round trip of the counter client against a local validator
+//!
+//! Ignored by default. Start a validator with the prebuilt counter program
+//! loaded (`scripts/start-devnet.sh` passes `--bpf-program` for
+//! `test-programs/counter.so`), then run:
+//!
+//! ```text
+//! SOLANA_TEST_VALIDATOR_URL=http://127.0.0.1:8899 \
+//! COUNTER_PROGRAM_ID=<program id> \
+//!     cargo test -p agent-wallet-dapp --test counter_validator -- --ignored
+//! ```
+//!
+//! The wallet is configured with an unreachable primary endpoint ahead of the
+//! validator, so every call also exercises RPC failover.
+
+use std::error::Error;
+use std::sync::Arc;
+
+use agent_wallet_core::config::RpcEndpoint;
+use agent_wallet_core::prelude::Zeroizing;
+use agent_wallet_core::protocols::ProtocolSettings;
+use agent_wallet_core::{Lamports, Wallet, WalletConfig};
+use agent_wallet_dapp::test_program::{CounterClient, CounterError};
+use agent_wallet_dapp::DappError;
+use solana_client::nonblocking::rpc_client::RpcClient as SolanaRpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+/// Endpoint nothing listens on, tried first
+const DEAD_ENDPOINT: &str = "http://127.0.0.1:1";
+
+fn env(name: &str) -> Result<String, Box<dyn Error>> {
+    std::env::var(name).map_err(|_| format!("{} must be set", name).into())
+}
+
+#[tokio::test]
+#[ignore = "needs solana-test-validator with the counter program loaded"]
+async fn counter_round_trip() -> Result<(), Box<dyn Error>> {
+    let url = env("SOLANA_TEST_VALIDATOR_URL")?;
+    let program_id: Pubkey = env("COUNTER_PROGRAM_ID")?.parse()?;
+    let storage = tempfile::tempdir()?;
+
+    let mut config = WalletConfig::builder()
+        .with_storage_path(storage.path())
+        .with_allowed_protocol(ProtocolSettings::new(program_id.to_string()))
+        .build();
+    config.rpc.endpoints = vec![
+        RpcEndpoint::with_priority(DEAD_ENDPOINT, 0),
+        RpcEndpoint::with_priority(url.as_str(), 1),
+    ];
+    config.rpc.use_websocket = false;
+
+    let passphrase = Zeroizing::new("counter-round-trip".to_string());
+    let wallet = Wallet::create("counter-e2e", &passphrase, config).await?;
+    // The local validator is a custom cluster, so its faucet is allowed
+    wallet.request_airdrop(Lamports::from_sol_str("1")?).await?;
+    let raw = Arc::new(SolanaRpcClient::new(url));
+
+    // Writes go through the wallet, reads through its failover provider
+    let mut counter = CounterClient::new(program_id, wallet.rpc_client());
+    let address = counter.initialize(&wallet).await?;
+    assert_eq!(counter.get_count().await?, 0);
+
+    counter.increment(&wallet).await?;
+    counter.increment(&wallet).await?;
+    assert_eq!(counter.get_count().await?, 2);
+
+    counter.decrement(&wallet).await?;
+    counter.decrement(&wallet).await?;
+
+    // The same account read through a plain Solana client
+    let direct = CounterClient::new(program_id, raw).with_counter(address);
+    assert_eq!(direct.get_count().await?, 0);
+
+    match counter.decrement(&wallet).await {
+        Err(DappError::Program { code, .. }) => {
+            assert_eq!(CounterError::from_code(code), Some(CounterError::Underflow));
+        }
+        other => return Err(format!("expected an underflow, got {:?}", other).into()),
+    }
+    assert_eq!(counter.get_count().await?, 0);
+
+    Ok(())
+}